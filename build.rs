@@ -95,4 +95,49 @@ pub fn main() {
     assert!(dynarmic_legal.contains(dynarmic_license_oneline));
     let dynarmic_summary = dynarmic_legal.replace(dynarmic_license_oneline, &dynarmic_license);
     std::fs::write(out_dir.join("dynarmic_license.txt"), dynarmic_summary).unwrap();
+
+    // Build in the plugins in the directories listed in TOUCHHLE_PLUGINS.
+    // This is used in plugins.rs
+
+    println!("cargo:rerun-if-env-changed=TOUCHHLE_PLUGINS");
+    let mut plugins_string = String::new();
+    let mut plugin_names = Vec::new();
+    for dir in std::env::split_paths(&std::env::var_os("TOUCHHLE_PLUGINS").unwrap_or_default()) {
+        if dir.as_os_str().is_empty() {
+            continue;
+        }
+        let dir = dir
+            .canonicalize()
+            .unwrap_or_else(|e| panic!("Couldn't find plugin {}: {}", dir.display(), e));
+        let name = dir.file_name().unwrap().to_str().unwrap().to_string();
+        if name.starts_with(|c: char| c.is_ascii_digit())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            panic!("Plugin directory name {:?} isn't a valid module name", name);
+        }
+        if plugin_names.contains(&name) {
+            panic!("There's more than one plugin called {:?}", name);
+        }
+        // Cargo checks everything in the directory for changes.
+        rerun_if_changed(&dir);
+        writeln!(
+            &mut plugins_string,
+            "#[path = {:?}]\nmod {};",
+            dir.join("mod.rs").to_str().unwrap(),
+            name
+        )
+        .unwrap();
+        plugin_names.push(name);
+    }
+    let plugins: Vec<String> = plugin_names
+        .iter()
+        .map(|name| format!("({:?}, &{}::PLUGIN)", name, name))
+        .collect();
+    writeln!(
+        &mut plugins_string,
+        "pub const PLUGINS: &[(&str, &Plugin)] = &[{}];",
+        plugins.join(", ")
+    )
+    .unwrap();
+    std::fs::write(out_dir.join("plugins.rs"), plugins_string).unwrap();
 }
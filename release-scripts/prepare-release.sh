@@ -5,5 +5,15 @@ cp -r ../touchHLE_dylibs new_release/
 mv new_release/touchHLE_dylibs/README.md new_release/touchHLE_dylibs/README.txt
 cp -r ../touchHLE_fonts new_release/
 mv new_release/touchHLE_fonts/README.md new_release/touchHLE_fonts/README.txt
+cp -r ../touchHLE_keyboard_maps new_release/
+mv new_release/touchHLE_keyboard_maps/README.md new_release/touchHLE_keyboard_maps/README.txt
+cp -r ../touchHLE_virtual_controls new_release/
+mv new_release/touchHLE_virtual_controls/README.md new_release/touchHLE_virtual_controls/README.txt
+cp -r ../touchHLE_config new_release/
+mv new_release/touchHLE_config/README.md new_release/touchHLE_config/README.txt
+cp -r ../touchHLE_scripts new_release/
+mv new_release/touchHLE_scripts/README.md new_release/touchHLE_scripts/README.txt
+cp -r ../touchHLE_apps new_release/
+mv new_release/touchHLE_apps/README.md new_release/touchHLE_apps/README.txt
 cp ../README.md new_release/README.txt
 cp -r gpl-3.0.txt new_release/COPYING
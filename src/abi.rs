@@ -14,6 +14,7 @@
 use crate::cpu::Cpu;
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr, SafeRead};
 use crate::Environment;
+use std::fmt::Debug;
 
 /// The register number of the frame pointer in Apple's ABI.
 pub const FRAME_POINTER: usize = 7;
@@ -134,7 +135,14 @@ impl GuestFunction {
 ///
 /// See also [GuestFunction::call].
 pub trait CallFromGuest {
-    fn call_from_guest(&self, env: &mut Environment);
+    fn call_from_guest(&self, env: &mut Environment) {
+        self.call_from_guest_traced(env, None)
+    }
+
+    /// Like [CallFromGuest::call_from_guest], but if `trace` is provided, the
+    /// call is logged under that name with its arguments and return value.
+    /// See [crate::api_trace].
+    fn call_from_guest_traced(&self, env: &mut Environment, trace: Option<&str>);
 }
 
 macro_rules! impl_CallFromGuest {
@@ -143,7 +151,7 @@ macro_rules! impl_CallFromGuest {
             where R: GuestRet, $($P: GuestArg,)* {
             // ignore warnings for the zero-argument case
             #[allow(unused_variables, unused_mut, clippy::unused_unit)]
-            fn call_from_guest(&self, env: &mut Environment) {
+            fn call_from_guest_traced(&self, env: &mut Environment, trace: Option<&str>) {
                 let mut reg_offset = 0;
                 let regs = env.cpu.regs();
                 let retval_ptr = R::SIZE_IN_MEM.map(|_| {
@@ -152,7 +160,13 @@ macro_rules! impl_CallFromGuest {
                 let args: ($($P,)*) = {
                     ($(read_next_arg::<$P>(&mut reg_offset, regs, &env.mem),)*)
                 };
+                if let Some(name) = trace {
+                    crate::api_trace::log_call(env, name, &[$(&args.$p),*], false);
+                }
                 let retval = self(env, $(args.$p),*);
+                if let Some(name) = trace {
+                    crate::api_trace::log_return(env, name, &retval);
+                }
                 if let Some(retval_ptr) = retval_ptr {
                     retval.to_mem(retval_ptr, &mut env.mem);
                 } else {
@@ -164,7 +178,7 @@ macro_rules! impl_CallFromGuest {
             where R: GuestRet, $($P: GuestArg,)* {
             // ignore warnings for the zero-argument case
             #[allow(unused_variables, unused_mut, clippy::unused_unit)]
-            fn call_from_guest(&self, env: &mut Environment) {
+            fn call_from_guest_traced(&self, env: &mut Environment, trace: Option<&str>) {
                 let mut reg_offset = 0;
                 let regs = env.cpu.regs();
                 let retval_ptr = R::SIZE_IN_MEM.map(|_| {
@@ -174,7 +188,13 @@ macro_rules! impl_CallFromGuest {
                     ($(read_next_arg::<$P>(&mut reg_offset, regs, &env.mem),)*)
                 };
                 let va_list = VAList { reg_offset };
+                if let Some(name) = trace {
+                    crate::api_trace::log_call(env, name, &[$(&args.$p),*], true);
+                }
                 let retval = self(env, $(args.$p,)* va_list);
+                if let Some(name) = trace {
+                    crate::api_trace::log_return(env, name, &retval);
+                }
                 if let Some(retval_ptr) = retval_ptr {
                     retval.to_mem(retval_ptr, &mut env.mem);
                 } else {
@@ -195,6 +215,8 @@ impl_CallFromGuest!(0 => P0, 1 => P1, 2 => P2, 3 => P3, 4 => P4, 5 => P5);
 impl_CallFromGuest!(0 => P0, 1 => P1, 2 => P2, 3 => P3, 4 => P4, 5 => P5, 6 => P6);
 impl_CallFromGuest!(0 => P0, 1 => P1, 2 => P2, 3 => P3, 4 => P4, 5 => P5, 6 => P6, 7 => P7);
 impl_CallFromGuest!(0 => P0, 1 => P1, 2 => P2, 3 => P3, 4 => P4, 5 => P5, 6 => P6, 7 => P7, 8 => P8);
+impl_CallFromGuest!(0 => P0, 1 => P1, 2 => P2, 3 => P3, 4 => P4, 5 => P5, 6 => P6, 7 => P7, 8 => P8, 9 => P9);
+impl_CallFromGuest!(0 => P0, 1 => P1, 2 => P2, 3 => P3, 4 => P4, 5 => P5, 6 => P6, 7 => P7, 8 => P8, 9 => P9, 10 => P10);
 
 /// This trait represents a guest or host function that can be called from host
 /// code, but using the guest ABI. See [CallFromGuest], which this is the
@@ -266,7 +288,9 @@ impl_CallFromHost!(0 => P0, 1 => P1, 2 => P2, 3 => P3, 4 => P4, 5 => P5, 6 => P6
 impl_CallFromHost!(0 => P0, 1 => P1, 2 => P2, 3 => P3, 4 => P4, 5 => P5, 6 => P6, 7 => P7, 8 => P8);
 
 /// Calling convention translation for a function argument type.
-pub trait GuestArg: Sized {
+///
+/// [Debug] is needed so that calls can be traced, see [crate::api_trace].
+pub trait GuestArg: Sized + Debug {
     /// How many registers does this argument type consume?
     const REG_COUNT: usize;
 
@@ -285,7 +309,7 @@ fn read_next_arg<T: GuestArg>(reg_offset: &mut usize, regs: &[u32], mem: &Mem) -
     // After the fourth register is used, the arguments go on the stack.
     // In some cases the argument is split over both registers and the stack.
 
-    let mut fake_regs = [0u32; 4]; // Rust doesn't allow [0u32; Trait::T] alas.
+    let mut fake_regs = [0u32; 8]; // Rust doesn't allow [0u32; Trait::T] alas.
     let fake_regs = &mut fake_regs[0..T::REG_COUNT];
 
     for fake_reg in fake_regs.iter_mut() {
@@ -331,7 +355,7 @@ pub fn write_next_arg<T: GuestArg>(
     // After the fourth register is used, the arguments go on the stack.
     // In some cases the argument is split over both registers and the stack.
 
-    let mut fake_regs = [0u32; 4]; // Rust doesn't allow [0u32; Trait::T] alas.
+    let mut fake_regs = [0u32; 8]; // Rust doesn't allow [0u32; Trait::T] alas.
     let fake_regs = &mut fake_regs[0..T::REG_COUNT];
     arg.to_regs(fake_regs);
 
@@ -464,7 +488,9 @@ impl GuestArg for f64 {
 // usually behave the same? Are there exceptions? Do we merge the types?
 
 /// Calling convention translation for a function return type.
-pub trait GuestRet: Sized {
+///
+/// [Debug] is needed so that calls can be traced, see [crate::api_trace].
+pub trait GuestRet: Sized + Debug {
     /// If this is `None`, then the return value is passed directly in
     /// registers and the `to_regs` and `from_regs` methods should be used.
     /// If this is `Some(size)`, then the return value is of `size` bytes and is
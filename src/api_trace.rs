@@ -0,0 +1,182 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Tracing of calls from the app to touchHLE's implementations of functions
+//! and Objective-C methods, a bit like `strace` (`--trace-api=`).
+//!
+//! Each traced call is logged when it begins, with its arguments, and again
+//! when it returns, with its return value. The arguments are decoded using the
+//! types of the host function (see [crate::abi::CallFromGuest]), so e.g. a
+//! `CGRect` is shown as one value rather than four words. For Objective-C
+//! methods, the receiver and selector are the first two arguments.
+//!
+//! Calls are matched against patterns where `*` matches anything. C functions
+//! are named without the leading underscore of their symbol, e.g. `fopen`, and
+//! methods are named like `-[UIView setFrame:]`. Patterns can also match the
+//! path of the module of touchHLE that implements a call, or of its parent
+//! modules, e.g. `libc::stdio` or `uikit`. Framework modules are named without
+//! `frameworks::`.
+
+use crate::dyld::LinkedHostFunction;
+use crate::objc::{ObjC, SEL};
+use crate::{Environment, Options};
+use std::fmt::Debug;
+
+/// A pattern to match calls against, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern(String);
+impl Pattern {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        if pattern.is_empty() {
+            return Err("The API trace pattern must not be empty".to_string());
+        }
+        Ok(Pattern(pattern.to_string()))
+    }
+
+    /// Check if a call, with the path of the module implementing it if known,
+    /// matches this pattern.
+    fn matches(&self, module: Option<&str>, name: &str) -> bool {
+        if glob_matches(&self.0, name) {
+            return true;
+        }
+        let Some(module) = module else {
+            return false;
+        };
+        // Check the module and each of its parents.
+        module
+            .match_indices("::")
+            .map(|(idx, _)| &module[..idx])
+            .chain(std::iter::once(module))
+            .any(|module| glob_matches(&self.0, module))
+    }
+}
+
+/// Match some text against a pattern where `*` matches any characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+    let Some(mut text) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let last = parts.pop().unwrap();
+    // Matching each part as early as possible leaves the most room for the
+    // rest.
+    for part in parts {
+        let Some(idx) = text.find(part) else {
+            return false;
+        };
+        text = &text[idx + part.len()..];
+    }
+    text.len() >= last.len() && text.ends_with(last)
+}
+
+/// For use by the emulator's main loop: get the name to trace a call to a C
+/// function under, if it should be traced.
+pub fn c_function(options: &Options, f: &LinkedHostFunction) -> Option<&'static str> {
+    if options.trace_api.is_empty() {
+        return None;
+    }
+    let name = f.symbol.strip_prefix('_').unwrap_or(f.symbol);
+    options
+        .trace_api
+        .iter()
+        .any(|pattern| pattern.matches(Some(f.module), name))
+        .then_some(name)
+}
+
+/// For use by `objc_msgSend` and friends: get the name to trace a call to a
+/// host method under, if it should be traced.
+pub fn objc_method(
+    env: &Environment,
+    class_name: &str,
+    is_metaclass: bool,
+    selector: SEL,
+) -> Option<String> {
+    if env.options.trace_api.is_empty() {
+        return None;
+    }
+    let name = format!(
+        "{}[{} {}]",
+        if is_metaclass { '+' } else { '-' },
+        class_name,
+        selector.as_str(&env.mem)
+    );
+    let module = ObjC::find_template_module(&env.plugins, class_name);
+    env.options
+        .trace_api
+        .iter()
+        .any(|pattern| pattern.matches(module, &name))
+        .then_some(name)
+}
+
+/// For use by [crate::abi::CallFromGuest] implementations: log the start of a
+/// traced call.
+pub fn log_call(env: &Environment, name: &str, args: &[&dyn Debug], variadic: bool) {
+    let mut args: Vec<String> = args.iter().map(|arg| format!("{:?}", arg)).collect();
+    if variadic {
+        args.push("...".to_string());
+    }
+    log!(
+        "[thread {}] -> {}({})",
+        env.current_thread,
+        name,
+        args.join(", ")
+    );
+}
+
+/// For use by [crate::abi::CallFromGuest] implementations: log the end of a
+/// traced call.
+pub fn log_return<R: Debug>(env: &Environment, name: &str, retval: &R) {
+    if std::mem::size_of::<R>() == 0 {
+        log!("[thread {}] <- {}", env.current_thread, name);
+    } else {
+        log!("[thread {}] <- {} = {:?}", env.current_thread, name, retval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        assert!(glob_matches("fopen", "fopen"));
+        assert!(!glob_matches("fopen", "fopen2"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("gl*", "glDrawArrays"));
+        assert!(!glob_matches("gl*", "alGetError"));
+        assert!(glob_matches("*Error", "alGetError"));
+        assert!(glob_matches("-[UIView *]", "-[UIView setFrame:]"));
+        assert!(!glob_matches("-[UIView *]", "+[UIView alloc]"));
+        assert!(glob_matches("*[* init*]", "-[NSObject initWithCoder:]"));
+        assert!(glob_matches("a*a*a", "aaa"));
+        assert!(!glob_matches("a*a*a", "aa"));
+        assert!(glob_matches("a*b*c", "axxbyybc"));
+    }
+
+    #[test]
+    fn pattern() {
+        let pattern = Pattern::parse("libc::stdio").unwrap();
+        assert!(pattern.matches(Some("libc::stdio"), "fopen"));
+        assert!(pattern.matches(Some("libc::stdio::printf"), "printf"));
+        assert!(!pattern.matches(Some("libc::string"), "strlen"));
+        assert!(!pattern.matches(None, "fopen"));
+
+        let pattern = Pattern::parse("uikit").unwrap();
+        assert!(pattern.matches(Some("uikit::ui_view"), "-[UIView setFrame:]"));
+        assert!(!pattern.matches(Some("uikit_extra"), "foo"));
+
+        let pattern = Pattern::parse("core_*").unwrap();
+        assert!(pattern.matches(Some("core_graphics::cg_context"), "CGContextFillRect"));
+
+        let pattern = Pattern::parse("-[* dealloc]").unwrap();
+        assert!(pattern.matches(None, "-[NSObject dealloc]"));
+        assert!(!pattern.matches(Some("foundation::ns_object"), "-[NSObject init]"));
+
+        assert!(Pattern::parse("").is_err());
+    }
+}
@@ -3,18 +3,26 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! Audio file decoding and OpenAL bindings.
+//! Audio file decoding, OpenAL bindings and mixer settings.
 //!
 //! The audio file decoding support is an abstraction over various libraries
-//! (currently [caf] and [hound]), usage of which should be confined to this
-//! module.
+//! (currently [caf], [hound] and [symphonia]), usage of which should be
+//! confined to this module.
+//!
+//! Formats that apps can't be expected to decode themselves (MP3, AAC, µ-law,
+//! A-law and anything in an AIFF file) are decoded to 16-bit linear PCM when
+//! the file is opened, so apps see them as PCM files.
 //!
 //! Resources:
 //! - [Apple Core Audio Format Specification 1.0](https://developer.apple.com/library/archive/documentation/MusicAudio/Reference/CAFSpec/CAF_intro/CAF_intro.html)
 
+mod aiff;
+mod compressed;
+mod g711;
 mod ima4;
+pub mod mixer;
 
-pub use ima4::decode_ima4;
+pub use ima4::decode_ima4_interleaved;
 pub use touchHLE_openal_soft_wrapper as openal;
 
 use crate::fs::{Fs, GuestPath};
@@ -42,10 +50,20 @@ pub struct AudioDescription {
     pub bits_per_channel: u32,
 }
 
+/// Audio decoded to 16-bit linear PCM when the file was opened.
+struct DecodedAudio {
+    /// Hz
+    sample_rate: u32,
+    channels: u32,
+    /// Interleaved signed 16-bit little-endian samples.
+    pcm_bytes: Vec<u8>,
+}
+
 pub struct AudioFile(AudioFileInner);
 enum AudioFileInner {
     Wave(hound::WavReader<Cursor<Vec<u8>>>),
     Caf(caf::CafPacketReader<Cursor<Vec<u8>>>),
+    Decoded(DecodedAudio),
 }
 
 impl AudioFile {
@@ -53,6 +71,26 @@ impl AudioFile {
         // TODO: it would be better not to load the whole file at once
         let bytes = fs.read(path.as_ref())?;
 
+        let extension = path
+            .as_ref()
+            .file_name()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, extension)| extension);
+        let Ok(audio_file) = Self::open_from_bytes(bytes, extension) else {
+            // We may eventually want to return an error here, this is just more
+            // useful currently.
+            panic!(
+                "Could not decode audio file at path {:?}, likely an unimplemented file format.",
+                path.as_ref()
+            );
+        };
+        Ok(audio_file)
+    }
+
+    /// Like [Self::open_for_reading], but for a file that's already in memory.
+    /// `extension` is the file name extension, if known. Returns [Err] if the
+    /// file format isn't supported.
+    pub fn open_from_bytes(bytes: Vec<u8>, extension: Option<&str>) -> Result<Self, ()> {
         // Both WavReader::new() and CafPacketReader::new() consume the reader
         // (in this case, a Cursor) passed to them. This is a bit annoying
         // considering we don't know which is appropriate for the file without
@@ -65,14 +103,20 @@ impl AudioFile {
             Ok(AudioFile(AudioFileInner::Wave(reader)))
         } else if caf::CafPacketReader::new(Cursor::new(&bytes), vec![]).is_ok() {
             let reader = caf::CafPacketReader::new(Cursor::new(bytes), vec![]).unwrap();
-            Ok(AudioFile(AudioFileInner::Caf(reader)))
+            if matches!(
+                reader.audio_desc.format_id,
+                caf::FormatType::Ulaw | caf::FormatType::Alaw
+            ) {
+                let decoded = decode_caf_companded(reader)?;
+                Ok(AudioFile(AudioFileInner::Decoded(decoded)))
+            } else {
+                Ok(AudioFile(AudioFileInner::Caf(reader)))
+            }
+        } else if let Ok(decoded) = aiff::decode(&bytes) {
+            Ok(AudioFile(AudioFileInner::Decoded(decoded)))
         } else {
-            // We may eventually want to return an error here, this is just more
-            // useful currently.
-            panic!(
-                "Could not decode audio file at path {:?}, likely an unimplemented file format.",
-                path.as_ref()
-            );
+            let decoded = compressed::decode(bytes, extension)?;
+            Ok(AudioFile(AudioFileInner::Decoded(decoded)))
         }
     }
 
@@ -142,6 +186,21 @@ impl AudioFile {
                     bits_per_channel,
                 }
             }
+            AudioFileInner::Decoded(DecodedAudio {
+                sample_rate,
+                channels,
+                ..
+            }) => AudioDescription {
+                sample_rate: sample_rate.into(),
+                format: AudioFormat::LinearPcm {
+                    is_float: false,
+                    is_little_endian: true,
+                },
+                bytes_per_packet: channels * 2,
+                frames_per_packet: 1,
+                channels_per_frame: channels,
+                bits_per_channel: 16,
+            },
         }
     }
 
@@ -169,12 +228,13 @@ impl AudioFile {
                 // variable size not implemented
                 u64::from(self.packet_size_fixed()) * self.packet_count()
             }
+            AudioFileInner::Decoded(ref decoded) => decoded.pcm_bytes.len().try_into().unwrap(),
         }
     }
 
     pub fn packet_count(&self) -> u64 {
         match self.0 {
-            AudioFileInner::Wave(_) => {
+            AudioFileInner::Wave(_) | AudioFileInner::Decoded(_) => {
                 // never variable-size
                 self.byte_count() / u64::from(self.packet_size_fixed())
             }
@@ -252,6 +312,112 @@ impl AudioFile {
                 }
                 Ok(byte_offset)
             }
+            AudioFileInner::Decoded(ref decoded) => {
+                let pcm_bytes = &decoded.pcm_bytes;
+                let offset: usize = offset.try_into().unwrap();
+                if offset > pcm_bytes.len() {
+                    return Err(());
+                }
+                let byte_count = buffer.len().min(pcm_bytes.len() - offset);
+                buffer[..byte_count].copy_from_slice(&pcm_bytes[offset..][..byte_count]);
+                Ok(byte_count)
+            }
+        }
+    }
+
+    /// Total number of frames (samples for each channel) in the file.
+    pub fn frame_count(&self) -> u64 {
+        self.packet_count() * u64::from(self.audio_description().frames_per_packet)
+    }
+
+    /// Read up to `out.len()` samples, starting at frame `first_frame`, and
+    /// decode them to interleaved 16-bit linear PCM. `out.len()` must be a
+    /// multiple of the channel count. Returns the number of frames read, which
+    /// is less than requested at the end of the file.
+    pub fn read_pcm_frames(&mut self, first_frame: u64, out: &mut [i16]) -> Result<usize, ()> {
+        let AudioDescription {
+            format,
+            frames_per_packet,
+            channels_per_frame,
+            bits_per_channel,
+            ..
+        } = self.audio_description();
+        let channels = u64::from(channels_per_frame);
+        assert!(u64::try_from(out.len()).unwrap() % channels == 0);
+
+        let frame_count = (u64::try_from(out.len()).unwrap() / channels)
+            .min(self.frame_count().saturating_sub(first_frame));
+        let sample_count = usize::try_from(frame_count * channels).unwrap();
+
+        match format {
+            AudioFormat::LinearPcm {
+                is_float: false,
+                is_little_endian,
+            } if bits_per_channel == 16 => {
+                let mut bytes = vec![0u8; sample_count * 2];
+                let bytes_read = self.read_bytes(first_frame * channels * 2, &mut bytes)?;
+                assert!(bytes_read == bytes.len());
+                for (sample, bytes) in out.iter_mut().zip(bytes.chunks_exact(2)) {
+                    let bytes = bytes.try_into().unwrap();
+                    *sample = if is_little_endian {
+                        i16::from_le_bytes(bytes)
+                    } else {
+                        i16::from_be_bytes(bytes)
+                    };
+                }
+            }
+            AudioFormat::AppleIma4 => {
+                let frames_per_packet = u64::from(frames_per_packet);
+                let packet_size = u64::from(self.packet_size_fixed());
+                let first_packet = first_frame / frames_per_packet;
+                let skipped_frames = first_frame % frames_per_packet;
+                let packet_count = (skipped_frames + frame_count).div_ceil(frames_per_packet);
+
+                let mut bytes = vec![0u8; usize::try_from(packet_count * packet_size).unwrap()];
+                let bytes_read = self.read_bytes(first_packet * packet_size, &mut bytes)?;
+                assert!(bytes_read == bytes.len());
+                let mut samples = Vec::new();
+                decode_ima4_interleaved(&bytes, channels_per_frame as usize, &mut samples);
+                let skipped_samples = usize::try_from(skipped_frames * channels).unwrap();
+                out[..sample_count].copy_from_slice(&samples[skipped_samples..][..sample_count]);
+            }
+            _ => panic!(
+                "Decoding {:?} with {} bits per channel to PCM is not supported yet",
+                format, bits_per_channel
+            ),
         }
+
+        Ok(frame_count.try_into().unwrap())
     }
 }
+
+/// Decode a µ-law or A-law CAF file, which has a one-byte sample for each
+/// channel in each packet.
+fn decode_caf_companded(
+    mut reader: caf::CafPacketReader<Cursor<Vec<u8>>>,
+) -> Result<DecodedAudio, ()> {
+    let caf::chunks::AudioDescription {
+        sample_rate,
+        ref format_id,
+        channels_per_frame,
+        ..
+    } = reader.audio_desc;
+    let decode_sample = match format_id {
+        caf::FormatType::Ulaw => g711::decode_ulaw,
+        caf::FormatType::Alaw => g711::decode_alaw,
+        _ => unreachable!(),
+    };
+
+    let mut pcm_bytes = Vec::new();
+    while let Some(packet) = reader.next_packet().map_err(|_| ())? {
+        for &sample in &packet {
+            pcm_bytes.extend_from_slice(&decode_sample(sample).to_le_bytes());
+        }
+    }
+
+    Ok(DecodedAudio {
+        sample_rate: sample_rate.round() as u32,
+        channels: channels_per_frame,
+        pcm_bytes,
+    })
+}
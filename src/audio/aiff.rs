@@ -0,0 +1,206 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Reading of AIFF and AIFF-C files (`.aif`, `.aiff`, `.aifc`).
+//!
+//! Like with [super::compressed], the whole file is decoded to 16-bit linear
+//! PCM when it is opened. AIFF files are usually short sound effects, and the
+//! samples are big-endian, which neither OpenAL nor the audio queue
+//! implementation accept.
+//!
+//! Resources:
+//! - Apple's [Audio Interchange File Format: "AIFF"](https://web.archive.org/web/20080705115916/http://developer.apple.com/documentation/QuickTime/INMAC/SOUND/imsoundmgr.30.htm) specification, version 1.3
+//! - Apple's _Audio Interchange File Format AIFF-C_ draft, 1991
+//!   ([mirror](http://www-mmsp.ece.mcgill.ca/Documents/AudioFormats/AIFF/Docs/AIFF-C.9.26.91.pdf))
+
+use super::g711::{decode_alaw, decode_ulaw};
+use super::ima4::decode_ima4_interleaved;
+use super::DecodedAudio;
+
+/// Decode an AIFF or AIFF-C file. Returns [Err] if the file isn't one, or uses
+/// a compression type that isn't supported.
+pub fn decode(bytes: &[u8]) -> Result<DecodedAudio, ()> {
+    if bytes.len() < 12 || &bytes[0..4] != b"FORM" {
+        return Err(());
+    }
+    let is_aifc = match &bytes[8..12] {
+        b"AIFF" => false,
+        b"AIFC" => true,
+        _ => return Err(()),
+    };
+
+    let mut common = None;
+    let mut sound_data = None;
+
+    let mut chunks = &bytes[12..];
+    while chunks.len() >= 8 {
+        let id = &chunks[0..4];
+        let size = u32::from_be_bytes(chunks[4..8].try_into().unwrap()) as usize;
+        let data = chunks[8..].get(..size).ok_or(())?;
+        match id {
+            b"COMM" => common = Some(data),
+            b"SSND" => {
+                if data.len() < 8 {
+                    return Err(());
+                }
+                let offset = u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize;
+                sound_data = Some(data[8..].get(offset..).ok_or(())?);
+            }
+            _ => (),
+        }
+        // Chunks are padded to an even size.
+        let padded_size = size + (size & 1);
+        chunks = chunks.get(8 + padded_size..).unwrap_or(&[]);
+    }
+
+    let common = common.ok_or(())?;
+    if common.len() < 18 || (is_aifc && common.len() < 22) {
+        return Err(());
+    }
+    let channels = u16::from_be_bytes(common[0..2].try_into().unwrap());
+    let frame_count = u32::from_be_bytes(common[2..6].try_into().unwrap());
+    let sample_size = u16::from_be_bytes(common[6..8].try_into().unwrap());
+    let sample_rate = parse_extended(common[8..18].try_into().unwrap());
+    let compression: &[u8] = if is_aifc { &common[18..22] } else { b"NONE" };
+
+    if channels == 0 || !(1.0..=1_000_000.0).contains(&sample_rate) {
+        return Err(());
+    }
+
+    // A file with no samples may have no sound data chunk.
+    let sound_data = sound_data.unwrap_or(&[]);
+
+    let mut samples = Vec::<i16>::new();
+    match compression {
+        // Big-endian ("twos" complement) and little-endian ("sowt") PCM.
+        // Samples are padded to a whole number of bytes, and sizes other than
+        // 8 and 16 bits are reduced to 16 bits.
+        b"NONE" | b"twos" | b"sowt" => {
+            let sample_bytes = usize::from(sample_size).div_ceil(8);
+            if !(1..=4).contains(&sample_bytes) {
+                return Err(());
+            }
+            let sample_count = (frame_count as usize) * usize::from(channels);
+            for sample in sound_data.chunks_exact(sample_bytes).take(sample_count) {
+                let (high, low) = match (compression, sample_bytes) {
+                    (_, 1) => (sample[0], 0),
+                    (b"sowt", n) => (sample[n - 1], sample[n - 2]),
+                    _ => (sample[0], sample[1]),
+                };
+                samples.push(i16::from_be_bytes([high, low]));
+            }
+        }
+        b"ulaw" | b"ULAW" => samples.extend(sound_data.iter().map(|&s| decode_ulaw(s))),
+        b"alaw" | b"ALAW" => samples.extend(sound_data.iter().map(|&s| decode_alaw(s))),
+        b"ima4" => {
+            let channels = usize::from(channels);
+            let packet_size = 34 * channels;
+            let whole_packets = &sound_data[..sound_data.len() - sound_data.len() % packet_size];
+            decode_ima4_interleaved(whole_packets, channels, &mut samples);
+        }
+        _ => {
+            log!(
+                "Warning: AIFF-C compression type {:?} is not supported",
+                String::from_utf8_lossy(compression)
+            );
+            return Err(());
+        }
+    }
+
+    Ok(DecodedAudio {
+        sample_rate: sample_rate.round() as u32,
+        channels: channels.into(),
+        pcm_bytes: samples.iter().flat_map(|s| s.to_le_bytes()).collect(),
+    })
+}
+
+/// Convert an 80-bit IEEE 754 extended precision number, which is how AIFF
+/// stores the sample rate.
+fn parse_extended(bytes: [u8; 10]) -> f64 {
+    let sign_and_exponent = u16::from_be_bytes(bytes[0..2].try_into().unwrap());
+    // Unlike in the other IEEE 754 formats, the integer bit is explicit.
+    let mantissa = u64::from_be_bytes(bytes[2..10].try_into().unwrap());
+    let exponent = i32::from(sign_and_exponent & 0x7fff) - 16383 - 63;
+    let value = (mantissa as f64) * 2f64.powi(exponent);
+    if sign_and_exponent & 0x8000 != 0 {
+        -value
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended() {
+        assert_eq!(
+            parse_extended([0x40, 0x0e, 0xac, 0x44, 0, 0, 0, 0, 0, 0]),
+            44100.0
+        );
+        assert_eq!(
+            parse_extended([0x40, 0x0d, 0xac, 0x44, 0, 0, 0, 0, 0, 0]),
+            22050.0
+        );
+        assert_eq!(parse_extended([0; 10]), 0.0);
+    }
+
+    fn aiff(form_type: &[u8; 4], common: &[u8], sound_data: &[u8]) -> Vec<u8> {
+        let mut chunks = Vec::new();
+        chunks.extend_from_slice(b"COMM");
+        chunks.extend_from_slice(&(common.len() as u32).to_be_bytes());
+        chunks.extend_from_slice(common);
+        chunks.extend_from_slice(b"SSND");
+        chunks.extend_from_slice(&(sound_data.len() as u32 + 8).to_be_bytes());
+        chunks.extend_from_slice(&[0; 8]);
+        chunks.extend_from_slice(sound_data);
+
+        let mut file = Vec::new();
+        file.extend_from_slice(b"FORM");
+        file.extend_from_slice(&(chunks.len() as u32 + 4).to_be_bytes());
+        file.extend_from_slice(form_type);
+        file.extend_from_slice(&chunks);
+        file
+    }
+
+    const RATE_22050: [u8; 10] = [0x40, 0x0d, 0xac, 0x44, 0, 0, 0, 0, 0, 0];
+
+    #[test]
+    fn pcm() {
+        let mut common = vec![0, 2, 0, 0, 0, 2, 0, 16];
+        common.extend_from_slice(&RATE_22050);
+        let file = aiff(b"AIFF", &common, &[0x12, 0x34, 0xff, 0xfe, 0, 1, 0x80, 0]);
+        let decoded = decode(&file).unwrap();
+        assert_eq!(decoded.sample_rate, 22050);
+        assert_eq!(decoded.channels, 2);
+        assert_eq!(decoded.pcm_bytes, [0x34, 0x12, 0xfe, 0xff, 1, 0, 0, 0x80]);
+    }
+
+    #[test]
+    fn pcm_8_bit() {
+        let mut common = vec![0, 1, 0, 0, 0, 2, 0, 8];
+        common.extend_from_slice(&RATE_22050);
+        let file = aiff(b"AIFF", &common, &[0x7f, 0x80]);
+        let decoded = decode(&file).unwrap();
+        assert_eq!(decoded.pcm_bytes, [0, 0x7f, 0, 0x80]);
+    }
+
+    #[test]
+    fn aifc_ulaw() {
+        let mut common = vec![0, 1, 0, 0, 0, 2, 0, 16];
+        common.extend_from_slice(&RATE_22050);
+        common.extend_from_slice(b"ulaw\0\0");
+        let file = aiff(b"AIFC", &common, &[0xff, 0x80]);
+        let decoded = decode(&file).unwrap();
+        assert_eq!(decoded.pcm_bytes, [0, 0, 0x7c, 0x7d]);
+    }
+
+    #[test]
+    fn not_aiff() {
+        assert!(decode(b"RIFF\0\0\0\0WAVE").is_err());
+        assert!(decode(b"").is_err());
+    }
+}
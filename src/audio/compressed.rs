@@ -0,0 +1,114 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Decoding of compressed audio files (MP3, and AAC in MPEG-4 or ADTS
+//! containers) using [symphonia].
+//!
+//! Apps get the decoded audio as 16-bit linear PCM rather than the original
+//! packets: this way they can play it with the same code paths as any other
+//! PCM file, and there's no need for an MP3 or AAC decoder in the audio queue
+//! implementation. The whole file is decoded when it is opened, which takes
+//! some time for a long music track, but apps usually open those before
+//! playing them and not during gameplay.
+
+use super::DecodedAudio;
+use std::io::Cursor;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Decode an MP3 or AAC file. `extension` is the file name extension, if any,
+/// which helps with recognizing the format. Returns [Err] if the file isn't in
+/// a supported format.
+pub fn decode(bytes: Vec<u8>, extension: Option<&str>) -> Result<DecodedAudio, ()> {
+    let mut hint = Hint::new();
+    if let Some(extension) = extension {
+        hint.with_extension(extension);
+    }
+    let stream = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|_| ())?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(())?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|_| ())?;
+
+    let mut sample_rate = track.codec_params.sample_rate;
+    let mut channels = track.codec_params.channels.map(|c| c.count());
+    let mut pcm_bytes = Vec::new();
+    let mut sample_buffer: Option<SampleBuffer<i16>> = None;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            // This is how the end of the file is reported.
+            Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => {
+                log!("Warning: stopped decoding audio file early: {}", e);
+                break;
+            }
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // Corrupt packets are skipped, like other decoders do.
+            Err(Error::DecodeError(e)) => {
+                log_dbg!("Skipping undecodable audio packet: {}", e);
+                continue;
+            }
+            Err(e) => {
+                log!("Warning: stopped decoding audio file early: {}", e);
+                break;
+            }
+        };
+
+        let spec = *decoded.spec();
+        sample_rate.get_or_insert(spec.rate);
+        channels.get_or_insert(spec.channels.count());
+
+        // The buffer only needs to be recreated if the packet is larger than
+        // any before it.
+        let sample_count = decoded.capacity() * spec.channels.count();
+        let sample_buffer = match sample_buffer {
+            Some(ref mut sample_buffer) if sample_buffer.capacity() >= sample_count => {
+                sample_buffer
+            }
+            _ => sample_buffer.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+        };
+        sample_buffer.copy_interleaved_ref(decoded);
+        for &sample in sample_buffer.samples() {
+            pcm_bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+    }
+
+    let (Some(sample_rate), Some(channels)) = (sample_rate, channels) else {
+        return Err(());
+    };
+    Ok(DecodedAudio {
+        sample_rate,
+        channels: channels.try_into().unwrap(),
+        pcm_bytes,
+    })
+}
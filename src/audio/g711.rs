@@ -0,0 +1,69 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Decoders for the G.711 companded formats, µ-law (FourCC: `ulaw`) and
+//! A-law (FourCC: `alaw`). Each sample is a single byte.
+//!
+//! Resources:
+//! - ITU-T Recommendation [G.711](https://www.itu.int/rec/T-REC-G.711)
+//! - MultimediaWiki's [PCM](https://wiki.multimedia.cx/index.php/PCM#Logarithmic_PCM) page
+
+/// Decode a µ-law sample to 16-bit signed integer PCM.
+pub fn decode_ulaw(sample: u8) -> i16 {
+    // All bits are stored inverted.
+    let sample = !sample;
+    let exponent = (sample >> 4) & 0x7;
+    let mantissa = i16::from(sample & 0xf);
+    // The bias of 0x84 (132) is added before encoding, so it's taken away
+    // here.
+    let magnitude = (((mantissa << 3) + 0x84) << exponent) - 0x84;
+    if sample & 0x80 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Decode an A-law sample to 16-bit signed integer PCM.
+pub fn decode_alaw(sample: u8) -> i16 {
+    // Every other bit is stored inverted.
+    let sample = sample ^ 0x55;
+    let exponent = (sample >> 4) & 0x7;
+    let mantissa = i16::from(sample & 0xf);
+    let magnitude = if exponent == 0 {
+        (mantissa << 4) + 8
+    } else {
+        ((mantissa << 4) + 0x108) << (exponent - 1)
+    };
+    if sample & 0x80 != 0 {
+        magnitude
+    } else {
+        -magnitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ulaw() {
+        assert_eq!(decode_ulaw(0xff), 0);
+        assert_eq!(decode_ulaw(0x7f), 0);
+        assert_eq!(decode_ulaw(0xfe), 8);
+        assert_eq!(decode_ulaw(0x80), 32124);
+        assert_eq!(decode_ulaw(0x00), -32124);
+        assert_eq!(decode_ulaw(0xef), 132);
+    }
+
+    #[test]
+    fn alaw() {
+        assert_eq!(decode_alaw(0xd5), 8);
+        assert_eq!(decode_alaw(0x55), -8);
+        assert_eq!(decode_alaw(0xaa), 32256);
+        assert_eq!(decode_alaw(0x2a), -32256);
+        assert_eq!(decode_alaw(0xc5), 264);
+    }
+}
@@ -75,3 +75,19 @@ pub fn decode_ima4(in_packet: &[u8; 34]) -> [i16; 64] {
 
     out_packet
 }
+
+/// Decode IMA4 data with any number of channels to interleaved 16-bit signed
+/// integer PCM, appending it to `out`. Each packet in `data` has one 34-byte
+/// packet for each channel, as described for [decode_ima4].
+pub fn decode_ima4_interleaved(data: &[u8], channels: usize, out: &mut Vec<i16>) {
+    assert!(data.len() % (34 * channels) == 0);
+    for packet in data.chunks_exact(34 * channels) {
+        let decoded: Vec<[i16; 64]> = packet
+            .chunks_exact(34)
+            .map(|channel| decode_ima4(channel.try_into().unwrap()))
+            .collect();
+        for frame in 0..64 {
+            out.extend(decoded.iter().map(|channel| channel[frame]));
+        }
+    }
+}
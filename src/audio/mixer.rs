@@ -0,0 +1,174 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Settings for OpenAL Soft's mixer, which plays all of the app's audio on the
+//! host. See `--audio-buffer-size=` and `--audio-resampler=`.
+
+use super::openal as al;
+use super::openal::al_types::*;
+use super::openal::alc_types::*;
+use crate::Options;
+use std::ffi::CStr;
+
+/// Resampler used when a sound's sample rate differs from the host device's,
+/// or its pitch is changed. See `--audio-resampler=`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Resampler {
+    /// Nearest sample. Cheapest, but very noisy.
+    Point,
+    Linear,
+    Cubic,
+    /// The highest-quality sinc resampler OpenAL Soft has.
+    Sinc,
+}
+impl Resampler {
+    pub fn parse(value: &str) -> Result<Resampler, String> {
+        match value {
+            "point" => Ok(Resampler::Point),
+            "linear" => Ok(Resampler::Linear),
+            "cubic" => Ok(Resampler::Cubic),
+            "sinc" => Ok(Resampler::Sinc),
+            _ => Err(format!("Unknown audio resampler {:?}", value)),
+        }
+    }
+
+    /// Find the index of this resampler among the names OpenAL Soft lists.
+    /// The names vary between versions of OpenAL Soft, so this is fuzzy.
+    fn find_in(self, names: &[String]) -> Option<usize> {
+        let mut names = names
+            .iter()
+            .map(|name| name.to_ascii_lowercase())
+            .enumerate();
+        match self {
+            Resampler::Point => names
+                .find(|(_, name)| name.contains("nearest") || name.contains("point"))
+                .map(|(i, _)| i),
+            Resampler::Linear => names
+                .find(|(_, name)| name.contains("linear"))
+                .map(|(i, _)| i),
+            Resampler::Cubic => names
+                .find(|(_, name)| name.contains("cubic"))
+                .map(|(i, _)| i),
+            // Sinc resamplers are listed from lowest to highest quality.
+            Resampler::Sinc => names
+                .rev()
+                .find(|(_, name)| name.contains("sinc"))
+                .map(|(i, _)| i),
+        }
+    }
+}
+
+/// Get the attributes to pass to `alcCreateContext` for a context on a host
+/// device, in addition to any the app passes. The list isn't terminated.
+pub fn context_attributes(device: *mut ALCdevice, options: &Options) -> Vec<ALCint> {
+    let mut attrs = Vec::new();
+    if let Some(buffer_size) = options.audio_buffer_size {
+        // OpenAL Soft mixes the device's buffers at a fixed rate, so the
+        // buffer size is set via the rate at which they're mixed.
+        let mut frequency = 0;
+        unsafe { al::alcGetIntegerv(device, al::ALC_FREQUENCY, 1, &mut frequency) };
+        if frequency <= 0 {
+            frequency = 44100;
+        }
+        let refresh = (frequency / ALCint::try_from(buffer_size).unwrap()).max(1);
+        attrs.extend_from_slice(&[al::ALC_REFRESH, refresh]);
+    }
+    attrs
+}
+
+/// Apply `--audio-resampler=` to a newly created OpenAL source. The context
+/// it belongs to must be current. This doesn't check for errors, so that it
+/// can't clear an error the app hasn't checked for yet.
+pub fn configure_source(al_source: ALuint, options: &Options) {
+    let Some(resampler) = options.audio_resampler else {
+        return;
+    };
+    let names = resampler_names();
+    let Some(index) = resampler.find_in(&names) else {
+        log_dbg!(
+            "No {:?} resampler in OpenAL Soft's list: {:?}",
+            resampler,
+            names
+        );
+        return;
+    };
+    unsafe {
+        al::alSourcei(
+            al_source,
+            al::AL_SOURCE_RESAMPLER_SOFT,
+            index.try_into().unwrap(),
+        )
+    };
+}
+
+fn resampler_names() -> Vec<String> {
+    let mut count: ALint = 0;
+    unsafe { al::alGetIntegerv(al::AL_NUM_RESAMPLERS_SOFT, &mut count) };
+    (0..count)
+        .map(|i| {
+            let name = unsafe { al::alGetStringiSOFT(al::AL_RESAMPLER_NAME_SOFT, i) };
+            assert!(!name.is_null());
+            unsafe { CStr::from_ptr(name) }
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+/// Pick a playback rate (OpenAL pitch) for a stream whose audio is produced
+/// on the guest's clock but consumed on the host device's clock, which always
+/// runs a little faster or slower. Playing slightly faster when too much audio
+/// is queued, or slower when too little is, keeps the amount queued close to
+/// the target, instead of it slowly growing (more latency) or shrinking
+/// (crackling). Both amounts are in seconds.
+pub fn drift_correction_pitch(queued: f64, target: f64) -> f32 {
+    /// Changes in pitch this small can't be heard.
+    const MAX_CORRECTION: f64 = 0.005;
+    let error = (queued - target) / target;
+    (1.0 + (error * MAX_CORRECTION).clamp(-MAX_CORRECTION, MAX_CORRECTION)) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resampler_lookup() {
+        let old = [
+            "Point",
+            "Linear",
+            "Cubic",
+            "11th order Sinc",
+            "23rd order Sinc",
+        ]
+        .map(String::from);
+        let new = [
+            "Nearest",
+            "Linear",
+            "Cubic Spline",
+            "4-point Gaussian",
+            "11th order Sinc (fast)",
+            "11th order Sinc",
+            "23rd order Sinc (fast)",
+            "23rd order Sinc",
+        ]
+        .map(String::from);
+        assert_eq!(Resampler::Point.find_in(&old), Some(0));
+        assert_eq!(Resampler::Point.find_in(&new), Some(0));
+        assert_eq!(Resampler::Cubic.find_in(&new), Some(2));
+        assert_eq!(Resampler::Sinc.find_in(&old), Some(4));
+        assert_eq!(Resampler::Sinc.find_in(&new), Some(7));
+        assert_eq!(Resampler::Sinc.find_in(&old[..3]), None);
+    }
+
+    #[test]
+    fn drift_correction() {
+        assert_eq!(drift_correction_pitch(0.05, 0.05), 1.0);
+        assert!(drift_correction_pitch(0.06, 0.05) > 1.0);
+        assert!(drift_correction_pitch(0.04, 0.05) < 1.0);
+        assert!((drift_correction_pitch(10.0, 0.05) - 1.005).abs() < 1e-6);
+        assert!((drift_correction_pitch(0.0, 0.05) - 0.995).abs() < 1e-6);
+    }
+}
@@ -48,6 +48,18 @@ pub const ALC_FALSE: ALCboolean = 0;
 #[allow(dead_code)]
 pub const ALC_TRUE: ALCboolean = 1;
 
+pub const ALC_DEFAULT_DEVICE_SPECIFIER: ALCenum = 0x1004;
+pub const ALC_DEVICE_SPECIFIER: ALCenum = 0x1005;
+pub const ALC_EXTENSIONS: ALCenum = 0x1006;
+pub const ALC_FREQUENCY: ALCenum = 0x1007;
+pub const ALC_REFRESH: ALCenum = 0x1008;
+pub const ALC_CAPTURE_DEVICE_SPECIFIER: ALCenum = 0x310;
+pub const ALC_CAPTURE_DEFAULT_DEVICE_SPECIFIER: ALCenum = 0x311;
+pub const ALC_CAPTURE_SAMPLES: ALCenum = 0x312;
+
+pub const ALC_INVALID_DEVICE: ALCenum = 0xA001;
+pub const ALC_INVALID_VALUE: ALCenum = 0xA004;
+
 extern "C" {
     pub fn alcOpenDevice(devicename: *const ALCchar) -> *mut ALCdevice;
     pub fn alcCloseDevice(device: *mut ALCdevice) -> ALCboolean;
@@ -56,9 +68,31 @@ extern "C" {
     pub fn alcDestroyContext(context: *mut ALCcontext);
 
     pub fn alcMakeContextCurrent(context: *mut ALCcontext) -> ALCboolean;
+    pub fn alcProcessContext(context: *mut ALCcontext);
+    pub fn alcSuspendContext(context: *mut ALCcontext);
     pub fn alcGetCurrentContext() -> *mut ALCcontext;
+    pub fn alcGetContextsDevice(context: *mut ALCcontext) -> *mut ALCdevice;
 
     pub fn alcGetError(device: *mut ALCdevice) -> ALCenum;
+
+    pub fn alcGetString(device: *mut ALCdevice, param: ALCenum) -> *const ALCchar;
+    pub fn alcGetIntegerv(
+        device: *mut ALCdevice,
+        param: ALCenum,
+        size: ALCsizei,
+        values: *mut ALCint,
+    );
+
+    pub fn alcCaptureOpenDevice(
+        devicename: *const ALCchar,
+        frequency: ALCuint,
+        format: ALCenum,
+        buffersize: ALCsizei,
+    ) -> *mut ALCdevice;
+    pub fn alcCaptureCloseDevice(device: *mut ALCdevice) -> ALCboolean;
+    pub fn alcCaptureStart(device: *mut ALCdevice);
+    pub fn alcCaptureStop(device: *mut ALCdevice);
+    pub fn alcCaptureSamples(device: *mut ALCdevice, buffer: *mut ALCvoid, samples: ALCsizei);
 }
 
 // === al.h ===
@@ -84,8 +118,20 @@ pub mod al_types {
 use al_types::*;
 
 pub const AL_NO_ERROR: ALenum = 0;
-
+pub const AL_INVALID_OPERATION: ALenum = 0xA004;
+
+pub const AL_FALSE: ALboolean = 0;
+pub const AL_TRUE: ALboolean = 1;
+
+pub const AL_PITCH: ALenum = 0x1003;
+pub const AL_POSITION: ALenum = 0x1004;
+pub const AL_DIRECTION: ALenum = 0x1005;
+pub const AL_VELOCITY: ALenum = 0x1006;
+pub const AL_LOOPING: ALenum = 0x1007;
+pub const AL_BUFFER: ALenum = 0x1009;
+pub const AL_GAIN: ALenum = 0x100A;
 pub const AL_MAX_GAIN: ALenum = 0x100E;
+pub const AL_ORIENTATION: ALenum = 0x100F;
 
 pub const AL_SOURCE_STATE: ALenum = 0x1010;
 
@@ -97,30 +143,75 @@ pub const AL_STOPPED: ALenum = 0x1014;
 pub const AL_BUFFERS_QUEUED: ALenum = 0x1015;
 pub const AL_BUFFERS_PROCESSED: ALenum = 0x1016;
 
+pub const AL_SEC_OFFSET: ALenum = 0x1024;
+pub const AL_SAMPLE_OFFSET: ALenum = 0x1025;
+
 pub const AL_FORMAT_MONO8: ALenum = 0x1100;
 pub const AL_FORMAT_MONO16: ALenum = 0x1101;
 pub const AL_FORMAT_STEREO8: ALenum = 0x1102;
 pub const AL_FORMAT_STEREO16: ALenum = 0x1103;
 
+pub const AL_VENDOR: ALenum = 0xB001;
+pub const AL_VERSION: ALenum = 0xB002;
+pub const AL_RENDERER: ALenum = 0xB003;
+pub const AL_EXTENSIONS: ALenum = 0xB004;
+
 extern "C" {
     pub fn alGetError() -> ALenum;
 
+    pub fn alGetString(param: ALenum) -> *const ALchar;
+    pub fn alGetBooleanv(param: ALenum, values: *mut ALboolean);
+    pub fn alGetIntegerv(param: ALenum, values: *mut ALint);
+    pub fn alGetFloatv(param: ALenum, values: *mut ALfloat);
+    pub fn alGetDoublev(param: ALenum, values: *mut ALdouble);
+
+    pub fn alDopplerFactor(value: ALfloat);
+    pub fn alDopplerVelocity(value: ALfloat);
+    pub fn alSpeedOfSound(value: ALfloat);
+    pub fn alDistanceModel(distance_model: ALenum);
+
+    pub fn alListenerf(param: ALenum, value: ALfloat);
+    pub fn alListenerfv(param: ALenum, values: *const ALfloat);
+    pub fn alListeneri(param: ALenum, value: ALint);
+    pub fn alListeneriv(param: ALenum, values: *const ALint);
+    pub fn alGetListenerf(param: ALenum, value: *mut ALfloat);
+    pub fn alGetListenerfv(param: ALenum, values: *mut ALfloat);
+    pub fn alGetListeneri(param: ALenum, value: *mut ALint);
+    pub fn alGetListeneriv(param: ALenum, values: *mut ALint);
+
     pub fn alGenSources(n: ALsizei, sources: *mut ALuint);
     pub fn alDeleteSources(n: ALsizei, sources: *const ALuint);
+    pub fn alIsSource(source: ALuint) -> ALboolean;
 
     pub fn alSourcef(source: ALuint, param: ALenum, value: ALfloat);
+    pub fn alSourcefv(source: ALuint, param: ALenum, values: *const ALfloat);
     pub fn alSourcei(source: ALuint, param: ALenum, value: ALint);
+    pub fn alSourceiv(source: ALuint, param: ALenum, values: *const ALint);
     pub fn alGetSourcef(source: ALuint, param: ALenum, value: *mut ALfloat);
+    pub fn alGetSourcefv(source: ALuint, param: ALenum, values: *mut ALfloat);
     pub fn alGetSourcei(source: ALuint, param: ALenum, value: *mut ALint);
+    pub fn alGetSourceiv(source: ALuint, param: ALenum, values: *mut ALint);
 
     pub fn alSourcePlay(source: ALuint);
     pub fn alSourceStop(source: ALuint);
+    pub fn alSourcePause(source: ALuint);
+    pub fn alSourceRewind(source: ALuint);
+    pub fn alSourcePlayv(n: ALsizei, sources: *const ALuint);
+    pub fn alSourceStopv(n: ALsizei, sources: *const ALuint);
+    pub fn alSourcePausev(n: ALsizei, sources: *const ALuint);
+    pub fn alSourceRewindv(n: ALsizei, sources: *const ALuint);
 
     pub fn alSourceQueueBuffers(source: ALuint, nb: ALsizei, buffers: *const ALuint);
     pub fn alSourceUnqueueBuffers(source: ALuint, nb: ALsizei, buffers: *mut ALuint);
 
     pub fn alGenBuffers(n: ALsizei, buffers: *mut ALuint);
     pub fn alDeleteBuffers(n: ALsizei, buffers: *const ALuint);
+    pub fn alIsBuffer(buffer: ALuint) -> ALboolean;
+
+    pub fn alBufferf(buffer: ALuint, param: ALenum, value: ALfloat);
+    pub fn alBufferi(buffer: ALuint, param: ALenum, value: ALint);
+    pub fn alGetBufferf(buffer: ALuint, param: ALenum, value: *mut ALfloat);
+    pub fn alGetBufferi(buffer: ALuint, param: ALenum, value: *mut ALint);
 
     pub fn alBufferData(
         buffer: ALuint,
@@ -130,3 +221,15 @@ extern "C" {
         samplerate: ALsizei,
     );
 }
+
+// === alext.h ===
+
+// AL_SOFT_source_resampler
+pub const AL_NUM_RESAMPLERS_SOFT: ALenum = 0x1210;
+pub const AL_DEFAULT_RESAMPLER_SOFT: ALenum = 0x1211;
+pub const AL_SOURCE_RESAMPLER_SOFT: ALenum = 0x1212;
+pub const AL_RESAMPLER_NAME_SOFT: ALenum = 0x1213;
+
+extern "C" {
+    pub fn alGetStringiSOFT(pname: ALenum, index: ALsizei) -> *const ALchar;
+}
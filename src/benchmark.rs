@@ -0,0 +1,252 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Benchmark mode (`--benchmark-seconds=` and `--benchmark-frames=`), for
+//! measuring the performance of touchHLE objectively.
+//!
+//! Measuring starts when the app presents its first frame, so that loading
+//! isn't counted, and stops after the chosen number of seconds or frames. Then
+//! statistics are printed and the app exits (see [Environment::exit]).
+//!
+//! As well as frame times, the time spent on each [Activity] is measured. The
+//! current activity is changed at the boundaries between guest and host code,
+//! and the previous one is restored afterwards, so that e.g. guest code called
+//! by a host function is counted correctly.
+
+use crate::mem::GuestUSize;
+use crate::Environment;
+use std::time::{Duration, Instant};
+
+/// What touchHLE is spending its time on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Activity {
+    /// Running guest code on the emulated CPU.
+    Guest,
+    /// Running touchHLE's OpenGL ES functions, and presenting frames.
+    Gl,
+    /// Anything else: other host functions, handling events, waiting, etc.
+    Host,
+}
+impl Activity {
+    const ALL: [Activity; 3] = [Activity::Guest, Activity::Gl, Activity::Host];
+
+    fn description(self) -> &'static str {
+        match self {
+            Activity::Guest => "emulating the CPU",
+            Activity::Gl => "in OpenGL ES and presenting",
+            Activity::Host => "in other host code and waiting",
+        }
+    }
+}
+
+pub struct Benchmark {
+    max_duration: Option<Duration>,
+    max_frames: Option<u32>,
+    /// When the first frame was presented, i.e. when measuring started, and
+    /// when the most recent one was.
+    first_frame: Option<Instant>,
+    last_frame: Option<Instant>,
+    /// The time between each frame and the one before it.
+    frame_times: Vec<Duration>,
+    activity: Activity,
+    activity_since: Instant,
+    /// Time spent on each activity, indexed like [Activity::ALL].
+    activity_times: [Duration; 3],
+    /// [crate::mem::Mem::allocation_counts] and
+    /// [crate::mem::Mem::bytes_allocated] when measuring started.
+    start_allocation_counts: (u64, u64),
+    start_bytes_allocated: GuestUSize,
+}
+
+impl Benchmark {
+    /// Returns [None] if benchmark mode isn't enabled.
+    pub fn new(options: &crate::Options) -> Option<Self> {
+        if options.benchmark_seconds.is_none() && options.benchmark_frames.is_none() {
+            return None;
+        }
+        Some(Benchmark {
+            max_duration: options.benchmark_seconds.map(Duration::from_secs_f64),
+            max_frames: options.benchmark_frames,
+            first_frame: None,
+            last_frame: None,
+            frame_times: Vec::new(),
+            activity: Activity::Host,
+            activity_since: Instant::now(),
+            activity_times: Default::default(),
+            start_allocation_counts: (0, 0),
+            start_bytes_allocated: 0,
+        })
+    }
+}
+
+/// Change what touchHLE is spending its time on, returning what it was before,
+/// so that it can be restored.
+pub fn set_activity(env: &mut Environment, activity: Activity) -> Activity {
+    let Some(benchmark) = &mut env.benchmark else {
+        return activity;
+    };
+    let now = Instant::now();
+    if benchmark.first_frame.is_some() {
+        let idx = Activity::ALL
+            .iter()
+            .position(|&a| a == benchmark.activity)
+            .unwrap();
+        benchmark.activity_times[idx] += now - benchmark.activity_since;
+    }
+    benchmark.activity_since = now;
+    std::mem::replace(&mut benchmark.activity, activity)
+}
+
+/// For use by [crate::frameworks::opengles::eagl]: record that the app
+/// presented a frame.
+pub fn frame_presented(env: &mut Environment) {
+    let Some(benchmark) = &mut env.benchmark else {
+        return;
+    };
+    let now = Instant::now();
+    if let Some(last_frame) = benchmark.last_frame {
+        benchmark.frame_times.push(now - last_frame);
+    } else {
+        log!("Benchmark started");
+        benchmark.first_frame = Some(now);
+        benchmark.activity_since = now;
+        benchmark.start_allocation_counts = env.mem.allocation_counts();
+        benchmark.start_bytes_allocated = env.mem.bytes_allocated();
+        // The peak is kept by the allocator, so that allocations that are
+        // freed before the next frame still count.
+        env.mem.reset_peak_bytes_allocated();
+    }
+    benchmark.last_frame = Some(now);
+    check_finished(env);
+}
+
+/// Print the results and exit, if the benchmark has run for long enough. This
+/// is checked on each frame, but should also be called regularly in case the
+/// app stops presenting frames.
+pub fn check_finished(env: &mut Environment) {
+    let Some(benchmark) = &env.benchmark else {
+        return;
+    };
+    let Some(first_frame) = benchmark.first_frame else {
+        return;
+    };
+    let frames: u32 = benchmark.frame_times.len().try_into().unwrap();
+    if benchmark
+        .max_duration
+        .is_some_and(|max_duration| first_frame.elapsed() >= max_duration)
+        || benchmark
+            .max_frames
+            .is_some_and(|max_frames| frames >= max_frames)
+    {
+        finish(env);
+    }
+}
+
+fn finish(env: &mut Environment) -> ! {
+    // Make sure the time since the last change is counted.
+    let activity = env.benchmark.as_ref().unwrap().activity;
+    set_activity(env, activity);
+    let benchmark = env.benchmark.as_ref().unwrap();
+
+    let duration = benchmark.first_frame.unwrap().elapsed();
+    let mut frame_times = benchmark.frame_times.clone();
+    frame_times.sort();
+
+    println!(
+        "Benchmark results ({} frames in {:.2}s):",
+        frame_times.len(),
+        duration.as_secs_f64()
+    );
+    if !frame_times.is_empty() {
+        let total: Duration = frame_times.iter().sum();
+        let mean = total / u32::try_from(frame_times.len()).unwrap();
+        println!(
+            "Frame times: mean {}, median {}, 90th percentile {}, 99th percentile {}, worst {} ({:.1} FPS on average)",
+            format_ms(mean),
+            format_ms(percentile(&frame_times, 50.0)),
+            format_ms(percentile(&frame_times, 90.0)),
+            format_ms(percentile(&frame_times, 99.0)),
+            format_ms(*frame_times.last().unwrap()),
+            frame_times.len() as f64 / total.as_secs_f64(),
+        );
+    }
+
+    let total_activity_time: Duration = benchmark.activity_times.iter().sum();
+    let activities: Vec<String> = Activity::ALL
+        .iter()
+        .zip(benchmark.activity_times)
+        .map(|(activity, time)| {
+            format!(
+                "{:.1}% {} ({:.2}s)",
+                fraction(time, total_activity_time) * 100.0,
+                activity.description(),
+                time.as_secs_f64()
+            )
+        })
+        .collect();
+    println!("Time spent: {}", activities.join(", "));
+
+    let (allocations, frees) = env.mem.allocation_counts();
+    let (start_allocations, start_frees) = benchmark.start_allocation_counts;
+    println!(
+        "Guest heap: {} allocations, {} frees; {} bytes allocated at the start, {} at the end, {} at the peak",
+        allocations - start_allocations,
+        frees - start_frees,
+        benchmark.start_bytes_allocated,
+        env.mem.bytes_allocated(),
+        env.mem.peak_bytes_allocated(),
+    );
+
+    env.exit(0);
+}
+
+/// Get a percentile of some sorted durations, using the nearest-rank method.
+fn percentile(sorted: &[Duration], percent: f64) -> Duration {
+    assert!(!sorted.is_empty());
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn fraction(part: Duration, total: Duration) -> f64 {
+    if total.is_zero() {
+        0.0
+    } else {
+        part.as_secs_f64() / total.as_secs_f64()
+    }
+}
+
+fn format_ms(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let ms = Duration::from_millis;
+        let times: Vec<Duration> = (1..=100).map(ms).collect();
+        assert_eq!(percentile(&times, 50.0), ms(50));
+        assert_eq!(percentile(&times, 90.0), ms(90));
+        assert_eq!(percentile(&times, 99.0), ms(99));
+        assert_eq!(percentile(&times, 100.0), ms(100));
+        assert_eq!(percentile(&times, 0.0), ms(1));
+
+        let times = [ms(10), ms(20), ms(30)];
+        assert_eq!(percentile(&times, 50.0), ms(20));
+        assert_eq!(percentile(&times, 90.0), ms(30));
+        assert_eq!(percentile(&[ms(5)], 99.0), ms(5));
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(fraction(Duration::ZERO, Duration::ZERO), 0.0);
+        assert_eq!(
+            fraction(Duration::from_secs(1), Duration::from_secs(4)),
+            0.25
+        );
+    }
+}
@@ -64,6 +64,11 @@ impl Bundle {
         &self.path
     }
 
+    /// The contents of the `Info.plist` file.
+    pub fn info_plist(&self) -> &Dictionary {
+        &self.plist
+    }
+
     pub fn bundle_identifier(&self) -> &str {
         self.plist["CFBundleIdentifier"].as_string().unwrap()
     }
@@ -95,6 +100,18 @@ impl Bundle {
         }
     }
 
+    /// Fonts shipped in the bundle for the app's own use (`UIAppFonts`).
+    pub fn app_font_paths(&self) -> Vec<GuestPathBuf> {
+        let Some(fonts) = self.plist.get("UIAppFonts").and_then(Value::as_array) else {
+            return Vec::new();
+        };
+        fonts
+            .iter()
+            .filter_map(Value::as_string)
+            .map(|filename| self.path.join(filename))
+            .collect()
+    }
+
     pub fn main_nib_file_path(&self) -> GuestPathBuf {
         // FIXME: There might not be a main nib file, or it might be localised
         // and have multiple paths. This method should definitely be removed
@@ -0,0 +1,95 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The clock the app sees. It keeps time with the host's clock, except while
+//! fast-forwarding (see the `fast-forward` hotkey), when it runs faster so that
+//! the app does too.
+//!
+//! Things the app uses to measure time, like `mach_absolute_time`,
+//! `CACurrentMediaTime`, `NSDate` and timers, should use this rather than
+//! [Instant] directly.
+
+use std::time::{Duration, Instant};
+
+pub struct Clock {
+    started_at: Instant,
+    /// When the speed last changed, by the host's clock.
+    changed_at: Instant,
+    /// [Clock::uptime] when the speed last changed.
+    uptime_at_change: Duration,
+    speed: f64,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        let now = Instant::now();
+        Clock {
+            started_at: now,
+            changed_at: now,
+            uptime_at_change: Duration::ZERO,
+            speed: 1.0,
+        }
+    }
+
+    /// Time since the app was started, by the app's clock.
+    pub fn uptime(&self) -> Duration {
+        self.uptime_at(Instant::now())
+    }
+
+    fn uptime_at(&self, now: Instant) -> Duration {
+        self.uptime_at_change + now.duration_since(self.changed_at).mul_f64(self.speed)
+    }
+
+    /// How far the app's clock has got ahead of the host's by fast-forwarding.
+    pub fn lead(&self) -> Duration {
+        let now = Instant::now();
+        self.uptime_at(now)
+            .saturating_sub(now.duration_since(self.started_at))
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Change how many times faster than the host's clock the app's clock
+    /// runs.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.set_speed_at(speed, Instant::now());
+    }
+
+    fn set_speed_at(&mut self, speed: f64, now: Instant) {
+        assert!(speed > 0.0);
+        self.uptime_at_change = self.uptime_at(now);
+        self.changed_at = now;
+        self.speed = speed;
+    }
+
+    /// Convert a duration by the app's clock to one by the host's clock, e.g.
+    /// to find out how long to sleep for.
+    pub fn host_duration(&self, duration: Duration) -> Duration {
+        duration.div_f64(self.speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_changes() {
+        let mut clock = Clock::new();
+        let start = clock.started_at;
+        let seconds = Duration::from_secs;
+        assert_eq!(clock.uptime_at(start + seconds(2)), seconds(2));
+
+        clock.set_speed_at(4.0, start + seconds(2));
+        assert_eq!(clock.uptime_at(start + seconds(3)), seconds(6));
+        assert_eq!(clock.host_duration(seconds(8)), seconds(2));
+
+        clock.set_speed_at(1.0, start + seconds(3));
+        assert_eq!(clock.uptime_at(start + seconds(5)), seconds(8));
+        assert_eq!(clock.host_duration(seconds(8)), seconds(8));
+    }
+}
@@ -0,0 +1,108 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Configuration files, which give options without a long command line. See
+//! `--config-dir=`.
+//!
+//! A configuration file is TOML. Each key is the name of an option without the
+//! leading `--`, for example:
+//!
+//! ```toml
+//! internal-resolution = 2
+//! frame-rate = "vsync"
+//! fullscreen = true
+//! auto-tap = ["160,240,5", "160,400,10"]
+//! ```
+//!
+//! Options that take a value can be given a string or a number. Options that
+//! don't are given `true` or `false`. Options that can be used several times
+//! can be given an array.
+//!
+//! Options are applied in this order, so later ones win: touchHLE's defaults,
+//! `default.toml` (for every app), `<bundle identifier>.toml` (for one app),
+//! and then the command line. A key in the app's file replaces the same key in
+//! `default.toml`, so `fullscreen = false` there undoes `fullscreen = true` in
+//! `default.toml`.
+
+use std::path::Path;
+use toml::value::Table;
+use toml::Value;
+
+pub fn parse(text: &str) -> Result<Table, String> {
+    toml::from_str(text).map_err(|e: toml::de::Error| e.to_string())
+}
+
+/// Read a configuration file, if it exists.
+pub fn load(path: &Path) -> Result<Table, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Table::new()),
+        Err(e) => return Err(format!("Couldn't read {}: {}", path.display(), e)),
+    };
+    log!("Using the options in {}", path.display());
+    parse(&text).map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+}
+
+/// Turn a configuration into the equivalent command-line arguments.
+pub fn to_arguments(config: &Table) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for (name, value) in config {
+        let values = match value {
+            Value::Array(values) => values.as_slice(),
+            value => std::slice::from_ref(value),
+        };
+        for value in values {
+            match value {
+                Value::Boolean(true) => args.push(format!("--{}", name)),
+                Value::Boolean(false) => (),
+                Value::String(value) => args.push(format!("--{}={}", name, value)),
+                Value::Integer(value) => args.push(format!("--{}={}", name, value)),
+                Value::Float(value) => args.push(format!("--{}={}", name, value)),
+                _ => return Err(format!("Unsupported value for {:?}: {}", name, value)),
+            }
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config() {
+        let mut config = parse(
+            "# A comment\n\
+             internal-resolution = 2\n\
+             fullscreen = true\n\
+             auto-tap = [\"160,240,5\", \"160,400,10\"]\n",
+        )
+        .unwrap();
+        config.extend(
+            parse(
+                "fullscreen = false\n\
+                 frame-rate = \"vsync\"\n\
+                 y-tilt-offset = 24.5\n",
+            )
+            .unwrap(),
+        );
+        let mut args = to_arguments(&config).unwrap();
+        args.sort();
+        assert_eq!(
+            args,
+            [
+                "--auto-tap=160,240,5",
+                "--auto-tap=160,400,10",
+                "--frame-rate=vsync",
+                "--internal-resolution=2",
+                "--y-tilt-offset=24.5",
+            ]
+        );
+
+        assert!(parse("fullscreen = ").is_err());
+        assert!(to_arguments(&parse("[section]\nkey = 1").unwrap()).is_err());
+        assert!(to_arguments(&parse("auto-tap = [[1, 2]]").unwrap()).is_err());
+    }
+}
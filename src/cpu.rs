@@ -11,14 +11,14 @@
 //! For the moment, only ARMv6 has been tested.
 
 use crate::abi::GuestFunction;
-use crate::mem::{ConstPtr, GuestUSize, Mem, MutPtr, Ptr, SafeRead, SafeWrite};
+use crate::mem::{guest_size_of, ConstPtr, GuestUSize, Mem, MutPtr, Ptr, SafeRead, WatchpointHit};
 
 // Import functions from C++
 use touchHLE_dynarmic_wrapper::*;
 
 type VAddr = u32;
 
-fn touchHLE_cpu_read_impl<T: SafeRead + Default>(
+fn touchHLE_cpu_read_impl<T: SafeRead + Default + Copy + Into<u64>>(
     mem: *mut touchHLE_Mem,
     addr: VAddr,
     error: *mut bool,
@@ -30,6 +30,10 @@ fn touchHLE_cpu_read_impl<T: SafeRead + Default>(
     // with only Rust stack frames to worry about and with CPU state information
     // available that's useful for debugging.
     //
+    // Hitting a watchpoint isn't an error: the access completes normally and
+    // the hit is only recorded, so that [Cpu::run] can report it once the
+    // instruction has finished.
+    //
     // TODO: Disable this in debug mode? This relies on dynarmic's
     // check_halt_on_memory_access option which surely has a significant
     // performance impact.
@@ -39,6 +43,30 @@ fn touchHLE_cpu_read_impl<T: SafeRead + Default>(
     let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
         let mem = unsafe { &mut *mem.cast::<Mem>() };
         let ptr: ConstPtr<T> = Ptr::from_bits(addr);
+        let value = mem.read(ptr);
+        let size = guest_size_of::<T>();
+        if mem.is_watched(addr, size, /* is_write: */ false) {
+            mem.add_watchpoint_hit(WatchpointHit {
+                addr,
+                size,
+                value: value.into(),
+                old_value: None,
+            });
+        }
+        value
+    }));
+    unsafe {
+        error.write(res.is_err());
+    }
+    res.unwrap_or_default()
+}
+
+fn touchHLE_cpu_read_code_impl(mem: *mut touchHLE_Mem, addr: VAddr, error: *mut bool) -> u32 {
+    // See comments above about catch_unwind. Fetching instructions doesn't
+    // hit watchpoints.
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let mem = unsafe { &mut *mem.cast::<Mem>() };
+        let ptr: ConstPtr<u32> = Ptr::from_bits(addr);
         mem.read(ptr)
     }));
     unsafe {
@@ -47,12 +75,25 @@ fn touchHLE_cpu_read_impl<T: SafeRead + Default>(
     res.unwrap_or_default()
 }
 
-fn touchHLE_cpu_write_impl<T: SafeWrite>(mem: *mut touchHLE_Mem, addr: VAddr, value: T) -> bool {
+fn touchHLE_cpu_write_impl<T: SafeRead + Copy + Into<u64>>(
+    mem: *mut touchHLE_Mem,
+    addr: VAddr,
+    value: T,
+) -> bool {
     // See comments above about catch_unwind
     let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
         let mem = unsafe { &mut *mem.cast::<Mem>() };
         let ptr: MutPtr<T> = Ptr::from_bits(addr);
-        mem.write(ptr, value)
+        let size = guest_size_of::<T>();
+        if mem.is_watched(addr, size, /* is_write: */ true) {
+            mem.add_watchpoint_hit(WatchpointHit {
+                addr,
+                size,
+                value: value.into(),
+                old_value: Some(mem.read(ptr).into()),
+            });
+        }
+        mem.write(ptr, value);
     }));
     res.is_err()
 }
@@ -75,6 +116,14 @@ extern "C" fn touchHLE_cpu_read_u64(mem: *mut touchHLE_Mem, addr: VAddr, error:
     touchHLE_cpu_read_impl(mem, addr, error)
 }
 #[no_mangle]
+extern "C" fn touchHLE_cpu_read_code_u32(
+    mem: *mut touchHLE_Mem,
+    addr: VAddr,
+    error: *mut bool,
+) -> u32 {
+    touchHLE_cpu_read_code_impl(mem, addr, error)
+}
+#[no_mangle]
 extern "C" fn touchHLE_cpu_write_u8(mem: *mut touchHLE_Mem, addr: VAddr, value: u8) -> bool {
     touchHLE_cpu_write_impl(mem, addr, value)
 }
@@ -125,6 +174,10 @@ pub enum CpuState {
     Normal,
     /// SVC instruction encountered.
     Svc(u32),
+    /// Guest code accessed memory watched by a watchpoint (see
+    /// [Mem::add_watchpoint]). The instruction at `pc` that did it has
+    /// completed, and execution can continue as normal.
+    Watchpoint { pc: VAddr, hits: Vec<WatchpointHit> },
 }
 
 impl Cpu {
@@ -236,6 +289,9 @@ impl Cpu {
     /// of ticks). Check the return value!
     #[must_use]
     pub fn run(&mut self, mem: &mut Mem, ticks: &mut u64) -> CpuState {
+        if mem.has_watchpoints() {
+            return self.run_with_watchpoints(mem, ticks);
+        }
         let res = unsafe {
             touchHLE_DynarmicWrapper_run(
                 self.dynarmic_wrapper,
@@ -248,11 +304,36 @@ impl Cpu {
                 assert!(*ticks == 0);
                 CpuState::Normal
             }
-            -2 => {
-                panic!("Memory error during CPU execution!");
-            }
+            -2 => panic!("Memory error during CPU execution!"),
             _ if res < -2 => panic!("Unexpected CPU execution result"),
             svc => CpuState::Svc(svc as u32),
         }
     }
+
+    /// Like [Self::run], but executing one instruction at a time, so that
+    /// watchpoint hits can be reported as soon as the instruction that caused
+    /// them has completed. This is much slower.
+    fn run_with_watchpoints(&mut self, mem: &mut Mem, ticks: &mut u64) -> CpuState {
+        while *ticks > 0 {
+            let pc = self.regs()[Self::PC];
+            let res = unsafe {
+                touchHLE_DynarmicWrapper_step(
+                    self.dynarmic_wrapper,
+                    mem as *mut Mem as *mut touchHLE_Mem,
+                )
+            };
+            *ticks -= 1;
+            match res {
+                -1 => (),
+                -2 => panic!("Memory error during CPU execution!"),
+                _ if res < -2 => panic!("Unexpected CPU execution result"),
+                svc => return CpuState::Svc(svc as u32),
+            }
+            let hits = mem.take_watchpoint_hits();
+            if !hits.is_empty() {
+                return CpuState::Watchpoint { pc, hits };
+            }
+        }
+        CpuState::Normal
+    }
 }
@@ -21,6 +21,8 @@ std::uint8_t touchHLE_cpu_read_u8(touchHLE_Mem *mem, VAddr addr, bool *error);
 std::uint16_t touchHLE_cpu_read_u16(touchHLE_Mem *mem, VAddr addr, bool *error);
 std::uint32_t touchHLE_cpu_read_u32(touchHLE_Mem *mem, VAddr addr, bool *error);
 std::uint64_t touchHLE_cpu_read_u64(touchHLE_Mem *mem, VAddr addr, bool *error);
+std::uint32_t touchHLE_cpu_read_code_u32(touchHLE_Mem *mem, VAddr addr,
+                                         bool *error);
 bool touchHLE_cpu_write_u8(touchHLE_Mem *mem, VAddr addr, std::uint8_t value);
 bool touchHLE_cpu_write_u16(touchHLE_Mem *mem, VAddr addr, std::uint16_t value);
 bool touchHLE_cpu_write_u32(touchHLE_Mem *mem, VAddr addr, std::uint32_t value);
@@ -72,7 +74,7 @@ private:
 
   std::optional<std::uint32_t> MemoryReadCode(VAddr vaddr) override {
     bool error;
-    auto value = touchHLE_cpu_read_u32(mem, vaddr, &error);
+    auto value = touchHLE_cpu_read_code_u32(mem, vaddr, &error);
     if (error) {
       return std::nullopt;
     } else {
@@ -162,20 +164,31 @@ public:
     env.mem = mem;
     env.ticks_remaining = *ticks;
     Dynarmic::HaltReason hr = cpu->Run();
-    std::int32_t res;
+    env.mem = nullptr;
+    *ticks = env.ticks_remaining;
+    return halt_reason_to_result(hr);
+  }
+
+  std::int32_t step(touchHLE_Mem *mem) {
+    env.mem = mem;
+    env.ticks_remaining = 1;
+    Dynarmic::HaltReason hr = cpu->Step();
+    env.mem = nullptr;
+    return halt_reason_to_result(hr & ~Dynarmic::HaltReason::Step);
+  }
+
+private:
+  std::int32_t halt_reason_to_result(Dynarmic::HaltReason hr) {
     if (!hr) {
-      res = -1;
+      return -1;
     } else if (Dynarmic::Has(hr, Dynarmic::HaltReason::MemoryAbort)) {
-      res = -2;
+      return -2;
     } else if (Dynarmic::Has(hr, HaltReasonSvc)) {
-      res = std::int32_t(env.halting_svc);
+      return std::int32_t(env.halting_svc);
     } else {
       printf("unhandled halt reason %u\n", hr);
       abort();
     }
-    env.mem = nullptr;
-    *ticks = env.ticks_remaining;
-    return res;
   }
 };
 
@@ -219,6 +232,11 @@ std::int32_t touchHLE_DynarmicWrapper_run(DynarmicWrapper *cpu,
   return cpu->run(mem, ticks);
 }
 
+std::int32_t touchHLE_DynarmicWrapper_step(DynarmicWrapper *cpu,
+                                           touchHLE_Mem *mem) {
+  return cpu->step(mem);
+}
+
 void *touchHLE_DynarmicWrapper_Context_new() {
   return (void *)new Dynarmic::A32::Context();
 }
@@ -46,6 +46,10 @@ extern "C" {
         mem: *mut touchHLE_Mem,
         ticks: *mut u64,
     ) -> i32;
+    pub fn touchHLE_DynarmicWrapper_step(
+        cpu: *mut touchHLE_DynarmicWrapper,
+        mem: *mut touchHLE_Mem,
+    ) -> i32;
 
     pub fn touchHLE_DynarmicWrapper_Context_new() -> *mut Dynarmic_A32_Context;
     pub fn touchHLE_DynarmicWrapper_Context_delete(context: *mut Dynarmic_A32_Context);
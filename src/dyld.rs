@@ -29,9 +29,10 @@ use crate::frameworks::foundation::ns_string;
 use crate::mach_o::MachO;
 use crate::mem::{ConstVoidPtr, GuestUSize, Mem, MutPtr, Ptr};
 use crate::objc::ObjC;
-use crate::Environment;
+use crate::report::{self, Reporter};
+use crate::{plugins, Environment};
 
-type HostFunction = &'static dyn CallFromGuest;
+pub type HostFunction = &'static dyn CallFromGuest;
 
 /// Type for lists of functions exported by host implementations of frameworks.
 ///
@@ -114,6 +115,22 @@ pub enum HostConstant {
 /// See also [FunctionExports], [crate::objc::ClassExports].
 pub type ConstantExports = &'static [(&'static str, HostConstant)];
 
+/// Makes a list of symbol lists in the style of [FunctionExports], labelled
+/// with the path of the module each one is from (for [crate::api_trace]), e.g.
+/// `labelled_lists![FUNCTIONS; libc::stdio, openal]`.
+macro_rules! labelled_lists {
+    ($list:ident; $($first:ident $(:: $rest:ident)*),* $(,)?) => {
+        &[$((
+            concat!(stringify!($first) $(, "::", stringify!($rest))*),
+            $first $(:: $rest)* :: $list,
+        )),*]
+    };
+}
+pub(crate) use labelled_lists;
+
+/// See [labelled_lists].
+pub type LabelledLists<T> = &'static [(&'static str, &'static [(&'static str, T)])];
+
 /// Helper for working with symbol lists in the style of [FunctionExports].
 pub fn search_lists<T>(
     lists: &'static [&'static [(&'static str, T)]],
@@ -126,6 +143,29 @@ pub fn search_lists<T>(
         .map(|&(_, ref f)| f)
 }
 
+/// Like [search_lists], but returns the path of the module the symbol is
+/// from and the list's copy of the symbol name too.
+pub fn search_labelled_lists<T>(
+    lists: LabelledLists<T>,
+    symbol: &str,
+) -> Option<(&'static str, &'static str, &'static T)> {
+    lists.iter().find_map(|&(module, list)| {
+        list.iter()
+            .find(|&(sym, _)| *sym == symbol)
+            .map(|(sym, f)| (module, *sym, f))
+    })
+}
+
+/// A host function that guest code can call, and where it's from.
+#[derive(Copy, Clone)]
+pub struct LinkedHostFunction {
+    /// The mangled symbol name.
+    pub symbol: &'static str,
+    /// The path of the module it's from. See [labelled_lists].
+    pub module: &'static str,
+    pub function: HostFunction,
+}
+
 fn encode_a32_svc(imm: u32) -> u32 {
     assert!(imm & 0xff000000 == 0);
     imm | 0xef000000
@@ -141,7 +181,7 @@ fn encode_a32_trap() -> u32 {
 }
 
 pub struct Dyld {
-    linked_host_functions: Vec<HostFunction>,
+    linked_host_functions: Vec<LinkedHostFunction>,
     return_to_host_routine: Option<GuestFunction>,
     constants_to_link_later: Vec<(MutPtr<ConstVoidPtr>, &'static HostConstant)>,
 }
@@ -153,9 +193,21 @@ impl Dyld {
     pub const SVC_RETURN_TO_HOST: u32 = 1;
     /// We reserve this SVC ID for breakpoints.
     const SVC_BREAKPOINT: u32 = 2;
+    /// We reserve this SVC ID for guest functions replaced by scripts (see
+    /// [crate::scripting]).
+    pub const SVC_SCRIPT_HOOK: u32 = 3;
     /// The range of SVC IDs `SVC_LINKED_FUNCTIONS_BASE..` is used to reference
     /// [Self::linked_host_functions] entries.
-    const SVC_LINKED_FUNCTIONS_BASE: u32 = Self::SVC_BREAKPOINT + 1;
+    const SVC_LINKED_FUNCTIONS_BASE: u32 = Self::SVC_SCRIPT_HOOK + 1;
+
+    /// System libraries that touchHLE has host implementations of, so apps'
+    /// dependencies on them don't need loading.
+    pub const HOST_IMPLEMENTED_LIBRARIES: &'static [&'static str] = &[
+        "/usr/lib/libSystem.B.dylib",
+        "/usr/lib/libobjc.A.dylib",
+        "/usr/lib/libsqlite3.dylib",
+        "/usr/lib/libsqlite3.0.dylib",
+    ];
 
     const SYMBOL_STUB_INSTRUCTIONS: [u32; 2] = [0xe59fc000, 0xe59cf000];
     const PIC_SYMBOL_STUB_INSTRUCTIONS: [u32; 3] = [0xe59fc004, 0xe08fc00c, 0xe59cf000];
@@ -174,7 +226,14 @@ impl Dyld {
 
     /// Do linking-related tasks that need doing right after loading the
     /// binaries.
-    pub fn do_initial_linking(&mut self, bins: &[MachO], mem: &mut Mem, objc: &mut ObjC) {
+    pub fn do_initial_linking(
+        &mut self,
+        bins: &[MachO],
+        mem: &mut Mem,
+        objc: &mut ObjC,
+        plugins: &plugins::Enabled,
+        report: Option<&Reporter>,
+    ) {
         assert!(self.return_to_host_routine.is_none());
         self.return_to_host_routine = {
             let routine = [
@@ -201,7 +260,7 @@ impl Dyld {
             self.setup_lazy_linking(bin, mem);
             // Must happen before `register_bin_classes`, else superclass
             // pointers will be wrong.
-            self.do_non_lazy_linking(bin, bins, mem, objc);
+            self.do_non_lazy_linking(bin, bins, mem, objc, plugins, report);
         }
 
         objc.register_bin_classes(&bins[0], mem);
@@ -268,7 +327,15 @@ impl Dyld {
     ///
     /// `bin` is the binary to link non-lazy symbols for, `bins` is the set of
     /// binaries symbols may be looked up in.
-    fn do_non_lazy_linking(&mut self, bin: &MachO, bins: &[MachO], mem: &mut Mem, objc: &mut ObjC) {
+    fn do_non_lazy_linking(
+        &mut self,
+        bin: &MachO,
+        bins: &[MachO],
+        mem: &mut Mem,
+        objc: &mut ObjC,
+        plugins: &plugins::Enabled,
+        report: Option<&Reporter>,
+    ) {
         for &(ptr_ptr, ref name) in &bin.external_relocations {
             let ptr = if let Some(name) = name.strip_prefix("_OBJC_CLASS_$_") {
                 objc.link_class(name, /* is_metaclass: */ false, mem)
@@ -278,6 +345,7 @@ impl Dyld {
                 ns_string::handle_constant_string(mem, objc, Ptr::from_bits(ptr_ptr))
             } else {
                 // TODO: look up symbol, write pointer
+                report::unimplemented(report, name);
                 log!(
                     "Warning: unhandled external relocation {:?} at {:#x} in \"{}\"",
                     name,
@@ -312,13 +380,17 @@ impl Dyld {
                 }
             }
 
-            if let Some(template) = search_lists(constant_lists::CONSTANT_LISTS, symbol) {
+            if let Some(template) = plugins
+                .search_constants(symbol)
+                .or_else(|| search_lists(constant_lists::CONSTANT_LISTS, symbol))
+            {
                 // Delay linking of constant until we have a `&mut Environment`,
                 // that makes it much easier to build NSString objects etc.
                 self.constants_to_link_later.push((ptr_ptr, template));
                 continue;
             }
 
+            report::unimplemented(report, symbol);
             log!(
                 "Warning: unhandled non-lazy symbol {:?} at {:?} in \"{}\"",
                 symbol,
@@ -357,18 +429,22 @@ impl Dyld {
     /// Return a host function that can be called to handle an SVC instruction
     /// encountered during CPU emulation. If `None` is returned, the execution
     /// needs to resume at `svc_pc`.
+    #[allow(clippy::too_many_arguments)]
     pub fn get_svc_handler(
         &mut self,
         bins: &[MachO],
         mem: &mut Mem,
         cpu: &mut Cpu,
+        plugins: &plugins::Enabled,
+        report: Option<&Reporter>,
         svc_pc: u32,
         svc: u32,
-    ) -> Option<HostFunction> {
+    ) -> Option<LinkedHostFunction> {
         match svc {
-            Self::SVC_LAZY_LINK => self.do_lazy_link(bins, mem, cpu, svc_pc),
+            Self::SVC_LAZY_LINK => self.do_lazy_link(bins, mem, cpu, plugins, report, svc_pc),
             Self::SVC_RETURN_TO_HOST => unreachable!(), // don't handle here
             Self::SVC_BREAKPOINT => panic!("Breakpoint"),
+            Self::SVC_SCRIPT_HOOK => unreachable!(), // don't handle here
             Self::SVC_LINKED_FUNCTIONS_BASE.. => {
                 let f = self
                     .linked_host_functions
@@ -381,13 +457,44 @@ impl Dyld {
         }
     }
 
+    /// For `--inspect`: check if a symbol an app needs has a host
+    /// implementation that linking would use, whether a function, constant or
+    /// Objective-C class.
+    pub fn has_host_implementation(plugins: &plugins::Enabled, symbol: &str) -> bool {
+        if let Some(class_name) = symbol
+            .strip_prefix("_OBJC_CLASS_$_")
+            .or_else(|| symbol.strip_prefix("_OBJC_METACLASS_$_"))
+        {
+            return ObjC::find_template_module(plugins, class_name).is_some();
+        }
+        symbol == "___CFConstantStringClassReference"
+            || Self::find_host_function(plugins, symbol).is_some()
+            || plugins.search_constants(symbol).is_some()
+            || search_lists(constant_lists::CONSTANT_LISTS, symbol).is_some()
+    }
+
+    /// Find a host function by its mangled symbol name, in an enabled plugin
+    /// (see [crate::plugins]) or in [function_lists::FUNCTION_LISTS].
+    fn find_host_function(plugins: &plugins::Enabled, symbol: &str) -> Option<LinkedHostFunction> {
+        plugins
+            .search_functions(symbol)
+            .or_else(|| search_labelled_lists(function_lists::FUNCTION_LISTS, symbol))
+            .map(|(module, symbol, &function)| LinkedHostFunction {
+                symbol,
+                module,
+                function,
+            })
+    }
+
     fn do_lazy_link(
         &mut self,
         bins: &[MachO],
         mem: &mut Mem,
         cpu: &mut Cpu,
+        plugins: &plugins::Enabled,
+        report: Option<&Reporter>,
         svc_pc: u32,
-    ) -> Option<HostFunction> {
+    ) -> Option<LinkedHostFunction> {
         let stubs = bins
             .iter()
             .flat_map(|bin| {
@@ -405,7 +512,7 @@ impl Dyld {
 
         let symbol = info.indirect_undef_symbols[idx].as_deref().unwrap();
 
-        if let Some(&f) = search_lists(function_lists::FUNCTION_LISTS, symbol) {
+        if let Some(f) = Self::find_host_function(plugins, symbol) {
             // Allocate an SVC ID for this host function
             let idx: u32 = self.linked_host_functions.len().try_into().unwrap();
             let svc = idx + Self::SVC_LINKED_FUNCTIONS_BASE;
@@ -460,6 +567,7 @@ impl Dyld {
             }
         }
 
+        report::unimplemented(report, symbol);
         panic!("Call to unimplemented function {}", symbol);
     }
 
@@ -474,9 +582,10 @@ impl Dyld {
         &mut self,
         mem: &mut Mem,
         cpu: &mut Cpu,
+        plugins: &plugins::Enabled,
         symbol: &str,
     ) -> Result<GuestFunction, ()> {
-        let &f = search_lists(function_lists::FUNCTION_LISTS, symbol).ok_or(())?;
+        let f = Self::find_host_function(plugins, symbol).ok_or(())?;
 
         // Allocate an SVC ID for this host function
         let idx: u32 = self.linked_host_functions.len().try_into().unwrap();
@@ -504,16 +613,28 @@ impl Dyld {
     /// get overwritten by that. **Do not call this after CPU execution has
     /// begun**, it does not clear the instruction cache!
     pub fn set_breakpoint(&mut self, mem: &mut Mem, at: u32) {
-        let at = GuestFunction::from_addr_with_thumb_bit(at);
+        Self::write_svc(
+            mem,
+            GuestFunction::from_addr_with_thumb_bit(at),
+            Self::SVC_BREAKPOINT,
+        );
+    }
+
+    /// Overwrite the first instruction of a guest function with a special SVC,
+    /// so that a script's hook is run instead of it. Unlike
+    /// [Self::set_breakpoint], this can be used at any time.
+    pub fn set_script_hook(&mut self, mem: &mut Mem, cpu: &mut Cpu, at: GuestFunction) {
+        Self::write_svc(mem, at, Self::SVC_SCRIPT_HOOK);
+        cpu.invalidate_cache_range(at.addr_without_thumb_bit(), 4);
+    }
+
+    fn write_svc(mem: &mut Mem, at: GuestFunction, svc: u32) {
         if at.is_thumb() {
             let ptr: MutPtr<u16> = Ptr::from_bits(at.addr_without_thumb_bit());
-            mem.write(
-                ptr,
-                encode_t32_svc(Self::SVC_BREAKPOINT.try_into().unwrap()),
-            );
+            mem.write(ptr, encode_t32_svc(svc.try_into().unwrap()));
         } else {
             let ptr: MutPtr<u32> = Ptr::from_bits(at.addr_without_thumb_bit());
-            mem.write(ptr, encode_a32_svc(Self::SVC_BREAKPOINT));
+            mem.write(ptr, encode_a32_svc(svc));
         }
     }
 }
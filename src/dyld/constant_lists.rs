@@ -6,15 +6,54 @@
 //! Separate module just for the constant lists, since this will probably be a
 //! very long and frequently-updated list.
 
-use crate::frameworks::{core_foundation, core_graphics, foundation, opengles};
+use crate::frameworks::{
+    address_book, audio_toolbox, cf_network, core_animation, core_data, core_foundation,
+    core_graphics, core_location, core_text, foundation, game_kit, media_player, message_ui,
+    opengles, store_kit, uikit,
+};
 use crate::libc;
 
 /// All the lists of constants that the linker should search through.
 pub const CONSTANT_LISTS: &[super::ConstantExports] = &[
     libc::ctype::CONSTANTS,
+    address_book::ab_person::CONSTANTS,
+    audio_toolbox::audio_session::CONSTANTS,
+    cf_network::cf_http_message::CONSTANTS,
+    cf_network::cf_http_stream::CONSTANTS,
+    cf_network::cf_socket_stream::CONSTANTS,
+    core_animation::ca_animation::CONSTANTS,
+    core_animation::ca_media_timing_function::CONSTANTS,
+    core_animation::ca_transaction::CONSTANTS,
+    core_animation::ca_transform_3d::CONSTANTS,
+    core_data::ns_persistent_store_coordinator::CONSTANTS,
     core_foundation::cf_allocator::CONSTANTS,
+    core_foundation::cf_bag::CONSTANTS,
+    core_foundation::cf_binary_heap::CONSTANTS,
+    core_foundation::cf_preferences::CONSTANTS,
     core_foundation::cf_run_loop::CONSTANTS,
+    core_foundation::cf_stream::CONSTANTS,
+    core_foundation::cf_string::CONSTANTS,
+    core_graphics::cg_affine_transform::CONSTANTS,
     core_graphics::cg_color_space::CONSTANTS,
+    core_location::cl_location::CONSTANTS,
+    core_location::cl_location_manager::CONSTANTS,
+    core_text::ct_string_attributes::CONSTANTS,
+    foundation::ns_attributed_string::CONSTANTS,
+    foundation::ns_calendar::CONSTANTS,
+    foundation::ns_error::CONSTANTS,
+    foundation::ns_file_manager::CONSTANTS,
     foundation::ns_run_loop::CONSTANTS,
+    foundation::ns_stream::CONSTANTS,
+    game_kit::gk_session::CONSTANTS,
+    media_player::mp_media_item::CONSTANTS,
+    media_player::mp_movie_player_controller::CONSTANTS,
+    media_player::mp_music_player_controller::CONSTANTS,
+    message_ui::mf_mail_compose_view_controller::CONSTANTS,
     opengles::eagl::CONSTANTS,
+    store_kit::sk_error::CONSTANTS,
+    uikit::ui_application::CONSTANTS,
+    uikit::ui_device::CONSTANTS,
+    uikit::ui_image_picker_controller::CONSTANTS,
+    uikit::ui_keyboard::CONSTANTS,
+    uikit::ui_scroll_view::CONSTANTS,
 ];
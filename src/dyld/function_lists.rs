@@ -6,42 +6,86 @@
 //! Separate module just for the function lists, since this will probably be a
 //! very long and frequently-updated list.
 
+use super::{labelled_lists, HostFunction, LabelledLists};
 use crate::frameworks::{
-    audio_toolbox, core_foundation, core_graphics, foundation, openal, opengles, uikit,
+    address_book, audio_toolbox, audio_unit, cf_network, core_animation, core_foundation,
+    core_graphics, core_location, core_text, foundation, graphics_services, map_kit, openal,
+    opengles, security, uikit,
 };
-use crate::libc;
+use crate::{libc, objc, sqlite3};
 
 /// All the lists of functions that the linker should search through.
-pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
-    libc::ctype::FUNCTIONS,
-    libc::cxxabi::FUNCTIONS,
-    libc::dlfcn::FUNCTIONS,
-    libc::keymgr::FUNCTIONS,
-    libc::mach_thread_info::FUNCTIONS,
-    libc::mach_time::FUNCTIONS,
-    libc::math::FUNCTIONS,
-    libc::pthread::key::FUNCTIONS,
-    libc::pthread::mutex::FUNCTIONS,
-    libc::pthread::once::FUNCTIONS,
-    libc::pthread::thread::FUNCTIONS,
-    libc::stdio::FUNCTIONS,
-    libc::stdio::printf::FUNCTIONS,
-    libc::stdlib::FUNCTIONS,
-    libc::string::FUNCTIONS,
-    libc::time::FUNCTIONS,
-    crate::objc::FUNCTIONS,
-    audio_toolbox::audio_file::FUNCTIONS,
-    audio_toolbox::audio_queue::FUNCTIONS,
-    core_foundation::cf_bundle::FUNCTIONS,
-    core_foundation::cf_run_loop::FUNCTIONS,
-    core_foundation::cf_type::FUNCTIONS,
-    core_foundation::cf_url::FUNCTIONS,
-    core_graphics::cg_bitmap_context::FUNCTIONS,
-    core_graphics::cg_color_space::FUNCTIONS,
-    core_graphics::cg_context::FUNCTIONS,
-    foundation::ns_file_manager::FUNCTIONS,
-    openal::FUNCTIONS,
-    opengles::FUNCTIONS,
-    uikit::ui_application::FUNCTIONS,
-    uikit::ui_graphics::FUNCTIONS,
+pub const FUNCTION_LISTS: LabelledLists<HostFunction> = labelled_lists![FUNCTIONS;
+    libc::ctype,
+    libc::cxxabi,
+    libc::dlfcn,
+    libc::keymgr,
+    libc::mach_thread_info,
+    libc::mach_time,
+    libc::math,
+    libc::pthread::key,
+    libc::pthread::mutex,
+    libc::pthread::once,
+    libc::pthread::thread,
+    libc::stdio,
+    libc::stdio::printf,
+    libc::stdlib,
+    libc::string,
+    libc::time,
+    objc,
+    sqlite3,
+    address_book::ab_address_book,
+    address_book::ab_multi_value,
+    address_book::ab_person,
+    address_book::ab_record,
+    audio_toolbox::audio_file,
+    audio_toolbox::audio_queue,
+    audio_toolbox::audio_services,
+    audio_toolbox::audio_session,
+    audio_toolbox::ext_audio_file,
+    audio_unit,
+    cf_network::cf_http_message,
+    cf_network::cf_http_stream,
+    core_animation::ca_base,
+    core_animation::ca_transform_3d,
+    core_foundation::cf_bag,
+    core_foundation::cf_binary_heap,
+    core_foundation::cf_bundle,
+    core_foundation::cf_preferences,
+    core_foundation::cf_run_loop,
+    core_foundation::cf_socket,
+    core_foundation::cf_stream,
+    core_foundation::cf_string,
+    core_foundation::cf_tree,
+    core_foundation::cf_type,
+    core_foundation::cf_url,
+    core_foundation::cf_uuid,
+    core_graphics::cg_affine_transform,
+    core_graphics::cg_bitmap_context,
+    core_graphics::cg_color,
+    core_graphics::cg_color_space,
+    core_graphics::cg_context,
+    core_graphics::cg_data_provider,
+    core_graphics::cg_function,
+    core_graphics::cg_gradient,
+    core_graphics::cg_image,
+    core_graphics::cg_path,
+    core_graphics::cg_pattern,
+    core_graphics::cg_pdf_document,
+    core_graphics::cg_pdf_page,
+    core_graphics::cg_shading,
+    core_location::cl_location,
+    core_text::ct_font,
+    core_text::ct_frame,
+    core_text::ct_framesetter,
+    core_text::ct_line,
+    core_text::ct_paragraph_style,
+    foundation::ns_file_manager,
+    graphics_services,
+    map_kit::mk_geometry,
+    openal,
+    opengles,
+    security::secure_transport,
+    uikit::ui_application,
+    uikit::ui_graphics,
 ];
@@ -13,11 +13,76 @@
 //! switch to something like cosmic-text in future, but that has a _lot_ more
 //! dependencies.
 
-use rusttype::{Point, Rect, Scale};
-use std::cmp;
+use rusttype::{GlyphId, OutlineBuilder, Point, Scale};
 
 pub struct Font {
     font: rusttype::Font<'static>,
+    names: FontNames,
+    /// Cap height and x-height, in ems.
+    cap_and_x_height: (f32, f32),
+}
+
+/// Names from a font's `name` table.
+#[derive(Clone, Debug, Default)]
+pub struct FontNames {
+    pub family: Option<String>,
+    pub full: Option<String>,
+    pub postscript: Option<String>,
+}
+
+/// Vertical metrics of a font at some size. Like the corresponding `UIFont`
+/// properties, the descender is negative.
+#[derive(Copy, Clone, Debug)]
+pub struct FontMetrics {
+    pub ascender: f32,
+    pub descender: f32,
+    /// Extra space between lines.
+    pub leading: f32,
+    pub cap_height: f32,
+    pub x_height: f32,
+}
+impl FontMetrics {
+    pub fn line_height(&self) -> f32 {
+        self.ascender - self.descender + self.leading
+    }
+}
+
+/// Part of a glyph outline. Coordinates are in ems, with y pointing down.
+#[derive(Copy, Clone, Debug)]
+pub enum OutlineSegment {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    QuadTo(f32, f32, f32, f32),
+    CurveTo(f32, f32, f32, f32, f32, f32),
+    Close,
+}
+
+/// How to pick a glyph from a font.
+#[derive(Copy, Clone, Debug)]
+pub enum GlyphSelector {
+    Char(char),
+    /// Index into the font's glyphs, for e.g. fonts embedded in documents
+    /// which have their own character mapping.
+    Index(u16),
+}
+
+struct OutlineCollector(Vec<OutlineSegment>);
+impl OutlineBuilder for OutlineCollector {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.push(OutlineSegment::MoveTo(x, y));
+    }
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.push(OutlineSegment::LineTo(x, y));
+    }
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0.push(OutlineSegment::QuadTo(x1, y1, x, y));
+    }
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.push(OutlineSegment::CurveTo(x1, y1, x2, y2, x, y));
+    }
+    fn close(&mut self) {
+        self.0.push(OutlineSegment::Close);
+    }
 }
 
 pub enum TextAlignment {
@@ -32,31 +97,186 @@ pub enum WrapMode {
     Char,
 }
 
-fn update_bounds(text_bounds: &mut Rect<i32>, glyph_bounds: &Rect<i32>) {
-    text_bounds.min.x = cmp::min(text_bounds.min.x, glyph_bounds.min.x);
-    text_bounds.min.y = cmp::min(text_bounds.min.y, glyph_bounds.min.y);
-    text_bounds.max.x = cmp::max(text_bounds.max.x, glyph_bounds.max.x);
-    text_bounds.max.y = cmp::max(text_bounds.max.y, glyph_bounds.max.y);
+/// Where to remove text from when truncating a line with an ellipsis.
+#[derive(Copy, Clone)]
+pub enum Truncation {
+    Head,
+    Middle,
+    Tail,
 }
 
-fn scale(font_size: f32) -> Scale {
-    // iPhone OS's interpretation of font size is slightly different, reason
-    // unknown. This is not the same as the Windows pt vs Mac pt issue.
-    // This scale factor has been eyeball'd, it's not exact.
-    Scale::uniform(font_size * 1.125)
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Find a table in a TrueType or OpenType font. For collections, the first
+/// font is used, like rusttype does.
+fn find_table<'a>(data: &'a [u8], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    let base = if data.starts_with(b"ttcf") {
+        read_u32(data, 12)? as usize
+    } else {
+        0
+    };
+    let table_count = read_u16(data, base + 4)? as usize;
+    (0..table_count).find_map(|i| {
+        let record = base + 12 + i * 16;
+        if data.get(record..record + 4)? != tag {
+            return None;
+        }
+        let offset = read_u32(data, record + 8)? as usize;
+        let length = read_u32(data, record + 12)? as usize;
+        data.get(offset..offset.checked_add(length)?)
+    })
+}
+
+fn read_names(data: &[u8]) -> FontNames {
+    let Some(table) = find_table(data, b"name") else {
+        return FontNames::default();
+    };
+    let count = read_u16(table, 2).unwrap_or(0) as usize;
+    let strings = read_u16(table, 4).unwrap_or(0) as usize;
+
+    // Each name may be present for several platforms and languages. US English
+    // Unicode names are preferred, then Mac Roman names.
+    let mut best: [Option<(u8, String)>; 3] = Default::default();
+    for i in 0..count {
+        let record = 6 + i * 12;
+        let (Some(platform), Some(encoding), Some(language), Some(name_id)) = (
+            read_u16(table, record),
+            read_u16(table, record + 2),
+            read_u16(table, record + 4),
+            read_u16(table, record + 6),
+        ) else {
+            break;
+        };
+        // The typographic family name (16) groups more styles together than
+        // the legacy family name (1), so it's closer to Apple's idea of a
+        // family.
+        let slot = match name_id {
+            1 | 16 => 0,
+            4 => 1,
+            6 => 2,
+            _ => continue,
+        };
+        let length = read_u16(table, record + 8).unwrap_or(0) as usize;
+        let offset = strings + read_u16(table, record + 10).unwrap_or(0) as usize;
+        let Some(bytes) = table.get(offset..offset + length) else {
+            continue;
+        };
+        let (rank, string) = match (platform, encoding, language) {
+            (0, _, _) | (3, 1 | 10, 0x409) => {
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect();
+                (3, String::from_utf16_lossy(&units))
+            }
+            (3, 1 | 10, _) => {
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect();
+                (2, String::from_utf16_lossy(&units))
+            }
+            // Names are almost always ASCII, so treating Mac Roman as Latin-1
+            // is good enough.
+            (1, 0, 0) => (1, bytes.iter().map(|&b| b as char).collect()),
+            _ => continue,
+        };
+        let rank = rank * 2 + u8::from(name_id == 16);
+        if !matches!(best[slot], Some((best_rank, _)) if best_rank >= rank) {
+            best[slot] = Some((rank, string));
+        }
+    }
+    let [family, full, postscript] = best.map(|name| name.map(|(_, name)| name));
+    FontNames {
+        family,
+        full,
+        postscript,
+    }
+}
+
+/// Get the cap height and x-height from the `OS/2` table, in font units.
+/// They were only added in version 2 of the table.
+fn read_cap_and_x_height(data: &[u8]) -> Option<(i16, i16)> {
+    let table = find_table(data, b"OS/2")?;
+    if read_u16(table, 0)? < 2 {
+        return None;
+    }
+    let x_height = read_u16(table, 86)? as i16;
+    let cap_height = read_u16(table, 88)? as i16;
+    Some((cap_height, x_height))
 }
 
 impl Font {
     fn from_file(path: &str) -> Font {
         let Ok(bytes) = std::fs::read(path) else {
-            panic!("Couldn't read bundled font file {:?}. Perhaps the directory is missing?", path);
+            panic!(
+                "Couldn't read bundled font file {:?}. Perhaps the directory is missing?",
+                path
+            );
         };
 
-        let Some(font) = rusttype::Font::try_from_vec(bytes) else {
+        let Some(font) = Self::from_bytes(bytes) else {
             panic!("Couldn't parse bundled font file {:?}. This probably means the file is corrupt. Try re-downloading it.", path);
         };
 
-        Font { font }
+        font
+    }
+
+    /// Parse a TrueType or OpenType font from memory, e.g. one embedded in a
+    /// document or shipped in an app bundle. Returns [None] if the font can't
+    /// be parsed.
+    pub fn from_bytes(bytes: Vec<u8>) -> Option<Font> {
+        let names = read_names(&bytes);
+        let cap_and_x_height = read_cap_and_x_height(&bytes);
+        let font = rusttype::Font::try_from_vec(bytes)?;
+        let mut font = Font {
+            font,
+            names,
+            cap_and_x_height: (0.0, 0.0),
+        };
+        font.cap_and_x_height = match cap_and_x_height {
+            Some((cap_height, x_height)) if cap_height > 0 && x_height > 0 => {
+                let units_per_em = font.font.units_per_em() as f32;
+                (
+                    cap_height as f32 / units_per_em,
+                    x_height as f32 / units_per_em,
+                )
+            }
+            // Older fonts don't have these, so measure the glyphs instead.
+            _ => {
+                let height = |c| {
+                    let glyph = font.glyph(GlyphSelector::Char(c))?.scaled(font.em_scale());
+                    glyph.exact_bounding_box().map(|bounds| -bounds.min.y)
+                };
+                (height('H').unwrap_or(0.7), height('x').unwrap_or(0.5))
+            }
+        };
+        Some(font)
+    }
+
+    /// Family, full and PostScript names of the font.
+    pub fn names(&self) -> &FontNames {
+        &self.names
+    }
+
+    pub fn metrics(&self, font_size: f32) -> FontMetrics {
+        let v_metrics = self.font.v_metrics_unscaled();
+        let units_to_points = font_size / self.font.units_per_em() as f32;
+        let (cap_height, x_height) = self.cap_and_x_height;
+        FontMetrics {
+            ascender: v_metrics.ascent * units_to_points,
+            descender: v_metrics.descent * units_to_points,
+            leading: v_metrics.line_gap * units_to_points,
+            cap_height: cap_height * font_size,
+            x_height: x_height * font_size,
+        }
     }
 
     pub fn sans_regular() -> Font {
@@ -75,27 +295,121 @@ impl Font {
         Self::from_file("touchHLE_fonts/NotoSansJP-Bold.otf")
     }
 
+    /// Scale at which glyph metrics and outlines are in ems.
+    fn em_scale(&self) -> Scale {
+        // rusttype scales relative to the ascent-to-descent height rather than
+        // the em size.
+        let v_metrics = self.font.v_metrics_unscaled();
+        Scale::uniform((v_metrics.ascent - v_metrics.descent) / self.font.units_per_em() as f32)
+    }
+
+    /// Scale at which glyph metrics and outlines are in points, for a font
+    /// size in points.
+    fn scale(&self, font_size: f32) -> Scale {
+        let em_scale = self.em_scale();
+        Scale::uniform(em_scale.y * font_size)
+    }
+
+    fn glyph(&self, selector: GlyphSelector) -> Option<rusttype::Glyph<'static>> {
+        let glyph = match selector {
+            GlyphSelector::Char(c) => self.font.glyph(c),
+            GlyphSelector::Index(index) => {
+                if index as usize >= self.font.glyph_count() {
+                    return None;
+                }
+                self.font.glyph(GlyphId(index))
+            }
+        };
+        // Glyph 0 is the "missing glyph" glyph.
+        (glyph.id().0 != 0).then_some(glyph)
+    }
+
+    /// Get the outline of a glyph, in ems. Returns [None] if the font has no
+    /// such glyph, or an empty [Vec] for glyphs like spaces.
+    pub fn glyph_outline(&self, selector: GlyphSelector) -> Option<Vec<OutlineSegment>> {
+        let glyph = self.glyph(selector)?.scaled(self.em_scale());
+        let mut collector = OutlineCollector(Vec::new());
+        glyph.build_outline(&mut collector);
+        Some(collector.0)
+    }
+
+    /// Get the horizontal advance of a glyph, in ems.
+    pub fn glyph_advance(&self, selector: GlyphSelector) -> Option<f32> {
+        let glyph = self.glyph(selector)?.scaled(self.em_scale());
+        Some(glyph.h_metrics().advance_width)
+    }
+
     fn line_height_and_gap(&self, font_size: f32) -> (f32, f32) {
-        let v_metrics = self.font.v_metrics(scale(font_size));
-        (v_metrics.ascent - v_metrics.descent, v_metrics.line_gap)
+        let metrics = self.metrics(font_size);
+        (metrics.ascender - metrics.descender, metrics.leading)
     }
 
-    /// Calculate the width of a line. This does not handle newlines!
-    fn calculate_line_width(&self, font_size: f32, line: &str) -> f32 {
-        let mut line_bounds: Rect<i32> = Default::default();
+    /// Calculate the width of a line, i.e. the sum of the advances of its
+    /// glyphs, including kerning. This does not handle newlines!
+    pub fn calculate_line_width(&self, font_size: f32, line: &str) -> f32 {
+        self.font
+            .layout(line, self.scale(font_size), Point { x: 0.0, y: 0.0 })
+            .last()
+            .map_or(0.0, |glyph| {
+                glyph.position().x + glyph.unpositioned().h_metrics().advance_width
+            })
+    }
 
-        for glyph in self.font.layout(line, scale(font_size), Default::default()) {
-            let Some(glyph_bounds) = glyph.pixel_bounding_box() else {
-                continue;
-            };
-            update_bounds(&mut line_bounds, &glyph_bounds);
+    /// Get the outline of a line of text, in points, with y pointing down and
+    /// the origin at the start of the baseline. This does not handle newlines!
+    pub fn line_outline(&self, font_size: f32, line: &str) -> Vec<OutlineSegment> {
+        let mut collector = OutlineCollector(Vec::new());
+        for glyph in self
+            .font
+            .layout(line, self.scale(font_size), Point { x: 0.0, y: 0.0 })
+        {
+            glyph.build_outline(&mut collector);
         }
+        collector.0
+    }
 
-        line_bounds.width() as f32
+    /// Get the length in bytes of the longest prefix of a line that fits in a
+    /// width. At least one character is always included.
+    fn fit_chars(&self, font_size: f32, line: &str, width: f32) -> usize {
+        let ends: Vec<usize> = line.char_indices().map(|(i, c)| i + c.len_utf8()).collect();
+        let fitting = ends
+            .partition_point(|&end| self.calculate_line_width(font_size, &line[..end]) <= width);
+        ends.get(fitting.saturating_sub(1)).copied().unwrap_or(0)
+    }
+
+    /// Shorten a line to fit in a width by replacing some of it with an
+    /// ellipsis. Lines that already fit are returned unchanged.
+    pub fn truncate_line(
+        &self,
+        font_size: f32,
+        line: &str,
+        width: f32,
+        truncation: Truncation,
+    ) -> String {
+        if self.calculate_line_width(font_size, line) <= width {
+            return line.to_string();
+        }
+        let chars: Vec<char> = line.chars().collect();
+        let with_ellipsis = |keep: usize| -> String {
+            let (head, tail) = match truncation {
+                Truncation::Head => (0, keep),
+                Truncation::Middle => (keep - keep / 2, keep / 2),
+                Truncation::Tail => (keep, 0),
+            };
+            let head: String = chars[..head].iter().collect();
+            let tail: String = chars[chars.len() - tail..].iter().collect();
+            format!("{}\u{2026}{}", head.trim_end(), tail.trim_start())
+        };
+        let keep = (0..chars.len())
+            .collect::<Vec<_>>()
+            .partition_point(|&keep| {
+                self.calculate_line_width(font_size, &with_ellipsis(keep)) <= width
+            });
+        with_ellipsis(keep.saturating_sub(1))
     }
 
     /// Break text into lines with known widths.
-    fn break_lines<'a>(
+    pub fn break_lines<'a>(
         &self,
         font_size: f32,
         text: &'a str,
@@ -158,16 +472,28 @@ impl Font {
                         let line_width = self.calculate_line_width(font_size, line);
                         line_width.partial_cmp(&wrap_width).unwrap()
                     });
-                let wrap_point_idx = match wrap_search_result {
-                    Ok(i) => next_wrap_point_idx + i,
-                    Err(i) => (next_wrap_point_idx + i).wrapping_sub(1),
+                let fitting = match wrap_search_result {
+                    Ok(i) => i + 1,
+                    Err(i) => i,
                 };
 
-                let line_end = wrap_points[wrap_point_idx];
+                let line_end = if fitting > 0 {
+                    next_wrap_point_idx += fitting;
+                    wrap_points[next_wrap_point_idx - 1]
+                } else {
+                    // Not even one word fits, so it has to be broken between
+                    // characters.
+                    let word_end = wrap_points[next_wrap_point_idx];
+                    let word = &line[line_start..word_end];
+                    let end = line_start + self.fit_chars(font_size, word, wrap_width);
+                    if end == word_end {
+                        next_wrap_point_idx += 1;
+                    }
+                    end
+                };
                 let line = &line[line_start..line_end];
                 lines.push((self.calculate_line_width(font_size, line), line));
 
-                next_wrap_point_idx = wrap_point_idx + 1;
                 line_start = line_end;
             }
         }
@@ -188,7 +514,8 @@ impl Font {
             .iter()
             .fold(0f32, |widest, &(line_width, _line)| widest.max(line_width));
         let (line_height, line_gap) = self.line_height_and_gap(font_size);
-        let height = line_height * (lines.len() as f32) + line_gap * ((lines.len() - 1) as f32);
+        let height =
+            line_height * (lines.len() as f32) + line_gap * (lines.len().saturating_sub(1) as f32);
 
         (width, height)
     }
@@ -207,9 +534,9 @@ impl Font {
         let lines = self.break_lines(font_size, text, wrap);
 
         let (line_height, line_gap) = self.line_height_and_gap(font_size);
-        let mut line_y = line_height * ((lines.len() - 1) as f32)
+        let mut line_y = line_height * (lines.len().saturating_sub(1) as f32)
             + line_gap * (lines.len().saturating_sub(2) as f32)
-            - self.font.v_metrics(scale(font_size)).descent;
+            - self.metrics(font_size).descender;
 
         for (line_width, line_text) in lines {
             let line_x_offset = match alignment {
@@ -219,7 +546,7 @@ impl Font {
             };
             for glyph in self.font.layout(
                 line_text,
-                scale(font_size),
+                self.scale(font_size),
                 Point {
                     x: origin.0 + line_x_offset,
                     y: 0.0,
@@ -242,3 +569,64 @@ impl Font {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_metrics() {
+        let font = Font::sans_bold();
+        assert_eq!(font.names().family.as_deref(), Some("Liberation Sans"));
+        assert_eq!(
+            font.names().postscript.as_deref(),
+            Some("LiberationSans-Bold")
+        );
+        // The font has 2048 units per em, so this makes a unit 0.01pt.
+        let metrics = font.metrics(20.48);
+        assert!((metrics.ascender - 18.54).abs() < 0.001);
+        assert!((metrics.descender + 4.34).abs() < 0.001);
+        assert!((metrics.leading - 0.67).abs() < 0.001);
+        assert!((metrics.cap_height - 14.09).abs() < 0.001);
+        assert!((metrics.x_height - 10.82).abs() < 0.001);
+    }
+
+    #[test]
+    fn line_breaking() {
+        let font = Font::sans_regular();
+        let width = font.calculate_line_width(12.0, "hello ");
+        let wrap = Some((width + 0.1, WrapMode::Word));
+        let lines = font.break_lines(12.0, "hello hello hello", wrap);
+        let lines: Vec<&str> = lines.iter().map(|&(_, line)| line).collect();
+        assert_eq!(lines, ["hello ", "hello ", "hello"]);
+
+        // A word wider than the line has to be broken between characters.
+        let wrap = Some((width / 2.0, WrapMode::Word));
+        let lines = font.break_lines(12.0, "hello", wrap);
+        assert!(lines.len() > 1);
+        assert!(lines
+            .iter()
+            .all(|&(line_width, _)| line_width <= width / 2.0));
+        let joined: String = lines.iter().map(|&(_, line)| line).collect();
+        assert_eq!(joined, "hello");
+    }
+
+    #[test]
+    fn truncation() {
+        let font = Font::sans_regular();
+        let text = "The quick brown fox";
+        let width = font.calculate_line_width(12.0, "The quick\u{2026}");
+        assert_eq!(
+            font.truncate_line(12.0, text, width, Truncation::Tail),
+            "The quick\u{2026}"
+        );
+        let head = font.truncate_line(12.0, text, width, Truncation::Head);
+        assert!(head.starts_with('\u{2026}') && head.ends_with("fox"));
+        let middle = font.truncate_line(12.0, text, width, Truncation::Middle);
+        assert!(middle.starts_with("The") && middle.ends_with("fox"));
+        assert_eq!(
+            font.truncate_line(12.0, text, 1000.0, Truncation::Middle),
+            text
+        );
+    }
+}
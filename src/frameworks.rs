@@ -20,23 +20,72 @@
 #![allow(non_upper_case_globals)] // Lots of Apple constants begin with "k"
 #![allow(clippy::too_many_arguments)] // It's not our fault!
 
+pub mod address_book;
+pub mod address_book_ui;
 pub mod audio_toolbox;
+pub mod audio_unit;
+pub mod av_foundation;
+pub mod cf_network;
 pub mod core_animation;
 pub mod core_audio_types;
+pub mod core_data;
 pub mod core_foundation;
 pub mod core_graphics;
+pub mod core_location;
+pub mod core_text;
 pub mod foundation;
+pub mod game_kit;
+pub mod graphics_services;
 pub mod mac_types;
+pub mod map_kit;
+pub mod media_player;
+pub mod message_ui;
 pub mod openal;
 pub mod opengles;
+pub mod security;
+pub mod store_kit;
 pub mod uikit;
 
+/// Functions that check for things to do in touchHLE's implementations of
+/// frameworks, e.g. whether a sound has finished playing so the delegate can be
+/// told. Each is called on every iteration of the main run loop in its common
+/// modes, as a run loop source (see
+/// [core_foundation::cf_run_loop::add_host_source]). There is only one run
+/// loop, so the things they check don't keep track of which one they were
+/// created on.
+pub const RUN_LOOP_POLLERS: &[fn(&mut crate::Environment)] = &[
+    av_foundation::av_audio_player::handle_audio_players,
+    audio_unit::handle_audio_units,
+    audio_toolbox::audio_services::handle_system_sounds,
+    media_player::mp_movie_player_controller::handle_movie_players,
+    media_player::mp_music_player_controller::handle_music_players,
+    core_location::cl_location_manager::handle_location_managers,
+    map_kit::mk_map_view::handle_map_views,
+    store_kit::sk_request::handle_requests,
+    store_kit::sk_payment_queue::handle_payment_queue,
+    game_kit::gk_session::handle_sessions,
+];
+
 /// Container for state of various child modules
 #[derive(Default)]
 pub struct State {
+    address_book: address_book::State,
+    address_book_ui: address_book_ui::State,
     audio_toolbox: audio_toolbox::State,
+    audio_unit: audio_unit::State,
+    av_foundation: av_foundation::State,
+    cf_network: cf_network::State,
+    core_animation: core_animation::State,
+    core_data: core_data::State,
+    core_foundation: core_foundation::State,
+    core_location: core_location::State,
     foundation: foundation::State,
+    game_kit: game_kit::State,
+    map_kit: map_kit::State,
+    media_player: media_player::State,
+    message_ui: message_ui::State,
     openal: openal::State,
     opengles: opengles::State,
+    store_kit: store_kit::State,
     uikit: uikit::State,
 }
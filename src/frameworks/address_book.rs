@@ -0,0 +1,23 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The AddressBook framework.
+//!
+//! There's no real address book, so the contacts come from a file the user
+//! provides, if any (see [contacts]). They can be read, but not changed.
+//!
+//! Useful resources:
+//! - Apple's [Address Book Programming Guide for iOS](https://developer.apple.com/library/archive/documentation/ContactData/Conceptual/AddressBookProgrammingGuideforiPhone/Introduction.html)
+
+pub mod ab_address_book;
+pub mod ab_multi_value;
+pub mod ab_person;
+pub mod ab_record;
+pub mod contacts;
+
+#[derive(Default)]
+pub struct State {
+    contacts: contacts::State,
+}
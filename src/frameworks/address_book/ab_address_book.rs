@@ -0,0 +1,224 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `ABAddressBook`.
+//!
+//! Each address book has its own copy of the contacts (see [super::contacts]).
+//! Nothing can be changed, so there's never anything to save.
+
+use super::ab_person::{self, ABRecordID};
+use super::ab_record::ABRecordRef;
+use super::contacts;
+use crate::abi::GuestFunction;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_string::CFStringRef;
+use crate::frameworks::core_foundation::{CFIndex, CFTypeRef};
+use crate::frameworks::foundation::ns_array;
+use crate::frameworks::foundation::ns_string::{from_rust_string, to_rust_string};
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{id, nil, objc_classes, release, retain, ClassExports, HostObject};
+use crate::Environment;
+
+pub type ABAddressBookRef = CFTypeRef;
+
+pub type ABAuthorizationStatus = CFIndex;
+pub const kABAuthorizationStatusAuthorized: ABAuthorizationStatus = 3;
+
+struct ABAddressBookHostObject {
+    /// `ABPerson`s, strong references, in the order of the contacts file.
+    people: Vec<id>,
+}
+impl HostObject for ABAddressBookHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// ABAddressBook is a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// exposed.
+@implementation _touchHLE_ABAddressBook: NSObject
+
+- (())dealloc {
+    let people = std::mem::take(&mut env.objc.borrow_mut::<ABAddressBookHostObject>(this).people);
+    for person in people {
+        release(env, person);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+fn people(env: &mut Environment, address_book: ABAddressBookRef) -> Vec<id> {
+    env.objc
+        .borrow::<ABAddressBookHostObject>(address_book)
+        .people
+        .clone()
+}
+
+/// Make an `NSArray` of people, which are retained.
+fn array_of_people(env: &mut Environment, people: Vec<id>) -> id {
+    for &person in &people {
+        retain(env, person);
+    }
+    ns_array::from_vec(env, people)
+}
+
+pub fn ABAddressBookCreate(env: &mut Environment) -> ABAddressBookRef {
+    let contacts = contacts::contacts(env);
+    let people = contacts
+        .iter()
+        .enumerate()
+        .map(|(index, contact)| {
+            let record_id = ABRecordID::try_from(index + 1).unwrap();
+            ab_person::new_person(env, record_id, contact.clone())
+        })
+        .collect();
+    let host_object = Box::new(ABAddressBookHostObject { people });
+    let class = env
+        .objc
+        .get_known_class("_touchHLE_ABAddressBook", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+fn ABAddressBookCreateWithOptions(
+    env: &mut Environment,
+    _options: CFTypeRef,
+    _error: MutPtr<CFTypeRef>,
+) -> ABAddressBookRef {
+    ABAddressBookCreate(env)
+}
+
+fn ABAddressBookGetAuthorizationStatus(_env: &mut Environment) -> ABAuthorizationStatus {
+    kABAuthorizationStatusAuthorized
+}
+
+fn ABAddressBookGetPersonCount(env: &mut Environment, address_book: ABAddressBookRef) -> CFIndex {
+    people(env, address_book).len().try_into().unwrap()
+}
+
+fn ABAddressBookGetPersonWithRecordID(
+    env: &mut Environment,
+    address_book: ABAddressBookRef,
+    record_id: ABRecordID,
+) -> ABRecordRef {
+    // Record IDs are assigned in order, starting from 1.
+    let index = usize::try_from(record_id)
+        .ok()
+        .and_then(|n| n.checked_sub(1));
+    index
+        .and_then(|index| people(env, address_book).get(index).copied())
+        .unwrap_or(nil)
+}
+
+pub fn ABAddressBookCopyArrayOfAllPeople(
+    env: &mut Environment,
+    address_book: ABAddressBookRef,
+) -> CFTypeRef {
+    let people = people(env, address_book);
+    array_of_people(env, people)
+}
+
+fn ABAddressBookCopyPeopleWithName(
+    env: &mut Environment,
+    address_book: ABAddressBookRef,
+    name: CFStringRef,
+) -> CFTypeRef {
+    let name = to_rust_string(env, name).to_lowercase();
+    let matches = people(env, address_book)
+        .into_iter()
+        .filter(|&person| {
+            let contact = ab_person::contact(env, person);
+            let Some(composite_name) = ab_person::composite_name(contact) else {
+                return false;
+            };
+            let composite_name = composite_name.to_lowercase();
+            // Matching is by prefix, of the whole name or any word in it.
+            composite_name.starts_with(&name)
+                || composite_name
+                    .split_whitespace()
+                    .any(|word| word.starts_with(&name))
+        })
+        .collect();
+    array_of_people(env, matches)
+}
+
+fn ABAddressBookGetGroupCount(_env: &mut Environment, _address_book: ABAddressBookRef) -> CFIndex {
+    0
+}
+
+fn ABAddressBookCopyArrayOfAllGroups(
+    env: &mut Environment,
+    _address_book: ABAddressBookRef,
+) -> CFTypeRef {
+    ns_array::from_vec(env, Vec::new())
+}
+
+fn ABAddressBookHasUnsavedChanges(_env: &mut Environment, _address_book: ABAddressBookRef) -> bool {
+    false
+}
+
+fn ABAddressBookSave(
+    _env: &mut Environment,
+    _address_book: ABAddressBookRef,
+    _error: MutPtr<CFTypeRef>,
+) -> bool {
+    true
+}
+
+fn ABAddressBookRevert(_env: &mut Environment, _address_book: ABAddressBookRef) {}
+
+fn ABAddressBookRegisterExternalChangeCallback(
+    _env: &mut Environment,
+    _address_book: ABAddressBookRef,
+    _callback: GuestFunction,
+    _context: MutVoidPtr,
+) {
+    // The contacts never change, so the callback is never called.
+}
+
+fn ABAddressBookUnregisterExternalChangeCallback(
+    _env: &mut Environment,
+    _address_book: ABAddressBookRef,
+    _callback: GuestFunction,
+    _context: MutVoidPtr,
+) {
+}
+
+fn ABAddressBookCopyLocalizedLabel(env: &mut Environment, label: CFStringRef) -> CFStringRef {
+    let label = to_rust_string(env, label);
+    // Built-in labels look like "_$!<HomeFAX>!$_".
+    let localized = match label
+        .strip_prefix("_$!<")
+        .and_then(|label| label.strip_suffix(">!$_"))
+    {
+        Some("HomeFAX") => "home fax".to_string(),
+        Some("WorkFAX") => "work fax".to_string(),
+        Some("HomePage") => "home page".to_string(),
+        Some(name) => name.to_lowercase(),
+        None => label.to_string(),
+    };
+    from_rust_string(env, localized)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(ABAddressBookCreate()),
+    export_c_func!(ABAddressBookCreateWithOptions(_, _)),
+    export_c_func!(ABAddressBookGetAuthorizationStatus()),
+    export_c_func!(ABAddressBookGetPersonCount(_)),
+    export_c_func!(ABAddressBookGetPersonWithRecordID(_, _)),
+    export_c_func!(ABAddressBookCopyArrayOfAllPeople(_)),
+    export_c_func!(ABAddressBookCopyPeopleWithName(_, _)),
+    export_c_func!(ABAddressBookGetGroupCount(_)),
+    export_c_func!(ABAddressBookCopyArrayOfAllGroups(_)),
+    export_c_func!(ABAddressBookHasUnsavedChanges(_)),
+    export_c_func!(ABAddressBookSave(_, _)),
+    export_c_func!(ABAddressBookRevert(_)),
+    export_c_func!(ABAddressBookRegisterExternalChangeCallback(_, _, _)),
+    export_c_func!(ABAddressBookUnregisterExternalChangeCallback(_, _, _)),
+    export_c_func!(ABAddressBookCopyLocalizedLabel(_)),
+];
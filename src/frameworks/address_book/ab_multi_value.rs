@@ -0,0 +1,167 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `ABMultiValue`, for properties like phone numbers that can have several
+//! labeled values.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_string::CFStringRef;
+use crate::frameworks::core_foundation::{CFIndex, CFTypeRef};
+use crate::frameworks::foundation::ns_array;
+use crate::frameworks::foundation::ns_string::get_static_str;
+use crate::objc::{id, nil, objc_classes, release, retain, ClassExports, HostObject};
+use crate::Environment;
+
+pub type ABMultiValueRef = CFTypeRef;
+
+pub type ABPropertyType = u32;
+pub const kABStringPropertyType: ABPropertyType = 1;
+pub const kABDateTimePropertyType: ABPropertyType = 4;
+pub const kABDictionaryPropertyType: ABPropertyType = 5;
+pub const kABMultiValueMask: ABPropertyType = 1 << 8;
+pub const kABMultiStringPropertyType: ABPropertyType = kABMultiValueMask | kABStringPropertyType;
+pub const kABMultiDateTimePropertyType: ABPropertyType =
+    kABMultiValueMask | kABDateTimePropertyType;
+pub const kABMultiDictionaryPropertyType: ABPropertyType =
+    kABMultiValueMask | kABDictionaryPropertyType;
+
+pub type ABMultiValueIdentifier = i32;
+pub const kABMultiValueInvalidIdentifier: ABMultiValueIdentifier = -1;
+
+struct ABMultiValueHostObject {
+    property_type: ABPropertyType,
+    /// Labels and values, strong references. The identifier of each value is
+    /// its index, since multi-values can't be changed.
+    values: Vec<(&'static str, id)>,
+}
+impl HostObject for ABMultiValueHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// ABMultiValue is a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// exposed.
+@implementation _touchHLE_ABMultiValue: NSObject
+
+- (())dealloc {
+    let values = std::mem::take(&mut env.objc.borrow_mut::<ABMultiValueHostObject>(this).values);
+    for (_, value) in values {
+        release(env, value);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+/// Create a multi-value. The values must already be retained.
+pub(super) fn new_multi_value(
+    env: &mut Environment,
+    property_type: ABPropertyType,
+    values: Vec<(&'static str, id)>,
+) -> ABMultiValueRef {
+    let host_object = Box::new(ABMultiValueHostObject {
+        property_type,
+        values,
+    });
+    let class = env
+        .objc
+        .get_known_class("_touchHLE_ABMultiValue", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+fn values(env: &mut Environment, multi_value: ABMultiValueRef) -> &[(&'static str, id)] {
+    &env.objc
+        .borrow::<ABMultiValueHostObject>(multi_value)
+        .values
+}
+
+fn ABMultiValueGetCount(env: &mut Environment, multi_value: ABMultiValueRef) -> CFIndex {
+    values(env, multi_value).len().try_into().unwrap()
+}
+
+fn ABMultiValueGetPropertyType(
+    env: &mut Environment,
+    multi_value: ABMultiValueRef,
+) -> ABPropertyType {
+    env.objc
+        .borrow::<ABMultiValueHostObject>(multi_value)
+        .property_type
+}
+
+fn ABMultiValueCopyValueAtIndex(
+    env: &mut Environment,
+    multi_value: ABMultiValueRef,
+    index: CFIndex,
+) -> CFTypeRef {
+    let (_, value) = values(env, multi_value)[index as usize];
+    retain(env, value)
+}
+
+fn ABMultiValueCopyLabelAtIndex(
+    env: &mut Environment,
+    multi_value: ABMultiValueRef,
+    index: CFIndex,
+) -> CFStringRef {
+    let (label, _) = values(env, multi_value)[index as usize];
+    let label = get_static_str(env, label);
+    retain(env, label)
+}
+
+fn ABMultiValueCopyArrayOfAllValues(
+    env: &mut Environment,
+    multi_value: ABMultiValueRef,
+) -> CFTypeRef {
+    let values: Vec<id> = values(env, multi_value)
+        .iter()
+        .map(|&(_, value)| value)
+        .collect();
+    if values.is_empty() {
+        return nil;
+    }
+    for &value in &values {
+        retain(env, value);
+    }
+    ns_array::from_vec(env, values)
+}
+
+fn ABMultiValueGetIdentifierAtIndex(
+    env: &mut Environment,
+    multi_value: ABMultiValueRef,
+    index: CFIndex,
+) -> ABMultiValueIdentifier {
+    let count = values(env, multi_value).len();
+    if index >= 0 && (index as usize) < count {
+        index
+    } else {
+        kABMultiValueInvalidIdentifier
+    }
+}
+
+fn ABMultiValueGetIndexForIdentifier(
+    env: &mut Environment,
+    multi_value: ABMultiValueRef,
+    identifier: ABMultiValueIdentifier,
+) -> CFIndex {
+    let count = values(env, multi_value).len();
+    if identifier >= 0 && (identifier as usize) < count {
+        identifier
+    } else {
+        -1
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(ABMultiValueGetCount(_)),
+    export_c_func!(ABMultiValueGetPropertyType(_)),
+    export_c_func!(ABMultiValueCopyValueAtIndex(_, _)),
+    export_c_func!(ABMultiValueCopyLabelAtIndex(_, _)),
+    export_c_func!(ABMultiValueCopyArrayOfAllValues(_)),
+    export_c_func!(ABMultiValueGetIdentifierAtIndex(_, _)),
+    export_c_func!(ABMultiValueGetIndexForIdentifier(_, _)),
+];
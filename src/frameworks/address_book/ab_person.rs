@@ -0,0 +1,395 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `ABPerson`.
+//!
+//! People are only read from the contacts file (see [super::contacts]), so
+//! they can't be created or changed.
+
+use super::ab_multi_value::{
+    self, kABDateTimePropertyType, kABMultiDateTimePropertyType, kABMultiDictionaryPropertyType,
+    kABMultiStringPropertyType, kABMultiValueMask, kABStringPropertyType, ABPropertyType,
+};
+use super::ab_record::ABRecordRef;
+use super::contacts::Contact;
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::core_foundation::cf_string::CFStringRef;
+use crate::frameworks::core_foundation::{
+    kCFCompareEqualTo, kCFCompareGreaterThan, kCFCompareLessThan, CFComparisonResult, CFTypeRef,
+};
+use crate::frameworks::foundation::ns_calendar::days_from_civil;
+use crate::frameworks::foundation::ns_date::{self, NSTimeIntervalSince1970};
+use crate::frameworks::foundation::ns_dictionary::dict_from_keys_and_objects;
+use crate::frameworks::foundation::ns_string::{from_rust_string, get_static_str};
+use crate::mem::{ConstVoidPtr, Mem};
+use crate::objc::{id, nil, objc_classes, release, ClassExports, HostObject};
+use crate::Environment;
+
+pub type ABRecordID = i32;
+
+pub type ABPropertyID = i32;
+pub const kABPersonFirstNameProperty: ABPropertyID = 0;
+pub const kABPersonLastNameProperty: ABPropertyID = 1;
+pub const kABPersonPhoneProperty: ABPropertyID = 3;
+pub const kABPersonEmailProperty: ABPropertyID = 4;
+pub const kABPersonAddressProperty: ABPropertyID = 5;
+pub const kABPersonMiddleNameProperty: ABPropertyID = 6;
+pub const kABPersonFirstNamePhoneticProperty: ABPropertyID = 7;
+pub const kABPersonLastNamePhoneticProperty: ABPropertyID = 8;
+pub const kABPersonMiddleNamePhoneticProperty: ABPropertyID = 9;
+pub const kABPersonOrganizationProperty: ABPropertyID = 10;
+pub const kABPersonDepartmentProperty: ABPropertyID = 11;
+pub const kABPersonDateProperty: ABPropertyID = 12;
+pub const kABPersonInstantMessageProperty: ABPropertyID = 13;
+pub const kABPersonNoteProperty: ABPropertyID = 14;
+pub const kABPersonBirthdayProperty: ABPropertyID = 17;
+pub const kABPersonJobTitleProperty: ABPropertyID = 18;
+pub const kABPersonNicknameProperty: ABPropertyID = 19;
+pub const kABPersonPrefixProperty: ABPropertyID = 20;
+pub const kABPersonSuffixProperty: ABPropertyID = 21;
+pub const kABPersonURLProperty: ABPropertyID = 22;
+pub const kABPersonRelatedNamesProperty: ABPropertyID = 23;
+pub const kABPersonCreationDateProperty: ABPropertyID = 27;
+pub const kABPersonModificationDateProperty: ABPropertyID = 28;
+
+pub const kABWorkLabel: &str = "_$!<Work>!$_";
+pub const kABHomeLabel: &str = "_$!<Home>!$_";
+pub const kABOtherLabel: &str = "_$!<Other>!$_";
+pub const kABPersonPhoneMobileLabel: &str = "_$!<Mobile>!$_";
+pub const kABPersonPhoneIPhoneLabel: &str = "iPhone";
+pub const kABPersonPhoneMainLabel: &str = "_$!<Main>!$_";
+pub const kABPersonPhoneHomeFAXLabel: &str = "_$!<HomeFAX>!$_";
+pub const kABPersonPhoneWorkFAXLabel: &str = "_$!<WorkFAX>!$_";
+pub const kABPersonPhonePagerLabel: &str = "_$!<Pager>!$_";
+pub const kABPersonHomePageLabel: &str = "_$!<HomePage>!$_";
+
+pub const kABPersonAddressStreetKey: &str = "Street";
+pub const kABPersonAddressCityKey: &str = "City";
+pub const kABPersonAddressStateKey: &str = "State";
+pub const kABPersonAddressZIPKey: &str = "ZIP";
+pub const kABPersonAddressCountryKey: &str = "Country";
+pub const kABPersonAddressCountryCodeKey: &str = "CountryCode";
+
+pub type ABPersonSortOrdering = u32;
+pub const kABPersonSortByFirstName: ABPersonSortOrdering = 0;
+pub const kABPersonSortByLastName: ABPersonSortOrdering = 1;
+
+pub type ABPersonCompositeNameFormat = u32;
+pub const kABPersonCompositeNameFormatFirstNameFirst: ABPersonCompositeNameFormat = 0;
+
+/// Property ID constants are variables in Apple's implementation, so the app
+/// reads their values from memory.
+macro_rules! property_constant {
+    ($name:ident) => {
+        (
+            concat!("_", stringify!($name)),
+            HostConstant::Custom(|mem: &mut Mem| -> ConstVoidPtr {
+                mem.alloc_and_write($name).cast().cast_const()
+            }),
+        )
+    };
+}
+
+pub const CONSTANTS: ConstantExports = &[
+    property_constant!(kABPersonFirstNameProperty),
+    property_constant!(kABPersonLastNameProperty),
+    property_constant!(kABPersonPhoneProperty),
+    property_constant!(kABPersonEmailProperty),
+    property_constant!(kABPersonAddressProperty),
+    property_constant!(kABPersonMiddleNameProperty),
+    property_constant!(kABPersonFirstNamePhoneticProperty),
+    property_constant!(kABPersonLastNamePhoneticProperty),
+    property_constant!(kABPersonMiddleNamePhoneticProperty),
+    property_constant!(kABPersonOrganizationProperty),
+    property_constant!(kABPersonDepartmentProperty),
+    property_constant!(kABPersonDateProperty),
+    property_constant!(kABPersonInstantMessageProperty),
+    property_constant!(kABPersonNoteProperty),
+    property_constant!(kABPersonBirthdayProperty),
+    property_constant!(kABPersonJobTitleProperty),
+    property_constant!(kABPersonNicknameProperty),
+    property_constant!(kABPersonPrefixProperty),
+    property_constant!(kABPersonSuffixProperty),
+    property_constant!(kABPersonURLProperty),
+    property_constant!(kABPersonRelatedNamesProperty),
+    property_constant!(kABPersonCreationDateProperty),
+    property_constant!(kABPersonModificationDateProperty),
+    ("_kABWorkLabel", HostConstant::NSString(kABWorkLabel)),
+    ("_kABHomeLabel", HostConstant::NSString(kABHomeLabel)),
+    ("_kABOtherLabel", HostConstant::NSString(kABOtherLabel)),
+    (
+        "_kABPersonPhoneMobileLabel",
+        HostConstant::NSString(kABPersonPhoneMobileLabel),
+    ),
+    (
+        "_kABPersonPhoneIPhoneLabel",
+        HostConstant::NSString(kABPersonPhoneIPhoneLabel),
+    ),
+    (
+        "_kABPersonPhoneMainLabel",
+        HostConstant::NSString(kABPersonPhoneMainLabel),
+    ),
+    (
+        "_kABPersonPhoneHomeFAXLabel",
+        HostConstant::NSString(kABPersonPhoneHomeFAXLabel),
+    ),
+    (
+        "_kABPersonPhoneWorkFAXLabel",
+        HostConstant::NSString(kABPersonPhoneWorkFAXLabel),
+    ),
+    (
+        "_kABPersonPhonePagerLabel",
+        HostConstant::NSString(kABPersonPhonePagerLabel),
+    ),
+    (
+        "_kABPersonHomePageLabel",
+        HostConstant::NSString(kABPersonHomePageLabel),
+    ),
+    (
+        "_kABPersonAddressStreetKey",
+        HostConstant::NSString(kABPersonAddressStreetKey),
+    ),
+    (
+        "_kABPersonAddressCityKey",
+        HostConstant::NSString(kABPersonAddressCityKey),
+    ),
+    (
+        "_kABPersonAddressStateKey",
+        HostConstant::NSString(kABPersonAddressStateKey),
+    ),
+    (
+        "_kABPersonAddressZIPKey",
+        HostConstant::NSString(kABPersonAddressZIPKey),
+    ),
+    (
+        "_kABPersonAddressCountryKey",
+        HostConstant::NSString(kABPersonAddressCountryKey),
+    ),
+    (
+        "_kABPersonAddressCountryCodeKey",
+        HostConstant::NSString(kABPersonAddressCountryCodeKey),
+    ),
+];
+
+pub(super) struct ABPersonHostObject {
+    pub(super) record_id: ABRecordID,
+    pub(super) contact: Contact,
+}
+impl HostObject for ABPersonHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// ABPerson is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not exposed.
+@implementation _touchHLE_ABPerson: NSObject
+@end
+
+};
+
+pub(super) fn new_person(env: &mut Environment, record_id: ABRecordID, contact: Contact) -> id {
+    let host_object = Box::new(ABPersonHostObject { record_id, contact });
+    let class = env.objc.get_known_class("_touchHLE_ABPerson", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+pub(super) fn contact(env: &mut Environment, person: ABRecordRef) -> &Contact {
+    &env.objc.borrow::<ABPersonHostObject>(person).contact
+}
+
+/// The name shown for a person, e.g. "Ada Lovelace", or the organization if
+/// there's no name.
+pub(super) fn composite_name(contact: &Contact) -> Option<String> {
+    let parts = [
+        &contact.prefix,
+        &contact.first_name,
+        &contact.middle_name,
+        &contact.last_name,
+        &contact.suffix,
+    ];
+    let name: Vec<&str> = parts.iter().filter_map(|part| part.as_deref()).collect();
+    if name.is_empty() {
+        contact.organization.clone()
+    } else {
+        Some(name.join(" "))
+    }
+}
+
+fn property_type(property: ABPropertyID) -> ABPropertyType {
+    match property {
+        kABPersonBirthdayProperty
+        | kABPersonCreationDateProperty
+        | kABPersonModificationDateProperty => kABDateTimePropertyType,
+        kABPersonPhoneProperty
+        | kABPersonEmailProperty
+        | kABPersonURLProperty
+        | kABPersonRelatedNamesProperty => kABMultiStringPropertyType,
+        kABPersonAddressProperty | kABPersonInstantMessageProperty => {
+            kABMultiDictionaryPropertyType
+        }
+        kABPersonDateProperty => kABMultiDateTimePropertyType,
+        _ => kABStringPropertyType,
+    }
+}
+
+/// Implementation of `ABRecordCopyValue()` for people.
+pub(super) fn copy_value(env: &mut Environment, person: ABRecordRef, property: ABPropertyID) -> id {
+    let contact = contact(env, person).clone();
+    let string = match property {
+        kABPersonFirstNameProperty => &contact.first_name,
+        kABPersonLastNameProperty => &contact.last_name,
+        kABPersonMiddleNameProperty => &contact.middle_name,
+        kABPersonPrefixProperty => &contact.prefix,
+        kABPersonSuffixProperty => &contact.suffix,
+        kABPersonNicknameProperty => &contact.nickname,
+        kABPersonOrganizationProperty => &contact.organization,
+        kABPersonDepartmentProperty => &contact.department,
+        kABPersonJobTitleProperty => &contact.job_title,
+        kABPersonNoteProperty => &contact.note,
+        kABPersonBirthdayProperty => {
+            let Some((year, month, day)) = contact.birthday else {
+                return nil;
+            };
+            // Midday UTC, so it's the same day in most time zones.
+            let seconds = days_from_civil(year, month, day) * 24 * 60 * 60 + 12 * 60 * 60;
+            let time_interval = seconds as f64 - NSTimeIntervalSince1970;
+            return ns_date::from_time_interval(env, time_interval);
+        }
+        kABPersonPhoneProperty | kABPersonEmailProperty | kABPersonURLProperty => {
+            let strings = match property {
+                kABPersonPhoneProperty => &contact.phones,
+                kABPersonEmailProperty => &contact.emails,
+                _ => &contact.urls,
+            };
+            let values = strings
+                .iter()
+                .map(|&(label, ref value)| (label, from_rust_string(env, value.clone())))
+                .collect();
+            return ab_multi_value::new_multi_value(env, kABMultiStringPropertyType, values);
+        }
+        kABPersonAddressProperty => {
+            let values = contact
+                .addresses
+                .iter()
+                .map(|&(label, ref address)| {
+                    let fields = [
+                        (kABPersonAddressStreetKey, &address.street),
+                        (kABPersonAddressCityKey, &address.city),
+                        (kABPersonAddressStateKey, &address.state),
+                        (kABPersonAddressZIPKey, &address.zip),
+                        (kABPersonAddressCountryKey, &address.country),
+                    ];
+                    let keys_and_objects: Vec<(id, id)> = fields
+                        .into_iter()
+                        .filter(|(_, value)| !value.is_empty())
+                        .map(|(key, value)| {
+                            let key = get_static_str(env, key);
+                            (key, from_rust_string(env, value.clone()))
+                        })
+                        .collect();
+                    let dict = dict_from_keys_and_objects(env, &keys_and_objects);
+                    for (_, object) in keys_and_objects {
+                        release(env, object);
+                    }
+                    (label, dict)
+                })
+                .collect();
+            return ab_multi_value::new_multi_value(env, kABMultiDictionaryPropertyType, values);
+        }
+        _ if property_type(property) & kABMultiValueMask != 0 => {
+            return ab_multi_value::new_multi_value(env, property_type(property), Vec::new());
+        }
+        _ => return nil,
+    };
+    match string {
+        Some(string) => from_rust_string(env, string.clone()),
+        None => nil,
+    }
+}
+
+fn ABPersonGetTypeOfProperty(_env: &mut Environment, property: ABPropertyID) -> ABPropertyType {
+    property_type(property)
+}
+
+fn ABPersonCopyLocalizedPropertyName(env: &mut Environment, property: ABPropertyID) -> CFStringRef {
+    let name = match property {
+        kABPersonFirstNameProperty => "First",
+        kABPersonLastNameProperty => "Last",
+        kABPersonMiddleNameProperty => "Middle",
+        kABPersonPrefixProperty => "Prefix",
+        kABPersonSuffixProperty => "Suffix",
+        kABPersonNicknameProperty => "Nickname",
+        kABPersonFirstNamePhoneticProperty => "First Phonetic",
+        kABPersonLastNamePhoneticProperty => "Last Phonetic",
+        kABPersonMiddleNamePhoneticProperty => "Middle Phonetic",
+        kABPersonOrganizationProperty => "Company",
+        kABPersonDepartmentProperty => "Department",
+        kABPersonJobTitleProperty => "Job Title",
+        kABPersonNoteProperty => "Notes",
+        kABPersonBirthdayProperty => "Birthday",
+        kABPersonPhoneProperty => "Phone",
+        kABPersonEmailProperty => "Email",
+        kABPersonAddressProperty => "Address",
+        kABPersonURLProperty => "URL",
+        kABPersonDateProperty => "Date",
+        kABPersonInstantMessageProperty => "Instant Message",
+        kABPersonRelatedNamesProperty => "Related People",
+        kABPersonCreationDateProperty => "Creation Date",
+        kABPersonModificationDateProperty => "Modification Date",
+        _ => "",
+    };
+    from_rust_string(env, name.to_string())
+}
+
+fn ABPersonGetSortOrdering(_env: &mut Environment) -> ABPersonSortOrdering {
+    kABPersonSortByFirstName
+}
+
+fn ABPersonGetCompositeNameFormat(_env: &mut Environment) -> ABPersonCompositeNameFormat {
+    kABPersonCompositeNameFormatFirstNameFirst
+}
+
+fn ABPersonComparePeopleByName(
+    env: &mut Environment,
+    person1: ABRecordRef,
+    person2: ABRecordRef,
+    ordering: ABPersonSortOrdering,
+) -> CFComparisonResult {
+    let sort_key = |contact: &Contact| {
+        let first = contact.first_name.as_deref().unwrap_or("").to_lowercase();
+        let last = contact.last_name.as_deref().unwrap_or("").to_lowercase();
+        if ordering == kABPersonSortByLastName {
+            (last, first)
+        } else {
+            (first, last)
+        }
+    };
+    let key1 = sort_key(contact(env, person1));
+    let key2 = sort_key(contact(env, person2));
+    match key1.cmp(&key2) {
+        std::cmp::Ordering::Less => kCFCompareLessThan,
+        std::cmp::Ordering::Equal => kCFCompareEqualTo,
+        std::cmp::Ordering::Greater => kCFCompareGreaterThan,
+    }
+}
+
+fn ABPersonHasImageData(_env: &mut Environment, _person: ABRecordRef) -> bool {
+    false
+}
+
+fn ABPersonCopyImageData(_env: &mut Environment, _person: ABRecordRef) -> CFTypeRef {
+    nil
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(ABPersonGetTypeOfProperty(_)),
+    export_c_func!(ABPersonCopyLocalizedPropertyName(_)),
+    export_c_func!(ABPersonGetSortOrdering()),
+    export_c_func!(ABPersonGetCompositeNameFormat()),
+    export_c_func!(ABPersonComparePeopleByName(_, _, _)),
+    export_c_func!(ABPersonHasImageData(_)),
+    export_c_func!(ABPersonCopyImageData(_)),
+];
@@ -0,0 +1,51 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `ABRecord`, the common interface of people and groups.
+//!
+//! There are no groups, so every record is a person.
+
+use super::ab_person::{self, ABPersonHostObject, ABPropertyID, ABRecordID};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_string::CFStringRef;
+use crate::frameworks::core_foundation::CFTypeRef;
+use crate::frameworks::foundation::ns_string::from_rust_string;
+use crate::objc::nil;
+use crate::Environment;
+
+pub type ABRecordRef = CFTypeRef;
+
+pub type ABRecordType = u32;
+pub const kABPersonType: ABRecordType = 0;
+
+fn ABRecordGetRecordID(env: &mut Environment, record: ABRecordRef) -> ABRecordID {
+    env.objc.borrow::<ABPersonHostObject>(record).record_id
+}
+
+fn ABRecordGetRecordType(_env: &mut Environment, _record: ABRecordRef) -> ABRecordType {
+    kABPersonType
+}
+
+fn ABRecordCopyValue(
+    env: &mut Environment,
+    record: ABRecordRef,
+    property: ABPropertyID,
+) -> CFTypeRef {
+    ab_person::copy_value(env, record, property)
+}
+
+fn ABRecordCopyCompositeName(env: &mut Environment, record: ABRecordRef) -> CFStringRef {
+    match ab_person::composite_name(ab_person::contact(env, record)) {
+        Some(name) => from_rust_string(env, name),
+        None => nil,
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(ABRecordGetRecordID(_)),
+    export_c_func!(ABRecordGetRecordType(_)),
+    export_c_func!(ABRecordCopyValue(_, _)),
+    export_c_func!(ABRecordCopyCompositeName(_)),
+];
@@ -0,0 +1,540 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The contacts in the address book, read from the file set with
+//! `--contacts=`.
+//!
+//! The file can be a vCard file (`.vcf`) with any number of cards, as exported
+//! by most address book apps, or a CSV file (`.csv`) whose first row names the
+//! columns, like this:
+//!
+//! ```text
+//! First Name,Last Name,Mobile Phone,E-mail Address
+//! Ada,Lovelace,+44 20 7946 0000,ada@example.com
+//! ```
+//!
+//! Recognized CSV columns are `First Name`, `Middle Name`, `Last Name`,
+//! `Name` (a full name, used when the others are missing), `Prefix`, `Suffix`,
+//! `Nickname`, `Organization` (or `Company`), `Department`, `Job Title`,
+//! `Birthday` (`YYYY-MM-DD`), `Notes`, and any column with "phone" or "e-mail"
+//! in its name. Other columns are ignored. Without a file, the address book is
+//! empty.
+
+use super::ab_person::{
+    kABHomeLabel, kABOtherLabel, kABPersonHomePageLabel, kABPersonPhoneHomeFAXLabel,
+    kABPersonPhoneIPhoneLabel, kABPersonPhoneMainLabel, kABPersonPhoneMobileLabel,
+    kABPersonPhonePagerLabel, kABPersonPhoneWorkFAXLabel, kABWorkLabel,
+};
+use crate::Environment;
+use std::path::Path;
+use std::rc::Rc;
+
+/// A phone number, e-mail address or similar, with its label.
+pub type Labeled<T> = (&'static str, T);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Address {
+    pub street: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub country: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Contact {
+    pub first_name: Option<String>,
+    pub middle_name: Option<String>,
+    pub last_name: Option<String>,
+    pub prefix: Option<String>,
+    pub suffix: Option<String>,
+    pub nickname: Option<String>,
+    pub organization: Option<String>,
+    pub department: Option<String>,
+    pub job_title: Option<String>,
+    pub note: Option<String>,
+    /// Year, month and day.
+    pub birthday: Option<(i64, i64, i64)>,
+    pub phones: Vec<Labeled<String>>,
+    pub emails: Vec<Labeled<String>>,
+    pub urls: Vec<Labeled<String>>,
+    pub addresses: Vec<Labeled<Address>>,
+}
+impl Contact {
+    /// Use a full name for the name fields, if none of them are set.
+    fn set_full_name(&mut self, name: &str) {
+        if self.first_name.is_some() || self.last_name.is_some() {
+            return;
+        }
+        let mut words: Vec<&str> = name.split_whitespace().collect();
+        if let Some(last) = words.pop() {
+            if words.is_empty() {
+                self.first_name = Some(last.to_string());
+            } else {
+                self.first_name = Some(words.join(" "));
+                self.last_name = Some(last.to_string());
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Contact::default()
+    }
+}
+
+#[derive(Default)]
+pub struct State {
+    /// Loaded on first use.
+    contacts: Option<Rc<Vec<Contact>>>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.address_book.contacts
+    }
+}
+
+/// Get the contacts, reading them from disk if that hasn't happened yet.
+pub fn contacts(env: &mut Environment) -> Rc<Vec<Contact>> {
+    if let Some(contacts) = &State::get(&mut env.framework_state).contacts {
+        return contacts.clone();
+    }
+    let contacts = match env.options.contacts_file.as_deref() {
+        Some(path) => load(path).unwrap_or_else(|e| {
+            log!(
+                "Warning: couldn't read contacts from {}: {}. The address book will be empty.",
+                path.display(),
+                e
+            );
+            Vec::new()
+        }),
+        None => {
+            log!(
+                "The app is using the address book, but there's no contacts file, so it's \
+                 empty. See --contacts=."
+            );
+            Vec::new()
+        }
+    };
+    log_dbg!("Loaded {} contacts", contacts.len());
+    let contacts = Rc::new(contacts);
+    State::get(&mut env.framework_state).contacts = Some(contacts.clone());
+    contacts
+}
+
+fn load(path: &Path) -> Result<Vec<Contact>, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let text = String::from_utf8_lossy(&bytes);
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    let is_csv = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    if is_csv {
+        parse_csv(text)
+    } else {
+        parse_vcard(text)
+    }
+}
+
+/// Split a vCard value on an unescaped separator, and unescape the parts.
+fn split_vcard_value(value: &str, separator: Option<char>) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => parts.last_mut().unwrap().push('\n'),
+                Some(c) => parts.last_mut().unwrap().push(c),
+                None => (),
+            },
+            c if Some(c) == separator => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+    parts
+}
+
+fn unescape_vcard_value(value: &str) -> String {
+    split_vcard_value(value, None).remove(0)
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Parse a date in `YYYY-MM-DD` or `YYYYMMDD` form, ignoring any time.
+fn parse_date(value: &str) -> Option<(i64, i64, i64)> {
+    let date = value.trim().split('T').next()?;
+    let digits: String = date.chars().filter(|&c| c != '-').collect();
+    if digits.len() != 8 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let year = digits[0..4].parse().ok()?;
+    let month = digits[4..6].parse().ok()?;
+    let day = digits[6..8].parse().ok()?;
+    ((1..=12).contains(&month) && (1..=31).contains(&day)).then_some((year, month, day))
+}
+
+fn phone_label(types: &[String]) -> &'static str {
+    let has = |name: &str| types.iter().any(|t| t == name);
+    if has("fax") {
+        if has("work") {
+            kABPersonPhoneWorkFAXLabel
+        } else {
+            kABPersonPhoneHomeFAXLabel
+        }
+    } else if has("iphone") {
+        kABPersonPhoneIPhoneLabel
+    } else if has("cell") || has("mobile") {
+        kABPersonPhoneMobileLabel
+    } else if has("pager") {
+        kABPersonPhonePagerLabel
+    } else if has("main") || (has("pref") && !has("home") && !has("work")) {
+        kABPersonPhoneMainLabel
+    } else {
+        other_label(types)
+    }
+}
+
+fn other_label(types: &[String]) -> &'static str {
+    if types.iter().any(|t| t == "home") {
+        kABHomeLabel
+    } else if types.iter().any(|t| t == "work") {
+        kABWorkLabel
+    } else {
+        kABOtherLabel
+    }
+}
+
+pub fn parse_vcard(text: &str) -> Result<Vec<Contact>, String> {
+    // Long lines are folded by starting the following lines with whitespace.
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match line.strip_prefix([' ', '\t']) {
+            Some(rest) if !lines.is_empty() => lines.last_mut().unwrap().push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut contacts = Vec::new();
+    let mut current: Option<Contact> = None;
+    for (line_number, line) in lines.iter().enumerate() {
+        let line_number = line_number + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((name_and_params, value)) = line.split_once(':') else {
+            return Err(format!("Line {}: expected 'NAME:value'", line_number));
+        };
+        let mut params = name_and_params.split(';');
+        let name = params.next().unwrap();
+        // Properties can be grouped, as in "item1.TEL".
+        let name = name.rsplit('.').next().unwrap().to_ascii_uppercase();
+        // Both "TYPE=HOME,VOICE" (vCard 3.0) and "HOME;VOICE" (vCard 2.1).
+        let types: Vec<String> = params
+            .flat_map(|param| {
+                let param = param.to_ascii_lowercase();
+                let param = match param.split_once('=') {
+                    Some(("type", types)) => types.to_string(),
+                    Some(_) => String::new(),
+                    None => param,
+                };
+                param
+                    .split(',')
+                    .map(|t| t.trim_matches('"').to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        if name == "BEGIN" && value.eq_ignore_ascii_case("VCARD") {
+            current = Some(Contact::default());
+            continue;
+        }
+        let Some(contact) = current.as_mut() else {
+            return Err(format!(
+                "Line {}: {:?} outside of a card",
+                line_number, name
+            ));
+        };
+        match name.as_str() {
+            "END" => {
+                contacts.push(current.take().unwrap());
+            }
+            "N" => {
+                let parts = split_vcard_value(value, Some(';'));
+                let part = |i: usize| parts.get(i).and_then(|part| non_empty(part));
+                contact.last_name = part(0);
+                contact.first_name = part(1);
+                contact.middle_name = part(2);
+                contact.prefix = part(3);
+                contact.suffix = part(4);
+            }
+            "FN" => {
+                // Handled after the card is complete, since N may come later.
+            }
+            "NICKNAME" => contact.nickname = non_empty(&split_vcard_value(value, Some(','))[0]),
+            "ORG" => {
+                let parts = split_vcard_value(value, Some(';'));
+                contact.organization = non_empty(&parts[0]);
+                contact.department = parts.get(1).and_then(|part| non_empty(part));
+            }
+            "TITLE" => contact.job_title = non_empty(&unescape_vcard_value(value)),
+            "NOTE" => contact.note = non_empty(&unescape_vcard_value(value)),
+            "BDAY" => contact.birthday = parse_date(value),
+            "TEL" => {
+                // vCard 4.0 numbers can be URIs.
+                let value = value.strip_prefix("tel:").unwrap_or(value);
+                if let Some(number) = non_empty(value) {
+                    contact.phones.push((phone_label(&types), number));
+                }
+            }
+            "EMAIL" => {
+                if let Some(address) = non_empty(value) {
+                    contact.emails.push((other_label(&types), address));
+                }
+            }
+            "URL" => {
+                if let Some(url) = non_empty(&unescape_vcard_value(value)) {
+                    let label = if types.is_empty() {
+                        kABPersonHomePageLabel
+                    } else {
+                        other_label(&types)
+                    };
+                    contact.urls.push((label, url));
+                }
+            }
+            "ADR" => {
+                let parts = split_vcard_value(value, Some(';'));
+                let part = |i: usize| parts.get(i).map_or(String::new(), |p| p.trim().into());
+                let address = Address {
+                    // The post office box and extended address come first.
+                    street: part(2),
+                    city: part(3),
+                    state: part(4),
+                    zip: part(5),
+                    country: part(6),
+                };
+                if address != Address::default() {
+                    contact.addresses.push((other_label(&types), address));
+                }
+            }
+            _ => (),
+        }
+    }
+    if current.is_some() {
+        return Err("Card without END:VCARD".to_string());
+    }
+
+    // A second pass for full names.
+    for (contact, full_name) in contacts.iter_mut().zip(full_names(&lines)) {
+        if let Some(full_name) = full_name {
+            if contact.organization.as_deref() != Some(full_name.as_str()) {
+                contact.set_full_name(&full_name);
+            }
+        }
+    }
+    Ok(contacts)
+}
+
+/// The FN property of each card, in order.
+fn full_names(lines: &[String]) -> Vec<Option<String>> {
+    let mut names = Vec::new();
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = name.split(';').next().unwrap();
+        let name = name.rsplit('.').next().unwrap();
+        if name.eq_ignore_ascii_case("BEGIN") {
+            names.push(None);
+        } else if name.eq_ignore_ascii_case("FN") {
+            if let Some(last) = names.last_mut() {
+                *last = non_empty(&unescape_vcard_value(value));
+            }
+        }
+    }
+    names
+}
+
+/// Split CSV text into rows of fields. Quoted fields can contain commas,
+/// newlines and doubled quotes.
+fn csv_rows(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.is_empty() => in_quotes = true,
+            ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => (),
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("Unterminated quoted field".to_string());
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|row| row.iter().any(|field| !field.trim().is_empty()));
+    Ok(rows)
+}
+
+pub fn parse_csv(text: &str) -> Result<Vec<Contact>, String> {
+    let mut rows = csv_rows(text)?.into_iter();
+    let Some(header) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let header: Vec<String> = header
+        .iter()
+        .map(|column| column.trim().to_ascii_lowercase())
+        .collect();
+
+    let mut contacts = Vec::new();
+    for row in rows {
+        let mut contact = Contact::default();
+        let mut full_name = None;
+        for (column, value) in header.iter().zip(&row) {
+            let Some(value) = non_empty(value) else {
+                continue;
+            };
+            let words: Vec<String> = column
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .map(String::from)
+                .collect();
+            match column.as_str() {
+                "first name" | "given name" => contact.first_name = Some(value),
+                "middle name" | "additional name" => contact.middle_name = Some(value),
+                "last name" | "family name" | "surname" => contact.last_name = Some(value),
+                "name" | "full name" | "display name" => full_name = Some(value),
+                "prefix" | "name prefix" => contact.prefix = Some(value),
+                "suffix" | "name suffix" => contact.suffix = Some(value),
+                "nickname" => contact.nickname = Some(value),
+                "organization" | "company" => contact.organization = Some(value),
+                "department" => contact.department = Some(value),
+                "job title" => contact.job_title = Some(value),
+                "birthday" => contact.birthday = parse_date(&value),
+                "notes" | "note" => contact.note = Some(value),
+                _ if column.contains("phone") || words.iter().any(|w| w == "mobile") => {
+                    contact.phones.push((phone_label(&words), value));
+                }
+                _ if column.contains("e-mail") || column.contains("email") => {
+                    contact.emails.push((other_label(&words), value));
+                }
+                _ => (),
+            }
+        }
+        if let Some(full_name) = full_name {
+            contact.set_full_name(&full_name);
+        }
+        if !contact.is_empty() {
+            contacts.push(contact);
+        }
+    }
+    Ok(contacts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vcard() {
+        let text = "\
+BEGIN:VCARD\r
+VERSION:3.0\r
+N:Lovelace;Ada;;Countess;\r
+FN:Countess Ada Lovelace\r
+ORG:Analytical Engines\\, Ltd.;Research\r
+TEL;TYPE=CELL:+44 20 7946 0000\r
+TEL;TYPE=WORK,FAX:+44 20 7946 0001\r
+item1.EMAIL;TYPE=INTERNET,HOME:ada@exam\r
+ ple.com\r
+ADR;TYPE=WORK:;;12 Example St;London;;SW1A 1AA;UK\r
+BDAY:1815-12-10\r
+NOTE:First line\\nSecond line\r
+END:VCARD\r
+BEGIN:VCARD\r
+VERSION:2.1\r
+FN:Charles Babbage\r
+TEL;HOME;VOICE:555-0100\r
+END:VCARD\r
+";
+        let contacts = parse_vcard(text).unwrap();
+        assert_eq!(contacts.len(), 2);
+
+        let ada = &contacts[0];
+        assert_eq!(ada.first_name.as_deref(), Some("Ada"));
+        assert_eq!(ada.last_name.as_deref(), Some("Lovelace"));
+        assert_eq!(ada.prefix.as_deref(), Some("Countess"));
+        assert_eq!(
+            ada.organization.as_deref(),
+            Some("Analytical Engines, Ltd.")
+        );
+        assert_eq!(ada.department.as_deref(), Some("Research"));
+        assert_eq!(
+            ada.phones,
+            [
+                (kABPersonPhoneMobileLabel, "+44 20 7946 0000".to_string()),
+                (kABPersonPhoneWorkFAXLabel, "+44 20 7946 0001".to_string()),
+            ]
+        );
+        assert_eq!(ada.emails, [(kABHomeLabel, "ada@example.com".to_string())]);
+        assert_eq!(ada.addresses[0].0, kABWorkLabel);
+        assert_eq!(ada.addresses[0].1.city, "London");
+        assert_eq!(ada.addresses[0].1.zip, "SW1A 1AA");
+        assert_eq!(ada.birthday, Some((1815, 12, 10)));
+        assert_eq!(ada.note.as_deref(), Some("First line\nSecond line"));
+
+        let charles = &contacts[1];
+        assert_eq!(charles.first_name.as_deref(), Some("Charles"));
+        assert_eq!(charles.last_name.as_deref(), Some("Babbage"));
+        assert_eq!(charles.phones, [(kABHomeLabel, "555-0100".to_string())]);
+
+        assert!(parse_vcard("BEGIN:VCARD\nFN:Nobody\n").is_err());
+    }
+
+    #[test]
+    fn csv() {
+        let text = "\
+First Name,Last Name,Mobile Phone,Business Phone,E-mail Address,Notes,Ignored
+Ada,Lovelace,+44 20 7946 0000,,ada@example.com,\"Likes \"\"engines\"\",
+and poetry\",x
+,,,,,,
+Name,,,,,,
+";
+        let contacts = parse_csv(text).unwrap();
+        assert_eq!(contacts.len(), 2);
+        assert_eq!(contacts[0].first_name.as_deref(), Some("Ada"));
+        assert_eq!(
+            contacts[0].phones,
+            [(kABPersonPhoneMobileLabel, "+44 20 7946 0000".to_string())]
+        );
+        assert_eq!(
+            contacts[0].emails,
+            [(kABOtherLabel, "ada@example.com".to_string())]
+        );
+        assert_eq!(
+            contacts[0].note.as_deref(),
+            Some("Likes \"engines\",\nand poetry")
+        );
+        assert_eq!(contacts[1].first_name.as_deref(), Some("Name"));
+    }
+}
@@ -0,0 +1,13 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The AddressBookUI framework.
+
+pub mod ab_people_picker_navigation_controller;
+
+#[derive(Default)]
+pub struct State {
+    ab_people_picker_navigation_controller: ab_people_picker_navigation_controller::State,
+}
@@ -0,0 +1,93 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `ABPeoplePickerNavigationController`.
+//!
+//! There's no picker UI. Once the picker has appeared, the first person in the
+//! address book is picked, as if the user had tapped them. If the address book
+//! is empty, or the delegate wants the user to go on and pick one of the
+//! person's properties, the picker is cancelled instead, as if the user had
+//! tapped "Cancel".
+
+use crate::frameworks::address_book::ab_address_book::{
+    ABAddressBookCopyArrayOfAllPeople, ABAddressBookCreate,
+};
+use crate::frameworks::foundation::NSUInteger;
+use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports};
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct State {
+    /// The delegate of each picker, weak references. This can't go in a host
+    /// object, because the picker's host object is `UIViewController`'s.
+    delegates: HashMap<id, id>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state
+            .address_book_ui
+            .ab_people_picker_navigation_controller
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation ABPeoplePickerNavigationController: UINavigationController
+
+- (id)peoplePickerDelegate {
+    State::get(&mut env.framework_state)
+        .delegates
+        .get(&this)
+        .copied()
+        .unwrap_or(nil)
+}
+- (())setPeoplePickerDelegate:(id)delegate { // id<ABPeoplePickerNavigationControllerDelegate>
+    State::get(&mut env.framework_state).delegates.insert(this, delegate);
+}
+
+- (id)displayedProperties {
+    nil
+}
+- (())setDisplayedProperties:(id)_properties { // NSArray*
+    // Properties are never shown.
+}
+
+- (())viewDidAppear:(bool)_animated {
+    let delegate: id = msg![env; this peoplePickerDelegate];
+    if delegate == nil {
+        log!("Warning: {:?} has no delegate, so it can't be dismissed", this);
+        return;
+    }
+
+    // The delegate is likely to dismiss the picker.
+    retain(env, this);
+    let address_book = ABAddressBookCreate(env);
+    let people = ABAddressBookCopyArrayOfAllPeople(env, address_book);
+    let count: NSUInteger = msg![env; people count];
+    let should_continue = if count > 0 {
+        let person: id = msg![env; people objectAtIndex:0u32];
+        log!("{:?} picking the first person in the address book", this);
+        msg![env; delegate peoplePickerNavigationController:this
+                         shouldContinueAfterSelectingPerson:person]
+    } else {
+        log!("{:?} has no people to pick, cancelling", this);
+        false
+    };
+    if count == 0 || should_continue {
+        if should_continue {
+            log!("Warning: {:?} can't show a person's properties, cancelling", this);
+        }
+        () = msg![env; delegate peoplePickerNavigationControllerDidCancel:this];
+    }
+    release(env, people);
+    release(env, address_book);
+    release(env, this);
+}
+
+@end
+
+};
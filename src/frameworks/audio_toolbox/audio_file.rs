@@ -57,6 +57,56 @@ const kAudioFilePropertyAudioDataByteCount: AudioFilePropertyID = fourcc(b"bcnt"
 const kAudioFilePropertyAudioDataPacketCount: AudioFilePropertyID = fourcc(b"pcnt");
 const kAudioFilePropertyPacketSizeUpperBound: AudioFilePropertyID = fourcc(b"pkub");
 
+/// Get the Core Audio Types equivalent of an [audio::AudioDescription].
+pub(super) fn to_stream_description(desc: audio::AudioDescription) -> AudioStreamBasicDescription {
+    let audio::AudioDescription {
+        sample_rate,
+        format,
+        bytes_per_packet,
+        frames_per_packet,
+        channels_per_frame,
+        bits_per_channel,
+    } = desc;
+
+    match format {
+        audio::AudioFormat::LinearPcm {
+            is_float,
+            is_little_endian,
+        } => {
+            let is_packed = (bits_per_channel * channels_per_frame * frames_per_packet)
+                == (bytes_per_packet * 8);
+            let format_flags = (u32::from(is_float) * kAudioFormatFlagIsFloat)
+                | (u32::from(!is_float) * kAudioFormatFlagIsSignedInteger)
+                | (u32::from(is_packed) * kAudioFormatFlagIsPacked)
+                | (u32::from(!is_little_endian) * kAudioFormatFlagIsBigEndian);
+            AudioStreamBasicDescription {
+                sample_rate,
+                format_id: kAudioFormatLinearPCM,
+                format_flags,
+                bytes_per_packet,
+                frames_per_packet,
+                bytes_per_frame: bytes_per_packet / frames_per_packet,
+                channels_per_frame,
+                bits_per_channel,
+                _reserved: 0,
+            }
+        }
+        audio::AudioFormat::AppleIma4 => {
+            AudioStreamBasicDescription {
+                sample_rate,
+                format_id: kAudioFormatAppleIMA4,
+                format_flags: 0,
+                bytes_per_packet,
+                frames_per_packet,
+                bytes_per_frame: 0, // compressed
+                channels_per_frame,
+                bits_per_channel,
+                _reserved: 0,
+            }
+        }
+    }
+}
+
 fn AudioFileOpenURL(
     env: &mut Environment,
     in_file_ref: CFURLRef,
@@ -122,52 +172,7 @@ fn AudioFileGetProperty(
 
     match in_property_id {
         kAudioFilePropertyDataFormat => {
-            let audio::AudioDescription {
-                sample_rate,
-                format,
-                bytes_per_packet,
-                frames_per_packet,
-                channels_per_frame,
-                bits_per_channel,
-            } = host_object.audio_file.audio_description();
-
-            let desc: AudioStreamBasicDescription = match format {
-                audio::AudioFormat::LinearPcm {
-                    is_float,
-                    is_little_endian,
-                } => {
-                    let is_packed = (bits_per_channel * channels_per_frame * frames_per_packet)
-                        == (bytes_per_packet * 8);
-                    let format_flags = (u32::from(is_float) * kAudioFormatFlagIsFloat)
-                        | (u32::from(!is_float) * kAudioFormatFlagIsSignedInteger)
-                        | (u32::from(is_packed) * kAudioFormatFlagIsPacked)
-                        | (u32::from(!is_little_endian) * kAudioFormatFlagIsBigEndian);
-                    AudioStreamBasicDescription {
-                        sample_rate,
-                        format_id: kAudioFormatLinearPCM,
-                        format_flags,
-                        bytes_per_packet,
-                        frames_per_packet,
-                        bytes_per_frame: bytes_per_packet / frames_per_packet,
-                        channels_per_frame,
-                        bits_per_channel,
-                        _reserved: 0,
-                    }
-                }
-                audio::AudioFormat::AppleIma4 => {
-                    AudioStreamBasicDescription {
-                        sample_rate,
-                        format_id: kAudioFormatAppleIMA4,
-                        format_flags: 0,
-                        bytes_per_packet,
-                        frames_per_packet,
-                        bytes_per_frame: 0, // compressed
-                        channels_per_frame,
-                        bits_per_channel,
-                        _reserved: 0,
-                    }
-                }
-            };
+            let desc = to_stream_description(host_object.audio_file.audio_description());
             env.mem.write(out_property_data.cast(), desc);
         }
         kAudioFilePropertyAudioDataByteCount => {
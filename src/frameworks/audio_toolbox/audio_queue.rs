@@ -7,16 +7,23 @@
 //!
 //! The audio playback here is mapped onto OpenAL Soft for convenience.
 //! Apple's implementation probably uses Core Audio instead.
+//!
+//! Input queues record from the host's microphone with OpenAL Soft's capture
+//! extension, but only if the user allows it with `--allow-microphone`.
+//! Otherwise they record silence.
 
 use crate::abi::{CallFromHost, GuestFunction};
-use crate::audio::decode_ima4;
+use crate::audio::decode_ima4_interleaved;
+use crate::audio::mixer;
 use crate::audio::openal as al;
 use crate::audio::openal::al_types::*;
 use crate::audio::openal::alc_types::*;
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::core_audio_types::{
-    kAudioFormatAppleIMA4, kAudioFormatFlagIsBigEndian, kAudioFormatFlagIsFloat,
-    kAudioFormatFlagIsPacked, kAudioFormatLinearPCM, AudioStreamBasicDescription,
+    debug_fourcc, fourcc, kAudioFormatAppleIMA4, kAudioFormatFlagIsBigEndian,
+    kAudioFormatFlagIsFloat, kAudioFormatFlagIsNonInterleaved, kAudioFormatFlagIsPacked,
+    kAudioFormatFlagIsSignedInteger, kAudioFormatLinearPCM, kAudioTimeStampHostTimeValid,
+    kAudioTimeStampSampleTimeValid, AudioStreamBasicDescription, AudioTimeStamp,
 };
 use crate::frameworks::core_foundation::cf_run_loop::{
     kCFRunLoopCommonModes, CFRunLoopMode, CFRunLoopRef,
@@ -24,10 +31,13 @@ use crate::frameworks::core_foundation::cf_run_loop::{
 use crate::frameworks::foundation::ns_run_loop;
 use crate::frameworks::foundation::ns_string::get_static_str;
 use crate::frameworks::mac_types::OSStatus;
-use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr, SafeRead};
-use crate::objc::msg;
-use crate::Environment;
+use crate::mem::{
+    guest_size_of, ConstPtr, ConstVoidPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr, SafeRead,
+};
+use crate::objc::{msg, msg_class};
+use crate::{Environment, Options};
 use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
 
 #[derive(Default)]
 pub struct State {
@@ -38,11 +48,13 @@ impl State {
     fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
         &mut framework_state.audio_toolbox.audio_queue
     }
-    fn make_al_context_current(&mut self) -> ContextManager {
+    fn make_al_context_current(&mut self, options: &Options) -> ContextManager {
         if self.al_device_and_context.is_none() {
             let device = unsafe { al::alcOpenDevice(std::ptr::null()) };
             assert!(!device.is_null());
-            let context = unsafe { al::alcCreateContext(device, std::ptr::null()) };
+            let mut attrs = mixer::context_attributes(device, options);
+            attrs.push(0);
+            let context = unsafe { al::alcCreateContext(device, attrs.as_ptr()) };
             assert!(!context.is_null());
             log_dbg!(
                 "New internal OpenAL device ({:?}) and context ({:?})",
@@ -56,12 +68,34 @@ impl State {
 
         // This object will make sure the existing context, which will belong
         // to the guest app, is restored once we're done.
-        ContextManager::make_active(context)
+        let manager = ContextManager::make_active(context);
+        // The volume can change at any time (see `--volume=`), so it's set
+        // whenever the context is used.
+        unsafe { al::alListenerf(al::AL_GAIN, options.volume) };
+        manager
     }
 }
 
+impl Drop for State {
+    fn drop(&mut self) {
+        if let Some((device, context)) = self.al_device_and_context {
+            unsafe {
+                al::alcMakeContextCurrent(std::ptr::null_mut());
+                al::alcDestroyContext(context);
+                al::alcCloseDevice(device);
+            }
+        }
+    }
+}
+
+/// Make touchHLE's internal OpenAL context current, for host code that plays
+/// audio. `AVAudioPlayer` shares the context with audio queues.
+pub fn make_al_context_current(env: &mut Environment) -> ContextManager {
+    State::get(&mut env.framework_state).make_al_context_current(&env.options)
+}
+
 #[must_use]
-struct ContextManager(*mut ALCcontext);
+pub struct ContextManager(*mut ALCcontext);
 impl ContextManager {
     pub fn make_active(new_context: *mut ALCcontext) -> ContextManager {
         let old_context = unsafe { al::alcGetCurrentContext() };
@@ -77,6 +111,7 @@ impl Drop for ContextManager {
 
 struct AudioQueueHostObject {
     format: AudioStreamBasicDescription,
+    /// An [AudioQueueInputCallback] for input queues.
     callback_proc: AudioQueueOutputCallback,
     callback_user_data: MutVoidPtr,
     /// Weak reference
@@ -89,9 +124,38 @@ struct AudioQueueHostObject {
     buffer_queue: VecDeque<AudioQueueBufferRef>,
     /// Tracks whether this audio queue has been started, so we can restart the
     /// OpenAL source if it automatically stops due to running out of data.
+    /// This is also `kAudioQueueProperty_IsRunning`.
     is_running: bool,
+    /// Set by `AudioQueuePause`. The queue is still running while paused.
+    is_paused: bool,
+    /// Set by a non-immediate `AudioQueueStop`: the queue stops by itself once
+    /// the buffers already enqueued have been played.
+    is_stopping: bool,
+    /// Set when `is_running` changes, so the listeners can be called from the
+    /// run loop rather than from within the function that changed it.
+    is_running_changed: bool,
+    is_running_listeners: Vec<(AudioQueuePropertyListenerProc, MutVoidPtr)>,
     al_source: Option<ALuint>,
     al_unused_buffers: Vec<ALuint>,
+    /// Set for queues created with `AudioQueueNewInput`.
+    input: Option<InputQueue>,
+    /// `kAudioQueueProperty_EnableLevelMetering`
+    level_metering_enabled: bool,
+    /// Levels of each channel in the most recent buffer played or recorded
+    /// while metering is enabled, see [update_level_meter].
+    level_meter: Vec<AudioQueueLevelMeterState>,
+}
+
+struct InputQueue {
+    /// The host microphone, which is open while the queue is running and not
+    /// paused. Always [None] if the microphone isn't allowed, and then the
+    /// queue records silence.
+    capture_device: Option<*mut ALCdevice>,
+    /// When recording last (re)started, and the number of frames recorded
+    /// since then. [None] while not recording.
+    recording_since: Option<(Instant, u64)>,
+    /// Total number of frames recorded, for time stamps.
+    frames_recorded: u64,
 }
 
 #[repr(C, packed)]
@@ -121,11 +185,41 @@ type AudioQueueBufferRef = MutPtr<AudioQueueBuffer>;
 /// (*void)(void *in_user_data, AudioQueueRef in_aq, AudioQueueBufferRef in_buf)
 type AudioQueueOutputCallback = GuestFunction;
 
+/// (*void)(void *in_user_data, AudioQueueRef in_aq, AudioQueueBufferRef in_buf,
+///         const AudioTimeStamp *in_start_time, u32 in_num_packet_descs,
+///         const AudioStreamPacketDescription *in_packet_descs)
+type AudioQueueInputCallback = GuestFunction;
+
+#[derive(Copy, Clone, Default)]
+#[repr(C, packed)]
+struct AudioQueueLevelMeterState {
+    average_power: f32,
+    peak_power: f32,
+}
+unsafe impl SafeRead for AudioQueueLevelMeterState {}
+
+/// (*void)(void *in_user_data, AudioQueueRef in_aq, AudioQueuePropertyID in_id)
+type AudioQueuePropertyListenerProc = GuestFunction;
+
 type AudioQueueParameterID = u32;
 const kAudioQueueParam_Volume: AudioQueueParameterID = 1;
 
 type AudioQueueParameterValue = f32;
 
+/// Usually a FourCC.
+type AudioQueuePropertyID = u32;
+const kAudioQueueProperty_IsRunning: AudioQueuePropertyID = fourcc(b"aqrn");
+const kAudioQueueProperty_StreamDescription: AudioQueuePropertyID = fourcc(b"aqft");
+const kAudioQueueProperty_EnableLevelMetering: AudioQueuePropertyID = fourcc(b"aqme");
+const kAudioQueueProperty_CurrentLevelMeter: AudioQueuePropertyID = fourcc(b"aqmv");
+const kAudioQueueProperty_CurrentLevelMeterDB: AudioQueuePropertyID = fourcc(b"aqmd");
+
+const kAudioQueueErr_InvalidBuffer: OSStatus = -66687;
+const kAudioQueueErr_BufferEmpty: OSStatus = -66686;
+const kAudioQueueErr_InvalidPropertySize: OSStatus = -66683;
+const kAudioQueueErr_BufferInQueue: OSStatus = -66679;
+const kAudioFormatUnsupportedDataFormatError: OSStatus = fourcc(b"fmt?") as _;
+
 fn AudioQueueNewOutput(
     env: &mut Environment,
     in_format: ConstPtr<AudioStreamBasicDescription>,
@@ -138,8 +232,97 @@ fn AudioQueueNewOutput(
 ) -> OSStatus {
     // reserved
     assert!(in_flags == 0);
-    // NULL not implemented
-    assert!(!in_callback_run_loop.is_null());
+
+    let format = env.mem.read(in_format);
+    let aq_ref = new_audio_queue(
+        env,
+        format,
+        in_callback_proc,
+        in_user_data,
+        in_callback_run_loop,
+        in_callback_run_loop_mode,
+        None,
+    );
+    env.mem.write(out_aq, aq_ref);
+
+    if !is_supported_audio_format(&format) {
+        log_dbg!("Warning: Audio queue {:?} will be ignored because its format is not yet supported: {:#?}", aq_ref, format);
+    }
+
+    log_dbg!(
+        "AudioQueueNewOutput() for format {:#?}, new audio queue handle: {:?}",
+        format,
+        aq_ref,
+    );
+
+    0 // success
+}
+
+fn AudioQueueNewInput(
+    env: &mut Environment,
+    in_format: ConstPtr<AudioStreamBasicDescription>,
+    in_callback_proc: AudioQueueInputCallback,
+    in_user_data: MutVoidPtr,
+    in_callback_run_loop: CFRunLoopRef,
+    in_callback_run_loop_mode: CFRunLoopMode,
+    in_flags: u32,
+    out_aq: MutPtr<AudioQueueRef>,
+) -> OSStatus {
+    // reserved
+    assert!(in_flags == 0);
+
+    let format = env.mem.read(in_format);
+    if !is_supported_input_format(&format) {
+        log!(
+            "Warning: AudioQueueNewInput() failed, format is not supported: {:#?}",
+            format
+        );
+        return kAudioFormatUnsupportedDataFormatError;
+    }
+
+    let input = InputQueue {
+        capture_device: None,
+        recording_since: None,
+        frames_recorded: 0,
+    };
+    let aq_ref = new_audio_queue(
+        env,
+        format,
+        in_callback_proc,
+        in_user_data,
+        in_callback_run_loop,
+        in_callback_run_loop_mode,
+        Some(input),
+    );
+    env.mem.write(out_aq, aq_ref);
+
+    log_dbg!(
+        "AudioQueueNewInput() for format {:#?}, new audio queue handle: {:?}",
+        format,
+        aq_ref,
+    );
+
+    0 // success
+}
+
+fn new_audio_queue(
+    env: &mut Environment,
+    format: AudioStreamBasicDescription,
+    in_callback_proc: GuestFunction,
+    in_user_data: MutVoidPtr,
+    in_callback_run_loop: CFRunLoopRef,
+    in_callback_run_loop_mode: CFRunLoopMode,
+    input: Option<InputQueue>,
+) -> AudioQueueRef {
+    // NULL means the callback is called on one of the audio queue's internal
+    // threads. The app can't tell the difference between that and the main
+    // thread's run loop, so long as the main thread's run loop keeps running,
+    // which it does in every app that uses UIApplicationMain().
+    let in_callback_run_loop = if in_callback_run_loop.is_null() {
+        msg_class![env; NSRunLoop mainRunLoop]
+    } else {
+        in_callback_run_loop
+    };
     // NULL is a synonym of kCFRunLoopCommonModes here
     assert!(
         in_callback_run_loop_mode.is_null() || {
@@ -148,8 +331,6 @@ fn AudioQueueNewOutput(
         }
     );
 
-    let format = env.mem.read(in_format);
-
     let host_object = AudioQueueHostObject {
         format,
         callback_proc: in_callback_proc,
@@ -159,29 +340,25 @@ fn AudioQueueNewOutput(
         buffers: Vec::new(),
         buffer_queue: VecDeque::new(),
         is_running: false,
+        is_paused: false,
+        is_stopping: false,
+        is_running_changed: false,
+        is_running_listeners: Vec::new(),
         al_source: None,
         al_unused_buffers: Vec::new(),
+        input,
+        level_metering_enabled: false,
+        level_meter: vec![Default::default(); format.channels_per_frame as usize],
     };
 
     let aq_ref = env.mem.alloc_and_write(OpaqueAudioQueue { _filler: 0 });
     State::get(&mut env.framework_state)
         .audio_queues
         .insert(aq_ref, host_object);
-    env.mem.write(out_aq, aq_ref);
 
     ns_run_loop::add_audio_queue(env, in_callback_run_loop, aq_ref);
 
-    if !is_supported_audio_format(&format) {
-        log_dbg!("Warning: Audio queue {:?} will be ignored because its format is not yet supported: {:#?}", aq_ref, format);
-    }
-
-    log_dbg!(
-        "AudioQueueNewOutput() for format {:#?}, new audio queue handle: {:?}",
-        format,
-        aq_ref,
-    );
-
-    0 // success
+    aq_ref
 }
 
 fn AudioQueueSetParameter(
@@ -197,7 +374,7 @@ fn AudioQueueSetParameter(
 
     host_object.volume = in_value;
     if let Some(al_source) = host_object.al_source {
-        let _context_manager = state.make_al_context_current();
+        let _context_manager = state.make_al_context_current(&env.options);
         unsafe {
             al::alSourcef(al_source, al::AL_MAX_GAIN, in_value);
             assert!(al::alGetError() == 0);
@@ -207,6 +384,219 @@ fn AudioQueueSetParameter(
     0 // success
 }
 
+fn AudioQueueGetParameter(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    in_param_id: AudioQueueParameterID,
+    out_value: MutPtr<AudioQueueParameterValue>,
+) -> OSStatus {
+    assert!(in_param_id == kAudioQueueParam_Volume); // others unimplemented
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+
+    env.mem.write(out_value, host_object.volume);
+
+    0 // success
+}
+
+fn property_size(host_object: &AudioQueueHostObject, in_id: AudioQueuePropertyID) -> GuestUSize {
+    match in_id {
+        kAudioQueueProperty_IsRunning | kAudioQueueProperty_EnableLevelMetering => {
+            guest_size_of::<u32>()
+        }
+        kAudioQueueProperty_StreamDescription => guest_size_of::<AudioStreamBasicDescription>(),
+        kAudioQueueProperty_CurrentLevelMeter | kAudioQueueProperty_CurrentLevelMeterDB => {
+            guest_size_of::<AudioQueueLevelMeterState>() * host_object.format.channels_per_frame
+        }
+        _ => unimplemented!("Unimplemented property ID: {}", debug_fourcc(in_id)),
+    }
+}
+
+fn AudioQueueGetPropertySize(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    in_id: AudioQueuePropertyID,
+    out_data_size: MutPtr<u32>,
+) -> OSStatus {
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get(&in_aq)
+        .unwrap();
+    env.mem
+        .write(out_data_size, property_size(host_object, in_id));
+    0 // success
+}
+
+fn AudioQueueGetProperty(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    in_id: AudioQueuePropertyID,
+    out_data: MutVoidPtr,
+    io_data_size: MutPtr<u32>,
+) -> OSStatus {
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+
+    let required_size = property_size(host_object, in_id);
+    if env.mem.read(io_data_size) < required_size {
+        log!("Warning: AudioQueueGetProperty() failed");
+        return kAudioQueueErr_InvalidPropertySize;
+    }
+
+    match in_id {
+        kAudioQueueProperty_IsRunning => {
+            env.mem
+                .write(out_data.cast(), u32::from(host_object.is_running));
+        }
+        kAudioQueueProperty_StreamDescription => {
+            env.mem.write(out_data.cast(), host_object.format);
+        }
+        kAudioQueueProperty_EnableLevelMetering => {
+            env.mem.write(
+                out_data.cast(),
+                u32::from(host_object.level_metering_enabled),
+            );
+        }
+        kAudioQueueProperty_CurrentLevelMeter | kAudioQueueProperty_CurrentLevelMeterDB => {
+            let out_levels = out_data.cast::<AudioQueueLevelMeterState>();
+            for (i, &level) in host_object.level_meter.iter().enumerate() {
+                let level = if in_id == kAudioQueueProperty_CurrentLevelMeterDB {
+                    let AudioQueueLevelMeterState {
+                        average_power,
+                        peak_power,
+                    } = level;
+                    AudioQueueLevelMeterState {
+                        average_power: power_to_db(average_power),
+                        peak_power: power_to_db(peak_power),
+                    }
+                } else {
+                    level
+                };
+                env.mem.write(out_levels + i.try_into().unwrap(), level);
+            }
+        }
+        _ => unreachable!(),
+    }
+    env.mem.write(io_data_size, required_size);
+
+    0 // success
+}
+
+fn AudioQueueSetProperty(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    in_id: AudioQueuePropertyID,
+    in_data: ConstVoidPtr,
+    in_data_size: u32,
+) -> OSStatus {
+    // others unimplemented
+    assert!(in_id == kAudioQueueProperty_EnableLevelMetering);
+
+    if in_data_size != guest_size_of::<u32>() {
+        log!("Warning: AudioQueueSetProperty() failed");
+        return kAudioQueueErr_InvalidPropertySize;
+    }
+    let enabled: u32 = env.mem.read(in_data.cast());
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+    host_object.level_metering_enabled = enabled != 0;
+    if !host_object.level_metering_enabled {
+        host_object.level_meter.fill(Default::default());
+    }
+
+    0 // success
+}
+
+/// Convert a power level in the range [0, 1] to decibels. Silence is reported
+/// as -120 dB rather than negative infinity.
+fn power_to_db(power: f32) -> f32 {
+    (20.0 * power.log10()).max(-120.0)
+}
+
+/// Measure the levels of some interleaved 16-bit samples, if metering is
+/// enabled.
+fn update_level_meter(host_object: &mut AudioQueueHostObject, samples: &[i16]) {
+    if !host_object.level_metering_enabled {
+        return;
+    }
+    let channels = host_object.level_meter.len();
+    for (channel, level) in host_object.level_meter.iter_mut().enumerate() {
+        let mut sum_of_squares = 0.0;
+        let mut peak = 0.0f32;
+        let mut count = 0;
+        for &sample in samples.iter().skip(channel).step_by(channels) {
+            let sample = f32::from(sample) / 32768.0;
+            sum_of_squares += sample * sample;
+            peak = peak.max(sample.abs());
+            count += 1;
+        }
+        let average = if count == 0 {
+            0.0
+        } else {
+            (sum_of_squares / count as f32).sqrt()
+        };
+        *level = AudioQueueLevelMeterState {
+            average_power: average,
+            peak_power: peak,
+        };
+    }
+}
+
+fn AudioQueueAddPropertyListener(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    in_id: AudioQueuePropertyID,
+    in_proc: AudioQueuePropertyListenerProc,
+    in_user_data: MutVoidPtr,
+) -> OSStatus {
+    // others unimplemented
+    assert!(in_id == kAudioQueueProperty_IsRunning);
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+
+    host_object
+        .is_running_listeners
+        .push((in_proc, in_user_data));
+
+    0 // success
+}
+
+fn AudioQueueRemovePropertyListener(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    in_id: AudioQueuePropertyID,
+    in_proc: AudioQueuePropertyListenerProc,
+    in_user_data: MutVoidPtr,
+) -> OSStatus {
+    // others unimplemented
+    assert!(in_id == kAudioQueueProperty_IsRunning);
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+
+    host_object
+        .is_running_listeners
+        .retain(|&(proc_, user_data)| {
+            proc_.addr_with_thumb_bit() != in_proc.addr_with_thumb_bit()
+                || user_data != in_user_data
+        });
+
+    0 // success
+}
+
 fn AudioQueueAllocateBuffer(
     env: &mut Environment,
     in_aq: AudioQueueRef,
@@ -249,8 +639,18 @@ fn AudioQueueEnqueueBuffer(
         .get_mut(&in_aq)
         .unwrap();
 
-    // TODO: Return error if buffer doesn't belong to audio queue
-    assert!(host_object.buffers.contains(&in_buffer));
+    if !host_object.buffers.contains(&in_buffer) {
+        log!(
+            "Warning: AudioQueueEnqueueBuffer() with buffer {:?} that doesn't belong to queue {:?}",
+            in_buffer,
+            in_aq
+        );
+        return kAudioQueueErr_InvalidBuffer;
+    }
+    // Buffers for input queues are empty until something is recorded.
+    if host_object.input.is_none() && env.mem.read(in_buffer).audio_data_byte_size == 0 {
+        return kAudioQueueErr_BufferEmpty;
+    }
 
     host_object.buffer_queue.push_back(in_buffer);
     log_dbg!("New buffer enqueued: {:?}", in_buffer);
@@ -258,6 +658,31 @@ fn AudioQueueEnqueueBuffer(
     0 // success
 }
 
+fn AudioQueueFreeBuffer(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    in_buffer: AudioQueueBufferRef,
+) -> OSStatus {
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+
+    if host_object.buffer_queue.contains(&in_buffer) {
+        return kAudioQueueErr_BufferInQueue;
+    }
+    let Some(idx) = host_object.buffers.iter().position(|&b| b == in_buffer) else {
+        return kAudioQueueErr_InvalidBuffer;
+    };
+    host_object.buffers.swap_remove(idx);
+
+    let buffer = env.mem.read(in_buffer);
+    env.mem.free(buffer.audio_data);
+    env.mem.free(in_buffer.cast());
+
+    0 // success
+}
+
 /// Check if the format of an audio queue is one we currently support.
 /// If not, we should skip trying to play it rather than crash.
 fn is_supported_audio_format(format: &AudioStreamBasicDescription) -> bool {
@@ -269,10 +694,7 @@ fn is_supported_audio_format(format: &AudioStreamBasicDescription) -> bool {
         ..
     } = format;
     match format_id {
-        kAudioFormatAppleIMA4 => {
-            // TODO: stereo (requires interleaving)
-            channels_per_frame == 1
-        }
+        kAudioFormatAppleIMA4 => channels_per_frame == 1 || channels_per_frame == 2,
         kAudioFormatLinearPCM => {
             // TODO: support more PCM formats
             (channels_per_frame == 1 || channels_per_frame == 2)
@@ -285,6 +707,33 @@ fn is_supported_audio_format(format: &AudioStreamBasicDescription) -> bool {
     }
 }
 
+/// Check if the format of an input queue is one we support. OpenAL Soft can
+/// record in other formats, but 16-bit is what apps use.
+fn is_supported_input_format(format: &AudioStreamBasicDescription) -> bool {
+    let &AudioStreamBasicDescription {
+        sample_rate,
+        format_id,
+        format_flags,
+        frames_per_packet,
+        bytes_per_frame,
+        channels_per_frame,
+        bits_per_channel,
+        ..
+    } = format;
+    format_id == kAudioFormatLinearPCM
+        && (1.0..=192_000.0).contains(&sample_rate)
+        && frames_per_packet == 1
+        && (channels_per_frame == 1 || channels_per_frame == 2)
+        && bits_per_channel == 16
+        && bytes_per_frame == 2 * channels_per_frame
+        && (format_flags & kAudioFormatFlagIsSignedInteger) != 0
+        && (format_flags
+            & (kAudioFormatFlagIsBigEndian
+                | kAudioFormatFlagIsFloat
+                | kAudioFormatFlagIsNonInterleaved))
+            == 0
+}
+
 /// Decode an [AudioQueueBuffer]'s content to raw PCM suitable for an OpenAL
 /// buffer.
 fn decode_buffer(
@@ -298,17 +747,17 @@ fn decode_buffer(
 
     match format.format_id {
         kAudioFormatAppleIMA4 => {
-            assert!(data_slice.len() % 34 == 0);
-            let mut out_pcm = Vec::<u8>::with_capacity((data_slice.len() / 34) * 64 * 2);
-
-            for packet in data_slice.chunks(34) {
-                let pcm_packet: [i16; 64] = decode_ima4(packet.try_into().unwrap());
-                let pcm_bytes: &[u8] =
-                    unsafe { std::slice::from_raw_parts(pcm_packet.as_ptr() as *const u8, 128) };
-                out_pcm.extend_from_slice(pcm_bytes);
-            }
-
-            (al::AL_FORMAT_MONO16, format.sample_rate as ALsizei, out_pcm)
+            let channels = format.channels_per_frame as usize;
+            let mut out_pcm = Vec::<i16>::with_capacity((data_slice.len() / 34) * 64);
+            decode_ima4_interleaved(data_slice, channels, &mut out_pcm);
+            let out_pcm: Vec<u8> = out_pcm.iter().flat_map(|s| s.to_le_bytes()).collect();
+
+            let f = if channels == 1 {
+                al::AL_FORMAT_MONO16
+            } else {
+                al::AL_FORMAT_STEREO16
+            };
+            (f, format.sample_rate as ALsizei, out_pcm)
         }
         kAudioFormatLinearPCM => {
             let f = match (format.channels_per_frame, format.bits_per_channel) {
@@ -333,10 +782,11 @@ fn prime_audio_queue(
 ) -> ContextManager {
     let state = State::get(&mut env.framework_state);
 
-    let context_manager = context_manager.unwrap_or_else(|| state.make_al_context_current());
+    let context_manager =
+        context_manager.unwrap_or_else(|| state.make_al_context_current(&env.options));
     let host_object = state.audio_queues.get_mut(&in_aq).unwrap();
 
-    if !is_supported_audio_format(&host_object.format) {
+    if host_object.input.is_some() || !is_supported_audio_format(&host_object.format) {
         return context_manager;
     }
 
@@ -347,6 +797,7 @@ fn prime_audio_queue(
             al::alSourcef(al_source, al::AL_MAX_GAIN, host_object.volume);
             assert!(al::alGetError() == 0);
         };
+        mixer::configure_source(al_source, &env.options);
         host_object.al_source = Some(al_source);
     }
     let al_source = host_object.al_source.unwrap();
@@ -392,6 +843,21 @@ fn prime_audio_queue(
 
         let (al_format, al_frequency, data) =
             decode_buffer(&env.mem, &host_object.format, &next_buffer);
+        // This is measured a little before the buffer is heard, but that's
+        // close enough.
+        if host_object.level_metering_enabled {
+            let samples: Vec<i16> = match al_format {
+                al::AL_FORMAT_MONO8 | al::AL_FORMAT_STEREO8 => data
+                    .iter()
+                    .map(|&sample| (i16::from(sample) - 128) << 8)
+                    .collect(),
+                _ => data
+                    .chunks_exact(2)
+                    .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+                    .collect(),
+            };
+            update_level_meter(host_object, &samples);
+        }
         unsafe {
             al::alBufferData(
                 next_al_buffer,
@@ -408,7 +874,9 @@ fn prime_audio_queue(
     context_manager
 }
 
-fn unqueue_buffers<F: FnMut(ALuint)>(al_source: ALuint, mut callback: F) {
+/// Unqueue the buffers an OpenAL source has finished playing, calling
+/// `callback` with each one. Also used by the RemoteIO audio unit.
+pub fn unqueue_buffers<F: FnMut(ALuint)>(al_source: ALuint, mut callback: F) {
     loop {
         let mut al_buffers_processed = 0;
         unsafe {
@@ -433,15 +901,78 @@ fn unqueue_buffers<F: FnMut(ALuint)>(al_source: ALuint, mut callback: F) {
     }
 }
 
+/// Stop an audio queue's OpenAL source and forget all enqueued buffers, which
+/// the app then owns again.
+fn reset_audio_queue(state: &mut State, options: &Options, in_aq: AudioQueueRef) {
+    let _context_manager = state.make_al_context_current(options);
+
+    let host_object = state.audio_queues.get_mut(&in_aq).unwrap();
+    host_object.buffer_queue.clear();
+
+    let Some(al_source) = host_object.al_source else {
+        return;
+    };
+    unsafe {
+        al::alSourceStop(al_source);
+        assert!(al::alGetError() == 0);
+    }
+    // Stopping a source marks all its buffers as processed.
+    unqueue_buffers(al_source, |al_buffer| {
+        host_object.al_unused_buffers.push(al_buffer)
+    });
+}
+
+fn set_is_running(host_object: &mut AudioQueueHostObject, is_running: bool) {
+    if host_object.is_running != is_running {
+        host_object.is_running = is_running;
+        host_object.is_running_changed = true;
+    }
+}
+
+/// Call the `kAudioQueueProperty_IsRunning` listeners if the property changed.
+fn notify_is_running_listeners(env: &mut Environment, in_aq: AudioQueueRef) {
+    let state = State::get(&mut env.framework_state);
+    let Some(host_object) = state.audio_queues.get_mut(&in_aq) else {
+        return;
+    };
+    if !std::mem::take(&mut host_object.is_running_changed) {
+        return;
+    }
+    let listeners = host_object.is_running_listeners.clone();
+    for (proc_, user_data) in listeners {
+        log_dbg!(
+            "Calling IsRunning listener {:?} with user data {:?} for queue {:?}",
+            proc_,
+            user_data,
+            in_aq
+        );
+        let () = proc_.call_from_host(env, (user_data, in_aq, kAudioQueueProperty_IsRunning));
+    }
+}
+
 /// For use by `NSRunLoop`: check the status of an audio queue, recycle buffers,
 /// call callbacks, push new buffers etc.
 pub fn handle_audio_queue(env: &mut Environment, in_aq: AudioQueueRef) {
+    update_audio_queue(env, in_aq);
+    notify_is_running_listeners(env, in_aq);
+}
+
+fn update_audio_queue(env: &mut Environment, in_aq: AudioQueueRef) {
     // Collect used buffers and call the user callback so the app can provide
     // new buffers.
 
     let state = State::get(&mut env.framework_state);
 
-    let context_manager = state.make_al_context_current();
+    // The queue may have been disposed of by a callback for another queue.
+    let Some(host_object) = state.audio_queues.get(&in_aq) else {
+        return;
+    };
+    if host_object.input.is_some() {
+        update_input_queue(env, in_aq);
+        return;
+    }
+
+    let context_manager = state.make_al_context_current(&env.options);
 
     let host_object = state.audio_queues.get_mut(&in_aq).unwrap();
     let Some(al_source) = host_object.al_source else {
@@ -462,7 +993,6 @@ pub fn handle_audio_queue(env: &mut Environment, in_aq: AudioQueueRef) {
     let &mut AudioQueueHostObject {
         callback_proc,
         callback_user_data,
-        is_running,
         ..
     } = host_object;
 
@@ -476,13 +1006,33 @@ pub fn handle_audio_queue(env: &mut Environment, in_aq: AudioQueueRef) {
         );
 
         let () = callback_proc.call_from_host(env, (callback_user_data, in_aq, buffer_ref));
+
+        // The callback may dispose of the queue.
+        if !State::get(&mut env.framework_state)
+            .audio_queues
+            .contains_key(&in_aq)
+        {
+            return;
+        }
     }
 
     // Push new buffers etc.
 
     let _context_manager = prime_audio_queue(env, in_aq, Some(context_manager));
 
-    if is_running {
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+
+    if host_object.is_stopping && host_object.buffer_queue.is_empty() {
+        log_dbg!("Queue {:?} finished playing and has stopped", in_aq);
+        host_object.is_stopping = false;
+        host_object.is_paused = false;
+        set_is_running(host_object, false);
+    }
+
+    if host_object.is_running && !host_object.is_paused {
         unsafe {
             let mut al_source_state = 0;
             al::alGetSourcei(al_source, al::AL_SOURCE_STATE, &mut al_source_state);
@@ -500,6 +1050,171 @@ pub fn handle_audio_queue(env: &mut Environment, in_aq: AudioQueueRef) {
     }
 }
 
+/// Open the microphone, if that's allowed, and start recording.
+fn start_input(env: &mut Environment, in_aq: AudioQueueRef) {
+    let allow_microphone = env.options.allow_microphone;
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+    let format = host_object.format;
+    let input = host_object.input.as_mut().unwrap();
+    if input.recording_since.is_some() {
+        return;
+    }
+    input.recording_since = Some((Instant::now(), 0));
+
+    if !allow_microphone {
+        log!("The app is recording audio. It will only get silence, because the microphone isn't allowed. Use the --allow-microphone option to allow it.");
+        return;
+    }
+    let al_format = if format.channels_per_frame == 1 {
+        al::AL_FORMAT_MONO16
+    } else {
+        al::AL_FORMAT_STEREO16
+    };
+    // Buffer up to a second, in case the run loop is slow.
+    let sample_rate = format.sample_rate as u32;
+    let device = unsafe {
+        al::alcCaptureOpenDevice(
+            std::ptr::null(),
+            sample_rate,
+            al_format,
+            sample_rate.try_into().unwrap(),
+        )
+    };
+    if device.is_null() {
+        log!("Warning: Couldn't open the host's microphone. The app will get silence.");
+        return;
+    }
+    unsafe { al::alcCaptureStart(device) };
+    log_dbg!(
+        "Opened capture device {:?} for audio queue {:?}",
+        device,
+        in_aq
+    );
+    input.capture_device = Some(device);
+}
+
+/// Stop recording and close the microphone, if it's open.
+fn stop_input(host_object: &mut AudioQueueHostObject) {
+    let input = host_object.input.as_mut().unwrap();
+    input.recording_since = None;
+    if let Some(device) = input.capture_device.take() {
+        unsafe {
+            al::alcCaptureStop(device);
+            al::alcCaptureCloseDevice(device);
+        }
+    }
+}
+
+/// Fill enqueued buffers of an input queue with recorded audio, if enough has
+/// been recorded, and call the callback with each one.
+fn update_input_queue(env: &mut Environment, in_aq: AudioQueueRef) {
+    loop {
+        // The callback may dispose of the queue.
+        let Some(host_object) = State::get(&mut env.framework_state)
+            .audio_queues
+            .get_mut(&in_aq)
+        else {
+            return;
+        };
+        if !host_object.is_running || host_object.is_paused {
+            return;
+        }
+        let Some(&buffer_ref) = host_object.buffer_queue.front() else {
+            return;
+        };
+        let format = host_object.format;
+        let buffer = env.mem.read(buffer_ref);
+        let frame_count = buffer.audio_data_bytes_capacity / format.bytes_per_frame;
+        if frame_count == 0 {
+            return;
+        }
+
+        let input = host_object.input.as_mut().unwrap();
+        let Some((start_time, frames_since_start)) = input.recording_since.as_mut() else {
+            return;
+        };
+        let frames_available = if let Some(device) = input.capture_device {
+            let mut samples = 0;
+            unsafe { al::alcGetIntegerv(device, al::ALC_CAPTURE_SAMPLES, 1, &mut samples) };
+            u64::try_from(samples).unwrap()
+        } else {
+            // Silence is "recorded" in real time.
+            let elapsed = start_time.elapsed().as_secs_f64();
+            ((elapsed * format.sample_rate) as u64).saturating_sub(*frames_since_start)
+        };
+        if frames_available < u64::from(frame_count) {
+            return;
+        }
+
+        let mut samples = vec![0i16; (frame_count * format.channels_per_frame) as usize];
+        if let Some(device) = input.capture_device {
+            unsafe {
+                al::alcCaptureSamples(
+                    device,
+                    samples.as_mut_ptr().cast(),
+                    frame_count.try_into().unwrap(),
+                )
+            };
+        }
+        *frames_since_start += u64::from(frame_count);
+        let sample_time = input.frames_recorded as f64;
+        input.frames_recorded += u64::from(frame_count);
+
+        update_level_meter(host_object, &samples);
+        host_object.buffer_queue.pop_front();
+        let &mut AudioQueueHostObject {
+            callback_proc,
+            callback_user_data,
+            ..
+        } = host_object;
+
+        let byte_count = frame_count * format.bytes_per_frame;
+        let out_bytes = env.mem.bytes_at_mut(buffer.audio_data.cast(), byte_count);
+        for (out, sample) in out_bytes.chunks_exact_mut(2).zip(samples) {
+            out.copy_from_slice(&sample.to_le_bytes());
+        }
+        env.mem.write(
+            buffer_ref,
+            AudioQueueBuffer {
+                audio_data_byte_size: byte_count,
+                ..buffer
+            },
+        );
+
+        let host_time = env.clock.uptime().as_nanos().try_into().unwrap();
+        let time_stamp = env.mem.alloc_and_write(AudioTimeStamp {
+            sample_time,
+            host_time,
+            rate_scalar: 1.0,
+            flags: kAudioTimeStampSampleTimeValid | kAudioTimeStampHostTimeValid,
+            ..Default::default()
+        });
+        log_dbg!(
+            "Recorded {} frames into buffer {:?} for queue {:?}. Calling callback {:?} with user data {:?}.",
+            frame_count,
+            buffer_ref,
+            in_aq,
+            callback_proc,
+            callback_user_data
+        );
+        let () = callback_proc.call_from_host(
+            env,
+            (
+                callback_user_data,
+                in_aq,
+                buffer_ref,
+                time_stamp.cast_const(),
+                0u32,
+                ConstVoidPtr::null(),
+            ),
+        );
+        env.mem.free(time_stamp.cast());
+    }
+}
+
 fn AudioQueuePrime(
     env: &mut Environment,
     in_aq: AudioQueueRef,
@@ -525,9 +1240,13 @@ fn AudioQueueStart(
         .get_mut(&in_aq)
         .unwrap();
 
-    host_object.is_running = true;
+    set_is_running(host_object, true);
+    host_object.is_paused = false;
+    host_object.is_stopping = false;
 
-    if is_supported_audio_format(&host_object.format) {
+    if host_object.input.is_some() {
+        start_input(env, in_aq);
+    } else if is_supported_audio_format(&host_object.format) {
         let al_source = host_object.al_source.unwrap();
         unsafe { al::alSourcePlay(al_source) };
         assert!(unsafe { al::alGetError() } == 0);
@@ -536,30 +1255,82 @@ fn AudioQueueStart(
     0 // success
 }
 
-fn AudioQueueStop(env: &mut Environment, in_aq: AudioQueueRef, in_immediate: bool) -> OSStatus {
+fn AudioQueuePause(env: &mut Environment, in_aq: AudioQueueRef) -> OSStatus {
     let state = State::get(&mut env.framework_state);
 
-    let _context_manager = state.make_al_context_current();
+    let _context_manager = state.make_al_context_current(&env.options);
+
+    let host_object = state.audio_queues.get_mut(&in_aq).unwrap();
+    host_object.is_paused = true;
+
+    if host_object.input.is_some() {
+        stop_input(host_object);
+    }
+    if let Some(al_source) = host_object.al_source {
+        unsafe { al::alSourcePause(al_source) };
+        assert!(unsafe { al::alGetError() } == 0);
+    }
+
+    0 // success
+}
+
+fn AudioQueueFlush(env: &mut Environment, in_aq: AudioQueueRef) -> OSStatus {
+    // This makes sure the last buffer is played in full before a non-immediate
+    // stop, rather than some of it being kept in the decoder. All of each
+    // buffer is always decoded here, so there's nothing to do.
+    assert!(State::get(&mut env.framework_state)
+        .audio_queues
+        .contains_key(&in_aq));
+    0 // success
+}
+
+fn AudioQueueStop(env: &mut Environment, in_aq: AudioQueueRef, in_immediate: bool) -> OSStatus {
+    let state = State::get(&mut env.framework_state);
 
     // This happens in Super Monkey Ball. TODO: figure out why.
-    let Some(mut host_object) = state.audio_queues.get_mut(&in_aq) else {
+    if !state.audio_queues.contains_key(&in_aq) {
         log!("Tolerating stopping of unknown audio queue {:?}", in_aq);
         return 0; // success
-    };
-    host_object.is_running = false;
+    }
 
-    if in_immediate {
-        if let Some(al_source) = host_object.al_source {
-            unsafe { al::alSourceStop(al_source) };
-            assert!(unsafe { al::alGetError() } == 0);
+    // Recording isn't buffered, so there's nothing to wait for when an input
+    // queue stops.
+    let is_input = state.audio_queues[&in_aq].input.is_some();
+    if in_immediate || is_input {
+        reset_audio_queue(state, &env.options, in_aq);
+        let host_object = state.audio_queues.get_mut(&in_aq).unwrap();
+        if is_input {
+            stop_input(host_object);
+        }
+        host_object.is_paused = false;
+        host_object.is_stopping = false;
+        set_is_running(host_object, false);
+    } else {
+        // The queue stops once the enqueued buffers have been played, see
+        // handle_audio_queue().
+        let host_object = state.audio_queues.get_mut(&in_aq).unwrap();
+        if host_object.is_running {
+            host_object.is_stopping = true;
         }
     }
 
     0 // success
 }
 
+fn AudioQueueReset(env: &mut Environment, in_aq: AudioQueueRef) -> OSStatus {
+    let state = State::get(&mut env.framework_state);
+    reset_audio_queue(state, &env.options, in_aq);
+    state.audio_queues.get_mut(&in_aq).unwrap().is_stopping = false;
+    0 // success
+}
+
 fn AudioQueueDispose(env: &mut Environment, in_aq: AudioQueueRef, in_immediate: bool) -> OSStatus {
-    assert!(in_immediate); // TODO
+    if !in_immediate {
+        // The queue should be disposed of once the enqueued buffers have been
+        // played, but apps only do this once they no longer want any sound
+        // from it, so this is close enough.
+        log_dbg!("TODO: Non-immediate disposal of audio queue {:?}", in_aq);
+    }
 
     let state = State::get(&mut env.framework_state);
 
@@ -571,6 +1342,10 @@ fn AudioQueueDispose(env: &mut Environment, in_aq: AudioQueueRef, in_immediate:
 
     log_dbg!("Disposing of audio queue {:?}", in_aq);
 
+    if host_object.input.is_some() {
+        stop_input(&mut host_object);
+    }
+
     env.mem.free(in_aq.cast());
 
     for buffer_ptr in host_object.buffers {
@@ -580,7 +1355,7 @@ fn AudioQueueDispose(env: &mut Environment, in_aq: AudioQueueRef, in_immediate:
     }
 
     if let Some(al_source) = host_object.al_source {
-        let _context_manager = state.make_al_context_current();
+        let _context_manager = state.make_al_context_current(&env.options);
 
         unsafe {
             al::alSourceStop(al_source);
@@ -592,6 +1367,7 @@ fn AudioQueueDispose(env: &mut Environment, in_aq: AudioQueueRef, in_immediate:
         });
 
         unsafe {
+            al::alDeleteSources(1, &al_source);
             al::alDeleteBuffers(
                 host_object.al_unused_buffers.len().try_into().unwrap(),
                 host_object.al_unused_buffers.as_ptr(),
@@ -607,11 +1383,22 @@ fn AudioQueueDispose(env: &mut Environment, in_aq: AudioQueueRef, in_immediate:
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(AudioQueueNewOutput(_, _, _, _, _, _, _)),
+    export_c_func!(AudioQueueNewInput(_, _, _, _, _, _, _)),
     export_c_func!(AudioQueueSetParameter(_, _, _)),
+    export_c_func!(AudioQueueGetParameter(_, _, _)),
+    export_c_func!(AudioQueueGetPropertySize(_, _, _)),
+    export_c_func!(AudioQueueGetProperty(_, _, _, _)),
+    export_c_func!(AudioQueueSetProperty(_, _, _, _)),
+    export_c_func!(AudioQueueAddPropertyListener(_, _, _, _)),
+    export_c_func!(AudioQueueRemovePropertyListener(_, _, _, _)),
     export_c_func!(AudioQueueAllocateBuffer(_, _, _)),
     export_c_func!(AudioQueueEnqueueBuffer(_, _, _, _)),
+    export_c_func!(AudioQueueFreeBuffer(_, _)),
     export_c_func!(AudioQueuePrime(_, _, _)),
     export_c_func!(AudioQueueStart(_, _)),
+    export_c_func!(AudioQueuePause(_)),
+    export_c_func!(AudioQueueFlush(_)),
     export_c_func!(AudioQueueStop(_, _)),
+    export_c_func!(AudioQueueReset(_)),
     export_c_func!(AudioQueueDispose(_, _)),
 ];
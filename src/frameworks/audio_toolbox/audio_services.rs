@@ -0,0 +1,436 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `AudioServices.h` (System Sound Services)
+//!
+//! Apps use this for short sound effects and to vibrate the device. Sounds are
+//! played with OpenAL on touchHLE's internal context, and vibration is mapped
+//! to game controller rumble, see [crate::window::Vibration].
+
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::audio; // Keep this module namespaced to avoid confusion
+use crate::audio::openal as al;
+use crate::audio::openal::al_types::*;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::audio_toolbox::audio_queue::make_al_context_current;
+use crate::frameworks::core_audio_types::{debug_fourcc, fourcc};
+use crate::frameworks::core_foundation::cf_run_loop::{CFRunLoopMode, CFRunLoopRef};
+use crate::frameworks::core_foundation::cf_url::CFURLRef;
+use crate::frameworks::foundation::ns_url::to_rust_path;
+use crate::frameworks::mac_types::OSStatus;
+use crate::mem::{guest_size_of, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr};
+use crate::window::Vibration;
+use crate::Environment;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+pub struct State {
+    sounds: HashMap<SystemSoundID, SystemSound>,
+    /// Sounds that have been started and whose completion hasn't been handled
+    /// yet.
+    playing: Vec<SystemSoundID>,
+    /// When the current vibration ends, if the device is vibrating.
+    vibration_end: Option<Instant>,
+    /// The app's vibration settings. Loaded on first use.
+    vibration: Option<Vibration>,
+    completions: HashMap<SystemSoundID, (AudioServicesSystemSoundCompletionProc, MutVoidPtr)>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.audio_toolbox.audio_services
+    }
+}
+
+struct SystemSound {
+    al_source: ALuint,
+    al_buffer: ALuint,
+    is_ui_sound: u32,
+    complete_playback_if_app_dies: u32,
+}
+
+pub type SystemSoundID = u32;
+const kSystemSoundID_Vibrate: SystemSoundID = 0x00000FFF;
+/// IDs for sounds created by the app seem to start here on a real device.
+const FIRST_SYSTEM_SOUND_ID: SystemSoundID = 0x00001000;
+
+/// (*void)(SystemSoundID ssID, void *clientData)
+type AudioServicesSystemSoundCompletionProc = GuestFunction;
+
+const kAudioServicesNoError: OSStatus = 0;
+const kAudioServicesUnsupportedPropertyError: OSStatus = fourcc(b"pty?") as _;
+const kAudioServicesBadPropertySizeError: OSStatus = fourcc(b"!siz") as _;
+const kAudioServicesBadSpecifierSizeError: OSStatus = fourcc(b"!spc") as _;
+const kAudioServicesSystemSoundUnspecifiedError: OSStatus = -1500;
+
+/// Usually a FourCC.
+type AudioServicesPropertyID = u32;
+const kAudioServicesPropertyIsUISound: AudioServicesPropertyID = fourcc(b"isui");
+const kAudioServicesPropertyCompletePlaybackIfAppDies: AudioServicesPropertyID = fourcc(b"ifdi");
+
+fn AudioServicesCreateSystemSoundID(
+    env: &mut Environment,
+    in_file_url: CFURLRef,
+    out_system_sound_id: MutPtr<SystemSoundID>,
+) -> OSStatus {
+    let path = to_rust_path(env, in_file_url);
+    let Ok(mut audio_file) = audio::AudioFile::open_for_reading(&path, &env.fs) else {
+        log!(
+            "Warning: AudioServicesCreateSystemSoundID() for path {:?} failed",
+            path
+        );
+        return kAudioServicesSystemSoundUnspecifiedError;
+    };
+    let audio::AudioDescription {
+        sample_rate,
+        channels_per_frame,
+        ..
+    } = audio_file.audio_description();
+    let format = match channels_per_frame {
+        1 => al::AL_FORMAT_MONO16,
+        2 => al::AL_FORMAT_STEREO16,
+        _ => {
+            log!(
+                "Warning: AudioServicesCreateSystemSoundID() for path {:?} failed, {} channels are not supported",
+                path,
+                channels_per_frame
+            );
+            return kAudioServicesSystemSoundUnspecifiedError;
+        }
+    };
+    let frame_count: usize = audio_file.frame_count().try_into().unwrap();
+    let mut samples = vec![0i16; frame_count * channels_per_frame as usize];
+    let Ok(frames_read) = audio_file.read_pcm_frames(0, &mut samples) else {
+        log!(
+            "Warning: AudioServicesCreateSystemSoundID() for path {:?} failed to decode the file",
+            path
+        );
+        return kAudioServicesSystemSoundUnspecifiedError;
+    };
+    samples.truncate(frames_read * channels_per_frame as usize);
+
+    let (al_source, al_buffer) = {
+        let _context_manager = make_al_context_current(env);
+        let mut al_source = 0;
+        let mut al_buffer = 0;
+        unsafe {
+            al::alGenSources(1, &mut al_source);
+            al::alGenBuffers(1, &mut al_buffer);
+            al::alBufferData(
+                al_buffer,
+                format,
+                samples.as_ptr() as *const ALvoid,
+                (samples.len() * 2).try_into().unwrap(),
+                sample_rate as ALsizei,
+            );
+            al::alSourcei(al_source, al::AL_BUFFER, al_buffer.try_into().unwrap());
+            assert!(al::alGetError() == al::AL_NO_ERROR);
+        }
+        audio::mixer::configure_source(al_source, &env.options);
+        (al_source, al_buffer)
+    };
+
+    let state = State::get(&mut env.framework_state);
+    let id = (FIRST_SYSTEM_SOUND_ID..)
+        .find(|id| !state.sounds.contains_key(id))
+        .unwrap();
+    state.sounds.insert(
+        id,
+        SystemSound {
+            al_source,
+            al_buffer,
+            is_ui_sound: 1,
+            complete_playback_if_app_dies: 0,
+        },
+    );
+    env.mem.write(out_system_sound_id, id);
+    log_dbg!(
+        "AudioServicesCreateSystemSoundID() created sound {:#x} for path {:?}",
+        id,
+        path
+    );
+    kAudioServicesNoError
+}
+
+fn AudioServicesDisposeSystemSoundID(
+    env: &mut Environment,
+    in_system_sound_id: SystemSoundID,
+) -> OSStatus {
+    let state = State::get(&mut env.framework_state);
+    let Some(sound) = state.sounds.remove(&in_system_sound_id) else {
+        log!(
+            "Warning: AudioServicesDisposeSystemSoundID() for unknown sound {:#x}",
+            in_system_sound_id
+        );
+        return kAudioServicesSystemSoundUnspecifiedError;
+    };
+    state.playing.retain(|&id| id != in_system_sound_id);
+    state.completions.remove(&in_system_sound_id);
+    let _context_manager = make_al_context_current(env);
+    unsafe {
+        al::alDeleteSources(1, &sound.al_source);
+        al::alDeleteBuffers(1, &sound.al_buffer);
+    }
+    log_dbg!(
+        "AudioServicesDisposeSystemSoundID() destroyed sound {:#x}",
+        in_system_sound_id
+    );
+    kAudioServicesNoError
+}
+
+fn AudioServicesPlaySystemSound(env: &mut Environment, in_system_sound_id: SystemSoundID) {
+    if in_system_sound_id == kSystemSoundID_Vibrate {
+        vibrate(env);
+        return;
+    }
+
+    let state = State::get(&mut env.framework_state);
+    let Some(sound) = state.sounds.get(&in_system_sound_id) else {
+        // The built-in sounds of iPhone OS aren't available.
+        log!(
+            "Warning: AudioServicesPlaySystemSound() for unknown sound {:#x}, ignoring",
+            in_system_sound_id
+        );
+        return;
+    };
+    let al_source = sound.al_source;
+    if !state.playing.contains(&in_system_sound_id) {
+        state.playing.push(in_system_sound_id);
+    }
+    let _context_manager = make_al_context_current(env);
+    // Playing a sound that is already playing restarts it.
+    unsafe { al::alSourcePlay(al_source) };
+}
+
+/// On an iPhone, alert sounds also vibrate the device.
+fn AudioServicesPlayAlertSound(env: &mut Environment, in_system_sound_id: SystemSoundID) {
+    if in_system_sound_id != kSystemSoundID_Vibrate {
+        AudioServicesPlaySystemSound(env, in_system_sound_id);
+    }
+    vibrate(env);
+}
+
+/// Get the app's vibration settings: the options, with any changes from the
+/// haptics file.
+fn vibration_settings(env: &mut Environment) -> Vibration {
+    if let Some(vibration) = State::get(&mut env.framework_state).vibration {
+        return vibration;
+    }
+    let vibration = Vibration {
+        strength: env.options.vibration_strength,
+        duration: env.options.vibration_duration,
+        shape: env.options.vibration_shape,
+    }
+    .load(&env.options.haptics_file, env.bundle.bundle_identifier());
+    State::get(&mut env.framework_state).vibration = Some(vibration);
+    vibration
+}
+
+/// Vibrate the device. Everything that vibrates it should use this.
+pub fn vibrate(env: &mut Environment) {
+    let vibration = vibration_settings(env);
+    if vibration.strength > 0.0 && !env.window.vibrate(vibration) {
+        log_dbg!("The app vibrated the device, but no game controller can rumble.");
+    }
+    State::get(&mut env.framework_state).vibration_end =
+        Some(Instant::now() + Duration::from_secs_f64(vibration.duration));
+}
+
+fn AudioServicesAddSystemSoundCompletion(
+    env: &mut Environment,
+    in_system_sound_id: SystemSoundID,
+    _in_run_loop: CFRunLoopRef,
+    _in_run_loop_mode: CFRunLoopMode,
+    in_completion_routine: AudioServicesSystemSoundCompletionProc,
+    in_client_data: MutVoidPtr,
+) -> OSStatus {
+    // There is only one run loop, so the run loop and mode are ignored.
+    // Completions are called from the main run loop in common modes.
+    let state = State::get(&mut env.framework_state);
+    if in_system_sound_id != kSystemSoundID_Vibrate
+        && !state.sounds.contains_key(&in_system_sound_id)
+    {
+        return kAudioServicesSystemSoundUnspecifiedError;
+    }
+    state
+        .completions
+        .insert(in_system_sound_id, (in_completion_routine, in_client_data));
+    kAudioServicesNoError
+}
+
+fn AudioServicesRemoveSystemSoundCompletion(
+    env: &mut Environment,
+    in_system_sound_id: SystemSoundID,
+) {
+    State::get(&mut env.framework_state)
+        .completions
+        .remove(&in_system_sound_id);
+}
+
+/// Check the specifier, which is always a sound ID for the supported
+/// properties, and find the sound.
+fn sound_for_specifier(
+    env: &mut Environment,
+    in_property_id: AudioServicesPropertyID,
+    in_specifier_size: u32,
+    in_specifier: ConstVoidPtr,
+) -> Result<&mut SystemSound, OSStatus> {
+    match in_property_id {
+        kAudioServicesPropertyIsUISound | kAudioServicesPropertyCompletePlaybackIfAppDies => (),
+        _ => {
+            log!(
+                "Warning: Unsupported Audio Services property {}",
+                debug_fourcc(in_property_id)
+            );
+            return Err(kAudioServicesUnsupportedPropertyError);
+        }
+    }
+    if in_specifier_size != guest_size_of::<SystemSoundID>() || in_specifier.is_null() {
+        return Err(kAudioServicesBadSpecifierSizeError);
+    }
+    let id: SystemSoundID = env.mem.read(in_specifier.cast());
+    State::get(&mut env.framework_state)
+        .sounds
+        .get_mut(&id)
+        .ok_or(kAudioServicesSystemSoundUnspecifiedError)
+}
+
+fn AudioServicesGetPropertyInfo(
+    env: &mut Environment,
+    in_property_id: AudioServicesPropertyID,
+    in_specifier_size: u32,
+    in_specifier: ConstVoidPtr,
+    out_property_data_size: MutPtr<u32>,
+    out_writable: MutPtr<u8>,
+) -> OSStatus {
+    if let Err(err) = sound_for_specifier(env, in_property_id, in_specifier_size, in_specifier) {
+        return err;
+    }
+    if !out_property_data_size.is_null() {
+        env.mem
+            .write(out_property_data_size, guest_size_of::<u32>());
+    }
+    if !out_writable.is_null() {
+        env.mem.write(out_writable, 1);
+    }
+    kAudioServicesNoError
+}
+
+fn AudioServicesGetProperty(
+    env: &mut Environment,
+    in_property_id: AudioServicesPropertyID,
+    in_specifier_size: u32,
+    in_specifier: ConstVoidPtr,
+    io_property_data_size: MutPtr<u32>,
+    out_property_data: MutVoidPtr,
+) -> OSStatus {
+    let value = match sound_for_specifier(env, in_property_id, in_specifier_size, in_specifier) {
+        Ok(sound) => match in_property_id {
+            kAudioServicesPropertyIsUISound => sound.is_ui_sound,
+            kAudioServicesPropertyCompletePlaybackIfAppDies => sound.complete_playback_if_app_dies,
+            _ => unreachable!(),
+        },
+        Err(err) => return err,
+    };
+    let size: GuestUSize = guest_size_of::<u32>();
+    if env.mem.read(io_property_data_size) < size {
+        return kAudioServicesBadPropertySizeError;
+    }
+    env.mem.write(out_property_data.cast(), value);
+    env.mem.write(io_property_data_size, size);
+    kAudioServicesNoError
+}
+
+fn AudioServicesSetProperty(
+    env: &mut Environment,
+    in_property_id: AudioServicesPropertyID,
+    in_specifier_size: u32,
+    in_specifier: ConstVoidPtr,
+    in_property_data_size: u32,
+    in_property_data: ConstVoidPtr,
+) -> OSStatus {
+    if in_property_data_size != guest_size_of::<u32>() {
+        return kAudioServicesBadPropertySizeError;
+    }
+    let value: u32 = env.mem.read(in_property_data.cast());
+    let sound = match sound_for_specifier(env, in_property_id, in_specifier_size, in_specifier) {
+        Ok(sound) => sound,
+        Err(err) => return err,
+    };
+    // Neither property changes anything in touchHLE: UI sounds can't be
+    // turned off, and the app dying means touchHLE exits.
+    match in_property_id {
+        kAudioServicesPropertyIsUISound => sound.is_ui_sound = value,
+        kAudioServicesPropertyCompletePlaybackIfAppDies => {
+            sound.complete_playback_if_app_dies = value
+        }
+        _ => unreachable!(),
+    }
+    kAudioServicesNoError
+}
+
+/// For use by `NSRunLoop`: call the completion routines of sounds that have
+/// finished playing, and of the vibration if it's over.
+pub fn handle_system_sounds(env: &mut Environment) {
+    let state = State::get(&mut env.framework_state);
+    if state.playing.is_empty() && state.vibration_end.is_none() {
+        return;
+    }
+
+    let mut finished = Vec::new();
+    if state
+        .vibration_end
+        .is_some_and(|vibration_end| Instant::now() >= vibration_end)
+    {
+        state.vibration_end = None;
+        finished.push(kSystemSoundID_Vibrate);
+    }
+    let playing: Vec<_> = state
+        .playing
+        .iter()
+        .map(|id| (*id, state.sounds[id].al_source))
+        .collect();
+    {
+        let _context_manager = make_al_context_current(env);
+        for (id, al_source) in playing {
+            let mut al_state = 0;
+            unsafe { al::alGetSourcei(al_source, al::AL_SOURCE_STATE, &mut al_state) };
+            if al_state == al::AL_STOPPED {
+                finished.push(id);
+            }
+        }
+    }
+
+    let state = State::get(&mut env.framework_state);
+    state.playing.retain(|id| !finished.contains(id));
+    let completions: Vec<_> = finished
+        .into_iter()
+        .filter_map(|id| {
+            state
+                .completions
+                .get(&id)
+                .map(|&completion| (id, completion))
+        })
+        .collect();
+    for (id, (completion_routine, client_data)) in completions {
+        log_dbg!(
+            "System sound {:#x} finished, calling completion routine",
+            id
+        );
+        let () = completion_routine.call_from_host(env, (id, client_data));
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(AudioServicesCreateSystemSoundID(_, _)),
+    export_c_func!(AudioServicesDisposeSystemSoundID(_)),
+    export_c_func!(AudioServicesPlaySystemSound(_)),
+    export_c_func!(AudioServicesPlayAlertSound(_)),
+    export_c_func!(AudioServicesAddSystemSoundCompletion(_, _, _, _, _)),
+    export_c_func!(AudioServicesRemoveSystemSoundCompletion(_)),
+    export_c_func!(AudioServicesGetPropertyInfo(_, _, _, _, _)),
+    export_c_func!(AudioServicesGetProperty(_, _, _, _, _)),
+    export_c_func!(AudioServicesSetProperty(_, _, _, _, _)),
+];
@@ -0,0 +1,586 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `AudioSession.h` (Audio Session Services)
+//!
+//! Apps use this to tell the system how their audio should interact with other
+//! audio on the device (the category), and to find out about interruptions and
+//! changes of the audio route. There is no other audio on the device as far as
+//! the app can tell: touchHLE doesn't control the host's media players, so
+//! music playing on the host keeps playing whatever the category is, and
+//! `kAudioSessionProperty_OtherAudioIsPlaying` is always false. The category
+//! is still remembered, because it decides the audio route.
+//!
+//! Interruptions (e.g. by a phone call) can be simulated with Ctrl+I, see
+//! [toggle_interruption].
+
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::av_foundation::av_audio_player;
+use crate::frameworks::core_audio_types::{debug_fourcc, fourcc};
+use crate::frameworks::core_foundation::cf_run_loop::{
+    kCFRunLoopCommonModes, CFRunLoopMode, CFRunLoopRef,
+};
+use crate::frameworks::foundation::ns_dictionary::dict_from_keys_and_objects;
+use crate::frameworks::foundation::ns_string::get_static_str;
+use crate::frameworks::mac_types::OSStatus;
+use crate::mem::{guest_size_of, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr};
+use crate::objc::{id, msg, msg_class, release};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// Set by `AudioSessionInitialize`. Nothing else works before then.
+    initialized: bool,
+    interruption_listener: Option<(AudioSessionInterruptionListener, MutVoidPtr)>,
+    property_listeners: Vec<(
+        AudioSessionPropertyID,
+        AudioSessionPropertyListener,
+        MutVoidPtr,
+    )>,
+    is_interrupted: bool,
+    /// [None] means the default, `kAudioSessionCategory_SoloAmbientSound`.
+    category: Option<u32>,
+    mix_with_others: bool,
+    duck_others: bool,
+    route_override: u32,
+    preferred_sample_rate: f64,
+    preferred_io_buffer_duration: f32,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.audio_toolbox.audio_session
+    }
+}
+
+/// (*void)(void *in_client_data, u32 in_interruption_state)
+type AudioSessionInterruptionListener = GuestFunction;
+
+const kAudioSessionBeginInterruption: u32 = 1;
+const kAudioSessionEndInterruption: u32 = 0;
+
+/// (*void)(void *in_client_data, AudioSessionPropertyID in_id,
+///         u32 in_data_size, const void *in_data)
+type AudioSessionPropertyListener = GuestFunction;
+
+const kAudioSessionNotInitialized: OSStatus = fourcc(b"!ini") as _;
+const kAudioSessionAlreadyInitialized: OSStatus = fourcc(b"init") as _;
+const kAudioSessionUnsupportedPropertyError: OSStatus = fourcc(b"pty?") as _;
+const kAudioSessionBadPropertySizeError: OSStatus = fourcc(b"!siz") as _;
+const kAudioSessionIncompatibleCategory: OSStatus = fourcc(b"!cat") as _;
+
+/// Usually a FourCC.
+type AudioSessionPropertyID = u32;
+const kAudioSessionProperty_PreferredHardwareSampleRate: AudioSessionPropertyID = fourcc(b"hwsr");
+const kAudioSessionProperty_PreferredHardwareIOBufferDuration: AudioSessionPropertyID =
+    fourcc(b"iobd");
+const kAudioSessionProperty_AudioCategory: AudioSessionPropertyID = fourcc(b"acat");
+const kAudioSessionProperty_AudioRoute: AudioSessionPropertyID = fourcc(b"rout");
+const kAudioSessionProperty_AudioRouteChange: AudioSessionPropertyID = fourcc(b"roch");
+const kAudioSessionProperty_CurrentHardwareSampleRate: AudioSessionPropertyID = fourcc(b"chsr");
+const kAudioSessionProperty_CurrentHardwareInputNumberChannels: AudioSessionPropertyID =
+    fourcc(b"chic");
+const kAudioSessionProperty_CurrentHardwareOutputNumberChannels: AudioSessionPropertyID =
+    fourcc(b"choc");
+const kAudioSessionProperty_CurrentHardwareOutputVolume: AudioSessionPropertyID = fourcc(b"chov");
+const kAudioSessionProperty_CurrentHardwareIOBufferDuration: AudioSessionPropertyID =
+    fourcc(b"chbd");
+const kAudioSessionProperty_OtherAudioIsPlaying: AudioSessionPropertyID = fourcc(b"othr");
+const kAudioSessionProperty_OverrideAudioRoute: AudioSessionPropertyID = fourcc(b"ovrd");
+const kAudioSessionProperty_AudioInputAvailable: AudioSessionPropertyID = fourcc(b"aiav");
+const kAudioSessionProperty_OverrideCategoryMixWithOthers: AudioSessionPropertyID = fourcc(b"cmix");
+const kAudioSessionProperty_OtherMixableAudioShouldDuck: AudioSessionPropertyID = fourcc(b"duck");
+
+const kAudioSessionCategory_AmbientSound: u32 = fourcc(b"ambi");
+const kAudioSessionCategory_SoloAmbientSound: u32 = fourcc(b"solo");
+const kAudioSessionCategory_MediaPlayback: u32 = fourcc(b"medi");
+const kAudioSessionCategory_RecordAudio: u32 = fourcc(b"reca");
+const kAudioSessionCategory_PlayAndRecord: u32 = fourcc(b"plar");
+const kAudioSessionCategory_AudioProcessing: u32 = fourcc(b"proc");
+const kAudioSessionCategory_UserInterfaceSoundEffects: u32 = fourcc(b"uifx");
+const kAudioSessionCategory_LiveAudio: u32 = fourcc(b"live");
+
+const kAudioSessionOverrideAudioRoute_None: u32 = 0;
+const kAudioSessionOverrideAudioRoute_Speaker: u32 = fourcc(b"spkr");
+
+const kAudioSessionRouteChangeReason_CategoryChange: i32 = 3;
+const kAudioSessionRouteChangeReason_Override: i32 = 4;
+
+pub const kAudioSession_AudioRouteChangeKey_Reason: &str = "OutputDeviceDidChange_Reason";
+pub const kAudioSession_AudioRouteChangeKey_OldRoute: &str = "OutputDeviceDidChange_OldRoute";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kAudioSession_AudioRouteChangeKey_Reason",
+        HostConstant::NSString(kAudioSession_AudioRouteChangeKey_Reason),
+    ),
+    (
+        "_kAudioSession_AudioRouteChangeKey_OldRoute",
+        HostConstant::NSString(kAudioSession_AudioRouteChangeKey_OldRoute),
+    ),
+];
+
+/// The hardware sample rate reported to apps. It's what the iPhone uses and
+/// OpenAL Soft resamples if the host uses something else.
+const HARDWARE_SAMPLE_RATE: f64 = 44100.0;
+/// The I/O buffer duration reported to apps if they don't have a preference.
+const DEFAULT_IO_BUFFER_DURATION: f32 = 0.023;
+
+impl State {
+    fn io_buffer_duration(&self) -> f32 {
+        if self.preferred_io_buffer_duration > 0.0 {
+            self.preferred_io_buffer_duration
+        } else {
+            DEFAULT_IO_BUFFER_DURATION
+        }
+    }
+
+    fn category(&self) -> u32 {
+        self.category
+            .unwrap_or(kAudioSessionCategory_SoloAmbientSound)
+    }
+
+    /// The name of the current audio route. The headphones are never plugged
+    /// in, so this depends only on the category and the route override.
+    fn route(&self) -> &'static str {
+        match self.category() {
+            kAudioSessionCategory_RecordAudio => "MicrophoneBuiltIn",
+            kAudioSessionCategory_PlayAndRecord
+                if self.route_override == kAudioSessionOverrideAudioRoute_None =>
+            {
+                "ReceiverAndMicrophone"
+            }
+            _ => "Speaker",
+        }
+    }
+}
+
+/// For use by the RemoteIO audio unit: the duration of the buffers that render
+/// callbacks are asked for, in seconds.
+pub fn current_io_buffer_duration(env: &mut Environment) -> f32 {
+    State::get(&mut env.framework_state).io_buffer_duration()
+}
+
+fn AudioSessionInitialize(
+    env: &mut Environment,
+    in_run_loop: CFRunLoopRef,
+    in_run_loop_mode: CFRunLoopMode,
+    in_interruption_listener: AudioSessionInterruptionListener,
+    in_client_data: MutVoidPtr,
+) -> OSStatus {
+    // NULL means the main thread's run loop, which is the only one touchHLE
+    // runs listeners on. See also AudioQueueNewOutput().
+    assert!(
+        in_run_loop.is_null() || {
+            let main_run_loop: CFRunLoopRef = msg_class![env; NSRunLoop mainRunLoop];
+            in_run_loop == main_run_loop
+        }
+    );
+    // NULL is a synonym of kCFRunLoopCommonModes here
+    assert!(
+        in_run_loop_mode.is_null() || {
+            let common_modes = get_static_str(env, kCFRunLoopCommonModes);
+            msg![env; in_run_loop_mode isEqualTo:common_modes]
+        }
+    );
+
+    let state = State::get(&mut env.framework_state);
+    if state.initialized {
+        log!("Warning: AudioSessionInitialize() called twice");
+        return kAudioSessionAlreadyInitialized;
+    }
+    state.initialized = true;
+    state.interruption_listener = if in_interruption_listener.addr_with_thumb_bit() == 0 {
+        None
+    } else {
+        Some((in_interruption_listener, in_client_data))
+    };
+    log_dbg!(
+        "AudioSessionInitialize() with interruption listener {:?}",
+        state.interruption_listener
+    );
+    0 // success
+}
+
+fn AudioSessionSetActive(env: &mut Environment, active: bool) -> OSStatus {
+    let state = State::get(&mut env.framework_state);
+    if !state.initialized {
+        return kAudioSessionNotInitialized;
+    }
+    log_dbg!("AudioSessionSetActive({})", active);
+    // Activating the session is how an app resumes after an interruption,
+    // if it didn't wait for the end of it.
+    if active {
+        state.is_interrupted = false;
+    }
+    0 // success
+}
+
+fn AudioSessionSetActiveWithFlags(env: &mut Environment, active: bool, _flags: u32) -> OSStatus {
+    // The only flag is about notifying other apps when deactivating.
+    AudioSessionSetActive(env, active)
+}
+
+/// The size of a property's value, or [None] if it's not supported.
+fn property_size(in_id: AudioSessionPropertyID) -> Option<GuestUSize> {
+    match in_id {
+        kAudioSessionProperty_PreferredHardwareSampleRate
+        | kAudioSessionProperty_CurrentHardwareSampleRate => Some(guest_size_of::<f64>()),
+        kAudioSessionProperty_PreferredHardwareIOBufferDuration
+        | kAudioSessionProperty_CurrentHardwareIOBufferDuration
+        | kAudioSessionProperty_CurrentHardwareOutputVolume => Some(guest_size_of::<f32>()),
+        kAudioSessionProperty_AudioRoute => Some(guest_size_of::<id>()),
+        kAudioSessionProperty_AudioCategory
+        | kAudioSessionProperty_CurrentHardwareInputNumberChannels
+        | kAudioSessionProperty_CurrentHardwareOutputNumberChannels
+        | kAudioSessionProperty_OtherAudioIsPlaying
+        | kAudioSessionProperty_OverrideAudioRoute
+        | kAudioSessionProperty_AudioInputAvailable
+        | kAudioSessionProperty_OverrideCategoryMixWithOthers
+        | kAudioSessionProperty_OtherMixableAudioShouldDuck => Some(guest_size_of::<u32>()),
+        _ => None,
+    }
+}
+
+fn AudioSessionGetPropertySize(
+    env: &mut Environment,
+    in_id: AudioSessionPropertyID,
+    out_data_size: MutPtr<u32>,
+) -> OSStatus {
+    if !State::get(&mut env.framework_state).initialized {
+        return kAudioSessionNotInitialized;
+    }
+    let Some(size) = property_size(in_id) else {
+        log!(
+            "Warning: AudioSessionGetPropertySize() for unsupported property {}",
+            debug_fourcc(in_id)
+        );
+        return kAudioSessionUnsupportedPropertyError;
+    };
+    env.mem.write(out_data_size, size);
+    0 // success
+}
+
+fn AudioSessionGetProperty(
+    env: &mut Environment,
+    in_id: AudioSessionPropertyID,
+    io_data_size: MutPtr<u32>,
+    out_data: MutVoidPtr,
+) -> OSStatus {
+    if !State::get(&mut env.framework_state).initialized {
+        return kAudioSessionNotInitialized;
+    }
+    let Some(size) = property_size(in_id) else {
+        log!(
+            "Warning: AudioSessionGetProperty() for unsupported property {}",
+            debug_fourcc(in_id)
+        );
+        return kAudioSessionUnsupportedPropertyError;
+    };
+    if env.mem.read(io_data_size) < size {
+        log!("Warning: AudioSessionGetProperty() failed");
+        return kAudioSessionBadPropertySizeError;
+    }
+
+    let state = State::get(&mut env.framework_state);
+    match in_id {
+        kAudioSessionProperty_PreferredHardwareSampleRate => {
+            let rate = state.preferred_sample_rate;
+            env.mem.write(out_data.cast(), rate);
+        }
+        kAudioSessionProperty_CurrentHardwareSampleRate => {
+            env.mem.write(out_data.cast(), HARDWARE_SAMPLE_RATE);
+        }
+        kAudioSessionProperty_PreferredHardwareIOBufferDuration => {
+            let duration = state.preferred_io_buffer_duration;
+            env.mem.write(out_data.cast(), duration);
+        }
+        kAudioSessionProperty_CurrentHardwareIOBufferDuration => {
+            let duration = state.io_buffer_duration();
+            env.mem.write(out_data.cast(), duration);
+        }
+        kAudioSessionProperty_CurrentHardwareOutputVolume => {
+            env.mem.write(out_data.cast(), 1.0f32);
+        }
+        kAudioSessionProperty_AudioRoute => {
+            // The caller is meant to release this string. Releasing a static
+            // string does nothing, so that's fine.
+            let route = state.route();
+            let route = get_static_str(env, route);
+            env.mem.write(out_data.cast(), route);
+        }
+        kAudioSessionProperty_AudioCategory => {
+            let category = state.category();
+            env.mem.write(out_data.cast(), category);
+        }
+        kAudioSessionProperty_CurrentHardwareInputNumberChannels
+        | kAudioSessionProperty_AudioInputAvailable => {
+            // There's only a microphone if the user allows it.
+            let input = u32::from(env.options.allow_microphone);
+            env.mem.write(out_data.cast(), input);
+        }
+        kAudioSessionProperty_OtherAudioIsPlaying => {
+            env.mem.write(out_data.cast(), 0u32);
+        }
+        kAudioSessionProperty_CurrentHardwareOutputNumberChannels => {
+            env.mem.write(out_data.cast(), 2u32);
+        }
+        kAudioSessionProperty_OverrideAudioRoute => {
+            let route_override = state.route_override;
+            env.mem.write(out_data.cast(), route_override);
+        }
+        kAudioSessionProperty_OverrideCategoryMixWithOthers => {
+            let mix_with_others = u32::from(state.mix_with_others);
+            env.mem.write(out_data.cast(), mix_with_others);
+        }
+        kAudioSessionProperty_OtherMixableAudioShouldDuck => {
+            let duck_others = u32::from(state.duck_others);
+            env.mem.write(out_data.cast(), duck_others);
+        }
+        _ => unreachable!(),
+    }
+    env.mem.write(io_data_size, size);
+
+    0 // success
+}
+
+fn AudioSessionSetProperty(
+    env: &mut Environment,
+    in_id: AudioSessionPropertyID,
+    in_data_size: u32,
+    in_data: ConstVoidPtr,
+) -> OSStatus {
+    if !State::get(&mut env.framework_state).initialized {
+        return kAudioSessionNotInitialized;
+    }
+    let Some(size) = property_size(in_id) else {
+        log!(
+            "Warning: AudioSessionSetProperty() for unsupported property {}",
+            debug_fourcc(in_id)
+        );
+        return kAudioSessionUnsupportedPropertyError;
+    };
+    if in_data_size != size {
+        log!("Warning: AudioSessionSetProperty() failed");
+        return kAudioSessionBadPropertySizeError;
+    }
+
+    let old_route = State::get(&mut env.framework_state).route();
+    let route_change_reason = match in_id {
+        kAudioSessionProperty_PreferredHardwareSampleRate => {
+            let rate: f64 = env.mem.read(in_data.cast());
+            State::get(&mut env.framework_state).preferred_sample_rate = rate;
+            None
+        }
+        kAudioSessionProperty_PreferredHardwareIOBufferDuration => {
+            let duration: f32 = env.mem.read(in_data.cast());
+            State::get(&mut env.framework_state).preferred_io_buffer_duration = duration;
+            None
+        }
+        kAudioSessionProperty_AudioCategory => {
+            let category: u32 = env.mem.read(in_data.cast());
+            if ![
+                kAudioSessionCategory_AmbientSound,
+                kAudioSessionCategory_SoloAmbientSound,
+                kAudioSessionCategory_MediaPlayback,
+                kAudioSessionCategory_RecordAudio,
+                kAudioSessionCategory_PlayAndRecord,
+                kAudioSessionCategory_AudioProcessing,
+                kAudioSessionCategory_UserInterfaceSoundEffects,
+                kAudioSessionCategory_LiveAudio,
+            ]
+            .contains(&category)
+            {
+                log!(
+                    "Warning: AudioSessionSetProperty() with unknown category {}",
+                    debug_fourcc(category)
+                );
+                return kAudioSessionIncompatibleCategory;
+            }
+            log_dbg!(
+                "AudioSessionSetProperty() set category to {}",
+                debug_fourcc(category)
+            );
+            State::get(&mut env.framework_state).category = Some(category);
+            Some(kAudioSessionRouteChangeReason_CategoryChange)
+        }
+        kAudioSessionProperty_OverrideAudioRoute => {
+            let route_override: u32 = env.mem.read(in_data.cast());
+            if ![
+                kAudioSessionOverrideAudioRoute_None,
+                kAudioSessionOverrideAudioRoute_Speaker,
+            ]
+            .contains(&route_override)
+            {
+                return kAudioSessionUnsupportedPropertyError;
+            }
+            State::get(&mut env.framework_state).route_override = route_override;
+            Some(kAudioSessionRouteChangeReason_Override)
+        }
+        kAudioSessionProperty_OverrideCategoryMixWithOthers => {
+            let mix_with_others: u32 = env.mem.read(in_data.cast());
+            State::get(&mut env.framework_state).mix_with_others = mix_with_others != 0;
+            None
+        }
+        kAudioSessionProperty_OtherMixableAudioShouldDuck => {
+            let duck_others: u32 = env.mem.read(in_data.cast());
+            State::get(&mut env.framework_state).duck_others = duck_others != 0;
+            None
+        }
+        _ => {
+            log!(
+                "Warning: AudioSessionSetProperty() for read-only property {}",
+                debug_fourcc(in_id)
+            );
+            return kAudioSessionUnsupportedPropertyError;
+        }
+    };
+
+    if let Some(reason) = route_change_reason {
+        if State::get(&mut env.framework_state).route() != old_route {
+            notify_route_change(env, reason, old_route);
+        }
+    }
+
+    0 // success
+}
+
+/// Call the `kAudioSessionProperty_AudioRouteChange` listeners.
+fn notify_route_change(env: &mut Environment, reason: i32, old_route: &'static str) {
+    log_dbg!(
+        "Audio route changed from {:?} to {:?}",
+        old_route,
+        State::get(&mut env.framework_state).route()
+    );
+
+    let listeners: Vec<_> = State::get(&mut env.framework_state)
+        .property_listeners
+        .iter()
+        .filter(|&&(id, _, _)| id == kAudioSessionProperty_AudioRouteChange)
+        .map(|&(_, proc_, client_data)| (proc_, client_data))
+        .collect();
+    if listeners.is_empty() {
+        return;
+    }
+
+    let reason_key = get_static_str(env, kAudioSession_AudioRouteChangeKey_Reason);
+    let reason: id = msg_class![env; NSNumber numberWithInt:reason];
+    let old_route_key = get_static_str(env, kAudioSession_AudioRouteChangeKey_OldRoute);
+    let old_route = get_static_str(env, old_route);
+    // CFDictionaryRef
+    let dict = dict_from_keys_and_objects(env, &[(reason_key, reason), (old_route_key, old_route)]);
+
+    let data_ptr = env.mem.alloc_and_write(dict);
+    for (proc_, client_data) in listeners {
+        let () = proc_.call_from_host(
+            env,
+            (
+                client_data,
+                kAudioSessionProperty_AudioRouteChange,
+                guest_size_of::<id>(),
+                data_ptr.cast_const().cast::<std::ffi::c_void>(),
+            ),
+        );
+    }
+    env.mem.free(data_ptr.cast());
+    release(env, dict);
+}
+
+fn AudioSessionAddPropertyListener(
+    env: &mut Environment,
+    in_id: AudioSessionPropertyID,
+    in_proc: AudioSessionPropertyListener,
+    in_client_data: MutVoidPtr,
+) -> OSStatus {
+    let state = State::get(&mut env.framework_state);
+    if !state.initialized {
+        return kAudioSessionNotInitialized;
+    }
+    // Listeners for other properties are accepted, but never called, because
+    // touchHLE never changes those properties by itself.
+    if in_id != kAudioSessionProperty_AudioRouteChange {
+        log_dbg!(
+            "AudioSessionAddPropertyListener() for {}, which never changes",
+            debug_fourcc(in_id)
+        );
+    }
+    state
+        .property_listeners
+        .push((in_id, in_proc, in_client_data));
+    0 // success
+}
+
+fn AudioSessionRemovePropertyListener(
+    env: &mut Environment,
+    in_id: AudioSessionPropertyID,
+) -> OSStatus {
+    let state = State::get(&mut env.framework_state);
+    if !state.initialized {
+        return kAudioSessionNotInitialized;
+    }
+    state.property_listeners.retain(|&(id, _, _)| id != in_id);
+    0 // success
+}
+
+fn AudioSessionRemovePropertyListenerWithUserData(
+    env: &mut Environment,
+    in_id: AudioSessionPropertyID,
+    in_proc: AudioSessionPropertyListener,
+    in_client_data: MutVoidPtr,
+) -> OSStatus {
+    let state = State::get(&mut env.framework_state);
+    if !state.initialized {
+        return kAudioSessionNotInitialized;
+    }
+    state
+        .property_listeners
+        .retain(|&(id, proc_, client_data)| {
+            id != in_id
+                || proc_.addr_with_thumb_bit() != in_proc.addr_with_thumb_bit()
+                || client_data != in_client_data
+        });
+    0 // success
+}
+
+/// For use by `UIApplication`'s event handling: begin an interruption, or end
+/// the current one, as if a phone call came in and was then declined.
+///
+/// Beginning an interruption pauses `AVAudioPlayer`s, before the app's interruption listener is called. Audio
+/// queues and OpenAL are not paused, which is only a problem for apps that
+/// expect them to be.
+pub fn toggle_interruption(env: &mut Environment) {
+    let state = State::get(&mut env.framework_state);
+    let begin = !state.is_interrupted;
+    state.is_interrupted = begin;
+    let listener = state.interruption_listener;
+    if begin {
+        println!("Simulating the start of an audio interruption (press Ctrl+I again to end it).");
+        av_audio_player::begin_interruption(env);
+    } else {
+        println!("Simulating the end of an audio interruption.");
+        av_audio_player::end_interruption(env);
+    }
+
+    if let Some((listener, client_data)) = listener {
+        let interruption_state = if begin {
+            kAudioSessionBeginInterruption
+        } else {
+            kAudioSessionEndInterruption
+        };
+        let () = listener.call_from_host(env, (client_data, interruption_state));
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(AudioSessionInitialize(_, _, _, _)),
+    export_c_func!(AudioSessionSetActive(_)),
+    export_c_func!(AudioSessionSetActiveWithFlags(_, _)),
+    export_c_func!(AudioSessionGetPropertySize(_, _)),
+    export_c_func!(AudioSessionGetProperty(_, _, _)),
+    export_c_func!(AudioSessionSetProperty(_, _, _)),
+    export_c_func!(AudioSessionAddPropertyListener(_, _, _)),
+    export_c_func!(AudioSessionRemovePropertyListener(_)),
+    export_c_func!(AudioSessionRemovePropertyListenerWithUserData(_, _, _)),
+];
@@ -0,0 +1,606 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `ExtAudioFile.h` (Extended Audio File Services)
+//!
+//! This is Audio File Services combined with a format converter. Apps mostly
+//! use it to get linear PCM for OpenAL buffers from compressed files. The file
+//! is decoded to 16-bit linear PCM, which is then converted to the client
+//! format: any linear PCM sample format, mono or stereo, and any sample rate.
+//! Sample rate conversion is linear interpolation, which is good enough for
+//! sound effects.
+
+use super::audio_file::to_stream_description;
+use crate::audio; // Keep this module namespaced to avoid confusion
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_audio_types::{
+    buffer_in_list, debug_fourcc, fourcc, kAudioFormatFlagIsBigEndian, kAudioFormatFlagIsFloat,
+    kAudioFormatFlagIsNonInterleaved, kAudioFormatFlagIsSignedInteger, kAudioFormatLinearPCM,
+    kLinearPCMFormatFlagsSampleFractionMask, kLinearPCMFormatFlagsSampleFractionShift, AudioBuffer,
+    AudioBufferList, AudioStreamBasicDescription,
+};
+use crate::frameworks::core_foundation::cf_url::CFURLRef;
+use crate::frameworks::foundation::ns_url::to_rust_path;
+use crate::frameworks::mac_types::OSStatus;
+use crate::mem::{guest_size_of, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, SafeRead};
+use crate::Environment;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct State {
+    ext_audio_files: HashMap<ExtAudioFileRef, ExtAudioFileHostObject>,
+}
+impl State {
+    pub fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.audio_toolbox.ext_audio_file
+    }
+}
+
+struct ExtAudioFileHostObject {
+    audio_file: audio::AudioFile,
+    /// The format the app reads in. Always linear PCM, see
+    /// [is_supported_client_format].
+    client_format: Option<AudioStreamBasicDescription>,
+    /// Position in frames of the client format, for `ExtAudioFileRead`. This
+    /// is different from the position in the file if the sample rate is.
+    position: u64,
+}
+
+#[repr(C, packed)]
+struct OpaqueExtAudioFile {
+    _filler: u8,
+}
+unsafe impl SafeRead for OpaqueExtAudioFile {}
+
+type ExtAudioFileRef = MutPtr<OpaqueExtAudioFile>;
+
+const kAudioFileFileNotFoundError: OSStatus = -43;
+const kExtAudioFileError_InvalidPropertySize: OSStatus = -66562;
+const kExtAudioFileError_NonPCMClientFormat: OSStatus = -66563;
+const kExtAudioFileError_InvalidSeek: OSStatus = -66568;
+const kExtAudioFileError_InvalidDataFormat: OSStatus = -66566;
+const kAudioConverterErr_FormatNotSupported: OSStatus = fourcc(b"fmt?") as _;
+
+/// Usually a FourCC.
+type ExtAudioFilePropertyID = u32;
+const kExtAudioFileProperty_FileDataFormat: ExtAudioFilePropertyID = fourcc(b"ffmt");
+const kExtAudioFileProperty_ClientDataFormat: ExtAudioFilePropertyID = fourcc(b"cfmt");
+const kExtAudioFileProperty_FileLengthFrames: ExtAudioFilePropertyID = fourcc(b"#frm");
+
+fn ExtAudioFileOpenURL(
+    env: &mut Environment,
+    in_url: CFURLRef,
+    out_ext_audio_file: MutPtr<ExtAudioFileRef>,
+) -> OSStatus {
+    let path = to_rust_path(env, in_url);
+    let Ok(audio_file) = audio::AudioFile::open_for_reading(path, &env.fs) else {
+        log!(
+            "Warning: ExtAudioFileOpenURL() for path {:?} failed",
+            in_url
+        );
+        return kAudioFileFileNotFoundError;
+    };
+
+    let host_object = ExtAudioFileHostObject {
+        audio_file,
+        client_format: None,
+        position: 0,
+    };
+
+    let guest_ext_audio_file = env.mem.alloc_and_write(OpaqueExtAudioFile { _filler: 0 });
+    State::get(&mut env.framework_state)
+        .ext_audio_files
+        .insert(guest_ext_audio_file, host_object);
+
+    env.mem.write(out_ext_audio_file, guest_ext_audio_file);
+
+    log_dbg!(
+        "ExtAudioFileOpenURL() opened path {:?}, new extended audio file handle: {:?}",
+        in_url,
+        guest_ext_audio_file
+    );
+
+    0 // success
+}
+
+fn ExtAudioFileGetProperty(
+    env: &mut Environment,
+    in_ext_audio_file: ExtAudioFileRef,
+    in_property_id: ExtAudioFilePropertyID,
+    io_property_data_size: MutPtr<u32>,
+    out_property_data: MutVoidPtr,
+) -> OSStatus {
+    let required_size: GuestUSize = match in_property_id {
+        kExtAudioFileProperty_FileDataFormat | kExtAudioFileProperty_ClientDataFormat => {
+            guest_size_of::<AudioStreamBasicDescription>()
+        }
+        kExtAudioFileProperty_FileLengthFrames => guest_size_of::<i64>(),
+        _ => unimplemented!(
+            "Unimplemented property ID: {}",
+            debug_fourcc(in_property_id)
+        ),
+    };
+    if env.mem.read(io_property_data_size) != required_size {
+        log!("Warning: ExtAudioFileGetProperty() failed");
+        return kExtAudioFileError_InvalidPropertySize;
+    }
+
+    let host_object = State::get(&mut env.framework_state)
+        .ext_audio_files
+        .get_mut(&in_ext_audio_file)
+        .unwrap();
+
+    match in_property_id {
+        kExtAudioFileProperty_FileDataFormat => {
+            let desc = to_stream_description(host_object.audio_file.audio_description());
+            env.mem.write(out_property_data.cast(), desc);
+        }
+        kExtAudioFileProperty_ClientDataFormat => {
+            let desc = host_object.client_format.unwrap_or_else(|| {
+                to_stream_description(host_object.audio_file.audio_description())
+            });
+            env.mem.write(out_property_data.cast(), desc);
+        }
+        kExtAudioFileProperty_FileLengthFrames => {
+            let frame_count: i64 = host_object.audio_file.frame_count().try_into().unwrap();
+            env.mem.write(out_property_data.cast(), frame_count);
+        }
+        _ => unreachable!(),
+    }
+
+    0 // success
+}
+
+fn ExtAudioFileSetProperty(
+    env: &mut Environment,
+    in_ext_audio_file: ExtAudioFileRef,
+    in_property_id: ExtAudioFilePropertyID,
+    in_property_data_size: u32,
+    in_property_data: ConstVoidPtr,
+) -> OSStatus {
+    // others unimplemented
+    assert!(in_property_id == kExtAudioFileProperty_ClientDataFormat);
+
+    if in_property_data_size != guest_size_of::<AudioStreamBasicDescription>() {
+        log!("Warning: ExtAudioFileSetProperty() failed");
+        return kExtAudioFileError_InvalidPropertySize;
+    }
+    let client_format: AudioStreamBasicDescription = env.mem.read(in_property_data.cast());
+
+    let host_object = State::get(&mut env.framework_state)
+        .ext_audio_files
+        .get_mut(&in_ext_audio_file)
+        .unwrap();
+
+    let audio::AudioDescription {
+        sample_rate,
+        channels_per_frame,
+        ..
+    } = host_object.audio_file.audio_description();
+
+    if client_format.format_id != kAudioFormatLinearPCM {
+        log!(
+            "Warning: ExtAudioFileSetProperty() with non-PCM client format {:#?}",
+            client_format
+        );
+        return kExtAudioFileError_NonPCMClientFormat;
+    }
+    if !is_supported_client_format(&client_format, channels_per_frame) {
+        log!(
+            "Warning: ExtAudioFileSetProperty() with unsupported client format {:#?} for file with sample rate {} and {} channels",
+            client_format,
+            sample_rate,
+            channels_per_frame
+        );
+        return kAudioConverterErr_FormatNotSupported;
+    }
+
+    // Keep the same time position when the sample rate changes.
+    let old_sample_rate = host_object
+        .client_format
+        .map_or(sample_rate, |format| format.sample_rate);
+    host_object.position =
+        (host_object.position as f64 * client_format.sample_rate / old_sample_rate) as u64;
+
+    log_dbg!(
+        "ExtAudioFileSetProperty() set client format for {:?}: {:#?}",
+        in_ext_audio_file,
+        client_format
+    );
+    host_object.client_format = Some(client_format);
+
+    0 // success
+}
+
+/// Check if a linear PCM format can be converted to from a file with
+/// `file_channels` channels.
+fn is_supported_client_format(format: &AudioStreamBasicDescription, file_channels: u32) -> bool {
+    let &AudioStreamBasicDescription {
+        sample_rate,
+        format_flags,
+        bytes_per_packet,
+        frames_per_packet,
+        bytes_per_frame,
+        channels_per_frame,
+        bits_per_channel,
+        ..
+    } = format;
+    let is_float = (format_flags & kAudioFormatFlagIsFloat) != 0;
+    let is_signed = (format_flags & kAudioFormatFlagIsSignedInteger) != 0;
+    let fraction_bits = (format_flags & kLinearPCMFormatFlagsSampleFractionMask)
+        >> kLinearPCMFormatFlagsSampleFractionShift;
+    // TODO: Mixing or splitting channels for more than two channels.
+    let channels_ok = channels_per_frame == file_channels
+        || ((1..=2).contains(&channels_per_frame) && (1..=2).contains(&file_channels));
+    let sample_format_ok = match (bits_per_channel, is_float) {
+        // Unsigned 8-bit is the usual 8-bit format.
+        (8, false) => fraction_bits == 0,
+        (16 | 24, false) => is_signed && fraction_bits == 0,
+        // Plain integer or fixed-point.
+        (32, false) => is_signed,
+        (32 | 64, true) => fraction_bits == 0,
+        _ => false,
+    };
+    // Samples that don't fill their bytes (i.e. formats without
+    // kAudioFormatFlagIsPacked) aren't supported.
+    let samples_per_frame = if is_non_interleaved(format) {
+        1
+    } else {
+        channels_per_frame
+    };
+    (1.0..=1_000_000.0).contains(&sample_rate)
+        && frames_per_packet == 1
+        && channels_ok
+        && sample_format_ok
+        && bytes_per_frame == bits_per_channel / 8 * samples_per_frame
+        && bytes_per_packet == bytes_per_frame
+}
+
+fn is_non_interleaved(format: &AudioStreamBasicDescription) -> bool {
+    (format.format_flags & kAudioFormatFlagIsNonInterleaved) != 0
+}
+
+/// Convert a 16-bit sample to a sample in a supported client format.
+fn encode_sample(format: &AudioStreamBasicDescription, sample: i16, out: &mut [u8]) {
+    let format_flags = format.format_flags;
+    let is_float = (format_flags & kAudioFormatFlagIsFloat) != 0;
+    let is_signed = (format_flags & kAudioFormatFlagIsSignedInteger) != 0;
+    match (format.bits_per_channel, is_float) {
+        (8, false) if is_signed => out[0] = (sample >> 8) as u8,
+        (8, false) => out[0] = ((sample >> 8) as u8) ^ 0x80,
+        (16, false) => out.copy_from_slice(&sample.to_le_bytes()),
+        (24, false) => out.copy_from_slice(&(i32::from(sample) << 8).to_le_bytes()[..3]),
+        (32, false) => {
+            let fraction_bits = (format_flags & kLinearPCMFormatFlagsSampleFractionMask)
+                >> kLinearPCMFormatFlagsSampleFractionShift;
+            let sample = i32::from(sample);
+            let sample = match fraction_bits {
+                0 => sample << 16,
+                // A 16-bit sample has 15 fractional bits.
+                15.. => sample << (fraction_bits - 15),
+                _ => sample >> (15 - fraction_bits),
+            };
+            out.copy_from_slice(&sample.to_le_bytes());
+        }
+        (32, true) => out.copy_from_slice(&(f32::from(sample) / 32768.0).to_le_bytes()),
+        (64, true) => out.copy_from_slice(&(f64::from(sample) / 32768.0).to_le_bytes()),
+        _ => unreachable!(),
+    }
+    if (format_flags & kAudioFormatFlagIsBigEndian) != 0 {
+        out.reverse();
+    }
+}
+
+/// Convert interleaved 16-bit samples from one channel count to another.
+/// Only mono and stereo are supported if the counts are different.
+fn convert_channels(samples: Vec<i16>, from_channels: u32, to_channels: u32) -> Vec<i16> {
+    match (from_channels, to_channels) {
+        (from, to) if from == to => samples,
+        (1, 2) => samples.iter().flat_map(|&s| [s, s]).collect(),
+        (2, 1) => samples
+            .chunks_exact(2)
+            .map(|frame| ((i32::from(frame[0]) + i32::from(frame[1])) / 2) as i16)
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+/// Resample interleaved 16-bit samples by linear interpolation. `input` starts
+/// at frame `input_start` of the input stream and `output_start` is the first
+/// output frame wanted. At most `max_output_frames` are produced, and fewer if
+/// `input` runs out. `ratio` is the input sample rate divided by the output
+/// sample rate.
+fn resample_linear(
+    input: &[i16],
+    input_start: u64,
+    channels: usize,
+    ratio: f64,
+    output_start: u64,
+    max_output_frames: usize,
+) -> Vec<i16> {
+    let input_frames = input.len() / channels;
+    let mut output = Vec::with_capacity(max_output_frames * channels);
+    for output_frame in output_start..(output_start + max_output_frames as u64) {
+        let position = output_frame as f64 * ratio - input_start as f64;
+        let index = position.floor() as usize;
+        if index >= input_frames {
+            break;
+        }
+        let fraction = position - position.floor();
+        // The last frame is held if there's nothing to interpolate towards.
+        let next_index = (index + 1).min(input_frames - 1);
+        for channel in 0..channels {
+            let a = f64::from(input[index * channels + channel]);
+            let b = f64::from(input[next_index * channels + channel]);
+            output.push((a + (b - a) * fraction).round() as i16);
+        }
+    }
+    output
+}
+
+/// Read up to `frame_count` frames in the client format, as 16-bit samples
+/// with the client's channel count. Returns [Err] if the file can't be read.
+fn read_client_frames(
+    host_object: &mut ExtAudioFileHostObject,
+    frame_count: u32,
+) -> Result<Vec<i16>, ()> {
+    let client_format = host_object.client_format.unwrap();
+    let audio::AudioDescription {
+        sample_rate,
+        channels_per_frame,
+        ..
+    } = host_object.audio_file.audio_description();
+    let ratio = sample_rate / client_format.sample_rate;
+    let position = host_object.position;
+
+    let samples = if ratio == 1.0 {
+        let mut samples = vec![0i16; (frame_count * channels_per_frame) as usize];
+        let frames_read = host_object
+            .audio_file
+            .read_pcm_frames(position, &mut samples)?;
+        samples.truncate(frames_read * channels_per_frame as usize);
+        samples
+    } else {
+        // The input frames needed to interpolate the output frames.
+        let file_frames = host_object.audio_file.frame_count();
+        let first = (position as f64 * ratio).floor() as u64;
+        if first >= file_frames {
+            return Ok(Vec::new());
+        }
+        let last = ((position + u64::from(frame_count)) as f64 * ratio).ceil() as u64;
+        let last = last.min(file_frames - 1);
+        let input_frames = (last - first + 1) as usize;
+        let mut input = vec![0i16; input_frames * channels_per_frame as usize];
+        let frames_read = host_object.audio_file.read_pcm_frames(first, &mut input)?;
+        input.truncate(frames_read * channels_per_frame as usize);
+        // Don't produce frames beyond the end of the file.
+        let total_frames = (file_frames as f64 / ratio).floor() as u64;
+        let max_frames = u64::from(frame_count).min(total_frames.saturating_sub(position));
+        resample_linear(
+            &input,
+            first,
+            channels_per_frame as usize,
+            ratio,
+            position,
+            max_frames as usize,
+        )
+    };
+    Ok(convert_channels(
+        samples,
+        channels_per_frame,
+        client_format.channels_per_frame,
+    ))
+}
+
+fn ExtAudioFileRead(
+    env: &mut Environment,
+    in_ext_audio_file: ExtAudioFileRef,
+    io_number_frames: MutPtr<u32>,
+    io_data: MutPtr<AudioBufferList>,
+) -> OSStatus {
+    let host_object = State::get(&mut env.framework_state)
+        .ext_audio_files
+        .get_mut(&in_ext_audio_file)
+        .unwrap();
+
+    // Reading the file's own format, if it's not linear PCM, is not
+    // implemented.
+    let client_format = host_object.client_format.unwrap();
+    let channels = client_format.channels_per_frame;
+    let non_interleaved = is_non_interleaved(&client_format);
+    let bytes_per_sample = client_format.bits_per_channel / 8;
+
+    // Non-interleaved formats have a buffer for each channel.
+    let number_buffers = env.mem.read(io_data.cast::<u32>());
+    let expected_buffers = if non_interleaved { channels } else { 1 };
+    if number_buffers != expected_buffers {
+        log!(
+            "Warning: ExtAudioFileRead() with {} buffers, expected {}",
+            number_buffers,
+            expected_buffers
+        );
+        return kExtAudioFileError_InvalidDataFormat;
+    }
+    let buffers: Vec<AudioBuffer> = (0..number_buffers)
+        .map(|i| env.mem.read(buffer_in_list(io_data, i)))
+        .collect();
+    let frames_to_read = buffers.iter().fold(
+        env.mem.read(io_number_frames),
+        |frames, &AudioBuffer { data_byte_size, .. }| {
+            frames.min(data_byte_size / client_format.bytes_per_frame)
+        },
+    );
+
+    let Ok(samples) = read_client_frames(host_object, frames_to_read) else {
+        log!("Warning: ExtAudioFileRead() failed");
+        return kExtAudioFileError_InvalidSeek;
+    };
+    let frames_read: u32 = (samples.len() / channels as usize).try_into().unwrap();
+    host_object.position += u64::from(frames_read);
+
+    let byte_count = frames_read * client_format.bytes_per_frame;
+    for (i, buffer) in buffers.into_iter().enumerate() {
+        let out_bytes = env.mem.bytes_at_mut(buffer.data.cast(), byte_count);
+        let out_samples = out_bytes.chunks_exact_mut(bytes_per_sample as usize);
+        if non_interleaved {
+            let channel_samples = samples.iter().skip(i).step_by(channels as usize);
+            for (out, &sample) in out_samples.zip(channel_samples) {
+                encode_sample(&client_format, sample, out);
+            }
+        } else {
+            for (out, &sample) in out_samples.zip(samples.iter()) {
+                encode_sample(&client_format, sample, out);
+            }
+        }
+        env.mem.write(
+            buffer_in_list(io_data, i.try_into().unwrap()),
+            AudioBuffer {
+                data_byte_size: byte_count,
+                ..buffer
+            },
+        );
+    }
+    env.mem.write(io_number_frames, frames_read);
+
+    0 // success
+}
+
+fn ExtAudioFileSeek(
+    env: &mut Environment,
+    in_ext_audio_file: ExtAudioFileRef,
+    in_frame_offset: i64,
+) -> OSStatus {
+    let host_object = State::get(&mut env.framework_state)
+        .ext_audio_files
+        .get_mut(&in_ext_audio_file)
+        .unwrap();
+
+    let Ok(position) = u64::try_from(in_frame_offset) else {
+        return kExtAudioFileError_InvalidSeek;
+    };
+    host_object.position = position;
+
+    0 // success
+}
+
+fn ExtAudioFileTell(
+    env: &mut Environment,
+    in_ext_audio_file: ExtAudioFileRef,
+    out_frame_offset: MutPtr<i64>,
+) -> OSStatus {
+    let position = State::get(&mut env.framework_state)
+        .ext_audio_files
+        .get(&in_ext_audio_file)
+        .unwrap()
+        .position;
+    env.mem
+        .write(out_frame_offset, position.try_into().unwrap());
+    0 // success
+}
+
+fn ExtAudioFileDispose(env: &mut Environment, in_ext_audio_file: ExtAudioFileRef) -> OSStatus {
+    let _host_object = State::get(&mut env.framework_state)
+        .ext_audio_files
+        .remove(&in_ext_audio_file)
+        .unwrap();
+    env.mem.free(in_ext_audio_file.cast());
+    log_dbg!(
+        "ExtAudioFileDispose() destroyed extended audio file handle: {:?}",
+        in_ext_audio_file
+    );
+    0 // success
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(ExtAudioFileOpenURL(_, _)),
+    export_c_func!(ExtAudioFileGetProperty(_, _, _, _)),
+    export_c_func!(ExtAudioFileSetProperty(_, _, _, _)),
+    export_c_func!(ExtAudioFileRead(_, _, _)),
+    export_c_func!(ExtAudioFileSeek(_, _)),
+    export_c_func!(ExtAudioFileTell(_, _)),
+    export_c_func!(ExtAudioFileDispose(_)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pcm_format(format_flags: u32, bits_per_channel: u32) -> AudioStreamBasicDescription {
+        AudioStreamBasicDescription {
+            sample_rate: 44100.0,
+            format_id: kAudioFormatLinearPCM,
+            format_flags,
+            bytes_per_packet: bits_per_channel / 8,
+            frames_per_packet: 1,
+            bytes_per_frame: bits_per_channel / 8,
+            channels_per_frame: 1,
+            bits_per_channel,
+            _reserved: 0,
+        }
+    }
+
+    fn encode(format_flags: u32, bits_per_channel: u32, sample: i16) -> Vec<u8> {
+        let mut out = vec![0; (bits_per_channel / 8) as usize];
+        encode_sample(
+            &pcm_format(format_flags, bits_per_channel),
+            sample,
+            &mut out,
+        );
+        out
+    }
+
+    #[test]
+    fn sample_formats() {
+        let signed = kAudioFormatFlagIsSignedInteger;
+        assert_eq!(encode(0, 8, -0x8000), [0x00]);
+        assert_eq!(encode(0, 8, 0x7fff), [0xff]);
+        assert_eq!(encode(signed, 8, 0x1234), [0x12]);
+        assert_eq!(encode(signed, 16, 0x1234), [0x34, 0x12]);
+        assert_eq!(
+            encode(signed | kAudioFormatFlagIsBigEndian, 16, 0x1234),
+            [0x12, 0x34]
+        );
+        assert_eq!(encode(signed, 24, 0x1234), [0x00, 0x34, 0x12]);
+        assert_eq!(encode(signed, 32, 0x1234), [0x00, 0x00, 0x34, 0x12]);
+        // 8.24 fixed-point, where 1.0 is 0x01000000
+        let fixed_8_24 = signed | (24 << kLinearPCMFormatFlagsSampleFractionShift);
+        assert_eq!(encode(fixed_8_24, 32, 0x4000), [0x00, 0x00, 0x80, 0x00]);
+        assert_eq!(
+            encode(kAudioFormatFlagIsFloat, 32, -0x4000),
+            (-0.5f32).to_le_bytes()
+        );
+        assert_eq!(
+            encode(kAudioFormatFlagIsFloat, 64, 0x4000),
+            0.5f64.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn channels() {
+        assert_eq!(convert_channels(vec![1, -2], 1, 2), [1, 1, -2, -2]);
+        assert_eq!(convert_channels(vec![10, 20, -4, 0], 2, 1), [15, -2]);
+        assert_eq!(convert_channels(vec![1, 2, 3], 3, 3), [1, 2, 3]);
+    }
+
+    #[test]
+    fn resampling() {
+        // Upsampling by 2: every other output frame is interpolated, and the
+        // last input frame is held.
+        assert_eq!(
+            resample_linear(&[0, 100, 200], 0, 1, 0.5, 0, 10),
+            [0, 50, 100, 150, 200, 200]
+        );
+        // Downsampling by 2, starting part of the way through.
+        assert_eq!(
+            resample_linear(&[20, 30, 40, 50, 60], 2, 1, 2.0, 2, 2),
+            [40, 60]
+        );
+        // Stereo
+        assert_eq!(
+            resample_linear(&[0, 10, 100, 110], 0, 2, 0.5, 1, 1),
+            [50, 60]
+        );
+    }
+}
@@ -0,0 +1,987 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Audio Unit framework (`AudioComponent.h`, `AUComponent.h`,
+//! `AudioOutputUnit.h`).
+//!
+//! Only the RemoteIO unit, which is the one that talks to the audio hardware,
+//! is implemented, and only for output. Apps attach a render callback to it
+//! and generate audio in that callback.
+//!
+//! On the iPhone, the callback is called on a real-time audio thread. touchHLE
+//! can only run guest code on the thread it belongs to, so instead the callback
+//! is called on the main thread's run loop, like audio queue callbacks, and the
+//! rendered audio is queued on an OpenAL source in touchHLE's internal context.
+//! To make up for the less regular timing, audio is rendered ahead by
+//! `--audio-latency=`, following the host's clock. The host audio device's
+//! clock never quite matches it, so the source's pitch is adjusted very
+//! slightly to keep the amount of audio queued constant.
+//! The buffers are the size the app asked for with
+//! `kAudioSessionProperty_PreferredHardwareIOBufferDuration`.
+//!
+//! Resources:
+//! - Apple's [Audio Unit Hosting Guide for iOS](https://developer.apple.com/library/archive/documentation/MusicAudio/Conceptual/AudioUnitHostingGuide_iOS/Introduction/Introduction.html)
+
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::audio::mixer;
+use crate::audio::openal as al;
+use crate::audio::openal::al_types::*;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::audio_toolbox::audio_queue::{make_al_context_current, unqueue_buffers};
+use crate::frameworks::audio_toolbox::audio_session::current_io_buffer_duration;
+use crate::frameworks::core_audio_types::{
+    buffer_in_list, debug_fourcc, fourcc, kAudioFormatFlagIsBigEndian, kAudioFormatFlagIsFloat,
+    kAudioFormatFlagIsNonInterleaved, kAudioFormatFlagIsPacked, kAudioFormatFlagIsSignedInteger,
+    kAudioFormatLinearPCM, kAudioTimeStampHostTimeValid, kAudioTimeStampSampleTimeValid,
+    kLinearPCMFormatFlagsSampleFractionMask, kLinearPCMFormatFlagsSampleFractionShift, AudioBuffer,
+    AudioBufferList, AudioStreamBasicDescription, AudioTimeStamp,
+};
+use crate::frameworks::mac_types::OSStatus;
+use crate::mem::{
+    guest_size_of, ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr, SafeRead,
+};
+use crate::Environment;
+use std::collections::HashMap;
+use std::time::Instant;
+
+#[derive(Default)]
+pub struct State {
+    /// Handle for the RemoteIO component, allocated on first use.
+    remote_io_component: Option<AudioComponent>,
+    audio_units: HashMap<AudioUnit, AudioUnitHostObject>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.audio_unit
+    }
+}
+
+struct AudioUnitHostObject {
+    /// The format of the audio the render callback provides (input scope of
+    /// the output element).
+    output_format: AudioStreamBasicDescription,
+    /// The format of audio from the microphone (output scope of the input
+    /// element). Only stored so it can be read back.
+    input_format: AudioStreamBasicDescription,
+    input_enabled: bool,
+    output_enabled: bool,
+    render_callback: Option<AURenderCallbackStruct>,
+    max_frames_per_slice: u32,
+    is_initialized: bool,
+    is_running: bool,
+    /// Number of frames rendered since the unit was started.
+    sample_time: f64,
+    al_source: Option<ALuint>,
+    al_unused_buffers: Vec<ALuint>,
+    /// Number of buffers queued on the OpenAL source.
+    al_queued_buffer_count: usize,
+    /// When the OpenAL source last started playing, and the sample time of
+    /// the audio it started with. Rendering follows the host clock from there.
+    /// [None] if it hasn't started yet, or ran out of audio and must restart.
+    clock_start: Option<(Instant, f64)>,
+    /// Guest memory passed to the render callback, allocated when the unit
+    /// starts: `AudioBufferList`, its data, the action flags and the time
+    /// stamp.
+    render_memory: Option<RenderMemory>,
+}
+
+#[derive(Copy, Clone)]
+struct RenderMemory {
+    buffer_list: MutPtr<AudioBufferList>,
+    data: MutPtr<u8>,
+    frame_count: u32,
+    action_flags: MutPtr<AudioUnitRenderActionFlags>,
+    time_stamp: MutPtr<AudioTimeStamp>,
+}
+
+#[repr(C, packed)]
+struct OpaqueAudioComponent {
+    _filler: u8,
+}
+unsafe impl SafeRead for OpaqueAudioComponent {}
+type AudioComponent = MutPtr<OpaqueAudioComponent>;
+
+#[repr(C, packed)]
+struct OpaqueAudioComponentInstance {
+    _filler: u8,
+}
+unsafe impl SafeRead for OpaqueAudioComponentInstance {}
+/// Also known as `AudioComponentInstance`.
+type AudioUnit = MutPtr<OpaqueAudioComponentInstance>;
+
+#[repr(C, packed)]
+struct AudioComponentDescription {
+    component_type: u32,
+    component_sub_type: u32,
+    component_manufacturer: u32,
+    _component_flags: u32,
+    _component_flags_mask: u32,
+}
+unsafe impl SafeRead for AudioComponentDescription {}
+
+const kAudioUnitType_Output: u32 = fourcc(b"auou");
+const kAudioUnitSubType_RemoteIO: u32 = fourcc(b"rioc");
+const kAudioUnitManufacturer_Apple: u32 = fourcc(b"appl");
+
+/// (*OSStatus)(void *in_ref_con, AudioUnitRenderActionFlags *io_action_flags,
+///             const AudioTimeStamp *in_time_stamp, u32 in_bus_number,
+///             u32 in_number_frames, AudioBufferList *io_data)
+type AURenderCallback = GuestFunction;
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C, packed)]
+struct AURenderCallbackStruct {
+    input_proc: AURenderCallback,
+    input_proc_ref_con: MutVoidPtr,
+}
+unsafe impl SafeRead for AURenderCallbackStruct {}
+
+type AudioUnitRenderActionFlags = u32;
+
+type AudioUnitPropertyID = u32;
+const kAudioUnitProperty_StreamFormat: AudioUnitPropertyID = 8;
+const kAudioUnitProperty_MaximumFramesPerSlice: AudioUnitPropertyID = 14;
+const kAudioUnitProperty_SetRenderCallback: AudioUnitPropertyID = 23;
+const kAudioUnitProperty_ShouldAllocateBuffer: AudioUnitPropertyID = 51;
+const kAudioOutputUnitProperty_EnableIO: AudioUnitPropertyID = 2003;
+const kAudioOutputUnitProperty_SetInputCallback: AudioUnitPropertyID = 2005;
+
+type AudioUnitScope = u32;
+const kAudioUnitScope_Global: AudioUnitScope = 0;
+const kAudioUnitScope_Input: AudioUnitScope = 1;
+const kAudioUnitScope_Output: AudioUnitScope = 2;
+
+type AudioUnitElement = u32;
+/// The element (bus) that is connected to the speaker.
+const OUTPUT_ELEMENT: AudioUnitElement = 0;
+/// The element (bus) that is connected to the microphone.
+const INPUT_ELEMENT: AudioUnitElement = 1;
+
+const kAudioUnitErr_InvalidProperty: OSStatus = -10879;
+const kAudioUnitErr_InvalidParameter: OSStatus = -10878;
+const kAudioUnitErr_InvalidElement: OSStatus = -10877;
+const kAudioUnitErr_Uninitialized: OSStatus = -10867;
+const kAudioUnitErr_FormatNotSupported: OSStatus = -10868;
+const kAudioUnitErr_InvalidScope: OSStatus = -10866;
+const kAudioUnitErr_PropertyNotWritable: OSStatus = -10865;
+const kAudioUnitErr_InvalidPropertyValue: OSStatus = -10851;
+
+/// `kAudioFormatFlagsAudioUnitCanonical`'s number of fractional bits.
+const kAudioUnitSampleFractionBits: u32 = 24;
+
+/// The default stream format, called the "canonical" format: 8.24 fixed-point
+/// non-interleaved stereo.
+fn canonical_format() -> AudioStreamBasicDescription {
+    AudioStreamBasicDescription {
+        sample_rate: 44100.0,
+        format_id: kAudioFormatLinearPCM,
+        format_flags: kAudioFormatFlagIsSignedInteger
+            | kAudioFormatFlagIsPacked
+            | kAudioFormatFlagIsNonInterleaved
+            | (kAudioUnitSampleFractionBits << kLinearPCMFormatFlagsSampleFractionShift),
+        bytes_per_packet: 4,
+        frames_per_packet: 1,
+        bytes_per_frame: 4,
+        channels_per_frame: 2,
+        bits_per_channel: 32,
+        _reserved: 0,
+    }
+}
+
+/// The default `kAudioUnitProperty_MaximumFramesPerSlice`.
+const DEFAULT_MAX_FRAMES_PER_SLICE: u32 = 1024;
+
+/// The minimum number of buffers to keep queued on the OpenAL source, however
+/// short `--audio-latency=` is.
+const MIN_QUEUED_BUFFERS: usize = 3;
+
+fn is_supported_format(format: &AudioStreamBasicDescription) -> bool {
+    let &AudioStreamBasicDescription {
+        sample_rate,
+        format_id,
+        format_flags,
+        frames_per_packet,
+        channels_per_frame,
+        bits_per_channel,
+        ..
+    } = format;
+    let fraction_bits = (format_flags & kLinearPCMFormatFlagsSampleFractionMask)
+        >> kLinearPCMFormatFlagsSampleFractionShift;
+    let is_float = (format_flags & kAudioFormatFlagIsFloat) != 0;
+    format_id == kAudioFormatLinearPCM
+        && sample_rate >= 1.0
+        && frames_per_packet == 1
+        && (1..=2).contains(&channels_per_frame)
+        && (format_flags & kAudioFormatFlagIsBigEndian) == 0
+        && match bits_per_channel {
+            16 => !is_float && fraction_bits == 0,
+            // Float, fixed-point or plain integer.
+            32 => !(is_float && fraction_bits != 0),
+            _ => false,
+        }
+        && (is_float || (format_flags & kAudioFormatFlagIsSignedInteger) != 0)
+}
+
+/// Bytes per channel per frame: the size of one sample.
+fn bytes_per_sample(format: &AudioStreamBasicDescription) -> u32 {
+    format.bits_per_channel / 8
+}
+
+fn is_non_interleaved(format: &AudioStreamBasicDescription) -> bool {
+    (format.format_flags & kAudioFormatFlagIsNonInterleaved) != 0
+}
+
+/// Convert one sample in a supported format to 16-bit signed integer PCM.
+fn sample_to_i16(format: &AudioStreamBasicDescription, bytes: &[u8]) -> i16 {
+    match bytes.len() {
+        2 => i16::from_le_bytes(bytes.try_into().unwrap()),
+        4 if (format.format_flags & kAudioFormatFlagIsFloat) != 0 => {
+            let sample = f32::from_le_bytes(bytes.try_into().unwrap());
+            (sample.clamp(-1.0, 1.0) * 32767.0) as i16
+        }
+        4 => {
+            let sample = i32::from_le_bytes(bytes.try_into().unwrap());
+            let fraction_bits = (format.format_flags & kLinearPCMFormatFlagsSampleFractionMask)
+                >> kLinearPCMFormatFlagsSampleFractionShift;
+            if fraction_bits == 0 {
+                (sample >> 16) as i16
+            } else {
+                // Fixed-point samples can go beyond ±1.0.
+                let shift = fraction_bits.saturating_sub(15);
+                (sample >> shift).clamp(i16::MIN.into(), i16::MAX.into()) as i16
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn AudioComponentFindNext(
+    env: &mut Environment,
+    in_component: AudioComponent,
+    in_desc: ConstPtr<AudioComponentDescription>,
+) -> AudioComponent {
+    let AudioComponentDescription {
+        component_type,
+        component_sub_type,
+        component_manufacturer,
+        ..
+    } = env.mem.read(in_desc);
+    // There's only one component, so searching after it finds nothing.
+    // Zero means "any" for each field.
+    if !in_component.is_null()
+        || ![0, kAudioUnitType_Output].contains(&component_type)
+        || ![0, kAudioUnitSubType_RemoteIO].contains(&component_sub_type)
+        || ![0, kAudioUnitManufacturer_Apple].contains(&component_manufacturer)
+    {
+        log!(
+            "Warning: AudioComponentFindNext() found nothing for type {}, subtype {}, manufacturer {}",
+            debug_fourcc(component_type),
+            debug_fourcc(component_sub_type),
+            debug_fourcc(component_manufacturer),
+        );
+        return Ptr::null();
+    }
+
+    let state = State::get(&mut env.framework_state);
+    *state
+        .remote_io_component
+        .get_or_insert_with(|| env.mem.alloc_and_write(OpaqueAudioComponent { _filler: 0 }))
+}
+
+fn AudioComponentInstanceNew(
+    env: &mut Environment,
+    in_component: AudioComponent,
+    out_instance: MutPtr<AudioUnit>,
+) -> OSStatus {
+    assert!(Some(in_component) == State::get(&mut env.framework_state).remote_io_component);
+
+    let host_object = AudioUnitHostObject {
+        output_format: canonical_format(),
+        input_format: canonical_format(),
+        input_enabled: false,
+        output_enabled: true,
+        render_callback: None,
+        max_frames_per_slice: DEFAULT_MAX_FRAMES_PER_SLICE,
+        is_initialized: false,
+        is_running: false,
+        sample_time: 0.0,
+        al_source: None,
+        al_unused_buffers: Vec::new(),
+        al_queued_buffer_count: 0,
+        clock_start: None,
+        render_memory: None,
+    };
+    let unit = env
+        .mem
+        .alloc_and_write(OpaqueAudioComponentInstance { _filler: 0 });
+    State::get(&mut env.framework_state)
+        .audio_units
+        .insert(unit, host_object);
+    env.mem.write(out_instance, unit);
+    log_dbg!("AudioComponentInstanceNew() => {:?}", unit);
+    0 // success
+}
+
+fn AudioComponentInstanceDispose(env: &mut Environment, in_instance: AudioUnit) -> OSStatus {
+    stop(env, in_instance);
+    let host_object = State::get(&mut env.framework_state)
+        .audio_units
+        .remove(&in_instance)
+        .unwrap();
+    if let Some(al_source) = host_object.al_source {
+        let _context_manager = make_al_context_current(env);
+        unsafe {
+            al::alDeleteSources(1, &al_source);
+            al::alDeleteBuffers(
+                host_object.al_unused_buffers.len().try_into().unwrap(),
+                host_object.al_unused_buffers.as_ptr(),
+            );
+            assert!(al::alGetError() == 0);
+        }
+    }
+    env.mem.free(in_instance.cast());
+    log_dbg!("AudioComponentInstanceDispose({:?})", in_instance);
+    0 // success
+}
+
+fn AudioUnitInitialize(env: &mut Environment, in_unit: AudioUnit) -> OSStatus {
+    let host_object = State::get(&mut env.framework_state)
+        .audio_units
+        .get_mut(&in_unit)
+        .unwrap();
+    host_object.is_initialized = true;
+    0 // success
+}
+
+fn AudioUnitUninitialize(env: &mut Environment, in_unit: AudioUnit) -> OSStatus {
+    stop(env, in_unit);
+    let host_object = State::get(&mut env.framework_state)
+        .audio_units
+        .get_mut(&in_unit)
+        .unwrap();
+    host_object.is_initialized = false;
+    0 // success
+}
+
+/// The size of a property's value, or [None] if it's not supported.
+fn property_size(in_id: AudioUnitPropertyID) -> Option<GuestUSize> {
+    match in_id {
+        kAudioUnitProperty_StreamFormat => Some(guest_size_of::<AudioStreamBasicDescription>()),
+        kAudioUnitProperty_SetRenderCallback | kAudioOutputUnitProperty_SetInputCallback => {
+            Some(guest_size_of::<AURenderCallbackStruct>())
+        }
+        kAudioUnitProperty_MaximumFramesPerSlice
+        | kAudioUnitProperty_ShouldAllocateBuffer
+        | kAudioOutputUnitProperty_EnableIO => Some(guest_size_of::<u32>()),
+        _ => None,
+    }
+}
+
+fn AudioUnitGetPropertyInfo(
+    env: &mut Environment,
+    in_unit: AudioUnit,
+    in_id: AudioUnitPropertyID,
+    _in_scope: AudioUnitScope,
+    _in_element: AudioUnitElement,
+    out_data_size: MutPtr<u32>,
+    out_writable: MutPtr<bool>,
+) -> OSStatus {
+    assert!(State::get(&mut env.framework_state)
+        .audio_units
+        .contains_key(&in_unit));
+    let Some(size) = property_size(in_id) else {
+        log!(
+            "Warning: AudioUnitGetPropertyInfo() for unsupported property {}",
+            in_id
+        );
+        return kAudioUnitErr_InvalidProperty;
+    };
+    if !out_data_size.is_null() {
+        env.mem.write(out_data_size, size);
+    }
+    if !out_writable.is_null() {
+        env.mem.write(out_writable, true);
+    }
+    0 // success
+}
+
+fn AudioUnitGetProperty(
+    env: &mut Environment,
+    in_unit: AudioUnit,
+    in_id: AudioUnitPropertyID,
+    in_scope: AudioUnitScope,
+    in_element: AudioUnitElement,
+    out_data: MutVoidPtr,
+    io_data_size: MutPtr<u32>,
+) -> OSStatus {
+    let Some(size) = property_size(in_id) else {
+        log!(
+            "Warning: AudioUnitGetProperty() for unsupported property {}",
+            in_id
+        );
+        return kAudioUnitErr_InvalidProperty;
+    };
+    if env.mem.read(io_data_size) < size {
+        return kAudioUnitErr_InvalidParameter;
+    }
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_units
+        .get_mut(&in_unit)
+        .unwrap();
+    match in_id {
+        kAudioUnitProperty_StreamFormat => {
+            let format = match (in_scope, in_element) {
+                (kAudioUnitScope_Input, OUTPUT_ELEMENT) => host_object.output_format,
+                (kAudioUnitScope_Output, INPUT_ELEMENT) => host_object.input_format,
+                // The hardware side of the unit. The unit converts to and from
+                // the canonical format.
+                (kAudioUnitScope_Output, OUTPUT_ELEMENT)
+                | (kAudioUnitScope_Input, INPUT_ELEMENT) => canonical_format(),
+                _ => return kAudioUnitErr_InvalidScope,
+            };
+            env.mem.write(out_data.cast(), format);
+        }
+        kAudioUnitProperty_MaximumFramesPerSlice => {
+            let max_frames_per_slice = host_object.max_frames_per_slice;
+            env.mem.write(out_data.cast(), max_frames_per_slice);
+        }
+        kAudioOutputUnitProperty_EnableIO => {
+            let enabled = match in_element {
+                OUTPUT_ELEMENT => host_object.output_enabled,
+                INPUT_ELEMENT => host_object.input_enabled,
+                _ => return kAudioUnitErr_InvalidElement,
+            };
+            env.mem.write(out_data.cast(), u32::from(enabled));
+        }
+        kAudioUnitProperty_ShouldAllocateBuffer => {
+            env.mem.write(out_data.cast(), 1u32);
+        }
+        _ => {
+            log!(
+                "Warning: AudioUnitGetProperty() for write-only property {}",
+                in_id
+            );
+            return kAudioUnitErr_InvalidProperty;
+        }
+    }
+    env.mem.write(io_data_size, size);
+    0 // success
+}
+
+fn AudioUnitSetProperty(
+    env: &mut Environment,
+    in_unit: AudioUnit,
+    in_id: AudioUnitPropertyID,
+    in_scope: AudioUnitScope,
+    in_element: AudioUnitElement,
+    in_data: ConstVoidPtr,
+    in_data_size: u32,
+) -> OSStatus {
+    let Some(size) = property_size(in_id) else {
+        log!(
+            "Warning: AudioUnitSetProperty() for unsupported property {}",
+            in_id
+        );
+        return kAudioUnitErr_InvalidProperty;
+    };
+    if in_data_size != size {
+        return kAudioUnitErr_InvalidParameter;
+    }
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_units
+        .get_mut(&in_unit)
+        .unwrap();
+    match in_id {
+        kAudioUnitProperty_StreamFormat => {
+            let format: AudioStreamBasicDescription = env.mem.read(in_data.cast());
+            match (in_scope, in_element) {
+                (kAudioUnitScope_Input, OUTPUT_ELEMENT) => {
+                    if !is_supported_format(&format) {
+                        log!(
+                            "Warning: RemoteIO unit {:?} doesn't support stream format {:#?}",
+                            in_unit,
+                            format
+                        );
+                        return kAudioUnitErr_FormatNotSupported;
+                    }
+                    if host_object.is_running {
+                        return kAudioUnitErr_PropertyNotWritable;
+                    }
+                    log_dbg!("RemoteIO unit {:?} stream format: {:#?}", in_unit, format);
+                    host_object.output_format = format;
+                }
+                (kAudioUnitScope_Output, INPUT_ELEMENT) => host_object.input_format = format,
+                (kAudioUnitScope_Output, OUTPUT_ELEMENT)
+                | (kAudioUnitScope_Input, INPUT_ELEMENT) => {
+                    return kAudioUnitErr_PropertyNotWritable;
+                }
+                _ => return kAudioUnitErr_InvalidScope,
+            }
+        }
+        kAudioUnitProperty_SetRenderCallback => {
+            if ![kAudioUnitScope_Input, kAudioUnitScope_Global].contains(&in_scope)
+                || in_element != OUTPUT_ELEMENT
+            {
+                return kAudioUnitErr_InvalidScope;
+            }
+            let callback: AURenderCallbackStruct = env.mem.read(in_data.cast());
+            log_dbg!(
+                "RemoteIO unit {:?} render callback: {:?}",
+                in_unit,
+                callback
+            );
+            host_object.render_callback = if callback.input_proc.addr_with_thumb_bit() == 0 {
+                None
+            } else {
+                Some(callback)
+            };
+        }
+        kAudioOutputUnitProperty_SetInputCallback => {
+            // touchHLE has no microphone, so this would never be called.
+            log_dbg!(
+                "RemoteIO unit {:?} input callback set, but it won't be called",
+                in_unit
+            );
+        }
+        kAudioUnitProperty_MaximumFramesPerSlice => {
+            let max_frames_per_slice: u32 = env.mem.read(in_data.cast());
+            if max_frames_per_slice == 0 {
+                return kAudioUnitErr_InvalidPropertyValue;
+            }
+            host_object.max_frames_per_slice = max_frames_per_slice;
+        }
+        kAudioOutputUnitProperty_EnableIO => {
+            let enabled: u32 = env.mem.read(in_data.cast());
+            match in_element {
+                OUTPUT_ELEMENT => host_object.output_enabled = enabled != 0,
+                INPUT_ELEMENT => {
+                    if enabled != 0 {
+                        log!("Warning: RemoteIO unit {:?} input enabled, but touchHLE doesn't support recording. Silence will be recorded.", in_unit);
+                    }
+                    host_object.input_enabled = enabled != 0;
+                }
+                _ => return kAudioUnitErr_InvalidElement,
+            }
+        }
+        kAudioUnitProperty_ShouldAllocateBuffer => (),
+        _ => unreachable!(),
+    }
+    0 // success
+}
+
+fn AudioUnitRender(
+    env: &mut Environment,
+    in_unit: AudioUnit,
+    _io_action_flags: MutPtr<AudioUnitRenderActionFlags>,
+    _in_time_stamp: ConstPtr<AudioTimeStamp>,
+    in_output_bus_number: u32,
+    in_number_frames: u32,
+    io_data: MutPtr<AudioBufferList>,
+) -> OSStatus {
+    // This is only useful for getting audio from the microphone, and there's no
+    // microphone, so the result is silence.
+    assert!(in_output_bus_number == INPUT_ELEMENT);
+    let host_object = State::get(&mut env.framework_state)
+        .audio_units
+        .get(&in_unit)
+        .unwrap();
+    if !host_object.is_initialized {
+        return kAudioUnitErr_Uninitialized;
+    }
+    let number_buffers = env.mem.read(io_data).number_buffers;
+    for i in 0..number_buffers {
+        let buffer_ptr = buffer_in_list(io_data, i);
+        let AudioBuffer {
+            data,
+            data_byte_size,
+            ..
+        } = env.mem.read(buffer_ptr);
+        if !data.is_null() {
+            env.mem.bytes_at_mut(data.cast(), data_byte_size).fill(0);
+        }
+    }
+    log_dbg!(
+        "AudioUnitRender() for {:?} rendered {} frames of silence",
+        in_unit,
+        in_number_frames
+    );
+    0 // success
+}
+
+fn AudioOutputUnitStart(env: &mut Environment, ci: AudioUnit) -> OSStatus {
+    let host_object = State::get(&mut env.framework_state)
+        .audio_units
+        .get_mut(&ci)
+        .unwrap();
+    if !host_object.is_initialized {
+        return kAudioUnitErr_Uninitialized;
+    }
+    if host_object.is_running {
+        return 0;
+    }
+    host_object.is_running = true;
+    host_object.sample_time = 0.0;
+    host_object.clock_start = None;
+
+    let format = host_object.output_format;
+    let max_frames_per_slice = host_object.max_frames_per_slice;
+    let io_buffer_duration = f64::from(current_io_buffer_duration(env));
+    // The hardware uses power-of-two buffer sizes.
+    let frame_count = ((io_buffer_duration * format.sample_rate) as u32)
+        .max(1)
+        .next_power_of_two()
+        .min(max_frames_per_slice);
+
+    // Allocate the memory for calling the render callback.
+    let buffer_count = if is_non_interleaved(&format) {
+        format.channels_per_frame
+    } else {
+        1
+    };
+    let buffer_list_size = 4 + buffer_count * guest_size_of::<AudioBuffer>();
+    let buffer_list: MutPtr<AudioBufferList> = env.mem.alloc(buffer_list_size).cast();
+    let data_size = frame_count * bytes_per_sample(&format) * format.channels_per_frame;
+    let data: MutPtr<u8> = env.mem.alloc(data_size).cast();
+    let action_flags = env.mem.alloc_and_write(0);
+    let time_stamp = env.mem.alloc_and_write(AudioTimeStamp::default());
+    env.mem.write(buffer_list.cast(), buffer_count);
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_units
+        .get_mut(&ci)
+        .unwrap();
+    host_object.render_memory = Some(RenderMemory {
+        buffer_list,
+        data,
+        frame_count,
+        action_flags,
+        time_stamp,
+    });
+
+    log_dbg!(
+        "AudioOutputUnitStart() for {:?}, rendering {} frames at a time",
+        ci,
+        frame_count
+    );
+    0 // success
+}
+
+fn AudioOutputUnitStop(env: &mut Environment, ci: AudioUnit) -> OSStatus {
+    stop(env, ci);
+    log_dbg!("AudioOutputUnitStop() for {:?}", ci);
+    0 // success
+}
+
+/// Stop an audio unit's OpenAL source and free its render memory, if it's
+/// running.
+fn stop(env: &mut Environment, unit: AudioUnit) {
+    let host_object = State::get(&mut env.framework_state)
+        .audio_units
+        .get_mut(&unit)
+        .unwrap();
+    if !host_object.is_running {
+        return;
+    }
+    host_object.is_running = false;
+    let render_memory = host_object.render_memory.take().unwrap();
+
+    if let Some(al_source) = host_object.al_source {
+        let _context_manager = make_al_context_current(env);
+        unsafe {
+            al::alSourceStop(al_source);
+            assert!(al::alGetError() == 0);
+        }
+        // Stopping a source marks all its buffers as processed.
+        let host_object = State::get(&mut env.framework_state)
+            .audio_units
+            .get_mut(&unit)
+            .unwrap();
+        unqueue_buffers(al_source, |al_buffer| {
+            host_object.al_unused_buffers.push(al_buffer);
+        });
+        host_object.al_queued_buffer_count = 0;
+    }
+
+    let RenderMemory {
+        buffer_list,
+        data,
+        action_flags,
+        time_stamp,
+        ..
+    } = render_memory;
+    env.mem.free(buffer_list.cast());
+    env.mem.free(data.cast());
+    env.mem.free(action_flags.cast());
+    env.mem.free(time_stamp.cast());
+}
+
+/// Call an audio unit's render callback once and return the result as
+/// interleaved 16-bit samples.
+fn render(env: &mut Environment, unit: AudioUnit) -> Vec<i16> {
+    let host_object = &State::get(&mut env.framework_state).audio_units[&unit];
+    let format = host_object.output_format;
+    let callback = host_object.render_callback.unwrap();
+    let sample_time = host_object.sample_time;
+    let RenderMemory {
+        buffer_list,
+        data,
+        frame_count,
+        action_flags,
+        time_stamp,
+    } = host_object.render_memory.unwrap();
+
+    let channels = format.channels_per_frame;
+    let sample_size = bytes_per_sample(&format);
+    let data_size = frame_count * sample_size * channels;
+    let non_interleaved = is_non_interleaved(&format);
+    let buffer_count = if non_interleaved { channels } else { 1 };
+
+    // The callback might have changed these, so they're set each time.
+    for i in 0..buffer_count {
+        let buffer_size = data_size / buffer_count;
+        env.mem.write(
+            buffer_in_list(buffer_list, i),
+            AudioBuffer {
+                number_channels: if non_interleaved { 1 } else { channels },
+                data_byte_size: buffer_size,
+                data: (data + i * buffer_size).cast(),
+            },
+        );
+    }
+    env.mem.bytes_at_mut(data, data_size).fill(0);
+    env.mem.write(action_flags, 0);
+    let host_time = env.clock.uptime().as_nanos().try_into().unwrap();
+    env.mem.write(
+        time_stamp,
+        AudioTimeStamp {
+            sample_time,
+            host_time,
+            rate_scalar: 1.0,
+            flags: kAudioTimeStampSampleTimeValid | kAudioTimeStampHostTimeValid,
+            ..Default::default()
+        },
+    );
+
+    let AURenderCallbackStruct {
+        input_proc,
+        input_proc_ref_con,
+    } = callback;
+    let result: OSStatus = input_proc.call_from_host(
+        env,
+        (
+            input_proc_ref_con,
+            action_flags,
+            time_stamp.cast_const(),
+            OUTPUT_ELEMENT,
+            frame_count,
+            buffer_list,
+        ),
+    );
+    if result != 0 {
+        log_dbg!(
+            "Render callback for audio unit {:?} returned error {}, using silence",
+            unit,
+            result
+        );
+        env.mem.bytes_at_mut(data, data_size).fill(0);
+    }
+
+    let bytes = env.mem.bytes_at(data.cast_const(), data_size);
+    let mut samples = Vec::with_capacity((frame_count * channels) as usize);
+    if non_interleaved {
+        let (channels, sample_size) = (channels as usize, sample_size as usize);
+        let buffer_size = bytes.len() / channels;
+        for frame in 0..(frame_count as usize) {
+            for channel in 0..channels {
+                let offset = channel * buffer_size + frame * sample_size;
+                samples.push(sample_to_i16(&format, &bytes[offset..offset + sample_size]));
+            }
+        }
+    } else {
+        samples.extend(
+            bytes
+                .chunks_exact(sample_size as usize)
+                .map(|sample| sample_to_i16(&format, sample)),
+        );
+    }
+    samples
+}
+
+/// For use by `NSRunLoop`: call the render callbacks of running audio units
+/// to keep enough audio queued.
+pub fn handle_audio_units(env: &mut Environment) {
+    let units: Vec<AudioUnit> = State::get(&mut env.framework_state)
+        .audio_units
+        .keys()
+        .copied()
+        .collect();
+    for unit in units {
+        handle_audio_unit(env, unit);
+    }
+}
+
+fn handle_audio_unit(env: &mut Environment, unit: AudioUnit) {
+    // The unit may have been disposed of by a callback for another unit.
+    let Some(host_object) = State::get(&mut env.framework_state).audio_units.get(&unit) else {
+        return;
+    };
+    if !host_object.is_running
+        || !host_object.output_enabled
+        || host_object.render_callback.is_none()
+    {
+        return;
+    }
+
+    // Recycle finished buffers.
+    let al_source = {
+        let context_manager = make_al_context_current(env);
+        let host_object = State::get(&mut env.framework_state)
+            .audio_units
+            .get_mut(&unit)
+            .unwrap();
+        let al_source = match host_object.al_source {
+            Some(al_source) => al_source,
+            None => {
+                let mut al_source = 0;
+                unsafe {
+                    al::alGenSources(1, &mut al_source);
+                    assert!(al::alGetError() == 0);
+                }
+                mixer::configure_source(al_source, &env.options);
+                al_source
+            }
+        };
+        let host_object = State::get(&mut env.framework_state)
+            .audio_units
+            .get_mut(&unit)
+            .unwrap();
+        host_object.al_source = Some(al_source);
+        unqueue_buffers(al_source, |al_buffer| {
+            host_object.al_unused_buffers.push(al_buffer);
+            host_object.al_queued_buffer_count -= 1;
+        });
+        // If the source ran out of audio, the clock must restart with it.
+        let mut al_source_state = 0;
+        unsafe {
+            al::alGetSourcei(al_source, al::AL_SOURCE_STATE, &mut al_source_state);
+            assert!(al::alGetError() == 0);
+        }
+        if al_source_state != al::AL_PLAYING {
+            host_object.clock_start = None;
+        }
+        drop(context_manager);
+        al_source
+    };
+
+    let latency = env.options.audio_latency;
+    loop {
+        let host_object = &State::get(&mut env.framework_state).audio_units[&unit];
+        let format = host_object.output_format;
+        let ahead = match host_object.clock_start {
+            Some((start_time, start_sample_time)) => {
+                (host_object.sample_time - start_sample_time) / format.sample_rate
+                    - start_time.elapsed().as_secs_f64()
+            }
+            // Before the source (re)starts, fill it up to the latency.
+            None => {
+                let frame_count = host_object.render_memory.unwrap().frame_count;
+                host_object.al_queued_buffer_count as f64 * f64::from(frame_count)
+                    / format.sample_rate
+            }
+        };
+        if ahead >= latency && host_object.al_queued_buffer_count >= MIN_QUEUED_BUFFERS {
+            break;
+        }
+        let frame_count = host_object.render_memory.unwrap().frame_count;
+
+        // The context isn't current while the callback runs, in case the app
+        // uses OpenAL itself.
+        let samples = render(env, unit);
+
+        // The callback may have stopped or disposed of the unit.
+        let Some(host_object) = State::get(&mut env.framework_state)
+            .audio_units
+            .get_mut(&unit)
+        else {
+            return;
+        };
+        if !host_object.is_running {
+            return;
+        }
+        host_object.sample_time += f64::from(frame_count);
+        let al_buffer = host_object.al_unused_buffers.pop();
+        host_object.al_queued_buffer_count += 1;
+
+        let _context_manager = make_al_context_current(env);
+        let al_format = match format.channels_per_frame {
+            1 => al::AL_FORMAT_MONO16,
+            2 => al::AL_FORMAT_STEREO16,
+            _ => unreachable!(),
+        };
+        unsafe {
+            let al_buffer = al_buffer.unwrap_or_else(|| {
+                let mut al_buffer = 0;
+                al::alGenBuffers(1, &mut al_buffer);
+                al_buffer
+            });
+            al::alBufferData(
+                al_buffer,
+                al_format,
+                samples.as_ptr() as *const ALvoid,
+                (samples.len() * 2).try_into().unwrap(),
+                format.sample_rate as ALsizei,
+            );
+            al::alSourceQueueBuffers(al_source, 1, &al_buffer);
+            assert!(al::alGetError() == 0);
+        }
+    }
+
+    let _context_manager = make_al_context_current(env);
+    let host_object = State::get(&mut env.framework_state)
+        .audio_units
+        .get_mut(&unit)
+        .unwrap();
+    let format = host_object.output_format;
+    let frame_count = host_object.render_memory.unwrap().frame_count;
+    let queued_frames = host_object.al_queued_buffer_count as f64 * f64::from(frame_count);
+    if host_object.clock_start.is_none() {
+        // Start the source, or restart it if it ran out of audio.
+        host_object.clock_start = Some((Instant::now(), host_object.sample_time - queued_frames));
+        unsafe {
+            al::alSourcef(al_source, al::AL_PITCH, 1.0);
+            al::alSourcePlay(al_source);
+            assert!(al::alGetError() == 0);
+        }
+    } else {
+        // Correct for drift between the host clock and the device's clock.
+        let mut played_frames = 0;
+        unsafe {
+            al::alGetSourcei(al_source, al::AL_SAMPLE_OFFSET, &mut played_frames);
+            assert!(al::alGetError() == 0);
+        }
+        let queued = (queued_frames - f64::from(played_frames)) / format.sample_rate;
+        let pitch = mixer::drift_correction_pitch(queued, latency);
+        unsafe {
+            al::alSourcef(al_source, al::AL_PITCH, pitch);
+            assert!(al::alGetError() == 0);
+        }
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(AudioComponentFindNext(_, _)),
+    export_c_func!(AudioComponentInstanceNew(_, _)),
+    export_c_func!(AudioComponentInstanceDispose(_)),
+    export_c_func!(AudioUnitInitialize(_)),
+    export_c_func!(AudioUnitUninitialize(_)),
+    export_c_func!(AudioUnitGetPropertyInfo(_, _, _, _, _, _)),
+    export_c_func!(AudioUnitGetProperty(_, _, _, _, _, _)),
+    export_c_func!(AudioUnitSetProperty(_, _, _, _, _, _)),
+    export_c_func!(AudioUnitRender(_, _, _, _, _, _)),
+    export_c_func!(AudioOutputUnitStart(_)),
+    export_c_func!(AudioOutputUnitStop(_)),
+];
@@ -0,0 +1,453 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `AVAudioPlayer`.
+//!
+//! The whole file is decoded to 16-bit linear PCM when the player is created
+//! and played with a single OpenAL source in touchHLE's internal context (the
+//! same one used by Audio Queue Services). Apps mostly use this class for
+//! background music, so this is simpler than streaming, and the decoding is
+//! done in the same place as for the other audio APIs (see [crate::audio]).
+
+use crate::audio; // Keep this module namespaced to avoid confusion
+use crate::audio::openal as al;
+use crate::audio::openal::al_types::*;
+use crate::frameworks::audio_toolbox::audio_queue::make_al_context_current;
+use crate::frameworks::foundation::ns_url::to_rust_path;
+use crate::frameworks::foundation::{NSInteger, NSTimeInterval, NSUInteger};
+use crate::mem::{ConstVoidPtr, MutPtr};
+use crate::objc::{
+    id, msg, msg_send, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// Players that are currently playing. Strong references, so a player
+    /// isn't deallocated while it's audible.
+    playing: Vec<id>,
+    /// Players that were paused by an interruption. Strong references.
+    interrupted: Vec<id>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.av_foundation.av_audio_player
+    }
+}
+
+struct AVAudioPlayerHostObject {
+    /// `NSURL*`, strong reference. Nil if created from data.
+    url: id,
+    /// `NSData*`, strong reference. Nil if created from a URL.
+    data: id,
+    /// Weak reference.
+    delegate: id,
+    /// Interleaved samples.
+    samples: Vec<i16>,
+    channels: u32,
+    sample_rate: u32,
+    /// Created by `prepareToPlay` (or `play`), along with `al_buffer`.
+    al_source: Option<ALuint>,
+    al_buffer: Option<ALuint>,
+    volume: f32,
+    number_of_loops: NSInteger,
+    /// How many more times the sound will be restarted when it finishes.
+    loops_remaining: NSInteger,
+    is_playing: bool,
+}
+impl HostObject for AVAudioPlayerHostObject {}
+
+fn borrow(env: &mut Environment, player: id) -> &mut AVAudioPlayerHostObject {
+    env.objc.borrow_mut(player)
+}
+
+/// Decode an audio file in memory and set up the player with it. Returns
+/// `false` if the format is unsupported.
+fn init_with_bytes(
+    env: &mut Environment,
+    this: id,
+    bytes: Vec<u8>,
+    extension: Option<&str>,
+) -> bool {
+    let Ok(mut audio_file) = audio::AudioFile::open_from_bytes(bytes, extension) else {
+        return false;
+    };
+    let audio::AudioDescription {
+        sample_rate,
+        channels_per_frame,
+        ..
+    } = audio_file.audio_description();
+    // OpenAL only has mono and stereo formats.
+    if !(1..=2).contains(&channels_per_frame) {
+        log!(
+            "Warning: AVAudioPlayer can't play audio with {} channels",
+            channels_per_frame
+        );
+        return false;
+    }
+    let frame_count: usize = audio_file.frame_count().try_into().unwrap();
+    let mut samples = vec![0i16; frame_count * channels_per_frame as usize];
+    let Ok(frames_read) = audio_file.read_pcm_frames(0, &mut samples) else {
+        return false;
+    };
+    samples.truncate(frames_read * channels_per_frame as usize);
+
+    let host_object = borrow(env, this);
+    host_object.samples = samples;
+    host_object.channels = channels_per_frame;
+    host_object.sample_rate = sample_rate as u32;
+    true
+}
+
+/// Create the OpenAL source and buffer, if that hasn't happened yet.
+fn prepare(env: &mut Environment, this: id) -> ALuint {
+    if let Some(al_source) = borrow(env, this).al_source {
+        return al_source;
+    }
+
+    let _context_manager = make_al_context_current(env);
+    let host_object = borrow(env, this);
+    let format = match host_object.channels {
+        1 => al::AL_FORMAT_MONO16,
+        2 => al::AL_FORMAT_STEREO16,
+        _ => unreachable!(),
+    };
+    let mut al_source = 0;
+    let mut al_buffer = 0;
+    unsafe {
+        al::alGenSources(1, &mut al_source);
+        al::alGenBuffers(1, &mut al_buffer);
+        al::alBufferData(
+            al_buffer,
+            format,
+            host_object.samples.as_ptr() as *const ALvoid,
+            (host_object.samples.len() * 2).try_into().unwrap(),
+            host_object.sample_rate.try_into().unwrap(),
+        );
+        al::alSourcei(al_source, al::AL_BUFFER, al_buffer.try_into().unwrap());
+        al::alSourcef(al_source, al::AL_GAIN, host_object.volume);
+        assert!(al::alGetError() == al::AL_NO_ERROR);
+    }
+    host_object.al_source = Some(al_source);
+    host_object.al_buffer = Some(al_buffer);
+    audio::mixer::configure_source(al_source, &env.options);
+    al_source
+}
+
+/// Pause the player's source and stop tracking it as playing.
+fn pause(env: &mut Environment, this: id) {
+    let host_object = borrow(env, this);
+    if !host_object.is_playing {
+        return;
+    }
+    host_object.is_playing = false;
+    let al_source = host_object.al_source.unwrap();
+    {
+        let _context_manager = make_al_context_current(env);
+        unsafe { al::alSourcePause(al_source) };
+    }
+    let playing = &mut State::get(&mut env.framework_state).playing;
+    let index = playing.iter().position(|&other| other == this).unwrap();
+    playing.remove(index);
+    release(env, this);
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation AVAudioPlayer: NSObject
+
++ (id)alloc {
+    let host_object = Box::new(AVAudioPlayerHostObject {
+        url: nil,
+        data: nil,
+        delegate: nil,
+        samples: Vec::new(),
+        channels: 0,
+        sample_rate: 0,
+        al_source: None,
+        al_buffer: None,
+        volume: 1.0,
+        number_of_loops: 0,
+        loops_remaining: 0,
+        is_playing: false,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithContentsOfURL:(id)url // NSURL*
+                      error:(MutPtr<id>)error { // NSError**
+    let path = to_rust_path(env, url);
+    let extension = path
+        .file_name()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension);
+    let success = match env.fs.read(&path) {
+        Ok(bytes) => init_with_bytes(env, this, bytes, extension),
+        Err(()) => false,
+    };
+    if !success {
+        log!("Warning: AVAudioPlayer couldn't open {:?}, returning nil", path);
+        if !error.is_null() {
+            // TODO: Return an actual NSError.
+            env.mem.write(error, nil);
+        }
+        release(env, this);
+        return nil;
+    }
+    retain(env, url);
+    borrow(env, this).url = url;
+    this
+}
+
+- (id)initWithData:(id)data // NSData*
+             error:(MutPtr<id>)error { // NSError**
+    let bytes: ConstVoidPtr = msg![env; data bytes];
+    let length: NSUInteger = msg![env; data length];
+    let bytes = env.mem.bytes_at(bytes.cast(), length).to_vec();
+    if !init_with_bytes(env, this, bytes, None) {
+        log!("Warning: AVAudioPlayer couldn't decode data {:?}, returning nil", data);
+        if !error.is_null() {
+            // TODO: Return an actual NSError.
+            env.mem.write(error, nil);
+        }
+        release(env, this);
+        return nil;
+    }
+    retain(env, data);
+    borrow(env, this).data = data;
+    this
+}
+
+- (())dealloc {
+    let &mut AVAudioPlayerHostObject {
+        url,
+        data,
+        al_source,
+        al_buffer,
+        ..
+    } = borrow(env, this);
+    if let (Some(al_source), Some(al_buffer)) = (al_source, al_buffer) {
+        let _context_manager = make_al_context_current(env);
+        unsafe {
+            al::alDeleteSources(1, &al_source);
+            al::alDeleteBuffers(1, &al_buffer);
+        }
+    }
+    release(env, url);
+    release(env, data);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)url {
+    borrow(env, this).url
+}
+- (id)data {
+    borrow(env, this).data
+}
+
+- (id)delegate {
+    borrow(env, this).delegate
+}
+- (())setDelegate:(id)delegate {
+    borrow(env, this).delegate = delegate;
+}
+
+- (bool)prepareToPlay {
+    prepare(env, this);
+    true
+}
+
+- (bool)play {
+    let al_source = prepare(env, this);
+    let host_object = borrow(env, this);
+    if host_object.is_playing {
+        return true;
+    }
+    host_object.is_playing = true;
+    host_object.loops_remaining = host_object.number_of_loops.max(0);
+    // A negative number of loops means looping forever.
+    let looping = host_object.number_of_loops < 0;
+    {
+        let _context_manager = make_al_context_current(env);
+        unsafe {
+            al::alSourcei(al_source, al::AL_LOOPING, looping.into());
+            al::alSourcePlay(al_source);
+        }
+    }
+    retain(env, this);
+    State::get(&mut env.framework_state).playing.push(this);
+    log_dbg!("Playing AVAudioPlayer {:?}", this);
+    true
+}
+
+- (())pause {
+    pause(env, this);
+}
+
+// Unlike pause, this undoes what prepareToPlay does, but there's no reason to
+// do that here. The playback position is kept in both cases.
+- (())stop {
+    pause(env, this);
+}
+
+- (bool)isPlaying {
+    borrow(env, this).is_playing
+}
+
+- (NSInteger)numberOfLoops {
+    borrow(env, this).number_of_loops
+}
+- (())setNumberOfLoops:(NSInteger)number_of_loops {
+    let host_object = borrow(env, this);
+    host_object.number_of_loops = number_of_loops;
+    if !host_object.is_playing {
+        return;
+    }
+    host_object.loops_remaining = number_of_loops.max(0);
+    let al_source = host_object.al_source.unwrap();
+    let _context_manager = make_al_context_current(env);
+    unsafe { al::alSourcei(al_source, al::AL_LOOPING, (number_of_loops < 0).into()) };
+}
+
+- (f32)volume {
+    borrow(env, this).volume
+}
+- (())setVolume:(f32)volume {
+    let host_object = borrow(env, this);
+    host_object.volume = volume;
+    if let Some(al_source) = host_object.al_source {
+        let _context_manager = make_al_context_current(env);
+        unsafe { al::alSourcef(al_source, al::AL_GAIN, volume) };
+    }
+}
+
+- (NSUInteger)numberOfChannels {
+    borrow(env, this).channels
+}
+
+- (NSTimeInterval)duration {
+    let host_object = borrow(env, this);
+    let frame_count = host_object.samples.len() / host_object.channels as usize;
+    frame_count as NSTimeInterval / NSTimeInterval::from(host_object.sample_rate)
+}
+
+- (NSTimeInterval)currentTime {
+    let Some(al_source) = borrow(env, this).al_source else {
+        return 0.0;
+    };
+    let _context_manager = make_al_context_current(env);
+    let mut offset = 0.0;
+    unsafe { al::alGetSourcef(al_source, al::AL_SEC_OFFSET, &mut offset) };
+    offset.into()
+}
+- (())setCurrentTime:(NSTimeInterval)current_time {
+    let al_source = prepare(env, this);
+    let _context_manager = make_al_context_current(env);
+    unsafe { al::alSourcef(al_source, al::AL_SEC_OFFSET, current_time as f32) };
+}
+
+@end
+
+};
+
+fn send_to_delegate(env: &mut Environment, player: id, selector: &str) {
+    let delegate = borrow(env, player).delegate;
+    if delegate == nil {
+        return;
+    }
+    let selector = env.objc.lookup_selector(selector).unwrap();
+    if msg![env; delegate respondsToSelector:selector] {
+        let () = msg_send(env, (delegate, selector, player));
+    }
+}
+
+/// For use by `NSRunLoop`: restart players that should loop, and tell the
+/// delegates of players that have finished.
+pub fn handle_audio_players(env: &mut Environment) {
+    let players = State::get(&mut env.framework_state).playing.clone();
+    if players.is_empty() {
+        return;
+    }
+    // Delegates might release the players.
+    for &player in &players {
+        retain(env, player);
+    }
+    for &player in &players {
+        let host_object = borrow(env, player);
+        // The player might have been stopped by a delegate.
+        if !host_object.is_playing {
+            continue;
+        }
+        let al_source = host_object.al_source.unwrap();
+        let state = {
+            let _context_manager = make_al_context_current(env);
+            let mut state = 0;
+            unsafe { al::alGetSourcei(al_source, al::AL_SOURCE_STATE, &mut state) };
+            state
+        };
+        if state != al::AL_STOPPED {
+            continue;
+        }
+
+        let host_object = borrow(env, player);
+        if host_object.loops_remaining > 0 {
+            host_object.loops_remaining -= 1;
+            let _context_manager = make_al_context_current(env);
+            unsafe { al::alSourcePlay(al_source) };
+            continue;
+        }
+
+        log_dbg!("AVAudioPlayer {:?} finished playing", player);
+        pause(env, player);
+        let delegate = borrow(env, player).delegate;
+        if delegate != nil {
+            let selector = env
+                .objc
+                .lookup_selector("audioPlayerDidFinishPlaying:successfully:")
+                .unwrap();
+            if msg![env; delegate respondsToSelector:selector] {
+                () = msg![env; delegate audioPlayerDidFinishPlaying:player successfully:true];
+            }
+        }
+    }
+    for player in players {
+        release(env, player);
+    }
+}
+
+/// Pause all playing players because of an audio session interruption, and
+/// tell their delegates.
+pub fn begin_interruption(env: &mut Environment) {
+    let players = std::mem::take(&mut State::get(&mut env.framework_state).playing);
+    for &player in &players {
+        let host_object = borrow(env, player);
+        host_object.is_playing = false;
+        let al_source = host_object.al_source.unwrap();
+        let _context_manager = make_al_context_current(env);
+        unsafe { al::alSourcePause(al_source) };
+    }
+    // The references are moved from one list to the other.
+    State::get(&mut env.framework_state)
+        .interrupted
+        .extend_from_slice(&players);
+    for player in players {
+        send_to_delegate(env, player, "audioPlayerBeginInterruption:");
+    }
+}
+
+/// Tell the delegates of players paused by [begin_interruption] that the
+/// interruption has ended. As on iPhone OS, the players aren't resumed
+/// automatically, that's up to the delegate.
+pub fn end_interruption(env: &mut Environment) {
+    let players = std::mem::take(&mut State::get(&mut env.framework_state).interrupted);
+    for &player in &players {
+        send_to_delegate(env, player, "audioPlayerEndInterruption:");
+    }
+    for player in players {
+        release(env, player);
+    }
+}
@@ -0,0 +1,23 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The CFNetwork framework.
+//!
+//! Like Core Foundation, this is implemented on top of Foundation and its types
+//! are really Objective-C objects. Requests are made with the host's sockets,
+//! and HTTPS uses the host's TLS library (see [crate::tls]).
+//!
+//! Useful resources:
+//! - Apple's [CFNetwork Programming Guide](https://developer.apple.com/library/archive/documentation/Networking/Conceptual/CFNetwork/Introduction/Introduction.html)
+
+pub mod cf_http_message;
+pub mod cf_http_stream;
+pub mod cf_socket_stream;
+pub mod redirection;
+
+#[derive(Default)]
+pub struct State {
+    redirection: redirection::State,
+}
@@ -0,0 +1,634 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFHTTPMessage`.
+
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::core_foundation::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use crate::frameworks::core_foundation::cf_url::CFURLRef;
+use crate::frameworks::core_foundation::{CFIndex, CFTypeRef};
+use crate::frameworks::foundation::ns_dictionary::dict_from_keys_and_objects;
+use crate::frameworks::foundation::{ns_data, ns_string};
+use crate::mem::ConstPtr;
+use crate::objc::{
+    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+pub type CFHTTPMessageRef = CFTypeRef;
+
+pub const kCFHTTPVersion1_0: &str = "HTTP/1.0";
+pub const kCFHTTPVersion1_1: &str = "HTTP/1.1";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCFHTTPVersion1_0",
+        HostConstant::NSString(kCFHTTPVersion1_0),
+    ),
+    (
+        "_kCFHTTPVersion1_1",
+        HostConstant::NSString(kCFHTTPVersion1_1),
+    ),
+];
+
+struct CFHTTPMessageHostObject {
+    is_request: bool,
+    /// Request only.
+    method: String,
+    /// Request only. Strong reference.
+    url: CFURLRef,
+    /// Response only.
+    status_code: CFIndex,
+    /// Response only.
+    status_line: String,
+    version: String,
+    headers: Headers,
+    body: Vec<u8>,
+    /// Set once the start line and headers are all there. Messages created
+    /// with `CFHTTPMessageCreateEmpty` only get this after enough bytes are
+    /// appended to them.
+    header_complete: bool,
+    /// Bytes appended with `CFHTTPMessageAppendBytes` that don't make up a
+    /// complete header yet.
+    incomplete_head: Vec<u8>,
+}
+impl HostObject for CFHTTPMessageHostObject {}
+
+/// HTTP header fields in the order they were set. Field names are compared
+/// case-insensitively.
+#[derive(Default, Clone)]
+pub struct Headers(pub(super) Vec<(String, String)>);
+
+impl Headers {
+    pub(super) fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(other, _)| other.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces any existing field with the same name, or removes it if
+    /// `value` is [None].
+    pub(super) fn set(&mut self, name: &str, value: Option<String>) {
+        let existing = self
+            .0
+            .iter()
+            .position(|(other, _)| other.eq_ignore_ascii_case(name));
+        match (existing, value) {
+            (Some(idx), Some(value)) => self.0[idx].1 = value,
+            (Some(idx), None) => {
+                self.0.remove(idx);
+            }
+            (None, Some(value)) => self.0.push((name.to_string(), value)),
+            (None, None) => (),
+        }
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        for (name, value) in &self.0 {
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(b": ");
+            out.extend_from_slice(value.as_bytes());
+            out.extend_from_slice(b"\r\n");
+        }
+    }
+}
+
+/// The start line and header fields of a message, see [parse_head].
+#[derive(Clone)]
+pub struct Head {
+    pub(super) start_line: String,
+    pub(super) headers: Headers,
+}
+
+/// If `bytes` starts with a complete start line and headers, parse them and
+/// return them along with the number of bytes used. Returns [None] if more
+/// bytes are needed.
+pub(super) fn parse_head(bytes: &[u8]) -> Option<(Head, usize)> {
+    // Tolerate bare LF line endings, as most HTTP implementations do.
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    loop {
+        let line_end = line_start + bytes[line_start..].iter().position(|&b| b == b'\n')?;
+        let line = &bytes[line_start..line_end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        line_start = line_end + 1;
+        if line.is_empty() {
+            // Blank lines before the start line are allowed.
+            if lines.is_empty() {
+                continue;
+            }
+            break;
+        }
+        lines.push(String::from_utf8_lossy(line).into_owned());
+    }
+
+    let mut lines = lines.into_iter();
+    let start_line = lines.next().unwrap();
+    let mut headers = Headers::default();
+    for line in lines {
+        // Obsolete line folding: continuation of the previous field.
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.0.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            continue;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            log!("Warning: ignoring malformed HTTP header line {:?}", line);
+            continue;
+        };
+        let (name, value) = (name.trim(), value.trim());
+        // Repeated fields are combined, as CFNetwork does.
+        if let Some(existing) = headers.get(name) {
+            let combined = format!("{}, {}", existing, value);
+            headers.set(name, Some(combined));
+        } else {
+            headers.set(name, Some(value.to_string()));
+        }
+    }
+
+    Some((
+        Head {
+            start_line,
+            headers,
+        },
+        line_start,
+    ))
+}
+
+/// Split a response status line like `HTTP/1.1 200 OK` into the version and
+/// the status code.
+pub(super) fn parse_status_line(status_line: &str) -> Option<(&str, CFIndex)> {
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next()?;
+    if !version.starts_with("HTTP/") {
+        return None;
+    }
+    let status_code = parts.next()?.parse().ok()?;
+    Some((version, status_code))
+}
+
+/// The parts of an absolute URL needed to make an HTTP request.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct UrlParts<'a> {
+    pub(super) scheme: &'a str,
+    /// Without brackets for IPv6 addresses.
+    pub(super) host: &'a str,
+    pub(super) port: Option<u16>,
+    /// Path and query, always starting with `/`. The fragment is removed.
+    pub(super) path: &'a str,
+}
+
+pub(super) fn split_url(url: &str) -> Option<UrlParts<'_>> {
+    let (scheme, rest) = url.split_once("://")?;
+    let rest = rest.split('#').next().unwrap();
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(idx) => rest.split_at(idx),
+        None => (rest, ""),
+    };
+    let path = if path.is_empty() { "/" } else { path };
+    // Credentials aren't supported, but shouldn't be mistaken for the host.
+    let authority = authority.rsplit('@').next().unwrap();
+    let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+        let (host, after) = bracketed.split_once(']')?;
+        (host, after.strip_prefix(':'))
+    } else {
+        match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    let port = match port {
+        Some(port) if !port.is_empty() => Some(port.parse().ok()?),
+        _ => None,
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some(UrlParts {
+        scheme,
+        host,
+        port,
+        path,
+    })
+}
+
+fn reason_phrase(status_code: CFIndex) -> &'static str {
+    match status_code {
+        100 => "Continue",
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CFHTTPMessage is a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// exposed.
+@implementation _touchHLE_CFHTTPMessage: NSObject
+
+- (())dealloc {
+    let url = env.objc.borrow::<CFHTTPMessageHostObject>(this).url;
+    if url != nil {
+        release(env, url);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+fn new_message(env: &mut Environment, is_request: bool, version: String) -> CFHTTPMessageRef {
+    let host_object = Box::new(CFHTTPMessageHostObject {
+        is_request,
+        method: String::new(),
+        url: nil,
+        status_code: 0,
+        status_line: String::new(),
+        version,
+        headers: Headers::default(),
+        body: Vec::new(),
+        header_complete: true,
+        incomplete_head: Vec::new(),
+    });
+    let class = env
+        .objc
+        .get_known_class("_touchHLE_CFHTTPMessage", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+/// For use by `CFReadStreamCopyProperty`: make a response message (+1
+/// reference) from a received status line and headers.
+pub(super) fn new_response(env: &mut Environment, head: &Head) -> CFHTTPMessageRef {
+    let (version, status_code) = parse_status_line(&head.start_line).unwrap();
+    let message = new_message(env, false, version.to_string());
+    let host_object = env.objc.borrow_mut::<CFHTTPMessageHostObject>(message);
+    host_object.status_code = status_code;
+    host_object.status_line = head.start_line.clone();
+    host_object.headers = head.headers.clone();
+    message
+}
+
+/// The parts of a request needed to send it, see [request_parts].
+pub(super) struct RequestParts {
+    pub(super) method: String,
+    pub(super) url: String,
+    pub(super) version: String,
+    pub(super) headers: Headers,
+    pub(super) body: Vec<u8>,
+}
+
+/// For use by `CFReadStreamCreateForHTTPRequest`.
+pub(super) fn request_parts(env: &mut Environment, message: CFHTTPMessageRef) -> RequestParts {
+    let host_object = env.objc.borrow::<CFHTTPMessageHostObject>(message);
+    assert!(host_object.is_request);
+    let url = host_object.url;
+    let parts = RequestParts {
+        method: host_object.method.clone(),
+        url: String::new(),
+        version: host_object.version.clone(),
+        headers: host_object.headers.clone(),
+        body: host_object.body.clone(),
+    };
+    let url: id = msg![env; url absoluteString];
+    RequestParts {
+        url: ns_string::to_rust_string(env, url).into_owned(),
+        ..parts
+    }
+}
+
+fn CFHTTPMessageCreateRequest(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    request_method: id, // CFStringRef
+    url: CFURLRef,
+    http_version: id, // CFStringRef
+) -> CFHTTPMessageRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let method = ns_string::to_rust_string(env, request_method).into_owned();
+    let version = ns_string::to_rust_string(env, http_version).into_owned();
+    retain(env, url);
+    let message = new_message(env, true, version);
+    let host_object = env.objc.borrow_mut::<CFHTTPMessageHostObject>(message);
+    host_object.method = method;
+    host_object.url = url;
+    message
+}
+
+fn CFHTTPMessageCreateResponse(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    status_code: CFIndex,
+    status_description: id, // CFStringRef
+    http_version: id,       // CFStringRef
+) -> CFHTTPMessageRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let version = ns_string::to_rust_string(env, http_version).into_owned();
+    let description = if status_description == nil {
+        reason_phrase(status_code).to_string()
+    } else {
+        ns_string::to_rust_string(env, status_description).into_owned()
+    };
+    let status_line = format!("{} {} {}", version, status_code, description);
+    let message = new_message(env, false, version);
+    let host_object = env.objc.borrow_mut::<CFHTTPMessageHostObject>(message);
+    host_object.status_code = status_code;
+    host_object.status_line = status_line;
+    message
+}
+
+fn CFHTTPMessageCreateEmpty(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    is_request: bool,
+) -> CFHTTPMessageRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let message = new_message(env, is_request, String::new());
+    env.objc
+        .borrow_mut::<CFHTTPMessageHostObject>(message)
+        .header_complete = false;
+    message
+}
+
+fn CFHTTPMessageIsRequest(env: &mut Environment, message: CFHTTPMessageRef) -> bool {
+    env.objc
+        .borrow::<CFHTTPMessageHostObject>(message)
+        .is_request
+}
+
+fn CFHTTPMessageCopyVersion(env: &mut Environment, message: CFHTTPMessageRef) -> id {
+    let version = env
+        .objc
+        .borrow::<CFHTTPMessageHostObject>(message)
+        .version
+        .clone();
+    ns_string::from_rust_string(env, version)
+}
+
+fn CFHTTPMessageCopyBody(env: &mut Environment, message: CFHTTPMessageRef) -> id {
+    let host_object = env.objc.borrow::<CFHTTPMessageHostObject>(message);
+    if !host_object.header_complete {
+        return nil;
+    }
+    let body = host_object.body.clone();
+    ns_data::from_bytes(env, &body)
+}
+
+fn CFHTTPMessageSetBody(env: &mut Environment, message: CFHTTPMessageRef, body_data: id) {
+    let body = ns_data::to_vec(env, body_data);
+    env.objc.borrow_mut::<CFHTTPMessageHostObject>(message).body = body;
+}
+
+fn CFHTTPMessageCopyHeaderFieldValue(
+    env: &mut Environment,
+    message: CFHTTPMessageRef,
+    header_field: id, // CFStringRef
+) -> id {
+    let header_field = ns_string::to_rust_string(env, header_field);
+    let host_object = env.objc.borrow::<CFHTTPMessageHostObject>(message);
+    match host_object.headers.get(&header_field) {
+        Some(value) => {
+            let value = value.to_string();
+            ns_string::from_rust_string(env, value)
+        }
+        None => nil,
+    }
+}
+
+fn CFHTTPMessageSetHeaderFieldValue(
+    env: &mut Environment,
+    message: CFHTTPMessageRef,
+    header_field: id, // CFStringRef
+    value: id,        // CFStringRef
+) {
+    let header_field = ns_string::to_rust_string(env, header_field).into_owned();
+    let value = if value == nil {
+        None
+    } else {
+        Some(ns_string::to_rust_string(env, value).into_owned())
+    };
+    env.objc
+        .borrow_mut::<CFHTTPMessageHostObject>(message)
+        .headers
+        .set(&header_field, value);
+}
+
+fn CFHTTPMessageCopyAllHeaderFields(env: &mut Environment, message: CFHTTPMessageRef) -> id {
+    let headers = env
+        .objc
+        .borrow::<CFHTTPMessageHostObject>(message)
+        .headers
+        .clone();
+    let keys_and_objects: Vec<(id, id)> = headers
+        .0
+        .into_iter()
+        .map(|(name, value)| {
+            (
+                ns_string::from_rust_string(env, name),
+                ns_string::from_rust_string(env, value),
+            )
+        })
+        .collect();
+    let dict = dict_from_keys_and_objects(env, &keys_and_objects);
+    for (key, object) in keys_and_objects {
+        release(env, key);
+        release(env, object);
+    }
+    dict
+}
+
+fn CFHTTPMessageAppendBytes(
+    env: &mut Environment,
+    message: CFHTTPMessageRef,
+    new_bytes: ConstPtr<u8>,
+    num_bytes: CFIndex,
+) -> bool {
+    let num_bytes: u32 = num_bytes.try_into().unwrap();
+    let new_bytes = if num_bytes == 0 {
+        Vec::new()
+    } else {
+        env.mem.bytes_at(new_bytes, num_bytes).to_vec()
+    };
+
+    let host_object = env.objc.borrow_mut::<CFHTTPMessageHostObject>(message);
+    if host_object.header_complete {
+        host_object.body.extend_from_slice(&new_bytes);
+        return true;
+    }
+    host_object.incomplete_head.extend_from_slice(&new_bytes);
+    let Some((head, head_len)) = parse_head(&host_object.incomplete_head) else {
+        return true;
+    };
+    let body = host_object.incomplete_head.split_off(head_len);
+    host_object.incomplete_head = Vec::new();
+
+    if host_object.is_request {
+        // e.g. "GET /index.html HTTP/1.1"
+        let mut parts = head.start_line.split(' ');
+        let (Some(method), Some(target), Some(version), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            log!("Warning: malformed HTTP request line {:?}", head.start_line);
+            return false;
+        };
+        host_object.method = method.to_string();
+        host_object.version = version.to_string();
+        let target = target.to_string();
+        host_object.headers = head.headers;
+        host_object.body = body;
+        host_object.header_complete = true;
+        let target = ns_string::from_rust_string(env, target);
+        let url: id = msg_class![env; NSURL alloc];
+        let url: id = msg![env; url initWithString:target];
+        release(env, target);
+        env.objc.borrow_mut::<CFHTTPMessageHostObject>(message).url = url;
+    } else {
+        let Some((version, status_code)) = parse_status_line(&head.start_line) else {
+            log!("Warning: malformed HTTP status line {:?}", head.start_line);
+            return false;
+        };
+        host_object.version = version.to_string();
+        host_object.status_code = status_code;
+        host_object.status_line = head.start_line;
+        host_object.headers = head.headers;
+        host_object.body = body;
+        host_object.header_complete = true;
+    }
+    true
+}
+
+fn CFHTTPMessageIsHeaderComplete(env: &mut Environment, message: CFHTTPMessageRef) -> bool {
+    env.objc
+        .borrow::<CFHTTPMessageHostObject>(message)
+        .header_complete
+}
+
+fn CFHTTPMessageCopySerializedMessage(env: &mut Environment, message: CFHTTPMessageRef) -> id {
+    let host_object = env.objc.borrow::<CFHTTPMessageHostObject>(message);
+    if !host_object.header_complete {
+        return nil;
+    }
+    let start_line = if host_object.is_request {
+        let (method, version, url) = (
+            host_object.method.clone(),
+            host_object.version.clone(),
+            host_object.url,
+        );
+        let url: id = msg![env; url absoluteString];
+        let url = ns_string::to_rust_string(env, url);
+        let target = split_url(&url).map_or(&*url, |parts| parts.path);
+        format!("{} {} {}", method, target, version)
+    } else {
+        host_object.status_line.clone()
+    };
+
+    let host_object = env.objc.borrow::<CFHTTPMessageHostObject>(message);
+    let mut bytes = start_line.into_bytes();
+    bytes.extend_from_slice(b"\r\n");
+    host_object.headers.serialize(&mut bytes);
+    bytes.extend_from_slice(b"\r\n");
+    bytes.extend_from_slice(&host_object.body);
+    ns_data::from_bytes(env, &bytes)
+}
+
+fn CFHTTPMessageCopyRequestURL(env: &mut Environment, message: CFHTTPMessageRef) -> CFURLRef {
+    let url = env.objc.borrow::<CFHTTPMessageHostObject>(message).url;
+    retain(env, url)
+}
+
+fn CFHTTPMessageCopyRequestMethod(env: &mut Environment, message: CFHTTPMessageRef) -> id {
+    let host_object = env.objc.borrow::<CFHTTPMessageHostObject>(message);
+    if !host_object.is_request || !host_object.header_complete {
+        return nil;
+    }
+    let method = host_object.method.clone();
+    ns_string::from_rust_string(env, method)
+}
+
+fn CFHTTPMessageGetResponseStatusCode(env: &mut Environment, message: CFHTTPMessageRef) -> CFIndex {
+    env.objc
+        .borrow::<CFHTTPMessageHostObject>(message)
+        .status_code
+}
+
+fn CFHTTPMessageCopyResponseStatusLine(env: &mut Environment, message: CFHTTPMessageRef) -> id {
+    let host_object = env.objc.borrow::<CFHTTPMessageHostObject>(message);
+    if host_object.is_request || !host_object.header_complete {
+        return nil;
+    }
+    let status_line = host_object.status_line.clone();
+    ns_string::from_rust_string(env, status_line)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFHTTPMessageCreateRequest(_, _, _, _)),
+    export_c_func!(CFHTTPMessageCreateResponse(_, _, _, _)),
+    export_c_func!(CFHTTPMessageCreateEmpty(_, _)),
+    export_c_func!(CFHTTPMessageIsRequest(_)),
+    export_c_func!(CFHTTPMessageCopyVersion(_)),
+    export_c_func!(CFHTTPMessageCopyBody(_)),
+    export_c_func!(CFHTTPMessageSetBody(_, _)),
+    export_c_func!(CFHTTPMessageCopyHeaderFieldValue(_, _)),
+    export_c_func!(CFHTTPMessageSetHeaderFieldValue(_, _, _)),
+    export_c_func!(CFHTTPMessageCopyAllHeaderFields(_)),
+    export_c_func!(CFHTTPMessageAppendBytes(_, _, _)),
+    export_c_func!(CFHTTPMessageIsHeaderComplete(_)),
+    export_c_func!(CFHTTPMessageCopySerializedMessage(_)),
+    export_c_func!(CFHTTPMessageCopyRequestURL(_)),
+    export_c_func!(CFHTTPMessageCopyRequestMethod(_)),
+    export_c_func!(CFHTTPMessageGetResponseStatusCode(_)),
+    export_c_func!(CFHTTPMessageCopyResponseStatusLine(_)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_splitting() {
+        assert_eq!(
+            split_url("http://example.com:8080/a/b?c=d#e"),
+            Some(UrlParts {
+                scheme: "http",
+                host: "example.com",
+                port: Some(8080),
+                path: "/a/b?c=d",
+            })
+        );
+        assert_eq!(
+            split_url("http://[::1]/"),
+            Some(UrlParts {
+                scheme: "http",
+                host: "::1",
+                port: None,
+                path: "/",
+            })
+        );
+        assert_eq!(split_url("/relative"), None);
+    }
+}
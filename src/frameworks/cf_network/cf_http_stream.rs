@@ -0,0 +1,535 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFHTTPStream.h`: read streams for HTTP requests.
+//!
+//! The stream is an ordinary `NSInputStream` (see
+//! [crate::frameworks::foundation::ns_stream]) backed by an [HttpConnection].
+//! Connecting and sending the request happen when the stream is opened, and
+//! block. The response is then received without blocking as the stream is
+//! polled. Redirects are not followed and persistent connections are not used.
+//!
+//! HTTPS requests use the host's TLS library (see [crate::tls]). Their TLS
+//! settings can be changed with the stream properties in
+//! [super::cf_socket_stream] before the stream is opened.
+//!
+//! The network rules (see [super::redirection]) can send a request to another
+//! server, or answer it with a file instead.
+
+use super::cf_http_message::{
+    self, parse_head, parse_status_line, split_url, CFHTTPMessageRef, Head, Headers, RequestParts,
+};
+use super::redirection::{self, Target};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::core_foundation::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use crate::frameworks::core_foundation::cf_stream::CFReadStreamRef;
+use crate::frameworks::core_foundation::CFIndex;
+use crate::frameworks::foundation::ns_stream;
+use crate::objc::{autorelease, id, nil};
+use crate::tls;
+use crate::Environment;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const kCFStreamPropertyHTTPResponseHeader: &str = "kCFStreamPropertyHTTPResponseHeader";
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_kCFStreamPropertyHTTPResponseHeader",
+    HostConstant::NSString(kCFStreamPropertyHTTPResponseHeader),
+)];
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How the body of the response is delimited, and how far through it we are.
+enum ResponseState {
+    Head,
+    /// Bytes remaining, from `Content-Length`.
+    Length(usize),
+    /// Waiting for a chunk size line.
+    ChunkSize,
+    /// Bytes remaining in the current chunk.
+    ChunkData(usize),
+    /// Waiting for the CRLF after a chunk.
+    ChunkEnd,
+    /// Skipping trailer fields after the last chunk.
+    Trailers,
+    /// Neither `Content-Length` nor chunked, so the body ends when the server
+    /// closes the connection.
+    UntilClose,
+    Done,
+}
+
+/// A connection to a server, with or without TLS.
+trait Transport: Read + Write {
+    fn make_nonblocking(&self) -> std::io::Result<()>;
+}
+impl Transport for TcpStream {
+    fn make_nonblocking(&self) -> std::io::Result<()> {
+        self.set_nonblocking(true)
+    }
+}
+impl Transport for tls::Stream {
+    fn make_nonblocking(&self) -> std::io::Result<()> {
+        self.get_ref().set_nonblocking(true)
+    }
+}
+
+/// The host side of an HTTP request stream.
+pub struct HttpConnection {
+    host: String,
+    port: u16,
+    /// Whether this is an HTTPS request.
+    use_tls: bool,
+    tls_settings: tls::Settings,
+    is_head_request: bool,
+    /// Serialized request, sent when the stream is opened.
+    request: Vec<u8>,
+    socket: Option<Box<dyn Transport>>,
+    /// If set, the response is this file on the host, and nothing is sent.
+    response_file: Option<PathBuf>,
+    state: ResponseState,
+    /// Received bytes that haven't been processed yet.
+    received: Vec<u8>,
+    /// Decoded body bytes that haven't been read yet.
+    body: Vec<u8>,
+    head: Option<Head>,
+    failed: bool,
+}
+
+impl HttpConnection {
+    /// `relaxed_tls` is whether TLS checks are relaxed by default (see
+    /// [tls::Settings::new]).
+    fn new(request: RequestParts, relaxed_tls: bool) -> Option<HttpConnection> {
+        let Some(url) = split_url(&request.url) else {
+            log!("Warning: can't make HTTP request for URL {:?}", request.url);
+            return None;
+        };
+        let use_tls = match url.scheme {
+            "http" => false,
+            "https" => true,
+            _ => {
+                log!(
+                    "Warning: can't make HTTP request for URL {:?}, only HTTP and HTTPS are supported",
+                    request.url
+                );
+                return None;
+            }
+        };
+
+        let mut headers = request.headers;
+        if headers.get("Host").is_none() {
+            let host = if url.host.contains(':') {
+                format!("[{}]", url.host)
+            } else {
+                url.host.to_string()
+            };
+            let host = match url.port {
+                Some(port) => format!("{}:{}", host, port),
+                None => host,
+            };
+            headers.set("Host", Some(host));
+        }
+        if headers.get("Connection").is_none() {
+            headers.set("Connection", Some("close".to_string()));
+        }
+        if !request.body.is_empty() && headers.get("Content-Length").is_none() {
+            headers.set("Content-Length", Some(request.body.len().to_string()));
+        }
+
+        let version = if request.version.is_empty() {
+            cf_http_message::kCFHTTPVersion1_1
+        } else {
+            &request.version
+        };
+        let mut bytes = format!("{} {} {}\r\n", request.method, url.path, version).into_bytes();
+        for (name, value) in &headers.0 {
+            bytes.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        bytes.extend_from_slice(b"\r\n");
+        bytes.extend_from_slice(&request.body);
+
+        Some(HttpConnection {
+            host: url.host.to_string(),
+            port: url.port.unwrap_or(if use_tls { 443 } else { 80 }),
+            use_tls,
+            tls_settings: tls::Settings::new(url.host, relaxed_tls),
+            is_head_request: request.method == "HEAD",
+            request: bytes,
+            socket: None,
+            response_file: None,
+            state: ResponseState::Head,
+            received: Vec::new(),
+            body: Vec::new(),
+            head: None,
+            failed: false,
+        })
+    }
+
+    /// Make a GET request on behalf of the host rather than the app, e.g. for
+    /// map tiles. Like for a stream, nothing is sent until it's opened.
+    pub fn new_get(url: &str, user_agent: &str) -> Option<HttpConnection> {
+        let mut headers = Headers::default();
+        headers.set("User-Agent", Some(user_agent.to_string()));
+        HttpConnection::new(
+            RequestParts {
+                method: "GET".to_string(),
+                url: url.to_string(),
+                version: String::new(),
+                headers,
+                body: Vec::new(),
+            },
+            false,
+        )
+    }
+
+    pub fn tls_settings(&self) -> &tls::Settings {
+        &self.tls_settings
+    }
+
+    /// Change the TLS settings. This only has an effect before the connection
+    /// is opened.
+    pub fn set_tls_settings(&mut self, settings: tls::Settings) {
+        self.tls_settings = settings;
+    }
+
+    /// Connect and send the request. This blocks.
+    pub fn open(&mut self) -> Result<(), ()> {
+        if let Some(path) = self.response_file.take() {
+            log_dbg!("Responding to HTTP request with {}", path.display());
+            self.received = file_response(&path);
+            self.process();
+            return Ok(());
+        }
+
+        let addresses = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(|e| {
+                log!("Warning: couldn't resolve {:?}: {}", self.host, e);
+            })?;
+        let mut socket = None;
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+                Ok(connected) => {
+                    socket = Some(connected);
+                    break;
+                }
+                Err(e) => {
+                    log!("Warning: couldn't connect to {}: {}", address, e);
+                }
+            }
+        }
+        let Some(socket) = socket else {
+            return Err(());
+        };
+        let mut socket: Box<dyn Transport> = if self.use_tls {
+            let stream = tls::connect(&self.host, socket, &self.tls_settings).map_err(|e| {
+                log!("Warning: {}", e);
+            })?;
+            Box::new(stream)
+        } else {
+            Box::new(socket)
+        };
+        socket.write_all(&self.request).map_err(|e| {
+            log!(
+                "Warning: couldn't send HTTP request to {:?}: {}",
+                self.host,
+                e
+            );
+        })?;
+        socket.make_nonblocking().unwrap();
+        log_dbg!("Sent HTTP request to {:?}:{}", self.host, self.port);
+        self.socket = Some(socket);
+        Ok(())
+    }
+
+    /// Receive whatever has arrived without blocking. Returns the number of
+    /// body bytes that can be read.
+    pub fn poll(&mut self) -> usize {
+        if let Some(socket) = self.socket.as_mut() {
+            let mut buffer = [0u8; 4096];
+            let mut closed = false;
+            loop {
+                match socket.read(&mut buffer) {
+                    Ok(0) => {
+                        closed = true;
+                        break;
+                    }
+                    Ok(count) => self.received.extend_from_slice(&buffer[..count]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        log!("Warning: HTTP connection to {:?} failed: {}", self.host, e);
+                        self.failed = true;
+                        closed = true;
+                        break;
+                    }
+                }
+            }
+            self.process();
+            if closed {
+                self.socket = None;
+                match self.state {
+                    ResponseState::UntilClose => self.state = ResponseState::Done,
+                    ResponseState::Done => (),
+                    _ => {
+                        log!(
+                            "Warning: HTTP connection to {:?} closed before the response was complete",
+                            self.host
+                        );
+                        self.failed = true;
+                    }
+                }
+            }
+        }
+        if matches!(self.state, ResponseState::Done) {
+            self.socket = None;
+        }
+        self.body.len()
+    }
+
+    /// Decode as much of the received bytes as possible.
+    fn process(&mut self) {
+        loop {
+            match self.state {
+                ResponseState::Head => {
+                    let Some((head, head_len)) = parse_head(&self.received) else {
+                        return;
+                    };
+                    self.received.drain(..head_len);
+                    let Some((_, status_code)) = parse_status_line(&head.start_line) else {
+                        log!("Warning: malformed HTTP status line {:?}", head.start_line);
+                        self.failed = true;
+                        self.state = ResponseState::Done;
+                        return;
+                    };
+                    // Interim responses like 100 Continue are skipped.
+                    if (100..200).contains(&status_code) {
+                        continue;
+                    }
+                    let chunked = head
+                        .headers
+                        .get("Transfer-Encoding")
+                        .is_some_and(|encoding| encoding.to_ascii_lowercase().contains("chunked"));
+                    let length = head
+                        .headers
+                        .get("Content-Length")
+                        .and_then(|length| length.trim().parse().ok());
+                    self.state = if self.is_head_request || status_code == 204 || status_code == 304
+                    {
+                        ResponseState::Done
+                    } else if chunked {
+                        ResponseState::ChunkSize
+                    } else if let Some(length) = length {
+                        ResponseState::Length(length)
+                    } else {
+                        ResponseState::UntilClose
+                    };
+                    if let ResponseState::Length(0) = self.state {
+                        self.state = ResponseState::Done;
+                    }
+                    self.head = Some(head);
+                }
+                ResponseState::Length(remaining) => {
+                    let count = remaining.min(self.received.len());
+                    self.body.extend(self.received.drain(..count));
+                    self.state = if count == remaining {
+                        ResponseState::Done
+                    } else {
+                        ResponseState::Length(remaining - count)
+                    };
+                    return;
+                }
+                ResponseState::ChunkSize => {
+                    let Some(line) = self.take_line() else {
+                        return;
+                    };
+                    let size = line.split(';').next().unwrap().trim();
+                    let Ok(size) = usize::from_str_radix(size, 16) else {
+                        log!("Warning: malformed HTTP chunk size {:?}", line);
+                        self.failed = true;
+                        self.state = ResponseState::Done;
+                        return;
+                    };
+                    self.state = if size == 0 {
+                        ResponseState::Trailers
+                    } else {
+                        ResponseState::ChunkData(size)
+                    };
+                }
+                ResponseState::ChunkData(remaining) => {
+                    let count = remaining.min(self.received.len());
+                    self.body.extend(self.received.drain(..count));
+                    if count < remaining {
+                        self.state = ResponseState::ChunkData(remaining - count);
+                        return;
+                    }
+                    self.state = ResponseState::ChunkEnd;
+                }
+                ResponseState::ChunkEnd => {
+                    if self.take_line().is_none() {
+                        return;
+                    }
+                    self.state = ResponseState::ChunkSize;
+                }
+                ResponseState::Trailers => {
+                    let Some(line) = self.take_line() else {
+                        return;
+                    };
+                    if line.is_empty() {
+                        self.state = ResponseState::Done;
+                    }
+                }
+                ResponseState::UntilClose => {
+                    self.body.append(&mut self.received);
+                    return;
+                }
+                ResponseState::Done => {
+                    self.received.clear();
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Remove a line from the received bytes, without its line ending.
+    fn take_line(&mut self) -> Option<String> {
+        let end = self.received.iter().position(|&b| b == b'\n')?;
+        let line: Vec<u8> = self.received.drain(..=end).collect();
+        let line = line.strip_suffix(b"\n").unwrap();
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        Some(String::from_utf8_lossy(line).into_owned())
+    }
+
+    pub fn read(&mut self, buffer: &mut [u8]) -> usize {
+        self.poll();
+        let count = buffer.len().min(self.body.len());
+        buffer[..count].copy_from_slice(&self.body[..count]);
+        self.body.drain(..count);
+        count
+    }
+
+    /// Whether the whole response has been received and read.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, ResponseState::Done) && self.body.is_empty()
+    }
+
+    pub fn has_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// The status line and headers of the response, if they've been received.
+    pub fn response_head(&self) -> Option<Head> {
+        self.head.clone()
+    }
+
+    /// The status code of the response, if its head has been received.
+    pub fn status_code(&self) -> Option<CFIndex> {
+        let head = self.head.as_ref()?;
+        parse_status_line(&head.start_line).map(|(_, status_code)| status_code)
+    }
+}
+
+/// For use by `NSStream`'s `propertyForKey:`. Returns [None] if `key` isn't an
+/// HTTP stream property.
+pub fn property_for_key(
+    env: &mut Environment,
+    response_head: Option<Head>,
+    key: &str,
+) -> Option<id> {
+    if key != kCFStreamPropertyHTTPResponseHeader {
+        return None;
+    }
+    Some(match response_head {
+        Some(head) => {
+            let message = cf_http_message::new_response(env, &head);
+            autorelease(env, message)
+        }
+        None => nil,
+    })
+}
+
+/// Make a complete response whose body is the contents of a file, or a 404
+/// response if it can't be read.
+fn file_response(path: &Path) -> Vec<u8> {
+    let (status, body) = match std::fs::read(path) {
+        Ok(body) => ("200 OK", body),
+        Err(e) => {
+            log!("Warning: couldn't read {}: {}", path.display(), e);
+            ("404 Not Found", Vec::new())
+        }
+    };
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    let content_type = match extension.as_deref() {
+        Some("html" | "htm") => "text/html",
+        Some("xml" | "plist") => "text/xml",
+        Some("txt") => "text/plain",
+        Some("json") => "application/json",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        _ => "application/octet-stream",
+    };
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(&body);
+    response
+}
+
+/// Apply the network rules to a request. Returns the file to respond with, if
+/// the request shouldn't be sent.
+fn redirect(env: &mut Environment, request: &mut RequestParts) -> Option<PathBuf> {
+    let url = split_url(&request.url)?;
+    let default_port = if url.scheme == "https" { 443 } else { 80 };
+    let target = redirection::rules(env)
+        .redirect_request(url.host, url.port.unwrap_or(default_port), url.path)?
+        .clone();
+    match target {
+        Target::File(path) => Some(redirection::file_path(env, &path)),
+        Target::Server { scheme, host, port } => {
+            let scheme = scheme.as_deref().unwrap_or(url.scheme);
+            let host = if host.contains(':') {
+                format!("[{}]", host)
+            } else {
+                host
+            };
+            let new_url = match port.or(url.port) {
+                Some(port) => format!("{}://{}:{}{}", scheme, host, port, url.path),
+                None => format!("{}://{}{}", scheme, host, url.path),
+            };
+            log_dbg!("Redirecting {:?} to {:?}", request.url, new_url);
+            request.url = new_url;
+            // The new server should see its own name.
+            request.headers.set("Host", None);
+            None
+        }
+    }
+}
+
+fn CFReadStreamCreateForHTTPRequest(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    request: CFHTTPMessageRef,
+) -> CFReadStreamRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let mut request = cf_http_message::request_parts(env, request);
+    let response_file = redirect(env, &mut request);
+    let mut connection = HttpConnection::new(request, env.options.relaxed_tls);
+    if let Some(ref mut connection) = connection {
+        connection.response_file = response_file;
+    }
+    ns_stream::input_stream_for_http(env, connection)
+}
+
+pub const FUNCTIONS: FunctionExports = &[export_c_func!(CFReadStreamCreateForHTTPRequest(_, _))];
@@ -0,0 +1,177 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFSocketStream.h`: stream properties for TLS.
+//!
+//! There are no socket streams yet, but HTTP streams (see
+//! [super::cf_http_stream]) take these properties too.
+
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::ns_string::{get_static_str, to_rust_string};
+use crate::objc::{id, msg, nil};
+use crate::tls;
+use crate::Environment;
+
+pub const kCFStreamPropertySocketSecurityLevel: &str = "kCFStreamPropertySocketSecurityLevel";
+pub const kCFStreamSocketSecurityLevelNone: &str = "kCFStreamSocketSecurityLevelNone";
+pub const kCFStreamSocketSecurityLevelSSLv2: &str = "kCFStreamSocketSecurityLevelSSLv2";
+pub const kCFStreamSocketSecurityLevelSSLv3: &str = "kCFStreamSocketSecurityLevelSSLv3";
+pub const kCFStreamSocketSecurityLevelTLSv1: &str = "kCFStreamSocketSecurityLevelTLSv1";
+pub const kCFStreamSocketSecurityLevelNegotiatedSSL: &str =
+    "kCFStreamSocketSecurityLevelNegotiatedSSL";
+
+pub const kCFStreamPropertySSLSettings: &str = "kCFStreamPropertySSLSettings";
+pub const kCFStreamSSLLevel: &str = "kCFStreamSSLLevel";
+pub const kCFStreamSSLAllowsExpiredCertificates: &str = "kCFStreamSSLAllowsExpiredCertificates";
+pub const kCFStreamSSLAllowsExpiredRoots: &str = "kCFStreamSSLAllowsExpiredRoots";
+pub const kCFStreamSSLAllowsAnyRoot: &str = "kCFStreamSSLAllowsAnyRoot";
+pub const kCFStreamSSLValidatesCertificateChain: &str = "kCFStreamSSLValidatesCertificateChain";
+pub const kCFStreamSSLPeerName: &str = "kCFStreamSSLPeerName";
+pub const kCFStreamSSLIsServer: &str = "kCFStreamSSLIsServer";
+pub const kCFStreamSSLCertificates: &str = "kCFStreamSSLCertificates";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCFStreamPropertySocketSecurityLevel",
+        HostConstant::NSString(kCFStreamPropertySocketSecurityLevel),
+    ),
+    (
+        "_kCFStreamSocketSecurityLevelNone",
+        HostConstant::NSString(kCFStreamSocketSecurityLevelNone),
+    ),
+    (
+        "_kCFStreamSocketSecurityLevelSSLv2",
+        HostConstant::NSString(kCFStreamSocketSecurityLevelSSLv2),
+    ),
+    (
+        "_kCFStreamSocketSecurityLevelSSLv3",
+        HostConstant::NSString(kCFStreamSocketSecurityLevelSSLv3),
+    ),
+    (
+        "_kCFStreamSocketSecurityLevelTLSv1",
+        HostConstant::NSString(kCFStreamSocketSecurityLevelTLSv1),
+    ),
+    (
+        "_kCFStreamSocketSecurityLevelNegotiatedSSL",
+        HostConstant::NSString(kCFStreamSocketSecurityLevelNegotiatedSSL),
+    ),
+    (
+        "_kCFStreamPropertySSLSettings",
+        HostConstant::NSString(kCFStreamPropertySSLSettings),
+    ),
+    (
+        "_kCFStreamSSLLevel",
+        HostConstant::NSString(kCFStreamSSLLevel),
+    ),
+    (
+        "_kCFStreamSSLAllowsExpiredCertificates",
+        HostConstant::NSString(kCFStreamSSLAllowsExpiredCertificates),
+    ),
+    (
+        "_kCFStreamSSLAllowsExpiredRoots",
+        HostConstant::NSString(kCFStreamSSLAllowsExpiredRoots),
+    ),
+    (
+        "_kCFStreamSSLAllowsAnyRoot",
+        HostConstant::NSString(kCFStreamSSLAllowsAnyRoot),
+    ),
+    (
+        "_kCFStreamSSLValidatesCertificateChain",
+        HostConstant::NSString(kCFStreamSSLValidatesCertificateChain),
+    ),
+    (
+        "_kCFStreamSSLPeerName",
+        HostConstant::NSString(kCFStreamSSLPeerName),
+    ),
+    (
+        "_kCFStreamSSLIsServer",
+        HostConstant::NSString(kCFStreamSSLIsServer),
+    ),
+    (
+        "_kCFStreamSSLCertificates",
+        HostConstant::NSString(kCFStreamSSLCertificates),
+    ),
+];
+
+/// Apply a security level (`kCFStreamSocketSecurityLevel...`).
+fn apply_level(env: &mut Environment, settings: &mut tls::Settings, level: id) {
+    let level = to_rust_string(env, level);
+    match &*level {
+        kCFStreamSocketSecurityLevelSSLv2 | kCFStreamSocketSecurityLevelSSLv3 => {
+            settings.allow_legacy_protocols = true;
+        }
+        kCFStreamSocketSecurityLevelNone => {
+            log!("Warning: TLS can't be turned off for a stream that uses it");
+        }
+        _ => (),
+    }
+}
+
+fn bool_for_key(env: &mut Environment, dict: id, key: &'static str) -> Option<bool> {
+    let key = get_static_str(env, key);
+    let value: id = msg![env; dict objectForKey:key];
+    if value == nil {
+        None
+    } else {
+        Some(msg![env; value boolValue])
+    }
+}
+
+/// Apply `kCFStreamPropertySocketSecurityLevel` or
+/// `kCFStreamPropertySSLSettings` to the TLS settings of a stream. Returns
+/// [false] if `key` isn't one of those.
+pub fn apply_property(
+    env: &mut Environment,
+    settings: &mut tls::Settings,
+    key: &str,
+    value: id,
+) -> bool {
+    match key {
+        kCFStreamPropertySocketSecurityLevel => {
+            apply_level(env, settings, value);
+            true
+        }
+        kCFStreamPropertySSLSettings => {
+            if value == nil {
+                return true;
+            }
+            let dict = value;
+            let level_key = get_static_str(env, kCFStreamSSLLevel);
+            let level: id = msg![env; dict objectForKey:level_key];
+            if level != nil {
+                apply_level(env, settings, level);
+            }
+            for key in [
+                kCFStreamSSLAllowsExpiredCertificates,
+                kCFStreamSSLAllowsExpiredRoots,
+                kCFStreamSSLAllowsAnyRoot,
+            ] {
+                if bool_for_key(env, dict, key) == Some(true) {
+                    settings.validate_certificate_chain = false;
+                }
+            }
+            if let Some(validates) = bool_for_key(env, dict, kCFStreamSSLValidatesCertificateChain)
+            {
+                settings.validate_certificate_chain = validates;
+            }
+            let peer_name_key = get_static_str(env, kCFStreamSSLPeerName);
+            let peer_name: id = msg![env; dict objectForKey:peer_name_key];
+            if peer_name != nil {
+                // kCFNull means the name isn't checked.
+                let string_class = env.objc.get_known_class("NSString", &mut env.mem);
+                settings.peer_name = if msg![env; peer_name isKindOfClass:string_class] {
+                    Some(to_rust_string(env, peer_name).into_owned())
+                } else {
+                    None
+                };
+            }
+            if bool_for_key(env, dict, kCFStreamSSLIsServer) == Some(true) {
+                log!("TODO: TLS server streams");
+            }
+            true
+        }
+        _ => false,
+    }
+}
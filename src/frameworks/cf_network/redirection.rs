@@ -0,0 +1,351 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Network rules: redirecting the app's connections to servers that are gone
+//! to replacements, or answering its HTTP requests with files on the host.
+//!
+//! The rules are kept in the directory set with `--network-rules-dir=`, in a
+//! file named after the app's bundle identifier, and look like this:
+//!
+//! ```text
+//! # Comments start with '#'.
+//! scores.example.com = scores.example.org
+//! *.example.com = localhost:8080
+//! api.example.com/news.xml = file:news.xml
+//! secure.example.com = http://localhost:8080
+//! 203.0.113.7:5000 = 192.168.1.20
+//! ```
+//!
+//! The left side is a host name or IP address, optionally with a port and, for
+//! HTTP, a path prefix. A leading `*.` matches any subdomain. The right side is
+//! the replacement host, optionally with a port (otherwise the original one is
+//! kept) and, for HTTP, a scheme, or `file:` and the path of a file to send as
+//! the response, relative to the rules directory. The first rule that matches
+//! is used.
+//!
+//! HTTP streams are matched on the host name in their URL and sockets on the
+//! address they connect or send to, since the app resolves names itself.
+
+use crate::Environment;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Server {
+        /// `http` or `https`, if it should be changed.
+        scheme: Option<String>,
+        host: String,
+        port: Option<u16>,
+    },
+    /// Relative to the rules directory.
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    /// Lowercase, without any `*.`.
+    host: String,
+    /// Whether the host had `*.` in front, so that subdomains match.
+    subdomains: bool,
+    port: Option<u16>,
+    /// Includes the leading `/`.
+    path: Option<String>,
+    target: Target,
+}
+impl Rule {
+    fn matches(&self, host: &str, port: u16, path: Option<&str>) -> bool {
+        let host = host.to_ascii_lowercase();
+        let host_matches = if self.subdomains {
+            host.strip_suffix(&self.host)
+                .is_some_and(|prefix| prefix.ends_with('.'))
+        } else {
+            host == self.host
+        };
+        host_matches
+            && self.port.is_none_or(|rule_port| rule_port == port)
+            && match (&self.path, path) {
+                (None, _) => true,
+                (Some(prefix), Some(path)) => path.starts_with(prefix.as_str()),
+                (Some(_), None) => false,
+            }
+    }
+}
+
+/// Split `host[:port]` into its parts. IPv6 addresses must be in brackets if
+/// there's a port.
+fn split_host_port(text: &str) -> Result<(&str, Option<u16>), String> {
+    let (host, port) = if let Some(bracketed) = text.strip_prefix('[') {
+        let Some((host, after)) = bracketed.split_once(']') else {
+            return Err(format!("unclosed '[' in {:?}", text));
+        };
+        match after.strip_prefix(':') {
+            Some(port) => (host, Some(port)),
+            None if after.is_empty() => (host, None),
+            None => return Err(format!("unexpected {:?} after address", after)),
+        }
+    } else if text.matches(':').count() > 1 {
+        // A bare IPv6 address.
+        (text, None)
+    } else {
+        match text.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (text, None),
+        }
+    };
+    if host.is_empty() {
+        return Err(format!("no host in {:?}", text));
+    }
+    let port = match port {
+        Some(port) => Some(
+            port.parse()
+                .map_err(|_| format!("invalid port {:?}", port))?,
+        ),
+        None => None,
+    };
+    Ok((host, port))
+}
+
+fn parse_target(text: &str) -> Result<Target, String> {
+    if let Some(path) = text.strip_prefix("file:") {
+        if path.is_empty() {
+            return Err("no path after 'file:'".to_string());
+        }
+        return Ok(Target::File(PathBuf::from(path)));
+    }
+    let (scheme, rest) = match text.split_once("://") {
+        Some((scheme, rest)) => {
+            let scheme = scheme.to_ascii_lowercase();
+            if scheme != "http" && scheme != "https" {
+                return Err(format!("unsupported scheme {:?}", scheme));
+            }
+            (Some(scheme), rest.strip_suffix('/').unwrap_or(rest))
+        }
+        None => (None, text),
+    };
+    let (host, port) = split_host_port(rest)?;
+    Ok(Target::Server {
+        scheme,
+        host: host.to_string(),
+        port,
+    })
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Rules(Vec<Rule>);
+impl Rules {
+    pub fn parse(text: &str) -> Result<Rules, String> {
+        let mut rules = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = line_number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((pattern, target)) = line.split_once('=') else {
+                return Err(format!("Line {}: expected 'from = to'", line_number));
+            };
+            let (pattern, target) = (pattern.trim(), target.trim());
+            let (address, path) = match pattern.find('/') {
+                Some(idx) => (&pattern[..idx], Some(pattern[idx..].to_string())),
+                None => (pattern, None),
+            };
+            let (host, port) =
+                split_host_port(address).map_err(|e| format!("Line {}: {}", line_number, e))?;
+            let (host, subdomains) = match host.strip_prefix("*.") {
+                Some(host) => (host, true),
+                None => (host, false),
+            };
+            let target =
+                parse_target(target).map_err(|e| format!("Line {}: {}", line_number, e))?;
+            rules.push(Rule {
+                host: host.to_ascii_lowercase(),
+                subdomains,
+                port,
+                path,
+                target,
+            });
+        }
+        Ok(Rules(rules))
+    }
+
+    /// Find where an HTTP request should go. `path` includes any query.
+    pub fn redirect_request(&self, host: &str, port: u16, path: &str) -> Option<&Target> {
+        self.0
+            .iter()
+            .find(|rule| rule.matches(host, port, Some(path)))
+            .map(|rule| &rule.target)
+    }
+
+    /// Find where a socket connection should go. Rules with a path or a file
+    /// don't apply to sockets.
+    pub fn redirect_socket(&self, host: &str, port: u16) -> Option<(&str, u16)> {
+        self.0.iter().find_map(|rule| match rule.target {
+            Target::Server {
+                host: ref new_host,
+                port: new_port,
+                ..
+            } if rule.path.is_none() && rule.matches(host, port, None) => {
+                Some((new_host.as_str(), new_port.unwrap_or(port)))
+            }
+            _ => None,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct State {
+    /// Loaded on first use.
+    rules: Option<Rules>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.cf_network.redirection
+    }
+}
+
+/// Get the app's network rules, reading them from disk if that hasn't
+/// happened yet.
+pub fn rules(env: &mut Environment) -> &Rules {
+    if State::get(&mut env.framework_state).rules.is_none() {
+        let path = env
+            .options
+            .network_rules_dir
+            .join(format!("{}.txt", env.bundle.bundle_identifier()));
+        let rules = match std::fs::read_to_string(&path) {
+            Ok(text) => match Rules::parse(&text) {
+                Ok(rules) => {
+                    log!(
+                        "Using {} network rule(s) from {}",
+                        rules.0.len(),
+                        path.display()
+                    );
+                    rules
+                }
+                Err(e) => {
+                    log!(
+                        "Warning: ignoring invalid network rules file {}: {}",
+                        path.display(),
+                        e
+                    );
+                    Rules::default()
+                }
+            },
+            Err(_) => {
+                log_dbg!("No network rules file at {}", path.display());
+                Rules::default()
+            }
+        };
+        State::get(&mut env.framework_state).rules = Some(rules);
+    }
+    State::get(&mut env.framework_state).rules.as_ref().unwrap()
+}
+
+/// Get the path on the host of a [Target::File].
+pub fn file_path(env: &Environment, path: &Path) -> PathBuf {
+    env.options.network_rules_dir.join(path)
+}
+
+/// For use by `CFSocket`: find where a connection to, or data sent to,
+/// `address` should go.
+pub fn redirect_socket_address(env: &mut Environment, address: SocketAddr) -> SocketAddr {
+    let Some((host, port)) = rules(env).redirect_socket(&address.ip().to_string(), address.port())
+    else {
+        return address;
+    };
+    let host = host.to_string();
+    // Prefer an address of the same kind, since the app's socket is one.
+    let new_address = (host.as_str(), port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|addresses| {
+            let addresses: Vec<SocketAddr> = addresses.collect();
+            addresses
+                .iter()
+                .find(|new| new.is_ipv6() == address.is_ipv6())
+                .or(addresses.first())
+                .copied()
+        });
+    match new_address {
+        Some(new_address) => {
+            log_dbg!("Redirecting {} to {}", address, new_address);
+            new_address
+        }
+        None => {
+            log!(
+                "Warning: couldn't resolve {:?} to redirect {} there",
+                host,
+                address
+            );
+            address
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_file() {
+        let rules = Rules::parse(
+            "# A comment\n\
+             \n\
+             Scores.Example.com = scores.example.org\n\
+             *.example.com = localhost:8080\n\
+             api.example.net/news = file:news.xml\n\
+             api.example.net:443 = http://localhost:8080/\n\
+             203.0.113.7:5000 = 192.168.1.20\n\
+             [2001:db8::1]:80 = ::1\n",
+        )
+        .unwrap();
+
+        let server = |scheme: Option<&str>, host: &str, port| Target::Server {
+            scheme: scheme.map(str::to_string),
+            host: host.to_string(),
+            port,
+        };
+        assert_eq!(
+            rules.redirect_request("scores.example.COM", 80, "/top"),
+            Some(&server(None, "scores.example.org", None))
+        );
+        assert_eq!(
+            rules.redirect_request("www.example.com", 443, "/"),
+            Some(&server(None, "localhost", Some(8080)))
+        );
+        assert_eq!(rules.redirect_request("example.com", 80, "/"), None);
+        assert_eq!(rules.redirect_request("badexample.com", 80, "/"), None);
+        assert_eq!(
+            rules.redirect_request("api.example.net", 80, "/news.xml?page=2"),
+            Some(&Target::File(PathBuf::from("news.xml")))
+        );
+        assert_eq!(
+            rules.redirect_request("api.example.net", 443, "/login"),
+            Some(&server(Some("http"), "localhost", Some(8080)))
+        );
+        assert_eq!(
+            rules.redirect_request("api.example.net", 80, "/login"),
+            None
+        );
+
+        assert_eq!(
+            rules.redirect_socket("scores.example.com", 1234),
+            Some(("scores.example.org", 1234))
+        );
+        assert_eq!(
+            rules.redirect_socket("203.0.113.7", 5000),
+            Some(("192.168.1.20", 5000))
+        );
+        assert_eq!(rules.redirect_socket("203.0.113.7", 5001), None);
+        assert_eq!(rules.redirect_socket("api.example.net", 80), None);
+        assert_eq!(rules.redirect_socket("2001:db8::1", 80), Some(("::1", 80)));
+
+        assert!(Rules::parse("example.com").is_err());
+        assert!(Rules::parse("example.com:http = localhost").is_err());
+        assert!(Rules::parse("example.com = ftp://localhost").is_err());
+        assert!(Rules::parse("example.com = file:").is_err());
+        assert!(Rules::parse("[::1 = localhost").is_err());
+    }
+}
@@ -5,5 +5,21 @@
  */
 //! The Core Animation framework.
 
+pub mod ca_animation;
+pub mod ca_base;
+pub mod ca_display_link;
 pub mod ca_eagl_layer;
 pub mod ca_layer;
+pub mod ca_media_timing_function;
+pub mod ca_transaction;
+pub mod ca_transform_3d;
+pub mod composition;
+pub mod presentation;
+
+#[derive(Default)]
+pub struct State {
+    ca_animation: ca_animation::State,
+    ca_layer: ca_layer::State,
+    ca_transaction: ca_transaction::State,
+    composition: composition::State,
+}
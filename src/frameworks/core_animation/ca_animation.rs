@@ -0,0 +1,952 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CAAnimation` and its subclasses.
+//!
+//! When an animation is added to a layer, a copy of it is made, as in Core
+//! Animation, and that copy is parsed into host-side [LayerAnimation] so the
+//! compositor doesn't have to send messages to find out what to draw. The
+//! parsed animations are applied to the layer's model values to get its
+//! presentation values (see [super::presentation]).
+
+use super::ca_base::CACurrentMediaTime;
+use super::ca_layer::CALayerHostObject;
+use super::ca_media_timing_function::{self, ControlPoints, LINEAR};
+use super::presentation::{self, Properties, Value};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_foundation::CFTimeInterval;
+use crate::frameworks::core_graphics::CGFloat;
+use crate::frameworks::foundation::ns_string::{get_static_str, to_rust_string};
+use crate::frameworks::foundation::NSUInteger;
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, release, retain, Class, ClassExports, HostObject,
+};
+use crate::Environment;
+
+pub const kCAFillModeForwards: &str = "forwards";
+pub const kCAFillModeBackwards: &str = "backwards";
+pub const kCAFillModeBoth: &str = "both";
+pub const kCAFillModeRemoved: &str = "removed";
+
+pub const kCAAnimationLinear: &str = "linear";
+pub const kCAAnimationDiscrete: &str = "discrete";
+pub const kCAAnimationPaced: &str = "paced";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCAFillModeForwards",
+        HostConstant::NSString(kCAFillModeForwards),
+    ),
+    (
+        "_kCAFillModeBackwards",
+        HostConstant::NSString(kCAFillModeBackwards),
+    ),
+    ("_kCAFillModeBoth", HostConstant::NSString(kCAFillModeBoth)),
+    (
+        "_kCAFillModeRemoved",
+        HostConstant::NSString(kCAFillModeRemoved),
+    ),
+    (
+        "_kCAAnimationLinear",
+        HostConstant::NSString(kCAAnimationLinear),
+    ),
+    (
+        "_kCAAnimationDiscrete",
+        HostConstant::NSString(kCAAnimationDiscrete),
+    ),
+    (
+        "_kCAAnimationPaced",
+        HostConstant::NSString(kCAAnimationPaced),
+    ),
+];
+
+/// The duration used for animations with a duration of zero.
+const DEFAULT_DURATION: CFTimeInterval = 0.25;
+
+#[derive(Default)]
+pub struct State {
+    /// Layers that have animations. Weak references.
+    animating_layers: Vec<id>,
+}
+
+/// Belongs to `CAAnimation` and all its subclasses. The fields for subclasses
+/// are unused in their superclasses.
+#[derive(Clone)]
+struct CAAnimationHostObject {
+    duration: CFTimeInterval,
+    begin_time: CFTimeInterval,
+    time_offset: CFTimeInterval,
+    speed: f32,
+    repeat_count: f32,
+    repeat_duration: CFTimeInterval,
+    autoreverses: bool,
+    /// `NSString*`, strong reference. Nil means `kCAFillModeRemoved`.
+    fill_mode: id,
+    removed_on_completion: bool,
+    /// `CAMediaTimingFunction*`, strong reference. Nil means linear.
+    timing_function: id,
+    /// Strong reference, unusually for a delegate.
+    delegate: id,
+    /// For `CAPropertyAnimation`: `NSString*`, strong reference.
+    key_path: id,
+    /// For `CAPropertyAnimation`.
+    additive: bool,
+    /// For `CAPropertyAnimation`.
+    cumulative: bool,
+    /// For `CABasicAnimation`: strong reference.
+    from_value: id,
+    /// For `CABasicAnimation`: strong reference.
+    to_value: id,
+    /// For `CABasicAnimation`: strong reference.
+    by_value: id,
+    /// For `CAKeyframeAnimation`: `NSArray*`, strong reference.
+    values: id,
+    /// For `CAKeyframeAnimation`: `NSArray<NSNumber*>*`, strong reference.
+    key_times: id,
+    /// For `CAKeyframeAnimation`: `NSArray<CAMediaTimingFunction*>*`, strong
+    /// reference.
+    timing_functions: id,
+    /// For `CAKeyframeAnimation`: `NSString*`, strong reference. Nil means
+    /// `kCAAnimationLinear`.
+    calculation_mode: id,
+    /// For `CAAnimationGroup`: `NSArray<CAAnimation*>*`, strong reference.
+    animations: id,
+}
+impl HostObject for CAAnimationHostObject {}
+
+impl CAAnimationHostObject {
+    /// All the object fields, which are strong references.
+    fn objects(&self) -> [id; 12] {
+        [
+            self.fill_mode,
+            self.timing_function,
+            self.delegate,
+            self.key_path,
+            self.from_value,
+            self.to_value,
+            self.by_value,
+            self.values,
+            self.key_times,
+            self.timing_functions,
+            self.calculation_mode,
+            self.animations,
+        ]
+    }
+}
+
+fn borrow(env: &mut Environment, animation: id) -> &mut CAAnimationHostObject {
+    env.objc.borrow_mut(animation)
+}
+
+/// Shared implementation of setters for object properties. `copy` is for
+/// properties declared with `copy` rather than `retain`.
+fn set_object(
+    env: &mut Environment,
+    this: id,
+    value: id,
+    copy: bool,
+    field: fn(&mut CAAnimationHostObject) -> &mut id,
+) {
+    let value = if copy && value != nil {
+        msg![env; value copy]
+    } else {
+        retain(env, value)
+    };
+    let old = std::mem::replace(field(borrow(env, this)), value);
+    release(env, old);
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CAAnimation: NSObject
+
++ (id)alloc {
+    let host_object = Box::new(CAAnimationHostObject {
+        duration: 0.0,
+        begin_time: 0.0,
+        time_offset: 0.0,
+        speed: 1.0,
+        repeat_count: 0.0,
+        repeat_duration: 0.0,
+        autoreverses: false,
+        fill_mode: nil,
+        removed_on_completion: true,
+        timing_function: nil,
+        delegate: nil,
+        key_path: nil,
+        additive: false,
+        cumulative: false,
+        from_value: nil,
+        to_value: nil,
+        by_value: nil,
+        values: nil,
+        key_times: nil,
+        timing_functions: nil,
+        calculation_mode: nil,
+        animations: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)animation {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new init];
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    for object in borrow(env, this).objects() {
+        release(env, object);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    let host_object = borrow(env, this).clone();
+    for object in host_object.objects() {
+        retain(env, object);
+    }
+    let class: Class = msg![env; this class];
+    let new: id = msg![env; class alloc];
+    *borrow(env, new) = host_object;
+    new
+}
+
+// CAMediaTiming implementation
+
+- (CFTimeInterval)duration {
+    borrow(env, this).duration
+}
+- (())setDuration:(CFTimeInterval)duration {
+    borrow(env, this).duration = duration;
+}
+- (CFTimeInterval)beginTime {
+    borrow(env, this).begin_time
+}
+- (())setBeginTime:(CFTimeInterval)begin_time {
+    borrow(env, this).begin_time = begin_time;
+}
+- (CFTimeInterval)timeOffset {
+    borrow(env, this).time_offset
+}
+- (())setTimeOffset:(CFTimeInterval)time_offset {
+    borrow(env, this).time_offset = time_offset;
+}
+- (f32)speed {
+    borrow(env, this).speed
+}
+- (())setSpeed:(f32)speed {
+    borrow(env, this).speed = speed;
+}
+- (f32)repeatCount {
+    borrow(env, this).repeat_count
+}
+- (())setRepeatCount:(f32)repeat_count {
+    borrow(env, this).repeat_count = repeat_count;
+}
+- (CFTimeInterval)repeatDuration {
+    borrow(env, this).repeat_duration
+}
+- (())setRepeatDuration:(CFTimeInterval)repeat_duration {
+    borrow(env, this).repeat_duration = repeat_duration;
+}
+- (bool)autoreverses {
+    borrow(env, this).autoreverses
+}
+- (())setAutoreverses:(bool)autoreverses {
+    borrow(env, this).autoreverses = autoreverses;
+}
+- (id)fillMode {
+    let fill_mode = borrow(env, this).fill_mode;
+    if fill_mode == nil {
+        get_static_str(env, kCAFillModeRemoved)
+    } else {
+        fill_mode
+    }
+}
+- (())setFillMode:(id)fill_mode { // NSString*
+    set_object(env, this, fill_mode, true, |host_object| &mut host_object.fill_mode);
+}
+
+- (bool)isRemovedOnCompletion {
+    borrow(env, this).removed_on_completion
+}
+- (())setRemovedOnCompletion:(bool)removed {
+    borrow(env, this).removed_on_completion = removed;
+}
+
+- (id)timingFunction {
+    borrow(env, this).timing_function
+}
+- (())setTimingFunction:(id)function { // CAMediaTimingFunction*
+    set_object(env, this, function, false, |host_object| &mut host_object.timing_function);
+}
+
+- (id)delegate {
+    borrow(env, this).delegate
+}
+- (())setDelegate:(id)delegate {
+    set_object(env, this, delegate, false, |host_object| &mut host_object.delegate);
+}
+
+// CAAction implementation
+- (())runActionForKey:(id)key // NSString*
+               object:(id)object // CALayer*
+            arguments:(id)_arguments { // NSDictionary*
+    () = msg![env; object addAnimation:this forKey:key];
+}
+
+@end
+
+@implementation CAPropertyAnimation: CAAnimation
+
++ (id)animationWithKeyPath:(id)key_path { // NSString*
+    let new: id = msg![env; this animation];
+    () = msg![env; new setKeyPath:key_path];
+    new
+}
+
+- (id)keyPath {
+    borrow(env, this).key_path
+}
+- (())setKeyPath:(id)key_path { // NSString*
+    set_object(env, this, key_path, true, |host_object| &mut host_object.key_path);
+}
+- (bool)isAdditive {
+    borrow(env, this).additive
+}
+- (())setAdditive:(bool)additive {
+    borrow(env, this).additive = additive;
+}
+- (bool)isCumulative {
+    borrow(env, this).cumulative
+}
+- (())setCumulative:(bool)cumulative {
+    borrow(env, this).cumulative = cumulative;
+}
+
+@end
+
+@implementation CABasicAnimation: CAPropertyAnimation
+
+- (id)fromValue {
+    borrow(env, this).from_value
+}
+- (())setFromValue:(id)value {
+    set_object(env, this, value, false, |host_object| &mut host_object.from_value);
+}
+- (id)toValue {
+    borrow(env, this).to_value
+}
+- (())setToValue:(id)value {
+    set_object(env, this, value, false, |host_object| &mut host_object.to_value);
+}
+- (id)byValue {
+    borrow(env, this).by_value
+}
+- (())setByValue:(id)value {
+    set_object(env, this, value, false, |host_object| &mut host_object.by_value);
+}
+
+@end
+
+@implementation CAKeyframeAnimation: CAPropertyAnimation
+
+- (id)values {
+    borrow(env, this).values
+}
+- (())setValues:(id)values { // NSArray*
+    set_object(env, this, values, true, |host_object| &mut host_object.values);
+}
+- (id)keyTimes {
+    borrow(env, this).key_times
+}
+- (())setKeyTimes:(id)key_times { // NSArray<NSNumber*>*
+    set_object(env, this, key_times, true, |host_object| &mut host_object.key_times);
+}
+- (id)timingFunctions {
+    borrow(env, this).timing_functions
+}
+- (())setTimingFunctions:(id)functions { // NSArray<CAMediaTimingFunction*>*
+    set_object(env, this, functions, true, |host_object| &mut host_object.timing_functions);
+}
+- (id)calculationMode {
+    let mode = borrow(env, this).calculation_mode;
+    if mode == nil {
+        get_static_str(env, kCAAnimationLinear)
+    } else {
+        mode
+    }
+}
+- (())setCalculationMode:(id)mode { // NSString*
+    set_object(env, this, mode, true, |host_object| &mut host_object.calculation_mode);
+}
+
+- (MutVoidPtr)path {
+    MutVoidPtr::null()
+}
+- (())setPath:(MutVoidPtr)path { // CGPathRef
+    if !path.is_null() {
+        log!("TODO: [(CAKeyframeAnimation*){:?} setPath:{:?}] (ignored)", this, path);
+    }
+}
+
+@end
+
+@implementation CAAnimationGroup: CAAnimation
+
+- (id)animations {
+    borrow(env, this).animations
+}
+- (())setAnimations:(id)animations { // NSArray<CAAnimation*>*
+    set_object(env, this, animations, true, |host_object| &mut host_object.animations);
+}
+
+@end
+
+};
+
+/// An animation that has been added to a layer.
+pub(super) struct LayerAnimation {
+    /// The key it was added for, if any.
+    pub(super) key: Option<String>,
+    /// `CAAnimation*`, the copy made when it was added. Strong reference.
+    pub(super) animation: id,
+    timing: Timing,
+    kind: Kind,
+    /// Whether `animationDidStart:` has been sent.
+    started: bool,
+    /// Whether `animationDidStop:finished:` has been sent, for animations
+    /// that aren't removed on completion.
+    stopped: bool,
+}
+
+/// The `CAMediaTiming` properties of an animation.
+#[derive(Copy, Clone, Debug)]
+struct Timing {
+    /// In the parent's time space: `CACurrentMediaTime()` for animations
+    /// added to a layer, the group's time for animations in a group.
+    begin_time: CFTimeInterval,
+    duration: CFTimeInterval,
+    speed: CFTimeInterval,
+    time_offset: CFTimeInterval,
+    repeat_count: CFTimeInterval,
+    repeat_duration: CFTimeInterval,
+    autoreverses: bool,
+    fill_backwards: bool,
+    fill_forwards: bool,
+    timing_function: ControlPoints,
+}
+
+#[derive(Clone, Debug)]
+enum Kind {
+    Basic {
+        property: PropertyAnimation,
+        from: Option<Value>,
+        to: Option<Value>,
+        by: Option<Value>,
+    },
+    Keyframe {
+        property: PropertyAnimation,
+        values: Vec<Value>,
+        /// Empty if the keyframes are evenly spaced.
+        key_times: Vec<CFTimeInterval>,
+        /// Empty if the segments are linear.
+        timing_functions: Vec<ControlPoints>,
+        discrete: bool,
+    },
+    Group(Vec<(Timing, Kind)>),
+    /// Animations that don't change any properties, like plain `CAAnimation`s,
+    /// which are still useful for their delegate messages.
+    Inert,
+}
+
+#[derive(Clone, Debug)]
+struct PropertyAnimation {
+    key_path: String,
+    additive: bool,
+    cumulative: bool,
+}
+
+impl Timing {
+    /// The duration of one cycle, going forwards and then (if autoreversing)
+    /// backwards.
+    fn cycle_duration(&self) -> CFTimeInterval {
+        if self.autoreverses {
+            self.duration * 2.0
+        } else {
+            self.duration
+        }
+    }
+
+    /// The duration of all the repeats, in local time.
+    fn active_duration(&self) -> CFTimeInterval {
+        if self.repeat_duration > 0.0 {
+            self.repeat_duration
+        } else if self.repeat_count > 0.0 {
+            self.cycle_duration() * self.repeat_count
+        } else {
+            self.cycle_duration()
+        }
+    }
+
+    /// When the animation ends, in the parent's time space.
+    fn end_time(&self) -> CFTimeInterval {
+        if self.speed > 0.0 {
+            self.begin_time + (self.active_duration() - self.time_offset).max(0.0) / self.speed
+        } else {
+            CFTimeInterval::INFINITY
+        }
+    }
+
+    /// Get the progress through the current cycle at `parent_time`, from 0
+    /// to 1 with the timing function applied, and the number of the cycle.
+    /// [None] means the animation has no effect at that time.
+    fn progress(&self, parent_time: CFTimeInterval) -> Option<(CGFloat, u32)> {
+        let active_duration = self.active_duration();
+        let local_time = (parent_time - self.begin_time) * self.speed + self.time_offset;
+        let local_time = if local_time < 0.0 {
+            if !self.fill_backwards {
+                return None;
+            }
+            0.0
+        } else if local_time >= active_duration {
+            if !self.fill_forwards {
+                return None;
+            }
+            active_duration
+        } else {
+            local_time
+        };
+        if self.duration <= 0.0 {
+            return Some((1.0, 0));
+        }
+
+        let cycle_duration = self.cycle_duration();
+        let mut cycle = (local_time / cycle_duration).floor();
+        let mut cycle_time = local_time - cycle * cycle_duration;
+        // The end of the last cycle, rather than the start of the next.
+        if local_time > 0.0 && local_time >= active_duration && cycle_time == 0.0 {
+            cycle -= 1.0;
+            cycle_time = cycle_duration;
+        }
+        if cycle_time > self.duration {
+            cycle_time = cycle_duration - cycle_time;
+        }
+        let t = (cycle_time / self.duration) as f32;
+        Some((
+            ca_media_timing_function::evaluate(self.timing_function, t),
+            cycle as u32,
+        ))
+    }
+}
+
+/// Get the objects in an `NSArray`, or nothing if it's nil.
+fn array_objects(env: &mut Environment, array: id) -> Vec<id> {
+    if array == nil {
+        return Vec::new();
+    }
+    let count: NSUInteger = msg![env; array count];
+    (0..count)
+        .map(|i| msg![env; array objectAtIndex:i])
+        .collect()
+}
+
+/// Read an animation's settings. `begin_time` replaces a begin time of zero.
+fn parse(env: &mut Environment, animation: id, begin_time: CFTimeInterval) -> (Timing, Kind) {
+    let host_object = borrow(env, animation).clone();
+    let fill_mode = if host_object.fill_mode == nil {
+        kCAFillModeRemoved.to_string()
+    } else {
+        to_rust_string(env, host_object.fill_mode).into_owned()
+    };
+    let timing_function = if host_object.timing_function == nil {
+        LINEAR
+    } else {
+        ca_media_timing_function::control_points(env, host_object.timing_function)
+    };
+    let timing = Timing {
+        begin_time: if host_object.begin_time == 0.0 {
+            begin_time
+        } else {
+            host_object.begin_time
+        },
+        duration: if host_object.duration == 0.0 {
+            DEFAULT_DURATION
+        } else {
+            host_object.duration
+        },
+        speed: host_object.speed.into(),
+        time_offset: host_object.time_offset,
+        repeat_count: host_object.repeat_count.into(),
+        repeat_duration: host_object.repeat_duration,
+        autoreverses: host_object.autoreverses,
+        fill_backwards: fill_mode == kCAFillModeBackwards || fill_mode == kCAFillModeBoth,
+        fill_forwards: fill_mode == kCAFillModeForwards || fill_mode == kCAFillModeBoth,
+        timing_function,
+    };
+
+    let property = if host_object.key_path != nil {
+        Some(PropertyAnimation {
+            key_path: to_rust_string(env, host_object.key_path).into_owned(),
+            additive: host_object.additive,
+            cumulative: host_object.cumulative,
+        })
+    } else {
+        None
+    };
+    let value = |env: &mut Environment, object: id| {
+        (object != nil).then(|| Value::from_object(env, object))
+    };
+
+    let basic_class = env.objc.get_known_class("CABasicAnimation", &mut env.mem);
+    let keyframe_class = env
+        .objc
+        .get_known_class("CAKeyframeAnimation", &mut env.mem);
+    let group_class = env.objc.get_known_class("CAAnimationGroup", &mut env.mem);
+    let is_basic: bool = msg![env; animation isKindOfClass:basic_class];
+    let is_keyframe: bool = msg![env; animation isKindOfClass:keyframe_class];
+    let is_group: bool = msg![env; animation isKindOfClass:group_class];
+    let kind = match property {
+        Some(property) if is_basic => Kind::Basic {
+            property,
+            from: value(env, host_object.from_value),
+            to: value(env, host_object.to_value),
+            by: value(env, host_object.by_value),
+        },
+        Some(property) if is_keyframe => {
+            let values = array_objects(env, host_object.values)
+                .into_iter()
+                .map(|object| Value::from_object(env, object))
+                .collect();
+            let key_times = array_objects(env, host_object.key_times)
+                .into_iter()
+                .map(|number| msg![env; number doubleValue])
+                .collect();
+            let timing_functions = array_objects(env, host_object.timing_functions)
+                .into_iter()
+                .map(|function| ca_media_timing_function::control_points(env, function))
+                .collect();
+            let discrete = host_object.calculation_mode != nil
+                && to_rust_string(env, host_object.calculation_mode) == kCAAnimationDiscrete;
+            // TODO: paced animations, which are treated as linear for now.
+            Kind::Keyframe {
+                property,
+                values,
+                key_times,
+                timing_functions,
+                discrete,
+            }
+        }
+        _ if is_group => Kind::Group(
+            array_objects(env, host_object.animations)
+                .into_iter()
+                .map(|child| parse(env, child, 0.0))
+                .collect(),
+        ),
+        _ => Kind::Inert,
+    };
+    (timing, kind)
+}
+
+/// Apply an animation to `properties` at `parent_time`.
+fn apply(timing: &Timing, kind: &Kind, parent_time: CFTimeInterval, properties: &mut Properties) {
+    let Some((t, cycle)) = timing.progress(parent_time) else {
+        return;
+    };
+    let property = match kind {
+        Kind::Inert => return,
+        Kind::Group(children) => {
+            let local_time = CFTimeInterval::from(t) * timing.duration;
+            for (child_timing, child_kind) in children {
+                apply(child_timing, child_kind, local_time, properties);
+            }
+            return;
+        }
+        Kind::Basic { property, .. } | Kind::Keyframe { property, .. } => property,
+    };
+    let Some(current) = presentation::get(properties, &property.key_path) else {
+        log_dbg!("Can't animate key path {:?}", property.key_path);
+        return;
+    };
+
+    // The value at the start and end of a cycle, and at `t`.
+    let (start, end, mut value) = match kind {
+        Kind::Basic { from, to, by, .. } => {
+            let (from, to) = match (*from, *to, *by) {
+                (Some(from), Some(to), _) => (from, to),
+                (Some(from), None, Some(by)) => (from, from.add(by)),
+                (None, Some(to), Some(by)) => (to.subtract(by), to),
+                (Some(from), None, None) => (from, current),
+                (None, Some(to), None) => (current, to),
+                (None, None, Some(by)) => (current, current.add(by)),
+                (None, None, None) => (current, current),
+            };
+            (from, to, from.interpolate(to, t))
+        }
+        Kind::Keyframe {
+            values,
+            key_times,
+            timing_functions,
+            discrete,
+            ..
+        } => {
+            let (Some(&first), Some(&last)) = (values.first(), values.last()) else {
+                return;
+            };
+            (
+                first,
+                last,
+                keyframe_value(values, key_times, timing_functions, *discrete, t),
+            )
+        }
+        Kind::Inert | Kind::Group(_) => unreachable!(),
+    };
+
+    if property.cumulative && cycle > 0 {
+        value = value.add(end.subtract(start).scale(cycle as CGFloat));
+    }
+    if property.additive {
+        value = current.add(value);
+    }
+    if !presentation::set(properties, &property.key_path, value) {
+        log_dbg!(
+            "Can't animate key path {:?} with value {:?}",
+            property.key_path,
+            value
+        );
+    }
+}
+
+/// Get the value of a keyframe animation at `t`.
+fn keyframe_value(
+    values: &[Value],
+    key_times: &[CFTimeInterval],
+    timing_functions: &[ControlPoints],
+    discrete: bool,
+    t: CGFloat,
+) -> Value {
+    let t = CFTimeInterval::from(t);
+    let count = values.len();
+    // Discrete animations have a key time for the end too.
+    let segments = if discrete { count } else { count - 1 };
+    let key_time = |i: usize| {
+        if key_times.len() > segments {
+            key_times[i]
+        } else if segments == 0 {
+            0.0
+        } else {
+            i as CFTimeInterval / segments as CFTimeInterval
+        }
+    };
+    let index = (0..count).rev().find(|&i| key_time(i) <= t).unwrap_or(0);
+    if discrete || index + 1 >= count {
+        return values[index];
+    }
+    let (start, end) = (key_time(index), key_time(index + 1));
+    let segment_t = if end > start {
+        ((t - start) / (end - start)) as f32
+    } else {
+        1.0
+    };
+    let segment_t = match timing_functions.get(index) {
+        Some(&function) => ca_media_timing_function::evaluate(function, segment_t),
+        None => segment_t,
+    };
+    values[index].interpolate(values[index + 1], segment_t)
+}
+
+/// For use by [presentation::presentation]: apply a layer's animations to its
+/// properties.
+pub(super) fn apply_animations(env: &mut Environment, layer: id, properties: &mut Properties) {
+    let now = CACurrentMediaTime(env);
+    for animation in &env.objc.borrow::<CALayerHostObject>(layer).animations {
+        apply(&animation.timing, &animation.kind, now, properties);
+    }
+}
+
+/// For use by `CALayer`: implementation of `addAnimation:forKey:`.
+pub(super) fn add_animation(env: &mut Environment, layer: id, animation: id, key: id) {
+    let key = (key != nil).then(|| to_rust_string(env, key).into_owned());
+    if let Some(ref key) = key {
+        remove_animation(env, layer, Some(key));
+    }
+
+    let animation: id = msg![env; animation copy];
+    let now = CACurrentMediaTime(env);
+    if borrow(env, animation).begin_time == 0.0 {
+        borrow(env, animation).begin_time = now;
+    }
+    let (timing, kind) = parse(env, animation, now);
+    log_dbg!(
+        "Adding animation {:?} for key {:?} to layer {:?}: {:?}, {:?}",
+        animation,
+        key,
+        layer,
+        timing,
+        kind
+    );
+    env.objc
+        .borrow_mut::<CALayerHostObject>(layer)
+        .animations
+        .push(LayerAnimation {
+            key,
+            animation,
+            timing,
+            kind,
+            started: false,
+            stopped: false,
+        });
+    let animating_layers = &mut env
+        .framework_state
+        .core_animation
+        .ca_animation
+        .animating_layers;
+    if !animating_layers.contains(&layer) {
+        animating_layers.push(layer);
+    }
+}
+
+/// For use by `CALayer`: remove the animation with a key, or all of them if
+/// `key` is [None], and tell their delegates they didn't finish.
+pub(super) fn remove_animation(env: &mut Environment, layer: id, key: Option<&str>) {
+    let animations = &mut env.objc.borrow_mut::<CALayerHostObject>(layer).animations;
+    let (removed, kept) = std::mem::take(animations)
+        .into_iter()
+        .partition::<Vec<_>, _>(|animation| key.is_none() || animation.key.as_deref() == key);
+    *animations = kept;
+    for animation in removed {
+        if !animation.stopped {
+            send_did_stop(env, animation.animation, false);
+        }
+        release(env, animation.animation);
+    }
+}
+
+/// For use by `CALayer`: get the animation with a key, or nil.
+pub(super) fn animation_for_key(env: &mut Environment, layer: id, key: &str) -> id {
+    env.objc
+        .borrow::<CALayerHostObject>(layer)
+        .animations
+        .iter()
+        .find(|animation| animation.key.as_deref() == Some(key))
+        .map_or(nil, |animation| animation.animation)
+}
+
+/// For use by `CALayer`'s `dealloc`: release its animations, without telling
+/// their delegates.
+pub(super) fn layer_deallocated(env: &mut Environment, layer: id) {
+    let animations =
+        std::mem::take(&mut env.objc.borrow_mut::<CALayerHostObject>(layer).animations);
+    for animation in animations {
+        release(env, animation.animation);
+    }
+    env.framework_state
+        .core_animation
+        .ca_animation
+        .animating_layers
+        .retain(|&other| other != layer);
+}
+
+fn send_did_start(env: &mut Environment, animation: id) {
+    let delegate = borrow(env, animation).delegate;
+    if delegate == nil {
+        return;
+    }
+    let selector = env.objc.lookup_selector("animationDidStart:").unwrap();
+    if msg![env; delegate respondsToSelector:selector] {
+        () = msg![env; delegate animationDidStart:animation];
+    }
+}
+
+fn send_did_stop(env: &mut Environment, animation: id, finished: bool) {
+    let delegate = borrow(env, animation).delegate;
+    if delegate == nil {
+        return;
+    }
+    let selector = env
+        .objc
+        .lookup_selector("animationDidStop:finished:")
+        .unwrap();
+    if msg![env; delegate respondsToSelector:selector] {
+        () = msg![env; delegate animationDidStop:animation finished:finished];
+    }
+}
+
+/// For use by the compositor: tell delegates about animations that have
+/// started or finished, and remove finished animations that should be.
+pub fn handle_animations(env: &mut Environment) {
+    if env
+        .framework_state
+        .core_animation
+        .ca_animation
+        .animating_layers
+        .is_empty()
+    {
+        return;
+    }
+    let now = CACurrentMediaTime(env);
+
+    let layers = env
+        .framework_state
+        .core_animation
+        .ca_animation
+        .animating_layers
+        .clone();
+    let mut started = Vec::new();
+    let mut stopped = Vec::new();
+    for &layer in &layers {
+        let animations =
+            std::mem::take(&mut env.objc.borrow_mut::<CALayerHostObject>(layer).animations);
+        let mut kept = Vec::with_capacity(animations.len());
+        for mut animation in animations {
+            if !animation.started && now >= animation.timing.begin_time {
+                animation.started = true;
+                retain(env, animation.animation);
+                started.push(animation.animation);
+            }
+            if !animation.stopped && now >= animation.timing.end_time() {
+                animation.stopped = true;
+                // The reference held by the layer is passed on if the
+                // animation is removed.
+                if !borrow(env, animation.animation).removed_on_completion {
+                    retain(env, animation.animation);
+                    stopped.push(animation.animation);
+                    kept.push(animation);
+                } else {
+                    stopped.push(animation.animation);
+                }
+                continue;
+            }
+            kept.push(animation);
+        }
+        env.objc.borrow_mut::<CALayerHostObject>(layer).animations = kept;
+    }
+    env.framework_state
+        .core_animation
+        .ca_animation
+        .animating_layers
+        .retain(|&layer| {
+            !env.objc
+                .borrow::<CALayerHostObject>(layer)
+                .animations
+                .is_empty()
+        });
+
+    // Delegates can add and remove animations, so nothing can be borrowed
+    // while sending these.
+    for animation in started {
+        send_did_start(env, animation);
+        release(env, animation);
+    }
+    for animation in stopped {
+        send_did_stop(env, animation, true);
+        release(env, animation);
+    }
+}
@@ -0,0 +1,221 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CADisplayLink`.
+//!
+//! The simulated screen refreshes at a fixed rate (see the `--frame-rate=`
+//! option), with its first refresh at the media time origin (see
+//! [CACurrentMediaTime]), so all display links are in phase, like on a real
+//! device. A display link fires on the first run loop iteration after each
+//! refresh it's due for, and the run loop sleeps no longer than it needs to
+//! for that (see [time_until_next_frame]). The `timestamp` is the time of the
+//! refresh rather than of the callback, so the app sees evenly-spaced frames
+//! even when callbacks are a little late. If the app falls more than a
+//! frame behind, the frames it missed are dropped rather than delivered in a
+//! burst.
+
+use super::ca_base::CACurrentMediaTime;
+use crate::frameworks::core_foundation::CFTimeInterval;
+use crate::frameworks::foundation::{ns_run_loop, NSInteger};
+use crate::objc::{
+    autorelease, id, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    HostObject, SEL,
+};
+use crate::Environment;
+use std::time::Duration;
+
+/// The refresh rate used if the host display's can't be found.
+const DEFAULT_FRAME_RATE: f64 = 60.0;
+
+struct CADisplayLinkHostObject {
+    /// Strong reference.
+    target: id,
+    selector: SEL,
+    frame_interval: NSInteger,
+    paused: bool,
+    /// The time of the refresh the last callback was for.
+    timestamp: CFTimeInterval,
+    /// The number of the next refresh this should fire for.
+    next_frame: u64,
+    /// The run loop the link is scheduled on, if any. Weak reference.
+    run_loop: id,
+}
+impl HostObject for CADisplayLinkHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CADisplayLink: NSObject
+
++ (id)displayLinkWithTarget:(id)target
+                   selector:(SEL)selector {
+    retain(env, target);
+    let host_object = Box::new(CADisplayLinkHostObject {
+        target,
+        selector,
+        frame_interval: 1,
+        paused: false,
+        timestamp: 0.0,
+        next_frame: 0,
+        run_loop: nil,
+    });
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    log_dbg!(
+        "New display link {:?}, target [{:?} {}]",
+        new,
+        target,
+        selector.as_str(&env.mem),
+    );
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let target = env.objc.borrow::<CADisplayLinkHostObject>(this).target;
+    release(env, target);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (())addToRunLoop:(id)run_loop // NSRunLoop*
+           forMode:(id)mode { // NSRunLoopMode
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(this);
+    if host_object.target == nil {
+        log!("Warning: adding invalidated display link {:?} to a run loop, ignoring", this);
+        return;
+    }
+    assert!(host_object.run_loop == nil || host_object.run_loop == run_loop);
+    host_object.run_loop = run_loop;
+    // The first callback is at the next refresh.
+    let next_frame = current_frame(env) + 1;
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(this);
+    host_object.next_frame = host_object.next_frame.max(next_frame);
+    ns_run_loop::add_display_link(env, run_loop, this, mode);
+}
+- (())removeFromRunLoop:(id)run_loop // NSRunLoop*
+                forMode:(id)mode { // NSRunLoopMode
+    if ns_run_loop::remove_display_link(env, run_loop, this, mode) {
+        env.objc.borrow_mut::<CADisplayLinkHostObject>(this).run_loop = nil;
+    }
+}
+- (())invalidate {
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(this);
+    let run_loop = std::mem::replace(&mut host_object.run_loop, nil);
+    let target = std::mem::replace(&mut host_object.target, nil);
+    // The run loop's reference might be the last one.
+    retain(env, this);
+    if run_loop != nil {
+        ns_run_loop::remove_display_link(env, run_loop, this, nil);
+    }
+    release(env, target);
+    release(env, this);
+}
+
+- (NSInteger)frameInterval {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).frame_interval
+}
+- (())setFrameInterval:(NSInteger)interval {
+    if interval < 1 {
+        log!("Warning: [{:?} setFrameInterval:{}] ignored", this, interval);
+        return;
+    }
+    env.objc.borrow_mut::<CADisplayLinkHostObject>(this).frame_interval = interval;
+}
+
+- (bool)isPaused {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).paused
+}
+- (())setPaused:(bool)paused {
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(this);
+    let was_paused = std::mem::replace(&mut host_object.paused, paused);
+    // Frames missed while paused aren't made up for.
+    if was_paused && !paused {
+        let next_frame = current_frame(env) + 1;
+        env.objc.borrow_mut::<CADisplayLinkHostObject>(this).next_frame = next_frame;
+    }
+}
+
+- (CFTimeInterval)timestamp {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).timestamp
+}
+- (CFTimeInterval)duration {
+    frame_period(env)
+}
+
+@end
+
+};
+
+/// The time between refreshes of the simulated screen, in seconds.
+fn frame_period(env: &mut Environment) -> CFTimeInterval {
+    let rate = env.options.frame_rate.unwrap_or_else(|| {
+        env.window
+            .refresh_rate()
+            .map_or(DEFAULT_FRAME_RATE, CFTimeInterval::from)
+    });
+    1.0 / rate
+}
+
+/// The number of the most recent refresh.
+fn current_frame(env: &mut Environment) -> u64 {
+    (CACurrentMediaTime(env) / frame_period(env)).floor() as u64
+}
+
+/// For use by `NSRunLoop`: fire a display link if a refresh it's due for has
+/// happened.
+pub fn handle_display_link(env: &mut Environment, link: id) {
+    let &CADisplayLinkHostObject {
+        target,
+        selector,
+        frame_interval,
+        paused,
+        next_frame,
+        ..
+    } = env.objc.borrow(link);
+    let frame = current_frame(env);
+    if paused || target == nil || frame < next_frame {
+        return;
+    }
+
+    let period = frame_period(env);
+    if frame > next_frame {
+        log_dbg!(
+            "Display link {:?} is {} frame(s) late",
+            link,
+            frame - next_frame
+        );
+    }
+    // Later refreshes are counted from the one the link was due for, so a
+    // late callback doesn't push the following ones back.
+    let interval = frame_interval as u64;
+    let due_frame = next_frame + (frame - next_frame) / interval * interval;
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(link);
+    host_object.next_frame = due_frame + interval;
+    host_object.timestamp = due_frame as CFTimeInterval * period;
+
+    // The link may be invalidated by its target.
+    retain(env, link);
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let () = msg_send(env, (target, selector, link));
+    release(env, pool);
+    release(env, link);
+}
+
+/// For use by `NSRunLoop`: how long until a display link should next fire, or
+/// [None] if it's paused.
+pub fn time_until_next_frame(env: &mut Environment, link: id) -> Option<Duration> {
+    let &CADisplayLinkHostObject {
+        target,
+        paused,
+        next_frame,
+        ..
+    } = env.objc.borrow(link);
+    if paused || target == nil {
+        return None;
+    }
+    let due = next_frame as CFTimeInterval * frame_period(env);
+    Some(Duration::from_secs_f64(
+        (due - CACurrentMediaTime(env)).max(0.0),
+    ))
+}
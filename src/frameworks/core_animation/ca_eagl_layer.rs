@@ -5,8 +5,17 @@
  */
 //! `CAEAGLLayer`.
 
-use super::ca_layer::CALayerHostObject;
-use crate::objc::{id, msg, objc_classes, ClassExports};
+use super::ca_layer::{new_contents_generation, CALayerHostObject};
+use crate::objc::{id, msg, objc_classes, release, ClassExports};
+use crate::Environment;
+
+/// A frame presented by the app with `presentRenderbuffer:`.
+pub(super) struct EAGLFrame {
+    /// RGBA8, bottom row first (like OpenGL).
+    pub(super) pixels: Vec<u8>,
+    pub(super) width: u32,
+    pub(super) height: u32,
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -23,9 +32,34 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (())setDrawableProperties:(id)props { // NSDictionary<NSString*, id>*
     let props: id = msg![env; props copy];
-    env.objc.borrow_mut::<CALayerHostObject>(this).drawable_properties = props;
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(this);
+    let old = std::mem::replace(&mut host_object.drawable_properties, props);
+    release(env, old);
+}
+
+- (())display {
+    // The contents come from presentRenderbuffer:, not a backing store.
 }
 
 @end
 
 };
+
+/// For use by `EAGLContext`: store a frame presented by the app, so the
+/// compositor can draw it (see [super::composition]).
+pub fn set_presented_frame(
+    env: &mut Environment,
+    layer: id,
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+) {
+    let generation = new_contents_generation(env);
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(layer);
+    host_object.presented_frame = Some(EAGLFrame {
+        pixels,
+        width,
+        height,
+    });
+    host_object.contents_generation = generation;
+}
@@ -4,18 +4,108 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `CALayer`.
+//!
+//! Layers are drawn by the compositor (see [super::composition]). A layer's
+//! `contents` is usually a `CGImageRef`, either provided by the app or
+//! created by `display`, which draws into a bitmap context (the layer's
+//! "backing store") with `drawInContext:`.
+//!
+//! Changing an animatable property runs the action for its key (see
+//! `actionForKey:`), which is usually an implicit animation from the old value
+//! to the new one.
 
-use crate::objc::{id, msg, nil, objc_classes, release, ClassExports, HostObject};
+use super::ca_animation::{self, LayerAnimation};
+use super::ca_eagl_layer::EAGLFrame;
+use super::ca_transaction;
+use super::ca_transform_3d::{CATransform3D, CATransform3DIdentity};
+use super::presentation;
+use crate::frameworks::core_graphics::cg_affine_transform::{
+    CGAffineTransform, CGAffineTransformIdentity,
+};
+use crate::frameworks::core_graphics::cg_bitmap_context::{self, CGBitmapContextCreate};
+use crate::frameworks::core_graphics::cg_color;
+use crate::frameworks::core_graphics::cg_color_space::{
+    CGColorSpaceCreateDeviceRGB, CGColorSpaceRelease,
+};
+use crate::frameworks::core_graphics::cg_context::{CGContextRef, CGContextRelease};
+use crate::frameworks::core_graphics::cg_image::{
+    self, kCGImageAlphaPremultipliedLast, CGImageRelease,
+};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string::{self, get_static_str, to_rust_string};
+use crate::frameworks::foundation::{ns_array, NSUInteger};
+use crate::mem::{GuestUSize, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, Class, ClassExports,
+    HostObject,
+};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// The last value given out by [new_contents_generation].
+    last_contents_generation: u64,
+}
 
 pub(super) struct CALayerHostObject {
     /// Possibly nil, usually a UIView. This is a weak reference.
-    delegate: id,
+    pub(super) delegate: id,
+    /// Weak reference.
+    pub(super) superlayer: id,
+    /// Strong references, back to front (before sorting by `zPosition`).
+    pub(super) sublayers: Vec<id>,
+    pub(super) bounds: CGRect,
+    /// Where the anchor point is in the superlayer's co-ordinate space.
+    pub(super) position: CGPoint,
+    /// In the unit co-ordinate space of the bounds, (0.5, 0.5) is the center.
+    pub(super) anchor_point: CGPoint,
+    pub(super) z_position: CGFloat,
+    /// Applied around the anchor point.
+    pub(super) transform: CATransform3D,
+    pub(super) opacity: f32,
+    pub(super) hidden: bool,
     opaque: bool,
+    pub(super) masks_to_bounds: bool,
+    pub(super) corner_radius: CGFloat,
+    /// `CGColorRef`, strong reference.
+    pub(super) background_color: id,
+    pub(super) border_width: CGFloat,
+    /// `CGColorRef`, strong reference. Nil means opaque black.
+    pub(super) border_color: id,
+    /// `CGColorRef`, strong reference. Nil means opaque black.
+    pub(super) shadow_color: id,
+    pub(super) shadow_opacity: f32,
+    pub(super) shadow_offset: CGSize,
+    pub(super) shadow_radius: CGFloat,
+    /// Usually a `CGImageRef`. Strong reference.
+    pub(super) contents: id,
+    /// Changes whenever the contents do, so the compositor knows when its copy
+    /// is out of date.
+    pub(super) contents_generation: u64,
+    pub(super) needs_display: bool,
+    needs_display_on_bounds_change: bool,
+    /// `NSDictionary<NSString*, id<CAAction>>*`, strong reference.
+    actions: id,
+    /// Added animations, in the order they're applied.
+    pub(super) animations: Vec<LayerAnimation>,
+    /// For presentation layers only: the layer they're a copy of. Strong
+    /// reference.
+    model_layer: id,
     /// For CAEAGLLayer only
     pub(super) drawable_properties: id,
+    /// For CAEAGLLayer only: the last frame presented by the app, if it needs
+    /// to be composited rather than presented directly.
+    pub(super) presented_frame: Option<EAGLFrame>,
 }
 impl HostObject for CALayerHostObject {}
 
+/// Get a value that hasn't been used as a contents generation before.
+pub(super) fn new_contents_generation(env: &mut Environment) -> u64 {
+    let state = &mut env.framework_state.core_animation.ca_layer;
+    state.last_contents_generation += 1;
+    state.last_contents_generation
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -25,22 +115,131 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (id)alloc {
     let host_object = Box::new(CALayerHostObject {
         delegate: nil,
+        superlayer: nil,
+        sublayers: Vec::new(),
+        bounds: CGRect {
+            origin: CGPoint { x: 0.0, y: 0.0 },
+            size: CGSize { width: 0.0, height: 0.0 },
+        },
+        position: CGPoint { x: 0.0, y: 0.0 },
+        anchor_point: CGPoint { x: 0.5, y: 0.5 },
+        z_position: 0.0,
+        transform: CATransform3DIdentity,
+        opacity: 1.0,
+        hidden: false,
         opaque: false,
+        masks_to_bounds: false,
+        corner_radius: 0.0,
+        background_color: nil,
+        border_width: 0.0,
+        border_color: nil,
+        shadow_color: nil,
+        shadow_opacity: 0.0,
+        shadow_offset: CGSize { width: 0.0, height: -3.0 },
+        shadow_radius: 3.0,
+        contents: nil,
+        contents_generation: 0,
+        needs_display: false,
+        needs_display_on_bounds_change: false,
+        actions: nil,
+        animations: Vec::new(),
+        model_layer: nil,
         drawable_properties: nil,
+        presented_frame: None,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
 + (id)layer {
     let new_layer: id = msg![env; this alloc];
-    msg![env; new_layer init]
+    let new_layer: id = msg![env; new_layer init];
+    autorelease(env, new_layer)
+}
+
++ (id)defaultActionForKey:(id)_key { // NSString*
+    nil
+}
+
+// Used for presentation layers, and by apps for their own copies.
+- (id)initWithLayer:(id)layer { // CALayer*
+    let &CALayerHostObject {
+        bounds,
+        position,
+        anchor_point,
+        z_position,
+        transform,
+        opacity,
+        hidden,
+        opaque,
+        masks_to_bounds,
+        corner_radius,
+        background_color,
+        border_width,
+        border_color,
+        shadow_color,
+        shadow_opacity,
+        shadow_offset,
+        shadow_radius,
+        contents,
+        contents_generation,
+        needs_display_on_bounds_change,
+        actions,
+        ..
+    } = env.objc.borrow(layer);
+    for object in [background_color, border_color, shadow_color, contents, actions] {
+        retain(env, object);
+    }
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(this);
+    host_object.bounds = bounds;
+    host_object.position = position;
+    host_object.anchor_point = anchor_point;
+    host_object.z_position = z_position;
+    host_object.transform = transform;
+    host_object.opacity = opacity;
+    host_object.hidden = hidden;
+    host_object.opaque = opaque;
+    host_object.masks_to_bounds = masks_to_bounds;
+    host_object.corner_radius = corner_radius;
+    host_object.background_color = background_color;
+    host_object.border_width = border_width;
+    host_object.border_color = border_color;
+    host_object.shadow_color = shadow_color;
+    host_object.shadow_opacity = shadow_opacity;
+    host_object.shadow_offset = shadow_offset;
+    host_object.shadow_radius = shadow_radius;
+    host_object.contents = contents;
+    host_object.contents_generation = contents_generation;
+    host_object.needs_display_on_bounds_change = needs_display_on_bounds_change;
+    host_object.actions = actions;
+    this
 }
 
 - (())dealloc {
-    let &CALayerHostObject { drawable_properties, .. } = env.objc.borrow(this);
-    if drawable_properties != nil {
-        release(env, drawable_properties);
+    ca_animation::layer_deallocated(env, this);
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(this);
+    let sublayers = std::mem::take(&mut host_object.sublayers);
+    let &mut CALayerHostObject {
+        background_color,
+        border_color,
+        shadow_color,
+        contents,
+        actions,
+        model_layer,
+        drawable_properties,
+        ..
+    } = host_object;
+    for sublayer in sublayers {
+        env.objc.borrow_mut::<CALayerHostObject>(sublayer).superlayer = nil;
+        release(env, sublayer);
     }
+    release(env, background_color);
+    release(env, border_color);
+    release(env, shadow_color);
+    release(env, contents);
+    release(env, actions);
+    release(env, model_layer);
+    release(env, drawable_properties);
+    env.objc.dealloc_object(this, &mut env.mem)
 }
 
 - (id)delegate {
@@ -57,8 +256,604 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow_mut::<CALayerHostObject>(this).opaque = opaque;
 }
 
-// TODO
+// Layer hierarchy
+
+- (id)superlayer {
+    env.objc.borrow::<CALayerHostObject>(this).superlayer
+}
+- (id)sublayers {
+    let sublayers = env.objc.borrow::<CALayerHostObject>(this).sublayers.clone();
+    if sublayers.is_empty() {
+        return nil;
+    }
+    for &sublayer in &sublayers {
+        retain(env, sublayer);
+    }
+    let sublayers = ns_array::from_vec(env, sublayers);
+    autorelease(env, sublayers)
+}
+- (())setSublayers:(id)sublayers { // NSArray<CALayer*>*
+    let old = env.objc.borrow::<CALayerHostObject>(this).sublayers.clone();
+    for sublayer in old {
+        () = msg![env; sublayer removeFromSuperlayer];
+    }
+    if sublayers == nil {
+        return;
+    }
+    let count: NSUInteger = msg![env; sublayers count];
+    for i in 0..count {
+        let sublayer: id = msg![env; sublayers objectAtIndex:i];
+        () = msg![env; this addSublayer:sublayer];
+    }
+}
+- (())addSublayer:(id)layer { // CALayer*
+    let count = env.objc.borrow::<CALayerHostObject>(this).sublayers.len();
+    insert_sublayer(env, this, layer, count);
+}
+- (())insertSublayer:(id)layer // CALayer*
+             atIndex:(u32)index {
+    insert_sublayer(env, this, layer, index as usize);
+}
+- (())insertSublayer:(id)layer // CALayer*
+               below:(id)sibling { // CALayer*
+    let index = env.objc.borrow::<CALayerHostObject>(this)
+        .sublayers
+        .iter()
+        .position(|&sublayer| sublayer == sibling)
+        .unwrap_or(0);
+    insert_sublayer(env, this, layer, index);
+}
+- (())insertSublayer:(id)layer // CALayer*
+               above:(id)sibling { // CALayer*
+    let sublayers = &env.objc.borrow::<CALayerHostObject>(this).sublayers;
+    let index = sublayers
+        .iter()
+        .position(|&sublayer| sublayer == sibling)
+        .map_or(sublayers.len(), |index| index + 1);
+    insert_sublayer(env, this, layer, index);
+}
+- (())replaceSublayer:(id)old_layer // CALayer*
+                 with:(id)new_layer { // CALayer*
+    let Some(index) = env.objc.borrow::<CALayerHostObject>(this)
+        .sublayers
+        .iter()
+        .position(|&sublayer| sublayer == old_layer)
+    else {
+        return;
+    };
+    retain(env, old_layer);
+    () = msg![env; old_layer removeFromSuperlayer];
+    insert_sublayer(env, this, new_layer, index);
+    release(env, old_layer);
+}
+- (())removeFromSuperlayer {
+    let superlayer = env.objc.borrow::<CALayerHostObject>(this).superlayer;
+    if superlayer == nil {
+        return;
+    }
+    env.objc.borrow_mut::<CALayerHostObject>(this).superlayer = nil;
+    env.objc.borrow_mut::<CALayerHostObject>(superlayer)
+        .sublayers
+        .retain(|&sublayer| sublayer != this);
+    release(env, this);
+}
+
+// Geometry
+
+- (CGRect)bounds {
+    env.objc.borrow::<CALayerHostObject>(this).bounds
+}
+- (())setBounds:(CGRect)bounds {
+    change_property(env, this, "bounds", |host_object| {
+        let old = std::mem::replace(&mut host_object.bounds, bounds);
+        if host_object.needs_display_on_bounds_change && old.size != bounds.size {
+            host_object.needs_display = true;
+        }
+    });
+}
+- (CGPoint)position {
+    env.objc.borrow::<CALayerHostObject>(this).position
+}
+- (())setPosition:(CGPoint)position {
+    change_property(env, this, "position", |host_object| host_object.position = position);
+}
+- (CGPoint)anchorPoint {
+    env.objc.borrow::<CALayerHostObject>(this).anchor_point
+}
+- (())setAnchorPoint:(CGPoint)anchor_point {
+    change_property(env, this, "anchorPoint", |host_object| host_object.anchor_point = anchor_point);
+}
+- (CGFloat)zPosition {
+    env.objc.borrow::<CALayerHostObject>(this).z_position
+}
+- (())setZPosition:(CGFloat)z_position {
+    change_property(env, this, "zPosition", |host_object| host_object.z_position = z_position);
+}
+- (CGRect)frame {
+    let &CALayerHostObject { bounds, position, anchor_point, transform, .. } = env.objc.borrow(this);
+    let size = bounds.size;
+    let frame = transform.to_affine().apply_to_rect(CGRect {
+        origin: CGPoint {
+            x: -anchor_point.x * size.width,
+            y: -anchor_point.y * size.height,
+        },
+        size,
+    });
+    CGRect {
+        origin: CGPoint {
+            x: position.x + frame.origin.x,
+            y: position.y + frame.origin.y,
+        },
+        size: frame.size,
+    }
+}
+- (())setFrame:(CGRect)frame {
+    let &CALayerHostObject { mut bounds, anchor_point, transform, .. } = env.objc.borrow(this);
+    let position = CGPoint {
+        x: frame.origin.x + anchor_point.x * frame.size.width,
+        y: frame.origin.y + anchor_point.y * frame.size.height,
+    };
+    () = msg![env; this setPosition:position];
+    if transform.is_identity() {
+        bounds.size = frame.size;
+        () = msg![env; this setBounds:bounds];
+    } else {
+        // The frame is undefined in this case, according to Apple.
+        log!("TODO: [{:?} setFrame:{:?}] with a non-identity transform, only moving the layer", this, frame);
+    }
+}
+
+- (CATransform3D)transform {
+    env.objc.borrow::<CALayerHostObject>(this).transform
+}
+- (())setTransform:(CATransform3D)transform {
+    change_property(env, this, "transform", |host_object| host_object.transform = transform);
+}
+- (CGAffineTransform)affineTransform {
+    let transform = env.objc.borrow::<CALayerHostObject>(this).transform;
+    if transform.is_affine() {
+        transform.to_affine()
+    } else {
+        CGAffineTransformIdentity
+    }
+}
+- (())setAffineTransform:(CGAffineTransform)transform {
+    let transform = CATransform3D::from_affine(transform);
+    () = msg![env; this setTransform:transform];
+}
+
+// Appearance
+
+- (f32)opacity {
+    env.objc.borrow::<CALayerHostObject>(this).opacity
+}
+- (())setOpacity:(f32)opacity {
+    change_property(env, this, "opacity", |host_object| host_object.opacity = opacity);
+}
+- (bool)isHidden {
+    env.objc.borrow::<CALayerHostObject>(this).hidden
+}
+- (())setHidden:(bool)hidden {
+    env.objc.borrow_mut::<CALayerHostObject>(this).hidden = hidden;
+}
+- (bool)masksToBounds {
+    env.objc.borrow::<CALayerHostObject>(this).masks_to_bounds
+}
+- (())setMasksToBounds:(bool)masks {
+    env.objc.borrow_mut::<CALayerHostObject>(this).masks_to_bounds = masks;
+}
+- (CGFloat)cornerRadius {
+    env.objc.borrow::<CALayerHostObject>(this).corner_radius
+}
+- (())setCornerRadius:(CGFloat)radius {
+    change_property(env, this, "cornerRadius", |host_object| host_object.corner_radius = radius);
+}
+
+- (id)backgroundColor {
+    env.objc.borrow::<CALayerHostObject>(this).background_color
+}
+- (())setBackgroundColor:(id)color { // CGColorRef
+    retain(env, color);
+    let mut old = nil;
+    change_property(env, this, "backgroundColor", |host_object| {
+        old = std::mem::replace(&mut host_object.background_color, color);
+    });
+    release(env, old);
+}
+
+- (CGFloat)borderWidth {
+    env.objc.borrow::<CALayerHostObject>(this).border_width
+}
+- (())setBorderWidth:(CGFloat)width {
+    change_property(env, this, "borderWidth", |host_object| host_object.border_width = width);
+}
+- (id)borderColor {
+    env.objc.borrow::<CALayerHostObject>(this).border_color
+}
+- (())setBorderColor:(id)color { // CGColorRef
+    retain(env, color);
+    let mut old = nil;
+    change_property(env, this, "borderColor", |host_object| {
+        old = std::mem::replace(&mut host_object.border_color, color);
+    });
+    release(env, old);
+}
+
+- (id)shadowColor {
+    env.objc.borrow::<CALayerHostObject>(this).shadow_color
+}
+- (())setShadowColor:(id)color { // CGColorRef
+    retain(env, color);
+    let mut old = nil;
+    change_property(env, this, "shadowColor", |host_object| {
+        old = std::mem::replace(&mut host_object.shadow_color, color);
+    });
+    release(env, old);
+}
+- (f32)shadowOpacity {
+    env.objc.borrow::<CALayerHostObject>(this).shadow_opacity
+}
+- (())setShadowOpacity:(f32)opacity {
+    change_property(env, this, "shadowOpacity", |host_object| host_object.shadow_opacity = opacity);
+}
+- (CGSize)shadowOffset {
+    env.objc.borrow::<CALayerHostObject>(this).shadow_offset
+}
+- (())setShadowOffset:(CGSize)offset {
+    change_property(env, this, "shadowOffset", |host_object| host_object.shadow_offset = offset);
+}
+- (CGFloat)shadowRadius {
+    env.objc.borrow::<CALayerHostObject>(this).shadow_radius
+}
+- (())setShadowRadius:(CGFloat)radius {
+    change_property(env, this, "shadowRadius", |host_object| host_object.shadow_radius = radius);
+}
+
+// Contents and drawing
+
+- (id)contents {
+    env.objc.borrow::<CALayerHostObject>(this).contents
+}
+- (())setContents:(id)contents { // usually CGImageRef
+    retain(env, contents);
+    let generation = new_contents_generation(env);
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(this);
+    let old = std::mem::replace(&mut host_object.contents, contents);
+    host_object.contents_generation = generation;
+    release(env, old);
+}
+
+// Animations
+
+- (())addAnimation:(id)animation // CAAnimation*
+            forKey:(id)key { // NSString*
+    ca_animation::add_animation(env, this, animation, key);
+}
+- (id)animationForKey:(id)key { // NSString*
+    let key = to_rust_string(env, key);
+    ca_animation::animation_for_key(env, this, &key)
+}
+- (())removeAnimationForKey:(id)key { // NSString*
+    let key = to_rust_string(env, key);
+    ca_animation::remove_animation(env, this, Some(&key));
+}
+- (())removeAllAnimations {
+    ca_animation::remove_animation(env, this, None);
+}
+- (id)animationKeys {
+    let keys: Vec<String> = env.objc.borrow::<CALayerHostObject>(this)
+        .animations
+        .iter()
+        .filter_map(|animation| animation.key.clone())
+        .collect();
+    if keys.is_empty() {
+        return nil;
+    }
+    let keys = keys
+        .into_iter()
+        .map(|key| ns_string::from_rust_string(env, key))
+        .collect();
+    let keys = ns_array::from_vec(env, keys);
+    autorelease(env, keys)
+}
+
+- (id)presentationLayer {
+    let model_layer: id = msg![env; this modelLayer];
+    let properties = presentation::presentation(env, model_layer);
+    let class: Class = msg![env; model_layer class];
+    let new: id = msg![env; class alloc];
+    let new: id = msg![env; new initWithLayer:model_layer];
+    retain(env, model_layer);
+    env.objc.borrow_mut::<CALayerHostObject>(new).model_layer = model_layer;
+
+    let presentation::Properties {
+        bounds,
+        position,
+        anchor_point,
+        z_position,
+        transform,
+        opacity,
+        hidden,
+        masks_to_bounds,
+        corner_radius,
+        background_color,
+        border_width,
+        border_color,
+        shadow_color,
+        shadow_opacity,
+        shadow_offset,
+        shadow_radius,
+        contents,
+    } = properties;
+    let model = presentation::model(env, model_layer);
+    let color = |env: &mut Environment, model: presentation::Rgba, rgba: presentation::Rgba| {
+        (model != rgba).then(|| cg_color::from_rgba(env, rgba))
+    };
+    let background_color = color(env, model.background_color, background_color);
+    let border_color = color(env, model.border_color, border_color);
+    let shadow_color = color(env, model.shadow_color, shadow_color);
+    retain(env, contents);
+
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(new);
+    host_object.bounds = bounds;
+    host_object.position = position;
+    host_object.anchor_point = anchor_point;
+    host_object.z_position = z_position;
+    host_object.transform = transform;
+    host_object.opacity = opacity;
+    host_object.hidden = hidden;
+    host_object.masks_to_bounds = masks_to_bounds;
+    host_object.corner_radius = corner_radius;
+    host_object.border_width = border_width;
+    host_object.shadow_opacity = shadow_opacity;
+    host_object.shadow_offset = shadow_offset;
+    host_object.shadow_radius = shadow_radius;
+    let old_contents = std::mem::replace(&mut host_object.contents, contents);
+    let mut old_colors = Vec::new();
+    for (field, color) in [
+        (&mut host_object.background_color, background_color),
+        (&mut host_object.border_color, border_color),
+        (&mut host_object.shadow_color, shadow_color),
+    ] {
+        if let Some(color) = color {
+            old_colors.push(std::mem::replace(field, color));
+        }
+    }
+    release(env, old_contents);
+    for old in old_colors {
+        release(env, old);
+    }
+    autorelease(env, new)
+}
+- (id)modelLayer {
+    let model_layer = env.objc.borrow::<CALayerHostObject>(this).model_layer;
+    if model_layer == nil {
+        this
+    } else {
+        model_layer
+    }
+}
+
+// Actions
+
+- (id)actions {
+    env.objc.borrow::<CALayerHostObject>(this).actions
+}
+- (())setActions:(id)actions { // NSDictionary<NSString*, id<CAAction>>*
+    let actions: id = msg![env; actions copy];
+    let old = std::mem::replace(&mut env.objc.borrow_mut::<CALayerHostObject>(this).actions, actions);
+    release(env, old);
+}
+
+- (id)actionForKey:(id)key { // NSString*
+    let null: id = msg_class![env; NSNull null];
+
+    let delegate = env.objc.borrow::<CALayerHostObject>(this).delegate;
+    if delegate != nil {
+        let selector = env.objc.lookup_selector("actionForLayer:forKey:").unwrap();
+        if msg![env; delegate respondsToSelector:selector] {
+            let action: id = msg![env; delegate actionForLayer:this forKey:key];
+            if action != nil {
+                return if action == null { nil } else { action };
+            }
+        }
+    }
+
+    let actions = env.objc.borrow::<CALayerHostObject>(this).actions;
+    if actions != nil {
+        let action: id = msg![env; actions objectForKey:key];
+        if action != nil {
+            return if action == null { nil } else { action };
+        }
+    }
+
+    let class: Class = msg![env; this class];
+    let action: id = msg![env; class defaultActionForKey:key];
+    if action != nil {
+        return if action == null { nil } else { action };
+    }
+
+    let key_string = to_rust_string(env, key);
+    if !IMPLICITLY_ANIMATED_KEYS.contains(&key_string.as_ref()) {
+        return nil;
+    }
+    let duration = ca_transaction::animation_duration(env);
+    let timing_function = ca_transaction::implicit_timing_function(env);
+    let animation: id = msg_class![env; CABasicAnimation animationWithKeyPath:key];
+    () = msg![env; animation setDuration:duration];
+    () = msg![env; animation setTimingFunction:timing_function];
+    animation
+}
+
+- (bool)needsDisplayOnBoundsChange {
+    env.objc.borrow::<CALayerHostObject>(this).needs_display_on_bounds_change
+}
+- (())setNeedsDisplayOnBoundsChange:(bool)needs_display {
+    env.objc.borrow_mut::<CALayerHostObject>(this).needs_display_on_bounds_change = needs_display;
+}
+
+- (bool)needsDisplay {
+    env.objc.borrow::<CALayerHostObject>(this).needs_display
+}
+- (())setNeedsDisplay {
+    env.objc.borrow_mut::<CALayerHostObject>(this).needs_display = true;
+}
+- (())setNeedsDisplayInRect:(CGRect)_rect {
+    // TODO: partial redraws
+    msg![env; this setNeedsDisplay]
+}
+- (())displayIfNeeded {
+    let host_object = env.objc.borrow_mut::<CALayerHostObject>(this);
+    if std::mem::take(&mut host_object.needs_display) {
+        () = msg![env; this display];
+    }
+}
+
+- (())display {
+    env.objc.borrow_mut::<CALayerHostObject>(this).needs_display = false;
+
+    let delegate = env.objc.borrow::<CALayerHostObject>(this).delegate;
+    if delegate != nil {
+        if let Some(selector) = env.objc.lookup_selector("displayLayer:") {
+            if msg![env; delegate respondsToSelector:selector] {
+                () = msg![env; delegate displayLayer:this];
+                return;
+            }
+        }
+    }
+
+    // TODO: use contentsScale once there's a scale other than 1.
+    let size = env.objc.borrow::<CALayerHostObject>(this).bounds.size;
+    let width = size.width.ceil().max(0.0) as GuestUSize;
+    let height = size.height.ceil().max(0.0) as GuestUSize;
+    if width == 0 || height == 0 {
+        () = msg![env; this setContents:nil];
+        return;
+    }
+
+    let color_space = CGColorSpaceCreateDeviceRGB(env);
+    let context = CGBitmapContextCreate(
+        env,
+        MutVoidPtr::null(),
+        width,
+        height,
+        8,
+        width * 4,
+        color_space,
+        kCGImageAlphaPremultipliedLast,
+    );
+    CGColorSpaceRelease(env, color_space);
+    () = msg![env; this drawInContext:context];
+    let image = cg_bitmap_context::to_image(env, context);
+    CGContextRelease(env, context);
+
+    let image = cg_image::from_image(env, image);
+    () = msg![env; this setContents:image];
+    CGImageRelease(env, image);
+}
+
+- (())drawInContext:(CGContextRef)context {
+    let delegate = env.objc.borrow::<CALayerHostObject>(this).delegate;
+    if delegate == nil {
+        return;
+    }
+    let selector = env.objc.lookup_selector("drawLayer:inContext:").unwrap();
+    if msg![env; delegate respondsToSelector:selector] {
+        () = msg![env; delegate drawLayer:this inContext:context];
+    }
+}
 
 @end
 
 };
+
+/// The properties that get an animation by default when they change.
+const IMPLICITLY_ANIMATED_KEYS: &[&str] = &[
+    "bounds",
+    "position",
+    "anchorPoint",
+    "zPosition",
+    "transform",
+    "opacity",
+    "cornerRadius",
+    "backgroundColor",
+    "borderWidth",
+    "borderColor",
+    "shadowColor",
+    "shadowOpacity",
+    "shadowOffset",
+    "shadowRadius",
+];
+
+/// Shared implementation of the setters of animatable properties: `change`
+/// updates the model value, and if the value changed, the action for `key` is
+/// run (see `actionForKey:`).
+fn change_property(
+    env: &mut Environment,
+    this: id,
+    key: &'static str,
+    change: impl FnOnce(&mut CALayerHostObject),
+) {
+    // Layers that aren't in a layer tree have nothing on screen to animate
+    // from, and the same goes for presentation layers.
+    let &CALayerHostObject {
+        superlayer,
+        model_layer,
+        ..
+    } = env.objc.borrow(this);
+    if superlayer == nil || model_layer != nil || ca_transaction::disable_actions(env) {
+        change(env.objc.borrow_mut(this));
+        return;
+    }
+
+    let key_string = get_static_str(env, key);
+    let action: id = msg![env; this actionForKey:key_string];
+    if action == nil {
+        change(env.objc.borrow_mut(this));
+        return;
+    }
+    retain(env, action);
+    let old_model = presentation::get(&presentation::model(env, this), key);
+    let old_presentation = presentation::get(&presentation::presentation(env, this), key);
+    change(env.objc.borrow_mut(this));
+    let new_model = presentation::get(&presentation::model(env, this), key);
+    if new_model == old_model {
+        release(env, action);
+        return;
+    }
+
+    // An animation without any values animates from the old value as it was
+    // presented.
+    let basic_class = env.objc.get_known_class("CABasicAnimation", &mut env.mem);
+    let action = if msg![env; action isKindOfClass:basic_class] {
+        let from_value: id = msg![env; action fromValue];
+        let to_value: id = msg![env; action toValue];
+        let by_value: id = msg![env; action byValue];
+        if from_value == nil && to_value == nil && by_value == nil {
+            let copy: id = msg![env; action copy];
+            release(env, action);
+            let from_value = old_presentation.unwrap().to_object(env);
+            () = msg![env; copy setFromValue:from_value];
+            copy
+        } else {
+            action
+        }
+    } else {
+        action
+    };
+    () = msg![env; action runActionForKey:key_string object:this arguments:nil];
+    release(env, action);
+}
+
+/// Shared implementation of `addSublayer:` and the `insertSublayer:` methods.
+fn insert_sublayer(env: &mut Environment, this: id, layer: id, index: usize) {
+    if layer == nil {
+        return;
+    }
+    retain(env, layer);
+    () = msg![env; layer removeFromSuperlayer];
+    env.objc.borrow_mut::<CALayerHostObject>(layer).superlayer = this;
+    let sublayers = &mut env.objc.borrow_mut::<CALayerHostObject>(this).sublayers;
+    let index = index.min(sublayers.len());
+    sublayers.insert(index, layer);
+}
@@ -0,0 +1,160 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CAMediaTimingFunction`.
+//!
+//! A timing function is a cubic Bézier curve from (0, 0) to (1, 1), given by
+//! its two other control points.
+//!
+//! TODO: `functionWithControlPoints::::` and `initWithControlPoints::::`,
+//! which can't be written with `objc_classes!` yet.
+
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::ns_string::to_rust_string;
+use crate::mem::{GuestUSize, MutPtr, MutVoidPtr};
+use crate::objc::{autorelease, id, msg, objc_classes, retain, ClassExports, HostObject};
+use crate::Environment;
+
+pub const kCAMediaTimingFunctionLinear: &str = "linear";
+pub const kCAMediaTimingFunctionEaseIn: &str = "easeIn";
+pub const kCAMediaTimingFunctionEaseOut: &str = "easeOut";
+pub const kCAMediaTimingFunctionEaseInEaseOut: &str = "easeInEaseOut";
+pub const kCAMediaTimingFunctionDefault: &str = "default";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCAMediaTimingFunctionLinear",
+        HostConstant::NSString(kCAMediaTimingFunctionLinear),
+    ),
+    (
+        "_kCAMediaTimingFunctionEaseIn",
+        HostConstant::NSString(kCAMediaTimingFunctionEaseIn),
+    ),
+    (
+        "_kCAMediaTimingFunctionEaseOut",
+        HostConstant::NSString(kCAMediaTimingFunctionEaseOut),
+    ),
+    (
+        "_kCAMediaTimingFunctionEaseInEaseOut",
+        HostConstant::NSString(kCAMediaTimingFunctionEaseInEaseOut),
+    ),
+    (
+        "_kCAMediaTimingFunctionDefault",
+        HostConstant::NSString(kCAMediaTimingFunctionDefault),
+    ),
+];
+
+/// The two inner control points, `(x1, y1, x2, y2)`.
+pub type ControlPoints = (f32, f32, f32, f32);
+
+pub const LINEAR: ControlPoints = (0.0, 0.0, 1.0, 1.0);
+
+/// Get the control points of one of the named timing functions.
+pub fn control_points_for_name(name: &str) -> Option<ControlPoints> {
+    match name {
+        kCAMediaTimingFunctionLinear => Some(LINEAR),
+        kCAMediaTimingFunctionEaseIn => Some((0.42, 0.0, 1.0, 1.0)),
+        kCAMediaTimingFunctionEaseOut => Some((0.0, 0.0, 0.58, 1.0)),
+        kCAMediaTimingFunctionEaseInEaseOut => Some((0.42, 0.0, 0.58, 1.0)),
+        kCAMediaTimingFunctionDefault => Some((0.25, 0.1, 0.25, 1.0)),
+        _ => None,
+    }
+}
+
+struct CAMediaTimingFunctionHostObject {
+    control_points: ControlPoints,
+}
+impl HostObject for CAMediaTimingFunctionHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CAMediaTimingFunction: NSObject
+
++ (id)alloc {
+    let host_object = Box::new(CAMediaTimingFunctionHostObject {
+        control_points: LINEAR,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)functionWithName:(id)name { // NSString*
+    let name_string = to_rust_string(env, name);
+    let Some(control_points) = control_points_for_name(&name_string) else {
+        panic!("Unknown timing function name {:?}", name_string);
+    };
+    let new: id = msg![env; this alloc];
+    env.objc.borrow_mut::<CAMediaTimingFunctionHostObject>(new).control_points = control_points;
+    autorelease(env, new)
+}
+
+- (())getControlPointAtIndex:(GuestUSize)index
+                      values:(MutPtr<f32>)values { // float[2]
+    let (x1, y1, x2, y2) = env.objc.borrow::<CAMediaTimingFunctionHostObject>(this).control_points;
+    let (x, y) = match index {
+        0 => (0.0, 0.0),
+        1 => (x1, y1),
+        2 => (x2, y2),
+        3 => (1.0, 1.0),
+        _ => panic!("Invalid control point index {}", index),
+    };
+    env.mem.write(values, x);
+    env.mem.write(values + 1, y);
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+@end
+
+};
+
+/// For use by other Core Animation classes: create a timing function with the
+/// given control points. The result is autoreleased.
+pub fn from_control_points(env: &mut Environment, control_points: ControlPoints) -> id {
+    let class = env
+        .objc
+        .get_known_class("CAMediaTimingFunction", &mut env.mem);
+    let new: id = msg![env; class alloc];
+    env.objc
+        .borrow_mut::<CAMediaTimingFunctionHostObject>(new)
+        .control_points = control_points;
+    autorelease(env, new)
+}
+
+/// For use by other Core Animation classes: get the control points of a
+/// `CAMediaTimingFunction*`.
+pub fn control_points(env: &mut Environment, function: id) -> ControlPoints {
+    env.objc
+        .borrow::<CAMediaTimingFunctionHostObject>(function)
+        .control_points
+}
+
+/// Map linear progress `t` in [0, 1] through a timing function.
+pub fn evaluate(control_points: ControlPoints, t: f32) -> f32 {
+    if control_points == LINEAR {
+        return t;
+    }
+    let (x1, y1, x2, y2) = control_points;
+    let bezier = |p1: f32, p2: f32, s: f32| {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * s * p1 + 3.0 * inv * s * s * p2 + s * s * s
+    };
+    // x(s) is monotonic when the control points' x values are in [0, 1], so
+    // bisection finds the s where x(s) = t.
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..20 {
+        let mid = (low + high) / 2.0;
+        if bezier(x1, x2, mid) < t {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    bezier(y1, y2, (low + high) / 2.0)
+}
@@ -0,0 +1,226 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CATransaction`.
+//!
+//! Changes to layers aren't batched: they take effect straight away, and the
+//! compositor picks them up next time it runs. Transactions only hold the
+//! settings for implicit animations.
+
+use super::ca_media_timing_function::{self, kCAMediaTimingFunctionDefault};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_foundation::CFTimeInterval;
+use crate::frameworks::foundation::ns_string::to_rust_string;
+use crate::objc::{id, msg, msg_class, nil, objc_classes, release, retain, ClassExports};
+use crate::Environment;
+
+pub const kCATransactionAnimationDuration: &str = "animationDuration";
+pub const kCATransactionDisableActions: &str = "disableActions";
+pub const kCATransactionAnimationTimingFunction: &str = "animationTimingFunction";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCATransactionAnimationDuration",
+        HostConstant::NSString(kCATransactionAnimationDuration),
+    ),
+    (
+        "_kCATransactionDisableActions",
+        HostConstant::NSString(kCATransactionDisableActions),
+    ),
+    (
+        "_kCATransactionAnimationTimingFunction",
+        HostConstant::NSString(kCATransactionAnimationTimingFunction),
+    ),
+];
+
+/// The duration of implicit animations outside any transaction that sets one.
+const DEFAULT_DURATION: CFTimeInterval = 0.25;
+
+#[derive(Default)]
+pub struct State {
+    /// Transactions that have begun but not been committed, innermost last.
+    stack: Vec<Transaction>,
+}
+
+/// The settings of a transaction. [None] means the enclosing transaction's
+/// setting is used.
+#[derive(Default)]
+struct Transaction {
+    /// Implicit transactions are begun when a setting is changed outside any
+    /// transaction, and committed by the compositor (see
+    /// [commit_implicit_transaction]).
+    implicit: bool,
+    disable_actions: Option<bool>,
+    animation_duration: Option<CFTimeInterval>,
+    /// `CAMediaTimingFunction*`, strong reference.
+    animation_timing_function: Option<id>,
+}
+
+fn state(env: &mut Environment) -> &mut State {
+    &mut env.framework_state.core_animation.ca_transaction
+}
+
+/// Get the innermost transaction, beginning an implicit one if there's none.
+fn current(env: &mut Environment) -> &mut Transaction {
+    let stack = &mut state(env).stack;
+    if stack.is_empty() {
+        stack.push(Transaction {
+            implicit: true,
+            ..Default::default()
+        });
+    }
+    stack.last_mut().unwrap()
+}
+
+fn end_transaction(env: &mut Environment, transaction: Transaction) {
+    if let Some(function) = transaction.animation_timing_function {
+        release(env, function);
+    }
+}
+
+/// For use by the compositor: commit the implicit transaction, if there is
+/// one and it's the only one, like at the end of a run loop iteration.
+pub fn commit_implicit_transaction(env: &mut Environment) {
+    let stack = &mut state(env).stack;
+    if stack.len() == 1 && stack[0].implicit {
+        let transaction = stack.pop().unwrap();
+        end_transaction(env, transaction);
+    }
+}
+
+/// For use by `CALayer`: are implicit animations disabled?
+pub fn disable_actions(env: &mut Environment) -> bool {
+    state(env)
+        .stack
+        .iter()
+        .rev()
+        .find_map(|transaction| transaction.disable_actions)
+        .unwrap_or(false)
+}
+
+/// For use by `CALayer`: the duration of implicit animations.
+pub fn animation_duration(env: &mut Environment) -> CFTimeInterval {
+    state(env)
+        .stack
+        .iter()
+        .rev()
+        .find_map(|transaction| transaction.animation_duration)
+        .unwrap_or(DEFAULT_DURATION)
+}
+
+/// For use by `CALayer`: the timing function of implicit animations, which may
+/// be nil.
+pub fn animation_timing_function(env: &mut Environment) -> id {
+    state(env)
+        .stack
+        .iter()
+        .rev()
+        .find_map(|transaction| transaction.animation_timing_function)
+        .unwrap_or(nil)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CATransaction: NSObject
+
++ (())begin {
+    state(env).stack.push(Transaction::default());
+}
++ (())commit {
+    let stack = &mut state(env).stack;
+    if stack.last().is_some_and(|transaction| !transaction.implicit) {
+        let transaction = stack.pop().unwrap();
+        end_transaction(env, transaction);
+    } else {
+        log!("Warning: [CATransaction commit] without a matching begin");
+    }
+}
++ (())flush {
+    // Changes are never batched, so there's nothing to flush.
+}
++ (())lock {
+    // Layers are only used from the main thread.
+}
++ (())unlock {
+}
+
++ (bool)disableActions {
+    disable_actions(env)
+}
++ (())setDisableActions:(bool)disable {
+    current(env).disable_actions = Some(disable);
+}
+
++ (CFTimeInterval)animationDuration {
+    animation_duration(env)
+}
++ (())setAnimationDuration:(CFTimeInterval)duration {
+    current(env).animation_duration = Some(duration);
+}
+
++ (id)animationTimingFunction {
+    animation_timing_function(env)
+}
++ (())setAnimationTimingFunction:(id)function { // CAMediaTimingFunction*
+    retain(env, function);
+    if let Some(old) = current(env).animation_timing_function.replace(function) {
+        release(env, old);
+    }
+}
+
++ (id)valueForKey:(id)key { // NSString*
+    match to_rust_string(env, key).as_ref() {
+        kCATransactionDisableActions => {
+            let disable = disable_actions(env);
+            msg_class![env; NSNumber numberWithBool:disable]
+        }
+        kCATransactionAnimationDuration => {
+            let duration = animation_duration(env);
+            msg_class![env; NSNumber numberWithDouble:duration]
+        }
+        kCATransactionAnimationTimingFunction => animation_timing_function(env),
+        other => {
+            log!("TODO: [CATransaction valueForKey:{:?}]", other);
+            nil
+        }
+    }
+}
++ (())setValue:(id)value
+        forKey:(id)key { // NSString*
+    match to_rust_string(env, key).as_ref() {
+        kCATransactionDisableActions => {
+            let disable: bool = msg![env; value boolValue];
+            () = msg![env; this setDisableActions:disable];
+        }
+        kCATransactionAnimationDuration => {
+            let duration: CFTimeInterval = msg![env; value doubleValue];
+            () = msg![env; this setAnimationDuration:duration];
+        }
+        kCATransactionAnimationTimingFunction => {
+            () = msg![env; this setAnimationTimingFunction:value];
+        }
+        other => {
+            log!("TODO: [CATransaction setValue:{:?} forKey:{:?}]", value, other);
+        }
+    }
+}
+
+@end
+
+};
+
+/// For use by `CALayer`: the timing function of implicit animations, or the
+/// default one if no transaction sets one. The caller doesn't own the result.
+pub fn implicit_timing_function(env: &mut Environment) -> id {
+    let function = animation_timing_function(env);
+    if function != nil {
+        return function;
+    }
+    let control_points =
+        ca_media_timing_function::control_points_for_name(kCAMediaTimingFunctionDefault).unwrap();
+    ca_media_timing_function::from_control_points(env, control_points)
+}
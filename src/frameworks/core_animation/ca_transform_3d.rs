@@ -0,0 +1,386 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CATransform3D.h`
+
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::core_graphics::cg_affine_transform::CGAffineTransform;
+use crate::frameworks::core_graphics::CGFloat;
+use crate::mem::{ConstVoidPtr, Mem, SafeRead};
+use crate::Environment;
+
+/// A 4×4 matrix applied to row vectors, like [CGAffineTransform]:
+/// `(x', y', z', w') = (x, y, z, w) * M`, so the translation is in `m41`,
+/// `m42` and `m43`, and perspective is usually done with `m34`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C, packed)]
+pub struct CATransform3D {
+    pub m11: CGFloat,
+    pub m12: CGFloat,
+    pub m13: CGFloat,
+    pub m14: CGFloat,
+    pub m21: CGFloat,
+    pub m22: CGFloat,
+    pub m23: CGFloat,
+    pub m24: CGFloat,
+    pub m31: CGFloat,
+    pub m32: CGFloat,
+    pub m33: CGFloat,
+    pub m34: CGFloat,
+    pub m41: CGFloat,
+    pub m42: CGFloat,
+    pub m43: CGFloat,
+    pub m44: CGFloat,
+}
+unsafe impl SafeRead for CATransform3D {}
+impl_GuestRet_for_large_struct!(CATransform3D);
+impl GuestArg for CATransform3D {
+    const REG_COUNT: usize = 16;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        let mut rows = [[0.0; 4]; 4];
+        for (i, value) in rows.iter_mut().flatten().enumerate() {
+            *value = GuestArg::from_regs(&regs[i..i + 1]);
+        }
+        CATransform3D::from_rows(rows)
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        for (i, value) in self.rows().into_iter().flatten().enumerate() {
+            value.to_regs(&mut regs[i..i + 1]);
+        }
+    }
+}
+
+pub const CATransform3DIdentity: CATransform3D = CATransform3D {
+    m11: 1.0,
+    m12: 0.0,
+    m13: 0.0,
+    m14: 0.0,
+    m21: 0.0,
+    m22: 1.0,
+    m23: 0.0,
+    m24: 0.0,
+    m31: 0.0,
+    m32: 0.0,
+    m33: 1.0,
+    m34: 0.0,
+    m41: 0.0,
+    m42: 0.0,
+    m43: 0.0,
+    m44: 1.0,
+};
+
+impl CATransform3D {
+    pub fn rows(self) -> [[CGFloat; 4]; 4] {
+        let CATransform3D {
+            m11,
+            m12,
+            m13,
+            m14,
+            m21,
+            m22,
+            m23,
+            m24,
+            m31,
+            m32,
+            m33,
+            m34,
+            m41,
+            m42,
+            m43,
+            m44,
+        } = self;
+        [
+            [m11, m12, m13, m14],
+            [m21, m22, m23, m24],
+            [m31, m32, m33, m34],
+            [m41, m42, m43, m44],
+        ]
+    }
+    pub fn from_rows(rows: [[CGFloat; 4]; 4]) -> CATransform3D {
+        let [[m11, m12, m13, m14], [m21, m22, m23, m24], [m31, m32, m33, m34], [m41, m42, m43, m44]] =
+            rows;
+        CATransform3D {
+            m11,
+            m12,
+            m13,
+            m14,
+            m21,
+            m22,
+            m23,
+            m24,
+            m31,
+            m32,
+            m33,
+            m34,
+            m41,
+            m42,
+            m43,
+            m44,
+        }
+    }
+
+    pub fn translation(tx: CGFloat, ty: CGFloat, tz: CGFloat) -> CATransform3D {
+        CATransform3D {
+            m41: tx,
+            m42: ty,
+            m43: tz,
+            ..CATransform3DIdentity
+        }
+    }
+    pub fn scale(sx: CGFloat, sy: CGFloat, sz: CGFloat) -> CATransform3D {
+        CATransform3D {
+            m11: sx,
+            m22: sy,
+            m33: sz,
+            ..CATransform3DIdentity
+        }
+    }
+    /// Rotation by `angle` radians around the vector `(x, y, z)`. If the
+    /// vector has a length of zero, the identity is returned.
+    pub fn rotation(angle: CGFloat, x: CGFloat, y: CGFloat, z: CGFloat) -> CATransform3D {
+        let length = (x * x + y * y + z * z).sqrt();
+        if length == 0.0 {
+            return CATransform3DIdentity;
+        }
+        let (x, y, z) = (x / length, y / length, z / length);
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+        CATransform3D::from_rows([
+            [
+                t * x * x + cos,
+                t * x * y + z * sin,
+                t * x * z - y * sin,
+                0.0,
+            ],
+            [
+                t * x * y - z * sin,
+                t * y * y + cos,
+                t * y * z + x * sin,
+                0.0,
+            ],
+            [
+                t * x * z + y * sin,
+                t * y * z - x * sin,
+                t * z * z + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == CATransform3DIdentity
+    }
+
+    /// `self` followed by `other`.
+    pub fn concat(self, other: CATransform3D) -> CATransform3D {
+        let (a, b) = (self.rows(), other.rows());
+        let mut rows = [[0.0; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+            }
+        }
+        CATransform3D::from_rows(rows)
+    }
+
+    /// Returns the transform unchanged if it isn't invertible, like
+    /// `CATransform3DInvert`.
+    pub fn invert(self) -> CATransform3D {
+        // Gauss-Jordan elimination with partial pivoting.
+        let mut m = self.rows();
+        let mut inverse = CATransform3DIdentity.rows();
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))
+                .unwrap();
+            if m[pivot][column] == 0.0 {
+                return self;
+            }
+            m.swap(column, pivot);
+            inverse.swap(column, pivot);
+            let divisor = m[column][column];
+            for j in 0..4 {
+                m[column][j] /= divisor;
+                inverse[column][j] /= divisor;
+            }
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+                let factor = m[row][column];
+                for j in 0..4 {
+                    m[row][j] -= factor * m[column][j];
+                    inverse[row][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+        CATransform3D::from_rows(inverse)
+    }
+
+    pub fn from_affine(t: CGAffineTransform) -> CATransform3D {
+        let CGAffineTransform { a, b, c, d, tx, ty } = t;
+        CATransform3D {
+            m11: a,
+            m12: b,
+            m21: c,
+            m22: d,
+            m41: tx,
+            m42: ty,
+            ..CATransform3DIdentity
+        }
+    }
+
+    /// Whether the transform only does what a [CGAffineTransform] could.
+    pub fn is_affine(&self) -> bool {
+        let &CATransform3D {
+            m13,
+            m14,
+            m23,
+            m24,
+            m31,
+            m32,
+            m33,
+            m34,
+            m43,
+            m44,
+            ..
+        } = self;
+        [m13, m14, m23, m24, m31, m32, m34, m43] == [0.0; 8] && m33 == 1.0 && m44 == 1.0
+    }
+
+    /// The 2D part of the transform, ignoring anything else.
+    pub fn to_affine(self) -> CGAffineTransform {
+        CGAffineTransform {
+            a: self.m11,
+            b: self.m12,
+            c: self.m21,
+            d: self.m22,
+            tx: self.m41,
+            ty: self.m42,
+        }
+    }
+
+    /// Transform a point (with a `w` of 1) and do the perspective division.
+    pub fn apply_to_point(&self, (x, y, z): (CGFloat, CGFloat, CGFloat)) -> (CGFloat, CGFloat) {
+        let m = self.rows();
+        let v = [x, y, z, 1.0];
+        let [x, y, _, w] = [0, 1, 2, 3].map(|j| (0..4).map(|i| v[i] * m[i][j]).sum::<CGFloat>());
+        (x / w, y / w)
+    }
+}
+
+fn CATransform3DIsIdentity(_env: &mut Environment, t: CATransform3D) -> bool {
+    t.is_identity()
+}
+fn CATransform3DEqualToTransform(
+    _env: &mut Environment,
+    a: CATransform3D,
+    b: CATransform3D,
+) -> bool {
+    a == b
+}
+
+pub fn CATransform3DMakeTranslation(
+    _env: &mut Environment,
+    tx: CGFloat,
+    ty: CGFloat,
+    tz: CGFloat,
+) -> CATransform3D {
+    CATransform3D::translation(tx, ty, tz)
+}
+pub fn CATransform3DMakeScale(
+    _env: &mut Environment,
+    sx: CGFloat,
+    sy: CGFloat,
+    sz: CGFloat,
+) -> CATransform3D {
+    CATransform3D::scale(sx, sy, sz)
+}
+pub fn CATransform3DMakeRotation(
+    _env: &mut Environment,
+    angle: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+    z: CGFloat,
+) -> CATransform3D {
+    CATransform3D::rotation(angle, x, y, z)
+}
+
+fn CATransform3DTranslate(
+    _env: &mut Environment,
+    t: CATransform3D,
+    tx: CGFloat,
+    ty: CGFloat,
+    tz: CGFloat,
+) -> CATransform3D {
+    CATransform3D::translation(tx, ty, tz).concat(t)
+}
+fn CATransform3DScale(
+    _env: &mut Environment,
+    t: CATransform3D,
+    sx: CGFloat,
+    sy: CGFloat,
+    sz: CGFloat,
+) -> CATransform3D {
+    CATransform3D::scale(sx, sy, sz).concat(t)
+}
+fn CATransform3DRotate(
+    _env: &mut Environment,
+    t: CATransform3D,
+    angle: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+    z: CGFloat,
+) -> CATransform3D {
+    CATransform3D::rotation(angle, x, y, z).concat(t)
+}
+fn CATransform3DConcat(
+    _env: &mut Environment,
+    a: CATransform3D,
+    b: CATransform3D,
+) -> CATransform3D {
+    a.concat(b)
+}
+fn CATransform3DInvert(_env: &mut Environment, t: CATransform3D) -> CATransform3D {
+    t.invert()
+}
+
+fn CATransform3DMakeAffineTransform(_env: &mut Environment, t: CGAffineTransform) -> CATransform3D {
+    CATransform3D::from_affine(t)
+}
+fn CATransform3DIsAffine(_env: &mut Environment, t: CATransform3D) -> bool {
+    t.is_affine()
+}
+fn CATransform3DGetAffineTransform(_env: &mut Environment, t: CATransform3D) -> CGAffineTransform {
+    t.to_affine()
+}
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_CATransform3DIdentity",
+    HostConstant::Custom(|mem: &mut Mem| -> ConstVoidPtr {
+        mem.alloc_and_write(CATransform3DIdentity)
+            .cast()
+            .cast_const()
+    }),
+)];
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CATransform3DIsIdentity(_)),
+    export_c_func!(CATransform3DEqualToTransform(_, _)),
+    export_c_func!(CATransform3DMakeTranslation(_, _, _)),
+    export_c_func!(CATransform3DMakeScale(_, _, _)),
+    export_c_func!(CATransform3DMakeRotation(_, _, _, _)),
+    export_c_func!(CATransform3DTranslate(_, _, _, _)),
+    export_c_func!(CATransform3DScale(_, _, _, _)),
+    export_c_func!(CATransform3DRotate(_, _, _, _, _)),
+    export_c_func!(CATransform3DConcat(_, _)),
+    export_c_func!(CATransform3DInvert(_)),
+    export_c_func!(CATransform3DMakeAffineTransform(_)),
+    export_c_func!(CATransform3DIsAffine(_)),
+    export_c_func!(CATransform3DGetAffineTransform(_)),
+];
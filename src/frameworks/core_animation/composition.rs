@@ -0,0 +1,921 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The compositor, which draws the layer tree on the screen.
+//!
+//! Each time the main thread's run loop goes round, at most 60 times a second,
+//! the layers that need display are redrawn, then the layer trees of the
+//! visible windows are turned into a list of drawing operations, which is
+//! drawn with OpenGL into a texture in the compositor's own context. That
+//! texture is then presented like a frame presented by an OpenGL ES app, with
+//! the UI drawn by the host on top.
+//!
+//! OpenGL ES games are special-cased: if a `CAEAGLLayer` covers the whole
+//! screen and nothing is drawn on top of it, the app's frames are presented
+//! directly and the compositor does nothing, so there's no extra cost.
+//! Otherwise, presented frames are read back and drawn like any other layer
+//! contents.
+
+use super::ca_layer::CALayerHostObject;
+use super::ca_transform_3d::{CATransform3D, CATransform3DIdentity};
+use super::presentation::{self, Properties, Rgba};
+use super::{ca_animation, ca_transaction};
+use crate::frameworks::core_graphics::cg_image::borrow_image;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::media_player::mp_movie_player_controller;
+use crate::frameworks::opengles::eagl::present_frame;
+use crate::frameworks::uikit::ui_window;
+use crate::objc::{id, msg, nil, release, retain, Class};
+use crate::window::gl21compat as gl;
+use crate::window::gl21compat::types::*;
+use crate::window::{FilterStage, GLContext, GLVersion};
+use crate::Environment;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// The shortest time between composited frames.
+const FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// How long the compositor stays out of the way after an app presents a frame
+/// directly from a layer that isn't in any window.
+const DIRECT_PRESENTATION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The number of line segments in each rounded corner.
+const CORNER_SEGMENTS: usize = 8;
+
+/// The number of translucent layers used to approximate a blurred shadow.
+const SHADOW_STEPS: usize = 4;
+
+#[derive(Default)]
+pub struct State {
+    gl_ctx: Option<GLContext>,
+    target: Option<RenderTarget>,
+    /// Uploaded layer contents, by layer, with the contents generation they
+    /// were uploaded from.
+    textures: HashMap<id, (u64, GLuint)>,
+    last_composite: Option<Instant>,
+    /// The `CAEAGLLayer*` whose frames are presented directly, if any. Weak
+    /// reference.
+    direct_layer: Option<id>,
+    last_direct_presentation: Option<Instant>,
+    filter_stage: Option<FilterStage>,
+}
+
+/// The framebuffer everything is drawn into before being presented.
+struct RenderTarget {
+    framebuffer: GLuint,
+    texture: GLuint,
+    /// Depth and stencil renderbuffer, though only the stencil part is used.
+    depth_stencil: GLuint,
+    /// Whether there's a stencil buffer, which is used for masks. If there
+    /// isn't, masks are approximated with the scissor test.
+    has_stencil: bool,
+    size: (u32, u32),
+}
+
+/// Premultiplied RGBA.
+type Color = (f32, f32, f32, f32);
+
+/// A rectangle with rounded corners.
+#[derive(Copy, Clone)]
+struct Shape {
+    rect: CGRect,
+    corner_radius: CGFloat,
+}
+
+enum ContentsSource {
+    /// `CGImageRef`
+    Image(id),
+    /// A frame presented from a `CAEAGLLayer`.
+    EAGLFrame,
+}
+
+/// A drawing operation. Each one is drawn in a layer's co-ordinate space,
+/// given by `matrix`.
+enum Op {
+    Fill {
+        matrix: CATransform3D,
+        shape: Shape,
+        color: Color,
+    },
+    Contents {
+        matrix: CATransform3D,
+        layer: id,
+        source: ContentsSource,
+        rect: CGRect,
+        opacity: f32,
+        /// The number of masks this is drawn inside.
+        mask_depth: usize,
+    },
+    Border {
+        matrix: CATransform3D,
+        shape: Shape,
+        width: CGFloat,
+        color: Color,
+    },
+    PushMask {
+        matrix: CATransform3D,
+        shape: Shape,
+    },
+    PopMask {
+        matrix: CATransform3D,
+        shape: Shape,
+    },
+}
+
+/// For use by `NSRunLoop`: redraw the layers that need it and composite a new
+/// frame, if it's time to.
+pub fn recomposite_if_necessary(env: &mut Environment) {
+    // TODO: layers belong to the main thread, but other threads can change
+    // them in real Core Animation.
+    if env.current_thread != 0 {
+        return;
+    }
+    ca_transaction::commit_implicit_transaction(env);
+    ca_animation::handle_animations(env);
+
+    let now = Instant::now();
+    let state = &mut env.framework_state.core_animation.composition;
+    if state
+        .last_composite
+        .is_some_and(|last| now.duration_since(last) < FRAME_INTERVAL)
+    {
+        return;
+    }
+    state.last_composite = Some(now);
+
+    let roots: Vec<id> = ui_window::visible_windows(env)
+        .into_iter()
+        .map(|window| msg![env; window layer])
+        .collect();
+    if roots.is_empty() {
+        // Nothing to draw, and OpenGL ES apps without windows shouldn't have
+        // their frames drawn over.
+        env.framework_state.core_animation.composition.direct_layer = None;
+        return;
+    }
+
+    for &root in &roots {
+        display_layers(env, root);
+    }
+
+    let eagl_class = env.objc.get_known_class("CAEAGLLayer", &mut env.mem);
+    let mut ops = Vec::new();
+    for &root in &roots {
+        build_ops(
+            env,
+            eagl_class,
+            root,
+            CATransform3DIdentity,
+            1.0,
+            0,
+            &mut ops,
+        );
+    }
+
+    let direct_layer = find_direct_layer(env, &ops);
+    // A movie covers everything, and the app might not present any frames
+    // while it plays.
+    let movie_playing = mp_movie_player_controller::is_playing(env);
+    let state = &mut env.framework_state.core_animation.composition;
+    state.direct_layer = direct_layer;
+    if !movie_playing
+        && (direct_layer.is_some()
+            || state
+                .last_direct_presentation
+                .is_some_and(|last| now.duration_since(last) < DIRECT_PRESENTATION_TIMEOUT))
+    {
+        return;
+    }
+
+    unsafe { composite(env, &ops) };
+}
+
+/// For use by `EAGLContext`: should a frame presented from this layer go
+/// straight to the screen? That's the case if it's the layer covering the
+/// screen (see the module documentation), or if it's not in a window at all,
+/// in which case there's nothing to composite it with.
+pub fn should_present_directly(env: &mut Environment, layer: id) -> bool {
+    if env.framework_state.core_animation.composition.direct_layer == Some(layer) {
+        return true;
+    }
+    let mut root = layer;
+    loop {
+        let superlayer = env.objc.borrow::<CALayerHostObject>(root).superlayer;
+        if superlayer == nil {
+            break;
+        }
+        root = superlayer;
+    }
+    !ui_window::visible_windows(env).into_iter().any(|window| {
+        let window_layer: id = msg![env; window layer];
+        window_layer == root
+    })
+}
+
+/// For use by `EAGLContext`: note that the app presented a frame directly.
+pub fn note_direct_presentation(env: &mut Environment) {
+    env.framework_state
+        .core_animation
+        .composition
+        .last_direct_presentation = Some(Instant::now());
+}
+
+/// Send `displayIfNeeded` to every layer in a tree that needs display. This
+/// can run app code, which can change the tree, so the layers are collected
+/// first.
+fn display_layers(env: &mut Environment, root: id) {
+    let mut layers = Vec::new();
+    let mut stack = vec![root];
+    while let Some(layer) = stack.pop() {
+        let host_object = env.objc.borrow::<CALayerHostObject>(layer);
+        if host_object.hidden {
+            continue;
+        }
+        if host_object.needs_display {
+            layers.push(layer);
+        }
+        stack.extend_from_slice(&host_object.sublayers);
+    }
+    for layer in layers {
+        retain(env, layer);
+        () = msg![env; layer displayIfNeeded];
+        release(env, layer);
+    }
+}
+
+fn premultiply((r, g, b, a): Rgba, opacity: f32) -> Color {
+    let a = a * opacity;
+    (r * a, g * a, b * a, a)
+}
+
+/// Turn a layer and its sublayers into drawing operations. `parent_matrix`
+/// maps the superlayer's co-ordinate space to the screen's.
+fn build_ops(
+    env: &mut Environment,
+    eagl_class: Class,
+    layer: id,
+    parent_matrix: CATransform3D,
+    parent_opacity: f32,
+    mask_depth: usize,
+    ops: &mut Vec<Op>,
+) {
+    let Properties {
+        bounds,
+        position,
+        anchor_point,
+        transform,
+        opacity,
+        hidden,
+        masks_to_bounds,
+        corner_radius,
+        background_color,
+        border_width,
+        border_color,
+        shadow_color,
+        shadow_opacity,
+        shadow_offset,
+        shadow_radius,
+        contents,
+        ..
+    } = presentation::presentation(env, layer);
+    let opacity = parent_opacity * opacity.clamp(0.0, 1.0);
+    if hidden || opacity <= 0.0 {
+        return;
+    }
+
+    // Maps the layer's co-ordinate space to the screen's.
+    let matrix = CATransform3D::translation(
+        -(bounds.origin.x + anchor_point.x * bounds.size.width),
+        -(bounds.origin.y + anchor_point.y * bounds.size.height),
+        0.0,
+    )
+    .concat(transform)
+    .concat(CATransform3D::translation(position.x, position.y, 0.0))
+    .concat(parent_matrix);
+    let shape = Shape {
+        rect: bounds,
+        corner_radius,
+    };
+
+    let is_eagl = msg![env; layer isKindOfClass:eagl_class];
+    let source = if is_eagl {
+        Some(ContentsSource::EAGLFrame)
+    } else if contents != nil {
+        Some(ContentsSource::Image(contents))
+    } else {
+        None
+    };
+    let background = Some(premultiply(background_color, opacity)).filter(|color| color.3 > 0.0);
+
+    // The shadow should have the shape of the layer's content, but a
+    // rectangle is a good approximation for the usual opaque views.
+    if shadow_opacity > 0.0 && (background.is_some() || source.is_some()) {
+        let color = premultiply(shadow_color, opacity * shadow_opacity);
+        let step_color = {
+            let fraction = 1.0 / SHADOW_STEPS as f32;
+            (
+                color.0 * fraction,
+                color.1 * fraction,
+                color.2 * fraction,
+                color.3 * fraction,
+            )
+        };
+        for i in 0..SHADOW_STEPS {
+            let spread = shadow_radius * (i as CGFloat / SHADOW_STEPS as CGFloat - 0.5);
+            ops.push(Op::Fill {
+                matrix,
+                shape: Shape {
+                    rect: CGRect {
+                        origin: CGPoint {
+                            x: bounds.origin.x + shadow_offset.width - spread,
+                            y: bounds.origin.y + shadow_offset.height - spread,
+                        },
+                        size: CGSize {
+                            width: bounds.size.width + spread * 2.0,
+                            height: bounds.size.height + spread * 2.0,
+                        },
+                    },
+                    corner_radius: corner_radius + spread.max(0.0),
+                },
+                color: step_color,
+            });
+        }
+    }
+
+    if let Some(color) = background {
+        ops.push(Op::Fill {
+            matrix,
+            shape,
+            color,
+        });
+    }
+
+    if masks_to_bounds {
+        ops.push(Op::PushMask { matrix, shape });
+    }
+    let mask_depth = mask_depth + usize::from(masks_to_bounds);
+
+    // Contents are clipped to rounded corners too, but only if the layer
+    // masks to bounds.
+    if let Some(source) = source {
+        ops.push(Op::Contents {
+            matrix,
+            layer,
+            source,
+            rect: bounds,
+            opacity,
+            mask_depth,
+        });
+    }
+
+    let mut sublayers = env
+        .objc
+        .borrow::<CALayerHostObject>(layer)
+        .sublayers
+        .clone();
+    // This is a stable sort, so layers with the same zPosition stay in order.
+    sublayers.sort_by(|&a, &b| {
+        let a = env.objc.borrow::<CALayerHostObject>(a).z_position;
+        let b = env.objc.borrow::<CALayerHostObject>(b).z_position;
+        a.total_cmp(&b)
+    });
+    for sublayer in sublayers {
+        build_ops(env, eagl_class, sublayer, matrix, opacity, mask_depth, ops);
+    }
+
+    if masks_to_bounds {
+        ops.push(Op::PopMask { matrix, shape });
+    }
+
+    if border_width > 0.0 {
+        let color = premultiply(border_color, opacity);
+        if color.3 > 0.0 {
+            ops.push(Op::Border {
+                matrix,
+                shape,
+                width: border_width,
+                color,
+            });
+        }
+    }
+}
+
+/// Find the `CAEAGLLayer` whose frames can be presented directly, if any: it
+/// must be the last thing drawn, fully opaque, unmasked, and exactly cover
+/// the screen.
+fn find_direct_layer(env: &mut Environment, ops: &[Op]) -> Option<id> {
+    let last = ops
+        .iter()
+        .rev()
+        .find(|op| !matches!(op, Op::PushMask { .. } | Op::PopMask { .. }))?;
+    let &Op::Contents {
+        matrix,
+        layer,
+        source: ContentsSource::EAGLFrame,
+        rect,
+        opacity,
+        mask_depth: 0,
+    } = last
+    else {
+        return None;
+    };
+    if opacity < 1.0 {
+        return None;
+    }
+    let (width, height) = env.window.size_unrotated_unscaled();
+    let (width, height) = (width as CGFloat, height as CGFloat);
+    let corners = [
+        ((rect.origin.x, rect.origin.y), (0.0, 0.0)),
+        (
+            (rect.origin.x + rect.size.width, rect.origin.y),
+            (width, 0.0),
+        ),
+        (
+            (rect.origin.x, rect.origin.y + rect.size.height),
+            (0.0, height),
+        ),
+        (
+            (
+                rect.origin.x + rect.size.width,
+                rect.origin.y + rect.size.height,
+            ),
+            (width, height),
+        ),
+    ];
+    corners
+        .into_iter()
+        .all(|((x, y), (expected_x, expected_y))| {
+            let (x, y) = matrix.apply_to_point((x, y, 0.0));
+            (x - expected_x).abs() < 0.5 && (y - expected_y).abs() < 0.5
+        })
+        .then_some(layer)
+}
+
+/// The outline of a shape, as a fixed number of points going clockwise (on
+/// screen) from the top-left corner.
+fn outline(shape: Shape) -> Vec<(f32, f32)> {
+    let CGRect { origin, size } = shape.rect;
+    let radius = shape
+        .corner_radius
+        .min(size.width / 2.0)
+        .min(size.height / 2.0)
+        .max(0.0);
+    // Corner centers, and the angle each corner's arc starts at.
+    let corners = [
+        (origin.x + radius, origin.y + radius, 180.0f32),
+        (origin.x + size.width - radius, origin.y + radius, 270.0),
+        (
+            origin.x + size.width - radius,
+            origin.y + size.height - radius,
+            0.0,
+        ),
+        (origin.x + radius, origin.y + size.height - radius, 90.0),
+    ];
+    let mut points = Vec::with_capacity(corners.len() * (CORNER_SEGMENTS + 1));
+    for (center_x, center_y, start) in corners {
+        for i in 0..=CORNER_SEGMENTS {
+            let angle = (start + 90.0 * i as f32 / CORNER_SEGMENTS as f32).to_radians();
+            let (sin, cos) = angle.sin_cos();
+            points.push((center_x + cos * radius, center_y + sin * radius));
+        }
+    }
+    points
+}
+
+unsafe fn draw_vertices(mode: GLenum, vertices: &[(f32, f32)]) {
+    gl::VertexPointer(2, gl::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+    gl::DrawArrays(mode, 0, vertices.len() as GLsizei);
+}
+
+unsafe fn fill_shape(shape: Shape) {
+    let mut vertices = outline(shape);
+    // A convex shape can be drawn as a fan from any of its points.
+    vertices.push(vertices[0]);
+    draw_vertices(gl::TRIANGLE_FAN, &vertices);
+}
+
+unsafe fn load_matrix(matrix: CATransform3D) {
+    // CATransform3D is for row vectors and OpenGL's matrices are for column
+    // vectors, but OpenGL's are also column-major, so the layout is the same.
+    let rows = matrix.rows();
+    gl::MatrixMode(gl::MODELVIEW);
+    gl::LoadMatrixf(rows.as_ptr() as *const GLfloat);
+}
+
+/// Create or resize the framebuffer that layers are drawn into.
+unsafe fn prepare_target(target: &mut Option<RenderTarget>, size: (u32, u32)) -> &RenderTarget {
+    if target.as_ref().is_some_and(|target| target.size != size) {
+        let old = target.take().unwrap();
+        gl::DeleteFramebuffersEXT(1, &old.framebuffer);
+        gl::DeleteTextures(1, &old.texture);
+        gl::DeleteRenderbuffersEXT(1, &old.depth_stencil);
+    }
+    target.get_or_insert_with(|| {
+        let mut texture = 0;
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA as _,
+            size.0 as _,
+            size.1 as _,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            std::ptr::null(),
+        );
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+
+        let mut framebuffer = 0;
+        gl::GenFramebuffersEXT(1, &mut framebuffer);
+        gl::BindFramebufferEXT(gl::FRAMEBUFFER_EXT, framebuffer);
+        gl::FramebufferTexture2DEXT(
+            gl::FRAMEBUFFER_EXT,
+            gl::COLOR_ATTACHMENT0_EXT,
+            gl::TEXTURE_2D,
+            texture,
+            0,
+        );
+
+        // Stencil-only renderbuffers are poorly supported, so a packed
+        // depth-stencil one is used (GL_EXT_packed_depth_stencil).
+        const DEPTH24_STENCIL8_EXT: GLenum = 0x88F0;
+        let mut depth_stencil = 0;
+        gl::GenRenderbuffersEXT(1, &mut depth_stencil);
+        gl::BindRenderbufferEXT(gl::RENDERBUFFER_EXT, depth_stencil);
+        gl::RenderbufferStorageEXT(
+            gl::RENDERBUFFER_EXT,
+            DEPTH24_STENCIL8_EXT,
+            size.0 as _,
+            size.1 as _,
+        );
+        gl::FramebufferRenderbufferEXT(
+            gl::FRAMEBUFFER_EXT,
+            gl::STENCIL_ATTACHMENT_EXT,
+            gl::RENDERBUFFER_EXT,
+            depth_stencil,
+        );
+        let has_stencil =
+            gl::CheckFramebufferStatusEXT(gl::FRAMEBUFFER_EXT) == gl::FRAMEBUFFER_COMPLETE_EXT;
+        if !has_stencil {
+            log!("Warning: no stencil buffer for compositing, masks will be approximate");
+            gl::FramebufferRenderbufferEXT(
+                gl::FRAMEBUFFER_EXT,
+                gl::STENCIL_ATTACHMENT_EXT,
+                gl::RENDERBUFFER_EXT,
+                0,
+            );
+        }
+
+        RenderTarget {
+            framebuffer,
+            texture,
+            depth_stencil,
+            has_stencil,
+            size,
+        }
+    })
+}
+
+/// Get a texture with a layer's contents, uploading them if they've changed.
+/// Returns the texture and whether its rows are bottom-first.
+unsafe fn contents_texture(
+    env: &mut Environment,
+    layer: id,
+    source: &ContentsSource,
+) -> Option<(GLuint, bool)> {
+    let host_object = env.objc.borrow::<CALayerHostObject>(layer);
+    let generation = host_object.contents_generation;
+    let opaque: bool = msg![env; layer isOpaque];
+    let bottom_first = matches!(source, ContentsSource::EAGLFrame);
+
+    let textures = &mut env.framework_state.core_animation.composition.textures;
+    if let Some(&(uploaded_generation, texture)) = textures.get(&layer) {
+        if uploaded_generation == generation {
+            return Some((texture, bottom_first));
+        }
+    }
+
+    // Textures are stored premultiplied.
+    let (pixels, width, height): (Vec<u8>, u32, u32) = match *source {
+        ContentsSource::Image(image) => {
+            let image = borrow_image(&env.objc, image);
+            let (width, height) = image.dimensions();
+            let pixels = image
+                .pixels()
+                .chunks_exact(4)
+                .flat_map(|pixel| {
+                    let a = pixel[3] as u32;
+                    let premultiply = |c: u8| ((c as u32 * a + 127) / 255) as u8;
+                    [
+                        premultiply(pixel[0]),
+                        premultiply(pixel[1]),
+                        premultiply(pixel[2]),
+                        pixel[3],
+                    ]
+                })
+                .collect();
+            (pixels, width, height)
+        }
+        ContentsSource::EAGLFrame => {
+            let host_object = env.objc.borrow::<CALayerHostObject>(layer);
+            let frame = host_object.presented_frame.as_ref()?;
+            let mut pixels = frame.pixels.clone();
+            // OpenGL ES output is already premultiplied, but the alpha
+            // channel is meaningless if the layer is opaque.
+            if opaque {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel[3] = 255;
+                }
+            }
+            (pixels, frame.width, frame.height)
+        }
+    };
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let textures = &mut env.framework_state.core_animation.composition.textures;
+    let texture = match textures.get(&layer) {
+        Some(&(_, texture)) => texture,
+        None => {
+            let mut texture = 0;
+            gl::GenTextures(1, &mut texture);
+            texture
+        }
+    };
+    textures.insert(layer, (generation, texture));
+    gl::BindTexture(gl::TEXTURE_2D, texture);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+    gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        gl::RGBA as _,
+        width as _,
+        height as _,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        pixels.as_ptr() as *const _,
+    );
+    Some((texture, bottom_first))
+}
+
+/// Approximate a mask with the scissor test, by finding the pixels covered by
+/// its bounding box.
+fn scissor_rect(matrix: CATransform3D, shape: Shape, scale: f32, height: u32) -> [i32; 4] {
+    let CGRect { origin, size } = shape.rect;
+    let corners = [
+        (origin.x, origin.y),
+        (origin.x + size.width, origin.y),
+        (origin.x, origin.y + size.height),
+        (origin.x + size.width, origin.y + size.height),
+    ]
+    .map(|(x, y)| matrix.apply_to_point((x, y, 0.0)));
+    let min_x = corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min);
+    let max_x = corners
+        .iter()
+        .map(|c| c.0)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let min_y = corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min);
+    let max_y = corners
+        .iter()
+        .map(|c| c.1)
+        .fold(f32::NEG_INFINITY, f32::max);
+    // The framebuffer's origin is the bottom-left.
+    [
+        (min_x * scale).floor() as i32,
+        height as i32 - (max_y * scale).ceil() as i32,
+        ((max_x - min_x) * scale).ceil() as i32,
+        ((max_y - min_y) * scale).ceil() as i32,
+    ]
+}
+
+fn intersect_scissor(a: [i32; 4], b: [i32; 4]) -> [i32; 4] {
+    let x = a[0].max(b[0]);
+    let y = a[1].max(b[1]);
+    let right = (a[0] + a[2]).min(b[0] + b[2]);
+    let top = (a[1] + a[3]).min(b[1] + b[3]);
+    [x, y, (right - x).max(0), (top - y).max(0)]
+}
+
+/// Draw the operations into the compositor's framebuffer and present it.
+unsafe fn composite(env: &mut Environment, ops: &[Op]) {
+    let state = &mut env.framework_state.core_animation.composition;
+    let gl_ctx = state
+        .gl_ctx
+        .get_or_insert_with(|| env.window.create_gl_context(GLVersion::GL21Compat));
+    env.window.make_internal_gl_context_current(gl_ctx);
+
+    let size = env.window.size_unrotated_scalehacked();
+    let (points_width, points_height) = env.window.size_unrotated_unscaled();
+    let scale = size.0 as f32 / points_width as f32;
+    let state = &mut env.framework_state.core_animation.composition;
+    let &RenderTarget {
+        framebuffer,
+        texture: target_texture,
+        has_stencil,
+        ..
+    } = prepare_target(&mut state.target, size);
+
+    gl::BindFramebufferEXT(gl::FRAMEBUFFER_EXT, framebuffer);
+    gl::Viewport(0, 0, size.0 as _, size.1 as _);
+    gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+    gl::ClearStencil(0);
+    gl::Clear(gl::COLOR_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+
+    // Points to normalized device co-ordinates, with the origin at the top
+    // left, and z flattened so that 3D transforms are never clipped.
+    let (w, h) = (points_width as f32, points_height as f32);
+    let projection: [f32; 16] = [
+        2.0 / w,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        -2.0 / h,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        -1.0,
+        1.0,
+        0.0,
+        1.0,
+    ];
+    gl::MatrixMode(gl::PROJECTION);
+    gl::LoadMatrixf(projection.as_ptr());
+
+    gl::EnableClientState(gl::VERTEX_ARRAY);
+    gl::Enable(gl::BLEND);
+    gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+    if has_stencil {
+        gl::Enable(gl::STENCIL_TEST);
+        gl::StencilFunc(gl::EQUAL, 0, 0xff);
+        gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+    }
+    let full_scissor = [0, 0, size.0 as i32, size.1 as i32];
+    let mut scissors = vec![full_scissor];
+
+    let mut used_textures = Vec::new();
+    let mut mask_depth: GLint = 0;
+    for op in ops {
+        match *op {
+            Op::Fill {
+                matrix,
+                shape,
+                color,
+            } => {
+                load_matrix(matrix);
+                gl::Color4f(color.0, color.1, color.2, color.3);
+                fill_shape(shape);
+            }
+            Op::Contents {
+                matrix,
+                layer,
+                ref source,
+                rect,
+                opacity,
+                ..
+            } => {
+                let Some((texture, bottom_first)) = contents_texture(env, layer, source) else {
+                    continue;
+                };
+                used_textures.push(layer);
+                load_matrix(matrix);
+                gl::Color4f(opacity, opacity, opacity, opacity);
+                gl::Enable(gl::TEXTURE_2D);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+                gl::EnableClientState(gl::TEXTURE_COORD_ARRAY);
+                let (top, bottom) = if bottom_first { (1.0, 0.0) } else { (0.0, 1.0) };
+                let tex_coords: [(f32, f32); 4] =
+                    [(0.0, top), (1.0, top), (0.0, bottom), (1.0, bottom)];
+                gl::TexCoordPointer(2, gl::FLOAT, 0, tex_coords.as_ptr() as *const GLvoid);
+                let CGRect { origin, size } = rect;
+                let vertices = [
+                    (origin.x, origin.y),
+                    (origin.x + size.width, origin.y),
+                    (origin.x, origin.y + size.height),
+                    (origin.x + size.width, origin.y + size.height),
+                ];
+                draw_vertices(gl::TRIANGLE_STRIP, &vertices);
+                gl::DisableClientState(gl::TEXTURE_COORD_ARRAY);
+                gl::Disable(gl::TEXTURE_2D);
+            }
+            Op::Border {
+                matrix,
+                shape,
+                width,
+                color,
+            } => {
+                let CGRect { origin, size } = shape.rect;
+                let inner = Shape {
+                    rect: CGRect {
+                        origin: CGPoint {
+                            x: origin.x + width,
+                            y: origin.y + width,
+                        },
+                        size: CGSize {
+                            width: (size.width - width * 2.0).max(0.0),
+                            height: (size.height - width * 2.0).max(0.0),
+                        },
+                    },
+                    corner_radius: (shape.corner_radius - width).max(0.0),
+                };
+                let outer = outline(shape);
+                let inner = outline(inner);
+                let mut vertices = Vec::with_capacity(outer.len() * 2 + 2);
+                for (&outer, &inner) in outer.iter().zip(inner.iter()) {
+                    vertices.push(outer);
+                    vertices.push(inner);
+                }
+                vertices.push(outer[0]);
+                vertices.push(inner[0]);
+                load_matrix(matrix);
+                gl::Color4f(color.0, color.1, color.2, color.3);
+                draw_vertices(gl::TRIANGLE_STRIP, &vertices);
+            }
+            Op::PushMask { matrix, shape } => {
+                if has_stencil {
+                    // Increment the stencil value inside the shape, where
+                    // it's inside all the outer masks.
+                    load_matrix(matrix);
+                    gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+                    gl::StencilOp(gl::KEEP, gl::KEEP, gl::INCR);
+                    fill_shape(shape);
+                    gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                    gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+                    mask_depth += 1;
+                    gl::StencilFunc(gl::EQUAL, mask_depth, 0xff);
+                } else {
+                    let rect = scissor_rect(matrix, shape, scale, size.1);
+                    let rect = intersect_scissor(*scissors.last().unwrap(), rect);
+                    scissors.push(rect);
+                    gl::Enable(gl::SCISSOR_TEST);
+                    gl::Scissor(rect[0], rect[1], rect[2], rect[3]);
+                }
+            }
+            Op::PopMask { matrix, shape } => {
+                if has_stencil {
+                    load_matrix(matrix);
+                    gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+                    gl::StencilOp(gl::KEEP, gl::KEEP, gl::DECR);
+                    fill_shape(shape);
+                    gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                    gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+                    mask_depth -= 1;
+                    gl::StencilFunc(gl::EQUAL, mask_depth, 0xff);
+                } else {
+                    scissors.pop();
+                    let rect = *scissors.last().unwrap();
+                    gl::Scissor(rect[0], rect[1], rect[2], rect[3]);
+                    if scissors.len() == 1 {
+                        gl::Disable(gl::SCISSOR_TEST);
+                    }
+                }
+            }
+        }
+    }
+
+    // Forget the contents of layers that weren't drawn this time.
+    let textures = &mut env.framework_state.core_animation.composition.textures;
+    textures.retain(|layer, &mut (_, texture)| {
+        let keep = used_textures.contains(layer);
+        if !keep {
+            gl::DeleteTextures(1, &texture);
+        }
+        keep
+    });
+
+    // Reset the state, as present_frame expects.
+    gl::BindFramebufferEXT(gl::FRAMEBUFFER_EXT, 0);
+    gl::DisableClientState(gl::VERTEX_ARRAY);
+    gl::Disable(gl::BLEND);
+    gl::Disable(gl::STENCIL_TEST);
+    gl::Disable(gl::SCISSOR_TEST);
+    gl::Color4f(1.0, 1.0, 1.0, 1.0);
+    for mode in [gl::MODELVIEW, gl::PROJECTION, gl::TEXTURE] {
+        gl::MatrixMode(mode);
+        gl::LoadIdentity();
+    }
+    gl::MatrixMode(gl::MODELVIEW);
+
+    let mut filter_stage = env
+        .framework_state
+        .core_animation
+        .composition
+        .filter_stage
+        .take();
+    present_frame(env, target_texture, &mut filter_stage);
+    env.framework_state.core_animation.composition.filter_stage = filter_stage;
+}
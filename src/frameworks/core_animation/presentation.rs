@@ -0,0 +1,656 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Layer property values, as set by the app (the "model") and as currently
+//! shown with animations applied (the "presentation").
+//!
+//! Animations refer to properties by key paths like `position.x` or
+//! `transform.rotation.z`, and their values are Objective-C objects
+//! (`NSNumber`, `NSValue`, `CGColorRef`...), so this module converts between
+//! those and host-side [Value]s that can be interpolated.
+
+use super::ca_animation;
+use super::ca_layer::CALayerHostObject;
+use super::ca_transform_3d::{CATransform3D, CATransform3DIdentity};
+use crate::frameworks::core_graphics::cg_color;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::mem::ConstPtr;
+use crate::objc::{autorelease, id, msg, msg_class, nil, Class};
+use crate::Environment;
+
+/// Red, green, blue and alpha, not premultiplied.
+pub type Rgba = (CGFloat, CGFloat, CGFloat, CGFloat);
+
+/// A snapshot of a layer's animatable properties.
+#[derive(Copy, Clone, Debug)]
+pub struct Properties {
+    pub bounds: CGRect,
+    pub position: CGPoint,
+    pub anchor_point: CGPoint,
+    pub z_position: CGFloat,
+    pub transform: CATransform3D,
+    pub opacity: f32,
+    pub hidden: bool,
+    pub masks_to_bounds: bool,
+    pub corner_radius: CGFloat,
+    /// Transparent if the layer has none.
+    pub background_color: Rgba,
+    pub border_width: CGFloat,
+    pub border_color: Rgba,
+    pub shadow_color: Rgba,
+    pub shadow_opacity: f32,
+    pub shadow_offset: CGSize,
+    pub shadow_radius: CGFloat,
+    /// Usually a `CGImageRef`. Not retained.
+    pub contents: id,
+}
+
+/// Get a layer's properties as the app has set them.
+pub fn model(env: &mut Environment, layer: id) -> Properties {
+    let &CALayerHostObject {
+        bounds,
+        position,
+        anchor_point,
+        z_position,
+        transform,
+        opacity,
+        hidden,
+        masks_to_bounds,
+        corner_radius,
+        background_color,
+        border_width,
+        border_color,
+        shadow_color,
+        shadow_opacity,
+        shadow_offset,
+        shadow_radius,
+        contents,
+        ..
+    } = env.objc.borrow(layer);
+    let mut color = |color: id, default: Rgba| {
+        if color == nil {
+            default
+        } else {
+            cg_color::get_rgba(env, color)
+        }
+    };
+    let background_color = color(background_color, (0.0, 0.0, 0.0, 0.0));
+    let border_color = color(border_color, (0.0, 0.0, 0.0, 1.0));
+    let shadow_color = color(shadow_color, (0.0, 0.0, 0.0, 1.0));
+    Properties {
+        bounds,
+        position,
+        anchor_point,
+        z_position,
+        transform,
+        opacity,
+        hidden,
+        masks_to_bounds,
+        corner_radius,
+        background_color,
+        border_width,
+        border_color,
+        shadow_color,
+        shadow_opacity,
+        shadow_offset,
+        shadow_radius,
+        contents,
+    }
+}
+
+/// Get a layer's properties as they currently appear, with its animations
+/// applied.
+pub fn presentation(env: &mut Environment, layer: id) -> Properties {
+    let mut properties = model(env, layer);
+    ca_animation::apply_animations(env, layer, &mut properties);
+    properties
+}
+
+/// The value of an animatable property, or of part of one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(CGFloat),
+    Point(CGPoint),
+    Size(CGSize),
+    Rect(CGRect),
+    Transform(CATransform3D),
+    Color(Rgba),
+    /// Anything that can't be interpolated, e.g. a `CGImageRef` for
+    /// `contents`. Not retained.
+    Object(id),
+}
+
+impl Value {
+    /// Convert an Objective-C value (`NSNumber`, `NSValue` or `CGColorRef`).
+    pub fn from_object(env: &mut Environment, object: id) -> Value {
+        if object == nil {
+            return Value::Object(nil);
+        }
+        let number_class: Class = msg_class![env; NSNumber class];
+        if msg![env; object isKindOfClass:number_class] {
+            return Value::Number(msg![env; object floatValue]);
+        }
+        let value_class: Class = msg_class![env; NSValue class];
+        if msg![env; object isKindOfClass:value_class] {
+            let objc_type: ConstPtr<u8> = msg![env; object objCType];
+            let objc_type = env.mem.cstr_at_utf8(objc_type);
+            if objc_type.starts_with("{CGPoint=") {
+                return Value::Point(msg![env; object CGPointValue]);
+            } else if objc_type.starts_with("{CGSize=") {
+                return Value::Size(msg![env; object CGSizeValue]);
+            } else if objc_type.starts_with("{CGRect=") {
+                return Value::Rect(msg![env; object CGRectValue]);
+            } else if objc_type.starts_with("{CATransform3D=") {
+                return Value::Transform(msg![env; object CATransform3DValue]);
+            }
+            return Value::Object(object);
+        }
+        let color_class = env.objc.get_known_class("_touchHLE_CGColor", &mut env.mem);
+        if msg![env; object isKindOfClass:color_class] {
+            return Value::Color(cg_color::get_rgba(env, object));
+        }
+        Value::Object(object)
+    }
+
+    /// Convert to an Objective-C value. The result is autoreleased.
+    pub fn to_object(self, env: &mut Environment) -> id {
+        match self {
+            Value::Number(number) => msg_class![env; NSNumber numberWithFloat:number],
+            Value::Point(point) => msg_class![env; NSValue valueWithCGPoint:point],
+            Value::Size(size) => msg_class![env; NSValue valueWithCGSize:size],
+            Value::Rect(rect) => msg_class![env; NSValue valueWithCGRect:rect],
+            Value::Transform(transform) => {
+                msg_class![env; NSValue valueWithCATransform3D:transform]
+            }
+            Value::Color(rgba) => {
+                let color = cg_color::from_rgba(env, rgba);
+                autorelease(env, color)
+            }
+            Value::Object(object) => object,
+        }
+    }
+
+    fn map2(self, other: Value, f: impl Fn(CGFloat, CGFloat) -> CGFloat) -> Option<Value> {
+        let point = |a: CGPoint, b: CGPoint| CGPoint {
+            x: f(a.x, b.x),
+            y: f(a.y, b.y),
+        };
+        let size = |a: CGSize, b: CGSize| CGSize {
+            width: f(a.width, b.width),
+            height: f(a.height, b.height),
+        };
+        Some(match (self, other) {
+            (Value::Number(a), Value::Number(b)) => Value::Number(f(a, b)),
+            (Value::Point(a), Value::Point(b)) => Value::Point(point(a, b)),
+            (Value::Size(a), Value::Size(b)) => Value::Size(size(a, b)),
+            (Value::Rect(a), Value::Rect(b)) => Value::Rect(CGRect {
+                origin: point(a.origin, b.origin),
+                size: size(a.size, b.size),
+            }),
+            (Value::Color(a), Value::Color(b)) => {
+                Value::Color((f(a.0, b.0), f(a.1, b.1), f(a.2, b.2), f(a.3, b.3)))
+            }
+            _ => return None,
+        })
+    }
+
+    /// Interpolation between `self` (`t` = 0) and `to` (`t` = 1). Values that
+    /// can't be interpolated switch halfway.
+    pub fn interpolate(self, to: Value, t: CGFloat) -> Value {
+        if let (Value::Transform(a), Value::Transform(b)) = (self, to) {
+            return Value::Transform(interpolate_transforms(a, b, t));
+        }
+        self.map2(to, |a, b| a + (b - a) * t)
+            .unwrap_or(if t < 0.5 { self } else { to })
+    }
+
+    /// Used for `byValue`, additive and cumulative animations.
+    pub fn add(self, other: Value) -> Value {
+        if let (Value::Transform(a), Value::Transform(b)) = (self, other) {
+            return Value::Transform(b.concat(a));
+        }
+        self.map2(other, |a, b| a + b).unwrap_or(other)
+    }
+
+    /// Inverse of [Value::add].
+    pub fn subtract(self, other: Value) -> Value {
+        if let (Value::Transform(a), Value::Transform(b)) = (self, other) {
+            return Value::Transform(b.invert().concat(a));
+        }
+        self.map2(other, |a, b| a - b).unwrap_or(self)
+    }
+
+    /// Multiply by a scalar, for cumulative animations.
+    pub fn scale(self, factor: CGFloat) -> Value {
+        match self {
+            Value::Transform(transform) => {
+                let mut result = CATransform3DIdentity;
+                for _ in 0..(factor.max(0.0) as u32) {
+                    result = result.concat(transform);
+                }
+                Value::Transform(result)
+            }
+            _ => self.map2(self, |a, _| a * factor).unwrap_or(self),
+        }
+    }
+}
+
+/// Get the value of a property or part of one, e.g. `position.x`.
+pub fn get(properties: &Properties, key_path: &str) -> Option<Value> {
+    let (key, rest) = match key_path.split_once('.') {
+        Some((key, rest)) => (key, Some(rest)),
+        None => (key_path, None),
+    };
+    let bool_value = |value: bool| Value::Number(if value { 1.0 } else { 0.0 });
+    let value = match key {
+        "bounds" => Value::Rect(properties.bounds),
+        "position" => Value::Point(properties.position),
+        "anchorPoint" => Value::Point(properties.anchor_point),
+        "zPosition" => Value::Number(properties.z_position),
+        "transform" => Value::Transform(properties.transform),
+        "opacity" => Value::Number(properties.opacity),
+        "hidden" => bool_value(properties.hidden),
+        "masksToBounds" => bool_value(properties.masks_to_bounds),
+        "cornerRadius" => Value::Number(properties.corner_radius),
+        "backgroundColor" => Value::Color(properties.background_color),
+        "borderWidth" => Value::Number(properties.border_width),
+        "borderColor" => Value::Color(properties.border_color),
+        "shadowColor" => Value::Color(properties.shadow_color),
+        "shadowOpacity" => Value::Number(properties.shadow_opacity),
+        "shadowOffset" => Value::Size(properties.shadow_offset),
+        "shadowRadius" => Value::Number(properties.shadow_radius),
+        "contents" => Value::Object(properties.contents),
+        _ => return None,
+    };
+    match rest {
+        Some(rest) => get_component(value, rest),
+        None => Some(value),
+    }
+}
+
+/// Set the value of a property or part of one, e.g. `position.x`. Returns
+/// [false] if the key path or the value's type isn't supported.
+pub fn set(properties: &mut Properties, key_path: &str, value: Value) -> bool {
+    let (key, rest) = match key_path.split_once('.') {
+        Some((key, rest)) => (key, Some(rest)),
+        None => (key_path, None),
+    };
+    let value = match rest {
+        Some(rest) => {
+            let Some(whole) = get(properties, key) else {
+                return false;
+            };
+            let Some(whole) = set_component(whole, rest, value) else {
+                return false;
+            };
+            whole
+        }
+        None => value,
+    };
+    match (key, value) {
+        ("bounds", Value::Rect(rect)) => properties.bounds = rect,
+        ("position", Value::Point(point)) => properties.position = point,
+        ("anchorPoint", Value::Point(point)) => properties.anchor_point = point,
+        ("zPosition", Value::Number(number)) => properties.z_position = number,
+        ("transform", Value::Transform(transform)) => properties.transform = transform,
+        ("opacity", Value::Number(number)) => properties.opacity = number,
+        ("hidden", Value::Number(number)) => properties.hidden = number >= 0.5,
+        ("masksToBounds", Value::Number(number)) => properties.masks_to_bounds = number >= 0.5,
+        ("cornerRadius", Value::Number(number)) => properties.corner_radius = number,
+        ("backgroundColor", Value::Color(rgba)) => properties.background_color = rgba,
+        ("backgroundColor", Value::Object(object)) if object == nil => {
+            properties.background_color = (0.0, 0.0, 0.0, 0.0)
+        }
+        ("borderWidth", Value::Number(number)) => properties.border_width = number,
+        ("borderColor", Value::Color(rgba)) => properties.border_color = rgba,
+        ("shadowColor", Value::Color(rgba)) => properties.shadow_color = rgba,
+        ("shadowOpacity", Value::Number(number)) => properties.shadow_opacity = number,
+        ("shadowOffset", Value::Size(size)) => properties.shadow_offset = size,
+        ("shadowRadius", Value::Number(number)) => properties.shadow_radius = number,
+        ("contents", Value::Object(object)) => properties.contents = object,
+        _ => return false,
+    }
+    true
+}
+
+fn get_component(value: Value, path: &str) -> Option<Value> {
+    let (key, rest) = match path.split_once('.') {
+        Some((key, rest)) => (key, Some(rest)),
+        None => (path, None),
+    };
+    let component = match (value, key) {
+        (Value::Point(point), "x") => Value::Number(point.x),
+        (Value::Point(point), "y") => Value::Number(point.y),
+        (Value::Size(size), "width") => Value::Number(size.width),
+        (Value::Size(size), "height") => Value::Number(size.height),
+        (Value::Rect(rect), "origin") => Value::Point(rect.origin),
+        (Value::Rect(rect), "size") => Value::Size(rect.size),
+        (Value::Transform(transform), _) => {
+            return get_transform_component(Decomposed::new(transform), path)
+        }
+        _ => return None,
+    };
+    match rest {
+        Some(rest) => get_component(component, rest),
+        None => Some(component),
+    }
+}
+
+fn set_component(value: Value, path: &str, component: Value) -> Option<Value> {
+    let (key, rest) = match path.split_once('.') {
+        Some((key, rest)) => (key, Some(rest)),
+        None => (path, None),
+    };
+    if let Value::Transform(transform) = value {
+        let mut decomposed = Decomposed::new(transform);
+        set_transform_component(&mut decomposed, path, component)?;
+        return Some(Value::Transform(decomposed.recompose()));
+    }
+    let component = match rest {
+        Some(rest) => set_component(get_component(value, key)?, rest, component)?,
+        None => component,
+    };
+    Some(match (value, key, component) {
+        (Value::Point(point), "x", Value::Number(x)) => Value::Point(CGPoint { x, ..point }),
+        (Value::Point(point), "y", Value::Number(y)) => Value::Point(CGPoint { y, ..point }),
+        (Value::Size(size), "width", Value::Number(width)) => Value::Size(CGSize { width, ..size }),
+        (Value::Size(size), "height", Value::Number(height)) => {
+            Value::Size(CGSize { height, ..size })
+        }
+        (Value::Rect(rect), "origin", Value::Point(origin)) => {
+            Value::Rect(CGRect { origin, ..rect })
+        }
+        (Value::Rect(rect), "size", Value::Size(size)) => Value::Rect(CGRect { size, ..rect }),
+        _ => return None,
+    })
+}
+
+fn axis_index(axis: &str) -> Option<usize> {
+    match axis {
+        "x" => Some(0),
+        "y" => Some(1),
+        "z" => Some(2),
+        _ => None,
+    }
+}
+
+fn get_transform_component(decomposed: Decomposed, path: &str) -> Option<Value> {
+    let Decomposed {
+        translation, scale, ..
+    } = decomposed;
+    Some(match path.split_once('.') {
+        None => match path {
+            "rotation" => Value::Number(decomposed.euler_angles()[2]),
+            // This is the uniform scale, so z is ignored, since 2D transforms
+            // usually leave it alone.
+            "scale" => Value::Number((scale[0] + scale[1]) / 2.0),
+            "translation" => Value::Size(CGSize {
+                width: translation[0],
+                height: translation[1],
+            }),
+            _ => return None,
+        },
+        Some(("rotation", axis)) => Value::Number(decomposed.euler_angles()[axis_index(axis)?]),
+        Some(("scale", axis)) => Value::Number(scale[axis_index(axis)?]),
+        Some(("translation", axis)) => Value::Number(translation[axis_index(axis)?]),
+        _ => return None,
+    })
+}
+
+fn set_transform_component(decomposed: &mut Decomposed, path: &str, value: Value) -> Option<()> {
+    match (path.split_once('.'), value) {
+        (None, Value::Number(number)) if path == "rotation" => {
+            let mut angles = decomposed.euler_angles();
+            angles[2] = number;
+            decomposed.set_euler_angles(angles);
+        }
+        (None, Value::Number(number)) if path == "scale" => {
+            decomposed.scale = [number; 3];
+        }
+        (None, Value::Size(size)) if path == "translation" => {
+            decomposed.translation[0] = size.width;
+            decomposed.translation[1] = size.height;
+        }
+        (Some(("rotation", axis)), Value::Number(number)) => {
+            let mut angles = decomposed.euler_angles();
+            angles[axis_index(axis)?] = number;
+            decomposed.set_euler_angles(angles);
+        }
+        (Some(("scale", axis)), Value::Number(number)) => {
+            decomposed.scale[axis_index(axis)?] = number;
+        }
+        (Some(("translation", axis)), Value::Number(number)) => {
+            decomposed.translation[axis_index(axis)?] = number;
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Interpolate between transforms like Core Animation does: by decomposing
+/// them into translation, scale and rotation, so that rotations turn rather
+/// than squashing.
+fn interpolate_transforms(a: CATransform3D, b: CATransform3D, t: CGFloat) -> CATransform3D {
+    if a == b || t <= 0.0 {
+        return a;
+    } else if t >= 1.0 {
+        return b;
+    }
+    let (a, b) = (Decomposed::new(a), Decomposed::new(b));
+    let lerp3 = |a: [CGFloat; 3], b: [CGFloat; 3]| [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t);
+    Decomposed {
+        translation: lerp3(a.translation, b.translation),
+        scale: lerp3(a.scale, b.scale),
+        rotation: slerp(a.rotation, b.rotation, t),
+        perspective: [0, 1, 2, 3]
+            .map(|i| a.perspective[i] + (b.perspective[i] - a.perspective[i]) * t),
+    }
+    .recompose()
+}
+
+/// A unit quaternion `(x, y, z, w)`.
+type Quaternion = [CGFloat; 4];
+
+fn slerp(a: Quaternion, b: Quaternion, t: CGFloat) -> Quaternion {
+    let mut dot: CGFloat = (0..4).map(|i| a[i] * b[i]).sum();
+    let mut b = b;
+    if dot < 0.0 {
+        b = b.map(|c| -c);
+        dot = -dot;
+    }
+    let (weight_a, weight_b) = if dot > 0.9995 {
+        (1.0 - t, t)
+    } else {
+        let theta = dot.acos();
+        let sin_theta = theta.sin();
+        (
+            ((1.0 - t) * theta).sin() / sin_theta,
+            (t * theta).sin() / sin_theta,
+        )
+    };
+    let result: Quaternion = [0, 1, 2, 3].map(|i| a[i] * weight_a + b[i] * weight_b);
+    let length = result.iter().map(|c| c * c).sum::<CGFloat>().sqrt();
+    result.map(|c| c / length)
+}
+
+/// A transform split into parts that can be interpolated separately. Shear
+/// isn't represented.
+#[derive(Copy, Clone, Debug)]
+struct Decomposed {
+    translation: [CGFloat; 3],
+    scale: [CGFloat; 3],
+    rotation: Quaternion,
+    /// The fourth column of the matrix, `(m14, m24, m34, m44)`.
+    perspective: [CGFloat; 4],
+}
+
+impl Decomposed {
+    fn new(transform: CATransform3D) -> Decomposed {
+        let rows = transform.rows();
+        let mut basis = [0, 1, 2].map(|i| [rows[i][0], rows[i][1], rows[i][2]]);
+        let mut scale = basis.map(|row| row.iter().map(|c| c * c).sum::<CGFloat>().sqrt());
+        let determinant = basis[0][0] * (basis[1][1] * basis[2][2] - basis[1][2] * basis[2][1])
+            - basis[0][1] * (basis[1][0] * basis[2][2] - basis[1][2] * basis[2][0])
+            + basis[0][2] * (basis[1][0] * basis[2][1] - basis[1][1] * basis[2][0]);
+        if determinant < 0.0 {
+            scale[0] = -scale[0];
+        }
+        for (i, row) in basis.iter_mut().enumerate() {
+            if scale[i] == 0.0 {
+                *row = [0, 1, 2].map(|j| if i == j { 1.0 } else { 0.0 });
+            } else {
+                *row = row.map(|c| c / scale[i]);
+            }
+        }
+
+        // The rows are for row vectors, so this is the transpose of the
+        // usual rotation matrix for column vectors.
+        let r = |i: usize, j: usize| basis[j][i];
+        let trace = r(0, 0) + r(1, 1) + r(2, 2);
+        let rotation = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            [
+                (r(2, 1) - r(1, 2)) / s,
+                (r(0, 2) - r(2, 0)) / s,
+                (r(1, 0) - r(0, 1)) / s,
+                s / 4.0,
+            ]
+        } else if r(0, 0) > r(1, 1) && r(0, 0) > r(2, 2) {
+            let s = (1.0 + r(0, 0) - r(1, 1) - r(2, 2)).sqrt() * 2.0;
+            [
+                s / 4.0,
+                (r(0, 1) + r(1, 0)) / s,
+                (r(0, 2) + r(2, 0)) / s,
+                (r(2, 1) - r(1, 2)) / s,
+            ]
+        } else if r(1, 1) > r(2, 2) {
+            let s = (1.0 + r(1, 1) - r(0, 0) - r(2, 2)).sqrt() * 2.0;
+            [
+                (r(0, 1) + r(1, 0)) / s,
+                s / 4.0,
+                (r(1, 2) + r(2, 1)) / s,
+                (r(0, 2) - r(2, 0)) / s,
+            ]
+        } else {
+            let s = (1.0 + r(2, 2) - r(0, 0) - r(1, 1)).sqrt() * 2.0;
+            [
+                (r(0, 2) + r(2, 0)) / s,
+                (r(1, 2) + r(2, 1)) / s,
+                s / 4.0,
+                (r(1, 0) - r(0, 1)) / s,
+            ]
+        };
+
+        Decomposed {
+            translation: [rows[3][0], rows[3][1], rows[3][2]],
+            scale,
+            rotation,
+            perspective: [rows[0][3], rows[1][3], rows[2][3], rows[3][3]],
+        }
+    }
+
+    fn recompose(self) -> CATransform3D {
+        let [x, y, z, w] = self.rotation;
+        let r = [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ];
+        let mut rows = [[0.0; 4]; 4];
+        for i in 0..3 {
+            for j in 0..3 {
+                rows[i][j] = r[j][i] * self.scale[i];
+            }
+            rows[i][3] = self.perspective[i];
+        }
+        rows[3] = [
+            self.translation[0],
+            self.translation[1],
+            self.translation[2],
+            self.perspective[3],
+        ];
+        CATransform3D::from_rows(rows)
+    }
+
+    /// Rotations around the x, y and z axes, applied in that order.
+    fn euler_angles(&self) -> [CGFloat; 3] {
+        let [x, y, z, w] = self.rotation;
+        [
+            (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y)),
+            (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin(),
+            (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z)),
+        ]
+    }
+
+    fn set_euler_angles(&mut self, angles: [CGFloat; 3]) {
+        let [(sin_x, cos_x), (sin_y, cos_y), (sin_z, cos_z)] =
+            angles.map(|angle| (angle / 2.0).sin_cos());
+        self.rotation = [
+            sin_x * cos_y * cos_z - cos_x * sin_y * sin_z,
+            cos_x * sin_y * cos_z + sin_x * cos_y * sin_z,
+            cos_x * cos_y * sin_z - sin_x * sin_y * cos_z,
+            cos_x * cos_y * cos_z + sin_x * sin_y * sin_z,
+        ];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: CATransform3D, b: CATransform3D) {
+        for (row_a, row_b) in a.rows().iter().zip(b.rows().iter()) {
+            for (a, b) in row_a.iter().zip(row_b.iter()) {
+                assert!((a - b).abs() < 0.0001, "{:?} != {:?}", row_a, row_b);
+            }
+        }
+    }
+
+    #[test]
+    fn transform_decomposition() {
+        let transform = CATransform3D::scale(2.0, 3.0, 1.0)
+            .concat(CATransform3D::rotation(0.5, 0.0, 0.0, 1.0))
+            .concat(CATransform3D::translation(10.0, 20.0, 0.0));
+        assert_close(Decomposed::new(transform).recompose(), transform);
+
+        let mut flip = CATransform3D::rotation(1.0, 0.0, 1.0, 0.0);
+        flip.m34 = -1.0 / 500.0;
+        assert_close(Decomposed::new(flip).recompose(), flip);
+
+        let rotation = Value::Transform(CATransform3D::rotation(0.75, 0.0, 0.0, 1.0));
+        let Some(Value::Number(angle)) = get_component(rotation, "rotation.z") else {
+            panic!();
+        };
+        assert!((angle - 0.75).abs() < 0.0001);
+
+        let Some(Value::Transform(rotated)) = set_component(
+            Value::Transform(CATransform3DIdentity),
+            "rotation",
+            Value::Number(0.75),
+        ) else {
+            panic!();
+        };
+        assert_close(rotated, CATransform3D::rotation(0.75, 0.0, 0.0, 1.0));
+
+        let halfway = interpolate_transforms(
+            CATransform3DIdentity,
+            CATransform3D::rotation(1.5, 0.0, 0.0, 1.0),
+            0.5,
+        );
+        assert_close(halfway, CATransform3D::rotation(0.75, 0.0, 0.0, 1.0));
+    }
+}
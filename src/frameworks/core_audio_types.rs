@@ -5,7 +5,7 @@
  */
 //! The Core Audio Types framework. (Yes, it's not part of Core Audio?)
 
-use crate::mem::SafeRead;
+use crate::mem::{MutPtr, MutVoidPtr, SafeRead};
 
 // The audio frameworks love FourCC's, and we currently don't need these
 // anywhere else, so this is as good a place to put this as any.
@@ -68,6 +68,9 @@ impl std::fmt::Debug for AudioStreamBasicDescription {
                 if (format_flags & kAudioFormatFlagIsPacked) != 0 {
                     flags.push("kAudioFormatFlagIsPacked");
                 }
+                if (format_flags & kAudioFormatFlagIsNonInterleaved) != 0 {
+                    flags.push("kAudioFormatFlagIsNonInterleaved");
+                }
                 flags
             })
             .field("bytes_per_packet", &bytes_per_packet)
@@ -89,3 +92,66 @@ pub const kAudioFormatFlagIsFloat: AudioFormatFlags = 1 << 0;
 pub const kAudioFormatFlagIsBigEndian: AudioFormatFlags = 1 << 1;
 pub const kAudioFormatFlagIsSignedInteger: AudioFormatFlags = 1 << 2;
 pub const kAudioFormatFlagIsPacked: AudioFormatFlags = 1 << 3;
+pub const kAudioFormatFlagIsNonInterleaved: AudioFormatFlags = 1 << 5;
+/// Fixed-point formats have the number of fractional bits stored in the flags.
+pub const kLinearPCMFormatFlagsSampleFractionShift: u32 = 7;
+pub const kLinearPCMFormatFlagsSampleFractionMask: AudioFormatFlags =
+    0x3f << kLinearPCMFormatFlagsSampleFractionShift;
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct AudioBuffer {
+    pub number_channels: u32,
+    pub data_byte_size: u32,
+    pub data: MutVoidPtr,
+}
+unsafe impl SafeRead for AudioBuffer {}
+
+/// This is variable-size: there are `number_buffers` buffers, which can be
+/// more than one.
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct AudioBufferList {
+    pub number_buffers: u32,
+    pub buffers: [AudioBuffer; 1],
+}
+unsafe impl SafeRead for AudioBufferList {}
+
+/// Get a pointer to one of the buffers in a variable-size `AudioBufferList`.
+pub fn buffer_in_list(list: MutPtr<AudioBufferList>, index: u32) -> MutPtr<AudioBuffer> {
+    // The buffers array comes after the 4-byte buffer count.
+    (list.cast::<u8>() + 4).cast::<AudioBuffer>() + index
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C, packed)]
+pub struct SMPTETime {
+    pub subframes: i16,
+    pub subframe_divisor: i16,
+    pub counter: u32,
+    pub type_: u32,
+    pub flags: u32,
+    pub hours: i16,
+    pub minutes: i16,
+    pub seconds: i16,
+    pub frames: i16,
+}
+unsafe impl SafeRead for SMPTETime {}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C, packed)]
+pub struct AudioTimeStamp {
+    pub sample_time: f64,
+    /// In `mach_absolute_time()` units.
+    pub host_time: u64,
+    pub rate_scalar: f64,
+    pub word_clock_time: u64,
+    pub smpte_time: SMPTETime,
+    pub flags: AudioTimeStampFlags,
+    pub _reserved: u32,
+}
+unsafe impl SafeRead for AudioTimeStamp {}
+
+pub type AudioTimeStampFlags = u32;
+pub const kAudioTimeStampSampleTimeValid: AudioTimeStampFlags = 1 << 0;
+pub const kAudioTimeStampHostTimeValid: AudioTimeStampFlags = 1 << 1;
@@ -0,0 +1,36 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Core Data framework.
+//!
+//! Compiled models (`.mom` files and `.momd` bundles) are read on the host
+//! (see [model]), and the SQLite store is implemented with the SQLite library
+//! bundled for `libsqlite3` (see [store]). The in-memory store type is the
+//! same store backed by an in-memory database. The binary store type isn't
+//! supported and is replaced by an SQLite store.
+//!
+//! Objects are kept simple: a context keeps every object it has registered
+//! alive until it's reset, predicates and sort descriptors are evaluated on
+//! the host rather than translated to SQL, and there's no undo support,
+//! merging between contexts, migration beyond adding new columns, or
+//! `NSFetchedResultsController`.
+//!
+//! Useful resources:
+//! - Apple's [Core Data Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/CoreData/index.html)
+
+pub mod core_data_errors;
+pub mod model;
+pub mod ns_entity_description;
+pub mod ns_fetch_request;
+pub mod ns_managed_object;
+pub mod ns_managed_object_context;
+pub mod ns_managed_object_model;
+pub mod ns_persistent_store_coordinator;
+pub mod store;
+
+#[derive(Default)]
+pub struct State {
+    ns_managed_object: ns_managed_object::State,
+}
@@ -0,0 +1,14 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CoreDataErrors.h`
+//!
+//! These errors are in `NSCocoaErrorDomain`.
+
+use crate::frameworks::foundation::NSInteger;
+
+pub const NSPersistentStoreInvalidTypeError: NSInteger = 134000;
+pub const NSPersistentStoreSaveError: NSInteger = 134030;
+pub const NSPersistentStoreOpenError: NSInteger = 134080;
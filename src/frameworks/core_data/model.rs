@@ -0,0 +1,653 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Host-side representation of managed object models, and parsing of compiled
+//! model files (`.mom`).
+//!
+//! A `.mom` file is an `NSKeyedArchiver` archive of an `NSManagedObjectModel`
+//! and its entity and property descriptions. Rather than unarchiving it into
+//! guest objects, we pick out the parts we need on the host. Things the app
+//! can't observe through the supported API (validation rules, user info,
+//! version hashes, fetch request templates…) are ignored.
+
+use plist::{Dictionary, Value};
+use std::io::Cursor;
+
+/// `NSAttributeType`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AttributeType {
+    Undefined,
+    Integer16,
+    Integer32,
+    Integer64,
+    Decimal,
+    Double,
+    Float,
+    String,
+    Boolean,
+    Date,
+    Binary,
+    Transformable,
+}
+impl AttributeType {
+    fn from_raw(raw: i64) -> AttributeType {
+        match raw {
+            100 => AttributeType::Integer16,
+            200 => AttributeType::Integer32,
+            300 => AttributeType::Integer64,
+            400 => AttributeType::Decimal,
+            500 => AttributeType::Double,
+            600 => AttributeType::Float,
+            700 => AttributeType::String,
+            800 => AttributeType::Boolean,
+            900 => AttributeType::Date,
+            1000 => AttributeType::Binary,
+            1800 => AttributeType::Transformable,
+            _ => AttributeType::Undefined,
+        }
+    }
+    pub fn is_integer(self) -> bool {
+        matches!(
+            self,
+            AttributeType::Integer16 | AttributeType::Integer32 | AttributeType::Integer64
+        )
+    }
+}
+
+/// Default value of an attribute, as stored in the model.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefaultValue {
+    Integer(i64),
+    Real(f64),
+    Boolean(bool),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub name: String,
+    pub attribute_type: AttributeType,
+    pub transient: bool,
+    pub default_value: Option<DefaultValue>,
+}
+
+/// `NSDeleteRule`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DeleteRule {
+    NoAction,
+    Nullify,
+    Cascade,
+    Deny,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relationship {
+    pub name: String,
+    /// Name of the destination entity.
+    pub destination: String,
+    /// Name of the inverse relationship on the destination entity, if any.
+    pub inverse: Option<String>,
+    pub to_many: bool,
+    pub delete_rule: DeleteRule,
+    pub transient: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    pub name: String,
+    /// Name of the Objective-C class for instances, e.g. `NSManagedObject`.
+    pub class_name: String,
+    pub superentity: Option<String>,
+    pub is_abstract: bool,
+    /// The entity's own attributes (not inherited ones), sorted by name.
+    pub attributes: Vec<Attribute>,
+    /// The entity's own relationships (not inherited ones), sorted by name.
+    pub relationships: Vec<Relationship>,
+}
+
+/// The contents of an `NSManagedObjectModel`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Model {
+    /// Sorted by name. Indices into this are used to identify entities.
+    pub entities: Vec<Entity>,
+}
+
+impl Model {
+    /// Parse a compiled model file.
+    pub fn from_bytes(data: &[u8]) -> Result<Model, String> {
+        let plist = Value::from_reader(Cursor::new(data)).map_err(|e| e.to_string())?;
+        Model::from_archive(&plist)
+    }
+
+    fn from_archive(plist: &Value) -> Result<Model, String> {
+        let plist = plist.as_dictionary().ok_or("not a dictionary")?;
+        if plist.get("$archiver").and_then(Value::as_string) != Some("NSKeyedArchiver") {
+            return Err("not a keyed archive".to_string());
+        }
+        let archive = Archive {
+            objects: plist
+                .get("$objects")
+                .and_then(Value::as_array)
+                .ok_or("no $objects")?,
+        };
+        let root = plist
+            .get("$top")
+            .and_then(Value::as_dictionary)
+            .and_then(|top| archive.get(top, &["root"]))
+            .and_then(Value::as_dictionary)
+            .ok_or("no root object")?;
+        if archive.class_name(root) != Some("NSManagedObjectModel") {
+            return Err(format!(
+                "root object is a {:?}, not a model",
+                archive.class_name(root)
+            ));
+        }
+
+        let mut entities = Vec::new();
+        if let Some(entity_dict) = archive.get(root, &["NSEntities"]) {
+            for (_name, entity) in archive.dictionary_entries(entity_dict) {
+                let entity = entity.as_dictionary().ok_or("entity isn't an object")?;
+                entities.push(archive.entity(entity)?);
+            }
+        }
+        entities.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Model { entities })
+    }
+
+    /// Combine several models into one, like
+    /// `+[NSManagedObjectModel modelByMergingModels:]`.
+    pub fn merge(models: Vec<Model>) -> Result<Model, String> {
+        let mut entities: Vec<Entity> = Vec::new();
+        for model in models {
+            for entity in model.entities {
+                if entities.iter().any(|existing| existing.name == entity.name) {
+                    return Err(format!("entity {} is in more than one model", entity.name));
+                }
+                entities.push(entity);
+            }
+        }
+        entities.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Model { entities })
+    }
+
+    pub fn entity_index(&self, name: &str) -> Option<usize> {
+        self.entities
+            .binary_search_by(|entity| entity.name.as_str().cmp(name))
+            .ok()
+    }
+
+    pub fn superentity(&self, entity: usize) -> Option<usize> {
+        self.entities[entity]
+            .superentity
+            .as_deref()
+            .and_then(|name| self.entity_index(name))
+    }
+
+    /// The entity at the top of an entity's inheritance hierarchy. All the
+    /// entities in a hierarchy share one table in the store.
+    pub fn root_entity(&self, entity: usize) -> usize {
+        let mut entity = entity;
+        while let Some(superentity) = self.superentity(entity) {
+            entity = superentity;
+        }
+        entity
+    }
+
+    pub fn is_kind_of_entity(&self, entity: usize, ancestor: usize) -> bool {
+        let mut entity = Some(entity);
+        while let Some(current) = entity {
+            if current == ancestor {
+                return true;
+            }
+            entity = self.superentity(current);
+        }
+        false
+    }
+
+    /// An entity and all the entities that inherit from it, in index order.
+    pub fn entity_and_subentities(&self, entity: usize) -> Vec<usize> {
+        (0..self.entities.len())
+            .filter(|&other| self.is_kind_of_entity(other, entity))
+            .collect()
+    }
+
+    /// Entities from the root of the hierarchy down to this one.
+    fn lineage(&self, entity: usize) -> Vec<usize> {
+        let mut lineage = vec![entity];
+        while let Some(superentity) = self.superentity(*lineage.last().unwrap()) {
+            lineage.push(superentity);
+        }
+        lineage.reverse();
+        lineage
+    }
+
+    /// All the attributes of an entity, including inherited ones.
+    pub fn attributes(&self, entity: usize) -> Vec<&Attribute> {
+        let mut attributes: Vec<&Attribute> = Vec::new();
+        for entity in self.lineage(entity) {
+            for attribute in &self.entities[entity].attributes {
+                if !attributes.iter().any(|other| other.name == attribute.name) {
+                    attributes.push(attribute);
+                }
+            }
+        }
+        attributes
+    }
+
+    /// All the relationships of an entity, including inherited ones.
+    pub fn relationships(&self, entity: usize) -> Vec<&Relationship> {
+        let mut relationships: Vec<&Relationship> = Vec::new();
+        for entity in self.lineage(entity) {
+            for relationship in &self.entities[entity].relationships {
+                if !relationships
+                    .iter()
+                    .any(|other| other.name == relationship.name)
+                {
+                    relationships.push(relationship);
+                }
+            }
+        }
+        relationships
+    }
+
+    pub fn attribute(&self, entity: usize, name: &str) -> Option<&Attribute> {
+        self.attributes(entity)
+            .into_iter()
+            .find(|attribute| attribute.name == name)
+    }
+
+    pub fn relationship(&self, entity: usize, name: &str) -> Option<&Relationship> {
+        self.relationships(entity)
+            .into_iter()
+            .find(|relationship| relationship.name == name)
+    }
+
+    /// Get the destination entity and inverse relationship of a relationship.
+    pub fn destination(
+        &self,
+        relationship: &Relationship,
+    ) -> (Option<usize>, Option<&Relationship>) {
+        let destination = self.entity_index(&relationship.destination);
+        let inverse = destination.and_then(|destination| {
+            relationship
+                .inverse
+                .as_deref()
+                .and_then(|inverse| self.relationship(destination, inverse))
+        });
+        (destination, inverse)
+    }
+}
+
+/// Helper for reading objects from a keyed archive.
+struct Archive<'a> {
+    objects: &'a [Value],
+}
+
+impl<'a> Archive<'a> {
+    fn resolve(&self, value: &'a Value) -> Option<&'a Value> {
+        let value = match value {
+            Value::Uid(uid) => self.objects.get(uid.get() as usize)?,
+            other => other,
+        };
+        // Uid 0 is always the null object.
+        (value.as_string() != Some("$null")).then_some(value)
+    }
+
+    /// Get the value of the first of `keys` that an object has.
+    fn get(&self, object: &'a Dictionary, keys: &[&str]) -> Option<&'a Value> {
+        keys.iter()
+            .find_map(|&key| object.get(key))
+            .and_then(|value| self.resolve(value))
+    }
+
+    fn class_name(&self, object: &'a Dictionary) -> Option<&'a str> {
+        self.get(object, &["$class"])?
+            .as_dictionary()?
+            .get("$classname")?
+            .as_string()
+    }
+
+    fn string(&self, value: &'a Value) -> Option<String> {
+        match value {
+            Value::String(string) => Some(string.clone()),
+            // NSMutableString and friends
+            Value::Dictionary(dict) => self.get(dict, &["NS.string"]).and_then(|s| self.string(s)),
+            _ => None,
+        }
+    }
+
+    fn integer(&self, value: &'a Value) -> Option<i64> {
+        match *value {
+            Value::Integer(integer) => integer.as_signed(),
+            Value::Boolean(boolean) => Some(boolean as i64),
+            Value::Real(real) => Some(real as i64),
+            _ => None,
+        }
+    }
+
+    fn boolean(&self, value: &'a Value) -> Option<bool> {
+        self.integer(value).map(|integer| integer != 0)
+    }
+
+    fn dictionary_entries(&self, value: &'a Value) -> Vec<(&'a Value, &'a Value)> {
+        let Some(dict) = value.as_dictionary() else {
+            return Vec::new();
+        };
+        let list = |key| {
+            dict.get(key)
+                .and_then(Value::as_array)
+                .map_or(&[][..], |array| &array[..])
+        };
+        list("NS.keys")
+            .iter()
+            .zip(list("NS.objects"))
+            .filter_map(|(key, value)| Some((self.resolve(key)?, self.resolve(value)?)))
+            .collect()
+    }
+
+    /// Get the name of an object that has one, like an entity or property.
+    fn name(&self, value: &'a Value, keys: &[&str]) -> Option<String> {
+        match value {
+            Value::Dictionary(dict) if self.class_name(dict) != Some("NSString") => {
+                self.get(dict, keys).and_then(|name| self.string(name))
+            }
+            other => self.string(other),
+        }
+    }
+
+    fn entity(&self, entity: &'a Dictionary) -> Result<Entity, String> {
+        let name = self
+            .get(entity, &["NSEntityName"])
+            .and_then(|name| self.string(name))
+            .ok_or("entity has no name")?;
+        let class_name = self
+            .get(entity, &["NSClassNameForEntity"])
+            .and_then(|name| self.string(name))
+            .unwrap_or_else(|| "NSManagedObject".to_string());
+        let superentity = self
+            .get(entity, &["NSSuperentity", "NSSuperEntity"])
+            .and_then(|superentity| self.name(superentity, &["NSEntityName"]));
+        let is_abstract = self
+            .get(entity, &["NSIsAbstract"])
+            .and_then(|value| self.boolean(value))
+            .unwrap_or(false);
+
+        let mut attributes = Vec::new();
+        let mut relationships = Vec::new();
+        let properties = self.get(entity, &["NSProperties"]);
+        for (_name, property) in properties.map_or(Vec::new(), |p| self.dictionary_entries(p)) {
+            let Some(property) = property.as_dictionary() else {
+                continue;
+            };
+            let property_name = self
+                .get(property, &["NSPropertyName"])
+                .and_then(|name| self.string(name))
+                .ok_or_else(|| format!("property of {} has no name", name))?;
+            let transient = self
+                .get(property, &["NSIsTransient"])
+                .and_then(|value| self.boolean(value))
+                .unwrap_or(false);
+            match self.class_name(property) {
+                Some("NSAttributeDescription") => {
+                    let attribute_type = self
+                        .get(property, &["NSAttributeType"])
+                        .and_then(|value| self.integer(value))
+                        .map_or(AttributeType::Undefined, AttributeType::from_raw);
+                    let default_value = self
+                        .get(property, &["NSDefaultValue"])
+                        .and_then(|value| self.default_value(value));
+                    attributes.push(Attribute {
+                        name: property_name,
+                        attribute_type,
+                        transient,
+                        default_value,
+                    });
+                }
+                Some("NSRelationshipDescription") => {
+                    let destination = self
+                        .get(
+                            property,
+                            &["NSDestinationEntity", "NSDestinationEntityName"],
+                        )
+                        .and_then(|destination| self.name(destination, &["NSEntityName"]))
+                        .ok_or_else(|| {
+                            format!("relationship {}.{} has no destination", name, property_name)
+                        })?;
+                    let inverse = self
+                        .get(
+                            property,
+                            &["NSInverseRelationship", "NSInverseRelationshipName"],
+                        )
+                        .and_then(|inverse| self.name(inverse, &["NSPropertyName"]));
+                    // A maximum count of 0 means there's no limit.
+                    let max_count = self
+                        .get(property, &["NSMaxCount"])
+                        .and_then(|value| self.integer(value))
+                        .unwrap_or(1);
+                    let delete_rule = match self
+                        .get(property, &["NSDeleteRule"])
+                        .and_then(|value| self.integer(value))
+                    {
+                        Some(0) => DeleteRule::NoAction,
+                        Some(2) => DeleteRule::Cascade,
+                        Some(3) => DeleteRule::Deny,
+                        _ => DeleteRule::Nullify,
+                    };
+                    relationships.push(Relationship {
+                        name: property_name,
+                        destination,
+                        inverse,
+                        to_many: max_count != 1,
+                        delete_rule,
+                        transient,
+                    });
+                }
+                // Fetched properties aren't supported.
+                _ => (),
+            }
+        }
+        attributes.sort_by(|a, b| a.name.cmp(&b.name));
+        relationships.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Entity {
+            name,
+            class_name,
+            superentity,
+            is_abstract,
+            attributes,
+            relationships,
+        })
+    }
+
+    fn default_value(&self, value: &'a Value) -> Option<DefaultValue> {
+        match *value {
+            Value::Integer(integer) => integer.as_signed().map(DefaultValue::Integer),
+            Value::Real(real) => Some(DefaultValue::Real(real)),
+            Value::Boolean(boolean) => Some(DefaultValue::Boolean(boolean)),
+            _ => self.string(value).map(DefaultValue::String),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plist::Uid;
+
+    /// Builds the `$objects` array of a keyed archive.
+    struct Builder {
+        objects: Vec<Value>,
+    }
+    impl Builder {
+        fn new() -> Builder {
+            Builder {
+                objects: vec![Value::String("$null".to_string())],
+            }
+        }
+        fn add(&mut self, value: Value) -> Value {
+            self.objects.push(value);
+            Value::Uid(Uid::new(self.objects.len() as u64 - 1))
+        }
+        fn object(&mut self, class: &str, fields: Vec<(&str, Value)>) -> Value {
+            let mut class_dict = Dictionary::new();
+            class_dict.insert("$classname".to_string(), class.into());
+            let class = self.add(Value::Dictionary(class_dict));
+            let mut dict = Dictionary::new();
+            dict.insert("$class".to_string(), class);
+            for (key, value) in fields {
+                dict.insert(key.to_string(), value);
+            }
+            self.add(Value::Dictionary(dict))
+        }
+        fn dictionary(&mut self, entries: Vec<(&str, Value)>) -> Value {
+            let mut keys = Vec::new();
+            let mut objects = Vec::new();
+            for (key, value) in entries {
+                keys.push(self.add(key.into()));
+                objects.push(value);
+            }
+            self.object(
+                "NSDictionary",
+                vec![
+                    ("NS.keys", Value::Array(keys)),
+                    ("NS.objects", Value::Array(objects)),
+                ],
+            )
+        }
+        fn finish(self, root: Value) -> Value {
+            let mut top = Dictionary::new();
+            top.insert("root".to_string(), root);
+            let mut plist = Dictionary::new();
+            plist.insert("$archiver".to_string(), "NSKeyedArchiver".into());
+            plist.insert("$version".to_string(), 100000.into());
+            plist.insert("$top".to_string(), Value::Dictionary(top));
+            plist.insert("$objects".to_string(), Value::Array(self.objects));
+            Value::Dictionary(plist)
+        }
+    }
+
+    #[test]
+    fn parse_model() {
+        let mut b = Builder::new();
+        let folder_name = b.add("Folder".into());
+        let note_name = b.add("Note".into());
+
+        let title = b.object(
+            "NSAttributeDescription",
+            vec![
+                ("NSPropertyName", "title".into()),
+                ("NSAttributeType", 700.into()),
+                ("NSDefaultValue", "Untitled".into()),
+            ],
+        );
+        let folder_rel = b.object(
+            "NSRelationshipDescription",
+            vec![
+                ("NSPropertyName", "folder".into()),
+                ("NSDestinationEntityName", folder_name.clone()),
+                ("NSInverseRelationshipName", "notes".into()),
+                ("NSMaxCount", 1.into()),
+            ],
+        );
+        let note_properties = b.dictionary(vec![("title", title), ("folder", folder_rel.clone())]);
+        let note = b.object(
+            "NSEntityDescription",
+            vec![
+                ("NSEntityName", note_name),
+                ("NSClassNameForEntity", "Note".into()),
+                ("NSProperties", note_properties),
+            ],
+        );
+
+        let notes_rel = b.object(
+            "NSRelationshipDescription",
+            vec![
+                ("NSPropertyName", "notes".into()),
+                ("NSDestinationEntity", note.clone()),
+                ("NSInverseRelationship", folder_rel),
+                ("NSMaxCount", 0.into()),
+                ("NSDeleteRule", 2.into()),
+            ],
+        );
+        let folder_properties = b.dictionary(vec![("notes", notes_rel)]);
+        let folder = b.object(
+            "NSEntityDescription",
+            vec![
+                ("NSEntityName", folder_name),
+                ("NSProperties", folder_properties),
+                ("NSIsAbstract", false.into()),
+            ],
+        );
+        let smart_folder = b.object(
+            "NSEntityDescription",
+            vec![
+                ("NSEntityName", "SmartFolder".into()),
+                ("NSSuperentity", folder.clone()),
+            ],
+        );
+
+        let entities = b.dictionary(vec![
+            ("Note", note),
+            ("SmartFolder", smart_folder),
+            ("Folder", folder),
+        ]);
+        let root = b.object("NSManagedObjectModel", vec![("NSEntities", entities)]);
+        let model = Model::from_archive(&b.finish(root)).unwrap();
+
+        let names: Vec<&str> = model.entities.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["Folder", "Note", "SmartFolder"]);
+        let folder = model.entity_index("Folder").unwrap();
+        let note = model.entity_index("Note").unwrap();
+        let smart_folder = model.entity_index("SmartFolder").unwrap();
+
+        assert_eq!(model.entities[folder].class_name, "NSManagedObject");
+        assert_eq!(model.entities[note].class_name, "Note");
+        assert_eq!(
+            model.attribute(note, "title"),
+            Some(&Attribute {
+                name: "title".to_string(),
+                attribute_type: AttributeType::String,
+                transient: false,
+                default_value: Some(DefaultValue::String("Untitled".to_string())),
+            })
+        );
+
+        let notes = model.relationship(smart_folder, "notes").unwrap();
+        assert!(notes.to_many);
+        assert_eq!(notes.delete_rule, DeleteRule::Cascade);
+        let (destination, inverse) = model.destination(notes);
+        assert_eq!(destination, Some(note));
+        let inverse = inverse.unwrap();
+        assert_eq!(inverse.name, "folder");
+        assert!(!inverse.to_many);
+        assert_eq!(inverse.delete_rule, DeleteRule::Nullify);
+
+        assert_eq!(model.root_entity(smart_folder), folder);
+        assert_eq!(model.entity_and_subentities(folder), [folder, smart_folder]);
+        assert_eq!(model.entity_and_subentities(smart_folder), [smart_folder]);
+    }
+
+    #[test]
+    fn merge() {
+        let entity = |name: &str| Entity {
+            name: name.to_string(),
+            class_name: "NSManagedObject".to_string(),
+            superentity: None,
+            is_abstract: false,
+            attributes: Vec::new(),
+            relationships: Vec::new(),
+        };
+        let a = Model {
+            entities: vec![entity("B")],
+        };
+        let b = Model {
+            entities: vec![entity("A"), entity("C")],
+        };
+        let merged = Model::merge(vec![a.clone(), b]).unwrap();
+        assert_eq!(merged.entity_index("A"), Some(0));
+        assert_eq!(merged.entity_index("B"), Some(1));
+        assert!(Model::merge(vec![a.clone(), a]).is_err());
+    }
+}
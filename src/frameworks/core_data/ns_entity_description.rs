@@ -0,0 +1,159 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSEntityDescription`.
+
+use super::model::Model;
+use super::ns_managed_object_model::NSManagedObjectModelHostObject;
+use crate::frameworks::foundation::ns_array;
+use crate::frameworks::foundation::ns_string::{from_rust_string, to_rust_string};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, retain, Class, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::rc::Rc;
+
+pub(super) struct NSEntityDescriptionHostObject {
+    pub(super) model: Rc<Model>,
+    /// Index into the model's entities.
+    pub(super) index: usize,
+    /// `NSManagedObjectModel*`, weak reference (the model owns its entities).
+    pub(super) managed_object_model: id,
+}
+impl HostObject for NSEntityDescriptionHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSEntityDescription: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSEntityDescriptionHostObject {
+        model: Default::default(),
+        index: 0,
+        managed_object_model: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)entityForName:(id)name // NSString*
+inManagedObjectContext:(id)context { // NSManagedObjectContext*
+    let coordinator: id = msg![env; context persistentStoreCoordinator];
+    let model: id = msg![env; coordinator managedObjectModel];
+    if model == nil {
+        return nil;
+    }
+    let name = to_rust_string(env, name);
+    entity_for_name(env, model, &name).unwrap_or(nil)
+}
+
++ (id)insertNewObjectForEntityForName:(id)name // NSString*
+               inManagedObjectContext:(id)context { // NSManagedObjectContext*
+    let entity: id = msg![env; this entityForName:name inManagedObjectContext:context];
+    if entity == nil {
+        let name = to_rust_string(env, name);
+        panic!("No entity named {:?} in the Core Data model", name);
+    }
+    let class = class_for_entity(env, entity);
+    let object: id = msg![env; class alloc];
+    let object: id = msg![env; object initWithEntity:entity insertIntoManagedObjectContext:context];
+    autorelease(env, object)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+- (id)name {
+    let (model, index) = model_and_index(env, this);
+    let name = from_rust_string(env, model.entities[index].name.clone());
+    autorelease(env, name)
+}
+- (id)managedObjectClassName {
+    let (model, index) = model_and_index(env, this);
+    let name = from_rust_string(env, model.entities[index].class_name.clone());
+    autorelease(env, name)
+}
+- (bool)isAbstract {
+    let (model, index) = model_and_index(env, this);
+    model.entities[index].is_abstract
+}
+
+- (id)managedObjectModel {
+    env.objc.borrow::<NSEntityDescriptionHostObject>(this).managed_object_model
+}
+- (id)superentity {
+    let (model, index) = model_and_index(env, this);
+    match model.superentity(index) {
+        Some(superentity) => entity_for_index(env, this, superentity),
+        None => nil,
+    }
+}
+- (id)subentities {
+    let (model, index) = model_and_index(env, this);
+    let subentities: Vec<id> = (0..model.entities.len())
+        .filter(|&other| model.superentity(other) == Some(index))
+        .map(|other| {
+            let entity = entity_for_index(env, this, other);
+            retain(env, entity)
+        })
+        .collect();
+    let array = ns_array::from_vec(env, subentities);
+    autorelease(env, array)
+}
+
+- (id)description {
+    let (model, index) = model_and_index(env, this);
+    let description = format!("<NSEntityDescription: {}>", model.entities[index].name);
+    let description = from_rust_string(env, description);
+    autorelease(env, description)
+}
+
+@end
+
+};
+
+pub(super) fn model_and_index(env: &mut Environment, entity: id) -> (Rc<Model>, usize) {
+    let host_object = env.objc.borrow::<NSEntityDescriptionHostObject>(entity);
+    (host_object.model.clone(), host_object.index)
+}
+
+/// Get another entity description from the same model.
+pub(super) fn entity_for_index(env: &mut Environment, entity: id, index: usize) -> id {
+    let model = env
+        .objc
+        .borrow::<NSEntityDescriptionHostObject>(entity)
+        .managed_object_model;
+    env.objc
+        .borrow::<NSManagedObjectModelHostObject>(model)
+        .entities[index]
+}
+
+/// Find an entity description in an `NSManagedObjectModel*`.
+pub(super) fn entity_for_name(env: &mut Environment, model: id, name: &str) -> Option<id> {
+    let host_object = env.objc.borrow::<NSManagedObjectModelHostObject>(model);
+    let index = host_object.model.entity_index(name)?;
+    Some(host_object.entities[index])
+}
+
+/// Get the class to use for instances of an entity.
+pub(super) fn class_for_entity(env: &mut Environment, entity: id) -> Class {
+    let (model, index) = model_and_index(env, entity);
+    let class_name = &model.entities[index].class_name;
+    if let Some(class) = env.objc.lookup_class(class_name, &env.mem) {
+        return class;
+    }
+    if class_name != "NSManagedObject" {
+        log!(
+            "Warning: class {:?} for Core Data entity {:?} not found, using NSManagedObject",
+            class_name,
+            model.entities[index].name
+        );
+    }
+    env.objc.get_known_class("NSManagedObject", &mut env.mem)
+}
@@ -0,0 +1,172 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSFetchRequest`.
+
+use crate::frameworks::foundation::NSUInteger;
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+#[derive(Clone)]
+pub(super) struct NSFetchRequestHostObject {
+    /// `NSEntityDescription*`, strong reference.
+    pub(super) entity: id,
+    /// `NSString*`, strong reference. Used if there's no entity description.
+    pub(super) entity_name: id,
+    /// `NSPredicate*`, strong reference.
+    pub(super) predicate: id,
+    /// `NSArray*` of `NSSortDescriptor*`, strong reference.
+    pub(super) sort_descriptors: id,
+    /// 0 means no limit.
+    pub(super) fetch_limit: NSUInteger,
+    pub(super) fetch_offset: NSUInteger,
+    pub(super) includes_subentities: bool,
+    /// Ignored, since results are never batched.
+    fetch_batch_size: NSUInteger,
+}
+impl HostObject for NSFetchRequestHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSFetchRequest: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSFetchRequestHostObject {
+        entity: nil,
+        entity_name: nil,
+        predicate: nil,
+        sort_descriptors: nil,
+        fetch_limit: 0,
+        fetch_offset: 0,
+        includes_subentities: true,
+        fetch_batch_size: 0,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)fetchRequestWithEntityName:(id)entity_name { // NSString*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithEntityName:entity_name];
+    autorelease(env, new)
+}
+
+- (id)initWithEntityName:(id)entity_name { // NSString*
+    let entity_name: id = msg![env; entity_name copy];
+    env.objc.borrow_mut::<NSFetchRequestHostObject>(this).entity_name = entity_name;
+    this
+}
+
+- (())dealloc {
+    let &NSFetchRequestHostObject {
+        entity,
+        entity_name,
+        predicate,
+        sort_descriptors,
+        ..
+    } = env.objc.borrow(this);
+    release(env, entity);
+    release(env, entity_name);
+    release(env, predicate);
+    release(env, sort_descriptors);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    let host_object = env.objc.borrow::<NSFetchRequestHostObject>(this).clone();
+    retain(env, host_object.entity);
+    retain(env, host_object.entity_name);
+    retain(env, host_object.predicate);
+    retain(env, host_object.sort_descriptors);
+    let new: id = msg_class![env; NSFetchRequest alloc];
+    *env.objc.borrow_mut(new) = host_object;
+    new
+}
+
+- (id)entity {
+    env.objc.borrow::<NSFetchRequestHostObject>(this).entity
+}
+- (())setEntity:(id)entity { // NSEntityDescription*
+    retain(env, entity);
+    let host_object = env.objc.borrow_mut::<NSFetchRequestHostObject>(this);
+    let old = std::mem::replace(&mut host_object.entity, entity);
+    release(env, old);
+}
+- (id)entityName {
+    let &NSFetchRequestHostObject {
+        entity,
+        entity_name,
+        ..
+    } = env.objc.borrow(this);
+    if entity != nil {
+        msg![env; entity name]
+    } else {
+        entity_name
+    }
+}
+
+- (id)predicate {
+    env.objc.borrow::<NSFetchRequestHostObject>(this).predicate
+}
+- (())setPredicate:(id)predicate { // NSPredicate*
+    let predicate: id = msg![env; predicate copy];
+    let host_object = env.objc.borrow_mut::<NSFetchRequestHostObject>(this);
+    let old = std::mem::replace(&mut host_object.predicate, predicate);
+    release(env, old);
+}
+
+- (id)sortDescriptors {
+    env.objc.borrow::<NSFetchRequestHostObject>(this).sort_descriptors
+}
+- (())setSortDescriptors:(id)sort_descriptors { // NSArray*
+    let sort_descriptors: id = msg![env; sort_descriptors copy];
+    let host_object = env.objc.borrow_mut::<NSFetchRequestHostObject>(this);
+    let old = std::mem::replace(&mut host_object.sort_descriptors, sort_descriptors);
+    release(env, old);
+}
+
+- (NSUInteger)fetchLimit {
+    env.objc.borrow::<NSFetchRequestHostObject>(this).fetch_limit
+}
+- (())setFetchLimit:(NSUInteger)fetch_limit {
+    env.objc.borrow_mut::<NSFetchRequestHostObject>(this).fetch_limit = fetch_limit;
+}
+- (NSUInteger)fetchOffset {
+    env.objc.borrow::<NSFetchRequestHostObject>(this).fetch_offset
+}
+- (())setFetchOffset:(NSUInteger)fetch_offset {
+    env.objc.borrow_mut::<NSFetchRequestHostObject>(this).fetch_offset = fetch_offset;
+}
+- (NSUInteger)fetchBatchSize {
+    env.objc.borrow::<NSFetchRequestHostObject>(this).fetch_batch_size
+}
+- (())setFetchBatchSize:(NSUInteger)fetch_batch_size {
+    env.objc.borrow_mut::<NSFetchRequestHostObject>(this).fetch_batch_size = fetch_batch_size;
+}
+- (bool)includesSubentities {
+    env.objc.borrow::<NSFetchRequestHostObject>(this).includes_subentities
+}
+- (())setIncludesSubentities:(bool)includes_subentities {
+    env.objc.borrow_mut::<NSFetchRequestHostObject>(this).includes_subentities =
+        includes_subentities;
+}
+
+// These are only hints, so they're ignored.
+- (())setReturnsObjectsAsFaults:(bool)_returns_objects_as_faults {}
+- (())setRelationshipKeyPathsForPrefetching:(id)_key_paths {} // NSArray*
+
+@end
+
+};
+
+/// Shortcut for host code: get the settings of a fetch request.
+pub(super) fn settings(env: &mut Environment, request: id) -> NSFetchRequestHostObject {
+    env.objc.borrow::<NSFetchRequestHostObject>(request).clone()
+}
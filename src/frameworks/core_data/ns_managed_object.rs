@@ -0,0 +1,809 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSManagedObject` and `NSManagedObjectID`.
+//!
+//! Apps usually declare their model properties with `@dynamic`, so there's no
+//! implementation of the accessor methods in the app. Like Apple's
+//! implementation, we add them to each entity's class when the model is loaded
+//! (see [add_accessors]).
+
+use super::model::{AttributeType, DefaultValue, Model, Relationship};
+use super::ns_entity_description::{self, model_and_index};
+use super::ns_managed_object_context;
+use super::store::SqlValue;
+use crate::frameworks::foundation::ns_fast_enumeration::NSFastEnumerationState;
+use crate::frameworks::foundation::ns_string::{from_rust_string, to_rust_string};
+use crate::frameworks::foundation::{ns_data, ns_date, NSUInteger};
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, Class,
+    ClassExports, HostObject, IMP, SEL,
+};
+use crate::Environment;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Default)]
+pub struct State {
+    /// The property and kind of accessor for each method added by
+    /// [add_accessors].
+    accessors: HashMap<SEL, (Accessor, String)>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.core_data.ns_managed_object
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Accessor {
+    /// `- (id)foo`
+    Get,
+    /// `- (void)setFoo:(id)value`
+    Set,
+    /// `- (void)addFooObject:(id)value`
+    AddObject,
+    /// `- (void)removeFooObject:(id)value`
+    RemoveObject,
+    /// `- (void)addFoo:(NSSet*)values`
+    AddObjects,
+    /// `- (void)removeFoo:(NSSet*)values`
+    RemoveObjects,
+}
+
+pub(super) struct NSManagedObjectHostObject {
+    /// `NSEntityDescription*`, strong reference, or [nil] before the object is
+    /// initialized.
+    pub(super) entity: id,
+    /// `NSManagedObjectContext*` the object is registered with, weak reference.
+    pub(super) context: id,
+    /// Primary key in the store, or 0 if the object was never registered.
+    pub(super) pk: i64,
+    /// Whether the object hasn't been saved to the store yet.
+    pub(super) temporary: bool,
+    /// Whether the values haven't been loaded from the store yet.
+    pub(super) fault: bool,
+    pub(super) inserted: bool,
+    pub(super) updated: bool,
+    pub(super) deleted: bool,
+    /// Values of properties, all strong references. Missing values are [nil].
+    /// To-many relationships are `NSMutableSet*`s.
+    pub(super) values: HashMap<String, id>,
+}
+impl HostObject for NSManagedObjectHostObject {}
+impl Default for NSManagedObjectHostObject {
+    fn default() -> Self {
+        NSManagedObjectHostObject {
+            entity: nil,
+            context: nil,
+            pk: 0,
+            temporary: true,
+            fault: false,
+            inserted: false,
+            updated: false,
+            deleted: false,
+            values: HashMap::new(),
+        }
+    }
+}
+
+struct NSManagedObjectIDHostObject {
+    /// `NSEntityDescription*`, strong reference.
+    entity: id,
+    pk: i64,
+    temporary: bool,
+}
+impl HostObject for NSManagedObjectIDHostObject {}
+
+/// Belongs to _touchHLE_NSManagedObjectSet, the set returned by
+/// `mutableSetValueForKey:`. Changes to it go through [link] and [unlink], so
+/// the inverse relationship is kept up to date.
+struct ManagedObjectSetHostObject {
+    /// `NSManagedObject*`, strong reference.
+    object: id,
+    key: String,
+}
+impl HostObject for ManagedObjectSetHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSManagedObject: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::<NSManagedObjectHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithEntity:(id)entity // NSEntityDescription*
+insertIntoManagedObjectContext:(id)context { // NSManagedObjectContext*
+    retain(env, entity);
+    env.objc.borrow_mut::<NSManagedObjectHostObject>(this).entity = entity;
+
+    let (model, index) = model_and_index(env, entity);
+    for attribute in model.attributes(index) {
+        if let Some(default_value) = &attribute.default_value {
+            let value = object_from_default(env, attribute.attribute_type, default_value);
+            env.objc
+                .borrow_mut::<NSManagedObjectHostObject>(this)
+                .values
+                .insert(attribute.name.clone(), value);
+        }
+    }
+
+    if context != nil {
+        let () = msg![env; context insertObject:this];
+    }
+    this
+}
+
+- (())dealloc {
+    clear_values(env, this);
+    let entity = env.objc.borrow::<NSManagedObjectHostObject>(this).entity;
+    release(env, entity);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)entity {
+    env.objc.borrow::<NSManagedObjectHostObject>(this).entity
+}
+- (id)managedObjectContext {
+    env.objc.borrow::<NSManagedObjectHostObject>(this).context
+}
+- (id)objectID {
+    let &NSManagedObjectHostObject {
+        entity,
+        pk,
+        temporary,
+        ..
+    } = env.objc.borrow(this);
+    retain(env, entity);
+    let object_id: id = msg_class![env; NSManagedObjectID alloc];
+    *env.objc.borrow_mut(object_id) = NSManagedObjectIDHostObject {
+        entity,
+        pk,
+        temporary,
+    };
+    autorelease(env, object_id)
+}
+
+- (bool)isInserted {
+    env.objc.borrow::<NSManagedObjectHostObject>(this).inserted
+}
+- (bool)isUpdated {
+    env.objc.borrow::<NSManagedObjectHostObject>(this).updated
+}
+- (bool)isDeleted {
+    env.objc.borrow::<NSManagedObjectHostObject>(this).deleted
+}
+- (bool)isFault {
+    env.objc.borrow::<NSManagedObjectHostObject>(this).fault
+}
+
+// NSKeyValueCoding overrides
+- (id)valueForKey:(id)key { // NSString*
+    let key_string = to_rust_string(env, key);
+    if let Some(value) = value_for_property(env, this, &key_string) {
+        return value;
+    }
+    // Not a modeled property, so use a normal getter method.
+    let class = msg![env; this class];
+    if let Some(sel) = env.objc.lookup_selector(&key_string) {
+        if env.objc.class_has_method(class, sel) {
+            return msg_send(env, (this, sel));
+        }
+    }
+    unimplemented!("TODO: {:?} has no property or getter for {}", this, key_string);
+}
+- (())setValue:(id)value
+       forKey:(id)key { // NSString*
+    let key_string = to_rust_string(env, key);
+    if !set_value_for_property(env, this, &key_string, value) {
+        let class = msg![env; this class];
+        let sel = env.objc.lookup_selector(&setter_name(&key_string));
+        match sel {
+            Some(sel) if env.objc.class_has_method(class, sel) => {
+                let () = msg_send(env, (this, sel, value));
+            }
+            _ => {
+                unimplemented!("TODO: {:?} has no property or setter for {}", this, key_string);
+            }
+        }
+    }
+}
+- (id)mutableSetValueForKey:(id)key { // NSString*
+    let key = to_rust_string(env, key).into_owned();
+    retain(env, this);
+    let set: id = msg_class![env; _touchHLE_NSManagedObjectSet alloc];
+    *env.objc.borrow_mut(set) = ManagedObjectSetHostObject { object: this, key };
+    autorelease(env, set)
+}
+
+- (id)primitiveValueForKey:(id)key { // NSString*
+    let key = to_rust_string(env, key);
+    primitive_value(env, this, &key)
+}
+- (())setPrimitiveValue:(id)value
+                 forKey:(id)key { // NSString*
+    let key = to_rust_string(env, key);
+    set_primitive_value(env, this, &key, value);
+}
+
+// Change notifications aren't supported, so these do nothing.
+- (())willAccessValueForKey:(id)_key {} // NSString*
+- (())didAccessValueForKey:(id)_key {} // NSString*
+- (())willChangeValueForKey:(id)_key {} // NSString*
+- (())didChangeValueForKey:(id)_key {} // NSString*
+
+// These are for subclasses to override.
+- (())awakeFromInsert {}
+- (())awakeFromFetch {}
+- (())willSave {}
+- (())didSave {}
+- (())willTurnIntoFault {}
+- (())didTurnIntoFault {}
+
+@end
+
+@implementation NSManagedObjectID: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSManagedObjectIDHostObject {
+        entity: nil,
+        pk: 0,
+        temporary: true,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let entity = env.objc.borrow::<NSManagedObjectIDHostObject>(this).entity;
+    release(env, entity);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+- (id)entity {
+    env.objc.borrow::<NSManagedObjectIDHostObject>(this).entity
+}
+- (bool)isTemporaryID {
+    env.objc.borrow::<NSManagedObjectIDHostObject>(this).temporary
+}
+
+- (id)URIRepresentation {
+    let &NSManagedObjectIDHostObject {
+        entity,
+        pk,
+        temporary,
+    } = env.objc.borrow(this);
+    let (model, index) = model_and_index(env, entity);
+    let url = format!(
+        "x-coredata://touchHLE/{}/{}{}",
+        model.entities[index].name,
+        if temporary { 't' } else { 'p' },
+        pk
+    );
+    let string = from_rust_string(env, url);
+    let url: id = msg_class![env; NSURL URLWithString:string];
+    release(env, string);
+    url
+}
+
+- (NSUInteger)hash {
+    let &NSManagedObjectIDHostObject { entity, pk, .. } = env.objc.borrow(this);
+    let (model, index) = model_and_index(env, entity);
+    ((model.root_entity(index) as NSUInteger) << 24) ^ (pk as NSUInteger)
+}
+- (bool)isEqual:(id)other {
+    if this == other {
+        return true;
+    }
+    let class: Class = msg![env; this class];
+    if other == nil {
+        return false;
+    }
+    let is_id: bool = msg![env; other isKindOfClass:class];
+    if !is_id {
+        return false;
+    }
+    let &NSManagedObjectIDHostObject { entity, pk, .. } = env.objc.borrow(this);
+    let &NSManagedObjectIDHostObject {
+        entity: other_entity,
+        pk: other_pk,
+        ..
+    } = env.objc.borrow(other);
+    let (model, index) = model_and_index(env, entity);
+    let (other_model, other_index) = model_and_index(env, other_entity);
+    pk == other_pk
+        && Rc::ptr_eq(&model, &other_model)
+        && model.root_entity(index) == model.root_entity(other_index)
+}
+- (bool)isEqualTo:(id)other {
+    msg![env; this isEqual:other]
+}
+
+- (id)description {
+    let url: id = msg![env; this URIRepresentation];
+    msg![env; url absoluteString]
+}
+
+@end
+
+// Proxy for the contents of a to-many relationship.
+@implementation _touchHLE_NSManagedObjectSet: NSMutableSet
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(ManagedObjectSetHostObject {
+        object: nil,
+        key: String::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let object = env.objc.borrow::<ManagedObjectSetHostObject>(this).object;
+    release(env, object);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (NSUInteger)count {
+    let set = proxied_set(env, this);
+    msg![env; set count]
+}
+- (id)member:(id)object {
+    let set = proxied_set(env, this);
+    msg![env; set member:object]
+}
+- (id)anyObject {
+    let set = proxied_set(env, this);
+    msg![env; set anyObject]
+}
+- (id)allObjects {
+    let set = proxied_set(env, this);
+    msg![env; set allObjects]
+}
+- (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
+                                  objects:(MutPtr<id>)stackbuf
+                                    count:(NSUInteger)len {
+    let set = proxied_set(env, this);
+    msg![env; set countByEnumeratingWithState:state objects:stackbuf count:len]
+}
+
+- (())addObject:(id)value {
+    let ManagedObjectSetHostObject { object, key } =
+        env.objc.borrow::<ManagedObjectSetHostObject>(this);
+    let (object, key) = (*object, key.clone());
+    link(env, object, &key, value);
+}
+- (())removeObject:(id)value {
+    let ManagedObjectSetHostObject { object, key } =
+        env.objc.borrow::<ManagedObjectSetHostObject>(this);
+    let (object, key) = (*object, key.clone());
+    unlink(env, object, &key, value);
+}
+
+@end
+
+};
+
+fn proxied_set(env: &mut Environment, proxy: id) -> id {
+    let ManagedObjectSetHostObject { object, key } =
+        env.objc.borrow::<ManagedObjectSetHostObject>(proxy);
+    let (object, key) = (*object, key.clone());
+    primitive_value(env, object, &key)
+}
+
+fn setter_name(key: &str) -> String {
+    let mut chars = key.chars();
+    let first = chars.next().unwrap_or_default().to_ascii_uppercase();
+    format!("set{}{}:", first, chars.as_str())
+}
+
+/// Add methods for the properties of an entity to its class, if the class
+/// doesn't have them already.
+pub(super) fn add_accessors(env: &mut Environment, model: &Model, entity: usize) {
+    let class_name = &model.entities[entity].class_name;
+    if class_name == "NSManagedObject" {
+        return;
+    }
+    let Some(class) = env.objc.lookup_class(class_name, &env.mem) else {
+        return;
+    };
+
+    let mut methods = Vec::new();
+    for attribute in model.attributes(entity) {
+        methods.push((attribute.name.clone(), Accessor::Get, &attribute.name));
+        methods.push((setter_name(&attribute.name), Accessor::Set, &attribute.name));
+    }
+    for relationship in model.relationships(entity) {
+        let name = &relationship.name;
+        methods.push((name.clone(), Accessor::Get, name));
+        methods.push((setter_name(name), Accessor::Set, name));
+        if relationship.to_many {
+            let setter = setter_name(name);
+            let capitalized = &setter[3..setter.len() - 1];
+            let to_many_methods = [
+                (format!("add{}Object:", capitalized), Accessor::AddObject),
+                (
+                    format!("remove{}Object:", capitalized),
+                    Accessor::RemoveObject,
+                ),
+                (format!("add{}:", capitalized), Accessor::AddObjects),
+                (format!("remove{}:", capitalized), Accessor::RemoveObjects),
+            ];
+            for (selector, accessor) in to_many_methods {
+                methods.push((selector, accessor, name));
+            }
+        }
+    }
+
+    for (selector, accessor, property) in methods {
+        // The selector won't exist if the app never uses this method.
+        let Some(sel) = env.objc.lookup_selector(&selector) else {
+            continue;
+        };
+        // Don't replace the app's own implementation.
+        if env.objc.class_has_method(class, sel) {
+            continue;
+        }
+        let imp = match accessor {
+            Accessor::Get => IMP::Host(&(dynamic_getter as fn(&mut Environment, id, SEL) -> id)),
+            _ => IMP::Host(&(dynamic_setter as fn(&mut Environment, id, SEL, id))),
+        };
+        env.objc.add_method(class, sel, imp);
+        State::get(&mut env.framework_state)
+            .accessors
+            .insert(sel, (accessor, property.clone()));
+    }
+}
+
+fn dynamic_getter(env: &mut Environment, this: id, sel: SEL) -> id {
+    let (_, key) = State::get(&mut env.framework_state).accessors[&sel].clone();
+    value_for_property(env, this, &key).unwrap()
+}
+
+fn dynamic_setter(env: &mut Environment, this: id, sel: SEL, value: id) {
+    let (accessor, key) = State::get(&mut env.framework_state).accessors[&sel].clone();
+    match accessor {
+        Accessor::Get => unreachable!(),
+        Accessor::Set => {
+            set_value_for_property(env, this, &key, value);
+        }
+        Accessor::AddObject => link(env, this, &key, value),
+        Accessor::RemoveObject => unlink(env, this, &key, value),
+        Accessor::AddObjects | Accessor::RemoveObjects => {
+            let values: id = msg![env; value allObjects];
+            let count: NSUInteger = msg![env; values count];
+            for i in 0..count {
+                let value: id = msg![env; values objectAtIndex:i];
+                if let Accessor::AddObjects = accessor {
+                    link(env, this, &key, value);
+                } else {
+                    unlink(env, this, &key, value);
+                }
+            }
+        }
+    }
+}
+
+/// Get the model and entity index of a managed object.
+pub(super) fn entity_of(env: &mut Environment, object: id) -> (Rc<Model>, usize) {
+    let entity = env.objc.borrow::<NSManagedObjectHostObject>(object).entity;
+    model_and_index(env, entity)
+}
+
+fn fire_fault(env: &mut Environment, object: id) {
+    if env.objc.borrow::<NSManagedObjectHostObject>(object).fault {
+        ns_managed_object_context::fill_object(env, object);
+    }
+}
+
+/// Release all of an object's values. This is used when it's deallocated or
+/// turned back into a fault.
+pub(super) fn clear_values(env: &mut Environment, object: id) {
+    let values = std::mem::take(
+        &mut env
+            .objc
+            .borrow_mut::<NSManagedObjectHostObject>(object)
+            .values,
+    );
+    for (_key, value) in values {
+        release(env, value);
+    }
+}
+
+/// Get the value of a property without any conversion. For a to-many
+/// relationship, this is the `NSMutableSet*` the object keeps.
+pub(super) fn primitive_value(env: &mut Environment, object: id, key: &str) -> id {
+    fire_fault(env, object);
+    let host_object = env.objc.borrow::<NSManagedObjectHostObject>(object);
+    if let Some(&value) = host_object.values.get(key) {
+        return value;
+    }
+    let (model, entity) = entity_of(env, object);
+    if model
+        .relationship(entity, key)
+        .map_or(false, |relationship| relationship.to_many)
+    {
+        let set: id = msg_class![env; NSMutableSet alloc];
+        let set: id = msg![env; set init];
+        env.objc
+            .borrow_mut::<NSManagedObjectHostObject>(object)
+            .values
+            .insert(key.to_string(), set);
+        return set;
+    }
+    nil
+}
+
+/// Set the value of a property without maintaining relationships.
+pub(super) fn set_primitive_value(env: &mut Environment, object: id, key: &str, value: id) {
+    fire_fault(env, object);
+    retain(env, value);
+    let values = &mut env
+        .objc
+        .borrow_mut::<NSManagedObjectHostObject>(object)
+        .values;
+    let old = if value == nil {
+        values.remove(key)
+    } else {
+        values.insert(key.to_string(), value)
+    };
+    if let Some(old) = old {
+        release(env, old);
+    }
+    ns_managed_object_context::object_changed(env, object);
+}
+
+/// Get the value of a modeled property, or [None] if there's no such property.
+fn value_for_property(env: &mut Environment, object: id, key: &str) -> Option<id> {
+    let (model, entity) = entity_of(env, object);
+    if model.attribute(entity, key).is_some() {
+        return Some(primitive_value(env, object, key));
+    }
+    let relationship = model.relationship(entity, key)?;
+    let value = primitive_value(env, object, key);
+    if !relationship.to_many {
+        return Some(value);
+    }
+    // A copy, so the app can change the relationship while enumerating it.
+    let copy: id = msg![env; value copy];
+    Some(autorelease(env, copy))
+}
+
+/// Set the value of a modeled property. Returns [false] if there's no such
+/// property.
+fn set_value_for_property(env: &mut Environment, object: id, key: &str, value: id) -> bool {
+    let (model, entity) = entity_of(env, object);
+    if model.attribute(entity, key).is_some() {
+        set_primitive_value(env, object, key, value);
+        return true;
+    }
+    let Some(relationship) = model.relationship(entity, key) else {
+        return false;
+    };
+    if !relationship.to_many {
+        link(env, object, key, value);
+        return true;
+    }
+    let current = primitive_value(env, object, key);
+    let old: id = msg![env; current allObjects];
+    let old_count: NSUInteger = msg![env; old count];
+    for i in 0..old_count {
+        let destination: id = msg![env; old objectAtIndex:i];
+        unlink(env, object, key, destination);
+    }
+    if value != nil {
+        let new: id = msg![env; value allObjects];
+        let new_count: NSUInteger = msg![env; new count];
+        for i in 0..new_count {
+            let destination: id = msg![env; new objectAtIndex:i];
+            link(env, object, key, destination);
+        }
+    }
+    true
+}
+
+/// Add an object to one side of a relationship.
+fn add_to_side(env: &mut Environment, object: id, relationship: &Relationship, destination: id) {
+    if relationship.to_many {
+        let set = primitive_value(env, object, &relationship.name);
+        let () = msg![env; set addObject:destination];
+        ns_managed_object_context::object_changed(env, object);
+    } else {
+        set_primitive_value(env, object, &relationship.name, destination);
+    }
+}
+
+/// Remove an object from one side of a relationship.
+fn remove_from_side(
+    env: &mut Environment,
+    object: id,
+    relationship: &Relationship,
+    destination: id,
+) {
+    if relationship.to_many {
+        let set = primitive_value(env, object, &relationship.name);
+        let () = msg![env; set removeObject:destination];
+        ns_managed_object_context::object_changed(env, object);
+    } else if primitive_value(env, object, &relationship.name) == destination {
+        set_primitive_value(env, object, &relationship.name, nil);
+    }
+}
+
+/// Add an object to a relationship (or set a to-one relationship, if
+/// `destination` isn't [nil]) and update the inverse relationship.
+pub(super) fn link(env: &mut Environment, object: id, key: &str, destination: id) {
+    let (model, entity) = entity_of(env, object);
+    let relationship = model.relationship(entity, key).unwrap();
+    let (_, inverse) = model.destination(relationship);
+
+    if !relationship.to_many {
+        let old = primitive_value(env, object, key);
+        if old == destination {
+            return;
+        }
+        if old != nil {
+            unlink(env, object, key, old);
+        }
+    }
+    if destination == nil {
+        return;
+    }
+    if let Some(inverse) = inverse.filter(|inverse| !inverse.to_many) {
+        let old_owner = primitive_value(env, destination, &inverse.name);
+        if old_owner != nil && old_owner != object {
+            unlink(env, destination, &inverse.name, old_owner);
+        }
+    }
+
+    add_to_side(env, object, relationship, destination);
+    if let Some(inverse) = inverse {
+        add_to_side(env, destination, inverse, object);
+    }
+}
+
+/// Remove an object from a relationship and update the inverse relationship.
+pub(super) fn unlink(env: &mut Environment, object: id, key: &str, destination: id) {
+    let (model, entity) = entity_of(env, object);
+    let relationship = model.relationship(entity, key).unwrap();
+    let (_, inverse) = model.destination(relationship);
+    remove_from_side(env, object, relationship, destination);
+    if let Some(inverse) = inverse {
+        remove_from_side(env, destination, inverse, object);
+    }
+}
+
+/// Create an object (+1 reference) for an attribute's default value.
+fn object_from_default(
+    env: &mut Environment,
+    attribute_type: AttributeType,
+    default_value: &DefaultValue,
+) -> id {
+    let value = match *default_value {
+        DefaultValue::Integer(value) => SqlValue::Integer(value),
+        DefaultValue::Real(value) => SqlValue::Real(value),
+        DefaultValue::Boolean(value) => SqlValue::Integer(value.into()),
+        DefaultValue::String(ref value) => {
+            if attribute_type.is_integer() || attribute_type == AttributeType::Boolean {
+                SqlValue::Integer(value.trim().parse().unwrap_or(0))
+            } else if attribute_type == AttributeType::String {
+                SqlValue::Text(value.clone())
+            } else {
+                SqlValue::Real(value.trim().parse().unwrap_or(0.0))
+            }
+        }
+    };
+    object_from_sql(env, attribute_type, &value)
+}
+
+/// Create an object (+1 reference) for a value of an attribute loaded from the
+/// store.
+pub(super) fn object_from_sql(
+    env: &mut Environment,
+    attribute_type: AttributeType,
+    value: &SqlValue,
+) -> id {
+    match (attribute_type, value) {
+        (_, SqlValue::Null) => nil,
+        (AttributeType::Boolean, &SqlValue::Integer(value)) => {
+            let number: id = msg_class![env; NSNumber alloc];
+            msg![env; number initWithBool:(value != 0)]
+        }
+        (_, &SqlValue::Integer(value)) if attribute_type.is_integer() => {
+            let number: id = msg_class![env; NSNumber alloc];
+            msg![env; number initWithLongLong:value]
+        }
+        (AttributeType::Date, &SqlValue::Real(value)) => ns_date::from_time_interval(env, value),
+        (AttributeType::Date, &SqlValue::Integer(value)) => {
+            ns_date::from_time_interval(env, value as f64)
+        }
+        (AttributeType::Decimal | AttributeType::Double | AttributeType::Float, value) => {
+            let value = match *value {
+                SqlValue::Integer(value) => value as f64,
+                SqlValue::Real(value) => value,
+                _ => 0.0,
+            };
+            let number: id = msg_class![env; NSNumber alloc];
+            msg![env; number initWithDouble:value]
+        }
+        (_, SqlValue::Text(text)) => from_rust_string(env, text.clone()),
+        (_, SqlValue::Blob(bytes)) => ns_data::from_bytes(env, bytes),
+        (_, value) => {
+            log!(
+                "Warning: ignoring Core Data value {:?} for {:?} attribute",
+                value,
+                attribute_type
+            );
+            nil
+        }
+    }
+}
+
+/// Convert an attribute's value for saving to the store.
+pub(super) fn object_to_sql(
+    env: &mut Environment,
+    attribute_type: AttributeType,
+    value: id,
+) -> SqlValue {
+    if value == nil {
+        return SqlValue::Null;
+    }
+    match attribute_type {
+        AttributeType::Boolean => {
+            let value: bool = msg![env; value boolValue];
+            SqlValue::Integer(value.into())
+        }
+        _ if attribute_type.is_integer() => SqlValue::Integer(msg![env; value longLongValue]),
+        AttributeType::Decimal | AttributeType::Double | AttributeType::Float => {
+            SqlValue::Real(msg![env; value doubleValue])
+        }
+        AttributeType::String => SqlValue::Text(to_rust_string(env, value).into_owned()),
+        AttributeType::Date => SqlValue::Real(ns_date::get_time_interval(env, value)),
+        AttributeType::Binary | AttributeType::Transformable | AttributeType::Undefined => {
+            let class = env.objc.get_known_class("NSData", &mut env.mem);
+            if msg![env; value isKindOfClass:class] {
+                SqlValue::Blob(ns_data::to_vec(env, value))
+            } else {
+                // TODO: use the value transformer (NSKeyedUnarchiveFromData
+                // by default) for transformable attributes
+                log!(
+                    "Warning: can't save {:?} in a Core Data {:?} attribute",
+                    value,
+                    attribute_type
+                );
+                SqlValue::Null
+            }
+        }
+    }
+}
+
+/// Create an entity's object with no values loaded. The caller registers it.
+pub(super) fn new_fault(env: &mut Environment, entity: id, context: id, pk: i64) -> id {
+    let class = ns_entity_description::class_for_entity(env, entity);
+    let object: id = msg![env; class alloc];
+    retain(env, entity);
+    *env.objc.borrow_mut(object) = NSManagedObjectHostObject {
+        entity,
+        context,
+        pk,
+        temporary: false,
+        fault: true,
+        ..Default::default()
+    };
+    object
+}
+
+/// Get the entity and primary key of an `NSManagedObjectID*`.
+pub(super) fn object_id_parts(env: &mut Environment, object_id: id) -> (id, i64) {
+    let &NSManagedObjectIDHostObject { entity, pk, .. } = env.objc.borrow(object_id);
+    (entity, pk)
+}
@@ -0,0 +1,683 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSManagedObjectContext`.
+//!
+//! A context holds a strong reference to every object registered with it, so
+//! that each object in the store has at most one instance per context. They
+//! are only released when they're deleted and saved, or the context is reset
+//! or deallocated.
+
+use super::core_data_errors::NSPersistentStoreSaveError;
+use super::model::DeleteRule;
+use super::ns_entity_description::{self, model_and_index};
+use super::ns_fetch_request::{self, NSFetchRequestHostObject};
+use super::ns_managed_object::{
+    self, clear_values, entity_of, object_from_sql, object_to_sql, primitive_value, unlink,
+    NSManagedObjectHostObject,
+};
+use super::ns_managed_object_model::NSManagedObjectModelHostObject;
+use super::ns_persistent_store_coordinator::with_store;
+use super::store::{Changes, Row, SqlValue, ToManyContents};
+use crate::frameworks::foundation::ns_error::{new_error, NSCocoaErrorDomain};
+use crate::frameworks::foundation::ns_string::to_rust_string;
+use crate::frameworks::foundation::{
+    ns_array, ns_predicate, ns_set, ns_sort_descriptor, NSUInteger,
+};
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct NSManagedObjectContextHostObject {
+    /// `NSPersistentStoreCoordinator*`, strong reference.
+    coordinator: id,
+    /// `NSUndoManager*`, strong reference. It's never used.
+    undo_manager: id,
+    /// `NSManagedObject*`s, keyed by root entity and primary key. Strong
+    /// references.
+    registered: HashMap<(usize, i64), id>,
+    /// `NSManagedObject*`s with changes to save, in the order they changed.
+    inserted: Vec<id>,
+    updated: Vec<id>,
+    deleted: Vec<id>,
+}
+impl HostObject for NSManagedObjectContextHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSManagedObjectContext: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::<NSManagedObjectContextHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    unregister_all(env, this);
+    let &NSManagedObjectContextHostObject {
+        coordinator,
+        undo_manager,
+        ..
+    } = env.objc.borrow(this);
+    release(env, coordinator);
+    release(env, undo_manager);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)persistentStoreCoordinator {
+    env.objc.borrow::<NSManagedObjectContextHostObject>(this).coordinator
+}
+- (())setPersistentStoreCoordinator:(id)coordinator { // NSPersistentStoreCoordinator*
+    retain(env, coordinator);
+    let host_object = env.objc.borrow_mut::<NSManagedObjectContextHostObject>(this);
+    let old = std::mem::replace(&mut host_object.coordinator, coordinator);
+    release(env, old);
+}
+
+- (id)undoManager {
+    env.objc.borrow::<NSManagedObjectContextHostObject>(this).undo_manager
+}
+- (())setUndoManager:(id)undo_manager { // NSUndoManager*
+    if undo_manager != nil {
+        log!("Warning: Core Data undo isn't supported");
+    }
+    retain(env, undo_manager);
+    let host_object = env.objc.borrow_mut::<NSManagedObjectContextHostObject>(this);
+    let old = std::mem::replace(&mut host_object.undo_manager, undo_manager);
+    release(env, old);
+}
+
+// There's only one thread and changes are processed immediately, so these do
+// nothing.
+- (())lock {}
+- (())unlock {}
+- (bool)tryLock {
+    true
+}
+- (())processPendingChanges {}
+- (())setMergePolicy:(id)_merge_policy {}
+- (())setStalenessInterval:(f64)_interval {}
+
+- (())insertObject:(id)object { // NSManagedObject*
+    let host_object = env.objc.borrow::<NSManagedObjectHostObject>(object);
+    if host_object.context == this {
+        return;
+    }
+    assert!(host_object.context == nil, "Object is already in another context");
+    let (model, entity) = entity_of(env, object);
+    let coordinator = env.objc.borrow::<NSManagedObjectContextHostObject>(this).coordinator;
+    let Some(pk) = with_store(env, coordinator, |store| store.allocate_pk(entity)) else {
+        panic!("Can't insert Core Data object into context without a persistent store");
+    };
+
+    let host_object = env.objc.borrow_mut::<NSManagedObjectHostObject>(object);
+    host_object.context = this;
+    host_object.pk = pk;
+    host_object.temporary = true;
+    host_object.fault = false;
+    host_object.inserted = true;
+    retain(env, object);
+    let host_object = env.objc.borrow_mut::<NSManagedObjectContextHostObject>(this);
+    host_object.registered.insert((model.root_entity(entity), pk), object);
+    host_object.inserted.push(object);
+
+    let () = msg![env; object awakeFromInsert];
+}
+
+- (())deleteObject:(id)object { // NSManagedObject*
+    delete_object(env, this, object);
+}
+
+- (id)executeFetchRequest:(id)request // NSFetchRequest*
+                    error:(MutPtr<id>)error { // NSError**
+    if !error.is_null() {
+        env.mem.write(error, nil);
+    }
+    let objects = fetch(env, this, request);
+    for &object in &objects {
+        retain(env, object);
+    }
+    let array = ns_array::from_vec(env, objects);
+    autorelease(env, array)
+}
+
+- (NSUInteger)countForFetchRequest:(id)request // NSFetchRequest*
+                             error:(MutPtr<id>)error { // NSError**
+    if !error.is_null() {
+        env.mem.write(error, nil);
+    }
+    fetch(env, this, request).len() as NSUInteger
+}
+
+- (id)objectWithID:(id)object_id { // NSManagedObjectID*
+    let (entity, pk) = ns_managed_object::object_id_parts(env, object_id);
+    let (_, index) = model_and_index(env, entity);
+    object_for_pk(env, this, index, pk)
+}
+
+- (bool)hasChanges {
+    let host_object = env.objc.borrow::<NSManagedObjectContextHostObject>(this);
+    !host_object.inserted.is_empty()
+        || !host_object.updated.is_empty()
+        || !host_object.deleted.is_empty()
+}
+- (id)insertedObjects {
+    let objects = env.objc.borrow::<NSManagedObjectContextHostObject>(this).inserted.clone();
+    set_of(env, objects)
+}
+- (id)updatedObjects {
+    let objects = env.objc.borrow::<NSManagedObjectContextHostObject>(this).updated.clone();
+    set_of(env, objects)
+}
+- (id)deletedObjects {
+    let objects = env.objc.borrow::<NSManagedObjectContextHostObject>(this).deleted.clone();
+    set_of(env, objects)
+}
+- (id)registeredObjects {
+    let objects = env
+        .objc
+        .borrow::<NSManagedObjectContextHostObject>(this)
+        .registered
+        .values()
+        .copied()
+        .collect();
+    set_of(env, objects)
+}
+
+- (bool)save:(MutPtr<id>)error { // NSError**
+    match save(env, this) {
+        Ok(()) => {
+            if !error.is_null() {
+                env.mem.write(error, nil);
+            }
+            true
+        }
+        Err(e) => {
+            log!("Warning: couldn't save Core Data changes: {}", e);
+            if !error.is_null() {
+                let new_error = new_error(env, NSCocoaErrorDomain, NSPersistentStoreSaveError);
+                env.mem.write(error, new_error);
+            }
+            false
+        }
+    }
+}
+
+- (())rollback {
+    let host_object = env.objc.borrow_mut::<NSManagedObjectContextHostObject>(this);
+    let inserted = std::mem::take(&mut host_object.inserted);
+    let updated = std::mem::take(&mut host_object.updated);
+    let deleted = std::mem::take(&mut host_object.deleted);
+    for object in inserted {
+        unregister(env, this, object);
+    }
+    // Changed objects are reloaded from the store when they're next used.
+    for object in updated.into_iter().chain(deleted) {
+        clear_values(env, object);
+        let host_object = env.objc.borrow_mut::<NSManagedObjectHostObject>(object);
+        host_object.fault = true;
+        host_object.updated = false;
+        host_object.deleted = false;
+    }
+}
+
+- (())reset {
+    unregister_all(env, this);
+}
+
+@end
+
+};
+
+fn set_of(env: &mut Environment, objects: Vec<id>) -> id {
+    for &object in &objects {
+        retain(env, object);
+    }
+    let set = ns_set::from_vec(env, objects);
+    autorelease(env, set)
+}
+
+/// Remove an object from a context and release the context's reference.
+fn unregister(env: &mut Environment, context: id, object: id) {
+    let (model, entity) = entity_of(env, object);
+    let host_object = env.objc.borrow_mut::<NSManagedObjectHostObject>(object);
+    let key = (model.root_entity(entity), host_object.pk);
+    host_object.context = nil;
+    host_object.fault = false;
+    host_object.inserted = false;
+    host_object.updated = false;
+    host_object.deleted = false;
+    // Relationships could keep objects alive after the context forgets them.
+    clear_values(env, object);
+    let host_object = env
+        .objc
+        .borrow_mut::<NSManagedObjectContextHostObject>(context);
+    host_object.registered.remove(&key);
+    host_object.inserted.retain(|&other| other != object);
+    host_object.updated.retain(|&other| other != object);
+    host_object.deleted.retain(|&other| other != object);
+    release(env, object);
+}
+
+fn unregister_all(env: &mut Environment, context: id) {
+    let objects: Vec<id> = env
+        .objc
+        .borrow::<NSManagedObjectContextHostObject>(context)
+        .registered
+        .values()
+        .copied()
+        .collect();
+    // Keep them all alive until they're all unregistered, since they might
+    // reference each other.
+    for &object in &objects {
+        retain(env, object);
+    }
+    for &object in &objects {
+        unregister(env, context, object);
+    }
+    for object in objects {
+        release(env, object);
+    }
+}
+
+/// Record that an object has unsaved changes.
+pub(super) fn object_changed(env: &mut Environment, object: id) {
+    let host_object = env.objc.borrow_mut::<NSManagedObjectHostObject>(object);
+    let context = host_object.context;
+    if context == nil || host_object.inserted || host_object.deleted || host_object.updated {
+        return;
+    }
+    host_object.updated = true;
+    env.objc
+        .borrow_mut::<NSManagedObjectContextHostObject>(context)
+        .updated
+        .push(object);
+}
+
+/// Get the registered object for a primary key, creating a fault for it if
+/// needed.
+fn object_for_pk(env: &mut Environment, context: id, entity: usize, pk: i64) -> id {
+    let coordinator = env
+        .objc
+        .borrow::<NSManagedObjectContextHostObject>(context)
+        .coordinator;
+    let model: id = msg![env; coordinator managedObjectModel];
+    let host_object = env.objc.borrow::<NSManagedObjectModelHostObject>(model);
+    let key = (host_object.model.root_entity(entity), pk);
+    let entity_description = host_object.entities[entity];
+    let host_object = env.objc.borrow::<NSManagedObjectContextHostObject>(context);
+    if let Some(&object) = host_object.registered.get(&key) {
+        return object;
+    }
+
+    let object = ns_managed_object::new_fault(env, entity_description, context, pk);
+    env.objc
+        .borrow_mut::<NSManagedObjectContextHostObject>(context)
+        .registered
+        .insert(key, object);
+    object
+}
+
+/// Set an object's values from a row of the store.
+fn apply_row(env: &mut Environment, context: id, object: id, row: Row) {
+    let (model, entity) = entity_of(env, object);
+    let coordinator = env
+        .objc
+        .borrow::<NSManagedObjectContextHostObject>(context)
+        .coordinator;
+
+    let mut values = HashMap::new();
+    for (property, value) in row.values {
+        let value = if let Some(attribute) = model.attribute(entity, &property) {
+            object_from_sql(env, attribute.attribute_type, &value)
+        } else {
+            let relationship = model.relationship(entity, &property).unwrap();
+            let (Some(destination), SqlValue::Integer(pk)) =
+                (model.entity_index(&relationship.destination), value)
+            else {
+                continue;
+            };
+            let destination = with_store(env, coordinator, |store| {
+                store.entity_for_pk(destination, pk)
+            })
+            .unwrap()
+            .unwrap_or_else(|e| {
+                log!("Warning: Core Data fetch failed: {}", e);
+                None
+            });
+            let Some(destination) = destination else {
+                continue;
+            };
+            let destination = object_for_pk(env, context, destination, pk);
+            retain(env, destination)
+        };
+        if value != nil {
+            values.insert(property, value);
+        }
+    }
+
+    for relationship in model.relationships(entity) {
+        if !relationship.to_many || relationship.transient {
+            continue;
+        }
+        let destinations = with_store(env, coordinator, |store| {
+            store.fetch_to_many(entity, relationship, row.pk)
+        })
+        .unwrap()
+        .unwrap_or_else(|e| {
+            log!("Warning: Core Data fetch failed: {}", e);
+            Vec::new()
+        });
+        let set: id = msg_class![env; NSMutableSet alloc];
+        let set: id = msg![env; set init];
+        for (destination, pk) in destinations {
+            let destination = object_for_pk(env, context, destination, pk);
+            let () = msg![env; set addObject:destination];
+        }
+        values.insert(relationship.name.clone(), set);
+    }
+
+    clear_values(env, object);
+    let host_object = env.objc.borrow_mut::<NSManagedObjectHostObject>(object);
+    host_object.values = values;
+    host_object.fault = false;
+    let () = msg![env; object awakeFromFetch];
+}
+
+/// Load the values of a fault from the store.
+pub(super) fn fill_object(env: &mut Environment, object: id) {
+    let &NSManagedObjectHostObject { context, pk, .. } = env.objc.borrow(object);
+    let (_, entity) = entity_of(env, object);
+    let coordinator = env
+        .objc
+        .borrow::<NSManagedObjectContextHostObject>(context)
+        .coordinator;
+    let row = with_store(env, coordinator, |store| store.fetch_one(entity, pk)).unwrap();
+    match row {
+        Ok(Some(row)) => apply_row(env, context, object, row),
+        Ok(None) => {
+            log!(
+                "Warning: Core Data object {:?} no longer exists in the store",
+                object
+            );
+            env.objc
+                .borrow_mut::<NSManagedObjectHostObject>(object)
+                .fault = false;
+        }
+        Err(e) => {
+            log!(
+                "Warning: couldn't load Core Data object {:?}: {}",
+                object,
+                e
+            );
+            env.objc
+                .borrow_mut::<NSManagedObjectHostObject>(object)
+                .fault = false;
+        }
+    }
+}
+
+fn delete_object(env: &mut Environment, context: id, object: id) {
+    let host_object = env.objc.borrow::<NSManagedObjectHostObject>(object);
+    if host_object.deleted || host_object.context != context {
+        return;
+    }
+    let inserted = host_object.inserted;
+    // Make sure the object stays alive until it's unregistered.
+    retain(env, object);
+
+    let (model, entity) = entity_of(env, object);
+    for relationship in model.relationships(entity) {
+        if relationship.delete_rule == DeleteRule::NoAction {
+            continue;
+        }
+        let value = primitive_value(env, object, &relationship.name);
+        let destinations: Vec<id> = if value == nil {
+            Vec::new()
+        } else if relationship.to_many {
+            let array: id = msg![env; value allObjects];
+            let count: NSUInteger = msg![env; array count];
+            (0..count)
+                .map(|i| msg![env; array objectAtIndex:i])
+                .collect()
+        } else {
+            vec![value]
+        };
+        for &destination in &destinations {
+            retain(env, destination);
+            unlink(env, object, &relationship.name, destination);
+        }
+        for destination in destinations {
+            if relationship.delete_rule == DeleteRule::Cascade {
+                delete_object(env, context, destination);
+            }
+            release(env, destination);
+        }
+    }
+
+    if inserted {
+        unregister(env, context, object);
+    } else {
+        let host_object = env.objc.borrow_mut::<NSManagedObjectHostObject>(object);
+        host_object.deleted = true;
+        host_object.updated = false;
+        let host_object = env
+            .objc
+            .borrow_mut::<NSManagedObjectContextHostObject>(context);
+        host_object.updated.retain(|&other| other != object);
+        host_object.deleted.push(object);
+    }
+    release(env, object);
+}
+
+/// Find the objects matching a fetch request, including unsaved changes.
+fn fetch(env: &mut Environment, context: id, request: id) -> Vec<id> {
+    let NSFetchRequestHostObject {
+        entity: entity_description,
+        entity_name,
+        predicate,
+        sort_descriptors,
+        fetch_limit,
+        fetch_offset,
+        includes_subentities,
+        ..
+    } = ns_fetch_request::settings(env, request);
+    let coordinator = env
+        .objc
+        .borrow::<NSManagedObjectContextHostObject>(context)
+        .coordinator;
+    let entity_description = if entity_description != nil {
+        entity_description
+    } else {
+        let model: id = msg![env; coordinator managedObjectModel];
+        let name = to_rust_string(env, entity_name);
+        let Some(entity) = ns_entity_description::entity_for_name(env, model, &name) else {
+            panic!("No entity named {:?} in the Core Data model", name);
+        };
+        entity
+    };
+    let (model, entity) = model_and_index(env, entity_description);
+    let entities = if includes_subentities {
+        model.entity_and_subentities(entity)
+    } else {
+        vec![entity]
+    };
+
+    let rows = with_store(env, coordinator, |store| store.fetch(&entities))
+        .unwrap_or(Ok(Vec::new()))
+        .unwrap_or_else(|e| {
+            log!("Warning: Core Data fetch failed: {}", e);
+            Vec::new()
+        });
+    let mut objects = Vec::new();
+    for row in rows {
+        let object = object_for_pk(env, context, row.entity, row.pk);
+        let host_object = env.objc.borrow::<NSManagedObjectHostObject>(object);
+        if host_object.deleted {
+            continue;
+        }
+        if host_object.fault {
+            apply_row(env, context, object, row);
+        }
+        objects.push(object);
+    }
+    let inserted = env
+        .objc
+        .borrow::<NSManagedObjectContextHostObject>(context)
+        .inserted
+        .clone();
+    for object in inserted {
+        let (_, object_entity) = entity_of(env, object);
+        if entities.contains(&object_entity) {
+            objects.push(object);
+        }
+    }
+
+    if predicate != nil {
+        objects.retain(|&object| ns_predicate::evaluate(env, predicate, object));
+    }
+    if sort_descriptors != nil {
+        // Vec::sort_by can't be used because the comparison needs the
+        // environment, so this is an insertion sort. It's stable, like Apple's.
+        for i in 1..objects.len() {
+            let mut j = i;
+            while j > 0
+                && ns_sort_descriptor::compare_with_descriptors(
+                    env,
+                    sort_descriptors,
+                    objects[j - 1],
+                    objects[j],
+                )
+                .is_gt()
+            {
+                objects.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+    }
+    let objects = objects.into_iter().skip(fetch_offset as usize);
+    if fetch_limit != 0 {
+        objects.take(fetch_limit as usize).collect()
+    } else {
+        objects.collect()
+    }
+}
+
+fn save(env: &mut Environment, context: id) -> Result<(), String> {
+    let host_object = env.objc.borrow::<NSManagedObjectContextHostObject>(context);
+    let coordinator = host_object.coordinator;
+    let inserted = host_object.inserted.clone();
+    let updated = host_object.updated.clone();
+    let deleted = host_object.deleted.clone();
+    if inserted.is_empty() && updated.is_empty() && deleted.is_empty() {
+        return Ok(());
+    }
+
+    for &object in inserted.iter().chain(&updated) {
+        let () = msg![env; object willSave];
+    }
+
+    let mut changes = Changes::default();
+    for (objects, rows) in [
+        (&inserted, &mut changes.inserted),
+        (&updated, &mut changes.updated),
+    ] {
+        for &object in objects {
+            let (row, to_many) = row_for_object(env, object);
+            rows.push(row);
+            changes.to_many.extend(to_many);
+        }
+    }
+    for &object in &deleted {
+        let (_, entity) = entity_of(env, object);
+        let pk = env.objc.borrow::<NSManagedObjectHostObject>(object).pk;
+        changes.deleted.push((entity, pk));
+    }
+
+    with_store(env, coordinator, |store| store.save(changes)).ok_or("no persistent store")??;
+
+    for &object in inserted.iter().chain(&updated) {
+        let host_object = env.objc.borrow_mut::<NSManagedObjectHostObject>(object);
+        host_object.temporary = false;
+        host_object.inserted = false;
+        host_object.updated = false;
+    }
+    let host_object = env
+        .objc
+        .borrow_mut::<NSManagedObjectContextHostObject>(context);
+    host_object.inserted.clear();
+    host_object.updated.clear();
+    for object in deleted {
+        unregister(env, context, object);
+    }
+    for &object in inserted.iter().chain(&updated) {
+        let () = msg![env; object didSave];
+    }
+    Ok(())
+}
+
+/// Get the values to save for an object, and the contents of its to-many
+/// relationships.
+fn row_for_object(env: &mut Environment, object: id) -> (Row, Vec<ToManyContents>) {
+    let (model, entity) = entity_of(env, object);
+    let pk = env.objc.borrow::<NSManagedObjectHostObject>(object).pk;
+    let mut values = Vec::new();
+    for attribute in model.attributes(entity) {
+        if attribute.transient {
+            continue;
+        }
+        let value = primitive_value(env, object, &attribute.name);
+        let value = object_to_sql(env, attribute.attribute_type, value);
+        values.push((attribute.name.clone(), value));
+    }
+
+    let mut to_many = Vec::new();
+    for relationship in model.relationships(entity) {
+        if relationship.transient {
+            continue;
+        }
+        let value = primitive_value(env, object, &relationship.name);
+        if !relationship.to_many {
+            let value = match destination_pk(env, value) {
+                Some(pk) => SqlValue::Integer(pk),
+                None => SqlValue::Null,
+            };
+            values.push((relationship.name.clone(), value));
+            continue;
+        }
+        let array: id = msg![env; value allObjects];
+        let count: NSUInteger = msg![env; array count];
+        let mut pks = Vec::new();
+        for i in 0..count {
+            let destination: id = msg![env; array objectAtIndex:i];
+            if let Some(pk) = destination_pk(env, destination) {
+                pks.push(pk);
+            }
+        }
+        to_many.push((entity, pk, relationship.name.clone(), pks));
+    }
+    (Row { entity, pk, values }, to_many)
+}
+
+fn destination_pk(env: &mut Environment, destination: id) -> Option<i64> {
+    if destination == nil {
+        return None;
+    }
+    let host_object = env.objc.borrow::<NSManagedObjectHostObject>(destination);
+    if host_object.context == nil || host_object.deleted {
+        log!(
+            "Warning: Core Data relationship to {:?}, which isn't in a context, won't be saved",
+            destination
+        );
+        return None;
+    }
+    Some(host_object.pk)
+}
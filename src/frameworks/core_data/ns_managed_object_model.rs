@@ -0,0 +1,202 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSManagedObjectModel`.
+
+use super::model::Model;
+use super::ns_entity_description::NSEntityDescriptionHostObject;
+use super::ns_managed_object;
+use crate::frameworks::foundation::ns_string::{from_rust_string, to_rust_string};
+use crate::frameworks::foundation::{ns_array, ns_dictionary, ns_url, NSUInteger};
+use crate::fs::GuestPath;
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::rc::Rc;
+
+pub(super) struct NSManagedObjectModelHostObject {
+    pub(super) model: Rc<Model>,
+    /// `NSEntityDescription*` for each entity, in the same order as the
+    /// model's entities. Strong references.
+    pub(super) entities: Vec<id>,
+}
+impl HostObject for NSManagedObjectModelHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSManagedObjectModel: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSManagedObjectModelHostObject {
+        model: Default::default(),
+        entities: Vec::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)mergedModelFromBundles:(id)bundles { // NSArray* of NSBundle*
+    let bundles: Vec<id> = if bundles == nil {
+        vec![msg_class![env; NSBundle mainBundle]]
+    } else {
+        let count: NSUInteger = msg![env; bundles count];
+        (0..count).map(|i| msg![env; bundles objectAtIndex:i]).collect()
+    };
+
+    let mut models = Vec::new();
+    for bundle in bundles {
+        for path in model_paths_in_bundle(env, bundle) {
+            match load_model(env, &path) {
+                Ok(model) => models.push(model),
+                Err(e) => log!("Warning: couldn't load Core Data model {:?}: {}", path, e),
+            }
+        }
+    }
+    let model = match Model::merge(models) {
+        Ok(model) => model,
+        Err(e) => {
+            log!("Warning: couldn't merge Core Data models: {}", e);
+            return nil;
+        }
+    };
+    let new: id = msg![env; this alloc];
+    set_model(env, new, model);
+    autorelease(env, new)
+}
+
+- (id)initWithContentsOfURL:(id)url { // NSURL*
+    let path = ns_url::to_rust_path(env, url);
+    match load_model(env, path.as_str()) {
+        Ok(model) => {
+            set_model(env, this, model);
+            this
+        }
+        Err(e) => {
+            log!("Warning: couldn't load Core Data model {:?}: {}", path, e);
+            release(env, this);
+            nil
+        }
+    }
+}
+
+- (())dealloc {
+    let entities = std::mem::take(
+        &mut env.objc.borrow_mut::<NSManagedObjectModelHostObject>(this).entities
+    );
+    for entity in entities {
+        release(env, entity);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    // Models can't be changed once they're used, so this is fine.
+    retain(env, this)
+}
+
+- (id)entities {
+    let entities = env.objc.borrow::<NSManagedObjectModelHostObject>(this).entities.clone();
+    for &entity in &entities {
+        retain(env, entity);
+    }
+    let array = ns_array::from_vec(env, entities);
+    autorelease(env, array)
+}
+
+- (id)entitiesByName {
+    let entities = env.objc.borrow::<NSManagedObjectModelHostObject>(this).entities.clone();
+    let mut keys_and_objects = Vec::new();
+    for entity in entities {
+        let name: id = msg![env; entity name];
+        keys_and_objects.push((name, entity));
+    }
+    let dict = ns_dictionary::dict_from_keys_and_objects(env, &keys_and_objects);
+    autorelease(env, dict)
+}
+
+@end
+
+};
+
+/// Set up a newly-allocated model object with its entities.
+fn set_model(env: &mut Environment, object: id, model: Model) {
+    let model = Rc::new(model);
+    let mut entities = Vec::new();
+    for index in 0..model.entities.len() {
+        let entity: id = msg_class![env; NSEntityDescription alloc];
+        *env.objc.borrow_mut(entity) = NSEntityDescriptionHostObject {
+            model: model.clone(),
+            index,
+            managed_object_model: object,
+        };
+        entities.push(entity);
+        ns_managed_object::add_accessors(env, &model, index);
+    }
+    *env.objc.borrow_mut(object) = NSManagedObjectModelHostObject { model, entities };
+}
+
+/// Find the compiled models in a bundle's resources: `.mom` files, and the
+/// current version in each `.momd` directory.
+fn model_paths_in_bundle(env: &mut Environment, bundle: id) -> Vec<String> {
+    let mut paths = Vec::new();
+    for extension in ["mom", "momd"] {
+        let extension = from_rust_string(env, extension.to_string());
+        let found: id = msg![env; bundle pathsForResourcesOfType:extension inDirectory:nil];
+        release(env, extension);
+        let count: NSUInteger = msg![env; found count];
+        for i in 0..count {
+            let path: id = msg![env; found objectAtIndex:i];
+            paths.push(to_rust_string(env, path).into_owned());
+        }
+    }
+    paths
+}
+
+/// Load a `.mom` file, or the current version in a `.momd` directory.
+fn load_model(env: &mut Environment, path: &str) -> Result<Model, String> {
+    if env.fs.is_file(GuestPath::new(path)) {
+        let data = env
+            .fs
+            .read(GuestPath::new(path))
+            .map_err(|_| "couldn't read file".to_string())?;
+        return Model::from_bytes(&data);
+    }
+
+    let version_info = GuestPath::new(path).join("VersionInfo.plist");
+    let current_version = env
+        .fs
+        .read(&version_info)
+        .ok()
+        .and_then(|data| plist::Value::from_reader(std::io::Cursor::new(data)).ok())
+        .and_then(|plist| {
+            plist
+                .as_dictionary()?
+                .get("NSManagedObjectModel_CurrentVersionName")?
+                .as_string()
+                .map(String::from)
+        });
+    let file_name = match current_version {
+        Some(version) => format!("{}.mom", version),
+        None => {
+            // Without version information, guess the only or last version.
+            let mut names = env
+                .fs
+                .read_dir_names(GuestPath::new(path))
+                .map_err(|_| "couldn't read directory".to_string())?;
+            names.retain(|name| name.ends_with(".mom"));
+            names.sort();
+            names.pop().ok_or("no model in directory")?
+        }
+    };
+    let data = env
+        .fs
+        .read(GuestPath::new(path).join(file_name))
+        .map_err(|_| "couldn't read current model version".to_string())?;
+    Model::from_bytes(&data)
+}
@@ -0,0 +1,264 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSPersistentStoreCoordinator` and `NSPersistentStore`.
+//!
+//! A coordinator can have several stores, but only the first one is used.
+
+use super::core_data_errors::{NSPersistentStoreInvalidTypeError, NSPersistentStoreOpenError};
+use super::ns_managed_object_model::NSManagedObjectModelHostObject;
+use super::store::Store;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::ns_error::{new_error, NSCocoaErrorDomain};
+use crate::frameworks::foundation::ns_string::{get_static_str, to_rust_string};
+use crate::frameworks::foundation::{ns_array, ns_url, NSInteger};
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+use libsqlite3_sys as ffi;
+use std::ffi::CString;
+
+pub const NSSQLiteStoreType: &str = "SQLite";
+pub const NSBinaryStoreType: &str = "Binary";
+pub const NSInMemoryStoreType: &str = "InMemory";
+
+pub const NSReadOnlyPersistentStoreOption: &str = "NSReadOnlyPersistentStoreOption";
+pub const NSMigratePersistentStoresAutomaticallyOption: &str =
+    "NSMigratePersistentStoresAutomaticallyOption";
+pub const NSInferMappingModelAutomaticallyOption: &str = "NSInferMappingModelAutomaticallyOption";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSSQLiteStoreType",
+        HostConstant::NSString(NSSQLiteStoreType),
+    ),
+    (
+        "_NSBinaryStoreType",
+        HostConstant::NSString(NSBinaryStoreType),
+    ),
+    (
+        "_NSInMemoryStoreType",
+        HostConstant::NSString(NSInMemoryStoreType),
+    ),
+    (
+        "_NSReadOnlyPersistentStoreOption",
+        HostConstant::NSString(NSReadOnlyPersistentStoreOption),
+    ),
+    (
+        "_NSMigratePersistentStoresAutomaticallyOption",
+        HostConstant::NSString(NSMigratePersistentStoresAutomaticallyOption),
+    ),
+    (
+        "_NSInferMappingModelAutomaticallyOption",
+        HostConstant::NSString(NSInferMappingModelAutomaticallyOption),
+    ),
+];
+
+struct NSPersistentStoreCoordinatorHostObject {
+    /// `NSManagedObjectModel*`, strong reference.
+    model: id,
+    /// `NSPersistentStore*`s, strong references.
+    stores: Vec<id>,
+}
+impl HostObject for NSPersistentStoreCoordinatorHostObject {}
+
+struct NSPersistentStoreHostObject {
+    store: Option<Store>,
+    /// `NSString*`, strong reference.
+    store_type: id,
+    /// `NSURL*`, strong reference.
+    url: id,
+}
+impl HostObject for NSPersistentStoreHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSPersistentStoreCoordinator: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSPersistentStoreCoordinatorHostObject {
+        model: nil,
+        stores: Vec::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithManagedObjectModel:(id)model { // NSManagedObjectModel*
+    retain(env, model);
+    env.objc.borrow_mut::<NSPersistentStoreCoordinatorHostObject>(this).model = model;
+    this
+}
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<NSPersistentStoreCoordinatorHostObject>(this);
+    let model = host_object.model;
+    let stores = std::mem::take(&mut host_object.stores);
+    release(env, model);
+    for store in stores {
+        release(env, store);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)managedObjectModel {
+    env.objc.borrow::<NSPersistentStoreCoordinatorHostObject>(this).model
+}
+
+- (id)persistentStores {
+    let stores = env.objc.borrow::<NSPersistentStoreCoordinatorHostObject>(this).stores.clone();
+    for &store in &stores {
+        retain(env, store);
+    }
+    let array = ns_array::from_vec(env, stores);
+    autorelease(env, array)
+}
+
+- (id)addPersistentStoreWithType:(id)store_type // NSString*
+                   configuration:(id)configuration // NSString*
+                             URL:(id)url // NSURL*
+                         options:(id)options // NSDictionary*
+                           error:(MutPtr<id>)error { // NSError**
+    if configuration != nil {
+        log!("Warning: ignoring Core Data model configuration");
+    }
+    let type_string = to_rust_string(env, store_type);
+    let (filename, mut flags) = match &*type_string {
+        NSSQLiteStoreType | NSBinaryStoreType if url != nil => {
+            if type_string == NSBinaryStoreType {
+                log!("Warning: using an SQLite store instead of a binary store");
+            }
+            let path = ns_url::to_rust_path(env, url);
+            let filename = CString::new(path.as_str()).unwrap();
+            (filename, ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE)
+        }
+        NSInMemoryStoreType => (
+            CString::new(":memory:").unwrap(),
+            ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE,
+        ),
+        _ => {
+            log!("Warning: unsupported Core Data store type {:?}", type_string);
+            return fail(env, error, NSPersistentStoreInvalidTypeError);
+        }
+    };
+
+    if options != nil {
+        let key = get_static_str(env, NSReadOnlyPersistentStoreOption);
+        let read_only: id = msg![env; options objectForKey:key];
+        if read_only != nil && msg![env; read_only boolValue] {
+            flags = ffi::SQLITE_OPEN_READONLY;
+        }
+    }
+
+    let store = crate::sqlite3::host_path_for_open(env, &filename, flags)
+        .map_err(|()| "couldn't open file".to_string())
+        .and_then(|(host_path, flags)| {
+            let model = env.objc.borrow::<NSPersistentStoreCoordinatorHostObject>(this).model;
+            let model = env.objc.borrow::<NSManagedObjectModelHostObject>(model).model.clone();
+            Store::open(&host_path, flags, model)
+        });
+    let store = match store {
+        Ok(store) => store,
+        Err(e) => {
+            log!("Warning: couldn't open Core Data store {:?}: {}", filename, e);
+            return fail(env, error, NSPersistentStoreOpenError);
+        }
+    };
+
+    let store_type: id = msg![env; store_type copy];
+    retain(env, url);
+    let object: id = msg_class![env; NSPersistentStore alloc];
+    *env.objc.borrow_mut(object) = NSPersistentStoreHostObject {
+        store: Some(store),
+        store_type,
+        url,
+    };
+    let host_object = env.objc.borrow_mut::<NSPersistentStoreCoordinatorHostObject>(this);
+    if !host_object.stores.is_empty() {
+        log!("Warning: only the first Core Data store will be used");
+    }
+    host_object.stores.push(object);
+    if !error.is_null() {
+        env.mem.write(error, nil);
+    }
+    object
+}
+
+- (bool)removePersistentStore:(id)store // NSPersistentStore*
+                        error:(MutPtr<id>)error { // NSError**
+    let host_object = env.objc.borrow_mut::<NSPersistentStoreCoordinatorHostObject>(this);
+    let Some(index) = host_object.stores.iter().position(|&other| other == store) else {
+        return false;
+    };
+    host_object.stores.remove(index);
+    release(env, store);
+    if !error.is_null() {
+        env.mem.write(error, nil);
+    }
+    true
+}
+
+@end
+
+@implementation NSPersistentStore: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSPersistentStoreHostObject {
+        store: None,
+        store_type: nil,
+        url: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<NSPersistentStoreHostObject>(this);
+    host_object.store = None;
+    let store_type = host_object.store_type;
+    let url = host_object.url;
+    release(env, store_type);
+    release(env, url);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)type {
+    env.objc.borrow::<NSPersistentStoreHostObject>(this).store_type
+}
+- (id)URL {
+    env.objc.borrow::<NSPersistentStoreHostObject>(this).url
+}
+
+@end
+
+};
+
+fn fail(env: &mut Environment, error: MutPtr<id>, code: NSInteger) -> id {
+    if !error.is_null() {
+        let new_error = new_error(env, NSCocoaErrorDomain, code);
+        env.mem.write(error, new_error);
+    }
+    nil
+}
+
+/// Do something with a coordinator's store. Returns [None] if there's no
+/// store.
+pub(super) fn with_store<F, R>(env: &mut Environment, coordinator: id, f: F) -> Option<R>
+where
+    F: FnOnce(&mut Store) -> R,
+{
+    if coordinator == nil {
+        return None;
+    }
+    let &store = env
+        .objc
+        .borrow::<NSPersistentStoreCoordinatorHostObject>(coordinator)
+        .stores
+        .first()?;
+    let host_object = env.objc.borrow_mut::<NSPersistentStoreHostObject>(store);
+    Some(f(host_object.store.as_mut().unwrap()))
+}
@@ -0,0 +1,887 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The SQLite persistent store, using the SQLite library bundled for
+//! [crate::sqlite3].
+//!
+//! The schema is modeled on the one Apple's implementation uses, so that a
+//! database looks familiar when inspected, but it's not meant to be
+//! interchangeable with Apple's:
+//! - Each entity hierarchy has a table named after its root entity, e.g.
+//!   `ZNOTE`, with the columns `Z_PK` (primary key), `Z_ENT` (entity number)
+//!   and `Z_OPT` (number of times the row was saved), and a column for each
+//!   attribute and to-one relationship of each entity in the hierarchy, e.g.
+//!   `ZTITLE`. To-one relationships store the primary key of the destination.
+//! - To-many relationships whose inverse is to-one are stored by the inverse.
+//!   Other to-many relationships each have a join table, e.g. `Z_1TAGS`.
+//! - `Z_PRIMARYKEY` maps entity names to entity numbers and records the
+//!   highest primary key used for each hierarchy.
+
+use super::model::{AttributeType, Model, Relationship};
+use libsqlite3_sys as ffi;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+/// Owned handle for a host SQLite database.
+struct Database {
+    raw: *mut ffi::sqlite3,
+}
+
+impl Database {
+    fn open(filename: &CStr, flags: c_int) -> Result<Database, String> {
+        let mut raw = std::ptr::null_mut();
+        let result =
+            unsafe { ffi::sqlite3_open_v2(filename.as_ptr(), &mut raw, flags, std::ptr::null()) };
+        let database = Database { raw };
+        if result != ffi::SQLITE_OK {
+            return Err(database.error());
+        }
+        Ok(database)
+    }
+
+    fn error(&self) -> String {
+        if self.raw.is_null() {
+            return "out of memory".to_string();
+        }
+        unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.raw)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Run a single statement and return the rows it produces.
+    fn query(&self, sql: &str, params: &[SqlValue]) -> Result<Vec<Vec<SqlValue>>, String> {
+        let sql_c = CString::new(sql).unwrap();
+        let mut stmt = std::ptr::null_mut();
+        let result = unsafe {
+            ffi::sqlite3_prepare_v2(
+                self.raw,
+                sql_c.as_ptr(),
+                -1,
+                &mut stmt,
+                std::ptr::null_mut(),
+            )
+        };
+        if result != ffi::SQLITE_OK {
+            return Err(format!("{} (in {:?})", self.error(), sql));
+        }
+
+        for (i, param) in params.iter().enumerate() {
+            let index = i as c_int + 1;
+            let result = unsafe {
+                match *param {
+                    SqlValue::Null => ffi::sqlite3_bind_null(stmt, index),
+                    SqlValue::Integer(value) => ffi::sqlite3_bind_int64(stmt, index, value),
+                    SqlValue::Real(value) => ffi::sqlite3_bind_double(stmt, index, value),
+                    SqlValue::Text(ref value) => ffi::sqlite3_bind_text(
+                        stmt,
+                        index,
+                        value.as_ptr() as *const c_char,
+                        value.len() as c_int,
+                        ffi::SQLITE_TRANSIENT(),
+                    ),
+                    SqlValue::Blob(ref value) => ffi::sqlite3_bind_blob(
+                        stmt,
+                        index,
+                        value.as_ptr().cast(),
+                        value.len() as c_int,
+                        ffi::SQLITE_TRANSIENT(),
+                    ),
+                }
+            };
+            assert_eq!(result, ffi::SQLITE_OK);
+        }
+
+        let column_count = unsafe { ffi::sqlite3_column_count(stmt) };
+        let mut rows = Vec::new();
+        let result = loop {
+            let result = unsafe { ffi::sqlite3_step(stmt) };
+            if result != ffi::SQLITE_ROW {
+                break result;
+            }
+            rows.push(
+                (0..column_count)
+                    .map(|column| unsafe { column_value(stmt, column) })
+                    .collect(),
+            );
+        };
+        let error = (result != ffi::SQLITE_DONE).then(|| self.error());
+        unsafe { ffi::sqlite3_finalize(stmt) };
+        match error {
+            Some(error) => Err(format!("{} (in {:?})", error, sql)),
+            None => Ok(rows),
+        }
+    }
+
+    fn execute(&self, sql: &str, params: &[SqlValue]) -> Result<(), String> {
+        self.query(sql, params).map(|_| ())
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        unsafe { ffi::sqlite3_close_v2(self.raw) };
+    }
+}
+
+/// # Safety
+///
+/// `stmt` must be a valid statement that has a current row.
+unsafe fn column_value(stmt: *mut ffi::sqlite3_stmt, column: c_int) -> SqlValue {
+    match ffi::sqlite3_column_type(stmt, column) {
+        ffi::SQLITE_INTEGER => SqlValue::Integer(ffi::sqlite3_column_int64(stmt, column)),
+        ffi::SQLITE_FLOAT => SqlValue::Real(ffi::sqlite3_column_double(stmt, column)),
+        ffi::SQLITE_TEXT => {
+            let text = ffi::sqlite3_column_text(stmt, column);
+            let len = ffi::sqlite3_column_bytes(stmt, column) as usize;
+            if len == 0 {
+                SqlValue::Text(String::new())
+            } else {
+                let bytes = std::slice::from_raw_parts(text, len);
+                SqlValue::Text(String::from_utf8_lossy(bytes).into_owned())
+            }
+        }
+        ffi::SQLITE_BLOB => {
+            let blob = ffi::sqlite3_column_blob(stmt, column);
+            let len = ffi::sqlite3_column_bytes(stmt, column) as usize;
+            if len == 0 {
+                SqlValue::Blob(Vec::new())
+            } else {
+                SqlValue::Blob(std::slice::from_raw_parts(blob.cast::<u8>(), len).to_vec())
+            }
+        }
+        _ => SqlValue::Null,
+    }
+}
+
+/// How a to-many relationship is stored in a join table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinTable {
+    pub table: String,
+    /// Column with the primary key of the object that has the relationship.
+    pub source_column: String,
+    /// Column with the primary key of the destination object.
+    pub destination_column: String,
+    /// Whether the table belongs to this side of a many-to-many relationship.
+    /// Only the owning side writes to it.
+    pub owned: bool,
+}
+
+/// Values for a row of an entity table. The values are keyed by property name
+/// and are only for attributes and to-one relationships.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub entity: usize,
+    pub pk: i64,
+    pub values: Vec<(String, SqlValue)>,
+}
+
+/// New contents of a to-many relationship stored in a join table: entity,
+/// primary key, relationship name and destination primary keys.
+pub type ToManyContents = (usize, i64, String, Vec<i64>);
+
+/// A set of changes to save in one transaction.
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub inserted: Vec<Row>,
+    pub updated: Vec<Row>,
+    /// Entity and primary key of each deleted object.
+    pub deleted: Vec<(usize, i64)>,
+    pub to_many: Vec<ToManyContents>,
+}
+
+pub struct Store {
+    db: Database,
+    model: Rc<Model>,
+    /// `Z_ENT` number of each entity, indexed like the model's entities.
+    entity_numbers: Vec<i64>,
+    /// Highest primary key used for each hierarchy, keyed by root entity.
+    max_pks: HashMap<usize, i64>,
+}
+
+fn column_name(property: &str) -> String {
+    format!("Z{}", property.to_uppercase())
+}
+
+fn column_type(attribute_type: AttributeType) -> &'static str {
+    match attribute_type {
+        AttributeType::Integer16
+        | AttributeType::Integer32
+        | AttributeType::Integer64
+        | AttributeType::Boolean => "INTEGER",
+        AttributeType::Decimal => "DECIMAL",
+        AttributeType::Double | AttributeType::Float => "FLOAT",
+        AttributeType::String => "VARCHAR",
+        AttributeType::Date => "TIMESTAMP",
+        AttributeType::Binary | AttributeType::Transformable | AttributeType::Undefined => "BLOB",
+    }
+}
+
+impl Store {
+    /// Open or create a store. `filename` is a host path, or `:memory:`.
+    pub fn open(filename: &CStr, flags: c_int, model: Rc<Model>) -> Result<Store, String> {
+        let db = Database::open(filename, flags)?;
+        let mut store = Store {
+            db,
+            model,
+            entity_numbers: Vec::new(),
+            max_pks: HashMap::new(),
+        };
+        store.create_schema()?;
+        Ok(store)
+    }
+
+    fn create_schema(&mut self) -> Result<(), String> {
+        let model = self.model.clone();
+        self.db.execute(
+            "CREATE TABLE IF NOT EXISTS Z_PRIMARYKEY \
+             (Z_ENT INTEGER PRIMARY KEY, Z_NAME VARCHAR, Z_SUPER INTEGER, Z_MAX INTEGER)",
+            &[],
+        )?;
+
+        // Keep the numbers of entities the store already knows about.
+        let mut known = HashMap::new();
+        for row in self
+            .db
+            .query("SELECT Z_ENT, Z_NAME, Z_MAX FROM Z_PRIMARYKEY", &[])?
+        {
+            if let [SqlValue::Integer(number), SqlValue::Text(name), max] = &row[..] {
+                let max = match *max {
+                    SqlValue::Integer(max) => max,
+                    _ => 0,
+                };
+                known.insert(name.clone(), (*number, max));
+            }
+        }
+        let mut next_number = known.values().map(|&(number, _)| number).max().unwrap_or(0) + 1;
+        self.entity_numbers = model
+            .entities
+            .iter()
+            .map(|entity| match known.get(&entity.name) {
+                Some(&(number, _)) => number,
+                None => {
+                    next_number += 1;
+                    next_number - 1
+                }
+            })
+            .collect();
+        for (index, entity) in model.entities.iter().enumerate() {
+            if known.contains_key(&entity.name) {
+                continue;
+            }
+            let superentity = model
+                .superentity(index)
+                .map_or(0, |superentity| self.entity_numbers[superentity]);
+            self.db.execute(
+                "INSERT INTO Z_PRIMARYKEY (Z_ENT, Z_NAME, Z_SUPER, Z_MAX) VALUES (?, ?, ?, 0)",
+                &[
+                    SqlValue::Integer(self.entity_numbers[index]),
+                    SqlValue::Text(entity.name.clone()),
+                    SqlValue::Integer(superentity),
+                ],
+            )?;
+        }
+
+        for root in 0..model.entities.len() {
+            if model.root_entity(root) != root {
+                continue;
+            }
+            let table = self.table_name(root);
+            self.db.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} \
+                     (Z_PK INTEGER PRIMARY KEY, Z_ENT INTEGER, Z_OPT INTEGER)",
+                    table
+                ),
+                &[],
+            )?;
+            // Columns for properties added in a later version of the model.
+            let existing: Vec<String> = self
+                .db
+                .query(&format!("PRAGMA table_info({})", table), &[])?
+                .into_iter()
+                .filter_map(|row| match row.get(1) {
+                    Some(SqlValue::Text(name)) => Some(name.clone()),
+                    _ => None,
+                })
+                .collect();
+            for (column, column_type) in self.columns(root) {
+                if !existing.contains(&column) {
+                    self.db.execute(
+                        &format!(
+                            "ALTER TABLE {} ADD COLUMN {} {}",
+                            table, column, column_type
+                        ),
+                        &[],
+                    )?;
+                }
+            }
+
+            let max_pk = match self
+                .db
+                .query(&format!("SELECT MAX(Z_PK) FROM {}", table), &[])?
+                .first()
+                .and_then(|row| row.first())
+            {
+                Some(&SqlValue::Integer(max)) => max,
+                _ => 0,
+            };
+            let recorded_max = known
+                .get(&model.entities[root].name)
+                .map_or(0, |&(_, max)| max);
+            self.max_pks.insert(root, max_pk.max(recorded_max));
+
+            for entity in model.entity_and_subentities(root) {
+                for relationship in &model.entities[entity].relationships {
+                    let Some(join) = self.join_table(entity, relationship) else {
+                        continue;
+                    };
+                    if !join.owned {
+                        continue;
+                    }
+                    self.db.execute(
+                        &format!(
+                            "CREATE TABLE IF NOT EXISTS {} ({} INTEGER, {} INTEGER, \
+                             PRIMARY KEY ({}, {}))",
+                            join.table,
+                            join.source_column,
+                            join.destination_column,
+                            join.source_column,
+                            join.destination_column
+                        ),
+                        &[],
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn table_name(&self, entity: usize) -> String {
+        let root = self.model.root_entity(entity);
+        column_name(&self.model.entities[root].name)
+    }
+
+    /// Names and types of the columns for the properties of a hierarchy.
+    fn columns(&self, root: usize) -> Vec<(String, &'static str)> {
+        let mut columns: Vec<(String, &'static str)> = Vec::new();
+        for entity in self.model.entity_and_subentities(root) {
+            let entity = &self.model.entities[entity];
+            let attributes = entity
+                .attributes
+                .iter()
+                .filter(|attribute| !attribute.transient)
+                .map(|attribute| (&attribute.name, column_type(attribute.attribute_type)));
+            let relationships = entity
+                .relationships
+                .iter()
+                .filter(|relationship| !relationship.to_many && !relationship.transient)
+                .map(|relationship| (&relationship.name, "INTEGER"));
+            for (name, column_type) in attributes.chain(relationships) {
+                let column = column_name(name);
+                if !columns.iter().any(|(existing, _)| *existing == column) {
+                    columns.push((column, column_type));
+                }
+            }
+        }
+        columns
+    }
+
+    /// Names of the stored properties of an entity that have a column.
+    fn column_properties(&self, entity: usize) -> Vec<String> {
+        let attributes = self
+            .model
+            .attributes(entity)
+            .into_iter()
+            .filter(|attribute| !attribute.transient)
+            .map(|attribute| attribute.name.clone());
+        let relationships = self
+            .model
+            .relationships(entity)
+            .into_iter()
+            .filter(|relationship| !relationship.to_many && !relationship.transient)
+            .map(|relationship| relationship.name.clone());
+        attributes.chain(relationships).collect()
+    }
+
+    /// Find the join table for a to-many relationship, if it has one.
+    pub fn join_table(&self, entity: usize, relationship: &Relationship) -> Option<JoinTable> {
+        if !relationship.to_many || relationship.transient {
+            return None;
+        }
+        let (destination, inverse) = self.model.destination(relationship);
+        let destination = destination?;
+        if inverse.map_or(false, |inverse| !inverse.to_many) {
+            return None;
+        }
+        let root = self.model.root_entity(entity);
+        let destination_root = self.model.root_entity(destination);
+        let this_side = (&self.model.entities[root].name, &relationship.name);
+        let (owned, other_side) = match inverse {
+            Some(inverse) => {
+                let other_side = (&self.model.entities[destination_root].name, &inverse.name);
+                (this_side <= other_side, Some(inverse.name.as_str()))
+            }
+            None => (true, None),
+        };
+
+        // Names from the point of view of the owning side.
+        let (owner, owner_relationship, other, other_relationship) = if owned {
+            (
+                root,
+                relationship.name.as_str(),
+                destination_root,
+                other_side,
+            )
+        } else {
+            (
+                destination_root,
+                other_side.unwrap(),
+                root,
+                Some(relationship.name.as_str()),
+            )
+        };
+        let owner_number = self.entity_numbers[owner];
+        let other_number = self.entity_numbers[other];
+        let table = format!("Z_{}{}", owner_number, owner_relationship.to_uppercase());
+        let owner_column = format!(
+            "Z_{}{}",
+            owner_number,
+            other_relationship.unwrap_or("INVERSE").to_uppercase()
+        );
+        let mut other_column = format!("Z_{}{}", other_number, owner_relationship.to_uppercase());
+        if other_column == owner_column {
+            other_column.push('1');
+        }
+
+        Some(if owned {
+            JoinTable {
+                table,
+                source_column: owner_column,
+                destination_column: other_column,
+                owned,
+            }
+        } else {
+            JoinTable {
+                table,
+                source_column: other_column,
+                destination_column: owner_column,
+                owned,
+            }
+        })
+    }
+
+    fn entity_for_number(&self, number: i64) -> Option<usize> {
+        self.entity_numbers.iter().position(|&n| n == number)
+    }
+
+    /// Reserve a primary key for a new object.
+    pub fn allocate_pk(&mut self, entity: usize) -> i64 {
+        let root = self.model.root_entity(entity);
+        let max = self.max_pks.entry(root).or_insert(0);
+        *max += 1;
+        *max
+    }
+
+    fn select(
+        &self,
+        entity: usize,
+        condition: &str,
+        params: &[SqlValue],
+    ) -> Result<Vec<Row>, String> {
+        let root = self.model.root_entity(entity);
+        let columns = self.columns(root);
+        let mut sql = "SELECT Z_PK, Z_ENT".to_string();
+        for (column, _) in &columns {
+            sql.push_str(", ");
+            sql.push_str(column);
+        }
+        sql.push_str(&format!(
+            " FROM {} WHERE {}",
+            self.table_name(root),
+            condition
+        ));
+
+        let mut rows = Vec::new();
+        for row in self.db.query(&sql, params)? {
+            let mut row = row.into_iter();
+            let (Some(SqlValue::Integer(pk)), Some(SqlValue::Integer(number))) =
+                (row.next(), row.next())
+            else {
+                continue;
+            };
+            let Some(entity) = self.entity_for_number(number) else {
+                log!(
+                    "Warning: Core Data row {} has unknown entity {}",
+                    pk,
+                    number
+                );
+                continue;
+            };
+            let mut by_column: HashMap<String, SqlValue> = columns
+                .iter()
+                .map(|(column, _)| column.clone())
+                .zip(row)
+                .collect();
+            let values = self
+                .column_properties(entity)
+                .into_iter()
+                .map(|property| {
+                    let value = by_column
+                        .remove(&column_name(&property))
+                        .unwrap_or(SqlValue::Null);
+                    (property, value)
+                })
+                .collect();
+            rows.push(Row { entity, pk, values });
+        }
+        Ok(rows)
+    }
+
+    /// Get the rows for all objects of some entities. The entities must be in
+    /// the same hierarchy.
+    pub fn fetch(&self, entities: &[usize]) -> Result<Vec<Row>, String> {
+        let Some(&first) = entities.first() else {
+            return Ok(Vec::new());
+        };
+        let numbers: Vec<String> = entities
+            .iter()
+            .map(|&entity| self.entity_numbers[entity].to_string())
+            .collect();
+        self.select(
+            first,
+            &format!("Z_ENT IN ({}) ORDER BY Z_PK", numbers.join(", ")),
+            &[],
+        )
+    }
+
+    /// Get the row for a single object in an entity's hierarchy.
+    pub fn fetch_one(&self, entity: usize, pk: i64) -> Result<Option<Row>, String> {
+        Ok(self
+            .select(entity, "Z_PK = ?", &[SqlValue::Integer(pk)])?
+            .pop())
+    }
+
+    /// Get the entities and primary keys of the objects in a to-many
+    /// relationship.
+    pub fn fetch_to_many(
+        &self,
+        entity: usize,
+        relationship: &Relationship,
+        pk: i64,
+    ) -> Result<Vec<(usize, i64)>, String> {
+        let Some(destination) = self.model.entity_index(&relationship.destination) else {
+            return Ok(Vec::new());
+        };
+        let destination_table = self.table_name(destination);
+        let sql = if let Some(join) = self.join_table(entity, relationship) {
+            format!(
+                "SELECT d.Z_PK, d.Z_ENT FROM {} d JOIN {} j ON d.Z_PK = j.{} \
+                 WHERE j.{} = ? ORDER BY d.Z_PK",
+                destination_table, join.table, join.destination_column, join.source_column
+            )
+        } else {
+            let Some(inverse) = &relationship.inverse else {
+                return Ok(Vec::new());
+            };
+            format!(
+                "SELECT Z_PK, Z_ENT FROM {} WHERE {} = ? ORDER BY Z_PK",
+                destination_table,
+                column_name(inverse)
+            )
+        };
+        Ok(self
+            .db
+            .query(&sql, &[SqlValue::Integer(pk)])?
+            .into_iter()
+            .filter_map(|row| match row[..] {
+                [SqlValue::Integer(pk), SqlValue::Integer(number)] => {
+                    Some((self.entity_for_number(number)?, pk))
+                }
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Get the entity of an object, given its primary key and an entity in
+    /// its hierarchy.
+    pub fn entity_for_pk(&self, entity: usize, pk: i64) -> Result<Option<usize>, String> {
+        let sql = format!(
+            "SELECT Z_ENT FROM {} WHERE Z_PK = ?",
+            self.table_name(entity)
+        );
+        Ok(
+            match self
+                .db
+                .query(&sql, &[SqlValue::Integer(pk)])?
+                .first()
+                .map(|row| &row[..])
+            {
+                Some(&[SqlValue::Integer(number)]) => self.entity_for_number(number),
+                _ => None,
+            },
+        )
+    }
+
+    pub fn save(&mut self, changes: Changes) -> Result<(), String> {
+        self.db.execute("BEGIN", &[])?;
+        match self.save_in_transaction(changes) {
+            Ok(()) => self.db.execute("COMMIT", &[]),
+            Err(error) => {
+                let _ = self.db.execute("ROLLBACK", &[]);
+                Err(error)
+            }
+        }
+    }
+
+    fn save_in_transaction(&mut self, changes: Changes) -> Result<(), String> {
+        for row in changes.inserted {
+            let mut columns = vec!["Z_PK", "Z_ENT", "Z_OPT"]
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>();
+            let mut params = vec![
+                SqlValue::Integer(row.pk),
+                SqlValue::Integer(self.entity_numbers[row.entity]),
+                SqlValue::Integer(1),
+            ];
+            for (property, value) in row.values {
+                columns.push(column_name(&property));
+                params.push(value);
+            }
+            let placeholders = vec!["?"; columns.len()].join(", ");
+            self.db.execute(
+                &format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    self.table_name(row.entity),
+                    columns.join(", "),
+                    placeholders
+                ),
+                &params,
+            )?;
+        }
+
+        for row in changes.updated {
+            let mut sql = format!(
+                "UPDATE {} SET Z_OPT = Z_OPT + 1",
+                self.table_name(row.entity)
+            );
+            let mut params = Vec::new();
+            for (property, value) in row.values {
+                sql.push_str(&format!(", {} = ?", column_name(&property)));
+                params.push(value);
+            }
+            sql.push_str(" WHERE Z_PK = ?");
+            params.push(SqlValue::Integer(row.pk));
+            self.db.execute(&sql, &params)?;
+        }
+
+        let model = self.model.clone();
+        for (entity, pk, relationship, destinations) in changes.to_many {
+            let Some(relationship) = model.relationship(entity, &relationship) else {
+                continue;
+            };
+            let Some(join) = self.join_table(entity, relationship) else {
+                continue;
+            };
+            // The owning side has the same contents, so there's nothing to do
+            // for the other side.
+            if !join.owned {
+                continue;
+            }
+            self.db.execute(
+                &format!(
+                    "DELETE FROM {} WHERE {} = ?",
+                    join.table, join.source_column
+                ),
+                &[SqlValue::Integer(pk)],
+            )?;
+            for destination in destinations {
+                self.db.execute(
+                    &format!(
+                        "INSERT OR IGNORE INTO {} ({}, {}) VALUES (?, ?)",
+                        join.table, join.source_column, join.destination_column
+                    ),
+                    &[SqlValue::Integer(pk), SqlValue::Integer(destination)],
+                )?;
+            }
+        }
+
+        for (entity, pk) in changes.deleted {
+            self.db.execute(
+                &format!("DELETE FROM {} WHERE Z_PK = ?", self.table_name(entity)),
+                &[SqlValue::Integer(pk)],
+            )?;
+            for relationship in model.relationships(entity) {
+                let Some(join) = self.join_table(entity, relationship) else {
+                    continue;
+                };
+                self.db.execute(
+                    &format!(
+                        "DELETE FROM {} WHERE {} = ?",
+                        join.table, join.source_column
+                    ),
+                    &[SqlValue::Integer(pk)],
+                )?;
+            }
+        }
+
+        for (&root, &max) in &self.max_pks {
+            self.db.execute(
+                "UPDATE Z_PRIMARYKEY SET Z_MAX = ? WHERE Z_ENT = ?",
+                &[
+                    SqlValue::Integer(max),
+                    SqlValue::Integer(self.entity_numbers[root]),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::model::{Attribute, DeleteRule, Entity};
+    use super::*;
+
+    fn test_model() -> Model {
+        let attribute = |name: &str, attribute_type| Attribute {
+            name: name.to_string(),
+            attribute_type,
+            transient: false,
+            default_value: None,
+        };
+        let relationship = |name: &str, destination: &str, inverse: &str, to_many| Relationship {
+            name: name.to_string(),
+            destination: destination.to_string(),
+            inverse: Some(inverse.to_string()),
+            to_many,
+            delete_rule: DeleteRule::Nullify,
+            transient: false,
+        };
+        Model {
+            entities: vec![
+                Entity {
+                    name: "Note".to_string(),
+                    class_name: "NSManagedObject".to_string(),
+                    superentity: None,
+                    is_abstract: false,
+                    attributes: vec![attribute("title", AttributeType::String)],
+                    relationships: vec![
+                        relationship("folder", "Folder", "notes", false),
+                        relationship("tags", "Tag", "notes", true),
+                    ],
+                },
+                Entity {
+                    name: "Folder".to_string(),
+                    class_name: "NSManagedObject".to_string(),
+                    superentity: None,
+                    is_abstract: false,
+                    attributes: vec![],
+                    relationships: vec![relationship("notes", "Note", "folder", true)],
+                },
+                Entity {
+                    name: "Tag".to_string(),
+                    class_name: "NSManagedObject".to_string(),
+                    superentity: None,
+                    is_abstract: false,
+                    attributes: vec![],
+                    relationships: vec![relationship("notes", "Note", "tags", true)],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let model = Rc::new(Model::merge(vec![test_model()]).unwrap());
+        let note = model.entity_index("Note").unwrap();
+        let folder = model.entity_index("Folder").unwrap();
+        let tag = model.entity_index("Tag").unwrap();
+        let flags = ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE;
+        let mut store = Store::open(c":memory:", flags, model.clone()).unwrap();
+
+        let folder_pk = store.allocate_pk(folder);
+        let note_pk = store.allocate_pk(note);
+        let tag_pk = store.allocate_pk(tag);
+        store
+            .save(Changes {
+                inserted: vec![
+                    Row {
+                        entity: folder,
+                        pk: folder_pk,
+                        values: vec![],
+                    },
+                    Row {
+                        entity: note,
+                        pk: note_pk,
+                        values: vec![
+                            ("folder".to_string(), SqlValue::Integer(folder_pk)),
+                            ("title".to_string(), SqlValue::Text("Hello".to_string())),
+                        ],
+                    },
+                    Row {
+                        entity: tag,
+                        pk: tag_pk,
+                        values: vec![],
+                    },
+                ],
+                to_many: vec![
+                    (note, note_pk, "tags".to_string(), vec![tag_pk]),
+                    (tag, tag_pk, "notes".to_string(), vec![note_pk]),
+                ],
+                ..Default::default()
+            })
+            .unwrap();
+
+        let rows = store.fetch(&[note]).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].pk, note_pk);
+        assert!(rows[0]
+            .values
+            .contains(&("title".to_string(), SqlValue::Text("Hello".to_string()))));
+
+        let notes = model.relationship(folder, "notes").unwrap();
+        assert_eq!(store.join_table(folder, notes), None);
+        assert_eq!(
+            store.fetch_to_many(folder, notes, folder_pk).unwrap(),
+            [(note, note_pk)]
+        );
+        let tags = model.relationship(note, "tags").unwrap();
+        assert!(store.join_table(note, tags).unwrap().owned);
+        assert_eq!(
+            store.fetch_to_many(note, tags, note_pk).unwrap(),
+            [(tag, tag_pk)]
+        );
+        let tag_notes = model.relationship(tag, "notes").unwrap();
+        assert!(!store.join_table(tag, tag_notes).unwrap().owned);
+        assert_eq!(
+            store.fetch_to_many(tag, tag_notes, tag_pk).unwrap(),
+            [(note, note_pk)]
+        );
+        assert_eq!(store.entity_for_pk(note, note_pk).unwrap(), Some(note));
+
+        store
+            .save(Changes {
+                deleted: vec![(note, note_pk)],
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(store.fetch_one(note, note_pk).unwrap().is_none());
+        assert!(store
+            .fetch_to_many(tag, tag_notes, tag_pk)
+            .unwrap()
+            .is_empty());
+    }
+}
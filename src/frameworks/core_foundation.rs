@@ -17,12 +17,62 @@
 //! - Apple's [Memory Management Programming Guide for Core Foundation](https://developer.apple.com/library/archive/documentation/CoreFoundation/Conceptual/CFMemoryMgmt/CFMemoryMgmt.html)
 
 pub mod cf_allocator;
+pub mod cf_bag;
+pub mod cf_binary_heap;
 pub mod cf_bundle;
+pub mod cf_collection_callbacks;
+pub mod cf_preferences;
 pub mod cf_run_loop;
+pub mod cf_socket;
+pub mod cf_stream;
 pub mod cf_string;
+pub mod cf_tree;
 pub mod cf_type;
 pub mod cf_url;
+pub mod cf_uuid;
+
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
+use crate::mem::SafeRead;
 
 pub use cf_type::{CFRelease, CFRetain, CFTypeRef};
 
+#[derive(Default)]
+pub struct State {
+    cf_socket: cf_socket::State,
+}
+
 pub type CFIndex = i32;
+pub type CFOptionFlags = u32;
+/// Same as `NSTimeInterval`.
+pub type CFTimeInterval = f64;
+pub type CFHashCode = u32;
+
+pub type CFComparisonResult = CFIndex;
+pub const kCFCompareLessThan: CFComparisonResult = -1;
+pub const kCFCompareEqualTo: CFComparisonResult = 0;
+#[allow(dead_code)]
+pub const kCFCompareGreaterThan: CFComparisonResult = 1;
+
+/// `CFRange`, like `NSRange` but with signed fields.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(C, packed)]
+pub struct CFRange {
+    pub location: CFIndex,
+    pub length: CFIndex,
+}
+unsafe impl SafeRead for CFRange {}
+impl_GuestRet_for_large_struct!(CFRange);
+impl GuestArg for CFRange {
+    const REG_COUNT: usize = 2;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        CFRange {
+            location: GuestArg::from_regs(&regs[0..1]),
+            length: GuestArg::from_regs(&regs[1..2]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.location.to_regs(&mut regs[0..1]);
+        self.length.to_regs(&mut regs[1..2]);
+    }
+}
@@ -0,0 +1,294 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFBag` and `CFMutableBag`.
+//!
+//! A bag is like a set, but the same value can be in it several times. There's
+//! no Foundation equivalent except `NSCountedSet`, which can only hold objects.
+
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::cf_collection_callbacks::{
+    write_copy_string_bag_callbacks, write_type_bag_callbacks, CFBagCallBacks, ValueCallbacks,
+};
+use super::{CFHashCode, CFIndex, CFTypeRef};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::mem::{ConstPtr, ConstVoidPtr, MutPtr, MutVoidPtr};
+use crate::objc::{objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub type CFBagRef = CFTypeRef;
+pub type CFMutableBagRef = CFTypeRef;
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCFTypeBagCallBacks",
+        HostConstant::Custom(write_type_bag_callbacks),
+    ),
+    (
+        "_kCFCopyStringBagCallBacks",
+        HostConstant::Custom(write_copy_string_bag_callbacks),
+    ),
+];
+
+struct Entry {
+    value: ConstVoidPtr,
+    hash: CFHashCode,
+    count: CFIndex,
+}
+
+struct CFBagHostObject {
+    callbacks: ValueCallbacks,
+    /// Distinct values in the order they were first added. Each one is
+    /// retained once, regardless of its count.
+    entries: Vec<Entry>,
+}
+impl HostObject for CFBagHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CFBag is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not exposed.
+@implementation _touchHLE_CFBag: NSObject
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<CFBagHostObject>(this);
+    let callbacks = host_object.callbacks;
+    let entries = std::mem::take(&mut host_object.entries);
+    for entry in entries {
+        callbacks.release(env, entry.value);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+fn new_bag(env: &mut Environment, callbacks: ValueCallbacks) -> CFMutableBagRef {
+    let host_object = Box::new(CFBagHostObject {
+        callbacks,
+        entries: Vec::new(),
+    });
+    let class = env.objc.get_known_class("_touchHLE_CFBag", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+/// Find the index of the entry for a value, if it's in the bag.
+fn find(env: &mut Environment, bag: CFBagRef, value: ConstVoidPtr) -> Option<usize> {
+    let callbacks = env.objc.borrow::<CFBagHostObject>(bag).callbacks;
+    let hash = callbacks.hash(env, value);
+    let candidates: Vec<(usize, ConstVoidPtr)> = env
+        .objc
+        .borrow::<CFBagHostObject>(bag)
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.hash == hash)
+        .map(|(idx, entry)| (idx, entry.value))
+        .collect();
+    candidates
+        .into_iter()
+        .find(|&(_, candidate)| callbacks.equal(env, candidate, value))
+        .map(|(idx, _)| idx)
+}
+
+/// Values in the bag, each repeated as many times as it occurs.
+fn values(env: &mut Environment, bag: CFBagRef) -> Vec<ConstVoidPtr> {
+    env.objc
+        .borrow::<CFBagHostObject>(bag)
+        .entries
+        .iter()
+        .flat_map(|entry| (0..entry.count).map(|_| entry.value))
+        .collect()
+}
+
+fn CFBagCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    values: ConstPtr<ConstVoidPtr>,
+    num_values: CFIndex,
+    callbacks: ConstPtr<CFBagCallBacks>,
+) -> CFBagRef {
+    let bag = CFBagCreateMutable(env, allocator, 0, callbacks);
+    for i in 0..num_values.try_into().unwrap() {
+        let value = env.mem.read(values + i);
+        CFBagAddValue(env, bag, value);
+    }
+    bag
+}
+
+fn CFBagCreateMutable(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    _capacity: CFIndex,
+    callbacks: ConstPtr<CFBagCallBacks>,
+) -> CFMutableBagRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let callbacks = ValueCallbacks::from_bag_callbacks(env, callbacks);
+    new_bag(env, callbacks)
+}
+
+fn CFBagCreateCopy(env: &mut Environment, allocator: CFAllocatorRef, bag: CFBagRef) -> CFBagRef {
+    CFBagCreateMutableCopy(env, allocator, 0, bag)
+}
+
+fn CFBagCreateMutableCopy(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    _capacity: CFIndex,
+    bag: CFBagRef,
+) -> CFMutableBagRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let callbacks = env.objc.borrow::<CFBagHostObject>(bag).callbacks;
+    let new = new_bag(env, callbacks);
+    for value in values(env, bag) {
+        CFBagAddValue(env, new, value);
+    }
+    new
+}
+
+fn CFBagGetCount(env: &mut Environment, bag: CFBagRef) -> CFIndex {
+    env.objc
+        .borrow::<CFBagHostObject>(bag)
+        .entries
+        .iter()
+        .map(|entry| entry.count)
+        .sum()
+}
+
+fn CFBagGetCountOfValue(env: &mut Environment, bag: CFBagRef, value: ConstVoidPtr) -> CFIndex {
+    match find(env, bag, value) {
+        Some(idx) => env.objc.borrow::<CFBagHostObject>(bag).entries[idx].count,
+        None => 0,
+    }
+}
+
+fn CFBagContainsValue(env: &mut Environment, bag: CFBagRef, value: ConstVoidPtr) -> bool {
+    find(env, bag, value).is_some()
+}
+
+fn CFBagGetValue(env: &mut Environment, bag: CFBagRef, value: ConstVoidPtr) -> ConstVoidPtr {
+    match find(env, bag, value) {
+        Some(idx) => env.objc.borrow::<CFBagHostObject>(bag).entries[idx].value,
+        None => ConstVoidPtr::null(),
+    }
+}
+
+fn CFBagGetValueIfPresent(
+    env: &mut Environment,
+    bag: CFBagRef,
+    candidate: ConstVoidPtr,
+    value: MutPtr<ConstVoidPtr>,
+) -> bool {
+    let Some(idx) = find(env, bag, candidate) else {
+        return false;
+    };
+    if !value.is_null() {
+        let found = env.objc.borrow::<CFBagHostObject>(bag).entries[idx].value;
+        env.mem.write(value, found);
+    }
+    true
+}
+
+fn CFBagGetValues(env: &mut Environment, bag: CFBagRef, out_values: MutPtr<ConstVoidPtr>) {
+    for (i, value) in values(env, bag).into_iter().enumerate() {
+        env.mem.write(out_values + i.try_into().unwrap(), value);
+    }
+}
+
+fn CFBagApplyFunction(
+    env: &mut Environment,
+    bag: CFBagRef,
+    applier: GuestFunction, // CFBagApplierFunction
+    context: MutVoidPtr,
+) {
+    for value in values(env, bag) {
+        let () = applier.call_from_host(env, (value, context));
+    }
+}
+
+fn CFBagAddValue(env: &mut Environment, bag: CFMutableBagRef, value: ConstVoidPtr) {
+    if let Some(idx) = find(env, bag, value) {
+        env.objc.borrow_mut::<CFBagHostObject>(bag).entries[idx].count += 1;
+        return;
+    }
+    let callbacks = env.objc.borrow::<CFBagHostObject>(bag).callbacks;
+    let hash = callbacks.hash(env, value);
+    let value = callbacks.retain(env, value);
+    env.objc
+        .borrow_mut::<CFBagHostObject>(bag)
+        .entries
+        .push(Entry {
+            value,
+            hash,
+            count: 1,
+        });
+}
+
+fn CFBagReplaceValue(env: &mut Environment, bag: CFMutableBagRef, value: ConstVoidPtr) {
+    if find(env, bag, value).is_some() {
+        CFBagSetValue(env, bag, value);
+    }
+}
+
+fn CFBagSetValue(env: &mut Environment, bag: CFMutableBagRef, value: ConstVoidPtr) {
+    let callbacks = env.objc.borrow::<CFBagHostObject>(bag).callbacks;
+    let Some(idx) = find(env, bag, value) else {
+        CFBagAddValue(env, bag, value);
+        return;
+    };
+    // Every occurrence is replaced by the new value.
+    let new_value = callbacks.retain(env, value);
+    let old_value = std::mem::replace(
+        &mut env.objc.borrow_mut::<CFBagHostObject>(bag).entries[idx].value,
+        new_value,
+    );
+    callbacks.release(env, old_value);
+}
+
+fn CFBagRemoveValue(env: &mut Environment, bag: CFMutableBagRef, value: ConstVoidPtr) {
+    let Some(idx) = find(env, bag, value) else {
+        return;
+    };
+    let host_object = env.objc.borrow_mut::<CFBagHostObject>(bag);
+    let callbacks = host_object.callbacks;
+    let entry = &mut host_object.entries[idx];
+    entry.count -= 1;
+    if entry.count == 0 {
+        let entry = host_object.entries.remove(idx);
+        callbacks.release(env, entry.value);
+    }
+}
+
+fn CFBagRemoveAllValues(env: &mut Environment, bag: CFMutableBagRef) {
+    let host_object = env.objc.borrow_mut::<CFBagHostObject>(bag);
+    let callbacks = host_object.callbacks;
+    let entries = std::mem::take(&mut host_object.entries);
+    for entry in entries {
+        callbacks.release(env, entry.value);
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFBagCreate(_, _, _, _)),
+    export_c_func!(CFBagCreateMutable(_, _, _)),
+    export_c_func!(CFBagCreateCopy(_, _)),
+    export_c_func!(CFBagCreateMutableCopy(_, _, _)),
+    export_c_func!(CFBagGetCount(_)),
+    export_c_func!(CFBagGetCountOfValue(_, _)),
+    export_c_func!(CFBagContainsValue(_, _)),
+    export_c_func!(CFBagGetValue(_, _)),
+    export_c_func!(CFBagGetValueIfPresent(_, _, _)),
+    export_c_func!(CFBagGetValues(_, _)),
+    export_c_func!(CFBagApplyFunction(_, _, _)),
+    export_c_func!(CFBagAddValue(_, _)),
+    export_c_func!(CFBagReplaceValue(_, _)),
+    export_c_func!(CFBagSetValue(_, _)),
+    export_c_func!(CFBagRemoveValue(_, _)),
+    export_c_func!(CFBagRemoveAllValues(_)),
+];
@@ -0,0 +1,320 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFBinaryHeap`, a priority queue.
+//!
+//! The minimum value according to the compare callback is at the top.
+
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::cf_collection_callbacks::{
+    write_string_binary_heap_callbacks, CFBinaryHeapCallBacks, ValueCallbacks,
+};
+use super::cf_run_loop::{release_info, retain_info};
+use super::{kCFCompareEqualTo, kCFCompareLessThan, CFIndex, CFTypeRef};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::mem::{ConstPtr, ConstVoidPtr, MutPtr, MutVoidPtr, Ptr, SafeRead};
+use crate::objc::{objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub type CFBinaryHeapRef = CFTypeRef;
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_kCFStringBinaryHeapCallBacks",
+    HostConstant::Custom(write_string_binary_heap_callbacks),
+)];
+
+#[allow(dead_code)]
+#[repr(C, packed)]
+pub struct CFBinaryHeapCompareContext {
+    version: CFIndex,
+    info: MutVoidPtr,
+    retain: GuestFunction,
+    release: GuestFunction,
+    copy_description: GuestFunction,
+}
+unsafe impl SafeRead for CFBinaryHeapCompareContext {}
+
+struct CFBinaryHeapHostObject {
+    callbacks: ValueCallbacks,
+    /// The `retain` and `release` callbacks and retained `info` from the
+    /// compare context, if there is one.
+    compare_context: Option<(GuestFunction, GuestFunction, MutVoidPtr)>,
+    /// Heap-ordered values, each retained.
+    values: Vec<ConstVoidPtr>,
+}
+impl HostObject for CFBinaryHeapHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CFBinaryHeap is a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// exposed.
+@implementation _touchHLE_CFBinaryHeap: NSObject
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<CFBinaryHeapHostObject>(this);
+    let callbacks = host_object.callbacks;
+    let context = host_object.compare_context.take();
+    let values = std::mem::take(&mut host_object.values);
+    for value in values {
+        callbacks.release(env, value);
+    }
+    if let Some((_, release, info)) = context {
+        release_info(env, release, info);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+fn is_less(
+    env: &mut Environment,
+    callbacks: &ValueCallbacks,
+    a: ConstVoidPtr,
+    b: ConstVoidPtr,
+) -> bool {
+    callbacks.compare(env, a, b) == kCFCompareLessThan
+}
+
+/// Restore the heap order after pushing a value onto the end.
+fn sift_up(env: &mut Environment, callbacks: &ValueCallbacks, values: &mut [ConstVoidPtr]) {
+    let mut idx = values.len() - 1;
+    while idx > 0 {
+        let parent = (idx - 1) / 2;
+        if !is_less(env, callbacks, values[idx], values[parent]) {
+            break;
+        }
+        values.swap(idx, parent);
+        idx = parent;
+    }
+}
+
+/// Restore the heap order after replacing the first value.
+fn sift_down(env: &mut Environment, callbacks: &ValueCallbacks, values: &mut [ConstVoidPtr]) {
+    let mut idx = 0;
+    loop {
+        let mut smallest = idx;
+        for child in [idx * 2 + 1, idx * 2 + 2] {
+            if child < values.len() && is_less(env, callbacks, values[child], values[smallest]) {
+                smallest = child;
+            }
+        }
+        if smallest == idx {
+            break;
+        }
+        values.swap(idx, smallest);
+        idx = smallest;
+    }
+}
+
+/// The values in sorted order.
+fn sorted_values(env: &mut Environment, heap: CFBinaryHeapRef) -> Vec<ConstVoidPtr> {
+    let host_object = env.objc.borrow::<CFBinaryHeapHostObject>(heap);
+    let callbacks = host_object.callbacks;
+    let mut values = host_object.values.clone();
+    values.sort_by(|&a, &b| callbacks.compare(env, a, b).cmp(&0));
+    values
+}
+
+/// Find the index of a value equal to `value` according to the compare
+/// callback.
+fn find(env: &mut Environment, heap: CFBinaryHeapRef, value: ConstVoidPtr) -> Option<usize> {
+    let host_object = env.objc.borrow::<CFBinaryHeapHostObject>(heap);
+    let callbacks = host_object.callbacks;
+    let values = host_object.values.clone();
+    values
+        .into_iter()
+        .position(|candidate| callbacks.compare(env, candidate, value) == kCFCompareEqualTo)
+}
+
+fn CFBinaryHeapCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    _capacity: CFIndex,
+    callbacks: ConstPtr<CFBinaryHeapCallBacks>,
+    compare_context: ConstPtr<CFBinaryHeapCompareContext>,
+) -> CFBinaryHeapRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let (info, compare_context) = if compare_context.is_null() {
+        (Ptr::null(), None)
+    } else {
+        let CFBinaryHeapCompareContext {
+            version,
+            info,
+            retain,
+            release,
+            ..
+        } = env.mem.read(compare_context);
+        assert!(version == 0);
+        let info = retain_info(env, retain, info);
+        (info, Some((retain, release, info)))
+    };
+    let callbacks = ValueCallbacks::from_binary_heap_callbacks(env, callbacks, info);
+    let host_object = Box::new(CFBinaryHeapHostObject {
+        callbacks,
+        compare_context,
+        values: Vec::new(),
+    });
+    let class = env
+        .objc
+        .get_known_class("_touchHLE_CFBinaryHeap", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+fn CFBinaryHeapCreateCopy(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    _capacity: CFIndex,
+    heap: CFBinaryHeapRef,
+) -> CFBinaryHeapRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let host_object = env.objc.borrow::<CFBinaryHeapHostObject>(heap);
+    let callbacks = host_object.callbacks;
+    let values = host_object.values.clone();
+    let compare_context = host_object.compare_context;
+    // The copy shares the compare context's info, so it needs its own
+    // reference to it.
+    if let Some((retain, _, info)) = compare_context {
+        retain_info(env, retain, info);
+    }
+    let values = values
+        .into_iter()
+        .map(|value| callbacks.retain(env, value))
+        .collect();
+    let host_object = Box::new(CFBinaryHeapHostObject {
+        callbacks,
+        compare_context,
+        values,
+    });
+    let class = env
+        .objc
+        .get_known_class("_touchHLE_CFBinaryHeap", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+fn CFBinaryHeapGetCount(env: &mut Environment, heap: CFBinaryHeapRef) -> CFIndex {
+    let count = env.objc.borrow::<CFBinaryHeapHostObject>(heap).values.len();
+    count.try_into().unwrap()
+}
+
+fn CFBinaryHeapGetCountOfValue(
+    env: &mut Environment,
+    heap: CFBinaryHeapRef,
+    value: ConstVoidPtr,
+) -> CFIndex {
+    let host_object = env.objc.borrow::<CFBinaryHeapHostObject>(heap);
+    let callbacks = host_object.callbacks;
+    let values = host_object.values.clone();
+    let count = values
+        .into_iter()
+        .filter(|&candidate| callbacks.compare(env, candidate, value) == kCFCompareEqualTo)
+        .count();
+    count.try_into().unwrap()
+}
+
+fn CFBinaryHeapContainsValue(
+    env: &mut Environment,
+    heap: CFBinaryHeapRef,
+    value: ConstVoidPtr,
+) -> bool {
+    find(env, heap, value).is_some()
+}
+
+fn CFBinaryHeapGetMinimum(env: &mut Environment, heap: CFBinaryHeapRef) -> ConstVoidPtr {
+    let values = &env.objc.borrow::<CFBinaryHeapHostObject>(heap).values;
+    values.first().copied().unwrap_or(Ptr::null())
+}
+
+fn CFBinaryHeapGetMinimumIfPresent(
+    env: &mut Environment,
+    heap: CFBinaryHeapRef,
+    value: MutPtr<ConstVoidPtr>,
+) -> bool {
+    let values = &env.objc.borrow::<CFBinaryHeapHostObject>(heap).values;
+    let Some(&minimum) = values.first() else {
+        return false;
+    };
+    if !value.is_null() {
+        env.mem.write(value, minimum);
+    }
+    true
+}
+
+fn CFBinaryHeapGetValues(
+    env: &mut Environment,
+    heap: CFBinaryHeapRef,
+    out_values: MutPtr<ConstVoidPtr>,
+) {
+    for (i, value) in sorted_values(env, heap).into_iter().enumerate() {
+        env.mem.write(out_values + i.try_into().unwrap(), value);
+    }
+}
+
+fn CFBinaryHeapApplyFunction(
+    env: &mut Environment,
+    heap: CFBinaryHeapRef,
+    applier: GuestFunction, // CFBinaryHeapApplierFunction
+    context: MutVoidPtr,
+) {
+    for value in sorted_values(env, heap) {
+        let () = applier.call_from_host(env, (value, context));
+    }
+}
+
+fn CFBinaryHeapAddValue(env: &mut Environment, heap: CFBinaryHeapRef, value: ConstVoidPtr) {
+    let callbacks = env.objc.borrow::<CFBinaryHeapHostObject>(heap).callbacks;
+    let value = callbacks.retain(env, value);
+    // The values are taken out of the heap while comparing, in case the
+    // compare callback looks at the heap.
+    let mut values =
+        std::mem::take(&mut env.objc.borrow_mut::<CFBinaryHeapHostObject>(heap).values);
+    values.push(value);
+    sift_up(env, &callbacks, &mut values);
+    env.objc.borrow_mut::<CFBinaryHeapHostObject>(heap).values = values;
+}
+
+fn CFBinaryHeapRemoveMinimumValue(env: &mut Environment, heap: CFBinaryHeapRef) {
+    let callbacks = env.objc.borrow::<CFBinaryHeapHostObject>(heap).callbacks;
+    let mut values =
+        std::mem::take(&mut env.objc.borrow_mut::<CFBinaryHeapHostObject>(heap).values);
+    if values.is_empty() {
+        return;
+    }
+    let minimum = values.swap_remove(0);
+    if !values.is_empty() {
+        sift_down(env, &callbacks, &mut values);
+    }
+    env.objc.borrow_mut::<CFBinaryHeapHostObject>(heap).values = values;
+    callbacks.release(env, minimum);
+}
+
+fn CFBinaryHeapRemoveAllValues(env: &mut Environment, heap: CFBinaryHeapRef) {
+    let host_object = env.objc.borrow_mut::<CFBinaryHeapHostObject>(heap);
+    let callbacks = host_object.callbacks;
+    let values = std::mem::take(&mut host_object.values);
+    for value in values {
+        callbacks.release(env, value);
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFBinaryHeapCreate(_, _, _, _)),
+    export_c_func!(CFBinaryHeapCreateCopy(_, _, _)),
+    export_c_func!(CFBinaryHeapGetCount(_)),
+    export_c_func!(CFBinaryHeapGetCountOfValue(_, _)),
+    export_c_func!(CFBinaryHeapContainsValue(_, _)),
+    export_c_func!(CFBinaryHeapGetMinimum(_)),
+    export_c_func!(CFBinaryHeapGetMinimumIfPresent(_, _)),
+    export_c_func!(CFBinaryHeapGetValues(_, _)),
+    export_c_func!(CFBinaryHeapApplyFunction(_, _, _)),
+    export_c_func!(CFBinaryHeapAddValue(_, _)),
+    export_c_func!(CFBinaryHeapRemoveMinimumValue(_)),
+    export_c_func!(CFBinaryHeapRemoveAllValues(_)),
+];
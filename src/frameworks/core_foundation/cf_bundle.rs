@@ -8,9 +8,12 @@
 //! This is not even toll-free bridged to `NSBundle` in Apple's implementation,
 //! but here it is the same type.
 
+use super::cf_string::CFStringRef;
 use super::cf_url::CFURLRef;
+use super::CFTypeRef;
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::objc::{msg, msg_class};
+use crate::frameworks::foundation::{ns_array, NSUInteger};
+use crate::objc::{id, msg, msg_class, retain};
 use crate::Environment;
 
 pub type CFBundleRef = super::CFTypeRef;
@@ -24,7 +27,66 @@ fn CFBundleCopyResourcesDirectoryURL(env: &mut Environment, bundle: CFBundleRef)
     msg![env; url copy]
 }
 
+fn CFBundleCopyBundleURL(env: &mut Environment, bundle: CFBundleRef) -> CFURLRef {
+    let url: CFURLRef = msg![env; bundle bundleURL];
+    msg![env; url copy]
+}
+
+fn CFBundleCopyResourceURL(
+    env: &mut Environment,
+    bundle: CFBundleRef,
+    resource_name: CFStringRef,
+    resource_type: CFStringRef,
+    sub_dir_name: CFStringRef,
+) -> CFURLRef {
+    let url: CFURLRef = msg![env; bundle URLForResource:resource_name
+                                          withExtension:resource_type
+                                           subdirectory:sub_dir_name];
+    retain(env, url)
+}
+
+fn CFBundleCopyResourcesOfType(
+    env: &mut Environment,
+    bundle: CFBundleRef,
+    resource_type: CFStringRef,
+    sub_dir_name: CFStringRef,
+) -> id {
+    let paths: id = msg![env; bundle pathsForResourcesOfType:resource_type
+                                                 inDirectory:sub_dir_name];
+    let count: NSUInteger = msg![env; paths count];
+    let urls = (0..count)
+        .map(|i| {
+            let path: id = msg![env; paths objectAtIndex:i];
+            let url: id = msg_class![env; NSURL alloc];
+            msg![env; url initFileURLWithPath:path]
+        })
+        .collect();
+    ns_array::from_vec(env, urls)
+}
+
+fn CFBundleGetInfoDictionary(env: &mut Environment, bundle: CFBundleRef) -> id {
+    msg![env; bundle infoDictionary]
+}
+
+fn CFBundleGetValueForInfoDictionaryKey(
+    env: &mut Environment,
+    bundle: CFBundleRef,
+    key: CFStringRef,
+) -> CFTypeRef {
+    msg![env; bundle objectForInfoDictionaryKey:key]
+}
+
+fn CFBundleGetIdentifier(env: &mut Environment, bundle: CFBundleRef) -> CFStringRef {
+    msg![env; bundle bundleIdentifier]
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFBundleGetMainBundle()),
     export_c_func!(CFBundleCopyResourcesDirectoryURL(_)),
+    export_c_func!(CFBundleCopyBundleURL(_)),
+    export_c_func!(CFBundleCopyResourceURL(_, _, _, _)),
+    export_c_func!(CFBundleCopyResourcesOfType(_, _, _)),
+    export_c_func!(CFBundleGetInfoDictionary(_)),
+    export_c_func!(CFBundleGetValueForInfoDictionaryKey(_, _)),
+    export_c_func!(CFBundleGetIdentifier(_)),
 ];
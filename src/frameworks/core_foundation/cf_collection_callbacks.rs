@@ -0,0 +1,296 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Value callbacks shared by the Core Foundation collection types, e.g.
+//! `CFBagCallBacks` and `CFBinaryHeapCallBacks`.
+//!
+//! Apple's predefined callbacks like `kCFTypeBagCallBacks` point to functions
+//! in Core Foundation. Ours instead contain special addresses that are
+//! recognized here and handled by host code, so they work even if the app
+//! copies the structure and replaces some of the callbacks. The guest can't
+//! call these addresses itself.
+
+use super::cf_allocator::kCFAllocatorDefault;
+use super::{CFComparisonResult, CFHashCode, CFIndex};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::mem::{ConstPtr, ConstVoidPtr, Mem, MutVoidPtr, Ptr, SafeRead};
+use crate::objc::{id, msg, release, retain};
+use crate::Environment;
+
+/// Base of the special addresses, which are not valid guest code addresses.
+const HOST_CALLBACK_BASE: u32 = 0xFFFF_F000;
+
+/// Host implementations of callbacks, see the module documentation.
+#[derive(Copy, Clone)]
+#[repr(u32)]
+enum HostCallback {
+    Retain = 1,
+    Release,
+    CopyDescription,
+    Equal,
+    Hash,
+    /// Retain callback that copies strings.
+    CopyString,
+    /// Compare callback for strings.
+    CompareStrings,
+}
+
+impl HostCallback {
+    fn to_guest(self) -> GuestFunction {
+        GuestFunction::from_addr_with_thumb_bit(HOST_CALLBACK_BASE + self as u32)
+    }
+
+    fn from_guest(function: GuestFunction) -> Option<HostCallback> {
+        use HostCallback::*;
+        let addr = function
+            .addr_with_thumb_bit()
+            .checked_sub(HOST_CALLBACK_BASE)?;
+        [
+            Retain,
+            Release,
+            CopyDescription,
+            Equal,
+            Hash,
+            CopyString,
+            CompareStrings,
+        ]
+        .into_iter()
+        .find(|&callback| callback as u32 == addr)
+    }
+}
+
+/// One of the callbacks in a callbacks structure.
+#[derive(Copy, Clone)]
+enum Callback {
+    /// `NULL`, so the default behavior is used.
+    None,
+    Host(HostCallback),
+    Guest(GuestFunction),
+}
+
+impl Callback {
+    fn new(function: GuestFunction) -> Callback {
+        if function.addr_with_thumb_bit() == 0 {
+            Callback::None
+        } else if let Some(host) = HostCallback::from_guest(function) {
+            Callback::Host(host)
+        } else {
+            Callback::Guest(function)
+        }
+    }
+}
+
+/// `CFBagCallBacks` and `CFSetCallBacks`.
+#[allow(dead_code)]
+#[repr(C, packed)]
+pub struct CFBagCallBacks {
+    version: CFIndex,
+    /// `const void *(*)(CFAllocatorRef, const void *)`
+    retain: GuestFunction,
+    /// `void (*)(CFAllocatorRef, const void *)`
+    release: GuestFunction,
+    /// `CFStringRef (*)(const void *)`
+    copy_description: GuestFunction,
+    /// `Boolean (*)(const void *, const void *)`
+    equal: GuestFunction,
+    /// `CFHashCode (*)(const void *)`
+    hash: GuestFunction,
+}
+unsafe impl SafeRead for CFBagCallBacks {}
+
+/// `CFBinaryHeapCallBacks`.
+#[allow(dead_code)]
+#[repr(C, packed)]
+pub struct CFBinaryHeapCallBacks {
+    version: CFIndex,
+    /// `const void *(*)(CFAllocatorRef, const void *)`
+    retain: GuestFunction,
+    /// `void (*)(CFAllocatorRef, const void *)`
+    release: GuestFunction,
+    /// `CFStringRef (*)(const void *)`
+    copy_description: GuestFunction,
+    /// `CFComparisonResult (*)(const void *, const void *, void *)`
+    compare: GuestFunction,
+}
+unsafe impl SafeRead for CFBinaryHeapCallBacks {}
+
+pub fn write_type_bag_callbacks(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(CFBagCallBacks {
+        version: 0,
+        retain: HostCallback::Retain.to_guest(),
+        release: HostCallback::Release.to_guest(),
+        copy_description: HostCallback::CopyDescription.to_guest(),
+        equal: HostCallback::Equal.to_guest(),
+        hash: HostCallback::Hash.to_guest(),
+    })
+    .cast()
+    .cast_const()
+}
+
+pub fn write_copy_string_bag_callbacks(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(CFBagCallBacks {
+        version: 0,
+        retain: HostCallback::CopyString.to_guest(),
+        release: HostCallback::Release.to_guest(),
+        copy_description: HostCallback::CopyDescription.to_guest(),
+        equal: HostCallback::Equal.to_guest(),
+        hash: HostCallback::Hash.to_guest(),
+    })
+    .cast()
+    .cast_const()
+}
+
+pub fn write_string_binary_heap_callbacks(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(CFBinaryHeapCallBacks {
+        version: 0,
+        retain: HostCallback::Retain.to_guest(),
+        release: HostCallback::Release.to_guest(),
+        copy_description: HostCallback::CopyDescription.to_guest(),
+        compare: HostCallback::CompareStrings.to_guest(),
+    })
+    .cast()
+    .cast_const()
+}
+
+/// Decoded callbacks for a collection's values.
+#[derive(Copy, Clone)]
+pub struct ValueCallbacks {
+    retain: Callback,
+    release: Callback,
+    equal: Callback,
+    hash: Callback,
+    compare: Callback,
+    /// Passed to the compare callback.
+    compare_info: MutVoidPtr,
+}
+
+impl ValueCallbacks {
+    /// Callbacks for a `NULL` callbacks pointer: values are not retained and
+    /// are compared by address.
+    fn none() -> ValueCallbacks {
+        ValueCallbacks {
+            retain: Callback::None,
+            release: Callback::None,
+            equal: Callback::None,
+            hash: Callback::None,
+            compare: Callback::None,
+            compare_info: Ptr::null(),
+        }
+    }
+
+    pub fn from_bag_callbacks(
+        env: &mut Environment,
+        callbacks: ConstPtr<CFBagCallBacks>,
+    ) -> ValueCallbacks {
+        if callbacks.is_null() {
+            return ValueCallbacks::none();
+        }
+        let CFBagCallBacks {
+            retain,
+            release,
+            equal,
+            hash,
+            ..
+        } = env.mem.read(callbacks);
+        ValueCallbacks {
+            retain: Callback::new(retain),
+            release: Callback::new(release),
+            equal: Callback::new(equal),
+            hash: Callback::new(hash),
+            ..ValueCallbacks::none()
+        }
+    }
+
+    pub fn from_binary_heap_callbacks(
+        env: &mut Environment,
+        callbacks: ConstPtr<CFBinaryHeapCallBacks>,
+        compare_info: MutVoidPtr,
+    ) -> ValueCallbacks {
+        if callbacks.is_null() {
+            return ValueCallbacks::none();
+        }
+        let CFBinaryHeapCallBacks {
+            retain,
+            release,
+            compare,
+            ..
+        } = env.mem.read(callbacks);
+        ValueCallbacks {
+            retain: Callback::new(retain),
+            release: Callback::new(release),
+            compare: Callback::new(compare),
+            compare_info,
+            ..ValueCallbacks::none()
+        }
+    }
+
+    /// Call the retain callback, if any. Returns the value to store, which
+    /// might be a copy.
+    pub fn retain(&self, env: &mut Environment, value: ConstVoidPtr) -> ConstVoidPtr {
+        match self.retain {
+            Callback::None => value,
+            Callback::Host(HostCallback::CopyString) => {
+                let string: id = value.cast_mut().cast();
+                let copy: id = msg![env; string copy];
+                copy.cast().cast_const()
+            }
+            Callback::Host(_) => retain(env, value.cast_mut().cast()).cast().cast_const(),
+            Callback::Guest(f) => f.call_from_host(env, (kCFAllocatorDefault, value)),
+        }
+    }
+
+    pub fn release(&self, env: &mut Environment, value: ConstVoidPtr) {
+        match self.release {
+            Callback::None => (),
+            Callback::Host(_) => release(env, value.cast_mut().cast()),
+            Callback::Guest(f) => {
+                let () = f.call_from_host(env, (kCFAllocatorDefault, value));
+            }
+        }
+    }
+
+    pub fn equal(&self, env: &mut Environment, a: ConstVoidPtr, b: ConstVoidPtr) -> bool {
+        if a == b {
+            return true;
+        }
+        match self.equal {
+            Callback::None => false,
+            Callback::Host(_) => {
+                let a: id = a.cast_mut().cast();
+                let b: id = b.cast_mut().cast();
+                msg![env; a isEqualTo:b]
+            }
+            Callback::Guest(f) => f.call_from_host(env, (a, b)),
+        }
+    }
+
+    pub fn hash(&self, env: &mut Environment, value: ConstVoidPtr) -> CFHashCode {
+        match self.hash {
+            Callback::None => value.to_bits(),
+            Callback::Host(_) => {
+                let value: id = value.cast_mut().cast();
+                msg![env; value hash]
+            }
+            Callback::Guest(f) => f.call_from_host(env, (value,)),
+        }
+    }
+
+    pub fn compare(
+        &self,
+        env: &mut Environment,
+        a: ConstVoidPtr,
+        b: ConstVoidPtr,
+    ) -> CFComparisonResult {
+        match self.compare {
+            Callback::None => a.to_bits().cmp(&b.to_bits()) as CFComparisonResult,
+            Callback::Host(_) => {
+                let a: id = a.cast_mut().cast();
+                let b: id = b.cast_mut().cast();
+                msg![env; a compare:b]
+            }
+            Callback::Guest(f) => f.call_from_host(env, (a, b, self.compare_info)),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFPreferences`.
+//!
+//! This uses the same store as `NSUserDefaults`, see
+//! [crate::frameworks::foundation::ns_user_defaults]. Only the current
+//! application's preferences for the current user are available.
+
+use super::{CFIndex, CFTypeRef};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::{ns_string, ns_user_defaults};
+use crate::mem::MutPtr;
+use crate::objc::{id, msg, nil, retain};
+use crate::Environment;
+
+pub type CFPropertyListRef = CFTypeRef;
+
+pub const kCFPreferencesAnyApplication: &str = "kCFPreferencesAnyApplication";
+pub const kCFPreferencesCurrentApplication: &str = "kCFPreferencesCurrentApplication";
+pub const kCFPreferencesAnyHost: &str = "kCFPreferencesAnyHost";
+pub const kCFPreferencesCurrentHost: &str = "kCFPreferencesCurrentHost";
+pub const kCFPreferencesAnyUser: &str = "kCFPreferencesAnyUser";
+pub const kCFPreferencesCurrentUser: &str = "kCFPreferencesCurrentUser";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCFPreferencesAnyApplication",
+        HostConstant::NSString(kCFPreferencesAnyApplication),
+    ),
+    (
+        "_kCFPreferencesCurrentApplication",
+        HostConstant::NSString(kCFPreferencesCurrentApplication),
+    ),
+    (
+        "_kCFPreferencesAnyHost",
+        HostConstant::NSString(kCFPreferencesAnyHost),
+    ),
+    (
+        "_kCFPreferencesCurrentHost",
+        HostConstant::NSString(kCFPreferencesCurrentHost),
+    ),
+    (
+        "_kCFPreferencesAnyUser",
+        HostConstant::NSString(kCFPreferencesAnyUser),
+    ),
+    (
+        "_kCFPreferencesCurrentUser",
+        HostConstant::NSString(kCFPreferencesCurrentUser),
+    ),
+];
+
+/// Check that an application ID refers to the current application. Other
+/// applications' preferences can't be accessed.
+fn is_current_application(env: &mut Environment, application_id: id) -> bool {
+    let application_id = ns_string::to_rust_string(env, application_id);
+    if application_id == kCFPreferencesCurrentApplication
+        || application_id == env.bundle.bundle_identifier()
+    {
+        true
+    } else {
+        log!(
+            "Warning: app tried to access preferences of application {:?}, ignoring",
+            application_id
+        );
+        false
+    }
+}
+
+/// Check that a user and host are ones the app's preferences are stored for.
+fn is_current_user_and_any_host(env: &mut Environment, user_name: id, host_name: id) -> bool {
+    let user_name = ns_string::to_rust_string(env, user_name);
+    let host_name = ns_string::to_rust_string(env, host_name);
+    if user_name == kCFPreferencesCurrentUser && host_name == kCFPreferencesAnyHost {
+        true
+    } else {
+        log!(
+            "TODO: preferences for user {:?} and host {:?}, ignoring",
+            user_name,
+            host_name
+        );
+        false
+    }
+}
+
+fn CFPreferencesCopyAppValue(
+    env: &mut Environment,
+    key: id,            // CFStringRef
+    application_id: id, // CFStringRef
+) -> CFPropertyListRef {
+    if !is_current_application(env, application_id) {
+        return nil;
+    }
+    let key = ns_string::to_rust_string(env, key);
+    let value = ns_user_defaults::get_value(env, &key);
+    retain(env, value)
+}
+
+fn CFPreferencesSetAppValue(
+    env: &mut Environment,
+    key: id, // CFStringRef
+    value: CFPropertyListRef,
+    application_id: id, // CFStringRef
+) {
+    if !is_current_application(env, application_id) {
+        return;
+    }
+    let key = ns_string::to_rust_string(env, key);
+    ns_user_defaults::set_value(env, &key, value);
+}
+
+fn CFPreferencesAppSynchronize(env: &mut Environment, application_id: id) -> bool {
+    is_current_application(env, application_id) && ns_user_defaults::synchronize(env)
+}
+
+/// Shared implementation of `CFPreferencesGetAppIntegerValue` and
+/// `CFPreferencesGetAppBooleanValue`.
+fn get_app_number(
+    env: &mut Environment,
+    key: id,                                     // CFStringRef
+    application_id: id,                          // CFStringRef
+    key_exists_and_has_valid_format: MutPtr<u8>, // Boolean*
+) -> Option<id> {
+    let number = if is_current_application(env, application_id) {
+        let key = ns_string::to_rust_string(env, key);
+        let value = ns_user_defaults::get_value(env, &key);
+        // TODO: strings like "YES" and "123" are also valid
+        let number_class = env.objc.get_known_class("NSNumber", &mut env.mem);
+        let is_number = value != nil && msg![env; value isKindOfClass:number_class];
+        is_number.then_some(value)
+    } else {
+        None
+    };
+    if !key_exists_and_has_valid_format.is_null() {
+        env.mem
+            .write(key_exists_and_has_valid_format, number.is_some().into());
+    }
+    number
+}
+
+fn CFPreferencesGetAppIntegerValue(
+    env: &mut Environment,
+    key: id,                                     // CFStringRef
+    application_id: id,                          // CFStringRef
+    key_exists_and_has_valid_format: MutPtr<u8>, // Boolean*
+) -> CFIndex {
+    match get_app_number(env, key, application_id, key_exists_and_has_valid_format) {
+        Some(number) => msg![env; number integerValue],
+        None => 0,
+    }
+}
+
+fn CFPreferencesGetAppBooleanValue(
+    env: &mut Environment,
+    key: id,                                     // CFStringRef
+    application_id: id,                          // CFStringRef
+    key_exists_and_has_valid_format: MutPtr<u8>, // Boolean*
+) -> bool {
+    match get_app_number(env, key, application_id, key_exists_and_has_valid_format) {
+        Some(number) => msg![env; number boolValue],
+        None => false,
+    }
+}
+
+fn CFPreferencesCopyValue(
+    env: &mut Environment,
+    key: id,            // CFStringRef
+    application_id: id, // CFStringRef
+    user_name: id,      // CFStringRef
+    host_name: id,      // CFStringRef
+) -> CFPropertyListRef {
+    if !is_current_user_and_any_host(env, user_name, host_name) {
+        return nil;
+    }
+    CFPreferencesCopyAppValue(env, key, application_id)
+}
+
+fn CFPreferencesSetValue(
+    env: &mut Environment,
+    key: id, // CFStringRef
+    value: CFPropertyListRef,
+    application_id: id, // CFStringRef
+    user_name: id,      // CFStringRef
+    host_name: id,      // CFStringRef
+) {
+    if !is_current_user_and_any_host(env, user_name, host_name) {
+        return;
+    }
+    CFPreferencesSetAppValue(env, key, value, application_id)
+}
+
+fn CFPreferencesSynchronize(
+    env: &mut Environment,
+    application_id: id, // CFStringRef
+    user_name: id,      // CFStringRef
+    host_name: id,      // CFStringRef
+) -> bool {
+    is_current_user_and_any_host(env, user_name, host_name)
+        && CFPreferencesAppSynchronize(env, application_id)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFPreferencesCopyAppValue(_, _)),
+    export_c_func!(CFPreferencesSetAppValue(_, _, _)),
+    export_c_func!(CFPreferencesAppSynchronize(_)),
+    export_c_func!(CFPreferencesGetAppIntegerValue(_, _, _)),
+    export_c_func!(CFPreferencesGetAppBooleanValue(_, _, _)),
+    export_c_func!(CFPreferencesCopyValue(_, _, _, _)),
+    export_c_func!(CFPreferencesSetValue(_, _, _, _, _)),
+    export_c_func!(CFPreferencesSynchronize(_, _, _)),
+];
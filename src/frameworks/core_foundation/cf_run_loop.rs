@@ -3,17 +3,177 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! `CFRunLoop`.
+//! `CFRunLoop`, `CFRunLoopObserver` and `CFRunLoopSource`.
 //!
-//! This is not even toll-free bridged to `NSRunLoop` in Apple's implementation,
-//! but here it is the same type.
+//! `CFRunLoop` is not even toll-free bridged to `NSRunLoop` in Apple's
+//! implementation, but here it is the same type. The modes and the loop itself
+//! are implemented in [ns_run_loop].
+//!
+//! Only version 0 (manually signalled) sources and sources for a `CFSocket`
+//! are supported. There are also host sources, which are how touchHLE's own
+//! frameworks get polled (see [add_host_source]).
 
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::cf_socket::{self, CFSocketRef};
+use super::{CFIndex, CFOptionFlags, CFTimeInterval};
+use crate::abi::{CallFromHost, GuestFunction};
 use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
-use crate::objc::msg_class;
+use crate::frameworks::foundation::{ns_run_loop, ns_string};
+use crate::mem::{ConstPtr, MutVoidPtr, SafeRead};
+use crate::objc::{id, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject};
 use crate::Environment;
+use std::time::Duration;
 
 pub type CFRunLoopRef = super::CFTypeRef;
 pub type CFRunLoopMode = super::cf_string::CFStringRef;
+pub type CFRunLoopObserverRef = super::CFTypeRef;
+pub type CFRunLoopSourceRef = super::CFTypeRef;
+
+pub type CFRunLoopRunResult = i32;
+pub const kCFRunLoopRunFinished: CFRunLoopRunResult = 1;
+pub const kCFRunLoopRunStopped: CFRunLoopRunResult = 2;
+pub const kCFRunLoopRunTimedOut: CFRunLoopRunResult = 3;
+pub const kCFRunLoopRunHandledSource: CFRunLoopRunResult = 4;
+
+pub type CFRunLoopActivity = CFOptionFlags;
+pub const kCFRunLoopEntry: CFRunLoopActivity = 1 << 0;
+pub const kCFRunLoopBeforeTimers: CFRunLoopActivity = 1 << 1;
+pub const kCFRunLoopBeforeSources: CFRunLoopActivity = 1 << 2;
+pub const kCFRunLoopBeforeWaiting: CFRunLoopActivity = 1 << 5;
+pub const kCFRunLoopAfterWaiting: CFRunLoopActivity = 1 << 6;
+pub const kCFRunLoopExit: CFRunLoopActivity = 1 << 7;
+
+#[allow(dead_code)]
+#[repr(C, packed)]
+pub struct CFRunLoopObserverContext {
+    version: CFIndex,
+    info: MutVoidPtr,
+    retain: GuestFunction,
+    release: GuestFunction,
+    copy_description: GuestFunction,
+}
+unsafe impl SafeRead for CFRunLoopObserverContext {}
+
+/// Version 0 of the context. Version 1 has Mach port callbacks instead of
+/// `schedule`, `cancel` and `perform`.
+#[allow(dead_code)]
+#[repr(C, packed)]
+pub struct CFRunLoopSourceContext {
+    version: CFIndex,
+    info: MutVoidPtr,
+    retain: GuestFunction,
+    release: GuestFunction,
+    copy_description: GuestFunction,
+    equal: GuestFunction,
+    hash: GuestFunction,
+    schedule: GuestFunction,
+    cancel: GuestFunction,
+    perform: GuestFunction,
+}
+unsafe impl SafeRead for CFRunLoopSourceContext {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// These are CFType-based types, but in our implementation those are just
+// Objective-C types, so we need classes for them, but their names are not
+// visible anywhere.
+
+@implementation _touchHLE_CFRunLoopObserver: NSObject
+
+- (())dealloc {
+    let &CFRunLoopObserverHostObject { info, release, .. } = env.objc.borrow(this);
+    release_info(env, release, info);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+@implementation _touchHLE_CFRunLoopSource: NSObject
+
+- (())dealloc {
+    match env.objc.borrow::<CFRunLoopSourceHostObject>(this).kind {
+        SourceKind::Custom(CustomSource { info, release, .. }) => release_info(env, release, info),
+        SourceKind::Socket(socket) => release(env, socket),
+        SourceKind::Host(_) => (),
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+struct CFRunLoopObserverHostObject {
+    activities: CFRunLoopActivity,
+    repeats: bool,
+    order: CFIndex,
+    /// `void (*)(CFRunLoopObserverRef, CFRunLoopActivity, void*)`
+    callout: GuestFunction,
+    info: MutVoidPtr,
+    /// `void (*)(const void*)`, may be `NULL`.
+    release: GuestFunction,
+    valid: bool,
+    /// Weak reference. Set when the observer is first added to a run loop.
+    run_loop: CFRunLoopRef,
+}
+impl HostObject for CFRunLoopObserverHostObject {}
+
+struct CFRunLoopSourceHostObject {
+    order: CFIndex,
+    kind: SourceKind,
+    valid: bool,
+    /// Weak reference. Set when the source is first added to a run loop.
+    run_loop: CFRunLoopRef,
+}
+impl HostObject for CFRunLoopSourceHostObject {}
+
+enum SourceKind {
+    /// Version 0 source, see `CFRunLoopSourceCreate`.
+    Custom(CustomSource),
+    /// Strong reference. See `CFSocketCreateRunLoopSource`.
+    Socket(CFSocketRef),
+    /// Called on each iteration of the run loop. See [add_host_source].
+    Host(fn(&mut Environment)),
+}
+
+struct CustomSource {
+    info: MutVoidPtr,
+    /// `void (*)(const void*)`, may be `NULL`.
+    release: GuestFunction,
+    /// `void (*)(void*, CFRunLoopRef, CFRunLoopMode)`, may be `NULL`.
+    schedule: GuestFunction,
+    /// `void (*)(void*, CFRunLoopRef, CFRunLoopMode)`, may be `NULL`.
+    cancel: GuestFunction,
+    /// `void (*)(void*)`
+    perform: GuestFunction,
+    signaled: bool,
+}
+
+fn is_null(function: GuestFunction) -> bool {
+    function.addr_with_thumb_bit() == 0
+}
+
+/// Call a context's `retain` callback, if any, on its `info`.
+pub(super) fn retain_info(
+    env: &mut Environment,
+    retain: GuestFunction,
+    info: MutVoidPtr,
+) -> MutVoidPtr {
+    if is_null(retain) {
+        info
+    } else {
+        retain.call_from_host(env, (info,))
+    }
+}
+
+/// Call a context's `release` callback, if any, on its `info`.
+pub(super) fn release_info(env: &mut Environment, release: GuestFunction, info: MutVoidPtr) {
+    if !is_null(release) {
+        let () = release.call_from_host(env, (info,));
+    }
+}
 
 fn CFRunLoopGetCurrent(env: &mut Environment) -> CFRunLoopRef {
     msg_class![env; NSRunLoop currentRunLoop]
@@ -23,6 +183,414 @@ fn CFRunLoopGetMain(env: &mut Environment) -> CFRunLoopRef {
     msg_class![env; NSRunLoop mainRunLoop]
 }
 
+fn CFRunLoopRun(env: &mut Environment) {
+    let run_loop = CFRunLoopGetCurrent(env);
+    loop {
+        let result = ns_run_loop::run_run_loop(env, run_loop, kCFRunLoopDefaultMode, None, false);
+        if result == kCFRunLoopRunStopped || result == kCFRunLoopRunFinished {
+            break;
+        }
+    }
+}
+
+fn CFRunLoopRunInMode(
+    env: &mut Environment,
+    mode: CFRunLoopMode,
+    seconds: CFTimeInterval,
+    return_after_source_handled: bool,
+) -> CFRunLoopRunResult {
+    let run_loop = CFRunLoopGetCurrent(env);
+    let mode = ns_string::to_rust_string(env, mode);
+    // Very large values are common as a way of saying "forever".
+    let timeout = Duration::try_from_secs_f64(seconds.max(0.0)).ok();
+    ns_run_loop::run_run_loop(env, run_loop, &mode, timeout, return_after_source_handled)
+}
+
+fn CFRunLoopStop(env: &mut Environment, run_loop: CFRunLoopRef) {
+    ns_run_loop::stop(env, run_loop);
+}
+
+fn CFRunLoopWakeUp(_env: &mut Environment, _run_loop: CFRunLoopRef) {
+    // The run loop never really sleeps, so there is nothing to do.
+}
+
+fn CFRunLoopIsWaiting(_env: &mut Environment, _run_loop: CFRunLoopRef) -> bool {
+    false
+}
+
+fn CFRunLoopCopyCurrentMode(env: &mut Environment, run_loop: CFRunLoopRef) -> CFRunLoopMode {
+    match ns_run_loop::current_mode(env, run_loop) {
+        Some(mode) => ns_string::from_rust_string(env, mode),
+        None => nil,
+    }
+}
+
+fn CFRunLoopAddCommonMode(env: &mut Environment, run_loop: CFRunLoopRef, mode: CFRunLoopMode) {
+    ns_run_loop::add_common_mode(env, run_loop, mode);
+}
+
+fn CFRunLoopObserverCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    activities: CFRunLoopActivity,
+    repeats: bool,
+    order: CFIndex,
+    callout: GuestFunction,
+    context: ConstPtr<CFRunLoopObserverContext>,
+) -> CFRunLoopObserverRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let (info, release) = if context.is_null() {
+        (
+            MutVoidPtr::null(),
+            GuestFunction::from_addr_with_thumb_bit(0),
+        )
+    } else {
+        let CFRunLoopObserverContext {
+            version,
+            info,
+            retain,
+            release,
+            ..
+        } = env.mem.read(context);
+        assert!(version == 0);
+        (retain_info(env, retain, info), release)
+    };
+
+    let host_object = Box::new(CFRunLoopObserverHostObject {
+        activities,
+        repeats,
+        order,
+        callout,
+        info,
+        release,
+        valid: true,
+        run_loop: nil,
+    });
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CFRunLoopObserver", &mut env.mem);
+    let new = env.objc.alloc_object(isa, host_object, &mut env.mem);
+    log_dbg!(
+        "New run loop observer {:?} for activities {:#x}, order {}",
+        new,
+        activities,
+        order
+    );
+    new
+}
+
+fn CFRunLoopObserverGetActivities(
+    env: &mut Environment,
+    observer: CFRunLoopObserverRef,
+) -> CFRunLoopActivity {
+    env.objc
+        .borrow::<CFRunLoopObserverHostObject>(observer)
+        .activities
+}
+fn CFRunLoopObserverDoesRepeat(env: &mut Environment, observer: CFRunLoopObserverRef) -> bool {
+    env.objc
+        .borrow::<CFRunLoopObserverHostObject>(observer)
+        .repeats
+}
+fn CFRunLoopObserverGetOrder(env: &mut Environment, observer: CFRunLoopObserverRef) -> CFIndex {
+    observer_order(env, observer)
+}
+fn CFRunLoopObserverIsValid(env: &mut Environment, observer: CFRunLoopObserverRef) -> bool {
+    env.objc
+        .borrow::<CFRunLoopObserverHostObject>(observer)
+        .valid
+}
+fn CFRunLoopObserverInvalidate(env: &mut Environment, observer: CFRunLoopObserverRef) {
+    let host_object = env.objc.borrow_mut::<CFRunLoopObserverHostObject>(observer);
+    host_object.valid = false;
+    let run_loop = std::mem::replace(&mut host_object.run_loop, nil);
+    if run_loop != nil {
+        ns_run_loop::remove_observer(env, run_loop, observer, nil);
+    }
+}
+
+fn CFRunLoopAddObserver(
+    env: &mut Environment,
+    run_loop: CFRunLoopRef,
+    observer: CFRunLoopObserverRef,
+    mode: CFRunLoopMode,
+) {
+    let host_object = env.objc.borrow_mut::<CFRunLoopObserverHostObject>(observer);
+    if !host_object.valid {
+        return;
+    }
+    assert!(host_object.run_loop == nil || host_object.run_loop == run_loop); // TODO
+    host_object.run_loop = run_loop;
+    ns_run_loop::add_observer(env, run_loop, observer, mode);
+}
+fn CFRunLoopRemoveObserver(
+    env: &mut Environment,
+    run_loop: CFRunLoopRef,
+    observer: CFRunLoopObserverRef,
+    mode: CFRunLoopMode,
+) {
+    // The run loop may be holding the last reference.
+    retain(env, observer);
+    if ns_run_loop::remove_observer(env, run_loop, observer, mode) {
+        env.objc
+            .borrow_mut::<CFRunLoopObserverHostObject>(observer)
+            .run_loop = nil;
+    }
+    release(env, observer);
+}
+fn CFRunLoopContainsObserver(
+    env: &mut Environment,
+    run_loop: CFRunLoopRef,
+    observer: CFRunLoopObserverRef,
+    mode: CFRunLoopMode,
+) -> bool {
+    ns_run_loop::contains_observer(env, run_loop, observer, mode)
+}
+
+fn CFRunLoopSourceCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    order: CFIndex,
+    context: ConstPtr<CFRunLoopSourceContext>,
+) -> CFRunLoopSourceRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let CFRunLoopSourceContext {
+        version,
+        info,
+        retain,
+        release,
+        schedule,
+        cancel,
+        perform,
+        ..
+    } = env.mem.read(context);
+    assert!(version == 0); // TODO: version 1 (Mach port) sources
+    let info = retain_info(env, retain, info);
+
+    let kind = SourceKind::Custom(CustomSource {
+        info,
+        release,
+        schedule,
+        cancel,
+        perform,
+        signaled: false,
+    });
+    new_source(env, order, kind)
+}
+
+/// For use by `CFSocketCreateRunLoopSource`: make a new source (+1 reference)
+/// that delivers the socket's callbacks.
+pub(super) fn new_socket_source(
+    env: &mut Environment,
+    order: CFIndex,
+    socket: CFSocketRef,
+) -> CFRunLoopSourceRef {
+    retain(env, socket);
+    new_source(env, order, SourceKind::Socket(socket))
+}
+
+/// For use by `NSRunLoop`: schedule a source in the common modes of a run loop
+/// that calls `poll` on each iteration. touchHLE's frameworks use this to check
+/// for things to do, e.g. whether a sound has finished, like the real ones
+/// would with their own sources. See [crate::frameworks::RUN_LOOP_POLLERS].
+pub fn add_host_source(env: &mut Environment, run_loop: CFRunLoopRef, poll: fn(&mut Environment)) {
+    let source = new_source(env, 0, SourceKind::Host(poll));
+    env.objc
+        .borrow_mut::<CFRunLoopSourceHostObject>(source)
+        .run_loop = run_loop;
+    let mode = ns_string::get_static_str(env, kCFRunLoopCommonModes);
+    ns_run_loop::add_source(env, run_loop, source, mode);
+    release(env, source);
+}
+
+fn new_source(env: &mut Environment, order: CFIndex, kind: SourceKind) -> CFRunLoopSourceRef {
+    let host_object = Box::new(CFRunLoopSourceHostObject {
+        order,
+        kind,
+        valid: true,
+        run_loop: nil,
+    });
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CFRunLoopSource", &mut env.mem);
+    let new = env.objc.alloc_object(isa, host_object, &mut env.mem);
+    log_dbg!("New run loop source {:?}, order {}", new, order);
+    new
+}
+
+fn CFRunLoopSourceGetOrder(env: &mut Environment, source: CFRunLoopSourceRef) -> CFIndex {
+    source_order(env, source)
+}
+fn CFRunLoopSourceIsValid(env: &mut Environment, source: CFRunLoopSourceRef) -> bool {
+    env.objc.borrow::<CFRunLoopSourceHostObject>(source).valid
+}
+fn CFRunLoopSourceSignal(env: &mut Environment, source: CFRunLoopSourceRef) {
+    let host_object = env.objc.borrow_mut::<CFRunLoopSourceHostObject>(source);
+    if let SourceKind::Custom(ref mut custom) = host_object.kind {
+        custom.signaled |= host_object.valid;
+    }
+}
+pub fn CFRunLoopSourceInvalidate(env: &mut Environment, source: CFRunLoopSourceRef) {
+    let host_object = env.objc.borrow_mut::<CFRunLoopSourceHostObject>(source);
+    if !host_object.valid {
+        return;
+    }
+    host_object.valid = false;
+    if let SourceKind::Custom(ref mut custom) = host_object.kind {
+        custom.signaled = false;
+    }
+    let run_loop = std::mem::replace(&mut host_object.run_loop, nil);
+    if run_loop == nil {
+        return;
+    }
+    // The run loop may be holding the last reference.
+    retain(env, source);
+    let (modes, _) = ns_run_loop::remove_source(env, run_loop, source, nil);
+    for mode in modes {
+        cancel_source(env, source, run_loop, mode);
+    }
+    release(env, source);
+}
+
+fn CFRunLoopAddSource(
+    env: &mut Environment,
+    run_loop: CFRunLoopRef,
+    source: CFRunLoopSourceRef,
+    mode: CFRunLoopMode,
+) {
+    let host_object = env.objc.borrow_mut::<CFRunLoopSourceHostObject>(source);
+    if !host_object.valid {
+        return;
+    }
+    assert!(host_object.run_loop == nil || host_object.run_loop == run_loop); // TODO
+    host_object.run_loop = run_loop;
+    if ns_run_loop::add_source(env, run_loop, source, mode) {
+        let host_object = env.objc.borrow::<CFRunLoopSourceHostObject>(source);
+        if let SourceKind::Custom(CustomSource { schedule, info, .. }) = host_object.kind {
+            if !is_null(schedule) {
+                let () = schedule.call_from_host(env, (info, run_loop, mode));
+            }
+        }
+    }
+}
+fn CFRunLoopRemoveSource(
+    env: &mut Environment,
+    run_loop: CFRunLoopRef,
+    source: CFRunLoopSourceRef,
+    mode: CFRunLoopMode,
+) {
+    // The run loop may be holding the last reference.
+    retain(env, source);
+    let (modes, now_unscheduled) = ns_run_loop::remove_source(env, run_loop, source, mode);
+    for mode in modes {
+        cancel_source(env, source, run_loop, mode);
+    }
+    if now_unscheduled {
+        env.objc
+            .borrow_mut::<CFRunLoopSourceHostObject>(source)
+            .run_loop = nil;
+    }
+    release(env, source);
+}
+fn CFRunLoopContainsSource(
+    env: &mut Environment,
+    run_loop: CFRunLoopRef,
+    source: CFRunLoopSourceRef,
+    mode: CFRunLoopMode,
+) -> bool {
+    ns_run_loop::contains_source(env, run_loop, source, mode)
+}
+
+fn cancel_source(env: &mut Environment, source: CFRunLoopSourceRef, run_loop: id, mode: String) {
+    let host_object = env.objc.borrow::<CFRunLoopSourceHostObject>(source);
+    let SourceKind::Custom(CustomSource { cancel, info, .. }) = host_object.kind else {
+        return;
+    };
+    if is_null(cancel) {
+        return;
+    }
+    let mode = ns_string::from_rust_string(env, mode);
+    let () = cancel.call_from_host(env, (info, run_loop, mode));
+    release(env, mode);
+}
+
+/// For use by `NSRunLoop`.
+pub fn observer_order(env: &Environment, observer: CFRunLoopObserverRef) -> CFIndex {
+    env.objc
+        .borrow::<CFRunLoopObserverHostObject>(observer)
+        .order
+}
+
+/// For use by `NSRunLoop`: call the observer's callout if it's interested in
+/// `activity`. Observers that don't repeat are invalidated afterwards.
+pub fn handle_observer(
+    env: &mut Environment,
+    observer: CFRunLoopObserverRef,
+    activity: CFRunLoopActivity,
+) {
+    let &CFRunLoopObserverHostObject {
+        activities,
+        repeats,
+        callout,
+        info,
+        valid,
+        ..
+    } = env.objc.borrow(observer);
+    if !valid || activities & activity == 0 {
+        return;
+    }
+
+    log_dbg!(
+        "Run loop observer {:?} notified of activity {:#x}",
+        observer,
+        activity
+    );
+
+    if !repeats {
+        CFRunLoopObserverInvalidate(env, observer);
+    }
+
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let () = callout.call_from_host(env, (observer, activity, info));
+    release(env, pool);
+}
+
+/// For use by `NSRunLoop`.
+pub fn source_order(env: &Environment, source: CFRunLoopSourceRef) -> CFIndex {
+    env.objc.borrow::<CFRunLoopSourceHostObject>(source).order
+}
+
+/// For use by `NSRunLoop`: perform the source if it has been signalled, or
+/// deliver its socket's callbacks. Returns `true` if anything was done.
+pub fn handle_source(env: &mut Environment, source: CFRunLoopSourceRef) -> bool {
+    let host_object = env.objc.borrow_mut::<CFRunLoopSourceHostObject>(source);
+    if !host_object.valid {
+        return false;
+    }
+    let (perform, info) = match host_object.kind {
+        SourceKind::Custom(ref mut custom) => {
+            if !std::mem::take(&mut custom.signaled) {
+                return false;
+            }
+            (custom.perform, custom.info)
+        }
+        SourceKind::Socket(socket) => return cf_socket::handle_socket(env, socket),
+        SourceKind::Host(poll) => {
+            // Polling doesn't count as performing the source, or else
+            // `CFRunLoopRunInMode` would always return straight away.
+            poll(env);
+            return false;
+        }
+    };
+
+    log_dbg!("Performing run loop source {:?}", source);
+
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let () = perform.call_from_host(env, (info,));
+    release(env, pool);
+    true
+}
+
 pub const kCFRunLoopCommonModes: &str = "kCFRunLoopCommonModes";
 pub const kCFRunLoopDefaultMode: &str = "kCFRunLoopDefaultMode";
 
@@ -40,4 +608,28 @@ pub const CONSTANTS: ConstantExports = &[
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFRunLoopGetCurrent()),
     export_c_func!(CFRunLoopGetMain()),
+    export_c_func!(CFRunLoopRun()),
+    export_c_func!(CFRunLoopRunInMode(_, _, _)),
+    export_c_func!(CFRunLoopStop(_)),
+    export_c_func!(CFRunLoopWakeUp(_)),
+    export_c_func!(CFRunLoopIsWaiting(_)),
+    export_c_func!(CFRunLoopCopyCurrentMode(_)),
+    export_c_func!(CFRunLoopAddCommonMode(_, _)),
+    export_c_func!(CFRunLoopObserverCreate(_, _, _, _, _, _)),
+    export_c_func!(CFRunLoopObserverGetActivities(_)),
+    export_c_func!(CFRunLoopObserverDoesRepeat(_)),
+    export_c_func!(CFRunLoopObserverGetOrder(_)),
+    export_c_func!(CFRunLoopObserverIsValid(_)),
+    export_c_func!(CFRunLoopObserverInvalidate(_)),
+    export_c_func!(CFRunLoopAddObserver(_, _, _)),
+    export_c_func!(CFRunLoopRemoveObserver(_, _, _)),
+    export_c_func!(CFRunLoopContainsObserver(_, _, _)),
+    export_c_func!(CFRunLoopSourceCreate(_, _, _)),
+    export_c_func!(CFRunLoopSourceGetOrder(_)),
+    export_c_func!(CFRunLoopSourceIsValid(_)),
+    export_c_func!(CFRunLoopSourceSignal(_)),
+    export_c_func!(CFRunLoopSourceInvalidate(_)),
+    export_c_func!(CFRunLoopAddSource(_, _, _)),
+    export_c_func!(CFRunLoopRemoveSource(_, _, _)),
+    export_c_func!(CFRunLoopContainsSource(_, _, _)),
 ];
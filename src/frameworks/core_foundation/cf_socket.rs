@@ -0,0 +1,796 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFSocket`.
+//!
+//! Sockets are backed by the host's sockets. They are put in non-blocking mode
+//! and polled whenever the run loop handles their run loop source, which is
+//! when the callbacks are delivered. Connecting and sending do block though.
+//!
+//! Connections and data sent to an address go through the network rules (see
+//! [crate::frameworks::cf_network::redirection]) first.
+//!
+//! The "native handles" of sockets are not real file descriptors, since the app
+//! can't use the BSD sockets API here anyway. They are only useful for passing
+//! a connection accepted by a listening socket to `CFSocketCreateWithNative`.
+
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::cf_run_loop::{self, release_info, retain_info, CFRunLoopSourceRef};
+use super::{CFIndex, CFOptionFlags, CFTimeInterval, CFTypeRef};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::cf_network::redirection;
+use crate::frameworks::foundation::ns_data;
+use crate::mem::{ConstPtr, ConstVoidPtr, MutVoidPtr, Ptr, SafeRead};
+use crate::objc::{id, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject};
+use crate::Environment;
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{
+    Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, TcpListener, TcpStream, UdpSocket,
+};
+use std::time::Duration;
+
+pub type CFSocketRef = CFTypeRef;
+/// This would be a file descriptor in Apple's implementation, see the module
+/// documentation.
+pub type CFSocketNativeHandle = i32;
+
+pub type CFSocketCallBackType = CFOptionFlags;
+#[allow(dead_code)]
+pub const kCFSocketNoCallBack: CFSocketCallBackType = 0;
+pub const kCFSocketReadCallBack: CFSocketCallBackType = 1;
+pub const kCFSocketAcceptCallBack: CFSocketCallBackType = 2;
+pub const kCFSocketDataCallBack: CFSocketCallBackType = 3;
+pub const kCFSocketConnectCallBack: CFSocketCallBackType = 4;
+pub const kCFSocketWriteCallBack: CFSocketCallBackType = 8;
+/// The read, accept and data callbacks are mutually exclusive and share these
+/// bits.
+const READ_TYPE_MASK: CFSocketCallBackType = 3;
+
+pub const kCFSocketAutomaticallyReenableReadCallBack: CFOptionFlags = 1;
+pub const kCFSocketAutomaticallyReenableAcceptCallBack: CFOptionFlags = 2;
+pub const kCFSocketAutomaticallyReenableDataCallBack: CFOptionFlags = 3;
+pub const kCFSocketAutomaticallyReenableWriteCallBack: CFOptionFlags = 8;
+pub const kCFSocketCloseOnInvalidate: CFOptionFlags = 128;
+const DEFAULT_FLAGS: CFOptionFlags = kCFSocketAutomaticallyReenableReadCallBack
+    | kCFSocketAutomaticallyReenableAcceptCallBack
+    | kCFSocketAutomaticallyReenableDataCallBack
+    | kCFSocketCloseOnInvalidate;
+
+pub type CFSocketError = CFIndex;
+pub const kCFSocketSuccess: CFSocketError = 0;
+pub const kCFSocketError: CFSocketError = -1;
+pub const kCFSocketTimeout: CFSocketError = -2;
+
+// These are the values from Apple's `<sys/socket.h>`.
+const PF_INET: i32 = 2;
+const PF_INET6: i32 = 30;
+const SOCK_STREAM: i32 = 1;
+const SOCK_DGRAM: i32 = 2;
+
+/// How much data is read for each data callback.
+const DATA_CALLBACK_MAX: usize = 64 * 1024;
+
+#[allow(dead_code)]
+#[repr(C, packed)]
+pub struct CFSocketContext {
+    version: CFIndex,
+    info: MutVoidPtr,
+    retain: GuestFunction,
+    release: GuestFunction,
+    copy_description: GuestFunction,
+}
+unsafe impl SafeRead for CFSocketContext {}
+
+#[derive(Default)]
+pub struct State {
+    /// Connections accepted by listening sockets that haven't been passed to
+    /// `CFSocketCreateWithNative` yet.
+    /// TODO: Close these if they are never used?
+    accepted: HashMap<CFSocketNativeHandle, TcpStream>,
+    /// Starts from an arbitrary value that looks like a file descriptor.
+    next_native_handle: Option<CFSocketNativeHandle>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.framework_state.core_foundation.cf_socket
+    }
+
+    fn new_native_handle(&mut self) -> CFSocketNativeHandle {
+        let handle = self.next_native_handle.unwrap_or(16);
+        self.next_native_handle = Some(handle + 1);
+        handle
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CFSocket is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not visible
+// anywhere.
+@implementation _touchHLE_CFSocket: NSObject
+
+- (())dealloc {
+    let &CFSocketHostObject { info, release, .. } = env.objc.borrow(this);
+    release_info(env, release, info);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+enum HostSocket {
+    /// Not yet connected or bound, or closed.
+    None,
+    Stream(TcpStream),
+    Listener(TcpListener),
+    Datagram(UdpSocket),
+}
+
+struct CFSocketHostObject {
+    is_datagram: bool,
+    is_ipv6: bool,
+    socket: HostSocket,
+    native_handle: CFSocketNativeHandle,
+    /// The callbacks that are currently enabled.
+    callback_types: CFSocketCallBackType,
+    flags: CFOptionFlags,
+    /// `void (*)(CFSocketRef, CFSocketCallBackType, CFDataRef, const void*,
+    /// void*)`
+    callout: GuestFunction,
+    info: MutVoidPtr,
+    /// `void (*)(const void*)`, may be `NULL`.
+    release: GuestFunction,
+    /// Result of a background connection attempt, not yet delivered to the
+    /// connect callback.
+    pending_connect: Option<CFSocketError>,
+    valid: bool,
+    /// Weak reference.
+    run_loop_source: CFRunLoopSourceRef,
+}
+impl HostObject for CFSocketHostObject {}
+
+/// Something that happened that the read, accept or data callback should be
+/// told about.
+enum ReadEvent {
+    Readable,
+    Accepted(TcpStream, SocketAddr),
+    /// Empty if the connection was closed.
+    Data(Vec<u8>, Option<SocketAddr>),
+}
+
+impl CFSocketHostObject {
+    fn poll_read(&mut self, read_type: CFSocketCallBackType) -> Option<ReadEvent> {
+        match (read_type, &mut self.socket) {
+            (kCFSocketReadCallBack, HostSocket::Stream(stream)) => {
+                match stream.peek(&mut [0u8]) {
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => None,
+                    // End of stream and errors are "readable" too.
+                    _ => Some(ReadEvent::Readable),
+                }
+            }
+            (kCFSocketReadCallBack, HostSocket::Datagram(socket)) => {
+                match socket.peek_from(&mut [0u8]) {
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => None,
+                    _ => Some(ReadEvent::Readable),
+                }
+            }
+            (kCFSocketAcceptCallBack, HostSocket::Listener(listener)) => match listener.accept() {
+                Ok((stream, address)) => Some(ReadEvent::Accepted(stream, address)),
+                Err(e) => {
+                    if e.kind() != ErrorKind::WouldBlock {
+                        log!("Warning: accepting connection failed: {}", e);
+                    }
+                    None
+                }
+            },
+            (kCFSocketDataCallBack, HostSocket::Stream(stream)) => {
+                let mut buffer = vec![0u8; DATA_CALLBACK_MAX];
+                match stream.read(&mut buffer) {
+                    Ok(bytes_read) => {
+                        buffer.truncate(bytes_read);
+                        Some(ReadEvent::Data(buffer, stream.peer_addr().ok()))
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => None,
+                    Err(e) => {
+                        log!("Warning: reading from socket failed: {}", e);
+                        Some(ReadEvent::Data(Vec::new(), None))
+                    }
+                }
+            }
+            (kCFSocketDataCallBack, HostSocket::Datagram(socket)) => {
+                let mut buffer = vec![0u8; DATA_CALLBACK_MAX];
+                match socket.recv_from(&mut buffer) {
+                    Ok((bytes_read, address)) => {
+                        buffer.truncate(bytes_read);
+                        Some(ReadEvent::Data(buffer, Some(address)))
+                    }
+                    Err(_) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parse a `struct sockaddr_in` or `struct sockaddr_in6`, as found in the
+/// `CFData` addresses used by `CFSocket`.
+fn sockaddr_to_rust(bytes: &[u8]) -> Option<SocketAddr> {
+    let family = *bytes.get(1)?;
+    let port = u16::from_be_bytes(bytes.get(2..4)?.try_into().unwrap());
+    match family as i32 {
+        PF_INET => {
+            let ip: [u8; 4] = bytes.get(4..8)?.try_into().unwrap();
+            Some(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(ip), port)))
+        }
+        PF_INET6 => {
+            let flowinfo = u32::from_be_bytes(bytes.get(4..8)?.try_into().unwrap());
+            let ip: [u8; 16] = bytes.get(8..24)?.try_into().unwrap();
+            let scope_id = u32::from_le_bytes(bytes.get(24..28)?.try_into().unwrap());
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(ip),
+                port,
+                flowinfo,
+                scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
+
+/// The reverse of [sockaddr_to_rust].
+fn sockaddr_from_rust(address: SocketAddr) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    match address {
+        SocketAddr::V4(address) => {
+            bytes.extend_from_slice(&[16, PF_INET as u8]);
+            bytes.extend_from_slice(&address.port().to_be_bytes());
+            bytes.extend_from_slice(&address.ip().octets());
+            bytes.extend_from_slice(&[0; 8]);
+        }
+        SocketAddr::V6(address) => {
+            bytes.extend_from_slice(&[28, PF_INET6 as u8]);
+            bytes.extend_from_slice(&address.port().to_be_bytes());
+            bytes.extend_from_slice(&address.flowinfo().to_be_bytes());
+            bytes.extend_from_slice(&address.ip().octets());
+            bytes.extend_from_slice(&address.scope_id().to_le_bytes());
+        }
+    }
+    bytes
+}
+
+fn address_from_data(env: &mut Environment, address: id) -> Option<SocketAddr> {
+    if address == nil {
+        return None;
+    }
+    let bytes = ns_data::to_vec(env, address);
+    let parsed = sockaddr_to_rust(&bytes);
+    if parsed.is_none() {
+        log!("Warning: unsupported socket address {:?}", bytes);
+    }
+    parsed
+}
+
+/// Returns a new `CFDataRef` (+1 reference), or `NULL`.
+fn address_to_data(env: &mut Environment, address: Option<SocketAddr>) -> id {
+    match address {
+        Some(address) => ns_data::from_bytes(env, &sockaddr_from_rust(address)),
+        None => nil,
+    }
+}
+
+fn timeout_duration(timeout: CFTimeInterval) -> Option<Duration> {
+    if timeout > 0.0 {
+        Duration::try_from_secs_f64(timeout).ok()
+    } else {
+        None
+    }
+}
+
+fn new_socket(
+    env: &mut Environment,
+    is_datagram: bool,
+    is_ipv6: bool,
+    socket: HostSocket,
+    callback_types: CFSocketCallBackType,
+    callout: GuestFunction,
+    context: ConstPtr<CFSocketContext>,
+) -> CFSocketRef {
+    let (info, release) = if context.is_null() {
+        (
+            MutVoidPtr::null(),
+            GuestFunction::from_addr_with_thumb_bit(0),
+        )
+    } else {
+        let CFSocketContext {
+            version,
+            info,
+            retain,
+            release,
+            ..
+        } = env.mem.read(context);
+        assert!(version == 0);
+        (retain_info(env, retain, info), release)
+    };
+
+    let native_handle = State::get(env).new_native_handle();
+    let host_object = Box::new(CFSocketHostObject {
+        is_datagram,
+        is_ipv6,
+        socket,
+        native_handle,
+        callback_types,
+        flags: DEFAULT_FLAGS,
+        callout,
+        info,
+        release,
+        pending_connect: None,
+        valid: true,
+        run_loop_source: nil,
+    });
+    let isa = env.objc.get_known_class("_touchHLE_CFSocket", &mut env.mem);
+    let new = env.objc.alloc_object(isa, host_object, &mut env.mem);
+    log_dbg!(
+        "New {} socket {:?}, native handle {}, callbacks {:#x}",
+        if is_datagram { "datagram" } else { "stream" },
+        new,
+        native_handle,
+        callback_types
+    );
+    new
+}
+
+fn CFSocketCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    protocol_family: i32,
+    socket_type: i32,
+    _protocol: i32,
+    callback_types: CFSocketCallBackType,
+    callout: GuestFunction,
+    context: ConstPtr<CFSocketContext>,
+) -> CFSocketRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let is_ipv6 = match protocol_family {
+        ..=0 | PF_INET => false,
+        PF_INET6 => true,
+        _ => unimplemented!("Protocol family {}", protocol_family),
+    };
+    let is_datagram = match socket_type {
+        ..=0 | SOCK_STREAM => false,
+        SOCK_DGRAM => true,
+        _ => unimplemented!("Socket type {}", socket_type),
+    };
+    new_socket(
+        env,
+        is_datagram,
+        is_ipv6,
+        HostSocket::None,
+        callback_types,
+        callout,
+        context,
+    )
+}
+
+fn CFSocketCreateWithNative(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    handle: CFSocketNativeHandle,
+    callback_types: CFSocketCallBackType,
+    callout: GuestFunction,
+    context: ConstPtr<CFSocketContext>,
+) -> CFSocketRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let Some(stream) = State::get(env).accepted.remove(&handle) else {
+        log!(
+            "Warning: CFSocketCreateWithNative() with unknown handle {}",
+            handle
+        );
+        return nil;
+    };
+    let is_ipv6 = stream.local_addr().is_ok_and(|address| address.is_ipv6());
+    let socket = HostSocket::Stream(stream);
+    let new = new_socket(
+        env,
+        false,
+        is_ipv6,
+        socket,
+        callback_types,
+        callout,
+        context,
+    );
+    // Keep the handle the app already knows about.
+    env.objc.borrow_mut::<CFSocketHostObject>(new).native_handle = handle;
+    new
+}
+
+fn CFSocketConnectToAddress(
+    env: &mut Environment,
+    socket: CFSocketRef,
+    address: id, // CFDataRef
+    timeout: CFTimeInterval,
+) -> CFSocketError {
+    let Some(address) = address_from_data(env, address) else {
+        return kCFSocketError;
+    };
+    let host_object = env.objc.borrow::<CFSocketHostObject>(socket);
+    if !host_object.valid || host_object.is_ipv6 != address.is_ipv6() {
+        return kCFSocketError;
+    }
+    let address = redirection::redirect_socket_address(env, address);
+    let host_object = env.objc.borrow_mut::<CFSocketHostObject>(socket);
+    log_dbg!("Socket {:?} connecting to {}", socket, address);
+    let result = if host_object.is_datagram {
+        let unspecified: SocketAddr = if address.is_ipv6() {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        };
+        UdpSocket::bind(unspecified)
+            .and_then(|udp| udp.connect(address).map(|()| udp))
+            .map(HostSocket::Datagram)
+    } else {
+        // TODO: Connect in the background when the timeout is negative,
+        // rather than blocking.
+        let connect_timeout = timeout_duration(timeout).unwrap_or(Duration::from_secs(30));
+        TcpStream::connect_timeout(&address, connect_timeout).map(HostSocket::Stream)
+    };
+    let error = match result {
+        Ok(new_socket) => {
+            host_object.socket = new_socket;
+            set_nonblocking(&host_object.socket);
+            kCFSocketSuccess
+        }
+        Err(e) => {
+            log!(
+                "Warning: socket {:?} couldn't connect to {}: {}",
+                socket,
+                address,
+                e
+            );
+            if e.kind() == ErrorKind::TimedOut {
+                kCFSocketTimeout
+            } else {
+                kCFSocketError
+            }
+        }
+    };
+    if timeout < 0.0 {
+        // The result is meant to be delivered to the connect callback.
+        host_object.pending_connect = Some(error);
+        kCFSocketSuccess
+    } else {
+        error
+    }
+}
+
+fn set_nonblocking(socket: &HostSocket) {
+    let result = match socket {
+        HostSocket::None => Ok(()),
+        HostSocket::Stream(stream) => stream.set_nonblocking(true),
+        HostSocket::Listener(listener) => listener.set_nonblocking(true),
+        HostSocket::Datagram(socket) => socket.set_nonblocking(true),
+    };
+    result.unwrap();
+}
+
+fn CFSocketSetAddress(
+    env: &mut Environment,
+    socket: CFSocketRef,
+    address: id, // CFDataRef
+) -> CFSocketError {
+    let Some(address) = address_from_data(env, address) else {
+        return kCFSocketError;
+    };
+    let host_object = env.objc.borrow_mut::<CFSocketHostObject>(socket);
+    if !host_object.valid || host_object.is_ipv6 != address.is_ipv6() {
+        return kCFSocketError;
+    }
+    log_dbg!("Socket {:?} binding to {}", socket, address);
+    let result = if host_object.is_datagram {
+        UdpSocket::bind(address).map(HostSocket::Datagram)
+    } else {
+        TcpListener::bind(address).map(HostSocket::Listener)
+    };
+    match result {
+        Ok(new_socket) => {
+            host_object.socket = new_socket;
+            set_nonblocking(&host_object.socket);
+            kCFSocketSuccess
+        }
+        Err(e) => {
+            log!(
+                "Warning: socket {:?} couldn't bind to {}: {}",
+                socket,
+                address,
+                e
+            );
+            kCFSocketError
+        }
+    }
+}
+
+fn CFSocketCopyAddress(env: &mut Environment, socket: CFSocketRef) -> id {
+    let address = match env.objc.borrow::<CFSocketHostObject>(socket).socket {
+        HostSocket::None => None,
+        HostSocket::Stream(ref stream) => stream.local_addr().ok(),
+        HostSocket::Listener(ref listener) => listener.local_addr().ok(),
+        HostSocket::Datagram(ref socket) => socket.local_addr().ok(),
+    };
+    address_to_data(env, address)
+}
+
+fn CFSocketCopyPeerAddress(env: &mut Environment, socket: CFSocketRef) -> id {
+    let address = match env.objc.borrow::<CFSocketHostObject>(socket).socket {
+        HostSocket::None | HostSocket::Listener(_) => None,
+        HostSocket::Stream(ref stream) => stream.peer_addr().ok(),
+        HostSocket::Datagram(ref socket) => socket.peer_addr().ok(),
+    };
+    address_to_data(env, address)
+}
+
+fn CFSocketSendData(
+    env: &mut Environment,
+    socket: CFSocketRef,
+    address: id, // CFDataRef
+    data: id,    // CFDataRef
+    timeout: CFTimeInterval,
+) -> CFSocketError {
+    let address = address_from_data(env, address)
+        .map(|address| redirection::redirect_socket_address(env, address));
+    let bytes = ns_data::to_vec(env, data);
+    let host_object = env.objc.borrow_mut::<CFSocketHostObject>(socket);
+    if !host_object.valid {
+        return kCFSocketError;
+    }
+    let result = match host_object.socket {
+        HostSocket::Datagram(ref socket) => match address {
+            Some(address) => socket.send_to(&bytes, address),
+            None => socket.send(&bytes),
+        }
+        .map(|_| ()),
+        HostSocket::Stream(ref mut stream) => {
+            // Writing isn't done in the background, so this temporarily makes
+            // the socket blocking.
+            stream.set_nonblocking(false).unwrap();
+            stream.set_write_timeout(timeout_duration(timeout)).unwrap();
+            let result = stream.write_all(&bytes);
+            stream.set_nonblocking(true).unwrap();
+            result
+        }
+        HostSocket::None | HostSocket::Listener(_) => return kCFSocketError,
+    };
+    match result {
+        Ok(()) => kCFSocketSuccess,
+        Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => {
+            kCFSocketTimeout
+        }
+        Err(e) => {
+            log!("Warning: socket {:?} couldn't send data: {}", socket, e);
+            kCFSocketError
+        }
+    }
+}
+
+fn CFSocketGetNative(env: &mut Environment, socket: CFSocketRef) -> CFSocketNativeHandle {
+    env.objc.borrow::<CFSocketHostObject>(socket).native_handle
+}
+
+fn CFSocketIsValid(env: &mut Environment, socket: CFSocketRef) -> bool {
+    env.objc.borrow::<CFSocketHostObject>(socket).valid
+}
+
+fn CFSocketInvalidate(env: &mut Environment, socket: CFSocketRef) {
+    let host_object = env.objc.borrow_mut::<CFSocketHostObject>(socket);
+    if !host_object.valid {
+        return;
+    }
+    host_object.valid = false;
+    // There's no way for the app to use the socket after this, so it's closed
+    // even without kCFSocketCloseOnInvalidate.
+    host_object.socket = HostSocket::None;
+    let source = std::mem::replace(&mut host_object.run_loop_source, nil);
+    if source != nil {
+        cf_run_loop::CFRunLoopSourceInvalidate(env, source);
+    }
+}
+
+fn CFSocketEnableCallBacks(
+    env: &mut Environment,
+    socket: CFSocketRef,
+    callback_types: CFSocketCallBackType,
+) {
+    env.objc
+        .borrow_mut::<CFSocketHostObject>(socket)
+        .callback_types |= callback_types;
+}
+fn CFSocketDisableCallBacks(
+    env: &mut Environment,
+    socket: CFSocketRef,
+    callback_types: CFSocketCallBackType,
+) {
+    env.objc
+        .borrow_mut::<CFSocketHostObject>(socket)
+        .callback_types &= !callback_types;
+}
+
+fn CFSocketGetSocketFlags(env: &mut Environment, socket: CFSocketRef) -> CFOptionFlags {
+    env.objc.borrow::<CFSocketHostObject>(socket).flags
+}
+fn CFSocketSetSocketFlags(env: &mut Environment, socket: CFSocketRef, flags: CFOptionFlags) {
+    env.objc.borrow_mut::<CFSocketHostObject>(socket).flags = flags;
+}
+
+fn CFSocketCreateRunLoopSource(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    socket: CFSocketRef,
+    order: CFIndex,
+) -> CFRunLoopSourceRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let existing = env
+        .objc
+        .borrow::<CFSocketHostObject>(socket)
+        .run_loop_source;
+    if existing != nil {
+        return retain(env, existing);
+    }
+    let source = cf_run_loop::new_socket_source(env, order, socket);
+    env.objc
+        .borrow_mut::<CFSocketHostObject>(socket)
+        .run_loop_source = source;
+    source
+}
+
+/// Call the socket's callout.
+fn call_callout(
+    env: &mut Environment,
+    socket: CFSocketRef,
+    callback_type: CFSocketCallBackType,
+    address: id,
+    data: ConstVoidPtr,
+) {
+    let &CFSocketHostObject { callout, info, .. } = env.objc.borrow(socket);
+    log_dbg!(
+        "Socket {:?} callback {:#x}, address {:?}, data {:?}",
+        socket,
+        callback_type,
+        address,
+        data
+    );
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let () = callout.call_from_host(env, (socket, callback_type, address, data, info));
+    release(env, pool);
+}
+
+/// Call the socket's callout with a pointer to a 32-bit integer as the data.
+fn call_callout_with_i32(
+    env: &mut Environment,
+    socket: CFSocketRef,
+    callback_type: CFSocketCallBackType,
+    address: id,
+    value: i32,
+) {
+    let ptr = env.mem.alloc_and_write(value);
+    call_callout(env, socket, callback_type, address, ptr.cast().cast_const());
+    env.mem.free(ptr.cast());
+}
+
+/// For use by `CFRunLoopSource`: deliver any callbacks that are due. Returns
+/// `true` if any were delivered.
+pub(super) fn handle_socket(env: &mut Environment, socket: CFSocketRef) -> bool {
+    let mut handled = false;
+
+    let host_object = env.objc.borrow_mut::<CFSocketHostObject>(socket);
+    if let Some(error) = host_object.pending_connect.take() {
+        let enabled = host_object.callback_types & kCFSocketConnectCallBack != 0;
+        host_object.callback_types &= !kCFSocketConnectCallBack;
+        if enabled {
+            if error == kCFSocketSuccess {
+                call_callout(env, socket, kCFSocketConnectCallBack, nil, Ptr::null());
+            } else {
+                call_callout_with_i32(env, socket, kCFSocketConnectCallBack, nil, error);
+            }
+            handled = true;
+        }
+    }
+
+    let host_object = env.objc.borrow_mut::<CFSocketHostObject>(socket);
+    if !host_object.valid {
+        return handled;
+    }
+    let read_type = host_object.callback_types & READ_TYPE_MASK;
+    let event = host_object.poll_read(read_type);
+    if let Some(event) = event {
+        if host_object.flags & read_type != read_type {
+            host_object.callback_types &= !READ_TYPE_MASK;
+        }
+        match event {
+            ReadEvent::Readable => {
+                call_callout(env, socket, read_type, nil, Ptr::null());
+            }
+            ReadEvent::Accepted(stream, address) => {
+                stream.set_nonblocking(true).unwrap();
+                let state = State::get(env);
+                let handle = state.new_native_handle();
+                state.accepted.insert(handle, stream);
+                log_dbg!(
+                    "Socket {:?} accepted connection {} from {}",
+                    socket,
+                    handle,
+                    address
+                );
+                let address = address_to_data(env, Some(address));
+                call_callout_with_i32(env, socket, read_type, address, handle);
+                release(env, address);
+            }
+            ReadEvent::Data(bytes, address) => {
+                if bytes.is_empty() {
+                    // The connection was closed, there won't be any more data.
+                    env.objc
+                        .borrow_mut::<CFSocketHostObject>(socket)
+                        .callback_types &= !READ_TYPE_MASK;
+                }
+                let address = address_to_data(env, address);
+                let data = ns_data::from_bytes(env, &bytes);
+                call_callout(env, socket, read_type, address, data.cast().cast_const());
+                release(env, data);
+                release(env, address);
+            }
+        }
+        handled = true;
+    }
+
+    let host_object = env.objc.borrow_mut::<CFSocketHostObject>(socket);
+    if host_object.valid
+        && host_object.callback_types & kCFSocketWriteCallBack != 0
+        && matches!(host_object.socket, HostSocket::Stream(_))
+    {
+        if host_object.flags & kCFSocketAutomaticallyReenableWriteCallBack == 0 {
+            host_object.callback_types &= !kCFSocketWriteCallBack;
+        }
+        call_callout(env, socket, kCFSocketWriteCallBack, nil, Ptr::null());
+        handled = true;
+    }
+
+    handled
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFSocketCreate(_, _, _, _, _, _, _)),
+    export_c_func!(CFSocketCreateWithNative(_, _, _, _, _)),
+    export_c_func!(CFSocketConnectToAddress(_, _, _)),
+    export_c_func!(CFSocketSetAddress(_, _)),
+    export_c_func!(CFSocketCopyAddress(_)),
+    export_c_func!(CFSocketCopyPeerAddress(_)),
+    export_c_func!(CFSocketSendData(_, _, _, _)),
+    export_c_func!(CFSocketGetNative(_)),
+    export_c_func!(CFSocketIsValid(_)),
+    export_c_func!(CFSocketInvalidate(_)),
+    export_c_func!(CFSocketEnableCallBacks(_, _)),
+    export_c_func!(CFSocketDisableCallBacks(_, _)),
+    export_c_func!(CFSocketGetSocketFlags(_)),
+    export_c_func!(CFSocketSetSocketFlags(_, _)),
+    export_c_func!(CFSocketCreateRunLoopSource(_, _, _)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sockaddr_round_trip() {
+        for address in ["127.0.0.1:80", "[::1]:8080"] {
+            let address: SocketAddr = address.parse().unwrap();
+            let bytes = sockaddr_from_rust(address);
+            assert_eq!(bytes[0] as usize, bytes.len());
+            assert_eq!(sockaddr_to_rust(&bytes), Some(address));
+        }
+    }
+}
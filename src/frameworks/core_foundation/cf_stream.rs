@@ -0,0 +1,275 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFReadStream` and `CFWriteStream`.
+//!
+//! These are toll-free bridged to `NSInputStream` and `NSOutputStream` in
+//! Apple's implementation. Here they are the same types.
+
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::cf_run_loop::{CFRunLoopMode, CFRunLoopRef};
+use super::cf_url::CFURLRef;
+use super::CFIndex;
+use crate::abi::GuestFunction;
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::ns_stream::{
+    self, NSStreamDataWrittenToMemoryStreamKey, NSStreamStatusError, StreamClient,
+};
+use crate::frameworks::foundation::{NSInteger, NSUInteger};
+use crate::mem::{ConstPtr, MutPtr, MutVoidPtr, SafeRead};
+use crate::objc::{id, msg, msg_class, retain};
+use crate::Environment;
+
+pub type CFReadStreamRef = super::CFTypeRef;
+pub type CFWriteStreamRef = super::CFTypeRef;
+
+/// Same values as `NSStreamStatus`.
+pub type CFStreamStatus = CFIndex;
+/// Same values as `NSStreamEvent`.
+pub type CFStreamEventType = u32;
+
+#[allow(dead_code)]
+#[repr(C, packed)]
+pub struct CFStreamClientContext {
+    version: CFIndex,
+    info: MutVoidPtr,
+    retain: GuestFunction,
+    release: GuestFunction,
+    copy_description: GuestFunction,
+}
+unsafe impl SafeRead for CFStreamClientContext {}
+
+pub const kCFStreamPropertyDataWritten: &str = NSStreamDataWrittenToMemoryStreamKey;
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_kCFStreamPropertyDataWritten",
+    HostConstant::NSString(kCFStreamPropertyDataWritten),
+)];
+
+fn CFReadStreamCreateWithFile(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    file_url: CFURLRef,
+) -> CFReadStreamRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let path: id = msg![env; file_url path];
+    let stream: id = msg_class![env; NSInputStream alloc];
+    msg![env; stream initWithFileAtPath:path]
+}
+
+/// The bytes are copied, so the deallocator is never used.
+fn CFReadStreamCreateWithBytesNoCopy(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    bytes: ConstPtr<u8>,
+    length: CFIndex,
+    _bytes_deallocator: CFAllocatorRef,
+) -> CFReadStreamRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let length: NSUInteger = length.try_into().unwrap();
+    let bytes = if length == 0 {
+        Vec::new()
+    } else {
+        env.mem.bytes_at(bytes, length).to_vec()
+    };
+    ns_stream::input_stream_with_bytes(env, bytes)
+}
+
+fn CFWriteStreamCreateWithFile(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    file_url: CFURLRef,
+) -> CFWriteStreamRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let path: id = msg![env; file_url path];
+    let stream: id = msg_class![env; NSOutputStream alloc];
+    msg![env; stream initToFileAtPath:path append:false]
+}
+
+fn CFWriteStreamCreateWithAllocatedBuffers(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    buffer_allocator: CFAllocatorRef,
+) -> CFWriteStreamRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    assert!(buffer_allocator == kCFAllocatorDefault); // unimplemented
+    let stream: id = msg_class![env; NSOutputStream alloc];
+    msg![env; stream initToMemory]
+}
+
+fn open_stream(env: &mut Environment, stream: id) -> bool {
+    let () = msg![env; stream open];
+    let status: NSUInteger = msg![env; stream streamStatus];
+    status != NSStreamStatusError
+}
+
+fn get_status(env: &mut Environment, stream: id) -> CFStreamStatus {
+    let status: NSUInteger = msg![env; stream streamStatus];
+    status.try_into().unwrap()
+}
+
+fn set_client(
+    env: &mut Environment,
+    stream: id,
+    events: CFStreamEventType,
+    callback: GuestFunction,
+    context: ConstPtr<CFStreamClientContext>,
+) -> bool {
+    if callback.addr_with_thumb_bit() == 0 {
+        ns_stream::set_client(env, stream, None);
+        return true;
+    }
+    assert!(!context.is_null());
+    // TODO: call the context's retain and release callbacks
+    let CFStreamClientContext { version, info, .. } = env.mem.read(context);
+    assert!(version == 0);
+    ns_stream::set_client(
+        env,
+        stream,
+        Some(StreamClient {
+            events,
+            callback,
+            info,
+        }),
+    );
+    true
+}
+
+fn CFReadStreamOpen(env: &mut Environment, stream: CFReadStreamRef) -> bool {
+    open_stream(env, stream)
+}
+fn CFReadStreamClose(env: &mut Environment, stream: CFReadStreamRef) {
+    msg![env; stream close]
+}
+fn CFReadStreamGetStatus(env: &mut Environment, stream: CFReadStreamRef) -> CFStreamStatus {
+    get_status(env, stream)
+}
+fn CFReadStreamHasBytesAvailable(env: &mut Environment, stream: CFReadStreamRef) -> bool {
+    msg![env; stream hasBytesAvailable]
+}
+fn CFReadStreamRead(
+    env: &mut Environment,
+    stream: CFReadStreamRef,
+    buffer: MutPtr<u8>,
+    buffer_length: CFIndex,
+) -> CFIndex {
+    let buffer_length: NSUInteger = buffer_length.try_into().unwrap();
+    let bytes_read: NSInteger = msg![env; stream read:buffer maxLength:buffer_length];
+    bytes_read
+}
+fn CFReadStreamCopyProperty(
+    env: &mut Environment,
+    stream: CFReadStreamRef,
+    property_name: id, // CFStringRef
+) -> id {
+    let property: id = msg![env; stream propertyForKey:property_name];
+    retain(env, property)
+}
+fn CFReadStreamSetClient(
+    env: &mut Environment,
+    stream: CFReadStreamRef,
+    events: CFStreamEventType,
+    callback: GuestFunction,
+    context: ConstPtr<CFStreamClientContext>,
+) -> bool {
+    set_client(env, stream, events, callback, context)
+}
+fn CFReadStreamScheduleWithRunLoop(
+    env: &mut Environment,
+    stream: CFReadStreamRef,
+    run_loop: CFRunLoopRef,
+    mode: CFRunLoopMode,
+) {
+    msg![env; stream scheduleInRunLoop:run_loop forMode:mode]
+}
+fn CFReadStreamUnscheduleFromRunLoop(
+    env: &mut Environment,
+    stream: CFReadStreamRef,
+    run_loop: CFRunLoopRef,
+    mode: CFRunLoopMode,
+) {
+    msg![env; stream removeFromRunLoop:run_loop forMode:mode]
+}
+
+fn CFWriteStreamOpen(env: &mut Environment, stream: CFWriteStreamRef) -> bool {
+    open_stream(env, stream)
+}
+fn CFWriteStreamClose(env: &mut Environment, stream: CFWriteStreamRef) {
+    msg![env; stream close]
+}
+fn CFWriteStreamGetStatus(env: &mut Environment, stream: CFWriteStreamRef) -> CFStreamStatus {
+    get_status(env, stream)
+}
+fn CFWriteStreamCanAcceptBytes(env: &mut Environment, stream: CFWriteStreamRef) -> bool {
+    msg![env; stream hasSpaceAvailable]
+}
+fn CFWriteStreamWrite(
+    env: &mut Environment,
+    stream: CFWriteStreamRef,
+    buffer: ConstPtr<u8>,
+    buffer_length: CFIndex,
+) -> CFIndex {
+    let buffer_length: NSUInteger = buffer_length.try_into().unwrap();
+    let bytes_written: NSInteger = msg![env; stream write:buffer maxLength:buffer_length];
+    bytes_written
+}
+fn CFWriteStreamCopyProperty(
+    env: &mut Environment,
+    stream: CFWriteStreamRef,
+    property_name: id, // CFStringRef
+) -> id {
+    let property: id = msg![env; stream propertyForKey:property_name];
+    retain(env, property)
+}
+fn CFWriteStreamSetClient(
+    env: &mut Environment,
+    stream: CFWriteStreamRef,
+    events: CFStreamEventType,
+    callback: GuestFunction,
+    context: ConstPtr<CFStreamClientContext>,
+) -> bool {
+    set_client(env, stream, events, callback, context)
+}
+fn CFWriteStreamScheduleWithRunLoop(
+    env: &mut Environment,
+    stream: CFWriteStreamRef,
+    run_loop: CFRunLoopRef,
+    mode: CFRunLoopMode,
+) {
+    msg![env; stream scheduleInRunLoop:run_loop forMode:mode]
+}
+fn CFWriteStreamUnscheduleFromRunLoop(
+    env: &mut Environment,
+    stream: CFWriteStreamRef,
+    run_loop: CFRunLoopRef,
+    mode: CFRunLoopMode,
+) {
+    msg![env; stream removeFromRunLoop:run_loop forMode:mode]
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFReadStreamCreateWithFile(_, _)),
+    export_c_func!(CFReadStreamCreateWithBytesNoCopy(_, _, _, _)),
+    export_c_func!(CFWriteStreamCreateWithFile(_, _)),
+    export_c_func!(CFWriteStreamCreateWithAllocatedBuffers(_, _)),
+    export_c_func!(CFReadStreamOpen(_)),
+    export_c_func!(CFReadStreamClose(_)),
+    export_c_func!(CFReadStreamGetStatus(_)),
+    export_c_func!(CFReadStreamHasBytesAvailable(_)),
+    export_c_func!(CFReadStreamRead(_, _, _)),
+    export_c_func!(CFReadStreamCopyProperty(_, _)),
+    export_c_func!(CFReadStreamSetClient(_, _, _, _)),
+    export_c_func!(CFReadStreamScheduleWithRunLoop(_, _, _)),
+    export_c_func!(CFReadStreamUnscheduleFromRunLoop(_, _, _)),
+    export_c_func!(CFWriteStreamOpen(_)),
+    export_c_func!(CFWriteStreamClose(_)),
+    export_c_func!(CFWriteStreamGetStatus(_)),
+    export_c_func!(CFWriteStreamCanAcceptBytes(_)),
+    export_c_func!(CFWriteStreamWrite(_, _, _)),
+    export_c_func!(CFWriteStreamCopyProperty(_, _)),
+    export_c_func!(CFWriteStreamSetClient(_, _, _, _)),
+    export_c_func!(CFWriteStreamScheduleWithRunLoop(_, _, _)),
+    export_c_func!(CFWriteStreamUnscheduleFromRunLoop(_, _, _)),
+];
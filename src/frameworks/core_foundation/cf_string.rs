@@ -7,5 +7,274 @@
 //!
 //! This is toll-free bridged to `CFURL` in Apple's implementation. Here it is
 //! the same type.
+//!
+//! There's no separate mutable string type yet, so the functions that modify a
+//! `CFMutableString` in place work on any non-constant string.
+
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::{CFIndex, CFRange};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::ns_string::normalization::{normalize, NormalizationForm};
+use crate::frameworks::foundation::ns_string::transliteration;
+use crate::frameworks::foundation::ns_string::{
+    self, NSASCIIStringEncoding, NSISOLatin1StringEncoding, NSMacOSRomanStringEncoding,
+    NSShiftJISStringEncoding, NSStringEncoding, NSUTF16StringEncoding, NSUTF8StringEncoding,
+    NSWindowsCP1252StringEncoding,
+};
+use crate::frameworks::foundation::NSUInteger;
+use crate::mem::{ConstPtr, MutPtr};
+use crate::objc::{id, msg, msg_class};
+use crate::Environment;
 
 pub type CFStringRef = super::CFTypeRef;
+pub type CFMutableStringRef = CFStringRef;
+
+pub type CFStringEncoding = u32;
+pub const kCFStringEncodingMacRoman: CFStringEncoding = 0;
+pub const kCFStringEncodingWindowsLatin1: CFStringEncoding = 0x500;
+pub const kCFStringEncodingISOLatin1: CFStringEncoding = 0x201;
+pub const kCFStringEncodingASCII: CFStringEncoding = 0x600;
+pub const kCFStringEncodingUnicode: CFStringEncoding = 0x100;
+pub const kCFStringEncodingUTF8: CFStringEncoding = 0x8000100;
+pub const kCFStringEncodingDOSJapanese: CFStringEncoding = 0x421;
+
+pub type CFStringNormalizationForm = CFIndex;
+pub const kCFStringNormalizationFormD: CFStringNormalizationForm = 0;
+pub const kCFStringNormalizationFormKD: CFStringNormalizationForm = 1;
+pub const kCFStringNormalizationFormC: CFStringNormalizationForm = 2;
+pub const kCFStringNormalizationFormKC: CFStringNormalizationForm = 3;
+
+pub const kCFStringTransformStripCombiningMarks: &str = "NFD; [:nonspacing mark:]Remove; NFC";
+pub const kCFStringTransformToLatin: &str = "Any-Latin";
+pub const kCFStringTransformFullwidthHalfwidth: &str = "Fullwidth-Halfwidth";
+pub const kCFStringTransformHiraganaKatakana: &str = "Hiragana-Katakana";
+pub const kCFStringTransformToXMLHex: &str = "Any-Hex/XML";
+pub const kCFStringTransformStripDiacritics: &str = "NFD; [:Nonspacing Mark:] Remove; NFC";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCFStringTransformStripCombiningMarks",
+        HostConstant::NSString(kCFStringTransformStripCombiningMarks),
+    ),
+    (
+        "_kCFStringTransformToLatin",
+        HostConstant::NSString(kCFStringTransformToLatin),
+    ),
+    (
+        "_kCFStringTransformFullwidthHalfwidth",
+        HostConstant::NSString(kCFStringTransformFullwidthHalfwidth),
+    ),
+    (
+        "_kCFStringTransformHiraganaKatakana",
+        HostConstant::NSString(kCFStringTransformHiraganaKatakana),
+    ),
+    (
+        "_kCFStringTransformToXMLHex",
+        HostConstant::NSString(kCFStringTransformToXMLHex),
+    ),
+    (
+        "_kCFStringTransformStripDiacritics",
+        HostConstant::NSString(kCFStringTransformStripDiacritics),
+    ),
+];
+
+/// `NSStringEncoding` values with the high bit set are just
+/// `CFStringEncoding` values with that bit added.
+const NS_STRING_ENCODING_CF_BIT: NSStringEncoding = 0x80000000;
+
+pub fn CFStringConvertEncodingToNSStringEncoding(
+    _env: &mut Environment,
+    encoding: CFStringEncoding,
+) -> NSStringEncoding {
+    match encoding {
+        kCFStringEncodingMacRoman => NSMacOSRomanStringEncoding,
+        kCFStringEncodingWindowsLatin1 => NSWindowsCP1252StringEncoding,
+        kCFStringEncodingISOLatin1 => NSISOLatin1StringEncoding,
+        kCFStringEncodingASCII => NSASCIIStringEncoding,
+        kCFStringEncodingUnicode => NSUTF16StringEncoding,
+        kCFStringEncodingUTF8 => NSUTF8StringEncoding,
+        kCFStringEncodingDOSJapanese => NSShiftJISStringEncoding,
+        _ => encoding | NS_STRING_ENCODING_CF_BIT,
+    }
+}
+
+pub fn CFStringConvertNSStringEncodingToEncoding(
+    _env: &mut Environment,
+    encoding: NSStringEncoding,
+) -> CFStringEncoding {
+    match encoding {
+        NSMacOSRomanStringEncoding => kCFStringEncodingMacRoman,
+        NSWindowsCP1252StringEncoding => kCFStringEncodingWindowsLatin1,
+        NSISOLatin1StringEncoding => kCFStringEncodingISOLatin1,
+        NSASCIIStringEncoding => kCFStringEncodingASCII,
+        NSUTF16StringEncoding => kCFStringEncodingUnicode,
+        NSUTF8StringEncoding => kCFStringEncodingUTF8,
+        NSShiftJISStringEncoding => kCFStringEncodingDOSJapanese,
+        _ if encoding & NS_STRING_ENCODING_CF_BIT != 0 => encoding & !NS_STRING_ENCODING_CF_BIT,
+        _ => unimplemented!("NSStringEncoding {:#x}", encoding),
+    }
+}
+
+pub fn CFStringCreateWithBytes(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    bytes: ConstPtr<u8>,
+    num_bytes: CFIndex,
+    encoding: CFStringEncoding,
+    is_external_representation: bool,
+) -> CFStringRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    assert!(!is_external_representation); // unimplemented
+
+    let num_bytes: NSUInteger = num_bytes.try_into().unwrap();
+    let encoding = CFStringConvertEncodingToNSStringEncoding(env, encoding);
+
+    let string: id = msg_class![env; NSString alloc];
+    msg![env; string initWithBytes:bytes length:num_bytes encoding:encoding]
+}
+
+pub fn CFStringGetCString(
+    env: &mut Environment,
+    string: CFStringRef,
+    buffer: MutPtr<u8>,
+    buffer_size: CFIndex,
+    encoding: CFStringEncoding,
+) -> bool {
+    let buffer_size: NSUInteger = buffer_size.try_into().unwrap();
+    let encoding = CFStringConvertEncodingToNSStringEncoding(env, encoding);
+
+    msg![env; string getCString:buffer maxLength:buffer_size encoding:encoding]
+}
+
+pub fn CFStringCreateMutableCopy(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    max_length: CFIndex,
+    string: CFStringRef,
+) -> CFMutableStringRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    assert!(max_length == 0); // unimplemented
+
+    let contents = ns_string::to_rust_string(env, string).into_owned();
+    ns_string::from_rust_string(env, contents)
+}
+
+pub fn CFStringNormalize(
+    env: &mut Environment,
+    string: CFMutableStringRef,
+    form: CFStringNormalizationForm,
+) {
+    let form = match form {
+        kCFStringNormalizationFormD => NormalizationForm::D,
+        kCFStringNormalizationFormKD => NormalizationForm::KD,
+        kCFStringNormalizationFormC => NormalizationForm::C,
+        kCFStringNormalizationFormKC => NormalizationForm::KC,
+        _ => panic!("Unknown CFStringNormalizationForm {}", form),
+    };
+    let normalized = normalize(&ns_string::to_rust_string(env, string), form);
+    ns_string::replace_contents(env, string, normalized);
+}
+
+/// Apply a single step of an ICU transform ID, e.g. `Any-Latin`. Returns
+/// [None] if the step isn't supported.
+fn apply_transform_step(string: &str, step: &str, reverse: bool) -> Option<String> {
+    // ICU IDs are case-insensitive and whitespace is ignored.
+    let step: String = step
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    Some(match (step.as_str(), reverse) {
+        ("nfd", false) | ("nfc", true) | ("nfkc", true) => normalize(string, NormalizationForm::D),
+        ("nfc", false) | ("nfd", true) | ("nfkd", true) => normalize(string, NormalizationForm::C),
+        ("nfkd", false) => normalize(string, NormalizationForm::KD),
+        ("nfkc", false) => normalize(string, NormalizationForm::KC),
+        ("[:nonspacingmark:]remove" | "[:mn:]remove", false) => {
+            transliteration::strip_combining_marks(string)
+        }
+        // The inverse of removing something is doing nothing.
+        ("[:nonspacingmark:]remove" | "[:mn:]remove", true) => string.to_string(),
+        (
+            "any-latin" | "greek-latin" | "cyrillic-latin" | "hiragana-latin" | "katakana-latin"
+            | "hangul-latin",
+            false,
+        ) => transliteration::to_latin(string),
+        ("latin-ascii", false) => transliteration::to_ascii(string),
+        ("hiragana-katakana", _) => transliteration::hiragana_to_katakana(string, reverse),
+        ("fullwidth-halfwidth" | "fullwidthhalfwidth", _) => {
+            transliteration::fullwidth_to_halfwidth(string, reverse)
+        }
+        ("any-hex/xml", false) => string
+            .chars()
+            .map(|c| format!("&#x{:X};", c as u32))
+            .collect(),
+        _ => return None,
+    })
+}
+
+pub fn CFStringTransform(
+    env: &mut Environment,
+    string: CFMutableStringRef,
+    range: MutPtr<CFRange>,
+    transform: CFStringRef,
+    reverse: bool,
+) -> bool {
+    let transform = ns_string::to_rust_string(env, transform).into_owned();
+    let utf16: Vec<u16> = ns_string::to_rust_string(env, string)
+        .encode_utf16()
+        .collect();
+    let (start, end) = if range.is_null() {
+        (0, utf16.len())
+    } else {
+        let CFRange { location, length } = env.mem.read(range);
+        let start: usize = location.try_into().unwrap();
+        let length: usize = length.try_into().unwrap();
+        (start, start + length)
+    };
+
+    let mut result = String::from_utf16(&utf16[start..end]).unwrap();
+    let mut steps: Vec<&str> = transform
+        .split(';')
+        .filter(|s| !s.trim().is_empty())
+        .collect();
+    if reverse {
+        steps.reverse();
+    }
+    for step in steps {
+        let Some(transformed) = apply_transform_step(&result, step, reverse) else {
+            log!(
+                "TODO: CFStringTransform() with transform {:?} (reverse: {}), ignoring",
+                transform,
+                reverse
+            );
+            return false;
+        };
+        result = transformed;
+    }
+
+    let result: Vec<u16> = result.encode_utf16().collect();
+    if !range.is_null() {
+        let length = result.len().try_into().unwrap();
+        env.mem.write(
+            range,
+            CFRange {
+                location: start.try_into().unwrap(),
+                length,
+            },
+        );
+    }
+    let new_contents = [&utf16[..start], &result[..], &utf16[end..]].concat();
+    let new_contents = String::from_utf16(&new_contents).unwrap();
+    ns_string::replace_contents(env, string, new_contents);
+    true
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFStringConvertEncodingToNSStringEncoding(_)),
+    export_c_func!(CFStringConvertNSStringEncodingToEncoding(_)),
+    export_c_func!(CFStringCreateWithBytes(_, _, _, _, _)),
+    export_c_func!(CFStringGetCString(_, _, _, _)),
+    export_c_func!(CFStringCreateMutableCopy(_, _, _)),
+    export_c_func!(CFStringNormalize(_, _)),
+    export_c_func!(CFStringTransform(_, _, _, _)),
+];
@@ -0,0 +1,259 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFTree`.
+//!
+//! Each tree node holds some app-defined `info` and its children, which it
+//! retains. Parents are not retained by their children.
+
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::cf_run_loop::{release_info, retain_info};
+use super::{CFComparisonResult, CFIndex, CFTypeRef};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::mem::{ConstPtr, MutPtr, MutVoidPtr, SafeRead};
+use crate::objc::{nil, objc_classes, release, retain, ClassExports, HostObject};
+use crate::Environment;
+
+pub type CFTreeRef = CFTypeRef;
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct CFTreeContext {
+    version: CFIndex,
+    info: MutVoidPtr,
+    /// `const void *(*)(const void *)`, may be `NULL`.
+    retain: GuestFunction,
+    /// `void (*)(const void *)`, may be `NULL`.
+    release: GuestFunction,
+    /// `CFStringRef (*)(const void *)`, may be `NULL`.
+    copy_description: GuestFunction,
+}
+unsafe impl SafeRead for CFTreeContext {}
+
+struct CFTreeHostObject {
+    /// The `info` is retained.
+    context: CFTreeContext,
+    /// Weak reference.
+    parent: CFTreeRef,
+    /// Strong references.
+    children: Vec<CFTreeRef>,
+}
+impl HostObject for CFTreeHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CFTree is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not exposed.
+@implementation _touchHLE_CFTree: NSObject
+
+- (())dealloc {
+    CFTreeRemoveAllChildren(env, this);
+    let context = env.objc.borrow::<CFTreeHostObject>(this).context;
+    release_info(env, context.release, context.info);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+fn host_object(env: &mut Environment, tree: CFTreeRef) -> &mut CFTreeHostObject {
+    env.objc.borrow_mut(tree)
+}
+
+/// Read a context and retain its `info`.
+fn read_context(env: &mut Environment, context: ConstPtr<CFTreeContext>) -> CFTreeContext {
+    let mut context = env.mem.read(context);
+    assert!(context.version == 0);
+    context.info = retain_info(env, context.retain, context.info);
+    context
+}
+
+/// Position of a tree among its parent's children.
+fn index_in_parent(env: &mut Environment, tree: CFTreeRef) -> Option<(CFTreeRef, usize)> {
+    let parent = host_object(env, tree).parent;
+    if parent == nil {
+        return None;
+    }
+    let idx = host_object(env, parent)
+        .children
+        .iter()
+        .position(|&child| child == tree)
+        .unwrap();
+    Some((parent, idx))
+}
+
+/// Add a parentless tree as a child at some index.
+fn insert_child(env: &mut Environment, tree: CFTreeRef, idx: usize, new_child: CFTreeRef) {
+    assert!(host_object(env, new_child).parent == nil);
+    retain(env, new_child);
+    host_object(env, new_child).parent = tree;
+    host_object(env, tree).children.insert(idx, new_child);
+}
+
+fn CFTreeCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    context: ConstPtr<CFTreeContext>,
+) -> CFTreeRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let context = read_context(env, context);
+    let host_object = Box::new(CFTreeHostObject {
+        context,
+        parent: nil,
+        children: Vec::new(),
+    });
+    let class = env.objc.get_known_class("_touchHLE_CFTree", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+fn CFTreeGetContext(env: &mut Environment, tree: CFTreeRef, context: MutPtr<CFTreeContext>) {
+    let tree_context = host_object(env, tree).context;
+    env.mem.write(context, tree_context);
+}
+
+fn CFTreeSetContext(env: &mut Environment, tree: CFTreeRef, context: ConstPtr<CFTreeContext>) {
+    let new_context = read_context(env, context);
+    let old_context = std::mem::replace(&mut host_object(env, tree).context, new_context);
+    release_info(env, old_context.release, old_context.info);
+}
+
+fn CFTreeGetParent(env: &mut Environment, tree: CFTreeRef) -> CFTreeRef {
+    host_object(env, tree).parent
+}
+
+fn CFTreeGetNextSibling(env: &mut Environment, tree: CFTreeRef) -> CFTreeRef {
+    let Some((parent, idx)) = index_in_parent(env, tree) else {
+        return nil;
+    };
+    let siblings = &host_object(env, parent).children;
+    siblings.get(idx + 1).copied().unwrap_or(nil)
+}
+
+fn CFTreeGetFirstChild(env: &mut Environment, tree: CFTreeRef) -> CFTreeRef {
+    host_object(env, tree)
+        .children
+        .first()
+        .copied()
+        .unwrap_or(nil)
+}
+
+fn CFTreeGetChildAtIndex(env: &mut Environment, tree: CFTreeRef, idx: CFIndex) -> CFTreeRef {
+    let idx: usize = idx.try_into().unwrap();
+    host_object(env, tree).children[idx]
+}
+
+fn CFTreeGetChildCount(env: &mut Environment, tree: CFTreeRef) -> CFIndex {
+    host_object(env, tree).children.len().try_into().unwrap()
+}
+
+fn CFTreeGetChildren(env: &mut Environment, tree: CFTreeRef, children: MutPtr<CFTreeRef>) {
+    let tree_children = host_object(env, tree).children.clone();
+    for (i, child) in tree_children.into_iter().enumerate() {
+        env.mem.write(children + i.try_into().unwrap(), child);
+    }
+}
+
+fn CFTreeFindRoot(env: &mut Environment, tree: CFTreeRef) -> CFTreeRef {
+    let mut root = tree;
+    loop {
+        let parent = host_object(env, root).parent;
+        if parent == nil {
+            return root;
+        }
+        root = parent;
+    }
+}
+
+fn CFTreeAppendChild(env: &mut Environment, tree: CFTreeRef, new_child: CFTreeRef) {
+    let idx = host_object(env, tree).children.len();
+    insert_child(env, tree, idx, new_child);
+}
+
+fn CFTreePrependChild(env: &mut Environment, tree: CFTreeRef, new_child: CFTreeRef) {
+    insert_child(env, tree, 0, new_child);
+}
+
+fn CFTreeInsertSibling(env: &mut Environment, tree: CFTreeRef, new_sibling: CFTreeRef) {
+    let (parent, idx) = index_in_parent(env, tree).unwrap();
+    insert_child(env, parent, idx + 1, new_sibling);
+}
+
+fn CFTreeRemove(env: &mut Environment, tree: CFTreeRef) {
+    let Some((parent, idx)) = index_in_parent(env, tree) else {
+        return;
+    };
+    host_object(env, parent).children.remove(idx);
+    host_object(env, tree).parent = nil;
+    release(env, tree);
+}
+
+fn CFTreeRemoveAllChildren(env: &mut Environment, tree: CFTreeRef) {
+    let children = std::mem::take(&mut host_object(env, tree).children);
+    for child in children {
+        host_object(env, child).parent = nil;
+        release(env, child);
+    }
+}
+
+fn CFTreeApplyFunctionToChildren(
+    env: &mut Environment,
+    tree: CFTreeRef,
+    applier: GuestFunction, // CFTreeApplierFunction
+    context: MutVoidPtr,
+) {
+    let children = host_object(env, tree).children.clone();
+    for child in children {
+        let () = applier.call_from_host(env, (child, context));
+    }
+}
+
+fn CFTreeSortChildren(
+    env: &mut Environment,
+    tree: CFTreeRef,
+    comparator: GuestFunction, // CFComparatorFunction
+    context: MutVoidPtr,
+) {
+    // The comparator is called with the children's info values.
+    let mut children: Vec<(CFTreeRef, MutVoidPtr)> = host_object(env, tree)
+        .children
+        .clone()
+        .into_iter()
+        .map(|child| {
+            (
+                child,
+                env.objc.borrow::<CFTreeHostObject>(child).context.info,
+            )
+        })
+        .collect();
+    children.sort_by(|&(_, a), &(_, b)| {
+        let result: CFComparisonResult = comparator.call_from_host(env, (a, b, context));
+        result.cmp(&0)
+    });
+    host_object(env, tree).children = children.into_iter().map(|(child, _)| child).collect();
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFTreeCreate(_, _)),
+    export_c_func!(CFTreeGetContext(_, _)),
+    export_c_func!(CFTreeSetContext(_, _)),
+    export_c_func!(CFTreeGetParent(_)),
+    export_c_func!(CFTreeGetNextSibling(_)),
+    export_c_func!(CFTreeGetFirstChild(_)),
+    export_c_func!(CFTreeGetChildAtIndex(_, _)),
+    export_c_func!(CFTreeGetChildCount(_)),
+    export_c_func!(CFTreeGetChildren(_, _)),
+    export_c_func!(CFTreeFindRoot(_)),
+    export_c_func!(CFTreeAppendChild(_, _)),
+    export_c_func!(CFTreePrependChild(_, _)),
+    export_c_func!(CFTreeInsertSibling(_, _)),
+    export_c_func!(CFTreeRemove(_)),
+    export_c_func!(CFTreeRemoveAllChildren(_)),
+    export_c_func!(CFTreeApplyFunctionToChildren(_, _, _)),
+    export_c_func!(CFTreeSortChildren(_, _, _)),
+];
@@ -53,7 +53,26 @@ pub fn CFURLCreateFromFileSystemRepresentation(
     msg![env; url initFileURLWithPath:string isDirectory:is_directory]
 }
 
+pub fn CFURLCreateWithString(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    url_string: id, // CFStringRef
+    base_url: CFURLRef,
+) -> CFURLRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    assert!(base_url.is_null()); // unimplemented
+
+    let url: id = msg_class![env; NSURL alloc];
+    msg![env; url initWithString:url_string]
+}
+
+pub fn CFURLGetString(env: &mut Environment, url: CFURLRef) -> id {
+    msg![env; url absoluteString]
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFURLGetFileSystemRepresentation(_, _, _, _)),
     export_c_func!(CFURLCreateFromFileSystemRepresentation(_, _, _, _)),
+    export_c_func!(CFURLCreateWithString(_, _, _)),
+    export_c_func!(CFURLGetString(_)),
 ];
@@ -0,0 +1,145 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFUUID`.
+//!
+//! UUIDs are generated the same way as for `NSUUID`, see
+//! [crate::frameworks::foundation::ns_uuid].
+
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::cf_string::CFStringRef;
+use super::CFTypeRef;
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::foundation::ns_uuid::{self, UUIDBytes};
+use crate::frameworks::foundation::{ns_string, NSUInteger};
+use crate::mem::SafeRead;
+use crate::objc::{id, msg, msg_class, nil, objc_classes, Class, ClassExports, HostObject};
+use crate::Environment;
+
+pub type CFUUIDRef = CFTypeRef;
+
+/// `CFUUIDBytes`, a UUID passed by value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(C, packed)]
+pub struct CFUUIDBytes {
+    pub bytes: UUIDBytes,
+}
+unsafe impl SafeRead for CFUUIDBytes {}
+impl_GuestRet_for_large_struct!(CFUUIDBytes);
+impl GuestArg for CFUUIDBytes {
+    const REG_COUNT: usize = 4;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        let mut bytes: UUIDBytes = [0; 16];
+        for (chunk, &reg) in bytes.chunks_exact_mut(4).zip(regs) {
+            chunk.copy_from_slice(&reg.to_le_bytes());
+        }
+        CFUUIDBytes { bytes }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        let bytes = self.bytes;
+        for (reg, chunk) in regs.iter_mut().zip(bytes.chunks_exact(4)) {
+            *reg = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+    }
+}
+
+struct CFUUIDHostObject {
+    bytes: UUIDBytes,
+}
+impl HostObject for CFUUIDHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CFUUID is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not exposed.
+@implementation _touchHLE_CFUUID: NSObject
+
+- (NSUInteger)hash {
+    let bytes = env.objc.borrow::<CFUUIDHostObject>(this).bytes;
+    NSUInteger::from_le_bytes(bytes[12..].try_into().unwrap())
+}
+- (bool)isEqual:(id)other {
+    if this == other {
+        return true;
+    }
+    let class: Class = msg_class![env; _touchHLE_CFUUID class];
+    if other == nil || !msg![env; other isKindOfClass:class] {
+        return false;
+    }
+    let a = env.objc.borrow::<CFUUIDHostObject>(this).bytes;
+    let b = env.objc.borrow::<CFUUIDHostObject>(other).bytes;
+    a == b
+}
+
+@end
+
+};
+
+fn new_uuid(env: &mut Environment, bytes: UUIDBytes) -> CFUUIDRef {
+    let host_object = Box::new(CFUUIDHostObject { bytes });
+    let class = env.objc.get_known_class("_touchHLE_CFUUID", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+fn CFUUIDCreate(env: &mut Environment, allocator: CFAllocatorRef) -> CFUUIDRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let bytes = ns_uuid::generate(env);
+    new_uuid(env, bytes)
+}
+
+fn CFUUIDCreateFromUUIDBytes(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    bytes: CFUUIDBytes,
+) -> CFUUIDRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    new_uuid(env, bytes.bytes)
+}
+
+fn CFUUIDCreateFromString(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    string: CFStringRef,
+) -> CFUUIDRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let string = ns_string::to_rust_string(env, string);
+    match ns_uuid::from_string(&string) {
+        Some(bytes) => new_uuid(env, bytes),
+        None => {
+            log!(
+                "Warning: CFUUIDCreateFromString() with invalid UUID {:?}",
+                string
+            );
+            nil
+        }
+    }
+}
+
+fn CFUUIDCreateString(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    uuid: CFUUIDRef,
+) -> CFStringRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let bytes = env.objc.borrow::<CFUUIDHostObject>(uuid).bytes;
+    ns_string::from_rust_string(env, ns_uuid::to_string(&bytes))
+}
+
+fn CFUUIDGetUUIDBytes(env: &mut Environment, uuid: CFUUIDRef) -> CFUUIDBytes {
+    let bytes = env.objc.borrow::<CFUUIDHostObject>(uuid).bytes;
+    CFUUIDBytes { bytes }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFUUIDCreate(_)),
+    export_c_func!(CFUUIDCreateFromUUIDBytes(_, _)),
+    export_c_func!(CFUUIDCreateFromString(_, _)),
+    export_c_func!(CFUUIDCreateString(_, _)),
+    export_c_func!(CFUUIDGetUUIDBytes(_)),
+];
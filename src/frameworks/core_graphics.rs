@@ -5,11 +5,23 @@
  */
 //! The Core Graphics framework.
 
+pub mod cg_affine_transform;
 pub mod cg_bitmap_context;
+pub mod cg_color;
 pub mod cg_color_space;
 pub mod cg_context;
+pub mod cg_data_provider;
+pub mod cg_function;
 mod cg_geometry;
+pub mod cg_gradient;
 pub mod cg_image;
+pub mod cg_path;
+pub mod cg_pattern;
+pub mod cg_pdf_document;
+pub mod cg_pdf_page;
+pub mod cg_shading;
+mod pdf;
+mod rasterizer;
 
 pub type CGFloat = f32;
 
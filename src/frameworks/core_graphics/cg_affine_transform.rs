@@ -0,0 +1,290 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGAffineTransform.h`
+
+use super::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::mem::{ConstVoidPtr, Mem, SafeRead};
+use crate::Environment;
+
+/// A 3×3 matrix with an implied last column of (0, 0, 1), applied to row
+/// vectors: `(x', y') = (a*x + c*y + tx, b*x + d*y + ty)`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C, packed)]
+pub struct CGAffineTransform {
+    pub a: CGFloat,
+    pub b: CGFloat,
+    pub c: CGFloat,
+    pub d: CGFloat,
+    pub tx: CGFloat,
+    pub ty: CGFloat,
+}
+unsafe impl SafeRead for CGAffineTransform {}
+impl_GuestRet_for_large_struct!(CGAffineTransform);
+impl GuestArg for CGAffineTransform {
+    const REG_COUNT: usize = 6;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        CGAffineTransform {
+            a: GuestArg::from_regs(&regs[0..1]),
+            b: GuestArg::from_regs(&regs[1..2]),
+            c: GuestArg::from_regs(&regs[2..3]),
+            d: GuestArg::from_regs(&regs[3..4]),
+            tx: GuestArg::from_regs(&regs[4..5]),
+            ty: GuestArg::from_regs(&regs[5..6]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.a.to_regs(&mut regs[0..1]);
+        self.b.to_regs(&mut regs[1..2]);
+        self.c.to_regs(&mut regs[2..3]);
+        self.d.to_regs(&mut regs[3..4]);
+        self.tx.to_regs(&mut regs[4..5]);
+        self.ty.to_regs(&mut regs[5..6]);
+    }
+}
+
+pub const CGAffineTransformIdentity: CGAffineTransform = CGAffineTransform {
+    a: 1.0,
+    b: 0.0,
+    c: 0.0,
+    d: 1.0,
+    tx: 0.0,
+    ty: 0.0,
+};
+
+impl CGAffineTransform {
+    pub fn is_identity(&self) -> bool {
+        *self == CGAffineTransformIdentity
+    }
+
+    /// `self` followed by `other`.
+    pub fn concat(self, other: CGAffineTransform) -> CGAffineTransform {
+        let CGAffineTransform { a, b, c, d, tx, ty } = self;
+        CGAffineTransform {
+            a: a * other.a + b * other.c,
+            b: a * other.b + b * other.d,
+            c: c * other.a + d * other.c,
+            d: c * other.b + d * other.d,
+            tx: tx * other.a + ty * other.c + other.tx,
+            ty: tx * other.b + ty * other.d + other.ty,
+        }
+    }
+
+    /// Returns the transform unchanged if it isn't invertible, like
+    /// `CGAffineTransformInvert`.
+    pub fn invert(self) -> CGAffineTransform {
+        let CGAffineTransform { a, b, c, d, tx, ty } = self;
+        let determinant = a * d - b * c;
+        if determinant == 0.0 {
+            return self;
+        }
+        CGAffineTransform {
+            a: d / determinant,
+            b: -b / determinant,
+            c: -c / determinant,
+            d: a / determinant,
+            tx: (c * ty - d * tx) / determinant,
+            ty: (b * tx - a * ty) / determinant,
+        }
+    }
+
+    pub fn apply_to_point(&self, point: CGPoint) -> CGPoint {
+        CGPoint {
+            x: self.a * point.x + self.c * point.y + self.tx,
+            y: self.b * point.x + self.d * point.y + self.ty,
+        }
+    }
+
+    pub fn apply_to_size(&self, size: CGSize) -> CGSize {
+        CGSize {
+            width: self.a * size.width + self.c * size.height,
+            height: self.b * size.width + self.d * size.height,
+        }
+    }
+
+    /// The smallest rectangle containing the transformed corners of `rect`.
+    pub fn apply_to_rect(&self, rect: CGRect) -> CGRect {
+        bounding_rect(rect, |corner| self.apply_to_point(corner))
+    }
+}
+
+/// The smallest rectangle containing the corners of `rect` after they have
+/// been passed through `map`.
+pub fn bounding_rect<F>(rect: CGRect, mut map: F) -> CGRect
+where
+    F: FnMut(CGPoint) -> CGPoint,
+{
+    let CGRect { origin, size } = rect;
+    let corners = [
+        (origin.x, origin.y),
+        (origin.x + size.width, origin.y),
+        (origin.x, origin.y + size.height),
+        (origin.x + size.width, origin.y + size.height),
+    ]
+    .map(|(x, y)| map(CGPoint { x, y }));
+    let min_x = corners.iter().map(|p| p.x).fold(f32::INFINITY, f32::min);
+    let min_y = corners.iter().map(|p| p.y).fold(f32::INFINITY, f32::min);
+    let max_x = corners
+        .iter()
+        .map(|p| p.x)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let max_y = corners
+        .iter()
+        .map(|p| p.y)
+        .fold(f32::NEG_INFINITY, f32::max);
+    CGRect {
+        origin: CGPoint { x: min_x, y: min_y },
+        size: CGSize {
+            width: max_x - min_x,
+            height: max_y - min_y,
+        },
+    }
+}
+
+pub fn CGAffineTransformMake(
+    _env: &mut Environment,
+    a: CGFloat,
+    b: CGFloat,
+    c: CGFloat,
+    d: CGFloat,
+    tx: CGFloat,
+    ty: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransform { a, b, c, d, tx, ty }
+}
+pub fn CGAffineTransformMakeRotation(_env: &mut Environment, angle: CGFloat) -> CGAffineTransform {
+    let (sin, cos) = angle.sin_cos();
+    CGAffineTransform {
+        a: cos,
+        b: sin,
+        c: -sin,
+        d: cos,
+        tx: 0.0,
+        ty: 0.0,
+    }
+}
+pub fn CGAffineTransformMakeScale(
+    _env: &mut Environment,
+    sx: CGFloat,
+    sy: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransform {
+        a: sx,
+        b: 0.0,
+        c: 0.0,
+        d: sy,
+        tx: 0.0,
+        ty: 0.0,
+    }
+}
+pub fn CGAffineTransformMakeTranslation(
+    _env: &mut Environment,
+    tx: CGFloat,
+    ty: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransform {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        tx,
+        ty,
+    }
+}
+
+fn CGAffineTransformRotate(
+    env: &mut Environment,
+    t: CGAffineTransform,
+    angle: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransformMakeRotation(env, angle).concat(t)
+}
+fn CGAffineTransformScale(
+    env: &mut Environment,
+    t: CGAffineTransform,
+    sx: CGFloat,
+    sy: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransformMakeScale(env, sx, sy).concat(t)
+}
+fn CGAffineTransformTranslate(
+    env: &mut Environment,
+    t: CGAffineTransform,
+    tx: CGFloat,
+    ty: CGFloat,
+) -> CGAffineTransform {
+    CGAffineTransformMakeTranslation(env, tx, ty).concat(t)
+}
+fn CGAffineTransformConcat(
+    _env: &mut Environment,
+    t1: CGAffineTransform,
+    t2: CGAffineTransform,
+) -> CGAffineTransform {
+    t1.concat(t2)
+}
+fn CGAffineTransformInvert(_env: &mut Environment, t: CGAffineTransform) -> CGAffineTransform {
+    t.invert()
+}
+fn CGAffineTransformIsIdentity(_env: &mut Environment, t: CGAffineTransform) -> bool {
+    t.is_identity()
+}
+fn CGAffineTransformEqualToTransform(
+    _env: &mut Environment,
+    t1: CGAffineTransform,
+    t2: CGAffineTransform,
+) -> bool {
+    t1 == t2
+}
+
+fn CGPointApplyAffineTransform(
+    _env: &mut Environment,
+    point: CGPoint,
+    t: CGAffineTransform,
+) -> CGPoint {
+    t.apply_to_point(point)
+}
+fn CGSizeApplyAffineTransform(
+    _env: &mut Environment,
+    size: CGSize,
+    t: CGAffineTransform,
+) -> CGSize {
+    t.apply_to_size(size)
+}
+fn CGRectApplyAffineTransform(
+    _env: &mut Environment,
+    rect: CGRect,
+    t: CGAffineTransform,
+) -> CGRect {
+    t.apply_to_rect(rect)
+}
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_CGAffineTransformIdentity",
+    HostConstant::Custom(|mem: &mut Mem| -> ConstVoidPtr {
+        mem.alloc_and_write(CGAffineTransformIdentity)
+            .cast()
+            .cast_const()
+    }),
+)];
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGAffineTransformMake(_, _, _, _, _, _)),
+    export_c_func!(CGAffineTransformMakeRotation(_)),
+    export_c_func!(CGAffineTransformMakeScale(_, _)),
+    export_c_func!(CGAffineTransformMakeTranslation(_, _)),
+    export_c_func!(CGAffineTransformRotate(_, _)),
+    export_c_func!(CGAffineTransformScale(_, _, _)),
+    export_c_func!(CGAffineTransformTranslate(_, _, _)),
+    export_c_func!(CGAffineTransformConcat(_, _)),
+    export_c_func!(CGAffineTransformInvert(_)),
+    export_c_func!(CGAffineTransformIsIdentity(_)),
+    export_c_func!(CGAffineTransformEqualToTransform(_, _)),
+    export_c_func!(CGPointApplyAffineTransform(_, _)),
+    export_c_func!(CGSizeApplyAffineTransform(_, _)),
+    export_c_func!(CGRectApplyAffineTransform(_, _)),
+];
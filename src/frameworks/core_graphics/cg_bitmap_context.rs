@@ -6,16 +6,27 @@
 //! `CGBitmapContext.h`
 
 use super::cg_color_space::{kCGColorSpaceGenericRGB, CGColorSpaceHostObject, CGColorSpaceRef};
-use super::cg_context::{CGContextHostObject, CGContextRef, CGContextSubclass};
+use super::cg_context::{
+    kCGBlendModeClear, kCGBlendModeColor, kCGBlendModeColorBurn, kCGBlendModeColorDodge,
+    kCGBlendModeCopy, kCGBlendModeDarken, kCGBlendModeDestinationAtop, kCGBlendModeDestinationIn,
+    kCGBlendModeDestinationOut, kCGBlendModeDestinationOver, kCGBlendModeDifference,
+    kCGBlendModeExclusion, kCGBlendModeHardLight, kCGBlendModeHue, kCGBlendModeLighten,
+    kCGBlendModeLuminosity, kCGBlendModeMultiply, kCGBlendModeNormal, kCGBlendModeOverlay,
+    kCGBlendModePlusDarker, kCGBlendModePlusLighter, kCGBlendModeSaturation, kCGBlendModeScreen,
+    kCGBlendModeSoftLight, kCGBlendModeSourceAtop, kCGBlendModeSourceIn, kCGBlendModeSourceOut,
+    kCGBlendModeXOR, CGBlendMode, CGContextHostObject, CGContextRef, CGContextSubclass,
+};
 use super::cg_image::{
-    kCGImageAlphaFirst, kCGImageAlphaLast, kCGImageAlphaNone, kCGImageAlphaNoneSkipFirst,
+    self, kCGImageAlphaFirst, kCGImageAlphaLast, kCGImageAlphaNone, kCGImageAlphaNoneSkipFirst,
     kCGImageAlphaNoneSkipLast, kCGImageAlphaOnly, kCGImageAlphaPremultipliedFirst,
-    kCGImageAlphaPremultipliedLast, CGImageAlphaInfo,
+    kCGImageAlphaPremultipliedLast, CGImageAlphaInfo, CGImageRef,
 };
-use super::{CGFloat, CGRect};
+use super::rasterizer::Mask;
+use super::CGFloat;
 use crate::dyld::{export_c_func, FunctionExports};
+use crate::image::Image;
 use crate::mem::{GuestUSize, Mem, MutVoidPtr};
-use crate::objc::ObjC;
+use crate::objc::{nil, ObjC};
 use crate::Environment;
 
 #[derive(Copy, Clone)]
@@ -27,9 +38,17 @@ pub(super) struct CGBitmapContextData {
     bytes_per_row: GuestUSize,
     color_space: &'static str,
     alpha_info: CGImageAlphaInfo,
+    /// Whether `data` was allocated by `CGBitmapContextCreate` and must be
+    /// freed along with the context.
+    pub(super) owns_data: bool,
+}
+impl CGBitmapContextData {
+    pub(super) fn owned_data(&self) -> Option<MutVoidPtr> {
+        self.owns_data.then_some(self.data)
+    }
 }
 
-fn CGBitmapContextCreate(
+pub fn CGBitmapContextCreate(
     env: &mut Environment,
     data: MutVoidPtr,
     width: GuestUSize,
@@ -39,16 +58,30 @@ fn CGBitmapContextCreate(
     color_space: CGColorSpaceRef,
     bitmap_info: u32,
 ) -> CGContextRef {
-    assert!(!data.is_null()); // TODO: support memory allocation
     assert!(bits_per_component == 8); // TODO: support other bit depths
     assert!(components_for_rgb(bitmap_info).is_ok());
 
+    let bytes_per_row = if bytes_per_row == 0 {
+        width * components_for_rgb(bitmap_info).unwrap()
+    } else {
+        bytes_per_row
+    };
+    // If no buffer is provided, the context owns one. New allocations are
+    // zeroed, i.e. transparent black.
+    let owns_data = data.is_null();
+    let data = if owns_data {
+        env.mem
+            .alloc(height.checked_mul(bytes_per_row).unwrap().max(1))
+    } else {
+        data
+    };
+
     let color_space = env.objc.borrow::<CGColorSpaceHostObject>(color_space).name;
     // TODO: support other color spaces
     assert!(color_space == kCGColorSpaceGenericRGB);
 
-    let host_object = CGContextHostObject {
-        subclass: CGContextSubclass::CGBitmapContext(CGBitmapContextData {
+    let host_object =
+        CGContextHostObject::new(CGContextSubclass::CGBitmapContext(CGBitmapContextData {
             data,
             width,
             height,
@@ -56,10 +89,8 @@ fn CGBitmapContextCreate(
             bytes_per_row,
             color_space: kCGColorSpaceGenericRGB,
             alpha_info: bitmap_info,
-        }),
-        // TODO: is this the correct default?
-        rgb_fill_color: (0.0, 0.0, 0.0, 0.0),
-    };
+            owns_data,
+        }));
     let isa = env
         .objc
         .get_known_class("_touchHLE_CGContext", &mut env.mem);
@@ -180,10 +211,9 @@ impl CGBitmapContextDrawer<'_> {
         mem: &'a mut Mem,
         context: CGContextRef,
     ) -> CGBitmapContextDrawer<'a> {
-        let &CGContextHostObject {
-            subclass: CGContextSubclass::CGBitmapContext(bitmap_info),
-            rgb_fill_color,
-        } = objc.borrow(context);
+        let host_object = objc.borrow::<CGContextHostObject>(context);
+        let CGContextSubclass::CGBitmapContext(bitmap_info) = host_object.subclass;
+        let rgb_fill_color = host_object.gstate.fill_color;
 
         let pixels = get_pixels(&bitmap_info, mem);
 
@@ -209,23 +239,274 @@ impl CGBitmapContextDrawer<'_> {
     }
 }
 
-/// Implementation of `CGContextFillRect` for `CGBitmapContext`.
-pub(super) fn fill_rect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
-    let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
+/// Get a pixel as non-premultiplied RGBA.
+fn get_pixel(
+    data: &CGBitmapContextData,
+    pixels: &[u8],
+    (x, y): (GuestUSize, GuestUSize),
+) -> [u8; 4] {
+    let first_component_idx = (y * data.bytes_per_row + x * bytes_per_pixel(data)) as usize;
+    let p = &pixels[first_component_idx..];
+    let unpremultiply = |[r, g, b, a]: [u8; 4]| {
+        if a == 0 {
+            [0, 0, 0, 0]
+        } else {
+            let f = |c: u8| ((c as u32 * 255 + a as u32 / 2) / a as u32).min(255) as u8;
+            [f(r), f(g), f(b), a]
+        }
+    };
+    match data.alpha_info {
+        kCGImageAlphaNone => [p[0], p[1], p[2], 255],
+        kCGImageAlphaPremultipliedLast => unpremultiply([p[0], p[1], p[2], p[3]]),
+        kCGImageAlphaPremultipliedFirst => unpremultiply([p[1], p[2], p[3], p[0]]),
+        kCGImageAlphaLast => [p[0], p[1], p[2], p[3]],
+        kCGImageAlphaFirst => [p[1], p[2], p[3], p[0]],
+        kCGImageAlphaNoneSkipLast => [p[0], p[1], p[2], 255],
+        kCGImageAlphaNoneSkipFirst => [p[1], p[2], p[3], 255],
+        kCGImageAlphaOnly => [0, 0, 0, p[0]],
+        _ => unreachable!(), // checked by bytes_per_pixel
+    }
+}
 
-    // TODO: correct anti-aliasing
-    let x_start = (rect.origin.x.round() as GuestUSize).min(0);
-    let y_start = (rect.origin.y.round() as GuestUSize).min(0);
-    let x_end = ((rect.origin.x + rect.size.width).round() as GuestUSize).max(drawer.width());
-    let y_end = ((rect.origin.y + rect.size.height).round() as GuestUSize).max(drawer.height());
+/// Copy the contents of a bitmap context into a new [Image].
+pub fn to_image(env: &mut Environment, context: CGContextRef) -> Image {
+    let &CGContextHostObject {
+        subclass: CGContextSubclass::CGBitmapContext(data),
+        ..
+    } = env.objc.borrow(context);
+    let pixels = get_pixels(&data, &mut env.mem);
+    let mut rgba = Vec::with_capacity(data.width as usize * data.height as usize * 4);
+    for y in 0..data.height {
+        for x in 0..data.width {
+            rgba.extend_from_slice(&get_pixel(&data, pixels, (x, y)));
+        }
+    }
+    Image::from_pixels(rgba, (data.width, data.height))
+}
+
+/// Width and height of a bitmap context, i.e. the size of device space.
+pub(super) fn dimensions(env: &mut Environment, context: CGContextRef) -> (GuestUSize, GuestUSize) {
+    let &CGContextHostObject {
+        subclass: CGContextSubclass::CGBitmapContext(data),
+        ..
+    } = env.objc.borrow(context);
+    (data.width, data.height)
+}
+
+/// Composite a source through a coverage mask, applying the clip, alpha and
+/// blend mode of the context's graphics state. `source` gives the
+/// non-premultiplied RGBA color for each device pixel.
+pub(super) fn paint<F>(env: &mut Environment, context: CGContextRef, mask: &Mask, mut source: F)
+where
+    F: FnMut(GuestUSize, GuestUSize) -> (CGFloat, CGFloat, CGFloat, CGFloat),
+{
+    let host_object = env.objc.borrow::<CGContextHostObject>(context);
+    let CGContextSubclass::CGBitmapContext(data) = host_object.subclass;
+    let clip = host_object.gstate.clip.clone();
+    let alpha = host_object.gstate.alpha;
+    let blend_mode = host_object.gstate.blend_mode;
+    assert!(mask.width == data.width && mask.height == data.height);
 
-    let color = drawer.rgb_fill_color();
-    for y in y_start..y_end {
-        for x in x_start..x_end {
-            drawer.put_pixel((x as _, y as _), color)
+    let Some((x_start, y_start, width, height)) = mask.bounds() else {
+        return;
+    };
+    let pixels = get_pixels(&data, &mut env.mem);
+    for y in y_start..(y_start + height) {
+        for x in x_start..(x_start + width) {
+            let mut coverage = mask.get(x, y);
+            if let Some(ref clip) = clip {
+                coverage *= clip.get(x, y);
+            }
+            if coverage <= 0.0 {
+                continue;
+            }
+
+            let (r, g, b, a) = source(x, y);
+            let a = a * alpha;
+            let src = [r * a, g * a, b * a, a];
+            let dst = get_pixel(&data, pixels, (x, y)).map(|c| c as CGFloat / 255.0);
+            let dst = [dst[0] * dst[3], dst[1] * dst[3], dst[2] * dst[3], dst[3]];
+
+            // Partial coverage fades between the old and new pixel, which is
+            // right for every blend mode, not just normal source-over.
+            let blended = blend(blend_mode, src, dst);
+            let [r, g, b, a] =
+                [0, 1, 2, 3].map(|i| (dst[i] + (blended[i] - dst[i]) * coverage).clamp(0.0, 1.0));
+            let color = if a > 0.0 {
+                (r / a, g / a, b / a, a)
+            } else {
+                (0.0, 0.0, 0.0, 0.0)
+            };
+            put_pixel(&data, pixels, (x as i32, y as i32), color);
         }
     }
 }
 
-pub const FUNCTIONS: FunctionExports =
-    &[export_c_func!(CGBitmapContextCreate(_, _, _, _, _, _, _))];
+/// Blend premultiplied RGBA colors according to a `CGBlendMode`.
+fn blend(mode: CGBlendMode, s: [CGFloat; 4], d: [CGFloat; 4]) -> [CGFloat; 4] {
+    let (sa, da) = (s[3], d[3]);
+    let porter_duff = |fs: CGFloat, fd: CGFloat| [0, 1, 2, 3].map(|i| s[i] * fs + d[i] * fd);
+    // For the PDF-style blend modes, the blend function B gets
+    // non-premultiplied colors and its result is weighted by the overlap.
+    let unpremultiply = |c: [CGFloat; 4]| {
+        if c[3] > 0.0 {
+            [c[0] / c[3], c[1] / c[3], c[2] / c[3]]
+        } else {
+            [0.0; 3]
+        }
+    };
+    let (cs, cb) = (unpremultiply(s), unpremultiply(d));
+    let composite = |b: [CGFloat; 3]| {
+        let [r, g, b] = [0, 1, 2].map(|i| (1.0 - da) * s[i] + (1.0 - sa) * d[i] + sa * da * b[i]);
+        [r, g, b, sa + da - sa * da]
+    };
+    let separable =
+        |f: fn(CGFloat, CGFloat) -> CGFloat| composite([0, 1, 2].map(|i| f(cs[i], cb[i])));
+
+    match mode {
+        kCGBlendModeNormal => porter_duff(1.0, 1.0 - sa),
+        kCGBlendModeMultiply => separable(|s, b| s * b),
+        kCGBlendModeScreen => separable(screen),
+        kCGBlendModeOverlay => separable(|s, b| hard_light(b, s)),
+        kCGBlendModeDarken => separable(CGFloat::min),
+        kCGBlendModeLighten => separable(CGFloat::max),
+        kCGBlendModeColorDodge => separable(|s, b| {
+            if b <= 0.0 {
+                0.0
+            } else if s >= 1.0 {
+                1.0
+            } else {
+                (b / (1.0 - s)).min(1.0)
+            }
+        }),
+        kCGBlendModeColorBurn => separable(|s, b| {
+            if b >= 1.0 {
+                1.0
+            } else if s <= 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - b) / s).min(1.0)
+            }
+        }),
+        kCGBlendModeSoftLight => separable(|s, b| {
+            if s <= 0.5 {
+                b - (1.0 - 2.0 * s) * b * (1.0 - b)
+            } else {
+                let d = if b <= 0.25 {
+                    ((16.0 * b - 12.0) * b + 4.0) * b
+                } else {
+                    b.sqrt()
+                };
+                b + (2.0 * s - 1.0) * (d - b)
+            }
+        }),
+        kCGBlendModeHardLight => separable(hard_light),
+        kCGBlendModeDifference => separable(|s, b| (s - b).abs()),
+        kCGBlendModeExclusion => separable(|s, b| s + b - 2.0 * s * b),
+        kCGBlendModeHue => composite(set_lum(set_sat(cs, sat(cb)), lum(cb))),
+        kCGBlendModeSaturation => composite(set_lum(set_sat(cb, sat(cs)), lum(cb))),
+        kCGBlendModeColor => composite(set_lum(cs, lum(cb))),
+        kCGBlendModeLuminosity => composite(set_lum(cb, lum(cs))),
+        kCGBlendModeClear => [0.0; 4],
+        kCGBlendModeCopy => s,
+        kCGBlendModeSourceIn => porter_duff(da, 0.0),
+        kCGBlendModeSourceOut => porter_duff(1.0 - da, 0.0),
+        kCGBlendModeSourceAtop => porter_duff(da, 1.0 - sa),
+        kCGBlendModeDestinationOver => porter_duff(1.0 - da, 1.0),
+        kCGBlendModeDestinationIn => porter_duff(0.0, sa),
+        kCGBlendModeDestinationOut => porter_duff(0.0, 1.0 - sa),
+        kCGBlendModeDestinationAtop => porter_duff(1.0 - da, sa),
+        kCGBlendModeXOR => porter_duff(1.0 - da, 1.0 - sa),
+        kCGBlendModePlusDarker => composite([0, 1, 2].map(|i| (cs[i] + cb[i] - 1.0).max(0.0))),
+        kCGBlendModePlusLighter => [0, 1, 2, 3].map(|i| (s[i] + d[i]).min(1.0)),
+        _ => unreachable!(), // checked by CGContextSetBlendMode
+    }
+}
+
+fn screen(s: CGFloat, b: CGFloat) -> CGFloat {
+    s + b - s * b
+}
+fn hard_light(s: CGFloat, b: CGFloat) -> CGFloat {
+    if s <= 0.5 {
+        b * 2.0 * s
+    } else {
+        screen(b, 2.0 * s - 1.0)
+    }
+}
+fn lum([r, g, b]: [CGFloat; 3]) -> CGFloat {
+    0.3 * r + 0.59 * g + 0.11 * b
+}
+fn clip_color(c: [CGFloat; 3]) -> [CGFloat; 3] {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+    c.map(|c| {
+        let c = if n < 0.0 {
+            l + (c - l) * l / (l - n)
+        } else {
+            c
+        };
+        if x > 1.0 {
+            l + (c - l) * (1.0 - l) / (x - l)
+        } else {
+            c
+        }
+    })
+}
+fn set_lum(c: [CGFloat; 3], l: CGFloat) -> [CGFloat; 3] {
+    let d = l - lum(c);
+    clip_color(c.map(|c| c + d))
+}
+fn sat(c: [CGFloat; 3]) -> CGFloat {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+fn set_sat(c: [CGFloat; 3], s: CGFloat) -> [CGFloat; 3] {
+    let max = c[0].max(c[1]).max(c[2]);
+    let min = c[0].min(c[1]).min(c[2]);
+    if max > min {
+        c.map(|c| (c - min) * s / (max - min))
+    } else {
+        [0.0; 3]
+    }
+}
+
+fn borrow_data(env: &mut Environment, context: CGContextRef) -> Option<CGBitmapContextData> {
+    if context == nil {
+        return None;
+    }
+    let &CGContextHostObject {
+        subclass: CGContextSubclass::CGBitmapContext(data),
+        ..
+    } = env.objc.borrow(context);
+    Some(data)
+}
+
+fn CGBitmapContextGetData(env: &mut Environment, context: CGContextRef) -> MutVoidPtr {
+    borrow_data(env, context).map_or(MutVoidPtr::null(), |data| data.data)
+}
+fn CGBitmapContextGetWidth(env: &mut Environment, context: CGContextRef) -> GuestUSize {
+    borrow_data(env, context).map_or(0, |data| data.width)
+}
+fn CGBitmapContextGetHeight(env: &mut Environment, context: CGContextRef) -> GuestUSize {
+    borrow_data(env, context).map_or(0, |data| data.height)
+}
+fn CGBitmapContextGetBytesPerRow(env: &mut Environment, context: CGContextRef) -> GuestUSize {
+    borrow_data(env, context).map_or(0, |data| data.bytes_per_row)
+}
+
+fn CGBitmapContextCreateImage(env: &mut Environment, context: CGContextRef) -> CGImageRef {
+    if context == nil {
+        return nil;
+    }
+    let image = to_image(env, context);
+    cg_image::from_image(env, image)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGBitmapContextCreate(_, _, _, _, _, _, _)),
+    export_c_func!(CGBitmapContextGetData(_)),
+    export_c_func!(CGBitmapContextGetWidth(_)),
+    export_c_func!(CGBitmapContextGetHeight(_)),
+    export_c_func!(CGBitmapContextGetBytesPerRow(_)),
+    export_c_func!(CGBitmapContextCreateImage(_)),
+];
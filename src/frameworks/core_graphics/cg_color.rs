@@ -0,0 +1,141 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGColor.h`
+
+use super::cg_color_space::CGColorSpaceRef;
+use super::CGFloat;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::mem::{ConstPtr, GuestUSize, MutPtr};
+use crate::objc::{nil, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGColor seems to be a CFType-based type, but in our implementation those
+// are just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CGColor: NSObject
+
+- (())dealloc {
+    let components = env.objc.borrow::<CGColorHostObject>(this).components;
+    if !components.is_null() {
+        env.mem.free(components.cast());
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+struct CGColorHostObject {
+    rgba: (CGFloat, CGFloat, CGFloat, CGFloat),
+    /// Array of four components returned by `CGColorGetComponents`, allocated
+    /// the first time it's needed.
+    components: MutPtr<CGFloat>,
+}
+impl HostObject for CGColorHostObject {}
+
+pub type CGColorRef = CFTypeRef;
+
+/// Create a CGColor with non-premultiplied RGBA components. The color has a
+/// reference count of one, like any `CGColorCreate*` result.
+pub fn from_rgba(env: &mut Environment, rgba: (CGFloat, CGFloat, CGFloat, CGFloat)) -> CGColorRef {
+    let isa = env.objc.get_known_class("_touchHLE_CGColor", &mut env.mem);
+    let host_object = CGColorHostObject {
+        rgba,
+        components: MutPtr::null(),
+    };
+    env.objc
+        .alloc_object(isa, Box::new(host_object), &mut env.mem)
+}
+
+/// Shortcut for host code, gets the red, green, blue and alpha components of
+/// a CGColor.
+pub fn get_rgba(env: &mut Environment, color: CGColorRef) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+    env.objc.borrow::<CGColorHostObject>(color).rgba
+}
+
+fn CGColorCreate(
+    env: &mut Environment,
+    _space: CGColorSpaceRef,
+    components: ConstPtr<CGFloat>,
+) -> CGColorRef {
+    // TODO: support color spaces other than RGB
+    let [r, g, b, a] = [0, 1, 2, 3].map(|i| env.mem.read(components + i));
+    from_rgba(env, (r, g, b, a))
+}
+
+pub fn CGColorRelease(env: &mut Environment, color: CGColorRef) {
+    if !color.is_null() {
+        CFRelease(env, color);
+    }
+}
+pub fn CGColorRetain(env: &mut Environment, color: CGColorRef) -> CGColorRef {
+    if !color.is_null() {
+        CFRetain(env, color)
+    } else {
+        color
+    }
+}
+
+fn CGColorCreateCopyWithAlpha(
+    env: &mut Environment,
+    color: CGColorRef,
+    alpha: CGFloat,
+) -> CGColorRef {
+    if color == nil {
+        return nil;
+    }
+    let (r, g, b, _) = get_rgba(env, color);
+    from_rgba(env, (r, g, b, alpha))
+}
+
+fn CGColorEqualToColor(env: &mut Environment, a: CGColorRef, b: CGColorRef) -> bool {
+    if a == b {
+        return true;
+    }
+    if a == nil || b == nil {
+        return false;
+    }
+    get_rgba(env, a) == get_rgba(env, b)
+}
+
+fn CGColorGetAlpha(env: &mut Environment, color: CGColorRef) -> CGFloat {
+    get_rgba(env, color).3
+}
+
+fn CGColorGetNumberOfComponents(_env: &mut Environment, _color: CGColorRef) -> GuestUSize {
+    4 // RGBA
+}
+
+fn CGColorGetComponents(env: &mut Environment, color: CGColorRef) -> ConstPtr<CGFloat> {
+    let &CGColorHostObject { rgba, components } = env.objc.borrow(color);
+    if !components.is_null() {
+        return components.cast_const();
+    }
+    let (r, g, b, a) = rgba;
+    let components: MutPtr<CGFloat> = env.mem.alloc(4 * 4).cast();
+    for (i, value) in [r, g, b, a].into_iter().enumerate() {
+        env.mem.write(components + i as GuestUSize, value);
+    }
+    env.objc.borrow_mut::<CGColorHostObject>(color).components = components;
+    components.cast_const()
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGColorCreate(_, _)),
+    export_c_func!(CGColorRetain(_)),
+    export_c_func!(CGColorRelease(_)),
+    export_c_func!(CGColorCreateCopyWithAlpha(_, _)),
+    export_c_func!(CGColorEqualToColor(_, _)),
+    export_c_func!(CGColorGetAlpha(_)),
+    export_c_func!(CGColorGetNumberOfComponents(_)),
+    export_c_func!(CGColorGetComponents(_)),
+];
@@ -33,21 +33,67 @@ pub type CGColorSpaceRef = CFTypeRef;
 
 pub fn CGColorSpaceCreateWithName(env: &mut Environment, name: CFStringRef) -> CGColorSpaceRef {
     let generic_rgb = ns_string::get_static_str(env, kCGColorSpaceGenericRGB);
-    // TODO: support more color spaces
-    assert!(msg![env; name isEqualToString:generic_rgb]);
+    let generic_gray = ns_string::get_static_str(env, kCGColorSpaceGenericGray);
+    let name = if msg![env; name isEqualToString:generic_rgb] {
+        kCGColorSpaceGenericRGB
+    } else if msg![env; name isEqualToString:generic_gray] {
+        kCGColorSpaceGenericGray
+    } else {
+        // TODO: support more color spaces
+        panic!("Unsupported color space name");
+    };
+
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGColorSpace", &mut env.mem);
+    env.objc
+        .alloc_object(isa, Box::new(CGColorSpaceHostObject { name }), &mut env.mem)
+}
 
+pub fn CGColorSpaceCreateDeviceRGB(env: &mut Environment) -> CGColorSpaceRef {
     let isa = env
         .objc
         .get_known_class("_touchHLE_CGColorSpace", &mut env.mem);
     env.objc.alloc_object(
         isa,
         Box::new(CGColorSpaceHostObject {
+            // TODO: distinguish device RGB from generic RGB, if that ever
+            // matters.
             name: kCGColorSpaceGenericRGB,
         }),
         &mut env.mem,
     )
 }
 
+fn CGColorSpaceCreateDeviceGray(env: &mut Environment) -> CGColorSpaceRef {
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGColorSpace", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CGColorSpaceHostObject {
+            name: kCGColorSpaceGenericGray,
+        }),
+        &mut env.mem,
+    )
+}
+
+/// Pattern color spaces only exist to be passed to
+/// `CGContextSetFillColorSpace` before `CGContextSetFillPattern`, so the base
+/// color space isn't kept.
+fn CGColorSpaceCreatePattern(env: &mut Environment, _base: CGColorSpaceRef) -> CGColorSpaceRef {
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGColorSpace", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CGColorSpaceHostObject {
+            name: PATTERN_COLOR_SPACE_NAME,
+        }),
+        &mut env.mem,
+    )
+}
+
 pub fn CGColorSpaceRelease(env: &mut Environment, cs: CGColorSpaceRef) {
     if !cs.is_null() {
         CFRelease(env, cs);
@@ -62,14 +108,26 @@ pub fn CGColorSpaceRetain(env: &mut Environment, cs: CGColorSpaceRef) -> CGColor
 }
 
 pub const kCGColorSpaceGenericRGB: &str = "kCGColorSpaceGenericRGB";
+pub const kCGColorSpaceGenericGray: &str = "kCGColorSpaceGenericGray";
+/// Not a real constant, just a marker for `CGColorSpaceCreatePattern`.
+const PATTERN_COLOR_SPACE_NAME: &str = "_touchHLE_pattern";
 
-pub const CONSTANTS: ConstantExports = &[(
-    "_kCGColorSpaceGenericRGB",
-    HostConstant::NSString(kCGColorSpaceGenericRGB),
-)];
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCGColorSpaceGenericRGB",
+        HostConstant::NSString(kCGColorSpaceGenericRGB),
+    ),
+    (
+        "_kCGColorSpaceGenericGray",
+        HostConstant::NSString(kCGColorSpaceGenericGray),
+    ),
+];
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGColorSpaceCreateWithName(_)),
+    export_c_func!(CGColorSpaceCreateDeviceRGB()),
+    export_c_func!(CGColorSpaceCreateDeviceGray()),
+    export_c_func!(CGColorSpaceCreatePattern(_)),
     export_c_func!(CGColorSpaceRetain(_)),
     export_c_func!(CGColorSpaceRelease(_)),
 ];
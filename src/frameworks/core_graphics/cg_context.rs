@@ -5,12 +5,31 @@
  */
 //! `CGContext.h`
 
+use super::cg_affine_transform::{CGAffineTransform, CGAffineTransformIdentity};
 use super::cg_bitmap_context;
-use super::{CGFloat, CGRect};
+use super::cg_color::{self, CGColorRef};
+use super::cg_color_space::CGColorSpaceRef;
+use super::cg_gradient::{
+    self, kCGGradientDrawsAfterEndLocation, kCGGradientDrawsBeforeStartLocation,
+    CGGradientDrawingOptions, CGGradientRef,
+};
+use super::cg_image::{self, CGImageRef};
+use super::cg_path::{self, CGPathRef, Path, NULL_RECT};
+use super::cg_pattern::{self, CGPatternRef, CGPatternRelease, CGPatternRetain};
+use super::cg_pdf_page::{self, kCGPDFCropBox, CGPDFPageGetBoxRect, CGPDFPageRef};
+use super::cg_shading::{self, CGShadingRef, Geometry};
+use super::pdf;
+use super::rasterizer::{
+    self, kCGLineCapButt, kCGLineCapRound, kCGLineCapSquare, kCGLineJoinBevel, kCGLineJoinMiter,
+    kCGLineJoinRound, CGLineCap, CGLineJoin, FillRule, Mask, StrokeStyle,
+};
+use super::{CGFloat, CGPoint, CGRect, CGSize};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
-use crate::objc::{objc_classes, ClassExports, HostObject};
+use crate::mem::{ConstPtr, GuestUSize};
+use crate::objc::{nil, objc_classes, ClassExports, HostObject};
 use crate::Environment;
+use std::rc::Rc;
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -20,22 +39,135 @@ pub const CLASSES: ClassExports = objc_classes! {
 // are just Objective-C types, so we need a class for it, but its name is not
 // visible anywhere.
 @implementation _touchHLE_CGContext: NSObject
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<CGContextHostObject>(this);
+    let CGContextSubclass::CGBitmapContext(data) = host_object.subclass;
+    let mut gstates = std::mem::take(&mut host_object.saved_gstates);
+    gstates.push(host_object.gstate.clone());
+    for gstate in gstates {
+        release_patterns(env, &gstate);
+    }
+    if let Some(data) = data.owned_data() {
+        env.mem.free(data);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
 @end
 
 };
 
 pub(super) struct CGContextHostObject {
     pub(super) subclass: CGContextSubclass,
-    pub(super) rgb_fill_color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    pub(super) gstate: GState,
+    /// Graphics states saved by `CGContextSaveGState`.
+    pub(super) saved_gstates: Vec<GState>,
+    /// The current path, in device space. It is not part of the graphics
+    /// state.
+    pub(super) path: Path,
+    /// The text matrix, whose translation is the text position. Like the
+    /// path, it is not part of the graphics state.
+    pub(super) text_matrix: CGAffineTransform,
 }
 impl HostObject for CGContextHostObject {}
+impl CGContextHostObject {
+    pub(super) fn new(subclass: CGContextSubclass) -> CGContextHostObject {
+        CGContextHostObject {
+            subclass,
+            gstate: GState::default(),
+            saved_gstates: Vec::new(),
+            path: Path::default(),
+            text_matrix: CGAffineTransformIdentity,
+        }
+    }
+}
 
 pub(super) enum CGContextSubclass {
     CGBitmapContext(cg_bitmap_context::CGBitmapContextData),
 }
 
+/// The parts of the graphics state that are saved and restored by
+/// `CGContextSaveGState` and `CGContextRestoreGState`.
+#[derive(Clone)]
+pub(super) struct GState {
+    pub(super) ctm: CGAffineTransform,
+    /// Non-premultiplied RGBA.
+    pub(super) fill_color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    /// Non-premultiplied RGBA.
+    pub(super) stroke_color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    pub(super) alpha: CGFloat,
+    pub(super) blend_mode: CGBlendMode,
+    pub(super) stroke_style: StrokeStyle,
+    /// Device-space coverage that drawing is restricted to, if clipped.
+    pub(super) clip: Option<Rc<Mask>>,
+    /// Pattern used instead of `fill_color`, if any. The pattern is retained
+    /// by each graphics state that refers to it.
+    pub(super) fill_pattern: Option<CGPatternRef>,
+    /// Pattern used instead of `stroke_color`, if any. Retained like
+    /// `fill_pattern`.
+    pub(super) stroke_pattern: Option<CGPatternRef>,
+    pub(super) pattern_phase: CGSize,
+}
+impl Default for GState {
+    fn default() -> Self {
+        GState {
+            ctm: CGAffineTransformIdentity,
+            fill_color: (0.0, 0.0, 0.0, 1.0),
+            stroke_color: (0.0, 0.0, 0.0, 1.0),
+            alpha: 1.0,
+            blend_mode: kCGBlendModeNormal,
+            stroke_style: StrokeStyle::default(),
+            clip: None,
+            fill_pattern: None,
+            stroke_pattern: None,
+            pattern_phase: CGSize {
+                width: 0.0,
+                height: 0.0,
+            },
+        }
+    }
+}
+
 pub type CGContextRef = CFTypeRef;
 
+pub type CGBlendMode = i32;
+pub const kCGBlendModeNormal: CGBlendMode = 0;
+pub const kCGBlendModeMultiply: CGBlendMode = 1;
+pub const kCGBlendModeScreen: CGBlendMode = 2;
+pub const kCGBlendModeOverlay: CGBlendMode = 3;
+pub const kCGBlendModeDarken: CGBlendMode = 4;
+pub const kCGBlendModeLighten: CGBlendMode = 5;
+pub const kCGBlendModeColorDodge: CGBlendMode = 6;
+pub const kCGBlendModeColorBurn: CGBlendMode = 7;
+pub const kCGBlendModeSoftLight: CGBlendMode = 8;
+pub const kCGBlendModeHardLight: CGBlendMode = 9;
+pub const kCGBlendModeDifference: CGBlendMode = 10;
+pub const kCGBlendModeExclusion: CGBlendMode = 11;
+pub const kCGBlendModeHue: CGBlendMode = 12;
+pub const kCGBlendModeSaturation: CGBlendMode = 13;
+pub const kCGBlendModeColor: CGBlendMode = 14;
+pub const kCGBlendModeLuminosity: CGBlendMode = 15;
+pub const kCGBlendModeClear: CGBlendMode = 16;
+pub const kCGBlendModeCopy: CGBlendMode = 17;
+pub const kCGBlendModeSourceIn: CGBlendMode = 18;
+pub const kCGBlendModeSourceOut: CGBlendMode = 19;
+pub const kCGBlendModeSourceAtop: CGBlendMode = 20;
+pub const kCGBlendModeDestinationOver: CGBlendMode = 21;
+pub const kCGBlendModeDestinationIn: CGBlendMode = 22;
+pub const kCGBlendModeDestinationOut: CGBlendMode = 23;
+pub const kCGBlendModeDestinationAtop: CGBlendMode = 24;
+pub const kCGBlendModeXOR: CGBlendMode = 25;
+pub const kCGBlendModePlusDarker: CGBlendMode = 26;
+pub const kCGBlendModePlusLighter: CGBlendMode = 27;
+
+pub type CGPathDrawingMode = i32;
+pub const kCGPathFill: CGPathDrawingMode = 0;
+pub const kCGPathEOFill: CGPathDrawingMode = 1;
+pub const kCGPathStroke: CGPathDrawingMode = 2;
+pub const kCGPathFillStroke: CGPathDrawingMode = 3;
+pub const kCGPathEOFillStroke: CGPathDrawingMode = 4;
+
 pub fn CGContextRelease(env: &mut Environment, c: CGContextRef) {
     if !c.is_null() {
         CFRelease(env, c);
@@ -49,7 +181,163 @@ pub fn CGContextRetain(env: &mut Environment, c: CGContextRef) -> CGContextRef {
     }
 }
 
-fn CGContextSetRGBFillColor(
+fn borrow_host_object(env: &mut Environment, context: CGContextRef) -> &mut CGContextHostObject {
+    env.objc.borrow_mut::<CGContextHostObject>(context)
+}
+fn borrow_gstate(env: &mut Environment, context: CGContextRef) -> &mut GState {
+    &mut borrow_host_object(env, context).gstate
+}
+
+fn release_patterns(env: &mut Environment, gstate: &GState) {
+    for pattern in [gstate.fill_pattern, gstate.stroke_pattern]
+        .into_iter()
+        .flatten()
+    {
+        CGPatternRelease(env, pattern);
+    }
+}
+
+pub fn CGContextSaveGState(env: &mut Environment, context: CGContextRef) {
+    let host_object = borrow_host_object(env, context);
+    let gstate = host_object.gstate.clone();
+    host_object.saved_gstates.push(gstate.clone());
+    for pattern in [gstate.fill_pattern, gstate.stroke_pattern]
+        .into_iter()
+        .flatten()
+    {
+        CGPatternRetain(env, pattern);
+    }
+}
+pub fn CGContextRestoreGState(env: &mut Environment, context: CGContextRef) {
+    let host_object = borrow_host_object(env, context);
+    if let Some(gstate) = host_object.saved_gstates.pop() {
+        let old_gstate = std::mem::replace(&mut host_object.gstate, gstate);
+        release_patterns(env, &old_gstate);
+    } else {
+        log!("Warning: CGContextRestoreGState() with no saved state");
+    }
+}
+
+// Current transformation matrix
+
+pub fn CGContextGetCTM(env: &mut Environment, context: CGContextRef) -> CGAffineTransform {
+    borrow_gstate(env, context).ctm
+}
+pub fn CGContextConcatCTM(
+    env: &mut Environment,
+    context: CGContextRef,
+    transform: CGAffineTransform,
+) {
+    let gstate = borrow_gstate(env, context);
+    gstate.ctm = transform.concat(gstate.ctm);
+}
+pub fn CGContextTranslateCTM(
+    env: &mut Environment,
+    context: CGContextRef,
+    tx: CGFloat,
+    ty: CGFloat,
+) {
+    let transform = CGAffineTransform {
+        tx,
+        ty,
+        ..CGAffineTransformIdentity
+    };
+    CGContextConcatCTM(env, context, transform);
+}
+pub fn CGContextScaleCTM(env: &mut Environment, context: CGContextRef, sx: CGFloat, sy: CGFloat) {
+    let transform = CGAffineTransform {
+        a: sx,
+        d: sy,
+        ..CGAffineTransformIdentity
+    };
+    CGContextConcatCTM(env, context, transform);
+}
+pub fn CGContextRotateCTM(env: &mut Environment, context: CGContextRef, angle: CGFloat) {
+    let (sin, cos) = angle.sin_cos();
+    let transform = CGAffineTransform {
+        a: cos,
+        b: sin,
+        c: -sin,
+        d: cos,
+        tx: 0.0,
+        ty: 0.0,
+    };
+    CGContextConcatCTM(env, context, transform);
+}
+fn CGContextGetUserSpaceToDeviceSpaceTransform(
+    env: &mut Environment,
+    context: CGContextRef,
+) -> CGAffineTransform {
+    borrow_gstate(env, context).ctm
+}
+fn CGContextConvertPointToDeviceSpace(
+    env: &mut Environment,
+    context: CGContextRef,
+    point: CGPoint,
+) -> CGPoint {
+    borrow_gstate(env, context).ctm.apply_to_point(point)
+}
+fn CGContextConvertPointToUserSpace(
+    env: &mut Environment,
+    context: CGContextRef,
+    point: CGPoint,
+) -> CGPoint {
+    borrow_gstate(env, context)
+        .ctm
+        .invert()
+        .apply_to_point(point)
+}
+fn CGContextConvertRectToDeviceSpace(
+    env: &mut Environment,
+    context: CGContextRef,
+    rect: CGRect,
+) -> CGRect {
+    borrow_gstate(env, context).ctm.apply_to_rect(rect)
+}
+fn CGContextConvertRectToUserSpace(
+    env: &mut Environment,
+    context: CGContextRef,
+    rect: CGRect,
+) -> CGRect {
+    borrow_gstate(env, context).ctm.invert().apply_to_rect(rect)
+}
+
+// Colors and drawing parameters
+
+fn set_fill_color(
+    env: &mut Environment,
+    context: CGContextRef,
+    rgba: (CGFloat, CGFloat, CGFloat, CGFloat),
+) {
+    let gstate = borrow_gstate(env, context);
+    gstate.fill_color = rgba;
+    if let Some(pattern) = gstate.fill_pattern.take() {
+        CGPatternRelease(env, pattern);
+    }
+}
+fn set_stroke_color(
+    env: &mut Environment,
+    context: CGContextRef,
+    rgba: (CGFloat, CGFloat, CGFloat, CGFloat),
+) {
+    let gstate = borrow_gstate(env, context);
+    gstate.stroke_color = rgba;
+    if let Some(pattern) = gstate.stroke_pattern.take() {
+        CGPatternRelease(env, pattern);
+    }
+}
+
+pub fn CGContextSetRGBFillColor(
+    env: &mut Environment,
+    context: CGContextRef,
+    red: CGFloat,
+    green: CGFloat,
+    blue: CGFloat,
+    alpha: CGFloat,
+) {
+    set_fill_color(env, context, (red, green, blue, alpha));
+}
+pub fn CGContextSetRGBStrokeColor(
     env: &mut Environment,
     context: CGContextRef,
     red: CGFloat,
@@ -57,19 +345,844 @@ fn CGContextSetRGBFillColor(
     blue: CGFloat,
     alpha: CGFloat,
 ) {
-    let color = (red, green, blue, alpha);
-    env.objc
-        .borrow_mut::<CGContextHostObject>(context)
-        .rgb_fill_color = color;
+    set_stroke_color(env, context, (red, green, blue, alpha));
+}
+fn CGContextSetGrayFillColor(
+    env: &mut Environment,
+    context: CGContextRef,
+    gray: CGFloat,
+    alpha: CGFloat,
+) {
+    set_fill_color(env, context, (gray, gray, gray, alpha));
+}
+fn CGContextSetGrayStrokeColor(
+    env: &mut Environment,
+    context: CGContextRef,
+    gray: CGFloat,
+    alpha: CGFloat,
+) {
+    set_stroke_color(env, context, (gray, gray, gray, alpha));
+}
+pub fn CGContextSetFillColorWithColor(
+    env: &mut Environment,
+    context: CGContextRef,
+    color: CGColorRef,
+) {
+    let rgba = if color == nil {
+        (0.0, 0.0, 0.0, 0.0)
+    } else {
+        cg_color::get_rgba(env, color)
+    };
+    set_fill_color(env, context, rgba);
+}
+pub fn CGContextSetStrokeColorWithColor(
+    env: &mut Environment,
+    context: CGContextRef,
+    color: CGColorRef,
+) {
+    let rgba = if color == nil {
+        (0.0, 0.0, 0.0, 0.0)
+    } else {
+        cg_color::get_rgba(env, color)
+    };
+    set_stroke_color(env, context, rgba);
+}
+/// Colors given as a component array. Only RGBA (and gray-alpha, by count) is
+/// supported since that's all our color spaces can be.
+fn read_color_components(
+    env: &mut Environment,
+    components: ConstPtr<CGFloat>,
+) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+    let [r, g, b, a] = [0, 1, 2, 3].map(|i| env.mem.read(components + i));
+    (r, g, b, a)
+}
+fn CGContextSetFillColor(
+    env: &mut Environment,
+    context: CGContextRef,
+    components: ConstPtr<CGFloat>,
+) {
+    let rgba = read_color_components(env, components);
+    set_fill_color(env, context, rgba);
+}
+fn CGContextSetStrokeColor(
+    env: &mut Environment,
+    context: CGContextRef,
+    components: ConstPtr<CGFloat>,
+) {
+    let rgba = read_color_components(env, components);
+    set_stroke_color(env, context, rgba);
+}
+/// Read the components passed to `CGContextSetFillPattern` or
+/// `CGContextSetStrokePattern`: a color and alpha for uncolored patterns,
+/// just an alpha for colored ones.
+fn read_pattern_components(
+    env: &mut Environment,
+    pattern: CGPatternRef,
+    components: ConstPtr<CGFloat>,
+) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+    if components.is_null() {
+        (0.0, 0.0, 0.0, 1.0)
+    } else if cg_pattern::is_colored(env, pattern) {
+        (1.0, 1.0, 1.0, env.mem.read(components))
+    } else {
+        read_color_components(env, components)
+    }
+}
+fn CGContextSetFillPattern(
+    env: &mut Environment,
+    context: CGContextRef,
+    pattern: CGPatternRef,
+    components: ConstPtr<CGFloat>,
+) {
+    let color = read_pattern_components(env, pattern, components);
+    set_fill_color(env, context, color);
+    CGPatternRetain(env, pattern);
+    borrow_gstate(env, context).fill_pattern = Some(pattern);
+}
+fn CGContextSetStrokePattern(
+    env: &mut Environment,
+    context: CGContextRef,
+    pattern: CGPatternRef,
+    components: ConstPtr<CGFloat>,
+) {
+    let color = read_pattern_components(env, pattern, components);
+    set_stroke_color(env, context, color);
+    CGPatternRetain(env, pattern);
+    borrow_gstate(env, context).stroke_pattern = Some(pattern);
+}
+fn CGContextSetPatternPhase(env: &mut Environment, context: CGContextRef, phase: CGSize) {
+    borrow_gstate(env, context).pattern_phase = phase;
+}
+fn CGContextSetFillColorSpace(
+    _env: &mut Environment,
+    _context: CGContextRef,
+    _space: CGColorSpaceRef,
+) {
+    // TODO: color spaces other than RGB. Patterns are handled by
+    // CGContextSetFillPattern, so pattern color spaces need no state here.
+}
+fn CGContextSetStrokeColorSpace(
+    _env: &mut Environment,
+    _context: CGContextRef,
+    _space: CGColorSpaceRef,
+) {
+    // TODO: color spaces other than RGB.
+}
+pub fn CGContextSetAlpha(env: &mut Environment, context: CGContextRef, alpha: CGFloat) {
+    borrow_gstate(env, context).alpha = alpha.clamp(0.0, 1.0);
+}
+pub fn CGContextSetBlendMode(env: &mut Environment, context: CGContextRef, mode: CGBlendMode) {
+    if !(kCGBlendModeNormal..=kCGBlendModePlusLighter).contains(&mode) {
+        log!("Warning: unknown blend mode {}, using normal", mode);
+        borrow_gstate(env, context).blend_mode = kCGBlendModeNormal;
+        return;
+    }
+    borrow_gstate(env, context).blend_mode = mode;
+}
+pub fn CGContextSetLineWidth(env: &mut Environment, context: CGContextRef, width: CGFloat) {
+    borrow_gstate(env, context).stroke_style.line_width = width;
+}
+pub fn CGContextSetLineCap(env: &mut Environment, context: CGContextRef, cap: CGLineCap) {
+    if !matches!(cap, kCGLineCapButt | kCGLineCapRound | kCGLineCapSquare) {
+        log!("Warning: unknown line cap {}, ignoring", cap);
+        return;
+    }
+    borrow_gstate(env, context).stroke_style.line_cap = cap;
+}
+pub fn CGContextSetLineJoin(env: &mut Environment, context: CGContextRef, join: CGLineJoin) {
+    if !matches!(join, kCGLineJoinMiter | kCGLineJoinRound | kCGLineJoinBevel) {
+        log!("Warning: unknown line join {}, ignoring", join);
+        return;
+    }
+    borrow_gstate(env, context).stroke_style.line_join = join;
+}
+pub fn CGContextSetMiterLimit(env: &mut Environment, context: CGContextRef, limit: CGFloat) {
+    borrow_gstate(env, context).stroke_style.miter_limit = limit;
+}
+fn CGContextSetLineDash(
+    env: &mut Environment,
+    context: CGContextRef,
+    phase: CGFloat,
+    lengths: ConstPtr<CGFloat>,
+    count: GuestUSize,
+) {
+    let dash = if lengths.is_null() || count == 0 {
+        None
+    } else {
+        let lengths = (0..count).map(|i| env.mem.read(lengths + i)).collect();
+        Some((phase, lengths))
+    };
+    set_line_dash(env, context, dash);
+}
+/// [CGContextSetLineDash] for host code. [None] means solid lines.
+pub(super) fn set_line_dash(
+    env: &mut Environment,
+    context: CGContextRef,
+    dash: Option<(CGFloat, Vec<CGFloat>)>,
+) {
+    borrow_gstate(env, context).stroke_style.dash = dash;
+}
+fn CGContextSetShouldAntialias(_env: &mut Environment, _context: CGContextRef, _should: bool) {
+    // TODO: aliased drawing. Anti-aliasing is always on.
+}
+fn CGContextSetAllowsAntialiasing(_env: &mut Environment, _context: CGContextRef, _allows: bool) {
+    // TODO: aliased drawing. Anti-aliasing is always on.
+}
+fn CGContextSetFlatness(_env: &mut Environment, _context: CGContextRef, _flatness: CGFloat) {
+    // Curves are always flattened to within a quarter pixel.
+}
+
+// Path construction
+
+pub fn CGContextBeginPath(env: &mut Environment, context: CGContextRef) {
+    borrow_host_object(env, context).path = Path::default();
+}
+pub fn CGContextMoveToPoint(env: &mut Environment, context: CGContextRef, x: CGFloat, y: CGFloat) {
+    let host_object = borrow_host_object(env, context);
+    let point = host_object.gstate.ctm.apply_to_point(CGPoint { x, y });
+    host_object.path.move_to(point);
+}
+pub fn CGContextAddLineToPoint(
+    env: &mut Environment,
+    context: CGContextRef,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let host_object = borrow_host_object(env, context);
+    let point = host_object.gstate.ctm.apply_to_point(CGPoint { x, y });
+    host_object.path.line_to(point);
+}
+pub fn CGContextAddQuadCurveToPoint(
+    env: &mut Environment,
+    context: CGContextRef,
+    cpx: CGFloat,
+    cpy: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let host_object = borrow_host_object(env, context);
+    let ctm = host_object.gstate.ctm;
+    host_object.path.quad_curve_to(
+        ctm.apply_to_point(CGPoint { x: cpx, y: cpy }),
+        ctm.apply_to_point(CGPoint { x, y }),
+    );
+}
+pub fn CGContextAddCurveToPoint(
+    env: &mut Environment,
+    context: CGContextRef,
+    cp1x: CGFloat,
+    cp1y: CGFloat,
+    cp2x: CGFloat,
+    cp2y: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let host_object = borrow_host_object(env, context);
+    let ctm = host_object.gstate.ctm;
+    host_object.path.curve_to(
+        ctm.apply_to_point(CGPoint { x: cp1x, y: cp1y }),
+        ctm.apply_to_point(CGPoint { x: cp2x, y: cp2y }),
+        ctm.apply_to_point(CGPoint { x, y }),
+    );
+}
+fn CGContextAddArc(
+    env: &mut Environment,
+    context: CGContextRef,
+    x: CGFloat,
+    y: CGFloat,
+    radius: CGFloat,
+    start_angle: CGFloat,
+    end_angle: CGFloat,
+    clockwise: i32,
+) {
+    let host_object = borrow_host_object(env, context);
+    let ctm = host_object.gstate.ctm;
+    host_object.path.add_arc(
+        CGPoint { x, y },
+        radius,
+        start_angle,
+        end_angle,
+        clockwise != 0,
+        ctm,
+    );
+}
+fn CGContextAddArcToPoint(
+    env: &mut Environment,
+    context: CGContextRef,
+    x1: CGFloat,
+    y1: CGFloat,
+    x2: CGFloat,
+    y2: CGFloat,
+    radius: CGFloat,
+) {
+    let host_object = borrow_host_object(env, context);
+    let ctm = host_object.gstate.ctm;
+    host_object.path.add_arc_to_point(
+        CGPoint { x: x1, y: y1 },
+        CGPoint { x: x2, y: y2 },
+        radius,
+        ctm,
+    );
+}
+pub fn CGContextAddRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
+    let host_object = borrow_host_object(env, context);
+    let ctm = host_object.gstate.ctm;
+    host_object.path.add_rect(rect, ctm);
+}
+fn CGContextAddRects(
+    env: &mut Environment,
+    context: CGContextRef,
+    rects: ConstPtr<CGRect>,
+    count: GuestUSize,
+) {
+    for i in 0..count {
+        let rect = env.mem.read(rects + i);
+        CGContextAddRect(env, context, rect);
+    }
+}
+fn CGContextAddEllipseInRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
+    let host_object = borrow_host_object(env, context);
+    let ctm = host_object.gstate.ctm;
+    host_object.path.add_ellipse_in_rect(rect, ctm);
+}
+fn CGContextAddLines(
+    env: &mut Environment,
+    context: CGContextRef,
+    points: ConstPtr<CGPoint>,
+    count: GuestUSize,
+) {
+    for i in 0..count {
+        let CGPoint { x, y } = env.mem.read(points + i);
+        if i == 0 {
+            CGContextMoveToPoint(env, context, x, y);
+        } else {
+            CGContextAddLineToPoint(env, context, x, y);
+        }
+    }
+}
+pub fn CGContextAddPath(env: &mut Environment, context: CGContextRef, path: CGPathRef) {
+    if path == nil {
+        return;
+    }
+    let path = cg_path::borrow_path(env, path).clone();
+    let host_object = borrow_host_object(env, context);
+    let ctm = host_object.gstate.ctm;
+    host_object.path.add_path(&path, ctm);
+}
+pub fn CGContextClosePath(env: &mut Environment, context: CGContextRef) {
+    borrow_host_object(env, context).path.close();
+}
+fn CGContextIsPathEmpty(env: &mut Environment, context: CGContextRef) -> bool {
+    borrow_host_object(env, context).path.is_empty()
+}
+pub fn CGContextGetPathCurrentPoint(env: &mut Environment, context: CGContextRef) -> CGPoint {
+    let host_object = borrow_host_object(env, context);
+    match host_object.path.current_point() {
+        Some(point) => host_object.gstate.ctm.invert().apply_to_point(point),
+        None => CGPoint { x: 0.0, y: 0.0 },
+    }
+}
+fn CGContextGetPathBoundingBox(env: &mut Environment, context: CGContextRef) -> CGRect {
+    let host_object = borrow_host_object(env, context);
+    let inverse = host_object.gstate.ctm.invert();
+    match host_object.path.transformed(inverse).bounding_box() {
+        Some(rect) => rect,
+        None => NULL_RECT,
+    }
+}
+pub fn CGContextCopyPath(env: &mut Environment, context: CGContextRef) -> CGPathRef {
+    let host_object = borrow_host_object(env, context);
+    let inverse = host_object.gstate.ctm.invert();
+    let path = host_object.path.transformed(inverse);
+    cg_path::from_path(env, path)
+}
+fn CGContextPathContainsPoint(
+    env: &mut Environment,
+    context: CGContextRef,
+    point: CGPoint,
+    mode: CGPathDrawingMode,
+) -> bool {
+    let host_object = borrow_host_object(env, context);
+    let point = host_object.gstate.ctm.apply_to_point(point);
+    let rule = match mode {
+        kCGPathEOFill | kCGPathEOFillStroke => FillRule::EvenOdd,
+        _ => FillRule::Winding,
+    };
+    host_object.path.contains_point(point, rule)
+}
+
+// Drawing
+
+/// Fill and/or stroke the current path, then clear it.
+pub fn CGContextDrawPath(env: &mut Environment, context: CGContextRef, mode: CGPathDrawingMode) {
+    let (width, height) = cg_bitmap_context::dimensions(env, context);
+    let host_object = borrow_host_object(env, context);
+    let path = std::mem::take(&mut host_object.path);
+    let GState {
+        ctm,
+        fill_color,
+        stroke_color,
+        ref stroke_style,
+        fill_pattern,
+        stroke_pattern,
+        pattern_phase,
+        ..
+    } = host_object.gstate;
+    let stroke_style = stroke_style.clone();
+
+    let polylines = path.flatten();
+    let fill_rule = match mode {
+        kCGPathFill | kCGPathFillStroke => Some(FillRule::Winding),
+        kCGPathEOFill | kCGPathEOFillStroke => Some(FillRule::EvenOdd),
+        kCGPathStroke => None,
+        _ => {
+            log!("Warning: unknown path drawing mode {}", mode);
+            return;
+        }
+    };
+    if let Some(rule) = fill_rule {
+        let mask = rasterizer::fill(&polylines, rule, width, height);
+        paint_color_or_pattern(env, context, &mask, fill_color, fill_pattern, pattern_phase);
+    }
+    if matches!(
+        mode,
+        kCGPathStroke | kCGPathFillStroke | kCGPathEOFillStroke
+    ) {
+        let outline = rasterizer::stroke(&polylines, &stroke_style, ctm);
+        let mask = rasterizer::fill(&outline, FillRule::Winding, width, height);
+        paint_color_or_pattern(
+            env,
+            context,
+            &mask,
+            stroke_color,
+            stroke_pattern,
+            pattern_phase,
+        );
+    }
+}
+fn paint_color_or_pattern(
+    env: &mut Environment,
+    context: CGContextRef,
+    mask: &Mask,
+    color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    pattern: Option<CGPatternRef>,
+    phase: CGSize,
+) {
+    if let Some(pattern) = pattern {
+        let tile = cg_pattern::render_tile(env, pattern, color, phase);
+        cg_bitmap_context::paint(env, context, mask, |x, y| tile.sample(x, y));
+    } else {
+        cg_bitmap_context::paint(env, context, mask, |_, _| color);
+    }
+}
+fn CGContextFillPath(env: &mut Environment, context: CGContextRef) {
+    CGContextDrawPath(env, context, kCGPathFill);
+}
+fn CGContextEOFillPath(env: &mut Environment, context: CGContextRef) {
+    CGContextDrawPath(env, context, kCGPathEOFill);
+}
+fn CGContextStrokePath(env: &mut Environment, context: CGContextRef) {
+    CGContextDrawPath(env, context, kCGPathStroke);
+}
+
+/// Run a drawing function on a temporary path, leaving the current path
+/// untouched, like the `CGContext*Rect` convenience functions do.
+fn with_temporary_path<F>(
+    env: &mut Environment,
+    context: CGContextRef,
+    build: F,
+    mode: CGPathDrawingMode,
+) where
+    F: FnOnce(&mut Path, CGAffineTransform),
+{
+    let host_object = borrow_host_object(env, context);
+    let ctm = host_object.gstate.ctm;
+    let mut path = Path::default();
+    build(&mut path, ctm);
+    let saved_path = std::mem::replace(&mut host_object.path, path);
+    CGContextDrawPath(env, context, mode);
+    borrow_host_object(env, context).path = saved_path;
+}
+
+/// Fill a path given in user space, leaving the current path untouched. For
+/// host code that draws shapes, like text.
+pub fn fill_path(env: &mut Environment, context: CGContextRef, path: &Path) {
+    with_temporary_path(
+        env,
+        context,
+        |temporary, ctm| temporary.add_path(path, ctm),
+        kCGPathFill,
+    );
+}
+
+pub fn CGContextFillRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
+    with_temporary_path(
+        env,
+        context,
+        |path, ctm| path.add_rect(rect, ctm),
+        kCGPathFill,
+    );
+}
+fn CGContextFillRects(
+    env: &mut Environment,
+    context: CGContextRef,
+    rects: ConstPtr<CGRect>,
+    count: GuestUSize,
+) {
+    let rects: Vec<CGRect> = (0..count).map(|i| env.mem.read(rects + i)).collect();
+    with_temporary_path(
+        env,
+        context,
+        |path, ctm| {
+            for rect in rects {
+                path.add_rect(rect, ctm);
+            }
+        },
+        kCGPathFill,
+    );
+}
+fn CGContextStrokeRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
+    with_temporary_path(
+        env,
+        context,
+        |path, ctm| path.add_rect(rect, ctm),
+        kCGPathStroke,
+    );
+}
+fn CGContextStrokeRectWithWidth(
+    env: &mut Environment,
+    context: CGContextRef,
+    rect: CGRect,
+    width: CGFloat,
+) {
+    CGContextSaveGState(env, context);
+    CGContextSetLineWidth(env, context, width);
+    CGContextStrokeRect(env, context, rect);
+    CGContextRestoreGState(env, context);
+}
+fn CGContextFillEllipseInRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
+    with_temporary_path(
+        env,
+        context,
+        |path, ctm| path.add_ellipse_in_rect(rect, ctm),
+        kCGPathFill,
+    );
+}
+fn CGContextStrokeEllipseInRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
+    with_temporary_path(
+        env,
+        context,
+        |path, ctm| path.add_ellipse_in_rect(rect, ctm),
+        kCGPathStroke,
+    );
+}
+fn CGContextStrokeLineSegments(
+    env: &mut Environment,
+    context: CGContextRef,
+    points: ConstPtr<CGPoint>,
+    count: GuestUSize,
+) {
+    let points: Vec<CGPoint> = (0..count).map(|i| env.mem.read(points + i)).collect();
+    with_temporary_path(
+        env,
+        context,
+        |path, ctm| {
+            for pair in points.chunks_exact(2) {
+                path.move_to(ctm.apply_to_point(pair[0]));
+                path.line_to(ctm.apply_to_point(pair[1]));
+            }
+        },
+        kCGPathStroke,
+    );
+}
+/// Make an area transparent, regardless of blend mode and colors.
+fn CGContextClearRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
+    CGContextSaveGState(env, context);
+    CGContextSetBlendMode(env, context, kCGBlendModeClear);
+    CGContextSetAlpha(env, context, 1.0);
+    CGContextFillRect(env, context, rect);
+    CGContextRestoreGState(env, context);
 }
 
-fn CGContextFillRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
-    cg_bitmap_context::fill_rect(env, context, rect);
+// Images
+
+/// Draw an image scaled to fill a rectangle in user space. The image's first
+/// row goes at the rectangle's minimum y coordinate.
+pub fn CGContextDrawImage(
+    env: &mut Environment,
+    context: CGContextRef,
+    rect: CGRect,
+    image: CGImageRef,
+) {
+    if image.is_null() || rect.size.width == 0.0 || rect.size.height == 0.0 {
+        return;
+    }
+    let (width, height) = cg_bitmap_context::dimensions(env, context);
+    let ctm = borrow_gstate(env, context).ctm;
+    let mut path = Path::default();
+    path.add_rect(rect, ctm);
+    let mask = rasterizer::fill(&path.flatten(), FillRule::Winding, width, height);
+
+    let image = cg_image::borrow_image(&env.objc, image);
+    let (image_width, image_height) = image.dimensions();
+    if image_width == 0 || image_height == 0 {
+        return;
+    }
+    let pixels = image.pixels().to_vec();
+    let to_user = ctm.invert();
+    // TODO: interpolation when scaling
+    cg_bitmap_context::paint(env, context, &mask, |x, y| {
+        let point = to_user.apply_to_point(CGPoint {
+            x: x as CGFloat + 0.5,
+            y: y as CGFloat + 0.5,
+        });
+        let u = (point.x - rect.origin.x) / rect.size.width * image_width as CGFloat;
+        let v = (point.y - rect.origin.y) / rect.size.height * image_height as CGFloat;
+        let u = (u.floor().max(0.0) as u32).min(image_width - 1);
+        let v = (v.floor().max(0.0) as u32).min(image_height - 1);
+        let idx = (v as usize * image_width as usize + u as usize) * 4;
+        let [r, g, b, a] = [0, 1, 2, 3].map(|i| pixels[idx + i] as CGFloat / 255.0);
+        (r, g, b, a)
+    });
+}
+
+// PDF
+
+/// Draw a page in the current user space, clipped to its crop box.
+fn CGContextDrawPDFPage(env: &mut Environment, context: CGContextRef, page: CGPDFPageRef) {
+    if page.is_null() {
+        return;
+    }
+    let crop_box = CGPDFPageGetBoxRect(env, page, kCGPDFCropBox);
+    let (document, dict) = cg_pdf_page::page_data(env, page);
+    CGContextSaveGState(env, context);
+    CGContextClipToRect(env, context, crop_box);
+    pdf::draw_page(env, context, &document, &dict);
+    CGContextRestoreGState(env, context);
+}
+
+// Text
+
+fn CGContextSetTextMatrix(env: &mut Environment, context: CGContextRef, t: CGAffineTransform) {
+    borrow_host_object(env, context).text_matrix = t;
+}
+pub fn CGContextGetTextMatrix(env: &mut Environment, context: CGContextRef) -> CGAffineTransform {
+    borrow_host_object(env, context).text_matrix
+}
+pub fn CGContextSetTextPosition(
+    env: &mut Environment,
+    context: CGContextRef,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let text_matrix = &mut borrow_host_object(env, context).text_matrix;
+    text_matrix.tx = x;
+    text_matrix.ty = y;
+}
+fn CGContextGetTextPosition(env: &mut Environment, context: CGContextRef) -> CGPoint {
+    let text_matrix = borrow_host_object(env, context).text_matrix;
+    CGPoint {
+        x: text_matrix.tx,
+        y: text_matrix.ty,
+    }
+}
+
+// Gradients and shadings
+
+fn CGContextDrawLinearGradient(
+    env: &mut Environment,
+    context: CGContextRef,
+    gradient: CGGradientRef,
+    start: CGPoint,
+    end: CGPoint,
+    options: CGGradientDrawingOptions,
+) {
+    let samples = cg_gradient::samples(env, gradient).to_vec();
+    let extend = (
+        options & kCGGradientDrawsBeforeStartLocation != 0,
+        options & kCGGradientDrawsAfterEndLocation != 0,
+    );
+    cg_shading::draw(
+        env,
+        context,
+        Geometry::Axial { start, end },
+        extend,
+        &samples,
+    );
+}
+fn CGContextDrawRadialGradient(
+    env: &mut Environment,
+    context: CGContextRef,
+    gradient: CGGradientRef,
+    start: CGPoint,
+    start_radius: CGFloat,
+    end: CGPoint,
+    end_radius: CGFloat,
+    options: CGGradientDrawingOptions,
+) {
+    let samples = cg_gradient::samples(env, gradient).to_vec();
+    let extend = (
+        options & kCGGradientDrawsBeforeStartLocation != 0,
+        options & kCGGradientDrawsAfterEndLocation != 0,
+    );
+    let geometry = Geometry::Radial {
+        start,
+        start_radius,
+        end,
+        end_radius,
+    };
+    cg_shading::draw(env, context, geometry, extend, &samples);
+}
+fn CGContextDrawShading(env: &mut Environment, context: CGContextRef, shading: CGShadingRef) {
+    cg_shading::draw_shading(env, context, shading);
+}
+
+// Clipping
+
+/// Intersect the clip with the current path, then clear it.
+fn clip_to_path(env: &mut Environment, context: CGContextRef, rule: FillRule) {
+    let (width, height) = cg_bitmap_context::dimensions(env, context);
+    let host_object = borrow_host_object(env, context);
+    let path = std::mem::take(&mut host_object.path);
+    let mut mask = rasterizer::fill(&path.flatten(), rule, width, height);
+    if let Some(ref clip) = host_object.gstate.clip {
+        mask.intersect(clip);
+    }
+    host_object.gstate.clip = Some(Rc::new(mask));
+}
+pub fn CGContextClip(env: &mut Environment, context: CGContextRef) {
+    clip_to_path(env, context, FillRule::Winding);
+}
+pub fn CGContextEOClip(env: &mut Environment, context: CGContextRef) {
+    clip_to_path(env, context, FillRule::EvenOdd);
+}
+pub fn CGContextClipToRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
+    let host_object = borrow_host_object(env, context);
+    let ctm = host_object.gstate.ctm;
+    let mut path = Path::default();
+    path.add_rect(rect, ctm);
+    let saved_path = std::mem::replace(&mut host_object.path, path);
+    clip_to_path(env, context, FillRule::Winding);
+    borrow_host_object(env, context).path = saved_path;
+}
+fn CGContextClipToRects(
+    env: &mut Environment,
+    context: CGContextRef,
+    rects: ConstPtr<CGRect>,
+    count: GuestUSize,
+) {
+    let host_object = borrow_host_object(env, context);
+    let ctm = host_object.gstate.ctm;
+    let mut path = Path::default();
+    for i in 0..count {
+        path.add_rect(env.mem.read(rects + i), ctm);
+    }
+    let saved_path = std::mem::replace(&mut borrow_host_object(env, context).path, path);
+    clip_to_path(env, context, FillRule::Winding);
+    borrow_host_object(env, context).path = saved_path;
+}
+fn CGContextGetClipBoundingBox(env: &mut Environment, context: CGContextRef) -> CGRect {
+    let (width, height) = cg_bitmap_context::dimensions(env, context);
+    let gstate = borrow_gstate(env, context);
+    let device_rect = match gstate.clip {
+        None => Some((0, 0, width, height)),
+        Some(ref clip) => clip.bounds(),
+    };
+    match device_rect {
+        Some((x, y, width, height)) => gstate.ctm.invert().apply_to_rect(CGRect {
+            origin: CGPoint {
+                x: x as CGFloat,
+                y: y as CGFloat,
+            },
+            size: CGSize {
+                width: width as CGFloat,
+                height: height as CGFloat,
+            },
+        }),
+        None => NULL_RECT,
+    }
 }
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGContextRetain(_)),
     export_c_func!(CGContextRelease(_)),
+    export_c_func!(CGContextSaveGState(_)),
+    export_c_func!(CGContextRestoreGState(_)),
+    export_c_func!(CGContextGetCTM(_)),
+    export_c_func!(CGContextConcatCTM(_, _)),
+    export_c_func!(CGContextTranslateCTM(_, _, _)),
+    export_c_func!(CGContextScaleCTM(_, _, _)),
+    export_c_func!(CGContextRotateCTM(_, _)),
+    export_c_func!(CGContextGetUserSpaceToDeviceSpaceTransform(_)),
+    export_c_func!(CGContextConvertPointToDeviceSpace(_, _)),
+    export_c_func!(CGContextConvertPointToUserSpace(_, _)),
+    export_c_func!(CGContextConvertRectToDeviceSpace(_, _)),
+    export_c_func!(CGContextConvertRectToUserSpace(_, _)),
     export_c_func!(CGContextSetRGBFillColor(_, _, _, _, _)),
+    export_c_func!(CGContextSetRGBStrokeColor(_, _, _, _, _)),
+    export_c_func!(CGContextSetGrayFillColor(_, _, _)),
+    export_c_func!(CGContextSetGrayStrokeColor(_, _, _)),
+    export_c_func!(CGContextSetFillColorWithColor(_, _)),
+    export_c_func!(CGContextSetStrokeColorWithColor(_, _)),
+    export_c_func!(CGContextSetFillColor(_, _)),
+    export_c_func!(CGContextSetStrokeColor(_, _)),
+    export_c_func!(CGContextSetFillPattern(_, _, _)),
+    export_c_func!(CGContextSetStrokePattern(_, _, _)),
+    export_c_func!(CGContextSetPatternPhase(_, _)),
+    export_c_func!(CGContextSetFillColorSpace(_, _)),
+    export_c_func!(CGContextSetStrokeColorSpace(_, _)),
+    export_c_func!(CGContextSetAlpha(_, _)),
+    export_c_func!(CGContextSetBlendMode(_, _)),
+    export_c_func!(CGContextSetLineWidth(_, _)),
+    export_c_func!(CGContextSetLineCap(_, _)),
+    export_c_func!(CGContextSetLineJoin(_, _)),
+    export_c_func!(CGContextSetMiterLimit(_, _)),
+    export_c_func!(CGContextSetLineDash(_, _, _, _)),
+    export_c_func!(CGContextSetShouldAntialias(_, _)),
+    export_c_func!(CGContextSetAllowsAntialiasing(_, _)),
+    export_c_func!(CGContextSetFlatness(_, _)),
+    export_c_func!(CGContextBeginPath(_)),
+    export_c_func!(CGContextMoveToPoint(_, _, _)),
+    export_c_func!(CGContextAddLineToPoint(_, _, _)),
+    export_c_func!(CGContextAddQuadCurveToPoint(_, _, _, _, _)),
+    export_c_func!(CGContextAddCurveToPoint(_, _, _, _, _, _, _)),
+    export_c_func!(CGContextAddArc(_, _, _, _, _, _, _)),
+    export_c_func!(CGContextAddArcToPoint(_, _, _, _, _, _)),
+    export_c_func!(CGContextAddRect(_, _)),
+    export_c_func!(CGContextAddRects(_, _, _)),
+    export_c_func!(CGContextAddEllipseInRect(_, _)),
+    export_c_func!(CGContextAddLines(_, _, _)),
+    export_c_func!(CGContextAddPath(_, _)),
+    export_c_func!(CGContextClosePath(_)),
+    export_c_func!(CGContextIsPathEmpty(_)),
+    export_c_func!(CGContextGetPathCurrentPoint(_)),
+    export_c_func!(CGContextGetPathBoundingBox(_)),
+    export_c_func!(CGContextCopyPath(_)),
+    export_c_func!(CGContextPathContainsPoint(_, _, _)),
+    export_c_func!(CGContextDrawPath(_, _)),
+    export_c_func!(CGContextFillPath(_)),
+    export_c_func!(CGContextEOFillPath(_)),
+    export_c_func!(CGContextStrokePath(_)),
     export_c_func!(CGContextFillRect(_, _)),
+    export_c_func!(CGContextFillRects(_, _, _)),
+    export_c_func!(CGContextStrokeRect(_, _)),
+    export_c_func!(CGContextStrokeRectWithWidth(_, _, _)),
+    export_c_func!(CGContextFillEllipseInRect(_, _)),
+    export_c_func!(CGContextStrokeEllipseInRect(_, _)),
+    export_c_func!(CGContextStrokeLineSegments(_, _, _)),
+    export_c_func!(CGContextClearRect(_, _)),
+    export_c_func!(CGContextDrawImage(_, _, _)),
+    export_c_func!(CGContextDrawPDFPage(_, _)),
+    export_c_func!(CGContextSetTextMatrix(_, _)),
+    export_c_func!(CGContextGetTextMatrix(_)),
+    export_c_func!(CGContextSetTextPosition(_, _, _)),
+    export_c_func!(CGContextGetTextPosition(_)),
+    export_c_func!(CGContextDrawLinearGradient(_, _, _, _, _)),
+    export_c_func!(CGContextDrawRadialGradient(_, _, _, _, _, _, _)),
+    export_c_func!(CGContextDrawShading(_, _)),
+    export_c_func!(CGContextClip(_)),
+    export_c_func!(CGContextEOClip(_)),
+    export_c_func!(CGContextClipToRect(_, _)),
+    export_c_func!(CGContextClipToRects(_, _, _)),
+    export_c_func!(CGContextGetClipBoundingBox(_)),
 ];
@@ -0,0 +1,304 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGDataProvider.h`
+//!
+//! Providers backed by a `CFData` just hold an `NSData`, since the two are
+//! toll-free bridged. Callback-based providers are read all at once when an
+//! image is created from them, rather than lazily.
+
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::frameworks::foundation::ns_data;
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutVoidPtr, SafeRead};
+use crate::objc::{id, objc_classes, release, retain, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGDataProvider seems to be a CFType-based type, but in our implementation
+// those are just Objective-C types, so we need a class for it, but its name is
+// not visible anywhere.
+@implementation _touchHLE_CGDataProvider: NSObject
+
+- (())dealloc {
+    let source = env.objc.borrow::<CGDataProviderHostObject>(this).source;
+    match source {
+        Source::Data(data) => release(env, data),
+        Source::Bytes {
+            info,
+            bytes,
+            size,
+            release_data,
+        } => {
+            if release_data.addr_with_thumb_bit() != 0 {
+                let () = release_data.call_from_host(env, (info, bytes, size));
+            }
+        }
+        Source::Sequential {
+            info,
+            callbacks: CGDataProviderSequentialCallbacks { release_info, .. },
+        }
+        | Source::Direct {
+            info,
+            callbacks: CGDataProviderDirectCallbacks { release_info, .. },
+            ..
+        } => {
+            if release_info.addr_with_thumb_bit() != 0 {
+                let () = release_info.call_from_host(env, (info,));
+            }
+        }
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+#[allow(dead_code)]
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct CGDataProviderSequentialCallbacks {
+    version: u32,
+    /// `size_t (*)(void *info, void *buffer, size_t count)`
+    get_bytes: GuestFunction,
+    /// `off_t (*)(void *info, off_t count)`
+    skip_forward: GuestFunction,
+    /// `void (*)(void *info)`
+    rewind: GuestFunction,
+    /// `void (*)(void *info)`, may be `NULL`.
+    release_info: GuestFunction,
+}
+unsafe impl SafeRead for CGDataProviderSequentialCallbacks {}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct CGDataProviderDirectCallbacks {
+    version: u32,
+    /// `const void *(*)(void *info)`, may be `NULL`.
+    get_byte_pointer: GuestFunction,
+    /// `void (*)(void *info, const void *pointer)`, may be `NULL`.
+    release_byte_pointer: GuestFunction,
+    /// `size_t (*)(void *info, void *buffer, off_t position, size_t count)`,
+    /// may be `NULL` if `get_byte_pointer` isn't.
+    get_bytes_at_position: GuestFunction,
+    /// `void (*)(void *info)`, may be `NULL`.
+    release_info: GuestFunction,
+}
+unsafe impl SafeRead for CGDataProviderDirectCallbacks {}
+
+#[derive(Copy, Clone)]
+enum Source {
+    /// Strong reference to an `NSData*` (`CFDataRef`).
+    Data(id),
+    /// Bytes owned by the app, see `CGDataProviderCreateWithData`.
+    Bytes {
+        info: MutVoidPtr,
+        bytes: ConstVoidPtr,
+        size: GuestUSize,
+        /// `void (*)(void *info, const void *data, size_t size)`, may be
+        /// `NULL`.
+        release_data: GuestFunction,
+    },
+    Sequential {
+        info: MutVoidPtr,
+        callbacks: CGDataProviderSequentialCallbacks,
+    },
+    Direct {
+        info: MutVoidPtr,
+        size: i64,
+        callbacks: CGDataProviderDirectCallbacks,
+    },
+}
+
+struct CGDataProviderHostObject {
+    source: Source,
+}
+impl HostObject for CGDataProviderHostObject {}
+
+pub type CGDataProviderRef = CFTypeRef;
+
+/// Size of the guest buffer used when reading from callbacks.
+const CHUNK_SIZE: GuestUSize = 4096;
+
+/// Get a copy of all the data a provider provides. For callback-based
+/// providers this calls into the guest.
+pub fn copy_bytes(env: &mut Environment, provider: CGDataProviderRef) -> Vec<u8> {
+    let source = env.objc.borrow::<CGDataProviderHostObject>(provider).source;
+    match source {
+        Source::Data(data) => ns_data::to_vec(env, data),
+        Source::Bytes { bytes, size, .. } => env.mem.bytes_at(bytes.cast(), size).to_vec(),
+        Source::Sequential {
+            info,
+            callbacks:
+                CGDataProviderSequentialCallbacks {
+                    get_bytes, rewind, ..
+                },
+        } => {
+            let () = rewind.call_from_host(env, (info,));
+            read_chunks(env, |env, buffer, _position| {
+                get_bytes.call_from_host(env, (info, buffer, CHUNK_SIZE))
+            })
+        }
+        Source::Direct {
+            info,
+            size,
+            callbacks:
+                CGDataProviderDirectCallbacks {
+                    get_byte_pointer,
+                    release_byte_pointer,
+                    get_bytes_at_position,
+                    ..
+                },
+        } => {
+            let size: GuestUSize = size.try_into().unwrap();
+            if get_byte_pointer.addr_with_thumb_bit() != 0 {
+                let pointer: ConstVoidPtr = get_byte_pointer.call_from_host(env, (info,));
+                let bytes = env.mem.bytes_at(pointer.cast(), size).to_vec();
+                if release_byte_pointer.addr_with_thumb_bit() != 0 {
+                    let () = release_byte_pointer.call_from_host(env, (info, pointer));
+                }
+                bytes
+            } else {
+                let mut bytes = read_chunks(env, |env, buffer, position| {
+                    let count = CHUNK_SIZE.min(size.saturating_sub(position));
+                    if count == 0 {
+                        return 0;
+                    }
+                    get_bytes_at_position
+                        .call_from_host(env, (info, buffer, position as i64, count))
+                });
+                bytes.truncate(size as usize);
+                bytes
+            }
+        }
+    }
+}
+
+/// Repeatedly call `read` with a guest buffer of [CHUNK_SIZE] bytes and the
+/// number of bytes read so far, until it returns zero.
+fn read_chunks<F>(env: &mut Environment, mut read: F) -> Vec<u8>
+where
+    F: FnMut(&mut Environment, MutVoidPtr, GuestUSize) -> GuestUSize,
+{
+    let buffer = env.mem.alloc(CHUNK_SIZE);
+    let mut bytes = Vec::new();
+    loop {
+        let count = read(env, buffer, bytes.len() as GuestUSize);
+        if count == 0 {
+            break;
+        }
+        let count = count.min(CHUNK_SIZE);
+        bytes.extend_from_slice(env.mem.bytes_at(buffer.cast(), count));
+    }
+    env.mem.free(buffer);
+    bytes
+}
+
+fn create(env: &mut Environment, source: Source) -> CGDataProviderRef {
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGDataProvider", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CGDataProviderHostObject { source }),
+        &mut env.mem,
+    )
+}
+
+/// Create a provider for a copy of some bytes, for host code.
+pub fn from_bytes(env: &mut Environment, bytes: &[u8]) -> CGDataProviderRef {
+    let data = ns_data::from_bytes(env, bytes);
+    create(env, Source::Data(data))
+}
+
+fn CGDataProviderCreateWithData(
+    env: &mut Environment,
+    info: MutVoidPtr,
+    bytes: ConstVoidPtr,
+    size: GuestUSize,
+    release_data: GuestFunction,
+) -> CGDataProviderRef {
+    create(
+        env,
+        Source::Bytes {
+            info,
+            bytes,
+            size,
+            release_data,
+        },
+    )
+}
+
+fn CGDataProviderCreateWithCFData(
+    env: &mut Environment,
+    data: id, // CFDataRef
+) -> CGDataProviderRef {
+    retain(env, data);
+    create(env, Source::Data(data))
+}
+
+fn CGDataProviderCreateSequential(
+    env: &mut Environment,
+    info: MutVoidPtr,
+    callbacks: ConstPtr<CGDataProviderSequentialCallbacks>,
+) -> CGDataProviderRef {
+    let callbacks = env.mem.read(callbacks);
+    create(env, Source::Sequential { info, callbacks })
+}
+
+fn CGDataProviderCreateDirect(
+    env: &mut Environment,
+    info: MutVoidPtr,
+    size: i64,
+    callbacks: ConstPtr<CGDataProviderDirectCallbacks>,
+) -> CGDataProviderRef {
+    let callbacks = env.mem.read(callbacks);
+    create(
+        env,
+        Source::Direct {
+            info,
+            size,
+            callbacks,
+        },
+    )
+}
+
+/// Returns a new `CFDataRef` (+1 reference).
+fn CGDataProviderCopyData(env: &mut Environment, provider: CGDataProviderRef) -> id {
+    let bytes = copy_bytes(env, provider);
+    ns_data::from_bytes(env, &bytes)
+}
+
+pub fn CGDataProviderRelease(env: &mut Environment, provider: CGDataProviderRef) {
+    if !provider.is_null() {
+        CFRelease(env, provider);
+    }
+}
+pub fn CGDataProviderRetain(
+    env: &mut Environment,
+    provider: CGDataProviderRef,
+) -> CGDataProviderRef {
+    if !provider.is_null() {
+        CFRetain(env, provider)
+    } else {
+        provider
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGDataProviderCreateWithData(_, _, _, _)),
+    export_c_func!(CGDataProviderCreateWithCFData(_)),
+    export_c_func!(CGDataProviderCreateSequential(_, _)),
+    export_c_func!(CGDataProviderCreateDirect(_, _, _)),
+    export_c_func!(CGDataProviderCopyData(_)),
+    export_c_func!(CGDataProviderRetain(_)),
+    export_c_func!(CGDataProviderRelease(_)),
+];
@@ -0,0 +1,169 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGFunction.h`
+
+use super::CGFloat;
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::mem::{ConstPtr, GuestUSize, MutPtr, MutVoidPtr, SafeRead};
+use crate::objc::{objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGFunction seems to be a CFType-based type, but in our implementation those
+// are just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CGFunction: NSObject
+
+- (())dealloc {
+    let &CGFunctionHostObject {
+        info, release_info, ..
+    } = env.objc.borrow(this);
+    if release_info.addr_with_thumb_bit() != 0 {
+        let () = release_info.call_from_host(env, (info,));
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+#[allow(dead_code)]
+#[repr(C, packed)]
+pub struct CGFunctionCallbacks {
+    version: u32,
+    /// `void (*)(void *info, const CGFloat *in, CGFloat *out)`
+    evaluate: GuestFunction,
+    /// `void (*)(void *info)`, may be `NULL`.
+    release_info: GuestFunction,
+}
+unsafe impl SafeRead for CGFunctionCallbacks {}
+
+struct CGFunctionHostObject {
+    info: MutVoidPtr,
+    /// Input domain (only one-dimensional functions are supported).
+    domain: (CGFloat, CGFloat),
+    range_dimension: GuestUSize,
+    /// Minimum and maximum for each output, if specified.
+    range: Option<Vec<(CGFloat, CGFloat)>>,
+    evaluate: GuestFunction,
+    release_info: GuestFunction,
+}
+impl HostObject for CGFunctionHostObject {}
+
+pub type CGFunctionRef = CFTypeRef;
+
+/// Number of samples taken of a function when it's used as a color ramp.
+pub const SAMPLE_COUNT: usize = 256;
+
+/// Evaluate a function over its whole domain, interpreting the outputs as a
+/// non-premultiplied color (gray, gray + alpha, RGB or RGBA depending on the
+/// number of outputs). Calling into the guest for every pixel would be far
+/// too slow, so shadings use this table instead.
+pub fn sample_colors(
+    env: &mut Environment,
+    function: CGFunctionRef,
+) -> Vec<(CGFloat, CGFloat, CGFloat, CGFloat)> {
+    let host_object = env.objc.borrow::<CGFunctionHostObject>(function);
+    let info = host_object.info;
+    let (d0, d1) = host_object.domain;
+    let range_dimension = host_object.range_dimension;
+    let range = host_object.range.clone();
+    let evaluate = host_object.evaluate;
+
+    let input: MutPtr<CGFloat> = env.mem.alloc(4).cast();
+    let output: MutPtr<CGFloat> = env.mem.alloc((4 * range_dimension).max(4)).cast();
+    let mut colors = Vec::with_capacity(SAMPLE_COUNT);
+    for i in 0..SAMPLE_COUNT {
+        let t = i as CGFloat / (SAMPLE_COUNT - 1) as CGFloat;
+        env.mem.write(input, d0 + (d1 - d0) * t);
+        let () = evaluate.call_from_host(env, (info, input.cast_const(), output));
+        let mut values: Vec<CGFloat> = (0..range_dimension)
+            .map(|i| env.mem.read(output + i))
+            .collect();
+        if let Some(ref range) = range {
+            for (value, &(min, max)) in values.iter_mut().zip(range.iter()) {
+                *value = value.clamp(min, max);
+            }
+        }
+        colors.push(match values[..] {
+            [gray] => (gray, gray, gray, 1.0),
+            [gray, alpha] => (gray, gray, gray, alpha),
+            [r, g, b] => (r, g, b, 1.0),
+            [r, g, b, a, ..] => (r, g, b, a),
+            [] => (0.0, 0.0, 0.0, 0.0),
+        });
+    }
+    env.mem.free(input.cast());
+    env.mem.free(output.cast());
+    colors
+}
+
+fn CGFunctionCreate(
+    env: &mut Environment,
+    info: MutVoidPtr,
+    domain_dimension: GuestUSize,
+    domain: ConstPtr<CGFloat>,
+    range_dimension: GuestUSize,
+    range: ConstPtr<CGFloat>,
+    callbacks: ConstPtr<CGFunctionCallbacks>,
+) -> CGFunctionRef {
+    // TODO: multi-dimensional functions, which aren't used by shadings.
+    assert!(domain_dimension == 1);
+    let domain = if domain.is_null() {
+        (0.0, 1.0)
+    } else {
+        (env.mem.read(domain), env.mem.read(domain + 1))
+    };
+    let range = (!range.is_null()).then(|| {
+        (0..range_dimension)
+            .map(|i| (env.mem.read(range + i * 2), env.mem.read(range + i * 2 + 1)))
+            .collect()
+    });
+    let CGFunctionCallbacks {
+        evaluate,
+        release_info,
+        ..
+    } = env.mem.read(callbacks);
+
+    let host_object = CGFunctionHostObject {
+        info,
+        domain,
+        range_dimension,
+        range,
+        evaluate,
+        release_info,
+    };
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGFunction", &mut env.mem);
+    env.objc
+        .alloc_object(isa, Box::new(host_object), &mut env.mem)
+}
+
+pub fn CGFunctionRelease(env: &mut Environment, function: CGFunctionRef) {
+    if !function.is_null() {
+        CFRelease(env, function);
+    }
+}
+pub fn CGFunctionRetain(env: &mut Environment, function: CGFunctionRef) -> CGFunctionRef {
+    if !function.is_null() {
+        CFRetain(env, function)
+    } else {
+        function
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGFunctionCreate(_, _, _, _, _, _)),
+    export_c_func!(CGFunctionRetain(_)),
+    export_c_func!(CGFunctionRelease(_)),
+];
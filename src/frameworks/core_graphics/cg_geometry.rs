@@ -9,7 +9,7 @@ use super::CGFloat;
 use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
 use crate::mem::SafeRead;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(C, packed)]
 pub struct CGPoint {
     pub x: CGFloat,
@@ -32,7 +32,7 @@ impl GuestArg for CGPoint {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(C, packed)]
 pub struct CGSize {
     pub width: CGFloat,
@@ -55,7 +55,7 @@ impl GuestArg for CGSize {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(C, packed)]
 pub struct CGRect {
     pub origin: CGPoint,
@@ -0,0 +1,168 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGGradient.h`
+
+use super::cg_color;
+use super::cg_color_space::CGColorSpaceRef;
+use super::cg_function::SAMPLE_COUNT;
+use super::CGFloat;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::frameworks::foundation::NSUInteger;
+use crate::mem::{ConstPtr, GuestUSize};
+use crate::objc::{id, msg, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGGradient seems to be a CFType-based type, but in our implementation those
+// are just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CGGradient: NSObject
+@end
+
+};
+
+struct CGGradientHostObject {
+    /// The gradient evaluated at evenly spaced locations from 0 to 1.
+    samples: Vec<(CGFloat, CGFloat, CGFloat, CGFloat)>,
+}
+impl HostObject for CGGradientHostObject {}
+
+pub type CGGradientRef = CFTypeRef;
+
+pub type CGGradientDrawingOptions = u32;
+pub const kCGGradientDrawsBeforeStartLocation: CGGradientDrawingOptions = 1 << 0;
+pub const kCGGradientDrawsAfterEndLocation: CGGradientDrawingOptions = 1 << 1;
+
+/// Get the gradient evaluated at [SAMPLE_COUNT] evenly spaced locations.
+pub fn samples(
+    env: &Environment,
+    gradient: CGGradientRef,
+) -> &[(CGFloat, CGFloat, CGFloat, CGFloat)] {
+    &env.objc.borrow::<CGGradientHostObject>(gradient).samples
+}
+
+/// Interpolate between color stops. `stops` must be sorted by location.
+fn sample_stops(
+    stops: &[(CGFloat, (CGFloat, CGFloat, CGFloat, CGFloat))],
+) -> Vec<(CGFloat, CGFloat, CGFloat, CGFloat)> {
+    (0..SAMPLE_COUNT)
+        .map(|i| {
+            let t = i as CGFloat / (SAMPLE_COUNT - 1) as CGFloat;
+            let next = stops.iter().position(|&(location, _)| location > t);
+            match next {
+                Some(0) => stops[0].1,
+                None => stops.last().unwrap().1,
+                Some(next) => {
+                    let (l0, c0) = stops[next - 1];
+                    let (l1, c1) = stops[next];
+                    let f = (t - l0) / (l1 - l0);
+                    (
+                        c0.0 + (c1.0 - c0.0) * f,
+                        c0.1 + (c1.1 - c0.1) * f,
+                        c0.2 + (c1.2 - c0.2) * f,
+                        c0.3 + (c1.3 - c0.3) * f,
+                    )
+                }
+            }
+        })
+        .collect()
+}
+
+fn create(
+    env: &mut Environment,
+    colors: Vec<(CGFloat, CGFloat, CGFloat, CGFloat)>,
+    locations: ConstPtr<CGFloat>,
+) -> CGGradientRef {
+    let count = colors.len() as GuestUSize;
+    let mut stops: Vec<_> = colors
+        .into_iter()
+        .enumerate()
+        .map(|(i, color)| {
+            let location = if locations.is_null() {
+                if count > 1 {
+                    i as CGFloat / (count - 1) as CGFloat
+                } else {
+                    0.0
+                }
+            } else {
+                env.mem.read(locations + i as GuestUSize)
+            };
+            (location, color)
+        })
+        .collect();
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let samples = if stops.is_empty() {
+        vec![(0.0, 0.0, 0.0, 0.0); SAMPLE_COUNT]
+    } else {
+        sample_stops(&stops)
+    };
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGGradient", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CGGradientHostObject { samples }),
+        &mut env.mem,
+    )
+}
+
+fn CGGradientCreateWithColorComponents(
+    env: &mut Environment,
+    _space: CGColorSpaceRef,
+    components: ConstPtr<CGFloat>,
+    locations: ConstPtr<CGFloat>,
+    count: GuestUSize,
+) -> CGGradientRef {
+    // TODO: support color spaces other than RGB
+    let colors = (0..count)
+        .map(|i| {
+            let [r, g, b, a] = [0, 1, 2, 3].map(|j| env.mem.read(components + i * 4 + j));
+            (r, g, b, a)
+        })
+        .collect();
+    create(env, colors, locations)
+}
+
+fn CGGradientCreateWithColors(
+    env: &mut Environment,
+    _space: CGColorSpaceRef,
+    colors: id, // CFArrayRef of CGColorRef
+    locations: ConstPtr<CGFloat>,
+) -> CGGradientRef {
+    let count: NSUInteger = msg![env; colors count];
+    let colors = (0..count)
+        .map(|i| {
+            let color: id = msg![env; colors objectAtIndex:i];
+            cg_color::get_rgba(env, color)
+        })
+        .collect();
+    create(env, colors, locations)
+}
+
+pub fn CGGradientRelease(env: &mut Environment, gradient: CGGradientRef) {
+    if !gradient.is_null() {
+        CFRelease(env, gradient);
+    }
+}
+pub fn CGGradientRetain(env: &mut Environment, gradient: CGGradientRef) -> CGGradientRef {
+    if !gradient.is_null() {
+        CFRetain(env, gradient)
+    } else {
+        gradient
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGGradientCreateWithColorComponents(_, _, _, _)),
+    export_c_func!(CGGradientCreateWithColors(_, _, _)),
+    export_c_func!(CGGradientRetain(_)),
+    export_c_func!(CGGradientRelease(_)),
+];
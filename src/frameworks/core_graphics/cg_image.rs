@@ -5,6 +5,45 @@
  */
 //! `CGImage.h`
 
+use super::cg_color_space::{kCGColorSpaceGenericGray, CGColorSpaceHostObject, CGColorSpaceRef};
+use super::cg_data_provider::{self, CGDataProviderRef, CGDataProviderRelease};
+use super::{CGFloat, CGRect};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::image::Image;
+use crate::mem::{ConstPtr, GuestUSize};
+use crate::objc::{nil, objc_classes, ClassExports, HostObject, ObjC};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGImage seems to be a CFType-based type, but in our implementation those
+// are just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CGImage: NSObject
+
+- (())dealloc {
+    if let Some(provider) = env.objc.borrow::<CGImageHostObject>(this).data_provider {
+        CGDataProviderRelease(env, provider);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+struct CGImageHostObject {
+    image: Image,
+    /// Created on demand by `CGImageGetDataProvider`.
+    data_provider: Option<CGDataProviderRef>,
+}
+impl HostObject for CGImageHostObject {}
+
+pub type CGImageRef = CFTypeRef;
+
 pub type CGImageAlphaInfo = u32;
 pub const kCGImageAlphaNone: CGImageAlphaInfo = 0;
 pub const kCGImageAlphaPremultipliedLast: CGImageAlphaInfo = 1;
@@ -14,3 +53,341 @@ pub const kCGImageAlphaFirst: CGImageAlphaInfo = 4;
 pub const kCGImageAlphaNoneSkipLast: CGImageAlphaInfo = 5;
 pub const kCGImageAlphaNoneSkipFirst: CGImageAlphaInfo = 6;
 pub const kCGImageAlphaOnly: CGImageAlphaInfo = 7;
+
+pub type CGBitmapInfo = u32;
+pub const kCGBitmapAlphaInfoMask: CGBitmapInfo = 0x1F;
+pub const kCGBitmapFloatComponents: CGBitmapInfo = 1 << 8;
+pub const kCGBitmapByteOrderMask: CGBitmapInfo = 0x7000;
+pub const kCGBitmapByteOrderDefault: CGBitmapInfo = 0;
+pub const kCGBitmapByteOrder16Little: CGBitmapInfo = 1 << 12;
+pub const kCGBitmapByteOrder32Little: CGBitmapInfo = 2 << 12;
+pub const kCGBitmapByteOrder16Big: CGBitmapInfo = 3 << 12;
+pub const kCGBitmapByteOrder32Big: CGBitmapInfo = 4 << 12;
+
+pub type CGColorRenderingIntent = i32;
+
+/// Create a CGImage from a decoded image. The image has a reference count of
+/// one, like any `CGImageCreate*` result.
+pub fn from_image(env: &mut Environment, image: Image) -> CGImageRef {
+    let isa = env.objc.get_known_class("_touchHLE_CGImage", &mut env.mem);
+    let host_object = CGImageHostObject {
+        image,
+        data_provider: None,
+    };
+    env.objc
+        .alloc_object(isa, Box::new(host_object), &mut env.mem)
+}
+
+/// Borrow the decoded image that a CGImage wraps.
+pub fn borrow_image(objc: &ObjC, image: CGImageRef) -> &Image {
+    &objc.borrow::<CGImageHostObject>(image).image
+}
+
+pub fn CGImageRelease(env: &mut Environment, image: CGImageRef) {
+    if !image.is_null() {
+        CFRelease(env, image);
+    }
+}
+pub fn CGImageRetain(env: &mut Environment, image: CGImageRef) -> CGImageRef {
+    if !image.is_null() {
+        CFRetain(env, image)
+    } else {
+        image
+    }
+}
+
+pub fn CGImageGetWidth(env: &mut Environment, image: CGImageRef) -> GuestUSize {
+    borrow_image(&env.objc, image).dimensions().0
+}
+pub fn CGImageGetHeight(env: &mut Environment, image: CGImageRef) -> GuestUSize {
+    borrow_image(&env.objc, image).dimensions().1
+}
+
+/// Decode pixel data in an arbitrary bitmap layout to 8-bit non-premultiplied
+/// RGBA. Returns [None] for unsupported layouts.
+fn decode_bitmap(
+    bytes: &[u8],
+    width: GuestUSize,
+    height: GuestUSize,
+    bits_per_component: GuestUSize,
+    bits_per_pixel: GuestUSize,
+    bytes_per_row: GuestUSize,
+    color_components: GuestUSize,
+    bitmap_info: CGBitmapInfo,
+) -> Option<Vec<u8>> {
+    let alpha_info = bitmap_info & kCGBitmapAlphaInfoMask;
+    let byte_order = bitmap_info & kCGBitmapByteOrderMask;
+    if bitmap_info & kCGBitmapFloatComponents != 0 {
+        return None; // TODO: floating-point components
+    }
+    if !(1..=16).contains(&bits_per_component)
+        || bits_per_pixel % 8 != 0
+        || !(8..=64).contains(&bits_per_pixel)
+    {
+        return None;
+    }
+
+    // Components are listed from the most significant bits of the pixel.
+    // Padding (the skipped alpha, or unused low bits) takes whatever bits are
+    // left over.
+    let (alpha_first, has_alpha, premultiplied, padding_first) = match alpha_info {
+        kCGImageAlphaNone => (false, false, false, false),
+        kCGImageAlphaPremultipliedLast => (false, true, true, false),
+        kCGImageAlphaPremultipliedFirst => (true, true, true, false),
+        kCGImageAlphaLast => (false, true, false, false),
+        kCGImageAlphaFirst => (true, true, false, false),
+        kCGImageAlphaNoneSkipLast => (false, false, false, false),
+        kCGImageAlphaNoneSkipFirst => (false, false, false, true),
+        kCGImageAlphaOnly => (false, true, false, false),
+        _ => return None,
+    };
+    let color_components = if alpha_info == kCGImageAlphaOnly {
+        0
+    } else {
+        color_components
+    };
+    let component_count = color_components + has_alpha as GuestUSize;
+    let padding = bits_per_pixel.checked_sub(component_count * bits_per_component)?;
+    // Bytes are reversed within groups of this size for little-endian
+    // layouts.
+    let swap_group = match byte_order {
+        kCGBitmapByteOrderDefault | kCGBitmapByteOrder16Big | kCGBitmapByteOrder32Big => 1,
+        kCGBitmapByteOrder16Little => 2,
+        kCGBitmapByteOrder32Little => 4,
+        _ => return None,
+    };
+    let bytes_per_pixel = bits_per_pixel / 8;
+    if bytes_per_pixel % swap_group != 0 {
+        return None;
+    }
+    let row_size = width.checked_mul(bytes_per_pixel)?;
+    if bytes_per_row < row_size {
+        return None;
+    }
+    if height > 0 {
+        let needed = (height - 1)
+            .checked_mul(bytes_per_row)?
+            .checked_add(row_size)?;
+        if (bytes.len() as u64) < needed as u64 {
+            return None;
+        }
+    }
+
+    let max = ((1u64 << bits_per_component) - 1) as CGFloat;
+    let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let start = (y * bytes_per_row + x * bytes_per_pixel) as usize;
+            let pixel_bytes = &bytes[start..start + bytes_per_pixel as usize];
+            let mut value: u64 = 0;
+            for group in pixel_bytes.chunks(swap_group as usize) {
+                for &byte in group.iter().rev() {
+                    value = (value << 8) | byte as u64;
+                }
+            }
+
+            let mut shift = bits_per_pixel;
+            if padding_first {
+                shift -= padding;
+            }
+            let mut next_component = || {
+                shift -= bits_per_component;
+                ((value >> shift) & ((1u64 << bits_per_component) - 1)) as CGFloat / max
+            };
+            let mut alpha = 1.0;
+            if has_alpha && alpha_first {
+                alpha = next_component();
+            }
+            let color: Vec<CGFloat> = (0..color_components).map(|_| next_component()).collect();
+            if has_alpha && !alpha_first {
+                alpha = next_component();
+            }
+
+            let (r, g, b) = match color[..] {
+                [] => (0.0, 0.0, 0.0),
+                [gray] => (gray, gray, gray),
+                [r, g, b] => (r, g, b),
+                _ => unreachable!(),
+            };
+            let (r, g, b) = if premultiplied && alpha > 0.0 {
+                (r / alpha, g / alpha, b / alpha)
+            } else {
+                (r, g, b)
+            };
+            rgba.extend_from_slice(
+                &[r, g, b, alpha].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8),
+            );
+        }
+    }
+    Some(rgba)
+}
+
+fn CGImageCreate(
+    env: &mut Environment,
+    width: GuestUSize,
+    height: GuestUSize,
+    bits_per_component: GuestUSize,
+    bits_per_pixel: GuestUSize,
+    bytes_per_row: GuestUSize,
+    color_space: CGColorSpaceRef,
+    bitmap_info: CGBitmapInfo,
+    provider: CGDataProviderRef,
+    decode: ConstPtr<CGFloat>,
+    _should_interpolate: bool,
+    _intent: CGColorRenderingIntent,
+) -> CGImageRef {
+    if !decode.is_null() {
+        log!("TODO: CGImageCreate() decode array is ignored");
+    }
+    let color_components = if color_space.is_null() {
+        0
+    } else if env.objc.borrow::<CGColorSpaceHostObject>(color_space).name
+        == kCGColorSpaceGenericGray
+    {
+        1
+    } else {
+        3
+    };
+    let bytes = cg_data_provider::copy_bytes(env, provider);
+    let Some(rgba) = decode_bitmap(
+        &bytes,
+        width,
+        height,
+        bits_per_component,
+        bits_per_pixel,
+        bytes_per_row,
+        color_components,
+        bitmap_info,
+    ) else {
+        log!(
+            "Warning: CGImageCreate() with unsupported layout ({}x{}, {} bpc, {} bpp, {} bytes per row, bitmap info {:#x}) or only {} bytes of data, returning NULL",
+            width,
+            height,
+            bits_per_component,
+            bits_per_pixel,
+            bytes_per_row,
+            bitmap_info,
+            bytes.len(),
+        );
+        return nil;
+    };
+    from_image(env, Image::from_pixels(rgba, (width, height)))
+}
+
+fn CGImageCreateWithImageInRect(
+    env: &mut Environment,
+    image: CGImageRef,
+    rect: CGRect,
+) -> CGImageRef {
+    let source = borrow_image(&env.objc, image);
+    let (width, height) = source.dimensions();
+    // The rectangle is made integral and then clipped to the image.
+    let x0 = rect.origin.x.floor().max(0.0);
+    let y0 = rect.origin.y.floor().max(0.0);
+    let x1 = (rect.origin.x + rect.size.width)
+        .ceil()
+        .min(width as CGFloat);
+    let y1 = (rect.origin.y + rect.size.height)
+        .ceil()
+        .min(height as CGFloat);
+    if !(x1 > x0 && y1 > y0) {
+        return nil;
+    }
+    let (x0, y0, x1, y1) = (x0 as usize, y0 as usize, x1 as usize, y1 as usize);
+
+    let pixels = source.pixels();
+    let mut rgba = Vec::with_capacity((x1 - x0) * (y1 - y0) * 4);
+    for y in y0..y1 {
+        let row_start = (y * width as usize + x0) * 4;
+        rgba.extend_from_slice(&pixels[row_start..row_start + (x1 - x0) * 4]);
+    }
+    let cropped = Image::from_pixels(rgba, ((x1 - x0) as u32, (y1 - y0) as u32));
+    from_image(env, cropped)
+}
+
+// Our CGImages are always stored as 8-bit non-premultiplied RGBA, so that's
+// what the layout getters describe.
+
+fn CGImageGetBitsPerComponent(_env: &mut Environment, _image: CGImageRef) -> GuestUSize {
+    8
+}
+fn CGImageGetBitsPerPixel(_env: &mut Environment, _image: CGImageRef) -> GuestUSize {
+    32
+}
+fn CGImageGetBytesPerRow(env: &mut Environment, image: CGImageRef) -> GuestUSize {
+    CGImageGetWidth(env, image) * 4
+}
+fn CGImageGetAlphaInfo(_env: &mut Environment, _image: CGImageRef) -> CGImageAlphaInfo {
+    kCGImageAlphaLast
+}
+fn CGImageGetBitmapInfo(_env: &mut Environment, _image: CGImageRef) -> CGBitmapInfo {
+    kCGImageAlphaLast | kCGBitmapByteOrderDefault
+}
+
+/// The result is owned by the image, like any `Get` function's.
+fn CGImageGetDataProvider(env: &mut Environment, image: CGImageRef) -> CGDataProviderRef {
+    if let Some(provider) = env.objc.borrow::<CGImageHostObject>(image).data_provider {
+        return provider;
+    }
+    let pixels = borrow_image(&env.objc, image).pixels().to_vec();
+    let provider = cg_data_provider::from_bytes(env, &pixels);
+    env.objc
+        .borrow_mut::<CGImageHostObject>(image)
+        .data_provider = Some(provider);
+    provider
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGImageCreate(_, _, _, _, _, _, _, _, _, _, _)),
+    export_c_func!(CGImageCreateWithImageInRect(_, _)),
+    export_c_func!(CGImageRetain(_)),
+    export_c_func!(CGImageRelease(_)),
+    export_c_func!(CGImageGetWidth(_)),
+    export_c_func!(CGImageGetHeight(_)),
+    export_c_func!(CGImageGetBitsPerComponent(_)),
+    export_c_func!(CGImageGetBitsPerPixel(_)),
+    export_c_func!(CGImageGetBytesPerRow(_)),
+    export_c_func!(CGImageGetAlphaInfo(_)),
+    export_c_func!(CGImageGetBitmapInfo(_)),
+    export_c_func!(CGImageGetDataProvider(_)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_rgba_premultiplied_last() {
+        let bytes = [128, 0, 0, 128, 0, 255, 0, 255];
+        let rgba = decode_bitmap(&bytes, 2, 1, 8, 32, 8, 3, kCGImageAlphaPremultipliedLast);
+        assert_eq!(rgba, Some(vec![255, 0, 0, 128, 0, 255, 0, 255]));
+    }
+
+    #[test]
+    fn decode_bgra_little_endian() {
+        // ARGB as a little-endian 32-bit integer is BGRA in memory.
+        let bytes = [0x30, 0x20, 0x10, 0xFF];
+        let info = kCGImageAlphaNoneSkipFirst | kCGBitmapByteOrder32Little;
+        let rgba = decode_bitmap(&bytes, 1, 1, 8, 32, 4, 3, info);
+        assert_eq!(rgba, Some(vec![0x10, 0x20, 0x30, 255]));
+    }
+
+    #[test]
+    fn decode_rgb555_and_row_padding() {
+        // X1R5G5B5, little-endian 16-bit, with two bytes of padding per row.
+        let bytes = [0x00, 0x7C, 0xAA, 0xAA, 0x1F, 0x00];
+        let info = kCGImageAlphaNoneSkipFirst | kCGBitmapByteOrder16Little;
+        let rgba = decode_bitmap(&bytes, 1, 2, 5, 16, 4, 3, info);
+        assert_eq!(rgba, Some(vec![255, 0, 0, 255, 0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn decode_gray_and_rejects_short_data() {
+        let rgba = decode_bitmap(&[0, 255], 2, 1, 8, 8, 2, 1, kCGImageAlphaNone);
+        assert_eq!(rgba, Some(vec![0, 0, 0, 255, 255, 255, 255, 255]));
+        assert_eq!(
+            decode_bitmap(&[0], 2, 1, 8, 8, 2, 1, kCGImageAlphaNone),
+            None
+        );
+    }
+}
@@ -0,0 +1,679 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGPath.h`
+
+use super::cg_affine_transform::CGAffineTransform;
+use super::rasterizer::{winding_number, FillRule};
+use super::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::mem::ConstPtr;
+use crate::objc::{nil, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+use std::f32::consts::{FRAC_PI_2, PI};
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGPath seems to be a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere. CGMutablePath is the same type.
+@implementation _touchHLE_CGPath: NSObject
+@end
+
+};
+
+struct CGPathHostObject {
+    path: Path,
+}
+impl HostObject for CGPathHostObject {}
+
+pub type CGPathRef = CFTypeRef;
+pub type CGMutablePathRef = CFTypeRef;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathElement {
+    MoveTo(CGPoint),
+    LineTo(CGPoint),
+    QuadCurveTo(CGPoint, CGPoint),
+    CurveTo(CGPoint, CGPoint, CGPoint),
+    Close,
+}
+
+/// Host-side representation of a path, shared by `CGPath` and the current
+/// path of a `CGContext`.
+#[derive(Clone, Debug, Default)]
+pub struct Path {
+    elements: Vec<PathElement>,
+    /// Start of the current subpath, where `Close` returns to.
+    subpath_start: Option<CGPoint>,
+    current_point: Option<CGPoint>,
+}
+
+/// A flattened subpath: a polyline, and whether it is closed.
+pub type Polyline = (Vec<(CGFloat, CGFloat)>, bool);
+
+/// Maximum distance in device pixels between a curve and its flattening.
+const FLATNESS: CGFloat = 0.25;
+
+impl Path {
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+    pub fn current_point(&self) -> Option<CGPoint> {
+        self.current_point
+    }
+    pub fn elements(&self) -> &[PathElement] {
+        &self.elements
+    }
+
+    pub fn move_to(&mut self, point: CGPoint) {
+        self.elements.push(PathElement::MoveTo(point));
+        self.subpath_start = Some(point);
+        self.current_point = Some(point);
+    }
+    /// Drawing from nowhere isn't allowed, but Core Graphics just logs an
+    /// error, and treating it as a move seems like the most useful fallback.
+    fn ensure_current_point(&mut self, point: CGPoint) -> bool {
+        if self.current_point.is_none() {
+            log!("Warning: path has no current point, treating as a move");
+            self.move_to(point);
+            return false;
+        }
+        true
+    }
+    pub fn line_to(&mut self, point: CGPoint) {
+        if self.ensure_current_point(point) {
+            self.elements.push(PathElement::LineTo(point));
+            self.current_point = Some(point);
+        }
+    }
+    pub fn quad_curve_to(&mut self, control: CGPoint, point: CGPoint) {
+        if self.ensure_current_point(point) {
+            self.elements.push(PathElement::QuadCurveTo(control, point));
+            self.current_point = Some(point);
+        }
+    }
+    pub fn curve_to(&mut self, control1: CGPoint, control2: CGPoint, point: CGPoint) {
+        if self.ensure_current_point(point) {
+            self.elements
+                .push(PathElement::CurveTo(control1, control2, point));
+            self.current_point = Some(point);
+        }
+    }
+    pub fn close(&mut self) {
+        if let Some(start) = self.subpath_start {
+            if !matches!(self.elements.last(), Some(PathElement::Close)) {
+                self.elements.push(PathElement::Close);
+            }
+            self.current_point = Some(start);
+        }
+    }
+
+    pub fn add_rect(&mut self, rect: CGRect, transform: CGAffineTransform) {
+        let CGRect { origin, size } = rect;
+        let p = |x, y| transform.apply_to_point(CGPoint { x, y });
+        self.move_to(p(origin.x, origin.y));
+        self.line_to(p(origin.x + size.width, origin.y));
+        self.line_to(p(origin.x + size.width, origin.y + size.height));
+        self.line_to(p(origin.x, origin.y + size.height));
+        self.close();
+    }
+
+    pub fn add_ellipse_in_rect(&mut self, rect: CGRect, transform: CGAffineTransform) {
+        let CGRect { origin, size } = rect;
+        let (rx, ry) = (size.width / 2.0, size.height / 2.0);
+        let (cx, cy) = (origin.x + rx, origin.y + ry);
+        // Standard constant for approximating a quarter circle with a cubic.
+        const K: CGFloat = 0.552_284_8;
+        let (kx, ky) = (rx * K, ry * K);
+        let p = |x, y| transform.apply_to_point(CGPoint { x, y });
+        self.move_to(p(cx + rx, cy));
+        self.curve_to(p(cx + rx, cy + ky), p(cx + kx, cy + ry), p(cx, cy + ry));
+        self.curve_to(p(cx - kx, cy + ry), p(cx - rx, cy + ky), p(cx - rx, cy));
+        self.curve_to(p(cx - rx, cy - ky), p(cx - kx, cy - ry), p(cx, cy - ry));
+        self.curve_to(p(cx + kx, cy - ry), p(cx + rx, cy - ky), p(cx + rx, cy));
+        self.close();
+    }
+
+    /// Add an arc of a circle, connected to the current point by a line if
+    /// there is one. Angles are in radians. `clockwise` is in terms of the
+    /// default (y-up) Core Graphics coordinate system, so it means the angle
+    /// decreases.
+    pub fn add_arc(
+        &mut self,
+        center: CGPoint,
+        radius: CGFloat,
+        start_angle: CGFloat,
+        end_angle: CGFloat,
+        clockwise: bool,
+        transform: CGAffineTransform,
+    ) {
+        let mut sweep = end_angle - start_angle;
+        if clockwise {
+            if sweep > 0.0 {
+                sweep = sweep.rem_euclid(2.0 * PI) - 2.0 * PI;
+            }
+            sweep = sweep.max(-2.0 * PI);
+        } else {
+            if sweep < 0.0 {
+                sweep = sweep.rem_euclid(2.0 * PI);
+            }
+            sweep = sweep.min(2.0 * PI);
+        }
+
+        let point_at = |angle: CGFloat| {
+            let (sin, cos) = angle.sin_cos();
+            transform.apply_to_point(CGPoint {
+                x: center.x + radius * cos,
+                y: center.y + radius * sin,
+            })
+        };
+        let start = point_at(start_angle);
+        if self.current_point.is_some() {
+            self.line_to(start);
+        } else {
+            self.move_to(start);
+        }
+
+        // Each piece is at most a quarter circle, so a cubic is a good fit.
+        let pieces = ((sweep.abs() / FRAC_PI_2).ceil() as u32).max(1);
+        let step = sweep / pieces as CGFloat;
+        let k = 4.0 / 3.0 * (step / 4.0).tan();
+        let mut angle = start_angle;
+        for _ in 0..pieces {
+            let next_angle = angle + step;
+            let (sin0, cos0) = angle.sin_cos();
+            let (sin1, cos1) = next_angle.sin_cos();
+            let control1 = transform.apply_to_point(CGPoint {
+                x: center.x + radius * (cos0 - k * sin0),
+                y: center.y + radius * (sin0 + k * cos0),
+            });
+            let control2 = transform.apply_to_point(CGPoint {
+                x: center.x + radius * (cos1 + k * sin1),
+                y: center.y + radius * (sin1 - k * cos1),
+            });
+            self.curve_to(control1, control2, point_at(next_angle));
+            angle = next_angle;
+        }
+    }
+
+    /// Add an arc tangent to the lines from the current point to `point1`, and
+    /// from `point1` to `point2`, like `CGContextAddArcToPoint`. Both points
+    /// are in the space that `transform` maps from.
+    pub fn add_arc_to_point(
+        &mut self,
+        point1: CGPoint,
+        point2: CGPoint,
+        radius: CGFloat,
+        transform: CGAffineTransform,
+    ) {
+        let Some(current) = self.current_point else {
+            self.move_to(transform.apply_to_point(point1));
+            return;
+        };
+        let p0 = transform.invert().apply_to_point(current);
+        let (d0x, d0y) = (p0.x - point1.x, p0.y - point1.y);
+        let (d2x, d2y) = (point2.x - point1.x, point2.y - point1.y);
+        let len0 = d0x.hypot(d0y);
+        let len2 = d2x.hypot(d2y);
+        let cross = d0x * d2y - d0y * d2x;
+        if len0 == 0.0 || len2 == 0.0 || cross.abs() < 1e-6 || radius <= 0.0 {
+            self.line_to(transform.apply_to_point(point1));
+            return;
+        }
+        let (u0x, u0y) = (d0x / len0, d0y / len0);
+        let (u2x, u2y) = (d2x / len2, d2y / len2);
+        // Half the angle between the two lines.
+        let half_angle = ((u0x * u2x + u0y * u2y).clamp(-1.0, 1.0).acos()) / 2.0;
+        let tangent_distance = radius / half_angle.tan();
+        let t0 = CGPoint {
+            x: point1.x + u0x * tangent_distance,
+            y: point1.y + u0y * tangent_distance,
+        };
+        let t2 = CGPoint {
+            x: point1.x + u2x * tangent_distance,
+            y: point1.y + u2y * tangent_distance,
+        };
+        let (bx, by) = (u0x + u2x, u0y + u2y);
+        let b_len = bx.hypot(by);
+        let center_distance = radius / half_angle.sin();
+        let center = CGPoint {
+            x: point1.x + bx / b_len * center_distance,
+            y: point1.y + by / b_len * center_distance,
+        };
+        let start_angle = (t0.y - center.y).atan2(t0.x - center.x);
+        let end_angle = (t2.y - center.y).atan2(t2.x - center.x);
+        self.add_arc(
+            center,
+            radius,
+            start_angle,
+            end_angle,
+            cross > 0.0,
+            transform,
+        );
+    }
+
+    pub fn add_path(&mut self, other: &Path, transform: CGAffineTransform) {
+        let p = |point| transform.apply_to_point(point);
+        for &element in &other.elements {
+            match element {
+                PathElement::MoveTo(a) => self.move_to(p(a)),
+                PathElement::LineTo(a) => self.line_to(p(a)),
+                PathElement::QuadCurveTo(a, b) => self.quad_curve_to(p(a), p(b)),
+                PathElement::CurveTo(a, b, c) => self.curve_to(p(a), p(b), p(c)),
+                PathElement::Close => self.close(),
+            }
+        }
+    }
+
+    pub fn transformed(&self, transform: CGAffineTransform) -> Path {
+        let mut new = Path::default();
+        new.add_path(self, transform);
+        new
+    }
+
+    /// Convert the path to polylines. Curves are subdivided finely enough for
+    /// the path's current coordinate space to be device pixels.
+    pub fn flatten(&self) -> Vec<Polyline> {
+        let mut polylines: Vec<Polyline> = Vec::new();
+        let mut current = (0.0, 0.0);
+        // Anything drawn after a close without a move starts a new subpath at
+        // the point the closed subpath started.
+        let mut after_close = false;
+        for &element in &self.elements {
+            if after_close && !matches!(element, PathElement::MoveTo(_) | PathElement::Close) {
+                polylines.push((vec![current], false));
+            }
+            after_close = false;
+            match element {
+                PathElement::MoveTo(p) => {
+                    current = (p.x, p.y);
+                    polylines.push((vec![current], false));
+                }
+                PathElement::LineTo(p) => {
+                    current = (p.x, p.y);
+                    polylines.last_mut().unwrap().0.push(current);
+                }
+                PathElement::QuadCurveTo(c, p) => {
+                    let (x0, y0) = current;
+                    // Elevate to a cubic, which is exact.
+                    let c1 = (x0 + 2.0 / 3.0 * (c.x - x0), y0 + 2.0 / 3.0 * (c.y - y0));
+                    let c2 = (p.x + 2.0 / 3.0 * (c.x - p.x), p.y + 2.0 / 3.0 * (c.y - p.y));
+                    let points = &mut polylines.last_mut().unwrap().0;
+                    flatten_cubic(current, c1, c2, (p.x, p.y), points);
+                    current = (p.x, p.y);
+                }
+                PathElement::CurveTo(c1, c2, p) => {
+                    let points = &mut polylines.last_mut().unwrap().0;
+                    flatten_cubic(current, (c1.x, c1.y), (c2.x, c2.y), (p.x, p.y), points);
+                    current = (p.x, p.y);
+                }
+                PathElement::Close => {
+                    let polyline = polylines.last_mut().unwrap();
+                    polyline.1 = true;
+                    current = polyline.0[0];
+                    after_close = true;
+                }
+            }
+        }
+        polylines
+    }
+
+    /// Bounding box of all points in the path, including control points, like
+    /// `CGPathGetBoundingBox`.
+    pub fn bounding_box(&self) -> Option<CGRect> {
+        let mut points = Vec::new();
+        for &element in &self.elements {
+            match element {
+                PathElement::MoveTo(a) | PathElement::LineTo(a) => points.push(a),
+                PathElement::QuadCurveTo(a, b) => points.extend([a, b]),
+                PathElement::CurveTo(a, b, c) => points.extend([a, b, c]),
+                PathElement::Close => (),
+            }
+        }
+        bounds_of_points(points.into_iter().map(|p| (p.x, p.y)))
+    }
+
+    /// Bounding box of the area the path covers, excluding control points,
+    /// like `CGPathGetPathBoundingBox`.
+    pub fn path_bounding_box(&self) -> Option<CGRect> {
+        let polylines = self.flatten();
+        bounds_of_points(
+            polylines
+                .iter()
+                .flat_map(|(points, _)| points.iter().copied()),
+        )
+    }
+
+    pub fn contains_point(&self, point: CGPoint, rule: FillRule) -> bool {
+        let polylines = self.flatten();
+        let winding = winding_number(&polylines, (point.x, point.y));
+        match rule {
+            FillRule::Winding => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+fn bounds_of_points(points: impl Iterator<Item = (CGFloat, CGFloat)>) -> Option<CGRect> {
+    let mut bounds: Option<(CGFloat, CGFloat, CGFloat, CGFloat)> = None;
+    for (x, y) in points {
+        bounds = Some(match bounds {
+            None => (x, y, x, y),
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        });
+    }
+    bounds.map(|(x0, y0, x1, y1)| CGRect {
+        origin: CGPoint { x: x0, y: y0 },
+        size: CGSize {
+            width: x1 - x0,
+            height: y1 - y0,
+        },
+    })
+}
+
+/// Append points approximating a cubic Bézier curve, excluding the start
+/// point, to `points`.
+fn flatten_cubic(
+    p0: (CGFloat, CGFloat),
+    p1: (CGFloat, CGFloat),
+    p2: (CGFloat, CGFloat),
+    p3: (CGFloat, CGFloat),
+    points: &mut Vec<(CGFloat, CGFloat)>,
+) {
+    // The second differences bound how far the curve strays from its chords,
+    // which gives a segment count for the requested flatness.
+    let ddx = (p0.0 - 2.0 * p1.0 + p2.0)
+        .abs()
+        .max((p1.0 - 2.0 * p2.0 + p3.0).abs());
+    let ddy = (p0.1 - 2.0 * p1.1 + p2.1)
+        .abs()
+        .max((p1.1 - 2.0 * p2.1 + p3.1).abs());
+    let dd = ddx.hypot(ddy);
+    let segments = ((0.75 * dd / FLATNESS).sqrt().ceil() as u32).clamp(1, 256);
+    for i in 1..=segments {
+        let t = i as CGFloat / segments as CGFloat;
+        let mt = 1.0 - t;
+        let a = mt * mt * mt;
+        let b = 3.0 * mt * mt * t;
+        let c = 3.0 * mt * t * t;
+        let d = t * t * t;
+        points.push((
+            a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+            a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+        ));
+    }
+}
+
+/// Create a new CGPath object owning `path`. It has a reference count of one.
+pub fn from_path(env: &mut Environment, path: Path) -> CGPathRef {
+    let isa = env.objc.get_known_class("_touchHLE_CGPath", &mut env.mem);
+    env.objc
+        .alloc_object(isa, Box::new(CGPathHostObject { path }), &mut env.mem)
+}
+
+/// Borrow the host representation of a CGPath.
+pub fn borrow_path(env: &Environment, path: CGPathRef) -> &Path {
+    &env.objc.borrow::<CGPathHostObject>(path).path
+}
+fn borrow_path_mut(env: &mut Environment, path: CGMutablePathRef) -> &mut Path {
+    &mut env.objc.borrow_mut::<CGPathHostObject>(path).path
+}
+
+/// Read the optional transform argument taken by many `CGPath` functions.
+fn read_transform(env: &Environment, m: ConstPtr<CGAffineTransform>) -> CGAffineTransform {
+    if m.is_null() {
+        super::cg_affine_transform::CGAffineTransformIdentity
+    } else {
+        env.mem.read(m)
+    }
+}
+
+fn CGPathCreateMutable(env: &mut Environment) -> CGMutablePathRef {
+    from_path(env, Path::default())
+}
+fn CGPathCreateCopy(env: &mut Environment, path: CGPathRef) -> CGPathRef {
+    let path = borrow_path(env, path).clone();
+    from_path(env, path)
+}
+fn CGPathCreateMutableCopy(env: &mut Environment, path: CGPathRef) -> CGMutablePathRef {
+    CGPathCreateCopy(env, path)
+}
+fn CGPathCreateWithRect(
+    env: &mut Environment,
+    rect: CGRect,
+    m: ConstPtr<CGAffineTransform>,
+) -> CGPathRef {
+    let mut path = Path::default();
+    path.add_rect(rect, read_transform(env, m));
+    from_path(env, path)
+}
+fn CGPathCreateWithEllipseInRect(
+    env: &mut Environment,
+    rect: CGRect,
+    m: ConstPtr<CGAffineTransform>,
+) -> CGPathRef {
+    let mut path = Path::default();
+    path.add_ellipse_in_rect(rect, read_transform(env, m));
+    from_path(env, path)
+}
+
+pub fn CGPathRelease(env: &mut Environment, path: CGPathRef) {
+    if !path.is_null() {
+        CFRelease(env, path);
+    }
+}
+pub fn CGPathRetain(env: &mut Environment, path: CGPathRef) -> CGPathRef {
+    if !path.is_null() {
+        CFRetain(env, path)
+    } else {
+        path
+    }
+}
+
+fn CGPathMoveToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let point = read_transform(env, m).apply_to_point(CGPoint { x, y });
+    borrow_path_mut(env, path).move_to(point);
+}
+fn CGPathAddLineToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let point = read_transform(env, m).apply_to_point(CGPoint { x, y });
+    borrow_path_mut(env, path).line_to(point);
+}
+fn CGPathAddQuadCurveToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    cpx: CGFloat,
+    cpy: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let transform = read_transform(env, m);
+    let control = transform.apply_to_point(CGPoint { x: cpx, y: cpy });
+    let point = transform.apply_to_point(CGPoint { x, y });
+    borrow_path_mut(env, path).quad_curve_to(control, point);
+}
+fn CGPathAddCurveToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    cp1x: CGFloat,
+    cp1y: CGFloat,
+    cp2x: CGFloat,
+    cp2y: CGFloat,
+    x: CGFloat,
+    y: CGFloat,
+) {
+    let transform = read_transform(env, m);
+    let control1 = transform.apply_to_point(CGPoint { x: cp1x, y: cp1y });
+    let control2 = transform.apply_to_point(CGPoint { x: cp2x, y: cp2y });
+    let point = transform.apply_to_point(CGPoint { x, y });
+    borrow_path_mut(env, path).curve_to(control1, control2, point);
+}
+fn CGPathAddArc(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    x: CGFloat,
+    y: CGFloat,
+    radius: CGFloat,
+    start_angle: CGFloat,
+    end_angle: CGFloat,
+    clockwise: bool,
+) {
+    let transform = read_transform(env, m);
+    borrow_path_mut(env, path).add_arc(
+        CGPoint { x, y },
+        radius,
+        start_angle,
+        end_angle,
+        clockwise,
+        transform,
+    );
+}
+fn CGPathAddArcToPoint(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    x1: CGFloat,
+    y1: CGFloat,
+    x2: CGFloat,
+    y2: CGFloat,
+    radius: CGFloat,
+) {
+    let transform = read_transform(env, m);
+    borrow_path_mut(env, path).add_arc_to_point(
+        CGPoint { x: x1, y: y1 },
+        CGPoint { x: x2, y: y2 },
+        radius,
+        transform,
+    );
+}
+fn CGPathAddRect(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    rect: CGRect,
+) {
+    let transform = read_transform(env, m);
+    borrow_path_mut(env, path).add_rect(rect, transform);
+}
+fn CGPathAddEllipseInRect(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    rect: CGRect,
+) {
+    let transform = read_transform(env, m);
+    borrow_path_mut(env, path).add_ellipse_in_rect(rect, transform);
+}
+fn CGPathAddPath(
+    env: &mut Environment,
+    path: CGMutablePathRef,
+    m: ConstPtr<CGAffineTransform>,
+    other: CGPathRef,
+) {
+    let transform = read_transform(env, m);
+    let other = borrow_path(env, other).clone();
+    borrow_path_mut(env, path).add_path(&other, transform);
+}
+fn CGPathCloseSubpath(env: &mut Environment, path: CGMutablePathRef) {
+    borrow_path_mut(env, path).close();
+}
+
+fn CGPathIsEmpty(env: &mut Environment, path: CGPathRef) -> bool {
+    path == nil || borrow_path(env, path).is_empty()
+}
+fn CGPathEqualToPath(env: &mut Environment, a: CGPathRef, b: CGPathRef) -> bool {
+    if a == b {
+        return true;
+    }
+    if a == nil || b == nil {
+        return false;
+    }
+    borrow_path(env, a).elements() == borrow_path(env, b).elements()
+}
+fn CGPathGetCurrentPoint(env: &mut Environment, path: CGPathRef) -> CGPoint {
+    borrow_path(env, path)
+        .current_point()
+        .unwrap_or(CGPoint { x: 0.0, y: 0.0 })
+}
+/// `CGRectNull`, returned for the bounds of empty paths.
+pub const NULL_RECT: CGRect = CGRect {
+    origin: CGPoint {
+        x: CGFloat::INFINITY,
+        y: CGFloat::INFINITY,
+    },
+    size: CGSize {
+        width: 0.0,
+        height: 0.0,
+    },
+};
+fn CGPathGetBoundingBox(env: &mut Environment, path: CGPathRef) -> CGRect {
+    borrow_path(env, path).bounding_box().unwrap_or(NULL_RECT)
+}
+fn CGPathGetPathBoundingBox(env: &mut Environment, path: CGPathRef) -> CGRect {
+    borrow_path(env, path)
+        .path_bounding_box()
+        .unwrap_or(NULL_RECT)
+}
+fn CGPathContainsPoint(
+    env: &mut Environment,
+    path: CGPathRef,
+    m: ConstPtr<CGAffineTransform>,
+    point: CGPoint,
+    eo_fill: bool,
+) -> bool {
+    let transform = read_transform(env, m);
+    let rule = if eo_fill {
+        FillRule::EvenOdd
+    } else {
+        FillRule::Winding
+    };
+    borrow_path(env, path)
+        .transformed(transform)
+        .contains_point(point, rule)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGPathCreateMutable()),
+    export_c_func!(CGPathCreateCopy(_)),
+    export_c_func!(CGPathCreateMutableCopy(_)),
+    export_c_func!(CGPathCreateWithRect(_, _)),
+    export_c_func!(CGPathCreateWithEllipseInRect(_, _)),
+    export_c_func!(CGPathRetain(_)),
+    export_c_func!(CGPathRelease(_)),
+    export_c_func!(CGPathMoveToPoint(_, _, _, _)),
+    export_c_func!(CGPathAddLineToPoint(_, _, _, _)),
+    export_c_func!(CGPathAddQuadCurveToPoint(_, _, _, _, _, _)),
+    export_c_func!(CGPathAddCurveToPoint(_, _, _, _, _, _, _, _)),
+    export_c_func!(CGPathAddArc(_, _, _, _, _, _, _, _)),
+    export_c_func!(CGPathAddArcToPoint(_, _, _, _, _, _, _)),
+    export_c_func!(CGPathAddRect(_, _, _)),
+    export_c_func!(CGPathAddEllipseInRect(_, _, _)),
+    export_c_func!(CGPathAddPath(_, _, _)),
+    export_c_func!(CGPathCloseSubpath(_)),
+    export_c_func!(CGPathIsEmpty(_)),
+    export_c_func!(CGPathEqualToPath(_, _)),
+    export_c_func!(CGPathGetCurrentPoint(_)),
+    export_c_func!(CGPathGetBoundingBox(_)),
+    export_c_func!(CGPathGetPathBoundingBox(_)),
+    export_c_func!(CGPathContainsPoint(_, _, _, _)),
+];
@@ -0,0 +1,253 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGPattern.h`
+
+use super::cg_affine_transform::CGAffineTransform;
+use super::cg_bitmap_context::{self, CGBitmapContextCreate};
+use super::cg_color_space::{CGColorSpaceCreateDeviceRGB, CGColorSpaceRelease};
+use super::cg_context::{
+    CGContextClipToRect, CGContextRef, CGContextRelease, CGContextScaleCTM, CGContextTranslateCTM,
+};
+use super::cg_image::kCGImageAlphaPremultipliedLast;
+use super::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::image::Image;
+use crate::mem::{ConstPtr, GuestUSize, MutVoidPtr, SafeRead};
+use crate::objc::{objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGPattern seems to be a CFType-based type, but in our implementation those
+// are just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CGPattern: NSObject
+
+- (())dealloc {
+    let &CGPatternHostObject {
+        info, release_info, ..
+    } = env.objc.borrow(this);
+    if release_info.addr_with_thumb_bit() != 0 {
+        let () = release_info.call_from_host(env, (info,));
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+#[allow(dead_code)]
+#[repr(C, packed)]
+pub struct CGPatternCallbacks {
+    version: u32,
+    /// `void (*)(void *info, CGContextRef c)`
+    draw_pattern: GuestFunction,
+    /// `void (*)(void *info)`, may be `NULL`.
+    release_info: GuestFunction,
+}
+unsafe impl SafeRead for CGPatternCallbacks {}
+
+struct CGPatternHostObject {
+    info: MutVoidPtr,
+    bounds: CGRect,
+    /// Pattern space to the context's base (default) user space.
+    matrix: CGAffineTransform,
+    x_step: CGFloat,
+    y_step: CGFloat,
+    /// Colored patterns specify colors when drawing a cell, uncolored ones
+    /// are a stencil for the color passed to `CGContextSetFillPattern`.
+    is_colored: bool,
+    draw_pattern: GuestFunction,
+    release_info: GuestFunction,
+}
+impl HostObject for CGPatternHostObject {}
+
+pub type CGPatternRef = CFTypeRef;
+
+pub type CGPatternTiling = i32;
+
+/// A rendered cell of a pattern, ready for filling with.
+pub(super) struct PatternTile {
+    image: Image,
+    /// Maps device space to pixels of `image`, before wrapping.
+    to_tile: CGAffineTransform,
+    is_colored: bool,
+    /// For uncolored patterns, the color to draw with. Only the alpha is
+    /// used for colored patterns.
+    color: (CGFloat, CGFloat, CGFloat, CGFloat),
+}
+impl PatternTile {
+    /// Get the non-premultiplied color of the pattern at a device pixel.
+    pub(super) fn sample(
+        &self,
+        x: GuestUSize,
+        y: GuestUSize,
+    ) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+        let (width, height) = self.image.dimensions();
+        let point = self.to_tile.apply_to_point(CGPoint {
+            x: x as CGFloat + 0.5,
+            y: y as CGFloat + 0.5,
+        });
+        let u = (point.x.floor() as i64).rem_euclid(width as i64) as usize;
+        let v = (point.y.floor() as i64).rem_euclid(height as i64) as usize;
+        let idx = (v * width as usize + u) * 4;
+        let [r, g, b, a] = [0, 1, 2, 3].map(|i| self.image.pixels()[idx + i] as CGFloat / 255.0);
+        let (cr, cg, cb, ca) = self.color;
+        if self.is_colored {
+            (r, g, b, a * ca)
+        } else {
+            (cr, cg, cb, a * ca)
+        }
+    }
+}
+
+/// Largest tile dimension, in pixels, to stop a silly step size from
+/// exhausting memory.
+const MAX_TILE_SIZE: CGFloat = 2048.0;
+
+/// Render one cell of a pattern by calling its guest drawing callback.
+/// `phase` is the pattern phase of the context being drawn in.
+pub(super) fn render_tile(
+    env: &mut Environment,
+    pattern: CGPatternRef,
+    color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    phase: CGSize,
+) -> PatternTile {
+    let &CGPatternHostObject {
+        info,
+        bounds,
+        matrix,
+        x_step,
+        y_step,
+        is_colored,
+        draw_pattern,
+        ..
+    } = env.objc.borrow(pattern);
+    // A step of zero means the bounds size, according to the documentation.
+    let x_step = if x_step == 0.0 {
+        bounds.size.width
+    } else {
+        x_step.abs()
+    };
+    let y_step = if y_step == 0.0 {
+        bounds.size.height
+    } else {
+        y_step.abs()
+    };
+
+    // Render at the resolution the pattern will appear at.
+    let scale_x = matrix.a.hypot(matrix.b).max(1e-3);
+    let scale_y = matrix.c.hypot(matrix.d).max(1e-3);
+    let width = (x_step * scale_x).ceil().clamp(1.0, MAX_TILE_SIZE);
+    let height = (y_step * scale_y).ceil().clamp(1.0, MAX_TILE_SIZE);
+    let (scale_x, scale_y) = (width / x_step.max(1e-3), height / y_step.max(1e-3));
+    let (width, height) = (width as GuestUSize, height as GuestUSize);
+
+    let color_space = CGColorSpaceCreateDeviceRGB(env);
+    let context = CGBitmapContextCreate(
+        env,
+        MutVoidPtr::null(),
+        width,
+        height,
+        8,
+        width * 4,
+        color_space,
+        kCGImageAlphaPremultipliedLast,
+    );
+    CGColorSpaceRelease(env, color_space);
+    CGContextScaleCTM(env, context, scale_x, scale_y);
+    CGContextTranslateCTM(env, context, -bounds.origin.x, -bounds.origin.y);
+    CGContextClipToRect(env, context, bounds);
+    let () = draw_pattern.call_from_host(env, (info, context));
+    let image = cg_bitmap_context::to_image(env, context);
+    CGContextRelease(env, context);
+
+    // device -> pattern space -> tile pixels
+    let to_pattern = matrix
+        .concat(CGAffineTransform {
+            tx: phase.width,
+            ty: phase.height,
+            ..super::cg_affine_transform::CGAffineTransformIdentity
+        })
+        .invert();
+    let to_tile = to_pattern.concat(CGAffineTransform {
+        a: scale_x,
+        b: 0.0,
+        c: 0.0,
+        d: scale_y,
+        tx: -bounds.origin.x * scale_x,
+        ty: -bounds.origin.y * scale_y,
+    });
+
+    PatternTile {
+        image,
+        to_tile,
+        is_colored,
+        color,
+    }
+}
+
+/// Whether a pattern specifies its own colors, see `CGPatternCreate`.
+pub(super) fn is_colored(env: &Environment, pattern: CGPatternRef) -> bool {
+    env.objc.borrow::<CGPatternHostObject>(pattern).is_colored
+}
+
+fn CGPatternCreate(
+    env: &mut Environment,
+    info: MutVoidPtr,
+    bounds: CGRect,
+    matrix: CGAffineTransform,
+    x_step: CGFloat,
+    y_step: CGFloat,
+    _tiling: CGPatternTiling,
+    is_colored: bool,
+    callbacks: ConstPtr<CGPatternCallbacks>,
+) -> CGPatternRef {
+    let CGPatternCallbacks {
+        draw_pattern,
+        release_info,
+        ..
+    } = env.mem.read(callbacks);
+    let host_object = CGPatternHostObject {
+        info,
+        bounds,
+        matrix,
+        x_step,
+        y_step,
+        is_colored,
+        draw_pattern,
+        release_info,
+    };
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGPattern", &mut env.mem);
+    env.objc
+        .alloc_object(isa, Box::new(host_object), &mut env.mem)
+}
+
+pub fn CGPatternRelease(env: &mut Environment, pattern: CGPatternRef) {
+    if !pattern.is_null() {
+        CFRelease(env, pattern);
+    }
+}
+pub fn CGPatternRetain(env: &mut Environment, pattern: CGPatternRef) -> CGPatternRef {
+    if !pattern.is_null() {
+        CFRetain(env, pattern)
+    } else {
+        pattern
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGPatternCreate(_, _, _, _, _, _, _, _)),
+    export_c_func!(CGPatternRetain(_)),
+    export_c_func!(CGPatternRelease(_)),
+];
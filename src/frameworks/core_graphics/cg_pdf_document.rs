@@ -0,0 +1,152 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGPDFDocument.h`
+//!
+//! Documents are parsed completely when they are created. See the `pdf`
+//! module for the parser and its limitations.
+
+use super::cg_data_provider::{self, CGDataProviderRef};
+use super::cg_pdf_page::{self, CGPDFPageRef, CGPDFPageRelease};
+use super::pdf::{Dictionary, Document};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_url::CFURLRef;
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::frameworks::foundation::ns_url::to_rust_path;
+use crate::mem::GuestUSize;
+use crate::objc::{nil, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+use std::rc::Rc;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGPDFDocument seems to be a CFType-based type, but in our implementation
+// those are just Objective-C types, so we need a class for it, but its name is
+// not visible anywhere.
+@implementation _touchHLE_CGPDFDocument: NSObject
+
+- (())dealloc {
+    let pages = std::mem::take(&mut env.objc.borrow_mut::<CGPDFDocumentHostObject>(this).pages);
+    for page in pages.into_iter().flatten() {
+        CGPDFPageRelease(env, page);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+struct CGPDFDocumentHostObject {
+    /// Shared with the document's pages.
+    document: Rc<Document>,
+    /// Page dictionaries, in order.
+    page_dicts: Vec<Dictionary>,
+    /// Page objects, created on demand by `CGPDFDocumentGetPage`. The document
+    /// owns a reference to each of them.
+    pages: Vec<Option<CGPDFPageRef>>,
+}
+impl HostObject for CGPDFDocumentHostObject {}
+
+pub type CGPDFDocumentRef = CFTypeRef;
+
+fn create(env: &mut Environment, bytes: Vec<u8>) -> CGPDFDocumentRef {
+    let document = match Document::parse(bytes) {
+        Ok(document) => document,
+        Err(e) => {
+            log!("Warning: couldn't parse PDF document: {}", e);
+            return nil;
+        }
+    };
+    let page_dicts = document.pages();
+    let host_object = CGPDFDocumentHostObject {
+        document: Rc::new(document),
+        pages: vec![None; page_dicts.len()],
+        page_dicts,
+    };
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGPDFDocument", &mut env.mem);
+    env.objc
+        .alloc_object(isa, Box::new(host_object), &mut env.mem)
+}
+
+fn CGPDFDocumentCreateWithURL(env: &mut Environment, url: CFURLRef) -> CGPDFDocumentRef {
+    let path = to_rust_path(env, url);
+    let Ok(bytes) = env.fs.read(&path) else {
+        log!("Warning: couldn't read PDF document {:?}", path);
+        return nil;
+    };
+    create(env, bytes)
+}
+
+fn CGPDFDocumentCreateWithProvider(
+    env: &mut Environment,
+    provider: CGDataProviderRef,
+) -> CGPDFDocumentRef {
+    let bytes = cg_data_provider::copy_bytes(env, provider);
+    create(env, bytes)
+}
+
+pub fn CGPDFDocumentRelease(env: &mut Environment, document: CGPDFDocumentRef) {
+    if !document.is_null() {
+        CFRelease(env, document);
+    }
+}
+pub fn CGPDFDocumentRetain(env: &mut Environment, document: CGPDFDocumentRef) -> CGPDFDocumentRef {
+    if !document.is_null() {
+        CFRetain(env, document)
+    } else {
+        document
+    }
+}
+
+fn CGPDFDocumentGetNumberOfPages(env: &mut Environment, document: CGPDFDocumentRef) -> GuestUSize {
+    if document.is_null() {
+        return 0;
+    }
+    let host_object = env.objc.borrow::<CGPDFDocumentHostObject>(document);
+    host_object.page_dicts.len().try_into().unwrap()
+}
+
+/// Get a page by its number, counting from 1. The result is owned by the
+/// document.
+fn CGPDFDocumentGetPage(
+    env: &mut Environment,
+    document: CGPDFDocumentRef,
+    page_number: GuestUSize,
+) -> CGPDFPageRef {
+    if document.is_null() {
+        return nil;
+    }
+    let host_object = env.objc.borrow::<CGPDFDocumentHostObject>(document);
+    let Some(index) = (page_number as usize).checked_sub(1) else {
+        return nil;
+    };
+    let Some(dict) = host_object.page_dicts.get(index) else {
+        return nil;
+    };
+    if let Some(page) = host_object.pages[index] {
+        return page;
+    }
+    let data = host_object.document.clone();
+    let dict = dict.clone();
+    let page = cg_pdf_page::create(env, document, data, dict, page_number);
+    env.objc
+        .borrow_mut::<CGPDFDocumentHostObject>(document)
+        .pages[index] = Some(page);
+    page
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGPDFDocumentCreateWithURL(_)),
+    export_c_func!(CGPDFDocumentCreateWithProvider(_)),
+    export_c_func!(CGPDFDocumentRetain(_)),
+    export_c_func!(CGPDFDocumentRelease(_)),
+    export_c_func!(CGPDFDocumentGetNumberOfPages(_)),
+    export_c_func!(CGPDFDocumentGetPage(_, _)),
+];
@@ -0,0 +1,225 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGPDFPage.h`
+
+use super::cg_affine_transform::{CGAffineTransform, CGAffineTransformIdentity};
+use super::cg_pdf_document::CGPDFDocumentRef;
+use super::pdf::{self, Dictionary, Document};
+use super::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::mem::GuestUSize;
+use crate::objc::{nil, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+use std::rc::Rc;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGPDFPage seems to be a CFType-based type, but in our implementation those
+// are just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CGPDFPage: NSObject
+@end
+
+};
+
+struct CGPDFPageHostObject {
+    /// Weak reference: the document owns its pages, not the other way around.
+    document: CGPDFDocumentRef,
+    data: Rc<Document>,
+    /// Page dictionary, with inherited attributes already filled in.
+    dict: Dictionary,
+    number: GuestUSize,
+}
+impl HostObject for CGPDFPageHostObject {}
+
+pub type CGPDFPageRef = CFTypeRef;
+
+pub type CGPDFBox = i32;
+pub const kCGPDFMediaBox: CGPDFBox = 0;
+pub const kCGPDFCropBox: CGPDFBox = 1;
+pub const kCGPDFBleedBox: CGPDFBox = 2;
+pub const kCGPDFTrimBox: CGPDFBox = 3;
+pub const kCGPDFArtBox: CGPDFBox = 4;
+
+/// For use by `CGPDFDocumentGetPage`.
+pub(super) fn create(
+    env: &mut Environment,
+    document: CGPDFDocumentRef,
+    data: Rc<Document>,
+    dict: Dictionary,
+    number: GuestUSize,
+) -> CGPDFPageRef {
+    let host_object = CGPDFPageHostObject {
+        document,
+        data,
+        dict,
+        number,
+    };
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGPDFPage", &mut env.mem);
+    env.objc
+        .alloc_object(isa, Box::new(host_object), &mut env.mem)
+}
+
+/// Get the parsed document and page dictionary, for drawing the page.
+pub(super) fn page_data(env: &Environment, page: CGPDFPageRef) -> (Rc<Document>, Dictionary) {
+    let host_object = env.objc.borrow::<CGPDFPageHostObject>(page);
+    (host_object.data.clone(), host_object.dict.clone())
+}
+
+pub fn CGPDFPageRelease(env: &mut Environment, page: CGPDFPageRef) {
+    if !page.is_null() {
+        CFRelease(env, page);
+    }
+}
+pub fn CGPDFPageRetain(env: &mut Environment, page: CGPDFPageRef) -> CGPDFPageRef {
+    if !page.is_null() {
+        CFRetain(env, page)
+    } else {
+        page
+    }
+}
+
+fn CGPDFPageGetDocument(env: &mut Environment, page: CGPDFPageRef) -> CGPDFDocumentRef {
+    if page.is_null() {
+        return nil;
+    }
+    env.objc.borrow::<CGPDFPageHostObject>(page).document
+}
+
+fn CGPDFPageGetPageNumber(env: &mut Environment, page: CGPDFPageRef) -> GuestUSize {
+    if page.is_null() {
+        return 0;
+    }
+    env.objc.borrow::<CGPDFPageHostObject>(page).number
+}
+
+pub fn CGPDFPageGetBoxRect(env: &mut Environment, page: CGPDFPageRef, box_: CGPDFBox) -> CGRect {
+    let host_object = env.objc.borrow::<CGPDFPageHostObject>(page);
+    let get = |key: &str| pdf::rect_from_object(&host_object.data, host_object.dict.get(key));
+    // Each box defaults to the crop box, which defaults to the media box.
+    // US Letter is the default media box used by other PDF readers.
+    let media_box = get("MediaBox").unwrap_or(CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: CGSize {
+            width: 612.0,
+            height: 792.0,
+        },
+    });
+    let crop_box = get("CropBox").unwrap_or(media_box);
+    match box_ {
+        kCGPDFMediaBox => media_box,
+        kCGPDFCropBox => crop_box,
+        kCGPDFBleedBox => get("BleedBox").unwrap_or(crop_box),
+        kCGPDFTrimBox => get("TrimBox").unwrap_or(crop_box),
+        kCGPDFArtBox => get("ArtBox").unwrap_or(crop_box),
+        _ => {
+            log!("Warning: unknown CGPDFBox {}, using crop box", box_);
+            crop_box
+        }
+    }
+}
+
+/// The page's rotation, clockwise in degrees: 0, 90, 180 or 270.
+fn CGPDFPageGetRotationAngle(env: &mut Environment, page: CGPDFPageRef) -> i32 {
+    let host_object = env.objc.borrow::<CGPDFPageHostObject>(page);
+    let rotate = host_object
+        .data
+        .lookup(&host_object.dict, "Rotate")
+        .as_integer()
+        .unwrap_or(0);
+    ((rotate / 90 * 90).rem_euclid(360)) as i32
+}
+
+/// Get a transform which maps a box of the page into `rect`, rotating it
+/// clockwise by `rotate` degrees in addition to the page's own rotation, and
+/// centering it. The page is scaled down if needed to fit, but never up.
+fn CGPDFPageGetDrawingTransform(
+    env: &mut Environment,
+    page: CGPDFPageRef,
+    box_: CGPDFBox,
+    rect: CGRect,
+    rotate: i32,
+    preserve_aspect_ratio: bool,
+) -> CGAffineTransform {
+    let box_rect = CGPDFPageGetBoxRect(env, page, box_);
+    if rotate % 90 != 0 {
+        log!(
+            "Warning: CGPDFPageGetDrawingTransform() rotation {} is not a multiple of 90",
+            rotate
+        );
+    }
+    let angle = (CGPDFPageGetRotationAngle(env, page) + rotate / 90 * 90).rem_euclid(360);
+
+    // Clockwise rotation, which is a negative angle when y points up.
+    let (sin, cos): (CGFloat, CGFloat) = match angle {
+        90 => (-1.0, 0.0),
+        180 => (0.0, -1.0),
+        270 => (1.0, 0.0),
+        _ => (0.0, 1.0),
+    };
+    let rotation = CGAffineTransform {
+        a: cos,
+        b: sin,
+        c: -sin,
+        d: cos,
+        tx: 0.0,
+        ty: 0.0,
+    };
+    let (width, height) = if angle % 180 == 0 {
+        (box_rect.size.width, box_rect.size.height)
+    } else {
+        (box_rect.size.height, box_rect.size.width)
+    };
+    if width <= 0.0 || height <= 0.0 {
+        return CGAffineTransformIdentity;
+    }
+    let (scale_x, scale_y) = (
+        (rect.size.width / width).min(1.0),
+        (rect.size.height / height).min(1.0),
+    );
+    let (scale_x, scale_y) = if preserve_aspect_ratio {
+        let scale = scale_x.min(scale_y);
+        (scale, scale)
+    } else {
+        (scale_x, scale_y)
+    };
+    let scale = CGAffineTransform {
+        a: scale_x,
+        d: scale_y,
+        ..CGAffineTransformIdentity
+    };
+
+    let translate = |tx, ty| CGAffineTransform {
+        tx,
+        ty,
+        ..CGAffineTransformIdentity
+    };
+    translate(
+        -(box_rect.origin.x + box_rect.size.width / 2.0),
+        -(box_rect.origin.y + box_rect.size.height / 2.0),
+    )
+    .concat(rotation)
+    .concat(scale)
+    .concat(translate(
+        rect.origin.x + rect.size.width / 2.0,
+        rect.origin.y + rect.size.height / 2.0,
+    ))
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGPDFPageRetain(_)),
+    export_c_func!(CGPDFPageRelease(_)),
+    export_c_func!(CGPDFPageGetDocument(_)),
+    export_c_func!(CGPDFPageGetPageNumber(_)),
+    export_c_func!(CGPDFPageGetBoxRect(_, _)),
+    export_c_func!(CGPDFPageGetRotationAngle(_)),
+    export_c_func!(CGPDFPageGetDrawingTransform(_, _, _, _, _)),
+];
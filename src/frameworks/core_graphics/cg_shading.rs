@@ -0,0 +1,247 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGShading.h`
+//!
+//! This also contains the rendering shared by shadings and gradients, which
+//! are both a one-dimensional color ramp spread over an axis or between two
+//! circles.
+
+use super::cg_bitmap_context;
+use super::cg_color_space::CGColorSpaceRef;
+use super::cg_context::{CGContextHostObject, CGContextRef};
+use super::cg_function::{self, CGFunctionRef, CGFunctionRelease, CGFunctionRetain, SAMPLE_COUNT};
+use super::rasterizer::Mask;
+use super::{CGFloat, CGPoint};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::objc::{objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGShading seems to be a CFType-based type, but in our implementation those
+// are just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CGShading: NSObject
+
+- (())dealloc {
+    let function = env.objc.borrow::<CGShadingHostObject>(this).function;
+    CGFunctionRelease(env, function);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+struct CGShadingHostObject {
+    geometry: Geometry,
+    extend: (bool, bool),
+    /// Strong reference.
+    function: CGFunctionRef,
+}
+impl HostObject for CGShadingHostObject {}
+
+pub type CGShadingRef = CFTypeRef;
+
+/// Where the color ramp is placed, in user space.
+#[derive(Copy, Clone, Debug)]
+pub enum Geometry {
+    /// The color varies along the line from `start` to `end`.
+    Axial { start: CGPoint, end: CGPoint },
+    /// The color varies between two circles.
+    Radial {
+        start: CGPoint,
+        start_radius: CGFloat,
+        end: CGPoint,
+        end_radius: CGFloat,
+    },
+}
+
+impl Geometry {
+    /// Find the position along the ramp of a point, if the shading covers it.
+    /// `extend` says whether the shading continues beyond the start and end.
+    fn position(&self, (px, py): (CGFloat, CGFloat), extend: (bool, bool)) -> Option<CGFloat> {
+        let in_range = |t: CGFloat| (t >= 0.0 || extend.0) && (t <= 1.0 || extend.1);
+        match *self {
+            Geometry::Axial { start, end } => {
+                let (dx, dy) = (end.x - start.x, end.y - start.y);
+                let length_squared = dx * dx + dy * dy;
+                if length_squared == 0.0 {
+                    return None;
+                }
+                let t = ((px - start.x) * dx + (py - start.y) * dy) / length_squared;
+                in_range(t).then_some(t)
+            }
+            Geometry::Radial {
+                start,
+                start_radius,
+                end,
+                end_radius,
+            } => {
+                // Find the largest t for which the point is on the circle
+                // interpolated between the two circles, solving
+                // |p - c(t)| = r(t) with r(t) >= 0.
+                let (cdx, cdy) = (end.x - start.x, end.y - start.y);
+                let (pdx, pdy) = (px - start.x, py - start.y);
+                let dr = end_radius - start_radius;
+                let a = cdx * cdx + cdy * cdy - dr * dr;
+                let b = pdx * cdx + pdy * cdy + start_radius * dr;
+                let c = pdx * pdx + pdy * pdy - start_radius * start_radius;
+                let valid = |t: CGFloat| start_radius + t * dr >= 0.0 && in_range(t);
+                if a.abs() < 1e-6 {
+                    if b == 0.0 {
+                        return None;
+                    }
+                    let t = c / (2.0 * b);
+                    return valid(t).then_some(t);
+                }
+                let discriminant = b * b - a * c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let root = discriminant.sqrt();
+                let (t0, t1) = ((b + root) / a, (b - root) / a);
+                let (larger, smaller) = if t0 > t1 { (t0, t1) } else { (t1, t0) };
+                if valid(larger) {
+                    Some(larger)
+                } else if valid(smaller) {
+                    Some(smaller)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Fill the context's clip area with a color ramp. `samples` are the colors
+/// (non-premultiplied RGBA) at evenly spaced positions from 0 to 1.
+pub(super) fn draw(
+    env: &mut Environment,
+    context: CGContextRef,
+    geometry: Geometry,
+    extend: (bool, bool),
+    samples: &[(CGFloat, CGFloat, CGFloat, CGFloat)],
+) {
+    let (width, height) = cg_bitmap_context::dimensions(env, context);
+    let to_user = env
+        .objc
+        .borrow::<CGContextHostObject>(context)
+        .gstate
+        .ctm
+        .invert();
+
+    let mut mask = Mask::new(width, height, 0.0);
+    let mut positions = vec![0.0; width as usize * height as usize];
+    for y in 0..height {
+        for x in 0..width {
+            // Sample at the pixel center.
+            let point = to_user.apply_to_point(CGPoint {
+                x: x as CGFloat + 0.5,
+                y: y as CGFloat + 0.5,
+            });
+            if let Some(t) = geometry.position((point.x, point.y), extend) {
+                let idx = (y * width + x) as usize;
+                mask.coverage[idx] = 1.0;
+                positions[idx] = t;
+            }
+        }
+    }
+
+    let last = samples.len() - 1;
+    cg_bitmap_context::paint(env, context, &mask, |x, y| {
+        let t = positions[(y * width + x) as usize].clamp(0.0, 1.0);
+        samples[(t * last as CGFloat).round() as usize]
+    });
+}
+
+fn create(
+    env: &mut Environment,
+    geometry: Geometry,
+    function: CGFunctionRef,
+    extend_start: bool,
+    extend_end: bool,
+) -> CGShadingRef {
+    let host_object = CGShadingHostObject {
+        geometry,
+        extend: (extend_start, extend_end),
+        function: CGFunctionRetain(env, function),
+    };
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGShading", &mut env.mem);
+    env.objc
+        .alloc_object(isa, Box::new(host_object), &mut env.mem)
+}
+
+fn CGShadingCreateAxial(
+    env: &mut Environment,
+    _space: CGColorSpaceRef,
+    start: CGPoint,
+    end: CGPoint,
+    function: CGFunctionRef,
+    extend_start: bool,
+    extend_end: bool,
+) -> CGShadingRef {
+    // TODO: support color spaces other than RGB
+    let geometry = Geometry::Axial { start, end };
+    create(env, geometry, function, extend_start, extend_end)
+}
+
+fn CGShadingCreateRadial(
+    env: &mut Environment,
+    _space: CGColorSpaceRef,
+    start: CGPoint,
+    start_radius: CGFloat,
+    end: CGPoint,
+    end_radius: CGFloat,
+    function: CGFunctionRef,
+    extend_start: bool,
+    extend_end: bool,
+) -> CGShadingRef {
+    let geometry = Geometry::Radial {
+        start,
+        start_radius,
+        end,
+        end_radius,
+    };
+    create(env, geometry, function, extend_start, extend_end)
+}
+
+/// Implementation of `CGContextDrawShading`.
+pub(super) fn draw_shading(env: &mut Environment, context: CGContextRef, shading: CGShadingRef) {
+    let &CGShadingHostObject {
+        geometry,
+        extend,
+        function,
+    } = env.objc.borrow(shading);
+    let samples = cg_function::sample_colors(env, function);
+    assert!(samples.len() == SAMPLE_COUNT);
+    draw(env, context, geometry, extend, &samples);
+}
+
+pub fn CGShadingRelease(env: &mut Environment, shading: CGShadingRef) {
+    if !shading.is_null() {
+        CFRelease(env, shading);
+    }
+}
+pub fn CGShadingRetain(env: &mut Environment, shading: CGShadingRef) -> CGShadingRef {
+    if !shading.is_null() {
+        CFRetain(env, shading)
+    } else {
+        shading
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGShadingCreateAxial(_, _, _, _, _, _)),
+    export_c_func!(CGShadingCreateRadial(_, _, _, _, _, _, _, _)),
+    export_c_func!(CGShadingRetain(_)),
+    export_c_func!(CGShadingRelease(_)),
+];
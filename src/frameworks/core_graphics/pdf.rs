@@ -0,0 +1,62 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! PDF parsing and rendering, used by `CGPDFDocument` and
+//! `CGContextDrawPDFPage`.
+//!
+//! This is not a complete PDF implementation. It aims to handle the kind of
+//! simple vector artwork apps bundle as PDF files, and to degrade gracefully
+//! (with a warning) on anything else.
+
+mod color;
+mod fonts;
+mod parser;
+mod renderer;
+
+pub use parser::{Dictionary, Document};
+pub use renderer::draw_page;
+
+use super::cg_affine_transform::{CGAffineTransform, CGAffineTransformIdentity};
+use super::{CGFloat, CGPoint, CGRect, CGSize};
+use parser::Object;
+
+/// Read a rectangle given as an array of two corners, in any order.
+pub fn rect_from_object(document: &Document, object: Option<&Object>) -> Option<CGRect> {
+    let object = document.resolve(object?);
+    let numbers: Vec<CGFloat> = object
+        .as_array()?
+        .iter()
+        .filter_map(|n| document.resolve(n).as_number())
+        .collect();
+    let &[x0, y0, x1, y1] = &numbers[..] else {
+        return None;
+    };
+    Some(CGRect {
+        origin: CGPoint {
+            x: x0.min(x1),
+            y: y0.min(y1),
+        },
+        size: CGSize {
+            width: (x1 - x0).abs(),
+            height: (y1 - y0).abs(),
+        },
+    })
+}
+
+/// Read a matrix given as an array of six numbers, or the identity matrix if
+/// there isn't one.
+pub fn matrix_from_object(document: &Document, object: Option<&Object>) -> CGAffineTransform {
+    let numbers: Vec<CGFloat> = match object.map(|object| document.resolve(object)) {
+        Some(Object::Array(array)) => array
+            .iter()
+            .filter_map(|n| document.resolve(n).as_number())
+            .collect(),
+        _ => Vec::new(),
+    };
+    match numbers[..] {
+        [a, b, c, d, tx, ty] => CGAffineTransform { a, b, c, d, tx, ty },
+        _ => CGAffineTransformIdentity,
+    }
+}
@@ -0,0 +1,485 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! PDF color spaces and functions.
+//!
+//! Everything is converted to RGB. Calibrated and ICC-based color spaces are
+//! treated as the device color space with the same number of components.
+
+use super::parser::{Dictionary, Document, Object};
+use crate::frameworks::core_graphics::CGFloat;
+use std::rc::Rc;
+
+#[derive(Clone, Debug)]
+pub enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    Indexed {
+        base: Box<ColorSpace>,
+        /// Highest valid index.
+        high: usize,
+        /// Components of `base` for each index, one byte each.
+        lookup: Rc<Vec<u8>>,
+    },
+    /// `Separation` and `DeviceN` color spaces, which use a function to get a
+    /// color in an alternate color space.
+    Tinted {
+        components: usize,
+        alternate: Box<ColorSpace>,
+        transform: Option<Rc<Function>>,
+    },
+    Pattern,
+}
+
+impl ColorSpace {
+    pub fn components(&self) -> usize {
+        match *self {
+            ColorSpace::Gray | ColorSpace::Indexed { .. } => 1,
+            ColorSpace::Rgb => 3,
+            ColorSpace::Cmyk => 4,
+            ColorSpace::Tinted { components, .. } => components,
+            ColorSpace::Pattern => 0,
+        }
+    }
+
+    /// The color selected when the color space is set.
+    pub fn initial_color(&self) -> Vec<CGFloat> {
+        match *self {
+            ColorSpace::Cmyk => vec![0.0, 0.0, 0.0, 1.0],
+            ColorSpace::Tinted { components, .. } => vec![1.0; components],
+            _ => vec![0.0; self.components()],
+        }
+    }
+
+    /// The default `Decode` array for image samples of a given bit depth.
+    pub fn default_decode(&self, bits_per_component: u32) -> Vec<(CGFloat, CGFloat)> {
+        match *self {
+            ColorSpace::Indexed { .. } => {
+                vec![(0.0, ((1u32 << bits_per_component) - 1) as CGFloat)]
+            }
+            _ => vec![(0.0, 1.0); self.components()],
+        }
+    }
+
+    pub fn to_rgb(&self, color: &[CGFloat]) -> (CGFloat, CGFloat, CGFloat) {
+        let get = |i: usize| color.get(i).copied().unwrap_or(0.0).clamp(0.0, 1.0);
+        match *self {
+            ColorSpace::Gray => (get(0), get(0), get(0)),
+            ColorSpace::Rgb => (get(0), get(1), get(2)),
+            ColorSpace::Cmyk => {
+                let k = 1.0 - get(3);
+                ((1.0 - get(0)) * k, (1.0 - get(1)) * k, (1.0 - get(2)) * k)
+            }
+            ColorSpace::Indexed {
+                ref base,
+                high,
+                ref lookup,
+            } => {
+                let index =
+                    (color.first().copied().unwrap_or(0.0).round().max(0.0) as usize).min(high);
+                let n = base.components();
+                let components: Vec<CGFloat> = (0..n)
+                    .map(|i| lookup.get(index * n + i).copied().unwrap_or(0) as CGFloat / 255.0)
+                    .collect();
+                base.to_rgb(&components)
+            }
+            ColorSpace::Tinted {
+                ref alternate,
+                ref transform,
+                ..
+            } => match transform {
+                Some(transform) => alternate.to_rgb(&transform.evaluate(color)),
+                // Approximate the tint as an amount of black ink.
+                None => {
+                    let v = 1.0 - get(0);
+                    (v, v, v)
+                }
+            },
+            ColorSpace::Pattern => (0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Parse a color space from a name or array. Names that aren't device
+    /// color spaces are looked up in the `ColorSpace` resource dictionary.
+    pub fn parse(document: &Document, resources: &Dictionary, object: &Object) -> ColorSpace {
+        Self::parse_inner(document, resources, object, 0)
+    }
+
+    fn parse_inner(
+        document: &Document,
+        resources: &Dictionary,
+        object: &Object,
+        depth: u32,
+    ) -> ColorSpace {
+        if depth > 8 {
+            return ColorSpace::Gray;
+        }
+        let object = document.resolve(object);
+        let (family, params) = match object {
+            Object::Name(ref name) => (name.as_str(), &[][..]),
+            Object::Array(ref array) => match array.split_first() {
+                Some((Object::Name(name), params)) => (name.as_str(), params),
+                _ => return ColorSpace::Gray,
+            },
+            _ => return ColorSpace::Gray,
+        };
+        let param = |i: usize| {
+            params
+                .get(i)
+                .map_or(Object::Null, |param| document.resolve(param))
+        };
+        match family {
+            "DeviceGray" | "G" | "CalGray" => ColorSpace::Gray,
+            "DeviceRGB" | "RGB" | "CalRGB" => ColorSpace::Rgb,
+            "DeviceCMYK" | "CMYK" => ColorSpace::Cmyk,
+            "Pattern" => ColorSpace::Pattern,
+            "Lab" => {
+                log_dbg!("TODO: Lab color space, treating it as RGB");
+                ColorSpace::Rgb
+            }
+            "ICCBased" => {
+                let n = param(0)
+                    .as_dict()
+                    .and_then(|dict| dict.get("N"))
+                    .and_then(Object::as_integer);
+                match n {
+                    Some(1) => ColorSpace::Gray,
+                    Some(4) => ColorSpace::Cmyk,
+                    _ => ColorSpace::Rgb,
+                }
+            }
+            "Indexed" | "I" => {
+                let base = Self::parse_inner(document, resources, &param(0), depth + 1);
+                let high = param(1).as_integer().unwrap_or(0).clamp(0, 255) as usize;
+                let lookup = match param(2) {
+                    Object::String(bytes) => bytes,
+                    Object::Stream(stream) => document.decode_stream(&stream).unwrap_or_default(),
+                    _ => Vec::new(),
+                };
+                ColorSpace::Indexed {
+                    base: Box::new(base),
+                    high,
+                    lookup: Rc::new(lookup),
+                }
+            }
+            "Separation" | "DeviceN" => {
+                let components = if family == "Separation" {
+                    1
+                } else {
+                    param(0).as_array().map_or(1, |names| names.len())
+                };
+                let alternate = Self::parse_inner(document, resources, &param(1), depth + 1);
+                let transform = Function::parse(document, &param(2)).map(Rc::new);
+                ColorSpace::Tinted {
+                    components,
+                    alternate: Box::new(alternate),
+                    transform,
+                }
+            }
+            name => {
+                let named = resources
+                    .get("ColorSpace")
+                    .map(|spaces| document.resolve(spaces))
+                    .and_then(|spaces| spaces.as_dict().and_then(|d| d.get(name).cloned()));
+                match named {
+                    Some(named) => Self::parse_inner(document, resources, &named, depth + 1),
+                    None => {
+                        log!("Warning: unknown PDF color space {:?}", name);
+                        ColorSpace::Gray
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A PDF function with a single input. PostScript calculator functions
+/// (type 4) are not supported.
+#[derive(Debug)]
+pub enum Function {
+    /// Type 0.
+    Sampled {
+        domain: (CGFloat, CGFloat),
+        encode: (CGFloat, CGFloat),
+        decode: Vec<(CGFloat, CGFloat)>,
+        /// Samples scaled to 0 to 1, `outputs` per input position.
+        samples: Vec<CGFloat>,
+        size: usize,
+        outputs: usize,
+    },
+    /// Type 2.
+    Exponential {
+        domain: (CGFloat, CGFloat),
+        c0: Vec<CGFloat>,
+        c1: Vec<CGFloat>,
+        exponent: CGFloat,
+    },
+    /// Type 3.
+    Stitching {
+        domain: (CGFloat, CGFloat),
+        functions: Vec<Function>,
+        bounds: Vec<CGFloat>,
+        encode: Vec<(CGFloat, CGFloat)>,
+    },
+    /// Several functions with one output each, as shadings and `DeviceN`
+    /// color spaces can use.
+    Array(Vec<Function>),
+}
+
+fn number_pairs(object: &Object) -> Vec<(CGFloat, CGFloat)> {
+    let numbers: Vec<CGFloat> = object
+        .as_array()
+        .unwrap_or(&[])
+        .iter()
+        .filter_map(Object::as_number)
+        .collect();
+    numbers.chunks_exact(2).map(|p| (p[0], p[1])).collect()
+}
+
+fn interpolate(x: CGFloat, (x0, x1): (CGFloat, CGFloat), (y0, y1): (CGFloat, CGFloat)) -> CGFloat {
+    if x1 == x0 {
+        y0
+    } else {
+        y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+    }
+}
+
+impl Function {
+    pub fn parse(document: &Document, object: &Object) -> Option<Function> {
+        Self::parse_inner(document, object, 0)
+    }
+
+    fn parse_inner(document: &Document, object: &Object, depth: u32) -> Option<Function> {
+        if depth > 8 {
+            return None;
+        }
+        let object = document.resolve(object);
+        if let Object::Array(ref functions) = object {
+            let functions = functions
+                .iter()
+                .map(|f| Self::parse_inner(document, f, depth + 1))
+                .collect::<Option<Vec<_>>>()?;
+            return Some(Function::Array(functions));
+        }
+        let dict = object.as_dict()?;
+        let get = |key: &str| document.lookup(dict, key);
+        let domain = number_pairs(&get("Domain"));
+        if domain.len() != 1 {
+            log!(
+                "TODO: PDF function with {} inputs is not supported",
+                domain.len()
+            );
+            return None;
+        }
+        let domain = domain[0];
+        let numbers = |key: &str| -> Option<Vec<CGFloat>> {
+            get(key)
+                .as_array()
+                .map(|a| a.iter().filter_map(Object::as_number).collect())
+        };
+
+        match get("FunctionType").as_integer() {
+            Some(0) => {
+                let stream = object.as_stream()?;
+                let size = numbers("Size")?.first().copied()? as usize;
+                let bits = get("BitsPerSample").as_integer()? as u32;
+                let range = number_pairs(&get("Range"));
+                let outputs = range.len();
+                if size == 0 || outputs == 0 || !matches!(bits, 1 | 2 | 4 | 8 | 16 | 32) {
+                    return None;
+                }
+                let encode = number_pairs(&get("Encode"))
+                    .first()
+                    .copied()
+                    .unwrap_or((0.0, (size - 1) as CGFloat));
+                let decode = match number_pairs(&get("Decode")) {
+                    decode if decode.len() == outputs => decode,
+                    _ => range,
+                };
+                let data = document.decode_stream(stream).ok()?;
+                let max = ((1u64 << bits) - 1) as CGFloat;
+                let samples = read_samples(&data, bits, size * outputs)
+                    .into_iter()
+                    .map(|sample| sample as CGFloat / max)
+                    .collect();
+                Some(Function::Sampled {
+                    domain,
+                    encode,
+                    decode,
+                    samples,
+                    size,
+                    outputs,
+                })
+            }
+            Some(2) => Some(Function::Exponential {
+                domain,
+                c0: numbers("C0").unwrap_or_else(|| vec![0.0]),
+                c1: numbers("C1").unwrap_or_else(|| vec![1.0]),
+                exponent: get("N").as_number().unwrap_or(1.0),
+            }),
+            Some(3) => {
+                let functions = get("Functions")
+                    .as_array()?
+                    .iter()
+                    .map(|f| Self::parse_inner(document, f, depth + 1))
+                    .collect::<Option<Vec<_>>>()?;
+                let bounds = numbers("Bounds").unwrap_or_default();
+                let encode = number_pairs(&get("Encode"));
+                if functions.is_empty()
+                    || bounds.len() + 1 != functions.len()
+                    || encode.len() != functions.len()
+                {
+                    return None;
+                }
+                Some(Function::Stitching {
+                    domain,
+                    functions,
+                    bounds,
+                    encode,
+                })
+            }
+            kind => {
+                log!("TODO: PDF function type {:?} is not supported", kind);
+                None
+            }
+        }
+    }
+
+    /// Evaluate the function. Only the first input is used.
+    pub fn evaluate(&self, inputs: &[CGFloat]) -> Vec<CGFloat> {
+        let x = inputs.first().copied().unwrap_or(0.0);
+        match *self {
+            Function::Sampled {
+                domain,
+                encode,
+                ref decode,
+                ref samples,
+                size,
+                outputs,
+            } => {
+                let x = x.clamp(domain.0.min(domain.1), domain.0.max(domain.1));
+                let e = interpolate(x, domain, encode).clamp(0.0, (size - 1) as CGFloat);
+                let (i0, frac) = (e.floor() as usize, e.fract());
+                let i1 = (i0 + 1).min(size - 1);
+                (0..outputs)
+                    .map(|j| {
+                        let s0 = samples.get(i0 * outputs + j).copied().unwrap_or(0.0);
+                        let s1 = samples.get(i1 * outputs + j).copied().unwrap_or(0.0);
+                        let s = s0 + (s1 - s0) * frac;
+                        interpolate(s, (0.0, 1.0), decode[j])
+                    })
+                    .collect()
+            }
+            Function::Exponential {
+                domain,
+                ref c0,
+                ref c1,
+                exponent,
+            } => {
+                let x = x.clamp(domain.0.min(domain.1), domain.0.max(domain.1));
+                let f = x.powf(exponent);
+                c0.iter()
+                    .zip(c1.iter())
+                    .map(|(&a, &b)| a + f * (b - a))
+                    .collect()
+            }
+            Function::Stitching {
+                domain,
+                ref functions,
+                ref bounds,
+                ref encode,
+            } => {
+                let x = x.clamp(domain.0.min(domain.1), domain.0.max(domain.1));
+                let i = bounds.iter().take_while(|&&bound| x >= bound).count();
+                let low = if i == 0 { domain.0 } else { bounds[i - 1] };
+                let high = if i == bounds.len() {
+                    domain.1
+                } else {
+                    bounds[i]
+                };
+                let x = interpolate(x, (low, high), encode[i]);
+                functions[i].evaluate(&[x])
+            }
+            Function::Array(ref functions) => functions
+                .iter()
+                .flat_map(|function| function.evaluate(inputs))
+                .collect(),
+        }
+    }
+}
+
+/// Read `count` big-endian samples of 1 to 32 bits each.
+pub fn read_samples(data: &[u8], bits: u32, count: usize) -> Vec<u32> {
+    let mut samples = Vec::with_capacity(count);
+    let mut bit_pos = 0usize;
+    for _ in 0..count {
+        let mut value = 0u32;
+        for _ in 0..bits {
+            let byte = data.get(bit_pos / 8).copied().unwrap_or(0);
+            let bit = (byte >> (7 - bit_pos % 8)) & 1;
+            value = value << 1 | bit as u32;
+            bit_pos += 1;
+        }
+        samples.push(value);
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_conversion() {
+        assert_eq!(
+            ColorSpace::Cmyk.to_rgb(&[1.0, 0.0, 0.0, 0.0]),
+            (0.0, 1.0, 1.0)
+        );
+        let indexed = ColorSpace::Indexed {
+            base: Box::new(ColorSpace::Rgb),
+            high: 1,
+            lookup: Rc::new(vec![0, 0, 0, 255, 0, 255]),
+        };
+        assert_eq!(indexed.to_rgb(&[1.0]), (1.0, 0.0, 1.0));
+        // Out-of-range indices are clamped.
+        assert_eq!(indexed.to_rgb(&[7.0]), (1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn functions() {
+        let exponential = |c0: CGFloat, c1: CGFloat| Function::Exponential {
+            domain: (0.0, 1.0),
+            c0: vec![c0],
+            c1: vec![c1],
+            exponent: 1.0,
+        };
+        let stitching = Function::Stitching {
+            domain: (0.0, 1.0),
+            functions: vec![exponential(0.0, 1.0), exponential(1.0, 0.0)],
+            bounds: vec![0.5],
+            encode: vec![(0.0, 1.0), (0.0, 1.0)],
+        };
+        assert_eq!(stitching.evaluate(&[0.25]), [0.5]);
+        assert_eq!(stitching.evaluate(&[0.75]), [0.5]);
+        assert_eq!(stitching.evaluate(&[1.0]), [0.0]);
+
+        let sampled = Function::Sampled {
+            domain: (0.0, 1.0),
+            encode: (0.0, 1.0),
+            decode: vec![(0.0, 1.0)],
+            samples: vec![0.0, 1.0],
+            size: 2,
+            outputs: 1,
+        };
+        assert_eq!(sampled.evaluate(&[0.5]), [0.5]);
+    }
+
+    #[test]
+    fn samples() {
+        assert_eq!(read_samples(&[0b1011_0001], 2, 4), [2, 3, 0, 1]);
+        assert_eq!(read_samples(&[0x12, 0x34], 16, 1), [0x1234]);
+    }
+}
@@ -0,0 +1,814 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! PDF fonts: mapping character codes to glyphs and widths.
+//!
+//! Embedded TrueType and OpenType fonts are used as-is. Everything else
+//! (the standard 14 fonts, Type 1 and bare CFF fonts, which [Font] can't
+//! parse) is drawn with the bundled fonts, using the font's encoding or
+//! `ToUnicode` map to find the intended characters. Type 3 fonts are not
+//! drawn at all.
+
+use super::parser::{Dictionary, Document, Lexer, Object, Token};
+use crate::font::{Font, GlyphSelector, OutlineSegment};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The bundled fonts, loaded on first use since they're quite large.
+#[derive(Default)]
+pub struct FallbackFonts {
+    regular: Option<Rc<Font>>,
+    bold: Option<Rc<Font>>,
+    italic: Option<Rc<Font>>,
+    japanese: Option<Rc<Font>>,
+}
+impl FallbackFonts {
+    /// Pick the closest bundled font for a PDF font's `BaseFont` name.
+    fn get(&mut self, base_font: &str, is_japanese: bool) -> Rc<Font> {
+        let (slot, load): (_, fn() -> Font) = if is_japanese {
+            (&mut self.japanese, Font::sans_regular_ja)
+        } else if base_font.contains("Bold") || base_font.contains("Black") {
+            (&mut self.bold, Font::sans_bold)
+        } else if base_font.contains("Italic") || base_font.contains("Oblique") {
+            (&mut self.italic, Font::sans_italic)
+        } else {
+            (&mut self.regular, Font::sans_regular)
+        };
+        slot.get_or_insert_with(|| Rc::new(load())).clone()
+    }
+}
+
+pub struct PdfFont {
+    font: Option<Rc<Font>>,
+    /// Whether `font` came from the PDF rather than being a fallback.
+    is_embedded: bool,
+    /// Composite (`Type0`) fonts use two-byte codes.
+    is_composite: bool,
+    /// For simple fonts, the character for each code according to the font's
+    /// encoding.
+    encoding: Option<[Option<char>; 256]>,
+    /// For composite fonts with an embedded TrueType font, maps CIDs to glyph
+    /// indices. [None] means the identity mapping.
+    cid_to_gid: Option<Vec<u16>>,
+    to_unicode: HashMap<u32, char>,
+    /// Widths in thousandths of an em.
+    widths: HashMap<u32, f32>,
+    default_width: Option<f32>,
+    outlines: RefCell<HashMap<u32, Option<Rc<Vec<OutlineSegment>>>>>,
+}
+
+impl PdfFont {
+    pub fn load(document: &Document, dict: &Dictionary, fallbacks: &mut FallbackFonts) -> PdfFont {
+        let subtype = document.lookup(dict, "Subtype");
+        let subtype = subtype.as_name().unwrap_or("Type1");
+        let base_font = document.lookup(dict, "BaseFont");
+        let base_font = base_font.as_name().unwrap_or("");
+        let is_composite = subtype == "Type0";
+        if subtype == "Type3" {
+            log!(
+                "TODO: Type 3 PDF fonts are not supported, text will be missing ({:?})",
+                base_font
+            );
+        }
+
+        // Composite fonts keep most things in their one descendant font.
+        let descendant = if is_composite {
+            let descendants = document.lookup(dict, "DescendantFonts");
+            let descendant = descendants.as_array().and_then(|d| d.first());
+            match descendant.map(|d| document.resolve(d)) {
+                Some(Object::Dictionary(descendant)) => descendant,
+                _ => Dictionary::new(),
+            }
+        } else {
+            dict.clone()
+        };
+        if is_composite {
+            match document.lookup(dict, "Encoding") {
+                Object::Name(name) if name == "Identity-H" || name == "Identity-V" => (),
+                encoding => log!(
+                    "TODO: PDF CMap {:?} is not supported, treating it as Identity-H",
+                    encoding.as_name()
+                ),
+            }
+        }
+
+        let descriptor = match document.lookup(&descendant, "FontDescriptor") {
+            Object::Dictionary(descriptor) => descriptor,
+            _ => Dictionary::new(),
+        };
+        let embedded = load_embedded_font(document, &descriptor);
+        let is_embedded = embedded.is_some();
+        let font = embedded.map(Rc::new).or_else(|| {
+            (subtype != "Type3").then(|| {
+                let ordering = document
+                    .lookup(&descendant, "CIDSystemInfo")
+                    .as_dict()
+                    .and_then(|info| info.get("Ordering"))
+                    .and_then(|o| o.as_string().map(|o| o.to_vec()));
+                fallbacks.get(base_font, ordering.as_deref() == Some(&b"Japan1"[..]))
+            })
+        });
+
+        let flags = document
+            .lookup(&descriptor, "Flags")
+            .as_integer()
+            .unwrap_or(0);
+        let is_symbolic = flags & (1 << 2) != 0 && flags & (1 << 5) == 0;
+        let encoding = (!is_composite).then(|| load_encoding(document, dict, is_symbolic));
+
+        let cid_to_gid = match document.lookup(&descendant, "CIDToGIDMap") {
+            Object::Stream(stream) => document.decode_stream(&stream).ok().map(|data| {
+                data.chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect()
+            }),
+            _ => None,
+        };
+
+        let to_unicode = match document.lookup(dict, "ToUnicode") {
+            Object::Stream(stream) => document
+                .decode_stream(&stream)
+                .map(|data| parse_to_unicode(&data))
+                .unwrap_or_default(),
+            _ => HashMap::new(),
+        };
+
+        let mut widths = HashMap::new();
+        let default_width;
+        if is_composite {
+            default_width = Some(
+                document
+                    .lookup(&descendant, "DW")
+                    .as_number()
+                    .unwrap_or(1000.0),
+            );
+            if let Object::Array(w) = document.lookup(&descendant, "W") {
+                parse_cid_widths(document, &w, &mut widths);
+            }
+        } else {
+            default_width = document.lookup(&descriptor, "MissingWidth").as_number();
+            let first_char = document.lookup(dict, "FirstChar").as_integer().unwrap_or(0);
+            if let Object::Array(w) = document.lookup(dict, "Widths") {
+                for (i, width) in w.iter().enumerate() {
+                    if let Some(width) = document.resolve(width).as_number() {
+                        widths.insert((first_char + i as i64) as u32, width);
+                    }
+                }
+            }
+        }
+
+        PdfFont {
+            font,
+            is_embedded,
+            is_composite,
+            encoding,
+            cid_to_gid,
+            to_unicode,
+            widths,
+            default_width,
+            outlines: Default::default(),
+        }
+    }
+
+    /// Split a string into character codes. The second value says whether
+    /// the code is a single-byte space, which word spacing applies to.
+    pub fn decode(&self, string: &[u8]) -> Vec<(u32, bool)> {
+        if self.is_composite {
+            string
+                .chunks(2)
+                .map(|pair| {
+                    let code = pair.iter().fold(0, |acc, &b| acc << 8 | b as u32);
+                    (code, false)
+                })
+                .collect()
+        } else {
+            string.iter().map(|&b| (b as u32, b == b' ')).collect()
+        }
+    }
+
+    /// The Unicode character a code represents, if known.
+    fn char_for_code(&self, code: u32) -> Option<char> {
+        if let Some(&c) = self.to_unicode.get(&code) {
+            return Some(c);
+        }
+        match self.encoding {
+            Some(ref encoding) => encoding.get(code as usize).copied().flatten(),
+            None => None,
+        }
+    }
+
+    /// Get a glyph's outline in ems with y pointing up, or [None] if there's
+    /// nothing to draw.
+    pub fn outline(&self, code: u32) -> Option<Rc<Vec<OutlineSegment>>> {
+        if let Some(outline) = self.outlines.borrow().get(&code) {
+            return outline.clone();
+        }
+        let outline = self.find_outline(code).map(|mut outline| {
+            for segment in outline.iter_mut() {
+                *segment = match *segment {
+                    OutlineSegment::MoveTo(x, y) => OutlineSegment::MoveTo(x, -y),
+                    OutlineSegment::LineTo(x, y) => OutlineSegment::LineTo(x, -y),
+                    OutlineSegment::QuadTo(x1, y1, x, y) => OutlineSegment::QuadTo(x1, -y1, x, -y),
+                    OutlineSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+                        OutlineSegment::CurveTo(x1, -y1, x2, -y2, x, -y)
+                    }
+                    OutlineSegment::Close => OutlineSegment::Close,
+                };
+            }
+            Rc::new(outline)
+        });
+        self.outlines.borrow_mut().insert(code, outline.clone());
+        outline
+    }
+
+    fn find_outline(&self, code: u32) -> Option<Vec<OutlineSegment>> {
+        let font = self.font.as_ref()?;
+        if self.is_embedded && self.is_composite {
+            let gid = match self.cid_to_gid {
+                Some(ref map) => *map.get(code as usize)?,
+                None => code as u16,
+            };
+            return font.glyph_outline(GlyphSelector::Index(gid));
+        }
+        if self.is_embedded {
+            // Symbolic TrueType fonts usually map codes into the private use
+            // area at U+F000 rather than to real characters.
+            let candidates = [
+                self.char_for_code(code),
+                char::from_u32(0xF000 + code),
+                char::from_u32(code),
+            ];
+            return candidates
+                .into_iter()
+                .flatten()
+                .find_map(|c| font.glyph_outline(GlyphSelector::Char(c)));
+        }
+        let c = self.char_for_code(code)?;
+        if c == ' ' {
+            return None;
+        }
+        font.glyph_outline(GlyphSelector::Char(c))
+    }
+
+    /// Get a glyph's horizontal advance in ems.
+    pub fn width(&self, code: u32) -> f32 {
+        if let Some(&width) = self.widths.get(&code) {
+            return width / 1000.0;
+        }
+        if let Some(width) = self.default_width {
+            return width / 1000.0;
+        }
+        // The standard 14 fonts don't need widths, so use the fallback's.
+        let font = self.font.as_ref();
+        let c = self.char_for_code(code);
+        match (font, c) {
+            (Some(font), Some(c)) => font.glyph_advance(GlyphSelector::Char(c)).unwrap_or(0.5),
+            _ => 0.5,
+        }
+    }
+}
+
+fn load_embedded_font(document: &Document, descriptor: &Dictionary) -> Option<Font> {
+    let (key, stream) = ["FontFile2", "FontFile3", "FontFile"]
+        .into_iter()
+        .find_map(|key| match document.lookup(descriptor, key) {
+            Object::Stream(stream) => Some((key, stream)),
+            _ => None,
+        })?;
+    let subtype = document.lookup(&stream.dict, "Subtype");
+    if key == "FontFile" || (key == "FontFile3" && subtype.as_name() != Some("OpenType")) {
+        log_dbg!(
+            "Embedded PDF font ({}, {:?}) is not supported, using a fallback",
+            key,
+            subtype.as_name()
+        );
+        return None;
+    }
+    let data = document.decode_stream(&stream).ok()?;
+    let font = Font::from_bytes(data);
+    if font.is_none() {
+        log!("Warning: couldn't parse embedded PDF font, using a fallback");
+    }
+    font
+}
+
+fn load_encoding(document: &Document, dict: &Dictionary, is_symbolic: bool) -> [Option<char>; 256] {
+    let (base, differences) = match document.lookup(dict, "Encoding") {
+        Object::Name(name) => (Some(name), None),
+        Object::Dictionary(encoding) => (
+            document
+                .lookup(&encoding, "BaseEncoding")
+                .as_name()
+                .map(str::to_string),
+            Some(document.lookup(&encoding, "Differences")),
+        ),
+        _ => (None, None),
+    };
+    let base_fn: fn(u8) -> Option<char> = match base.as_deref() {
+        Some("WinAnsiEncoding") => win_ansi_char,
+        Some("MacRomanEncoding") => mac_roman_char,
+        // The font's built-in encoding, which for symbolic fonts is
+        // unknowable without parsing the font program.
+        None if is_symbolic => |code| Some(code as char),
+        _ => standard_char,
+    };
+    let mut encoding: [Option<char>; 256] = std::array::from_fn(|code| base_fn(code as u8));
+
+    if let Some(Object::Array(differences)) = differences {
+        let mut code = 0;
+        for item in differences {
+            match item {
+                Object::Integer(i) => code = i,
+                Object::Name(name) => {
+                    if let Some(slot) = encoding.get_mut(code as usize) {
+                        *slot = char_for_glyph_name(&name);
+                    }
+                    code += 1;
+                }
+                _ => (),
+            }
+        }
+    }
+    encoding
+}
+
+/// Parse the `bfchar` and `bfrange` sections of a `ToUnicode` CMap. Mappings
+/// to more than one character (e.g. ligatures) keep only the first one.
+fn parse_to_unicode(data: &[u8]) -> HashMap<u32, char> {
+    fn code(bytes: &[u8]) -> u32 {
+        bytes.iter().fold(0, |acc, &b| acc << 8 | b as u32)
+    }
+    fn first_char(utf16be: &[u8]) -> Option<char> {
+        let units: Vec<u16> = utf16be
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        char::decode_utf16(units).next()?.ok()
+    }
+
+    let mut map = HashMap::new();
+    let mut lexer = Lexer::new(data, 0);
+    let mut section = None;
+    while let Some(token) = lexer.next_token() {
+        match token {
+            Token::Keyword(k) if k == "beginbfchar" || k == "beginbfrange" => section = Some(k),
+            Token::Keyword(k) if k == "endbfchar" || k == "endbfrange" => section = None,
+            Token::String(src) if section.as_deref() == Some("beginbfchar") => {
+                if let Some(Token::String(dst)) = lexer.next_token() {
+                    if let Some(c) = first_char(&dst) {
+                        map.insert(code(&src), c);
+                    }
+                }
+            }
+            Token::String(low) if section.as_deref() == Some("beginbfrange") => {
+                let Some(Token::String(high)) = lexer.next_token() else {
+                    continue;
+                };
+                let (low, high) = (code(&low), code(&high));
+                if high < low || high - low > 0xFFFF {
+                    continue;
+                }
+                match lexer.parse_object() {
+                    Some(Object::String(dst)) => {
+                        let Some(start) = first_char(&dst) else {
+                            continue;
+                        };
+                        for (i, src) in (low..=high).enumerate() {
+                            if let Some(c) = char::from_u32(start as u32 + i as u32) {
+                                map.insert(src, c);
+                            }
+                        }
+                    }
+                    Some(Object::Array(dsts)) => {
+                        for (src, dst) in (low..=high).zip(dsts.iter()) {
+                            if let Some(c) = dst.as_string().and_then(first_char) {
+                                map.insert(src, c);
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+    map
+}
+
+/// Parse a CID font's `W` array, which has entries of the form
+/// `first [w1 w2 ...]` and `first last w`.
+fn parse_cid_widths(document: &Document, w: &[Object], widths: &mut HashMap<u32, f32>) {
+    let mut i = 0;
+    while i < w.len() {
+        let Some(first) = document.resolve(&w[i]).as_integer() else {
+            break;
+        };
+        match w.get(i + 1).map(|next| document.resolve(next)) {
+            Some(Object::Array(list)) => {
+                for (j, width) in list.iter().enumerate() {
+                    if let Some(width) = width.as_number() {
+                        widths.insert((first + j as i64) as u32, width);
+                    }
+                }
+                i += 2;
+            }
+            Some(last) => {
+                let last = last.as_integer().unwrap_or(first);
+                let width = w.get(i + 2).and_then(|w| document.resolve(w).as_number());
+                if let Some(width) = width {
+                    for cid in first..=last.min(first + 0xFFFF) {
+                        widths.insert(cid as u32, width);
+                    }
+                }
+                i += 3;
+            }
+            None => break,
+        }
+    }
+}
+
+/// Windows-1252 characters 0x80 to 0x9F.
+const CP1252_HIGH: [Option<char>; 32] = [
+    Some('€'),
+    None,
+    Some('‚'),
+    Some('ƒ'),
+    Some('„'),
+    Some('…'),
+    Some('†'),
+    Some('‡'),
+    Some('ˆ'),
+    Some('‰'),
+    Some('Š'),
+    Some('‹'),
+    Some('Œ'),
+    None,
+    Some('Ž'),
+    None,
+    None,
+    Some('‘'),
+    Some('’'),
+    Some('“'),
+    Some('”'),
+    Some('•'),
+    Some('–'),
+    Some('—'),
+    Some('˜'),
+    Some('™'),
+    Some('š'),
+    Some('›'),
+    Some('œ'),
+    None,
+    Some('ž'),
+    Some('Ÿ'),
+];
+
+fn win_ansi_char(code: u8) -> Option<char> {
+    match code {
+        0x20..=0x7E | 0xA0..=0xFF => Some(code as char),
+        0x80..=0x9F => CP1252_HIGH[(code - 0x80) as usize],
+        _ => None,
+    }
+}
+
+/// Mac OS Roman characters 0x80 to 0xFF.
+const MAC_ROMAN_HIGH: [&str; 4] = [
+    "ÄÅÇÉÑÖÜáàâäãåçéèêëíìîïñóòôöõúùûü",
+    "†°¢£§•¶ß®©™´¨≠ÆØ∞±≤≥¥µ∂∑∏π∫ªºΩæø",
+    "¿¡¬√ƒ≈∆«»…\u{A0}ÀÃÕŒœ–—“”‘’÷◊ÿŸ⁄€‹›ﬁﬂ",
+    "‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ\u{F8FF}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ",
+];
+
+fn mac_roman_char(code: u8) -> Option<char> {
+    match code {
+        0x20..=0x7E => Some(code as char),
+        0x80..=0xFF => {
+            let code = (code - 0x80) as usize;
+            MAC_ROMAN_HIGH[code / 32].chars().nth(code % 32)
+        }
+        _ => None,
+    }
+}
+
+/// Adobe's StandardEncoding, the default for Type 1 fonts.
+fn standard_char(code: u8) -> Option<char> {
+    Some(match code {
+        0x27 => '’',
+        0x60 => '‘',
+        0x20..=0x7E => code as char,
+        0xA1 => '¡',
+        0xA2 => '¢',
+        0xA3 => '£',
+        0xA4 => '⁄',
+        0xA5 => '¥',
+        0xA6 => 'ƒ',
+        0xA7 => '§',
+        0xA8 => '¤',
+        0xA9 => '\'',
+        0xAA => '“',
+        0xAB => '«',
+        0xAC => '‹',
+        0xAD => '›',
+        0xAE => 'ﬁ',
+        0xAF => 'ﬂ',
+        0xB1 => '–',
+        0xB2 => '†',
+        0xB3 => '‡',
+        0xB4 => '·',
+        0xB6 => '¶',
+        0xB7 => '•',
+        0xB8 => '‚',
+        0xB9 => '„',
+        0xBA => '”',
+        0xBB => '»',
+        0xBC => '…',
+        0xBD => '‰',
+        0xBF => '¿',
+        0xC1 => '`',
+        0xC2 => '´',
+        0xC3 => 'ˆ',
+        0xC4 => '˜',
+        0xC5 => '¯',
+        0xC6 => '˘',
+        0xC7 => '˙',
+        0xC8 => '¨',
+        0xCA => '˚',
+        0xCB => '¸',
+        0xCD => '˝',
+        0xCE => '˛',
+        0xCF => 'ˇ',
+        0xD0 => '—',
+        0xE1 => 'Æ',
+        0xE3 => 'ª',
+        0xE8 => 'Ł',
+        0xE9 => 'Ø',
+        0xEA => 'Œ',
+        0xEB => 'º',
+        0xF1 => 'æ',
+        0xF5 => 'ı',
+        0xF8 => 'ł',
+        0xF9 => 'ø',
+        0xFA => 'œ',
+        0xFB => 'ß',
+        _ => return None,
+    })
+}
+
+/// Glyph names for ASCII punctuation and digits.
+const ASCII_GLYPH_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("exclam", '!'),
+    ("quotedbl", '"'),
+    ("numbersign", '#'),
+    ("dollar", '$'),
+    ("percent", '%'),
+    ("ampersand", '&'),
+    ("quotesingle", '\''),
+    ("parenleft", '('),
+    ("parenright", ')'),
+    ("asterisk", '*'),
+    ("plus", '+'),
+    ("comma", ','),
+    ("hyphen", '-'),
+    ("period", '.'),
+    ("slash", '/'),
+    ("zero", '0'),
+    ("one", '1'),
+    ("two", '2'),
+    ("three", '3'),
+    ("four", '4'),
+    ("five", '5'),
+    ("six", '6'),
+    ("seven", '7'),
+    ("eight", '8'),
+    ("nine", '9'),
+    ("colon", ':'),
+    ("semicolon", ';'),
+    ("less", '<'),
+    ("equal", '='),
+    ("greater", '>'),
+    ("question", '?'),
+    ("at", '@'),
+    ("bracketleft", '['),
+    ("backslash", '\\'),
+    ("bracketright", ']'),
+    ("asciicircum", '^'),
+    ("underscore", '_'),
+    ("grave", '`'),
+    ("braceleft", '{'),
+    ("bar", '|'),
+    ("braceright", '}'),
+    ("asciitilde", '~'),
+];
+
+/// Glyph names for U+00A0 to U+00FF.
+const LATIN1_GLYPH_NAMES: [&str; 96] = [
+    "nbspace",
+    "exclamdown",
+    "cent",
+    "sterling",
+    "currency",
+    "yen",
+    "brokenbar",
+    "section",
+    "dieresis",
+    "copyright",
+    "ordfeminine",
+    "guillemotleft",
+    "logicalnot",
+    "sfthyphen",
+    "registered",
+    "macron",
+    "degree",
+    "plusminus",
+    "twosuperior",
+    "threesuperior",
+    "acute",
+    "mu",
+    "paragraph",
+    "periodcentered",
+    "cedilla",
+    "onesuperior",
+    "ordmasculine",
+    "guillemotright",
+    "onequarter",
+    "onehalf",
+    "threequarters",
+    "questiondown",
+    "Agrave",
+    "Aacute",
+    "Acircumflex",
+    "Atilde",
+    "Adieresis",
+    "Aring",
+    "AE",
+    "Ccedilla",
+    "Egrave",
+    "Eacute",
+    "Ecircumflex",
+    "Edieresis",
+    "Igrave",
+    "Iacute",
+    "Icircumflex",
+    "Idieresis",
+    "Eth",
+    "Ntilde",
+    "Ograve",
+    "Oacute",
+    "Ocircumflex",
+    "Otilde",
+    "Odieresis",
+    "multiply",
+    "Oslash",
+    "Ugrave",
+    "Uacute",
+    "Ucircumflex",
+    "Udieresis",
+    "Yacute",
+    "Thorn",
+    "germandbls",
+    "agrave",
+    "aacute",
+    "acircumflex",
+    "atilde",
+    "adieresis",
+    "aring",
+    "ae",
+    "ccedilla",
+    "egrave",
+    "eacute",
+    "ecircumflex",
+    "edieresis",
+    "igrave",
+    "iacute",
+    "icircumflex",
+    "idieresis",
+    "eth",
+    "ntilde",
+    "ograve",
+    "oacute",
+    "ocircumflex",
+    "otilde",
+    "odieresis",
+    "divide",
+    "oslash",
+    "ugrave",
+    "uacute",
+    "ucircumflex",
+    "udieresis",
+    "yacute",
+    "thorn",
+    "ydieresis",
+];
+
+/// Other common glyph names.
+const OTHER_GLYPH_NAMES: &[(&str, char)] = &[
+    ("Euro", '€'),
+    ("quotesinglbase", '‚'),
+    ("florin", 'ƒ'),
+    ("quotedblbase", '„'),
+    ("ellipsis", '…'),
+    ("dagger", '†'),
+    ("daggerdbl", '‡'),
+    ("circumflex", 'ˆ'),
+    ("perthousand", '‰'),
+    ("Scaron", 'Š'),
+    ("guilsinglleft", '‹'),
+    ("OE", 'Œ'),
+    ("Zcaron", 'Ž'),
+    ("quoteleft", '‘'),
+    ("quoteright", '’'),
+    ("quotedblleft", '“'),
+    ("quotedblright", '”'),
+    ("bullet", '•'),
+    ("endash", '–'),
+    ("emdash", '—'),
+    ("tilde", '˜'),
+    ("trademark", '™'),
+    ("scaron", 'š'),
+    ("guilsinglright", '›'),
+    ("oe", 'œ'),
+    ("zcaron", 'ž'),
+    ("Ydieresis", 'Ÿ'),
+    ("fi", 'ﬁ'),
+    ("fl", 'ﬂ'),
+    ("fraction", '⁄'),
+    ("minus", '−'),
+    ("dotlessi", 'ı'),
+    ("Lslash", 'Ł'),
+    ("lslash", 'ł'),
+    ("breve", '˘'),
+    ("dotaccent", '˙'),
+    ("ring", '˚'),
+    ("hungarumlaut", '˝'),
+    ("ogonek", '˛'),
+    ("caron", 'ˇ'),
+];
+
+/// Map an Adobe glyph name to a character, as used in `Differences` arrays.
+fn char_for_glyph_name(name: &str) -> Option<char> {
+    // Variants like "a.sc" or "f_i" are reduced to their first component.
+    let name = name.split(['.', '_']).next().unwrap_or(name);
+    if name.len() == 1 && name.as_bytes()[0].is_ascii_alphabetic() {
+        return name.chars().next();
+    }
+    if let Some(&(_, c)) = ASCII_GLYPH_NAMES
+        .iter()
+        .chain(OTHER_GLYPH_NAMES)
+        .find(|&&(n, _)| n == name)
+    {
+        return Some(c);
+    }
+    if let Some(i) = LATIN1_GLYPH_NAMES.iter().position(|&n| n == name) {
+        return char::from_u32(0xA0 + i as u32);
+    }
+    // "uniXXXX" and "uXXXX" to "uXXXXXX".
+    let hex = name
+        .strip_prefix("uni")
+        .filter(|hex| hex.len() >= 4)
+        .map(|hex| &hex[..4])
+        .or_else(|| {
+            name.strip_prefix('u')
+                .filter(|hex| (4..=6).contains(&hex.len()))
+        })?;
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyph_names() {
+        assert_eq!(char_for_glyph_name("A"), Some('A'));
+        assert_eq!(char_for_glyph_name("seven"), Some('7'));
+        assert_eq!(char_for_glyph_name("eacute"), Some('é'));
+        assert_eq!(char_for_glyph_name("quoteright"), Some('’'));
+        assert_eq!(char_for_glyph_name("uni20AC"), Some('€'));
+        assert_eq!(char_for_glyph_name("u1F600"), Some('😀'));
+        assert_eq!(char_for_glyph_name("a.sc"), Some('a'));
+        assert_eq!(char_for_glyph_name("g123"), None);
+    }
+
+    #[test]
+    fn encodings() {
+        assert_eq!(win_ansi_char(0x93), Some('“'));
+        assert_eq!(win_ansi_char(0xE9), Some('é'));
+        assert_eq!(mac_roman_char(0x8E), Some('é'));
+        assert_eq!(mac_roman_char(0xFF), Some('ˇ'));
+        assert_eq!(standard_char(0x27), Some('’'));
+    }
+
+    #[test]
+    fn to_unicode() {
+        let cmap = b"2 beginbfchar <01> <0041> <02> <D83DDE00> endbfchar\n\
+                     1 beginbfrange <10> <12> <0061> endbfrange";
+        let map = parse_to_unicode(cmap);
+        assert_eq!(map[&0x01], 'A');
+        assert_eq!(map[&0x02], '😀');
+        assert_eq!(map[&0x12], 'c');
+    }
+}
@@ -0,0 +1,1292 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Parsing of PDF files: objects, cross-reference tables, object streams and
+//! stream filters.
+//!
+//! Both classic cross-reference tables and the compressed cross-reference
+//! streams of PDF 1.5 are supported. If the cross-reference data is missing or
+//! damaged, the objects are found by scanning the whole file instead, which is
+//! what most PDF readers do.
+
+use crate::image;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+pub type Dictionary = HashMap<String, Object>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Object {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Real(f32),
+    String(Vec<u8>),
+    Name(String),
+    Array(Vec<Object>),
+    Dictionary(Dictionary),
+    Stream(Rc<Stream>),
+    /// Object number and generation number.
+    Reference(u32, u16),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Stream {
+    pub dict: Dictionary,
+    /// The data before any filters are applied.
+    pub data: Vec<u8>,
+}
+
+impl Object {
+    pub fn as_number(&self) -> Option<f32> {
+        match *self {
+            Object::Integer(i) => Some(i as f32),
+            Object::Real(r) => Some(r),
+            _ => None,
+        }
+    }
+    pub fn as_integer(&self) -> Option<i64> {
+        match *self {
+            Object::Integer(i) => Some(i),
+            Object::Real(r) => Some(r as i64),
+            _ => None,
+        }
+    }
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Object::Boolean(b) => Some(b),
+            _ => None,
+        }
+    }
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            Object::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+    pub fn as_string(&self) -> Option<&[u8]> {
+        match self {
+            Object::String(string) => Some(string),
+            _ => None,
+        }
+    }
+    pub fn as_array(&self) -> Option<&[Object]> {
+        match self {
+            Object::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+    /// Get a dictionary, or the dictionary of a stream.
+    pub fn as_dict(&self) -> Option<&Dictionary> {
+        match self {
+            Object::Dictionary(dict) => Some(dict),
+            Object::Stream(stream) => Some(&stream.dict),
+            _ => None,
+        }
+    }
+    pub fn as_stream(&self) -> Option<&Rc<Stream>> {
+        match self {
+            Object::Stream(stream) => Some(stream),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Integer(i64),
+    Real(f32),
+    String(Vec<u8>),
+    Name(String),
+    ArrayStart,
+    ArrayEnd,
+    DictStart,
+    DictEnd,
+    /// Anything else: `true`, `obj`, `R`, content stream operators, etc.
+    Keyword(String),
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b'\0' | b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+fn is_delimiter(b: u8) -> bool {
+    matches!(
+        b,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+fn is_regular(b: u8) -> bool {
+    !is_whitespace(b) && !is_delimiter(b)
+}
+fn hex_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+pub struct Lexer<'a> {
+    data: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(data: &'a [u8], pos: usize) -> Lexer<'a> {
+        Lexer { data, pos }
+    }
+
+    fn peek_byte(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    /// Skip whitespace and comments.
+    pub fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek_byte() {
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                while let Some(b) = self.peek_byte() {
+                    if b == b'\r' || b == b'\n' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn next_token(&mut self) -> Option<Token> {
+        self.skip_whitespace();
+        let b = self.peek_byte()?;
+        self.pos += 1;
+        Some(match b {
+            b'(' => Token::String(self.literal_string()),
+            b'<' if self.peek_byte() == Some(b'<') => {
+                self.pos += 1;
+                Token::DictStart
+            }
+            b'<' => Token::String(self.hex_string()),
+            b'>' if self.peek_byte() == Some(b'>') => {
+                self.pos += 1;
+                Token::DictEnd
+            }
+            b'[' => Token::ArrayStart,
+            b']' => Token::ArrayEnd,
+            b'/' => Token::Name(self.name()),
+            b'0'..=b'9' | b'+' | b'-' | b'.' => {
+                let start = self.pos - 1;
+                while self
+                    .peek_byte()
+                    .is_some_and(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.'))
+                {
+                    self.pos += 1;
+                }
+                let text = std::str::from_utf8(&self.data[start..self.pos]).unwrap();
+                if text.contains('.') {
+                    // Some producers write things like "-.5" or "1.2.3";
+                    // be lenient.
+                    Token::Real(text.parse().unwrap_or(0.0))
+                } else {
+                    Token::Integer(text.parse().unwrap_or(0))
+                }
+            }
+            // Other delimiters: "{", "}", and stray ")" and ">".
+            _ if !is_regular(b) => Token::Keyword((b as char).to_string()),
+            _ => {
+                let start = self.pos - 1;
+                while self.peek_byte().is_some_and(is_regular) {
+                    self.pos += 1;
+                }
+                Token::Keyword(String::from_utf8_lossy(&self.data[start..self.pos]).into_owned())
+            }
+        })
+    }
+
+    pub fn peek_token(&mut self) -> Option<Token> {
+        let pos = self.pos;
+        let token = self.next_token();
+        self.pos = pos;
+        token
+    }
+
+    fn literal_string(&mut self) -> Vec<u8> {
+        let mut string = Vec::new();
+        let mut depth = 1;
+        while let Some(b) = self.peek_byte() {
+            self.pos += 1;
+            match b {
+                b'(' => {
+                    depth += 1;
+                    string.push(b);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    string.push(b);
+                }
+                b'\\' => {
+                    let Some(escaped) = self.peek_byte() else {
+                        break;
+                    };
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => string.push(b'\n'),
+                        b'r' => string.push(b'\r'),
+                        b't' => string.push(b'\t'),
+                        b'b' => string.push(b'\x08'),
+                        b'f' => string.push(b'\x0C'),
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek_byte() {
+                                    Some(d @ b'0'..=b'7') => {
+                                        value = value * 8 + (d - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            string.push(value as u8);
+                        }
+                        // Line continuation.
+                        b'\r' => {
+                            if self.peek_byte() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => (),
+                        // Includes \( \) and \\.
+                        _ => string.push(escaped),
+                    }
+                }
+                b'\r' => {
+                    // End-of-line markers become a single line feed.
+                    if self.peek_byte() == Some(b'\n') {
+                        self.pos += 1;
+                    }
+                    string.push(b'\n');
+                }
+                _ => string.push(b),
+            }
+        }
+        string
+    }
+
+    fn hex_string(&mut self) -> Vec<u8> {
+        let mut string = Vec::new();
+        let mut high: Option<u8> = None;
+        while let Some(b) = self.peek_byte() {
+            self.pos += 1;
+            if b == b'>' {
+                break;
+            }
+            let Some(value) = hex_value(b) else {
+                continue;
+            };
+            match high.take() {
+                Some(high) => string.push(high << 4 | value),
+                None => high = Some(value),
+            }
+        }
+        if let Some(high) = high {
+            string.push(high << 4);
+        }
+        string
+    }
+
+    fn name(&mut self) -> String {
+        let mut name = Vec::new();
+        while let Some(b) = self.peek_byte() {
+            if !is_regular(b) {
+                break;
+            }
+            self.pos += 1;
+            if b == b'#' {
+                let digits = self.data.get(self.pos..self.pos + 2);
+                if let Some(&[h, l]) = digits {
+                    if let (Some(h), Some(l)) = (hex_value(h), hex_value(l)) {
+                        name.push(h << 4 | l);
+                        self.pos += 2;
+                        continue;
+                    }
+                }
+            }
+            name.push(b);
+        }
+        String::from_utf8_lossy(&name).into_owned()
+    }
+
+    /// Parse the next object. Streams are not handled here since they need
+    /// the document to find their length, see [Document::parse_indirect_at].
+    pub fn parse_object(&mut self) -> Option<Object> {
+        let token = self.next_token()?;
+        self.parse_object_from(token)
+    }
+
+    fn parse_object_from(&mut self, token: Token) -> Option<Object> {
+        Some(match token {
+            Token::Integer(i) => {
+                // Could be the start of a reference: "12 0 R".
+                let pos = self.pos;
+                if let (Some(Token::Integer(generation)), Some(Token::Keyword(r))) =
+                    (self.next_token(), self.next_token())
+                {
+                    if r == "R" && (0..=u32::MAX as i64).contains(&i) {
+                        return Some(Object::Reference(i as u32, generation as u16));
+                    }
+                }
+                self.pos = pos;
+                Object::Integer(i)
+            }
+            Token::Real(r) => Object::Real(r),
+            Token::String(s) => Object::String(s),
+            Token::Name(n) => Object::Name(n),
+            Token::ArrayStart => {
+                let mut array = Vec::new();
+                loop {
+                    match self.next_token()? {
+                        Token::ArrayEnd => break,
+                        token => array.push(self.parse_object_from(token)?),
+                    }
+                }
+                Object::Array(array)
+            }
+            Token::DictStart => Object::Dictionary(self.parse_dictionary_body()?),
+            Token::Keyword(k) => match k.as_str() {
+                "true" => Object::Boolean(true),
+                "false" => Object::Boolean(false),
+                "null" => Object::Null,
+                _ => return None,
+            },
+            Token::ArrayEnd | Token::DictEnd => return None,
+        })
+    }
+
+    /// Parse the contents of a dictionary after its `<<`.
+    fn parse_dictionary_body(&mut self) -> Option<Dictionary> {
+        let mut dict = Dictionary::new();
+        loop {
+            match self.next_token()? {
+                Token::DictEnd => break,
+                Token::Name(key) => {
+                    let token = self.next_token()?;
+                    if token == Token::DictEnd {
+                        // Missing value, tolerated.
+                        break;
+                    }
+                    let value = self.parse_object_from(token)?;
+                    dict.insert(key, value);
+                }
+                // Skip junk.
+                _ => continue,
+            }
+        }
+        Some(dict)
+    }
+}
+
+/// An operation in a content stream: the operator and its operands.
+#[derive(Debug, PartialEq)]
+pub struct Operation {
+    pub operator: String,
+    pub operands: Vec<Object>,
+}
+
+/// Split a content stream into operations. Inline images (`BI` ... `ID` ...
+/// `EI`) become a single `BI` operation with the image dictionary and the
+/// data as operands.
+pub fn parse_content(data: &[u8]) -> Vec<Operation> {
+    let mut lexer = Lexer::new(data, 0);
+    let mut operations = Vec::new();
+    let mut operands = Vec::new();
+    while let Some(token) = lexer.next_token() {
+        match token {
+            Token::Keyword(k) if !matches!(k.as_str(), "true" | "false" | "null") => {
+                if k == "BI" {
+                    operations.push(parse_inline_image(&mut lexer));
+                    operands.clear();
+                    continue;
+                }
+                operations.push(Operation {
+                    operator: k,
+                    operands: std::mem::take(&mut operands),
+                });
+            }
+            token => match lexer.parse_object_from(token) {
+                Some(object) => operands.push(object),
+                // Malformed operand, e.g. an unbalanced "]": drop it.
+                None => operands.clear(),
+            },
+        }
+    }
+    operations
+}
+
+fn parse_inline_image(lexer: &mut Lexer) -> Operation {
+    let mut dict = Dictionary::new();
+    loop {
+        match lexer.next_token() {
+            Some(Token::Keyword(k)) if k == "ID" => break,
+            Some(Token::Name(key)) => {
+                if let Some(value) = lexer.parse_object() {
+                    dict.insert(key, value);
+                }
+            }
+            None => break,
+            _ => (),
+        }
+    }
+    // A single whitespace byte separates "ID" from the data.
+    lexer.pos += 1;
+    let start = lexer.pos.min(lexer.data.len());
+    // The data ends at "EI" surrounded by whitespace. The data can contain
+    // that sequence by chance, but there is no better way without decoding.
+    let mut end = lexer.data.len();
+    let mut i = start;
+    while i + 2 <= lexer.data.len() {
+        if &lexer.data[i..i + 2] == b"EI"
+            && i > start
+            && is_whitespace(lexer.data[i - 1])
+            && lexer.data.get(i + 2).map_or(true, |&b| is_whitespace(b))
+        {
+            end = i - 1;
+            break;
+        }
+        i += 1;
+    }
+    lexer.pos = (end + 3).min(lexer.data.len());
+    Operation {
+        operator: "BI".to_string(),
+        operands: vec![
+            Object::Dictionary(dict),
+            Object::String(lexer.data[start..end].to_vec()),
+        ],
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum XrefEntry {
+    /// Byte offset of an `obj` in the file.
+    Offset(usize),
+    /// Object stored in an object stream.
+    Compressed { stream: u32, index: u32 },
+}
+
+/// Limit on nested object lookups, guarding against reference cycles.
+const MAX_DEPTH: u32 = 32;
+
+pub struct Document {
+    data: Vec<u8>,
+    xref: HashMap<u32, XrefEntry>,
+    pub trailer: Dictionary,
+    cache: RefCell<HashMap<u32, Object>>,
+    depth: Cell<u32>,
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| i + from)
+}
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+impl Document {
+    pub fn parse(data: Vec<u8>) -> Result<Document, String> {
+        if find(&data[..data.len().min(1024)], b"%PDF-", 0).is_none() {
+            return Err("Not a PDF file".to_string());
+        }
+        let mut document = Document {
+            data,
+            xref: HashMap::new(),
+            trailer: Dictionary::new(),
+            cache: RefCell::new(HashMap::new()),
+            depth: Cell::new(0),
+        };
+
+        let tail_start = document.data.len().saturating_sub(1024);
+        let startxref = rfind(&document.data[tail_start..], b"startxref").and_then(|i| {
+            let mut lexer = Lexer::new(&document.data, tail_start + i + 9);
+            match lexer.next_token() {
+                Some(Token::Integer(offset)) => Some(offset as usize),
+                _ => None,
+            }
+        });
+        let loaded = match startxref {
+            Some(offset) => document.load_xref_chain(offset),
+            None => Err("No startxref".to_string()),
+        };
+        if let Err(e) = loaded {
+            log!(
+                "Warning: PDF cross-reference data is damaged ({}), scanning for objects",
+                e
+            );
+            document.xref.clear();
+            document.trailer.clear();
+            document.reconstruct_xref();
+        } else if !document.trailer.contains_key("Root") {
+            log!("Warning: PDF trailer has no /Root, scanning for objects");
+            document.reconstruct_xref();
+        }
+
+        if document.trailer.contains_key("Encrypt") {
+            return Err("Encrypted PDFs are not supported".to_string());
+        }
+        if !document.trailer.contains_key("Root") {
+            return Err("No document catalog found".to_string());
+        }
+        Ok(document)
+    }
+
+    /// Load a cross-reference section and all the older ones it refers to.
+    fn load_xref_chain(&mut self, offset: usize) -> Result<(), String> {
+        let mut visited = HashSet::new();
+        let mut next = Some(offset);
+        while let Some(offset) = next.take() {
+            if !visited.insert(offset) {
+                break;
+            }
+            let trailer = self.load_xref_section(offset)?;
+            // Hybrid files have a cross-reference stream for the objects that
+            // are only in object streams.
+            if let Some(Object::Integer(stream_offset)) = trailer.get("XRefStm") {
+                let stream_offset = *stream_offset as usize;
+                if visited.insert(stream_offset) {
+                    self.load_xref_section(stream_offset)?;
+                }
+            }
+            if let Some(Object::Integer(prev)) = trailer.get("Prev") {
+                next = Some(*prev as usize);
+            }
+            // Newer trailers take precedence.
+            for (key, value) in trailer {
+                self.trailer.entry(key).or_insert(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Load a single cross-reference table or stream, returning the trailer.
+    /// Entries already present (from newer sections) are kept.
+    fn load_xref_section(&mut self, offset: usize) -> Result<Dictionary, String> {
+        if offset >= self.data.len() {
+            return Err(format!("Offset {} is out of bounds", offset));
+        }
+        let mut lexer = Lexer::new(&self.data, offset);
+        match lexer.next_token() {
+            Some(Token::Keyword(k)) if k == "xref" => (),
+            Some(Token::Integer(_)) => return self.load_xref_stream(offset),
+            _ => return Err(format!("No cross-reference data at {}", offset)),
+        }
+
+        let mut entries = Vec::new();
+        loop {
+            match lexer.next_token() {
+                Some(Token::Keyword(k)) if k == "trailer" => break,
+                Some(Token::Integer(start)) => {
+                    let Some(Token::Integer(count)) = lexer.next_token() else {
+                        return Err("Bad cross-reference subsection".to_string());
+                    };
+                    for i in 0..count {
+                        let (Some(Token::Integer(offset)), Some(Token::Integer(_)), Some(kind)) =
+                            (lexer.next_token(), lexer.next_token(), lexer.next_token())
+                        else {
+                            return Err("Bad cross-reference entry".to_string());
+                        };
+                        if kind == Token::Keyword("n".to_string()) && offset > 0 {
+                            entries.push(((start + i) as u32, offset as usize));
+                        }
+                    }
+                }
+                _ => return Err("Bad cross-reference table".to_string()),
+            }
+        }
+        let Some(Object::Dictionary(trailer)) = lexer.parse_object() else {
+            return Err("Bad trailer".to_string());
+        };
+        for (number, offset) in entries {
+            self.xref.entry(number).or_insert(XrefEntry::Offset(offset));
+        }
+        Ok(trailer)
+    }
+
+    fn load_xref_stream(&mut self, offset: usize) -> Result<Dictionary, String> {
+        let Some((_, Object::Stream(stream))) = self.parse_indirect_at(offset) else {
+            return Err(format!("No cross-reference stream at {}", offset));
+        };
+        let data = self.decode_stream(&stream)?;
+        let widths: Vec<usize> = stream
+            .dict
+            .get("W")
+            .and_then(Object::as_array)
+            .ok_or("Cross-reference stream has no /W")?
+            .iter()
+            .map(|w| w.as_integer().unwrap_or(0) as usize)
+            .collect();
+        if widths.len() != 3 || widths.iter().any(|&w| w > 8) {
+            return Err("Bad cross-reference stream /W".to_string());
+        }
+        let size = stream
+            .dict
+            .get("Size")
+            .and_then(Object::as_integer)
+            .unwrap_or(0);
+        let index: Vec<i64> = match stream.dict.get("Index").and_then(Object::as_array) {
+            Some(index) => index.iter().filter_map(Object::as_integer).collect(),
+            None => vec![0, size],
+        };
+
+        let entry_size: usize = widths.iter().sum();
+        let read_field = |bytes: &[u8]| bytes.iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+        let mut entries = data.chunks_exact(entry_size.max(1));
+        for pair in index.chunks_exact(2) {
+            let (start, count) = (pair[0], pair[1]);
+            for i in 0..count {
+                let Some(entry) = entries.next() else {
+                    break;
+                };
+                let (kind, rest) = entry.split_at(widths[0]);
+                let (field2, field3) = rest.split_at(widths[1]);
+                // The type defaults to 1 if its field is absent.
+                let kind = if widths[0] == 0 { 1 } else { read_field(kind) };
+                let (field2, field3) = (read_field(field2), read_field(field3));
+                let xref_entry = match kind {
+                    1 => XrefEntry::Offset(field2 as usize),
+                    2 => XrefEntry::Compressed {
+                        stream: field2 as u32,
+                        index: field3 as u32,
+                    },
+                    _ => continue,
+                };
+                self.xref.entry((start + i) as u32).or_insert(xref_entry);
+            }
+        }
+
+        let mut trailer = stream.dict.clone();
+        // These are properties of the stream, not the trailer.
+        for key in ["Length", "Filter", "DecodeParms", "W", "Index", "Type"] {
+            trailer.remove(key);
+        }
+        Ok(trailer)
+    }
+
+    /// Find objects by scanning for "<number> <generation> obj".
+    fn reconstruct_xref(&mut self) {
+        let mut pos = 0;
+        while let Some(obj_pos) = find(&self.data, b"obj", pos) {
+            pos = obj_pos + 3;
+            // Walk back over "<number> <generation> ".
+            let mut start = obj_pos;
+            let mut numbers_seen = 0;
+            while numbers_seen < 2 {
+                while start > 0 && is_whitespace(self.data[start - 1]) {
+                    start -= 1;
+                }
+                let end = start;
+                while start > 0 && self.data[start - 1].is_ascii_digit() {
+                    start -= 1;
+                }
+                if start == end {
+                    break;
+                }
+                numbers_seen += 1;
+            }
+            if numbers_seen != 2 || (start > 0 && is_regular(self.data[start - 1])) {
+                continue;
+            }
+            let mut lexer = Lexer::new(&self.data, start);
+            if let Some(Token::Integer(number)) = lexer.next_token() {
+                // Later definitions win, as with incremental updates.
+                self.xref.insert(number as u32, XrefEntry::Offset(start));
+            }
+        }
+
+        // Use the last trailer dictionary, or failing that, find the catalog.
+        let mut search_end = self.data.len();
+        while let Some(trailer_pos) = rfind(&self.data[..search_end], b"trailer") {
+            let mut lexer = Lexer::new(&self.data, trailer_pos + 7);
+            if let Some(Object::Dictionary(trailer)) = lexer.parse_object() {
+                if trailer.contains_key("Root") {
+                    self.trailer = trailer;
+                    return;
+                }
+            }
+            search_end = trailer_pos;
+        }
+        let mut numbers: Vec<u32> = self.xref.keys().copied().collect();
+        numbers.sort_unstable();
+        for number in numbers {
+            let object = self.get(number);
+            if object
+                .as_dict()
+                .and_then(|dict| dict.get("Type"))
+                .and_then(Object::as_name)
+                == Some("Catalog")
+            {
+                self.trailer
+                    .insert("Root".to_string(), Object::Reference(number, 0));
+                return;
+            }
+        }
+    }
+
+    /// Parse "<number> <generation> obj ... endobj" at an offset, returning
+    /// the object number and the object.
+    fn parse_indirect_at(&self, offset: usize) -> Option<(u32, Object)> {
+        let mut lexer = Lexer::new(&self.data, offset);
+        let Some(Token::Integer(number)) = lexer.next_token() else {
+            return None;
+        };
+        let Some(Token::Integer(_generation)) = lexer.next_token() else {
+            return None;
+        };
+        if lexer.next_token() != Some(Token::Keyword("obj".to_string())) {
+            return None;
+        }
+        let object = lexer.parse_object()?;
+        let Object::Dictionary(dict) = object else {
+            return Some((number as u32, object));
+        };
+        if lexer.peek_token() != Some(Token::Keyword("stream".to_string())) {
+            return Some((number as u32, Object::Dictionary(dict)));
+        }
+        lexer.next_token();
+        // The keyword is followed by CRLF or LF.
+        let mut start = lexer.pos;
+        if self.data.get(start) == Some(&b'\r') {
+            start += 1;
+        }
+        if self.data.get(start) == Some(&b'\n') {
+            start += 1;
+        }
+
+        let length = dict
+            .get("Length")
+            .map(|length| self.resolve(length))
+            .and_then(|length| length.as_integer())
+            .and_then(|length| usize::try_from(length).ok());
+        let length_is_valid = length.is_some_and(|length| {
+            let Some(end) = start.checked_add(length) else {
+                return false;
+            };
+            end <= self.data.len() && {
+                let mut lexer = Lexer::new(&self.data, end);
+                lexer.next_token() == Some(Token::Keyword("endstream".to_string()))
+            }
+        });
+        let end = if length_is_valid {
+            start + length.unwrap()
+        } else {
+            let mut end = find(&self.data, b"endstream", start).unwrap_or(self.data.len());
+            // Drop the end-of-line marker before "endstream".
+            if end > start && self.data[end - 1] == b'\n' {
+                end -= 1;
+            }
+            if end > start && self.data[end - 1] == b'\r' {
+                end -= 1;
+            }
+            end
+        };
+        let stream = Stream {
+            dict,
+            data: self.data[start..end].to_vec(),
+        };
+        Some((number as u32, Object::Stream(Rc::new(stream))))
+    }
+
+    /// Get an indirect object by number. Missing objects are null.
+    pub fn get(&self, number: u32) -> Object {
+        if let Some(object) = self.cache.borrow().get(&number) {
+            return object.clone();
+        }
+        if self.depth.get() >= MAX_DEPTH {
+            log!("Warning: PDF objects nested too deeply, possible cycle");
+            return Object::Null;
+        }
+        self.depth.set(self.depth.get() + 1);
+        let object = match self.xref.get(&number) {
+            Some(&XrefEntry::Offset(offset)) => match self.parse_indirect_at(offset) {
+                Some((found_number, object)) if found_number == number => object,
+                _ => Object::Null,
+            },
+            Some(&XrefEntry::Compressed { stream, index }) => {
+                self.load_object_stream(stream);
+                let cache = self.cache.borrow();
+                match cache.get(&number) {
+                    Some(object) => object.clone(),
+                    None => {
+                        log_dbg!("PDF object {} not at index {} of {}", number, index, stream);
+                        Object::Null
+                    }
+                }
+            }
+            None => Object::Null,
+        };
+        self.depth.set(self.depth.get() - 1);
+        self.cache.borrow_mut().insert(number, object.clone());
+        object
+    }
+
+    /// Parse every object in an object stream into the cache.
+    fn load_object_stream(&self, number: u32) {
+        let Object::Stream(stream) = self.get(number) else {
+            return;
+        };
+        let Ok(data) = self.decode_stream(&stream) else {
+            return;
+        };
+        let count = stream.dict.get("N").and_then(Object::as_integer);
+        let first = stream.dict.get("First").and_then(Object::as_integer);
+        let (Some(count), Some(first)) = (count, first) else {
+            return;
+        };
+        let mut header = Lexer::new(&data, 0);
+        let mut cache = self.cache.borrow_mut();
+        for _ in 0..count {
+            let (Some(Token::Integer(object_number)), Some(Token::Integer(offset))) =
+                (header.next_token(), header.next_token())
+            else {
+                break;
+            };
+            let mut lexer = Lexer::new(&data, (first + offset) as usize);
+            if let Some(object) = lexer.parse_object() {
+                cache.entry(object_number as u32).or_insert(object);
+            }
+        }
+    }
+
+    /// Follow a reference, if the object is one.
+    pub fn resolve(&self, object: &Object) -> Object {
+        match *object {
+            Object::Reference(number, _) => self.get(number),
+            _ => object.clone(),
+        }
+    }
+
+    /// Look up a key in a dictionary and follow it if it's a reference.
+    pub fn lookup(&self, dict: &Dictionary, key: &str) -> Object {
+        dict.get(key)
+            .map_or(Object::Null, |object| self.resolve(object))
+    }
+
+    pub fn catalog(&self) -> Dictionary {
+        match self.lookup(&self.trailer, "Root") {
+            Object::Dictionary(dict) => dict,
+            _ => Dictionary::new(),
+        }
+    }
+
+    /// Get the page objects in order. Inheritable attributes are copied from
+    /// the page tree nodes into each page.
+    pub fn pages(&self) -> Vec<Dictionary> {
+        let mut pages = Vec::new();
+        let mut visited = HashSet::new();
+        let root = self.catalog().get("Pages").cloned().unwrap_or(Object::Null);
+        self.collect_pages(&root, &Dictionary::new(), &mut visited, &mut pages);
+        pages
+    }
+
+    fn collect_pages(
+        &self,
+        node: &Object,
+        inherited: &Dictionary,
+        visited: &mut HashSet<u32>,
+        pages: &mut Vec<Dictionary>,
+    ) {
+        if let Object::Reference(number, _) = *node {
+            if !visited.insert(number) {
+                return;
+            }
+        }
+        let Object::Dictionary(mut dict) = self.resolve(node) else {
+            return;
+        };
+        for key in ["Resources", "MediaBox", "CropBox", "Rotate"] {
+            if !dict.contains_key(key) {
+                if let Some(value) = inherited.get(key) {
+                    dict.insert(key.to_string(), value.clone());
+                }
+            }
+        }
+        let is_tree_node = match dict.get("Type").and_then(Object::as_name) {
+            Some(kind) => kind == "Pages",
+            None => dict.contains_key("Kids"),
+        };
+        if is_tree_node {
+            let kids = self.lookup(&dict, "Kids");
+            for kid in kids.as_array().unwrap_or(&[]) {
+                self.collect_pages(kid, &dict, visited, pages);
+            }
+        } else {
+            pages.push(dict);
+        }
+    }
+
+    /// Apply a stream's filters, stopping at image compression filters
+    /// (e.g. `DCTDecode`), whose name is returned along with the data.
+    pub fn decode_stream_for_image(
+        &self,
+        stream: &Stream,
+    ) -> Result<(Vec<u8>, Option<String>), String> {
+        let filters = match self.lookup(&stream.dict, "Filter") {
+            Object::Name(name) => vec![name],
+            Object::Array(names) => names
+                .iter()
+                .filter_map(|name| self.resolve(name).as_name().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let params = self.lookup(&stream.dict, "DecodeParms");
+        let mut data = stream.data.clone();
+        for (i, filter) in filters.iter().enumerate() {
+            let params = match params {
+                Object::Array(ref params) => params.get(i).map(|p| self.resolve(p)),
+                ref params => Some(params.clone()),
+            };
+            let params = params.as_ref().and_then(Object::as_dict);
+            data = match filter.as_str() {
+                "FlateDecode" | "Fl" => {
+                    let inflated = image::inflate(&data, true)
+                        .or_else(|_| image::inflate(&data, false))
+                        .map_err(|_| "Bad FlateDecode data".to_string())?;
+                    apply_predictor(inflated, params)?
+                }
+                "LZWDecode" | "LZW" => {
+                    let early_change = params
+                        .and_then(|p| p.get("EarlyChange"))
+                        .and_then(Object::as_integer)
+                        .unwrap_or(1);
+                    apply_predictor(lzw_decode(&data, early_change != 0), params)?
+                }
+                "ASCIIHexDecode" | "AHx" => ascii_hex_decode(&data),
+                "ASCII85Decode" | "A85" => ascii85_decode(&data),
+                "RunLengthDecode" | "RL" => run_length_decode(&data),
+                "DCTDecode" | "DCT" | "JPXDecode" | "CCITTFaxDecode" | "CCF" | "JBIG2Decode" => {
+                    return Ok((data, Some(filter.clone())));
+                }
+                _ => return Err(format!("Unsupported filter {}", filter)),
+            };
+        }
+        Ok((data, None))
+    }
+
+    /// Apply all of a stream's filters.
+    pub fn decode_stream(&self, stream: &Stream) -> Result<Vec<u8>, String> {
+        match self.decode_stream_for_image(stream)? {
+            (data, None) => Ok(data),
+            (_, Some(filter)) => Err(format!("Unexpected image filter {}", filter)),
+        }
+    }
+}
+
+/// Undo the PNG or TIFF predictor used by `FlateDecode` and `LZWDecode`.
+fn apply_predictor(data: Vec<u8>, params: Option<&Dictionary>) -> Result<Vec<u8>, String> {
+    let get = |key: &str, default: i64| {
+        params
+            .and_then(|p| p.get(key))
+            .and_then(Object::as_integer)
+            .unwrap_or(default)
+    };
+    let predictor = get("Predictor", 1);
+    if predictor == 1 {
+        return Ok(data);
+    }
+    let colors = get("Colors", 1).max(1) as usize;
+    let bits = get("BitsPerComponent", 8).max(1) as usize;
+    let columns = get("Columns", 1).max(1) as usize;
+    let bytes_per_pixel = (colors * bits).div_ceil(8);
+    let row_size = (colors * bits * columns).div_ceil(8);
+
+    if predictor == 2 {
+        // TIFF horizontal differencing, only for 8-bit components.
+        if bits != 8 {
+            return Err("Unsupported TIFF predictor bit depth".to_string());
+        }
+        let mut data = data;
+        for row in data.chunks_mut(row_size) {
+            for i in bytes_per_pixel..row.len() {
+                row[i] = row[i].wrapping_add(row[i - bytes_per_pixel]);
+            }
+        }
+        return Ok(data);
+    }
+
+    // PNG predictors: each row starts with a filter type byte.
+    let mut output = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_size];
+    for chunk in data.chunks(row_size + 1) {
+        let (&filter, row) = chunk.split_first().unwrap();
+        let mut current = row.to_vec();
+        current.resize(row_size, 0);
+        for i in 0..row_size {
+            let left = if i >= bytes_per_pixel {
+                current[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let up = previous[i];
+            let up_left = if i >= bytes_per_pixel {
+                previous[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            current[i] = current[i].wrapping_add(match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(format!("Bad PNG filter type {}", filter)),
+            });
+        }
+        output.extend_from_slice(&current[..row.len().min(row_size)]);
+        previous = current;
+    }
+    Ok(output)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn ascii_hex_decode(data: &[u8]) -> Vec<u8> {
+    let mut lexer_input = data.to_vec();
+    if !lexer_input.contains(&b'>') {
+        lexer_input.push(b'>');
+    }
+    Lexer::new(&lexer_input, 0).hex_string()
+}
+
+fn ascii85_decode(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut group = [0u8; 5];
+    let mut count = 0;
+    let data = data.strip_prefix(b"<~").unwrap_or(data);
+    for &b in data {
+        match b {
+            b'~' => break,
+            b'z' if count == 0 => output.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group[count] = b - b'!';
+                count += 1;
+                if count == 5 {
+                    let value = group
+                        .iter()
+                        .fold(0u32, |acc, &d| acc.wrapping_mul(85).wrapping_add(d as u32));
+                    output.extend_from_slice(&value.to_be_bytes());
+                    count = 0;
+                }
+            }
+            _ => (),
+        }
+    }
+    if count > 1 {
+        // Pad a partial final group with the highest digit.
+        for digit in group.iter_mut().skip(count) {
+            *digit = 84;
+        }
+        let value = group
+            .iter()
+            .fold(0u32, |acc, &d| acc.wrapping_mul(85).wrapping_add(d as u32));
+        output.extend_from_slice(&value.to_be_bytes()[..count - 1]);
+    }
+    output
+}
+
+fn run_length_decode(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let length = data[i];
+        i += 1;
+        match length {
+            128 => break,
+            0..=127 => {
+                let end = (i + length as usize + 1).min(data.len());
+                output.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+            _ => {
+                if let Some(&b) = data.get(i) {
+                    output.extend(std::iter::repeat(b).take(257 - length as usize));
+                }
+                i += 1;
+            }
+        }
+    }
+    output
+}
+
+fn lzw_decode(data: &[u8], early_change: bool) -> Vec<u8> {
+    const CLEAR: usize = 256;
+    const END: usize = 257;
+    let mut output = Vec::new();
+    let mut table: Vec<Vec<u8>> = Vec::new();
+    let reset = |table: &mut Vec<Vec<u8>>| {
+        table.clear();
+        table.extend((0..=255u8).map(|b| vec![b]));
+        table.push(Vec::new()); // clear
+        table.push(Vec::new()); // end
+    };
+    reset(&mut table);
+    let mut code_length = 9;
+    let mut previous: Option<Vec<u8>> = None;
+    let (mut buffer, mut buffered_bits) = (0u32, 0);
+    for &b in data {
+        buffer = buffer << 8 | b as u32;
+        buffered_bits += 8;
+        while buffered_bits >= code_length {
+            let code =
+                ((buffer >> (buffered_bits - code_length)) & ((1 << code_length) - 1)) as usize;
+            buffered_bits -= code_length;
+            match code {
+                CLEAR => {
+                    reset(&mut table);
+                    code_length = 9;
+                    previous = None;
+                    continue;
+                }
+                END => return output,
+                _ => (),
+            }
+            let entry = if code < table.len() {
+                table[code].clone()
+            } else if let Some(ref previous) = previous {
+                // The code being defined right now.
+                let mut entry = previous.clone();
+                entry.push(previous[0]);
+                entry
+            } else {
+                return output;
+            };
+            output.extend_from_slice(&entry);
+            if let Some(previous) = previous {
+                let mut new_entry = previous;
+                new_entry.push(entry[0]);
+                table.push(new_entry);
+            }
+            previous = Some(entry);
+            let limit = table.len() + early_change as usize;
+            code_length = match limit {
+                0..=511 => 9,
+                512..=1023 => 10,
+                1024..=2047 => 11,
+                _ => 12,
+            };
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lexing() {
+        let mut lexer = Lexer::new(
+            b"<< /Type /Pa#67e /N [1 -2.5 (a\\(b\\)c) <414>] /R 3 0 R >>",
+            0,
+        );
+        let Some(Object::Dictionary(dict)) = lexer.parse_object() else {
+            panic!();
+        };
+        assert_eq!(dict["Type"], Object::Name("Page".to_string()));
+        assert_eq!(
+            dict["N"],
+            Object::Array(vec![
+                Object::Integer(1),
+                Object::Real(-2.5),
+                Object::String(b"a(b)c".to_vec()),
+                Object::String(b"A@".to_vec()),
+            ])
+        );
+        assert_eq!(dict["R"], Object::Reference(3, 0));
+    }
+
+    #[test]
+    fn content_stream() {
+        let operations = parse_content(b"q 1 0 0 1 0 0 cm BI /W 1 /H 1 ID \x00 EI Q");
+        let operators: Vec<&str> = operations.iter().map(|o| o.operator.as_str()).collect();
+        assert_eq!(operators, ["q", "cm", "BI", "Q"]);
+        assert_eq!(operations[1].operands.len(), 6);
+        assert_eq!(operations[2].operands[1], Object::String(vec![0]));
+    }
+
+    fn make_pdf(with_valid_xref: bool) -> Vec<u8> {
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 /MediaBox [0 0 200 100] >>",
+            "<< /Type /Page /Parent 2 0 R /Contents 4 0 R >>",
+        ];
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+        }
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(b"4 0 obj\n<< /Length 5 0 R >>\nstream\n0 0 m\nendstream\nendobj\n");
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(b"5 0 obj\n5\nendobj\n");
+        let xref_offset = pdf.len();
+        pdf.extend_from_slice(b"xref\n0 6\n0000000000 65535 f \n");
+        for offset in offsets {
+            let offset = if with_valid_xref { offset } else { offset + 3 };
+            pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        pdf.extend_from_slice(b"trailer\n<< /Size 6 /Root 1 0 R >>\n");
+        pdf.extend_from_slice(format!("startxref\n{}\n%%EOF\n", xref_offset).as_bytes());
+        pdf
+    }
+
+    #[test]
+    fn document_structure() {
+        for valid in [true, false] {
+            let document = Document::parse(make_pdf(valid)).unwrap();
+            let pages = document.pages();
+            assert_eq!(pages.len(), 1);
+            // Inherited from the page tree node.
+            assert!(pages[0].contains_key("MediaBox"));
+            let contents = document.lookup(&pages[0], "Contents");
+            let stream = contents.as_stream().unwrap();
+            assert_eq!(document.decode_stream(stream).unwrap(), b"0 0 m");
+        }
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(ascii_hex_decode(b"48 65 6C6C 6F>"), b"Hello");
+        assert_eq!(ascii85_decode(b"<~87cURDZ~>"), b"Hello");
+        assert_eq!(
+            run_length_decode(&[2, b'a', b'b', b'c', 254, b'x', 128]),
+            b"abcxxx"
+        );
+        // PNG "Up" predictor, 2 columns.
+        let params = Dictionary::from([
+            ("Predictor".to_string(), Object::Integer(12)),
+            ("Columns".to_string(), Object::Integer(2)),
+        ]);
+        let data = vec![0, 1, 2, 2, 1, 1];
+        assert_eq!(apply_predictor(data, Some(&params)).unwrap(), [1, 2, 2, 3]);
+    }
+}
@@ -0,0 +1,1202 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Drawing PDF content streams by replaying them as CGContext calls.
+//!
+//! Only the parts of the PDF graphics state that CGContext doesn't have
+//! (colors in PDF color spaces, separate fill and stroke alpha, text state)
+//! are tracked here; the rest is saved and restored with the context's own
+//! graphics state.
+
+use super::color::{read_samples, ColorSpace, Function};
+use super::fonts::{FallbackFonts, PdfFont};
+use super::parser::{self, Dictionary, Document, Object, Operation, Stream};
+use super::{matrix_from_object, rect_from_object};
+use crate::font::OutlineSegment;
+use crate::frameworks::core_graphics::cg_affine_transform::{
+    CGAffineTransform, CGAffineTransformIdentity,
+};
+use crate::frameworks::core_graphics::cg_context::{
+    self, kCGBlendModeColor, kCGBlendModeColorBurn, kCGBlendModeColorDodge, kCGBlendModeDarken,
+    kCGBlendModeDifference, kCGBlendModeExclusion, kCGBlendModeHardLight, kCGBlendModeHue,
+    kCGBlendModeLighten, kCGBlendModeLuminosity, kCGBlendModeMultiply, kCGBlendModeNormal,
+    kCGBlendModeOverlay, kCGBlendModeSaturation, kCGBlendModeScreen, kCGBlendModeSoftLight,
+    kCGPathEOFill, kCGPathFill, kCGPathFillStroke, kCGPathStroke, CGBlendMode,
+    CGContextAddCurveToPoint, CGContextAddLineToPoint, CGContextAddPath,
+    CGContextAddQuadCurveToPoint, CGContextAddRect, CGContextBeginPath, CGContextClip,
+    CGContextClipToRect, CGContextClosePath, CGContextConcatCTM, CGContextCopyPath,
+    CGContextDrawImage, CGContextDrawPath, CGContextEOClip, CGContextGetCTM,
+    CGContextGetPathCurrentPoint, CGContextMoveToPoint, CGContextRef, CGContextRestoreGState,
+    CGContextSaveGState, CGContextSetBlendMode, CGContextSetLineCap, CGContextSetLineJoin,
+    CGContextSetLineWidth, CGContextSetMiterLimit, CGContextSetRGBFillColor,
+    CGContextSetRGBStrokeColor,
+};
+use crate::frameworks::core_graphics::cg_function::SAMPLE_COUNT;
+use crate::frameworks::core_graphics::cg_image::{self, CGImageRelease};
+use crate::frameworks::core_graphics::cg_path::{self, CGPathRef, CGPathRelease};
+use crate::frameworks::core_graphics::cg_shading::{self, Geometry};
+use crate::frameworks::core_graphics::rasterizer::FillRule;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::image::Image;
+use crate::Environment;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Limit on nested form XObjects and patterns, guarding against cycles.
+const MAX_NESTING: u32 = 12;
+/// Limit on `q` without matching `Q`.
+const MAX_SAVED_STATES: usize = 64;
+/// Largest image drawn, to stop a corrupt file from exhausting memory.
+const MAX_IMAGE_PIXELS: u64 = 4096 * 4096;
+/// Largest number of pattern cells drawn to fill one area.
+const MAX_PATTERN_CELLS: i64 = 4096;
+
+#[derive(Clone)]
+struct Paint {
+    space: ColorSpace,
+    color: Vec<CGFloat>,
+    /// Name of the pattern resource, if `space` is a pattern color space.
+    pattern: Option<String>,
+    alpha: CGFloat,
+}
+impl Default for Paint {
+    fn default() -> Self {
+        Paint {
+            space: ColorSpace::Gray,
+            color: vec![0.0],
+            pattern: None,
+            alpha: 1.0,
+        }
+    }
+}
+impl Paint {
+    fn rgba(&self) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+        let (r, g, b) = self.space.to_rgb(&self.color);
+        (r, g, b, self.alpha)
+    }
+    fn set_device(&mut self, space: ColorSpace, color: &[CGFloat]) {
+        self.space = space;
+        self.color = color.to_vec();
+        self.pattern = None;
+    }
+}
+
+#[derive(Clone)]
+struct TextState {
+    char_spacing: CGFloat,
+    word_spacing: CGFloat,
+    /// Ratio, not percentage.
+    horizontal_scaling: CGFloat,
+    leading: CGFloat,
+    font: Option<Rc<PdfFont>>,
+    size: CGFloat,
+    render_mode: i64,
+    rise: CGFloat,
+}
+impl Default for TextState {
+    fn default() -> Self {
+        TextState {
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            font: None,
+            size: 0.0,
+            render_mode: 0,
+            rise: 0.0,
+        }
+    }
+}
+
+/// The parts of the PDF graphics state which the context doesn't track.
+#[derive(Clone, Default)]
+struct State {
+    fill: Paint,
+    stroke: Paint,
+    text: TextState,
+}
+
+struct Renderer<'a> {
+    document: &'a Document,
+    context: CGContextRef,
+    /// Fonts by object number.
+    fonts: HashMap<u32, Rc<PdfFont>>,
+    fallback_fonts: FallbackFonts,
+    /// The CTM for the page's default user space, which patterns are
+    /// positioned relative to.
+    base_ctm: CGAffineTransform,
+    nesting: u32,
+}
+
+/// Draw a page's contents in the context's current user space.
+pub fn draw_page(
+    env: &mut Environment,
+    context: CGContextRef,
+    document: &Document,
+    page: &Dictionary,
+) {
+    let resources = match document.lookup(page, "Resources") {
+        Object::Dictionary(resources) => resources,
+        _ => Dictionary::new(),
+    };
+    let streams = match document.lookup(page, "Contents") {
+        Object::Array(streams) => streams.iter().map(|s| document.resolve(s)).collect(),
+        stream => vec![stream],
+    };
+    // Content streams can be split at any token boundary, so they must be
+    // joined before parsing.
+    let mut data = Vec::new();
+    for stream in streams {
+        let Object::Stream(stream) = stream else {
+            continue;
+        };
+        match document.decode_stream(&stream) {
+            Ok(decoded) => data.extend_from_slice(&decoded),
+            Err(e) => log!("Warning: couldn't decode PDF content stream: {}", e),
+        }
+        data.push(b'\n');
+    }
+    let operations = parser::parse_content(&data);
+
+    let mut renderer = Renderer {
+        document,
+        context,
+        fonts: HashMap::new(),
+        fallback_fonts: FallbackFonts::default(),
+        base_ctm: CGContextGetCTM(env, context),
+        nesting: 0,
+    };
+    renderer.run(env, &operations, &resources, State::default());
+}
+
+fn translation(tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
+    CGAffineTransform {
+        tx,
+        ty,
+        ..CGAffineTransformIdentity
+    }
+}
+
+fn blend_mode_for_name(name: &str) -> CGBlendMode {
+    match name {
+        "Multiply" => kCGBlendModeMultiply,
+        "Screen" => kCGBlendModeScreen,
+        "Overlay" => kCGBlendModeOverlay,
+        "Darken" => kCGBlendModeDarken,
+        "Lighten" => kCGBlendModeLighten,
+        "ColorDodge" => kCGBlendModeColorDodge,
+        "ColorBurn" => kCGBlendModeColorBurn,
+        "HardLight" => kCGBlendModeHardLight,
+        "SoftLight" => kCGBlendModeSoftLight,
+        "Difference" => kCGBlendModeDifference,
+        "Exclusion" => kCGBlendModeExclusion,
+        "Hue" => kCGBlendModeHue,
+        "Saturation" => kCGBlendModeSaturation,
+        "Color" => kCGBlendModeColor,
+        "Luminosity" => kCGBlendModeLuminosity,
+        _ => kCGBlendModeNormal,
+    }
+}
+
+/// Expand the abbreviated keys used in inline image dictionaries.
+fn expand_inline_image_dict(dict: &Dictionary) -> Dictionary {
+    dict.iter()
+        .map(|(key, value)| {
+            let key = match key.as_str() {
+                "BPC" => "BitsPerComponent",
+                "CS" => "ColorSpace",
+                "D" => "Decode",
+                "DP" => "DecodeParms",
+                "F" => "Filter",
+                "H" => "Height",
+                "IM" => "ImageMask",
+                "I" => "Interpolate",
+                "W" => "Width",
+                key => key,
+            };
+            (key.to_string(), value.clone())
+        })
+        .collect()
+}
+
+/// Nearest-neighbor resampling of one channel of an RGBA image.
+fn resample_channel(
+    pixels: &[u8],
+    (width, height): (u32, u32),
+    channel: usize,
+    (new_width, new_height): (u32, u32),
+) -> Vec<u8> {
+    let mut output = Vec::with_capacity((new_width * new_height) as usize);
+    for y in 0..new_height {
+        let src_y = (y as u64 * height as u64 / new_height as u64) as usize;
+        for x in 0..new_width {
+            let src_x = (x as u64 * width as u64 / new_width as u64) as usize;
+            output.push(pixels[(src_y * width as usize + src_x) * 4 + channel]);
+        }
+    }
+    output
+}
+
+impl Renderer<'_> {
+    fn resource(&self, resources: &Dictionary, category: &str, name: &str) -> Object {
+        match self.document.lookup(resources, category) {
+            Object::Dictionary(dict) => self.document.lookup(&dict, name),
+            _ => Object::Null,
+        }
+    }
+
+    fn set_colors(&self, env: &mut Environment, state: &State) {
+        let (r, g, b, a) = state.fill.rgba();
+        CGContextSetRGBFillColor(env, self.context, r, g, b, a);
+        let (r, g, b, a) = state.stroke.rgba();
+        CGContextSetRGBStrokeColor(env, self.context, r, g, b, a);
+    }
+
+    fn run(
+        &mut self,
+        env: &mut Environment,
+        operations: &[Operation],
+        resources: &Dictionary,
+        mut state: State,
+    ) {
+        let context = self.context;
+        let mut saved_states: Vec<State> = Vec::new();
+        let mut text_matrix = CGAffineTransformIdentity;
+        let mut line_matrix = CGAffineTransformIdentity;
+        let mut pending_clip: Option<FillRule> = None;
+
+        for operation in operations {
+            let operands = &operation.operands;
+            let numbers: Vec<CGFloat> = operands.iter().filter_map(Object::as_number).collect();
+            let name = operands.first().and_then(Object::as_name);
+            match (operation.operator.as_str(), &numbers[..]) {
+                // Graphics state
+                ("q", _) => {
+                    if saved_states.len() < MAX_SAVED_STATES {
+                        saved_states.push(state.clone());
+                        CGContextSaveGState(env, context);
+                    }
+                }
+                ("Q", _) => {
+                    if let Some(saved) = saved_states.pop() {
+                        state = saved;
+                        CGContextRestoreGState(env, context);
+                    }
+                }
+                ("cm", &[a, b, c, d, tx, ty]) => {
+                    CGContextConcatCTM(env, context, CGAffineTransform { a, b, c, d, tx, ty })
+                }
+                ("w", &[width]) => self.set_line_width(env, width),
+                ("J", &[cap]) => CGContextSetLineCap(env, context, cap as i32),
+                ("j", &[join]) => CGContextSetLineJoin(env, context, join as i32),
+                ("M", &[limit]) => CGContextSetMiterLimit(env, context, limit),
+                ("d", _) => self.set_line_dash(env, operands.first(), operands.get(1)),
+                ("gs", _) => {
+                    if let Some(name) = name {
+                        self.apply_ext_gstate(env, &mut state, resources, name);
+                    }
+                }
+                // Rendering intent and flatness don't matter here.
+                ("ri" | "i", _) => (),
+
+                // Path construction
+                ("m", &[x, y]) => CGContextMoveToPoint(env, context, x, y),
+                ("l", &[x, y]) => CGContextAddLineToPoint(env, context, x, y),
+                ("c", &[x1, y1, x2, y2, x3, y3]) => {
+                    CGContextAddCurveToPoint(env, context, x1, y1, x2, y2, x3, y3)
+                }
+                ("v", &[x2, y2, x3, y3]) => {
+                    let CGPoint { x: x1, y: y1 } = CGContextGetPathCurrentPoint(env, context);
+                    CGContextAddCurveToPoint(env, context, x1, y1, x2, y2, x3, y3)
+                }
+                ("y", &[x1, y1, x3, y3]) => {
+                    CGContextAddCurveToPoint(env, context, x1, y1, x3, y3, x3, y3)
+                }
+                ("h", _) => CGContextClosePath(env, context),
+                ("re", &[x, y, width, height]) => {
+                    let rect = CGRect {
+                        origin: CGPoint { x, y },
+                        size: CGSize { width, height },
+                    };
+                    CGContextAddRect(env, context, rect)
+                }
+
+                // Path painting
+                ("S", _) => {
+                    self.paint_path(env, &state, resources, None, true, pending_clip.take())
+                }
+                ("s", _) => {
+                    CGContextClosePath(env, context);
+                    self.paint_path(env, &state, resources, None, true, pending_clip.take())
+                }
+                ("f" | "F", _) => self.paint_path(
+                    env,
+                    &state,
+                    resources,
+                    Some(FillRule::Winding),
+                    false,
+                    pending_clip.take(),
+                ),
+                ("f*", _) => self.paint_path(
+                    env,
+                    &state,
+                    resources,
+                    Some(FillRule::EvenOdd),
+                    false,
+                    pending_clip.take(),
+                ),
+                ("B" | "b" | "B*" | "b*", _) => {
+                    if operation.operator.starts_with('b') {
+                        CGContextClosePath(env, context);
+                    }
+                    let rule = if operation.operator.ends_with('*') {
+                        FillRule::EvenOdd
+                    } else {
+                        FillRule::Winding
+                    };
+                    self.paint_path(
+                        env,
+                        &state,
+                        resources,
+                        Some(rule),
+                        true,
+                        pending_clip.take(),
+                    )
+                }
+                ("n", _) => {
+                    self.paint_path(env, &state, resources, None, false, pending_clip.take())
+                }
+                ("W", _) => pending_clip = Some(FillRule::Winding),
+                ("W*", _) => pending_clip = Some(FillRule::EvenOdd),
+
+                // Color
+                ("CS" | "cs", _) => {
+                    let Some(space) = operands.first() else {
+                        continue;
+                    };
+                    let space = ColorSpace::parse(self.document, resources, space);
+                    let paint = if operation.operator == "CS" {
+                        &mut state.stroke
+                    } else {
+                        &mut state.fill
+                    };
+                    paint.color = space.initial_color();
+                    paint.space = space;
+                    paint.pattern = None;
+                }
+                ("SC" | "SCN" | "sc" | "scn", _) => {
+                    let paint = if operation.operator.starts_with('S') {
+                        &mut state.stroke
+                    } else {
+                        &mut state.fill
+                    };
+                    paint.pattern = operands
+                        .last()
+                        .and_then(Object::as_name)
+                        .map(str::to_string);
+                    if paint.pattern.is_none() || !numbers.is_empty() {
+                        paint.color = numbers.clone();
+                    }
+                }
+                ("G", &[gray]) => state.stroke.set_device(ColorSpace::Gray, &[gray]),
+                ("g", &[gray]) => state.fill.set_device(ColorSpace::Gray, &[gray]),
+                ("RG", &[r, g, b]) => state.stroke.set_device(ColorSpace::Rgb, &[r, g, b]),
+                ("rg", &[r, g, b]) => state.fill.set_device(ColorSpace::Rgb, &[r, g, b]),
+                ("K", &[c, m, y, k]) => state.stroke.set_device(ColorSpace::Cmyk, &[c, m, y, k]),
+                ("k", &[c, m, y, k]) => state.fill.set_device(ColorSpace::Cmyk, &[c, m, y, k]),
+
+                // Shadings, images and forms
+                ("sh", _) => {
+                    if let Some(name) = name {
+                        let shading = self.resource(resources, "Shading", name);
+                        self.draw_shading(env, resources, &shading, state.fill.alpha);
+                    }
+                }
+                ("Do", _) => {
+                    if let Some(name) = name {
+                        self.draw_xobject(env, &state, resources, name);
+                    }
+                }
+                ("BI", _) => {
+                    if let [Object::Dictionary(dict), Object::String(data)] = &operands[..] {
+                        let stream = Stream {
+                            dict: expand_inline_image_dict(dict),
+                            data: data.clone(),
+                        };
+                        self.draw_image(env, &state, resources, &stream);
+                    }
+                }
+
+                // Text
+                ("BT", _) => {
+                    text_matrix = CGAffineTransformIdentity;
+                    line_matrix = CGAffineTransformIdentity;
+                }
+                ("ET", _) => (),
+                ("Tc", &[spacing]) => state.text.char_spacing = spacing,
+                ("Tw", &[spacing]) => state.text.word_spacing = spacing,
+                ("Tz", &[scale]) => state.text.horizontal_scaling = scale / 100.0,
+                ("TL", &[leading]) => state.text.leading = leading,
+                ("Ts", &[rise]) => state.text.rise = rise,
+                ("Tr", &[mode]) => state.text.render_mode = mode as i64,
+                ("Tf", &[size]) => {
+                    state.text.size = size;
+                    if let Some(name) = name {
+                        state.text.font = self.load_font(resources, name);
+                    }
+                }
+                ("Td" | "TD", &[tx, ty]) => {
+                    if operation.operator == "TD" {
+                        state.text.leading = -ty;
+                    }
+                    line_matrix = translation(tx, ty).concat(line_matrix);
+                    text_matrix = line_matrix;
+                }
+                ("Tm", &[a, b, c, d, tx, ty]) => {
+                    line_matrix = CGAffineTransform { a, b, c, d, tx, ty };
+                    text_matrix = line_matrix;
+                }
+                ("T*", _) => {
+                    line_matrix = translation(0.0, -state.text.leading).concat(line_matrix);
+                    text_matrix = line_matrix;
+                }
+                ("Tj" | "'" | "\"", _) => {
+                    if operation.operator == "\"" {
+                        if let &[word_spacing, char_spacing] = &numbers[..] {
+                            state.text.word_spacing = word_spacing;
+                            state.text.char_spacing = char_spacing;
+                        }
+                    }
+                    if operation.operator != "Tj" {
+                        line_matrix = translation(0.0, -state.text.leading).concat(line_matrix);
+                        text_matrix = line_matrix;
+                    }
+                    if let Some(string) = operands.last().and_then(Object::as_string) {
+                        self.show_text(env, &state, &mut text_matrix, string);
+                    }
+                }
+                ("TJ", _) => {
+                    let Some(items) = operands.first().and_then(Object::as_array) else {
+                        continue;
+                    };
+                    for item in items {
+                        match item {
+                            Object::String(string) => {
+                                self.show_text(env, &state, &mut text_matrix, string)
+                            }
+                            item => {
+                                let Some(adjustment) = item.as_number() else {
+                                    continue;
+                                };
+                                let tx = -adjustment / 1000.0
+                                    * state.text.size
+                                    * state.text.horizontal_scaling;
+                                text_matrix = translation(tx, 0.0).concat(text_matrix);
+                            }
+                        }
+                    }
+                }
+
+                // Type 3 glyph metrics, marked content and compatibility
+                // sections
+                ("d0" | "d1" | "BMC" | "BDC" | "EMC" | "MP" | "DP" | "BX" | "EX", _) => (),
+
+                (operator, _) => log_dbg!(
+                    "Unknown or malformed PDF operator {:?} with {} operands",
+                    operator,
+                    operands.len()
+                ),
+            }
+        }
+
+        for _ in saved_states {
+            CGContextRestoreGState(env, context);
+        }
+    }
+
+    fn set_line_width(&self, env: &mut Environment, width: CGFloat) {
+        let width = if width == 0.0 {
+            // Zero means the thinnest line the device can draw.
+            let ctm = CGContextGetCTM(env, self.context);
+            1.0 / (ctm.a * ctm.d - ctm.b * ctm.c).abs().sqrt().max(1e-6)
+        } else {
+            width
+        };
+        CGContextSetLineWidth(env, self.context, width);
+    }
+
+    fn set_line_dash(
+        &self,
+        env: &mut Environment,
+        lengths: Option<&Object>,
+        phase: Option<&Object>,
+    ) {
+        let lengths: Vec<CGFloat> = lengths
+            .and_then(Object::as_array)
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|length| self.document.resolve(length).as_number())
+            .collect();
+        let phase = phase.and_then(Object::as_number).unwrap_or(0.0);
+        // A pattern with no dashes or only zero-length dashes is solid.
+        let dash = if lengths.iter().any(|&length| length > 0.0) {
+            Some((phase, lengths))
+        } else {
+            None
+        };
+        cg_context::set_line_dash(env, self.context, dash);
+    }
+
+    fn apply_ext_gstate(
+        &mut self,
+        env: &mut Environment,
+        state: &mut State,
+        resources: &Dictionary,
+        name: &str,
+    ) {
+        let Object::Dictionary(dict) = self.resource(resources, "ExtGState", name) else {
+            log!("Warning: missing PDF ExtGState {:?}", name);
+            return;
+        };
+        for (key, value) in dict.iter() {
+            let value = self.document.resolve(value);
+            match (key.as_str(), value.as_number()) {
+                ("LW", Some(width)) => self.set_line_width(env, width),
+                ("LC", Some(cap)) => CGContextSetLineCap(env, self.context, cap as i32),
+                ("LJ", Some(join)) => CGContextSetLineJoin(env, self.context, join as i32),
+                ("ML", Some(limit)) => CGContextSetMiterLimit(env, self.context, limit),
+                ("CA", Some(alpha)) => state.stroke.alpha = alpha.clamp(0.0, 1.0),
+                ("ca", Some(alpha)) => state.fill.alpha = alpha.clamp(0.0, 1.0),
+                ("D", _) => {
+                    let dash = value.as_array().unwrap_or(&[]);
+                    self.set_line_dash(env, dash.first(), dash.get(1));
+                }
+                ("BM", _) => {
+                    // Arrays list blend modes in order of preference.
+                    let mode = match value {
+                        Object::Array(ref modes) => modes.first().and_then(Object::as_name),
+                        ref mode => mode.as_name(),
+                    };
+                    let mode = blend_mode_for_name(mode.unwrap_or("Normal"));
+                    CGContextSetBlendMode(env, self.context, mode);
+                }
+                ("Font", _) => {
+                    let font = value.as_array().unwrap_or(&[]);
+                    if let (Some(Object::Reference(number, _)), Some(size)) =
+                        (font.first(), font.get(1).and_then(Object::as_number))
+                    {
+                        state.text.font = self.load_font_object(*number);
+                        state.text.size = size;
+                    }
+                }
+                ("SMask", _) => {
+                    if value.as_name() != Some("None") {
+                        log_dbg!("TODO: PDF soft masks are not supported");
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn load_font(&mut self, resources: &Dictionary, name: &str) -> Option<Rc<PdfFont>> {
+        let fonts = self.document.lookup(resources, "Font");
+        match fonts.as_dict().and_then(|fonts| fonts.get(name)) {
+            Some(&Object::Reference(number, _)) => self.load_font_object(number),
+            Some(Object::Dictionary(dict)) => Some(Rc::new(PdfFont::load(
+                self.document,
+                dict,
+                &mut self.fallback_fonts,
+            ))),
+            _ => {
+                log!("Warning: missing PDF font {:?}", name);
+                None
+            }
+        }
+    }
+
+    fn load_font_object(&mut self, number: u32) -> Option<Rc<PdfFont>> {
+        if let Some(font) = self.fonts.get(&number) {
+            return Some(font.clone());
+        }
+        let Object::Dictionary(dict) = self.document.get(number) else {
+            return None;
+        };
+        let font = Rc::new(PdfFont::load(
+            self.document,
+            &dict,
+            &mut self.fallback_fonts,
+        ));
+        self.fonts.insert(number, font.clone());
+        Some(font)
+    }
+
+    /// Paint the current path, then intersect the clip with it if `clip` is
+    /// set. The path is cleared afterwards.
+    fn paint_path(
+        &mut self,
+        env: &mut Environment,
+        state: &State,
+        resources: &Dictionary,
+        fill: Option<FillRule>,
+        stroke: bool,
+        clip: Option<FillRule>,
+    ) {
+        let context = self.context;
+        // Each painting operation consumes the path, so keep a copy.
+        let path = CGContextCopyPath(env, context);
+        CGContextBeginPath(env, context);
+
+        if let Some(rule) = fill {
+            if let Some(ref pattern) = state.fill.pattern {
+                self.fill_with_pattern(env, state, resources, pattern, path, rule);
+            } else {
+                self.set_colors(env, state);
+                CGContextAddPath(env, context, path);
+                let mode = match rule {
+                    FillRule::Winding => kCGPathFill,
+                    FillRule::EvenOdd => kCGPathEOFill,
+                };
+                CGContextDrawPath(env, context, mode);
+            }
+        }
+        if stroke {
+            // TODO: stroking with patterns
+            self.set_colors(env, state);
+            CGContextAddPath(env, context, path);
+            CGContextDrawPath(env, context, kCGPathStroke);
+        }
+        if let Some(rule) = clip {
+            CGContextAddPath(env, context, path);
+            match rule {
+                FillRule::Winding => CGContextClip(env, context),
+                FillRule::EvenOdd => CGContextEOClip(env, context),
+            }
+        }
+        CGPathRelease(env, path);
+    }
+
+    fn fill_with_pattern(
+        &mut self,
+        env: &mut Environment,
+        state: &State,
+        resources: &Dictionary,
+        name: &str,
+        path: CGPathRef,
+        rule: FillRule,
+    ) {
+        let context = self.context;
+        let pattern = self.resource(resources, "Pattern", name);
+        let Some(dict) = pattern.as_dict() else {
+            log!("Warning: missing PDF pattern {:?}", name);
+            return;
+        };
+        let ctm = CGContextGetCTM(env, context);
+        // The bounds of the area to fill, in device space.
+        let bounds = match cg_path::borrow_path(env, path).bounding_box() {
+            Some(bounds) => ctm.apply_to_rect(bounds),
+            None => return,
+        };
+
+        CGContextSaveGState(env, context);
+        CGContextAddPath(env, context, path);
+        match rule {
+            FillRule::Winding => CGContextClip(env, context),
+            FillRule::EvenOdd => CGContextEOClip(env, context),
+        }
+        // Pattern space is relative to the page's default user space rather
+        // than the current one.
+        let pattern_matrix = matrix_from_object(self.document, dict.get("Matrix"));
+        let pattern_to_device = pattern_matrix.concat(self.base_ctm);
+        CGContextConcatCTM(env, context, pattern_to_device.concat(ctm.invert()));
+
+        match self.document.lookup(dict, "PatternType").as_integer() {
+            Some(1) => {
+                let bounds = pattern_to_device.invert().apply_to_rect(bounds);
+                self.draw_tiling_pattern(env, state, &pattern, bounds);
+            }
+            Some(2) => {
+                let shading = self.document.lookup(dict, "Shading");
+                self.draw_shading(env, resources, &shading, state.fill.alpha);
+            }
+            kind => log!("Warning: unknown PDF pattern type {:?}", kind),
+        }
+        CGContextRestoreGState(env, context);
+    }
+
+    /// Draw enough cells of a tiling pattern to cover `bounds`, which is in
+    /// pattern space.
+    fn draw_tiling_pattern(
+        &mut self,
+        env: &mut Environment,
+        state: &State,
+        pattern: &Object,
+        bounds: CGRect,
+    ) {
+        let context = self.context;
+        let Some(stream) = pattern.as_stream() else {
+            return;
+        };
+        let dict = &stream.dict;
+        let Some(bbox) = rect_from_object(self.document, dict.get("BBox")) else {
+            return;
+        };
+        let x_step = self
+            .document
+            .lookup(dict, "XStep")
+            .as_number()
+            .unwrap_or(0.0)
+            .abs();
+        let y_step = self
+            .document
+            .lookup(dict, "YStep")
+            .as_number()
+            .unwrap_or(0.0)
+            .abs();
+        if x_step == 0.0 || y_step == 0.0 || self.nesting >= MAX_NESTING {
+            return;
+        }
+        let (min_i, max_i) = (
+            ((bounds.origin.x - bbox.origin.x - bbox.size.width) / x_step).floor() as i64,
+            ((bounds.origin.x + bounds.size.width - bbox.origin.x) / x_step).ceil() as i64,
+        );
+        let (min_j, max_j) = (
+            ((bounds.origin.y - bbox.origin.y - bbox.size.height) / y_step).floor() as i64,
+            ((bounds.origin.y + bounds.size.height - bbox.origin.y) / y_step).ceil() as i64,
+        );
+        if (max_i - min_i + 1).saturating_mul(max_j - min_j + 1) > MAX_PATTERN_CELLS {
+            log!("Warning: PDF pattern has too many cells to draw, skipping");
+            return;
+        }
+        let data = match self.document.decode_stream(stream) {
+            Ok(data) => data,
+            Err(e) => {
+                log!("Warning: couldn't decode PDF pattern: {}", e);
+                return;
+            }
+        };
+        let operations = parser::parse_content(&data);
+        let resources = match self.document.lookup(dict, "Resources") {
+            Object::Dictionary(resources) => resources,
+            _ => Dictionary::new(),
+        };
+        // Uncolored patterns (paint type 2) should use the color given along
+        // with the pattern name, but that's not tracked.
+        // TODO: support uncolored patterns properly.
+        let cell_state = State {
+            text: state.text.clone(),
+            ..State::default()
+        };
+
+        self.nesting += 1;
+        for j in min_j..=max_j {
+            for i in min_i..=max_i {
+                CGContextSaveGState(env, context);
+                let offset = translation(i as CGFloat * x_step, j as CGFloat * y_step);
+                CGContextConcatCTM(env, context, offset);
+                CGContextClipToRect(env, context, bbox);
+                self.run(env, &operations, &resources, cell_state.clone());
+                CGContextRestoreGState(env, context);
+            }
+        }
+        self.nesting -= 1;
+    }
+
+    /// Draw an axial or radial shading over the whole clip area.
+    fn draw_shading(
+        &mut self,
+        env: &mut Environment,
+        resources: &Dictionary,
+        shading: &Object,
+        alpha: CGFloat,
+    ) {
+        let document = self.document;
+        let Some(dict) = shading.as_dict() else {
+            return;
+        };
+        let numbers = |key: &str| -> Vec<CGFloat> {
+            document
+                .lookup(dict, key)
+                .as_array()
+                .unwrap_or(&[])
+                .iter()
+                .filter_map(Object::as_number)
+                .collect()
+        };
+        let coords = numbers("Coords");
+        let geometry = match (
+            document.lookup(dict, "ShadingType").as_integer(),
+            &coords[..],
+        ) {
+            (Some(2), &[x0, y0, x1, y1]) => Geometry::Axial {
+                start: CGPoint { x: x0, y: y0 },
+                end: CGPoint { x: x1, y: y1 },
+            },
+            (Some(3), &[x0, y0, r0, x1, y1, r1]) => Geometry::Radial {
+                start: CGPoint { x: x0, y: y0 },
+                start_radius: r0,
+                end: CGPoint { x: x1, y: y1 },
+                end_radius: r1,
+            },
+            (kind, _) => {
+                log!("TODO: PDF shading type {:?} is not supported", kind);
+                return;
+            }
+        };
+        let space = ColorSpace::parse(document, resources, &document.lookup(dict, "ColorSpace"));
+        let Some(function) = Function::parse(document, &document.lookup(dict, "Function")) else {
+            log!("Warning: PDF shading has no usable function");
+            return;
+        };
+        let (t0, t1) = match numbers("Domain")[..] {
+            [t0, t1] => (t0, t1),
+            _ => (0.0, 1.0),
+        };
+        let extend = match document.lookup(dict, "Extend").as_array() {
+            Some([start, end]) => (
+                start.as_bool().unwrap_or(false),
+                end.as_bool().unwrap_or(false),
+            ),
+            _ => (false, false),
+        };
+        let samples: Vec<_> = (0..SAMPLE_COUNT)
+            .map(|i| {
+                let t = t0 + (t1 - t0) * i as CGFloat / (SAMPLE_COUNT - 1) as CGFloat;
+                let (r, g, b) = space.to_rgb(&function.evaluate(&[t]));
+                (r, g, b, alpha)
+            })
+            .collect();
+        cg_shading::draw(env, self.context, geometry, extend, &samples);
+    }
+
+    fn draw_xobject(
+        &mut self,
+        env: &mut Environment,
+        state: &State,
+        resources: &Dictionary,
+        name: &str,
+    ) {
+        let Object::Stream(stream) = self.resource(resources, "XObject", name) else {
+            log!("Warning: missing PDF XObject {:?}", name);
+            return;
+        };
+        match self.document.lookup(&stream.dict, "Subtype").as_name() {
+            Some("Image") => self.draw_image(env, state, resources, &stream),
+            Some("Form") => self.draw_form(env, state, resources, &stream),
+            // PostScript XObjects are meant to be ignored.
+            _ => (),
+        }
+    }
+
+    fn draw_form(
+        &mut self,
+        env: &mut Environment,
+        state: &State,
+        resources: &Dictionary,
+        stream: &Stream,
+    ) {
+        if self.nesting >= MAX_NESTING {
+            log!("Warning: PDF forms nested too deeply, skipping");
+            return;
+        }
+        let data = match self.document.decode_stream(stream) {
+            Ok(data) => data,
+            Err(e) => {
+                log!("Warning: couldn't decode PDF form: {}", e);
+                return;
+            }
+        };
+        let operations = parser::parse_content(&data);
+        // Old files may rely on forms using their parent's resources.
+        let resources = match self.document.lookup(&stream.dict, "Resources") {
+            Object::Dictionary(resources) => resources,
+            _ => resources.clone(),
+        };
+        let matrix = matrix_from_object(self.document, stream.dict.get("Matrix"));
+        let bbox = rect_from_object(self.document, stream.dict.get("BBox"));
+
+        let context = self.context;
+        CGContextSaveGState(env, context);
+        CGContextConcatCTM(env, context, matrix);
+        if let Some(bbox) = bbox {
+            CGContextClipToRect(env, context, bbox);
+        }
+        self.nesting += 1;
+        self.run(env, &operations, &resources, state.clone());
+        self.nesting -= 1;
+        CGContextRestoreGState(env, context);
+    }
+
+    /// Draw an image XObject or inline image in the unit square.
+    fn draw_image(
+        &mut self,
+        env: &mut Environment,
+        state: &State,
+        resources: &Dictionary,
+        stream: &Stream,
+    ) {
+        // The fill alpha is applied below, along with any mask.
+        let (r, g, b, _) = state.fill.rgba();
+        let Some(mut image) = self.decode_image(resources, stream, (r, g, b, 1.0)) else {
+            return;
+        };
+        let dimensions = image.dimensions();
+
+        // Soft masks and stencil masks both become the image's alpha.
+        let mask = match self.document.lookup(&stream.dict, "SMask") {
+            Object::Stream(smask) => self
+                .decode_image(resources, &smask, (0.0, 0.0, 0.0, 1.0))
+                .map(|smask| (smask, 0)),
+            _ => match self.document.lookup(&stream.dict, "Mask") {
+                Object::Stream(mask) => self
+                    .decode_image(resources, &mask, (0.0, 0.0, 0.0, 1.0))
+                    .map(|mask| (mask, 3)),
+                Object::Array(_) => {
+                    log_dbg!("TODO: PDF color key masking is not supported");
+                    None
+                }
+                _ => None,
+            },
+        };
+        let alpha = mask.map(|(mask, channel)| {
+            resample_channel(mask.pixels(), mask.dimensions(), channel, dimensions)
+        });
+        if alpha.is_some() || state.fill.alpha < 1.0 {
+            let mut pixels = image.pixels().to_vec();
+            for (i, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+                let mut a = pixel[3] as CGFloat * state.fill.alpha;
+                if let Some(ref alpha) = alpha {
+                    a = a * alpha[i] as CGFloat / 255.0;
+                }
+                pixel[3] = a.round() as u8;
+            }
+            image = Image::from_pixels(pixels, dimensions);
+        }
+
+        let context = self.context;
+        let image = cg_image::from_image(env, image);
+        CGContextSaveGState(env, context);
+        // The first row of an image is at the top of the unit square, but
+        // CGContextDrawImage puts it at the minimum y.
+        CGContextConcatCTM(
+            env,
+            context,
+            CGAffineTransform {
+                a: 1.0,
+                b: 0.0,
+                c: 0.0,
+                d: -1.0,
+                tx: 0.0,
+                ty: 1.0,
+            },
+        );
+        let unit_square = CGRect {
+            origin: CGPoint { x: 0.0, y: 0.0 },
+            size: CGSize {
+                width: 1.0,
+                height: 1.0,
+            },
+        };
+        CGContextDrawImage(env, context, unit_square, image);
+        CGContextRestoreGState(env, context);
+        CGImageRelease(env, image);
+    }
+
+    /// Decode an image to RGBA. Stencil masks (`ImageMask`) are painted with
+    /// `mask_color`.
+    fn decode_image(
+        &self,
+        resources: &Dictionary,
+        stream: &Stream,
+        mask_color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    ) -> Option<Image> {
+        let document = self.document;
+        let dict = &stream.dict;
+        let width = document.lookup(dict, "Width").as_integer().unwrap_or(0);
+        let height = document.lookup(dict, "Height").as_integer().unwrap_or(0);
+        if width <= 0 || height <= 0 || (width as u64) * (height as u64) > MAX_IMAGE_PIXELS {
+            log!(
+                "Warning: PDF image has unsupported dimensions {}x{}",
+                width,
+                height
+            );
+            return None;
+        }
+        let (width, height) = (width as u32, height as u32);
+
+        let (data, filter) = match document.decode_stream_for_image(stream) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                log!("Warning: couldn't decode PDF image: {}", e);
+                return None;
+            }
+        };
+        match filter.as_deref() {
+            None => (),
+            Some("DCTDecode" | "DCT") => {
+                return match Image::from_bytes(&data) {
+                    Ok(image) => Some(image),
+                    Err(()) => {
+                        log!("Warning: couldn't decode JPEG image in PDF");
+                        None
+                    }
+                };
+            }
+            Some(filter) => {
+                log!("TODO: PDF image filter {} is not supported", filter);
+                return None;
+            }
+        }
+
+        let is_mask = document
+            .lookup(dict, "ImageMask")
+            .as_bool()
+            .unwrap_or(false);
+        let (space, bits) = if is_mask {
+            (ColorSpace::Gray, 1)
+        } else {
+            let space =
+                ColorSpace::parse(document, resources, &document.lookup(dict, "ColorSpace"));
+            let bits = document
+                .lookup(dict, "BitsPerComponent")
+                .as_integer()
+                .unwrap_or(8) as u32;
+            (space, bits)
+        };
+        if !matches!(bits, 1 | 2 | 4 | 8 | 16) {
+            log!("Warning: PDF image has unsupported bit depth {}", bits);
+            return None;
+        }
+        let components = space.components().max(1);
+        let decode: Vec<(CGFloat, CGFloat)> = match document.lookup(dict, "Decode") {
+            Object::Array(decode) => {
+                let numbers: Vec<CGFloat> = decode.iter().filter_map(Object::as_number).collect();
+                numbers.chunks_exact(2).map(|p| (p[0], p[1])).collect()
+            }
+            _ => Vec::new(),
+        };
+        let decode = if decode.len() == components {
+            decode
+        } else {
+            space.default_decode(bits)
+        };
+        let max = ((1u32 << bits) - 1) as CGFloat;
+
+        let row_bytes = (width as usize * components * bits as usize).div_ceil(8);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        let mut values = vec![0.0; components];
+        for y in 0..height as usize {
+            let row = data.get(y * row_bytes..).unwrap_or(&[]);
+            let samples = read_samples(row, bits, width as usize * components);
+            for pixel in samples.chunks_exact(components) {
+                for ((value, &sample), &(d_min, d_max)) in
+                    values.iter_mut().zip(pixel).zip(decode.iter())
+                {
+                    *value = d_min + sample as CGFloat * (d_max - d_min) / max;
+                }
+                let rgba = if is_mask {
+                    // Zero samples (after decoding) are painted.
+                    let (r, g, b, a) = mask_color;
+                    let a = if values[0] < 0.5 { a } else { 0.0 };
+                    (r, g, b, a)
+                } else {
+                    let (r, g, b) = space.to_rgb(&values);
+                    (r, g, b, 1.0)
+                };
+                let (r, g, b, a) = rgba;
+                pixels.extend([r, g, b, a].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+            }
+        }
+        Some(Image::from_pixels(pixels, (width, height)))
+    }
+
+    fn show_text(
+        &mut self,
+        env: &mut Environment,
+        state: &State,
+        text_matrix: &mut CGAffineTransform,
+        string: &[u8],
+    ) {
+        let context = self.context;
+        let text = &state.text;
+        let Some(font) = text.font.clone() else {
+            return;
+        };
+        // TODO: clipping to text (modes 4 to 7)
+        let (fill, stroke) = match text.render_mode {
+            0 | 4 => (true, false),
+            1 | 5 => (false, true),
+            2 | 6 => (true, true),
+            _ => (false, false),
+        };
+
+        // The whole string is drawn as one path, which is much faster than
+        // drawing each glyph separately.
+        CGContextBeginPath(env, context);
+        let mut has_outlines = false;
+        for (code, is_space) in font.decode(string) {
+            if fill || stroke {
+                if let Some(outline) = font.outline(code) {
+                    let glyph_matrix = CGAffineTransform {
+                        a: text.size * text.horizontal_scaling,
+                        b: 0.0,
+                        c: 0.0,
+                        d: text.size,
+                        tx: 0.0,
+                        ty: text.rise,
+                    }
+                    .concat(*text_matrix);
+                    add_outline(env, context, &outline, glyph_matrix);
+                    has_outlines = true;
+                }
+            }
+            let word_spacing = if is_space { text.word_spacing } else { 0.0 };
+            let advance = (font.width(code) * text.size + text.char_spacing + word_spacing)
+                * text.horizontal_scaling;
+            *text_matrix = translation(advance, 0.0).concat(*text_matrix);
+        }
+        if !has_outlines {
+            return;
+        }
+        self.set_colors(env, state);
+        let mode = match (fill, stroke) {
+            (true, true) => kCGPathFillStroke,
+            (false, true) => kCGPathStroke,
+            _ => kCGPathFill,
+        };
+        CGContextDrawPath(env, context, mode);
+    }
+}
+
+/// Add a glyph outline to the context's path, transforming it to user space.
+fn add_outline(
+    env: &mut Environment,
+    context: CGContextRef,
+    outline: &[OutlineSegment],
+    matrix: CGAffineTransform,
+) {
+    let point = |x, y| matrix.apply_to_point(CGPoint { x, y });
+    for &segment in outline {
+        match segment {
+            OutlineSegment::MoveTo(x, y) => {
+                let p = point(x, y);
+                CGContextMoveToPoint(env, context, p.x, p.y);
+            }
+            OutlineSegment::LineTo(x, y) => {
+                let p = point(x, y);
+                CGContextAddLineToPoint(env, context, p.x, p.y);
+            }
+            OutlineSegment::QuadTo(x1, y1, x, y) => {
+                let (c, p) = (point(x1, y1), point(x, y));
+                CGContextAddQuadCurveToPoint(env, context, c.x, c.y, p.x, p.y);
+            }
+            OutlineSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+                let (c1, c2, p) = (point(x1, y1), point(x2, y2), point(x, y));
+                CGContextAddCurveToPoint(env, context, c1.x, c1.y, c2.x, c2.y, p.x, p.y);
+            }
+            OutlineSegment::Close => CGContextClosePath(env, context),
+        }
+    }
+}
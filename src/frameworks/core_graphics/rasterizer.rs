@@ -0,0 +1,535 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Host-side scan conversion of paths into anti-aliased coverage masks, used
+//! by the path drawing and clipping functions of `CGContext`.
+//!
+//! Everything here works in device space (pixels), with polylines produced by
+//! [super::cg_path::Path::flatten].
+
+use super::cg_affine_transform::CGAffineTransform;
+use super::cg_path::Polyline;
+use super::{CGFloat, CGPoint};
+use std::f32::consts::PI;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FillRule {
+    /// Non-zero winding number rule.
+    Winding,
+    EvenOdd,
+}
+
+pub type CGLineCap = i32;
+pub const kCGLineCapButt: CGLineCap = 0;
+pub const kCGLineCapRound: CGLineCap = 1;
+pub const kCGLineCapSquare: CGLineCap = 2;
+
+pub type CGLineJoin = i32;
+pub const kCGLineJoinMiter: CGLineJoin = 0;
+pub const kCGLineJoinRound: CGLineJoin = 1;
+pub const kCGLineJoinBevel: CGLineJoin = 2;
+
+/// Per-pixel coverage values between 0.0 and 1.0 for a whole bitmap, with the
+/// same row order as the bitmap's memory.
+#[derive(Clone, Debug)]
+pub struct Mask {
+    pub width: u32,
+    pub height: u32,
+    pub coverage: Vec<f32>,
+}
+impl Mask {
+    pub fn new(width: u32, height: u32, value: f32) -> Mask {
+        Mask {
+            width,
+            height,
+            coverage: vec![value; width as usize * height as usize],
+        }
+    }
+    pub fn get(&self, x: u32, y: u32) -> f32 {
+        self.coverage[(y * self.width + x) as usize]
+    }
+    /// Restrict this mask to the area covered by another one.
+    pub fn intersect(&mut self, other: &Mask) {
+        assert!(self.width == other.width && self.height == other.height);
+        for (a, b) in self.coverage.iter_mut().zip(other.coverage.iter()) {
+            *a *= b;
+        }
+    }
+    /// Smallest pixel rectangle (x, y, width, height) containing all non-zero
+    /// coverage, if any.
+    pub fn bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.get(x, y) > 0.0 {
+                    bounds = Some(match bounds {
+                        None => (x, y, x, y),
+                        Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                    });
+                }
+            }
+        }
+        bounds.map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+    }
+}
+
+/// Number of sample rows per pixel row. Horizontal coverage is computed
+/// exactly, so this only affects the quality of near-horizontal edges.
+const SUBSAMPLES: u32 = 4;
+
+/// Fill polygons (polylines are implicitly closed) into a coverage mask.
+pub fn fill(polylines: &[Polyline], rule: FillRule, width: u32, height: u32) -> Mask {
+    let mut mask = Mask::new(width, height, 0.0);
+
+    // Edges as (x0, y0, x1, y1, direction) with y0 < y1.
+    let mut edges: Vec<(f32, f32, f32, f32, i32)> = Vec::new();
+    for (points, _closed) in polylines {
+        if points.len() < 2 {
+            continue;
+        }
+        for i in 0..points.len() {
+            let (xa, ya) = points[i];
+            let (xb, yb) = points[(i + 1) % points.len()];
+            if ya == yb || !(ya.is_finite() && yb.is_finite()) {
+                continue;
+            }
+            if ya < yb {
+                edges.push((xa, ya, xb, yb, 1));
+            } else {
+                edges.push((xb, yb, xa, ya, -1));
+            }
+        }
+    }
+    if edges.is_empty() {
+        return mask;
+    }
+
+    let min_y = edges.iter().map(|e| e.1).fold(f32::INFINITY, f32::min);
+    let max_y = edges.iter().map(|e| e.3).fold(f32::NEG_INFINITY, f32::max);
+    let row_start = (min_y.floor().max(0.0) as u32).min(height);
+    let row_end = (max_y.ceil().max(0.0) as u32).min(height);
+
+    let sample_weight = 1.0 / SUBSAMPLES as f32;
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    for row in row_start..row_end {
+        let row_coverage = &mut mask.coverage[(row * width) as usize..((row + 1) * width) as usize];
+        for sample in 0..SUBSAMPLES {
+            let sy = row as f32 + (sample as f32 + 0.5) * sample_weight;
+            crossings.clear();
+            for &(x0, y0, x1, y1, dir) in &edges {
+                if sy >= y0 && sy < y1 {
+                    let x = x0 + (sy - y0) / (y1 - y0) * (x1 - x0);
+                    crossings.push((x, dir));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                let inside = match rule {
+                    FillRule::Winding => winding != 0,
+                    FillRule::EvenOdd => winding % 2 != 0,
+                };
+                if inside {
+                    add_span(row_coverage, pair[0].0, pair[1].0, sample_weight);
+                }
+            }
+        }
+    }
+    for value in mask.coverage.iter_mut() {
+        *value = value.min(1.0);
+    }
+    mask
+}
+
+/// Add `weight` times the horizontal coverage of the span from `x_start` to
+/// `x_end` to each pixel in a row.
+fn add_span(row: &mut [f32], x_start: f32, x_end: f32, weight: f32) {
+    let width = row.len() as f32;
+    let x_start = x_start.clamp(0.0, width);
+    let x_end = x_end.clamp(0.0, width);
+    if x_end <= x_start {
+        return;
+    }
+    let first = x_start.floor() as usize;
+    let last = (x_end.ceil() as usize).min(row.len());
+    for (px, value) in row.iter_mut().enumerate().take(last).skip(first) {
+        let px = px as f32;
+        let overlap = x_end.min(px + 1.0) - x_start.max(px);
+        if overlap > 0.0 {
+            *value += overlap * weight;
+        }
+    }
+}
+
+/// Winding number of the polygons around a point.
+pub fn winding_number(polylines: &[Polyline], (px, py): (f32, f32)) -> i32 {
+    let mut winding = 0;
+    for (points, _closed) in polylines {
+        if points.len() < 2 {
+            continue;
+        }
+        for i in 0..points.len() {
+            let (xa, ya) = points[i];
+            let (xb, yb) = points[(i + 1) % points.len()];
+            let side = (xb - xa) * (py - ya) - (px - xa) * (yb - ya);
+            if ya <= py && yb > py && side > 0.0 {
+                winding += 1;
+            } else if ya > py && yb <= py && side < 0.0 {
+                winding -= 1;
+            }
+        }
+    }
+    winding
+}
+
+/// Parameters for [stroke], in user space.
+#[derive(Clone, Debug)]
+pub struct StrokeStyle {
+    pub line_width: CGFloat,
+    pub line_cap: CGLineCap,
+    pub line_join: CGLineJoin,
+    pub miter_limit: CGFloat,
+    /// Phase and lengths of the dash pattern, if any.
+    pub dash: Option<(CGFloat, Vec<CGFloat>)>,
+}
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        StrokeStyle {
+            line_width: 1.0,
+            line_cap: kCGLineCapButt,
+            line_join: kCGLineJoinMiter,
+            miter_limit: 10.0,
+            dash: None,
+        }
+    }
+}
+
+/// Convert polylines in device space into polygons covering their stroke.
+/// The stroke is computed in user space, which `ctm` maps to device space, so
+/// that transformed strokes get the right shape. The resulting polygons all
+/// wind the same way, so they should be filled with [FillRule::Winding].
+pub fn stroke(
+    polylines: &[Polyline],
+    style: &StrokeStyle,
+    ctm: CGAffineTransform,
+) -> Vec<Polyline> {
+    let inverse = ctm.invert();
+    let to_user = |(x, y): (f32, f32)| {
+        let p = inverse.apply_to_point(CGPoint { x, y });
+        (p.x, p.y)
+    };
+    let to_device = |(x, y): (f32, f32)| {
+        let p = ctm.apply_to_point(CGPoint { x, y });
+        (p.x, p.y)
+    };
+    // Used to pick how finely to approximate round joins and caps.
+    let scale = (ctm.a * ctm.d - ctm.b * ctm.c).abs().sqrt().max(0.01);
+    let half_width = style.line_width.abs() / 2.0;
+
+    let mut polygons = Vec::new();
+    for (points, closed) in polylines {
+        let mut points: Vec<(f32, f32)> = points.iter().copied().map(to_user).collect();
+        points.dedup();
+        let mut closed = *closed;
+        if closed && points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        if points.len() < 2 {
+            closed = false;
+        }
+
+        let pieces = match style.dash {
+            Some((phase, ref lengths)) if lengths.iter().any(|&l| l > 0.0) => {
+                dash(&points, closed, phase, lengths)
+            }
+            _ => vec![(points, closed)],
+        };
+        for (piece, closed) in pieces {
+            stroke_polyline(&piece, closed, style, half_width, scale, &mut polygons);
+        }
+    }
+
+    for polygon in polygons.iter_mut() {
+        for point in polygon.0.iter_mut() {
+            *point = to_device(*point);
+        }
+        if signed_area(&polygon.0) < 0.0 {
+            polygon.0.reverse();
+        }
+    }
+    polygons
+}
+
+fn signed_area(points: &[(f32, f32)]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..points.len() {
+        let (xa, ya) = points[i];
+        let (xb, yb) = points[(i + 1) % points.len()];
+        area += xa * yb - xb * ya;
+    }
+    area / 2.0
+}
+
+fn circle(center: (f32, f32), radius: f32, scale: f32) -> Polyline {
+    let segments = ((radius * scale * 2.0).ceil() as u32).clamp(8, 64);
+    let points = (0..segments)
+        .map(|i| {
+            let angle = i as f32 / segments as f32 * 2.0 * PI;
+            let (sin, cos) = angle.sin_cos();
+            (center.0 + radius * cos, center.1 + radius * sin)
+        })
+        .collect();
+    (points, true)
+}
+
+fn stroke_polyline(
+    points: &[(f32, f32)],
+    closed: bool,
+    style: &StrokeStyle,
+    half_width: f32,
+    scale: f32,
+    polygons: &mut Vec<Polyline>,
+) {
+    if points.is_empty() || half_width == 0.0 {
+        return;
+    }
+    if points.len() == 1 {
+        // A degenerate subpath only draws something with round or square
+        // caps.
+        let (x, y) = points[0];
+        match style.line_cap {
+            kCGLineCapRound => polygons.push(circle((x, y), half_width, scale)),
+            kCGLineCapSquare => polygons.push((
+                vec![
+                    (x - half_width, y - half_width),
+                    (x + half_width, y - half_width),
+                    (x + half_width, y + half_width),
+                    (x - half_width, y + half_width),
+                ],
+                true,
+            )),
+            _ => (),
+        }
+        return;
+    }
+
+    let segment_count = if closed {
+        points.len()
+    } else {
+        points.len() - 1
+    };
+    let segment = |i: usize| {
+        let a = points[i % points.len()];
+        let b = points[(i + 1) % points.len()];
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len = dx.hypot(dy);
+        (a, b, (dx / len, dy / len))
+    };
+
+    for i in 0..segment_count {
+        let (a, b, (ux, uy)) = segment(i);
+        let (nx, ny) = (-uy * half_width, ux * half_width);
+        polygons.push((
+            vec![
+                (a.0 + nx, a.1 + ny),
+                (b.0 + nx, b.1 + ny),
+                (b.0 - nx, b.1 - ny),
+                (a.0 - nx, a.1 - ny),
+            ],
+            true,
+        ));
+    }
+
+    // Joins between consecutive segments.
+    let join_count = if closed {
+        segment_count
+    } else {
+        segment_count - 1
+    };
+    for i in 0..join_count {
+        let (_, p, (u0x, u0y)) = segment(i);
+        let (_, _, (u1x, u1y)) = segment(i + 1);
+        let cross = u0x * u1y - u0y * u1x;
+        if cross.abs() < 1e-6 && u0x * u1x + u0y * u1y > 0.0 {
+            continue; // straight continuation
+        }
+        if style.line_join == kCGLineJoinRound {
+            polygons.push(circle(p, half_width, scale));
+            continue;
+        }
+        // The outer side of the turn is opposite the direction it turns in.
+        let side = if cross > 0.0 { -1.0 } else { 1.0 };
+        let (n0x, n0y) = (-u0y * side, u0x * side);
+        let (n1x, n1y) = (-u1y * side, u1x * side);
+        let outer0 = (p.0 + n0x * half_width, p.1 + n0y * half_width);
+        let outer1 = (p.0 + n1x * half_width, p.1 + n1y * half_width);
+        // cos of half the angle between the normals
+        let (bx, by) = (n0x + n1x, n0y + n1y);
+        let cos_half = bx.hypot(by) / 2.0;
+        if style.line_join == kCGLineJoinMiter
+            && cos_half > 1e-6
+            && 1.0 / cos_half <= style.miter_limit
+        {
+            let miter_length = half_width / cos_half;
+            let b_len = bx.hypot(by);
+            let tip = (
+                p.0 + bx / b_len * miter_length,
+                p.1 + by / b_len * miter_length,
+            );
+            polygons.push((vec![p, outer0, tip, outer1], true));
+        } else {
+            polygons.push((vec![p, outer0, outer1], true));
+        }
+    }
+
+    if !closed {
+        let (start, _, (ux, uy)) = segment(0);
+        let (_, end, (vx, vy)) = segment(segment_count - 1);
+        for (p, (dx, dy)) in [(start, (-ux, -uy)), (end, (vx, vy))] {
+            match style.line_cap {
+                kCGLineCapRound => polygons.push(circle(p, half_width, scale)),
+                kCGLineCapSquare => {
+                    let (nx, ny) = (-dy * half_width, dx * half_width);
+                    let (ex, ey) = (dx * half_width, dy * half_width);
+                    polygons.push((
+                        vec![
+                            (p.0 + nx, p.1 + ny),
+                            (p.0 + nx + ex, p.1 + ny + ey),
+                            (p.0 - nx + ex, p.1 - ny + ey),
+                            (p.0 - nx, p.1 - ny),
+                        ],
+                        true,
+                    ))
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Split a polyline into the "on" pieces of a dash pattern.
+fn dash(points: &[(f32, f32)], closed: bool, phase: f32, lengths: &[f32]) -> Vec<Polyline> {
+    let pattern_length: f32 = lengths.iter().sum();
+    let mut index = 0;
+    let mut remaining = lengths[0];
+    let mut on = true;
+    // Skip into the pattern according to the phase.
+    let mut phase = phase.rem_euclid(pattern_length);
+    while phase > 0.0 {
+        if phase < remaining {
+            remaining -= phase;
+            break;
+        }
+        phase -= remaining;
+        index = (index + 1) % lengths.len();
+        remaining = lengths[index];
+        on = !on;
+    }
+
+    let mut pieces = Vec::new();
+    let mut current: Vec<(f32, f32)> = if on { vec![points[0]] } else { Vec::new() };
+    let point_count = if closed {
+        points.len() + 1
+    } else {
+        points.len()
+    };
+    for i in 1..point_count {
+        let a = points[i - 1];
+        let b = points[i % points.len()];
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len = dx.hypot(dy);
+        let mut travelled = 0.0;
+        while len - travelled > remaining {
+            travelled += remaining;
+            let t = travelled / len;
+            let p = (a.0 + dx * t, a.1 + dy * t);
+            if on {
+                current.push(p);
+                pieces.push((std::mem::take(&mut current), false));
+            } else {
+                current = vec![p];
+            }
+            on = !on;
+            index = (index + 1) % lengths.len();
+            remaining = lengths[index];
+        }
+        remaining -= len - travelled;
+        if on {
+            current.push(b);
+        }
+    }
+    if on && !current.is_empty() {
+        pieces.push((current, false));
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x0: f32, y0: f32, x1: f32, y1: f32) -> Polyline {
+        (vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1)], true)
+    }
+
+    #[test]
+    fn fill_pixel_aligned_rect() {
+        let mask = fill(&[square(1.0, 1.0, 3.0, 3.0)], FillRule::Winding, 4, 4);
+        assert_eq!(mask.get(0, 0), 0.0);
+        assert_eq!(mask.get(1, 1), 1.0);
+        assert_eq!(mask.get(2, 2), 1.0);
+        assert_eq!(mask.get(3, 3), 0.0);
+        assert_eq!(mask.bounds(), Some((1, 1, 2, 2)));
+    }
+
+    #[test]
+    fn fill_partial_coverage() {
+        let mask = fill(&[square(0.5, 0.0, 1.0, 1.0)], FillRule::Winding, 2, 1);
+        assert_eq!(mask.get(0, 0), 0.5);
+        assert_eq!(mask.get(1, 0), 0.0);
+    }
+
+    #[test]
+    fn fill_rules() {
+        // Two nested squares wound the same way: the inner one is a hole
+        // only with the even-odd rule.
+        let shapes = [square(0.0, 0.0, 4.0, 4.0), square(1.0, 1.0, 3.0, 3.0)];
+        let winding = fill(&shapes, FillRule::Winding, 4, 4);
+        let even_odd = fill(&shapes, FillRule::EvenOdd, 4, 4);
+        assert_eq!(winding.get(2, 2), 1.0);
+        assert_eq!(even_odd.get(2, 2), 0.0);
+        assert_eq!(even_odd.get(0, 0), 1.0);
+        assert_eq!(winding_number(&shapes, (2.0, 2.0)).abs(), 2);
+    }
+
+    #[test]
+    fn stroke_with_caps() {
+        let line = [(vec![(1.0, 2.0), (5.0, 2.0)], false)];
+        let mut style = StrokeStyle {
+            line_width: 2.0,
+            ..Default::default()
+        };
+        let identity = super::super::cg_affine_transform::CGAffineTransformIdentity;
+        let butt = fill(&stroke(&line, &style, identity), FillRule::Winding, 8, 4);
+        assert_eq!(butt.get(0, 1), 0.0);
+        assert_eq!(butt.get(1, 1), 1.0);
+        assert_eq!(butt.get(4, 2), 1.0);
+        assert_eq!(butt.get(5, 2), 0.0);
+        style.line_cap = kCGLineCapSquare;
+        let square = fill(&stroke(&line, &style, identity), FillRule::Winding, 8, 4);
+        assert_eq!(square.get(0, 1), 1.0);
+        assert_eq!(square.get(5, 2), 1.0);
+    }
+
+    #[test]
+    fn dash_pattern() {
+        let pieces = dash(&[(0.0, 0.0), (10.0, 0.0)], false, 0.0, &[2.0, 3.0]);
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[0].0, vec![(0.0, 0.0), (2.0, 0.0)]);
+        assert_eq!(pieces[1].0, vec![(5.0, 0.0), (7.0, 0.0)]);
+    }
+}
@@ -0,0 +1,17 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Core Location framework.
+//!
+//! Only location updates are implemented, there's no heading (compass) or
+//! region monitoring. Where the device is comes from [crate::location].
+
+pub mod cl_location;
+pub mod cl_location_manager;
+
+#[derive(Default)]
+pub struct State {
+    cl_location_manager: cl_location_manager::State,
+}
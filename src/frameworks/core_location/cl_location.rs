@@ -0,0 +1,264 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CLLocation` and `CLLocationCoordinate2D`.
+
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::{ns_date, ns_string, NSTimeInterval};
+use crate::location::{self, Fix, Reading};
+use crate::mem::{ConstVoidPtr, Mem, MutVoidPtr, SafeRead};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+pub type CLLocationDegrees = f64;
+/// In meters.
+pub type CLLocationDistance = f64;
+/// In meters. Negative means invalid.
+pub type CLLocationAccuracy = f64;
+/// In degrees clockwise from north. Negative means invalid.
+pub type CLLocationDirection = f64;
+/// In meters per second. Negative means invalid.
+pub type CLLocationSpeed = f64;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C, packed)]
+pub struct CLLocationCoordinate2D {
+    pub latitude: CLLocationDegrees,
+    pub longitude: CLLocationDegrees,
+}
+unsafe impl SafeRead for CLLocationCoordinate2D {}
+impl_GuestRet_for_large_struct!(CLLocationCoordinate2D);
+impl GuestArg for CLLocationCoordinate2D {
+    const REG_COUNT: usize = 4;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        CLLocationCoordinate2D {
+            latitude: GuestArg::from_regs(&regs[0..2]),
+            longitude: GuestArg::from_regs(&regs[2..4]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.latitude.to_regs(&mut regs[0..2]);
+        self.longitude.to_regs(&mut regs[2..4]);
+    }
+}
+impl CLLocationCoordinate2D {
+    fn is_valid(self) -> bool {
+        (-90.0..=90.0).contains(&{ self.latitude })
+            && (-180.0..=180.0).contains(&{ self.longitude })
+    }
+    fn to_fix(self) -> Fix {
+        Fix {
+            latitude: self.latitude,
+            longitude: self.longitude,
+            altitude: None,
+        }
+    }
+}
+
+pub const kCLLocationCoordinate2DInvalid: CLLocationCoordinate2D = CLLocationCoordinate2D {
+    latitude: 180.0,
+    longitude: 180.0,
+};
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_kCLLocationCoordinate2DInvalid",
+    HostConstant::Custom(|mem: &mut Mem| -> ConstVoidPtr {
+        mem.alloc_and_write(kCLLocationCoordinate2DInvalid)
+            .cast()
+            .cast_const()
+    }),
+)];
+
+/// Horizontal accuracy reported for host-provided locations, about as good as
+/// a real GPS fix gets.
+const HORIZONTAL_ACCURACY: CLLocationAccuracy = 5.0;
+/// Vertical accuracy reported for host-provided locations with an altitude.
+const VERTICAL_ACCURACY: CLLocationAccuracy = 10.0;
+
+struct CLLocationHostObject {
+    coordinate: CLLocationCoordinate2D,
+    altitude: CLLocationDistance,
+    horizontal_accuracy: CLLocationAccuracy,
+    vertical_accuracy: CLLocationAccuracy,
+    course: CLLocationDirection,
+    speed: CLLocationSpeed,
+    /// Relative to the reference date, like `NSDate`.
+    timestamp: NSTimeInterval,
+}
+impl HostObject for CLLocationHostObject {}
+
+fn borrow(env: &mut Environment, location: id) -> &mut CLLocationHostObject {
+    env.objc.borrow_mut(location)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CLLocation: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(CLLocationHostObject {
+        coordinate: kCLLocationCoordinate2DInvalid,
+        altitude: 0.0,
+        horizontal_accuracy: -1.0,
+        vertical_accuracy: -1.0,
+        course: -1.0,
+        speed: -1.0,
+        timestamp: 0.0,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithLatitude:(CLLocationDegrees)latitude
+             longitude:(CLLocationDegrees)longitude {
+    let host_object = borrow(env, this);
+    host_object.coordinate = CLLocationCoordinate2D { latitude, longitude };
+    host_object.horizontal_accuracy = 0.0;
+    host_object.timestamp = ns_date::now_since_reference_date();
+    this
+}
+
+- (id)initWithCoordinate:(CLLocationCoordinate2D)coordinate
+                altitude:(CLLocationDistance)altitude
+      horizontalAccuracy:(CLLocationAccuracy)horizontal_accuracy
+        verticalAccuracy:(CLLocationAccuracy)vertical_accuracy
+               timestamp:(id)timestamp { // NSDate*
+    let timestamp = if timestamp == nil {
+        ns_date::now_since_reference_date()
+    } else {
+        ns_date::get_time_interval(env, timestamp)
+    };
+    let host_object = borrow(env, this);
+    host_object.coordinate = coordinate;
+    host_object.altitude = altitude;
+    host_object.horizontal_accuracy = horizontal_accuracy;
+    host_object.vertical_accuracy = vertical_accuracy;
+    host_object.timestamp = timestamp;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+- (CLLocationCoordinate2D)coordinate {
+    borrow(env, this).coordinate
+}
+- (CLLocationDistance)altitude {
+    borrow(env, this).altitude
+}
+- (CLLocationAccuracy)horizontalAccuracy {
+    borrow(env, this).horizontal_accuracy
+}
+- (CLLocationAccuracy)verticalAccuracy {
+    borrow(env, this).vertical_accuracy
+}
+- (CLLocationDirection)course {
+    borrow(env, this).course
+}
+- (CLLocationSpeed)speed {
+    borrow(env, this).speed
+}
+- (id)timestamp {
+    let timestamp = borrow(env, this).timestamp;
+    let date = ns_date::from_time_interval(env, timestamp);
+    autorelease(env, date)
+}
+
+- (CLLocationDistance)distanceFromLocation:(id)other { // CLLocation*
+    let a = borrow(env, this).coordinate;
+    let b = borrow(env, other).coordinate;
+    location::distance(a.to_fix(), b.to_fix())
+}
+// Deprecated name of the above.
+- (CLLocationDistance)getDistanceFrom:(id)other { // CLLocation*
+    msg![env; this distanceFromLocation:other]
+}
+
+- (id)description {
+    let &mut CLLocationHostObject {
+        coordinate,
+        horizontal_accuracy,
+        course,
+        speed,
+        ..
+    } = borrow(env, this);
+    let timestamp: id = msg![env; this timestamp];
+    let timestamp: id = msg![env; timestamp description];
+    let timestamp = ns_string::to_rust_string(env, timestamp);
+    let description = format!(
+        "<{:+.8},{:+.8}> +/- {:.2}m (speed {:.2} mps / course {:.2}) @ {}",
+        { coordinate.latitude },
+        { coordinate.longitude },
+        horizontal_accuracy,
+        speed,
+        course,
+        timestamp
+    );
+    let description = ns_string::from_rust_string(env, description);
+    autorelease(env, description)
+}
+
+@end
+
+};
+
+fn CLLocationCoordinate2DIsValid(
+    _env: &mut Environment,
+    coordinate: CLLocationCoordinate2D,
+) -> bool {
+    coordinate.is_valid()
+}
+
+fn CLLocationCoordinate2DMake(
+    _env: &mut Environment,
+    latitude: CLLocationDegrees,
+    longitude: CLLocationDegrees,
+) -> CLLocationCoordinate2D {
+    CLLocationCoordinate2D {
+        latitude,
+        longitude,
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CLLocationCoordinate2DIsValid(_)),
+    export_c_func!(CLLocationCoordinate2DMake(_, _)),
+];
+
+/// Create a `CLLocation` for a host-provided [Reading], timestamped now. The
+/// result is owned by the caller.
+pub fn from_reading(env: &mut Environment, reading: Reading) -> id {
+    let new: id = msg_class![env; CLLocation alloc];
+    let host_object = borrow(env, new);
+    *host_object = CLLocationHostObject {
+        coordinate: CLLocationCoordinate2D {
+            latitude: reading.fix.latitude,
+            longitude: reading.fix.longitude,
+        },
+        altitude: reading.fix.altitude.unwrap_or(0.0),
+        horizontal_accuracy: HORIZONTAL_ACCURACY,
+        vertical_accuracy: if reading.fix.altitude.is_some() {
+            VERTICAL_ACCURACY
+        } else {
+            -1.0
+        },
+        course: reading.course.unwrap_or(-1.0),
+        speed: reading.speed.unwrap_or(-1.0),
+        timestamp: ns_date::now_since_reference_date(),
+    };
+    new
+}
+
+/// Get the fix a `CLLocation` represents.
+pub fn to_fix(env: &mut Environment, location: id) -> Fix {
+    borrow(env, location).coordinate.to_fix()
+}
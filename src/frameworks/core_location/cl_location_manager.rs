@@ -0,0 +1,392 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CLLocationManager`.
+//!
+//! Locations come from `--location=` (see [crate::location]). Without it,
+//! location services are off, and managers tell their delegates that the user
+//! denied access, so apps don't wait forever for a location.
+
+use super::cl_location::{self, CLLocationAccuracy, CLLocationDistance};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::ns_error;
+use crate::frameworks::foundation::{ns_array, NSInteger};
+use crate::location::{self, Provider};
+use crate::mem::{ConstVoidPtr, Mem};
+use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports, HostObject};
+use crate::Environment;
+use std::time::{Duration, Instant};
+
+pub const kCLErrorDomain: &str = "kCLErrorDomain";
+
+type CLError = NSInteger;
+const kCLErrorDenied: CLError = 1;
+
+type CLAuthorizationStatus = i32;
+const kCLAuthorizationStatusDenied: CLAuthorizationStatus = 2;
+const kCLAuthorizationStatusAuthorized: CLAuthorizationStatus = 3;
+
+pub const kCLLocationAccuracyBestForNavigation: CLLocationAccuracy = -2.0;
+pub const kCLLocationAccuracyBest: CLLocationAccuracy = -1.0;
+pub const kCLLocationAccuracyNearestTenMeters: CLLocationAccuracy = 10.0;
+pub const kCLLocationAccuracyHundredMeters: CLLocationAccuracy = 100.0;
+pub const kCLLocationAccuracyKilometer: CLLocationAccuracy = 1000.0;
+pub const kCLLocationAccuracyThreeKilometers: CLLocationAccuracy = 3000.0;
+pub const kCLDistanceFilterNone: CLLocationDistance = -1.0;
+
+fn write_accuracy_best_for_navigation(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(kCLLocationAccuracyBestForNavigation)
+        .cast()
+        .cast_const()
+}
+fn write_accuracy_best(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(kCLLocationAccuracyBest)
+        .cast()
+        .cast_const()
+}
+fn write_accuracy_nearest_ten_meters(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(kCLLocationAccuracyNearestTenMeters)
+        .cast()
+        .cast_const()
+}
+fn write_accuracy_hundred_meters(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(kCLLocationAccuracyHundredMeters)
+        .cast()
+        .cast_const()
+}
+fn write_accuracy_kilometer(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(kCLLocationAccuracyKilometer)
+        .cast()
+        .cast_const()
+}
+fn write_accuracy_three_kilometers(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(kCLLocationAccuracyThreeKilometers)
+        .cast()
+        .cast_const()
+}
+fn write_distance_filter_none(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(kCLDistanceFilterNone)
+        .cast()
+        .cast_const()
+}
+
+pub const CONSTANTS: ConstantExports = &[
+    ("_kCLErrorDomain", HostConstant::NSString(kCLErrorDomain)),
+    (
+        "_kCLLocationAccuracyBestForNavigation",
+        HostConstant::Custom(write_accuracy_best_for_navigation),
+    ),
+    (
+        "_kCLLocationAccuracyBest",
+        HostConstant::Custom(write_accuracy_best),
+    ),
+    (
+        "_kCLLocationAccuracyNearestTenMeters",
+        HostConstant::Custom(write_accuracy_nearest_ten_meters),
+    ),
+    (
+        "_kCLLocationAccuracyHundredMeters",
+        HostConstant::Custom(write_accuracy_hundred_meters),
+    ),
+    (
+        "_kCLLocationAccuracyKilometer",
+        HostConstant::Custom(write_accuracy_kilometer),
+    ),
+    (
+        "_kCLLocationAccuracyThreeKilometers",
+        HostConstant::Custom(write_accuracy_three_kilometers),
+    ),
+    (
+        "_kCLDistanceFilterNone",
+        HostConstant::Custom(write_distance_filter_none),
+    ),
+];
+
+/// How often the location is checked while managers are updating.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub struct State {
+    /// Managers between `startUpdatingLocation` and `stopUpdatingLocation`.
+    /// Weak references, removed when a manager is deallocated.
+    updating: Vec<id>,
+    /// Created when a manager first starts updating.
+    provider: Option<Provider>,
+    last_update: Option<Instant>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.core_location.cl_location_manager
+    }
+}
+
+struct CLLocationManagerHostObject {
+    /// Weak reference.
+    delegate: id,
+    desired_accuracy: CLLocationAccuracy,
+    distance_filter: CLLocationDistance,
+    /// `CLLocation*`, strong reference. The last location delivered.
+    location: id,
+    /// `NSString*`, strong reference.
+    purpose: id,
+    /// Set if the delegate has been told about a new location (or an error)
+    /// since updating started.
+    has_updated: bool,
+}
+impl HostObject for CLLocationManagerHostObject {}
+
+fn borrow(env: &mut Environment, manager: id) -> &mut CLLocationManagerHostObject {
+    env.objc.borrow_mut(manager)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CLLocationManager: NSObject
+
++ (id)alloc {
+    let host_object = Box::new(CLLocationManagerHostObject {
+        delegate: nil,
+        desired_accuracy: kCLLocationAccuracyBest,
+        distance_filter: kCLDistanceFilterNone,
+        location: nil,
+        purpose: nil,
+        has_updated: false,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (bool)locationServicesEnabled {
+    env.options.location.is_some()
+}
+// Deprecated instance method version of the above.
+- (bool)locationServicesEnabled {
+    env.options.location.is_some()
+}
+
++ (CLAuthorizationStatus)authorizationStatus {
+    if env.options.location.is_some() {
+        kCLAuthorizationStatusAuthorized
+    } else {
+        kCLAuthorizationStatusDenied
+    }
+}
+
+// There's no compass.
++ (bool)headingAvailable {
+    false
+}
+- (bool)headingAvailable {
+    false
+}
+- (())startUpdatingHeading {}
+- (())stopUpdatingHeading {}
+
++ (bool)significantLocationChangeMonitoringAvailable {
+    false
+}
+
+- (())dealloc {
+    State::get(&mut env.framework_state)
+        .updating
+        .retain(|&manager| manager != this);
+    let &mut CLLocationManagerHostObject {
+        location, purpose, ..
+    } = borrow(env, this);
+    release(env, location);
+    release(env, purpose);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)delegate {
+    borrow(env, this).delegate
+}
+- (())setDelegate:(id)delegate { // something implementing CLLocationManagerDelegate
+    borrow(env, this).delegate = delegate;
+}
+
+- (CLLocationAccuracy)desiredAccuracy {
+    borrow(env, this).desired_accuracy
+}
+- (())setDesiredAccuracy:(CLLocationAccuracy)accuracy {
+    borrow(env, this).desired_accuracy = accuracy;
+}
+
+- (CLLocationDistance)distanceFilter {
+    borrow(env, this).distance_filter
+}
+- (())setDistanceFilter:(CLLocationDistance)distance {
+    borrow(env, this).distance_filter = distance;
+}
+
+- (id)purpose {
+    borrow(env, this).purpose
+}
+- (())setPurpose:(id)purpose { // NSString*
+    let purpose: id = msg![env; purpose copy];
+    let old = std::mem::replace(&mut borrow(env, this).purpose, purpose);
+    release(env, old);
+}
+
+- (id)location {
+    borrow(env, this).location
+}
+
+- (())startUpdatingLocation {
+    log_dbg!("CLLocationManager {:?} starting updates", this);
+    borrow(env, this).has_updated = false;
+    let options_location = env.options.location.clone();
+    let state = State::get(&mut env.framework_state);
+    if !state.updating.contains(&this) {
+        state.updating.push(this);
+    }
+    if state.provider.is_none() {
+        match options_location {
+            Some(source) => state.provider = Some(Provider::new(source)),
+            None => log!("This app wants to know where you are. Use --location= to tell it."),
+        }
+    }
+    // Deliver the first update as soon as possible.
+    state.last_update = None;
+}
+- (())stopUpdatingLocation {
+    log_dbg!("CLLocationManager {:?} stopping updates", this);
+    State::get(&mut env.framework_state)
+        .updating
+        .retain(|&manager| manager != this);
+}
+
+// Without a cell network or Wi-Fi positioning, these are the same thing.
+- (())startMonitoringSignificantLocationChanges {
+    msg![env; this startUpdatingLocation]
+}
+- (())stopMonitoringSignificantLocationChanges {
+    msg![env; this stopUpdatingLocation]
+}
+
+@end
+
+};
+
+/// For use by `NSRunLoop`: send location updates to the delegates of updating
+/// managers, or tell them location services are off.
+pub fn handle_location_managers(env: &mut Environment) {
+    let state = State::get(&mut env.framework_state);
+    if state.updating.is_empty() {
+        return;
+    }
+    let now = Instant::now();
+    if state
+        .last_update
+        .is_some_and(|last| now.duration_since(last) < UPDATE_INTERVAL)
+    {
+        return;
+    }
+    state.last_update = Some(now);
+
+    let reading = state
+        .provider
+        .as_mut()
+        .and_then(|provider| provider.reading());
+    let enabled = state.provider.is_some();
+    let managers = state.updating.clone();
+    // Delegates might release the managers.
+    for &manager in &managers {
+        retain(env, manager);
+    }
+    for &manager in &managers {
+        // A delegate might have stopped this manager.
+        if !State::get(&mut env.framework_state)
+            .updating
+            .contains(&manager)
+        {
+            continue;
+        }
+        if !enabled {
+            deliver_error(env, manager);
+        } else if let Some(reading) = reading {
+            deliver_reading(env, manager, reading);
+        }
+    }
+    for manager in managers {
+        release(env, manager);
+    }
+}
+
+fn deliver_error(env: &mut Environment, manager: id) {
+    let host_object = borrow(env, manager);
+    if host_object.has_updated {
+        return;
+    }
+    host_object.has_updated = true;
+    let delegate = host_object.delegate;
+    if delegate == nil {
+        return;
+    }
+    // The selector won't exist if no class implements this method.
+    let Some(selector) = env
+        .objc
+        .lookup_selector("locationManager:didFailWithError:")
+    else {
+        return;
+    };
+    if msg![env; delegate respondsToSelector:selector] {
+        let error = ns_error::new_error(env, kCLErrorDomain, kCLErrorDenied);
+        () = msg![env; delegate locationManager:manager didFailWithError:error];
+    }
+}
+
+fn deliver_reading(env: &mut Environment, manager: id, reading: location::Reading) {
+    let &mut CLLocationManagerHostObject {
+        delegate,
+        distance_filter,
+        location: old_location,
+        has_updated,
+        ..
+    } = borrow(env, manager);
+    // The first location after updating starts is always delivered, even if
+    // it's the same as the last one, like on a real device.
+    if has_updated && old_location != nil {
+        let moved = location::distance(cl_location::to_fix(env, old_location), reading.fix);
+        if moved <= distance_filter.max(0.0) {
+            return;
+        }
+    }
+
+    let new_location = cl_location::from_reading(env, reading);
+    let host_object = borrow(env, manager);
+    host_object.location = new_location;
+    host_object.has_updated = true;
+    log_dbg!(
+        "CLLocationManager {:?} delivering location {:?}",
+        manager,
+        reading
+    );
+
+    if delegate != nil {
+        // iOS 6 replaced the delegate method. Apps that implement the new one
+        // only get that.
+        let new_selector = env
+            .objc
+            .lookup_selector("locationManager:didUpdateLocations:");
+        let old_selector = env
+            .objc
+            .lookup_selector("locationManager:didUpdateToLocation:fromLocation:");
+        if new_selector.is_some_and(|selector| msg![env; delegate respondsToSelector:selector]) {
+            retain(env, new_location);
+            let locations = ns_array::from_vec(env, vec![new_location]);
+            () = msg![env; delegate locationManager:manager didUpdateLocations:locations];
+            release(env, locations);
+        } else if old_selector
+            .is_some_and(|selector| msg![env; delegate respondsToSelector:selector])
+        {
+            () = msg![env; delegate locationManager:manager
+                                didUpdateToLocation:new_location
+                                       fromLocation:old_location];
+        }
+    }
+    release(env, old_location);
+}
@@ -0,0 +1,18 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Core Text framework.
+//!
+//! Text is measured and drawn with the same fonts as `UIStringDrawing` (see
+//! `uikit::ui_font`). To make that easy, `CTFont` is implemented as `UIFont`,
+//! even though they aren't the same type on iPhone OS.
+
+pub mod ct_font;
+pub mod ct_frame;
+pub mod ct_framesetter;
+pub mod ct_line;
+pub mod ct_paragraph_style;
+pub mod ct_string_attributes;
+mod layout;
@@ -0,0 +1,106 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CTFont.h`
+//!
+//! `CTFont` is implemented as `UIFont` (see the module documentation of the
+//! parent module).
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_string::CFStringRef;
+use crate::frameworks::core_foundation::CFTypeRef;
+use crate::frameworks::core_graphics::cg_affine_transform::CGAffineTransform;
+use crate::frameworks::core_graphics::CGFloat;
+use crate::mem::ConstPtr;
+use crate::objc::{id, msg, msg_class, retain};
+use crate::Environment;
+
+pub type CTFontRef = CFTypeRef;
+
+/// The size used when a size of zero is given.
+const DEFAULT_SIZE: CGFloat = 12.0;
+
+fn CTFontCreateWithName(
+    env: &mut Environment,
+    name: CFStringRef,
+    size: CGFloat,
+    matrix: ConstPtr<CGAffineTransform>,
+) -> CTFontRef {
+    if !matrix.is_null() {
+        log!("TODO: CTFontCreateWithName() font matrix is ignored");
+    }
+    let size = if size == 0.0 { DEFAULT_SIZE } else { size };
+    let font: id = msg_class![env; UIFont fontWithName:name size:size];
+    retain(env, font)
+}
+
+fn CTFontCreateCopyWithAttributes(
+    env: &mut Environment,
+    font: CTFontRef,
+    size: CGFloat,
+    matrix: ConstPtr<CGAffineTransform>,
+    _attributes: id, // CTFontDescriptorRef
+) -> CTFontRef {
+    if !matrix.is_null() {
+        log!("TODO: CTFontCreateCopyWithAttributes() font matrix is ignored");
+    }
+    let size: CGFloat = if size == 0.0 {
+        msg![env; font pointSize]
+    } else {
+        size
+    };
+    let new: id = msg![env; font fontWithSize:size];
+    retain(env, new)
+}
+
+fn CTFontGetSize(env: &mut Environment, font: CTFontRef) -> CGFloat {
+    msg![env; font pointSize]
+}
+
+fn CTFontGetAscent(env: &mut Environment, font: CTFontRef) -> CGFloat {
+    msg![env; font ascender]
+}
+fn CTFontGetDescent(env: &mut Environment, font: CTFontRef) -> CGFloat {
+    // Core Text's descent is positive, unlike UIKit's descender.
+    let descender: CGFloat = msg![env; font descender];
+    -descender
+}
+fn CTFontGetLeading(env: &mut Environment, font: CTFontRef) -> CGFloat {
+    msg![env; font leading]
+}
+fn CTFontGetCapHeight(env: &mut Environment, font: CTFontRef) -> CGFloat {
+    msg![env; font capHeight]
+}
+fn CTFontGetXHeight(env: &mut Environment, font: CTFontRef) -> CGFloat {
+    msg![env; font xHeight]
+}
+
+fn CTFontCopyPostScriptName(env: &mut Environment, font: CTFontRef) -> CFStringRef {
+    let name: id = msg![env; font fontName];
+    retain(env, name)
+}
+fn CTFontCopyFamilyName(env: &mut Environment, font: CTFontRef) -> CFStringRef {
+    let name: id = msg![env; font familyName];
+    retain(env, name)
+}
+fn CTFontCopyFullName(env: &mut Environment, font: CTFontRef) -> CFStringRef {
+    // TODO: The full name is usually more readable than the PostScript name.
+    let name: id = msg![env; font fontName];
+    retain(env, name)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CTFontCreateWithName(_, _, _)),
+    export_c_func!(CTFontCreateCopyWithAttributes(_, _, _, _)),
+    export_c_func!(CTFontGetSize(_)),
+    export_c_func!(CTFontGetAscent(_)),
+    export_c_func!(CTFontGetDescent(_)),
+    export_c_func!(CTFontGetLeading(_)),
+    export_c_func!(CTFontGetCapHeight(_)),
+    export_c_func!(CTFontGetXHeight(_)),
+    export_c_func!(CTFontCopyPostScriptName(_)),
+    export_c_func!(CTFontCopyFamilyName(_)),
+    export_c_func!(CTFontCopyFullName(_)),
+];
@@ -0,0 +1,152 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CTFrame.h`
+
+use super::ct_line::{self, CTLineRef};
+use super::layout::{self, Line};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFIndex, CFRange, CFTypeRef};
+use crate::frameworks::core_graphics::cg_context::{CGContextRef, CGContextSetTextPosition};
+use crate::frameworks::core_graphics::cg_path::{CGPathRef, CGPathRelease, CGPathRetain};
+use crate::frameworks::core_graphics::{CGPoint, CGRect};
+use crate::frameworks::foundation::ns_array;
+use crate::mem::{GuestUSize, MutPtr};
+use crate::objc::{id, objc_classes, release, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CTFrame seems to be a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CTFrame: NSObject
+
+- (())dealloc {
+    let &CTFrameHostObject { path, lines_array, .. } = env.objc.borrow(this);
+    CGPathRelease(env, path);
+    release(env, lines_array);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+struct CTFrameHostObject {
+    path: CGPathRef,
+    /// The rectangle text is laid out in.
+    rect: CGRect,
+    range: CFRange,
+    visible_range: CFRange,
+    /// `CFArray` of `CTLine`s, returned by `CTFrameGetLines`.
+    lines_array: id,
+    /// The same lines, owned by `lines_array`.
+    lines: Vec<CTLineRef>,
+    /// Origin of each line, relative to the origin of `rect`.
+    origins: Vec<CGPoint>,
+}
+impl HostObject for CTFrameHostObject {}
+
+pub type CTFrameRef = CFTypeRef;
+
+/// Create a frame from lines laid out in a path. The path is retained.
+pub fn create(
+    env: &mut Environment,
+    path: CGPathRef,
+    rect: CGRect,
+    range: CFRange,
+    visible_range: CFRange,
+    lines: Vec<(Line, CGPoint)>,
+) -> CTFrameRef {
+    let (lines, origins): (Vec<CTLineRef>, Vec<CGPoint>) = lines
+        .into_iter()
+        .map(|(line, origin)| (ct_line::from_line(env, line), origin))
+        .unzip();
+    let lines_array = ns_array::from_vec(env, lines.clone());
+    CGPathRetain(env, path);
+
+    let host_object = CTFrameHostObject {
+        path,
+        rect,
+        range,
+        visible_range,
+        lines_array,
+        lines,
+        origins,
+    };
+    let isa = env.objc.get_known_class("_touchHLE_CTFrame", &mut env.mem);
+    env.objc
+        .alloc_object(isa, Box::new(host_object), &mut env.mem)
+}
+
+fn borrow_frame(env: &Environment, frame: CTFrameRef) -> &CTFrameHostObject {
+    env.objc.borrow(frame)
+}
+
+/// Draw each line of a frame at its origin. This leaves the text position at
+/// the origin of the last line.
+fn CTFrameDraw(env: &mut Environment, frame: CTFrameRef, context: CGContextRef) {
+    let host_object = borrow_frame(env, frame);
+    let rect = host_object.rect;
+    let lines: Vec<(CTLineRef, CGPoint)> = host_object
+        .lines
+        .iter()
+        .copied()
+        .zip(host_object.origins.iter().copied())
+        .collect();
+
+    for (line, origin) in lines {
+        CGContextSetTextPosition(
+            env,
+            context,
+            rect.origin.x + origin.x,
+            rect.origin.y + origin.y,
+        );
+        let line = ct_line::borrow_line(env, line).clone();
+        layout::draw_line(env, context, &line);
+    }
+}
+
+fn CTFrameGetLines(env: &mut Environment, frame: CTFrameRef) -> id {
+    borrow_frame(env, frame).lines_array
+}
+
+fn CTFrameGetLineOrigins(
+    env: &mut Environment,
+    frame: CTFrameRef,
+    range: CFRange,
+    origins: MutPtr<CGPoint>,
+) {
+    let host_object = borrow_frame(env, frame);
+    let range = layout::resolve_range(range, host_object.origins.len() as CFIndex);
+    let selected = host_object.origins[range.start as usize..range.end as usize].to_vec();
+    for (i, origin) in selected.into_iter().enumerate() {
+        env.mem.write(origins + i as GuestUSize, origin);
+    }
+}
+
+fn CTFrameGetStringRange(env: &mut Environment, frame: CTFrameRef) -> CFRange {
+    borrow_frame(env, frame).range
+}
+
+fn CTFrameGetVisibleStringRange(env: &mut Environment, frame: CTFrameRef) -> CFRange {
+    borrow_frame(env, frame).visible_range
+}
+
+fn CTFrameGetPath(env: &mut Environment, frame: CTFrameRef) -> CGPathRef {
+    borrow_frame(env, frame).path
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CTFrameDraw(_, _)),
+    export_c_func!(CTFrameGetLines(_)),
+    export_c_func!(CTFrameGetLineOrigins(_, _, _)),
+    export_c_func!(CTFrameGetStringRange(_)),
+    export_c_func!(CTFrameGetVisibleStringRange(_)),
+    export_c_func!(CTFrameGetPath(_)),
+];
@@ -0,0 +1,189 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CTFramesetter.h`
+
+use super::ct_frame::{self, CTFrameRef};
+use super::layout::{self, Line};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFIndex, CFRange, CFTypeRef};
+use crate::frameworks::core_graphics::cg_path::{self, CGPathRef};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::mem::MutPtr;
+use crate::objc::{id, msg, objc_classes, release, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CTFramesetter seems to be a CFType-based type, but in our implementation
+// those are just Objective-C types, so we need a class for it, but its name is
+// not visible anywhere.
+@implementation _touchHLE_CTFramesetter: NSObject
+
+- (())dealloc {
+    let string = env.objc.borrow::<CTFramesetterHostObject>(this).string;
+    release(env, string);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+struct CTFramesetterHostObject {
+    /// `CFAttributedStringRef`, an immutable copy of the original.
+    string: id,
+}
+impl HostObject for CTFramesetterHostObject {}
+
+pub type CTFramesetterRef = CFTypeRef;
+
+/// Lines laid out by [lay_out_frame].
+struct FrameLayout {
+    /// Each line and its origin, with y pointing down from the top of the
+    /// frame.
+    lines: Vec<(Line, CGPoint)>,
+    range: CFRange,
+    visible_range: CFRange,
+    /// Height from the top of the frame to the bottom of the last line.
+    used_height: CGFloat,
+}
+
+/// Lay out text from the top of a frame downwards, stopping at the first
+/// line that doesn't fit.
+fn lay_out_frame(
+    env: &mut Environment,
+    framesetter: CTFramesetterRef,
+    range: CFRange,
+    size: CGSize,
+) -> FrameLayout {
+    let string = env
+        .objc
+        .borrow::<CTFramesetterHostObject>(framesetter)
+        .string;
+    let chars = layout::chars_from_attributed_string(env, string, range);
+    let range_start = match chars.first() {
+        Some(first) => first.location,
+        None => range.location,
+    };
+
+    let mut lines = Vec::new();
+    let mut y = 0.0;
+    let mut used_height = 0.0;
+    let mut visible_end = range_start;
+    'paragraphs: for (i, paragraph) in layout::paragraphs(&chars).into_iter().enumerate() {
+        let style = paragraph[0].paragraph_style;
+        if i != 0 {
+            y += style.paragraph_spacing_before;
+        }
+        for line_range in layout::break_paragraph(env, paragraph, size.width) {
+            let line = layout::lay_out_line(env, &paragraph[line_range]);
+            let baseline = y + line.ascent;
+            if baseline + line.descent > size.height {
+                break 'paragraphs;
+            }
+            let x = layout::alignment_offset(&line, &style, size.width);
+            y = baseline + line.descent + line.leading + style.line_spacing;
+            used_height = baseline + line.descent;
+            visible_end = line.range.location + line.range.length;
+            lines.push((line, CGPoint { x, y: baseline }));
+        }
+        y += style.paragraph_spacing;
+    }
+
+    let range_end = match chars.last() {
+        Some(last) => last.location + last.c.len_utf16() as CFIndex,
+        None => range_start,
+    };
+    FrameLayout {
+        lines,
+        range: CFRange {
+            location: range_start,
+            length: range_end - range_start,
+        },
+        visible_range: CFRange {
+            location: range_start,
+            length: visible_end - range_start,
+        },
+        used_height,
+    }
+}
+
+fn CTFramesetterCreateWithAttributedString(
+    env: &mut Environment,
+    string: id, // CFAttributedStringRef
+) -> CTFramesetterRef {
+    let string: id = msg![env; string copy];
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CTFramesetter", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CTFramesetterHostObject { string }),
+        &mut env.mem,
+    )
+}
+
+fn CTFramesetterCreateFrame(
+    env: &mut Environment,
+    framesetter: CTFramesetterRef,
+    range: CFRange,
+    path: CGPathRef,
+    _frame_attributes: id, // CFDictionaryRef
+) -> CTFrameRef {
+    // TODO: Non-rectangular paths. Text is laid out in the bounding box.
+    let rect = cg_path::borrow_path(env, path)
+        .bounding_box()
+        .unwrap_or(CGRect {
+            origin: CGPoint { x: 0.0, y: 0.0 },
+            size: CGSize {
+                width: 0.0,
+                height: 0.0,
+            },
+        });
+    let frame = lay_out_frame(env, framesetter, range, rect.size);
+    // Core Text's y axis points up, so the first line is at the top.
+    let lines = frame
+        .lines
+        .into_iter()
+        .map(|(line, origin)| {
+            let origin = CGPoint {
+                x: origin.x,
+                y: rect.size.height - origin.y,
+            };
+            (line, origin)
+        })
+        .collect();
+    ct_frame::create(env, path, rect, frame.range, frame.visible_range, lines)
+}
+
+fn CTFramesetterSuggestFrameSizeWithConstraints(
+    env: &mut Environment,
+    framesetter: CTFramesetterRef,
+    range: CFRange,
+    _frame_attributes: id, // CFDictionaryRef
+    constraints: CGSize,
+    fit_range: MutPtr<CFRange>,
+) -> CGSize {
+    let frame = lay_out_frame(env, framesetter, range, constraints);
+    if !fit_range.is_null() {
+        env.mem.write(fit_range, frame.visible_range);
+    }
+    let width = frame.lines.iter().fold(0.0, |widest: CGFloat, (line, _)| {
+        widest.max(line.width - line.trailing_whitespace_width)
+    });
+    CGSize {
+        width: width.ceil(),
+        height: frame.used_height.ceil(),
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CTFramesetterCreateWithAttributedString(_)),
+    export_c_func!(CTFramesetterCreateFrame(_, _, _, _)),
+    export_c_func!(CTFramesetterSuggestFrameSizeWithConstraints(_, _, _, _, _)),
+];
@@ -0,0 +1,120 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CTLine.h`
+
+use super::layout::{self, Line};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFIndex, CFRange, CFTypeRef};
+use crate::frameworks::core_graphics::cg_context::CGContextRef;
+use crate::frameworks::core_graphics::CGFloat;
+use crate::mem::MutPtr;
+use crate::objc::{id, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CTLine seems to be a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CTLine: NSObject
+@end
+
+};
+
+struct CTLineHostObject {
+    line: Line,
+}
+impl HostObject for CTLineHostObject {}
+
+pub type CTLineRef = CFTypeRef;
+
+/// Create a `CTLine` from a line laid out by host code.
+pub fn from_line(env: &mut Environment, line: Line) -> CTLineRef {
+    let isa = env.objc.get_known_class("_touchHLE_CTLine", &mut env.mem);
+    env.objc
+        .alloc_object(isa, Box::new(CTLineHostObject { line }), &mut env.mem)
+}
+
+/// Borrow the host representation of a `CTLine`.
+pub fn borrow_line(env: &Environment, line: CTLineRef) -> &Line {
+    &env.objc.borrow::<CTLineHostObject>(line).line
+}
+
+fn CTLineCreateWithAttributedString(
+    env: &mut Environment,
+    string: id, // CFAttributedStringRef
+) -> CTLineRef {
+    let range = CFRange {
+        location: 0,
+        length: 0,
+    };
+    let chars = layout::chars_from_attributed_string(env, string, range);
+    let line = layout::lay_out_line(env, &chars);
+    from_line(env, line)
+}
+
+/// Draw a line with its baseline starting at the text position.
+fn CTLineDraw(env: &mut Environment, line: CTLineRef, context: CGContextRef) {
+    let line = borrow_line(env, line).clone();
+    layout::draw_line(env, context, &line);
+}
+
+fn CTLineGetTypographicBounds(
+    env: &mut Environment,
+    line: CTLineRef,
+    ascent: MutPtr<CGFloat>,
+    descent: MutPtr<CGFloat>,
+    leading: MutPtr<CGFloat>,
+) -> f64 {
+    let line = borrow_line(env, line).clone();
+    for (ptr, value) in [
+        (ascent, line.ascent),
+        (descent, line.descent),
+        (leading, line.leading),
+    ] {
+        if !ptr.is_null() {
+            env.mem.write(ptr, value);
+        }
+    }
+    line.width.into()
+}
+
+fn CTLineGetTrailingWhitespaceWidth(env: &mut Environment, line: CTLineRef) -> f64 {
+    borrow_line(env, line).trailing_whitespace_width.into()
+}
+
+fn CTLineGetStringRange(env: &mut Environment, line: CTLineRef) -> CFRange {
+    borrow_line(env, line).range
+}
+
+fn CTLineGetGlyphCount(env: &mut Environment, line: CTLineRef) -> CFIndex {
+    // TODO: This is really the number of UTF-16 code units.
+    borrow_line(env, line).range.length
+}
+
+fn CTLineGetPenOffsetForFlush(
+    env: &mut Environment,
+    line: CTLineRef,
+    flush_factor: CGFloat,
+    flush_width: f64,
+) -> f64 {
+    let line = borrow_line(env, line);
+    let free = flush_width - f64::from(line.width - line.trailing_whitespace_width);
+    let flush_factor = f64::from(flush_factor.clamp(0.0, 1.0));
+    (free * flush_factor).max(0.0)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CTLineCreateWithAttributedString(_)),
+    export_c_func!(CTLineDraw(_, _)),
+    export_c_func!(CTLineGetTypographicBounds(_, _, _, _)),
+    export_c_func!(CTLineGetTrailingWhitespaceWidth(_)),
+    export_c_func!(CTLineGetStringRange(_)),
+    export_c_func!(CTLineGetGlyphCount(_)),
+    export_c_func!(CTLineGetPenOffsetForFlush(_, _, _)),
+];
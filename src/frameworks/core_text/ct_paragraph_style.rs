@@ -0,0 +1,177 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CTParagraphStyle.h`
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::CFTypeRef;
+use crate::frameworks::core_graphics::CGFloat;
+use crate::mem::{guest_size_of, ConstPtr, ConstVoidPtr, GuestUSize, MutVoidPtr, SafeRead};
+use crate::objc::{objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CTParagraphStyle seems to be a CFType-based type, but in our implementation
+// those are just Objective-C types, so we need a class for it, but its name is
+// not visible anywhere.
+@implementation _touchHLE_CTParagraphStyle: NSObject
+@end
+
+};
+
+pub type CTParagraphStyleRef = CFTypeRef;
+
+pub type CTTextAlignment = u8;
+pub const kCTLeftTextAlignment: CTTextAlignment = 0;
+pub const kCTRightTextAlignment: CTTextAlignment = 1;
+pub const kCTCenterTextAlignment: CTTextAlignment = 2;
+pub const kCTJustifiedTextAlignment: CTTextAlignment = 3;
+pub const kCTNaturalTextAlignment: CTTextAlignment = 4;
+
+pub type CTLineBreakMode = u8;
+pub const kCTLineBreakByWordWrapping: CTLineBreakMode = 0;
+pub const kCTLineBreakByCharWrapping: CTLineBreakMode = 1;
+pub const kCTLineBreakByClipping: CTLineBreakMode = 2;
+pub const kCTLineBreakByTruncatingHead: CTLineBreakMode = 3;
+pub const kCTLineBreakByTruncatingTail: CTLineBreakMode = 4;
+pub const kCTLineBreakByTruncatingMiddle: CTLineBreakMode = 5;
+
+pub type CTParagraphStyleSpecifier = u32;
+pub const kCTParagraphStyleSpecifierAlignment: CTParagraphStyleSpecifier = 0;
+pub const kCTParagraphStyleSpecifierLineBreakMode: CTParagraphStyleSpecifier = 6;
+pub const kCTParagraphStyleSpecifierLineSpacing: CTParagraphStyleSpecifier = 10;
+pub const kCTParagraphStyleSpecifierParagraphSpacing: CTParagraphStyleSpecifier = 11;
+pub const kCTParagraphStyleSpecifierParagraphSpacingBefore: CTParagraphStyleSpecifier = 12;
+
+#[allow(dead_code)]
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct CTParagraphStyleSetting {
+    spec: CTParagraphStyleSpecifier,
+    value_size: GuestUSize,
+    value: ConstVoidPtr,
+}
+unsafe impl SafeRead for CTParagraphStyleSetting {}
+
+/// The settings of a paragraph style that affect layout. Anything not listed
+/// here is ignored.
+#[derive(Copy, Clone, Debug)]
+pub struct ParagraphStyle {
+    pub alignment: CTTextAlignment,
+    pub line_break_mode: CTLineBreakMode,
+    /// Extra space between lines.
+    pub line_spacing: CGFloat,
+    /// Extra space after the paragraph.
+    pub paragraph_spacing: CGFloat,
+    /// Extra space before the paragraph.
+    pub paragraph_spacing_before: CGFloat,
+}
+impl Default for ParagraphStyle {
+    fn default() -> Self {
+        ParagraphStyle {
+            alignment: kCTNaturalTextAlignment,
+            line_break_mode: kCTLineBreakByWordWrapping,
+            line_spacing: 0.0,
+            paragraph_spacing: 0.0,
+            paragraph_spacing_before: 0.0,
+        }
+    }
+}
+
+struct CTParagraphStyleHostObject {
+    style: ParagraphStyle,
+}
+impl HostObject for CTParagraphStyleHostObject {}
+
+/// Shortcut for host code, gets the layout settings of a paragraph style.
+pub fn get_style(env: &mut Environment, style: CTParagraphStyleRef) -> ParagraphStyle {
+    env.objc.borrow::<CTParagraphStyleHostObject>(style).style
+}
+
+fn CTParagraphStyleCreate(
+    env: &mut Environment,
+    settings: ConstPtr<CTParagraphStyleSetting>,
+    count: GuestUSize,
+) -> CTParagraphStyleRef {
+    let mut style = ParagraphStyle::default();
+    for i in 0..count {
+        let setting = env.mem.read(settings + i);
+        let spec = setting.spec;
+        let value_size = setting.value_size;
+        let value = setting.value;
+        match spec {
+            kCTParagraphStyleSpecifierAlignment => {
+                assert_eq!(value_size, guest_size_of::<CTTextAlignment>());
+                style.alignment = env.mem.read(value.cast());
+            }
+            kCTParagraphStyleSpecifierLineBreakMode => {
+                assert_eq!(value_size, guest_size_of::<CTLineBreakMode>());
+                style.line_break_mode = env.mem.read(value.cast());
+            }
+            kCTParagraphStyleSpecifierLineSpacing => {
+                assert_eq!(value_size, guest_size_of::<CGFloat>());
+                style.line_spacing = env.mem.read(value.cast());
+            }
+            kCTParagraphStyleSpecifierParagraphSpacing => {
+                assert_eq!(value_size, guest_size_of::<CGFloat>());
+                style.paragraph_spacing = env.mem.read(value.cast());
+            }
+            kCTParagraphStyleSpecifierParagraphSpacingBefore => {
+                assert_eq!(value_size, guest_size_of::<CGFloat>());
+                style.paragraph_spacing_before = env.mem.read(value.cast());
+            }
+            _ => log!("Warning: ignoring paragraph style specifier {}", spec),
+        }
+    }
+
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CTParagraphStyle", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CTParagraphStyleHostObject { style }),
+        &mut env.mem,
+    )
+}
+
+fn CTParagraphStyleGetValueForSpecifier(
+    env: &mut Environment,
+    style: CTParagraphStyleRef,
+    spec: CTParagraphStyleSpecifier,
+    value_size: GuestUSize,
+    value_buffer: MutVoidPtr,
+) -> bool {
+    let style = get_style(env, style);
+    let value = match spec {
+        kCTParagraphStyleSpecifierAlignment => {
+            assert_eq!(value_size, guest_size_of::<CTTextAlignment>());
+            env.mem.write(value_buffer.cast(), style.alignment);
+            return true;
+        }
+        kCTParagraphStyleSpecifierLineBreakMode => {
+            assert_eq!(value_size, guest_size_of::<CTLineBreakMode>());
+            env.mem.write(value_buffer.cast(), style.line_break_mode);
+            return true;
+        }
+        kCTParagraphStyleSpecifierLineSpacing => style.line_spacing,
+        kCTParagraphStyleSpecifierParagraphSpacing => style.paragraph_spacing,
+        kCTParagraphStyleSpecifierParagraphSpacingBefore => style.paragraph_spacing_before,
+        _ => {
+            log!("Warning: unsupported paragraph style specifier {}", spec);
+            return false;
+        }
+    };
+    assert_eq!(value_size, guest_size_of::<CGFloat>());
+    env.mem.write(value_buffer.cast(), value);
+    true
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CTParagraphStyleCreate(_, _)),
+    export_c_func!(CTParagraphStyleGetValueForSpecifier(_, _, _, _)),
+];
@@ -0,0 +1,36 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CTStringAttributes.h`
+
+use crate::dyld::{ConstantExports, HostConstant};
+
+/// Value is a `CTFont`. This is the same as `NSFontAttributeName`.
+pub const kCTFontAttributeName: &str = "NSFont";
+/// Value is a `CGColor`.
+pub const kCTForegroundColorAttributeName: &str = "CTForegroundColor";
+/// Value is a `CFBoolean`.
+pub const kCTForegroundColorFromContextAttributeName: &str = "CTForegroundColorFromContext";
+/// Value is a `CTParagraphStyle`.
+pub const kCTParagraphStyleAttributeName: &str = "NSParagraphStyle";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCTFontAttributeName",
+        HostConstant::NSString(kCTFontAttributeName),
+    ),
+    (
+        "_kCTForegroundColorAttributeName",
+        HostConstant::NSString(kCTForegroundColorAttributeName),
+    ),
+    (
+        "_kCTForegroundColorFromContextAttributeName",
+        HostConstant::NSString(kCTForegroundColorFromContextAttributeName),
+    ),
+    (
+        "_kCTParagraphStyleAttributeName",
+        HostConstant::NSString(kCTParagraphStyleAttributeName),
+    ),
+];
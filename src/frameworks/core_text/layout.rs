@@ -0,0 +1,349 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Line breaking, measuring and drawing shared by `CTLine`, `CTFramesetter`
+//! and `CTFrame`.
+
+use super::ct_paragraph_style::{
+    self, kCTCenterTextAlignment, kCTJustifiedTextAlignment, kCTLeftTextAlignment,
+    kCTLineBreakByCharWrapping, kCTLineBreakByClipping, kCTLineBreakByTruncatingHead,
+    kCTLineBreakByTruncatingMiddle, kCTLineBreakByTruncatingTail, kCTLineBreakByWordWrapping,
+    kCTNaturalTextAlignment, kCTRightTextAlignment, ParagraphStyle,
+};
+use super::ct_string_attributes::{
+    kCTFontAttributeName, kCTForegroundColorAttributeName,
+    kCTForegroundColorFromContextAttributeName, kCTParagraphStyleAttributeName,
+};
+use crate::font::OutlineSegment;
+use crate::frameworks::core_foundation::{CFIndex, CFRange};
+use crate::frameworks::core_graphics::cg_context::{
+    self, CGContextGetTextMatrix, CGContextRef, CGContextRestoreGState, CGContextSaveGState,
+    CGContextSetRGBFillColor,
+};
+use crate::frameworks::core_graphics::cg_path::Path;
+use crate::frameworks::core_graphics::{cg_color, CGFloat, CGPoint};
+use crate::frameworks::foundation::ns_attributed_string;
+use crate::frameworks::uikit::ui_font;
+use crate::objc::{id, msg, msg_class, nil};
+use crate::Environment;
+use std::ops::Range;
+
+/// A character of an attributed string, with the attributes that matter for
+/// layout.
+pub struct Char {
+    pub c: char,
+    /// UTF-16 index in the attributed string.
+    pub location: CFIndex,
+    /// `CTFont*`
+    font: id,
+    /// Non-premultiplied RGBA, or [None] to use the context's fill color.
+    color: Option<(CGFloat, CGFloat, CGFloat, CGFloat)>,
+    pub paragraph_style: ParagraphStyle,
+}
+
+/// Part of a line that has a single font and color.
+#[derive(Clone)]
+pub struct GlyphRun {
+    pub width: CGFloat,
+    /// Outline in points, with y pointing up and the origin at the start of
+    /// the baseline.
+    outline: Vec<OutlineSegment>,
+    color: Option<(CGFloat, CGFloat, CGFloat, CGFloat)>,
+}
+
+/// A laid-out line of text.
+#[derive(Clone)]
+pub struct Line {
+    /// UTF-16 range in the attributed string.
+    pub range: CFRange,
+    pub runs: Vec<GlyphRun>,
+    pub width: CGFloat,
+    pub ascent: CGFloat,
+    /// Positive, unlike [crate::font::FontMetrics::descender].
+    pub descent: CGFloat,
+    pub leading: CGFloat,
+    pub trailing_whitespace_width: CGFloat,
+}
+
+/// Resolve a `CFRange` for a string of some length, where a length of zero
+/// means the rest of the string.
+pub fn resolve_range(range: CFRange, length: CFIndex) -> Range<CFIndex> {
+    let start = range.location.clamp(0, length);
+    if range.length == 0 {
+        start..length
+    } else {
+        start..(start + range.length).clamp(start, length)
+    }
+}
+
+/// Get the characters of an attributed string within a range.
+pub fn chars_from_attributed_string(
+    env: &mut Environment,
+    string: id,
+    range: CFRange,
+) -> Vec<Char> {
+    let runs = ns_attributed_string::attribute_runs(
+        env,
+        string,
+        &[
+            kCTFontAttributeName,
+            kCTForegroundColorAttributeName,
+            kCTForegroundColorFromContextAttributeName,
+            kCTParagraphStyleAttributeName,
+        ],
+    );
+    let length = runs.iter().map(|(text, _)| text.len() as CFIndex).sum();
+    let range = resolve_range(range, length);
+
+    // Apple's default is 12pt Helvetica in black.
+    let default_font: id = msg_class![env; UIFont systemFontOfSize:12.0f32];
+
+    let mut chars = Vec::new();
+    let mut location = 0;
+    for (text, values) in runs {
+        let &[font, color, color_from_context, paragraph_style] = &values[..] else {
+            unreachable!();
+        };
+        let font = if font == nil { default_font } else { font };
+        let color_from_context = color_from_context != nil && {
+            let value: bool = msg![env; color_from_context boolValue];
+            value
+        };
+        let color = if color_from_context {
+            None
+        } else if color == nil {
+            Some((0.0, 0.0, 0.0, 1.0))
+        } else {
+            Some(cg_color::get_rgba(env, color))
+        };
+        let paragraph_style = if paragraph_style == nil {
+            ParagraphStyle::default()
+        } else {
+            ct_paragraph_style::get_style(env, paragraph_style)
+        };
+
+        for c in char::decode_utf16(text.iter().copied()) {
+            let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+            if range.contains(&location) {
+                chars.push(Char {
+                    c,
+                    location,
+                    font,
+                    color,
+                    paragraph_style,
+                });
+            }
+            location += c.len_utf16() as CFIndex;
+        }
+    }
+    chars
+}
+
+fn is_paragraph_separator(c: char) -> bool {
+    matches!(c, '\n' | '\u{2029}')
+}
+
+/// Split characters into paragraphs. Each paragraph includes its separator.
+pub fn paragraphs(chars: &[Char]) -> Vec<&[Char]> {
+    let mut paragraphs = Vec::new();
+    let mut start = 0;
+    for (i, c) in chars.iter().enumerate() {
+        if is_paragraph_separator(c.c) {
+            paragraphs.push(&chars[start..=i]);
+            start = i + 1;
+        }
+    }
+    if start < chars.len() {
+        paragraphs.push(&chars[start..]);
+    }
+    paragraphs
+}
+
+/// Split characters into pieces with the same font and color, as strings that
+/// can be drawn (without line breaks).
+fn pieces(chars: &[Char]) -> Vec<(&Char, String)> {
+    let mut pieces: Vec<(&Char, String)> = Vec::new();
+    for c in chars {
+        if is_paragraph_separator(c.c) || c.c == '\r' {
+            continue;
+        }
+        match pieces.last_mut() {
+            Some((first, text)) if first.font == c.font && first.color == c.color => text.push(c.c),
+            _ => pieces.push((c, c.c.to_string())),
+        }
+    }
+    pieces
+}
+
+/// Width of some characters, ignoring any trailing whitespace.
+fn visible_width(env: &mut Environment, chars: &[Char]) -> CGFloat {
+    let end = chars
+        .iter()
+        .rposition(|c| !c.c.is_whitespace())
+        .map_or(0, |i| i + 1);
+    pieces(&chars[..end])
+        .into_iter()
+        .map(|(first, text)| ui_font::text_width(env, first.font, &text))
+        .sum()
+}
+
+/// Break a paragraph into lines that fit within a width, according to its
+/// line break mode. Returns the range of characters in each line.
+pub fn break_paragraph(
+    env: &mut Environment,
+    paragraph: &[Char],
+    width: CGFloat,
+) -> Vec<Range<usize>> {
+    if paragraph.is_empty() {
+        return Vec::new();
+    }
+    // Points at which the paragraph could be broken, in ascending order.
+    let break_points: Vec<usize> = match paragraph[0].paragraph_style.line_break_mode {
+        kCTLineBreakByWordWrapping => (1..paragraph.len())
+            .filter(|&i| paragraph[i - 1].c.is_whitespace() && !paragraph[i].c.is_whitespace())
+            .chain([paragraph.len()])
+            .collect(),
+        kCTLineBreakByCharWrapping => (1..=paragraph.len()).collect(),
+        // TODO: truncation. These modes only break lines at paragraph
+        // separators, so the text is currently just clipped.
+        kCTLineBreakByClipping
+        | kCTLineBreakByTruncatingHead
+        | kCTLineBreakByTruncatingTail
+        | kCTLineBreakByTruncatingMiddle => return vec![0..paragraph.len()],
+        mode => unimplemented!("TODO: line break mode {}", mode),
+    };
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while start < paragraph.len() {
+        let mut end = None;
+        for &break_point in break_points.iter().filter(|&&i| i > start) {
+            if visible_width(env, &paragraph[start..break_point]) > width {
+                break;
+            }
+            end = Some(break_point);
+        }
+        let end = end.unwrap_or_else(|| {
+            // Not even one word fits, so it has to be broken between
+            // characters. At least one character is always included.
+            let mut end = start + 1;
+            while end < paragraph.len()
+                && !paragraph[end].c.is_whitespace()
+                && visible_width(env, &paragraph[start..end + 1]) <= width
+            {
+                end += 1;
+            }
+            end
+        });
+        lines.push(start..end);
+        start = end;
+    }
+    lines
+}
+
+/// Measure characters as a single line and get their outlines.
+pub fn lay_out_line(env: &mut Environment, chars: &[Char]) -> Line {
+    let range = match (chars.first(), chars.last()) {
+        (Some(first), Some(last)) => CFRange {
+            location: first.location,
+            length: last.location + last.c.len_utf16() as CFIndex - first.location,
+        },
+        _ => CFRange {
+            location: 0,
+            length: 0,
+        },
+    };
+
+    let mut runs = Vec::new();
+    let (mut ascent, mut descent, mut leading): (CGFloat, CGFloat, CGFloat) = (0.0, 0.0, 0.0);
+    for (first, text) in pieces(chars) {
+        let metrics = ui_font::text_metrics(env, first.font, &text);
+        ascent = ascent.max(metrics.ascender);
+        descent = descent.max(-metrics.descender);
+        leading = leading.max(metrics.leading);
+        let outline = ui_font::text_outline(env, first.font, &text)
+            .into_iter()
+            .map(|segment| match segment {
+                OutlineSegment::MoveTo(x, y) => OutlineSegment::MoveTo(x, -y),
+                OutlineSegment::LineTo(x, y) => OutlineSegment::LineTo(x, -y),
+                OutlineSegment::QuadTo(x1, y1, x, y) => OutlineSegment::QuadTo(x1, -y1, x, -y),
+                OutlineSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+                    OutlineSegment::CurveTo(x1, -y1, x2, -y2, x, -y)
+                }
+                OutlineSegment::Close => OutlineSegment::Close,
+            })
+            .collect();
+        runs.push(GlyphRun {
+            width: ui_font::text_width(env, first.font, &text),
+            outline,
+            color: first.color,
+        });
+    }
+    // An empty line still has the height of its font.
+    if runs.is_empty() {
+        if let Some(first) = chars.first() {
+            let metrics = ui_font::text_metrics(env, first.font, "");
+            ascent = metrics.ascender;
+            descent = -metrics.descender;
+            leading = metrics.leading;
+        }
+    }
+
+    let width = runs.iter().map(|run| run.width).sum();
+    let trailing_whitespace_width = width - visible_width(env, chars);
+    Line {
+        range,
+        runs,
+        width,
+        ascent,
+        descent,
+        leading,
+        trailing_whitespace_width,
+    }
+}
+
+/// How far a line should be moved along to align it within a width.
+pub fn alignment_offset(line: &Line, style: &ParagraphStyle, width: CGFloat) -> CGFloat {
+    let free = width - (line.width - line.trailing_whitespace_width);
+    match style.alignment {
+        kCTLeftTextAlignment | kCTNaturalTextAlignment => 0.0,
+        kCTRightTextAlignment => free,
+        kCTCenterTextAlignment => free / 2.0,
+        // TODO: justification
+        kCTJustifiedTextAlignment => 0.0,
+        alignment => unimplemented!("TODO: text alignment {}", alignment),
+    }
+}
+
+/// Draw a line at the text position, transformed by the text matrix.
+pub fn draw_line(env: &mut Environment, context: CGContextRef, line: &Line) {
+    let text_matrix = CGContextGetTextMatrix(env, context);
+    let mut run_x = 0.0;
+    for run in &line.runs {
+        let p = |x: CGFloat, y: CGFloat| CGPoint { x: run_x + x, y };
+        let mut path = Path::default();
+        for &segment in &run.outline {
+            match segment {
+                OutlineSegment::MoveTo(x, y) => path.move_to(p(x, y)),
+                OutlineSegment::LineTo(x, y) => path.line_to(p(x, y)),
+                OutlineSegment::QuadTo(x1, y1, x, y) => path.quad_curve_to(p(x1, y1), p(x, y)),
+                OutlineSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+                    path.curve_to(p(x1, y1), p(x2, y2), p(x, y))
+                }
+                OutlineSegment::Close => path.close(),
+            }
+        }
+        let path = path.transformed(text_matrix);
+
+        CGContextSaveGState(env, context);
+        if let Some((r, g, b, a)) = run.color {
+            CGContextSetRGBFillColor(env, context, r, g, b, a);
+        }
+        cg_context::fill_path(env, context, &path);
+        CGContextRestoreGState(env, context);
+
+        run_x += run.width;
+    }
+}
@@ -11,36 +11,70 @@
 //! Being aware of this concept will make common types like `NSArray` and
 //! `NSString` easier to understand.
 
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg, GuestFunction};
+use crate::mem::{ConstVoidPtr, SafeRead};
+
 pub mod ns_array;
+pub mod ns_attributed_string;
 pub mod ns_autorelease_pool;
 pub mod ns_bundle;
+pub mod ns_cache;
+pub mod ns_calendar;
 pub mod ns_character_set;
 pub mod ns_coder;
 pub mod ns_data;
+pub mod ns_date;
+pub mod ns_date_components;
+pub mod ns_date_formatter;
 pub mod ns_dictionary;
+pub mod ns_error;
 pub mod ns_fast_enumeration;
 pub mod ns_file_manager;
+pub mod ns_hash_table;
+pub mod ns_index_path;
+pub mod ns_index_set;
 pub mod ns_keyed_unarchiver;
 pub mod ns_locale;
+pub mod ns_map_table;
+pub mod ns_notification;
+pub mod ns_notification_center;
 pub mod ns_null;
 pub mod ns_object;
+pub mod ns_pointer_array;
+pub mod ns_pointer_functions;
+pub mod ns_predicate;
 pub mod ns_process_info;
+pub mod ns_regular_expression;
 pub mod ns_run_loop;
 pub mod ns_set;
+pub mod ns_sort_descriptor;
+pub mod ns_stream;
 pub mod ns_string;
 pub mod ns_thread;
+pub mod ns_time_zone;
 pub mod ns_timer;
 pub mod ns_url;
+pub mod ns_url_request;
+pub mod ns_user_defaults;
+pub mod ns_uuid;
 pub mod ns_value;
 
 #[derive(Default)]
 pub struct State {
     ns_autorelease_pool: ns_autorelease_pool::State,
     ns_bundle: ns_bundle::State,
+    ns_cache: ns_cache::State,
+    ns_file_manager: ns_file_manager::State,
     ns_locale: ns_locale::State,
+    ns_notification_center: ns_notification_center::State,
     ns_null: ns_null::State,
+    ns_process_info: ns_process_info::State,
     ns_run_loop: ns_run_loop::State,
     ns_string: ns_string::State,
+    ns_time_zone: ns_time_zone::State,
+    ns_user_defaults: ns_user_defaults::State,
+    ns_uuid: ns_uuid::State,
+    ns_value: ns_value::State,
 }
 
 pub type NSInteger = i32;
@@ -49,6 +83,50 @@ pub type NSUInteger = u32;
 /// Number of seconds.
 pub type NSTimeInterval = f64;
 
+/// Used as an index or location to mean "not found" or "no such thing".
+pub const NSNotFound: NSInteger = NSInteger::MAX;
+
+/// `NSRange`, a range of indices (usually of UTF-16 code units in a string).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(C, packed)]
+pub struct NSRange {
+    pub location: NSUInteger,
+    pub length: NSUInteger,
+}
+unsafe impl SafeRead for NSRange {}
+impl_GuestRet_for_large_struct!(NSRange);
+impl GuestArg for NSRange {
+    const REG_COUNT: usize = 2;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        NSRange {
+            location: GuestArg::from_regs(&regs[0..1]),
+            length: GuestArg::from_regs(&regs[1..2]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.location.to_regs(&mut regs[0..1]);
+        self.length.to_regs(&mut regs[1..2]);
+    }
+}
+
+/// Layout of a block literal, enough to get at the function to invoke. The
+/// function's first argument is a pointer to the block literal.
+#[allow(dead_code)]
+#[repr(C, packed)]
+pub struct BlockLiteral {
+    isa: ConstVoidPtr,
+    flags: i32,
+    reserved: i32,
+    pub invoke: GuestFunction,
+}
+unsafe impl SafeRead for BlockLiteral {}
+
+pub type NSComparisonResult = NSInteger;
+pub const NSOrderedAscending: NSComparisonResult = -1;
+pub const NSOrderedSame: NSComparisonResult = 0;
+pub const NSOrderedDescending: NSComparisonResult = 1;
+
 /// Utility to help with implementing the `hash` method, which various classes
 /// in Foundation have to do.
 fn hash_helper<T: std::hash::Hash>(hashable: &T) -> NSUInteger {
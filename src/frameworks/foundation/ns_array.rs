@@ -5,9 +5,12 @@
  */
 //! The `NSArray` class cluster, including `NSMutableArray`.
 
-use super::{ns_keyed_unarchiver, NSUInteger};
-use crate::mem::MutVoidPtr;
-use crate::objc::{id, msg_class, objc_classes, release, retain, ClassExports, HostObject};
+use super::ns_fast_enumeration::{fast_enumeration_helper, NSFastEnumerationState};
+use super::{ns_keyed_unarchiver, ns_predicate, ns_sort_descriptor, NSUInteger};
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, objc_classes, release, retain, ClassExports, HostObject,
+};
 use crate::Environment;
 
 /// Belongs to _touchHLE_NSArray
@@ -40,6 +43,35 @@ pub const CLASSES: ClassExports = objc_classes! {
     retain(env, this)
 }
 
+- (id)filteredArrayUsingPredicate:(id)predicate { // NSPredicate*
+    let count: NSUInteger = msg![env; this count];
+    let mut objects = Vec::new();
+    for i in 0..count {
+        let object: id = msg![env; this objectAtIndex:i];
+        if ns_predicate::evaluate(env, predicate, object) {
+            objects.push(retain(env, object));
+        }
+    }
+    let array = from_vec(env, objects);
+    autorelease(env, array)
+}
+
+- (id)sortedArrayUsingDescriptors:(id)descriptors { // NSArray*
+    let count: NSUInteger = msg![env; this count];
+    let mut objects: Vec<id> = (0..count)
+        .map(|i| msg![env; this objectAtIndex:i])
+        .collect();
+    // This is a stable sort, like Apple's.
+    objects.sort_by(|&a, &b| {
+        ns_sort_descriptor::compare_with_descriptors(env, descriptors, a, b)
+    });
+    for &object in &objects {
+        retain(env, object);
+    }
+    let array = from_vec(env, objects);
+    autorelease(env, array)
+}
+
 @end
 
 // Our private subclass that is the single implementation of NSArray for the
@@ -97,6 +129,15 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<ArrayHostObject>(this).array[index as usize]
 }
 
+// NSFastEnumeration implementation
+- (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
+                                  objects:(MutPtr<id>)stackbuf
+                                    count:(NSUInteger)len {
+    // TODO: avoid copying the array on every call
+    let objects = env.objc.borrow::<ArrayHostObject>(this).array.clone();
+    fast_enumeration_helper(&mut env.mem, this, &objects, state, stackbuf, len)
+}
+
 @end
 
 };
@@ -0,0 +1,540 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSAttributedString` and `NSMutableAttributedString`.
+//!
+//! Unlike Apple's implementation, this is not a class cluster: both classes
+//! share the same host object.
+
+use super::ns_dictionary::{dict_from_keys_and_objects, DictionaryHostObject};
+use super::{ns_string, NSRange, NSUInteger};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::frameworks::uikit::{ui_color, ui_font};
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, Class, ClassExports,
+    HostObject,
+};
+use crate::Environment;
+
+// These are actually from UIKit (NSAttributedString.h there), but they are
+// only of use with NSAttributedString.
+pub const NSFontAttributeName: &str = "NSFont";
+pub const NSForegroundColorAttributeName: &str = "NSColor";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSFontAttributeName",
+        HostConstant::NSString(NSFontAttributeName),
+    ),
+    (
+        "_NSForegroundColorAttributeName",
+        HostConstant::NSString(NSForegroundColorAttributeName),
+    ),
+];
+
+/// A range of characters that share the same attributes.
+struct AttributeRun {
+    length: NSUInteger,
+    /// Strong references to both the keys and the values.
+    attributes: Vec<(id, id)>,
+}
+
+#[derive(Default)]
+struct AttributedStringHostObject {
+    /// UTF-16 code units.
+    text: Vec<u16>,
+    /// Covers the whole of `text`. Runs are never empty.
+    runs: Vec<AttributeRun>,
+}
+impl HostObject for AttributedStringHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSAttributedString: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::<AttributedStringHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let host_object = take_host_object(env, this);
+    release_runs(env, host_object.runs);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)initWithString:(id)string { // NSString*
+    msg![env; this initWithString:string attributes:nil]
+}
+- (id)initWithString:(id)string // NSString*
+          attributes:(id)attributes { // NSDictionary*
+    let mut text = Vec::new();
+    ns_string::for_each_code_unit(env, string, |_idx, c| text.push(c));
+    let attributes = attributes_from_dict(env, attributes);
+    let runs = if text.is_empty() {
+        release_attributes(env, attributes);
+        Vec::new()
+    } else {
+        vec![AttributeRun {
+            length: text.len() as NSUInteger,
+            attributes,
+        }]
+    };
+    let host_object = env.objc.borrow_mut::<AttributedStringHostObject>(this);
+    host_object.text = text;
+    host_object.runs = runs;
+    this
+}
+- (id)initWithAttributedString:(id)other { // NSAttributedString*
+    let length: NSUInteger = msg![env; other length];
+    let (text, runs) = copy_range(env, other, NSRange { location: 0, length });
+    let host_object = env.objc.borrow_mut::<AttributedStringHostObject>(this);
+    host_object.text = text;
+    host_object.runs = runs;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    let class: Class = msg![env; this class];
+    if class == env.objc.get_known_class("NSAttributedString", &mut env.mem) {
+        retain(env, this)
+    } else {
+        let new: id = msg_class![env; NSAttributedString alloc];
+        msg![env; new initWithAttributedString:this]
+    }
+}
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(MutVoidPtr)_zone {
+    let new: id = msg_class![env; NSMutableAttributedString alloc];
+    msg![env; new initWithAttributedString:this]
+}
+
+- (id)string {
+    let text = env.objc.borrow::<AttributedStringHostObject>(this).text.clone();
+    let string = ns_string::from_utf16_code_units(env, text);
+    autorelease(env, string)
+}
+- (NSUInteger)length {
+    env.objc.borrow::<AttributedStringHostObject>(this).text.len() as NSUInteger
+}
+
+- (id)attributesAtIndex:(NSUInteger)index
+         effectiveRange:(MutPtr<NSRange>)range { // NSDictionary*
+    let host_object = env.objc.borrow::<AttributedStringHostObject>(this);
+    let (run_idx, run_start) = find_run(host_object, index);
+    let run = &host_object.runs[run_idx];
+    let effective_range = NSRange {
+        location: run_start,
+        length: run.length,
+    };
+    let attributes = run.attributes.clone();
+    if !range.is_null() {
+        env.mem.write(range, effective_range);
+    }
+    let dict = dict_from_keys_and_objects(env, &attributes);
+    autorelease(env, dict)
+}
+- (id)attribute:(id)name // NSString*
+        atIndex:(NSUInteger)index
+ effectiveRange:(MutPtr<NSRange>)range {
+    let attributes: id = msg![env; this attributesAtIndex:index effectiveRange:range];
+    msg![env; attributes objectForKey:name]
+}
+
+- (id)attributedSubstringFromRange:(NSRange)range { // NSAttributedString*
+    let (text, runs) = copy_range(env, this, range);
+    let new: id = msg_class![env; NSAttributedString alloc];
+    let host_object = env.objc.borrow_mut::<AttributedStringHostObject>(new);
+    host_object.text = text;
+    host_object.runs = runs;
+    autorelease(env, new)
+}
+
+// These come from a category in UIKit (NSStringDrawing).
+// TODO: Implement categories so we can completely move the code to UIKit.
+- (CGSize)size {
+    let runs = text_runs(env, this);
+    ui_font::size_with_runs(env, &runs)
+}
+- (())drawAtPoint:(CGPoint)point {
+    let runs = text_runs(env, this);
+    ui_font::draw_runs(env, &runs, point);
+}
+- (())drawInRect:(CGRect)rect {
+    // TODO: wrapping and clipping
+    let runs = text_runs(env, this);
+    ui_font::draw_runs(env, &runs, rect.origin);
+}
+
+@end
+
+@implementation NSMutableAttributedString: NSAttributedString
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    let new: id = msg_class![env; NSAttributedString alloc];
+    msg![env; new initWithAttributedString:this]
+}
+
+- (())beginEditing {}
+- (())endEditing {}
+
+- (())setAttributedString:(id)other { // NSAttributedString*
+    let length: NSUInteger = msg![env; this length];
+    msg![env; this replaceCharactersInRange:(NSRange { location: 0, length })
+                       withAttributedString:other]
+}
+
+- (())replaceCharactersInRange:(NSRange)range
+                    withString:(id)string { // NSString*
+    let mut text = Vec::new();
+    ns_string::for_each_code_unit(env, string, |_idx, c| text.push(c));
+
+    // The new characters get the attributes of the first replaced character,
+    // or the preceding one if none are being replaced.
+    let host_object = env.objc.borrow::<AttributedStringHostObject>(this);
+    let attributes = if host_object.text.is_empty() {
+        Vec::new()
+    } else {
+        let index = if range.length == 0 {
+            range.location.saturating_sub(1)
+        } else {
+            range.location
+        };
+        let (run_idx, _) = find_run(host_object, index);
+        host_object.runs[run_idx].attributes.clone()
+    };
+    retain_attributes(env, &attributes);
+
+    let runs = if text.is_empty() {
+        release_attributes(env, attributes);
+        Vec::new()
+    } else {
+        vec![AttributeRun {
+            length: text.len() as NSUInteger,
+            attributes,
+        }]
+    };
+    replace_range(env, this, range, text, runs);
+}
+- (())replaceCharactersInRange:(NSRange)range
+          withAttributedString:(id)other { // NSAttributedString*
+    let length: NSUInteger = msg![env; other length];
+    let (text, runs) = copy_range(env, other, NSRange { location: 0, length });
+    replace_range(env, this, range, text, runs);
+}
+- (())insertAttributedString:(id)other // NSAttributedString*
+                     atIndex:(NSUInteger)index {
+    msg![env; this replaceCharactersInRange:(NSRange { location: index, length: 0 })
+                       withAttributedString:other]
+}
+- (())appendAttributedString:(id)other { // NSAttributedString*
+    let length: NSUInteger = msg![env; this length];
+    msg![env; this replaceCharactersInRange:(NSRange { location: length, length: 0 })
+                       withAttributedString:other]
+}
+- (())deleteCharactersInRange:(NSRange)range {
+    replace_range(env, this, range, Vec::new(), Vec::new());
+}
+
+- (())setAttributes:(id)attributes // NSDictionary*
+              range:(NSRange)range {
+    let attributes = attributes_from_dict(env, attributes);
+    modify_attributes(env, this, range, |env, run_attributes| {
+        release_attributes(env, std::mem::take(run_attributes));
+        retain_attributes(env, &attributes);
+        run_attributes.extend_from_slice(&attributes);
+    });
+    release_attributes(env, attributes);
+}
+- (())addAttribute:(id)name // NSString*
+             value:(id)value
+             range:(NSRange)range {
+    modify_attributes(env, this, range, |env, run_attributes| {
+        set_attribute(env, run_attributes, name, value);
+    });
+}
+- (())addAttributes:(id)attributes // NSDictionary*
+              range:(NSRange)range {
+    let attributes = attributes_from_dict(env, attributes);
+    modify_attributes(env, this, range, |env, run_attributes| {
+        for &(name, value) in &attributes {
+            set_attribute(env, run_attributes, name, value);
+        }
+    });
+    release_attributes(env, attributes);
+}
+- (())removeAttribute:(id)name // NSString*
+                range:(NSRange)range {
+    modify_attributes(env, this, range, |env, run_attributes| {
+        if let Some(idx) = find_attribute(env, run_attributes, name) {
+            let (old_name, old_value) = run_attributes.remove(idx);
+            release(env, old_name);
+            release(env, old_value);
+        }
+    });
+}
+
+@end
+
+};
+
+fn take_host_object(env: &mut Environment, string: id) -> AttributedStringHostObject {
+    std::mem::take(env.objc.borrow_mut(string))
+}
+
+fn retain_attributes(env: &mut Environment, attributes: &[(id, id)]) {
+    for &(name, value) in attributes {
+        retain(env, name);
+        retain(env, value);
+    }
+}
+fn release_attributes(env: &mut Environment, attributes: Vec<(id, id)>) {
+    for (name, value) in attributes {
+        release(env, name);
+        release(env, value);
+    }
+}
+fn release_runs(env: &mut Environment, runs: Vec<AttributeRun>) {
+    for run in runs {
+        release_attributes(env, run.attributes);
+    }
+}
+
+/// Get the key-value pairs of an `NSDictionary*` (may be `nil`), retaining
+/// them.
+fn attributes_from_dict(env: &mut Environment, dict: id) -> Vec<(id, id)> {
+    if dict == nil {
+        return Vec::new();
+    }
+    let keys: Vec<id> = env
+        .objc
+        .borrow::<DictionaryHostObject>(dict)
+        .iter_keys()
+        .collect();
+    keys.into_iter()
+        .map(|key| {
+            let value: id = msg![env; dict objectForKey:key];
+            retain(env, key);
+            retain(env, value);
+            (key, value)
+        })
+        .collect()
+}
+
+fn find_attribute(env: &mut Environment, attributes: &[(id, id)], name: id) -> Option<usize> {
+    attributes
+        .iter()
+        .position(|&(candidate, _)| candidate == name || msg![env; candidate isEqualTo:name])
+}
+
+fn set_attribute(env: &mut Environment, attributes: &mut Vec<(id, id)>, name: id, value: id) {
+    retain(env, name);
+    retain(env, value);
+    if let Some(idx) = find_attribute(env, attributes, name) {
+        let (old_name, old_value) = std::mem::replace(&mut attributes[idx], (name, value));
+        release(env, old_name);
+        release(env, old_value);
+    } else {
+        attributes.push((name, value));
+    }
+}
+
+/// Find the run containing a character. Returns the index of the run and the
+/// index of its first character.
+fn find_run(host_object: &AttributedStringHostObject, index: NSUInteger) -> (usize, NSUInteger) {
+    let mut run_start = 0;
+    for (run_idx, run) in host_object.runs.iter().enumerate() {
+        if index < run_start + run.length {
+            return (run_idx, run_start);
+        }
+        run_start += run.length;
+    }
+    // TODO: raise exception instead of panicking?
+    panic!(
+        "Index {} is out of bounds for attributed string of length {}",
+        index,
+        host_object.text.len()
+    );
+}
+
+fn check_range(host_object: &AttributedStringHostObject, range: NSRange) {
+    // TODO: raise exception instead of panicking?
+    assert!(
+        range.location.checked_add(range.length).unwrap() as usize <= host_object.text.len(),
+        "Range {:?} is out of bounds for attributed string of length {}",
+        range,
+        host_object.text.len()
+    );
+}
+
+/// Make sure there is a run boundary at `index`, splitting a run if needed.
+/// Returns the index of the run that starts at `index`.
+fn split_runs_at(
+    env: &mut Environment,
+    host_object: &mut AttributedStringHostObject,
+    index: NSUInteger,
+) -> usize {
+    let mut run_start = 0;
+    for run_idx in 0..host_object.runs.len() {
+        let run = &mut host_object.runs[run_idx];
+        if index == run_start {
+            return run_idx;
+        }
+        if index < run_start + run.length {
+            let attributes = run.attributes.clone();
+            retain_attributes(env, &attributes);
+            let new_run = AttributeRun {
+                length: run_start + run.length - index,
+                attributes,
+            };
+            run.length = index - run_start;
+            host_object.runs.insert(run_idx + 1, new_run);
+            return run_idx + 1;
+        }
+        run_start += run.length;
+    }
+    host_object.runs.len()
+}
+
+/// Copy the characters and runs in a range. The returned runs are retained.
+fn copy_range(env: &mut Environment, string: id, range: NSRange) -> (Vec<u16>, Vec<AttributeRun>) {
+    let host_object = env.objc.borrow::<AttributedStringHostObject>(string);
+    check_range(host_object, range);
+    let start = range.location;
+    let end = range.location + range.length;
+    let text = host_object.text[start as usize..end as usize].to_vec();
+
+    let mut runs = Vec::new();
+    let mut run_start = 0;
+    for run in &host_object.runs {
+        let run_end = run_start + run.length;
+        let overlap_start = run_start.max(start);
+        let overlap_end = run_end.min(end);
+        if overlap_start < overlap_end {
+            runs.push(AttributeRun {
+                length: overlap_end - overlap_start,
+                attributes: run.attributes.clone(),
+            });
+        }
+        run_start = run_end;
+    }
+    for run in &runs {
+        retain_attributes(env, &run.attributes);
+    }
+    (text, runs)
+}
+
+/// Replace the characters and runs in a range. The new runs must cover the
+/// new characters and already be retained.
+fn replace_range(
+    env: &mut Environment,
+    string: id,
+    range: NSRange,
+    text: Vec<u16>,
+    runs: Vec<AttributeRun>,
+) {
+    let mut host_object = take_host_object(env, string);
+    check_range(&host_object, range);
+    let start = range.location;
+    let end = range.location + range.length;
+
+    let first_run = split_runs_at(env, &mut host_object, start);
+    let end_run = split_runs_at(env, &mut host_object, end);
+    let old_runs: Vec<AttributeRun> = host_object.runs.splice(first_run..end_run, runs).collect();
+    host_object.text.splice(start as usize..end as usize, text);
+    *env.objc.borrow_mut(string) = host_object;
+
+    release_runs(env, old_runs);
+}
+
+/// Apply a function to the attributes of each run in a range.
+fn modify_attributes<F>(env: &mut Environment, string: id, range: NSRange, mut f: F)
+where
+    F: FnMut(&mut Environment, &mut Vec<(id, id)>),
+{
+    let mut host_object = take_host_object(env, string);
+    check_range(&host_object, range);
+    let first_run = split_runs_at(env, &mut host_object, range.location);
+    let end_run = split_runs_at(env, &mut host_object, range.location + range.length);
+    for run in &mut host_object.runs[first_run..end_run] {
+        f(env, &mut run.attributes);
+    }
+    *env.objc.borrow_mut(string) = host_object;
+}
+
+/// Get the text of an attributed string split up by font and color, for
+/// drawing.
+fn text_runs(env: &mut Environment, string: id) -> Vec<ui_font::TextRun> {
+    let host_object = take_host_object(env, string);
+
+    let font_name = ns_string::get_static_str(env, NSFontAttributeName);
+    let color_name = ns_string::get_static_str(env, NSForegroundColorAttributeName);
+    // Apple's defaults are 12pt Helvetica in black.
+    let default_font: id = msg_class![env; UIFont systemFontOfSize:12.0f32];
+
+    let mut runs = Vec::new();
+    let mut run_start = 0;
+    for run in &host_object.runs {
+        let run_end = run_start + run.length;
+        let text =
+            String::from_utf16_lossy(&host_object.text[run_start as usize..run_end as usize]);
+        run_start = run_end;
+
+        let font = match find_attribute(env, &run.attributes, font_name) {
+            Some(idx) => run.attributes[idx].1,
+            None => default_font,
+        };
+        let color = match find_attribute(env, &run.attributes, color_name) {
+            Some(idx) => ui_color::get_rgba(env, run.attributes[idx].1),
+            None => (0.0, 0.0, 0.0, 1.0),
+        };
+        runs.push(ui_font::TextRun { text, font, color });
+    }
+
+    *env.objc.borrow_mut(string) = host_object;
+    runs
+}
+
+/// For Core Text: get the text of an attributed string split up into runs of
+/// UTF-16 code units, with the value of each of the named attributes (or
+/// `nil`) for each run. The values are not retained.
+pub fn attribute_runs(
+    env: &mut Environment,
+    string: id,
+    names: &[&'static str],
+) -> Vec<(Vec<u16>, Vec<id>)> {
+    let host_object = take_host_object(env, string);
+
+    let names: Vec<id> = names
+        .iter()
+        .map(|&name| ns_string::get_static_str(env, name))
+        .collect();
+
+    let mut runs = Vec::new();
+    let mut run_start = 0;
+    for run in &host_object.runs {
+        let run_end = run_start + run.length;
+        let text = host_object.text[run_start as usize..run_end as usize].to_vec();
+        run_start = run_end;
+
+        let values = names
+            .iter()
+            .map(|&name| match find_attribute(env, &run.attributes, name) {
+                Some(idx) => run.attributes[idx].1,
+                None => nil,
+            })
+            .collect();
+        runs.push((text, values));
+    }
+
+    *env.objc.borrow_mut(string) = host_object;
+    runs
+}
@@ -5,9 +5,14 @@
  */
 //! `NSBundle`.
 
-use super::ns_string::from_rust_string;
+use super::ns_string::{from_rust_string, to_rust_string};
+use super::{ns_array, ns_user_defaults};
 use crate::bundle::Bundle;
-use crate::objc::{id, msg, msg_class, objc_classes, release, ClassExports, HostObject};
+use crate::fs::GuestPath;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
+};
+use crate::Environment;
 
 #[derive(Default)]
 pub struct State {
@@ -22,6 +27,9 @@ struct NSBundleHostObject {
     bundle_path: id,
     /// NSURL with bundle path. [None] if not created yet.
     bundle_url: Option<id>,
+    /// NSDictionary with the contents of `Info.plist`. [None] if not created
+    /// yet.
+    info_dictionary: Option<id>,
 }
 impl HostObject for NSBundleHostObject {}
 
@@ -41,6 +49,7 @@ pub const CLASSES: ClassExports = objc_classes! {
             _bundle: None,
             bundle_path,
             bundle_url: None,
+            info_dictionary: None,
         };
         let new = env.objc.alloc_object(
             this,
@@ -53,10 +62,17 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (())dealloc {
-    let &NSBundleHostObject { bundle_url, .. } = env.objc.borrow(this);
+    let &NSBundleHostObject {
+        bundle_url,
+        info_dictionary,
+        ..
+    } = env.objc.borrow(this);
     if let Some(bundle_url) = bundle_url {
         release(env, bundle_url);
     }
+    if let Some(info_dictionary) = info_dictionary {
+        release(env, info_dictionary);
+    }
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
@@ -86,8 +102,166 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; this bundleURL]
 }
 
+- (id)pathForResource:(id)name // NSString*
+                ofType:(id)extension { // NSString*
+    msg![env; this pathForResource:name ofType:extension inDirectory:nil]
+}
+- (id)pathForResource:(id)name // NSString*
+                ofType:(id)extension // NSString*
+           inDirectory:(id)directory { // NSString*
+    let name = optional_string(env, name);
+    let extension = optional_string(env, extension);
+    let directory = optional_string(env, directory);
+    let Some(path) = find_resource(env, this, &name, &extension, &directory) else {
+        log_dbg!(
+            "No resource {:?} of type {:?} in directory {:?}",
+            name,
+            extension,
+            directory
+        );
+        return nil;
+    };
+    let path = from_rust_string(env, path);
+    autorelease(env, path)
+}
+- (id)pathsForResourcesOfType:(id)extension // NSString*
+                  inDirectory:(id)directory { // NSString*
+    let extension = optional_string(env, extension);
+    let directory = optional_string(env, directory);
+    let paths = find_resources_of_type(env, this, &extension, &directory)
+        .into_iter()
+        .map(|path| from_rust_string(env, path))
+        .collect();
+    let paths = ns_array::from_vec(env, paths);
+    autorelease(env, paths)
+}
+
+- (id)URLForResource:(id)name // NSString*
+       withExtension:(id)extension { // NSString*
+    msg![env; this URLForResource:name withExtension:extension subdirectory:nil]
+}
+- (id)URLForResource:(id)name // NSString*
+       withExtension:(id)extension // NSString*
+        subdirectory:(id)directory { // NSString*
+    let path: id = msg![env; this pathForResource:name
+                                           ofType:extension
+                                      inDirectory:directory];
+    if path == nil {
+        return nil;
+    }
+    msg_class![env; NSURL fileURLWithPath:path]
+}
+
+- (id)infoDictionary {
+    if let Some(dict) = env.objc.borrow::<NSBundleHostObject>(this).info_dictionary {
+        return dict;
+    }
+    assert!(env.objc.borrow::<NSBundleHostObject>(this)._bundle.is_none()); // TODO
+    let plist = plist::Value::Dictionary(env.bundle.info_plist().clone());
+    let dict = ns_user_defaults::object_from_plist(env, &plist);
+    env.objc.borrow_mut::<NSBundleHostObject>(this).info_dictionary = Some(dict);
+    dict
+}
+- (id)objectForInfoDictionaryKey:(id)key { // NSString*
+    let dict: id = msg![env; this infoDictionary];
+    msg![env; dict objectForKey:key]
+}
+- (id)bundleIdentifier {
+    let key = from_rust_string(env, "CFBundleIdentifier".to_string());
+    let identifier: id = msg![env; this objectForInfoDictionaryKey:key];
+    release(env, key);
+    identifier
+}
+
 // TODO: constructors, more accessors
 
 @end
 
 };
+
+/// Localizations searched for resources, after the non-localized ones.
+/// TODO: use the user's preferred languages
+const LOCALIZATIONS: &[&str] = &["en.lproj", "English.lproj"];
+
+/// Like [to_rust_string], but `nil` is treated as an empty string.
+fn optional_string(env: &mut Environment, string: id) -> String {
+    if string == nil {
+        String::new()
+    } else {
+        to_rust_string(env, string).into_owned()
+    }
+}
+
+/// Directories in a bundle to search for resources, in order.
+fn resource_directories(env: &mut Environment, bundle: id, directory: &str) -> Vec<String> {
+    let resource_path: id = msg![env; bundle resourcePath];
+    let resource_path = to_rust_string(env, resource_path);
+    let mut directories = vec![resource_path.to_string()];
+    directories.extend(
+        LOCALIZATIONS
+            .iter()
+            .map(|localization| format!("{}/{}", resource_path, localization)),
+    );
+    if !directory.is_empty() {
+        for path in directories.iter_mut() {
+            path.push('/');
+            path.push_str(directory);
+        }
+    }
+    directories
+}
+
+/// Find a resource in a bundle and return its path. If `name` is empty, the
+/// first resource with the extension is found. If `extension` is empty,
+/// `name` must be the full file name.
+fn find_resource(
+    env: &mut Environment,
+    bundle: id,
+    name: &str,
+    extension: &str,
+    directory: &str,
+) -> Option<String> {
+    if name.is_empty() {
+        return find_resources_of_type(env, bundle, extension, directory)
+            .into_iter()
+            .next();
+    }
+    let file_name = if extension.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", name, extension)
+    };
+    resource_directories(env, bundle, directory)
+        .into_iter()
+        .map(|directory| format!("{}/{}", directory, file_name))
+        .find(|path| env.fs.metadata(GuestPath::new(path)).is_some())
+}
+
+/// Find all the resources with an extension in a bundle and return their
+/// paths. If `extension` is empty, all resources are returned.
+fn find_resources_of_type(
+    env: &mut Environment,
+    bundle: id,
+    extension: &str,
+    directory: &str,
+) -> Vec<String> {
+    let mut paths = Vec::new();
+    for directory in resource_directories(env, bundle, directory) {
+        let Ok(mut names) = env.fs.read_dir_names(GuestPath::new(&directory)) else {
+            continue;
+        };
+        names.sort();
+        paths.extend(
+            names
+                .into_iter()
+                .filter(|name| {
+                    extension.is_empty()
+                        || name
+                            .rsplit_once('.')
+                            .is_some_and(|(_, other)| other == extension)
+                })
+                .map(|name| format!("{}/{}", directory, name)),
+        );
+    }
+    paths
+}
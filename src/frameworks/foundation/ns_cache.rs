@@ -0,0 +1,274 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSCache`.
+//!
+//! Objects are evicted in least-recently-used order when a limit is exceeded,
+//! and all objects are evicted when there is a memory warning (see
+//! [handle_memory_warning]).
+
+use super::NSUInteger;
+use crate::mem::MutVoidPtr;
+use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, SEL};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// Weak references to every live `NSCache*`, so they can be emptied when
+    /// there is a memory warning.
+    caches: Vec<id>,
+}
+
+struct CacheEntry {
+    /// Strong reference. Unlike `NSDictionary`, keys are not copied.
+    key: id,
+    key_hash: NSUInteger,
+    /// Strong reference.
+    object: id,
+    cost: NSUInteger,
+}
+
+struct NSCacheHostObject {
+    /// In least-recently-used order.
+    entries: Vec<CacheEntry>,
+    total_cost: NSUInteger,
+    count_limit: NSUInteger,
+    total_cost_limit: NSUInteger,
+    evicts_objects_with_discarded_content: bool,
+    /// Strong reference, `NSString*`.
+    name: id,
+    /// Weak reference.
+    delegate: id,
+}
+impl HostObject for NSCacheHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSCache: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSCacheHostObject {
+        entries: Vec::new(),
+        total_cost: 0,
+        count_limit: 0,
+        total_cost_limit: 0,
+        evicts_objects_with_discarded_content: true,
+        name: nil,
+        delegate: nil,
+    });
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    env.framework_state.foundation.ns_cache.caches.push(new);
+    new
+}
+
+- (())dealloc {
+    let caches = &mut env.framework_state.foundation.ns_cache.caches;
+    let idx = caches.iter().position(|&cache| cache == this).unwrap();
+    caches.swap_remove(idx);
+
+    let host_object = env.objc.borrow_mut::<NSCacheHostObject>(this);
+    let entries = std::mem::take(&mut host_object.entries);
+    let name = host_object.name;
+    for CacheEntry { key, object, .. } in entries {
+        release(env, key);
+        release(env, object);
+    }
+    release(env, name);
+
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)name {
+    env.objc.borrow::<NSCacheHostObject>(this).name
+}
+- (())setName:(id)name { // NSString*
+    let name: id = msg![env; name copy];
+    let host_object = env.objc.borrow_mut::<NSCacheHostObject>(this);
+    let old_name = std::mem::replace(&mut host_object.name, name);
+    release(env, old_name);
+}
+
+- (id)delegate {
+    env.objc.borrow::<NSCacheHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate {
+    env.objc.borrow_mut::<NSCacheHostObject>(this).delegate = delegate;
+}
+
+- (NSUInteger)countLimit {
+    env.objc.borrow::<NSCacheHostObject>(this).count_limit
+}
+- (())setCountLimit:(NSUInteger)limit {
+    env.objc.borrow_mut::<NSCacheHostObject>(this).count_limit = limit;
+    evict_over_limits(env, this);
+}
+
+- (NSUInteger)totalCostLimit {
+    env.objc.borrow::<NSCacheHostObject>(this).total_cost_limit
+}
+- (())setTotalCostLimit:(NSUInteger)limit {
+    env.objc.borrow_mut::<NSCacheHostObject>(this).total_cost_limit = limit;
+    evict_over_limits(env, this);
+}
+
+- (bool)evictsObjectsWithDiscardedContent {
+    env.objc.borrow::<NSCacheHostObject>(this).evicts_objects_with_discarded_content
+}
+- (())setEvictsObjectsWithDiscardedContent:(bool)evicts {
+    env.objc.borrow_mut::<NSCacheHostObject>(this).evicts_objects_with_discarded_content = evicts;
+}
+
+- (id)objectForKey:(id)key {
+    let Some(idx) = find_entry(env, this, key) else {
+        return nil;
+    };
+    let host_object = env.objc.borrow_mut::<NSCacheHostObject>(this);
+    let entry = host_object.entries.remove(idx);
+    let object = entry.object;
+    let evicts_discarded = host_object.evicts_objects_with_discarded_content;
+    host_object.entries.push(entry);
+
+    if evicts_discarded && is_content_discarded(env, object) {
+        let idx = entry_count(env, this) - 1;
+        remove_entry(env, this, idx, false);
+        return nil;
+    }
+    object
+}
+
+- (())setObject:(id)object forKey:(id)key {
+    msg![env; this setObject:object forKey:key cost:0u32]
+}
+- (())setObject:(id)object forKey:(id)key cost:(NSUInteger)cost {
+    assert!(object != nil && key != nil);
+    if let Some(idx) = find_entry(env, this, key) {
+        remove_entry(env, this, idx, false);
+    }
+
+    retain(env, key);
+    retain(env, object);
+    let key_hash: NSUInteger = msg![env; key hash];
+    let host_object = env.objc.borrow_mut::<NSCacheHostObject>(this);
+    host_object.entries.push(CacheEntry {
+        key,
+        key_hash,
+        object,
+        cost,
+    });
+    host_object.total_cost = host_object.total_cost.saturating_add(cost);
+    evict_over_limits(env, this);
+}
+
+- (())removeObjectForKey:(id)key {
+    if let Some(idx) = find_entry(env, this, key) {
+        remove_entry(env, this, idx, false);
+    }
+}
+
+- (())removeAllObjects {
+    while entry_count(env, this) > 0 {
+        remove_entry(env, this, 0, false);
+    }
+}
+
+@end
+
+};
+
+fn entry_count(env: &mut Environment, cache: id) -> usize {
+    env.objc.borrow::<NSCacheHostObject>(cache).entries.len()
+}
+
+fn find_entry(env: &mut Environment, cache: id, key: id) -> Option<usize> {
+    let key_hash: NSUInteger = msg![env; key hash];
+    let mut idx = 0;
+    loop {
+        let entries = &env.objc.borrow::<NSCacheHostObject>(cache).entries;
+        let entry = entries.get(idx)?;
+        let other_key = entry.key;
+        if entry.key_hash == key_hash && (other_key == key || msg![env; other_key isEqualTo:key]) {
+            return Some(idx);
+        }
+        idx += 1;
+    }
+}
+
+/// Does the object implement `NSDiscardableContent` and has it been discarded?
+fn is_content_discarded(env: &mut Environment, object: id) -> bool {
+    let Some(selector) = env.objc.lookup_selector("isContentDiscarded") else {
+        return false;
+    };
+    msg![env; object respondsToSelector:selector] && msg![env; object isContentDiscarded]
+}
+
+/// Remove an entry. If `evicting` is true, the delegate is told and the
+/// object's content is discarded if possible.
+fn remove_entry(env: &mut Environment, cache: id, idx: usize, evicting: bool) {
+    let host_object = env.objc.borrow_mut::<NSCacheHostObject>(cache);
+    let CacheEntry {
+        key, object, cost, ..
+    } = host_object.entries.remove(idx);
+    host_object.total_cost = host_object.total_cost.saturating_sub(cost);
+    let delegate = host_object.delegate;
+
+    if evicting {
+        if delegate != nil {
+            let selector: Option<SEL> = env.objc.lookup_selector("cache:willEvictObject:");
+            if let Some(selector) = selector {
+                if msg![env; delegate respondsToSelector:selector] {
+                    let () = msg![env; delegate cache:cache willEvictObject:object];
+                }
+            }
+        }
+        if let Some(selector) = env.objc.lookup_selector("discardContentIfPossible") {
+            if msg![env; object respondsToSelector:selector] {
+                let () = msg![env; object discardContentIfPossible];
+            }
+        }
+    }
+
+    release(env, key);
+    release(env, object);
+}
+
+fn evict_over_limits(env: &mut Environment, cache: id) {
+    loop {
+        let host_object = env.objc.borrow::<NSCacheHostObject>(cache);
+        let count = host_object.entries.len() as NSUInteger;
+        let over_count = host_object.count_limit != 0 && count > host_object.count_limit;
+        let over_cost = host_object.total_cost_limit != 0
+            && host_object.total_cost > host_object.total_cost_limit;
+        if !over_count && !over_cost {
+            break;
+        }
+        log_dbg!("Cache {:?} is over its limits, evicting an object", cache);
+        remove_entry(env, cache, 0, true);
+    }
+}
+
+/// For use when there is a memory warning: evict everything from every cache.
+pub fn handle_memory_warning(env: &mut Environment) {
+    let caches = env.framework_state.foundation.ns_cache.caches.clone();
+    for cache in caches {
+        // A delegate might have caused an earlier cache to be deallocated.
+        let live_caches = &env.framework_state.foundation.ns_cache.caches;
+        if !live_caches.contains(&cache) {
+            continue;
+        }
+        let count = entry_count(env, cache);
+        if count == 0 {
+            continue;
+        }
+        log_dbg!("Evicting {} objects from cache {:?}", count, cache);
+        // Keep the cache alive in case a delegate releases it.
+        retain(env, cache);
+        while entry_count(env, cache) > 0 {
+            remove_entry(env, cache, 0, true);
+        }
+        release(env, cache);
+    }
+}
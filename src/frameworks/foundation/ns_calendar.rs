@@ -0,0 +1,495 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSCalendar`.
+//!
+//! Only the Gregorian calendar is implemented. The date arithmetic is done in
+//! [BrokenDownTime], which is also used by `NSDateFormatter`.
+//!
+//! Resources:
+//! - Howard Hinnant's [`chrono`-Compatible Low-Level Date Algorithms](https://howardhinnant.github.io/date_algorithms.html),
+//!   which the day-number conversions here are based on.
+
+use super::ns_date::{self, NSTimeIntervalSince1970};
+use super::ns_date_components::{self, NSUndefinedDateComponent};
+use super::{ns_string, ns_time_zone, NSInteger, NSTimeInterval, NSUInteger};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+pub const NSGregorianCalendar: &str = "gregorian";
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_NSGregorianCalendar",
+    HostConstant::NSString(NSGregorianCalendar),
+)];
+
+pub type NSCalendarUnit = NSUInteger;
+pub const NSEraCalendarUnit: NSCalendarUnit = 1 << 1;
+pub const NSYearCalendarUnit: NSCalendarUnit = 1 << 2;
+pub const NSMonthCalendarUnit: NSCalendarUnit = 1 << 3;
+pub const NSDayCalendarUnit: NSCalendarUnit = 1 << 4;
+pub const NSHourCalendarUnit: NSCalendarUnit = 1 << 5;
+pub const NSMinuteCalendarUnit: NSCalendarUnit = 1 << 6;
+pub const NSSecondCalendarUnit: NSCalendarUnit = 1 << 7;
+pub const NSWeekCalendarUnit: NSCalendarUnit = 1 << 8;
+pub const NSWeekdayCalendarUnit: NSCalendarUnit = 1 << 9;
+pub const NSWeekdayOrdinalCalendarUnit: NSCalendarUnit = 1 << 10;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Number of days since 1970-01-01 for a date in the proleptic Gregorian
+/// calendar. The day may be out of range for the month.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // normalize the month first, e.g. month 13 of 2000 is month 1 of 2001
+    let year = year + (month - 1).div_euclid(12);
+    let month = (month - 1).rem_euclid(12) + 1;
+
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of [days_from_civil]. Returns (year, month, day).
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+pub fn days_in_month(year: i64, month: i64) -> i64 {
+    days_from_civil(year, month + 1, 1) - days_from_civil(year, month, 1)
+}
+
+/// A date split into calendar fields, in some time zone. The fields are not
+/// required to be in range when converting back to a time interval, e.g. the
+/// 32nd of January is the 1st of February.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenDownTime {
+    pub year: i64,
+    /// 1 to 12
+    pub month: i64,
+    /// 1 to 31
+    pub day: i64,
+    pub hour: i64,
+    pub minute: i64,
+    pub second: i64,
+    /// Fractional part of the second, 0.0 to 1.0.
+    pub fraction: f64,
+}
+impl BrokenDownTime {
+    /// `offset` is the time zone's offset east of GMT, in seconds.
+    pub fn from_time_interval(time_interval: NSTimeInterval, offset: i32) -> Self {
+        let seconds = time_interval + NSTimeIntervalSince1970 + f64::from(offset);
+        let whole_seconds = seconds.floor();
+        let fraction = seconds - whole_seconds;
+        let whole_seconds = whole_seconds as i64;
+
+        let days = whole_seconds.div_euclid(SECONDS_PER_DAY);
+        let second_of_day = whole_seconds.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        BrokenDownTime {
+            year,
+            month,
+            day,
+            hour: second_of_day / 3600,
+            minute: (second_of_day / 60) % 60,
+            second: second_of_day % 60,
+            fraction,
+        }
+    }
+
+    /// `offset` is the time zone's offset east of GMT, in seconds.
+    pub fn to_time_interval(&self, offset: i32) -> NSTimeInterval {
+        let days = days_from_civil(self.year, self.month, self.day);
+        let seconds = days * SECONDS_PER_DAY + self.hour * 3600 + self.minute * 60 + self.second;
+        (seconds - i64::from(offset)) as f64 + self.fraction - NSTimeIntervalSince1970
+    }
+
+    fn days_since_1970(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day)
+    }
+
+    /// 1 (Sunday) to 7 (Saturday)
+    pub fn weekday(&self) -> i64 {
+        // 1970-01-01 was a Thursday.
+        (self.days_since_1970() + 4).rem_euclid(7) + 1
+    }
+
+    /// 1 to 366
+    pub fn day_of_year(&self) -> i64 {
+        self.days_since_1970() - days_from_civil(self.year, 1, 1) + 1
+    }
+
+    /// Week of the year, where weeks start on `first_weekday` and the first
+    /// week is the one containing January 1st.
+    pub fn week_of_year(&self, first_weekday: i64) -> i64 {
+        let jan_1 = BrokenDownTime {
+            month: 1,
+            day: 1,
+            ..self.clone()
+        };
+        let jan_1_offset = (jan_1.weekday() - first_weekday).rem_euclid(7);
+        (self.day_of_year() - 1 + jan_1_offset) / 7 + 1
+    }
+
+    /// Add a number of months, clamping the day to the length of the resulting
+    /// month, like Apple does (e.g. January 31st + 1 month = February 28th).
+    pub fn add_months(&mut self, months: i64) {
+        let month_index = self.year * 12 + (self.month - 1) + months;
+        self.year = month_index.div_euclid(12);
+        self.month = month_index.rem_euclid(12) + 1;
+        self.day = self.day.min(days_in_month(self.year, self.month));
+    }
+}
+
+struct NSCalendarHostObject {
+    /// `NSString*`
+    identifier: id,
+    /// `NSTimeZone*`
+    time_zone: id,
+    first_weekday: NSUInteger,
+}
+impl HostObject for NSCalendarHostObject {}
+
+fn get_time_zone_offset(env: &mut Environment, calendar: id) -> i32 {
+    let time_zone = env.objc.borrow::<NSCalendarHostObject>(calendar).time_zone;
+    ns_time_zone::seconds_from_gmt(env, time_zone)
+}
+
+fn is_component_defined(value: NSInteger) -> bool {
+    value != NSUndefinedDateComponent
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSCalendar: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSCalendarHostObject {
+        identifier: nil,
+        time_zone: nil,
+        first_weekday: 1,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)currentCalendar {
+    let identifier = ns_string::get_static_str(env, NSGregorianCalendar);
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCalendarIdentifier:identifier];
+    autorelease(env, new)
+}
++ (id)autoupdatingCurrentCalendar {
+    msg![env; this currentCalendar]
+}
+
+- (id)initWithCalendarIdentifier:(id)identifier { // NSString*
+    let identifier_str = ns_string::to_rust_string(env, identifier);
+    if identifier_str != NSGregorianCalendar {
+        log!(
+            "TODO: calendar {:?} is not supported, the Gregorian calendar will be used instead",
+            identifier_str
+        );
+    }
+    let identifier: id = msg![env; identifier copy];
+    let time_zone: id = msg_class![env; NSTimeZone defaultTimeZone];
+    retain(env, time_zone);
+    let host_object = env.objc.borrow_mut::<NSCalendarHostObject>(this);
+    host_object.identifier = identifier;
+    host_object.time_zone = time_zone;
+    this
+}
+
+- (())dealloc {
+    let &NSCalendarHostObject {
+        identifier,
+        time_zone,
+        ..
+    } = env.objc.borrow(this);
+    release(env, identifier);
+    release(env, time_zone);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    let &NSCalendarHostObject {
+        identifier,
+        time_zone,
+        first_weekday,
+    } = env.objc.borrow(this);
+    let new: id = msg_class![env; NSCalendar alloc];
+    let new: id = msg![env; new initWithCalendarIdentifier:identifier];
+    () = msg![env; new setTimeZone:time_zone];
+    () = msg![env; new setFirstWeekday:first_weekday];
+    new
+}
+
+- (id)calendarIdentifier {
+    env.objc.borrow::<NSCalendarHostObject>(this).identifier
+}
+
+- (id)timeZone {
+    env.objc.borrow::<NSCalendarHostObject>(this).time_zone
+}
+- (())setTimeZone:(id)time_zone { // NSTimeZone*
+    let time_zone = if time_zone == nil {
+        msg_class![env; NSTimeZone defaultTimeZone]
+    } else {
+        time_zone
+    };
+    retain(env, time_zone);
+    let host_object = env.objc.borrow_mut::<NSCalendarHostObject>(this);
+    let old = std::mem::replace(&mut host_object.time_zone, time_zone);
+    release(env, old);
+}
+
+- (NSUInteger)firstWeekday {
+    env.objc.borrow::<NSCalendarHostObject>(this).first_weekday
+}
+- (())setFirstWeekday:(NSUInteger)weekday {
+    assert!((1..=7).contains(&weekday));
+    env.objc.borrow_mut::<NSCalendarHostObject>(this).first_weekday = weekday;
+}
+
+- (id)components:(NSCalendarUnit)units
+        fromDate:(id)date { // NSDate*
+    let offset = get_time_zone_offset(env, this);
+    let first_weekday = env.objc.borrow::<NSCalendarHostObject>(this).first_weekday;
+    let time_interval = ns_date::get_time_interval(env, date);
+    let time = BrokenDownTime::from_time_interval(time_interval, offset);
+
+    let components: id = msg_class![env; NSDateComponents new];
+    let fields = ns_date_components::get_fields_mut(env, components);
+    if units & NSEraCalendarUnit != 0 {
+        fields.era = if time.year > 0 { 1 } else { 0 };
+    }
+    if units & NSYearCalendarUnit != 0 {
+        // There's no year 0 in the Gregorian calendar, the year before 1 AD is
+        // 1 BC.
+        let year = if time.year > 0 { time.year } else { 1 - time.year };
+        fields.year = year as NSInteger;
+    }
+    if units & NSMonthCalendarUnit != 0 {
+        fields.month = time.month as NSInteger;
+    }
+    if units & NSDayCalendarUnit != 0 {
+        fields.day = time.day as NSInteger;
+    }
+    if units & NSHourCalendarUnit != 0 {
+        fields.hour = time.hour as NSInteger;
+    }
+    if units & NSMinuteCalendarUnit != 0 {
+        fields.minute = time.minute as NSInteger;
+    }
+    if units & NSSecondCalendarUnit != 0 {
+        fields.second = time.second as NSInteger;
+    }
+    if units & NSWeekCalendarUnit != 0 {
+        fields.week = time.week_of_year(first_weekday.into()) as NSInteger;
+    }
+    if units & NSWeekdayCalendarUnit != 0 {
+        fields.weekday = time.weekday() as NSInteger;
+    }
+    if units & NSWeekdayOrdinalCalendarUnit != 0 {
+        fields.weekday_ordinal = ((time.day - 1) / 7 + 1) as NSInteger;
+    }
+    autorelease(env, components)
+}
+
+- (id)dateFromComponents:(id)components { // NSDateComponents*
+    let fields = ns_date_components::get_fields(env, components).clone();
+    let get = |value: NSInteger, default: i64| {
+        if is_component_defined(value) { i64::from(value) } else { default }
+    };
+    let year = get(fields.year, 1);
+    let year = if get(fields.era, 1) == 0 { 1 - year } else { year };
+    let mut time = BrokenDownTime {
+        year,
+        month: get(fields.month, 1),
+        day: get(fields.day, 1),
+        hour: get(fields.hour, 0),
+        minute: get(fields.minute, 0),
+        second: get(fields.second, 0),
+        fraction: 0.0,
+    };
+    if is_component_defined(fields.weekday) && !is_component_defined(fields.day) {
+        // Find the requested weekday, in the requested week of the month if
+        // there is one.
+        let ordinal = get(fields.weekday_ordinal, 1);
+        let first_of_month = time.weekday();
+        let days_to_weekday = (i64::from(fields.weekday) - first_of_month).rem_euclid(7);
+        time.day = 1 + days_to_weekday + (ordinal - 1) * 7;
+    }
+    let offset = get_time_zone_offset(env, this);
+    let new = ns_date::from_time_interval(env, time.to_time_interval(offset));
+    autorelease(env, new)
+}
+
+- (id)dateByAddingComponents:(id)components // NSDateComponents*
+                      toDate:(id)date // NSDate*
+                     options:(NSUInteger)_options {
+    let fields = ns_date_components::get_fields(env, components).clone();
+    let get = |value: NSInteger| {
+        if is_component_defined(value) { i64::from(value) } else { 0 }
+    };
+
+    let offset = get_time_zone_offset(env, this);
+    let time_interval = ns_date::get_time_interval(env, date);
+    let mut time = BrokenDownTime::from_time_interval(time_interval, offset);
+
+    // Months and years vary in length, so they have to be added first.
+    time.add_months(get(fields.year) * 12 + get(fields.month));
+    time.day += get(fields.day) + get(fields.week) * 7;
+    time.hour += get(fields.hour);
+    time.minute += get(fields.minute);
+    time.second += get(fields.second);
+
+    let new = ns_date::from_time_interval(env, time.to_time_interval(offset));
+    autorelease(env, new)
+}
+
+- (id)components:(NSCalendarUnit)units
+        fromDate:(id)start_date // NSDate*
+          toDate:(id)end_date // NSDate*
+         options:(NSUInteger)_options {
+    let offset = get_time_zone_offset(env, this);
+    let start_interval = ns_date::get_time_interval(env, start_date);
+    let end_interval = ns_date::get_time_interval(env, end_date);
+    let start = BrokenDownTime::from_time_interval(start_interval, offset);
+    let end = BrokenDownTime::from_time_interval(end_interval, offset);
+
+    // Count whole months first, rounding towards zero.
+    let mut months = if units & (NSYearCalendarUnit | NSMonthCalendarUnit) != 0 {
+        (end.year - start.year) * 12 + (end.month - start.month)
+    } else {
+        0
+    };
+    if units & NSMonthCalendarUnit == 0 {
+        months -= months % 12;
+    }
+    let step = if units & NSMonthCalendarUnit != 0 { 1 } else { 12 };
+    loop {
+        let mut base = start.clone();
+        base.add_months(months);
+        let base_interval = base.to_time_interval(offset);
+        let overshot = if months > 0 {
+            base_interval > end_interval
+        } else if months < 0 {
+            base_interval < end_interval
+        } else {
+            false
+        };
+        if !overshot {
+            break;
+        }
+        months -= months.signum() * step;
+    }
+    let mut base = start.clone();
+    base.add_months(months);
+
+    // The rest can be done with plain seconds.
+    let mut remainder = (end_interval - base.to_time_interval(offset)).trunc() as i64;
+
+    let components: id = msg_class![env; NSDateComponents new];
+    let fields = ns_date_components::get_fields_mut(env, components);
+    if units & NSYearCalendarUnit != 0 {
+        fields.year = (months / 12) as NSInteger;
+        months %= 12;
+    }
+    if units & NSMonthCalendarUnit != 0 {
+        fields.month = months as NSInteger;
+    }
+    let mut take = |unit: NSCalendarUnit, seconds: i64| -> Option<NSInteger> {
+        if units & unit == 0 {
+            return None;
+        }
+        let value = remainder / seconds;
+        remainder %= seconds;
+        Some(value as NSInteger)
+    };
+    if let Some(weeks) = take(NSWeekCalendarUnit, 7 * SECONDS_PER_DAY) {
+        fields.week = weeks;
+    }
+    if let Some(days) = take(NSDayCalendarUnit, SECONDS_PER_DAY) {
+        fields.day = days;
+    }
+    if let Some(hours) = take(NSHourCalendarUnit, 60 * 60) {
+        fields.hour = hours;
+    }
+    if let Some(minutes) = take(NSMinuteCalendarUnit, 60) {
+        fields.minute = minutes;
+    }
+    if let Some(seconds) = take(NSSecondCalendarUnit, 1) {
+        fields.second = seconds;
+    }
+    autorelease(env, components)
+}
+
+@end
+
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2001, 1, 1), 11323);
+        assert_eq!(civil_from_days(11323), (2001, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        for days in -800000..800000 {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+    }
+
+    #[test]
+    fn broken_down_time() {
+        let time = BrokenDownTime::from_time_interval(0.0, 0);
+        assert_eq!((time.year, time.month, time.day), (2001, 1, 1));
+        assert_eq!(time.weekday(), 2); // Monday
+        assert_eq!(time.to_time_interval(0), 0.0);
+
+        // 2001-01-01 00:00 in UTC+1 is 2000-12-31 23:00 UTC
+        let time = BrokenDownTime::from_time_interval(-3600.0, 3600);
+        assert_eq!(
+            (time.year, time.month, time.day, time.hour),
+            (2001, 1, 1, 0)
+        );
+        assert_eq!(time.to_time_interval(3600), -3600.0);
+
+        let mut time = BrokenDownTime::from_time_interval(2592000.0, 0); // Jan 31
+        assert_eq!((time.month, time.day), (1, 31));
+        time.add_months(1);
+        assert_eq!((time.year, time.month, time.day), (2001, 2, 28));
+        time.add_months(-3);
+        assert_eq!((time.year, time.month, time.day), (2000, 11, 28));
+    }
+}
@@ -3,18 +3,28 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! `NSData` and `NSMutableData`.
+//! `NSData`, `NSMutableData` and `NSPurgeableData`.
 
 use super::NSUInteger;
 use crate::mem::{ConstVoidPtr, MutVoidPtr, Ptr};
-use crate::objc::{autorelease, id, msg, objc_classes, retain, ClassExports, HostObject};
+use crate::objc::{
+    autorelease, id, msg, msg_class, objc_classes, retain, ClassExports, HostObject,
+};
+use crate::Environment;
 
 struct NSDataHostObject {
     bytes: MutVoidPtr,
     length: NSUInteger,
+    /// Only used by `NSPurgeableData`.
+    purgeable: Option<PurgeableState>,
 }
 impl HostObject for NSDataHostObject {}
 
+struct PurgeableState {
+    access_count: NSUInteger,
+    discarded: bool,
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -26,10 +36,18 @@ pub const CLASSES: ClassExports = objc_classes! {
     let host_object = Box::new(NSDataHostObject {
         bytes: Ptr::null(),
         length: 0,
+        purgeable: None,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
++ (id)dataWithBytes:(ConstVoidPtr)bytes
+             length:(NSUInteger)length {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithBytes:bytes length:length];
+    autorelease(env, new)
+}
+
 + (id)dataWithBytesNoCopy:(MutVoidPtr)bytes
                    length:(NSUInteger)length {
     let new: id = msg![env; this alloc];
@@ -46,6 +64,19 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (id)initWithBytes:(ConstVoidPtr)bytes
+             length:(NSUInteger)length {
+    let copy = if length == 0 {
+        Ptr::null()
+    } else {
+        let data = env.mem.bytes_at(bytes.cast(), length).to_vec();
+        let copy = env.mem.alloc(length);
+        env.mem.bytes_at_mut(copy.cast(), length).copy_from_slice(&data);
+        copy
+    };
+    msg![env; this initWithBytesNoCopy:copy length:length]
+}
+
 - (())dealloc {
     let &NSDataHostObject { bytes, .. } = env.objc.borrow(this);
     if !bytes.is_null() {
@@ -68,4 +99,96 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @end
 
+// TODO: This should be a subclass of NSMutableData.
+@implementation NSPurgeableData: NSData
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    // New purgeable data is already being accessed, as if
+    // beginContentAccess had been called.
+    let host_object = Box::new(NSDataHostObject {
+        bytes: Ptr::null(),
+        length: 0,
+        purgeable: Some(PurgeableState {
+            access_count: 1,
+            discarded: false,
+        }),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    let &NSDataHostObject { bytes, length, .. } = env.objc.borrow(this);
+    let new: id = msg_class![env; NSData alloc];
+    msg![env; new initWithBytes:(bytes.cast_const()) length:length]
+}
+
+// NSDiscardableContent implementation
+- (bool)beginContentAccess {
+    let state = purgeable_state(env, this);
+    if state.discarded {
+        return false;
+    }
+    state.access_count += 1;
+    true
+}
+- (())endContentAccess {
+    let state = purgeable_state(env, this);
+    assert!(state.access_count > 0);
+    state.access_count -= 1;
+}
+- (())discardContentIfPossible {
+    let state = purgeable_state(env, this);
+    if state.access_count > 0 || state.discarded {
+        return;
+    }
+    state.discarded = true;
+    let host_object = env.objc.borrow_mut::<NSDataHostObject>(this);
+    let bytes = std::mem::replace(&mut host_object.bytes, Ptr::null());
+    host_object.length = 0;
+    if !bytes.is_null() {
+        env.mem.free(bytes);
+    }
+}
+- (bool)isContentDiscarded {
+    purgeable_state(env, this).discarded
+}
+
+@end
+
 };
+
+fn purgeable_state(env: &mut Environment, data: id) -> &mut PurgeableState {
+    env.objc
+        .borrow_mut::<NSDataHostObject>(data)
+        .purgeable
+        .as_mut()
+        .unwrap()
+}
+
+/// Shortcut for host code: create a new `NSData*` (+1 reference) holding a copy
+/// of `bytes`.
+pub fn from_bytes(env: &mut Environment, bytes: &[u8]) -> id {
+    let length: NSUInteger = bytes.len().try_into().unwrap();
+    let ptr: MutVoidPtr = if length == 0 {
+        Ptr::null()
+    } else {
+        let ptr = env.mem.alloc(length);
+        env.mem
+            .bytes_at_mut(ptr.cast(), length)
+            .copy_from_slice(bytes);
+        ptr
+    };
+    let new: id = msg_class![env; NSData alloc];
+    msg![env; new initWithBytesNoCopy:ptr length:length]
+}
+
+/// Shortcut for host code: get a copy of the bytes of an `NSData*`.
+pub fn to_vec(env: &mut Environment, data: id) -> Vec<u8> {
+    let &NSDataHostObject { bytes, length, .. } = env.objc.borrow(data);
+    if length == 0 {
+        Vec::new()
+    } else {
+        env.mem.bytes_at(bytes.cast(), length).to_vec()
+    }
+}
@@ -0,0 +1,216 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSDate`.
+//!
+//! Dates are stored as an [NSTimeInterval] relative to the "reference date",
+//! 2001-01-01 00:00:00 UTC, like Apple's implementation does.
+
+use super::ns_string;
+use super::{NSComparisonResult, NSTimeInterval, NSUInteger};
+use super::{NSOrderedAscending, NSOrderedDescending, NSOrderedSame};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, objc_classes, retain, Class, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::time::SystemTime;
+
+/// Number of seconds between the Unix epoch (1970-01-01 00:00:00 UTC) and the
+/// reference date (2001-01-01 00:00:00 UTC).
+pub const NSTimeIntervalSince1970: NSTimeInterval = 978307200.0;
+
+struct NSDateHostObject {
+    time_interval: NSTimeInterval,
+}
+impl HostObject for NSDateHostObject {}
+
+/// Get the current time as an interval since the reference date.
+pub fn now_since_reference_date() -> NSTimeInterval {
+    let since_1970 = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64();
+    since_1970 - NSTimeIntervalSince1970
+}
+
+/// Like [now_since_reference_date], but by the app's clock (see
+/// [crate::clock]), which is ahead if the app has been fast-forwarded. Use
+/// this for dates the app may use to measure time.
+pub fn now_for_app(env: &Environment) -> NSTimeInterval {
+    now_since_reference_date() + env.clock.lead().as_secs_f64()
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// NSDate is an abstract class in Apple's implementation, but there's no need
+// for that here.
+@implementation NSDate: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSDateHostObject { time_interval: 0.0 });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)date {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new init];
+    autorelease(env, new)
+}
++ (id)dateWithTimeIntervalSinceNow:(NSTimeInterval)interval {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTimeIntervalSinceNow:interval];
+    autorelease(env, new)
+}
++ (id)dateWithTimeIntervalSinceReferenceDate:(NSTimeInterval)interval {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTimeIntervalSinceReferenceDate:interval];
+    autorelease(env, new)
+}
++ (id)dateWithTimeIntervalSince1970:(NSTimeInterval)interval {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTimeIntervalSince1970:interval];
+    autorelease(env, new)
+}
++ (id)distantFuture {
+    // Apple uses the year 4001.
+    msg![env; this dateWithTimeIntervalSinceReferenceDate:63113904000.0f64]
+}
++ (id)distantPast {
+    // Apple uses the year 1 BC.
+    msg![env; this dateWithTimeIntervalSinceReferenceDate:(-63114076800.0f64)]
+}
+
++ (NSTimeInterval)timeIntervalSinceReferenceDate {
+    now_for_app(env)
+}
+
+- (id)init {
+    env.objc.borrow_mut::<NSDateHostObject>(this).time_interval = now_for_app(env);
+    this
+}
+- (id)initWithTimeIntervalSinceNow:(NSTimeInterval)interval {
+    env.objc.borrow_mut::<NSDateHostObject>(this).time_interval =
+        now_for_app(env) + interval;
+    this
+}
+- (id)initWithTimeIntervalSinceReferenceDate:(NSTimeInterval)interval {
+    env.objc.borrow_mut::<NSDateHostObject>(this).time_interval = interval;
+    this
+}
+- (id)initWithTimeIntervalSince1970:(NSTimeInterval)interval {
+    env.objc.borrow_mut::<NSDateHostObject>(this).time_interval =
+        interval - NSTimeIntervalSince1970;
+    this
+}
+- (id)initWithTimeInterval:(NSTimeInterval)interval
+                 sinceDate:(id)date { // NSDate*
+    let base = get_time_interval(env, date);
+    env.objc.borrow_mut::<NSDateHostObject>(this).time_interval = base + interval;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    // NSDate is immutable
+    retain(env, this)
+}
+
+- (NSTimeInterval)timeIntervalSinceReferenceDate {
+    get_time_interval(env, this)
+}
+- (NSTimeInterval)timeIntervalSince1970 {
+    get_time_interval(env, this) + NSTimeIntervalSince1970
+}
+- (NSTimeInterval)timeIntervalSinceNow {
+    get_time_interval(env, this) - now_for_app(env)
+}
+- (NSTimeInterval)timeIntervalSinceDate:(id)other { // NSDate*
+    get_time_interval(env, this) - get_time_interval(env, other)
+}
+
+- (id)dateByAddingTimeInterval:(NSTimeInterval)interval {
+    let time_interval = get_time_interval(env, this) + interval;
+    let class: Class = msg![env; this class];
+    msg![env; class dateWithTimeIntervalSinceReferenceDate:time_interval]
+}
+- (id)addTimeInterval:(NSTimeInterval)interval {
+    // deprecated alias of the above
+    msg![env; this dateByAddingTimeInterval:interval]
+}
+
+- (NSComparisonResult)compare:(id)other { // NSDate*
+    let a = get_time_interval(env, this);
+    let b = get_time_interval(env, other);
+    if a < b {
+        NSOrderedAscending
+    } else if a > b {
+        NSOrderedDescending
+    } else {
+        NSOrderedSame
+    }
+}
+- (id)earlierDate:(id)other { // NSDate*
+    if get_time_interval(env, other) < get_time_interval(env, this) {
+        other
+    } else {
+        this
+    }
+}
+- (id)laterDate:(id)other { // NSDate*
+    if get_time_interval(env, other) > get_time_interval(env, this) {
+        other
+    } else {
+        this
+    }
+}
+
+- (NSUInteger)hash {
+    super::hash_helper(&get_time_interval(env, this).to_bits())
+}
+- (bool)isEqual:(id)other {
+    if this == other {
+        return true;
+    }
+    let class: Class = msg_class![env; NSDate class];
+    if !msg![env; other isKindOfClass:class] {
+        return false;
+    }
+    msg![env; this isEqualToDate:other]
+}
+- (bool)isEqualToDate:(id)other { // NSDate*
+    get_time_interval(env, this) == get_time_interval(env, other)
+}
+
+- (id)description {
+    let time_interval = get_time_interval(env, this);
+    let time = super::ns_calendar::BrokenDownTime::from_time_interval(time_interval, 0);
+    let desc = format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} +0000",
+        time.year, time.month, time.day, time.hour, time.minute, time.second,
+    );
+    let desc = ns_string::from_rust_string(env, desc);
+    autorelease(env, desc)
+}
+
+@end
+
+};
+
+/// Shortcut for host code: get the time interval since the reference date
+/// stored by an `NSDate`.
+pub fn get_time_interval(env: &mut Environment, date: id) -> NSTimeInterval {
+    env.objc.borrow::<NSDateHostObject>(date).time_interval
+}
+
+/// Shortcut for host code, roughly equivalent to
+/// `[[NSDate alloc] initWithTimeIntervalSinceReferenceDate:]`.
+pub fn from_time_interval(env: &mut Environment, time_interval: NSTimeInterval) -> id {
+    let new: id = msg_class![env; NSDate alloc];
+    env.objc.borrow_mut::<NSDateHostObject>(new).time_interval = time_interval;
+    new
+}
@@ -0,0 +1,113 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSDateComponents`.
+
+use super::NSInteger;
+use crate::mem::MutVoidPtr;
+use crate::objc::{id, msg_class, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+/// Value of components that haven't been set. This is `NSIntegerMax`.
+pub const NSUndefinedDateComponent: NSInteger = NSInteger::MAX;
+
+#[derive(Clone)]
+pub struct DateComponentFields {
+    pub era: NSInteger,
+    pub year: NSInteger,
+    pub month: NSInteger,
+    pub day: NSInteger,
+    pub hour: NSInteger,
+    pub minute: NSInteger,
+    pub second: NSInteger,
+    pub week: NSInteger,
+    pub weekday: NSInteger,
+    pub weekday_ordinal: NSInteger,
+}
+impl Default for DateComponentFields {
+    fn default() -> Self {
+        DateComponentFields {
+            era: NSUndefinedDateComponent,
+            year: NSUndefinedDateComponent,
+            month: NSUndefinedDateComponent,
+            day: NSUndefinedDateComponent,
+            hour: NSUndefinedDateComponent,
+            minute: NSUndefinedDateComponent,
+            second: NSUndefinedDateComponent,
+            week: NSUndefinedDateComponent,
+            weekday: NSUndefinedDateComponent,
+            weekday_ordinal: NSUndefinedDateComponent,
+        }
+    }
+}
+
+struct NSDateComponentsHostObject {
+    fields: DateComponentFields,
+}
+impl HostObject for NSDateComponentsHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSDateComponents: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSDateComponentsHostObject {
+        fields: Default::default(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    let fields = get_fields(env, this).clone();
+    let new: id = msg_class![env; NSDateComponents alloc];
+    *get_fields_mut(env, new) = fields;
+    new
+}
+
+- (NSInteger)era { get_fields(env, this).era }
+- (NSInteger)year { get_fields(env, this).year }
+- (NSInteger)month { get_fields(env, this).month }
+- (NSInteger)day { get_fields(env, this).day }
+- (NSInteger)hour { get_fields(env, this).hour }
+- (NSInteger)minute { get_fields(env, this).minute }
+- (NSInteger)second { get_fields(env, this).second }
+- (NSInteger)week { get_fields(env, this).week }
+- (NSInteger)weekday { get_fields(env, this).weekday }
+- (NSInteger)weekdayOrdinal { get_fields(env, this).weekday_ordinal }
+
+- (())setEra:(NSInteger)value { get_fields_mut(env, this).era = value; }
+- (())setYear:(NSInteger)value { get_fields_mut(env, this).year = value; }
+- (())setMonth:(NSInteger)value { get_fields_mut(env, this).month = value; }
+- (())setDay:(NSInteger)value { get_fields_mut(env, this).day = value; }
+- (())setHour:(NSInteger)value { get_fields_mut(env, this).hour = value; }
+- (())setMinute:(NSInteger)value { get_fields_mut(env, this).minute = value; }
+- (())setSecond:(NSInteger)value { get_fields_mut(env, this).second = value; }
+- (())setWeek:(NSInteger)value { get_fields_mut(env, this).week = value; }
+- (())setWeekday:(NSInteger)value { get_fields_mut(env, this).weekday = value; }
+- (())setWeekdayOrdinal:(NSInteger)value {
+    get_fields_mut(env, this).weekday_ordinal = value;
+}
+
+@end
+
+};
+
+/// For use by `NSCalendar`
+pub(super) fn get_fields(env: &mut Environment, components: id) -> &DateComponentFields {
+    &env.objc
+        .borrow::<NSDateComponentsHostObject>(components)
+        .fields
+}
+
+/// For use by `NSCalendar`
+pub(super) fn get_fields_mut(env: &mut Environment, components: id) -> &mut DateComponentFields {
+    &mut env
+        .objc
+        .borrow_mut::<NSDateComponentsHostObject>(components)
+        .fields
+}
@@ -0,0 +1,657 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSFormatter` and `NSDateFormatter`.
+//!
+//! Format strings use the Unicode date format patterns, as documented in
+//! [Unicode Technical Standard #35](https://unicode.org/reports/tr35/tr35-dates.html#Date_Format_Patterns).
+//! Only English month and weekday names are currently supported, but the date
+//! and time styles depend on the locale.
+
+use super::ns_calendar::BrokenDownTime;
+use super::{ns_date, ns_locale, ns_string, ns_time_zone, NSUInteger};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+pub type NSDateFormatterStyle = NSUInteger;
+pub const NSDateFormatterNoStyle: NSDateFormatterStyle = 0;
+pub const NSDateFormatterShortStyle: NSDateFormatterStyle = 1;
+pub const NSDateFormatterMediumStyle: NSDateFormatterStyle = 2;
+pub const NSDateFormatterLongStyle: NSDateFormatterStyle = 3;
+pub const NSDateFormatterFullStyle: NSDateFormatterStyle = 4;
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+struct NSDateFormatterHostObject {
+    /// `NSString*`, or `nil` if the styles should be used instead.
+    date_format: id,
+    /// `NSLocale*`, or `nil` for the current locale.
+    locale: id,
+    /// `NSTimeZone*`, or `nil` for the default time zone.
+    time_zone: id,
+    date_style: NSDateFormatterStyle,
+    time_style: NSDateFormatterStyle,
+}
+impl HostObject for NSDateFormatterHostObject {}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Literal(String),
+    /// A pattern letter and how many times it was repeated, e.g. `yyyy`.
+    Field(char, usize),
+}
+
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            // '' is a literal quote, otherwise quotes delimit literal text
+            if chars.peek() == Some(&'\'') {
+                chars.next();
+                literal.push('\'');
+                continue;
+            }
+            while let Some(c) = chars.next() {
+                if c == '\'' {
+                    if chars.peek() == Some(&'\'') {
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                literal.push(c);
+            }
+        } else if c.is_ascii_alphabetic() {
+            if !literal.is_empty() {
+                tokens.push(Token::Literal(std::mem::take(&mut literal)));
+            }
+            let mut count = 1;
+            while chars.peek() == Some(&c) {
+                chars.next();
+                count += 1;
+            }
+            tokens.push(Token::Field(c, count));
+        } else {
+            literal.push(c);
+        }
+    }
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+    tokens
+}
+
+fn format_offset(offset: i32, with_colon: bool) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let abs = offset.unsigned_abs();
+    let (hours, minutes) = (abs / 3600, (abs / 60) % 60);
+    if with_colon {
+        format!("{}{:02}:{:02}", sign, hours, minutes)
+    } else {
+        format!("{}{:02}{:02}", sign, hours, minutes)
+    }
+}
+
+fn format_date(pattern: &str, time: &BrokenDownTime, offset: i32) -> String {
+    let mut out = String::new();
+    for token in tokenize(pattern) {
+        let (letter, count) = match token {
+            Token::Literal(literal) => {
+                out.push_str(&literal);
+                continue;
+            }
+            Token::Field(letter, count) => (letter, count),
+        };
+        let number = |value: i64| format!("{:01$}", value, count);
+        let text = match letter {
+            'G' if count == 4 => {
+                let era = if time.year > 0 {
+                    "Anno Domini"
+                } else {
+                    "Before Christ"
+                };
+                era.to_string()
+            }
+            'G' => {
+                let era = if time.year > 0 { "AD" } else { "BC" };
+                era.to_string()
+            }
+            'y' | 'Y' | 'u' => {
+                let year = if letter != 'u' && time.year <= 0 {
+                    1 - time.year
+                } else {
+                    time.year
+                };
+                if count == 2 {
+                    format!("{:02}", year.rem_euclid(100))
+                } else {
+                    number(year)
+                }
+            }
+            'M' | 'L' => match count {
+                1 | 2 => number(time.month),
+                3 => MONTH_NAMES[time.month as usize - 1][..3].to_string(),
+                4 => MONTH_NAMES[time.month as usize - 1].to_string(),
+                _ => MONTH_NAMES[time.month as usize - 1][..1].to_string(),
+            },
+            'd' => number(time.day),
+            'D' => number(time.day_of_year()),
+            'E' | 'e' | 'c' => match count {
+                1 | 2 if letter != 'E' => number(time.weekday()),
+                1..=3 => WEEKDAY_NAMES[time.weekday() as usize - 1][..3].to_string(),
+                4 => WEEKDAY_NAMES[time.weekday() as usize - 1].to_string(),
+                _ => WEEKDAY_NAMES[time.weekday() as usize - 1][..1].to_string(),
+            },
+            'w' => number(time.week_of_year(1)),
+            'Q' | 'q' => number((time.month - 1) / 3 + 1),
+            'a' => {
+                let period = if time.hour < 12 { "AM" } else { "PM" };
+                period.to_string()
+            }
+            'h' => number((time.hour + 11) % 12 + 1),
+            'H' => number(time.hour),
+            'K' => number(time.hour % 12),
+            'k' => number(if time.hour == 0 { 24 } else { time.hour }),
+            'm' => number(time.minute),
+            's' => number(time.second),
+            'S' => {
+                // fractional seconds are truncated, not rounded
+                let digits = format!("{:.9}", time.fraction);
+                let digits = &digits[2..];
+                format!("{:0<1$}", &digits[..count.min(digits.len())], count)
+            }
+            'Z' => match count {
+                1..=3 => format_offset(offset, false),
+                4 => format!("GMT{}", format_offset(offset, true)),
+                _ => format_offset(offset, true),
+            },
+            'z' | 'v' | 'V' | 'O' => {
+                if offset == 0 {
+                    "GMT".to_string()
+                } else {
+                    format!("GMT{}", format_offset(offset, true))
+                }
+            }
+            _ => {
+                log!("TODO: date format pattern letter {:?} (ignored)", letter);
+                String::new()
+            }
+        };
+        out.push_str(&text);
+    }
+    out
+}
+
+fn take_number(rest: &mut &str, max_digits: usize) -> Option<i64> {
+    let digits = rest
+        .bytes()
+        .take(max_digits)
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    if digits == 0 {
+        return None;
+    }
+    let (number, remainder) = rest.split_at(digits);
+    *rest = remainder;
+    number.parse().ok()
+}
+
+/// Match an English name or its three-letter abbreviation, returning its
+/// one-based index.
+fn take_name(rest: &mut &str, names: &[&str]) -> Option<i64> {
+    for (i, name) in names.iter().enumerate() {
+        // get() is used for slicing because the string being parsed might not
+        // be ASCII, and slicing in the middle of a character would panic.
+        for candidate in [Some(*name), name.get(..3)].into_iter().flatten() {
+            if rest
+                .get(..candidate.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(candidate))
+            {
+                *rest = &rest[candidate.len()..];
+                // skip abbreviation dots, e.g. "Jan."
+                *rest = rest.strip_prefix('.').unwrap_or(rest);
+                return Some(i as i64 + 1);
+            }
+        }
+    }
+    None
+}
+
+/// Parses a string according to a date format pattern. Returns the broken-down
+/// time and the time zone offset, if one was present in the string.
+fn parse_date(pattern: &str, string: &str) -> Option<(BrokenDownTime, Option<i32>)> {
+    let mut time = BrokenDownTime {
+        year: 1970,
+        month: 1,
+        day: 1,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        fraction: 0.0,
+    };
+    let mut offset = None;
+    let mut is_pm = None;
+    let mut is_bc = false;
+
+    let tokens = tokenize(pattern);
+    let mut rest = string;
+    for (i, token) in tokens.iter().enumerate() {
+        let (letter, count) = match *token {
+            Token::Literal(ref literal) => {
+                // whitespace is treated leniently
+                rest = rest.trim_start();
+                if !literal.trim().is_empty() {
+                    rest = rest.strip_prefix(literal.trim())?.trim_start();
+                }
+                continue;
+            }
+            Token::Field(letter, count) => (letter, count),
+        };
+
+        // Numbers that are directly followed by another number (e.g. yyyyMMdd)
+        // must be fixed-width, otherwise any number of digits is accepted.
+        let next_is_numeric = matches!(
+            tokens.get(i + 1),
+            Some(&Token::Field(next, next_count))
+                if !(next == 'a' || (next == 'M' && next_count >= 3) || next == 'E'
+                     || next == 'G' || next == 'z' || next == 'Z')
+        );
+        let width = if next_is_numeric {
+            count.max(2)
+        } else {
+            usize::MAX
+        };
+
+        match letter {
+            'G' => {
+                if rest.starts_with("BC") || rest.starts_with("Before Christ") {
+                    is_bc = true;
+                }
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphabetic() && c != ' ')
+                    .unwrap_or(rest.len());
+                rest = &rest[len..];
+            }
+            'y' | 'Y' | 'u' => {
+                let year = take_number(&mut rest, if count == 2 { 2 } else { width })?;
+                time.year = if count == 2 {
+                    // two-digit years are assumed to be in 1950-2049
+                    if year < 50 {
+                        2000 + year
+                    } else {
+                        1900 + year
+                    }
+                } else {
+                    year
+                };
+            }
+            'M' | 'L' if count >= 3 => time.month = take_name(&mut rest, &MONTH_NAMES)?,
+            'M' | 'L' => time.month = take_number(&mut rest, width.min(2))?,
+            'd' => time.day = take_number(&mut rest, width.min(2))?,
+            'D' => {
+                time.month = 1;
+                time.day = take_number(&mut rest, width.min(3))?;
+            }
+            'E' => {
+                take_name(&mut rest, &WEEKDAY_NAMES)?;
+            }
+            'e' | 'c' if count >= 3 => {
+                take_name(&mut rest, &WEEKDAY_NAMES)?;
+            }
+            'e' | 'c' | 'w' | 'Q' | 'q' => {
+                // not needed to determine the date
+                take_number(&mut rest, width.min(2))?;
+            }
+            'a' => {
+                let start = rest.get(..2)?;
+                if start.eq_ignore_ascii_case("AM") {
+                    is_pm = Some(false);
+                } else if start.eq_ignore_ascii_case("PM") {
+                    is_pm = Some(true);
+                } else {
+                    return None;
+                }
+                rest = &rest[2..];
+            }
+            'h' | 'H' | 'K' | 'k' => {
+                let hour = take_number(&mut rest, width.min(2))?;
+                time.hour = match letter {
+                    'h' => hour % 12,
+                    'k' => hour % 24,
+                    _ => hour,
+                };
+            }
+            'm' => time.minute = take_number(&mut rest, width.min(2))?,
+            's' => time.second = take_number(&mut rest, width.min(2))?,
+            'S' => {
+                let len = rest
+                    .bytes()
+                    .take(if next_is_numeric { count } else { usize::MAX })
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                time.fraction = format!("0.{}", &rest[..len]).parse().ok()?;
+                rest = &rest[len..];
+            }
+            'Z' | 'z' | 'v' | 'V' | 'O' => {
+                if let Some(remainder) = rest.strip_prefix('Z') {
+                    rest = remainder;
+                    offset = Some(0);
+                    continue;
+                }
+                for prefix in ["GMT", "UTC"] {
+                    rest = rest.strip_prefix(prefix).unwrap_or(rest);
+                }
+                let sign = match rest.as_bytes().first() {
+                    Some(b'+') => 1,
+                    Some(b'-') => -1,
+                    _ => {
+                        // plain "GMT"
+                        offset = Some(0);
+                        continue;
+                    }
+                };
+                rest = &rest[1..];
+                let hours = take_number(&mut rest, 2)?;
+                rest = rest.strip_prefix(':').unwrap_or(rest);
+                let minutes = take_number(&mut rest, 2).unwrap_or(0);
+                offset = Some(sign * (hours * 3600 + minutes * 60) as i32);
+            }
+            _ => {
+                log!("TODO: date format pattern letter {:?} (ignored)", letter);
+            }
+        }
+    }
+    if !rest.trim().is_empty() {
+        return None;
+    }
+
+    if is_pm == Some(true) {
+        time.hour += 12;
+    }
+    if is_bc {
+        time.year = 1 - time.year;
+    }
+    if !(1..=12).contains(&time.month)
+        || !(1..=31).contains(&time.day)
+        || !(0..24).contains(&time.hour)
+        || !(0..60).contains(&time.minute)
+        || !(0..=60).contains(&time.second)
+    {
+        return None;
+    }
+    Some((time, offset))
+}
+
+/// Get a format pattern for the given styles, roughly matching what iPhone OS
+/// would use for the locale.
+fn pattern_for_styles(
+    date_style: NSDateFormatterStyle,
+    time_style: NSDateFormatterStyle,
+    locale_identifier: &str,
+) -> String {
+    let is_us = locale_identifier.is_empty()
+        || locale_identifier == "en"
+        || locale_identifier.starts_with("en_US");
+    let date_pattern = match (date_style, is_us) {
+        (NSDateFormatterNoStyle, _) => "",
+        (NSDateFormatterShortStyle, true) => "M/d/yy",
+        (NSDateFormatterMediumStyle, true) => "MMM d, y",
+        (NSDateFormatterLongStyle, true) => "MMMM d, y",
+        (NSDateFormatterFullStyle, true) => "EEEE, MMMM d, y",
+        (NSDateFormatterShortStyle, false) => "dd/MM/yy",
+        (NSDateFormatterMediumStyle, false) => "d MMM y",
+        (NSDateFormatterLongStyle, false) => "d MMMM y",
+        (NSDateFormatterFullStyle, false) => "EEEE d MMMM y",
+        _ => panic!("Unexpected date style {}", date_style),
+    };
+    let time_pattern = match (time_style, is_us) {
+        (NSDateFormatterNoStyle, _) => "",
+        (NSDateFormatterShortStyle, true) => "h:mm a",
+        (NSDateFormatterMediumStyle, true) => "h:mm:ss a",
+        (NSDateFormatterLongStyle, true) => "h:mm:ss a z",
+        (NSDateFormatterFullStyle, true) => "h:mm:ss a zzzz",
+        (NSDateFormatterShortStyle, false) => "HH:mm",
+        (NSDateFormatterMediumStyle, false) => "HH:mm:ss",
+        (NSDateFormatterLongStyle, false) => "HH:mm:ss z",
+        (NSDateFormatterFullStyle, false) => "HH:mm:ss zzzz",
+        _ => panic!("Unexpected time style {}", time_style),
+    };
+    if !date_pattern.is_empty() && !time_pattern.is_empty() {
+        format!("{} {}", date_pattern, time_pattern)
+    } else {
+        format!("{}{}", date_pattern, time_pattern)
+    }
+}
+
+/// Get the format pattern a formatter will use, taking into account the
+/// styles if there is no explicit format.
+fn effective_pattern(env: &mut Environment, formatter: id) -> String {
+    let &NSDateFormatterHostObject {
+        date_format,
+        date_style,
+        time_style,
+        ..
+    } = env.objc.borrow(formatter);
+    if date_format != nil {
+        return ns_string::to_rust_string(env, date_format).into_owned();
+    }
+    let locale: id = msg![env; formatter locale];
+    let identifier = ns_locale::get_identifier(env, locale);
+    pattern_for_styles(date_style, time_style, &identifier)
+}
+
+fn effective_offset(env: &mut Environment, formatter: id) -> i32 {
+    let time_zone: id = msg![env; formatter timeZone];
+    ns_time_zone::seconds_from_gmt(env, time_zone)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// NSFormatter is an abstract class.
+// TODO: NSCopying
+@implementation NSFormatter: NSObject
+@end
+
+@implementation NSDateFormatter: NSFormatter
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSDateFormatterHostObject {
+        date_format: nil,
+        locale: nil,
+        time_zone: nil,
+        date_style: NSDateFormatterNoStyle,
+        time_style: NSDateFormatterNoStyle,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)localizedStringFromDate:(id)date // NSDate*
+                    dateStyle:(NSDateFormatterStyle)date_style
+                    timeStyle:(NSDateFormatterStyle)time_style {
+    let formatter: id = msg![env; this new];
+    () = msg![env; formatter setDateStyle:date_style];
+    () = msg![env; formatter setTimeStyle:time_style];
+    let string: id = msg![env; formatter stringFromDate:date];
+    release(env, formatter);
+    string
+}
+
+- (())dealloc {
+    let &NSDateFormatterHostObject {
+        date_format,
+        locale,
+        time_zone,
+        ..
+    } = env.objc.borrow(this);
+    release(env, date_format);
+    release(env, locale);
+    release(env, time_zone);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (())setFormatterBehavior:(NSUInteger)_behavior {
+    // Only the 10.4+ behavior is supported, which is the default anyway.
+}
+
+- (id)dateFormat {
+    let date_format = env.objc.borrow::<NSDateFormatterHostObject>(this).date_format;
+    if date_format != nil {
+        return date_format;
+    }
+    let pattern = effective_pattern(env, this);
+    let pattern = ns_string::from_rust_string(env, pattern);
+    autorelease(env, pattern)
+}
+- (())setDateFormat:(id)format { // NSString*
+    let format: id = msg![env; format copy];
+    let host_object = env.objc.borrow_mut::<NSDateFormatterHostObject>(this);
+    let old = std::mem::replace(&mut host_object.date_format, format);
+    release(env, old);
+}
+
+- (NSDateFormatterStyle)dateStyle {
+    env.objc.borrow::<NSDateFormatterHostObject>(this).date_style
+}
+- (())setDateStyle:(NSDateFormatterStyle)style {
+    let host_object = env.objc.borrow_mut::<NSDateFormatterHostObject>(this);
+    host_object.date_style = style;
+    // setting a style overrides any explicit format
+    let old = std::mem::replace(&mut host_object.date_format, nil);
+    release(env, old);
+}
+- (NSDateFormatterStyle)timeStyle {
+    env.objc.borrow::<NSDateFormatterHostObject>(this).time_style
+}
+- (())setTimeStyle:(NSDateFormatterStyle)style {
+    let host_object = env.objc.borrow_mut::<NSDateFormatterHostObject>(this);
+    host_object.time_style = style;
+    let old = std::mem::replace(&mut host_object.date_format, nil);
+    release(env, old);
+}
+
+- (id)locale {
+    let locale = env.objc.borrow::<NSDateFormatterHostObject>(this).locale;
+    if locale != nil {
+        locale
+    } else {
+        msg_class![env; NSLocale currentLocale]
+    }
+}
+- (())setLocale:(id)locale { // NSLocale*
+    retain(env, locale);
+    let host_object = env.objc.borrow_mut::<NSDateFormatterHostObject>(this);
+    let old = std::mem::replace(&mut host_object.locale, locale);
+    release(env, old);
+}
+
+- (id)timeZone {
+    let time_zone = env.objc.borrow::<NSDateFormatterHostObject>(this).time_zone;
+    if time_zone != nil {
+        time_zone
+    } else {
+        msg_class![env; NSTimeZone defaultTimeZone]
+    }
+}
+- (())setTimeZone:(id)time_zone { // NSTimeZone*
+    retain(env, time_zone);
+    let host_object = env.objc.borrow_mut::<NSDateFormatterHostObject>(this);
+    let old = std::mem::replace(&mut host_object.time_zone, time_zone);
+    release(env, old);
+}
+
+- (id)stringFromDate:(id)date { // NSDate*
+    let pattern = effective_pattern(env, this);
+    let offset = effective_offset(env, this);
+    let time_interval = ns_date::get_time_interval(env, date);
+    let time = BrokenDownTime::from_time_interval(time_interval, offset);
+    let string = format_date(&pattern, &time, offset);
+    log_dbg!("[{:?} stringFromDate:{:?}] ({:?}) => {:?}", this, date, pattern, string);
+    let string = ns_string::from_rust_string(env, string);
+    autorelease(env, string)
+}
+- (id)dateFromString:(id)string { // NSString*
+    let pattern = effective_pattern(env, this);
+    let string_str = ns_string::to_rust_string(env, string);
+    let Some((time, parsed_offset)) = parse_date(&pattern, &string_str) else {
+        log_dbg!("[{:?} dateFromString:{:?}] ({:?}) => nil", this, string_str, pattern);
+        return nil;
+    };
+    let offset = match parsed_offset {
+        Some(offset) => offset,
+        None => effective_offset(env, this),
+    };
+    let date = ns_date::from_time_interval(env, time.to_time_interval(offset));
+    autorelease(env, date)
+}
+
+@end
+
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_and_parse() {
+        // 2009-06-15 13:45:30.25 UTC
+        let time_interval = 266766330.25;
+        let time = BrokenDownTime::from_time_interval(time_interval, 0);
+        assert_eq!(
+            format_date("yyyy-MM-dd'T'HH:mm:ss.SSSZ", &time, 0),
+            "2009-06-15T13:45:30.250+0000"
+        );
+        assert_eq!(
+            format_date("EEEE, MMMM d, yy 'at' h:mm a", &time, 0),
+            "Monday, June 15, 09 at 1:45 PM"
+        );
+
+        let (parsed, offset) =
+            parse_date("yyyy-MM-dd HH:mm:ss Z", "2009-06-15 14:45:30 +0100").unwrap();
+        assert_eq!(offset, Some(3600));
+        assert_eq!(parsed.to_time_interval(3600), time_interval.floor());
+
+        let (parsed, _) = parse_date("yyyyMMdd", "20090615").unwrap();
+        assert_eq!((parsed.year, parsed.month, parsed.day), (2009, 6, 15));
+        let (parsed, _) = parse_date("MMM d, yyyy h:mm a", "Jun 15, 2009 1:45 PM").unwrap();
+        assert_eq!((parsed.month, parsed.hour, parsed.minute), (6, 13, 45));
+
+        assert!(parse_date("yyyy-MM-dd", "2009-13-01").is_none());
+        assert!(parse_date("yyyy-MM-dd", "2009-06-15 trailing").is_none());
+        // non-ASCII text shouldn't be sliced in the middle of a character
+        assert!(parse_date("h a", "3 午後").is_none());
+        assert!(parse_date("a h", "午後 3").is_none());
+        assert!(parse_date("MMM d", "é… 15").is_none());
+    }
+}
@@ -67,6 +67,25 @@ impl DictionaryHostObject {
         collisions.push((key, value));
         self.count += 1;
     }
+    /// Remove a key-value pair. The caller is responsible for releasing the
+    /// returned key and value.
+    pub(super) fn remove(&mut self, env: &mut Environment, key: id) -> Option<(id, id)> {
+        let hash: Hash = msg![env; key hash];
+        let collisions = self.map.get_mut(&hash)?;
+        let mut found = None;
+        for (i, &(candidate_key, _value)) in collisions.iter().enumerate() {
+            if candidate_key == key || msg![env; candidate_key isEqualTo:key] {
+                found = Some(i);
+                break;
+            }
+        }
+        let pair = collisions.remove(found?);
+        if collisions.is_empty() {
+            self.map.remove(&hash);
+        }
+        self.count -= 1;
+        Some(pair)
+    }
     pub(super) fn release(&mut self, env: &mut Environment) {
         for collisions in self.map.values() {
             for &(key, value) in collisions {
@@ -114,6 +133,12 @@ pub const CLASSES: ClassExports = objc_classes! {
     retain(env, this)
 }
 
+// NSKeyValueCoding
+- (id)valueForKey:(id)key { // NSString*
+    // TODO: keys starting with @ should call the NSObject implementation
+    msg![env; this objectForKey:key]
+}
+
 // TODO
 
 @end
@@ -171,3 +196,17 @@ pub const CLASSES: ClassExports = objc_classes! {
 @end
 
 };
+
+/// Shortcut for host code, roughly equivalent to
+/// `[[NSDictionary alloc] initWithObjects:forKeys:count:]`.
+pub fn dict_from_keys_and_objects(env: &mut Environment, keys_and_objects: &[(id, id)]) -> id {
+    let dict: id = msg_class![env; NSDictionary alloc];
+
+    let mut host_object = <DictionaryHostObject as Default>::default();
+    for &(key, object) in keys_and_objects {
+        host_object.insert(env, key, object, /* copy_key: */ true);
+    }
+    *env.objc.borrow_mut(dict) = host_object;
+
+    dict
+}
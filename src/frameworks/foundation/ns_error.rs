@@ -0,0 +1,143 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSError`.
+
+use super::ns_string::{from_rust_string, get_static_str, to_rust_string};
+use super::NSInteger;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+pub const NSLocalizedDescriptionKey: &str = "NSLocalizedDescription";
+pub const NSCocoaErrorDomain: &str = "NSCocoaErrorDomain";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSLocalizedDescriptionKey",
+        HostConstant::NSString(NSLocalizedDescriptionKey),
+    ),
+    (
+        "_NSCocoaErrorDomain",
+        HostConstant::NSString(NSCocoaErrorDomain),
+    ),
+];
+
+struct NSErrorHostObject {
+    /// `NSString*`
+    domain: id,
+    code: NSInteger,
+    /// `NSDictionary*`
+    user_info: id,
+}
+impl HostObject for NSErrorHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSError: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSErrorHostObject {
+        domain: nil,
+        code: 0,
+        user_info: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)errorWithDomain:(id)domain // NSString*
+                 code:(NSInteger)code
+             userInfo:(id)user_info { // NSDictionary*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithDomain:domain code:code userInfo:user_info];
+    autorelease(env, new)
+}
+
+- (id)initWithDomain:(id)domain // NSString*
+                code:(NSInteger)code
+            userInfo:(id)user_info { // NSDictionary*
+    let domain: id = msg![env; domain copy];
+    retain(env, user_info);
+    *env.objc.borrow_mut(this) = NSErrorHostObject {
+        domain,
+        code,
+        user_info,
+    };
+    this
+}
+
+- (())dealloc {
+    let &NSErrorHostObject {
+        domain, user_info, ..
+    } = env.objc.borrow(this);
+    release(env, domain);
+    release(env, user_info);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+- (id)domain {
+    env.objc.borrow::<NSErrorHostObject>(this).domain
+}
+- (NSInteger)code {
+    env.objc.borrow::<NSErrorHostObject>(this).code
+}
+- (id)userInfo {
+    env.objc.borrow::<NSErrorHostObject>(this).user_info
+}
+
+- (id)localizedDescription {
+    let &NSErrorHostObject {
+        domain,
+        code,
+        user_info,
+    } = env.objc.borrow(this);
+    if user_info != nil {
+        let key = get_static_str(env, NSLocalizedDescriptionKey);
+        let description: id = msg![env; user_info objectForKey:key];
+        if description != nil {
+            return description;
+        }
+    }
+    let domain = to_rust_string(env, domain);
+    let description = format!(
+        "The operation couldn’t be completed. ({} error {}.)",
+        domain, code
+    );
+    let description = from_rust_string(env, description);
+    autorelease(env, description)
+}
+
+- (id)description {
+    let &NSErrorHostObject { domain, code, .. } = env.objc.borrow(this);
+    let domain = to_rust_string(env, domain);
+    let localized_description: id = msg![env; this localizedDescription];
+    let localized_description = to_rust_string(env, localized_description);
+    let description = format!(
+        "Error Domain={} Code={} \"{}\"",
+        domain, code, localized_description
+    );
+    let description = from_rust_string(env, description);
+    autorelease(env, description)
+}
+
+@end
+
+};
+
+/// Shortcut for host code: create an autoreleased error with no user info.
+pub fn new_error(env: &mut Environment, domain: &'static str, code: NSInteger) -> id {
+    let domain = get_static_str(env, domain);
+    msg_class![env; NSError errorWithDomain:domain code:code userInfo:nil]
+}
@@ -15,7 +15,8 @@
 //! Resources:
 //! - The GCC documentation's [Fast Enumeration Protocol section](https://gcc.gnu.org/onlinedocs/gcc/Fast-enumeration-protocol.html)
 
-use crate::mem::{MutPtr, MutVoidPtr, SafeRead};
+use super::NSUInteger;
+use crate::mem::{GuestUSize, Mem, MutPtr, MutVoidPtr, SafeRead};
 use crate::objc::id;
 
 #[repr(C, packed)]
@@ -26,3 +27,39 @@ pub struct NSFastEnumerationState {
     pub extra: [u32; 5],
 }
 unsafe impl SafeRead for NSFastEnumerationState {}
+
+/// Shortcut for host code implementing `countByEnumeratingWithState:` for a
+/// collection whose contents can be provided as a slice. `state` is used to
+/// store the index of the next object, so the slice must be the same on each
+/// call (i.e. the collection must not be mutated during enumeration).
+pub fn fast_enumeration_helper(
+    mem: &mut Mem,
+    this: id,
+    objects: &[id],
+    state: MutPtr<NSFastEnumerationState>,
+    stackbuf: MutPtr<id>,
+    len: NSUInteger,
+) -> NSUInteger {
+    let NSFastEnumerationState { state: start, .. } = mem.read(state);
+    let start = start as usize;
+    if start >= objects.len() {
+        return 0; // end of iteration
+    }
+
+    let batch = &objects[start..objects.len().min(start + len as usize)];
+    for (i, &object) in batch.iter().enumerate() {
+        mem.write(stackbuf + i as GuestUSize, object);
+    }
+    mem.write(
+        state,
+        NSFastEnumerationState {
+            state: (start + batch.len()) as u32,
+            items_ptr: stackbuf,
+            // can be anything as long as it's dereferenceable and the same
+            // each iteration
+            mutations_ptr: this.cast(),
+            extra: Default::default(),
+        },
+    );
+    batch.len() as NSUInteger
+}
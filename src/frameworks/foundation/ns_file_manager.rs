@@ -5,10 +5,14 @@
  */
 //! `NSFileManager` etc.
 
-use super::{ns_array, ns_string, NSUInteger};
-use crate::dyld::{export_c_func, FunctionExports};
-use crate::objc::{autorelease, id};
+use super::ns_date::NSTimeIntervalSince1970;
+use super::{ns_array, ns_date, ns_dictionary, ns_string, NSTimeInterval, NSUInteger};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::fs::GuestPath;
+use crate::mem::MutPtr;
+use crate::objc::{autorelease, id, nil, objc_classes, ClassExports, TrivialHostObject};
 use crate::Environment;
+use std::time::SystemTime;
 
 type NSSearchPathDirectory = NSUInteger;
 const NSDocumentDirectory: NSSearchPathDirectory = 9;
@@ -16,6 +20,37 @@ const NSDocumentDirectory: NSSearchPathDirectory = 9;
 type NSSearchPathDomainMask = NSUInteger;
 const NSUserDomainMask: NSSearchPathDomainMask = 1;
 
+pub const NSFileType: &str = "NSFileType";
+pub const NSFileTypeDirectory: &str = "NSFileTypeDirectory";
+pub const NSFileTypeRegular: &str = "NSFileTypeRegular";
+pub const NSFileCreationDate: &str = "NSFileCreationDate";
+pub const NSFileModificationDate: &str = "NSFileModificationDate";
+
+pub const CONSTANTS: ConstantExports = &[
+    ("_NSFileType", HostConstant::NSString(NSFileType)),
+    (
+        "_NSFileTypeDirectory",
+        HostConstant::NSString(NSFileTypeDirectory),
+    ),
+    (
+        "_NSFileTypeRegular",
+        HostConstant::NSString(NSFileTypeRegular),
+    ),
+    (
+        "_NSFileCreationDate",
+        HostConstant::NSString(NSFileCreationDate),
+    ),
+    (
+        "_NSFileModificationDate",
+        HostConstant::NSString(NSFileModificationDate),
+    ),
+];
+
+#[derive(Default)]
+pub struct State {
+    default_manager: Option<id>,
+}
+
 fn NSSearchPathForDirectoriesInDomains(
     env: &mut Environment,
     directory: NSSearchPathDirectory,
@@ -35,3 +70,97 @@ fn NSSearchPathForDirectoriesInDomains(
 
 pub const FUNCTIONS: FunctionExports =
     &[export_c_func!(NSSearchPathForDirectoriesInDomains(_, _, _))];
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSFileManager: NSObject
+
++ (id)defaultManager {
+    if let Some(existing) = env.framework_state.foundation.ns_file_manager.default_manager {
+        existing
+    } else {
+        let new = env.objc.alloc_static_object(
+            this,
+            Box::new(TrivialHostObject),
+            &mut env.mem
+        );
+        env.framework_state.foundation.ns_file_manager.default_manager = Some(new);
+        new
+    }
+}
+
+- (bool)fileExistsAtPath:(id)path { // NSString*
+    let path = ns_string::to_rust_string(env, path); // TODO: avoid copy
+    env.fs.metadata(GuestPath::new(&path)).is_some()
+}
+- (bool)fileExistsAtPath:(id)path // NSString*
+             isDirectory:(MutPtr<u8>)is_dir_out { // BOOL*
+    let path = ns_string::to_rust_string(env, path); // TODO: avoid copy
+    let Some(metadata) = env.fs.metadata(GuestPath::new(&path)) else {
+        return false;
+    };
+    if !is_dir_out.is_null() {
+        env.mem.write(is_dir_out, metadata.is_dir as u8);
+    }
+    true
+}
+
+- (id)attributesOfItemAtPath:(id)path // NSString*
+                       error:(MutPtr<id>)error { // NSError**
+    assert!(error.is_null()); // TODO: error handling
+    attributes_of_item(env, path)
+}
+// Deprecated predecessor of attributesOfItemAtPath:error:
+- (id)fileAttributesAtPath:(id)path // NSString*
+              traverseLink:(bool)_traverse_link {
+    // There are no symlinks in the guest filesystem.
+    attributes_of_item(env, path)
+}
+
+@end
+
+};
+
+fn date_from_system_time(env: &mut Environment, time: SystemTime) -> id {
+    let since_1970: NSTimeInterval = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    };
+    let date = ns_date::from_time_interval(env, since_1970 - NSTimeIntervalSince1970);
+    autorelease(env, date)
+}
+
+/// Returns an autoreleased `NSDictionary`, or `nil` if there is no such item.
+fn attributes_of_item(env: &mut Environment, path: id) -> id {
+    let path = ns_string::to_rust_string(env, path); // TODO: avoid copy
+    let Some(metadata) = env.fs.metadata(GuestPath::new(&path)) else {
+        return nil;
+    };
+
+    // TODO: NSFileSize and other attributes
+    let type_key = ns_string::get_static_str(env, NSFileType);
+    let type_value = ns_string::get_static_str(
+        env,
+        if metadata.is_dir {
+            NSFileTypeDirectory
+        } else {
+            NSFileTypeRegular
+        },
+    );
+    let created_key = ns_string::get_static_str(env, NSFileCreationDate);
+    let created_value = date_from_system_time(env, metadata.created);
+    let modified_key = ns_string::get_static_str(env, NSFileModificationDate);
+    let modified_value = date_from_system_time(env, metadata.modified);
+
+    let dict = ns_dictionary::dict_from_keys_and_objects(
+        env,
+        &[
+            (type_key, type_value),
+            (created_key, created_value),
+            (modified_key, modified_value),
+        ],
+    );
+    autorelease(env, dict)
+}
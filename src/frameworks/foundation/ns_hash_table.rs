@@ -0,0 +1,139 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSHashTable`.
+
+use super::ns_array;
+use super::ns_fast_enumeration::{fast_enumeration_helper, NSFastEnumerationState};
+use super::ns_pointer_functions::{
+    NSPointerFunctionsOptions, NSPointerFunctionsStrongMemory, NSPointerFunctionsWeakMemory,
+    PointerFunctions, Slot,
+};
+use super::NSUInteger;
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{autorelease, id, msg, nil, objc_classes, retain, ClassExports, HostObject};
+use crate::Environment;
+
+struct HashTableHostObject {
+    functions: PointerFunctions,
+    /// Lookups are linear, but hash tables are usually small.
+    members: Vec<Slot>,
+}
+impl HostObject for HashTableHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSHashTable: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(HashTableHostObject {
+        functions: PointerFunctions::new(NSPointerFunctionsStrongMemory),
+        members: Vec::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)hashTableWithOptions:(NSPointerFunctionsOptions)options {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithOptions:options capacity:0u32];
+    autorelease(env, new)
+}
++ (id)weakObjectsHashTable {
+    msg![env; this hashTableWithOptions:NSPointerFunctionsWeakMemory]
+}
+
+- (id)initWithOptions:(NSPointerFunctionsOptions)options
+             capacity:(NSUInteger)_capacity {
+    env.objc.borrow_mut::<HashTableHostObject>(this).functions = PointerFunctions::new(options);
+    this
+}
+
+- (())dealloc {
+    let () = msg![env; this removeAllObjects];
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (NSUInteger)count {
+    live_members(env, this).len() as NSUInteger
+}
+
+- (id)member:(id)object {
+    match find_member(env, this, object) {
+        Some(idx) => env.objc.borrow::<HashTableHostObject>(this).members[idx].load(&env.objc),
+        None => nil,
+    }
+}
+- (bool)containsObject:(id)object {
+    find_member(env, this, object).is_some()
+}
+- (id)anyObject {
+    live_members(env, this).first().copied().unwrap_or(nil)
+}
+- (id)allObjects {
+    let members = live_members(env, this);
+    for &member in &members {
+        retain(env, member);
+    }
+    let array = ns_array::from_vec(env, members);
+    autorelease(env, array)
+}
+
+- (())addObject:(id)object {
+    if object == nil || find_member(env, this, object).is_some() {
+        return;
+    }
+    let functions = env.objc.borrow::<HashTableHostObject>(this).functions;
+    let slot = functions.store(env, object);
+    env.objc.borrow_mut::<HashTableHostObject>(this).members.push(slot);
+}
+- (())removeObject:(id)object {
+    if let Some(idx) = find_member(env, this, object) {
+        let slot = env.objc.borrow_mut::<HashTableHostObject>(this).members.remove(idx);
+        slot.release(env);
+    }
+}
+- (())removeAllObjects {
+    let members = std::mem::take(&mut env.objc.borrow_mut::<HashTableHostObject>(this).members);
+    for slot in members {
+        slot.release(env);
+    }
+}
+
+// NSFastEnumeration implementation
+- (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
+                                  objects:(MutPtr<id>)stackbuf
+                                    count:(NSUInteger)len {
+    // TODO: avoid copying the members on every call
+    let members = live_members(env, this);
+    fast_enumeration_helper(&mut env.mem, this, &members, state, stackbuf, len)
+}
+
+@end
+
+};
+
+/// Get the members, forgetting any weakly-held ones that have been
+/// deallocated.
+fn live_members(env: &mut Environment, table: id) -> Vec<id> {
+    let host_object = env.objc.borrow_mut::<HashTableHostObject>(table);
+    let mut members = std::mem::take(&mut host_object.members);
+    members.retain(|slot| slot.load(&env.objc) != nil);
+    let loaded = members.iter().map(|slot| slot.load(&env.objc)).collect();
+    env.objc.borrow_mut::<HashTableHostObject>(table).members = members;
+    loaded
+}
+
+fn find_member(env: &mut Environment, table: id, object: id) -> Option<usize> {
+    let functions = env.objc.borrow::<HashTableHostObject>(table).functions;
+    let members = live_members(env, table);
+    for (idx, member) in members.into_iter().enumerate() {
+        if functions.is_equal(env, member, object) {
+            return Some(idx);
+        }
+    }
+    None
+}
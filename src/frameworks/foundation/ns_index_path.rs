@@ -0,0 +1,154 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSIndexPath`, including the UIKit additions for table views.
+
+use super::{NSComparisonResult, NSOrderedAscending, NSOrderedDescending, NSOrderedSame};
+use super::{NSNotFound, NSUInteger};
+use crate::mem::{ConstPtr, MutPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, objc_classes, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::cmp::Ordering;
+
+#[derive(Default)]
+struct IndexPathHostObject {
+    indexes: Vec<NSUInteger>,
+}
+impl HostObject for IndexPathHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSIndexPath: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::<IndexPathHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)indexPathWithIndex:(NSUInteger)index {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithIndex:index];
+    autorelease(env, new)
+}
++ (id)indexPathWithIndexes:(ConstPtr<NSUInteger>)indexes
+                    length:(NSUInteger)length {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithIndexes:indexes length:length];
+    autorelease(env, new)
+}
+
+// UIKit additions
++ (id)indexPathForRow:(NSUInteger)row
+            inSection:(NSUInteger)section {
+    let new: id = msg![env; this alloc];
+    env.objc.borrow_mut::<IndexPathHostObject>(new).indexes = vec![section, row];
+    autorelease(env, new)
+}
+
+- (id)initWithIndex:(NSUInteger)index {
+    env.objc.borrow_mut::<IndexPathHostObject>(this).indexes = vec![index];
+    this
+}
+- (id)initWithIndexes:(ConstPtr<NSUInteger>)indexes
+               length:(NSUInteger)length {
+    let indexes = (0..length).map(|i| env.mem.read(indexes + i)).collect();
+    env.objc.borrow_mut::<IndexPathHostObject>(this).indexes = indexes;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+- (NSUInteger)length {
+    env.objc.borrow::<IndexPathHostObject>(this).indexes.len().try_into().unwrap()
+}
+- (NSUInteger)indexAtPosition:(NSUInteger)position {
+    let indexes = &env.objc.borrow::<IndexPathHostObject>(this).indexes;
+    indexes
+        .get(position as usize)
+        .copied()
+        .unwrap_or(NSNotFound as NSUInteger)
+}
+- (())getIndexes:(MutPtr<NSUInteger>)buffer {
+    let indexes = env.objc.borrow::<IndexPathHostObject>(this).indexes.clone();
+    for (i, index) in indexes.into_iter().enumerate() {
+        env.mem.write(buffer + i as NSUInteger, index);
+    }
+}
+
+- (id)indexPathByAddingIndex:(NSUInteger)index {
+    let mut indexes = env.objc.borrow::<IndexPathHostObject>(this).indexes.clone();
+    indexes.push(index);
+    let new: id = msg_class![env; NSIndexPath alloc];
+    env.objc.borrow_mut::<IndexPathHostObject>(new).indexes = indexes;
+    autorelease(env, new)
+}
+- (id)indexPathByRemovingLastIndex {
+    let mut indexes = env.objc.borrow::<IndexPathHostObject>(this).indexes.clone();
+    indexes.pop();
+    let new: id = msg_class![env; NSIndexPath alloc];
+    env.objc.borrow_mut::<IndexPathHostObject>(new).indexes = indexes;
+    autorelease(env, new)
+}
+
+- (NSComparisonResult)compare:(id)other { // NSIndexPath*
+    let a = &env.objc.borrow::<IndexPathHostObject>(this).indexes;
+    let b = &env.objc.borrow::<IndexPathHostObject>(other).indexes;
+    match a.cmp(b) {
+        Ordering::Less => NSOrderedAscending,
+        Ordering::Equal => NSOrderedSame,
+        Ordering::Greater => NSOrderedDescending,
+    }
+}
+
+- (NSUInteger)hash {
+    let indexes = &env.objc.borrow::<IndexPathHostObject>(this).indexes;
+    indexes
+        .iter()
+        .fold(indexes.len() as NSUInteger, |hash, &index| {
+            hash.wrapping_mul(31).wrapping_add(index)
+        })
+}
+- (bool)isEqual:(id)other {
+    if this == other {
+        return true;
+    }
+    let class = env.objc.get_known_class("NSIndexPath", &mut env.mem);
+    if !msg![env; other isKindOfClass:class] {
+        return false;
+    }
+    env.objc.borrow::<IndexPathHostObject>(this).indexes
+        == env.objc.borrow::<IndexPathHostObject>(other).indexes
+}
+
+// UIKit additions
+- (NSUInteger)section {
+    msg![env; this indexAtPosition:0u32]
+}
+- (NSUInteger)row {
+    msg![env; this indexAtPosition:1u32]
+}
+
+@end
+
+};
+
+/// Shortcut for host code: create an autoreleased `NSIndexPath` for a row in a
+/// table view section.
+pub fn index_path_for_row(env: &mut Environment, section: NSUInteger, row: NSUInteger) -> id {
+    msg_class![env; NSIndexPath indexPathForRow:row inSection:section]
+}
+
+/// Shortcut for host code: get the section and row of an `NSIndexPath`.
+pub fn section_and_row(env: &mut Environment, index_path: id) -> (NSUInteger, NSUInteger) {
+    let indexes = &env.objc.borrow::<IndexPathHostObject>(index_path).indexes;
+    (indexes[0], indexes[1])
+}
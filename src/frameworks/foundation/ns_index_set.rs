@@ -0,0 +1,389 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSIndexSet` and `NSMutableIndexSet`.
+
+use super::{BlockLiteral, NSInteger, NSNotFound, NSRange, NSUInteger};
+use crate::abi::{CallFromHost, GuestArg};
+use crate::mem::{ConstVoidPtr, MutPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, objc_classes, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+pub type NSEnumerationOptions = NSUInteger;
+#[allow(dead_code)]
+pub const NSEnumerationConcurrent: NSEnumerationOptions = 1 << 0;
+pub const NSEnumerationReverse: NSEnumerationOptions = 1 << 1;
+
+const NOT_FOUND: NSUInteger = NSNotFound as NSUInteger;
+
+#[derive(Default, Clone)]
+struct IndexSetHostObject {
+    /// Sorted, non-empty, and neither overlapping nor adjacent.
+    ranges: Vec<NSRange>,
+}
+impl HostObject for IndexSetHostObject {}
+
+fn range_end(range: NSRange) -> NSUInteger {
+    range.location + range.length
+}
+
+impl IndexSetHostObject {
+    fn count(&self) -> NSUInteger {
+        self.ranges.iter().map(|range| range.length).sum()
+    }
+    fn contains(&self, index: NSUInteger) -> bool {
+        self.ranges
+            .iter()
+            .any(|&range| range.location <= index && index < range_end(range))
+    }
+    fn count_in_range(&self, within: NSRange) -> NSUInteger {
+        self.ranges
+            .iter()
+            .map(|&range| {
+                let start = range.location.max(within.location);
+                let end = range_end(range).min(range_end(within));
+                end.saturating_sub(start)
+            })
+            .sum()
+    }
+    fn indexes(&self) -> impl DoubleEndedIterator<Item = NSUInteger> + '_ {
+        self.ranges
+            .iter()
+            .flat_map(|&range| range.location..range_end(range))
+    }
+    /// The first index that is `>= index`.
+    fn index_at_or_after(&self, index: NSUInteger) -> NSUInteger {
+        for &range in &self.ranges {
+            if index < range_end(range) {
+                return range.location.max(index);
+            }
+        }
+        NOT_FOUND
+    }
+    /// The last index that is `<= index`.
+    fn index_at_or_before(&self, index: NSUInteger) -> NSUInteger {
+        for &range in self.ranges.iter().rev() {
+            if range.location <= index {
+                return (range_end(range) - 1).min(index);
+            }
+        }
+        NOT_FOUND
+    }
+
+    fn add_range(&mut self, new: NSRange) {
+        if new.length == 0 {
+            return;
+        }
+        let mut start = new.location;
+        let mut end = range_end(new);
+        // Absorb every range that overlaps or touches the new one.
+        self.ranges.retain(|&range| {
+            if range_end(range) < start || range.location > end {
+                true
+            } else {
+                start = start.min(range.location);
+                end = end.max(range_end(range));
+                false
+            }
+        });
+        let idx = self
+            .ranges
+            .iter()
+            .position(|range| range.location > start)
+            .unwrap_or(self.ranges.len());
+        self.ranges.insert(
+            idx,
+            NSRange {
+                location: start,
+                length: end - start,
+            },
+        );
+    }
+    fn remove_range(&mut self, removed: NSRange) {
+        if removed.length == 0 {
+            return;
+        }
+        let start = removed.location;
+        let end = range_end(removed);
+        let mut new_ranges = Vec::with_capacity(self.ranges.len() + 1);
+        for &range in &self.ranges {
+            if range.location < start {
+                new_ranges.push(NSRange {
+                    location: range.location,
+                    length: range_end(range).min(start) - range.location,
+                });
+            }
+            if range_end(range) > end {
+                let location = range.location.max(end);
+                new_ranges.push(NSRange {
+                    location,
+                    length: range_end(range) - location,
+                });
+            }
+        }
+        self.ranges = new_ranges;
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSIndexSet: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::<IndexSetHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)indexSet {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new init];
+    autorelease(env, new)
+}
++ (id)indexSetWithIndex:(NSUInteger)index {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithIndex:index];
+    autorelease(env, new)
+}
++ (id)indexSetWithIndexesInRange:(NSRange)range {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithIndexesInRange:range];
+    autorelease(env, new)
+}
+
+- (id)initWithIndex:(NSUInteger)index {
+    msg![env; this initWithIndexesInRange:(NSRange { location: index, length: 1 })]
+}
+- (id)initWithIndexesInRange:(NSRange)range {
+    env.objc.borrow_mut::<IndexSetHostObject>(this).add_range(range);
+    this
+}
+- (id)initWithIndexSet:(id)other { // NSIndexSet*
+    let ranges = env.objc.borrow::<IndexSetHostObject>(other).ranges.clone();
+    env.objc.borrow_mut::<IndexSetHostObject>(this).ranges = ranges;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(MutVoidPtr)_zone {
+    let new: id = msg_class![env; NSMutableIndexSet alloc];
+    msg![env; new initWithIndexSet:this]
+}
+
+- (NSUInteger)count {
+    env.objc.borrow::<IndexSetHostObject>(this).count()
+}
+- (NSUInteger)countOfIndexesInRange:(NSRange)range {
+    env.objc.borrow::<IndexSetHostObject>(this).count_in_range(range)
+}
+
+- (bool)containsIndex:(NSUInteger)index {
+    env.objc.borrow::<IndexSetHostObject>(this).contains(index)
+}
+- (bool)containsIndexesInRange:(NSRange)range {
+    let host_object = env.objc.borrow::<IndexSetHostObject>(this);
+    host_object.count_in_range(range) == range.length
+}
+- (bool)containsIndexes:(id)other { // NSIndexSet*
+    let other_ranges = env.objc.borrow::<IndexSetHostObject>(other).ranges.clone();
+    let host_object = env.objc.borrow::<IndexSetHostObject>(this);
+    other_ranges
+        .into_iter()
+        .all(|range| host_object.count_in_range(range) == range.length)
+}
+- (bool)intersectsIndexesInRange:(NSRange)range {
+    env.objc.borrow::<IndexSetHostObject>(this).count_in_range(range) != 0
+}
+- (bool)isEqualToIndexSet:(id)other { // NSIndexSet*
+    this == other
+        || env.objc.borrow::<IndexSetHostObject>(this).ranges
+            == env.objc.borrow::<IndexSetHostObject>(other).ranges
+}
+
+- (NSUInteger)firstIndex {
+    env.objc.borrow::<IndexSetHostObject>(this).index_at_or_after(0)
+}
+- (NSUInteger)lastIndex {
+    env.objc.borrow::<IndexSetHostObject>(this).index_at_or_before(NOT_FOUND - 1)
+}
+- (NSUInteger)indexGreaterThanIndex:(NSUInteger)index {
+    if index >= NOT_FOUND - 1 {
+        return NOT_FOUND;
+    }
+    env.objc.borrow::<IndexSetHostObject>(this).index_at_or_after(index + 1)
+}
+- (NSUInteger)indexGreaterThanOrEqualToIndex:(NSUInteger)index {
+    env.objc.borrow::<IndexSetHostObject>(this).index_at_or_after(index)
+}
+- (NSUInteger)indexLessThanIndex:(NSUInteger)index {
+    if index == 0 {
+        return NOT_FOUND;
+    }
+    env.objc.borrow::<IndexSetHostObject>(this).index_at_or_before(index - 1)
+}
+- (NSUInteger)indexLessThanOrEqualToIndex:(NSUInteger)index {
+    env.objc.borrow::<IndexSetHostObject>(this).index_at_or_before(index)
+}
+
+- (NSUInteger)getIndexes:(MutPtr<NSUInteger>)buffer
+                maxCount:(NSUInteger)max_count
+            inIndexRange:(MutPtr<NSRange>)range_ptr {
+    let within = if range_ptr.is_null() {
+        NSRange { location: 0, length: NOT_FOUND }
+    } else {
+        env.mem.read(range_ptr)
+    };
+    let indexes: Vec<NSUInteger> = env
+        .objc
+        .borrow::<IndexSetHostObject>(this)
+        .indexes()
+        .skip_while(|&index| index < within.location)
+        .take_while(|&index| index < range_end(within))
+        .take(max_count as usize)
+        .collect();
+    for (i, &index) in indexes.iter().enumerate() {
+        env.mem.write(buffer + i as NSUInteger, index);
+    }
+    if !range_ptr.is_null() {
+        // The range is updated so the next call can continue where this one
+        // stopped.
+        if let Some(&last) = indexes.last() {
+            env.mem.write(range_ptr, NSRange {
+                location: last + 1,
+                length: range_end(within) - (last + 1),
+            });
+        }
+    }
+    indexes.len() as NSUInteger
+}
+
+- (())enumerateIndexesUsingBlock:(ConstVoidPtr)block {
+    msg![env; this enumerateIndexesWithOptions:0u32 usingBlock:block]
+}
+- (())enumerateIndexesWithOptions:(NSEnumerationOptions)options
+                       usingBlock:(ConstVoidPtr)block {
+    let host_object = env.objc.borrow::<IndexSetHostObject>(this);
+    // A copy is needed in case the block mutates the set.
+    let indexes: Vec<NSUInteger> = if options & NSEnumerationReverse != 0 {
+        host_object.indexes().rev().collect()
+    } else {
+        host_object.indexes().collect()
+    };
+    call_block_for_each(env, block, indexes);
+}
+- (())enumerateRangesUsingBlock:(ConstVoidPtr)block {
+    msg![env; this enumerateRangesWithOptions:0u32 usingBlock:block]
+}
+- (())enumerateRangesWithOptions:(NSEnumerationOptions)options
+                      usingBlock:(ConstVoidPtr)block {
+    let mut ranges = env.objc.borrow::<IndexSetHostObject>(this).ranges.clone();
+    if options & NSEnumerationReverse != 0 {
+        ranges.reverse();
+    }
+    call_block_for_each(env, block, ranges);
+}
+
+@end
+
+@implementation NSMutableIndexSet: NSIndexSet
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    let new: id = msg_class![env; NSIndexSet alloc];
+    msg![env; new initWithIndexSet:this]
+}
+
+- (())addIndex:(NSUInteger)index {
+    msg![env; this addIndexesInRange:(NSRange { location: index, length: 1 })]
+}
+- (())addIndexesInRange:(NSRange)range {
+    env.objc.borrow_mut::<IndexSetHostObject>(this).add_range(range);
+}
+- (())addIndexes:(id)other { // NSIndexSet*
+    let other_ranges = env.objc.borrow::<IndexSetHostObject>(other).ranges.clone();
+    let host_object = env.objc.borrow_mut::<IndexSetHostObject>(this);
+    for range in other_ranges {
+        host_object.add_range(range);
+    }
+}
+
+- (())removeIndex:(NSUInteger)index {
+    msg![env; this removeIndexesInRange:(NSRange { location: index, length: 1 })]
+}
+- (())removeIndexesInRange:(NSRange)range {
+    env.objc.borrow_mut::<IndexSetHostObject>(this).remove_range(range);
+}
+- (())removeIndexes:(id)other { // NSIndexSet*
+    let other_ranges = env.objc.borrow::<IndexSetHostObject>(other).ranges.clone();
+    let host_object = env.objc.borrow_mut::<IndexSetHostObject>(this);
+    for range in other_ranges {
+        host_object.remove_range(range);
+    }
+}
+- (())removeAllIndexes {
+    env.objc.borrow_mut::<IndexSetHostObject>(this).ranges.clear();
+}
+
+- (())shiftIndexesStartingAtIndex:(NSUInteger)index
+                               by:(NSInteger)delta {
+    let host_object = env.objc.borrow_mut::<IndexSetHostObject>(this);
+    let old = std::mem::take(host_object);
+    let mut new = old.clone();
+    new.remove_range(NSRange {
+        location: index,
+        length: NOT_FOUND - index,
+    });
+    // When shifting down, the indexes that the shifted ones land on are
+    // removed.
+    if delta < 0 {
+        let removed_start = index.saturating_sub(delta.unsigned_abs());
+        new.remove_range(NSRange {
+            location: removed_start,
+            length: index - removed_start,
+        });
+    }
+    for range in old.ranges {
+        let start = range.location.max(index);
+        let end = range_end(range);
+        if start >= end {
+            continue;
+        }
+        // Indexes shifted below zero are dropped.
+        let shifted_start = (start as i64 + delta as i64).max(0);
+        let shifted_end = (end as i64 + delta as i64).min(NOT_FOUND as i64);
+        if shifted_start < shifted_end {
+            new.add_range(NSRange {
+                location: shifted_start as NSUInteger,
+                length: (shifted_end - shifted_start) as NSUInteger,
+            });
+        }
+    }
+    *env.objc.borrow_mut::<IndexSetHostObject>(this) = new;
+}
+
+@end
+
+};
+
+/// Call a `void (^)(T, BOOL *stop)` block for each item until it sets `stop`.
+fn call_block_for_each<T: GuestArg>(env: &mut Environment, block: ConstVoidPtr, items: Vec<T>) {
+    let BlockLiteral { invoke, .. } = env.mem.read(block.cast());
+    let stop: MutPtr<u8> = env.mem.alloc(1).cast();
+    env.mem.write(stop, 0);
+    for item in items {
+        let () = invoke.call_from_host(env, (block, item, stop));
+        if env.mem.read(stop) != 0 {
+            break;
+        }
+    }
+    env.mem.free(stop.cast());
+}
@@ -6,12 +6,14 @@
 //! `NSLocale`.
 
 use super::{ns_array, ns_string};
-use crate::objc::{id, objc_classes, ClassExports};
+use crate::mem::MutVoidPtr;
+use crate::objc::{autorelease, id, msg, objc_classes, retain, ClassExports, HostObject};
 use crate::Environment;
 
 #[derive(Default)]
 pub struct State {
     preferred_languages: Option<id>,
+    current_locale: Option<id>,
 }
 impl State {
     fn get(env: &mut Environment) -> &mut State {
@@ -19,6 +21,12 @@ impl State {
     }
 }
 
+struct NSLocaleHostObject {
+    /// Identifier like `en_US`, or the empty string for the system locale.
+    identifier: String,
+}
+impl HostObject for NSLocaleHostObject {}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -53,8 +61,72 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
-// TODO: constructors, more accessors
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSLocaleHostObject {
+        identifier: String::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)currentLocale {
+    if let Some(existing) = State::get(env).current_locale {
+        return existing;
+    }
+    let identifier = if let Ok(lang) = std::env::var("LANG") {
+        // turn e.g. "sv_SE.UTF-8" into just "sv_SE"
+        let identifier = lang.split_once('.')
+                             .map(|(a, _b)| a)
+                             .unwrap_or(&lang)
+                             .to_string();
+        log!("The app requested your locale. {:?} will be reported based on your LANG environment variable.", identifier);
+        identifier
+    } else {
+        let identifier = "en_US".to_string();
+        log!("The app requested your locale. No LANG environment variable was found, so {:?} (English, United States) will be reported.", identifier);
+        identifier
+    };
+    let new: id = msg![env; this alloc];
+    env.objc.borrow_mut::<NSLocaleHostObject>(new).identifier = identifier;
+    State::get(env).current_locale = Some(new);
+    new
+}
++ (id)autoupdatingCurrentLocale {
+    msg![env; this currentLocale]
+}
++ (id)systemLocale {
+    // The system locale has an empty identifier.
+    let new: id = msg![env; this alloc];
+    autorelease(env, new)
+}
+
+- (id)initWithLocaleIdentifier:(id)identifier { // NSString*
+    let identifier = ns_string::to_rust_string(env, identifier).into_owned();
+    env.objc.borrow_mut::<NSLocaleHostObject>(this).identifier = identifier;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    // NSLocale is immutable
+    retain(env, this)
+}
+
+- (id)localeIdentifier {
+    let identifier = get_identifier(env, this);
+    let identifier = ns_string::from_rust_string(env, identifier);
+    autorelease(env, identifier)
+}
+
+// TODO: more accessors
 
 @end
 
 };
+
+/// Shortcut for host code: get the identifier of a locale, e.g. `en_US`.
+pub fn get_identifier(env: &mut Environment, locale: id) -> String {
+    env.objc
+        .borrow::<NSLocaleHostObject>(locale)
+        .identifier
+        .clone()
+}
@@ -0,0 +1,201 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSMapTable`.
+
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::ns_fast_enumeration::{fast_enumeration_helper, NSFastEnumerationState};
+use super::ns_pointer_functions::{
+    NSPointerFunctionsOptions, NSPointerFunctionsStrongMemory, NSPointerFunctionsWeakMemory,
+    PointerFunctions, Slot,
+};
+use super::NSUInteger;
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{autorelease, id, msg, nil, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+struct MapTableHostObject {
+    key_functions: PointerFunctions,
+    value_functions: PointerFunctions,
+    /// Lookups are linear, but map tables are usually small.
+    entries: Vec<(Slot, Slot)>,
+}
+impl HostObject for MapTableHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSMapTable: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(MapTableHostObject {
+        key_functions: PointerFunctions::new(NSPointerFunctionsStrongMemory),
+        value_functions: PointerFunctions::new(NSPointerFunctionsStrongMemory),
+        entries: Vec::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)mapTableWithKeyOptions:(NSPointerFunctionsOptions)key_options
+                valueOptions:(NSPointerFunctionsOptions)value_options {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithKeyOptions:key_options
+                                      valueOptions:value_options
+                                          capacity:0u32];
+    autorelease(env, new)
+}
++ (id)strongToStrongObjectsMapTable {
+    msg![env; this mapTableWithKeyOptions:NSPointerFunctionsStrongMemory
+                             valueOptions:NSPointerFunctionsStrongMemory]
+}
++ (id)weakToStrongObjectsMapTable {
+    msg![env; this mapTableWithKeyOptions:NSPointerFunctionsWeakMemory
+                             valueOptions:NSPointerFunctionsStrongMemory]
+}
++ (id)strongToWeakObjectsMapTable {
+    msg![env; this mapTableWithKeyOptions:NSPointerFunctionsStrongMemory
+                             valueOptions:NSPointerFunctionsWeakMemory]
+}
++ (id)weakToWeakObjectsMapTable {
+    msg![env; this mapTableWithKeyOptions:NSPointerFunctionsWeakMemory
+                             valueOptions:NSPointerFunctionsWeakMemory]
+}
+
+- (id)initWithKeyOptions:(NSPointerFunctionsOptions)key_options
+            valueOptions:(NSPointerFunctionsOptions)value_options
+                capacity:(NSUInteger)_capacity {
+    let host_object = env.objc.borrow_mut::<MapTableHostObject>(this);
+    host_object.key_functions = PointerFunctions::new(key_options);
+    host_object.value_functions = PointerFunctions::new(value_options);
+    this
+}
+
+- (())dealloc {
+    let entries = std::mem::take(&mut env.objc.borrow_mut::<MapTableHostObject>(this).entries);
+    for (key, value) in entries {
+        key.release(env);
+        value.release(env);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (NSUInteger)count {
+    prune(env, this);
+    env.objc.borrow::<MapTableHostObject>(this).entries.len() as NSUInteger
+}
+
+- (id)objectForKey:(id)key {
+    prune(env, this);
+    match find_entry(env, this, key) {
+        Some(idx) => env.objc.borrow::<MapTableHostObject>(this).entries[idx]
+            .1
+            .load(&env.objc),
+        None => nil,
+    }
+}
+
+- (())setObject:(id)object
+         forKey:(id)key {
+    assert!(key != nil); // TODO: raise proper exception
+    prune(env, this);
+    let host_object = env.objc.borrow::<MapTableHostObject>(this);
+    let (key_functions, value_functions) = (host_object.key_functions, host_object.value_functions);
+    let value_slot = value_functions.store(env, object);
+    if let Some(idx) = find_entry(env, this, key) {
+        let entries = &mut env.objc.borrow_mut::<MapTableHostObject>(this).entries;
+        let old_value = std::mem::replace(&mut entries[idx].1, value_slot);
+        old_value.release(env);
+    } else {
+        let key_slot = key_functions.store(env, key);
+        env.objc
+            .borrow_mut::<MapTableHostObject>(this)
+            .entries
+            .push((key_slot, value_slot));
+    }
+}
+
+- (())removeObjectForKey:(id)key {
+    if let Some(idx) = find_entry(env, this, key) {
+        let (key, value) = env.objc.borrow_mut::<MapTableHostObject>(this).entries.remove(idx);
+        key.release(env);
+        value.release(env);
+    }
+    prune(env, this);
+}
+- (())removeAllObjects {
+    let entries = std::mem::take(&mut env.objc.borrow_mut::<MapTableHostObject>(this).entries);
+    for (key, value) in entries {
+        key.release(env);
+        value.release(env);
+    }
+}
+
+- (id)dictionaryRepresentation {
+    let keys_and_objects = live_entries(env, this);
+    let dict = dict_from_keys_and_objects(env, &keys_and_objects);
+    autorelease(env, dict)
+}
+
+// NSFastEnumeration implementation
+- (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
+                                  objects:(MutPtr<id>)stackbuf
+                                    count:(NSUInteger)len {
+    // TODO: avoid copying the keys on every call
+    let keys: Vec<id> = live_entries(env, this).into_iter().map(|(key, _)| key).collect();
+    fast_enumeration_helper(&mut env.mem, this, &keys, state, stackbuf, len)
+}
+
+@end
+
+};
+
+/// Remove entries whose weakly-held key or value has been deallocated.
+fn prune(env: &mut Environment, table: id) {
+    let host_object = env.objc.borrow::<MapTableHostObject>(table);
+    let dead: Vec<usize> = host_object
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(_, (key, value))| {
+            matches!(key, Slot::Weak(_)) && key.load(&env.objc) == nil
+                || matches!(value, Slot::Weak(_)) && value.load(&env.objc) == nil
+        })
+        .map(|(idx, _)| idx)
+        .collect();
+    for idx in dead.into_iter().rev() {
+        let (key, value) = env
+            .objc
+            .borrow_mut::<MapTableHostObject>(table)
+            .entries
+            .remove(idx);
+        key.release(env);
+        value.release(env);
+    }
+}
+
+fn find_entry(env: &mut Environment, table: id, key: id) -> Option<usize> {
+    let key_functions = env.objc.borrow::<MapTableHostObject>(table).key_functions;
+    let mut idx = 0;
+    loop {
+        let entries = &env.objc.borrow::<MapTableHostObject>(table).entries;
+        let candidate = entries.get(idx)?.0.load(&env.objc);
+        if candidate != nil && key_functions.is_equal(env, candidate, key) {
+            return Some(idx);
+        }
+        idx += 1;
+    }
+}
+
+fn live_entries(env: &mut Environment, table: id) -> Vec<(id, id)> {
+    prune(env, table);
+    env.objc
+        .borrow::<MapTableHostObject>(table)
+        .entries
+        .iter()
+        .map(|(key, value)| (key.load(&env.objc), value.load(&env.objc)))
+        .filter(|&(_, value)| value != nil)
+        .collect()
+}
@@ -0,0 +1,85 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSNotification`.
+
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+
+struct NSNotificationHostObject {
+    /// `NSString*`
+    name: id,
+    object: id,
+    /// `NSDictionary*`
+    user_info: id,
+}
+impl HostObject for NSNotificationHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSNotification: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSNotificationHostObject {
+        name: nil,
+        object: nil,
+        user_info: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)notificationWithName:(id)name // NSString*
+                    object:(id)object {
+    msg![env; this notificationWithName:name object:object userInfo:nil]
+}
++ (id)notificationWithName:(id)name // NSString*
+                    object:(id)object
+                  userInfo:(id)user_info { // NSDictionary*
+    let new: id = msg![env; this alloc];
+    let name: id = msg![env; name copy];
+    retain(env, object);
+    retain(env, user_info);
+    *env.objc.borrow_mut(new) = NSNotificationHostObject {
+        name,
+        object,
+        user_info,
+    };
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let &NSNotificationHostObject {
+        name,
+        object,
+        user_info,
+    } = env.objc.borrow(this);
+    release(env, name);
+    release(env, object);
+    release(env, user_info);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+- (id)name {
+    env.objc.borrow::<NSNotificationHostObject>(this).name
+}
+- (id)object {
+    env.objc.borrow::<NSNotificationHostObject>(this).object
+}
+- (id)userInfo {
+    env.objc.borrow::<NSNotificationHostObject>(this).user_info
+}
+
+@end
+
+};
@@ -0,0 +1,177 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSNotificationCenter`.
+
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    id, msg, msg_class, msg_send, nil, objc_classes, release, ClassExports, HostObject, SEL,
+};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    default_center: Option<id>,
+}
+
+struct Observer {
+    /// Weak reference.
+    observer: id,
+    selector: SEL,
+    /// `NSString*`, strong reference, or [nil] to observe all notifications.
+    name: id,
+    /// Weak reference, or [nil] to observe notifications from any object.
+    object: id,
+}
+
+#[derive(Default)]
+struct NSNotificationCenterHostObject {
+    observers: Vec<Observer>,
+}
+impl HostObject for NSNotificationCenterHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSNotificationCenter: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::<NSNotificationCenterHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)defaultCenter {
+    if let Some(existing) = env.framework_state.foundation.ns_notification_center.default_center {
+        existing
+    } else {
+        let new = env.objc.alloc_static_object(
+            this,
+            Box::<NSNotificationCenterHostObject>::default(),
+            &mut env.mem
+        );
+        env.framework_state.foundation.ns_notification_center.default_center = Some(new);
+        new
+    }
+}
+
+- (())dealloc {
+    let observers = std::mem::take(
+        &mut env.objc.borrow_mut::<NSNotificationCenterHostObject>(this).observers
+    );
+    for observer in observers {
+        release(env, observer.name);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (())addObserver:(id)observer
+         selector:(SEL)selector
+             name:(id)name // NSString*
+           object:(id)object {
+    if observer == nil {
+        return;
+    }
+    let name: id = if name == nil { nil } else { msg![env; name copy] };
+    env.objc.borrow_mut::<NSNotificationCenterHostObject>(this).observers.push(Observer {
+        observer,
+        selector,
+        name,
+        object,
+    });
+}
+
+- (())removeObserver:(id)observer {
+    () = msg![env; this removeObserver:observer name:nil object:nil];
+}
+- (())removeObserver:(id)observer
+                name:(id)name // NSString*
+              object:(id)object {
+    let observers = std::mem::take(
+        &mut env.objc.borrow_mut::<NSNotificationCenterHostObject>(this).observers
+    );
+    let mut kept = Vec::with_capacity(observers.len());
+    for entry in observers {
+        let remove = entry.observer == observer
+            && (name == nil || names_equal(env, entry.name, name))
+            && (object == nil || entry.object == object);
+        if remove {
+            release(env, entry.name);
+        } else {
+            kept.push(entry);
+        }
+    }
+    // Observers might have been added while releasing the names.
+    let host_object = env.objc.borrow_mut::<NSNotificationCenterHostObject>(this);
+    kept.append(&mut host_object.observers);
+    host_object.observers = kept;
+}
+
+- (())postNotification:(id)notification { // NSNotification*
+    let name: id = msg![env; notification name];
+    let object: id = msg![env; notification object];
+
+    let host_object = env.objc.borrow::<NSNotificationCenterHostObject>(this);
+    let candidates: Vec<(id, SEL, id, id)> = host_object
+        .observers
+        .iter()
+        .filter(|entry| entry.object == nil || entry.object == object)
+        .map(|entry| (entry.observer, entry.selector, entry.name, entry.object))
+        .collect();
+
+    for (observer, selector, observed_name, observed_object) in candidates {
+        if observed_name != nil && !names_equal(env, observed_name, name) {
+            continue;
+        }
+        // An earlier observer might have removed this one.
+        let still_observing = env
+            .objc
+            .borrow::<NSNotificationCenterHostObject>(this)
+            .observers
+            .iter()
+            .any(|entry| {
+                entry.observer == observer
+                    && entry.selector == selector
+                    && entry.name == observed_name
+                    && entry.object == observed_object
+            });
+        if !still_observing {
+            continue;
+        }
+        log_dbg!(
+            "Posting notification {:?} to {:?} ({:?})",
+            notification,
+            observer,
+            selector
+        );
+        let () = msg_send(env, (observer, selector, notification));
+    }
+}
+- (())postNotificationName:(id)name // NSString*
+                    object:(id)object {
+    () = msg![env; this postNotificationName:name object:object userInfo:nil];
+}
+- (())postNotificationName:(id)name // NSString*
+                    object:(id)object
+                  userInfo:(id)user_info { // NSDictionary*
+    let notification: id = msg_class![env; NSNotification notificationWithName:name
+                                                                         object:object
+                                                                       userInfo:user_info];
+    () = msg![env; this postNotification:notification];
+}
+
+@end
+
+};
+
+fn names_equal(env: &mut Environment, a: id, b: id) -> bool {
+    a == b || (a != nil && b != nil && msg![env; a isEqualToString:b])
+}
+
+/// Shortcut for host code: post a notification to the default center.
+pub fn post(env: &mut Environment, name: id, object: id, user_info: id) {
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    () = msg![env; center postNotificationName:name object:object userInfo:user_info];
+}
@@ -14,11 +14,12 @@
 //!
 //! See also: [crate::objc], especially the `objects` module.
 
-use super::ns_string::to_rust_string;
+use super::ns_string::{self, to_rust_string};
 use super::NSUInteger;
 use crate::mem::MutVoidPtr;
 use crate::objc::{
-    id, msg, msg_class, msg_send, objc_classes, Class, ClassExports, ObjC, TrivialHostObject,
+    id, msg, msg_class, msg_send, nil, objc_classes, release, Class, ClassExports, ObjC,
+    TrivialHostObject, SEL,
 };
 
 pub const CLASSES: ClassExports = objc_classes! {
@@ -92,6 +93,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     let this_class: Class = msg![env; this class];
     env.objc.class_is_subclass_of(this_class, class)
 }
+- (bool)respondsToSelector:(SEL)selector {
+    let class = ObjC::read_isa(this, &env.mem);
+    env.objc.class_has_method(class, selector)
+}
 
 - (NSUInteger)hash {
     this.to_bits()
@@ -107,6 +112,47 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 
 // NSKeyValueCoding
+- (id)valueForKey:(id)key { // NSString*
+    let key = to_rust_string(env, key); // TODO: avoid copy?
+    assert!(key.is_ascii()); // TODO: do we have to handle non-ASCII keys?
+
+    let class = msg![env; this class];
+
+    let capitalized = format!(
+        "{}{}",
+        key.as_bytes()[0].to_ascii_uppercase() as char,
+        &key[1..],
+    );
+    for name in [
+        key.to_string(),
+        format!("get{}", capitalized),
+        format!("is{}", capitalized),
+        format!("_{}", key),
+    ] {
+        if let Some(sel) = env.objc.lookup_selector(&name) {
+            if env.objc.class_has_method(class, sel) {
+                // TODO: box non-object return values
+                return msg_send(env, (this, sel));
+            }
+        }
+    }
+
+    unimplemented!("TODO: object {:?} does not have simple getter method for {}, use fallback", this, key);
+}
+- (id)valueForKeyPath:(id)key_path { // NSString*
+    let key_path = to_rust_string(env, key_path);
+    let mut object = this;
+    for key in key_path.split('.') {
+        if object == nil {
+            break;
+        }
+        let key = ns_string::from_rust_string(env, key.to_string());
+        object = msg![env; object valueForKey:key];
+        release(env, key);
+    }
+    object
+}
+
 - (())setValue:(id)value
        forKey:(id)key { // NSString*
     let key = to_rust_string(env, key); // TODO: avoid copy?
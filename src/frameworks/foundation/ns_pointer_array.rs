@@ -0,0 +1,160 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSPointerArray`.
+
+use super::ns_array;
+use super::ns_fast_enumeration::{fast_enumeration_helper, NSFastEnumerationState};
+use super::ns_pointer_functions::{
+    NSPointerFunctionsOptions, NSPointerFunctionsStrongMemory, NSPointerFunctionsWeakMemory,
+    PointerFunctions, Slot,
+};
+use super::NSUInteger;
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{autorelease, id, msg, nil, objc_classes, retain, ClassExports, HostObject};
+use crate::Environment;
+
+struct PointerArrayHostObject {
+    functions: PointerFunctions,
+    /// Unlike with other collections, `NULL` is allowed.
+    pointers: Vec<Slot>,
+}
+impl HostObject for PointerArrayHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSPointerArray: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(PointerArrayHostObject {
+        functions: PointerFunctions::new(NSPointerFunctionsStrongMemory),
+        pointers: Vec::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)pointerArrayWithOptions:(NSPointerFunctionsOptions)options {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithOptions:options];
+    autorelease(env, new)
+}
++ (id)strongObjectsPointerArray {
+    msg![env; this pointerArrayWithOptions:NSPointerFunctionsStrongMemory]
+}
++ (id)weakObjectsPointerArray {
+    msg![env; this pointerArrayWithOptions:NSPointerFunctionsWeakMemory]
+}
+
+- (id)initWithOptions:(NSPointerFunctionsOptions)options {
+    env.objc.borrow_mut::<PointerArrayHostObject>(this).functions = PointerFunctions::new(options);
+    this
+}
+
+- (())dealloc {
+    let () = msg![env; this setCount:0u32];
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (NSUInteger)count {
+    env.objc.borrow::<PointerArrayHostObject>(this).pointers.len() as NSUInteger
+}
+- (())setCount:(NSUInteger)count {
+    let count = count as usize;
+    let pointers = &mut env.objc.borrow_mut::<PointerArrayHostObject>(this).pointers;
+    if count >= pointers.len() {
+        pointers.resize(count, Slot::Opaque(nil));
+        return;
+    }
+    let removed = pointers.split_off(count);
+    for slot in removed {
+        slot.release(env);
+    }
+}
+
+- (MutVoidPtr)pointerAtIndex:(NSUInteger)index {
+    // TODO: raise exception instead of panicking?
+    let slot = env.objc.borrow::<PointerArrayHostObject>(this).pointers[index as usize];
+    slot.load(&env.objc).cast()
+}
+
+- (())addPointer:(MutVoidPtr)pointer {
+    let slot = store(env, this, pointer);
+    env.objc.borrow_mut::<PointerArrayHostObject>(this).pointers.push(slot);
+}
+- (())insertPointer:(MutVoidPtr)pointer
+            atIndex:(NSUInteger)index {
+    let slot = store(env, this, pointer);
+    env.objc
+        .borrow_mut::<PointerArrayHostObject>(this)
+        .pointers
+        .insert(index as usize, slot);
+}
+- (())removePointerAtIndex:(NSUInteger)index {
+    let slot = env.objc
+        .borrow_mut::<PointerArrayHostObject>(this)
+        .pointers
+        .remove(index as usize);
+    slot.release(env);
+}
+- (())replacePointerAtIndex:(NSUInteger)index
+                withPointer:(MutVoidPtr)pointer {
+    let slot = store(env, this, pointer);
+    let pointers = &mut env.objc.borrow_mut::<PointerArrayHostObject>(this).pointers;
+    let old_slot = std::mem::replace(&mut pointers[index as usize], slot);
+    old_slot.release(env);
+}
+
+// Removes NULLs, including weakly-held objects that have been deallocated.
+- (())compact {
+    let host_object = env.objc.borrow_mut::<PointerArrayHostObject>(this);
+    let mut pointers = std::mem::take(&mut host_object.pointers);
+    pointers.retain(|slot| slot.load(&env.objc) != nil);
+    env.objc.borrow_mut::<PointerArrayHostObject>(this).pointers = pointers;
+}
+
+- (id)allObjects {
+    let objects = non_null_pointers(env, this);
+    for &object in &objects {
+        retain(env, object);
+    }
+    let array = ns_array::from_vec(env, objects);
+    autorelease(env, array)
+}
+
+// NSFastEnumeration implementation
+- (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
+                                  objects:(MutPtr<id>)stackbuf
+                                    count:(NSUInteger)len {
+    // TODO: avoid copying the pointers on every call
+    let pointers: Vec<id> = env
+        .objc
+        .borrow::<PointerArrayHostObject>(this)
+        .pointers
+        .iter()
+        .map(|slot| slot.load(&env.objc))
+        .collect();
+    fast_enumeration_helper(&mut env.mem, this, &pointers, state, stackbuf, len)
+}
+
+@end
+
+};
+
+fn store(env: &mut Environment, array: id, pointer: MutVoidPtr) -> Slot {
+    let functions = env.objc.borrow::<PointerArrayHostObject>(array).functions;
+    functions.store(env, pointer.cast())
+}
+
+fn non_null_pointers(env: &mut Environment, array: id) -> Vec<id> {
+    env.objc
+        .borrow::<PointerArrayHostObject>(array)
+        .pointers
+        .iter()
+        .map(|slot| slot.load(&env.objc))
+        .filter(|&pointer| pointer != nil)
+        .collect()
+}
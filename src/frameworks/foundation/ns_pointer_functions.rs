@@ -0,0 +1,118 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSPointerFunctions.h` options, shared by `NSMapTable`, `NSHashTable` and
+//! `NSPointerArray`.
+//!
+//! The `NSPointerFunctions` class itself is not implemented.
+
+use super::NSUInteger;
+use crate::objc::{id, msg, nil, release, retain, ObjC, WeakRef};
+use crate::Environment;
+
+pub type NSPointerFunctionsOptions = NSUInteger;
+pub const NSPointerFunctionsStrongMemory: NSPointerFunctionsOptions = 0;
+/// Deprecated, treated like [NSPointerFunctionsWeakMemory].
+pub const NSPointerFunctionsZeroingWeakMemory: NSPointerFunctionsOptions = 1;
+pub const NSPointerFunctionsOpaqueMemory: NSPointerFunctionsOptions = 2;
+#[allow(dead_code)]
+pub const NSPointerFunctionsMallocMemory: NSPointerFunctionsOptions = 3;
+#[allow(dead_code)]
+pub const NSPointerFunctionsMachVirtualMemory: NSPointerFunctionsOptions = 4;
+pub const NSPointerFunctionsWeakMemory: NSPointerFunctionsOptions = 5;
+pub const NSPointerFunctionsObjectPersonality: NSPointerFunctionsOptions = 0 << 8;
+pub const NSPointerFunctionsOpaquePersonality: NSPointerFunctionsOptions = 1 << 8;
+pub const NSPointerFunctionsObjectPointerPersonality: NSPointerFunctionsOptions = 2 << 8;
+#[allow(dead_code)]
+pub const NSPointerFunctionsCStringPersonality: NSPointerFunctionsOptions = 3 << 8;
+#[allow(dead_code)]
+pub const NSPointerFunctionsStructPersonality: NSPointerFunctionsOptions = 4 << 8;
+#[allow(dead_code)]
+pub const NSPointerFunctionsIntegerPersonality: NSPointerFunctionsOptions = 5 << 8;
+pub const NSPointerFunctionsCopyIn: NSPointerFunctionsOptions = 1 << 16;
+
+const MEMORY_MASK: NSPointerFunctionsOptions = 0xFF;
+const PERSONALITY_MASK: NSPointerFunctionsOptions = 0xFF << 8;
+
+/// How a collection holds its items, decoded from [NSPointerFunctionsOptions].
+#[derive(Copy, Clone)]
+pub(super) struct PointerFunctions {
+    memory: Memory,
+    /// Whether to use `isEqual:` (really `isEqualTo:`) for comparisons rather
+    /// than pointer equality.
+    object_equality: bool,
+    copy_in: bool,
+}
+
+#[derive(Copy, Clone)]
+enum Memory {
+    Strong,
+    Weak,
+    Opaque,
+}
+
+/// An item held by a collection, see [PointerFunctions].
+#[derive(Copy, Clone)]
+pub(super) enum Slot {
+    /// Retained.
+    Strong(id),
+    Weak(WeakRef),
+    /// Not retained. Might not be an object.
+    Opaque(id),
+}
+
+impl PointerFunctions {
+    pub(super) fn new(options: NSPointerFunctionsOptions) -> PointerFunctions {
+        let memory = match options & MEMORY_MASK {
+            NSPointerFunctionsStrongMemory => Memory::Strong,
+            NSPointerFunctionsZeroingWeakMemory | NSPointerFunctionsWeakMemory => Memory::Weak,
+            NSPointerFunctionsOpaqueMemory => Memory::Opaque,
+            _ => unimplemented!("Pointer functions memory option {:#x}", options),
+        };
+        let object_equality = match options & PERSONALITY_MASK {
+            NSPointerFunctionsObjectPersonality => true,
+            NSPointerFunctionsObjectPointerPersonality | NSPointerFunctionsOpaquePersonality => {
+                false
+            }
+            _ => unimplemented!("Pointer functions personality option {:#x}", options),
+        };
+        PointerFunctions {
+            memory,
+            object_equality,
+            copy_in: options & NSPointerFunctionsCopyIn != 0,
+        }
+    }
+
+    /// Make a slot holding an item, retaining or copying it if appropriate.
+    pub(super) fn store(&self, env: &mut Environment, item: id) -> Slot {
+        match self.memory {
+            Memory::Strong if item == nil => Slot::Strong(nil),
+            Memory::Strong if self.copy_in => Slot::Strong(msg![env; item copy]),
+            Memory::Strong => Slot::Strong(retain(env, item)),
+            Memory::Weak => Slot::Weak(env.objc.weak_ref(item)),
+            Memory::Opaque => Slot::Opaque(item),
+        }
+    }
+
+    pub(super) fn is_equal(&self, env: &mut Environment, a: id, b: id) -> bool {
+        a == b || (self.object_equality && a != nil && b != nil && msg![env; a isEqualTo:b])
+    }
+}
+
+impl Slot {
+    /// Get the item, or `nil` if it was weakly held and has been deallocated.
+    pub(super) fn load(&self, objc: &ObjC) -> id {
+        match *self {
+            Slot::Strong(item) | Slot::Opaque(item) => item,
+            Slot::Weak(weak) => objc.load_weak(weak),
+        }
+    }
+
+    pub(super) fn release(self, env: &mut Environment) {
+        if let Slot::Strong(item) = self {
+            release(env, item);
+        }
+    }
+}
@@ -0,0 +1,1181 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSPredicate` and `NSCompoundPredicate`.
+//!
+//! Predicates are parsed from their format string into a tree the host
+//! evaluates. Only the commonly-used part of the syntax is supported:
+//! comparisons (including `BEGINSWITH`, `ENDSWITH`, `CONTAINS`, `LIKE`, `IN`
+//! and `BETWEEN`, with the `[c]` and `[d]` options), the `ANY`, `ALL` and
+//! `NONE` modifiers, `AND`, `OR` and `NOT`, key paths (with the `@count`,
+//! `@sum`, `@avg`, `@min` and `@max` operators), literals and the `%@`, `%K`,
+//! `%d`, `%f` and `%s` format specifiers. There are no variables, arithmetic,
+//! functions or regular expressions.
+//!
+//! Resources:
+//! - Apple's [Predicate Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/Predicates/AdditionalChapters/Introduction.html)
+
+use super::ns_string::{self, transliteration};
+use super::{ns_date, NSUInteger};
+use crate::mem::{ConstPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, Class, ClassExports,
+    HostObject,
+};
+use crate::Environment;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A constant in a predicate. Objects are retained by the predicate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    /// `NSDate`, as a time interval since the reference date.
+    Date(f64),
+    Object(id),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    SelfObject,
+    KeyPath(String),
+    Constant(Constant),
+    /// E.g. `{1, 2, 3}`.
+    Aggregate(Vec<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    BeginsWith,
+    EndsWith,
+    Contains,
+    Like,
+    In,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    Direct,
+    Any,
+    All,
+    NoneOf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Options {
+    pub case_insensitive: bool,
+    pub diacritic_insensitive: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Value(bool),
+    Comparison {
+        modifier: Modifier,
+        left: Expression,
+        operator: Operator,
+        options: Options,
+        right: Expression,
+    },
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+    Not(Box<Predicate>),
+}
+
+/// The argument a format specifier consumes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentKind {
+    /// `%@`
+    Object,
+    /// `%K`, an `NSString*` used as a key path.
+    KeyPath,
+    /// `%d`, `%i`, `%u` etc.
+    Int,
+    /// `%lld`, `%qd` etc.
+    LongLong,
+    /// `%f`, `%e`, `%g`
+    Double,
+    /// `%s`
+    CString,
+}
+
+impl Predicate {
+    fn for_each_object(&self, f: &mut impl FnMut(id)) {
+        fn expression_objects(expression: &Expression, f: &mut impl FnMut(id)) {
+            match expression {
+                Expression::Constant(Constant::Object(object)) => f(*object),
+                Expression::Aggregate(expressions) => {
+                    for expression in expressions {
+                        expression_objects(expression, f);
+                    }
+                }
+                _ => (),
+            }
+        }
+        match self {
+            Predicate::Value(_) => (),
+            Predicate::Comparison { left, right, .. } => {
+                expression_objects(left, f);
+                expression_objects(right, f);
+            }
+            Predicate::And(predicates) | Predicate::Or(predicates) => {
+                for predicate in predicates {
+                    predicate.for_each_object(f);
+                }
+            }
+            Predicate::Not(predicate) => predicate.for_each_object(f),
+        }
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Nil => write!(f, "nil"),
+            Constant::Bool(value) => write!(f, "{}", *value as i32),
+            Constant::Integer(value) => write!(f, "{}", value),
+            Constant::Float(value) => write!(f, "{}", value),
+            Constant::String(value) => write!(f, "{:?}", value),
+            Constant::Date(value) => write!(f, "CAST({}, \"NSDate\")", value),
+            Constant::Object(object) => write!(f, "<object {:?}>", object),
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::SelfObject => write!(f, "SELF"),
+            Expression::KeyPath(key_path) => write!(f, "{}", key_path),
+            Expression::Constant(constant) => write!(f, "{}", constant),
+            Expression::Aggregate(expressions) => {
+                write!(f, "{{")?;
+                for (i, expression) in expressions.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", expression)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Predicate::Value(true) => write!(f, "TRUEPREDICATE"),
+            Predicate::Value(false) => write!(f, "FALSEPREDICATE"),
+            Predicate::Comparison {
+                modifier,
+                left,
+                operator,
+                options,
+                right,
+            } => {
+                match modifier {
+                    Modifier::Direct => (),
+                    Modifier::Any => write!(f, "ANY ")?,
+                    Modifier::All => write!(f, "ALL ")?,
+                    Modifier::NoneOf => write!(f, "NONE ")?,
+                }
+                let operator = match operator {
+                    Operator::Equal => "==",
+                    Operator::NotEqual => "!=",
+                    Operator::Less => "<",
+                    Operator::LessOrEqual => "<=",
+                    Operator::Greater => ">",
+                    Operator::GreaterOrEqual => ">=",
+                    Operator::BeginsWith => "BEGINSWITH",
+                    Operator::EndsWith => "ENDSWITH",
+                    Operator::Contains => "CONTAINS",
+                    Operator::Like => "LIKE",
+                    Operator::In => "IN",
+                };
+                write!(f, "{} {}", left, operator)?;
+                if options.case_insensitive || options.diacritic_insensitive {
+                    write!(f, "[")?;
+                    if options.case_insensitive {
+                        write!(f, "c")?;
+                    }
+                    if options.diacritic_insensitive {
+                        write!(f, "d")?;
+                    }
+                    write!(f, "]")?;
+                }
+                write!(f, " {}", right)
+            }
+            Predicate::And(predicates) | Predicate::Or(predicates) => {
+                let joiner = if matches!(self, Predicate::And(_)) {
+                    " AND "
+                } else {
+                    " OR "
+                };
+                for (i, predicate) in predicates.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, "{}", joiner)?;
+                    }
+                    if matches!(predicate, Predicate::And(_) | Predicate::Or(_)) {
+                        write!(f, "({})", predicate)?;
+                    } else {
+                        write!(f, "{}", predicate)?;
+                    }
+                }
+                Ok(())
+            }
+            Predicate::Not(predicate) => write!(f, "NOT ({})", predicate),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Argument(ArgumentKind),
+    Options(Options),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: &[&str] = &[
+    "==", "!=", "<>", "<=", "=<", ">=", "=>", "&&", "||", "=", "<", ">", "!", "(", ")", "{", "}",
+    ",",
+];
+
+fn tokenize(format: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = format.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            // Format specifiers aren't substituted inside quotes.
+            let mut string = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err("unterminated string".to_string()),
+                    Some(&d) if d == c => break,
+                    Some('\\') => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some('n') => string.push('\n'),
+                            Some('t') => string.push('\t'),
+                            Some(&other) => string.push(other),
+                            None => return Err("unterminated string".to_string()),
+                        }
+                    }
+                    Some(&d) => string.push(d),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::String(string));
+        } else if c.is_ascii_digit()
+            || (c == '-' && chars.get(i + 1).map_or(false, |d| d.is_ascii_digit()))
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let token = if text.contains('.') {
+                text.parse().map(Token::Float).ok()
+            } else {
+                text.parse().map(Token::Integer).ok()
+            };
+            tokens.push(token.ok_or_else(|| format!("bad number {:?}", text))?);
+        } else if c.is_alphabetic() || c == '_' || c == '@' {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric()
+                    || chars[i] == '_'
+                    || chars[i] == '.'
+                    || chars[i] == '@')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect()));
+        } else if c == '%' {
+            i += 1;
+            let mut long_long = false;
+            let mut longs = 0;
+            while let Some(&m) = chars.get(i) {
+                match m {
+                    'l' => longs += 1,
+                    'q' => long_long = true,
+                    'h' | 'z' | 't' | 'j' => (),
+                    _ => break,
+                }
+                i += 1;
+            }
+            long_long |= longs >= 2;
+            let kind = match chars.get(i) {
+                Some('@') => ArgumentKind::Object,
+                Some('K') => ArgumentKind::KeyPath,
+                Some('d' | 'i' | 'u' | 'x' | 'X' | 'o' | 'c') if long_long => {
+                    ArgumentKind::LongLong
+                }
+                Some('d' | 'i' | 'u' | 'x' | 'X' | 'o' | 'c') => ArgumentKind::Int,
+                Some('f' | 'e' | 'E' | 'g' | 'G') => ArgumentKind::Double,
+                Some('s') => ArgumentKind::CString,
+                other => return Err(format!("unsupported format specifier %{:?}", other)),
+            };
+            i += 1;
+            tokens.push(Token::Argument(kind));
+        } else if c == '[' {
+            let Some(end) = chars[i..].iter().position(|&d| d == ']') else {
+                return Err("unterminated options".to_string());
+            };
+            let mut options = Options::default();
+            for &option in &chars[i + 1..i + end] {
+                match option {
+                    'c' => options.case_insensitive = true,
+                    'd' => options.diacritic_insensitive = true,
+                    // Normalized and locale-sensitive comparison are close
+                    // enough to the default.
+                    'n' | 'l' => (),
+                    other => return Err(format!("unknown comparison option {:?}", other)),
+                }
+            }
+            i += end + 1;
+            tokens.push(Token::Options(options));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let Some(&symbol) = SYMBOLS.iter().find(|&&symbol| rest.starts_with(symbol)) else {
+                return Err(format!("unexpected character {:?}", c));
+            };
+            i += symbol.len();
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a, F: FnMut(ArgumentKind) -> Constant> {
+    tokens: Vec<Token>,
+    pos: usize,
+    argument: &'a mut F,
+}
+
+impl<'a, F: FnMut(ArgumentKind) -> Constant> Parser<'a, F> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+    fn accept_keyword(&mut self, keywords: &[&str]) -> Option<usize> {
+        let Some(Token::Word(word)) = self.peek() else {
+            return None;
+        };
+        let idx = keywords
+            .iter()
+            .position(|keyword| word.eq_ignore_ascii_case(keyword))?;
+        self.pos += 1;
+        Some(idx)
+    }
+    fn accept_symbol(&mut self, symbols: &[&str]) -> Option<usize> {
+        let Some(&Token::Symbol(symbol)) = self.peek() else {
+            return None;
+        };
+        let idx = symbols.iter().position(|&candidate| candidate == symbol)?;
+        self.pos += 1;
+        Some(idx)
+    }
+
+    fn parse_or(&mut self) -> Result<Predicate, String> {
+        let mut predicates = vec![self.parse_and()?];
+        while self.accept_keyword(&["OR"]).is_some() || self.accept_symbol(&["||"]).is_some() {
+            predicates.push(self.parse_and()?);
+        }
+        Ok(if predicates.len() == 1 {
+            predicates.pop().unwrap()
+        } else {
+            Predicate::Or(predicates)
+        })
+    }
+    fn parse_and(&mut self) -> Result<Predicate, String> {
+        let mut predicates = vec![self.parse_not()?];
+        while self.accept_keyword(&["AND"]).is_some() || self.accept_symbol(&["&&"]).is_some() {
+            predicates.push(self.parse_not()?);
+        }
+        Ok(if predicates.len() == 1 {
+            predicates.pop().unwrap()
+        } else {
+            Predicate::And(predicates)
+        })
+    }
+    fn parse_not(&mut self) -> Result<Predicate, String> {
+        if self.accept_keyword(&["NOT"]).is_some() || self.accept_symbol(&["!"]).is_some() {
+            Ok(Predicate::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+    fn parse_primary(&mut self) -> Result<Predicate, String> {
+        if self.accept_symbol(&["("]).is_some() {
+            let predicate = self.parse_or()?;
+            if self.accept_symbol(&[")"]).is_none() {
+                return Err("missing )".to_string());
+            }
+            return Ok(predicate);
+        }
+        if let Some(idx) = self.accept_keyword(&["TRUEPREDICATE", "FALSEPREDICATE"]) {
+            return Ok(Predicate::Value(idx == 0));
+        }
+
+        let modifier = match self.accept_keyword(&["ANY", "SOME", "ALL", "NONE"]) {
+            None => Modifier::Direct,
+            Some(0 | 1) => Modifier::Any,
+            Some(2) => Modifier::All,
+            Some(_) => Modifier::NoneOf,
+        };
+        let left = self.parse_expression()?;
+
+        let operator = if let Some(idx) =
+            self.accept_symbol(&["==", "=", "!=", "<>", "<", "<=", "=<", ">", ">=", "=>"])
+        {
+            [
+                Operator::Equal,
+                Operator::Equal,
+                Operator::NotEqual,
+                Operator::NotEqual,
+                Operator::Less,
+                Operator::LessOrEqual,
+                Operator::LessOrEqual,
+                Operator::Greater,
+                Operator::GreaterOrEqual,
+                Operator::GreaterOrEqual,
+            ][idx]
+        } else if let Some(idx) = self.accept_keyword(&[
+            "BEGINSWITH",
+            "ENDSWITH",
+            "CONTAINS",
+            "LIKE",
+            "IN",
+            "BETWEEN",
+        ]) {
+            if idx == 5 {
+                return self.parse_between(modifier, left);
+            }
+            [
+                Operator::BeginsWith,
+                Operator::EndsWith,
+                Operator::Contains,
+                Operator::Like,
+                Operator::In,
+            ][idx]
+        } else {
+            return Err(format!("expected an operator, got {:?}", self.peek()));
+        };
+
+        let options = match self.peek() {
+            Some(&Token::Options(options)) => {
+                self.pos += 1;
+                options
+            }
+            _ => Options::default(),
+        };
+
+        let right = self.parse_expression()?;
+        Ok(Predicate::Comparison {
+            modifier,
+            left,
+            operator,
+            options,
+            right,
+        })
+    }
+    fn parse_between(&mut self, modifier: Modifier, left: Expression) -> Result<Predicate, String> {
+        let Expression::Aggregate(mut bounds) = self.parse_expression()? else {
+            return Err("BETWEEN needs a {lower, upper} aggregate".to_string());
+        };
+        if bounds.len() != 2 {
+            return Err("BETWEEN needs a {lower, upper} aggregate".to_string());
+        }
+        let upper = bounds.pop().unwrap();
+        let lower = bounds.pop().unwrap();
+        let comparison = |operator, right| Predicate::Comparison {
+            modifier,
+            left: left.clone(),
+            operator,
+            options: Options::default(),
+            right,
+        };
+        Ok(Predicate::And(vec![
+            comparison(Operator::GreaterOrEqual, lower),
+            comparison(Operator::LessOrEqual, upper),
+        ]))
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Word(word)) => {
+                let keyword = word.to_ascii_uppercase();
+                Ok(match &*keyword {
+                    "NIL" | "NULL" => Expression::Constant(Constant::Nil),
+                    "YES" | "TRUE" => Expression::Constant(Constant::Bool(true)),
+                    "NO" | "FALSE" => Expression::Constant(Constant::Bool(false)),
+                    "SELF" => Expression::SelfObject,
+                    _ => Expression::KeyPath(word),
+                })
+            }
+            Some(Token::String(string)) => Ok(Expression::Constant(Constant::String(string))),
+            Some(Token::Integer(value)) => Ok(Expression::Constant(Constant::Integer(value))),
+            Some(Token::Float(value)) => Ok(Expression::Constant(Constant::Float(value))),
+            Some(Token::Argument(ArgumentKind::KeyPath)) => {
+                match (self.argument)(ArgumentKind::KeyPath) {
+                    Constant::String(key_path) => Ok(Expression::KeyPath(key_path)),
+                    other => Err(format!("%K argument {:?} isn't a string", other)),
+                }
+            }
+            Some(Token::Argument(kind)) => Ok(Expression::Constant((self.argument)(kind))),
+            Some(Token::Symbol("{")) => {
+                let mut expressions = Vec::new();
+                if self.accept_symbol(&["}"]).is_some() {
+                    return Ok(Expression::Aggregate(expressions));
+                }
+                loop {
+                    expressions.push(self.parse_expression()?);
+                    match self.accept_symbol(&[",", "}"]) {
+                        Some(0) => continue,
+                        Some(_) => break,
+                        None => return Err("missing }".to_string()),
+                    }
+                }
+                Ok(Expression::Aggregate(expressions))
+            }
+            other => Err(format!("expected an expression, got {:?}", other)),
+        }
+    }
+}
+
+/// Parse a predicate format string. `argument` is called for each format
+/// specifier, in order.
+pub fn parse<F>(format: &str, mut argument: F) -> Result<Predicate, String>
+where
+    F: FnMut(ArgumentKind) -> Constant,
+{
+    let mut parser = Parser {
+        tokens: tokenize(format)?,
+        pos: 0,
+        argument: &mut argument,
+    };
+    let predicate = parser.parse_or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(format!("unexpected {:?}", parser.tokens[parser.pos]));
+    }
+    Ok(predicate)
+}
+
+/// A value a predicate's expressions evaluate to.
+#[derive(Debug, Clone)]
+enum Value {
+    Nil,
+    Number(f64),
+    String(String),
+    Date(f64),
+    Collection(Vec<Value>),
+    Object(id),
+}
+
+fn is_kind_of(env: &mut Environment, object: id, class_name: &str) -> bool {
+    let class: Class = env.objc.get_known_class(class_name, &mut env.mem);
+    msg![env; object isKindOfClass:class]
+}
+
+/// Get the members of an `NSArray` or `NSSet`, or [None] for anything else.
+fn collection_members(env: &mut Environment, object: id) -> Option<Vec<id>> {
+    let array = if is_kind_of(env, object, "NSArray") {
+        object
+    } else if is_kind_of(env, object, "NSSet") {
+        msg![env; object allObjects]
+    } else {
+        return None;
+    };
+    let count: NSUInteger = msg![env; array count];
+    Some(
+        (0..count)
+            .map(|i| msg![env; array objectAtIndex:i])
+            .collect(),
+    )
+}
+
+fn object_value(env: &mut Environment, object: id) -> Value {
+    if object == nil || is_kind_of(env, object, "NSNull") {
+        Value::Nil
+    } else if is_kind_of(env, object, "NSString") {
+        Value::String(ns_string::to_rust_string(env, object).into_owned())
+    } else if is_kind_of(env, object, "NSNumber") {
+        Value::Number(msg![env; object doubleValue])
+    } else if is_kind_of(env, object, "NSDate") {
+        Value::Date(ns_date::get_time_interval(env, object))
+    } else if let Some(members) = collection_members(env, object) {
+        Value::Collection(
+            members
+                .into_iter()
+                .map(|member| object_value(env, member))
+                .collect(),
+        )
+    } else {
+        Value::Object(object)
+    }
+}
+
+fn key_path_value(env: &mut Environment, object: id, keys: &[&str]) -> Value {
+    let Some((&key, rest)) = keys.split_first() else {
+        return object_value(env, object);
+    };
+    if object == nil {
+        return Value::Nil;
+    }
+    if let Some(members) = collection_members(env, object) {
+        if key.starts_with('@') {
+            let values: Vec<f64> = if key == "@count" {
+                vec![]
+            } else {
+                members
+                    .iter()
+                    .filter_map(|&member| match key_path_value(env, member, rest) {
+                        Value::Number(number) => Some(number),
+                        _ => None,
+                    })
+                    .collect()
+            };
+            return match key {
+                "@count" => Value::Number(members.len() as f64),
+                "@sum" => Value::Number(values.iter().sum()),
+                "@avg" if values.is_empty() => Value::Nil,
+                "@avg" => Value::Number(values.iter().sum::<f64>() / values.len() as f64),
+                "@min" => values
+                    .into_iter()
+                    .reduce(f64::min)
+                    .map_or(Value::Nil, Value::Number),
+                "@max" => values
+                    .into_iter()
+                    .reduce(f64::max)
+                    .map_or(Value::Nil, Value::Number),
+                _ => panic!("Unsupported key path collection operator {}", key),
+            };
+        }
+        return Value::Collection(
+            members
+                .into_iter()
+                .map(|member| key_path_value(env, member, keys))
+                .collect(),
+        );
+    }
+    let key = ns_string::from_rust_string(env, key.to_string());
+    let next: id = msg![env; object valueForKey:key];
+    release(env, key);
+    key_path_value(env, next, rest)
+}
+
+fn expression_value(env: &mut Environment, expression: &Expression, object: id) -> Value {
+    match expression {
+        Expression::SelfObject => object_value(env, object),
+        Expression::KeyPath(key_path) => {
+            let keys: Vec<&str> = key_path.split('.').collect();
+            key_path_value(env, object, &keys)
+        }
+        Expression::Constant(constant) => match *constant {
+            Constant::Nil => Value::Nil,
+            Constant::Bool(value) => Value::Number(value as i32 as f64),
+            Constant::Integer(value) => Value::Number(value as f64),
+            Constant::Float(value) => Value::Number(value),
+            Constant::String(ref value) => Value::String(value.clone()),
+            Constant::Date(value) => Value::Date(value),
+            Constant::Object(object) => object_value(env, object),
+        },
+        Expression::Aggregate(expressions) => Value::Collection(
+            expressions
+                .iter()
+                .map(|expression| expression_value(env, expression, object))
+                .collect(),
+        ),
+    }
+}
+
+fn fold_string(string: &str, options: Options) -> String {
+    let string = if options.diacritic_insensitive {
+        transliteration::strip_combining_marks(string)
+    } else {
+        string.to_string()
+    };
+    if options.case_insensitive {
+        string.to_lowercase()
+    } else {
+        string
+    }
+}
+
+fn values_equal(a: &Value, b: &Value, options: Options) -> bool {
+    match (a, b) {
+        (Value::Nil, Value::Nil) => true,
+        (Value::Number(a), Value::Number(b)) | (Value::Date(a), Value::Date(b)) => a == b,
+        (Value::String(a), Value::String(b)) => fold_string(a, options) == fold_string(b, options),
+        (Value::Collection(a), Value::Collection(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_equal(a, b, options))
+        }
+        (Value::Object(a), Value::Object(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn values_order(a: &Value, b: &Value, options: Options) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) | (Value::Date(a), Value::Date(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => {
+            Some(fold_string(a, options).cmp(&fold_string(b, options)))
+        }
+        _ => None,
+    }
+}
+
+/// `LIKE` matching, where `*` matches any run of characters and `?` matches
+/// any one character.
+fn like(string: &[char], pattern: &[char]) -> bool {
+    match pattern.split_first() {
+        None => string.is_empty(),
+        Some((&'*', rest)) => (0..=string.len()).any(|i| like(&string[i..], rest)),
+        Some((&'?', rest)) => !string.is_empty() && like(&string[1..], rest),
+        Some((c, rest)) => string.first() == Some(c) && like(&string[1..], rest),
+    }
+}
+
+fn compare(operator: Operator, options: Options, left: &Value, right: &Value) -> bool {
+    let strings = match (left, right) {
+        (Value::String(a), Value::String(b)) => {
+            Some((fold_string(a, options), fold_string(b, options)))
+        }
+        _ => None,
+    };
+    match operator {
+        Operator::Equal => values_equal(left, right, options),
+        Operator::NotEqual => !values_equal(left, right, options),
+        Operator::Less => values_order(left, right, options) == Some(Ordering::Less),
+        Operator::LessOrEqual => matches!(
+            values_order(left, right, options),
+            Some(Ordering::Less | Ordering::Equal)
+        ),
+        Operator::Greater => values_order(left, right, options) == Some(Ordering::Greater),
+        Operator::GreaterOrEqual => matches!(
+            values_order(left, right, options),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+        Operator::BeginsWith => strings.map_or(false, |(a, b)| a.starts_with(&b)),
+        Operator::EndsWith => strings.map_or(false, |(a, b)| a.ends_with(&b)),
+        Operator::Contains => match (left, strings) {
+            (_, Some((a, b))) => a.contains(&b),
+            (Value::Collection(members), None) => members
+                .iter()
+                .any(|member| values_equal(member, right, options)),
+            _ => false,
+        },
+        Operator::Like => strings.map_or(false, |(a, b)| {
+            let a: Vec<char> = a.chars().collect();
+            let b: Vec<char> = b.chars().collect();
+            like(&a, &b)
+        }),
+        Operator::In => match (right, strings) {
+            (_, Some((a, b))) => b.contains(&a),
+            (Value::Collection(members), None) => members
+                .iter()
+                .any(|member| values_equal(left, member, options)),
+            _ => false,
+        },
+    }
+}
+
+fn evaluate_predicate(env: &mut Environment, predicate: &Predicate, object: id) -> bool {
+    match predicate {
+        Predicate::Value(value) => *value,
+        Predicate::And(predicates) => predicates
+            .iter()
+            .all(|predicate| evaluate_predicate(env, predicate, object)),
+        Predicate::Or(predicates) => predicates
+            .iter()
+            .any(|predicate| evaluate_predicate(env, predicate, object)),
+        Predicate::Not(predicate) => !evaluate_predicate(env, predicate, object),
+        Predicate::Comparison {
+            modifier,
+            left,
+            operator,
+            options,
+            right,
+        } => {
+            let left = expression_value(env, left, object);
+            let right = expression_value(env, right, object);
+            let members = match left {
+                Value::Collection(members) if *modifier != Modifier::Direct => members,
+                left => return compare(*operator, *options, &left, &right),
+            };
+            let mut results = members
+                .iter()
+                .map(|member| compare(*operator, *options, member, &right));
+            match modifier {
+                Modifier::Direct => unreachable!(),
+                Modifier::Any => results.any(|result| result),
+                Modifier::All => results.all(|result| result),
+                Modifier::NoneOf => !results.any(|result| result),
+            }
+        }
+    }
+}
+
+struct NSPredicateHostObject {
+    predicate: Predicate,
+}
+impl HostObject for NSPredicateHostObject {}
+
+/// Create a new predicate object (not autoreleased). The objects in the
+/// predicate should already be retained on its behalf.
+fn new_predicate(env: &mut Environment, class: Class, predicate: Predicate) -> id {
+    let host_object = Box::new(NSPredicateHostObject { predicate });
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+/// Convert a `%@` argument to a constant, retaining it if it stays an object.
+fn object_constant(env: &mut Environment, object: id) -> Constant {
+    if object == nil {
+        Constant::Nil
+    } else if is_kind_of(env, object, "NSString") {
+        Constant::String(ns_string::to_rust_string(env, object).into_owned())
+    } else if is_kind_of(env, object, "NSNumber") {
+        let objc_type: ConstPtr<u8> = msg![env; object objCType];
+        match env.mem.read(objc_type) {
+            b'f' | b'd' => Constant::Float(msg![env; object doubleValue]),
+            _ => Constant::Integer(msg![env; object longLongValue]),
+        }
+    } else if is_kind_of(env, object, "NSDate") {
+        Constant::Date(ns_date::get_time_interval(env, object))
+    } else {
+        Constant::Object(retain(env, object))
+    }
+}
+
+fn predicate_with_format<F>(env: &mut Environment, format: id, mut next_object: F) -> Predicate
+where
+    F: FnMut(&mut Environment, ArgumentKind) -> Constant,
+{
+    let format = ns_string::to_rust_string(env, format).into_owned();
+    // The parser can't hold on to the environment, so the arguments are
+    // collected first and converted afterwards.
+    let mut kinds = Vec::new();
+    if let Err(error) = parse(&format, |kind| {
+        kinds.push(kind);
+        match kind {
+            ArgumentKind::KeyPath => Constant::String(String::new()),
+            _ => Constant::Nil,
+        }
+    }) {
+        panic!("Couldn't parse predicate format {:?}: {}", format, error);
+    }
+    let mut arguments: Vec<Constant> = kinds
+        .into_iter()
+        .map(|kind| next_object(env, kind))
+        .collect();
+    arguments.reverse();
+    parse(&format, |_kind| arguments.pop().unwrap()).unwrap()
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSPredicate: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    new_predicate(env, this, Predicate::Value(true))
+}
+
++ (id)predicateWithFormat:(id)format, ...args { // NSString*
+    let mut args = args;
+    let predicate = predicate_with_format(env, format, |env, kind| match kind {
+        ArgumentKind::Object => {
+            let object: id = args.next(env);
+            object_constant(env, object)
+        }
+        ArgumentKind::KeyPath => {
+            let key_path: id = args.next(env);
+            Constant::String(ns_string::to_rust_string(env, key_path).into_owned())
+        }
+        ArgumentKind::Int => Constant::Integer(args.next::<i32>(env).into()),
+        ArgumentKind::LongLong => Constant::Integer(args.next(env)),
+        ArgumentKind::Double => Constant::Float(args.next(env)),
+        ArgumentKind::CString => {
+            let string: ConstPtr<u8> = args.next(env);
+            Constant::String(env.mem.cstr_at_utf8(string).to_string())
+        }
+    });
+    let new = new_predicate(env, this, predicate);
+    autorelease(env, new)
+}
++ (id)predicateWithFormat:(id)format // NSString*
+            argumentArray:(id)arguments { // NSArray*
+    let mut i: NSUInteger = 0;
+    let predicate = predicate_with_format(env, format, |env, kind| {
+        let object: id = msg![env; arguments objectAtIndex:i];
+        i += 1;
+        match kind {
+            ArgumentKind::Object => object_constant(env, object),
+            ArgumentKind::KeyPath => {
+                Constant::String(ns_string::to_rust_string(env, object).into_owned())
+            }
+            _ => panic!("Format specifier {:?} needs a C argument", kind),
+        }
+    });
+    let new = new_predicate(env, this, predicate);
+    autorelease(env, new)
+}
++ (id)predicateWithValue:(bool)value {
+    let new = new_predicate(env, this, Predicate::Value(value));
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let host_object: &mut NSPredicateHostObject = env.objc.borrow_mut(this);
+    let predicate = std::mem::replace(&mut host_object.predicate, Predicate::Value(true));
+    predicate.for_each_object(&mut |object| release(env, object));
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+- (bool)evaluateWithObject:(id)object {
+    evaluate(env, this, object)
+}
+
+- (id)predicateFormat {
+    let format = env.objc.borrow::<NSPredicateHostObject>(this).predicate.to_string();
+    let format = ns_string::from_rust_string(env, format);
+    autorelease(env, format)
+}
+- (id)description {
+    msg![env; this predicateFormat]
+}
+
+@end
+
+@implementation NSCompoundPredicate: NSPredicate
+
++ (id)andPredicateWithSubpredicates:(id)subpredicates { // NSArray*
+    let predicates = subpredicates_of(env, subpredicates);
+    let new = new_predicate(env, this, Predicate::And(predicates));
+    autorelease(env, new)
+}
++ (id)orPredicateWithSubpredicates:(id)subpredicates { // NSArray*
+    let predicates = subpredicates_of(env, subpredicates);
+    let new = new_predicate(env, this, Predicate::Or(predicates));
+    autorelease(env, new)
+}
++ (id)notPredicateWithSubpredicate:(id)subpredicate { // NSPredicate*
+    let predicate = clone_predicate(env, subpredicate);
+    let new = new_predicate(env, this, Predicate::Not(Box::new(predicate)));
+    autorelease(env, new)
+}
+
+@end
+
+};
+
+/// Copy the tree of a predicate object, retaining the objects in it.
+fn clone_predicate(env: &mut Environment, predicate: id) -> Predicate {
+    let predicate = env
+        .objc
+        .borrow::<NSPredicateHostObject>(predicate)
+        .predicate
+        .clone();
+    predicate.for_each_object(&mut |object| {
+        retain(env, object);
+    });
+    predicate
+}
+
+fn subpredicates_of(env: &mut Environment, subpredicates: id) -> Vec<Predicate> {
+    let count: NSUInteger = msg![env; subpredicates count];
+    (0..count)
+        .map(|i| {
+            let subpredicate: id = msg![env; subpredicates objectAtIndex:i];
+            clone_predicate(env, subpredicate)
+        })
+        .collect()
+}
+
+/// Shortcut for host code, equivalent to `[predicate evaluateWithObject:]`.
+pub fn evaluate(env: &mut Environment, predicate: id, object: id) -> bool {
+    // The tree is copied so that evaluation can send messages. The objects in
+    // it are kept alive by the predicate object.
+    let tree = env
+        .objc
+        .borrow::<NSPredicateHostObject>(predicate)
+        .predicate
+        .clone();
+    evaluate_predicate(env, &tree, object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_with(format: &str, arguments: Vec<Constant>) -> Predicate {
+        let mut arguments = arguments.into_iter();
+        parse(format, |_kind| arguments.next().unwrap()).unwrap()
+    }
+
+    fn key_path(key_path: &str) -> Expression {
+        Expression::KeyPath(key_path.to_string())
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(
+            parse_with("name == 'Bob'", vec![]),
+            Predicate::Comparison {
+                modifier: Modifier::Direct,
+                left: key_path("name"),
+                operator: Operator::Equal,
+                options: Options::default(),
+                right: Expression::Constant(Constant::String("Bob".to_string())),
+            }
+        );
+        assert_eq!(
+            parse_with(
+                "ANY tags.name BEGINSWITH[cd] %@",
+                vec![Constant::Integer(3)]
+            ),
+            Predicate::Comparison {
+                modifier: Modifier::Any,
+                left: key_path("tags.name"),
+                operator: Operator::BeginsWith,
+                options: Options {
+                    case_insensitive: true,
+                    diacritic_insensitive: true,
+                },
+                right: Expression::Constant(Constant::Integer(3)),
+            }
+        );
+        assert_eq!(
+            parse_with("%K != nil", vec![Constant::String("date".to_string())]),
+            Predicate::Comparison {
+                modifier: Modifier::Direct,
+                left: key_path("date"),
+                operator: Operator::NotEqual,
+                options: Options::default(),
+                right: Expression::Constant(Constant::Nil),
+            }
+        );
+    }
+
+    #[test]
+    fn compound() {
+        let predicate = parse_with(
+            "(a > 1 AND b <= -2.5) || NOT c IN {1, 'x'} && TRUEPREDICATE",
+            vec![],
+        );
+        let Predicate::Or(predicates) = &predicate else {
+            panic!("{:?}", predicate);
+        };
+        assert!(matches!(&predicates[0], Predicate::And(inner) if inner.len() == 2));
+        assert!(matches!(&predicates[1], Predicate::And(inner) if inner.len() == 2));
+        assert_eq!(
+            predicate.to_string(),
+            "(a > 1 AND b <= -2.5) OR (NOT (c IN {1, \"x\"}) AND TRUEPREDICATE)"
+        );
+    }
+
+    #[test]
+    fn between() {
+        assert_eq!(
+            parse_with("age BETWEEN {%d, 65}", vec![Constant::Integer(18)]).to_string(),
+            "age >= 18 AND age <= 65"
+        );
+    }
+
+    #[test]
+    fn quoted_format_specifiers() {
+        // Format specifiers in quotes are literal and consume no arguments.
+        assert_eq!(
+            parse_with("name LIKE '%@*'", vec![]).to_string(),
+            "name LIKE \"%@*\""
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse("name ==", |_| Constant::Nil).is_err());
+        assert!(parse("name MATCHES '.*'", |_| Constant::Nil).is_err());
+        assert!(parse("(a == 1", |_| Constant::Nil).is_err());
+        assert!(parse("a == 'b", |_| Constant::Nil).is_err());
+    }
+
+    #[test]
+    fn string_operators() {
+        let string = |s: &str| Value::String(s.to_string());
+        let options = Options {
+            case_insensitive: true,
+            diacritic_insensitive: false,
+        };
+        assert!(compare(
+            Operator::Like,
+            Options::default(),
+            &string("touchHLE"),
+            &string("t?uch*")
+        ));
+        assert!(!compare(
+            Operator::Like,
+            Options::default(),
+            &string("touchHLE"),
+            &string("t?uch")
+        ));
+        assert!(compare(
+            Operator::Contains,
+            options,
+            &string("touchHLE"),
+            &string("hle")
+        ));
+        assert!(compare(
+            Operator::In,
+            Options::default(),
+            &string("b"),
+            &Value::Collection(vec![string("a"), string("b")])
+        ));
+        assert!(compare(
+            Operator::Less,
+            Options::default(),
+            &Value::Number(1.0),
+            &Value::Number(2.0)
+        ));
+        assert!(!compare(
+            Operator::Less,
+            Options::default(),
+            &Value::Nil,
+            &Value::Number(2.0)
+        ));
+    }
+}
@@ -5,9 +5,19 @@
  */
 //! `NSProcessInfo`.
 
-use super::NSTimeInterval;
-use crate::objc::{objc_classes, ClassExports};
-use std::time::Instant;
+use super::{ns_array, ns_dictionary, ns_string, NSInteger, NSTimeInterval, NSUInteger};
+use crate::objc::{autorelease, id, objc_classes, ClassExports, TrivialHostObject};
+
+/// Amount of RAM reported by `physicalMemory`. This is what the original
+/// iPhone and the iPhone 3G have. The host's amount is not used because apps
+/// may size their caches based on this, and the guest address space is only
+/// 32-bit anyway.
+const PHYSICAL_MEMORY: u64 = 128 * 1024 * 1024;
+
+#[derive(Default)]
+pub struct State {
+    process_info: Option<id>,
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -15,8 +25,67 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation NSProcessInfo: NSObject
 
++ (id)processInfo {
+    if let Some(process_info) = env.framework_state.foundation.ns_process_info.process_info {
+        process_info
+    } else {
+        let new = env.objc.alloc_static_object(
+            this,
+            Box::new(TrivialHostObject),
+            &mut env.mem
+        );
+        env.framework_state.foundation.ns_process_info.process_info = Some(new);
+        new
+    }
+}
+
+// Apple's API only has the instance method (see below), but this is harmless.
 + (NSTimeInterval)systemUptime {
-    Instant::now().duration_since(env.startup_time).as_secs_f64()
+    env.clock.uptime().as_secs_f64()
+}
+
+- (id)retain { this }
+- (())release {}
+- (id)autorelease { this }
+
+- (id)processName {
+    let executable_path = env.bundle.executable_path();
+    let name = executable_path.file_name().unwrap().to_string();
+    let name = ns_string::from_rust_string(env, name);
+    autorelease(env, name)
+}
+
+- (NSInteger)processIdentifier {
+    std::process::id() as NSInteger
+}
+
+- (id)arguments {
+    // Same as the argv passed to the app, see `Environment::new`.
+    let executable_path = env.bundle.executable_path();
+    let argv0 = ns_string::from_rust_string(env, executable_path.as_str().to_string());
+    let arguments = ns_array::from_vec(env, vec![argv0]);
+    autorelease(env, arguments)
+}
+
+- (id)environment {
+    // Same as the envp passed to the app, which is empty.
+    let environment = ns_dictionary::dict_from_keys_and_objects(env, &[]);
+    autorelease(env, environment)
+}
+
+- (NSTimeInterval)systemUptime {
+    env.clock.uptime().as_secs_f64()
+}
+
+- (u64)physicalMemory {
+    PHYSICAL_MEMORY
+}
+
+- (NSUInteger)processorCount {
+    1
+}
+- (NSUInteger)activeProcessorCount {
+    1
 }
 
 @end
@@ -0,0 +1,504 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSRegularExpression` and `NSTextCheckingResult`.
+//!
+//! Apple's implementation uses ICU's regular expressions. This one translates
+//! the pattern to the syntax of the `regex` crate, which is very similar but
+//! lacks some features, notably backreferences and lookaround. Patterns using
+//! these will fail to compile.
+//!
+//! Resources:
+//! - [ICU regular expression syntax](https://unicode-org.github.io/icu/userguide/strings/regexp.html)
+
+use super::{ns_array, ns_string, BlockLiteral, NSInteger, NSNotFound, NSRange, NSUInteger};
+use crate::abi::CallFromHost;
+use crate::mem::{ConstVoidPtr, MutPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::fmt::Write;
+
+pub type NSRegularExpressionOptions = NSUInteger;
+pub const NSRegularExpressionCaseInsensitive: NSRegularExpressionOptions = 1 << 0;
+pub const NSRegularExpressionAllowCommentsAndWhitespace: NSRegularExpressionOptions = 1 << 1;
+pub const NSRegularExpressionIgnoreMetacharacters: NSRegularExpressionOptions = 1 << 2;
+pub const NSRegularExpressionDotMatchesLineSeparators: NSRegularExpressionOptions = 1 << 3;
+pub const NSRegularExpressionAnchorsMatchLines: NSRegularExpressionOptions = 1 << 4;
+
+pub type NSMatchingOptions = NSUInteger;
+pub const NSMatchingReportCompletion: NSMatchingOptions = 1 << 1;
+pub const NSMatchingAnchored: NSMatchingOptions = 1 << 2;
+
+pub type NSMatchingFlags = NSUInteger;
+pub const NSMatchingCompleted: NSMatchingFlags = 1 << 1;
+
+pub type NSTextCheckingType = u64;
+pub const NSTextCheckingTypeRegularExpression: NSTextCheckingType = 1 << 10;
+
+struct NSRegularExpressionHostObject {
+    /// Strong reference, `NSString*`.
+    pattern: id,
+    options: NSRegularExpressionOptions,
+    regex: regex::Regex,
+}
+impl HostObject for NSRegularExpressionHostObject {}
+
+struct NSTextCheckingResultHostObject {
+    /// The first range is the whole match, the rest are capture groups.
+    ranges: Vec<NSRange>,
+    /// Strong reference.
+    regular_expression: id,
+}
+impl HostObject for NSTextCheckingResultHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSRegularExpression: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSRegularExpressionHostObject {
+        pattern: nil,
+        options: 0,
+        regex: regex::Regex::new("").unwrap(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)regularExpressionWithPattern:(id)pattern // NSString*
+                           options:(NSRegularExpressionOptions)options
+                             error:(MutPtr<id>)error { // NSError**
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithPattern:pattern options:options error:error];
+    autorelease(env, new)
+}
+
++ (id)escapedPatternForString:(id)string { // NSString*
+    let string = ns_string::to_rust_string(env, string);
+    let escaped = regex::escape(&string);
+    let escaped = ns_string::from_rust_string(env, escaped);
+    autorelease(env, escaped)
+}
+
++ (id)escapedTemplateForString:(id)string { // NSString*
+    let mut escaped = Vec::new();
+    ns_string::for_each_code_unit(env, string, |_, c| {
+        if c == b'$'.into() || c == b'\\'.into() {
+            escaped.push(b'\\'.into());
+        }
+        escaped.push(c);
+    });
+    let escaped = ns_string::from_utf16_code_units(env, escaped);
+    autorelease(env, escaped)
+}
+
+- (id)initWithPattern:(id)pattern // NSString*
+              options:(NSRegularExpressionOptions)options
+                error:(MutPtr<id>)error { // NSError**
+    let pattern_str = ns_string::to_rust_string(env, pattern);
+    let translated = translate_pattern(&pattern_str, options);
+    let regex = match regex::Regex::new(&translated) {
+        Ok(regex) => regex,
+        Err(e) => {
+            log!(
+                "Warning: couldn't compile regular expression {:?} (translated to {:?}): {}",
+                pattern_str,
+                translated,
+                e
+            );
+            // TODO: NSError
+            if !error.is_null() {
+                env.mem.write(error, nil);
+            }
+            release(env, this);
+            return nil;
+        }
+    };
+    let pattern: id = msg![env; pattern copy];
+    let host_object = env.objc.borrow_mut::<NSRegularExpressionHostObject>(this);
+    host_object.pattern = pattern;
+    host_object.options = options;
+    host_object.regex = regex;
+    this
+}
+
+- (())dealloc {
+    let pattern = env.objc.borrow::<NSRegularExpressionHostObject>(this).pattern;
+    release(env, pattern);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+- (id)pattern {
+    env.objc.borrow::<NSRegularExpressionHostObject>(this).pattern
+}
+- (NSRegularExpressionOptions)options {
+    env.objc.borrow::<NSRegularExpressionHostObject>(this).options
+}
+- (NSUInteger)numberOfCaptureGroups {
+    let regex = &env.objc.borrow::<NSRegularExpressionHostObject>(this).regex;
+    (regex.captures_len() - 1).try_into().unwrap()
+}
+
+- (id)matchesInString:(id)string // NSString*
+              options:(NSMatchingOptions)options
+                range:(NSRange)range {
+    let matches = find_matches(env, this, string, options, range, None);
+    let results: Vec<id> = matches
+        .into_iter()
+        .map(|ranges| new_result(env, this, ranges))
+        .collect();
+    let array = ns_array::from_vec(env, results);
+    autorelease(env, array)
+}
+
+- (NSUInteger)numberOfMatchesInString:(id)string // NSString*
+                              options:(NSMatchingOptions)options
+                                range:(NSRange)range {
+    let matches = find_matches(env, this, string, options, range, None);
+    matches.len().try_into().unwrap()
+}
+
+- (id)firstMatchInString:(id)string // NSString*
+                 options:(NSMatchingOptions)options
+                   range:(NSRange)range {
+    let mut matches = find_matches(env, this, string, options, range, Some(1));
+    if matches.is_empty() {
+        return nil;
+    }
+    let result = new_result(env, this, matches.remove(0));
+    autorelease(env, result)
+}
+
+- (NSRange)rangeOfFirstMatchInString:(id)string // NSString*
+                             options:(NSMatchingOptions)options
+                               range:(NSRange)range {
+    let matches = find_matches(env, this, string, options, range, Some(1));
+    match matches.first() {
+        Some(ranges) => ranges[0],
+        None => NSRange {
+            location: NSNotFound as NSUInteger,
+            length: 0,
+        },
+    }
+}
+
+- (())enumerateMatchesInString:(id)string // NSString*
+                       options:(NSMatchingOptions)options
+                         range:(NSRange)range
+                    usingBlock:(ConstVoidPtr)block {
+    let BlockLiteral { invoke, .. } = env.mem.read(block.cast());
+    let stop: MutPtr<u8> = env.mem.alloc(1).cast();
+    env.mem.write(stop, 0);
+
+    let matches = find_matches(env, this, string, options, range, None);
+    for ranges in matches {
+        let result = new_result(env, this, ranges);
+        let flags: NSMatchingFlags = 0;
+        let () = invoke.call_from_host(env, (block, result, flags, stop));
+        release(env, result);
+        if env.mem.read(stop) != 0 {
+            env.mem.free(stop.cast());
+            return;
+        }
+    }
+    if options & NSMatchingReportCompletion != 0 {
+        let () = invoke.call_from_host(env, (block, nil, NSMatchingCompleted, stop));
+    }
+    env.mem.free(stop.cast());
+}
+
+- (id)stringByReplacingMatchesInString:(id)string // NSString*
+                               options:(NSMatchingOptions)options
+                                 range:(NSRange)range
+                          withTemplate:(id)template { // NSString*
+    let matches = find_matches(env, this, string, options, range, None);
+    let string_utf16 = code_units(env, string);
+    let template_utf16 = code_units(env, template);
+
+    let mut result = Vec::with_capacity(string_utf16.len());
+    let mut copied_up_to = 0;
+    for ranges in matches {
+        let start = ranges[0].location as usize;
+        let end = start + ranges[0].length as usize;
+        result.extend_from_slice(&string_utf16[copied_up_to..start]);
+        expand_template(&template_utf16, &ranges, &string_utf16, 0, &mut result);
+        copied_up_to = end;
+    }
+    result.extend_from_slice(&string_utf16[copied_up_to..]);
+
+    let result = ns_string::from_utf16_code_units(env, result);
+    autorelease(env, result)
+}
+
+- (id)replacementStringForResult:(id)result // NSTextCheckingResult*
+                        inString:(id)string // NSString*
+                          offset:(NSInteger)offset
+                        template:(id)template { // NSString*
+    let ranges = env.objc.borrow::<NSTextCheckingResultHostObject>(result).ranges.clone();
+    let string_utf16 = code_units(env, string);
+    let template_utf16 = code_units(env, template);
+
+    let mut replacement = Vec::new();
+    expand_template(&template_utf16, &ranges, &string_utf16, offset, &mut replacement);
+
+    let replacement = ns_string::from_utf16_code_units(env, replacement);
+    autorelease(env, replacement)
+}
+
+@end
+
+@implementation NSTextCheckingResult: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSTextCheckingResultHostObject {
+        ranges: Vec::new(),
+        regular_expression: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &NSTextCheckingResultHostObject {
+        regular_expression, ..
+    } = env.objc.borrow(this);
+    release(env, regular_expression);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+- (NSTextCheckingType)resultType {
+    NSTextCheckingTypeRegularExpression
+}
+
+- (id)regularExpression {
+    env.objc.borrow::<NSTextCheckingResultHostObject>(this).regular_expression
+}
+
+- (NSRange)range {
+    env.objc.borrow::<NSTextCheckingResultHostObject>(this).ranges[0]
+}
+
+- (NSUInteger)numberOfRanges {
+    let ranges = &env.objc.borrow::<NSTextCheckingResultHostObject>(this).ranges;
+    ranges.len().try_into().unwrap()
+}
+
+- (NSRange)rangeAtIndex:(NSUInteger)idx {
+    let ranges = &env.objc.borrow::<NSTextCheckingResultHostObject>(this).ranges;
+    ranges[idx as usize]
+}
+
+@end
+
+};
+
+/// Translate an ICU regular expression pattern to the `regex` crate's syntax.
+fn translate_pattern(pattern: &str, options: NSRegularExpressionOptions) -> String {
+    let mut translated = String::with_capacity(pattern.len());
+
+    if options & NSRegularExpressionCaseInsensitive != 0 {
+        translated.push_str("(?i)");
+    }
+    if options & NSRegularExpressionAllowCommentsAndWhitespace != 0 {
+        translated.push_str("(?x)");
+    }
+    if options & NSRegularExpressionDotMatchesLineSeparators != 0 {
+        translated.push_str("(?s)");
+    }
+    if options & NSRegularExpressionAnchorsMatchLines != 0 {
+        translated.push_str("(?m)");
+    }
+
+    if options & NSRegularExpressionIgnoreMetacharacters != 0 {
+        translated.push_str(&regex::escape(pattern));
+        return translated;
+    }
+
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            translated.push(c);
+            continue;
+        }
+        match chars.next() {
+            // Quoted literal text: \Q...\E
+            Some('Q') => {
+                let mut literal = String::new();
+                while let Some(c) = chars.next() {
+                    if c == '\\' && chars.peek() == Some(&'E') {
+                        chars.next();
+                        break;
+                    }
+                    literal.push(c);
+                }
+                translated.push_str(&regex::escape(&literal));
+            }
+            // \uhhhh and \Uhhhhhhhh
+            Some(c @ ('u' | 'U')) => {
+                let digits = if c == 'u' { 4 } else { 8 };
+                let hex: String = chars.by_ref().take(digits).collect();
+                write!(translated, "\\x{{{}}}", hex).unwrap();
+            }
+            Some('e') => translated.push_str("\\x{1B}"),
+            // ICU's \Z also allows a final line terminator.
+            Some('Z') => translated.push_str("\\n?\\z"),
+            Some(c) => {
+                translated.push('\\');
+                translated.push(c);
+            }
+            None => translated.push('\\'),
+        }
+    }
+    translated
+}
+
+/// Get a copy of a string's UTF-16 code units.
+fn code_units(env: &mut Environment, string: id) -> Vec<u16> {
+    let mut code_units = Vec::new();
+    ns_string::for_each_code_unit(env, string, |_, c| code_units.push(c));
+    code_units
+}
+
+/// Find the matches of a regular expression in a range of a string. Each match
+/// is returned as a list of ranges (in UTF-16 code units), the first being the
+/// whole match and the rest being the capture groups.
+fn find_matches(
+    env: &mut Environment,
+    regular_expression: id,
+    string: id,
+    options: NSMatchingOptions,
+    range: NSRange,
+    limit: Option<usize>,
+) -> Vec<Vec<NSRange>> {
+    let regex = env
+        .objc
+        .borrow::<NSRegularExpressionHostObject>(regular_expression)
+        .regex
+        .clone();
+
+    let string_utf16 = code_units(env, string);
+    let start = range.location as usize;
+    let end = start + range.length as usize;
+    assert!(end <= string_utf16.len());
+
+    // The regex crate works on UTF-8, so the searched text has to be converted,
+    // keeping a mapping from UTF-8 byte offsets back to UTF-16 offsets.
+    // Unpaired surrogates become U+FFFD, which is also one code unit long.
+    let mut text = String::with_capacity(end - start);
+    let mut utf16_offsets: Vec<NSUInteger> = Vec::with_capacity(end - start + 1);
+    let mut utf16_offset = range.location;
+    for c in char::decode_utf16(string_utf16[start..end].iter().copied()) {
+        let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+        for _ in 0..c.len_utf8() {
+            utf16_offsets.push(utf16_offset);
+        }
+        text.push(c);
+        utf16_offset += c.len_utf16() as NSUInteger;
+    }
+    utf16_offsets.push(utf16_offset);
+
+    let mut matches = Vec::new();
+    let mut previous_end = 0;
+    for captures in regex.captures_iter(&text) {
+        let whole = captures.get(0).unwrap();
+        if options & NSMatchingAnchored != 0 && whole.start() != previous_end {
+            break;
+        }
+        previous_end = whole.end();
+
+        let ranges = (0..captures.len())
+            .map(|i| match captures.get(i) {
+                Some(m) => NSRange {
+                    location: utf16_offsets[m.start()],
+                    length: utf16_offsets[m.end()] - utf16_offsets[m.start()],
+                },
+                None => NSRange {
+                    location: NSNotFound as NSUInteger,
+                    length: 0,
+                },
+            })
+            .collect();
+        matches.push(ranges);
+
+        if Some(matches.len()) == limit {
+            break;
+        }
+    }
+    matches
+}
+
+/// Create an `NSTextCheckingResult` (+1 reference).
+fn new_result(env: &mut Environment, regular_expression: id, ranges: Vec<NSRange>) -> id {
+    retain(env, regular_expression);
+    let result: id = msg_class![env; NSTextCheckingResult alloc];
+    let host_object = env
+        .objc
+        .borrow_mut::<NSTextCheckingResultHostObject>(result);
+    host_object.ranges = ranges;
+    host_object.regular_expression = regular_expression;
+    result
+}
+
+/// Expand a replacement template, where `$n` is replaced by the `n`th capture
+/// group and a backslash escapes the next character. `offset` is added to the
+/// ranges before looking them up in `string`.
+fn expand_template(
+    template: &[u16],
+    ranges: &[NSRange],
+    string: &[u16],
+    offset: NSInteger,
+    out: &mut Vec<u16>,
+) {
+    let is_digit = |c: u16| (u16::from(b'0')..=u16::from(b'9')).contains(&c);
+
+    let mut i = 0;
+    while i < template.len() {
+        let c = template[i];
+        i += 1;
+        if c == u16::from(b'\\') {
+            if let Some(&escaped) = template.get(i) {
+                out.push(escaped);
+                i += 1;
+            }
+        } else if c == u16::from(b'$') && matches!(template.get(i), Some(&c) if is_digit(c)) {
+            // Like ICU, take as many digits as still give a valid group number.
+            let mut group = (template[i] - u16::from(b'0')) as usize;
+            i += 1;
+            while let Some(&c) = template.get(i) {
+                if !is_digit(c) {
+                    break;
+                }
+                let new_group = group * 10 + (c - u16::from(b'0')) as usize;
+                if new_group >= ranges.len() {
+                    break;
+                }
+                group = new_group;
+                i += 1;
+            }
+            let Some(&NSRange { location, length }) = ranges.get(group) else {
+                continue;
+            };
+            if location == NSNotFound as NSUInteger {
+                continue;
+            }
+            let start = (location as NSInteger + offset) as usize;
+            out.extend_from_slice(&string[start..][..length as usize]);
+        } else {
+            out.push(c);
+        }
+    }
+}
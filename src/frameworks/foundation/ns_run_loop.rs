@@ -5,17 +5,28 @@
  */
 //! `NSRunLoop`.
 //!
+//! This is also where the core of `CFRunLoop` lives: run loop modes, and
+//! scheduling of timers, display links, streams, sources and observers. The sources and
+//! observers themselves are implemented in [cf_run_loop].
+//!
 //! Resources:
 //! - Apple's [Threading Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/Multithreading/Introduction/Introduction.html)
 
-use super::{ns_string, ns_timer};
+use super::{ns_date, ns_stream, ns_string, ns_timer};
 use crate::dyld::{ConstantExports, HostConstant};
 use crate::frameworks::audio_toolbox::audio_queue::{handle_audio_queue, AudioQueueRef};
+use crate::frameworks::core_animation::{ca_display_link, composition};
 use crate::frameworks::core_foundation::cf_run_loop::{
-    kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoopRef,
+    self, kCFRunLoopAfterWaiting, kCFRunLoopBeforeSources, kCFRunLoopBeforeTimers,
+    kCFRunLoopBeforeWaiting, kCFRunLoopCommonModes, kCFRunLoopDefaultMode, kCFRunLoopEntry,
+    kCFRunLoopExit, kCFRunLoopRunFinished, kCFRunLoopRunHandledSource, kCFRunLoopRunStopped,
+    kCFRunLoopRunTimedOut, CFRunLoopActivity, CFRunLoopRef, CFRunLoopRunResult,
+};
+use crate::frameworks::uikit::ui_application::UITrackingRunLoopMode;
+use crate::frameworks::{uikit, RUN_LOOP_POLLERS};
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, HostObject,
 };
-use crate::frameworks::uikit;
-use crate::objc::{id, msg, objc_classes, release, retain, ClassExports, HostObject};
 use crate::Environment;
 use std::time::Duration;
 
@@ -41,13 +52,109 @@ pub struct State {
     main_thread_run_loop: Option<id>,
 }
 
+/// Things scheduled on a run loop, each with the modes it is scheduled in.
+///
+/// Something scheduled in [kCFRunLoopCommonModes] is tracked with that
+/// pseudo-mode rather than with the individual modes, so that it is also in any
+/// modes later added to the common modes.
+#[derive(Default)]
+struct Scheduled(Vec<(id, Vec<String>)>);
+
+impl Scheduled {
+    /// Schedule `item` in `mode`. Returns a pair of booleans: whether it wasn't
+    /// already scheduled in `mode`, and whether it wasn't scheduled in any mode.
+    fn add(&mut self, item: id, mode: String) -> (bool, bool) {
+        if let Some((_, modes)) = self.0.iter_mut().find(|(other, _)| *other == item) {
+            if modes.contains(&mode) {
+                (false, false)
+            } else {
+                modes.push(mode);
+                (true, false)
+            }
+        } else {
+            self.0.push((item, vec![mode]));
+            (true, true)
+        }
+    }
+
+    /// Unschedule `item` from `mode`, or from all modes if `mode` is [None].
+    /// Returns the modes it was removed from, and whether it is now not
+    /// scheduled in any mode.
+    fn remove(&mut self, item: id, mode: Option<&str>) -> (Vec<String>, bool) {
+        let Some(idx) = self.0.iter().position(|&(other, _)| other == item) else {
+            return (Vec::new(), false);
+        };
+        let modes = &mut self.0[idx].1;
+        let removed = match mode {
+            Some(mode) => match modes.iter().position(|other| other == mode) {
+                Some(mode_idx) => vec![modes.remove(mode_idx)],
+                None => Vec::new(),
+            },
+            None => std::mem::take(modes),
+        };
+        let now_unscheduled = modes.is_empty();
+        if now_unscheduled {
+            self.0.remove(idx);
+        }
+        (removed, now_unscheduled)
+    }
+
+    fn contains(&self, item: id, mode: &str, common_modes: &[String]) -> bool {
+        self.0.iter().any(|(other, modes)| {
+            *other == item
+                && (modes.iter().any(|other| other == mode)
+                    || is_in_mode(modes, mode, common_modes))
+        })
+    }
+
+    /// Get the items scheduled in `mode`, in the order they were scheduled.
+    fn in_mode(&self, mode: &str, common_modes: &[String]) -> Vec<id> {
+        self.0
+            .iter()
+            .filter(|(_, modes)| is_in_mode(modes, mode, common_modes))
+            .map(|&(item, _)| item)
+            .collect()
+    }
+
+    fn any_in_mode(&self, mode: &str, common_modes: &[String]) -> bool {
+        self.0
+            .iter()
+            .any(|(_, modes)| is_in_mode(modes, mode, common_modes))
+    }
+}
+
+fn is_in_mode(modes: &[String], mode: &str, common_modes: &[String]) -> bool {
+    modes.iter().any(|other| {
+        other == mode
+            || (other == kCFRunLoopCommonModes && common_modes.iter().any(|other| other == mode))
+    })
+}
+
 struct NSRunLoopHostObject {
     /// Weak reference. Audio queue must remove itself when destroyed (TODO).
-    /// They are in no particular order.
+    /// They are in no particular order. They are always in the common modes.
     audio_queues: Vec<AudioQueueRef>,
-    /// Strong references to `NSTimer*` in no particular order. Timers are owned
-    /// by the run loop. The timer must remove itself when invalidated.
-    timers: Vec<id>,
+    /// Strong references to `NSTimer*`. Timers are owned by the run loop. The
+    /// timer must remove itself when invalidated.
+    timers: Scheduled,
+    /// Strong references to `CADisplayLink*`. The display link must remove
+    /// itself when it is invalidated.
+    display_links: Scheduled,
+    /// Weak references to `NSStream*`. The stream must remove itself when it is
+    /// closed or destroyed.
+    streams: Scheduled,
+    /// Strong references to `CFRunLoopSourceRef`.
+    sources: Scheduled,
+    /// Strong references to `CFRunLoopObserverRef`.
+    observers: Scheduled,
+    /// The modes that [kCFRunLoopCommonModes] stands for. UI events are only
+    /// handled in these modes.
+    common_modes: Vec<String>,
+    /// The mode the run loop is running in, if it's running. When run loops
+    /// are nested, this is the mode of the innermost one.
+    current_mode: Option<String>,
+    /// Set by `CFRunLoopStop`.
+    stop_requested: bool,
 }
 impl HostObject for NSRunLoopHostObject {}
 
@@ -63,10 +170,23 @@ pub const CLASSES: ClassExports = objc_classes! {
     } else {
         let host_object = Box::new(NSRunLoopHostObject {
             audio_queues: Vec::new(),
-            timers: Vec::new(),
+            timers: Scheduled::default(),
+            display_links: Scheduled::default(),
+            streams: Scheduled::default(),
+            sources: Scheduled::default(),
+            observers: Scheduled::default(),
+            common_modes: vec![
+                kCFRunLoopDefaultMode.to_string(),
+                UITrackingRunLoopMode.to_string(),
+            ],
+            current_mode: None,
+            stop_requested: false,
         });
         let new = env.objc.alloc_static_object(this, host_object, &mut env.mem);
         env.framework_state.foundation.ns_run_loop.main_thread_run_loop = Some(new);
+        for &poll in RUN_LOOP_POLLERS {
+            cf_run_loop::add_host_source(env, new, poll);
+        }
         new
     }
 }
@@ -87,31 +207,67 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (NSRunLoopMode)currentMode {
+    match current_mode(env, this) {
+        Some(mode) => {
+            let mode = ns_string::from_rust_string(env, mode);
+            autorelease(env, mode)
+        }
+        None => nil,
+    }
+}
+
 - (())addTimer:(id)timer // NSTimer*
        forMode:(NSRunLoopMode)mode {
-    let default_mode = ns_string::get_static_str(env, NSDefaultRunLoopMode);
-    // TODO: handle other modes
-    assert!(msg![env; mode isEqualToString:default_mode]);
-
     log_dbg!("Adding timer {:?} to run loop {:?}", timer, this);
 
-    retain(env, timer);
-
+    let mode = ns_string::to_rust_string(env, mode).into_owned();
     let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(this);
-    assert!(!host_object.timers.contains(&timer)); // TODO: what do we do here?
-    host_object.timers.push(timer);
-    ns_timer::set_run_loop(env, timer, this);
+    let (_, newly_scheduled) = host_object.timers.add(timer, mode);
+    if newly_scheduled {
+        retain(env, timer);
+        ns_timer::set_run_loop(env, timer, this);
+    }
 }
 
 - (())run {
-    run_run_loop(env, this);
+    // Unlike CFRunLoopRun(), this can't be stopped with CFRunLoopStop().
+    loop {
+        let result = run_run_loop(env, this, NSDefaultRunLoopMode, None, false);
+        if result == kCFRunLoopRunFinished {
+            break;
+        }
+    }
+}
+- (bool)runMode:(NSRunLoopMode)mode
+     beforeDate:(id)limit_date { // NSDate*
+    let mode = ns_string::to_rust_string(env, mode);
+    let timeout = time_until(env, limit_date);
+    run_run_loop(env, this, &mode, Some(timeout), true) != kCFRunLoopRunFinished
+}
+- (())runUntilDate:(id)limit_date { // NSDate*
+    loop {
+        let timeout = time_until(env, limit_date);
+        let result = run_run_loop(env, this, NSDefaultRunLoopMode, Some(timeout), true);
+        if result == kCFRunLoopRunFinished || result == kCFRunLoopRunTimedOut {
+            break;
+        }
+    }
 }
-// TODO: other run methods
 
 @end
 
 };
 
+/// Time remaining until an `NSDate*`, or zero if it's in the past or `nil`.
+fn time_until(env: &mut Environment, date: id) -> Duration {
+    if date == nil {
+        return Duration::ZERO;
+    }
+    let interval = ns_date::get_time_interval(env, date) - ns_date::now_for_app(env);
+    Duration::try_from_secs_f64(interval).unwrap_or(Duration::ZERO)
+}
+
 /// For use by Audio Toolbox.
 /// TODO: Maybe replace this with a `CFRunLoopObserver` or some other generic
 /// mechanism?
@@ -135,62 +291,379 @@ pub fn remove_audio_queue(env: &mut Environment, run_loop: id, queue: AudioQueue
 
 /// For use by NSTimer so it can remove itself once it's invalidated.
 pub(super) fn remove_timer(env: &mut Environment, run_loop: id, timer: id) {
-    let NSRunLoopHostObject { timers, .. } = env.objc.borrow_mut(run_loop);
-
-    let mut i = 0;
-    let mut release_count = 0;
-    while i < timers.len() {
-        if timers[i] == timer {
-            timers.swap_remove(i);
-            release_count += 1;
-        } else {
-            i += 1;
-        }
+    let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+    let (_, now_unscheduled) = host_object.timers.remove(timer, None);
+    assert!(now_unscheduled); // TODO?
+    release(env, timer);
+}
+
+/// For use by `CADisplayLink`.
+pub fn add_display_link(env: &mut Environment, run_loop: id, link: id, mode: NSRunLoopMode) {
+    let mode = ns_string::to_rust_string(env, mode).into_owned();
+    let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+    let (_, newly_scheduled) = host_object.display_links.add(link, mode);
+    if newly_scheduled {
+        retain(env, link);
     }
-    assert!(release_count == 1); // TODO?
-    for _ in 0..release_count {
-        release(env, timer);
+}
+
+/// For use by `CADisplayLink`. Unschedules the display link from `mode`, or
+/// from all modes if `mode` is `nil`. Returns `true` if it is no longer
+/// scheduled in any mode.
+pub fn remove_display_link(
+    env: &mut Environment,
+    run_loop: id,
+    link: id,
+    mode: NSRunLoopMode,
+) -> bool {
+    let mode = (mode != nil).then(|| ns_string::to_rust_string(env, mode));
+    let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+    let (_, now_unscheduled) = host_object.display_links.remove(link, mode.as_deref());
+    if now_unscheduled {
+        release(env, link);
     }
+    now_unscheduled
 }
 
-fn run_run_loop(env: &mut Environment, run_loop: id) {
-    log_dbg!("Entering run loop {:?} (indefinitely)", run_loop);
+/// For use by NSStream.
+pub(super) fn add_stream(env: &mut Environment, run_loop: id, stream: id, mode: NSRunLoopMode) {
+    let mode = ns_string::to_rust_string(env, mode).into_owned();
+    let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+    host_object.streams.add(stream, mode);
+}
+
+/// For use by NSStream. Unschedules the stream from `mode`, or from all modes
+/// if `mode` is `nil`. Returns `true` if the stream is no longer scheduled in
+/// any mode.
+pub(super) fn remove_stream(
+    env: &mut Environment,
+    run_loop: id,
+    stream: id,
+    mode: NSRunLoopMode,
+) -> bool {
+    let mode = (mode != nil).then(|| ns_string::to_rust_string(env, mode));
+    let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+    let (_, now_unscheduled) = host_object.streams.remove(stream, mode.as_deref());
+    now_unscheduled
+}
+
+/// For use by `CFRunLoopAddSource`. Returns `true` if the source wasn't already
+/// scheduled in `mode`.
+pub fn add_source(env: &mut Environment, run_loop: id, source: id, mode: NSRunLoopMode) -> bool {
+    let mode = ns_string::to_rust_string(env, mode).into_owned();
+    let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+    let (newly_in_mode, newly_scheduled) = host_object.sources.add(source, mode);
+    if newly_scheduled {
+        retain(env, source);
+    }
+    newly_in_mode
+}
+
+/// For use by `CFRunLoopRemoveSource` and `CFRunLoopSourceInvalidate`.
+/// Unschedules the source from `mode`, or from all modes if `mode` is `nil`.
+/// Returns the modes it was removed from, and whether it is no longer scheduled
+/// in any mode.
+pub fn remove_source(
+    env: &mut Environment,
+    run_loop: id,
+    source: id,
+    mode: NSRunLoopMode,
+) -> (Vec<String>, bool) {
+    let mode = (mode != nil).then(|| ns_string::to_rust_string(env, mode));
+    let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+    let (removed, now_unscheduled) = host_object.sources.remove(source, mode.as_deref());
+    if now_unscheduled {
+        release(env, source);
+    }
+    (removed, now_unscheduled)
+}
+
+/// For use by `CFRunLoopContainsSource`.
+pub fn contains_source(
+    env: &mut Environment,
+    run_loop: id,
+    source: id,
+    mode: NSRunLoopMode,
+) -> bool {
+    let mode = ns_string::to_rust_string(env, mode);
+    let host_object = env.objc.borrow::<NSRunLoopHostObject>(run_loop);
+    host_object
+        .sources
+        .contains(source, &mode, &host_object.common_modes)
+}
+
+/// For use by `CFRunLoopAddObserver`.
+pub fn add_observer(env: &mut Environment, run_loop: id, observer: id, mode: NSRunLoopMode) {
+    let mode = ns_string::to_rust_string(env, mode).into_owned();
+    let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+    let (_, newly_scheduled) = host_object.observers.add(observer, mode);
+    if newly_scheduled {
+        retain(env, observer);
+    }
+}
 
-    // Temporary vectors used to track things without needing a reference to the
+/// For use by `CFRunLoopRemoveObserver` and `CFRunLoopObserverInvalidate`.
+/// Unschedules the observer from `mode`, or from all modes if `mode` is `nil`.
+/// Returns `true` if it is no longer scheduled in any mode.
+pub fn remove_observer(
+    env: &mut Environment,
+    run_loop: id,
+    observer: id,
+    mode: NSRunLoopMode,
+) -> bool {
+    let mode = (mode != nil).then(|| ns_string::to_rust_string(env, mode));
+    let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+    let (_, now_unscheduled) = host_object.observers.remove(observer, mode.as_deref());
+    if now_unscheduled {
+        release(env, observer);
+    }
+    now_unscheduled
+}
+
+/// For use by `CFRunLoopContainsObserver`.
+pub fn contains_observer(
+    env: &mut Environment,
+    run_loop: id,
+    observer: id,
+    mode: NSRunLoopMode,
+) -> bool {
+    let mode = ns_string::to_rust_string(env, mode);
+    let host_object = env.objc.borrow::<NSRunLoopHostObject>(run_loop);
+    host_object
+        .observers
+        .contains(observer, &mode, &host_object.common_modes)
+}
+
+/// For use by `CFRunLoopAddCommonMode`.
+pub fn add_common_mode(env: &mut Environment, run_loop: id, mode: NSRunLoopMode) {
+    let mode = ns_string::to_rust_string(env, mode).into_owned();
+    let common_modes = &mut env
+        .objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .common_modes;
+    if !common_modes.contains(&mode) {
+        common_modes.push(mode);
+    }
+}
+
+/// For use by `CFRunLoopCopyCurrentMode`.
+pub fn current_mode(env: &mut Environment, run_loop: id) -> Option<String> {
+    env.objc
+        .borrow::<NSRunLoopHostObject>(run_loop)
+        .current_mode
+        .clone()
+}
+
+/// For use by `CFRunLoopStop`: make the innermost invocation of the run loop
+/// return.
+pub fn stop(env: &mut Environment, run_loop: id) {
+    env.objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .stop_requested = true;
+}
+
+/// Run the run loop in `mode` until it is stopped, until `timeout` (if any) has
+/// elapsed, or (if `return_after_source_handled` is `true`) until a
+/// `CFRunLoopSource` has been performed. This is `CFRunLoopRunInMode`.
+///
+/// Run loops can be nested, e.g. a timer can run the run loop in another mode.
+pub fn run_run_loop(
+    env: &mut Environment,
+    run_loop: id,
+    mode: &str,
+    timeout: Option<Duration>,
+    return_after_source_handled: bool,
+) -> CFRunLoopRunResult {
+    if !has_anything_in_mode(env, run_loop, mode) {
+        log_dbg!("Run loop {:?} has nothing in mode {:?}", run_loop, mode);
+        return kCFRunLoopRunFinished;
+    }
+
+    log_dbg!(
+        "Entering run loop {:?} in mode {:?} ({})",
+        run_loop,
+        mode,
+        match timeout {
+            Some(timeout) => format!("for {}s", timeout.as_secs_f64()),
+            None => "indefinitely".to_string(),
+        }
+    );
+
+    // The timeout is by the app's clock, so it's shorter when fast-forwarding.
+    let deadline = timeout.and_then(|timeout| env.clock.uptime().checked_add(timeout));
+
+    let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+    let outer_mode = host_object.current_mode.replace(mode.to_string());
+    let is_common_mode = host_object.common_modes.iter().any(|other| other == mode);
+
+    notify_observers(env, run_loop, mode, kCFRunLoopEntry);
+
+    // Temporary vector used to track things without needing a reference to the
     // environment or to lock the object. Re-used each iteration for efficiency.
-    let mut timers_tmp = Vec::new();
     let mut audio_queues_tmp = Vec::new();
 
-    loop {
+    let result = loop {
         env.window.poll_for_events(&env.options);
 
-        uikit::handle_events(env);
+        if is_common_mode {
+            uikit::handle_events(env);
+        }
 
-        assert!(timers_tmp.is_empty());
-        timers_tmp.extend_from_slice(&env.objc.borrow::<NSRunLoopHostObject>(run_loop).timers);
+        notify_observers(env, run_loop, mode, kCFRunLoopBeforeTimers);
 
-        for timer in timers_tmp.drain(..) {
+        for timer in items_in_mode(env, run_loop, mode, |host| &host.timers) {
             ns_timer::handle_timer(env, timer);
         }
+        // Display links are owned by the run loop, but may be invalidated and
+        // removed while others are handled.
+        let display_links = items_in_mode(env, run_loop, mode, |host| &host.display_links);
+        for &link in &display_links {
+            retain(env, link);
+        }
+        for &link in &display_links {
+            ca_display_link::handle_display_link(env, link);
+        }
+        for link in display_links {
+            release(env, link);
+        }
 
-        assert!(audio_queues_tmp.is_empty());
-        audio_queues_tmp.extend_from_slice(
-            &env.objc
-                .borrow::<NSRunLoopHostObject>(run_loop)
-                .audio_queues,
-        );
+        notify_observers(env, run_loop, mode, kCFRunLoopBeforeSources);
 
-        for audio_queue in audio_queues_tmp.drain(..) {
-            handle_audio_queue(env, audio_queue);
+        if is_common_mode {
+            assert!(audio_queues_tmp.is_empty());
+            audio_queues_tmp.extend_from_slice(
+                &env.objc
+                    .borrow::<NSRunLoopHostObject>(run_loop)
+                    .audio_queues,
+            );
+
+            for audio_queue in audio_queues_tmp.drain(..) {
+                handle_audio_queue(env, audio_queue);
+            }
+        }
+
+        for stream in items_in_mode(env, run_loop, mode, |host| &host.streams) {
+            ns_stream::handle_stream(env, stream);
+        }
+
+        // Sources may be removed and released while others are performed.
+        let mut sources = items_in_mode(env, run_loop, mode, |host| &host.sources);
+        sources.sort_by_key(|&source| cf_run_loop::source_order(env, source));
+        let mut source_handled = false;
+        for &source in &sources {
+            retain(env, source);
+        }
+        for &source in &sources {
+            source_handled |= cf_run_loop::handle_source(env, source);
+        }
+        for source in sources {
+            release(env, source);
         }
 
+        if source_handled && return_after_source_handled {
+            break kCFRunLoopRunHandledSource;
+        }
+        let host_object = env.objc.borrow_mut::<NSRunLoopHostObject>(run_loop);
+        if std::mem::take(&mut host_object.stop_requested) {
+            break kCFRunLoopRunStopped;
+        }
+        if !has_anything_in_mode(env, run_loop, mode) {
+            break kCFRunLoopRunFinished;
+        }
+        let remaining = match deadline {
+            Some(deadline) => {
+                let now = env.clock.uptime();
+                if now >= deadline {
+                    break kCFRunLoopRunTimedOut;
+                }
+                deadline - now
+            }
+            None => Duration::MAX,
+        };
+
+        // Like Core Animation's observer in the real run loop, this draws
+        // whatever the app changed in this iteration.
+        composition::recomposite_if_necessary(env);
+
+        notify_observers(env, run_loop, mode, kCFRunLoopBeforeWaiting);
+
         // This is a hack, but it saves a lot of CPU usage, as much as 75%!
         // 5ms is an arbitrary but apparently effective value. If it's too small
         // there won't be much benefit, and if it's too large there'll be too
-        // much lag.
+        // much lag. Display links need better timing than that though, so the
+        // sleep ends when the next one is due.
         // TODO: Try to calculate how much time remains until the next event
         // and sleep only that much.
         // FIXME: Run the app's other threads if they are active.
-        std::thread::sleep(Duration::from_millis(5));
+        let mut sleep = remaining.min(Duration::from_millis(5));
+        for link in items_in_mode(env, run_loop, mode, |host| &host.display_links) {
+            if let Some(until_frame) = ca_display_link::time_until_next_frame(env, link) {
+                sleep = sleep.min(until_frame);
+            }
+        }
+        std::thread::sleep(env.clock.host_duration(sleep));
+
+        notify_observers(env, run_loop, mode, kCFRunLoopAfterWaiting);
+    };
+
+    notify_observers(env, run_loop, mode, kCFRunLoopExit);
+
+    env.objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .current_mode = outer_mode;
+
+    log_dbg!(
+        "Leaving run loop {:?} in mode {:?} (result {})",
+        run_loop,
+        mode,
+        result
+    );
+
+    result
+}
+
+/// Whether the run loop has anything that could be handled in `mode`. The
+/// common modes always have UI events and audio queues.
+fn has_anything_in_mode(env: &mut Environment, run_loop: id, mode: &str) -> bool {
+    let NSRunLoopHostObject {
+        timers,
+        display_links,
+        streams,
+        sources,
+        common_modes,
+        ..
+    } = env.objc.borrow(run_loop);
+    common_modes.iter().any(|other| other == mode)
+        || timers.any_in_mode(mode, common_modes)
+        || display_links.any_in_mode(mode, common_modes)
+        || streams.any_in_mode(mode, common_modes)
+        || sources.any_in_mode(mode, common_modes)
+}
+
+fn items_in_mode(
+    env: &mut Environment,
+    run_loop: id,
+    mode: &str,
+    get: fn(&NSRunLoopHostObject) -> &Scheduled,
+) -> Vec<id> {
+    let host_object = env.objc.borrow::<NSRunLoopHostObject>(run_loop);
+    get(host_object).in_mode(mode, &host_object.common_modes)
+}
+
+fn notify_observers(env: &mut Environment, run_loop: id, mode: &str, activity: CFRunLoopActivity) {
+    let mut observers = items_in_mode(env, run_loop, mode, |host| &host.observers);
+    if observers.is_empty() {
+        return;
+    }
+    observers.sort_by_key(|&observer| cf_run_loop::observer_order(env, observer));
+    // Observers may be removed and released while others are notified.
+    for &observer in &observers {
+        retain(env, observer);
+    }
+    for &observer in &observers {
+        cf_run_loop::handle_observer(env, observer, activity);
+    }
+    for observer in observers {
+        release(env, observer);
     }
 }
@@ -5,20 +5,34 @@
  */
 //! The `NSSet` class cluster, including `NSMutableSet` and `NSCountedSet`.
 
+use super::ns_array;
 use super::ns_dictionary::DictionaryHostObject;
-use super::ns_fast_enumeration::NSFastEnumerationState;
+use super::ns_fast_enumeration::{fast_enumeration_helper, NSFastEnumerationState};
 use super::NSUInteger;
 use crate::mem::{MutPtr, MutVoidPtr};
 use crate::objc::{
-    autorelease, id, msg, msg_class, nil, objc_classes, retain, ClassExports, HostObject,
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
 };
+use crate::Environment;
+use std::collections::HashMap;
 
-/// Belongs to _touchHLE_NSSet
+/// Belongs to _touchHLE_NSSet and _touchHLE_NSMutableSet
+#[derive(Default)]
 struct SetHostObject {
     dict: DictionaryHostObject,
 }
 impl HostObject for SetHostObject {}
 
+/// Belongs to NSCountedSet
+#[derive(Default)]
+struct CountedSetHostObject {
+    /// Each member is both the key and the value.
+    dict: DictionaryHostObject,
+    /// Keyed by the member stored in `dict`. Counts are never zero.
+    counts: HashMap<id, NSUInteger>,
+}
+impl HostObject for CountedSetHostObject {}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -38,6 +52,12 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg_class![env; _touchHLE_NSSet allocWithZone:zone]
 }
 
++ (id)set {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new init];
+    autorelease(env, new)
+}
+
 + (id)setWithObject:(id)object {
     assert!(object != nil);
     let new: id = msg![env; this alloc];
@@ -45,9 +65,20 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
++ (id)setWithArray:(id)array { // NSArray*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithArray:array];
+    autorelease(env, new)
+}
+
+- (bool)containsObject:(id)object {
+    let member: id = msg![env; this member:object];
+    member != nil
+}
+
 // NSCopying implementation
 - (id)copyWithZone:(MutVoidPtr)_zone {
-    // TODO: override this once we have NSMutableSet!
+    // NSMutableSet overrides this.
     retain(env, this)
 }
 
@@ -64,6 +95,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
+- (id)init {
+    this
+}
+
 - (id)initWithObject:(id)object {
     let null: id = msg_class![env; NSNull null];
 
@@ -75,6 +110,21 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (id)initWithArray:(id)array { // NSArray*
+    let null: id = msg_class![env; NSNull null];
+
+    let mut dict = <DictionaryHostObject as Default>::default();
+    let count: NSUInteger = if array == nil { 0 } else { msg![env; array count] };
+    for i in 0..count {
+        let object: id = msg![env; array objectAtIndex:i];
+        dict.insert(env, object, null, /* copy_key: */ false);
+    }
+
+    env.objc.borrow_mut::<SetHostObject>(this).dict = dict;
+
+    this
+}
+
 - (())dealloc {
     std::mem::take(&mut env.objc.borrow_mut::<SetHostObject>(this).dict).release(env);
     env.objc.dealloc_object(this, &mut env.mem)
@@ -82,48 +132,343 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 // TODO: more init methods, etc
 
-// TODO: accessors
+// TODO: more accessors
+
+- (NSUInteger)count {
+    env.objc.borrow::<SetHostObject>(this).dict.count
+}
+- (id)member:(id)object {
+    // TODO: avoid copying the set of members
+    let members: Vec<id> = env
+        .objc
+        .borrow::<SetHostObject>(this)
+        .dict
+        .iter_keys()
+        .collect();
+    let hash: NSUInteger = msg![env; object hash];
+    for member in members {
+        if member == object {
+            return member;
+        }
+        let member_hash: NSUInteger = msg![env; member hash];
+        if member_hash == hash && msg![env; member isEqualTo:object] {
+            return member;
+        }
+    }
+    nil
+}
+
+- (id)anyObject {
+    let host_object = env.objc.borrow::<SetHostObject>(this);
+    host_object.dict.iter_keys().next().unwrap_or(nil)
+}
+
+- (id)allObjects {
+    let objects: Vec<id> = env
+        .objc
+        .borrow::<SetHostObject>(this)
+        .dict
+        .iter_keys()
+        .collect();
+    for &object in &objects {
+        retain(env, object);
+    }
+    let array = ns_array::from_vec(env, objects);
+    autorelease(env, array)
+}
 
 // NSFastEnumeration implementation
 - (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
                                   objects:(MutPtr<id>)stackbuf
                                     count:(NSUInteger)len {
+    // TODO: avoid copying the set of members on every call
+    let members: Vec<id> = env
+        .objc
+        .borrow::<SetHostObject>(this)
+        .dict
+        .iter_keys()
+        .collect();
+    fast_enumeration_helper(&mut env.mem, this, &members, state, stackbuf, len)
+}
+
+@end
+
+// NSMutableSet is an abstract class. A subclass must provide everything
+// NSSet subclasses provide, plus:
+// - (void)addObject:(id)object;
+// - (void)removeObject:(id)object;
+// Like with NSSet, we always pick _touchHLE_NSMutableSet for now.
+@implementation NSMutableSet: NSSet
+
++ (id)allocWithZone:(MutVoidPtr)zone {
+    // NSMutableSet might be subclassed by something which needs
+    // allocWithZone: to have the normal behaviour. Unimplemented: call
+    // superclass alloc then.
+    assert!(this == env.objc.get_known_class("NSMutableSet", &mut env.mem));
+    msg_class![env; _touchHLE_NSMutableSet allocWithZone:zone]
+}
+
++ (id)setWithCapacity:(NSUInteger)capacity {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCapacity:capacity];
+    autorelease(env, new)
+}
+
+- (())addObjectsFromArray:(id)array { // NSArray*
+    let count: NSUInteger = msg![env; array count];
+    for i in 0..count {
+        let object: id = msg![env; array objectAtIndex:i];
+        let () = msg![env; this addObject:object];
+    }
+}
+- (())unionSet:(id)other { // NSSet*
+    let objects: id = msg![env; other allObjects];
+    let () = msg![env; this addObjectsFromArray:objects];
+}
+- (())minusSet:(id)other { // NSSet*
+    let objects: id = msg![env; other allObjects];
+    let count: NSUInteger = msg![env; objects count];
+    for i in 0..count {
+        let object: id = msg![env; objects objectAtIndex:i];
+        let () = msg![env; this removeObject:object];
+    }
+}
+- (())removeAllObjects {
+    let objects: id = msg![env; this allObjects];
+    let () = msg![env; this minusSet:objects];
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    let objects: id = msg![env; this allObjects];
+    let new: id = msg_class![env; NSSet alloc];
+    msg![env; new initWithArray:objects]
+}
+
+@end
+
+// Our private subclass that is the single implementation of NSMutableSet for
+// the time being.
+@implementation _touchHLE_NSMutableSet: NSMutableSet
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(SetHostObject {
+        dict: Default::default(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)init {
+    this
+}
+- (id)initWithCapacity:(NSUInteger)_capacity {
+    this
+}
+- (id)initWithArray:(id)array { // NSArray*
+    if array != nil {
+        let () = msg![env; this addObjectsFromArray:array];
+    }
+    this
+}
+
+- (())dealloc {
+    std::mem::take(&mut env.objc.borrow_mut::<SetHostObject>(this).dict).release(env);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (NSUInteger)count {
+    env.objc.borrow::<SetHostObject>(this).dict.count
+}
+- (id)member:(id)object {
+    // TODO: avoid copying the set of members
+    let members: Vec<id> = env
+        .objc
+        .borrow::<SetHostObject>(this)
+        .dict
+        .iter_keys()
+        .collect();
+    let hash: NSUInteger = msg![env; object hash];
+    for member in members {
+        if member == object {
+            return member;
+        }
+        let member_hash: NSUInteger = msg![env; member hash];
+        if member_hash == hash && msg![env; member isEqualTo:object] {
+            return member;
+        }
+    }
+    nil
+}
+
+- (id)anyObject {
     let host_object = env.objc.borrow::<SetHostObject>(this);
+    host_object.dict.iter_keys().next().unwrap_or(nil)
+}
+
+- (id)allObjects {
+    let objects: Vec<id> = env
+        .objc
+        .borrow::<SetHostObject>(this)
+        .dict
+        .iter_keys()
+        .collect();
+    for &object in &objects {
+        retain(env, object);
+    }
+    let array = ns_array::from_vec(env, objects);
+    autorelease(env, array)
+}
+
+- (())addObject:(id)object {
+    assert!(object != nil); // TODO: raise proper exception
+    let null: id = msg_class![env; NSNull null];
+    let mut host_object: SetHostObject = std::mem::take(env.objc.borrow_mut(this));
+    if host_object.dict.lookup(env, object) == nil {
+        host_object.dict.insert(env, object, null, /* copy_key: */ false);
+    }
+    *env.objc.borrow_mut(this) = host_object;
+}
+- (())removeObject:(id)object {
+    let mut host_object: SetHostObject = std::mem::take(env.objc.borrow_mut(this));
+    if let Some((key, value)) = host_object.dict.remove(env, object) {
+        release(env, key);
+        release(env, value);
+    }
+    *env.objc.borrow_mut(this) = host_object;
+}
+
+// NSFastEnumeration implementation
+- (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
+                                  objects:(MutPtr<id>)stackbuf
+                                    count:(NSUInteger)len {
+    // TODO: avoid copying the set of members on every call
+    let members: Vec<id> = env
+        .objc
+        .borrow::<SetHostObject>(this)
+        .dict
+        .iter_keys()
+        .collect();
+    fast_enumeration_helper(&mut env.mem, this, &members, state, stackbuf, len)
+}
+
+@end
+
+@implementation NSCountedSet: NSMutableSet
 
-    if host_object.dict.count == 0 {
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::<CountedSetHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithCapacity:(NSUInteger)_capacity {
+    this
+}
+- (id)initWithArray:(id)array { // NSArray*
+    let count: NSUInteger = msg![env; array count];
+    for i in 0..count {
+        let object: id = msg![env; array objectAtIndex:i];
+        let () = msg![env; this addObject:object];
+    }
+    this
+}
+
+- (())dealloc {
+    let mut host_object: CountedSetHostObject = std::mem::take(env.objc.borrow_mut(this));
+    host_object.dict.release(env);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (NSUInteger)count {
+    env.objc.borrow::<CountedSetHostObject>(this).dict.count
+}
+- (id)member:(id)object {
+    let host_object: CountedSetHostObject = std::mem::take(env.objc.borrow_mut(this));
+    let member = host_object.dict.lookup(env, object);
+    *env.objc.borrow_mut(this) = host_object;
+    member
+}
+- (bool)containsObject:(id)object {
+    let member: id = msg![env; this member:object];
+    member != nil
+}
+- (NSUInteger)countForObject:(id)object {
+    let member: id = msg![env; this member:object];
+    if member == nil {
         return 0;
     }
+    env.objc.borrow::<CountedSetHostObject>(this).counts[&member]
+}
 
-    // TODO: handle size > 1
-    assert!(host_object.dict.count == 1);
-    assert!(len >= host_object.dict.count);
-
-    let NSFastEnumerationState {
-        state: is_first_round,
-        ..
-    } = env.mem.read(state);
-
-    match is_first_round {
-        0 => {
-            let object = host_object.dict.iter_keys().next().unwrap();
-            env.mem.write(stackbuf, object);
-            env.mem.write(state, NSFastEnumerationState {
-                state: 1,
-                items_ptr: stackbuf,
-                // can be anything as long as it's dereferenceable and the same
-                // each iteration
-                mutations_ptr: stackbuf.cast(),
-                extra: Default::default(),
-            });
-            1 // returned object count
-        },
-        1 => {
-            0 // end of iteration
-        },
-        _ => panic!(), // app failed to initialize the buffer?
+- (())addObject:(id)object {
+    assert!(object != nil); // TODO: raise proper exception
+    let mut host_object: CountedSetHostObject = std::mem::take(env.objc.borrow_mut(this));
+    let mut member = host_object.dict.lookup(env, object);
+    if member == nil {
+        host_object.dict.insert(env, object, object, /* copy_key: */ false);
+        member = object;
+    }
+    *host_object.counts.entry(member).or_insert(0) += 1;
+    *env.objc.borrow_mut(this) = host_object;
+}
+- (())removeObject:(id)object {
+    let mut host_object: CountedSetHostObject = std::mem::take(env.objc.borrow_mut(this));
+    let member = host_object.dict.lookup(env, object);
+    if member != nil {
+        let count = host_object.counts.get_mut(&member).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            host_object.counts.remove(&member);
+            let (key, value) = host_object.dict.remove(env, member).unwrap();
+            release(env, key);
+            release(env, value);
+        }
     }
+    *env.objc.borrow_mut(this) = host_object;
+}
+
+- (id)allObjects {
+    let objects: Vec<id> = env
+        .objc
+        .borrow::<CountedSetHostObject>(this)
+        .dict
+        .iter_keys()
+        .collect();
+    for &object in &objects {
+        retain(env, object);
+    }
+    let array = ns_array::from_vec(env, objects);
+    autorelease(env, array)
+}
+
+// NSFastEnumeration implementation
+- (NSUInteger)countByEnumeratingWithState:(MutPtr<NSFastEnumerationState>)state
+                                  objects:(MutPtr<id>)stackbuf
+                                    count:(NSUInteger)len {
+    // TODO: avoid copying the set of members on every call
+    let members: Vec<id> = env
+        .objc
+        .borrow::<CountedSetHostObject>(this)
+        .dict
+        .iter_keys()
+        .collect();
+    fast_enumeration_helper(&mut env.mem, this, &members, state, stackbuf, len)
 }
 
 @end
 
 };
+
+/// Shortcut for host code, roughly equivalent to
+/// `[[NSSet alloc] initWithObjects:count]`.
+/// The elements should already be "retained by" the `Vec`.
+pub fn from_vec(env: &mut Environment, objects: Vec<id>) -> id {
+    let null: id = msg_class![env; NSNull null];
+    let mut dict = <DictionaryHostObject as Default>::default();
+    for object in objects {
+        dict.insert(env, object, null, /* copy_key: */ false);
+        release(env, object);
+    }
+    let set: id = msg_class![env; NSSet alloc];
+    env.objc.borrow_mut::<SetHostObject>(set).dict = dict;
+    set
+}
@@ -0,0 +1,166 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSSortDescriptor`.
+
+use super::{
+    NSComparisonResult, NSOrderedAscending, NSOrderedDescending, NSOrderedSame, NSUInteger,
+};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_send, nil, objc_classes, release, retain, Class, ClassExports,
+    HostObject, SEL,
+};
+use crate::Environment;
+use std::cmp::Ordering;
+
+struct NSSortDescriptorHostObject {
+    /// `NSString*`, or [nil] to compare the objects themselves.
+    key: id,
+    ascending: bool,
+    selector: SEL,
+}
+impl HostObject for NSSortDescriptorHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSSortDescriptor: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let selector = env.objc.lookup_selector("compare:").unwrap();
+    let host_object = Box::new(NSSortDescriptorHostObject {
+        key: nil,
+        ascending: true,
+        selector,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)sortDescriptorWithKey:(id)key // NSString*
+                  ascending:(bool)ascending {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithKey:key ascending:ascending];
+    autorelease(env, new)
+}
++ (id)sortDescriptorWithKey:(id)key // NSString*
+                  ascending:(bool)ascending
+                   selector:(SEL)selector {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithKey:key ascending:ascending selector:selector];
+    autorelease(env, new)
+}
+
+- (id)initWithKey:(id)key // NSString*
+        ascending:(bool)ascending {
+    let selector = env.objc.lookup_selector("compare:").unwrap();
+    msg![env; this initWithKey:key ascending:ascending selector:selector]
+}
+- (id)initWithKey:(id)key // NSString*
+        ascending:(bool)ascending
+         selector:(SEL)selector {
+    let key: id = if key == nil { nil } else { msg![env; key copy] };
+    let host_object = env.objc.borrow_mut::<NSSortDescriptorHostObject>(this);
+    let old_key = std::mem::replace(&mut host_object.key, key);
+    host_object.ascending = ascending;
+    host_object.selector = selector;
+    release(env, old_key);
+    this
+}
+
+- (())dealloc {
+    let key = env.objc.borrow::<NSSortDescriptorHostObject>(this).key;
+    release(env, key);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+- (id)key {
+    env.objc.borrow::<NSSortDescriptorHostObject>(this).key
+}
+- (bool)ascending {
+    env.objc.borrow::<NSSortDescriptorHostObject>(this).ascending
+}
+- (SEL)selector {
+    env.objc.borrow::<NSSortDescriptorHostObject>(this).selector
+}
+
+- (id)reversedSortDescriptor {
+    let &NSSortDescriptorHostObject {
+        key,
+        ascending,
+        selector,
+    } = env.objc.borrow(this);
+    let class: Class = msg![env; this class];
+    let new: id = msg![env; class alloc];
+    let new: id = msg![env; new initWithKey:key ascending:(!ascending) selector:selector];
+    autorelease(env, new)
+}
+
+- (NSComparisonResult)compareObject:(id)a toObject:(id)b {
+    match compare(env, this, a, b) {
+        Ordering::Less => NSOrderedAscending,
+        Ordering::Equal => NSOrderedSame,
+        Ordering::Greater => NSOrderedDescending,
+    }
+}
+
+@end
+
+};
+
+/// Compare two objects as a sort descriptor would.
+pub fn compare(env: &mut Environment, descriptor: id, a: id, b: id) -> Ordering {
+    let &NSSortDescriptorHostObject {
+        key,
+        ascending,
+        selector,
+    } = env.objc.borrow(descriptor);
+    let (a, b) = if key == nil {
+        (a, b)
+    } else {
+        (
+            msg![env; a valueForKeyPath:key],
+            msg![env; b valueForKeyPath:key],
+        )
+    };
+    // nil sorts before everything else.
+    let ordering = match (a == nil, b == nil) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => {
+            let result: NSComparisonResult = msg_send(env, (a, selector, b));
+            result.cmp(&NSOrderedSame)
+        }
+    };
+    if ascending {
+        ordering
+    } else {
+        ordering.reverse()
+    }
+}
+
+/// Compare two objects using an `NSArray` of sort descriptors, in order.
+pub fn compare_with_descriptors(env: &mut Environment, descriptors: id, a: id, b: id) -> Ordering {
+    let count: NSUInteger = if descriptors == nil {
+        0
+    } else {
+        msg![env; descriptors count]
+    };
+    for i in 0..count {
+        let descriptor: id = msg![env; descriptors objectAtIndex:i];
+        let ordering = compare(env, descriptor, a, b);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
@@ -0,0 +1,608 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSStream`, `NSInputStream` and `NSOutputStream`.
+//!
+//! Only file-backed and memory-backed streams are supported, plus HTTP request
+//! streams (see [crate::frameworks::cf_network::cf_http_stream]). Since
+//! reading and writing never block here, run loop scheduling just means that
+//! the delegate is sent events from [super::ns_run_loop]'s loop.
+//!
+//! Resources:
+//! - Apple's [Stream Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/Streams/Streams.html)
+
+use super::{ns_run_loop, ns_string, NSInteger, NSUInteger};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::cf_network::cf_http_stream::{self, HttpConnection};
+use crate::frameworks::cf_network::cf_socket_stream;
+use crate::fs::{GuestOpenOptions, GuestPath};
+use crate::mem::{ConstPtr, MutPtr, MutVoidPtr, Ptr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::io::{Read, Seek, Write};
+
+pub type NSStreamStatus = NSUInteger;
+pub const NSStreamStatusNotOpen: NSStreamStatus = 0;
+#[allow(dead_code)]
+pub const NSStreamStatusOpening: NSStreamStatus = 1;
+pub const NSStreamStatusOpen: NSStreamStatus = 2;
+pub const NSStreamStatusReading: NSStreamStatus = 3;
+pub const NSStreamStatusWriting: NSStreamStatus = 4;
+pub const NSStreamStatusAtEnd: NSStreamStatus = 5;
+pub const NSStreamStatusClosed: NSStreamStatus = 6;
+pub const NSStreamStatusError: NSStreamStatus = 7;
+
+pub type NSStreamEvent = NSUInteger;
+pub const NSStreamEventNone: NSStreamEvent = 0;
+pub const NSStreamEventOpenCompleted: NSStreamEvent = 1 << 0;
+pub const NSStreamEventHasBytesAvailable: NSStreamEvent = 1 << 1;
+pub const NSStreamEventHasSpaceAvailable: NSStreamEvent = 1 << 2;
+pub const NSStreamEventErrorOccurred: NSStreamEvent = 1 << 3;
+pub const NSStreamEventEndEncountered: NSStreamEvent = 1 << 4;
+
+pub const NSStreamDataWrittenToMemoryStreamKey: &str = "kCFStreamPropertyDataWritten";
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_NSStreamDataWrittenToMemoryStreamKey",
+    HostConstant::NSString(NSStreamDataWrittenToMemoryStreamKey),
+)];
+
+enum StreamBacking {
+    /// The file is only opened when the stream is opened.
+    File {
+        path: String,
+        append: bool,
+        file: Option<std::fs::File>,
+    },
+    /// Input streams read from a copy of an `NSData`'s bytes, output streams
+    /// to memory append to the `Vec`.
+    Memory { bytes: Vec<u8>, position: usize },
+    /// Input streams only. [None] if the request couldn't be made, in which
+    /// case opening the stream fails.
+    Http(Option<Box<HttpConnection>>),
+}
+
+/// A Core Foundation stream client, set with e.g. `CFReadStreamSetClient`.
+/// This is used instead of the delegate when present.
+#[derive(Copy, Clone)]
+pub struct StreamClient {
+    /// Bitmask of events the client wants to receive.
+    pub events: NSStreamEvent,
+    /// `void (*)(CFReadStreamRef, CFStreamEventType, void*)` or the
+    /// `CFWriteStream` equivalent.
+    pub callback: GuestFunction,
+    pub info: MutVoidPtr,
+}
+
+struct NSStreamHostObject {
+    is_input: bool,
+    backing: StreamBacking,
+    status: NSStreamStatus,
+    /// Weak reference.
+    delegate: id,
+    client: Option<StreamClient>,
+    /// Weak reference. The stream removes itself when it is closed or
+    /// destroyed.
+    run_loop: id,
+    open_event_sent: bool,
+    /// Set when the stream is opened and after each read or write, so the
+    /// delegate is told once more that it can read or write.
+    io_event_pending: bool,
+    end_event_sent: bool,
+}
+impl HostObject for NSStreamHostObject {}
+
+impl NSStreamHostObject {
+    fn new(is_input: bool, backing: StreamBacking) -> Self {
+        NSStreamHostObject {
+            is_input,
+            backing,
+            status: NSStreamStatusNotOpen,
+            delegate: nil,
+            client: None,
+            run_loop: nil,
+            open_event_sent: false,
+            io_event_pending: false,
+            end_event_sent: false,
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        matches!(
+            self.status,
+            NSStreamStatusOpen | NSStreamStatusReading | NSStreamStatusWriting
+        )
+    }
+
+    /// Number of bytes an input stream has left.
+    fn bytes_remaining(&mut self) -> u64 {
+        assert!(self.is_input);
+        match self.backing {
+            StreamBacking::File { ref mut file, .. } => {
+                let Some(file) = file else {
+                    return 0;
+                };
+                let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+                let position = file.stream_position().unwrap_or(len);
+                len.saturating_sub(position)
+            }
+            StreamBacking::Memory {
+                ref bytes,
+                position,
+            } => (bytes.len() - position) as u64,
+            StreamBacking::Http(ref mut connection) => {
+                connection.as_mut().map_or(0, |c| c.poll() as u64)
+            }
+        }
+    }
+
+    /// Whether an input stream has nothing left to read and never will.
+    fn is_exhausted(&mut self) -> bool {
+        match self.backing {
+            StreamBacking::Http(ref mut connection) => {
+                let Some(connection) = connection else {
+                    return true;
+                };
+                connection.poll();
+                connection.is_finished()
+            }
+            _ => self.bytes_remaining() == 0,
+        }
+    }
+
+    fn has_failed(&self) -> bool {
+        matches!(self.backing, StreamBacking::Http(Some(ref connection)) if connection.has_failed())
+    }
+
+    /// Work out which event, if any, should be sent next.
+    fn next_event(&mut self) -> NSStreamEvent {
+        if self.status == NSStreamStatusAtEnd || self.status == NSStreamStatusError {
+            if self.end_event_sent {
+                return NSStreamEventNone;
+            }
+            self.end_event_sent = true;
+            return if self.status == NSStreamStatusAtEnd {
+                NSStreamEventEndEncountered
+            } else {
+                NSStreamEventErrorOccurred
+            };
+        }
+        if !self.is_open() {
+            return NSStreamEventNone;
+        }
+        if !self.open_event_sent {
+            self.open_event_sent = true;
+            return NSStreamEventOpenCompleted;
+        }
+        if !self.io_event_pending {
+            return NSStreamEventNone;
+        }
+        let event = if !self.is_input {
+            NSStreamEventHasSpaceAvailable
+        } else if self.bytes_remaining() > 0 {
+            NSStreamEventHasBytesAvailable
+        } else if self.has_failed() {
+            self.status = NSStreamStatusError;
+            self.end_event_sent = true;
+            NSStreamEventErrorOccurred
+        } else if self.is_exhausted() {
+            self.status = NSStreamStatusAtEnd;
+            self.end_event_sent = true;
+            NSStreamEventEndEncountered
+        } else {
+            // Waiting for more bytes to arrive, so try again later.
+            return NSStreamEventNone;
+        };
+        self.io_event_pending = false;
+        event
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// Abstract class. The subclasses share a host object type.
+@implementation NSStream: NSObject
+
+- (())dealloc {
+    let run_loop = env.objc.borrow::<NSStreamHostObject>(this).run_loop;
+    if run_loop != nil {
+        ns_run_loop::remove_stream(env, run_loop, this, nil);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)delegate {
+    env.objc.borrow::<NSStreamHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate {
+    env.objc.borrow_mut::<NSStreamHostObject>(this).delegate = delegate;
+}
+
+- (NSStreamStatus)streamStatus {
+    env.objc.borrow::<NSStreamHostObject>(this).status
+}
+- (id)streamError {
+    // TODO: NSError
+    nil
+}
+
+- (())open {
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(this);
+    if host_object.status != NSStreamStatusNotOpen {
+        log!("Warning: stream {:?} was already opened", this);
+        return;
+    }
+    let is_input = host_object.is_input;
+    if let StreamBacking::Http(ref mut connection) = host_object.backing {
+        let result = connection.as_mut().map_or(Err(()), |c| c.open());
+        match result {
+            Ok(()) => {
+                host_object.status = NSStreamStatusOpen;
+                host_object.io_event_pending = true;
+            }
+            Err(()) => {
+                log!("Warning: stream {:?} couldn't make its HTTP request", this);
+                host_object.status = NSStreamStatusError;
+            }
+        }
+        return;
+    }
+    let StreamBacking::File { ref path, append, .. } = host_object.backing else {
+        host_object.status = NSStreamStatusOpen;
+        host_object.io_event_pending = true;
+        return;
+    };
+    let path = path.clone();
+
+    let result = if is_input {
+        env.fs.open(GuestPath::new(&path))
+    } else {
+        let mut options = GuestOpenOptions::new();
+        options.write().create();
+        if append {
+            options.append();
+        } else {
+            options.truncate();
+        }
+        env.fs.open_with_options(GuestPath::new(&path), options)
+    };
+
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(this);
+    match result {
+        Ok(new_file) => {
+            let StreamBacking::File { ref mut file, .. } = host_object.backing else {
+                unreachable!();
+            };
+            *file = Some(new_file);
+            host_object.status = NSStreamStatusOpen;
+            host_object.io_event_pending = true;
+        }
+        Err(()) => {
+            log!("Warning: stream {:?} couldn't open {:?}", this, path);
+            host_object.status = NSStreamStatusError;
+        }
+    }
+}
+
+- (())close {
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(this);
+    match host_object.backing {
+        StreamBacking::File { ref mut file, .. } => *file = None,
+        StreamBacking::Http(ref mut connection) => *connection = None,
+        StreamBacking::Memory { .. } => (),
+    }
+    host_object.status = NSStreamStatusClosed;
+    let run_loop = std::mem::replace(&mut host_object.run_loop, nil);
+    if run_loop != nil {
+        ns_run_loop::remove_stream(env, run_loop, this, nil);
+    }
+}
+
+- (())scheduleInRunLoop:(id)run_loop // NSRunLoop*
+                forMode:(id)mode { // NSRunLoopMode
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(this);
+    assert!(host_object.run_loop == nil || host_object.run_loop == run_loop); // TODO: multiple run loops
+    host_object.run_loop = run_loop;
+    ns_run_loop::add_stream(env, run_loop, this, mode);
+}
+- (())removeFromRunLoop:(id)run_loop // NSRunLoop*
+                forMode:(id)mode { // NSRunLoopMode
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(this);
+    if host_object.run_loop != run_loop {
+        return;
+    }
+    if ns_run_loop::remove_stream(env, run_loop, this, mode) {
+        env.objc.borrow_mut::<NSStreamHostObject>(this).run_loop = nil;
+    }
+}
+
+- (id)propertyForKey:(id)key { // NSString*
+    if let StreamBacking::Http(ref connection) = env.objc.borrow::<NSStreamHostObject>(this).backing {
+        let response_head = connection.as_ref().and_then(|c| c.response_head());
+        let key = ns_string::to_rust_string(env, key);
+        if let Some(property) = cf_http_stream::property_for_key(env, response_head, &key) {
+            return property;
+        }
+    }
+
+    let data_key = ns_string::get_static_str(env, NSStreamDataWrittenToMemoryStreamKey);
+    if !msg![env; key isEqualToString:data_key] {
+        log!("TODO: [{:?} propertyForKey:{:?}]", this, key);
+        return nil;
+    }
+    let host_object = env.objc.borrow::<NSStreamHostObject>(this);
+    let StreamBacking::Memory { ref bytes, .. } = host_object.backing else {
+        return nil;
+    };
+    if host_object.is_input {
+        return nil;
+    }
+    let bytes = bytes.clone();
+    let len: NSUInteger = bytes.len().try_into().unwrap();
+    let ptr: MutVoidPtr = if len == 0 {
+        Ptr::null()
+    } else {
+        let ptr = env.mem.alloc(len);
+        env.mem.bytes_at_mut(ptr.cast(), len).copy_from_slice(&bytes);
+        ptr
+    };
+    msg_class![env; NSData dataWithBytesNoCopy:ptr length:len]
+}
+- (bool)setProperty:(id)property forKey:(id)key {
+    if let StreamBacking::Http(Some(ref connection)) = env.objc.borrow::<NSStreamHostObject>(this).backing {
+        let mut settings = connection.tls_settings().clone();
+        let key = ns_string::to_rust_string(env, key);
+        if cf_socket_stream::apply_property(env, &mut settings, &key, property) {
+            let host_object = env.objc.borrow_mut::<NSStreamHostObject>(this);
+            let StreamBacking::Http(Some(ref mut connection)) = host_object.backing else {
+                unreachable!();
+            };
+            connection.set_tls_settings(settings);
+            return true;
+        }
+    }
+
+    log!("TODO: [{:?} setProperty:forKey:{:?}]", this, key);
+    false
+}
+
+@end
+
+@implementation NSInputStream: NSStream
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSStreamHostObject::new(
+        true,
+        StreamBacking::Memory {
+            bytes: Vec::new(),
+            position: 0,
+        },
+    ));
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)inputStreamWithFileAtPath:(id)path { // NSString*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithFileAtPath:path];
+    autorelease(env, new)
+}
++ (id)inputStreamWithData:(id)data { // NSData*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithData:data];
+    autorelease(env, new)
+}
+
+- (id)initWithFileAtPath:(id)path { // NSString*
+    if path == nil {
+        release(env, this);
+        return nil;
+    }
+    let path = ns_string::to_rust_string(env, path).into_owned();
+    env.objc.borrow_mut::<NSStreamHostObject>(this).backing = StreamBacking::File {
+        path,
+        append: false,
+        file: None,
+    };
+    this
+}
+- (id)initWithData:(id)data { // NSData*
+    let bytes: ConstPtr<u8> = msg![env; data bytes];
+    let length: NSUInteger = msg![env; data length];
+    let bytes = if length == 0 {
+        Vec::new()
+    } else {
+        env.mem.bytes_at(bytes, length).to_vec()
+    };
+    env.objc.borrow_mut::<NSStreamHostObject>(this).backing = StreamBacking::Memory {
+        bytes,
+        position: 0,
+    };
+    this
+}
+
+- (NSInteger)read:(MutPtr<u8>)buffer maxLength:(NSUInteger)max_length {
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(this);
+    if host_object.status == NSStreamStatusAtEnd {
+        return 0;
+    }
+    if !host_object.is_open() {
+        return -1;
+    }
+    host_object.io_event_pending = true;
+    let buffer = env.mem.bytes_at_mut(buffer, max_length);
+    let bytes_read = match host_object.backing {
+        StreamBacking::File { ref mut file, .. } => {
+            match file.as_mut().unwrap().read(buffer) {
+                Ok(bytes_read) => bytes_read,
+                Err(_) => {
+                    host_object.status = NSStreamStatusError;
+                    return -1;
+                }
+            }
+        }
+        StreamBacking::Memory {
+            ref bytes,
+            ref mut position,
+        } => {
+            let bytes_read = buffer.len().min(bytes.len() - *position);
+            buffer[..bytes_read].copy_from_slice(&bytes[*position..][..bytes_read]);
+            *position += bytes_read;
+            bytes_read
+        }
+        StreamBacking::Http(ref mut connection) => connection.as_mut().unwrap().read(buffer),
+    };
+    if host_object.has_failed() {
+        host_object.status = NSStreamStatusError;
+        return -1;
+    }
+    if host_object.is_exhausted() {
+        host_object.status = NSStreamStatusAtEnd;
+    }
+    bytes_read.try_into().unwrap()
+}
+
+- (bool)getBuffer:(MutPtr<MutPtr<u8>>)_buffer length:(MutPtr<NSUInteger>)_length {
+    // Apple's streams don't necessarily support this either.
+    false
+}
+
+- (bool)hasBytesAvailable {
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(this);
+    host_object.is_open() && host_object.bytes_remaining() > 0
+}
+
+@end
+
+@implementation NSOutputStream: NSStream
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSStreamHostObject::new(
+        false,
+        StreamBacking::Memory {
+            bytes: Vec::new(),
+            position: 0,
+        },
+    ));
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)outputStreamToMemory {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initToMemory];
+    autorelease(env, new)
+}
++ (id)outputStreamToFileAtPath:(id)path // NSString*
+                        append:(bool)append {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initToFileAtPath:path append:append];
+    autorelease(env, new)
+}
+
+- (id)initToMemory {
+    this
+}
+- (id)initToFileAtPath:(id)path // NSString*
+                append:(bool)append {
+    if path == nil {
+        release(env, this);
+        return nil;
+    }
+    let path = ns_string::to_rust_string(env, path).into_owned();
+    env.objc.borrow_mut::<NSStreamHostObject>(this).backing = StreamBacking::File {
+        path,
+        append,
+        file: None,
+    };
+    this
+}
+
+- (NSInteger)write:(ConstPtr<u8>)buffer maxLength:(NSUInteger)length {
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(this);
+    if !host_object.is_open() {
+        return -1;
+    }
+    host_object.io_event_pending = true;
+    let buffer = env.mem.bytes_at(buffer, length);
+    match host_object.backing {
+        StreamBacking::File { ref mut file, .. } => {
+            match file.as_mut().unwrap().write(buffer) {
+                Ok(bytes_written) => bytes_written.try_into().unwrap(),
+                Err(_) => {
+                    host_object.status = NSStreamStatusError;
+                    -1
+                }
+            }
+        }
+        StreamBacking::Memory { ref mut bytes, .. } => {
+            bytes.extend_from_slice(buffer);
+            length.try_into().unwrap()
+        }
+        StreamBacking::Http(_) => unreachable!(),
+    }
+}
+
+- (bool)hasSpaceAvailable {
+    env.objc.borrow::<NSStreamHostObject>(this).is_open()
+}
+
+@end
+
+};
+
+/// For use by Core Foundation's `CFReadStreamSetClient` etc. Replaces any
+/// existing client.
+pub fn set_client(env: &mut Environment, stream: id, client: Option<StreamClient>) {
+    env.objc.borrow_mut::<NSStreamHostObject>(stream).client = client;
+}
+
+/// For use by `CFReadStreamCreateWithBytesNoCopy`. Returns a new input stream
+/// (+1 reference) that reads from a copy of `bytes`.
+pub fn input_stream_with_bytes(env: &mut Environment, bytes: Vec<u8>) -> id {
+    let new: id = msg_class![env; NSInputStream alloc];
+    env.objc.borrow_mut::<NSStreamHostObject>(new).backing =
+        StreamBacking::Memory { bytes, position: 0 };
+    new
+}
+
+/// For use by `CFReadStreamCreateForHTTPRequest`. Returns a new input stream
+/// (+1 reference) for an HTTP request.
+pub fn input_stream_for_http(env: &mut Environment, connection: Option<HttpConnection>) -> id {
+    let new: id = msg_class![env; NSInputStream alloc];
+    env.objc.borrow_mut::<NSStreamHostObject>(new).backing =
+        StreamBacking::Http(connection.map(Box::new));
+    new
+}
+
+/// For use by `NSRunLoop`: send the next event, if any, to the stream's client
+/// or delegate.
+pub(super) fn handle_stream(env: &mut Environment, stream: id) {
+    let host_object = env.objc.borrow_mut::<NSStreamHostObject>(stream);
+    let event = host_object.next_event();
+    if event == NSStreamEventNone {
+        return;
+    }
+    let client = host_object.client;
+    let delegate = host_object.delegate;
+
+    log_dbg!("Stream {:?} has event {:#x}", stream, event);
+
+    if let Some(StreamClient {
+        events,
+        callback,
+        info,
+    }) = client
+    {
+        if events & event != 0 {
+            let () = callback.call_from_host(env, (stream, event, info));
+        }
+    } else if delegate != nil {
+        let () = msg![env; delegate stream:stream handleEvent:event];
+    }
+}
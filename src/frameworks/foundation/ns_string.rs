@@ -5,26 +5,42 @@
  */
 //! The `NSString` class cluster, including `NSMutableString`.
 
+mod encodings;
+pub mod normalization;
+pub mod transliteration;
+
 use super::ns_array;
-use super::NSUInteger;
-use crate::frameworks::core_graphics::{CGRect, CGSize};
+use super::{NSComparisonResult, NSUInteger};
+use super::{NSOrderedAscending, NSOrderedDescending, NSOrderedSame};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
 use crate::frameworks::uikit::ui_font::{
-    self, UILineBreakMode, UILineBreakModeWordWrap, UITextAlignment, UITextAlignmentLeft,
+    self, UIBaselineAdjustment, UIBaselineAdjustmentNone, UILineBreakMode, UILineBreakModeWordWrap,
+    UITextAlignment, UITextAlignmentLeft,
 };
 use crate::fs::GuestPath;
-use crate::mem::{ConstPtr, Mem, MutPtr, MutVoidPtr, SafeRead};
+use crate::mem::{ConstPtr, Mem, MutPtr, MutVoidPtr, Ptr, SafeRead};
 use crate::objc::{
-    autorelease, id, msg, msg_class, objc_classes, retain, Class, ClassExports, HostObject, ObjC,
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, Class, ClassExports,
+    HostObject, ObjC,
 };
 use crate::Environment;
+use normalization::{normalize, NormalizationForm};
 use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::string::FromUtf16Error;
 
 pub type NSStringEncoding = NSUInteger;
+pub const NSASCIIStringEncoding: NSUInteger = 1;
 pub const NSUTF8StringEncoding: NSUInteger = 4;
+pub const NSISOLatin1StringEncoding: NSUInteger = 5;
+pub const NSShiftJISStringEncoding: NSUInteger = 8;
 pub const NSUnicodeStringEncoding: NSUInteger = 10;
+pub const NSWindowsCP1252StringEncoding: NSUInteger = 12;
+pub const NSMacOSRomanStringEncoding: NSUInteger = 30;
 pub const NSUTF16StringEncoding: NSUInteger = NSUnicodeStringEncoding;
+pub const NSUTF16BigEndianStringEncoding: NSUInteger = 0x90000100;
+pub const NSUTF16LittleEndianStringEncoding: NSUInteger = 0x94000100;
 
 #[derive(Default)]
 pub struct State {
@@ -57,31 +73,36 @@ enum StringHostObject {
 }
 impl HostObject for StringHostObject {}
 impl StringHostObject {
-    fn decode(bytes: Cow<[u8]>, encoding: NSStringEncoding) -> StringHostObject {
+    /// Decode bytes in some encoding. Returns [None] if the bytes are not valid
+    /// in that encoding.
+    fn decode(bytes: Cow<[u8]>, encoding: NSStringEncoding) -> Option<StringHostObject> {
         if bytes.len() == 0 {
-            return StringHostObject::Utf8(Cow::Borrowed(""));
+            return Some(StringHostObject::Utf8(Cow::Borrowed("")));
         }
 
-        // TODO: error handling
-
         match encoding {
             NSUTF8StringEncoding => {
-                let string = String::from_utf8(bytes.into_owned()).unwrap();
-                StringHostObject::Utf8(Cow::Owned(string))
+                let string = String::from_utf8(bytes.into_owned()).ok()?;
+                Some(StringHostObject::Utf8(Cow::Owned(string)))
             }
-            NSUTF16StringEncoding => {
-                assert!(bytes.len() % 2 == 0);
-
-                let is_big_endian = match &bytes[0..2] {
-                    [0xFE, 0xFF] => true,
-                    [0xFF, 0xFE] => false,
-                    // TODO: what does NSUnicodeStringEncoding mean if no BOM is
-                    // present?
-                    _ => unimplemented!("Default endianness"),
+            NSUTF16StringEncoding
+            | NSUTF16BigEndianStringEncoding
+            | NSUTF16LittleEndianStringEncoding => {
+                if bytes.len() % 2 != 0 {
+                    return None;
+                }
+
+                // Only NSUTF16StringEncoding looks at the BOM, which is then
+                // stripped. Without a BOM, it's big-endian.
+                let (is_big_endian, bytes) = match (encoding, &bytes[0..2]) {
+                    (NSUTF16BigEndianStringEncoding, _) => (true, &bytes[..]),
+                    (NSUTF16LittleEndianStringEncoding, _) => (false, &bytes[..]),
+                    (_, [0xFE, 0xFF]) => (true, &bytes[2..]),
+                    (_, [0xFF, 0xFE]) => (false, &bytes[2..]),
+                    _ => (true, &bytes[..]),
                 };
-                // TODO: Should the BOM be stripped? Always/sometimes/never?
 
-                StringHostObject::Utf16(if is_big_endian {
+                Some(StringHostObject::Utf16(if is_big_endian {
                     bytes
                         .chunks(2)
                         .map(|chunk| u16::from_be_bytes(chunk.try_into().unwrap()))
@@ -91,9 +112,62 @@ impl StringHostObject {
                         .chunks(2)
                         .map(|chunk| u16::from_le_bytes(chunk.try_into().unwrap()))
                         .collect()
-                })
+                }))
+            }
+            _ if encodings::is_legacy_encoding(encoding) => {
+                let string = encodings::decode(&bytes, encoding)?;
+                Some(StringHostObject::Utf8(Cow::Owned(string)))
             }
-            _ => panic!("Unimplemented encoding: {}", encoding),
+            _ => panic!("Unimplemented encoding: {:#x}", encoding),
+        }
+    }
+    /// Encode the string in some encoding. If `lossy` is [false], returns
+    /// [None] if there are characters that can't be represented, otherwise
+    /// those characters are replaced with `?` (or U+FFFD for UTF-8).
+    ///
+    /// No null terminator is added. For NSUTF16StringEncoding, a BOM is added
+    /// and the byte order is little-endian, like on a real device.
+    fn encode(&self, encoding: NSStringEncoding, lossy: bool) -> Option<Vec<u8>> {
+        match encoding {
+            NSUTF8StringEncoding => {
+                let string = match self {
+                    StringHostObject::Utf8(utf8) => utf8.clone(),
+                    StringHostObject::Utf16(utf16) if lossy => {
+                        Cow::Owned(String::from_utf16_lossy(utf16))
+                    }
+                    StringHostObject::Utf16(utf16) => Cow::Owned(String::from_utf16(utf16).ok()?),
+                };
+                Some(string.into_owned().into_bytes())
+            }
+            NSUTF16StringEncoding | NSUTF16LittleEndianStringEncoding => {
+                let bom: &[u8] = if encoding == NSUTF16StringEncoding {
+                    &[0xFF, 0xFE]
+                } else {
+                    &[]
+                };
+                let mut bytes = bom.to_vec();
+                self.iter_code_units()
+                    .for_each(|c| bytes.extend_from_slice(&c.to_le_bytes()));
+                Some(bytes)
+            }
+            NSUTF16BigEndianStringEncoding => Some(
+                self.iter_code_units()
+                    .flat_map(|c| c.to_be_bytes())
+                    .collect(),
+            ),
+            _ if encodings::is_legacy_encoding(encoding) => {
+                let mut bytes = Vec::new();
+                for c in self.iter_code_units() {
+                    if !encodings::encode_code_unit(c, encoding, &mut bytes) {
+                        if !lossy {
+                            return None;
+                        }
+                        bytes.push(b'?');
+                    }
+                }
+                Some(bytes)
+            }
+            _ => panic!("Unimplemented encoding: {:#x}", encoding),
         }
     }
     fn to_utf8(&self) -> Result<Cow<'static, str>, FromUtf16Error> {
@@ -269,6 +343,36 @@ pub const CLASSES: ClassExports = objc_classes! {
     to_rust_string(env, this) == to_rust_string(env, other)
 }
 
+- (NSComparisonResult)compare:(id)other { // NSString*
+    // TODO: avoid copying
+    let a = to_rust_string(env, this);
+    let b = to_rust_string(env, other);
+    // This is a literal comparison of UTF-16 code units, like Apple's with no
+    // options.
+    match a.encode_utf16().cmp(b.encode_utf16()) {
+        Ordering::Less => NSOrderedAscending,
+        Ordering::Equal => NSOrderedSame,
+        Ordering::Greater => NSOrderedDescending,
+    }
+}
+- (NSComparisonResult)caseInsensitiveCompare:(id)other { // NSString*
+    // TODO: avoid copying
+    let a = to_rust_string(env, this).to_lowercase();
+    let b = to_rust_string(env, other).to_lowercase();
+    match a.encode_utf16().cmp(b.encode_utf16()) {
+        Ordering::Less => NSOrderedAscending,
+        Ordering::Equal => NSOrderedSame,
+        Ordering::Greater => NSOrderedDescending,
+    }
+}
+// TODO: use the current locale's collation for these
+- (NSComparisonResult)localizedCompare:(id)other { // NSString*
+    msg![env; this compare:other]
+}
+- (NSComparisonResult)localizedCaseInsensitiveCompare:(id)other { // NSString*
+    msg![env; this caseInsensitiveCompare:other]
+}
+
 // NSCopying implementation
 - (id)copyWithZone:(MutVoidPtr)_zone {
     // TODO: override this once we have NSMutableString!
@@ -278,21 +382,63 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (bool)getCString:(MutPtr<u8>)buffer
          maxLength:(NSUInteger)buffer_size
           encoding:(NSStringEncoding)encoding {
-    assert!(encoding == NSUTF8StringEncoding); // TODO: other encodings
-
-    let src = to_rust_string(env, this);
+    // TODO: handle foreign subclasses of NSString
+    let Some(src) = env.objc.borrow::<StringHostObject>(this).encode(encoding, false) else {
+        return false;
+    };
     let dest = env.mem.bytes_at_mut(buffer, buffer_size);
-    if dest.len() < src.as_bytes().len() + 1 { // include null terminator
+    if dest.len() < src.len() + 1 { // include null terminator
         return false;
     }
 
-    for (i, &byte) in src.as_bytes().iter().chain(b"\0".iter()).enumerate() {
+    for (i, &byte) in src.iter().chain(b"\0".iter()).enumerate() {
         dest[i] = byte;
     }
 
     true
 }
 
+- (ConstPtr<u8>)cStringUsingEncoding:(NSStringEncoding)encoding {
+    // TODO: handle foreign subclasses of NSString
+    let Some(bytes) = env.objc.borrow::<StringHostObject>(this).encode(encoding, false) else {
+        return Ptr::null();
+    };
+    let c_string = env.mem.alloc_and_write_cstr(&bytes).cast_const();
+    let length: NSUInteger = (bytes.len() + 1).try_into().unwrap();
+    // NSData will handle releasing the string (it is autoreleased)
+    let _: id = msg_class![env; NSData dataWithBytesNoCopy:c_string
+                                                    length:length];
+    c_string
+}
+
+- (bool)canBeConvertedToEncoding:(NSStringEncoding)encoding {
+    // TODO: handle foreign subclasses of NSString
+    env.objc.borrow::<StringHostObject>(this).encode(encoding, false).is_some()
+}
+
+- (NSUInteger)lengthOfBytesUsingEncoding:(NSStringEncoding)encoding {
+    // TODO: handle foreign subclasses of NSString
+    // TODO: avoid encoding the whole string just to get the length
+    let bytes = env.objc.borrow::<StringHostObject>(this).encode(encoding, false);
+    bytes.map_or(0, |bytes| bytes.len().try_into().unwrap())
+}
+
+- (id)dataUsingEncoding:(NSStringEncoding)encoding { // NSData*
+    msg![env; this dataUsingEncoding:encoding allowLossyConversion:false]
+}
+- (id)dataUsingEncoding:(NSStringEncoding)encoding // NSData*
+   allowLossyConversion:(bool)lossy {
+    // TODO: handle foreign subclasses of NSString
+    let Some(bytes) = env.objc.borrow::<StringHostObject>(this).encode(encoding, lossy) else {
+        return nil;
+    };
+    let length: NSUInteger = bytes.len().try_into().unwrap();
+    let ptr = env.mem.alloc(length);
+    env.mem.bytes_at_mut(ptr.cast(), length).copy_from_slice(&bytes);
+    msg_class![env; NSData dataWithBytesNoCopy:ptr
+                                        length:length]
+}
+
 - (id)componentsSeparatedByString:(id)separator { // NSString*
     // TODO: support foreign subclasses (perhaps via a helper function that
     // copies the string first)
@@ -427,6 +573,19 @@ pub const CLASSES: ClassExports = objc_classes! {
     new_string
 }
 
+- (id)decomposedStringWithCanonicalMapping {
+    normalized_copy(env, this, NormalizationForm::D)
+}
+- (id)decomposedStringWithCompatibilityMapping {
+    normalized_copy(env, this, NormalizationForm::KD)
+}
+- (id)precomposedStringWithCanonicalMapping {
+    normalized_copy(env, this, NormalizationForm::C)
+}
+- (id)precomposedStringWithCompatibilityMapping {
+    normalized_copy(env, this, NormalizationForm::KC)
+}
+
 - (ConstPtr<u8>)UTF8String {
     // TODO: avoid copying
     let string = to_rust_string(env, this);
@@ -440,7 +599,6 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 // These come from a category in UIKit (UIStringDrawing).
 // TODO: Implement categories so we can completely move the code to UIFont.
-// TODO: More `sizeWithFont:` variants
 - (CGSize)sizeWithFont:(id)font { // UIFont*
     // TODO: avoid copy
     let text = to_rust_string(env, this);
@@ -459,6 +617,113 @@ pub const CLASSES: ClassExports = objc_classes! {
     let text = to_rust_string(env, this);
     ui_font::size_with_font(env, font, &text, Some((size, line_break_mode)))
 }
+- (CGSize)sizeWithFont:(id)font // UIFont*
+              forWidth:(CGFloat)width
+         lineBreakMode:(UILineBreakMode)line_break_mode {
+    // TODO: avoid copy
+    let text = to_rust_string(env, this);
+    ui_font::size_with_font_for_width(env, font, &text, width, line_break_mode, None).0
+}
+- (CGSize)sizeWithFont:(id)font // UIFont*
+           minFontSize:(CGFloat)min_font_size
+        actualFontSize:(MutPtr<CGFloat>)actual_font_size
+              forWidth:(CGFloat)width
+         lineBreakMode:(UILineBreakMode)line_break_mode {
+    // TODO: avoid copy
+    let text = to_rust_string(env, this);
+    let (size, font_size) = ui_font::size_with_font_for_width(
+        env,
+        font,
+        &text,
+        width,
+        line_break_mode,
+        Some(min_font_size),
+    );
+    if !actual_font_size.is_null() {
+        env.mem.write(actual_font_size, font_size);
+    }
+    size
+}
+
+- (CGSize)drawAtPoint:(CGPoint)point
+             withFont:(id)font { // UIFont*
+    // TODO: avoid copy
+    let text = to_rust_string(env, this);
+    ui_font::draw_at_point(
+        env,
+        font,
+        &text,
+        point,
+        None,
+        None,
+        None,
+        UIBaselineAdjustmentNone,
+    )
+    .0
+}
+- (CGSize)drawAtPoint:(CGPoint)point
+             forWidth:(CGFloat)width
+             withFont:(id)font // UIFont*
+        lineBreakMode:(UILineBreakMode)line_break_mode {
+    // TODO: avoid copy
+    let text = to_rust_string(env, this);
+    ui_font::draw_at_point(
+        env,
+        font,
+        &text,
+        point,
+        Some((width, line_break_mode)),
+        None,
+        None,
+        UIBaselineAdjustmentNone,
+    )
+    .0
+}
+- (CGSize)drawAtPoint:(CGPoint)point
+             forWidth:(CGFloat)width
+             withFont:(id)font // UIFont*
+             fontSize:(CGFloat)font_size
+        lineBreakMode:(UILineBreakMode)line_break_mode
+   baselineAdjustment:(UIBaselineAdjustment)baseline_adjustment {
+    // TODO: avoid copy
+    let text = to_rust_string(env, this);
+    ui_font::draw_at_point(
+        env,
+        font,
+        &text,
+        point,
+        Some((width, line_break_mode)),
+        Some(font_size),
+        None,
+        baseline_adjustment,
+    )
+    .0
+}
+- (CGSize)drawAtPoint:(CGPoint)point
+             forWidth:(CGFloat)width
+             withFont:(id)font // UIFont*
+          minFontSize:(CGFloat)min_font_size
+       actualFontSize:(MutPtr<CGFloat>)actual_font_size
+        lineBreakMode:(UILineBreakMode)line_break_mode
+   baselineAdjustment:(UIBaselineAdjustment)baseline_adjustment {
+    // TODO: avoid copy
+    let text = to_rust_string(env, this);
+    let (size, font_size) = ui_font::draw_at_point(
+        env,
+        font,
+        &text,
+        point,
+        Some((width, line_break_mode)),
+        None,
+        Some(min_font_size),
+        baseline_adjustment,
+    );
+    if !actual_font_size.is_null() {
+        env.mem.write(actual_font_size, font_size);
+    }
+    size
+}
+
 
 - (CGSize)drawInRect:(CGRect)rect
             withFont:(id)font { // UIFont*
@@ -500,17 +765,26 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (id)initWithBytes:(ConstPtr<u8>)bytes
              length:(NSUInteger)len
            encoding:(NSStringEncoding)encoding {
-    assert!(encoding == NSUTF8StringEncoding); // TODO: other encodings
-
-    // TODO: error handling
     let slice = env.mem.bytes_at(bytes, len);
-    let host_object = StringHostObject::decode(Cow::Borrowed(slice), encoding);
+    let Some(host_object) = StringHostObject::decode(Cow::Borrowed(slice), encoding) else {
+        release(env, this);
+        return nil;
+    };
 
     *env.objc.borrow_mut(this) = host_object;
 
     this
 }
 
+- (id)initWithData:(id)data // NSData*
+          encoding:(NSStringEncoding)encoding {
+    let bytes: ConstPtr<u8> = msg![env; data bytes];
+    let length: NSUInteger = msg![env; data length];
+    msg![env; this initWithBytes:bytes
+                          length:length
+                        encoding:encoding]
+}
+
 - (id)initWithCString:(ConstPtr<u8>)c_string {
     // This is a deprecated method nobody should use, but unfortunately, it is
     // used. The encoding it should use is [NSString defaultCStringEncoding]
@@ -536,7 +810,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     let path = to_rust_string(env, path);
     let bytes = env.fs.read(GuestPath::new(&path)).unwrap();
 
-    let host_object = StringHostObject::decode(Cow::Owned(bytes), encoding);
+    let Some(host_object) = StringHostObject::decode(Cow::Owned(bytes), encoding) else {
+        release(env, this);
+        return nil;
+    };
 
     *env.objc.borrow_mut(this) = host_object;
 
@@ -617,6 +894,37 @@ pub fn from_rust_string(env: &mut Environment, from: String) -> id {
     string
 }
 
+/// Shortcut for host code, like [from_rust_string] but for UTF-16 code units,
+/// which do not have to be well-formed.
+pub fn from_utf16_code_units(env: &mut Environment, from: Vec<u16>) -> id {
+    let string: id = msg_class![env; _touchHLE_NSString alloc];
+    let host_object: &mut StringHostObject = env.objc.borrow_mut(string);
+    *host_object = StringHostObject::Utf16(from);
+    string
+}
+
+/// Shortcut for host code, used by Core Foundation functions that modify a
+/// `CFMutableString` in place. There's no `NSMutableString` yet, so this
+/// replaces the contents of an ordinary string, but not a static one.
+pub fn replace_contents(env: &mut Environment, string: id, new_contents: String) {
+    let static_class = env
+        .objc
+        .get_known_class("_touchHLE_NSString_Static", &mut env.mem);
+    assert!(
+        !msg![env; string isKindOfClass:static_class],
+        "Attempt to modify immutable string {:?}",
+        string
+    );
+    *env.objc.borrow_mut(string) = StringHostObject::Utf8(Cow::Owned(new_contents));
+}
+
+/// Autoreleased normalized copy of a string.
+fn normalized_copy(env: &mut Environment, string: id, form: NormalizationForm) -> id {
+    let normalized = normalize(&to_rust_string(env, string), form);
+    let new_string = from_rust_string(env, normalized);
+    autorelease(env, new_string)
+}
+
 /// Shortcut for host code, provides a view of a string in UTF-8.
 /// Warning: This may panic if the string is not valid UTF-16!
 ///
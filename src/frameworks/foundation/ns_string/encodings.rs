@@ -0,0 +1,177 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Conversion between Unicode and the legacy (non-UTF) string encodings.
+//!
+//! All the characters these encodings can represent are in the Basic
+//! Multilingual Plane, so encoding works one UTF-16 code unit at a time.
+
+use super::{
+    NSASCIIStringEncoding, NSISOLatin1StringEncoding, NSMacOSRomanStringEncoding,
+    NSShiftJISStringEncoding, NSStringEncoding, NSWindowsCP1252StringEncoding,
+};
+
+/// Mac OS Roman code points for bytes 0x80 to 0xFF.
+#[rustfmt::skip]
+const MAC_OS_ROMAN_HIGH: [u16; 128] = [
+    0x00C4, 0x00C5, 0x00C7, 0x00C9, 0x00D1, 0x00D6, 0x00DC, 0x00E1,
+    0x00E0, 0x00E2, 0x00E4, 0x00E3, 0x00E5, 0x00E7, 0x00E9, 0x00E8,
+    0x00EA, 0x00EB, 0x00ED, 0x00EC, 0x00EE, 0x00EF, 0x00F1, 0x00F3,
+    0x00F2, 0x00F4, 0x00F6, 0x00F5, 0x00FA, 0x00F9, 0x00FB, 0x00FC,
+    0x2020, 0x00B0, 0x00A2, 0x00A3, 0x00A7, 0x2022, 0x00B6, 0x00DF,
+    0x00AE, 0x00A9, 0x2122, 0x00B4, 0x00A8, 0x2260, 0x00C6, 0x00D8,
+    0x221E, 0x00B1, 0x2264, 0x2265, 0x00A5, 0x00B5, 0x2202, 0x2211,
+    0x220F, 0x03C0, 0x222B, 0x00AA, 0x00BA, 0x03A9, 0x00E6, 0x00F8,
+    0x00BF, 0x00A1, 0x00AC, 0x221A, 0x0192, 0x2248, 0x2206, 0x00AB,
+    0x00BB, 0x2026, 0x00A0, 0x00C0, 0x00C3, 0x00D5, 0x0152, 0x0153,
+    0x2013, 0x2014, 0x201C, 0x201D, 0x2018, 0x2019, 0x00F7, 0x25CA,
+    0x00FF, 0x0178, 0x2044, 0x20AC, 0x2039, 0x203A, 0xFB01, 0xFB02,
+    0x2021, 0x00B7, 0x201A, 0x201E, 0x2030, 0x00C2, 0x00CA, 0x00C1,
+    0x00CB, 0x00C8, 0x00CD, 0x00CE, 0x00CF, 0x00CC, 0x00D3, 0x00D4,
+    0xF8FF, 0x00D2, 0x00DA, 0x00DB, 0x00D9, 0x0131, 0x02C6, 0x02DC,
+    0x00AF, 0x02D8, 0x02D9, 0x02DA, 0x00B8, 0x02DD, 0x02DB, 0x02C7,
+];
+
+/// Windows-1252 code points for bytes 0x80 to 0x9F (the rest is the same as
+/// ISO Latin-1). Unassigned bytes are 0.
+#[rustfmt::skip]
+const WINDOWS_CP1252_HIGH: [u16; 32] = [
+    0x20AC, 0x0000, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021,
+    0x02C6, 0x2030, 0x0160, 0x2039, 0x0152, 0x0000, 0x017D, 0x0000,
+    0x0000, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x0000, 0x017E, 0x0178,
+];
+
+/// Shift-JIS double-byte code points, as big-endian `u16`s. This uses the
+/// Windows code page 932 variant of Shift-JIS, which is what Apple uses for
+/// `NSShiftJISStringEncoding`. Unassigned byte pairs are 0.
+///
+/// The table has [SHIFT_JIS_TRAIL_COUNT] entries for each lead byte in
+/// 0x81–0x9F and 0xE0–0xFC, for trail bytes 0x40–0xFC. It was generated with
+/// Python's `cp932` codec.
+const SHIFT_JIS_TABLE: &[u8] = include_bytes!("shift_jis.bin");
+const SHIFT_JIS_TRAIL_COUNT: usize = 0xFD - 0x40;
+
+fn shift_jis_lead_index(lead: u8) -> Option<usize> {
+    match lead {
+        0x81..=0x9F => Some((lead - 0x81) as usize),
+        0xE0..=0xFC => Some((lead - 0xE0) as usize + (0xA0 - 0x81)),
+        _ => None,
+    }
+}
+
+fn shift_jis_lookup(lead_index: usize, trail: u8) -> Option<u16> {
+    if !(0x40..=0xFC).contains(&trail) {
+        return None;
+    }
+    let idx = (lead_index * SHIFT_JIS_TRAIL_COUNT + (trail - 0x40) as usize) * 2;
+    let code_point = u16::from_be_bytes([SHIFT_JIS_TABLE[idx], SHIFT_JIS_TABLE[idx + 1]]);
+    if code_point == 0 {
+        None
+    } else {
+        Some(code_point)
+    }
+}
+
+/// Is this one of the encodings implemented by this module?
+pub(super) fn is_legacy_encoding(encoding: NSStringEncoding) -> bool {
+    matches!(
+        encoding,
+        NSASCIIStringEncoding
+            | NSISOLatin1StringEncoding
+            | NSMacOSRomanStringEncoding
+            | NSWindowsCP1252StringEncoding
+            | NSShiftJISStringEncoding
+    )
+}
+
+/// Decode bytes in a legacy encoding. Returns [None] if the bytes aren't
+/// valid in that encoding.
+pub(super) fn decode(bytes: &[u8], encoding: NSStringEncoding) -> Option<String> {
+    let mut string = String::with_capacity(bytes.len());
+    let mut iter = bytes.iter().copied();
+    while let Some(byte) = iter.next() {
+        let code_point: u16 = match (encoding, byte) {
+            (_, 0x00..=0x7F) => byte.into(),
+            (NSASCIIStringEncoding, _) => return None,
+            (NSISOLatin1StringEncoding, _) => byte.into(),
+            (NSMacOSRomanStringEncoding, _) => MAC_OS_ROMAN_HIGH[(byte - 0x80) as usize],
+            (NSWindowsCP1252StringEncoding, 0x80..=0x9F) => {
+                match WINDOWS_CP1252_HIGH[(byte - 0x80) as usize] {
+                    0 => return None,
+                    code_point => code_point,
+                }
+            }
+            (NSWindowsCP1252StringEncoding, _) => byte.into(),
+            // Half-width katakana
+            (NSShiftJISStringEncoding, 0xA1..=0xDF) => 0xFF61 + u16::from(byte - 0xA1),
+            (NSShiftJISStringEncoding, _) => {
+                let lead_index = shift_jis_lead_index(byte)?;
+                shift_jis_lookup(lead_index, iter.next()?)?
+            }
+            _ => unreachable!(),
+        };
+        string.push(char::from_u32(code_point.into())?);
+    }
+    Some(string)
+}
+
+/// Encode a single UTF-16 code unit in a legacy encoding, appending the result
+/// to `out`. Returns [false] if the character can't be represented.
+pub(super) fn encode_code_unit(
+    code_unit: u16,
+    encoding: NSStringEncoding,
+    out: &mut Vec<u8>,
+) -> bool {
+    if code_unit < 0x80 {
+        out.push(code_unit as u8);
+        return true;
+    }
+    let byte = match encoding {
+        NSASCIIStringEncoding => None,
+        NSISOLatin1StringEncoding => u8::try_from(code_unit).ok(),
+        NSMacOSRomanStringEncoding => MAC_OS_ROMAN_HIGH
+            .iter()
+            .position(|&c| c == code_unit)
+            .map(|idx| 0x80 + idx as u8),
+        NSWindowsCP1252StringEncoding => WINDOWS_CP1252_HIGH
+            .iter()
+            .position(|&c| c == code_unit)
+            .map(|idx| 0x80 + idx as u8)
+            .or_else(|| u8::try_from(code_unit).ok().filter(|&byte| byte >= 0xA0)),
+        NSShiftJISStringEncoding => {
+            if (0xFF61..=0xFF9F).contains(&code_unit) {
+                Some((code_unit - 0xFF61) as u8 + 0xA1)
+            } else {
+                // TODO: This is a linear search of the whole table. Build a
+                // reverse mapping if this turns out to be too slow.
+                let idx = SHIFT_JIS_TABLE
+                    .chunks(2)
+                    .position(|pair| u16::from_be_bytes([pair[0], pair[1]]) == code_unit);
+                let Some(idx) = idx else {
+                    return false;
+                };
+                let (lead_index, trail_index) =
+                    (idx / SHIFT_JIS_TRAIL_COUNT, idx % SHIFT_JIS_TRAIL_COUNT);
+                let lead = if lead_index < 0xA0 - 0x81 {
+                    0x81 + lead_index as u8
+                } else {
+                    0xE0 + (lead_index - (0xA0 - 0x81)) as u8
+                };
+                out.push(lead);
+                out.push(0x40 + trail_index as u8);
+                return true;
+            }
+        }
+        _ => unreachable!(),
+    };
+    match byte {
+        Some(byte) => {
+            out.push(byte);
+            true
+        }
+        None => false,
+    }
+}
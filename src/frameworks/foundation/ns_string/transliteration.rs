@@ -0,0 +1,335 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Transliteration to the Latin script, in the style of ICU's `Any-Latin`
+//! transform, and from there to ASCII (like ICU's `Latin-ASCII`).
+//!
+//! Greek, Cyrillic, kana and Hangul are supported. Han characters and other
+//! scripts are left unchanged.
+
+use super::normalization::{combining_class, normalize, NormalizationForm};
+
+/// Cyrillic lowercase letters U+0430 to U+044F (ISO 9, as ICU uses).
+const CYRILLIC: [&str; 32] = [
+    "a", "b", "v", "g", "d", "e", "ž", "z", "i", "j", "k", "l", "m", "n", "o", "p", "r", "s", "t",
+    "u", "f", "h", "c", "č", "š", "ŝ", "ʺ", "y", "ʹ", "è", "û", "â",
+];
+
+/// Greek lowercase letters U+03B1 to U+03C9.
+const GREEK: [&str; 25] = [
+    "a", "b", "g", "d", "e", "z", "ē", "th", "i", "k", "l", "m", "n", "x", "o", "p", "r", "s", "s",
+    "t", "y", "ph", "ch", "ps", "ō",
+];
+
+/// Romanizations of the kana from ぁ (U+3041) to ゖ (U+3096), in order.
+/// Katakana are the same, offset by 0x60. Small kana are handled separately.
+const KANA: [&str; 86] = [
+    "a", "a", "i", "i", "u", "u", "e", "e", "o", "o", // ぁ-お
+    "ka", "ga", "ki", "gi", "ku", "gu", "ke", "ge", "ko", "go", // か-ご
+    "sa", "za", "shi", "ji", "su", "zu", "se", "ze", "so", "zo", // さ-ぞ
+    "ta", "da", "chi", "ji", "tsu", "tsu", "zu", "te", "de", "to", "do", // た-ど
+    "na", "ni", "nu", "ne", "no", // な-の
+    "ha", "ba", "pa", "hi", "bi", "pi", "fu", "bu", "pu", "he", "be", "pe", "ho", "bo",
+    "po", // は-ぽ
+    "ma", "mi", "mu", "me", "mo", // ま-も
+    "ya", "ya", "yu", "yu", "yo", "yo", // ゃ-よ
+    "ra", "ri", "ru", "re", "ro", // ら-ろ
+    "wa", "wa", "wi", "we", "wo", "n", "vu", "ka", "ke", // ゎ-ゖ
+];
+
+const HANGUL_INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+const HANGUL_MEDIALS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+const HANGUL_FINALS: [&str; 28] = [
+    "", "g", "kk", "gs", "n", "nj", "nh", "d", "l", "lg", "lm", "lb", "ls", "lt", "lp", "lh", "m",
+    "b", "bs", "s", "ss", "ng", "j", "ch", "k", "t", "p", "h",
+];
+
+fn is_kana(c: char) -> bool {
+    ('\u{3041}'..='\u{3096}').contains(&c) || ('\u{30A1}'..='\u{30F6}').contains(&c)
+}
+
+/// Index into [KANA] for a kana character.
+fn kana_index(c: char) -> Option<usize> {
+    let code = c as u32;
+    match code {
+        0x3041..=0x3096 => Some((code - 0x3041) as usize),
+        0x30A1..=0x30F6 => Some((code - 0x30A1) as usize),
+        _ => None,
+    }
+}
+
+/// Whether a kana is one of the small ゃ, ゅ, ょ (or katakana equivalents)
+/// that combine with the preceding kana.
+fn is_small_y(c: char) -> bool {
+    matches!(c, 'ゃ' | 'ゅ' | 'ょ' | 'ャ' | 'ュ' | 'ョ')
+}
+
+/// Whether a kana is a small vowel (as in ファ "fa"), which combines with the
+/// preceding kana.
+fn is_small_vowel(c: char) -> bool {
+    matches!(
+        c,
+        'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' | 'ァ' | 'ィ' | 'ゥ' | 'ェ' | 'ォ'
+    )
+}
+
+/// Append a syllable, keeping the case of the source character.
+fn push_with_case(out: &mut String, latin: &str, uppercase: bool) {
+    if !uppercase {
+        out.push_str(latin);
+        return;
+    }
+    let mut chars = latin.chars();
+    if let Some(first) = chars.next() {
+        out.extend(first.to_uppercase());
+        out.push_str(chars.as_str());
+    }
+}
+
+/// Transliterate kana starting at `chars[i]`, returning the number of
+/// characters consumed.
+fn transliterate_kana(chars: &[char], i: usize, out: &mut String) -> usize {
+    let c = chars[i];
+    let next = chars.get(i + 1).copied();
+
+    // Small tsu doubles the next consonant.
+    if matches!(c, 'っ' | 'ッ') {
+        if let Some(idx) = next.and_then(kana_index) {
+            let consonant = KANA[idx].chars().next().unwrap();
+            if !"aiueon".contains(consonant) {
+                out.push(if KANA[idx].starts_with("ch") {
+                    't'
+                } else {
+                    consonant
+                });
+            }
+        }
+        return 1;
+    }
+    // The long vowel mark lengthens the previous vowel.
+    if c == 'ー' {
+        out.push('\u{0304}');
+        return 1;
+    }
+
+    let syllable = KANA[kana_index(c).unwrap()];
+    if c == 'ん' || c == 'ン' {
+        out.push('n');
+        // Disambiguate e.g. "kan'i" from "kani".
+        if next
+            .and_then(kana_index)
+            .is_some_and(|idx| "aiueoy".contains(KANA[idx].chars().next().unwrap()))
+        {
+            out.push('\'');
+        }
+        return 1;
+    }
+    match next {
+        Some(next) if is_small_y(next) && syllable.ends_with('i') && syllable.len() > 1 => {
+            let stem = &syllable[..syllable.len() - 1];
+            let y_vowel = &KANA[kana_index(next).unwrap()][1..];
+            // "shi" + "ya" is "sha", not "shya"
+            if stem.ends_with("sh") || stem.ends_with("ch") || stem == "j" {
+                out.push_str(stem);
+            } else {
+                out.push_str(stem);
+                out.push('y');
+            }
+            out.push_str(y_vowel);
+            2
+        }
+        Some(next) if is_small_vowel(next) && syllable.len() > 1 => {
+            out.push_str(&syllable[..syllable.len() - 1]);
+            out.push_str(KANA[kana_index(next).unwrap()]);
+            2
+        }
+        _ => {
+            out.push_str(syllable);
+            1
+        }
+    }
+}
+
+/// Transliterate a string to the Latin script. Diacritics are kept, see
+/// [to_ascii] to remove them.
+pub fn to_latin(string: &str) -> String {
+    // Greek letters with accents and Hangul syllables are decomposed so the
+    // letters can be looked up, but Cyrillic letters like й must not be.
+    let mut chars = Vec::with_capacity(string.len());
+    for c in normalize(string, NormalizationForm::C).chars() {
+        if matches!(c, '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' | '\u{AC00}'..='\u{D7A3}')
+        {
+            chars.extend(normalize(c.encode_utf8(&mut [0; 4]), NormalizationForm::D).chars());
+        } else {
+            chars.push(c);
+        }
+    }
+    let mut out = String::with_capacity(string.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let lower = c.to_lowercase().next().unwrap();
+        let uppercase = lower != c;
+        match lower {
+            'а'..='я' => {
+                let idx = lower as usize - 'а' as usize;
+                push_with_case(&mut out, CYRILLIC[idx], uppercase);
+            }
+            'ё' => push_with_case(&mut out, "ë", uppercase),
+            'є' => push_with_case(&mut out, "ê", uppercase),
+            'і' => push_with_case(&mut out, "ì", uppercase),
+            'ї' => push_with_case(&mut out, "ï", uppercase),
+            'ў' => push_with_case(&mut out, "ŭ", uppercase),
+            'ґ' => push_with_case(&mut out, "g̀", uppercase),
+            'ђ' => push_with_case(&mut out, "đ", uppercase),
+            'ј' => push_with_case(&mut out, "ǰ", uppercase),
+            'љ' => push_with_case(&mut out, "lj", uppercase),
+            'њ' => push_with_case(&mut out, "nj", uppercase),
+            'ћ' => push_with_case(&mut out, "ć", uppercase),
+            'џ' => push_with_case(&mut out, "dž", uppercase),
+            'α'..='ω' => {
+                let idx = lower as usize - 'α' as usize;
+                // Upsilon is "u" in diphthongs.
+                let previous = i
+                    .checked_sub(1)
+                    .map(|i| chars[i].to_lowercase().next().unwrap());
+                let latin = if lower == 'υ' && matches!(previous, Some('α' | 'ε' | 'η' | 'ο'))
+                {
+                    "u"
+                } else {
+                    GREEK[idx]
+                };
+                push_with_case(&mut out, latin, uppercase);
+            }
+            _ if is_kana(c) || c == 'ー' => {
+                i += transliterate_kana(&chars, i, &mut out);
+                continue;
+            }
+            '\u{1100}'..='\u{1112}' => {
+                out.push_str(HANGUL_INITIALS[c as usize - 0x1100]);
+            }
+            '\u{1161}'..='\u{1175}' => {
+                out.push_str(HANGUL_MEDIALS[c as usize - 0x1161]);
+            }
+            '\u{11A8}'..='\u{11C2}' => {
+                out.push_str(HANGUL_FINALS[c as usize - 0x11A7]);
+            }
+            '。' => out.push('.'),
+            '、' => out.push(','),
+            '「' | '」' => out.push('"'),
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+    normalize(&out, NormalizationForm::C)
+}
+
+/// Remove diacritics and replace the remaining non-ASCII Latin letters and
+/// punctuation with ASCII approximations where possible.
+pub fn to_ascii(string: &str) -> String {
+    let mut out = String::with_capacity(string.len());
+    for c in strip_combining_marks(&normalize(string, NormalizationForm::KD)).chars() {
+        let replacement = match c {
+            'ß' => "ss",
+            'æ' => "ae",
+            'Æ' => "AE",
+            'œ' => "oe",
+            'Œ' => "OE",
+            'ø' => "o",
+            'Ø' => "O",
+            'đ' | 'ð' => "d",
+            'Đ' | 'Ð' => "D",
+            'ł' => "l",
+            'Ł' => "L",
+            'þ' => "th",
+            'Þ' => "TH",
+            'ı' => "i",
+            'ʹ' | '′' | '‘' | '’' => "'",
+            'ʺ' | '″' | '“' | '”' => "\"",
+            '–' | '—' => "-",
+            _ => {
+                out.push(c);
+                continue;
+            }
+        };
+        out.push_str(replacement);
+    }
+    out
+}
+
+/// Remove combining marks from a string, after decomposing it.
+pub fn strip_combining_marks(string: &str) -> String {
+    let decomposed = normalize(string, NormalizationForm::D);
+    let stripped: String = decomposed
+        .chars()
+        .filter(|&c| !is_mark(c) && combining_class(c) == 0)
+        .collect();
+    normalize(&stripped, NormalizationForm::C)
+}
+
+/// Approximation of the nonspacing mark category: the combining diacritical
+/// mark blocks. Other characters with a non-zero combining class are also
+/// treated as nonspacing marks.
+fn is_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{3099}'..='\u{309A}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+/// Convert hiragana to katakana, or the reverse if `reverse` is set.
+pub fn hiragana_to_katakana(string: &str, reverse: bool) -> String {
+    string
+        .chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' | 'ゝ'..='ゞ' if !reverse => char::from_u32(c as u32 + 0x60).unwrap(),
+            'ァ'..='ヶ' | 'ヽ'..='ヾ' if reverse => char::from_u32(c as u32 - 0x60).unwrap(),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Convert fullwidth ASCII variants to ASCII, or the reverse if `reverse` is
+/// set. Halfwidth katakana are not handled.
+pub fn fullwidth_to_halfwidth(string: &str, reverse: bool) -> String {
+    string
+        .chars()
+        .map(|c| match c {
+            '\u{FF01}'..='\u{FF5E}' if !reverse => char::from_u32(c as u32 - 0xFEE0).unwrap(),
+            '\u{3000}' if !reverse => ' ',
+            '!'..='~' if reverse => char::from_u32(c as u32 + 0xFEE0).unwrap(),
+            ' ' if reverse => '\u{3000}',
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transliteration() {
+        assert_eq!(to_latin("Привет, Йошкар-Ола"), "Privet, Joškar-Ola");
+        assert_eq!(to_latin("Ελλάδα"), "Elláda");
+        assert_eq!(to_latin("とうきょう"), "toukyou");
+        assert_eq!(to_latin("ラーメン"), "rāmen");
+        assert_eq!(to_latin("한국"), "hangug");
+        assert_eq!(to_ascii(&to_latin("Щука")), "Suka");
+        assert_eq!(hiragana_to_katakana("ひらがな", false), "ヒラガナ");
+        assert_eq!(fullwidth_to_halfwidth("ＡＢＣ　１２３", false), "ABC 123");
+    }
+}
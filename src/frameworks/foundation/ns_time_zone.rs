@@ -0,0 +1,230 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSTimeZone`.
+//!
+//! Only fixed-offset time zones are supported. There's no time zone database
+//! here, so daylight saving time is never applied.
+
+use super::ns_string;
+use super::NSInteger;
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    system_time_zone: Option<id>,
+    default_time_zone: Option<id>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut State {
+        &mut env.framework_state.foundation.ns_time_zone
+    }
+}
+
+struct NSTimeZoneHostObject {
+    name: String,
+    seconds_from_gmt: i32,
+}
+impl HostObject for NSTimeZoneHostObject {}
+
+/// Format an offset like `GMT+0100`, the name Apple gives to time zones
+/// created with `timeZoneForSecondsFromGMT:`.
+fn name_for_offset(seconds_from_gmt: i32) -> String {
+    if seconds_from_gmt == 0 {
+        return "GMT".to_string();
+    }
+    let sign = if seconds_from_gmt < 0 { '-' } else { '+' };
+    let abs = seconds_from_gmt.unsigned_abs();
+    format!("GMT{}{:02}{:02}", sign, abs / 3600, (abs / 60) % 60)
+}
+
+/// Parse an offset in the style of `+01:00`, `-0530` or `+9`, as found after
+/// `GMT` or `UTC` in time zone abbreviations. Returns seconds east of GMT.
+fn parse_offset(offset: &str) -> Option<i32> {
+    let (sign, rest) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    // This also makes splitting by byte index below safe.
+    if !rest.is_ascii() {
+        return None;
+    }
+    let (hours, minutes) = if let Some((h, m)) = rest.split_once(':') {
+        (h, m)
+    } else if rest.len() > 2 {
+        rest.split_at(rest.len() - 2)
+    } else {
+        (rest, "0")
+    };
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 18 || minutes >= 60 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Parse a name or abbreviation like `UTC`, `GMT` or `GMT+2`.
+fn parse_name(name: &str) -> Option<i32> {
+    for prefix in ["GMT", "UTC", "Etc/GMT", "Etc/UTC"] {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            if rest.is_empty() {
+                return Some(0);
+            }
+            let offset = parse_offset(rest)?;
+            // The Etc/ zones are named POSIX-style, with the sign inverted.
+            return Some(if prefix.starts_with("Etc/") {
+                -offset
+            } else {
+                offset
+            });
+        }
+    }
+    None
+}
+
+/// Parse a POSIX `TZ` value like `CET-1` or `EST5EDT`, ignoring the DST part.
+/// Note that POSIX offsets are west of GMT, the opposite of what Apple uses.
+fn parse_posix_tz(tz: &str) -> Option<(String, i32)> {
+    let name_len = tz.find(|c: char| !c.is_ascii_alphabetic())?;
+    if name_len < 3 {
+        return None;
+    }
+    let (name, rest) = tz.split_at(name_len);
+    let offset_len = rest
+        .find(|c: char| !(c.is_ascii_digit() || c == ':' || c == '+' || c == '-'))
+        .unwrap_or(rest.len());
+    let offset = &rest[..offset_len];
+    let offset = if offset.starts_with(['+', '-']) {
+        parse_offset(offset)?
+    } else {
+        parse_offset(&format!("+{}", offset))?
+    };
+    Some((name.to_string(), -offset))
+}
+
+fn new_time_zone(env: &mut Environment, name: String, seconds_from_gmt: i32) -> id {
+    let host_object = Box::new(NSTimeZoneHostObject {
+        name,
+        seconds_from_gmt,
+    });
+    let class = env.objc.get_known_class("NSTimeZone", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSTimeZone: NSObject
+
++ (id)systemTimeZone {
+    if let Some(existing) = State::get(env).system_time_zone {
+        return existing;
+    }
+    let (name, offset) = match std::env::var("TZ").ok().as_deref() {
+        Some("") | None => {
+            log!("The app requested your time zone. No TZ environment variable was found, so UTC will be reported.");
+            ("GMT".to_string(), 0)
+        }
+        Some(tz) => {
+            if let Some(offset) = parse_name(tz) {
+                (name_for_offset(offset), offset)
+            } else if let Some((name, offset)) = parse_posix_tz(tz) {
+                log!("The app requested your time zone. {:?} ({}) will be reported based on your TZ environment variable. Daylight saving time is not supported.", name, name_for_offset(offset));
+                (name, offset)
+            } else {
+                log!("The app requested your time zone. Your TZ environment variable, {:?}, is not a fixed offset (e.g. \"CET-1\" or \"UTC+2\"), so UTC will be reported.", tz);
+                ("GMT".to_string(), 0)
+            }
+        }
+    };
+    let new = new_time_zone(env, name, offset);
+    State::get(env).system_time_zone = Some(new);
+    new
+}
++ (id)localTimeZone {
+    msg_class![env; NSTimeZone defaultTimeZone]
+}
++ (id)defaultTimeZone {
+    if let Some(existing) = State::get(env).default_time_zone {
+        existing
+    } else {
+        msg_class![env; NSTimeZone systemTimeZone]
+    }
+}
++ (())setDefaultTimeZone:(id)time_zone { // NSTimeZone*
+    retain(env, time_zone);
+    if let Some(old) = State::get(env).default_time_zone.replace(time_zone) {
+        release(env, old);
+    }
+}
+
++ (id)timeZoneForSecondsFromGMT:(NSInteger)seconds {
+    let new = new_time_zone(env, name_for_offset(seconds), seconds);
+    autorelease(env, new)
+}
++ (id)timeZoneWithName:(id)name { // NSString*
+    let name_str = ns_string::to_rust_string(env, name);
+    let Some(offset) = parse_name(&name_str) else {
+        log!("TODO: time zone {:?} is not supported, returning nil", name_str);
+        return nil;
+    };
+    let new = new_time_zone(env, name_str.into_owned(), offset);
+    autorelease(env, new)
+}
++ (id)timeZoneWithAbbreviation:(id)abbreviation { // NSString*
+    let abbreviation_str = ns_string::to_rust_string(env, abbreviation);
+    let Some(offset) = parse_name(&abbreviation_str) else {
+        log!("TODO: time zone abbreviation {:?} is not supported, returning nil", abbreviation_str);
+        return nil;
+    };
+    let new = new_time_zone(env, name_for_offset(offset), offset);
+    autorelease(env, new)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    // NSTimeZone is immutable
+    retain(env, this)
+}
+
+- (id)name {
+    let name = env.objc.borrow::<NSTimeZoneHostObject>(this).name.clone();
+    let name = ns_string::from_rust_string(env, name);
+    autorelease(env, name)
+}
+- (id)abbreviation {
+    let offset = seconds_from_gmt(env, this);
+    let abbreviation = ns_string::from_rust_string(env, name_for_offset(offset));
+    autorelease(env, abbreviation)
+}
+- (NSInteger)secondsFromGMT {
+    seconds_from_gmt(env, this)
+}
+- (NSInteger)secondsFromGMTForDate:(id)_date { // NSDate*
+    // No DST support, so this is the same for all dates.
+    seconds_from_gmt(env, this)
+}
+- (bool)isDaylightSavingTime {
+    false
+}
+
+@end
+
+};
+
+/// Shortcut for host code: get the offset of a time zone in seconds east of
+/// GMT.
+pub fn seconds_from_gmt(env: &mut Environment, time_zone: id) -> i32 {
+    env.objc
+        .borrow::<NSTimeZoneHostObject>(time_zone)
+        .seconds_from_gmt
+}
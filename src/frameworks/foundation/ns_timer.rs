@@ -13,7 +13,7 @@ use crate::objc::{
     HostObject, SEL,
 };
 use crate::Environment;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 struct NSTimerHostObject {
     ns_interval: NSTimeInterval,
@@ -25,7 +25,8 @@ struct NSTimerHostObject {
     /// Strong reference
     user_info: id,
     repeats: bool,
-    due_by: Option<Instant>,
+    /// By the app's clock (see [crate::clock::Clock::uptime]).
+    due_by: Option<Duration>,
     /// Weak reference
     run_loop: id,
 }
@@ -56,7 +57,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         selector,
         user_info,
         repeats,
-        due_by: Some(Instant::now().checked_add(rust_interval).unwrap()),
+        due_by: Some(env.clock.uptime().checked_add(rust_interval).unwrap()),
         run_loop: nil,
     });
     let new = env.objc.alloc_object(this, host_object, &mut env.mem);
@@ -149,13 +150,13 @@ pub(super) fn handle_timer(env: &mut Environment, timer: id) {
     // invalidated timers should have already been removed from the run loop
     let due_by = due_by.unwrap();
 
-    let now = Instant::now();
+    let now = env.clock.uptime();
 
     if due_by > now {
         return;
     }
 
-    let overdue_by = now.duration_since(due_by);
+    let overdue_by = now - due_by;
 
     // Timer may be released when it's invalidated, so we need to retain it so
     // it's still around to pass to the timer target.
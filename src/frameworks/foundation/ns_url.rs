@@ -5,7 +5,7 @@
  */
 //! `NSURL`.
 
-use super::ns_string::{to_rust_string, NSUTF8StringEncoding};
+use super::ns_string::{from_rust_string, get_static_str, to_rust_string, NSUTF8StringEncoding};
 use super::NSUInteger;
 use crate::fs::GuestPath;
 use crate::mem::{MutPtr, MutVoidPtr};
@@ -45,6 +45,12 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
++ (id)fileURLWithPath:(id)path { // NSString*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initFileURLWithPath:path];
+    autorelease(env, new)
+}
+
 - (())dealloc {
     match *env.objc.borrow(this) {
         NSURLHostObject::FileURL { ns_string } => release(env, ns_string),
@@ -81,6 +87,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (bool)isFileURL {
+    matches!(env.objc.borrow(this), NSURLHostObject::FileURL { .. })
+}
+
 - (id)path {
     let &NSURLHostObject::FileURL { ns_string } = env.objc.borrow(this) else {
         unimplemented!(); // TODO
@@ -96,6 +106,27 @@ pub const CLASSES: ClassExports = objc_classes! {
     ns_string
 }
 
+- (id)scheme {
+    let ns_string = match *env.objc.borrow(this) {
+        NSURLHostObject::FileURL { .. } => return get_static_str(env, "file"),
+        NSURLHostObject::OtherURL { ns_string } => ns_string,
+    };
+    let string = to_rust_string(env, ns_string);
+    let Some((scheme, _)) = string.split_once(':') else {
+        return nil;
+    };
+    let scheme = from_rust_string(env, scheme.to_string());
+    autorelease(env, scheme)
+}
+
+- (id)absoluteString {
+    // FIXME: don't assume URL is already absolute
+    let &NSURLHostObject::OtherURL { ns_string } = env.objc.borrow(this) else {
+        unimplemented!(); // TODO
+    };
+    ns_string
+}
+
 - (bool)getFileSystemRepresentation:(MutPtr<u8>)buffer
                           maxLength:(NSUInteger)buffer_size {
     let &NSURLHostObject::FileURL { ns_string } = env.objc.borrow(this) else {
@@ -0,0 +1,134 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSURLRequest` and `NSMutableURLRequest`.
+//!
+//! Nothing can load these yet except `UIWebView`, so only the URL matters.
+
+use super::{NSTimeInterval, NSUInteger};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+
+pub type NSURLRequestCachePolicy = NSUInteger;
+pub const NSURLRequestUseProtocolCachePolicy: NSURLRequestCachePolicy = 0;
+
+struct NSURLRequestHostObject {
+    /// `NSURL*`
+    url: id,
+    cache_policy: NSURLRequestCachePolicy,
+    timeout_interval: NSTimeInterval,
+}
+impl HostObject for NSURLRequestHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSURLRequest: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSURLRequestHostObject {
+        url: nil,
+        cache_policy: NSURLRequestUseProtocolCachePolicy,
+        timeout_interval: 60.0,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)requestWithURL:(id)url { // NSURL*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithURL:url];
+    autorelease(env, new)
+}
++ (id)requestWithURL:(id)url // NSURL*
+         cachePolicy:(NSURLRequestCachePolicy)cache_policy
+     timeoutInterval:(NSTimeInterval)timeout_interval {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithURL:url
+                                cachePolicy:cache_policy
+                            timeoutInterval:timeout_interval];
+    autorelease(env, new)
+}
+
+- (id)initWithURL:(id)url { // NSURL*
+    msg![env; this initWithURL:url
+                   cachePolicy:NSURLRequestUseProtocolCachePolicy
+               timeoutInterval:60.0f64]
+}
+- (id)initWithURL:(id)url // NSURL*
+      cachePolicy:(NSURLRequestCachePolicy)cache_policy
+  timeoutInterval:(NSTimeInterval)timeout_interval {
+    let url: id = msg![env; url copy];
+    *env.objc.borrow_mut(this) = NSURLRequestHostObject {
+        url,
+        cache_policy,
+        timeout_interval,
+    };
+    this
+}
+
+- (())dealloc {
+    let url = env.objc.borrow::<NSURLRequestHostObject>(this).url;
+    release(env, url);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(MutVoidPtr)_zone {
+    let &NSURLRequestHostObject {
+        url,
+        cache_policy,
+        timeout_interval,
+    } = env.objc.borrow(this);
+    let new: id = msg_class![env; NSMutableURLRequest alloc];
+    msg![env; new initWithURL:url cachePolicy:cache_policy timeoutInterval:timeout_interval]
+}
+
+- (id)URL {
+    env.objc.borrow::<NSURLRequestHostObject>(this).url
+}
+- (NSURLRequestCachePolicy)cachePolicy {
+    env.objc.borrow::<NSURLRequestHostObject>(this).cache_policy
+}
+- (NSTimeInterval)timeoutInterval {
+    env.objc.borrow::<NSURLRequestHostObject>(this).timeout_interval
+}
+
+@end
+
+@implementation NSMutableURLRequest: NSURLRequest
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    let &NSURLRequestHostObject {
+        url,
+        cache_policy,
+        timeout_interval,
+    } = env.objc.borrow(this);
+    let new: id = msg_class![env; NSURLRequest alloc];
+    msg![env; new initWithURL:url cachePolicy:cache_policy timeoutInterval:timeout_interval]
+}
+
+- (())setURL:(id)url { // NSURL*
+    let url: id = msg![env; url copy];
+    let old = std::mem::replace(&mut env.objc.borrow_mut::<NSURLRequestHostObject>(this).url, url);
+    release(env, old);
+}
+- (())setCachePolicy:(NSURLRequestCachePolicy)cache_policy {
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).cache_policy = cache_policy;
+}
+- (())setTimeoutInterval:(NSTimeInterval)timeout_interval {
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).timeout_interval = timeout_interval;
+}
+
+@end
+
+};
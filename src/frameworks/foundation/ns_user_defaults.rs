@@ -0,0 +1,452 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSUserDefaults`, and the app's preferences store it shares with
+//! `CFPreferences` (see [crate::frameworks::core_foundation::cf_preferences]).
+//!
+//! Only two domains exist: the app's persistent domain, which is stored as a
+//! binary property list at `Library/Preferences/<bundle identifier>.plist` in
+//! the app's home directory like on a real device, and the volatile
+//! registration domain. The persistent domain is written when it is
+//! synchronized and when the app exits.
+//!
+//! Resources:
+//! - Apple's [Preferences and Settings Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/UserDefaults/Introduction/Introduction.html)
+
+use super::ns_dictionary::{dict_from_keys_and_objects, DictionaryHostObject};
+use super::{ns_array, ns_data, ns_string, NSInteger, NSUInteger};
+use crate::fs::{GuestOpenOptions, GuestPathBuf};
+use crate::mem::ConstPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports,
+};
+use crate::Environment;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::time::{Duration, SystemTime};
+
+#[derive(Default)]
+pub struct State {
+    standard_user_defaults: Option<id>,
+    /// The app's persistent domain, loaded on first use. The values are
+    /// retained.
+    app_domain: Option<HashMap<String, id>>,
+    /// Whether the persistent domain has changed since it was last written.
+    app_domain_changed: bool,
+    /// Set by `registerDefaults:`. The values are retained.
+    registration_domain: HashMap<String, id>,
+}
+
+fn preferences_path(env: &Environment) -> GuestPathBuf {
+    env.fs.home_directory().join(format!(
+        "Library/Preferences/{}.plist",
+        env.bundle.bundle_identifier()
+    ))
+}
+
+fn is_kind_of(env: &mut Environment, object: id, class_name: &str) -> bool {
+    let class = env.objc.get_known_class(class_name, &mut env.mem);
+    msg![env; object isKindOfClass:class]
+}
+
+/// Convert a property list value to an object (+1 reference). Returns `nil`
+/// for values that have no equivalent.
+pub fn object_from_plist(env: &mut Environment, value: &plist::Value) -> id {
+    match value {
+        plist::Value::String(string) => ns_string::from_rust_string(env, string.clone()),
+        plist::Value::Boolean(boolean) => {
+            let number: id = msg_class![env; NSNumber alloc];
+            msg![env; number initWithBool:(*boolean)]
+        }
+        plist::Value::Integer(integer) => {
+            let number: id = msg_class![env; NSNumber alloc];
+            if let Some(value) = integer.as_signed() {
+                if let Ok(value) = i32::try_from(value) {
+                    msg![env; number initWithInt:value]
+                } else {
+                    msg![env; number initWithLongLong:value]
+                }
+            } else {
+                let value = integer.as_unsigned().unwrap();
+                msg![env; number initWithUnsignedLongLong:value]
+            }
+        }
+        plist::Value::Real(real) => {
+            let number: id = msg_class![env; NSNumber alloc];
+            msg![env; number initWithDouble:(*real)]
+        }
+        plist::Value::Data(bytes) => ns_data::from_bytes(env, bytes),
+        plist::Value::Date(date) => {
+            let since_1970 = match SystemTime::from(*date).duration_since(SystemTime::UNIX_EPOCH) {
+                Ok(duration) => duration.as_secs_f64(),
+                Err(e) => -e.duration().as_secs_f64(),
+            };
+            let date: id = msg_class![env; NSDate alloc];
+            msg![env; date initWithTimeIntervalSince1970:since_1970]
+        }
+        plist::Value::Array(values) => {
+            let objects = values
+                .iter()
+                .map(|value| object_from_plist(env, value))
+                .filter(|&object| object != nil)
+                .collect();
+            ns_array::from_vec(env, objects)
+        }
+        plist::Value::Dictionary(dict) => {
+            let mut keys_and_objects = Vec::new();
+            for (key, value) in dict {
+                let object = object_from_plist(env, value);
+                if object == nil {
+                    continue;
+                }
+                let key = ns_string::from_rust_string(env, key.clone());
+                keys_and_objects.push((key, object));
+            }
+            let dict = dict_from_keys_and_objects(env, &keys_and_objects);
+            for (key, object) in keys_and_objects {
+                release(env, key);
+                release(env, object);
+            }
+            dict
+        }
+        _ => {
+            log!(
+                "Warning: ignoring unsupported property list value {:?}",
+                value
+            );
+            nil
+        }
+    }
+}
+
+/// Convert an object to a property list value. Returns [None] if it isn't a
+/// property list object.
+fn object_to_plist(env: &mut Environment, object: id) -> Option<plist::Value> {
+    if object == nil {
+        return None;
+    }
+    if is_kind_of(env, object, "NSString") {
+        let string = ns_string::to_rust_string(env, object).into_owned();
+        Some(plist::Value::String(string))
+    } else if is_kind_of(env, object, "NSNumber") {
+        let objc_type: ConstPtr<u8> = msg![env; object objCType];
+        Some(match env.mem.read(objc_type) {
+            b'c' | b'B' => plist::Value::Boolean(msg![env; object boolValue]),
+            b'f' | b'd' => plist::Value::Real(msg![env; object doubleValue]),
+            b'Q' => {
+                let value: u64 = msg![env; object unsignedLongLongValue];
+                plist::Value::Integer(value.into())
+            }
+            _ => {
+                let value: i64 = msg![env; object longLongValue];
+                plist::Value::Integer(value.into())
+            }
+        })
+    } else if is_kind_of(env, object, "NSData") {
+        Some(plist::Value::Data(ns_data::to_vec(env, object)))
+    } else if is_kind_of(env, object, "NSDate") {
+        let since_1970: f64 = msg![env; object timeIntervalSince1970];
+        let time = if since_1970 >= 0.0 {
+            SystemTime::UNIX_EPOCH + Duration::from_secs_f64(since_1970)
+        } else {
+            SystemTime::UNIX_EPOCH - Duration::from_secs_f64(-since_1970)
+        };
+        Some(plist::Value::Date(time.into()))
+    } else if is_kind_of(env, object, "NSArray") {
+        let count: NSUInteger = msg![env; object count];
+        let mut values = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let item: id = msg![env; object objectAtIndex:i];
+            values.push(object_to_plist(env, item)?);
+        }
+        Some(plist::Value::Array(values))
+    } else if is_kind_of(env, object, "NSDictionary") {
+        let keys: Vec<id> = env
+            .objc
+            .borrow::<DictionaryHostObject>(object)
+            .iter_keys()
+            .collect();
+        let mut dict = plist::Dictionary::new();
+        for key in keys {
+            if !is_kind_of(env, key, "NSString") {
+                return None;
+            }
+            let item: id = msg![env; object objectForKey:key];
+            let key = ns_string::to_rust_string(env, key).into_owned();
+            dict.insert(key, object_to_plist(env, item)?);
+        }
+        Some(plist::Value::Dictionary(dict))
+    } else {
+        None
+    }
+}
+
+/// Get the app's persistent domain, reading it from disk if that hasn't
+/// happened yet.
+fn app_domain(env: &mut Environment) -> &mut HashMap<String, id> {
+    if env
+        .framework_state
+        .foundation
+        .ns_user_defaults
+        .app_domain
+        .is_none()
+    {
+        let path = preferences_path(env);
+        let mut domain = HashMap::new();
+        if let Ok(bytes) = env.fs.read(&path) {
+            match plist::Value::from_reader(Cursor::new(bytes)) {
+                Ok(plist::Value::Dictionary(dict)) => {
+                    for (key, value) in dict {
+                        let object = object_from_plist(env, &value);
+                        if object != nil {
+                            domain.insert(key, object);
+                        }
+                    }
+                }
+                _ => {
+                    log!("Warning: couldn't parse preferences file {:?}", path);
+                }
+            }
+        }
+        log_dbg!("Loaded {} preferences from {:?}", domain.len(), path);
+        env.framework_state.foundation.ns_user_defaults.app_domain = Some(domain);
+    }
+    env.framework_state
+        .foundation
+        .ns_user_defaults
+        .app_domain
+        .as_mut()
+        .unwrap()
+}
+
+/// Shortcut for host code, used by `CFPreferencesCopyAppValue` and
+/// `NSUserDefaults`: look up a preference in the app's persistent domain and
+/// then the registration domain. The value is not retained.
+pub fn get_value(env: &mut Environment, key: &str) -> id {
+    if let Some(&value) = app_domain(env).get(key) {
+        return value;
+    }
+    let state = &env.framework_state.foundation.ns_user_defaults;
+    state.registration_domain.get(key).copied().unwrap_or(nil)
+}
+
+/// Shortcut for host code, used by `CFPreferencesSetAppValue` and
+/// `NSUserDefaults`: set a preference in the app's persistent domain, or remove
+/// it if `value` is `nil`. The value is copied.
+pub fn set_value(env: &mut Environment, key: &str, value: id) {
+    if value != nil && object_to_plist(env, value).is_none() {
+        log!(
+            "Warning: attempt to set non-property-list object {:?} as preference {:?}, ignoring",
+            value,
+            key
+        );
+        return;
+    }
+    let value: id = if value == nil {
+        nil
+    } else {
+        msg![env; value copy]
+    };
+    let old_value = if value == nil {
+        app_domain(env).remove(key)
+    } else {
+        app_domain(env).insert(key.to_string(), value)
+    };
+    if let Some(old_value) = old_value {
+        release(env, old_value);
+    }
+    env.framework_state
+        .foundation
+        .ns_user_defaults
+        .app_domain_changed = true;
+}
+
+/// Shortcut for host code, used by `CFPreferencesAppSynchronize` and
+/// `NSUserDefaults`: write the app's persistent domain to disk if it has
+/// changed.
+pub fn synchronize(env: &mut Environment) -> bool {
+    let state = &env.framework_state.foundation.ns_user_defaults;
+    if !state.app_domain_changed {
+        return true;
+    }
+    let domain: Vec<(String, id)> = state
+        .app_domain
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|(key, &value)| (key.clone(), value))
+        .collect();
+    let mut dict = plist::Dictionary::new();
+    for (key, value) in domain {
+        dict.insert(key, object_to_plist(env, value).unwrap());
+    }
+
+    let path = preferences_path(env);
+    let mut options = GuestOpenOptions::new();
+    options.write().create().truncate();
+    let Ok(file) = env.fs.open_with_options(&path, options) else {
+        log!("Warning: couldn't open preferences file {:?}", path);
+        return false;
+    };
+    if let Err(e) = plist::Value::Dictionary(dict).to_writer_binary(file) {
+        log!("Warning: couldn't write preferences file {:?}: {}", path, e);
+        return false;
+    }
+    log_dbg!("Wrote preferences to {:?}", path);
+    env.framework_state
+        .foundation
+        .ns_user_defaults
+        .app_domain_changed = false;
+    true
+}
+
+/// Get a value if it is of the named class.
+fn get_value_of_kind(env: &mut Environment, key: id, class_name: &str) -> id {
+    let key = ns_string::to_rust_string(env, key);
+    let value = get_value(env, &key);
+    if value != nil && is_kind_of(env, value, class_name) {
+        value
+    } else {
+        nil
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSUserDefaults: NSObject
+
++ (id)standardUserDefaults {
+    if let Some(existing) = env.framework_state.foundation.ns_user_defaults.standard_user_defaults {
+        return existing;
+    }
+    let new: id = msg![env; this new];
+    env.framework_state.foundation.ns_user_defaults.standard_user_defaults = Some(new);
+    new
+}
++ (())resetStandardUserDefaults {
+    synchronize(env);
+}
+
+// Releasing the standard user defaults is not allowed, but the state is shared
+// anyway.
+
+- (id)objectForKey:(id)key { // NSString*
+    let key = ns_string::to_rust_string(env, key);
+    get_value(env, &key)
+}
+- (())setObject:(id)value forKey:(id)key { // NSString*
+    let key = ns_string::to_rust_string(env, key);
+    set_value(env, &key, value);
+}
+- (())removeObjectForKey:(id)key { // NSString*
+    let key = ns_string::to_rust_string(env, key);
+    set_value(env, &key, nil);
+}
+
+- (id)stringForKey:(id)key { // NSString*
+    get_value_of_kind(env, key, "NSString")
+}
+- (id)arrayForKey:(id)key { // NSString*
+    get_value_of_kind(env, key, "NSArray")
+}
+- (id)dictionaryForKey:(id)key { // NSString*
+    get_value_of_kind(env, key, "NSDictionary")
+}
+- (id)dataForKey:(id)key { // NSString*
+    get_value_of_kind(env, key, "NSData")
+}
+
+// TODO: the scalar getters should also parse strings
+- (NSInteger)integerForKey:(id)key { // NSString*
+    let number = get_value_of_kind(env, key, "NSNumber");
+    if number == nil {
+        0
+    } else {
+        msg![env; number integerValue]
+    }
+}
+- (f32)floatForKey:(id)key { // NSString*
+    let number = get_value_of_kind(env, key, "NSNumber");
+    if number == nil {
+        0.0
+    } else {
+        msg![env; number floatValue]
+    }
+}
+- (f64)doubleForKey:(id)key { // NSString*
+    let number = get_value_of_kind(env, key, "NSNumber");
+    if number == nil {
+        0.0
+    } else {
+        msg![env; number doubleValue]
+    }
+}
+- (bool)boolForKey:(id)key { // NSString*
+    let number = get_value_of_kind(env, key, "NSNumber");
+    number != nil && msg![env; number boolValue]
+}
+
+- (())setInteger:(NSInteger)value forKey:(id)key { // NSString*
+    let number: id = msg_class![env; NSNumber numberWithInteger:value];
+    () = msg![env; this setObject:number forKey:key];
+}
+- (())setFloat:(f32)value forKey:(id)key { // NSString*
+    let number: id = msg_class![env; NSNumber numberWithFloat:value];
+    () = msg![env; this setObject:number forKey:key];
+}
+- (())setDouble:(f64)value forKey:(id)key { // NSString*
+    let number: id = msg_class![env; NSNumber numberWithDouble:value];
+    () = msg![env; this setObject:number forKey:key];
+}
+- (())setBool:(bool)value forKey:(id)key { // NSString*
+    let number: id = msg_class![env; NSNumber numberWithBool:value];
+    () = msg![env; this setObject:number forKey:key];
+}
+
+- (())registerDefaults:(id)dict { // NSDictionary*
+    let keys: Vec<id> = env
+        .objc
+        .borrow::<DictionaryHostObject>(dict)
+        .iter_keys()
+        .collect();
+    for key in keys {
+        let value: id = msg![env; dict objectForKey:key];
+        let value = retain(env, value);
+        let key = ns_string::to_rust_string(env, key).into_owned();
+        let state = &mut env.framework_state.foundation.ns_user_defaults;
+        if let Some(old_value) = state.registration_domain.insert(key, value) {
+            release(env, old_value);
+        }
+    }
+}
+
+- (id)dictionaryRepresentation {
+    let mut values: HashMap<String, id> = env
+        .framework_state
+        .foundation
+        .ns_user_defaults
+        .registration_domain
+        .clone();
+    values.extend(app_domain(env).iter().map(|(key, &value)| (key.clone(), value)));
+    let keys_and_objects: Vec<(id, id)> = values
+        .into_iter()
+        .map(|(key, value)| (ns_string::from_rust_string(env, key), value))
+        .collect();
+    let dict = dict_from_keys_and_objects(env, &keys_and_objects);
+    for (key, _) in keys_and_objects {
+        release(env, key);
+    }
+    autorelease(env, dict)
+}
+
+- (bool)synchronize {
+    synchronize(env)
+}
+
+@end
+
+};
@@ -0,0 +1,196 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSUUID`, and UUID generation shared with `CFUUID`.
+//!
+//! UUIDs are random (version 4) unless the `--uuid-seed=` option is used, in
+//! which case the same sequence of UUIDs is generated on every run.
+
+use super::{ns_string, NSUInteger};
+use crate::mem::{ConstPtr, MutPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, Class, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+pub type UUIDBytes = [u8; 16];
+
+#[derive(Default)]
+pub struct State {
+    /// State of the generator used when UUIDs should be deterministic.
+    seeded_state: Option<u64>,
+    /// Counter to make UUIDs generated in quick succession differ.
+    counter: u64,
+}
+
+/// SplitMix64, see <https://prng.di.unimi.it/splitmix64.c>.
+pub fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// Generate a new version 4 (random) UUID.
+pub fn generate(env: &mut Environment) -> UUIDBytes {
+    let seed = env.options.uuid_seed;
+    let state = &mut env.framework_state.foundation.ns_uuid;
+    let (a, b) = if let Some(seed) = seed {
+        let seeded_state = state.seeded_state.get_or_insert(seed);
+        (splitmix64(seeded_state), splitmix64(seeded_state))
+    } else {
+        // The standard library has no random number generator, but the keys of
+        // RandomState are random, so hashing with them gives random numbers.
+        state.counter += 1;
+        let random = RandomState::new();
+        let hash = |n: u64| {
+            let mut hasher = random.build_hasher();
+            hasher.write_u64(n);
+            hasher.finish()
+        };
+        (hash(state.counter), hash(!state.counter))
+    };
+    let mut bytes: UUIDBytes = [0; 16];
+    bytes[..8].copy_from_slice(&a.to_be_bytes());
+    bytes[8..].copy_from_slice(&b.to_be_bytes());
+    bytes[6] = (bytes[6] & 0x0F) | 0x40; // version 4
+    bytes[8] = (bytes[8] & 0x3F) | 0x80; // RFC 4122 variant
+    bytes
+}
+
+/// Format a UUID like `68753A44-4D6F-1226-9C60-0050E4C00067`.
+pub fn to_string(bytes: &UUIDBytes) -> String {
+    let mut string = String::with_capacity(36);
+    for (i, byte) in bytes.iter().enumerate() {
+        if matches!(i, 4 | 6 | 8 | 10) {
+            string.push('-');
+        }
+        string.push_str(&format!("{:02X}", byte));
+    }
+    string
+}
+
+/// Parse a UUID string in the format produced by [to_string]. Lowercase is
+/// accepted too.
+pub fn from_string(string: &str) -> Option<UUIDBytes> {
+    if string.len() != 36 {
+        return None;
+    }
+    let mut bytes: UUIDBytes = [0; 16];
+    let mut digits = string.char_indices().filter(|&(i, c)| {
+        // Hyphens must be in the right places and nowhere else.
+        !(matches!(i, 8 | 13 | 18 | 23) && c == '-')
+    });
+    for byte in bytes.iter_mut() {
+        let (_, high) = digits.next()?;
+        let (_, low) = digits.next()?;
+        *byte = (high.to_digit(16)? << 4 | low.to_digit(16)?) as u8;
+    }
+    digits.next().is_none().then_some(bytes)
+}
+
+struct NSUUIDHostObject {
+    bytes: UUIDBytes,
+}
+impl HostObject for NSUUIDHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSUUID: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSUUIDHostObject { bytes: [0; 16] });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)UUID {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new init];
+    autorelease(env, new)
+}
+
+- (id)init {
+    env.objc.borrow_mut::<NSUUIDHostObject>(this).bytes = generate(env);
+    this
+}
+
+- (id)initWithUUIDString:(id)string { // NSString*
+    let string = ns_string::to_rust_string(env, string);
+    if let Some(bytes) = from_string(&string) {
+        env.objc.borrow_mut::<NSUUIDHostObject>(this).bytes = bytes;
+        this
+    } else {
+        release(env, this);
+        nil
+    }
+}
+
+- (id)initWithUUIDBytes:(ConstPtr<u8>)bytes { // const uuid_t
+    let bytes = env.mem.bytes_at(bytes, 16).try_into().unwrap();
+    env.objc.borrow_mut::<NSUUIDHostObject>(this).bytes = bytes;
+    this
+}
+
+- (())getUUIDBytes:(MutPtr<u8>)bytes { // uuid_t
+    let uuid = env.objc.borrow::<NSUUIDHostObject>(this).bytes;
+    env.mem
+        .bytes_at_mut(bytes, 16)
+        .copy_from_slice(&uuid);
+}
+
+- (id)UUIDString {
+    let string = to_string(&env.objc.borrow::<NSUUIDHostObject>(this).bytes);
+    let string = ns_string::from_rust_string(env, string);
+    autorelease(env, string)
+}
+
+- (NSUInteger)hash {
+    super::hash_helper(&env.objc.borrow::<NSUUIDHostObject>(this).bytes)
+}
+- (bool)isEqual:(id)other {
+    if this == other {
+        return true;
+    }
+    let class: Class = msg_class![env; NSUUID class];
+    if other == nil || !msg![env; other isKindOfClass:class] {
+        return false;
+    }
+    let a = env.objc.borrow::<NSUUIDHostObject>(this).bytes;
+    let b = env.objc.borrow::<NSUUIDHostObject>(other).bytes;
+    a == b
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    msg![env; this retain]
+}
+
+@end
+
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuid_strings() {
+        let bytes: UUIDBytes = [
+            0x68, 0x75, 0x3A, 0x44, 0x4D, 0x6F, 0x12, 0x26, 0x9C, 0x60, 0x00, 0x50, 0xE4, 0xC0,
+            0x00, 0x67,
+        ];
+        let string = to_string(&bytes);
+        assert_eq!(string, "68753A44-4D6F-1226-9C60-0050E4C00067");
+        assert_eq!(from_string(&string), Some(bytes));
+        assert_eq!(from_string(&string.to_lowercase()), Some(bytes));
+        assert_eq!(from_string("68753A44-4D6F-1226-9C600-050E4C00067"), None);
+        assert_eq!(from_string("68753A44-4D6F-1226-9C60-0050E4C0006"), None);
+    }
+}
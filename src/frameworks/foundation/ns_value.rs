@@ -3,32 +3,271 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! The `NSValue` class cluster, including `NSNumber`.
+//! The `NSValue` class cluster, including `NSNumber` and `NSDecimalNumber`.
 
-use super::NSUInteger;
-use crate::mem::MutVoidPtr;
+use super::{
+    ns_string, NSComparisonResult, NSInteger, NSOrderedAscending, NSOrderedDescending,
+    NSOrderedSame, NSRange, NSUInteger,
+};
+use crate::frameworks::core_animation::ca_transform_3d::CATransform3D;
+use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, SafeRead};
 use crate::objc::{
-    autorelease, id, msg, msg_class, objc_classes, retain, Class, ClassExports, HostObject,
+    autorelease, id, msg, msg_class, nil, objc_classes, retain, Class, ClassExports, HostObject,
 };
+use crate::Environment;
+use std::cmp::Ordering;
+
+const CGPOINT_ENCODING: &str = "{CGPoint=ff}";
+const CGSIZE_ENCODING: &str = "{CGSize=ff}";
+const CGRECT_ENCODING: &str = "{CGRect={CGPoint=ff}{CGSize=ff}}";
+const CATRANSFORM3D_ENCODING: &str = "{CATransform3D=ffffffffffffffff}";
+const NSRANGE_ENCODING: &str = "{_NSRange=II}";
+const POINTER_ENCODING: &str = "^v";
+const OBJECT_ENCODING: &str = "@";
 
+/// Belongs to NSValue (but not NSNumber).
+struct NSValueHostObject {
+    bytes: Vec<u8>,
+    /// Objective-C type encoding, e.g. `{CGPoint=ff}`.
+    objc_type: String,
+    /// Guest copy of `objc_type`, created on demand.
+    objc_type_c_string: Option<MutPtr<u8>>,
+}
+impl HostObject for NSValueHostObject {}
+
+#[derive(Copy, Clone)]
 enum NSNumberHostObject {
     Bool(bool),
+    Int(i32),
+    UnsignedInt(u32),
+    LongLong(i64),
+    UnsignedLongLong(u64),
+    Float(f32),
+    Double(f64),
 }
 impl HostObject for NSNumberHostObject {}
 
+impl NSNumberHostObject {
+    fn as_i64(self) -> i64 {
+        match self {
+            Self::Bool(value) => value as i64,
+            Self::Int(value) => value as i64,
+            Self::UnsignedInt(value) => value as i64,
+            Self::LongLong(value) => value,
+            Self::UnsignedLongLong(value) => value as i64,
+            Self::Float(value) => value as i64,
+            Self::Double(value) => value as i64,
+        }
+    }
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::Float(value) => value as f64,
+            Self::Double(value) => value,
+            Self::UnsignedLongLong(value) => value as f64,
+            _ => self.as_i64() as f64,
+        }
+    }
+    fn is_floating_point(self) -> bool {
+        matches!(self, Self::Float(_) | Self::Double(_))
+    }
+    fn objc_type(self) -> &'static [u8] {
+        match self {
+            // BOOL is a signed char
+            Self::Bool(_) => b"c\0",
+            Self::Int(_) => b"i\0",
+            Self::UnsignedInt(_) => b"I\0",
+            Self::LongLong(_) => b"q\0",
+            Self::UnsignedLongLong(_) => b"Q\0",
+            Self::Float(_) => b"f\0",
+            Self::Double(_) => b"d\0",
+        }
+    }
+    fn to_bytes(self) -> Vec<u8> {
+        match self {
+            Self::Bool(value) => vec![value as u8],
+            Self::Int(value) => value.to_le_bytes().to_vec(),
+            Self::UnsignedInt(value) => value.to_le_bytes().to_vec(),
+            Self::LongLong(value) => value.to_le_bytes().to_vec(),
+            Self::UnsignedLongLong(value) => value.to_le_bytes().to_vec(),
+            Self::Float(value) => value.to_le_bytes().to_vec(),
+            Self::Double(value) => value.to_le_bytes().to_vec(),
+        }
+    }
+    fn compare(self, other: Self) -> Ordering {
+        if self.is_floating_point() || other.is_floating_point() {
+            self.as_f64().total_cmp(&other.as_f64())
+        } else {
+            let a = match self {
+                Self::UnsignedLongLong(value) => value as i128,
+                _ => self.as_i64() as i128,
+            };
+            let b = match other {
+                Self::UnsignedLongLong(value) => value as i128,
+                _ => other.as_i64() as i128,
+            };
+            a.cmp(&b)
+        }
+    }
+    fn format(self) -> String {
+        match self {
+            Self::Bool(value) => (value as i32).to_string(),
+            Self::Int(value) => value.to_string(),
+            Self::UnsignedInt(value) => value.to_string(),
+            Self::LongLong(value) => value.to_string(),
+            Self::UnsignedLongLong(value) => value.to_string(),
+            Self::Float(value) => value.to_string(),
+            Self::Double(value) => value.to_string(),
+        }
+    }
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
-// NSValue is an abstract class. None of the things it should provide are
-// implemented here yet (TODO).
+// NSValue is an abstract class in Apple's implementation, but here it is
+// concrete and can hold any value. NSNumber overrides everything.
 @implementation NSValue: NSObject
 
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(NSValueHostObject {
+        bytes: Vec::new(),
+        objc_type: String::new(),
+        objc_type_c_string: None,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)valueWithBytes:(ConstVoidPtr)value
+            objCType:(ConstPtr<u8>)type_ {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithBytes:value objCType:type_];
+    autorelease(env, new)
+}
++ (id)value:(ConstVoidPtr)value
+withObjCType:(ConstPtr<u8>)type_ {
+    msg![env; this valueWithBytes:value objCType:type_]
+}
+
++ (id)valueWithPointer:(ConstVoidPtr)pointer {
+    let bytes = pointer.to_bits().to_le_bytes().to_vec();
+    new_value(env, bytes, POINTER_ENCODING)
+}
++ (id)valueWithNonretainedObject:(id)object {
+    let bytes = object.to_bits().to_le_bytes().to_vec();
+    new_value(env, bytes, OBJECT_ENCODING)
+}
++ (id)valueWithRange:(NSRange)range {
+    new_value(env, struct_to_bytes(range), NSRANGE_ENCODING)
+}
+// These come from a category in UIKit (UIGeometry).
++ (id)valueWithCGPoint:(CGPoint)point {
+    new_value(env, struct_to_bytes(point), CGPOINT_ENCODING)
+}
++ (id)valueWithCGSize:(CGSize)size {
+    new_value(env, struct_to_bytes(size), CGSIZE_ENCODING)
+}
++ (id)valueWithCGRect:(CGRect)rect {
+    new_value(env, struct_to_bytes(rect), CGRECT_ENCODING)
+}
+// This comes from a category in Core Animation.
++ (id)valueWithCATransform3D:(CATransform3D)transform {
+    new_value(env, struct_to_bytes(transform), CATRANSFORM3D_ENCODING)
+}
+
+- (id)initWithBytes:(ConstVoidPtr)value
+           objCType:(ConstPtr<u8>)type_ {
+    let objc_type = env.mem.cstr_at_utf8(type_).to_string();
+    let size = size_of_type_encoding(&objc_type);
+    let bytes = env.mem.bytes_at(value.cast(), size).to_vec();
+    let host_object = env.objc.borrow_mut::<NSValueHostObject>(this);
+    host_object.bytes = bytes;
+    host_object.objc_type = objc_type;
+    this
+}
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<NSValueHostObject>(this);
+    if let Some(c_string) = host_object.objc_type_c_string.take() {
+        env.mem.free(c_string.cast());
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
 // NSCopying implementation
 - (id)copyWithZone:(MutVoidPtr)_zone {
     retain(env, this)
 }
 
+- (ConstPtr<u8>)objCType {
+    let host_object = env.objc.borrow_mut::<NSValueHostObject>(this);
+    if let Some(c_string) = host_object.objc_type_c_string {
+        return c_string.cast_const();
+    }
+    let objc_type = host_object.objc_type.clone();
+    let c_string = env.mem.alloc_and_write_cstr(objc_type.as_bytes());
+    env.objc.borrow_mut::<NSValueHostObject>(this).objc_type_c_string = Some(c_string);
+    c_string.cast_const()
+}
+- (())getValue:(MutVoidPtr)buffer {
+    let bytes = env.objc.borrow::<NSValueHostObject>(this).bytes.clone();
+    env.mem
+        .bytes_at_mut(buffer.cast(), bytes.len() as GuestUSize)
+        .copy_from_slice(&bytes);
+}
+
+- (ConstVoidPtr)pointerValue {
+    let bytes = &env.objc.borrow::<NSValueHostObject>(this).bytes;
+    ConstVoidPtr::from_bits(u32::from_le_bytes(bytes[..4].try_into().unwrap()))
+}
+- (id)nonretainedObjectValue {
+    let bytes = &env.objc.borrow::<NSValueHostObject>(this).bytes;
+    id::from_bits(u32::from_le_bytes(bytes[..4].try_into().unwrap()))
+}
+- (NSRange)rangeValue {
+    value_as_struct(env, this, NSRANGE_ENCODING)
+}
+- (CGPoint)CGPointValue {
+    value_as_struct(env, this, CGPOINT_ENCODING)
+}
+- (CGSize)CGSizeValue {
+    value_as_struct(env, this, CGSIZE_ENCODING)
+}
+- (CGRect)CGRectValue {
+    value_as_struct(env, this, CGRECT_ENCODING)
+}
+- (CATransform3D)CATransform3DValue {
+    value_as_struct(env, this, CATRANSFORM3D_ENCODING)
+}
+
+- (NSUInteger)hash {
+    let host_object = env.objc.borrow::<NSValueHostObject>(this);
+    super::hash_helper(&host_object.bytes)
+}
+- (bool)isEqualTo:(id)other {
+    if this == other {
+        return true;
+    }
+    let class: Class = msg_class![env; NSValue class];
+    if !msg![env; other isKindOfClass:class] {
+        return false;
+    }
+    msg![env; this isEqualToValue:other]
+}
+- (bool)isEqualToValue:(id)other { // NSValue*
+    if this == other {
+        return true;
+    }
+    let number_class: Class = msg_class![env; NSNumber class];
+    if other == nil || msg![env; other isKindOfClass:number_class] {
+        return false;
+    }
+    let a = env.objc.borrow::<NSValueHostObject>(this);
+    let b = env.objc.borrow::<NSValueHostObject>(other);
+    a.objc_type == b.objc_type && a.bytes == b.bytes
+}
+
 @end
 
 // NSNumber is not an abstract class.
@@ -39,15 +278,52 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
+// TODO: for greater efficiency we could return static-lifetime values
 + (id)numberWithBool:(bool)value {
-    // TODO: for greater efficiency we could return a static-lifetime value
-
-    let new: id = msg![env; this alloc];
-    let new: id = msg![env; new initWithBool:value];
-    autorelease(env, new)
+    new_number(env, this, NSNumberHostObject::Bool(value))
+}
++ (id)numberWithChar:(i8)value {
+    new_number(env, this, NSNumberHostObject::Int(value.into()))
+}
++ (id)numberWithUnsignedChar:(u8)value {
+    new_number(env, this, NSNumberHostObject::UnsignedInt(value.into()))
+}
++ (id)numberWithShort:(i16)value {
+    new_number(env, this, NSNumberHostObject::Int(value.into()))
+}
++ (id)numberWithUnsignedShort:(u16)value {
+    new_number(env, this, NSNumberHostObject::UnsignedInt(value.into()))
+}
++ (id)numberWithInt:(i32)value {
+    new_number(env, this, NSNumberHostObject::Int(value))
+}
++ (id)numberWithUnsignedInt:(u32)value {
+    new_number(env, this, NSNumberHostObject::UnsignedInt(value))
+}
++ (id)numberWithLong:(i32)value {
+    new_number(env, this, NSNumberHostObject::Int(value))
+}
++ (id)numberWithUnsignedLong:(u32)value {
+    new_number(env, this, NSNumberHostObject::UnsignedInt(value))
+}
++ (id)numberWithInteger:(NSInteger)value {
+    new_number(env, this, NSNumberHostObject::Int(value))
+}
++ (id)numberWithUnsignedInteger:(NSUInteger)value {
+    new_number(env, this, NSNumberHostObject::UnsignedInt(value))
+}
++ (id)numberWithLongLong:(i64)value {
+    new_number(env, this, NSNumberHostObject::LongLong(value))
+}
++ (id)numberWithUnsignedLongLong:(u64)value {
+    new_number(env, this, NSNumberHostObject::UnsignedLongLong(value))
+}
++ (id)numberWithFloat:(f32)value {
+    new_number(env, this, NSNumberHostObject::Float(value))
+}
++ (id)numberWithDouble:(f64)value {
+    new_number(env, this, NSNumberHostObject::Double(value))
 }
-
-// TODO: types other than booleans
 
 - (id)initWithBool:(bool)value {
     *env.objc.borrow_mut::<NSNumberHostObject>(this) = NSNumberHostObject::Bool(
@@ -55,10 +331,53 @@ pub const CLASSES: ClassExports = objc_classes! {
     );
     this
 }
+- (id)initWithInt:(i32)value {
+    *env.objc.borrow_mut::<NSNumberHostObject>(this) = NSNumberHostObject::Int(value);
+    this
+}
+- (id)initWithUnsignedInt:(u32)value {
+    *env.objc.borrow_mut::<NSNumberHostObject>(this) = NSNumberHostObject::UnsignedInt(value);
+    this
+}
+- (id)initWithInteger:(NSInteger)value {
+    msg![env; this initWithInt:value]
+}
+- (id)initWithUnsignedInteger:(NSUInteger)value {
+    msg![env; this initWithUnsignedInt:value]
+}
+- (id)initWithLongLong:(i64)value {
+    *env.objc.borrow_mut::<NSNumberHostObject>(this) = NSNumberHostObject::LongLong(value);
+    this
+}
+- (id)initWithUnsignedLongLong:(u64)value {
+    *env.objc.borrow_mut::<NSNumberHostObject>(this) = NSNumberHostObject::UnsignedLongLong(
+        value,
+    );
+    this
+}
+- (id)initWithFloat:(f32)value {
+    *env.objc.borrow_mut::<NSNumberHostObject>(this) = NSNumberHostObject::Float(value);
+    this
+}
+- (id)initWithDouble:(f64)value {
+    *env.objc.borrow_mut::<NSNumberHostObject>(this) = NSNumberHostObject::Double(value);
+    this
+}
+
+// Overridden because NSValue's implementation expects its own host object.
+- (())dealloc {
+    env.objc.dealloc_object(this, &mut env.mem)
+}
 
 - (NSUInteger)hash {
-    let &NSNumberHostObject::Bool(value) = env.objc.borrow(this);
-    super::hash_helper(&value)
+    let &number = env.objc.borrow::<NSNumberHostObject>(this);
+    // Equal numbers must have equal hashes regardless of type.
+    let value = number.as_f64();
+    if value.fract() == 0.0 {
+        super::hash_helper(&number.as_i64())
+    } else {
+        super::hash_helper(&value.to_bits())
+    }
 }
 - (bool)isEqualTo:(id)other {
     if this == other {
@@ -68,13 +387,246 @@ pub const CLASSES: ClassExports = objc_classes! {
     if !msg![env; other isKindOfClass:class] {
         return false;
     }
-    let &NSNumberHostObject::Bool(a) = env.objc.borrow(this);
-    let &NSNumberHostObject::Bool(b) = env.objc.borrow(other);
-    a == b
+    msg![env; this isEqualToNumber:other]
+}
+- (bool)isEqualToNumber:(id)other { // NSNumber*
+    let result: NSComparisonResult = msg![env; this compare:other];
+    result == NSOrderedSame
+}
+- (NSComparisonResult)compare:(id)other { // NSNumber*
+    let &a = env.objc.borrow::<NSNumberHostObject>(this);
+    let &b = env.objc.borrow::<NSNumberHostObject>(other);
+    match a.compare(b) {
+        Ordering::Less => NSOrderedAscending,
+        Ordering::Equal => NSOrderedSame,
+        Ordering::Greater => NSOrderedDescending,
+    }
 }
 
-// TODO: accessors etc
+- (ConstPtr<u8>)objCType {
+    // Apple's implementation returns a static string too.
+    let &number = env.objc.borrow::<NSNumberHostObject>(this);
+    let objc_type = number.objc_type();
+    let state = &mut env.framework_state.foundation.ns_value;
+    let ptr = *state.number_type_strings.entry(objc_type).or_insert_with(|| {
+        env.mem.alloc_and_write_cstr(&objc_type[..objc_type.len() - 1])
+    });
+    ptr.cast_const()
+}
+- (())getValue:(MutVoidPtr)buffer {
+    let &number = env.objc.borrow::<NSNumberHostObject>(this);
+    let bytes = number.to_bytes();
+    env.mem
+        .bytes_at_mut(buffer.cast(), bytes.len() as GuestUSize)
+        .copy_from_slice(&bytes);
+}
+
+- (bool)boolValue {
+    let &number = env.objc.borrow::<NSNumberHostObject>(this);
+    if number.is_floating_point() {
+        number.as_f64() != 0.0
+    } else {
+        number.as_i64() != 0
+    }
+}
+- (i8)charValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as i8
+}
+- (u8)unsignedCharValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as u8
+}
+- (i16)shortValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as i16
+}
+- (u16)unsignedShortValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as u16
+}
+- (i32)intValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as i32
+}
+- (u32)unsignedIntValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as u32
+}
+- (i32)longValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as i32
+}
+- (u32)unsignedLongValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as u32
+}
+- (NSInteger)integerValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as NSInteger
+}
+- (NSUInteger)unsignedIntegerValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64() as NSUInteger
+}
+- (i64)longLongValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_i64()
+}
+- (u64)unsignedLongLongValue {
+    match *env.objc.borrow::<NSNumberHostObject>(this) {
+        NSNumberHostObject::UnsignedLongLong(value) => value,
+        number => number.as_i64() as u64,
+    }
+}
+- (f32)floatValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_f64() as f32
+}
+- (f64)doubleValue {
+    env.objc.borrow::<NSNumberHostObject>(this).as_f64()
+}
+
+- (id)stringValue {
+    let &number = env.objc.borrow::<NSNumberHostObject>(this);
+    let string = ns_string::from_rust_string(env, number.format());
+    autorelease(env, string)
+}
+- (id)description {
+    msg![env; this stringValue]
+}
+
+@end
+
+// This is backed by a double, so it isn't really decimal, but that's enough for
+// the prices StoreKit gives apps.
+@implementation NSDecimalNumber: NSNumber
+
++ (id)decimalNumberWithString:(id)string { // NSString*
+    let string = ns_string::to_rust_string(env, string);
+    // Apple's implementation returns NaN ("notANumber") for invalid strings.
+    let value = string.trim().parse().unwrap_or(f64::NAN);
+    new_number(env, this, NSNumberHostObject::Double(value))
+}
 
 @end
 
 };
+
+#[derive(Default)]
+pub struct State {
+    /// Guest C strings returned by `[NSNumber objCType]`.
+    number_type_strings: std::collections::HashMap<&'static [u8], MutPtr<u8>>,
+}
+
+fn new_number(env: &mut Environment, class: Class, number: NSNumberHostObject) -> id {
+    let new: id = msg![env; class alloc];
+    *env.objc.borrow_mut::<NSNumberHostObject>(new) = number;
+    autorelease(env, new)
+}
+
+fn new_value(env: &mut Environment, bytes: Vec<u8>, objc_type: &str) -> id {
+    let new: id = msg_class![env; NSValue alloc];
+    let host_object = env.objc.borrow_mut::<NSValueHostObject>(new);
+    host_object.bytes = bytes;
+    host_object.objc_type = objc_type.to_string();
+    autorelease(env, new)
+}
+
+fn struct_to_bytes<T: SafeRead>(value: T) -> Vec<u8> {
+    let size = std::mem::size_of::<T>();
+    // SAFETY: SafeRead types are plain data without padding.
+    unsafe { std::slice::from_raw_parts(&value as *const T as *const u8, size) }.to_vec()
+}
+
+fn value_as_struct<T: SafeRead>(env: &mut Environment, value: id, objc_type: &str) -> T {
+    let host_object = env.objc.borrow::<NSValueHostObject>(value);
+    assert!(
+        host_object.objc_type == objc_type,
+        "NSValue {:?} has type {:?}, not {:?}",
+        value,
+        host_object.objc_type,
+        objc_type
+    );
+    assert!(host_object.bytes.len() == std::mem::size_of::<T>());
+    // SAFETY: SafeRead types can have any bit pattern, and the size was
+    // checked.
+    unsafe { std::ptr::read_unaligned(host_object.bytes.as_ptr() as *const T) }
+}
+
+/// Get the size in bytes of a value with a given Objective-C type encoding.
+/// This follows the iPhone OS ABI, where 8-byte types are only 4-byte aligned.
+fn size_of_type_encoding(encoding: &str) -> GuestUSize {
+    let mut chars = encoding.as_bytes();
+    let (size, _align) = parse_type_encoding(&mut chars);
+    size
+}
+
+fn round_up(value: GuestUSize, align: GuestUSize) -> GuestUSize {
+    value.div_ceil(align) * align
+}
+
+/// Parse one type from the start of an Objective-C type encoding, returning
+/// its size and alignment.
+fn parse_type_encoding(chars: &mut &[u8]) -> (GuestUSize, GuestUSize) {
+    let (&c, rest) = chars.split_first().expect("Truncated type encoding");
+    *chars = rest;
+    match c {
+        // Qualifiers (const, in, out, etc)
+        b'r' | b'n' | b'N' | b'o' | b'O' | b'R' | b'V' => parse_type_encoding(chars),
+        b'c' | b'C' | b'B' => (1, 1),
+        b's' | b'S' => (2, 2),
+        b'i' | b'I' | b'l' | b'L' | b'f' | b'*' | b'@' | b'#' | b':' => (4, 4),
+        b'q' | b'Q' | b'd' => (8, 4),
+        b'v' => (0, 1),
+        b'^' => {
+            parse_type_encoding(chars);
+            (4, 4)
+        }
+        b'[' => {
+            let digits = chars.iter().take_while(|c| c.is_ascii_digit()).count();
+            let count: GuestUSize = std::str::from_utf8(&chars[..digits])
+                .unwrap()
+                .parse()
+                .unwrap();
+            *chars = &chars[digits..];
+            let (size, align) = parse_type_encoding(chars);
+            assert!(chars.first() == Some(&b']'));
+            *chars = &chars[1..];
+            (size * count, align)
+        }
+        b'{' | b'(' => {
+            let is_union = c == b'(';
+            let close = if is_union { b')' } else { b'}' };
+            // Skip the name. Fields only follow if there's an '='.
+            let name_len = chars
+                .iter()
+                .position(|&c| c == b'=' || c == close)
+                .expect("Truncated type encoding");
+            *chars = &chars[name_len..];
+            let mut size = 0;
+            let mut align = 1;
+            if chars[0] == b'=' {
+                *chars = &chars[1..];
+                while chars.first() != Some(&close) {
+                    let (field_size, field_align) = parse_type_encoding(chars);
+                    align = align.max(field_align);
+                    if is_union {
+                        size = size.max(field_size);
+                    } else {
+                        size = round_up(size, field_align) + field_size;
+                    }
+                }
+            }
+            *chars = &chars[1..];
+            (round_up(size, align), align)
+        }
+        _ => unimplemented!("Type encoding {:?}", c as char),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_encoding_sizes() {
+        assert_eq!(size_of_type_encoding("i"), 4);
+        assert_eq!(size_of_type_encoding("d"), 8);
+        assert_eq!(size_of_type_encoding("^{foo}"), 4);
+        assert_eq!(size_of_type_encoding(CGPOINT_ENCODING), 8);
+        assert_eq!(size_of_type_encoding(CGRECT_ENCODING), 16);
+        assert_eq!(size_of_type_encoding(NSRANGE_ENCODING), 8);
+        assert_eq!(size_of_type_encoding("{foo=cid}"), 16);
+        assert_eq!(size_of_type_encoding("{bar=[3c]s}"), 6);
+        assert_eq!(size_of_type_encoding("(baz=cq)"), 8);
+    }
+}
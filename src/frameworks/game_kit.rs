@@ -0,0 +1,23 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The GameKit framework.
+//!
+//! Only peer-to-peer sessions (`GKSession`) and the peer picker are
+//! implemented, not Game Center. Sessions connect touchHLE instances to each
+//! other over the host's network instead of Bluetooth (see [transport]).
+//!
+//! Useful resources:
+//! - Apple's [Game Kit Programming Guide](https://developer.apple.com/library/archive/documentation/NetworkingInternet/Conceptual/GameKit_Guide/Introduction/Introduction.html)
+
+pub mod gk_peer_picker_controller;
+pub mod gk_session;
+pub mod transport;
+
+#[derive(Default)]
+pub struct State {
+    gk_peer_picker_controller: gk_peer_picker_controller::State,
+    gk_session: gk_session::State,
+}
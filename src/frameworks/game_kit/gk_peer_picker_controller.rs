@@ -0,0 +1,244 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `GKPeerPickerController`.
+//!
+//! There's no picker UI. Instead, the picker connects to the first peer its
+//! session finds, as if the user had picked it and the peer had accepted. If
+//! GameKit networking is off, the picker is cancelled right away, as if the
+//! user had tapped "Cancel".
+
+use super::gk_session::{
+    self, Event, GKPeerStateAvailable, GKPeerStateConnected, GKSessionModePeer,
+};
+use crate::frameworks::foundation::{ns_string, NSUInteger};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+pub type GKPeerPickerConnectionType = NSUInteger;
+#[allow(dead_code)]
+pub const GKPeerPickerConnectionTypeOnline: GKPeerPickerConnectionType = 1 << 0;
+pub const GKPeerPickerConnectionTypeNearby: GKPeerPickerConnectionType = 1 << 1;
+
+#[derive(Default)]
+pub struct State {
+    /// Pickers between `show` and `dismiss`, strong references.
+    visible: Vec<id>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.game_kit.gk_peer_picker_controller
+    }
+}
+
+struct GKPeerPickerControllerHostObject {
+    /// Weak reference.
+    delegate: id,
+    connection_types_mask: GKPeerPickerConnectionType,
+    /// `GKSession*`, strong reference, or [nil] until the picker is shown.
+    session: id,
+    /// Set once the session has been set up after the picker was shown.
+    started: bool,
+}
+impl HostObject for GKPeerPickerControllerHostObject {}
+
+fn borrow(env: &mut Environment, picker: id) -> &mut GKPeerPickerControllerHostObject {
+    env.objc.borrow_mut(picker)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation GKPeerPickerController: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(GKPeerPickerControllerHostObject {
+        delegate: nil,
+        connection_types_mask: GKPeerPickerConnectionTypeNearby,
+        session: nil,
+        started: false,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let session = borrow(env, this).session;
+    if session != nil {
+        gk_session::set_picker(env, session, nil);
+        release(env, session);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)delegate {
+    borrow(env, this).delegate
+}
+- (())setDelegate:(id)delegate { // id<GKPeerPickerControllerDelegate>
+    borrow(env, this).delegate = delegate;
+}
+
+- (GKPeerPickerConnectionType)connectionTypesMask {
+    borrow(env, this).connection_types_mask
+}
+- (())setConnectionTypesMask:(GKPeerPickerConnectionType)mask {
+    borrow(env, this).connection_types_mask = mask;
+}
+
+- (bool)isVisible {
+    State::get(&mut env.framework_state).visible.contains(&this)
+}
+
+- (())show {
+    if State::get(&mut env.framework_state).visible.contains(&this) {
+        return;
+    }
+    let mask = borrow(env, this).connection_types_mask;
+    if mask & GKPeerPickerConnectionTypeNearby == 0 {
+        log!(
+            "Warning: GKPeerPickerController {:?} only allows online connections, which \
+             aren't supported",
+            this
+        );
+    }
+    retain(env, this);
+    State::get(&mut env.framework_state).visible.push(this);
+    borrow(env, this).started = false;
+}
+
+- (())dismiss {
+    let visible = &mut State::get(&mut env.framework_state).visible;
+    let Some(idx) = visible.iter().position(|&picker| picker == this) else {
+        return;
+    };
+    visible.remove(idx);
+    let session = borrow(env, this).session;
+    if session != nil {
+        gk_session::set_picker(env, session, nil);
+    }
+    release(env, this);
+}
+
+@end
+
+};
+
+/// For use by [gk_session::handle_sessions]: set up the sessions of pickers
+/// that have just been shown.
+pub fn handle_pickers(env: &mut Environment) {
+    let pickers = State::get(&mut env.framework_state).visible.clone();
+    for picker in pickers {
+        // A delegate might have dismissed another picker.
+        if !State::get(&mut env.framework_state)
+            .visible
+            .contains(&picker)
+        {
+            continue;
+        }
+        if std::mem::replace(&mut borrow(env, picker).started, true) {
+            continue;
+        }
+        retain(env, picker);
+        start(env, picker);
+        release(env, picker);
+    }
+}
+
+fn start(env: &mut Environment, picker: id) {
+    let delegate = borrow(env, picker).delegate;
+
+    if env.options.gamekit_network.is_none() {
+        log!(
+            "The app is looking for nearby GameKit peers, cancelling. Use --gamekit-network= to \
+             let it find other copies of touchHLE."
+        );
+        () = msg![env; picker dismiss];
+        // The selector won't exist if no class implements this method.
+        let Some(selector) = env.objc.lookup_selector("peerPickerControllerDidCancel:") else {
+            return;
+        };
+        if delegate != nil && msg![env; delegate respondsToSelector:selector] {
+            let () = msg_send(env, (delegate, selector, picker));
+        }
+        return;
+    }
+
+    let mut session = nil;
+    // The selector won't exist if no class implements this method.
+    if let Some(selector) = env
+        .objc
+        .lookup_selector("peerPickerController:sessionForConnectionType:")
+    {
+        if delegate != nil && msg![env; delegate respondsToSelector:selector] {
+            session = msg_send(
+                env,
+                (delegate, selector, picker, GKPeerPickerConnectionTypeNearby),
+            );
+        }
+    }
+    if session == nil {
+        let new: id = msg_class![env; GKSession alloc];
+        session =
+            msg![env; new initWithSessionID:nil displayName:nil sessionMode:GKSessionModePeer];
+    } else {
+        retain(env, session);
+    }
+    let old = std::mem::replace(&mut borrow(env, picker).session, session);
+    release(env, old);
+    log!(
+        "GKPeerPickerController {:?} will connect to the first peer it finds",
+        picker
+    );
+    gk_session::set_picker(env, session, picker);
+    () = msg![env; session setAvailable:true];
+}
+
+/// For use by [gk_session::handle_sessions]: handle an event for a session a
+/// picker is connecting. Returns [true] if the session's delegate shouldn't
+/// be told about it.
+pub fn handle_session_event(env: &mut Environment, picker: id, session: id, event: &Event) -> bool {
+    match event {
+        Event::StateChanged(peer_id, GKPeerStateAvailable) => {
+            if gk_session::should_connect(env, session, peer_id) {
+                gk_session::connect(env, session, peer_id, 0.0);
+            }
+            false
+        }
+        Event::ConnectionRequest(peer_id) => {
+            gk_session::accept(env, session, peer_id);
+            true
+        }
+        Event::ConnectionFailed(peer_id, code) => {
+            log!(
+                "Warning: GKPeerPickerController couldn't connect to peer {:?} (error {}), \
+                 waiting for another",
+                peer_id,
+                code
+            );
+            true
+        }
+        Event::StateChanged(peer_id, GKPeerStateConnected) => {
+            gk_session::set_picker(env, session, nil);
+            let delegate = borrow(env, picker).delegate;
+            // The selector won't exist if no class implements this method.
+            let Some(selector) = env
+                .objc
+                .lookup_selector("peerPickerController:didConnectPeer:toSession:")
+            else {
+                return true;
+            };
+            if delegate != nil && msg![env; delegate respondsToSelector:selector] {
+                let peer_id = ns_string::from_rust_string(env, peer_id.clone());
+                let () = msg_send(env, (delegate, selector, picker, peer_id, session));
+                release(env, peer_id);
+            }
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,953 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `GKSession`.
+//!
+//! Sessions find each other and connect over the host's network rather than
+//! Bluetooth, using [super::transport], and only if the user has chosen a
+//! network with `--gamekit-network=`. Otherwise, a session never finds any
+//! peers, as if nobody nearby were playing.
+//!
+//! Reliable and unreliable data are both sent over TCP, so nothing is lost.
+//! The delegate and the data receive handler are told about changes from the
+//! run loop, like on a real device.
+
+use super::gk_peer_picker_controller;
+use super::transport::{Announcement, Connection, Discovery, Frame, Listener, MAX_DATA_SIZE};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::{
+    ns_array, ns_data, ns_error, ns_string, ns_uuid, NSInteger, NSTimeInterval, NSUInteger,
+};
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_send, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+pub type GKSessionMode = i32;
+pub const GKSessionModeServer: GKSessionMode = 0;
+pub const GKSessionModeClient: GKSessionMode = 1;
+pub const GKSessionModePeer: GKSessionMode = 2;
+
+pub type GKPeerConnectionState = i32;
+pub const GKPeerStateAvailable: GKPeerConnectionState = 0;
+pub const GKPeerStateUnavailable: GKPeerConnectionState = 1;
+pub const GKPeerStateConnected: GKPeerConnectionState = 2;
+pub const GKPeerStateDisconnected: GKPeerConnectionState = 3;
+pub const GKPeerStateConnecting: GKPeerConnectionState = 4;
+
+pub type GKSendDataMode = i32;
+#[allow(dead_code)]
+pub const GKSendDataReliable: GKSendDataMode = 0;
+#[allow(dead_code)]
+pub const GKSendDataUnreliable: GKSendDataMode = 1;
+
+pub const GKSessionErrorDomain: &str = "com.apple.gamekit.GKSessionErrorDomain";
+
+pub type GKSessionError = NSInteger;
+pub const GKSessionInvalidParameterError: GKSessionError = 30500;
+pub const GKSessionPeerNotFoundError: GKSessionError = 30501;
+pub const GKSessionDeclinedError: GKSessionError = 30502;
+pub const GKSessionTimedOutError: GKSessionError = 30503;
+#[allow(dead_code)]
+pub const GKSessionCancelledError: GKSessionError = 30504;
+pub const GKSessionConnectionFailedError: GKSessionError = 30505;
+pub const GKSessionConnectionClosedError: GKSessionError = 30506;
+pub const GKSessionDataTooBigError: GKSessionError = 30507;
+pub const GKSessionNotConnectedError: GKSessionError = 30508;
+pub const GKSessionCannotEnableError: GKSessionError = 30509;
+pub const GKSessionInProgressError: GKSessionError = 30510;
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_GKSessionErrorDomain",
+    HostConstant::NSString(GKSessionErrorDomain),
+)];
+
+/// How often an available session announces itself.
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+/// How long after its last announcement a peer becomes unavailable.
+const PEER_EXPIRY: Duration = Duration::from_secs(5);
+/// Used when the app passes a timeout of zero to `connectToPeer:withTimeout:`.
+const DEFAULT_CONNECT_TIMEOUT: NSTimeInterval = 20.0;
+
+#[derive(Default)]
+pub struct State {
+    /// Weak references, removed when a session is deallocated.
+    sessions: Vec<id>,
+    warned_no_network: bool,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.game_kit.gk_session
+    }
+}
+
+/// Something the delegate or data receive handler needs to be told.
+#[derive(Debug)]
+pub enum Event {
+    StateChanged(String, GKPeerConnectionState),
+    ConnectionRequest(String),
+    ConnectionFailed(String, GKSessionError),
+    Data(String, Vec<u8>),
+    Failed(GKSessionError),
+}
+
+struct Peer {
+    display_name: String,
+    state: GKPeerConnectionState,
+    /// Where the peer accepts connections.
+    address: Option<SocketAddr>,
+    /// Set if the peer looks for others, i.e. it may connect to us.
+    browsing: bool,
+    last_seen: Instant,
+    connection: Option<Connection>,
+    /// Set while we're connecting to the peer.
+    connect_deadline: Option<Instant>,
+    /// Set if the peer asked to connect and the app hasn't answered yet.
+    requested: bool,
+}
+
+/// The sockets of a session, created when it first becomes available.
+struct Network {
+    discovery: Discovery,
+    listener: Listener,
+    last_announce: Option<Instant>,
+    /// Connections whose first frame hasn't arrived yet.
+    unidentified: Vec<Connection>,
+}
+
+struct GKSessionHostObject {
+    /// Weak reference.
+    delegate: id,
+    /// Weak reference.
+    data_handler: id,
+    data_context: MutVoidPtr,
+    /// `GKPeerPickerController*`, weak reference. Set while a peer picker is
+    /// connecting this session.
+    picker: id,
+    session_id: String,
+    display_name: String,
+    peer_id: String,
+    mode: GKSessionMode,
+    available: bool,
+    disconnect_timeout: NSTimeInterval,
+    network: Option<Network>,
+    /// Ordered by when they were found.
+    peers: Vec<(String, Peer)>,
+    events: Vec<Event>,
+}
+impl HostObject for GKSessionHostObject {}
+impl GKSessionHostObject {
+    fn advertising(&self) -> bool {
+        self.mode != GKSessionModeClient
+    }
+    fn browsing(&self) -> bool {
+        self.mode != GKSessionModeServer
+    }
+
+    fn peer(&mut self, peer_id: &str) -> Option<&mut Peer> {
+        self.peers
+            .iter_mut()
+            .find(|(id, _)| id == peer_id)
+            .map(|(_, peer)| peer)
+    }
+
+    fn announcement(&self) -> Announcement {
+        Announcement {
+            peer_id: self.peer_id.clone(),
+            session_id: self.session_id.clone(),
+            display_name: self.display_name.clone(),
+            advertising: self.advertising(),
+            browsing: self.browsing(),
+            available: self.available,
+            port: self.network.as_ref().unwrap().listener.port(),
+        }
+    }
+
+    /// Drop the connection to a peer, telling it if it's still there.
+    fn close(&mut self, peer_id: &str) {
+        let peer = self.peer(peer_id).unwrap();
+        if let Some(mut connection) = peer.connection.take() {
+            connection.send(&Frame::Goodbye);
+        }
+        if peer.state == GKPeerStateConnecting {
+            peer.state = GKPeerStateAvailable;
+        }
+        peer.connect_deadline = None;
+        peer.requested = false;
+    }
+
+    /// Disconnect a connected peer. It's forgotten until it's found again.
+    fn disconnect(&mut self, peer_id: &str) {
+        self.close(peer_id);
+        self.peers.retain(|(id, _)| id != peer_id);
+        self.events.push(Event::StateChanged(
+            peer_id.to_string(),
+            GKPeerStateDisconnected,
+        ));
+    }
+
+    /// Send and receive whatever's waiting on the network.
+    fn poll(&mut self, now: Instant) {
+        let Some(network) = &mut self.network else {
+            return;
+        };
+
+        let announcements = network.discovery.receive();
+        if self.available {
+            while let Some(connection) = network.listener.accept() {
+                network.unidentified.push(connection);
+            }
+        }
+        // A connection stops being unidentified once its hello has arrived.
+        let mut connections = Vec::new();
+        let mut i = 0;
+        while i < network.unidentified.len() {
+            let connection = &mut network.unidentified[i];
+            let hello = connection.poll().into_iter().find_map(|frame| match frame {
+                Frame::Hello {
+                    peer_id,
+                    display_name,
+                } => Some((peer_id, display_name)),
+                _ => None,
+            });
+            if let Some((peer_id, display_name)) = hello {
+                connections.push((peer_id, display_name, network.unidentified.remove(i)));
+            } else if connection.is_closed() {
+                network.unidentified.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        let announce = self.available
+            && network
+                .last_announce
+                .map_or(true, |last| now.duration_since(last) >= ANNOUNCE_INTERVAL);
+        if announce {
+            network.last_announce = Some(now);
+            let announcement = self.announcement();
+            let network = self.network.as_ref().unwrap();
+            network.discovery.announce(&announcement);
+        }
+
+        if self.available && self.browsing() {
+            for (announcement, ip) in announcements {
+                if announcement.session_id != self.session_id
+                    || announcement.peer_id == self.peer_id
+                    || !announcement.advertising
+                {
+                    continue;
+                }
+                let address = SocketAddr::new(ip, announcement.port);
+                if let Some(peer) = self.peer(&announcement.peer_id) {
+                    if announcement.available {
+                        peer.last_seen = now;
+                        peer.address = Some(address);
+                    } else if peer.state == GKPeerStateAvailable {
+                        // Expire it right away.
+                        peer.last_seen = now - PEER_EXPIRY;
+                    }
+                } else if announcement.available {
+                    log_dbg!(
+                        "GKSession found peer {:?} ({:?})",
+                        announcement.peer_id,
+                        announcement.display_name
+                    );
+                    self.peers.push((
+                        announcement.peer_id.clone(),
+                        Peer {
+                            display_name: announcement.display_name,
+                            state: GKPeerStateAvailable,
+                            address: Some(address),
+                            browsing: announcement.browsing,
+                            last_seen: now,
+                            connection: None,
+                            connect_deadline: None,
+                            requested: false,
+                        },
+                    ));
+                    self.events.push(Event::StateChanged(
+                        announcement.peer_id,
+                        GKPeerStateAvailable,
+                    ));
+                }
+            }
+        }
+
+        for (peer_id, display_name, mut connection) in connections {
+            if let Some(peer) = self.peer(&peer_id) {
+                if peer.connection.is_some() {
+                    // Both sides tried to connect at once. Keep ours.
+                    connection.send(&Frame::Deny);
+                    continue;
+                }
+                peer.connection = Some(connection);
+                peer.requested = true;
+                peer.last_seen = now;
+            } else {
+                self.peers.push((
+                    peer_id.clone(),
+                    Peer {
+                        display_name,
+                        state: GKPeerStateAvailable,
+                        address: None,
+                        browsing: true,
+                        last_seen: now,
+                        connection: Some(connection),
+                        connect_deadline: None,
+                        requested: true,
+                    },
+                ));
+            }
+            log_dbg!("GKSession got connection request from peer {:?}", peer_id);
+            self.events.push(Event::ConnectionRequest(peer_id));
+        }
+
+        let peer_ids: Vec<String> = self.peers.iter().map(|(id, _)| id.clone()).collect();
+        for peer_id in peer_ids {
+            self.poll_peer(&peer_id, now);
+        }
+    }
+
+    fn poll_peer(&mut self, peer_id: &str, now: Instant) {
+        let peer = self.peer(peer_id).unwrap();
+        let (frames, closed) = match &mut peer.connection {
+            Some(connection) => (connection.poll(), connection.is_closed()),
+            None => (Vec::new(), false),
+        };
+        for frame in frames {
+            let peer = self.peer(peer_id).unwrap();
+            match frame {
+                Frame::Accept if peer.connect_deadline.is_some() => {
+                    peer.connect_deadline = None;
+                    peer.state = GKPeerStateConnected;
+                    log!("GameKit: connected to peer {:?}", peer.display_name);
+                    self.events.push(Event::StateChanged(
+                        peer_id.to_string(),
+                        GKPeerStateConnected,
+                    ));
+                }
+                Frame::Deny if peer.connect_deadline.is_some() => {
+                    self.close(peer_id);
+                    self.events.push(Event::ConnectionFailed(
+                        peer_id.to_string(),
+                        GKSessionDeclinedError,
+                    ));
+                    return;
+                }
+                Frame::Data(data) if peer.state == GKPeerStateConnected => {
+                    self.events.push(Event::Data(peer_id.to_string(), data));
+                }
+                Frame::Goodbye => {
+                    self.peer_closed(peer_id);
+                    return;
+                }
+                _ => (),
+            }
+        }
+        if closed {
+            self.peer_closed(peer_id);
+            return;
+        }
+
+        let peer = self.peer(peer_id).unwrap();
+        if peer
+            .connect_deadline
+            .is_some_and(|deadline| now >= deadline)
+        {
+            self.close(peer_id);
+            self.events.push(Event::ConnectionFailed(
+                peer_id.to_string(),
+                GKSessionTimedOutError,
+            ));
+        } else if peer.state == GKPeerStateAvailable
+            && peer.connection.is_none()
+            && now.duration_since(peer.last_seen) >= PEER_EXPIRY
+        {
+            log_dbg!("GKSession lost peer {:?}", peer_id);
+            self.peers.retain(|(id, _)| id != peer_id);
+            self.events.push(Event::StateChanged(
+                peer_id.to_string(),
+                GKPeerStateUnavailable,
+            ));
+        }
+    }
+
+    /// The other end of a peer's connection went away.
+    fn peer_closed(&mut self, peer_id: &str) {
+        let peer = self.peer(peer_id).unwrap();
+        if peer.state == GKPeerStateConnected {
+            log!("GameKit: peer {:?} disconnected", peer.display_name);
+            self.disconnect(peer_id);
+        } else if peer.connect_deadline.is_some() {
+            self.close(peer_id);
+            self.events.push(Event::ConnectionFailed(
+                peer_id.to_string(),
+                GKSessionConnectionClosedError,
+            ));
+        } else {
+            self.close(peer_id);
+        }
+    }
+}
+
+fn borrow(env: &mut Environment, session: id) -> &mut GKSessionHostObject {
+    env.objc.borrow_mut(session)
+}
+
+/// Peer IDs are decimal numbers, like on a real device. The process ID is
+/// mixed in so that instances using `--uuid-seed=` still differ.
+fn new_peer_id(env: &mut Environment) -> String {
+    let bytes = ns_uuid::generate(env);
+    let number = u32::from_be_bytes(bytes[..4].try_into().unwrap()) ^ std::process::id();
+    format!("{}", number.max(1))
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation GKSession: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(GKSessionHostObject {
+        delegate: nil,
+        data_handler: nil,
+        data_context: MutVoidPtr::null(),
+        picker: nil,
+        session_id: String::new(),
+        display_name: String::new(),
+        peer_id: String::new(),
+        mode: GKSessionModePeer,
+        available: false,
+        disconnect_timeout: 20.0,
+        network: None,
+        peers: Vec::new(),
+        events: Vec::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithSessionID:(id)session_id // NSString*
+            displayName:(id)display_name // NSString*
+            sessionMode:(GKSessionMode)mode {
+    let session_id = if session_id == nil {
+        env.bundle.bundle_identifier().to_string()
+    } else {
+        ns_string::to_rust_string(env, session_id).into_owned()
+    };
+    let display_name = if display_name == nil {
+        env.options.device_model.clone()
+    } else {
+        ns_string::to_rust_string(env, display_name).into_owned()
+    };
+    let peer_id = new_peer_id(env);
+    log_dbg!(
+        "GKSession {:?}: session ID {:?}, display name {:?}, mode {}, peer ID {:?}",
+        this,
+        session_id,
+        display_name,
+        mode,
+        peer_id
+    );
+    let host_object = borrow(env, this);
+    host_object.session_id = session_id;
+    host_object.display_name = display_name;
+    host_object.peer_id = peer_id;
+    host_object.mode = mode;
+    State::get(&mut env.framework_state).sessions.push(this);
+    this
+}
+
+- (())dealloc {
+    State::get(&mut env.framework_state)
+        .sessions
+        .retain(|&session| session != this);
+    let host_object = borrow(env, this);
+    let peer_ids: Vec<String> = host_object.peers.iter().map(|(id, _)| id.clone()).collect();
+    for peer_id in peer_ids {
+        host_object.close(&peer_id);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)delegate {
+    borrow(env, this).delegate
+}
+- (())setDelegate:(id)delegate { // id<GKSessionDelegate>
+    borrow(env, this).delegate = delegate;
+}
+
+- (())setDataReceiveHandler:(id)handler
+                withContext:(MutVoidPtr)context {
+    let host_object = borrow(env, this);
+    host_object.data_handler = handler;
+    host_object.data_context = context;
+}
+
+- (id)sessionID {
+    let session_id = borrow(env, this).session_id.clone();
+    let session_id = ns_string::from_rust_string(env, session_id);
+    autorelease(env, session_id)
+}
+- (id)displayName {
+    let display_name = borrow(env, this).display_name.clone();
+    let display_name = ns_string::from_rust_string(env, display_name);
+    autorelease(env, display_name)
+}
+- (id)peerID {
+    let peer_id = borrow(env, this).peer_id.clone();
+    let peer_id = ns_string::from_rust_string(env, peer_id);
+    autorelease(env, peer_id)
+}
+- (GKSessionMode)sessionMode {
+    borrow(env, this).mode
+}
+
+- (NSTimeInterval)disconnectTimeout {
+    borrow(env, this).disconnect_timeout
+}
+- (())setDisconnectTimeout:(NSTimeInterval)timeout {
+    borrow(env, this).disconnect_timeout = timeout;
+}
+
+- (bool)isAvailable {
+    borrow(env, this).available
+}
+- (())setAvailable:(bool)available {
+    let Some(mode) = env.options.gamekit_network else {
+        borrow(env, this).available = available;
+        let state = State::get(&mut env.framework_state);
+        if available && !state.warned_no_network {
+            state.warned_no_network = true;
+            log!(
+                "The app is looking for nearby GameKit peers. Use \
+                 --gamekit-network= to let it find other copies of touchHLE."
+            );
+        }
+        return;
+    };
+    let host_object = borrow(env, this);
+    if host_object.available == available {
+        return;
+    }
+    if available && host_object.network.is_none() {
+        let network = Discovery::new(mode).and_then(|discovery| {
+            Ok(Network {
+                discovery,
+                listener: Listener::new(mode)?,
+                last_announce: None,
+                unidentified: Vec::new(),
+            })
+        });
+        match network {
+            Ok(network) => host_object.network = Some(network),
+            Err(e) => {
+                log!("Warning: couldn't set up GameKit networking: {}", e);
+                host_object.events.push(Event::Failed(GKSessionCannotEnableError));
+                return;
+            }
+        }
+    }
+    host_object.available = available;
+    if !available {
+        // Let others know right away.
+        let announcement = host_object.announcement();
+        let network = host_object.network.as_ref().unwrap();
+        network.discovery.announce(&announcement);
+    }
+}
+
+- (bool)sendDataToAllPeers:(id)data // NSData*
+              withDataMode:(GKSendDataMode)mode
+                     error:(MutPtr<id>)error { // NSError**
+    let connected = peer_ids_with_state(env, this, GKPeerStateConnected);
+    let peers = ns_array::from_vec(env, connected);
+    let result: bool = msg![env; this sendData:data
+                                       toPeers:peers
+                                  withDataMode:mode
+                                         error:error];
+    release(env, peers);
+    result
+}
+
+- (bool)sendData:(id)data // NSData*
+         toPeers:(id)peers // NSArray* of NSString*
+    withDataMode:(GKSendDataMode)_mode
+           error:(MutPtr<id>)error { // NSError**
+    let bytes = ns_data::to_vec(env, data);
+    let code = if bytes.len() > MAX_DATA_SIZE {
+        Some(GKSessionDataTooBigError)
+    } else if peers == nil {
+        Some(GKSessionInvalidParameterError)
+    } else {
+        None
+    };
+    let mut peer_ids = Vec::new();
+    if code.is_none() {
+        let count: NSUInteger = msg![env; peers count];
+        for i in 0..count {
+            let peer_id: id = msg![env; peers objectAtIndex:i];
+            peer_ids.push(ns_string::to_rust_string(env, peer_id).into_owned());
+        }
+    }
+    let host_object = borrow(env, this);
+    let code = code.or_else(|| {
+        let all_connected = peer_ids.iter().all(|peer_id| {
+            host_object
+                .peer(peer_id)
+                .is_some_and(|peer| peer.state == GKPeerStateConnected)
+        });
+        (!all_connected).then_some(GKSessionNotConnectedError)
+    });
+    if let Some(code) = code {
+        log_dbg!("GKSession {:?} couldn't send data: error {}", this, code);
+        if !error.is_null() {
+            let new_error = ns_error::new_error(env, GKSessionErrorDomain, code);
+            env.mem.write(error, new_error);
+        }
+        return false;
+    }
+    let frame = Frame::Data(bytes);
+    let host_object = borrow(env, this);
+    for peer_id in peer_ids {
+        let peer = host_object.peer(&peer_id).unwrap();
+        peer.connection.as_mut().unwrap().send(&frame);
+    }
+    true
+}
+
+- (())connectToPeer:(id)peer_id // NSString*
+        withTimeout:(NSTimeInterval)timeout {
+    let peer_id = ns_string::to_rust_string(env, peer_id).into_owned();
+    connect(env, this, &peer_id, timeout);
+}
+
+- (())cancelConnectToPeer:(id)peer_id { // NSString*
+    let peer_id = ns_string::to_rust_string(env, peer_id).into_owned();
+    let host_object = borrow(env, this);
+    if host_object
+        .peer(&peer_id)
+        .is_some_and(|peer| peer.connect_deadline.is_some())
+    {
+        host_object.close(&peer_id);
+    }
+}
+
+- (bool)acceptConnectionFromPeer:(id)peer_id // NSString*
+                           error:(MutPtr<id>)error { // NSError**
+    let peer_id = ns_string::to_rust_string(env, peer_id).into_owned();
+    if accept(env, this, &peer_id) {
+        return true;
+    }
+    if !error.is_null() {
+        let new_error = ns_error::new_error(env, GKSessionErrorDomain, GKSessionPeerNotFoundError);
+        env.mem.write(error, new_error);
+    }
+    false
+}
+
+- (())denyConnectionFromPeer:(id)peer_id { // NSString*
+    let peer_id = ns_string::to_rust_string(env, peer_id).into_owned();
+    let host_object = borrow(env, this);
+    let Some(peer) = host_object.peer(&peer_id) else {
+        return;
+    };
+    if !peer.requested {
+        return;
+    }
+    let mut connection = peer.connection.take().unwrap();
+    peer.requested = false;
+    connection.send(&Frame::Deny);
+}
+
+- (())disconnectFromAllPeers {
+    let host_object = borrow(env, this);
+    let connected: Vec<String> = host_object
+        .peers
+        .iter()
+        .filter(|(_, peer)| peer.state == GKPeerStateConnected)
+        .map(|(id, _)| id.clone())
+        .collect();
+    for peer_id in connected {
+        host_object.disconnect(&peer_id);
+    }
+}
+
+- (())disconnectPeerFromAllPeers:(id)peer_id { // NSString*
+    let peer_id = ns_string::to_rust_string(env, peer_id).into_owned();
+    let host_object = borrow(env, this);
+    if host_object
+        .peer(&peer_id)
+        .is_some_and(|peer| peer.state == GKPeerStateConnected)
+    {
+        host_object.disconnect(&peer_id);
+    }
+}
+
+- (id)peersWithConnectionState:(GKPeerConnectionState)state {
+    let peer_ids = peer_ids_with_state(env, this, state);
+    let peer_ids = ns_array::from_vec(env, peer_ids);
+    autorelease(env, peer_ids)
+}
+
+- (id)displayNameForPeer:(id)peer_id { // NSString*
+    let peer_id = ns_string::to_rust_string(env, peer_id);
+    let host_object = borrow(env, this);
+    let display_name = if peer_id == host_object.peer_id {
+        host_object.display_name.clone()
+    } else if let Some(peer) = host_object.peer(&peer_id) {
+        peer.display_name.clone()
+    } else {
+        return nil;
+    };
+    let display_name = ns_string::from_rust_string(env, display_name);
+    autorelease(env, display_name)
+}
+
+@end
+
+};
+
+/// Get new `NSString*`s (+1 references) for the IDs of the peers in a state.
+fn peer_ids_with_state(
+    env: &mut Environment,
+    session: id,
+    state: GKPeerConnectionState,
+) -> Vec<id> {
+    let peer_ids: Vec<String> = borrow(env, session)
+        .peers
+        .iter()
+        .filter(|(_, peer)| peer.state == state)
+        .map(|(id, _)| id.clone())
+        .collect();
+    peer_ids
+        .into_iter()
+        .map(|peer_id| ns_string::from_rust_string(env, peer_id))
+        .collect()
+}
+
+/// Start connecting to an available peer. The result is delivered later.
+pub fn connect(env: &mut Environment, session: id, peer_id: &str, timeout: NSTimeInterval) {
+    let timeout = if timeout > 0.0 {
+        timeout
+    } else {
+        DEFAULT_CONNECT_TIMEOUT
+    };
+    let host_object = borrow(env, session);
+    let own_peer_id = host_object.peer_id.clone();
+    let display_name = host_object.display_name.clone();
+    let Some(peer) = host_object.peer(peer_id) else {
+        log!(
+            "Warning: GKSession can't connect to unknown peer {:?}",
+            peer_id
+        );
+        host_object.events.push(Event::ConnectionFailed(
+            peer_id.to_string(),
+            GKSessionPeerNotFoundError,
+        ));
+        return;
+    };
+    if peer.connection.is_some() {
+        host_object.events.push(Event::ConnectionFailed(
+            peer_id.to_string(),
+            GKSessionInProgressError,
+        ));
+        return;
+    }
+    let Some(address) = peer.address else {
+        host_object.events.push(Event::ConnectionFailed(
+            peer_id.to_string(),
+            GKSessionConnectionFailedError,
+        ));
+        return;
+    };
+    log!(
+        "GameKit: connecting to peer {:?} at {}",
+        peer.display_name,
+        address
+    );
+    match Connection::connect(address) {
+        Ok(mut connection) => {
+            connection.send(&Frame::Hello {
+                peer_id: own_peer_id,
+                display_name,
+            });
+            peer.connection = Some(connection);
+            peer.state = GKPeerStateConnecting;
+            peer.connect_deadline =
+                Some(Instant::now() + Duration::from_secs_f64(timeout.min(3600.0)));
+        }
+        Err(e) => {
+            log!("Warning: couldn't connect to GameKit peer: {}", e);
+            host_object.events.push(Event::ConnectionFailed(
+                peer_id.to_string(),
+                GKSessionConnectionFailedError,
+            ));
+        }
+    }
+}
+
+/// Accept a peer's connection request. Returns [false] if there wasn't one.
+pub fn accept(env: &mut Environment, session: id, peer_id: &str) -> bool {
+    let host_object = borrow(env, session);
+    let Some(peer) = host_object.peer(peer_id) else {
+        return false;
+    };
+    if !peer.requested {
+        return false;
+    }
+    peer.requested = false;
+    peer.state = GKPeerStateConnected;
+    peer.connection.as_mut().unwrap().send(&Frame::Accept);
+    log!("GameKit: connected to peer {:?}", peer.display_name);
+    host_object.events.push(Event::StateChanged(
+        peer_id.to_string(),
+        GKPeerStateConnected,
+    ));
+    true
+}
+
+/// For use by `GKPeerPickerController`: shortcut to check whether a session
+/// should connect to a peer it has found, rather than wait for the peer to
+/// connect to it. Only one of two peers that can both connect does so.
+pub fn should_connect(env: &mut Environment, session: id, peer_id: &str) -> bool {
+    let host_object = borrow(env, session);
+    let own_peer_id = host_object.peer_id.clone();
+    let browsing = host_object.browsing();
+    let Some(peer) = host_object.peer(peer_id) else {
+        return false;
+    };
+    let (own, other) = (
+        own_peer_id.parse::<u32>().unwrap_or(0),
+        peer_id.parse::<u32>().unwrap_or(0),
+    );
+    browsing && (!peer.browsing || own < other)
+}
+
+/// For use by `GKPeerPickerController`: have a session's delegate events go
+/// to a peer picker first, or stop that if [nil] is passed.
+pub fn set_picker(env: &mut Environment, session: id, picker: id) {
+    borrow(env, session).picker = picker;
+}
+
+/// For use by `NSRunLoop`: check the network for every session and tell the
+/// delegates what happened.
+pub fn handle_sessions(env: &mut Environment) {
+    gk_peer_picker_controller::handle_pickers(env);
+
+    let sessions = State::get(&mut env.framework_state).sessions.clone();
+    if sessions.is_empty() {
+        return;
+    }
+    let now = Instant::now();
+    for session in sessions {
+        // A delegate might have released another session.
+        if !State::get(&mut env.framework_state)
+            .sessions
+            .contains(&session)
+        {
+            continue;
+        }
+        let host_object = borrow(env, session);
+        host_object.poll(now);
+        if host_object.events.is_empty() {
+            continue;
+        }
+        retain(env, session);
+        loop {
+            let host_object = borrow(env, session);
+            if host_object.events.is_empty() {
+                break;
+            }
+            let event = host_object.events.remove(0);
+            deliver_event(env, session, event);
+        }
+        release(env, session);
+    }
+}
+
+fn deliver_event(env: &mut Environment, session: id, event: Event) {
+    let &mut GKSessionHostObject {
+        delegate,
+        data_handler,
+        data_context,
+        picker,
+        ..
+    } = borrow(env, session);
+    log_dbg!("GKSession {:?} delivering event {:?}", session, event);
+
+    if picker != nil
+        && gk_peer_picker_controller::handle_session_event(env, picker, session, &event)
+    {
+        return;
+    }
+
+    if let Event::Data(peer_id, data) = event {
+        if data_handler == nil {
+            log!("Warning: GKSession received data but has no data receive handler, dropping");
+            return;
+        }
+        let peer_id = ns_string::from_rust_string(env, peer_id);
+        let data = ns_data::from_bytes(env, &data);
+        // The selector won't exist if no class implements this method.
+        if let Some(selector) = env
+            .objc
+            .lookup_selector("receiveData:fromPeer:inSession:context:")
+        {
+            let () = msg_send(
+                env,
+                (data_handler, selector, data, peer_id, session, data_context),
+            );
+        }
+        release(env, data);
+        release(env, peer_id);
+        return;
+    }
+
+    if delegate == nil {
+        return;
+    }
+    let selector_name = match event {
+        Event::StateChanged(..) => "session:peer:didChangeState:",
+        Event::ConnectionRequest(_) => "session:didReceiveConnectionRequestFromPeer:",
+        Event::ConnectionFailed(..) => "session:connectionWithPeerFailed:withError:",
+        Event::Failed(_) => "session:didFailWithError:",
+        Event::Data(..) => unreachable!(),
+    };
+    // The selector won't exist if no class implements this method.
+    let Some(selector) = env.objc.lookup_selector(selector_name) else {
+        return;
+    };
+    if !msg![env; delegate respondsToSelector:selector] {
+        return;
+    }
+    match event {
+        Event::StateChanged(peer_id, state) => {
+            let peer_id = ns_string::from_rust_string(env, peer_id);
+            let () = msg_send(env, (delegate, selector, session, peer_id, state));
+            release(env, peer_id);
+        }
+        Event::ConnectionRequest(peer_id) => {
+            let peer_id = ns_string::from_rust_string(env, peer_id);
+            let () = msg_send(env, (delegate, selector, session, peer_id));
+            release(env, peer_id);
+        }
+        Event::ConnectionFailed(peer_id, code) => {
+            let peer_id = ns_string::from_rust_string(env, peer_id);
+            let error = ns_error::new_error(env, GKSessionErrorDomain, code);
+            let () = msg_send(env, (delegate, selector, session, peer_id, error));
+            release(env, peer_id);
+        }
+        Event::Failed(code) => {
+            let error = ns_error::new_error(env, GKSessionErrorDomain, code);
+            let () = msg_send(env, (delegate, selector, session, error));
+        }
+        Event::Data(..) => unreachable!(),
+    }
+}
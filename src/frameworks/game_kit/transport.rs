@@ -0,0 +1,373 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The network protocol GameKit sessions use to find and talk to each other,
+//! in place of Bluetooth. See `--gamekit-network=`.
+//!
+//! Sessions announce themselves with UDP datagrams sent to a small range of
+//! ports, either broadcast to the local network or sent to the loopback
+//! address. Every touchHLE instance binds the first free port in the range, so
+//! several instances can run on one machine. Connected peers exchange frames
+//! over TCP: a one-byte type, a four-byte big-endian length, and the payload.
+//!
+//! This is only meant to connect touchHLE instances to each other, not to real
+//! devices, so the protocol is touchHLE's own.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::time::Duration;
+
+/// First line of every announcement.
+const MAGIC: &str = "touchHLE GameKit 1";
+/// First of the ports announcements are sent to.
+const DISCOVERY_PORT: u16 = 49450;
+/// How many touchHLE instances can run on one machine.
+const DISCOVERY_PORT_COUNT: u16 = 8;
+/// How long to wait for a TCP connection to be established.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+/// GameKit's limit on the size of a message is 87 KB.
+pub const MAX_DATA_SIZE: usize = 87 * 1024;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NetworkMode {
+    /// Find peers on the local network.
+    Lan,
+    /// Find peers on the same machine.
+    Loopback,
+}
+impl NetworkMode {
+    pub fn parse(value: &str) -> Result<NetworkMode, String> {
+        match value {
+            "lan" => Ok(NetworkMode::Lan),
+            "loopback" => Ok(NetworkMode::Loopback),
+            _ => Err(format!("Invalid GameKit network mode {:?}", value)),
+        }
+    }
+
+    fn bind_address(self) -> IpAddr {
+        match self {
+            NetworkMode::Lan => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            NetworkMode::Loopback => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        }
+    }
+
+    fn announce_address(self) -> IpAddr {
+        match self {
+            NetworkMode::Lan => IpAddr::V4(Ipv4Addr::BROADCAST),
+            NetworkMode::Loopback => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        }
+    }
+}
+
+/// A session telling others it exists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Announcement {
+    pub peer_id: String,
+    pub session_id: String,
+    pub display_name: String,
+    /// Set if the session accepts connections (server and peer mode).
+    pub advertising: bool,
+    /// Set if the session looks for others (client and peer mode).
+    pub browsing: bool,
+    /// Cleared when the session stops being available.
+    pub available: bool,
+    /// The TCP port the session accepts connections on.
+    pub port: u16,
+}
+impl Announcement {
+    fn encode(&self) -> Vec<u8> {
+        format!(
+            "{}\npeer={}\nsession={}\nname={}\nadvertising={}\nbrowsing={}\n\
+             available={}\nport={}\n",
+            MAGIC,
+            self.peer_id,
+            self.session_id,
+            self.display_name,
+            self.advertising,
+            self.browsing,
+            self.available,
+            self.port,
+        )
+        .into_bytes()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Announcement> {
+        let text = std::str::from_utf8(bytes).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != MAGIC {
+            return None;
+        }
+        let mut field = |name: &str| -> Option<String> {
+            let (key, value) = lines.next()?.split_once('=')?;
+            (key == name).then(|| value.to_string())
+        };
+        Some(Announcement {
+            peer_id: field("peer")?,
+            session_id: field("session")?,
+            display_name: field("name")?,
+            advertising: field("advertising")?.parse().ok()?,
+            browsing: field("browsing")?.parse().ok()?,
+            available: field("available")?.parse().ok()?,
+            port: field("port")?.parse().ok()?,
+        })
+    }
+}
+
+/// The UDP socket announcements are sent and received on.
+pub struct Discovery {
+    socket: UdpSocket,
+    mode: NetworkMode,
+}
+impl Discovery {
+    pub fn new(mode: NetworkMode) -> std::io::Result<Discovery> {
+        let mut result = Err(std::io::Error::from(ErrorKind::AddrInUse));
+        for port in DISCOVERY_PORT..DISCOVERY_PORT + DISCOVERY_PORT_COUNT {
+            result = UdpSocket::bind((mode.bind_address(), port));
+            if result.is_ok() {
+                break;
+            }
+        }
+        let socket = result?;
+        if mode == NetworkMode::Lan {
+            socket.set_broadcast(true)?;
+        }
+        socket.set_nonblocking(true)?;
+        Ok(Discovery { socket, mode })
+    }
+
+    pub fn announce(&self, announcement: &Announcement) {
+        let bytes = announcement.encode();
+        for port in DISCOVERY_PORT..DISCOVERY_PORT + DISCOVERY_PORT_COUNT {
+            let address = SocketAddr::new(self.mode.announce_address(), port);
+            // Errors are expected, e.g. there might be no network.
+            let _ = self.socket.send_to(&bytes, address);
+        }
+    }
+
+    /// Get the announcements that have arrived, with where they came from.
+    pub fn receive(&self) -> Vec<(Announcement, IpAddr)> {
+        let mut announcements = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while let Ok((size, from)) = self.socket.recv_from(&mut buffer) {
+            if let Some(announcement) = Announcement::decode(&buffer[..size]) {
+                announcements.push((announcement, from.ip()));
+            }
+        }
+        announcements
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    /// The first frame on a connection, saying who's connecting.
+    Hello {
+        peer_id: String,
+        display_name: String,
+    },
+    Accept,
+    Deny,
+    Data(Vec<u8>),
+    Goodbye,
+}
+impl Frame {
+    fn encode(&self) -> Vec<u8> {
+        let (kind, payload) = match self {
+            Frame::Hello {
+                peer_id,
+                display_name,
+            } => (1, format!("{}\n{}", peer_id, display_name).into_bytes()),
+            Frame::Accept => (2, Vec::new()),
+            Frame::Deny => (3, Vec::new()),
+            Frame::Data(data) => (4, data.clone()),
+            Frame::Goodbye => (5, Vec::new()),
+        };
+        let mut bytes = Vec::with_capacity(5 + payload.len());
+        bytes.push(kind);
+        bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+
+    /// Take a frame from the start of a buffer, if a whole one has arrived.
+    /// Returns [Err] if the data isn't valid.
+    fn take(buffer: &mut Vec<u8>) -> Result<Option<Frame>, ()> {
+        if buffer.len() < 5 {
+            return Ok(None);
+        }
+        let length = u32::from_be_bytes(buffer[1..5].try_into().unwrap()) as usize;
+        if length > MAX_DATA_SIZE {
+            return Err(());
+        }
+        if buffer.len() < 5 + length {
+            return Ok(None);
+        }
+        let kind = buffer[0];
+        let payload: Vec<u8> = buffer.drain(..5 + length).skip(5).collect();
+        let frame = match kind {
+            1 => {
+                let text = String::from_utf8(payload).map_err(|_| ())?;
+                let (peer_id, display_name) = text.split_once('\n').ok_or(())?;
+                Frame::Hello {
+                    peer_id: peer_id.to_string(),
+                    display_name: display_name.to_string(),
+                }
+            }
+            2 => Frame::Accept,
+            3 => Frame::Deny,
+            4 => Frame::Data(payload),
+            5 => Frame::Goodbye,
+            _ => return Err(()),
+        };
+        Ok(Some(frame))
+    }
+}
+
+/// A TCP connection to another session.
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    closed: bool,
+}
+impl Connection {
+    fn from_stream(stream: TcpStream) -> std::io::Result<Connection> {
+        stream.set_nonblocking(true)?;
+        // Games send lots of small messages, which shouldn't be delayed.
+        stream.set_nodelay(true)?;
+        Ok(Connection {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            closed: false,
+        })
+    }
+
+    /// Connect to a session. This blocks for a short time.
+    pub fn connect(address: SocketAddr) -> std::io::Result<Connection> {
+        Connection::from_stream(TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?)
+    }
+
+    pub fn send(&mut self, frame: &Frame) {
+        self.outgoing.extend_from_slice(&frame.encode());
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        while !self.outgoing.is_empty() && !self.closed {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => self.closed = true,
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(_) => self.closed = true,
+            }
+        }
+    }
+
+    /// Send what's waiting to be sent and get the frames that have arrived.
+    pub fn poll(&mut self) -> Vec<Frame> {
+        self.flush();
+        let mut buffer = [0u8; 4096];
+        while !self.closed {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(size) => self.incoming.extend_from_slice(&buffer[..size]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(_) => self.closed = true,
+            }
+        }
+        let mut frames = Vec::new();
+        loop {
+            match Frame::take(&mut self.incoming) {
+                Ok(Some(frame)) => frames.push(frame),
+                Ok(None) => break,
+                Err(()) => {
+                    log!("Warning: invalid data from GameKit peer, disconnecting");
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        frames
+    }
+
+    /// Set once the other end has gone away. Frames that arrived before then
+    /// are still returned by [Self::poll].
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+/// The TCP socket a session accepts connections on.
+pub struct Listener {
+    listener: TcpListener,
+}
+impl Listener {
+    pub fn new(mode: NetworkMode) -> std::io::Result<Listener> {
+        let listener = TcpListener::bind((mode.bind_address(), 0))?;
+        listener.set_nonblocking(true)?;
+        Ok(Listener { listener })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().unwrap().port()
+    }
+
+    pub fn accept(&self) -> Option<Connection> {
+        let (stream, _) = self.listener.accept().ok()?;
+        Connection::from_stream(stream).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn announcements() {
+        let announcement = Announcement {
+            peer_id: "1234567".to_string(),
+            session_id: "com.example.game".to_string(),
+            display_name: "iPhone".to_string(),
+            advertising: true,
+            browsing: false,
+            available: true,
+            port: 51234,
+        };
+        let bytes = announcement.encode();
+        assert_eq!(Announcement::decode(&bytes), Some(announcement));
+        assert_eq!(Announcement::decode(b"something else\npeer=1\n"), None);
+        assert_eq!(Announcement::decode(b"touchHLE GameKit 1\npeer=1\n"), None);
+    }
+
+    #[test]
+    fn frames() {
+        let frames = [
+            Frame::Hello {
+                peer_id: "1234567".to_string(),
+                display_name: "iPod touch".to_string(),
+            },
+            Frame::Accept,
+            Frame::Data(vec![1, 2, 3]),
+            Frame::Goodbye,
+        ];
+        let mut buffer: Vec<u8> = frames.iter().flat_map(Frame::encode).collect();
+        // The last frame hasn't fully arrived yet.
+        let last = buffer.pop().unwrap();
+        for frame in &frames[..3] {
+            assert_eq!(Frame::take(&mut buffer), Ok(Some(frame.clone())));
+        }
+        assert_eq!(Frame::take(&mut buffer), Ok(None));
+        buffer.push(last);
+        assert_eq!(Frame::take(&mut buffer), Ok(Some(Frame::Goodbye)));
+        assert!(buffer.is_empty());
+
+        let mut buffer = vec![9, 0, 0, 0, 0];
+        assert_eq!(Frame::take(&mut buffer), Err(()));
+    }
+}
@@ -0,0 +1,169 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Graphics Services private framework.
+//!
+//! Only `GSEvent` is implemented, since it's the only way for apps to get key
+//! events from an external keyboard on iPhone OS: they override
+//! `-[UIApplication sendEvent:]` and look at the `GSEvent` behind the
+//! `UIEvent` (`-[UIEvent _gsEvent]`). There's no public header for any of this,
+//! so the layout is based on what apps are known to expect.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_graphics::CGPoint;
+use crate::mem::{ConstPtr, ConstVoidPtr, MutVoidPtr, Ptr, SafeRead};
+use crate::window::HardwareKey;
+use crate::Environment;
+
+pub type GSEventType = u32;
+pub const kGSEventKeyDown: GSEventType = 10;
+pub const kGSEventKeyUp: GSEventType = 11;
+
+pub type GSEventFlags = u32;
+pub const kGSEventFlagMaskAlphaShift: GSEventFlags = 1 << 16;
+pub const kGSEventFlagMaskShift: GSEventFlags = 1 << 17;
+pub const kGSEventFlagMaskControl: GSEventFlags = 1 << 18;
+pub const kGSEventFlagMaskAlternate: GSEventFlags = 1 << 19;
+pub const kGSEventFlagMaskCommand: GSEventFlags = 1 << 20;
+
+/// `GSEventRecord`, preceded by the Core Foundation object header, which is
+/// how it appears in memory when apps read it directly.
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+struct GSEventRecord {
+    cf_isa: ConstVoidPtr,
+    cf_info: u32,
+    type_: GSEventType,
+    subtype: u32,
+    location: CGPoint,
+    window_location: CGPoint,
+    window_context_id: i32,
+    /// In `mach_absolute_time` units.
+    timestamp: u64,
+    window: ConstVoidPtr,
+    flags: GSEventFlags,
+    sender_pid: u32,
+    /// Size of the type-specific data following the record.
+    info_size: u32,
+}
+
+/// `GSEventKeyInfo`, which follows the record for key events.
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+struct GSEventKeyInfo {
+    key_code: u16,
+    character_ignoring_modifiers: u16,
+    character: u16,
+    character_set: u16,
+    is_key_repeating: u8,
+}
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+struct GSKeyEvent {
+    record: GSEventRecord,
+    key_info: GSEventKeyInfo,
+}
+unsafe impl SafeRead for GSKeyEvent {}
+
+type GSEventRef = ConstPtr<GSKeyEvent>;
+
+/// Allocate a `GSEvent` for a key event in guest memory. It must be freed by
+/// the caller.
+pub fn new_key_event(env: &mut Environment, key: HardwareKey) -> MutVoidPtr {
+    let mut flags = 0;
+    for (is_set, mask) in [
+        (key.caps_lock, kGSEventFlagMaskAlphaShift),
+        (key.shift, kGSEventFlagMaskShift),
+        (key.control, kGSEventFlagMaskControl),
+        (key.alt, kGSEventFlagMaskAlternate),
+        (key.command, kGSEventFlagMaskCommand),
+    ] {
+        if is_set {
+            flags |= mask;
+        }
+    }
+    let character_ignoring_modifiers = key
+        .character
+        .and_then(|c| u16::try_from(c as u32).ok())
+        .unwrap_or(0);
+    let character = key
+        .character
+        .map(|c| {
+            if key.shift != key.caps_lock {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .and_then(|c| u16::try_from(c as u32).ok())
+        .unwrap_or(0);
+    let timestamp = env.clock.uptime().as_nanos().try_into().unwrap();
+    let zero = CGPoint { x: 0.0, y: 0.0 };
+    let event = GSKeyEvent {
+        record: GSEventRecord {
+            cf_isa: Ptr::null(),
+            cf_info: 0,
+            type_: if key.down {
+                kGSEventKeyDown
+            } else {
+                kGSEventKeyUp
+            },
+            subtype: 0,
+            location: zero,
+            window_location: zero,
+            window_context_id: 0,
+            timestamp,
+            window: Ptr::null(),
+            flags,
+            sender_pid: 0,
+            info_size: std::mem::size_of::<GSEventKeyInfo>() as u32,
+        },
+        key_info: GSEventKeyInfo {
+            key_code: key.usage,
+            character_ignoring_modifiers,
+            character,
+            character_set: 0,
+            is_key_repeating: key.repeat.into(),
+        },
+    };
+    env.mem.alloc_and_write(event).cast()
+}
+
+fn GSEventGetType(env: &mut Environment, event: GSEventRef) -> GSEventType {
+    env.mem.read(event).record.type_
+}
+fn GSEventGetSubType(env: &mut Environment, event: GSEventRef) -> u32 {
+    env.mem.read(event).record.subtype
+}
+fn GSEventGetLocationInWindow(env: &mut Environment, event: GSEventRef) -> CGPoint {
+    env.mem.read(event).record.window_location
+}
+fn GSEventGetTimestamp(env: &mut Environment, event: GSEventRef) -> u64 {
+    env.mem.read(event).record.timestamp
+}
+fn GSEventGetModifierFlags(env: &mut Environment, event: GSEventRef) -> GSEventFlags {
+    env.mem.read(event).record.flags
+}
+fn GSEventGetKeyCode(env: &mut Environment, event: GSEventRef) -> u16 {
+    env.mem.read(event).key_info.key_code
+}
+fn GSEventGetCharacterIgnoringModifiers(env: &mut Environment, event: GSEventRef) -> u16 {
+    env.mem.read(event).key_info.character_ignoring_modifiers
+}
+fn GSEventIsKeyRepeating(env: &mut Environment, event: GSEventRef) -> bool {
+    env.mem.read(event).key_info.is_key_repeating != 0
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(GSEventGetType(_)),
+    export_c_func!(GSEventGetSubType(_)),
+    export_c_func!(GSEventGetLocationInWindow(_)),
+    export_c_func!(GSEventGetTimestamp(_)),
+    export_c_func!(GSEventGetModifierFlags(_)),
+    export_c_func!(GSEventGetKeyCode(_)),
+    export_c_func!(GSEventGetCharacterIgnoringModifiers(_)),
+    export_c_func!(GSEventIsKeyRepeating(_)),
+];
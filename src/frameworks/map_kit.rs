@@ -0,0 +1,28 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The MapKit framework.
+//!
+//! Maps are made of "slippy map" tiles, like OpenStreetMap's, downloaded from
+//! the server set with `--map-tile-server=` and cached on disk (see [tiles]).
+//! Map views are drawn as an overlay (see [crate::frameworks::uikit::overlay]),
+//! like UIKit's own controls, and so are their annotations and callouts. The
+//! user's location comes from Core Location.
+//!
+//! Useful resources:
+//! - OpenStreetMap wiki: [Slippy map tilenames](https://wiki.openstreetmap.org/wiki/Slippy_map_tilenames)
+
+pub mod mk_annotation_view;
+pub mod mk_geometry;
+pub mod mk_map_view;
+pub mod mk_user_location;
+pub mod tiles;
+
+#[derive(Default)]
+pub struct State {
+    mk_annotation_view: mk_annotation_view::State,
+    mk_map_view: mk_map_view::State,
+    tiles: tiles::State,
+}
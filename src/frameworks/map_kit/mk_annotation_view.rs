@@ -0,0 +1,259 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MKAnnotationView` and `MKPinAnnotationView`.
+//!
+//! These are views, but they're never added to the map view's hierarchy.
+//! Instead, the map view draws them itself (see [super::mk_map_view]), using
+//! their image or drawing a pin. Views drawn by the app aren't supported.
+
+use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::NSUInteger;
+use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports};
+use crate::Environment;
+use std::collections::HashMap;
+
+pub type MKPinAnnotationColor = NSUInteger;
+pub const MKPinAnnotationColorRed: MKPinAnnotationColor = 0;
+pub const MKPinAnnotationColorGreen: MKPinAnnotationColor = 1;
+pub const MKPinAnnotationColorPurple: MKPinAnnotationColor = 2;
+
+/// Size of a pin view.
+const PIN_SIZE: CGSize = CGSize {
+    width: 32.0,
+    height: 39.0,
+};
+
+#[derive(Default)]
+pub struct State {
+    /// The state of every annotation view, since they're `UIView`s and can't
+    /// have their own host object.
+    views: HashMap<id, AnnotationViewState>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.map_kit.mk_annotation_view
+    }
+}
+
+pub(super) struct AnnotationViewState {
+    /// Something implementing `MKAnnotation`, strong reference.
+    pub(super) annotation: id,
+    /// `NSString*`, strong reference.
+    reuse_identifier: id,
+    /// `UIImage*`, strong reference, or [nil].
+    pub(super) image: id,
+    pub(super) center_offset: CGPoint,
+    pub(super) callout_offset: CGPoint,
+    pub(super) can_show_callout: bool,
+    pub(super) enabled: bool,
+    pub(super) selected: bool,
+    /// `UIView*`, strong reference, or [nil].
+    left_callout_accessory_view: id,
+    /// `UIView*`, strong reference, or [nil].
+    pub(super) right_callout_accessory_view: id,
+    /// Only for `MKPinAnnotationView`.
+    pub(super) pin_color: Option<MKPinAnnotationColor>,
+    animates_drop: bool,
+}
+impl Default for AnnotationViewState {
+    fn default() -> Self {
+        AnnotationViewState {
+            annotation: nil,
+            reuse_identifier: nil,
+            image: nil,
+            center_offset: CGPoint { x: 0.0, y: 0.0 },
+            callout_offset: CGPoint { x: 0.0, y: 0.0 },
+            can_show_callout: false,
+            enabled: true,
+            selected: false,
+            left_callout_accessory_view: nil,
+            right_callout_accessory_view: nil,
+            pin_color: None,
+            animates_drop: false,
+        }
+    }
+}
+
+pub(super) fn state(env: &mut Environment, view: id) -> &mut AnnotationViewState {
+    State::get(&mut env.framework_state)
+        .views
+        .entry(view)
+        .or_default()
+}
+
+/// For use by `UIView`'s `dealloc`.
+pub(crate) fn release_state(env: &mut Environment, view: id) {
+    let Some(state) = State::get(&mut env.framework_state).views.remove(&view) else {
+        return;
+    };
+    release(env, state.annotation);
+    release(env, state.reuse_identifier);
+    release(env, state.image);
+    release(env, state.left_callout_accessory_view);
+    release(env, state.right_callout_accessory_view);
+}
+
+fn init_with_annotation(
+    env: &mut Environment,
+    this: id,
+    annotation: id,
+    reuse_identifier: id,
+    pin: bool,
+) -> id {
+    let size = if pin {
+        PIN_SIZE
+    } else {
+        CGSize {
+            width: 0.0,
+            height: 0.0,
+        }
+    };
+    let frame = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size,
+    };
+    let this: id = msg![env; this initWithFrame:frame];
+    retain(env, annotation);
+    let reuse_identifier: id = msg![env; reuse_identifier copy];
+    let state = state(env, this);
+    state.annotation = annotation;
+    state.reuse_identifier = reuse_identifier;
+    if pin {
+        state.pin_color = Some(MKPinAnnotationColorRed);
+    }
+    this
+}
+
+fn set_view_property(
+    env: &mut Environment,
+    this: id,
+    value: id,
+    field: fn(&mut AnnotationViewState) -> &mut id,
+) {
+    retain(env, value);
+    let old = std::mem::replace(field(state(env, this)), value);
+    release(env, old);
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation MKAnnotationView: UIView
+
+- (id)initWithAnnotation:(id)annotation // id<MKAnnotation>
+         reuseIdentifier:(id)reuse_identifier { // NSString*
+    init_with_annotation(env, this, annotation, reuse_identifier, false)
+}
+
+- (())prepareForReuse {}
+
+- (id)reuseIdentifier {
+    state(env, this).reuse_identifier
+}
+
+- (id)annotation {
+    state(env, this).annotation
+}
+- (())setAnnotation:(id)annotation { // id<MKAnnotation>
+    set_view_property(env, this, annotation, |state| &mut state.annotation);
+}
+
+- (id)image {
+    state(env, this).image
+}
+- (())setImage:(id)image { // UIImage*
+    set_view_property(env, this, image, |state| &mut state.image);
+    if image != nil {
+        let size: CGSize = msg![env; image size];
+        let bounds = CGRect {
+            origin: CGPoint { x: 0.0, y: 0.0 },
+            size,
+        };
+        () = msg![env; this setBounds:bounds];
+    }
+}
+
+- (CGPoint)centerOffset {
+    state(env, this).center_offset
+}
+- (())setCenterOffset:(CGPoint)offset {
+    state(env, this).center_offset = offset;
+}
+
+- (CGPoint)calloutOffset {
+    state(env, this).callout_offset
+}
+- (())setCalloutOffset:(CGPoint)offset {
+    state(env, this).callout_offset = offset;
+}
+
+- (bool)canShowCallout {
+    state(env, this).can_show_callout
+}
+- (())setCanShowCallout:(bool)can_show {
+    state(env, this).can_show_callout = can_show;
+}
+
+- (bool)isEnabled {
+    state(env, this).enabled
+}
+- (())setEnabled:(bool)enabled {
+    state(env, this).enabled = enabled;
+}
+
+- (bool)isSelected {
+    state(env, this).selected
+}
+- (())setSelected:(bool)selected {
+    state(env, this).selected = selected;
+}
+- (())setSelected:(bool)selected
+         animated:(bool)_animated {
+    state(env, this).selected = selected;
+}
+
+- (id)leftCalloutAccessoryView {
+    state(env, this).left_callout_accessory_view
+}
+- (())setLeftCalloutAccessoryView:(id)view { // UIView*
+    set_view_property(env, this, view, |state| &mut state.left_callout_accessory_view);
+}
+
+- (id)rightCalloutAccessoryView {
+    state(env, this).right_callout_accessory_view
+}
+- (())setRightCalloutAccessoryView:(id)view { // UIView*
+    set_view_property(env, this, view, |state| &mut state.right_callout_accessory_view);
+}
+
+@end
+
+@implementation MKPinAnnotationView: MKAnnotationView
+
+- (id)initWithAnnotation:(id)annotation // id<MKAnnotation>
+         reuseIdentifier:(id)reuse_identifier { // NSString*
+    init_with_annotation(env, this, annotation, reuse_identifier, true)
+}
+
+- (MKPinAnnotationColor)pinColor {
+    state(env, this).pin_color.unwrap_or(MKPinAnnotationColorRed)
+}
+- (())setPinColor:(MKPinAnnotationColor)color {
+    state(env, this).pin_color = Some(color);
+}
+
+// TODO: animation
+- (bool)animatesDrop {
+    state(env, this).animates_drop
+}
+- (())setAnimatesDrop:(bool)animates {
+    state(env, this).animates_drop = animates;
+}
+
+@end
+
+};
@@ -0,0 +1,111 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MKGeometry.h` (`MKCoordinateSpan`, `MKCoordinateRegion` etc).
+//!
+//! `MKCoordinateSpanMake` and `MKCoordinateRegionMake` are inline functions, so
+//! they don't need to be implemented here.
+
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_location::cl_location::{
+    CLLocationCoordinate2D, CLLocationDegrees, CLLocationDistance,
+};
+use crate::location::EARTH_RADIUS;
+use crate::mem::SafeRead;
+use crate::Environment;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C, packed)]
+pub struct MKCoordinateSpan {
+    pub latitudeDelta: CLLocationDegrees,
+    pub longitudeDelta: CLLocationDegrees,
+}
+unsafe impl SafeRead for MKCoordinateSpan {}
+impl_GuestRet_for_large_struct!(MKCoordinateSpan);
+impl GuestArg for MKCoordinateSpan {
+    const REG_COUNT: usize = 4;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        MKCoordinateSpan {
+            latitudeDelta: GuestArg::from_regs(&regs[0..2]),
+            longitudeDelta: GuestArg::from_regs(&regs[2..4]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.latitudeDelta.to_regs(&mut regs[0..2]);
+        self.longitudeDelta.to_regs(&mut regs[2..4]);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C, packed)]
+pub struct MKCoordinateRegion {
+    pub center: CLLocationCoordinate2D,
+    pub span: MKCoordinateSpan,
+}
+unsafe impl SafeRead for MKCoordinateRegion {}
+impl_GuestRet_for_large_struct!(MKCoordinateRegion);
+impl GuestArg for MKCoordinateRegion {
+    const REG_COUNT: usize = 8;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        MKCoordinateRegion {
+            center: GuestArg::from_regs(&regs[0..4]),
+            span: GuestArg::from_regs(&regs[4..8]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.center.to_regs(&mut regs[0..4]);
+        self.span.to_regs(&mut regs[4..8]);
+    }
+}
+
+/// The span of a region with a particular size in meters around a latitude.
+fn span_for_distance(
+    latitude: CLLocationDegrees,
+    latitudinal_meters: CLLocationDistance,
+    longitudinal_meters: CLLocationDistance,
+) -> MKCoordinateSpan {
+    let meters_per_degree = EARTH_RADIUS * std::f64::consts::PI / 180.0;
+    // Lines of longitude get closer together away from the equator.
+    let longitude_scale = latitude.to_radians().cos().max(f64::EPSILON);
+    MKCoordinateSpan {
+        latitudeDelta: latitudinal_meters / meters_per_degree,
+        longitudeDelta: (longitudinal_meters / (meters_per_degree * longitude_scale)).min(360.0),
+    }
+}
+
+fn MKCoordinateRegionMakeWithDistance(
+    _env: &mut Environment,
+    center: CLLocationCoordinate2D,
+    latitudinal_meters: CLLocationDistance,
+    longitudinal_meters: CLLocationDistance,
+) -> MKCoordinateRegion {
+    MKCoordinateRegion {
+        center,
+        span: span_for_distance(center.latitude, latitudinal_meters, longitudinal_meters),
+    }
+}
+
+pub const FUNCTIONS: FunctionExports =
+    &[export_c_func!(MKCoordinateRegionMakeWithDistance(_, _, _))];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_spans() {
+        // A degree of latitude is about 111 km everywhere.
+        let span = span_for_distance(0.0, 111_195.0, 111_195.0);
+        assert!((span.latitudeDelta - 1.0).abs() < 0.001);
+        assert!((span.longitudeDelta - 1.0).abs() < 0.001);
+        // At 60° north, a degree of longitude is half as wide.
+        let span = span_for_distance(60.0, 111_195.0, 111_195.0);
+        assert!((span.latitudeDelta - 1.0).abs() < 0.001);
+        assert!((span.longitudeDelta - 2.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,1565 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MKMapView`.
+//!
+//! The map, its annotations and the callout of the selected annotation are
+//! drawn by the host as an overlay, since views aren't composited yet. The map
+//! can be dragged, pinched and double-tapped to zoom in. All map types show
+//! the same tiles, since there's only one tile server.
+//!
+//! The app's annotation objects are asked for their coordinates and titles
+//! from the run loop (see [handle_map_views]) rather than while drawing, so
+//! that no app code runs while a frame is being presented.
+
+use super::mk_annotation_view::{
+    self, MKPinAnnotationColor, MKPinAnnotationColorGreen, MKPinAnnotationColorPurple,
+};
+use super::mk_geometry::{MKCoordinateRegion, MKCoordinateSpan};
+use super::mk_user_location;
+use super::tiles::{self, TileKey, MAX_ZOOM, TILE_SIZE};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::core_location::cl_location::CLLocationCoordinate2D;
+use crate::frameworks::foundation::{ns_array, ns_string, NSUInteger};
+use crate::frameworks::uikit::overlay::{contains, rect, Canvas, Color, Overlay};
+use crate::frameworks::uikit::{ui_font, ui_image, ui_view};
+use crate::objc::{
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    SEL,
+};
+use crate::Environment;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub type MKMapType = NSUInteger;
+pub const MKMapTypeStandard: MKMapType = 0;
+#[allow(dead_code)]
+pub const MKMapTypeSatellite: MKMapType = 1;
+#[allow(dead_code)]
+pub const MKMapTypeHybrid: MKMapType = 2;
+
+/// Zoom levels are continuous here: the world is `TILE_SIZE * 2^zoom` points
+/// wide. This is the furthest out a map can be zoomed.
+const MIN_ZOOM: f64 = 1.0;
+/// Tiles are scaled up beyond the deepest zoom level the server has.
+const MAX_VIEW_ZOOM: f64 = MAX_ZOOM as f64 + 2.0;
+/// How many zoom levels out a loaded tile can be used from while the tile that
+/// belongs there is loading.
+const FALLBACK_LEVELS: u8 = 4;
+
+/// Apps sometimes set the region before the view has a size, so the region is
+/// fitted to this instead.
+const DEFAULT_SIZE: CGSize = CGSize {
+    width: 320.0,
+    height: 460.0,
+};
+
+/// How often the annotations and tiles are checked for changes.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Distance in points a touch has to move before it drags rather than taps.
+const DRAG_THRESHOLD: CGFloat = 8.0;
+
+/// Height of a pin from its point to the top of its head.
+const PIN_HEIGHT: CGFloat = 30.0;
+const PIN_HEAD_RADIUS: CGFloat = 6.0;
+
+const CALLOUT_TITLE_SIZE: CGFloat = 16.0;
+const CALLOUT_SUBTITLE_SIZE: CGFloat = 12.0;
+const CALLOUT_PADDING: CGFloat = 10.0;
+const CALLOUT_ARROW_SIZE: CGFloat = 8.0;
+const CALLOUT_ACCESSORY_SIZE: CGFloat = 24.0;
+
+const BACKGROUND_COLOR: Color = (0.93, 0.92, 0.89, 1.0);
+const GRID_COLOR: Color = (0.82, 0.81, 0.78, 1.0);
+
+#[derive(Default)]
+pub struct State {
+    views: HashMap<id, MapViewState>,
+    last_refresh: Option<Instant>,
+    overlay: Option<Overlay>,
+    /// What the overlay shows: map views with their frame (x, y, width,
+    /// height) and generation, and the tile generation.
+    overlay_contents: (Vec<(id, [CGFloat; 4], u32)>, u32),
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.map_kit.mk_map_view
+    }
+}
+
+struct MapViewState {
+    /// Weak reference.
+    delegate: id,
+    map_type: MKMapType,
+    /// See [tiles::world_point].
+    center: (f64, f64),
+    zoom: f64,
+    scroll_enabled: bool,
+    zoom_enabled: bool,
+    /// `CLLocationManager*`, strong reference, or [nil] if the user's location
+    /// isn't being shown.
+    location_manager: id,
+    /// `MKUserLocation*`, strong reference, created when first needed.
+    user_location: id,
+    /// Things implementing `MKAnnotation`, strong references, in the order
+    /// they were added. The user location isn't included.
+    annotations: Vec<id>,
+    /// `MKAnnotationView*`s by annotation, strong references. These are
+    /// created by [handle_map_views].
+    annotation_views: HashMap<id, id>,
+    /// One of the annotations, or [nil].
+    selected: id,
+    /// What was last found out about the annotations, back to front.
+    drawn: Vec<DrawnAnnotation>,
+    user_coordinate: Option<CLLocationCoordinate2D>,
+    hidden: bool,
+    /// Set between `mapViewWillStartLoadingMap:` and
+    /// `mapViewDidFinishLoadingMap:`.
+    loading: bool,
+    gesture: Option<Gesture>,
+    /// Incremented whenever something that's drawn changes.
+    generation: u32,
+}
+impl Default for MapViewState {
+    fn default() -> Self {
+        MapViewState {
+            delegate: nil,
+            map_type: MKMapTypeStandard,
+            center: (0.5, 0.5),
+            zoom: MIN_ZOOM,
+            scroll_enabled: true,
+            zoom_enabled: true,
+            location_manager: nil,
+            user_location: nil,
+            annotations: Vec::new(),
+            annotation_views: HashMap::new(),
+            selected: nil,
+            drawn: Vec::new(),
+            user_coordinate: None,
+            hidden: false,
+            loading: false,
+            gesture: None,
+            generation: 0,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+struct DrawnAnnotation {
+    annotation: id,
+    /// `MKAnnotationView*`
+    view: id,
+    coordinate: CLLocationCoordinate2D,
+    title: Option<String>,
+    subtitle: Option<String>,
+}
+
+struct Gesture {
+    /// `UITouch*`s that are down, with their last location in the view.
+    touches: Vec<(id, CGPoint)>,
+    start: CGPoint,
+    /// Set once the touches have moved far enough to change the region.
+    moved: bool,
+    /// Set if there was ever more than one touch.
+    multiple: bool,
+}
+
+/// Where a callout goes, in the map view's co-ordinates.
+struct Callout {
+    frame: CGRect,
+    accessory: Option<CGRect>,
+    /// The point its arrow points at.
+    anchor: CGPoint,
+}
+
+fn world_size(zoom: f64) -> f64 {
+    TILE_SIZE * zoom.exp2()
+}
+
+impl MapViewState {
+    fn view_point(&self, size: CGSize, world: (f64, f64)) -> CGPoint {
+        let scale = world_size(self.zoom);
+        // The world wraps around horizontally, so use the nearest copy.
+        let dx = world.0 - self.center.0;
+        let dx = dx - dx.round();
+        let dy = world.1 - self.center.1;
+        CGPoint {
+            x: (size.width as f64 / 2.0 + dx * scale) as CGFloat,
+            y: (size.height as f64 / 2.0 + dy * scale) as CGFloat,
+        }
+    }
+
+    fn world_point_at(&self, size: CGSize, point: CGPoint) -> (f64, f64) {
+        let scale = world_size(self.zoom);
+        (
+            self.center.0 + (point.x as f64 - size.width as f64 / 2.0) / scale,
+            self.center.1 + (point.y as f64 - size.height as f64 / 2.0) / scale,
+        )
+    }
+
+    fn set_center(&mut self, world: (f64, f64)) {
+        self.center = (world.0.rem_euclid(1.0), world.1.clamp(0.0, 1.0));
+        self.generation += 1;
+    }
+
+    fn set_zoom(&mut self, zoom: f64) {
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_VIEW_ZOOM);
+        self.generation += 1;
+    }
+
+    /// Move the map so a world point is at a point in the view.
+    fn move_point_to(&mut self, size: CGSize, world: (f64, f64), point: CGPoint) {
+        let scale = world_size(self.zoom);
+        self.set_center((
+            world.0 - (point.x as f64 - size.width as f64 / 2.0) / scale,
+            world.1 - (point.y as f64 - size.height as f64 / 2.0) / scale,
+        ));
+    }
+
+    fn region(&self, size: CGSize) -> MKCoordinateRegion {
+        let scale = world_size(self.zoom);
+        let half_height = size.height as f64 / 2.0 / scale;
+        let (north, _) =
+            tiles::coordinate_for_world_point((0.0, (self.center.1 - half_height).max(0.0)));
+        let (south, _) =
+            tiles::coordinate_for_world_point((0.0, (self.center.1 + half_height).min(1.0)));
+        let (latitude, longitude) = tiles::coordinate_for_world_point(self.center);
+        MKCoordinateRegion {
+            center: CLLocationCoordinate2D {
+                latitude,
+                longitude,
+            },
+            span: MKCoordinateSpan {
+                latitudeDelta: north - south,
+                longitudeDelta: (360.0 * size.width as f64 / scale).min(360.0),
+            },
+        }
+    }
+}
+
+/// The world point of a region's center, and the zoom level where the whole
+/// region fits in a view.
+fn fit_region(region: MKCoordinateRegion, size: CGSize) -> ((f64, f64), f64) {
+    let (latitude, longitude) = (region.center.latitude, region.center.longitude);
+    let latitude_delta = region.span.latitudeDelta.abs();
+    let longitude_delta = region.span.longitudeDelta.abs();
+    let center = tiles::world_point(latitude, longitude);
+    let (_, north) = tiles::world_point(latitude + latitude_delta / 2.0, longitude);
+    let (_, south) = tiles::world_point(latitude - latitude_delta / 2.0, longitude);
+    let zoom_for = |points: CGFloat, world: f64| {
+        if world > 0.0 {
+            (points as f64 / (TILE_SIZE * world)).log2()
+        } else {
+            MAX_VIEW_ZOOM
+        }
+    };
+    let zoom = zoom_for(size.width, longitude_delta / 360.0)
+        .min(zoom_for(size.height, south - north))
+        .clamp(MIN_ZOOM, MAX_VIEW_ZOOM);
+    (center, zoom)
+}
+
+/// The tiles that cover a view, with where their top-left corners go, and how
+/// big they are drawn.
+fn tile_layout(center: (f64, f64), zoom: f64, size: CGSize) -> (Vec<(TileKey, CGPoint)>, CGFloat) {
+    let tile_zoom = (zoom.round() as u8).min(MAX_ZOOM);
+    let count = 1i64 << tile_zoom;
+    let scale = world_size(zoom);
+    let left = center.0 - size.width as f64 / 2.0 / scale;
+    let top = center.1 - size.height as f64 / 2.0 / scale;
+    let right = left + size.width as f64 / scale;
+    let bottom = top + size.height as f64 / scale;
+    let first_x = (left * count as f64).floor() as i64;
+    let last_x = (right * count as f64).ceil() as i64;
+    let first_y = ((top * count as f64).floor() as i64).max(0);
+    let last_y = ((bottom * count as f64).ceil() as i64).min(count);
+
+    let mut layout = Vec::new();
+    for y in first_y..last_y {
+        for x in first_x..last_x {
+            let key = TileKey {
+                zoom: tile_zoom,
+                x: x.rem_euclid(count) as u32,
+                y: y as u32,
+            };
+            let origin = CGPoint {
+                x: ((x as f64 / count as f64 - left) * scale) as CGFloat,
+                y: ((y as f64 / count as f64 - top) * scale) as CGFloat,
+            };
+            layout.push((key, origin));
+        }
+    }
+    (layout, (scale / count as f64) as CGFloat)
+}
+
+fn state(env: &mut Environment, map_view: id) -> &mut MapViewState {
+    if !State::get(&mut env.framework_state)
+        .views
+        .contains_key(&map_view)
+    {
+        State::get(&mut env.framework_state)
+            .views
+            .insert(map_view, MapViewState::default());
+        // Pinching needs two touches.
+        () = msg![env; map_view setMultipleTouchEnabled:true];
+    }
+    State::get(&mut env.framework_state)
+        .views
+        .get_mut(&map_view)
+        .unwrap()
+}
+
+/// For use by `UIView`'s `dealloc`.
+pub(crate) fn release_state(env: &mut Environment, map_view: id) {
+    let Some(state) = State::get(&mut env.framework_state).views.remove(&map_view) else {
+        return;
+    };
+    if state.location_manager != nil {
+        let manager = state.location_manager;
+        () = msg![env; manager stopUpdatingLocation];
+        release(env, manager);
+    }
+    release(env, state.user_location);
+    for annotation in state.annotations {
+        release(env, annotation);
+    }
+    for (_, view) in state.annotation_views {
+        release(env, view);
+    }
+}
+
+/// The size of a map view, or a typical size if it doesn't have one yet.
+fn view_size(env: &mut Environment, map_view: id) -> CGSize {
+    let bounds: CGRect = msg![env; map_view bounds];
+    if bounds.size.width > 0.0 && bounds.size.height > 0.0 {
+        bounds.size
+    } else {
+        DEFAULT_SIZE
+    }
+}
+
+fn user_location(env: &mut Environment, map_view: id) -> id {
+    let existing = state(env, map_view).user_location;
+    if existing != nil {
+        return existing;
+    }
+    let new = mk_user_location::new(env);
+    state(env, map_view).user_location = new;
+    new
+}
+
+fn responding_delegate(env: &mut Environment, map_view: id, selector: &str) -> Option<(id, SEL)> {
+    let delegate = state(env, map_view).delegate;
+    if delegate == nil {
+        return None;
+    }
+    // The selector won't exist if no class implements this method.
+    let selector = env.objc.lookup_selector(selector)?;
+    if msg![env; delegate respondsToSelector:selector] {
+        Some((delegate, selector))
+    } else {
+        None
+    }
+}
+
+fn notify_delegate(env: &mut Environment, map_view: id, selector: &str) {
+    if let Some((delegate, selector)) = responding_delegate(env, map_view, selector) {
+        let () = msg_send(env, (delegate, selector, map_view));
+    }
+}
+
+fn notify_region_change(env: &mut Environment, map_view: id, selector: &str, animated: bool) {
+    if let Some((delegate, selector)) = responding_delegate(env, map_view, selector) {
+        let () = msg_send(env, (delegate, selector, map_view, animated));
+    }
+}
+
+/// Change the region and tell the delegate.
+fn change_region(env: &mut Environment, map_view: id, center: (f64, f64), zoom: f64) {
+    notify_region_change(env, map_view, "mapView:regionWillChangeAnimated:", false);
+    let state = state(env, map_view);
+    state.set_zoom(zoom);
+    state.set_center(center);
+    notify_region_change(env, map_view, "mapView:regionDidChangeAnimated:", false);
+}
+
+fn select(env: &mut Environment, map_view: id, annotation: id) {
+    let old = state(env, map_view).selected;
+    if old == annotation {
+        return;
+    }
+    if old != nil {
+        deselect(env, map_view, old);
+    }
+    let state = state(env, map_view);
+    if !state.annotations.contains(&annotation) {
+        return;
+    }
+    state.selected = annotation;
+    state.generation += 1;
+    if let Some(&view) = state.annotation_views.get(&annotation) {
+        mk_annotation_view::state(env, view).selected = true;
+        if let Some((delegate, selector)) =
+            responding_delegate(env, map_view, "mapView:didSelectAnnotationView:")
+        {
+            let () = msg_send(env, (delegate, selector, map_view, view));
+        }
+    }
+}
+
+fn deselect(env: &mut Environment, map_view: id, annotation: id) {
+    let state = state(env, map_view);
+    if annotation == nil || state.selected != annotation {
+        return;
+    }
+    state.selected = nil;
+    state.generation += 1;
+    if let Some(&view) = state.annotation_views.get(&annotation) {
+        mk_annotation_view::state(env, view).selected = false;
+        if let Some((delegate, selector)) =
+            responding_delegate(env, map_view, "mapView:didDeselectAnnotationView:")
+        {
+            let () = msg_send(env, (delegate, selector, map_view, view));
+        }
+    }
+}
+
+fn add_annotation(env: &mut Environment, map_view: id, annotation: id) {
+    let state = state(env, map_view);
+    if state.annotations.contains(&annotation) {
+        return;
+    }
+    state.annotations.push(annotation);
+    state.generation += 1;
+    retain(env, annotation);
+}
+
+fn remove_annotation(env: &mut Environment, map_view: id, annotation: id) {
+    deselect(env, map_view, annotation);
+    let state = state(env, map_view);
+    let Some(idx) = state.annotations.iter().position(|&a| a == annotation) else {
+        return;
+    };
+    state.annotations.remove(idx);
+    state.drawn.retain(|drawn| drawn.annotation != annotation);
+    state.generation += 1;
+    let view = state.annotation_views.remove(&annotation);
+    release(env, annotation);
+    if let Some(view) = view {
+        release(env, view);
+    }
+}
+
+fn array_objects(env: &mut Environment, array: id) -> Vec<id> {
+    let count: NSUInteger = msg![env; array count];
+    (0..count)
+        .map(|i| msg![env; array objectAtIndex:i])
+        .collect()
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation MKMapView: UIView
+
+- (id)delegate {
+    state(env, this).delegate
+}
+- (())setDelegate:(id)delegate { // something implementing MKMapViewDelegate
+    state(env, this).delegate = delegate;
+}
+
+- (MKMapType)mapType {
+    state(env, this).map_type
+}
+- (())setMapType:(MKMapType)map_type {
+    if map_type != MKMapTypeStandard {
+        log_dbg!("MKMapView {:?}: map type {} will look like a standard map", this, map_type);
+    }
+    state(env, this).map_type = map_type;
+}
+
+- (MKCoordinateRegion)region {
+    let size = view_size(env, this);
+    state(env, this).region(size)
+}
+- (())setRegion:(MKCoordinateRegion)region {
+    let size = view_size(env, this);
+    let (center, zoom) = fit_region(region, size);
+    change_region(env, this, center, zoom);
+}
+// TODO: animation
+- (())setRegion:(MKCoordinateRegion)region
+       animated:(bool)_animated {
+    msg![env; this setRegion:region]
+}
+- (MKCoordinateRegion)regionThatFits:(MKCoordinateRegion)region {
+    let size = view_size(env, this);
+    let (center, zoom) = fit_region(region, size);
+    let mut fitted = MapViewState::default();
+    fitted.set_zoom(zoom);
+    fitted.set_center(center);
+    fitted.region(size)
+}
+
+- (CLLocationCoordinate2D)centerCoordinate {
+    let (latitude, longitude) = tiles::coordinate_for_world_point(state(env, this).center);
+    CLLocationCoordinate2D { latitude, longitude }
+}
+- (())setCenterCoordinate:(CLLocationCoordinate2D)coordinate {
+    let center = tiles::world_point(coordinate.latitude, coordinate.longitude);
+    let zoom = state(env, this).zoom;
+    change_region(env, this, center, zoom);
+}
+// TODO: animation
+- (())setCenterCoordinate:(CLLocationCoordinate2D)coordinate
+                 animated:(bool)_animated {
+    msg![env; this setCenterCoordinate:coordinate]
+}
+
+- (bool)isScrollEnabled {
+    state(env, this).scroll_enabled
+}
+- (())setScrollEnabled:(bool)enabled {
+    state(env, this).scroll_enabled = enabled;
+}
+- (bool)isZoomEnabled {
+    state(env, this).zoom_enabled
+}
+- (())setZoomEnabled:(bool)enabled {
+    state(env, this).zoom_enabled = enabled;
+}
+
+- (bool)showsUserLocation {
+    state(env, this).location_manager != nil
+}
+- (())setShowsUserLocation:(bool)shows {
+    let manager = state(env, this).location_manager;
+    if shows == (manager != nil) {
+        return;
+    }
+    let user_location = user_location(env, this);
+    if shows {
+        let manager: id = msg_class![env; CLLocationManager new];
+        () = msg![env; manager startUpdatingLocation];
+        state(env, this).location_manager = manager;
+    } else {
+        () = msg![env; manager stopUpdatingLocation];
+        release(env, manager);
+        mk_user_location::set_location(env, user_location, nil);
+        let state = state(env, this);
+        state.location_manager = nil;
+        state.user_coordinate = None;
+        state.generation += 1;
+    }
+}
+- (id)userLocation {
+    user_location(env, this)
+}
+- (bool)isUserLocationVisible {
+    let size = view_size(env, this);
+    let state = state(env, this);
+    let Some(coordinate) = state.user_coordinate else {
+        return false;
+    };
+    let world = tiles::world_point(coordinate.latitude, coordinate.longitude);
+    let point = state.view_point(size, world);
+    contains(rect(0.0, 0.0, size.width, size.height), (point.x, point.y))
+}
+
+- (id)annotations {
+    let mut annotations = state(env, this).annotations.clone();
+    if state(env, this).user_coordinate.is_some() {
+        annotations.push(user_location(env, this));
+    }
+    for &annotation in &annotations {
+        retain(env, annotation);
+    }
+    let array = ns_array::from_vec(env, annotations);
+    autorelease(env, array)
+}
+- (())addAnnotation:(id)annotation { // id<MKAnnotation>
+    add_annotation(env, this, annotation);
+}
+- (())addAnnotations:(id)annotations { // NSArray* of id<MKAnnotation>
+    for annotation in array_objects(env, annotations) {
+        add_annotation(env, this, annotation);
+    }
+}
+- (())removeAnnotation:(id)annotation { // id<MKAnnotation>
+    remove_annotation(env, this, annotation);
+}
+- (())removeAnnotations:(id)annotations { // NSArray* of id<MKAnnotation>
+    for annotation in array_objects(env, annotations) {
+        remove_annotation(env, this, annotation);
+    }
+}
+
+- (id)viewForAnnotation:(id)annotation { // id<MKAnnotation>
+    state(env, this)
+        .annotation_views
+        .get(&annotation)
+        .copied()
+        .unwrap_or(nil)
+}
+// Annotation views aren't reused, so there's never one to dequeue.
+- (id)dequeueReusableAnnotationViewWithIdentifier:(id)_identifier { // NSString*
+    nil
+}
+
+- (id)selectedAnnotations {
+    let selected = state(env, this).selected;
+    if selected == nil {
+        return nil;
+    }
+    retain(env, selected);
+    let array = ns_array::from_vec(env, vec![selected]);
+    autorelease(env, array)
+}
+- (())setSelectedAnnotations:(id)annotations { // NSArray* of id<MKAnnotation>
+    let annotations = if annotations == nil {
+        Vec::new()
+    } else {
+        array_objects(env, annotations)
+    };
+    match annotations.first() {
+        Some(&annotation) => select(env, this, annotation),
+        None => {
+            let selected = state(env, this).selected;
+            deselect(env, this, selected);
+        }
+    }
+}
+- (())selectAnnotation:(id)annotation // id<MKAnnotation>
+              animated:(bool)_animated {
+    select(env, this, annotation);
+}
+- (())deselectAnnotation:(id)annotation // id<MKAnnotation>
+                animated:(bool)_animated {
+    deselect(env, this, annotation);
+}
+
+- (CGPoint)convertCoordinate:(CLLocationCoordinate2D)coordinate
+               toPointToView:(id)view { // UIView*
+    let size = view_size(env, this);
+    let world = tiles::world_point(coordinate.latitude, coordinate.longitude);
+    let point = state(env, this).view_point(size, world);
+    msg![env; this convertPoint:point toView:view]
+}
+- (CLLocationCoordinate2D)convertPoint:(CGPoint)point
+                  toCoordinateFromView:(id)view { // UIView*
+    let point: CGPoint = msg![env; this convertPoint:point fromView:view];
+    let size = view_size(env, this);
+    let (x, y) = state(env, this).world_point_at(size, point);
+    let (latitude, longitude) = tiles::coordinate_for_world_point((x.rem_euclid(1.0), y));
+    CLLocationCoordinate2D { latitude, longitude }
+}
+
+- (())touchesBegan:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    touches_began(env, this, touches);
+}
+- (())touchesMoved:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    touches_moved(env, this, touches);
+}
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    touches_ended(env, this, touches, false);
+}
+- (())touchesCancelled:(id)touches // NSSet* of UITouch*
+             withEvent:(id)_event { // UIEvent*
+    touches_ended(env, this, touches, true);
+}
+
+@end
+
+};
+
+/// The locations of some touches in a map view.
+fn touch_locations(env: &mut Environment, map_view: id, touches: id) -> Vec<(id, CGPoint)> {
+    let touches: id = msg![env; touches allObjects];
+    array_objects(env, touches)
+        .into_iter()
+        .map(|touch| (touch, msg![env; touch locationInView:map_view]))
+        .collect()
+}
+
+fn distance(a: CGPoint, b: CGPoint) -> CGFloat {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+fn midpoint(a: CGPoint, b: CGPoint) -> CGPoint {
+    CGPoint {
+        x: (a.x + b.x) / 2.0,
+        y: (a.y + b.y) / 2.0,
+    }
+}
+
+fn touches_began(env: &mut Environment, map_view: id, touches: id) {
+    let began = touch_locations(env, map_view, touches);
+    let state = state(env, map_view);
+    let Some(&(_, start)) = began.first() else {
+        return;
+    };
+    let gesture = state.gesture.get_or_insert_with(|| Gesture {
+        touches: Vec::new(),
+        start,
+        moved: false,
+        multiple: false,
+    });
+    for (touch, location) in began {
+        if !gesture.touches.iter().any(|&(other, _)| other == touch) {
+            gesture.touches.push((touch, location));
+        }
+    }
+    gesture.multiple |= gesture.touches.len() > 1;
+}
+
+fn touches_moved(env: &mut Environment, map_view: id, touches: id) {
+    let moved = touch_locations(env, map_view, touches);
+    let size = view_size(env, map_view);
+    let state = state(env, map_view);
+    let (scroll_enabled, zoom_enabled) = (state.scroll_enabled, state.zoom_enabled);
+    let Some(gesture) = state.gesture.as_mut() else {
+        return;
+    };
+    let old: Vec<CGPoint> = gesture
+        .touches
+        .iter()
+        .map(|&(_, location)| location)
+        .collect();
+    for (touch, location) in moved {
+        if let Some(entry) = gesture
+            .touches
+            .iter_mut()
+            .find(|(other, _)| *other == touch)
+        {
+            entry.1 = location;
+        }
+    }
+    let new: Vec<CGPoint> = gesture
+        .touches
+        .iter()
+        .map(|&(_, location)| location)
+        .collect();
+    let Some(&first) = new.first() else {
+        return;
+    };
+
+    let started_moving = !gesture.moved;
+    if started_moving {
+        // Small movements don't stop a touch from being a tap.
+        if !gesture.multiple && distance(first, gesture.start) < DRAG_THRESHOLD {
+            return;
+        }
+        gesture.moved = true;
+    }
+
+    match (old.as_slice(), new.as_slice()) {
+        ([a0, b0, ..], [a1, b1, ..]) if zoom_enabled => {
+            let (d0, d1) = (distance(*a0, *b0), distance(*a1, *b1));
+            if d0 >= 1.0 && d1 >= 1.0 {
+                let anchor = state.world_point_at(size, midpoint(*a0, *b0));
+                state.set_zoom(state.zoom + (d1 as f64 / d0 as f64).log2());
+                state.move_point_to(size, anchor, midpoint(*a1, *b1));
+            }
+        }
+        ([a0, ..], [a1, ..]) if scroll_enabled => {
+            let anchor = state.world_point_at(size, *a0);
+            state.move_point_to(size, anchor, *a1);
+        }
+        _ => (),
+    }
+
+    if started_moving {
+        notify_region_change(env, map_view, "mapView:regionWillChangeAnimated:", false);
+    }
+}
+
+fn touches_ended(env: &mut Environment, map_view: id, touches: id, cancelled: bool) {
+    let ended = touch_locations(env, map_view, touches);
+    let tap_count: NSUInteger = match ended.first() {
+        Some(&(touch, _)) => msg![env; touch tapCount],
+        None => 0,
+    };
+    let map_state = state(env, map_view);
+    let Some(gesture) = map_state.gesture.as_mut() else {
+        return;
+    };
+    gesture
+        .touches
+        .retain(|&(touch, _)| !ended.iter().any(|&(other, _)| other == touch));
+    if !gesture.touches.is_empty() {
+        return;
+    }
+    let gesture = map_state.gesture.take().unwrap();
+    let zoom_enabled = map_state.zoom_enabled;
+
+    if gesture.moved {
+        notify_region_change(env, map_view, "mapView:regionDidChangeAnimated:", false);
+        return;
+    }
+    if cancelled || gesture.multiple {
+        return;
+    }
+    let Some(&(_, location)) = ended.first() else {
+        return;
+    };
+    // UIKit creates and drains autorelease pools when handling events.
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    if tap_count == 2 && zoom_enabled {
+        // Zoom in on the tapped point.
+        let size = view_size(env, map_view);
+        let state = state(env, map_view);
+        let anchor = state.world_point_at(size, location);
+        let mut zoomed = MapViewState {
+            center: state.center,
+            zoom: state.zoom,
+            ..Default::default()
+        };
+        zoomed.set_zoom(state.zoom + 1.0);
+        zoomed.move_point_to(size, anchor, location);
+        change_region(env, map_view, zoomed.center, zoomed.zoom);
+    } else {
+        tap(env, map_view, location);
+    }
+    release(env, pool);
+}
+
+fn tap(env: &mut Environment, map_view: id, location: CGPoint) {
+    let point = (location.x, location.y);
+    let size = view_size(env, map_view);
+
+    let selected = state(env, map_view).selected;
+    if selected != nil {
+        if let Some((drawn, callout)) = selected_callout(env, map_view, size) {
+            let view = drawn.view;
+            if callout
+                .accessory
+                .is_some_and(|accessory| contains(accessory, point))
+            {
+                let control = mk_annotation_view::state(env, view).right_callout_accessory_view;
+                if let Some((delegate, selector)) = responding_delegate(
+                    env,
+                    map_view,
+                    "mapView:annotationView:calloutAccessoryControlTapped:",
+                ) {
+                    let () = msg_send(env, (delegate, selector, map_view, view, control));
+                }
+                return;
+            }
+            if contains(callout.frame, point) {
+                return;
+            }
+        }
+    }
+
+    // The front-most annotations are at the end.
+    let drawn = state(env, map_view).drawn.clone();
+    for annotation in drawn.iter().rev() {
+        let map_state = state(env, map_view);
+        let annotation_point = map_state.view_point(
+            size,
+            tiles::world_point(
+                annotation.coordinate.latitude,
+                annotation.coordinate.longitude,
+            ),
+        );
+        let Some((marker, _)) = marker_geometry(env, annotation.view, annotation_point) else {
+            continue;
+        };
+        // Annotations are small, so allow for some imprecision.
+        let slop = 6.0;
+        let marker = rect(
+            marker.origin.x - slop,
+            marker.origin.y - slop,
+            marker.size.width + slop * 2.0,
+            marker.size.height + slop * 2.0,
+        );
+        if contains(marker, point) && mk_annotation_view::state(env, annotation.view).enabled {
+            select(env, map_view, annotation.annotation);
+            return;
+        }
+    }
+
+    deselect(env, map_view, selected);
+}
+
+/// Where an annotation view is drawn, given where its coordinate is: the rect
+/// it covers, and the point its callout hangs from. [None] if it's not drawn.
+fn marker_geometry(env: &mut Environment, view: id, point: CGPoint) -> Option<(CGRect, CGPoint)> {
+    let &mut mk_annotation_view::AnnotationViewState {
+        image,
+        center_offset,
+        callout_offset,
+        pin_color,
+        ..
+    } = mk_annotation_view::state(env, view);
+    let (marker, anchor) = if image != nil {
+        let (width, height) = ui_image::borrow_image(&env.objc, image).dimensions();
+        let (width, height) = (width as CGFloat, height as CGFloat);
+        let center = CGPoint {
+            x: point.x + center_offset.x,
+            y: point.y + center_offset.y,
+        };
+        let marker = rect(
+            center.x - width / 2.0,
+            center.y - height / 2.0,
+            width,
+            height,
+        );
+        (
+            marker,
+            CGPoint {
+                x: center.x,
+                y: marker.origin.y,
+            },
+        )
+    } else if pin_color.is_some() {
+        let marker = rect(
+            point.x - PIN_HEAD_RADIUS,
+            point.y - PIN_HEIGHT - PIN_HEAD_RADIUS,
+            PIN_HEAD_RADIUS * 2.0,
+            PIN_HEIGHT + PIN_HEAD_RADIUS,
+        );
+        (
+            marker,
+            CGPoint {
+                x: point.x,
+                y: marker.origin.y,
+            },
+        )
+    } else {
+        return None;
+    };
+    let anchor = CGPoint {
+        x: anchor.x + callout_offset.x,
+        y: anchor.y + callout_offset.y,
+    };
+    Some((marker, anchor))
+}
+
+/// The callout for the selected annotation, if it has one.
+fn selected_callout(
+    env: &mut Environment,
+    map_view: id,
+    size: CGSize,
+) -> Option<(DrawnAnnotation, Callout)> {
+    let state = state(env, map_view);
+    let selected = state.selected;
+    let drawn = state
+        .drawn
+        .iter()
+        .find(|drawn| drawn.annotation == selected)?
+        .clone();
+    let point = state.view_point(
+        size,
+        tiles::world_point(drawn.coordinate.latitude, drawn.coordinate.longitude),
+    );
+    let view_state = mk_annotation_view::state(env, drawn.view);
+    let has_accessory = view_state.right_callout_accessory_view != nil;
+    if !view_state.can_show_callout {
+        return None;
+    }
+    let title = drawn.title.as_deref().filter(|title| !title.is_empty())?;
+    let (_, anchor) = marker_geometry(env, drawn.view, point)?;
+    let callout = callout_layout(
+        env,
+        size,
+        anchor,
+        title,
+        drawn.subtitle.as_deref(),
+        has_accessory,
+    );
+    Some((drawn, callout))
+}
+
+fn callout_layout(
+    env: &mut Environment,
+    size: CGSize,
+    anchor: CGPoint,
+    title: &str,
+    subtitle: Option<&str>,
+    has_accessory: bool,
+) -> Callout {
+    let (title_width, title_height) =
+        ui_font::system_font(env, true, title).calculate_text_size(CALLOUT_TITLE_SIZE, title, None);
+    let (subtitle_width, subtitle_height) = match subtitle {
+        Some(subtitle) if !subtitle.is_empty() => ui_font::system_font(env, false, subtitle)
+            .calculate_text_size(CALLOUT_SUBTITLE_SIZE, subtitle, None),
+        _ => (0.0, 0.0),
+    };
+    let accessory_width = if has_accessory {
+        CALLOUT_ACCESSORY_SIZE + CALLOUT_PADDING
+    } else {
+        0.0
+    };
+    let width = (title_width.max(subtitle_width) + accessory_width + CALLOUT_PADDING * 2.0)
+        .min(size.width - CALLOUT_PADDING * 2.0);
+    let height = (title_height + subtitle_height + CALLOUT_PADDING * 2.0)
+        .max(CALLOUT_ACCESSORY_SIZE + CALLOUT_PADDING);
+    let frame = rect(
+        anchor.x - width / 2.0,
+        anchor.y - CALLOUT_ARROW_SIZE - height,
+        width,
+        height,
+    );
+    let accessory = has_accessory.then(|| {
+        rect(
+            frame.origin.x + width - CALLOUT_PADDING - CALLOUT_ACCESSORY_SIZE,
+            frame.origin.y + (height - CALLOUT_ACCESSORY_SIZE) / 2.0,
+            CALLOUT_ACCESSORY_SIZE,
+            CALLOUT_ACCESSORY_SIZE,
+        )
+    });
+    Callout {
+        frame,
+        accessory,
+        anchor,
+    }
+}
+
+/// Get the string an `MKAnnotation` method returns, if it's implemented.
+fn optional_string(env: &mut Environment, object: id, selector: &str) -> Option<String> {
+    let selector = env.objc.lookup_selector(selector)?;
+    if !msg![env; object respondsToSelector:selector] {
+        return None;
+    }
+    let string: id = msg_send(env, (object, selector));
+    if string == nil {
+        None
+    } else {
+        Some(ns_string::to_rust_string(env, string).into_owned())
+    }
+}
+
+/// Map views that currently exist.
+fn map_views(env: &mut Environment) -> Vec<id> {
+    let class = env.objc.get_known_class("MKMapView", &mut env.mem);
+    ui_view::all_views(env)
+        .into_iter()
+        .filter(|&view| msg![env; view isKindOfClass:class])
+        .collect()
+}
+
+/// For use by `NSRunLoop`: download tiles, create views for new annotations,
+/// and keep track of where the annotations and the user are.
+pub fn handle_map_views(env: &mut Environment) {
+    tiles::poll(env);
+
+    let now = Instant::now();
+    let state = State::get(&mut env.framework_state);
+    if state
+        .last_refresh
+        .is_some_and(|last| now.duration_since(last) < REFRESH_INTERVAL)
+    {
+        return;
+    }
+    state.last_refresh = Some(now);
+
+    for map_view in map_views(env) {
+        retain(env, map_view);
+        refresh(env, map_view);
+        release(env, map_view);
+    }
+}
+
+fn refresh(env: &mut Environment, map_view: id) {
+    let hidden: bool = msg![env; map_view isHidden];
+    state(env, map_view).hidden = hidden;
+
+    // The user's location.
+    let manager = state(env, map_view).location_manager;
+    if manager != nil {
+        let location: id = msg![env; manager location];
+        let user_location = user_location(env, map_view);
+        if mk_user_location::set_location(env, user_location, location) {
+            let coordinate: CLLocationCoordinate2D = msg![env; user_location coordinate];
+            let state = state(env, map_view);
+            state.user_coordinate = Some(coordinate);
+            state.generation += 1;
+            if let Some((delegate, selector)) =
+                responding_delegate(env, map_view, "mapView:didUpdateUserLocation:")
+            {
+                let () = msg_send(env, (delegate, selector, map_view, user_location));
+            }
+        }
+    }
+
+    // Views for new annotations.
+    let annotations = state(env, map_view).annotations.clone();
+    let mut new_views = Vec::new();
+    for &annotation in &annotations {
+        if state(env, map_view)
+            .annotation_views
+            .contains_key(&annotation)
+        {
+            continue;
+        }
+        let mut view = nil;
+        if let Some((delegate, selector)) =
+            responding_delegate(env, map_view, "mapView:viewForAnnotation:")
+        {
+            view = msg_send(env, (delegate, selector, map_view, annotation));
+            retain(env, view);
+        }
+        if view == nil {
+            view = msg_class![env; MKPinAnnotationView alloc];
+            view = msg![env; view initWithAnnotation:annotation reuseIdentifier:nil];
+            () = msg![env; view setCanShowCallout:true];
+        }
+        // The delegate might have removed the annotation.
+        let state = state(env, map_view);
+        if !state.annotations.contains(&annotation) {
+            release(env, view);
+            continue;
+        }
+        state.annotation_views.insert(annotation, view);
+        state.generation += 1;
+        let selected = state.selected == annotation;
+        mk_annotation_view::state(env, view).selected = selected;
+        new_views.push(view);
+    }
+    if !new_views.is_empty() {
+        if let Some((delegate, selector)) =
+            responding_delegate(env, map_view, "mapView:didAddAnnotationViews:")
+        {
+            for &view in &new_views {
+                retain(env, view);
+            }
+            let views = ns_array::from_vec(env, new_views);
+            let () = msg_send(env, (delegate, selector, map_view, views));
+            release(env, views);
+        }
+    }
+
+    // Where the annotations are and what they're called.
+    let mut drawn = Vec::new();
+    for annotation in state(env, map_view).annotations.clone() {
+        let Some(&view) = state(env, map_view).annotation_views.get(&annotation) else {
+            continue;
+        };
+        let coordinate: CLLocationCoordinate2D = msg![env; annotation coordinate];
+        let title = optional_string(env, annotation, "title");
+        let subtitle = optional_string(env, annotation, "subtitle");
+        drawn.push(DrawnAnnotation {
+            annotation,
+            view,
+            coordinate,
+            title,
+            subtitle,
+        });
+    }
+    // Lower annotations are drawn in front.
+    drawn.sort_by(|a, b| {
+        let (a, b) = (a.coordinate.latitude, b.coordinate.latitude);
+        b.total_cmp(&a)
+    });
+    let map_state = state(env, map_view);
+    if drawn != map_state.drawn {
+        map_state.drawn = drawn;
+        map_state.generation += 1;
+    }
+
+    // Tiles.
+    if hidden || ui_view::frame_on_screen(env, map_view).is_none() {
+        return;
+    }
+    let size = view_size(env, map_view);
+    let map_state = state(env, map_view);
+    let (layout, _) = tile_layout(map_state.center, map_state.zoom, size);
+    let mut done = true;
+    for (key, _) in layout {
+        done &= tiles::request(env, key);
+    }
+    let was_loading = state(env, map_view).loading;
+    if !done && !was_loading {
+        state(env, map_view).loading = true;
+        notify_delegate(env, map_view, "mapViewWillStartLoadingMap:");
+    } else if done && was_loading {
+        state(env, map_view).loading = false;
+        notify_delegate(env, map_view, "mapViewDidFinishLoadingMap:");
+    }
+}
+
+fn intersection(a: CGRect, b: CGRect) -> CGRect {
+    let left = a.origin.x.max(b.origin.x);
+    let top = a.origin.y.max(b.origin.y);
+    let right = (a.origin.x + a.size.width).min(b.origin.x + b.size.width);
+    let bottom = (a.origin.y + a.size.height).min(b.origin.y + b.size.height);
+    rect(left, top, (right - left).max(0.0), (bottom - top).max(0.0))
+}
+
+fn draw_tile(env: &mut Environment, canvas: &mut Canvas, key: TileKey, tile: CGRect, clip: CGRect) {
+    if let Some(image) = tiles::loaded(env, key) {
+        canvas.draw_image(image, tile);
+        return;
+    }
+    // Use part of a tile from further out while this one loads.
+    for levels in 1..=key.zoom.min(FALLBACK_LEVELS) {
+        let parent = TileKey {
+            zoom: key.zoom - levels,
+            x: key.x >> levels,
+            y: key.y >> levels,
+        };
+        let Some(image) = tiles::loaded(env, parent) else {
+            continue;
+        };
+        let mask = (1u32 << levels) - 1;
+        let factor = (1u32 << levels) as CGFloat;
+        let parent_rect = rect(
+            tile.origin.x - (key.x & mask) as CGFloat * tile.size.width,
+            tile.origin.y - (key.y & mask) as CGFloat * tile.size.height,
+            tile.size.width * factor,
+            tile.size.height * factor,
+        );
+        canvas.set_clip(Some(intersection(tile, clip)));
+        canvas.draw_image(image, parent_rect);
+        canvas.set_clip(Some(clip));
+        return;
+    }
+    // Nothing to show yet, so just draw the tile's edges.
+    canvas.fill_rounded_rect(
+        rect(tile.origin.x, tile.origin.y, tile.size.width, 1.0),
+        0.0,
+        GRID_COLOR,
+    );
+    canvas.fill_rounded_rect(
+        rect(tile.origin.x, tile.origin.y, 1.0, tile.size.height),
+        0.0,
+        GRID_COLOR,
+    );
+}
+
+fn pin_color(color: MKPinAnnotationColor) -> Color {
+    match color {
+        MKPinAnnotationColorGreen => (0.2, 0.75, 0.2, 1.0),
+        MKPinAnnotationColorPurple => (0.6, 0.25, 0.85, 1.0),
+        _ => (0.85, 0.1, 0.1, 1.0),
+    }
+}
+
+fn draw_pin(canvas: &mut Canvas, point: CGPoint, color: MKPinAnnotationColor) {
+    let head = CGPoint {
+        x: point.x,
+        y: point.y - PIN_HEIGHT,
+    };
+    canvas.draw_line(point, head, 1.5, (0.6, 0.6, 0.65, 1.0));
+    canvas.fill_rounded_rect(
+        rect(
+            head.x - PIN_HEAD_RADIUS,
+            head.y - PIN_HEAD_RADIUS,
+            PIN_HEAD_RADIUS * 2.0,
+            PIN_HEAD_RADIUS * 2.0,
+        ),
+        PIN_HEAD_RADIUS,
+        pin_color(color),
+    );
+    // A highlight, so it looks round.
+    canvas.fill_rounded_rect(
+        rect(head.x - 3.5, head.y - 3.5, 3.0, 3.0),
+        1.5,
+        (1.0, 1.0, 1.0, 0.6),
+    );
+}
+
+fn draw_user_location(canvas: &mut Canvas, point: CGPoint) {
+    canvas.fill_rounded_rect(
+        rect(point.x - 9.0, point.y - 9.0, 18.0, 18.0),
+        9.0,
+        (1.0, 1.0, 1.0, 1.0),
+    );
+    canvas.fill_rounded_rect(
+        rect(point.x - 7.0, point.y - 7.0, 14.0, 14.0),
+        7.0,
+        (0.1, 0.45, 0.95, 1.0),
+    );
+}
+
+fn draw_callout(
+    env: &mut Environment,
+    canvas: &mut Canvas,
+    origin: CGPoint,
+    callout: &Callout,
+    title: &str,
+    subtitle: Option<&str>,
+) {
+    let offset = |r: CGRect| {
+        rect(
+            origin.x + r.origin.x,
+            origin.y + r.origin.y,
+            r.size.width,
+            r.size.height,
+        )
+    };
+    let color = (0.1, 0.1, 0.12, 0.9);
+    let frame = offset(callout.frame);
+    canvas.fill_rounded_rect(frame, 8.0, color);
+    // The arrow, one row of pixels at a time.
+    let bottom = frame.origin.y + frame.size.height;
+    let tip_x = origin.x + callout.anchor.x;
+    for row in 0..(CALLOUT_ARROW_SIZE as u32) {
+        let half_width = CALLOUT_ARROW_SIZE - row as CGFloat;
+        canvas.fill_rounded_rect(
+            rect(
+                tip_x - half_width,
+                bottom + row as CGFloat,
+                half_width * 2.0,
+                1.0,
+            ),
+            0.0,
+            color,
+        );
+    }
+
+    let white = (1.0, 1.0, 1.0, 1.0);
+    let text_left = frame.origin.x + CALLOUT_PADDING;
+    let mut text_top = frame.origin.y + CALLOUT_PADDING;
+    let font = ui_font::system_font(env, true, title);
+    let (_, title_height) = font.calculate_text_size(CALLOUT_TITLE_SIZE, title, None);
+    canvas.draw_text_at(
+        font,
+        CALLOUT_TITLE_SIZE,
+        title,
+        CGPoint {
+            x: text_left,
+            y: text_top,
+        },
+        white,
+    );
+    text_top += title_height;
+    if let Some(subtitle) = subtitle {
+        let font = ui_font::system_font(env, false, subtitle);
+        canvas.draw_text_at(
+            font,
+            CALLOUT_SUBTITLE_SIZE,
+            subtitle,
+            CGPoint {
+                x: text_left,
+                y: text_top,
+            },
+            white,
+        );
+    }
+
+    // A detail disclosure button.
+    if let Some(accessory) = callout.accessory {
+        let accessory = offset(accessory);
+        canvas.fill_rounded_rect(accessory, CALLOUT_ACCESSORY_SIZE / 2.0, white);
+        let inner = rect(
+            accessory.origin.x + 2.0,
+            accessory.origin.y + 2.0,
+            accessory.size.width - 4.0,
+            accessory.size.height - 4.0,
+        );
+        canvas.fill_rounded_rect(inner, inner.size.width / 2.0, (0.15, 0.45, 0.95, 1.0));
+        let center = CGPoint {
+            x: inner.origin.x + inner.size.width / 2.0,
+            y: inner.origin.y + inner.size.height / 2.0,
+        };
+        let tip = CGPoint {
+            x: center.x + 3.0,
+            y: center.y,
+        };
+        let top = CGPoint {
+            x: center.x - 2.0,
+            y: center.y - 5.0,
+        };
+        let bottom = CGPoint {
+            x: center.x - 2.0,
+            y: center.y + 5.0,
+        };
+        canvas.draw_line(top, tip, 2.5, white);
+        canvas.draw_line(bottom, tip, 2.5, white);
+    }
+}
+
+fn draw_map_view(env: &mut Environment, canvas: &mut Canvas, map_view: id, frame: CGRect) {
+    let size = view_size(env, map_view);
+    canvas.set_clip(Some(frame));
+    canvas.fill_rounded_rect(frame, 0.0, BACKGROUND_COLOR);
+
+    let map_state = state(env, map_view);
+    let (layout, tile_size) = tile_layout(map_state.center, map_state.zoom, size);
+    for (key, origin) in layout {
+        let tile = rect(
+            frame.origin.x + origin.x,
+            frame.origin.y + origin.y,
+            tile_size,
+            tile_size,
+        );
+        draw_tile(env, canvas, key, tile, frame);
+    }
+
+    let to_screen = |map_state: &MapViewState, coordinate: CLLocationCoordinate2D| {
+        let point = map_state.view_point(
+            size,
+            tiles::world_point(coordinate.latitude, coordinate.longitude),
+        );
+        CGPoint {
+            x: frame.origin.x + point.x,
+            y: frame.origin.y + point.y,
+        }
+    };
+    let map_state = state(env, map_view);
+    if let Some(coordinate) = map_state.user_coordinate {
+        draw_user_location(canvas, to_screen(map_state, coordinate));
+    }
+
+    for annotation in map_state.drawn.clone() {
+        let point = to_screen(state(env, map_view), annotation.coordinate);
+        let Some((marker, _)) = marker_geometry(env, annotation.view, point) else {
+            continue;
+        };
+        let &mut mk_annotation_view::AnnotationViewState {
+            image, pin_color, ..
+        } = mk_annotation_view::state(env, annotation.view);
+        if image != nil {
+            canvas.draw_image(ui_image::borrow_image(&env.objc, image), marker);
+        } else if let Some(color) = pin_color {
+            draw_pin(canvas, point, color);
+        }
+    }
+
+    if let Some((drawn, callout)) = selected_callout(env, map_view, size) {
+        draw_callout(
+            env,
+            canvas,
+            frame.origin,
+            &callout,
+            drawn.title.as_deref().unwrap(),
+            drawn.subtitle.as_deref(),
+        );
+    }
+
+    canvas.set_clip(None);
+}
+
+/// Map views that are in a window, with their frames on the screen.
+fn visible_map_views(env: &mut Environment) -> Vec<(id, CGRect)> {
+    let mut visible = Vec::new();
+    for map_view in ui_view::all_views(env) {
+        let Some(state) = State::get(&mut env.framework_state).views.get(&map_view) else {
+            continue;
+        };
+        if state.hidden {
+            continue;
+        }
+        let Some(frame) = ui_view::frame_on_screen(env, map_view) else {
+            continue;
+        };
+        if frame.size.width > 0.0 && frame.size.height > 0.0 {
+            visible.push((map_view, frame));
+        }
+    }
+    visible
+}
+
+/// For use by [crate::frameworks::uikit::overlay]: redraw the map views if
+/// anything changed.
+pub fn update_overlay(env: &mut Environment) {
+    let map_views = visible_map_views(env);
+    let contents: Vec<_> = map_views
+        .iter()
+        .map(|&(map_view, frame)| {
+            (
+                map_view,
+                [
+                    frame.origin.x,
+                    frame.origin.y,
+                    frame.size.width,
+                    frame.size.height,
+                ],
+                State::get(&mut env.framework_state).views[&map_view].generation,
+            )
+        })
+        .collect();
+    let contents = (contents, tiles::generation(env));
+
+    let state = State::get(&mut env.framework_state);
+    if contents == state.overlay_contents && (state.overlay.is_some() || contents.0.is_empty()) {
+        return;
+    }
+    state.overlay_contents = contents;
+    if map_views.is_empty() {
+        state.overlay = None;
+        return;
+    }
+
+    let mut canvas = Canvas::new(env);
+    for (map_view, frame) in map_views {
+        draw_map_view(env, &mut canvas, map_view, frame);
+    }
+    State::get(&mut env.framework_state).overlay = Some(canvas.into_overlay());
+}
+
+/// For use by [crate::frameworks::uikit::overlay]: get the drawing of the map
+/// views, if any are visible.
+pub fn current_overlay(framework_state: &crate::frameworks::State) -> Option<&Overlay> {
+    framework_state.map_kit.mk_map_view.overlay.as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions() {
+        let size = CGSize {
+            width: 320.0,
+            height: 460.0,
+        };
+        let region = MKCoordinateRegion {
+            center: CLLocationCoordinate2D {
+                latitude: 51.5,
+                longitude: -0.12,
+            },
+            span: MKCoordinateSpan {
+                latitudeDelta: 0.05,
+                longitudeDelta: 0.1,
+            },
+        };
+        let (center, zoom) = fit_region(region, size);
+        let mut map = MapViewState::default();
+        map.set_zoom(zoom);
+        map.set_center(center);
+        let fitted = map.region(size);
+        assert!((fitted.center.latitude - 51.5).abs() < 1e-9);
+        assert!((fitted.center.longitude - -0.12).abs() < 1e-9);
+        // The region is wider than it is tall, so the width fits exactly and
+        // more is shown vertically.
+        assert!((fitted.span.longitudeDelta - 0.1).abs() < 1e-9);
+        assert!(fitted.span.latitudeDelta > 0.05);
+
+        // The map's center is in the middle of the view.
+        let point = map.view_point(size, center);
+        assert_eq!((point.x, point.y), (160.0, 230.0));
+        let world = map.world_point_at(size, point);
+        assert!((world.0 - center.0).abs() < 1e-9 && (world.1 - center.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tiles_covering_view() {
+        let size = CGSize {
+            width: 320.0,
+            height: 460.0,
+        };
+        // At zoom level 1 the world is two tiles across, and the view shows
+        // part of each, on both sides of the center.
+        let (layout, tile_size) = tile_layout((0.5, 0.5), 1.0, size);
+        assert_eq!(tile_size, 256.0);
+        let keys: Vec<(u32, u32)> = layout.iter().map(|(key, _)| (key.x, key.y)).collect();
+        assert_eq!(keys, [(0, 0), (1, 0), (0, 1), (1, 1)]);
+        assert_eq!((layout[0].1.x, layout[0].1.y), (-96.0, -26.0));
+
+        // The world wraps around horizontally.
+        let (layout, _) = tile_layout((0.0, 0.5), 1.0, size);
+        assert_eq!(layout[0].0.x, 1);
+        assert_eq!(layout[1].0.x, 0);
+    }
+}
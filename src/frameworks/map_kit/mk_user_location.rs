@@ -0,0 +1,119 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MKUserLocation`.
+
+use crate::frameworks::core_location::cl_location::{
+    kCLLocationCoordinate2DInvalid, CLLocationCoordinate2D,
+};
+use crate::frameworks::foundation::ns_string;
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+struct MKUserLocationHostObject {
+    /// `CLLocation*`, strong reference.
+    location: id,
+    /// `NSString*`, strong reference.
+    title: id,
+    /// `NSString*`, strong reference.
+    subtitle: id,
+    updating: bool,
+}
+impl HostObject for MKUserLocationHostObject {}
+
+fn borrow(env: &mut Environment, user_location: id) -> &mut MKUserLocationHostObject {
+    env.objc.borrow_mut(user_location)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation MKUserLocation: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let title = ns_string::get_static_str(env, "Current Location");
+    let host_object = Box::new(MKUserLocationHostObject {
+        location: nil,
+        title,
+        subtitle: nil,
+        updating: false,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &mut MKUserLocationHostObject {
+        location,
+        title,
+        subtitle,
+        ..
+    } = borrow(env, this);
+    release(env, location);
+    release(env, title);
+    release(env, subtitle);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)location {
+    borrow(env, this).location
+}
+
+- (bool)isUpdating {
+    borrow(env, this).updating
+}
+
+// MKAnnotation implementation
+- (CLLocationCoordinate2D)coordinate {
+    let location = borrow(env, this).location;
+    if location == nil {
+        kCLLocationCoordinate2DInvalid
+    } else {
+        msg![env; location coordinate]
+    }
+}
+- (id)title {
+    borrow(env, this).title
+}
+- (())setTitle:(id)title { // NSString*
+    let title: id = msg![env; title copy];
+    let old = std::mem::replace(&mut borrow(env, this).title, title);
+    release(env, old);
+}
+- (id)subtitle {
+    borrow(env, this).subtitle
+}
+- (())setSubtitle:(id)subtitle { // NSString*
+    let subtitle: id = msg![env; subtitle copy];
+    let old = std::mem::replace(&mut borrow(env, this).subtitle, subtitle);
+    release(env, old);
+}
+
+@end
+
+};
+
+/// For use by `MKMapView`: create a user location. The result is owned by the
+/// caller.
+pub(super) fn new(env: &mut Environment) -> id {
+    msg_class![env; MKUserLocation new]
+}
+
+/// For use by `MKMapView`: update the location, which can be [nil]. Returns
+/// [true] if it changed.
+pub(super) fn set_location(env: &mut Environment, user_location: id, location: id) -> bool {
+    let host_object = borrow(env, user_location);
+    host_object.updating = location != nil;
+    if host_object.location == location {
+        return false;
+    }
+    let old = std::mem::replace(&mut host_object.location, location);
+    retain(env, location);
+    release(env, old);
+    true
+}
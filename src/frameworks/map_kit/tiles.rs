@@ -0,0 +1,304 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Map tiles: the Web Mercator projection they use, and getting them from the
+//! disk cache or the tile server.
+//!
+//! Tiles are downloaded with CFNetwork's HTTP code (see
+//! [crate::frameworks::cf_network::cf_http_stream]), so only tile servers that
+//! speak plain HTTP can be used. Connecting blocks, so only a few tiles are
+//! requested at a time. Tiles that have been downloaded are kept in the cache
+//! directory, laid out like the server's URLs (`ZOOM/X/Y.png`), so maps can be
+//! used offline by putting tiles there.
+
+use crate::frameworks::cf_network::cf_http_stream::HttpConnection;
+use crate::image::Image;
+use crate::Environment;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::path::{Path, PathBuf};
+
+/// Width and height of a tile in pixels. At zoom level 0, the whole world is
+/// one tile, and it's this many points wide.
+pub const TILE_SIZE: f64 = 256.0;
+/// The deepest zoom level most tile servers have.
+pub const MAX_ZOOM: u8 = 18;
+/// The latitude where the square Web Mercator map is cut off.
+pub const MAX_LATITUDE: f64 = 85.051_128_78;
+
+/// How many tiles can be downloading at once.
+const MAX_LOADS: usize = 4;
+/// How many decoded tiles are kept in memory. Each one takes 256 KiB.
+const MAX_LOADED: usize = 256;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TileKey {
+    pub zoom: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+enum Tile {
+    Loading {
+        connection: HttpConnection,
+        body: Vec<u8>,
+    },
+    Loaded {
+        image: Image,
+        last_used: u64,
+    },
+    /// Not in the cache and couldn't be downloaded. This isn't retried.
+    Missing,
+}
+
+#[derive(Default)]
+pub struct State {
+    tiles: HashMap<TileKey, Tile>,
+    /// Incremented whenever a tile is loaded, so maps get redrawn.
+    generation: u32,
+    /// Incremented whenever a tile is used, to find the least recently used
+    /// ones.
+    clock: u64,
+    warned_no_server: bool,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.map_kit.tiles
+    }
+
+    fn insert_loaded(&mut self, key: TileKey, image: Image) {
+        let loaded = self
+            .tiles
+            .values()
+            .filter(|tile| matches!(tile, Tile::Loaded { .. }))
+            .count();
+        if loaded >= MAX_LOADED {
+            let oldest = self
+                .tiles
+                .iter()
+                .filter_map(|(&key, tile)| match *tile {
+                    Tile::Loaded { last_used, .. } => Some((last_used, key)),
+                    _ => None,
+                })
+                .min_by_key(|&(last_used, _)| last_used)
+                .map(|(_, key)| key)
+                .unwrap();
+            self.tiles.remove(&oldest);
+        }
+        self.clock += 1;
+        let last_used = self.clock;
+        self.tiles.insert(key, Tile::Loaded { image, last_used });
+        self.generation += 1;
+    }
+}
+
+/// Project a coordinate onto the map. The world is a unit square, with (0, 0)
+/// in the top-left (180° W, about 85° N).
+pub fn world_point(latitude: f64, longitude: f64) -> (f64, f64) {
+    let latitude = latitude.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+    let x = (longitude + 180.0) / 360.0;
+    let y = (1.0 - (latitude.tan() + 1.0 / latitude.cos()).ln() / PI) / 2.0;
+    (x, y)
+}
+
+/// Inverse of [world_point]. Returns the latitude and longitude.
+pub fn coordinate_for_world_point((x, y): (f64, f64)) -> (f64, f64) {
+    let latitude = (PI * (1.0 - 2.0 * y)).sinh().atan().to_degrees();
+    let longitude = x * 360.0 - 180.0;
+    (latitude, longitude)
+}
+
+/// Fill in a tile server URL template, e.g.
+/// `http://tile.example.com/{z}/{x}/{y}.png`.
+pub fn tile_url(template: &str, key: TileKey) -> String {
+    template
+        .replace("{z}", &key.zoom.to_string())
+        .replace("{x}", &key.x.to_string())
+        .replace("{y}", &key.y.to_string())
+}
+
+fn cache_path(dir: &Path, key: TileKey) -> PathBuf {
+    dir.join(key.zoom.to_string())
+        .join(key.x.to_string())
+        .join(format!("{}.png", key.y))
+}
+
+/// Used while drawing: get a tile if it's loaded. This doesn't start loading
+/// it, see [request].
+pub fn loaded(env: &mut Environment, key: TileKey) -> Option<&Image> {
+    let state = State::get(&mut env.framework_state);
+    state.clock += 1;
+    let clock = state.clock;
+    match state.tiles.get_mut(&key) {
+        Some(Tile::Loaded { image, last_used }) => {
+            *last_used = clock;
+            Some(image)
+        }
+        _ => None,
+    }
+}
+
+/// Start loading a tile if it isn't loaded or loading already. Returns [false]
+/// if the tile is still on its way, or [true] if it's loaded or can't be.
+pub fn request(env: &mut Environment, key: TileKey) -> bool {
+    let state = State::get(&mut env.framework_state);
+    match state.tiles.get(&key) {
+        Some(Tile::Loading { .. }) => return false,
+        Some(_) => return true,
+        None => (),
+    }
+
+    let path = cache_path(&env.options.map_tile_dir, key);
+    if let Ok(bytes) = std::fs::read(&path) {
+        match Image::from_bytes(&bytes) {
+            Ok(image) => {
+                state.insert_loaded(key, image);
+                return true;
+            }
+            // Maybe a download was interrupted. Try again.
+            Err(()) => log!("Warning: couldn't decode map tile {}", path.display()),
+        }
+    }
+
+    let Some(ref template) = env.options.map_tile_server else {
+        if !state.warned_no_server {
+            log!("Map tiles aren't cached and there's no tile server, see --map-tile-server=");
+            state.warned_no_server = true;
+        }
+        state.tiles.insert(key, Tile::Missing);
+        return true;
+    };
+
+    let loading = state
+        .tiles
+        .values()
+        .filter(|tile| matches!(tile, Tile::Loading { .. }))
+        .count();
+    if loading >= MAX_LOADS {
+        return false;
+    }
+
+    let url = tile_url(template, key);
+    log_dbg!("Downloading map tile {:?} from {:?}", key, url);
+    let user_agent = format!("touchHLE/{}", crate::VERSION.trim());
+    let tile = match HttpConnection::new_get(&url, &user_agent) {
+        Some(mut connection) if connection.open().is_ok() => Tile::Loading {
+            connection,
+            body: Vec::new(),
+        },
+        _ => Tile::Missing,
+    };
+    let done = matches!(tile, Tile::Missing);
+    state.tiles.insert(key, tile);
+    done
+}
+
+/// Receive what has arrived for the tiles being downloaded.
+pub fn poll(env: &mut Environment) {
+    let state = State::get(&mut env.framework_state);
+    let mut finished = Vec::new();
+    for (&key, tile) in state.tiles.iter_mut() {
+        let Tile::Loading { connection, body } = tile else {
+            continue;
+        };
+        let mut buffer = [0u8; 4096];
+        loop {
+            let count = connection.read(&mut buffer);
+            if count == 0 {
+                break;
+            }
+            body.extend_from_slice(&buffer[..count]);
+        }
+        if connection.has_failed() || connection.is_finished() {
+            finished.push(key);
+        }
+    }
+
+    for key in finished {
+        let Some(Tile::Loading { connection, body }) = state.tiles.remove(&key) else {
+            unreachable!();
+        };
+        match finish_load(key, connection, body, &env.options.map_tile_dir) {
+            Some(image) => state.insert_loaded(key, image),
+            None => {
+                state.tiles.insert(key, Tile::Missing);
+            }
+        }
+    }
+}
+
+fn finish_load(
+    key: TileKey,
+    connection: HttpConnection,
+    body: Vec<u8>,
+    cache_dir: &Path,
+) -> Option<Image> {
+    if connection.has_failed() {
+        return None;
+    }
+    let status_code = connection.status_code();
+    if status_code != Some(200) {
+        log!(
+            "Warning: tile server responded with status {:?} for map tile {:?}",
+            status_code,
+            key
+        );
+        return None;
+    }
+    let Ok(image) = Image::from_bytes(&body) else {
+        log!("Warning: couldn't decode map tile {:?}", key);
+        return None;
+    };
+    let path = cache_path(cache_dir, key);
+    if let Err(e) =
+        std::fs::create_dir_all(path.parent().unwrap()).and_then(|()| std::fs::write(&path, &body))
+    {
+        log!(
+            "Warning: couldn't cache map tile at {}: {}",
+            path.display(),
+            e
+        );
+    }
+    Some(image)
+}
+
+/// Incremented whenever a tile is loaded.
+pub fn generation(env: &mut Environment) -> u32 {
+    State::get(&mut env.framework_state).generation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn projection() {
+        assert_eq!(world_point(0.0, 0.0), (0.5, 0.5));
+        let (x, y) = world_point(MAX_LATITUDE, -180.0);
+        assert_eq!(x, 0.0);
+        assert!(y.abs() < 1e-9);
+
+        let (latitude, longitude) = coordinate_for_world_point(world_point(51.5007, -0.1246));
+        assert!((latitude - 51.5007).abs() < 1e-9);
+        assert!((longitude - -0.1246).abs() < 1e-9);
+    }
+
+    #[test]
+    fn urls() {
+        let key = TileKey {
+            zoom: 12,
+            x: 2046,
+            y: 1362,
+        };
+        assert_eq!(
+            tile_url("http://localhost:8080/tiles/{z}/{x}/{y}.png", key),
+            "http://localhost:8080/tiles/12/2046/1362.png"
+        );
+        assert_eq!(
+            cache_path(Path::new("tiles"), key),
+            Path::new("tiles/12/2046/1362.png")
+        );
+    }
+}
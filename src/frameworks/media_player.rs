@@ -0,0 +1,23 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Media Player framework.
+//!
+//! Movie playback (`MPMoviePlayerController`) and playing songs from the iPod
+//! library (`MPMusicPlayerController`) are implemented so far. The iPod library
+//! is a directory on the host, see `--music-dir=`.
+
+pub mod mp_media_item;
+pub mod mp_media_item_collection;
+pub mod mp_media_query;
+pub mod mp_movie_player_controller;
+pub mod mp_music_player_controller;
+
+#[derive(Default)]
+pub struct State {
+    mp_media_query: mp_media_query::State,
+    mp_movie_player_controller: mp_movie_player_controller::State,
+    mp_music_player_controller: mp_music_player_controller::State,
+}
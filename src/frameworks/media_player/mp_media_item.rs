@@ -0,0 +1,225 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MPMediaItem`.
+//!
+//! Each item is a song in the "iPod library", which is a directory on the host
+//! (see `--music-dir=`). There are no tags or artwork, just the file name.
+
+use crate::audio; // Keep this module namespaced to avoid confusion
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::{ns_string, NSTimeInterval, NSUInteger};
+use crate::objc::{autorelease, id, msg_class, nil, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+use std::path::{Path, PathBuf};
+
+pub const MPMediaItemPropertyPersistentID: &str = "persistentID";
+pub const MPMediaItemPropertyMediaType: &str = "mediaType";
+pub const MPMediaItemPropertyTitle: &str = "title";
+pub const MPMediaItemPropertyAlbumTitle: &str = "albumTitle";
+pub const MPMediaItemPropertyArtist: &str = "artist";
+pub const MPMediaItemPropertyAlbumArtist: &str = "albumArtist";
+pub const MPMediaItemPropertyGenre: &str = "genre";
+pub const MPMediaItemPropertyPlaybackDuration: &str = "playbackDuration";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_MPMediaItemPropertyPersistentID",
+        HostConstant::NSString(MPMediaItemPropertyPersistentID),
+    ),
+    (
+        "_MPMediaItemPropertyMediaType",
+        HostConstant::NSString(MPMediaItemPropertyMediaType),
+    ),
+    (
+        "_MPMediaItemPropertyTitle",
+        HostConstant::NSString(MPMediaItemPropertyTitle),
+    ),
+    (
+        "_MPMediaItemPropertyAlbumTitle",
+        HostConstant::NSString(MPMediaItemPropertyAlbumTitle),
+    ),
+    (
+        "_MPMediaItemPropertyArtist",
+        HostConstant::NSString(MPMediaItemPropertyArtist),
+    ),
+    (
+        "_MPMediaItemPropertyAlbumArtist",
+        HostConstant::NSString(MPMediaItemPropertyAlbumArtist),
+    ),
+    (
+        "_MPMediaItemPropertyGenre",
+        HostConstant::NSString(MPMediaItemPropertyGenre),
+    ),
+    (
+        "_MPMediaItemPropertyPlaybackDuration",
+        HostConstant::NSString(MPMediaItemPropertyPlaybackDuration),
+    ),
+];
+
+pub type MPMediaType = NSUInteger;
+pub const MPMediaTypeMusic: MPMediaType = 1 << 0;
+
+pub type MPMediaEntityPersistentID = u64;
+
+/// File name extensions of the formats [audio::AudioFile] can decode.
+const EXTENSIONS: &[&str] = &["mp3", "m4a", "aac", "wav", "caf", "aif", "aiff"];
+
+struct MPMediaItemHostObject {
+    path: PathBuf,
+    persistent_id: MPMediaEntityPersistentID,
+    title: String,
+    /// In seconds. Filled in when first needed, because finding it out means
+    /// decoding the whole file.
+    duration: Option<NSTimeInterval>,
+}
+impl HostObject for MPMediaItemHostObject {}
+
+fn borrow(env: &mut Environment, item: id) -> &mut MPMediaItemHostObject {
+    env.objc.borrow_mut(item)
+}
+
+/// A song's decoded audio.
+pub struct Song {
+    /// Interleaved samples.
+    pub samples: Vec<i16>,
+    pub channels: u32,
+    pub sample_rate: u32,
+}
+impl Song {
+    /// In seconds.
+    fn duration(&self) -> NSTimeInterval {
+        (self.samples.len() / self.channels as usize) as f64 / f64::from(self.sample_rate)
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation MPMediaItem: NSObject
+
+// The items are created by the host, see [new_item].
+
+- (id)valueForProperty:(id)property { // NSString*
+    let property = ns_string::to_rust_string(env, property);
+    match &*property {
+        MPMediaItemPropertyPersistentID => {
+            let persistent_id = borrow(env, this).persistent_id;
+            msg_class![env; NSNumber numberWithUnsignedLongLong:persistent_id]
+        }
+        MPMediaItemPropertyMediaType => {
+            msg_class![env; NSNumber numberWithUnsignedInteger:MPMediaTypeMusic]
+        }
+        MPMediaItemPropertyTitle => {
+            let title = borrow(env, this).title.clone();
+            let title = ns_string::from_rust_string(env, title);
+            autorelease(env, title)
+        }
+        MPMediaItemPropertyPlaybackDuration => {
+            let duration = duration(env, this);
+            msg_class![env; NSNumber numberWithDouble:duration]
+        }
+        _ => {
+            log_dbg!("[(MPMediaItem*){:?} valueForProperty:{:?}] => nil", this, property);
+            nil
+        }
+    }
+}
+
+@end
+
+};
+
+/// Scan a host directory for songs, sorted by file name.
+pub fn find_songs(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+                })
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Create an `MPMediaItem` for a song on the host. The result is owned by the
+/// caller.
+pub fn new_item(env: &mut Environment, path: PathBuf) -> id {
+    use std::hash::{Hash, Hasher};
+
+    let title = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    // Persistent IDs are supposed to stay the same between runs, so this must
+    // not depend on the order songs are found in.
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    path.file_name().hash(&mut hasher);
+    let persistent_id = hasher.finish();
+
+    let host_object = Box::new(MPMediaItemHostObject {
+        path,
+        persistent_id,
+        title,
+        duration: None,
+    });
+    let class = env.objc.get_known_class("MPMediaItem", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+/// Decode a song. Returns [None] if the file can't be read or its format isn't
+/// supported.
+pub fn decode(env: &mut Environment, item: id) -> Option<Song> {
+    let path = borrow(env, item).path.clone();
+    let song = decode_file(&path);
+    if song.is_none() {
+        log!("Warning: Couldn't decode song {:?}, skipping it", path);
+    }
+    let host_object = borrow(env, item);
+    if let Some(ref song) = song {
+        host_object.duration = Some(song.duration());
+    }
+    song
+}
+
+fn decode_file(path: &Path) -> Option<Song> {
+    let bytes = std::fs::read(path).ok()?;
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let mut audio_file = audio::AudioFile::open_from_bytes(bytes, extension).ok()?;
+    let audio::AudioDescription {
+        sample_rate,
+        channels_per_frame,
+        ..
+    } = audio_file.audio_description();
+    // OpenAL only has mono and stereo formats.
+    if !(1..=2).contains(&channels_per_frame) {
+        return None;
+    }
+    let frame_count: usize = audio_file.frame_count().try_into().unwrap();
+    let mut samples = vec![0i16; frame_count * channels_per_frame as usize];
+    let frames_read = audio_file.read_pcm_frames(0, &mut samples).ok()?;
+    samples.truncate(frames_read * channels_per_frame as usize);
+    Some(Song {
+        samples,
+        channels: channels_per_frame,
+        sample_rate: sample_rate as u32,
+    })
+}
+
+fn duration(env: &mut Environment, item: id) -> NSTimeInterval {
+    if let Some(duration) = borrow(env, item).duration {
+        return duration;
+    }
+    let duration = decode(env, item).map_or(0.0, |song| song.duration());
+    borrow(env, item).duration = Some(duration);
+    duration
+}
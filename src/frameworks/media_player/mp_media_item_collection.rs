@@ -0,0 +1,67 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MPMediaItemCollection`.
+
+use crate::frameworks::foundation::NSUInteger;
+use crate::objc::{autorelease, id, msg, nil, objc_classes, release, ClassExports, HostObject};
+
+struct MPMediaItemCollectionHostObject {
+    /// `NSArray*` of `MPMediaItem*`, strong reference.
+    items: id,
+}
+impl HostObject for MPMediaItemCollectionHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation MPMediaItemCollection: NSObject
+
++ (id)alloc {
+    let host_object = Box::new(MPMediaItemCollectionHostObject { items: nil });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)collectionWithItems:(id)items { // NSArray*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithItems:items];
+    autorelease(env, new)
+}
+
+- (id)initWithItems:(id)items { // NSArray*
+    let items: id = msg![env; items copy];
+    env.objc.borrow_mut::<MPMediaItemCollectionHostObject>(this).items = items;
+    this
+}
+
+- (())dealloc {
+    let items = env.objc.borrow::<MPMediaItemCollectionHostObject>(this).items;
+    release(env, items);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)items {
+    env.objc.borrow::<MPMediaItemCollectionHostObject>(this).items
+}
+
+- (NSUInteger)count {
+    let items = env.objc.borrow::<MPMediaItemCollectionHostObject>(this).items;
+    msg![env; items count]
+}
+
+- (id)representativeItem {
+    let items = env.objc.borrow::<MPMediaItemCollectionHostObject>(this).items;
+    let count: NSUInteger = msg![env; items count];
+    if count == 0 {
+        nil
+    } else {
+        msg![env; items objectAtIndex:0u32]
+    }
+}
+
+@end
+
+};
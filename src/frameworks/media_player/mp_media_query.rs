@@ -0,0 +1,296 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MPMediaQuery` and `MPMediaPropertyPredicate`.
+//!
+//! Queries search the songs in the host's music directory (see
+//! `--music-dir=`). Since the songs have no tags, every grouping other than
+//! by song puts them all in a single collection.
+
+use super::mp_media_item;
+use crate::frameworks::foundation::{ns_array, ns_string, NSInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, Class, ClassExports,
+    HostObject,
+};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// Every song in the library, found when it's first queried. Strong
+    /// references.
+    library: Option<Vec<id>>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.media_player.mp_media_query
+    }
+}
+
+type MPMediaGrouping = NSInteger;
+const MPMediaGroupingTitle: MPMediaGrouping = 0;
+const MPMediaGroupingAlbum: MPMediaGrouping = 1;
+const MPMediaGroupingArtist: MPMediaGrouping = 2;
+const MPMediaGroupingPlaylist: MPMediaGrouping = 6;
+
+type MPMediaPredicateComparison = NSInteger;
+const MPMediaPredicateComparisonEqualTo: MPMediaPredicateComparison = 0;
+const MPMediaPredicateComparisonContains: MPMediaPredicateComparison = 1;
+
+struct MPMediaQueryHostObject {
+    /// `MPMediaPropertyPredicate*`s, strong references.
+    filter_predicates: Vec<id>,
+    grouping: MPMediaGrouping,
+}
+impl HostObject for MPMediaQueryHostObject {}
+
+struct MPMediaPropertyPredicateHostObject {
+    /// Strong reference.
+    value: id,
+    /// `NSString*`, strong reference.
+    property: id,
+    comparison_type: MPMediaPredicateComparison,
+}
+impl HostObject for MPMediaPropertyPredicateHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation MPMediaQuery: NSObject
+
++ (id)alloc {
+    let host_object = Box::new(MPMediaQueryHostObject {
+        filter_predicates: Vec::new(),
+        grouping: MPMediaGroupingTitle,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)songsQuery {
+    new_query(env, this, MPMediaGroupingTitle)
+}
++ (id)albumsQuery {
+    new_query(env, this, MPMediaGroupingAlbum)
+}
++ (id)artistsQuery {
+    new_query(env, this, MPMediaGroupingArtist)
+}
++ (id)playlistsQuery {
+    new_query(env, this, MPMediaGroupingPlaylist)
+}
+
+- (id)init {
+    this
+}
+
+- (())dealloc {
+    let predicates = std::mem::take(&mut borrow_query(env, this).filter_predicates);
+    for predicate in predicates {
+        release(env, predicate);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (MPMediaGrouping)groupingType {
+    borrow_query(env, this).grouping
+}
+- (())setGroupingType:(MPMediaGrouping)grouping {
+    borrow_query(env, this).grouping = grouping;
+}
+
+- (())addFilterPredicate:(id)predicate { // MPMediaPredicate*
+    retain(env, predicate);
+    borrow_query(env, this).filter_predicates.push(predicate);
+}
+- (())removeFilterPredicate:(id)predicate { // MPMediaPredicate*
+    let predicates = &mut borrow_query(env, this).filter_predicates;
+    if let Some(index) = predicates.iter().position(|&p| p == predicate) {
+        predicates.remove(index);
+        release(env, predicate);
+    }
+}
+
+- (id)items {
+    let items = query_items(env, this);
+    for &item in &items {
+        retain(env, item);
+    }
+    let array = ns_array::from_vec(env, items);
+    autorelease(env, array)
+}
+
+- (id)collections {
+    let items = query_items(env, this);
+    let collections = match borrow_query(env, this).grouping {
+        _ if items.is_empty() => Vec::new(),
+        MPMediaGroupingTitle => items.into_iter().map(|item| vec![item]).collect(),
+        // There are no playlists.
+        MPMediaGroupingPlaylist => Vec::new(),
+        _ => vec![items],
+    };
+    let collections = collections
+        .into_iter()
+        .map(|items| {
+            for &item in &items {
+                retain(env, item);
+            }
+            let items = ns_array::from_vec(env, items);
+            let collection: id = msg_class![env; MPMediaItemCollection alloc];
+            let collection: id = msg![env; collection initWithItems:items];
+            release(env, items);
+            collection
+        })
+        .collect();
+    let array = ns_array::from_vec(env, collections);
+    autorelease(env, array)
+}
+
+@end
+
+@implementation MPMediaPredicate: NSObject
+@end
+
+@implementation MPMediaPropertyPredicate: MPMediaPredicate
+
++ (id)alloc {
+    let host_object = Box::new(MPMediaPropertyPredicateHostObject {
+        value: nil,
+        property: nil,
+        comparison_type: MPMediaPredicateComparisonEqualTo,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)predicateWithValue:(id)value
+             forProperty:(id)property { // NSString*
+    msg![env; this predicateWithValue:value
+                          forProperty:property
+                       comparisonType:MPMediaPredicateComparisonEqualTo]
+}
++ (id)predicateWithValue:(id)value
+             forProperty:(id)property // NSString*
+          comparisonType:(MPMediaPredicateComparison)comparison_type {
+    let new: id = msg![env; this alloc];
+    retain(env, value);
+    let property: id = msg![env; property copy];
+    *borrow_predicate(env, new) = MPMediaPropertyPredicateHostObject {
+        value,
+        property,
+        comparison_type,
+    };
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let &mut MPMediaPropertyPredicateHostObject {
+        value, property, ..
+    } = borrow_predicate(env, this);
+    release(env, value);
+    release(env, property);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)value {
+    borrow_predicate(env, this).value
+}
+- (id)property {
+    borrow_predicate(env, this).property
+}
+- (MPMediaPredicateComparison)comparisonType {
+    borrow_predicate(env, this).comparison_type
+}
+
+@end
+
+};
+
+fn borrow_query(env: &mut Environment, query: id) -> &mut MPMediaQueryHostObject {
+    env.objc.borrow_mut(query)
+}
+
+fn borrow_predicate(
+    env: &mut Environment,
+    predicate: id,
+) -> &mut MPMediaPropertyPredicateHostObject {
+    env.objc.borrow_mut(predicate)
+}
+
+fn new_query(env: &mut Environment, class: Class, grouping: MPMediaGrouping) -> id {
+    let new: id = msg![env; class alloc];
+    let new: id = msg![env; new init];
+    borrow_query(env, new).grouping = grouping;
+    autorelease(env, new)
+}
+
+/// Get every song in the library, scanning the music directory the first time.
+/// The references are owned by the library.
+fn library(env: &mut Environment) -> Vec<id> {
+    if let Some(library) = &State::get(&mut env.framework_state).library {
+        return library.clone();
+    }
+    let dir = env.options.music_dir.clone();
+    let paths = match mp_media_item::find_songs(&dir) {
+        Ok(paths) => paths,
+        Err(e) => {
+            log!(
+                "Warning: Couldn't read music directory {:?} ({}), the iPod library will be empty.",
+                dir,
+                e
+            );
+            Vec::new()
+        }
+    };
+    log_dbg!("iPod library has {} songs from {:?}", paths.len(), dir);
+    let library: Vec<id> = paths
+        .into_iter()
+        .map(|path| mp_media_item::new_item(env, path))
+        .collect();
+    State::get(&mut env.framework_state).library = Some(library.clone());
+    library
+}
+
+/// Get the songs that match all of a query's predicates. The references are
+/// owned by the library.
+fn query_items(env: &mut Environment, query: id) -> Vec<id> {
+    let predicates = borrow_query(env, query).filter_predicates.clone();
+    let mut items = library(env);
+    for predicate in predicates {
+        items.retain(|&item| predicate_matches(env, predicate, item));
+    }
+    items
+}
+
+fn predicate_matches(env: &mut Environment, predicate: id, item: id) -> bool {
+    let class = env
+        .objc
+        .get_known_class("MPMediaPropertyPredicate", &mut env.mem);
+    if !msg![env; predicate isKindOfClass:class] {
+        log!(
+            "Warning: Ignoring unsupported media predicate {:?}",
+            predicate
+        );
+        return true;
+    }
+    let &mut MPMediaPropertyPredicateHostObject {
+        value,
+        property,
+        comparison_type,
+    } = borrow_predicate(env, predicate);
+    let item_value: id = msg![env; item valueForProperty:property];
+    if value == nil || item_value == nil {
+        return value == item_value;
+    }
+    let string_class = env.objc.get_known_class("NSString", &mut env.mem);
+    if comparison_type == MPMediaPredicateComparisonContains
+        && msg![env; value isKindOfClass:string_class]
+        && msg![env; item_value isKindOfClass:string_class]
+    {
+        let value = ns_string::to_rust_string(env, value).to_lowercase();
+        let item_value = ns_string::to_rust_string(env, item_value).to_lowercase();
+        return item_value.contains(&value);
+    }
+    msg![env; value isEqualTo:item_value]
+}
@@ -0,0 +1,702 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MPMoviePlayerController`.
+//!
+//! Apps mostly use this for intro movies and cutscenes, and only care that
+//! the movie plays full-screen and that they're told when it's over. The
+//! movie is shown as an overlay (see [crate::frameworks::uikit::overlay]) in
+//! landscape orientation, and tapping it skips it, like pressing "Done".
+//!
+//! The soundtrack is decoded and played like an `AVAudioPlayer`'s audio (see
+//! [crate::audio]), and the movie's header is read to find out how long it is
+//! and how big its picture is. There's no video decoder yet, so the picture
+//! is black, but it's scaled and framed by the background color according to
+//! the scaling mode, like the real thing.
+
+use crate::audio; // Keep this module namespaced to avoid confusion
+use crate::audio::openal as al;
+use crate::audio::openal::al_types::*;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::audio_toolbox::audio_queue::make_al_context_current;
+use crate::frameworks::core_graphics::{CGFloat, CGRect};
+use crate::frameworks::foundation::ns_dictionary::dict_from_keys_and_objects;
+use crate::frameworks::foundation::ns_notification_center;
+use crate::frameworks::foundation::ns_string::get_static_str;
+use crate::frameworks::foundation::ns_url::to_rust_path;
+use crate::frameworks::foundation::{NSInteger, NSTimeInterval};
+use crate::frameworks::uikit::overlay::{rect, Canvas, Overlay};
+use crate::frameworks::uikit::ui_color;
+use crate::objc::{
+    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::window::{DeviceOrientation, Event};
+use crate::Environment;
+use std::time::Instant;
+
+pub const MPMoviePlayerContentPreloadDidFinishNotification: &str =
+    "MPMoviePlayerContentPreloadDidFinishNotification";
+pub const MPMoviePlayerPlaybackDidFinishNotification: &str =
+    "MPMoviePlayerPlaybackDidFinishNotification";
+pub const MPMoviePlayerScalingModeDidChangeNotification: &str =
+    "MPMoviePlayerScalingModeDidChangeNotification";
+pub const MPMoviePlayerPlaybackDidFinishReasonUserInfoKey: &str =
+    "MPMoviePlayerPlaybackDidFinishReasonUserInfoKey";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_MPMoviePlayerContentPreloadDidFinishNotification",
+        HostConstant::NSString(MPMoviePlayerContentPreloadDidFinishNotification),
+    ),
+    (
+        "_MPMoviePlayerPlaybackDidFinishNotification",
+        HostConstant::NSString(MPMoviePlayerPlaybackDidFinishNotification),
+    ),
+    (
+        "_MPMoviePlayerScalingModeDidChangeNotification",
+        HostConstant::NSString(MPMoviePlayerScalingModeDidChangeNotification),
+    ),
+    (
+        "_MPMoviePlayerPlaybackDidFinishReasonUserInfoKey",
+        HostConstant::NSString(MPMoviePlayerPlaybackDidFinishReasonUserInfoKey),
+    ),
+];
+
+type MPMovieScalingMode = NSInteger;
+const MPMovieScalingModeNone: MPMovieScalingMode = 0;
+const MPMovieScalingModeAspectFit: MPMovieScalingMode = 1;
+const MPMovieScalingModeAspectFill: MPMovieScalingMode = 2;
+const MPMovieScalingModeFill: MPMovieScalingMode = 3;
+
+type MPMovieControlMode = NSInteger;
+const MPMovieControlModeDefault: MPMovieControlMode = 0;
+
+type MPMovieFinishReason = NSInteger;
+const MPMovieFinishReasonPlaybackEnded: MPMovieFinishReason = 0;
+const MPMovieFinishReasonPlaybackError: MPMovieFinishReason = 1;
+const MPMovieFinishReasonUserExited: MPMovieFinishReason = 2;
+
+#[derive(Default)]
+pub struct State {
+    /// Players that haven't yet posted
+    /// `MPMoviePlayerContentPreloadDidFinishNotification`. Strong references.
+    preloading: Vec<id>,
+    /// The movie being shown, if any.
+    playback: Option<Playback>,
+    /// Cached drawing of the movie.
+    overlay: Option<Overlay>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.media_player.mp_movie_player_controller
+    }
+}
+
+struct Playback {
+    /// Strong reference, so the player isn't deallocated mid-movie.
+    player: id,
+    started_at: Instant,
+    /// How long until the movie ends, in seconds, from `started_at`.
+    remaining: f64,
+    /// The soundtrack's source and buffer, if it has one.
+    al_source_and_buffer: Option<(ALuint, ALuint)>,
+}
+
+/// The parts of a movie's header that matter here.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct MovieInfo {
+    /// In seconds.
+    duration: f64,
+    /// Size of the picture in pixels, if there's a video track.
+    video_size: Option<(CGFloat, CGFloat)>,
+}
+
+struct Soundtrack {
+    /// Interleaved samples.
+    samples: Vec<i16>,
+    channels: u32,
+    sample_rate: u32,
+}
+impl Soundtrack {
+    /// In seconds.
+    fn duration(&self) -> f64 {
+        (self.samples.len() / self.channels as usize) as f64 / f64::from(self.sample_rate)
+    }
+}
+
+struct MPMoviePlayerControllerHostObject {
+    /// `NSURL*`, strong reference.
+    content_url: id,
+    /// [None] if the file couldn't be read or isn't a QuickTime or MPEG-4
+    /// movie.
+    movie: Option<MovieInfo>,
+    soundtrack: Option<Soundtrack>,
+    scaling_mode: MPMovieScalingMode,
+    control_mode: MPMovieControlMode,
+    /// `UIColor*`, strong reference. Nil means black.
+    background_color: id,
+    initial_playback_time: NSTimeInterval,
+}
+impl HostObject for MPMoviePlayerControllerHostObject {}
+
+fn borrow(env: &mut Environment, player: id) -> &mut MPMoviePlayerControllerHostObject {
+    env.objc.borrow_mut(player)
+}
+
+/// Iterate over the boxes (atoms) in some QuickTime or MPEG-4 data, giving the
+/// type and contents of each. Stops at the first malformed box.
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> + '_ {
+    std::iter::from_fn(move || {
+        let size = read_u32(data, 0)?;
+        let box_type: [u8; 4] = data.get(4..8)?.try_into().unwrap();
+        let (header_size, size) = match size {
+            // The box extends to the end of the data.
+            0 => (8, data.len() as u64),
+            1 => (16, read_u64(data, 8)?),
+            _ => (8, u64::from(size)),
+        };
+        if size < header_size || size > data.len() as u64 {
+            return None;
+        }
+        let contents = &data[header_size as usize..size as usize];
+        data = &data[size as usize..];
+        Some((box_type, contents))
+    })
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().unwrap(),
+    ))
+}
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        data.get(offset..offset + 8)?.try_into().unwrap(),
+    ))
+}
+
+/// Read the duration and picture size from a movie's `moov` box.
+fn parse_movie_info(data: &[u8]) -> Option<MovieInfo> {
+    let (_, moov) = boxes(data).find(|&(box_type, _)| &box_type == b"moov")?;
+    let (_, mvhd) = boxes(moov).find(|&(box_type, _)| &box_type == b"mvhd")?;
+    let (timescale, duration) = match mvhd.first()? {
+        0 => (read_u32(mvhd, 12)?, u64::from(read_u32(mvhd, 16)?)),
+        1 => (read_u32(mvhd, 20)?, read_u64(mvhd, 24)?),
+        _ => return None,
+    };
+    if timescale == 0 {
+        return None;
+    }
+    // Only video tracks have a non-zero size in their track header.
+    let video_size = boxes(moov)
+        .filter(|&(box_type, _)| &box_type == b"trak")
+        .find_map(|(_, trak)| {
+            let (_, tkhd) = boxes(trak).find(|&(box_type, _)| &box_type == b"tkhd")?;
+            let offset = match tkhd.first()? {
+                0 => 76,
+                1 => 88,
+                _ => return None,
+            };
+            // 16.16 fixed-point
+            let width = read_u32(tkhd, offset)? as CGFloat / 65536.0;
+            let height = read_u32(tkhd, offset + 4)? as CGFloat / 65536.0;
+            (width > 0.0 && height > 0.0).then_some((width, height))
+        });
+    Some(MovieInfo {
+        duration: duration as f64 / f64::from(timescale),
+        video_size,
+    })
+}
+
+/// Decode a movie's soundtrack, if it has one in a supported format.
+fn decode_soundtrack(bytes: Vec<u8>, extension: Option<&str>) -> Option<Soundtrack> {
+    let mut audio_file = audio::AudioFile::open_from_bytes(bytes, extension).ok()?;
+    let audio::AudioDescription {
+        sample_rate,
+        channels_per_frame,
+        ..
+    } = audio_file.audio_description();
+    // OpenAL only has mono and stereo formats.
+    if !(1..=2).contains(&channels_per_frame) {
+        return None;
+    }
+    let frame_count: usize = audio_file.frame_count().try_into().unwrap();
+    let mut samples = vec![0i16; frame_count * channels_per_frame as usize];
+    let frames_read = audio_file.read_pcm_frames(0, &mut samples).ok()?;
+    samples.truncate(frames_read * channels_per_frame as usize);
+    Some(Soundtrack {
+        samples,
+        channels: channels_per_frame,
+        sample_rate: sample_rate as u32,
+    })
+}
+
+/// Where the picture goes within the screen, for a scaling mode.
+fn video_rect(
+    scaling_mode: MPMovieScalingMode,
+    video_size: Option<(CGFloat, CGFloat)>,
+    (screen_width, screen_height): (CGFloat, CGFloat),
+) -> CGRect {
+    let Some((video_width, video_height)) = video_size else {
+        return rect(0.0, 0.0, screen_width, screen_height);
+    };
+    let (width, height) = match scaling_mode {
+        MPMovieScalingModeNone => (video_width, video_height),
+        MPMovieScalingModeAspectFit | MPMovieScalingModeAspectFill => {
+            let scale_x = screen_width / video_width;
+            let scale_y = screen_height / video_height;
+            let scale = if scaling_mode == MPMovieScalingModeAspectFit {
+                scale_x.min(scale_y)
+            } else {
+                scale_x.max(scale_y)
+            };
+            (video_width * scale, video_height * scale)
+        }
+        _ => (screen_width, screen_height),
+    };
+    rect(
+        (screen_width - width) / 2.0,
+        (screen_height - height) / 2.0,
+        width,
+        height,
+    )
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation MPMoviePlayerController: NSObject
+
++ (id)alloc {
+    let host_object = Box::new(MPMoviePlayerControllerHostObject {
+        content_url: nil,
+        movie: None,
+        soundtrack: None,
+        scaling_mode: MPMovieScalingModeAspectFit,
+        control_mode: MPMovieControlModeDefault,
+        background_color: nil,
+        initial_playback_time: 0.0,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithContentURL:(id)url { // NSURL*
+    let path = to_rust_path(env, url);
+    let extension = path
+        .file_name()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension);
+    let (movie, soundtrack) = match env.fs.read(&path) {
+        Ok(bytes) => {
+            let movie = parse_movie_info(&bytes);
+            let soundtrack = decode_soundtrack(bytes, extension);
+            (movie, soundtrack)
+        }
+        Err(()) => (None, None),
+    };
+    if movie.is_none() {
+        log!(
+            "Warning: MPMoviePlayerController couldn't read the movie {:?}, it will fail to play",
+            path
+        );
+    }
+    log_dbg!(
+        "MPMoviePlayerController {:?} loaded {:?}: {:?}, soundtrack: {}",
+        this,
+        path,
+        movie,
+        soundtrack.is_some()
+    );
+
+    retain(env, url);
+    let host_object = borrow(env, this);
+    host_object.content_url = url;
+    host_object.movie = movie;
+    host_object.soundtrack = soundtrack;
+
+    // The notification is posted once the app has had a chance to observe it.
+    retain(env, this);
+    State::get(&mut env.framework_state).preloading.push(this);
+    this
+}
+
+- (())dealloc {
+    let &mut MPMoviePlayerControllerHostObject {
+        content_url,
+        background_color,
+        ..
+    } = borrow(env, this);
+    release(env, content_url);
+    release(env, background_color);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)contentURL {
+    borrow(env, this).content_url
+}
+
+- (MPMovieScalingMode)scalingMode {
+    borrow(env, this).scaling_mode
+}
+- (())setScalingMode:(MPMovieScalingMode)scaling_mode {
+    borrow(env, this).scaling_mode = scaling_mode;
+    invalidate_overlay(env, this);
+    let name = get_static_str(env, MPMoviePlayerScalingModeDidChangeNotification);
+    ns_notification_center::post(env, name, this, nil);
+}
+
+- (MPMovieControlMode)movieControlMode {
+    borrow(env, this).control_mode
+}
+- (())setMovieControlMode:(MPMovieControlMode)control_mode {
+    borrow(env, this).control_mode = control_mode;
+}
+
+- (id)backgroundColor {
+    let color = borrow(env, this).background_color;
+    if color == nil {
+        msg_class![env; UIColor blackColor]
+    } else {
+        color
+    }
+}
+- (())setBackgroundColor:(id)color { // UIColor*
+    retain(env, color);
+    let old = std::mem::replace(&mut borrow(env, this).background_color, color);
+    release(env, old);
+    invalidate_overlay(env, this);
+}
+
+- (NSTimeInterval)initialPlaybackTime {
+    borrow(env, this).initial_playback_time
+}
+- (())setInitialPlaybackTime:(NSTimeInterval)time {
+    borrow(env, this).initial_playback_time = time.max(0.0);
+}
+
+- (())play {
+    play(env, this);
+}
+
+- (())stop {
+    let state = State::get(&mut env.framework_state);
+    if state.playback.as_ref().is_some_and(|playback| playback.player == this) {
+        finish(env, MPMovieFinishReasonPlaybackEnded);
+    }
+}
+
+@end
+
+};
+
+fn play(env: &mut Environment, player: id) {
+    let state = State::get(&mut env.framework_state);
+    if let Some(playback) = &state.playback {
+        if playback.player == player {
+            return;
+        }
+        // Only one movie can be shown at a time.
+        finish(env, MPMovieFinishReasonPlaybackEnded);
+    }
+
+    let host_object = borrow(env, player);
+    let initial_playback_time = host_object.initial_playback_time;
+    let duration = match (&host_object.movie, &host_object.soundtrack) {
+        (Some(movie), _) => Some(movie.duration),
+        (None, Some(soundtrack)) => Some(soundtrack.duration()),
+        (None, None) => None,
+    };
+    let Some(duration) = duration else {
+        // This gets reported on the next poll, so the app isn't re-entered.
+        log!(
+            "Warning: MPMoviePlayerController {:?} can't play its movie",
+            player
+        );
+        retain(env, player);
+        State::get(&mut env.framework_state).playback = Some(Playback {
+            player,
+            started_at: Instant::now(),
+            remaining: -1.0,
+            al_source_and_buffer: None,
+        });
+        return;
+    };
+
+    let al_source_and_buffer = host_object.soundtrack.as_ref().map(|soundtrack| {
+        let format = match soundtrack.channels {
+            1 => al::AL_FORMAT_MONO16,
+            2 => al::AL_FORMAT_STEREO16,
+            _ => unreachable!(),
+        };
+        let samples: *const i16 = soundtrack.samples.as_ptr();
+        let size = soundtrack.samples.len() * 2;
+        let sample_rate = soundtrack.sample_rate;
+        (format, samples, size, sample_rate)
+    });
+    let al_source_and_buffer = al_source_and_buffer.map(|(format, samples, size, sample_rate)| {
+        let _context_manager = make_al_context_current(env);
+        let mut al_source = 0;
+        let mut al_buffer = 0;
+        unsafe {
+            al::alGenSources(1, &mut al_source);
+            al::alGenBuffers(1, &mut al_buffer);
+            al::alBufferData(
+                al_buffer,
+                format,
+                samples as *const ALvoid,
+                size.try_into().unwrap(),
+                sample_rate.try_into().unwrap(),
+            );
+            al::alSourcei(al_source, al::AL_BUFFER, al_buffer.try_into().unwrap());
+            assert!(al::alGetError() == al::AL_NO_ERROR);
+        }
+        audio::mixer::configure_source(al_source, &env.options);
+        unsafe {
+            al::alSourcef(
+                al_source,
+                al::AL_SEC_OFFSET,
+                initial_playback_time as ALfloat,
+            );
+            // An offset past the end is an error, but that's fine: the movie
+            // will be over straight away.
+            al::alGetError();
+            al::alSourcePlay(al_source);
+            assert!(al::alGetError() == al::AL_NO_ERROR);
+        }
+        (al_source, al_buffer)
+    });
+
+    log_dbg!(
+        "Playing movie of MPMoviePlayerController {:?} from {}s",
+        player,
+        initial_playback_time
+    );
+    retain(env, player);
+    let state = State::get(&mut env.framework_state);
+    state.playback = Some(Playback {
+        player,
+        started_at: Instant::now(),
+        remaining: duration - initial_playback_time,
+        al_source_and_buffer,
+    });
+    state.overlay = None;
+}
+
+/// Stop showing the current movie and tell the app it's over.
+fn finish(env: &mut Environment, reason: MPMovieFinishReason) {
+    let state = State::get(&mut env.framework_state);
+    let Some(playback) = state.playback.take() else {
+        return;
+    };
+    state.overlay = None;
+    if let Some((al_source, al_buffer)) = playback.al_source_and_buffer {
+        let _context_manager = make_al_context_current(env);
+        unsafe {
+            al::alSourceStop(al_source);
+            al::alDeleteSources(1, &al_source);
+            al::alDeleteBuffers(1, &al_buffer);
+            assert!(al::alGetError() == al::AL_NO_ERROR);
+        }
+    }
+    let player = playback.player;
+    log_dbg!(
+        "MPMoviePlayerController {:?} finished playing (reason {})",
+        player,
+        reason
+    );
+
+    let reason: id = msg_class![env; NSNumber numberWithInteger:reason];
+    let key = get_static_str(env, MPMoviePlayerPlaybackDidFinishReasonUserInfoKey);
+    let user_info = dict_from_keys_and_objects(env, &[(key, reason)]);
+    let name = get_static_str(env, MPMoviePlayerPlaybackDidFinishNotification);
+    ns_notification_center::post(env, name, player, user_info);
+    release(env, user_info);
+    release(env, player);
+}
+
+fn invalidate_overlay(env: &mut Environment, player: id) {
+    let state = State::get(&mut env.framework_state);
+    if state
+        .playback
+        .as_ref()
+        .is_some_and(|playback| playback.player == player)
+    {
+        state.overlay = None;
+    }
+}
+
+/// For use by `NSRunLoop`: post notifications for players that have loaded
+/// their movies or finished playing them.
+pub fn handle_movie_players(env: &mut Environment) {
+    let preloading = std::mem::take(&mut State::get(&mut env.framework_state).preloading);
+    for player in preloading {
+        let name = get_static_str(env, MPMoviePlayerContentPreloadDidFinishNotification);
+        ns_notification_center::post(env, name, player, nil);
+        release(env, player);
+    }
+
+    let Some(&Playback {
+        started_at,
+        remaining,
+        ..
+    }) = State::get(&mut env.framework_state).playback.as_ref()
+    else {
+        return;
+    };
+    if remaining < 0.0 {
+        finish(env, MPMovieFinishReasonPlaybackError);
+    } else if started_at.elapsed().as_secs_f64() >= remaining {
+        finish(env, MPMovieFinishReasonPlaybackEnded);
+    }
+}
+
+/// For use by the compositor: is a movie being shown? The app's own frames
+/// are hidden by it, so it needs compositing even if they would otherwise be
+/// presented directly.
+pub fn is_playing(env: &mut Environment) -> bool {
+    State::get(&mut env.framework_state).playback.is_some()
+}
+
+/// For use by [crate::frameworks::uikit::overlay]: draw the movie if needed.
+pub fn update_overlay(env: &mut Environment) {
+    let state = State::get(&mut env.framework_state);
+    if state.overlay.is_some() {
+        return;
+    }
+    let Some(player) = state.playback.as_ref().map(|playback| playback.player) else {
+        return;
+    };
+
+    // Movies are always shown in landscape.
+    let orientation = match env.window.device_orientation() {
+        DeviceOrientation::LandscapeRight => DeviceOrientation::LandscapeRight,
+        _ => DeviceOrientation::LandscapeLeft,
+    };
+    let (points_height, points_width) = env.window.size_unrotated_unscaled();
+    let screen_size = (points_width as CGFloat, points_height as CGFloat);
+
+    let background_color: id = msg![env; player backgroundColor];
+    let background_color = ui_color::get_rgba(env, background_color);
+    let &mut MPMoviePlayerControllerHostObject {
+        scaling_mode,
+        movie,
+        ..
+    } = borrow(env, player);
+    let video_size = movie.and_then(|movie| movie.video_size);
+
+    let mut canvas = Canvas::new_for_orientation(env, orientation);
+    canvas.fill_rounded_rect(
+        rect(0.0, 0.0, screen_size.0, screen_size.1),
+        0.0,
+        background_color,
+    );
+    canvas.fill_rounded_rect(
+        video_rect(scaling_mode, video_size, screen_size),
+        0.0,
+        (0.0, 0.0, 0.0, 1.0),
+    );
+    State::get(&mut env.framework_state).overlay = Some(canvas.into_overlay());
+}
+
+/// For use by [crate::frameworks::uikit::overlay]: get the drawing of the
+/// movie, if one is playing.
+pub fn current_overlay(framework_state: &crate::frameworks::State) -> Option<&Overlay> {
+    let state = &framework_state.media_player.mp_movie_player_controller;
+    if state.playback.is_none() {
+        None
+    } else {
+        state.overlay.as_ref()
+    }
+}
+
+/// For use by [crate::frameworks::uikit::handle_events]: the movie takes all
+/// touches while it's playing. Tapping it skips it, unless the app hid the
+/// controls. Returns [true] if the event was consumed.
+pub fn handle_event(env: &mut Environment, event: &Event) -> bool {
+    let Some(player) = State::get(&mut env.framework_state)
+        .playback
+        .as_ref()
+        .map(|playback| playback.player)
+    else {
+        return false;
+    };
+    if let Event::TouchUp(..) = event {
+        if borrow(env, player).control_mode == MPMovieControlModeDefault {
+            finish(env, MPMovieFinishReasonUserExited);
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_box(box_type: &[u8; 4], contents: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(8 + contents.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(box_type);
+        bytes.extend_from_slice(contents);
+        bytes
+    }
+
+    fn make_tkhd(width: u32, height: u32) -> Vec<u8> {
+        let mut tkhd = vec![0u8; 84];
+        tkhd[76..80].copy_from_slice(&(width << 16).to_be_bytes());
+        tkhd[80..84].copy_from_slice(&(height << 16).to_be_bytes());
+        make_box(b"tkhd", &tkhd)
+    }
+
+    #[test]
+    fn movie_info() {
+        let mut mvhd = vec![0u8; 100];
+        mvhd[12..16].copy_from_slice(&600u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&1500u32.to_be_bytes());
+        let audio_trak = make_box(b"trak", &make_tkhd(0, 0));
+        let video_trak = make_box(b"trak", &make_tkhd(480, 320));
+        let moov = make_box(
+            b"moov",
+            &[make_box(b"mvhd", &mvhd), audio_trak, video_trak].concat(),
+        );
+        let file = [make_box(b"ftyp", b"M4V \0\0\0\0"), moov].concat();
+        assert_eq!(
+            parse_movie_info(&file),
+            Some(MovieInfo {
+                duration: 2.5,
+                video_size: Some((480.0, 320.0)),
+            })
+        );
+        // Truncated files
+        assert_eq!(parse_movie_info(&file[..file.len() - 1]), None);
+        assert_eq!(parse_movie_info(b""), None);
+    }
+
+    #[test]
+    fn scaling_modes() {
+        let screen = (480.0, 320.0);
+        let video = Some((240.0, 240.0));
+        assert_eq!(
+            video_rect(MPMovieScalingModeNone, video, screen),
+            rect(120.0, 40.0, 240.0, 240.0)
+        );
+        assert_eq!(
+            video_rect(MPMovieScalingModeAspectFit, video, screen),
+            rect(80.0, 0.0, 320.0, 320.0)
+        );
+        assert_eq!(
+            video_rect(MPMovieScalingModeAspectFill, video, screen),
+            rect(0.0, -80.0, 480.0, 480.0)
+        );
+        assert_eq!(
+            video_rect(MPMovieScalingModeFill, video, screen),
+            rect(0.0, 0.0, 480.0, 320.0)
+        );
+        assert_eq!(
+            video_rect(MPMovieScalingModeAspectFit, None, screen),
+            rect(0.0, 0.0, 480.0, 320.0)
+        );
+    }
+}
@@ -0,0 +1,480 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MPMusicPlayerController`.
+//!
+//! Apps use this to let the user play their own music from the iPod library,
+//! which is the host's music directory (see `--music-dir=`). Each song is
+//! decoded when it starts and played with a single OpenAL source in
+//! touchHLE's internal context, like an `AVAudioPlayer`. Shuffling isn't
+//! supported, songs are always played in order.
+
+use super::mp_media_item;
+use crate::audio; // Keep this module namespaced to avoid confusion
+use crate::audio::openal as al;
+use crate::audio::openal::al_types::*;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::audio_toolbox::audio_queue::make_al_context_current;
+use crate::frameworks::foundation::ns_notification_center;
+use crate::frameworks::foundation::ns_string::get_static_str;
+use crate::frameworks::foundation::{NSInteger, NSTimeInterval, NSUInteger};
+use crate::objc::{id, msg, nil, objc_classes, release, retain, Class, ClassExports, HostObject};
+use crate::Environment;
+
+pub const MPMusicPlayerControllerPlaybackStateDidChangeNotification: &str =
+    "MPMusicPlayerControllerPlaybackStateDidChangeNotification";
+pub const MPMusicPlayerControllerNowPlayingItemDidChangeNotification: &str =
+    "MPMusicPlayerControllerNowPlayingItemDidChangeNotification";
+pub const MPMusicPlayerControllerVolumeDidChangeNotification: &str =
+    "MPMusicPlayerControllerVolumeDidChangeNotification";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_MPMusicPlayerControllerPlaybackStateDidChangeNotification",
+        HostConstant::NSString(MPMusicPlayerControllerPlaybackStateDidChangeNotification),
+    ),
+    (
+        "_MPMusicPlayerControllerNowPlayingItemDidChangeNotification",
+        HostConstant::NSString(MPMusicPlayerControllerNowPlayingItemDidChangeNotification),
+    ),
+    (
+        "_MPMusicPlayerControllerVolumeDidChangeNotification",
+        HostConstant::NSString(MPMusicPlayerControllerVolumeDidChangeNotification),
+    ),
+];
+
+type MPMusicPlaybackState = NSInteger;
+const MPMusicPlaybackStateStopped: MPMusicPlaybackState = 0;
+const MPMusicPlaybackStatePlaying: MPMusicPlaybackState = 1;
+const MPMusicPlaybackStatePaused: MPMusicPlaybackState = 2;
+
+type MPMusicRepeatMode = NSInteger;
+const MPMusicRepeatModeDefault: MPMusicRepeatMode = 0;
+const MPMusicRepeatModeOne: MPMusicRepeatMode = 2;
+const MPMusicRepeatModeAll: MPMusicRepeatMode = 3;
+
+type MPMusicShuffleMode = NSInteger;
+const MPMusicShuffleModeDefault: MPMusicShuffleMode = 0;
+const MPMusicShuffleModeOff: MPMusicShuffleMode = 1;
+
+#[derive(Default)]
+pub struct State {
+    ipod_music_player: Option<id>,
+    application_music_player: Option<id>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.media_player.mp_music_player_controller
+    }
+}
+
+struct MPMusicPlayerControllerHostObject {
+    /// `MPMediaItem*`s, strong references.
+    queue: Vec<id>,
+    /// Index in `queue` of the now-playing item.
+    index: usize,
+    playback_state: MPMusicPlaybackState,
+    /// Created when the now-playing item starts, along with `al_buffer`.
+    al_source: Option<ALuint>,
+    al_buffer: Option<ALuint>,
+    volume: f32,
+    repeat_mode: MPMusicRepeatMode,
+    shuffle_mode: MPMusicShuffleMode,
+    generating_notifications: bool,
+}
+impl HostObject for MPMusicPlayerControllerHostObject {}
+
+fn borrow(env: &mut Environment, player: id) -> &mut MPMusicPlayerControllerHostObject {
+    env.objc.borrow_mut(player)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation MPMusicPlayerController: NSObject
+
++ (id)iPodMusicPlayer {
+    if let Some(existing) = State::get(&mut env.framework_state).ipod_music_player {
+        existing
+    } else {
+        let new = new_player(env, this);
+        State::get(&mut env.framework_state).ipod_music_player = Some(new);
+        new
+    }
+}
+
++ (id)applicationMusicPlayer {
+    if let Some(existing) = State::get(&mut env.framework_state).application_music_player {
+        existing
+    } else {
+        let new = new_player(env, this);
+        State::get(&mut env.framework_state).application_music_player = Some(new);
+        new
+    }
+}
+
+- (())setQueueWithQuery:(id)query { // MPMediaQuery*
+    let items: id = msg![env; query items];
+    set_queue(env, this, items);
+}
+
+- (())setQueueWithItemCollection:(id)collection { // MPMediaItemCollection*
+    let items: id = if collection == nil {
+        nil
+    } else {
+        msg![env; collection items]
+    };
+    set_queue(env, this, items);
+}
+
+- (id)nowPlayingItem {
+    let host_object = borrow(env, this);
+    host_object.queue.get(host_object.index).copied().unwrap_or(nil)
+}
+- (())setNowPlayingItem:(id)item { // MPMediaItem*
+    let Some(index) = borrow(env, this).queue.iter().position(|&i| i == item) else {
+        log!(
+            "Warning: MPMusicPlayerController {:?} can't play {:?}, it isn't in the queue",
+            this,
+            item
+        );
+        return;
+    };
+    skip_to(env, this, index);
+}
+
+- (MPMusicPlaybackState)playbackState {
+    borrow(env, this).playback_state
+}
+
+- (())play {
+    play(env, this);
+}
+
+- (())pause {
+    let host_object = borrow(env, this);
+    if host_object.playback_state != MPMusicPlaybackStatePlaying {
+        return;
+    }
+    host_object.playback_state = MPMusicPlaybackStatePaused;
+    if let Some(al_source) = host_object.al_source {
+        let _context_manager = make_al_context_current(env);
+        unsafe { al::alSourcePause(al_source) };
+    }
+    post(env, this, MPMusicPlayerControllerPlaybackStateDidChangeNotification);
+}
+
+- (())stop {
+    stop(env, this);
+}
+
+- (())skipToNextItem {
+    let host_object = borrow(env, this);
+    let next = host_object.index + 1;
+    if next < host_object.queue.len() {
+        skip_to(env, this, next);
+    } else {
+        stop(env, this);
+    }
+}
+- (())skipToPreviousItem {
+    let index = borrow(env, this).index;
+    skip_to(env, this, index.saturating_sub(1));
+}
+- (())skipToBeginning {
+    if let Some(al_source) = borrow(env, this).al_source {
+        let _context_manager = make_al_context_current(env);
+        unsafe { al::alSourcef(al_source, al::AL_SEC_OFFSET, 0.0) };
+    }
+}
+
+- (NSTimeInterval)currentPlaybackTime {
+    let Some(al_source) = borrow(env, this).al_source else {
+        return 0.0;
+    };
+    let _context_manager = make_al_context_current(env);
+    let mut offset = 0.0;
+    unsafe { al::alGetSourcef(al_source, al::AL_SEC_OFFSET, &mut offset) };
+    offset.into()
+}
+- (())setCurrentPlaybackTime:(NSTimeInterval)time {
+    if let Some(al_source) = borrow(env, this).al_source {
+        let _context_manager = make_al_context_current(env);
+        unsafe { al::alSourcef(al_source, al::AL_SEC_OFFSET, time as ALfloat) };
+    }
+}
+
+- (f32)volume {
+    borrow(env, this).volume
+}
+- (())setVolume:(f32)volume {
+    let host_object = borrow(env, this);
+    host_object.volume = volume.clamp(0.0, 1.0);
+    let volume = host_object.volume;
+    if let Some(al_source) = host_object.al_source {
+        let _context_manager = make_al_context_current(env);
+        unsafe { al::alSourcef(al_source, al::AL_GAIN, volume) };
+    }
+    post(env, this, MPMusicPlayerControllerVolumeDidChangeNotification);
+}
+
+- (MPMusicRepeatMode)repeatMode {
+    borrow(env, this).repeat_mode
+}
+- (())setRepeatMode:(MPMusicRepeatMode)repeat_mode {
+    borrow(env, this).repeat_mode = repeat_mode;
+}
+
+- (MPMusicShuffleMode)shuffleMode {
+    borrow(env, this).shuffle_mode
+}
+- (())setShuffleMode:(MPMusicShuffleMode)shuffle_mode {
+    if !matches!(shuffle_mode, MPMusicShuffleModeDefault | MPMusicShuffleModeOff) {
+        log!("TODO: MPMusicPlayerController shuffle mode {}, playing in order", shuffle_mode);
+    }
+    borrow(env, this).shuffle_mode = shuffle_mode;
+}
+
+- (())beginGeneratingPlaybackNotifications {
+    borrow(env, this).generating_notifications = true;
+}
+- (())endGeneratingPlaybackNotifications {
+    borrow(env, this).generating_notifications = false;
+}
+
+@end
+
+};
+
+fn new_player(env: &mut Environment, class: Class) -> id {
+    let host_object = Box::new(MPMusicPlayerControllerHostObject {
+        queue: Vec::new(),
+        index: 0,
+        playback_state: MPMusicPlaybackStateStopped,
+        al_source: None,
+        al_buffer: None,
+        volume: 1.0,
+        repeat_mode: MPMusicRepeatModeDefault,
+        shuffle_mode: MPMusicShuffleModeDefault,
+        generating_notifications: false,
+    });
+    env.objc
+        .alloc_static_object(class, host_object, &mut env.mem)
+}
+
+/// Post a notification, if the app asked for them.
+fn post(env: &mut Environment, player: id, name: &'static str) {
+    if !borrow(env, player).generating_notifications {
+        return;
+    }
+    let name = get_static_str(env, name);
+    ns_notification_center::post(env, name, player, nil);
+}
+
+fn set_queue(env: &mut Environment, player: id, items: id) {
+    stop(env, player);
+    let count: NSUInteger = if items == nil {
+        0
+    } else {
+        msg![env; items count]
+    };
+    let mut queue = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let item: id = msg![env; items objectAtIndex:i];
+        retain(env, item);
+        queue.push(item);
+    }
+    log_dbg!(
+        "MPMusicPlayerController {:?} queue set to {} items",
+        player,
+        queue.len()
+    );
+    let host_object = borrow(env, player);
+    let old_queue = std::mem::replace(&mut host_object.queue, queue);
+    host_object.index = 0;
+    for item in old_queue {
+        release(env, item);
+    }
+    post(
+        env,
+        player,
+        MPMusicPlayerControllerNowPlayingItemDidChangeNotification,
+    );
+}
+
+/// Delete the now-playing item's source and buffer, if any.
+fn unload(env: &mut Environment, player: id) {
+    let host_object = borrow(env, player);
+    let (Some(al_source), Some(al_buffer)) =
+        (host_object.al_source.take(), host_object.al_buffer.take())
+    else {
+        return;
+    };
+    let _context_manager = make_al_context_current(env);
+    unsafe {
+        al::alSourceStop(al_source);
+        al::alDeleteSources(1, &al_source);
+        al::alDeleteBuffers(1, &al_buffer);
+        assert!(al::alGetError() == al::AL_NO_ERROR);
+    }
+}
+
+/// Decode the now-playing item and create a source for it. If it can't be
+/// decoded, the next item that can is used instead. Returns [false] if none
+/// can be.
+fn load(env: &mut Environment, player: id) -> bool {
+    unload(env, player);
+    let host_object = borrow(env, player);
+    let (mut index, queue) = (host_object.index, host_object.queue.clone());
+    let song = loop {
+        let Some(&item) = queue.get(index) else {
+            return false;
+        };
+        if let Some(song) = mp_media_item::decode(env, item) {
+            break song;
+        }
+        index += 1;
+    };
+    let volume = {
+        let host_object = borrow(env, player);
+        host_object.index = index;
+        host_object.volume
+    };
+
+    let format = match song.channels {
+        1 => al::AL_FORMAT_MONO16,
+        2 => al::AL_FORMAT_STEREO16,
+        _ => unreachable!(),
+    };
+    let _context_manager = make_al_context_current(env);
+    let mut al_source = 0;
+    let mut al_buffer = 0;
+    unsafe {
+        al::alGenSources(1, &mut al_source);
+        al::alGenBuffers(1, &mut al_buffer);
+        al::alBufferData(
+            al_buffer,
+            format,
+            song.samples.as_ptr() as *const ALvoid,
+            (song.samples.len() * 2).try_into().unwrap(),
+            song.sample_rate.try_into().unwrap(),
+        );
+        al::alSourcei(al_source, al::AL_BUFFER, al_buffer.try_into().unwrap());
+        al::alSourcef(al_source, al::AL_GAIN, volume);
+        assert!(al::alGetError() == al::AL_NO_ERROR);
+    }
+    audio::mixer::configure_source(al_source, &env.options);
+    let host_object = borrow(env, player);
+    host_object.al_source = Some(al_source);
+    host_object.al_buffer = Some(al_buffer);
+    true
+}
+
+fn play(env: &mut Environment, player: id) {
+    if borrow(env, player).playback_state == MPMusicPlaybackStatePlaying {
+        return;
+    }
+    let old_index = borrow(env, player).index;
+    if borrow(env, player).al_source.is_none() && !load(env, player) {
+        log!("MPMusicPlayerController {:?} has nothing to play", player);
+        return;
+    }
+    let host_object = borrow(env, player);
+    let index = host_object.index;
+    host_object.playback_state = MPMusicPlaybackStatePlaying;
+    let al_source = host_object.al_source.unwrap();
+    {
+        let _context_manager = make_al_context_current(env);
+        unsafe { al::alSourcePlay(al_source) };
+    }
+    log_dbg!(
+        "MPMusicPlayerController {:?} playing item {}",
+        player,
+        index
+    );
+    if index != old_index {
+        post(
+            env,
+            player,
+            MPMusicPlayerControllerNowPlayingItemDidChangeNotification,
+        );
+    }
+    post(
+        env,
+        player,
+        MPMusicPlayerControllerPlaybackStateDidChangeNotification,
+    );
+}
+
+fn stop(env: &mut Environment, player: id) {
+    unload(env, player);
+    let host_object = borrow(env, player);
+    if host_object.playback_state == MPMusicPlaybackStateStopped {
+        return;
+    }
+    host_object.playback_state = MPMusicPlaybackStateStopped;
+    post(
+        env,
+        player,
+        MPMusicPlayerControllerPlaybackStateDidChangeNotification,
+    );
+}
+
+fn skip_to(env: &mut Environment, player: id, index: usize) {
+    let was_playing = borrow(env, player).playback_state == MPMusicPlaybackStatePlaying;
+    unload(env, player);
+    let host_object = borrow(env, player);
+    host_object.index = index;
+    if was_playing {
+        host_object.playback_state = MPMusicPlaybackStatePaused;
+        play(env, player);
+    }
+    post(
+        env,
+        player,
+        MPMusicPlayerControllerNowPlayingItemDidChangeNotification,
+    );
+}
+
+/// For use by `NSRunLoop`: move on to the next song when one ends.
+pub fn handle_music_players(env: &mut Environment) {
+    let state = State::get(&mut env.framework_state);
+    let players = [state.ipod_music_player, state.application_music_player];
+    for player in players.into_iter().flatten() {
+        let host_object = borrow(env, player);
+        if host_object.playback_state != MPMusicPlaybackStatePlaying {
+            continue;
+        }
+        let al_source = host_object.al_source.unwrap();
+        let source_state = {
+            let _context_manager = make_al_context_current(env);
+            let mut source_state = 0;
+            unsafe { al::alGetSourcei(al_source, al::AL_SOURCE_STATE, &mut source_state) };
+            source_state
+        };
+        if source_state != al::AL_STOPPED {
+            continue;
+        }
+
+        let host_object = borrow(env, player);
+        let (index, len) = (host_object.index, host_object.queue.len());
+        match host_object.repeat_mode {
+            MPMusicRepeatModeOne => {
+                let _context_manager = make_al_context_current(env);
+                unsafe { al::alSourcePlay(al_source) };
+            }
+            _ if index + 1 < len => skip_to(env, player, index + 1),
+            MPMusicRepeatModeAll => skip_to(env, player, 0),
+            _ => {
+                log_dbg!(
+                    "MPMusicPlayerController {:?} reached the end of its queue",
+                    player
+                );
+                stop(env, player);
+            }
+        }
+    }
+}
@@ -0,0 +1,13 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The MessageUI framework.
+
+pub mod mf_mail_compose_view_controller;
+
+#[derive(Default)]
+pub struct State {
+    mf_mail_compose_view_controller: mf_mail_compose_view_controller::State,
+}
@@ -0,0 +1,803 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MFMailComposeViewController`.
+//!
+//! Apps mostly use this for "send feedback" buttons. While a composer is
+//! presented, the message the app composed is drawn by the host as a sheet (see
+//! [crate::frameworks::uikit::overlay]) with "Cancel" and "Send" buttons, and
+//! the sheet takes all new touches. The message can't be edited.
+//!
+//! Sending a message saves it as an `.eml` file (see the `--mail-dir=` option)
+//! and/or opens it in the host's mail client (see the `--open-mail-client`
+//! option). Otherwise it goes nowhere, but the app is still told it was sent.
+
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::foundation::ns_calendar::civil_from_days;
+use crate::frameworks::foundation::ns_error::new_error;
+use crate::frameworks::foundation::ns_string::to_rust_string;
+use crate::frameworks::foundation::{ns_data, NSInteger, NSUInteger};
+use crate::frameworks::uikit::overlay::{contains, rect, Canvas, Overlay};
+use crate::frameworks::uikit::ui_font;
+use crate::frameworks::uikit::ui_view_controller::UIViewControllerHostObject;
+use crate::mem::MutVoidPtr;
+use crate::objc::{id, msg, msg_class, nil, objc_classes, release, retain, ClassExports};
+use crate::window::{Event, TouchSource};
+use crate::Environment;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub type MFMailComposeResult = NSInteger;
+pub const MFMailComposeResultCancelled: MFMailComposeResult = 0;
+pub const MFMailComposeResultSent: MFMailComposeResult = 2;
+pub const MFMailComposeResultFailed: MFMailComposeResult = 3;
+
+pub const MFMailComposeErrorDomain: &str = "MFMailComposeErrorDomain";
+
+pub type MFMailComposeErrorCode = NSInteger;
+pub const MFMailComposeErrorCodeSendFailed: MFMailComposeErrorCode = 1;
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_MFMailComposeErrorDomain",
+    HostConstant::NSString(MFMailComposeErrorDomain),
+)];
+
+#[derive(Default)]
+pub struct State {
+    /// The presented composer, if there is one. Weak reference, the presenting
+    /// view controller owns it.
+    composer: Option<id>,
+    /// Cached drawing of the sheet, cleared when it needs redrawing.
+    overlay: Option<Overlay>,
+    /// The touch being tracked by the sheet, if any, with the button it began
+    /// on.
+    touch: Option<(TouchSource, Option<Button>)>,
+    /// The button under the current touch.
+    pressed: Option<Button>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.message_ui.mf_mail_compose_view_controller
+    }
+}
+
+pub(crate) struct ComposerState {
+    /// Weak reference.
+    delegate: id,
+    message: Message,
+}
+
+#[derive(Default, Clone)]
+struct Message {
+    to: Vec<String>,
+    cc: Vec<String>,
+    bcc: Vec<String>,
+    subject: String,
+    body: String,
+    is_html: bool,
+    attachments: Vec<Attachment>,
+}
+
+#[derive(Clone)]
+struct Attachment {
+    mime_type: String,
+    file_name: String,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Button {
+    Cancel,
+    Send,
+}
+
+const NAV_BAR_HEIGHT: CGFloat = 44.0;
+const FIELD_HEIGHT: CGFloat = 36.0;
+const MARGIN: CGFloat = 8.0;
+const TITLE_SIZE: CGFloat = 20.0;
+const BUTTON_TITLE_SIZE: CGFloat = 13.0;
+const TEXT_SIZE: CGFloat = 15.0;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation MFMailComposeViewController: UINavigationController
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let mut host_object = UIViewControllerHostObject::new();
+    host_object.mail_composer = Some(Box::new(ComposerState {
+        delegate: nil,
+        message: Message::default(),
+    }));
+    env.objc.alloc_object(this, Box::new(host_object), &mut env.mem)
+}
+
++ (bool)canSendMail {
+    true
+}
+
+- (id)mailComposeDelegate {
+    composer_state(env, this).delegate
+}
+- (())setMailComposeDelegate:(id)delegate { // id<MFMailComposeViewControllerDelegate>
+    composer_state(env, this).delegate = delegate;
+}
+
+- (())setSubject:(id)subject { // NSString*
+    let subject = optional_string(env, subject);
+    composer_state(env, this).message.subject = subject;
+}
+- (())setToRecipients:(id)recipients { // NSArray<NSString*>*
+    let recipients = strings_from_array(env, recipients);
+    composer_state(env, this).message.to = recipients;
+}
+- (())setCcRecipients:(id)recipients { // NSArray<NSString*>*
+    let recipients = strings_from_array(env, recipients);
+    composer_state(env, this).message.cc = recipients;
+}
+- (())setBccRecipients:(id)recipients { // NSArray<NSString*>*
+    let recipients = strings_from_array(env, recipients);
+    composer_state(env, this).message.bcc = recipients;
+}
+- (())setMessageBody:(id)body // NSString*
+              isHTML:(bool)is_html {
+    let body = optional_string(env, body);
+    let message = &mut composer_state(env, this).message;
+    message.body = body;
+    message.is_html = is_html;
+}
+- (())addAttachmentData:(id)data // NSData*
+               mimeType:(id)mime_type // NSString*
+               fileName:(id)file_name { // NSString*
+    let data = ns_data::to_vec(env, data);
+    let mime_type = to_rust_string(env, mime_type).into_owned();
+    let file_name = to_rust_string(env, file_name).into_owned();
+    composer_state(env, this).message.attachments.push(Attachment {
+        mime_type,
+        file_name,
+        data,
+    });
+}
+
+@end
+
+};
+
+fn composer_state(env: &mut Environment, composer: id) -> &mut ComposerState {
+    env.objc
+        .borrow_mut::<UIViewControllerHostObject>(composer)
+        .mail_composer
+        .as_mut()
+        .unwrap()
+}
+
+fn optional_string(env: &mut Environment, string: id) -> String {
+    if string == nil {
+        String::new()
+    } else {
+        to_rust_string(env, string).into_owned()
+    }
+}
+
+fn strings_from_array(env: &mut Environment, array: id) -> Vec<String> {
+    if array == nil {
+        return Vec::new();
+    }
+    let count: NSUInteger = msg![env; array count];
+    (0..count)
+        .map(|index| {
+            let string: id = msg![env; array objectAtIndex:index];
+            to_rust_string(env, string).into_owned()
+        })
+        .collect()
+}
+
+/// For use by `UIViewController`: show the sheet when a composer is
+/// presented.
+pub(crate) fn present(env: &mut Environment, composer: id) {
+    log_dbg!("Mail composer {:?} presented", composer);
+    let state = State::get(&mut env.framework_state);
+    state.composer = Some(composer);
+    state.overlay = None;
+    state.touch = None;
+    state.pressed = None;
+}
+
+/// For use by `UIViewController`: hide the sheet when a composer is
+/// dismissed.
+pub(crate) fn dismiss(env: &mut Environment, composer: id) {
+    let state = State::get(&mut env.framework_state);
+    if state.composer == Some(composer) {
+        state.composer = None;
+        state.overlay = None;
+        state.touch = None;
+    }
+}
+
+fn button_rect(env: &mut Environment, button: Button) -> CGRect {
+    let (points_width, _) = env.window.size_unrotated_unscaled();
+    match button {
+        Button::Cancel => rect(8.0, 7.0, 62.0, 30.0),
+        Button::Send => rect(points_width as CGFloat - 70.0, 7.0, 62.0, 30.0),
+    }
+}
+
+fn button_at(env: &mut Environment, location: (f32, f32)) -> Option<Button> {
+    [Button::Cancel, Button::Send]
+        .into_iter()
+        .find(|&button| contains(button_rect(env, button), location))
+}
+
+fn draw(env: &mut Environment, composer: id, pressed: Option<Button>) -> Overlay {
+    let (points_width, points_height) = env.window.size_unrotated_unscaled();
+    let (points_width, points_height) = (points_width as CGFloat, points_height as CGFloat);
+
+    let message = &composer_state(env, composer).message;
+    let title = if message.subject.is_empty() {
+        "New Message".to_string()
+    } else {
+        message.subject.clone()
+    };
+    let mut fields = vec![("To:", message.to.join(", "))];
+    if !message.cc.is_empty() {
+        fields.push(("Cc:", message.cc.join(", ")));
+    }
+    if !message.bcc.is_empty() {
+        fields.push(("Bcc:", message.bcc.join(", ")));
+    }
+    fields.push(("Subject:", message.subject.clone()));
+    let attachments: Vec<String> = message
+        .attachments
+        .iter()
+        .map(|attachment| {
+            format!(
+                "Attachment: {} ({} KB)",
+                attachment.file_name,
+                attachment.data.len().div_ceil(1024)
+            )
+        })
+        .collect();
+    let body = if message.is_html {
+        html_to_text(&message.body)
+    } else {
+        message.body.clone()
+    };
+
+    let mut canvas = Canvas::new(env);
+    canvas.fill_rounded_rect(
+        rect(0.0, 0.0, points_width, points_height),
+        0.0,
+        (1.0, 1.0, 1.0, 1.0),
+    );
+
+    let mut y = NAV_BAR_HEIGHT;
+    for (label, value) in fields {
+        let font = ui_font::system_font(env, false, label);
+        let label_origin = CGPoint {
+            x: MARGIN,
+            y: y + (FIELD_HEIGHT - TEXT_SIZE) / 2.0,
+        };
+        canvas.draw_text_at(font, TEXT_SIZE, label, label_origin, (0.5, 0.5, 0.5, 1.0));
+        let font = ui_font::system_font(env, false, &value);
+        let value_origin = CGPoint {
+            x: MARGIN + 64.0,
+            ..label_origin
+        };
+        canvas.draw_text_at(font, TEXT_SIZE, &value, value_origin, (0.0, 0.0, 0.0, 1.0));
+        y += FIELD_HEIGHT;
+        canvas.fill_rounded_rect(
+            rect(0.0, y - 1.0, points_width, 1.0),
+            0.0,
+            (0.88, 0.88, 0.88, 1.0),
+        );
+    }
+    for attachment in attachments {
+        let font = ui_font::system_font(env, false, &attachment);
+        let origin = CGPoint {
+            x: MARGIN,
+            y: y + (FIELD_HEIGHT - TEXT_SIZE) / 2.0,
+        };
+        canvas.draw_text_at(font, TEXT_SIZE, &attachment, origin, (0.02, 0.45, 0.9, 1.0));
+        y += FIELD_HEIGHT;
+    }
+    let body_rect = rect(
+        MARGIN,
+        y + MARGIN,
+        points_width - MARGIN * 2.0,
+        points_height - y - MARGIN * 2.0,
+    );
+    canvas.set_clip(Some(body_rect));
+    let font = ui_font::system_font(env, false, &body);
+    canvas.draw_paragraph(font, TEXT_SIZE, &body, body_rect, (0.0, 0.0, 0.0, 1.0));
+    canvas.set_clip(None);
+
+    canvas.fill_rounded_rect(
+        rect(0.0, 0.0, points_width, NAV_BAR_HEIGHT),
+        0.0,
+        (0.43, 0.52, 0.63, 1.0),
+    );
+    let font = ui_font::system_font(env, true, &title);
+    canvas.draw_text_centered(
+        font,
+        TITLE_SIZE,
+        &title,
+        rect(76.0, 0.0, points_width - 152.0, NAV_BAR_HEIGHT),
+        (1.0, 1.0, 1.0, 1.0),
+    );
+    for (button, title, color) in [
+        (Button::Cancel, "Cancel", (0.29, 0.38, 0.5, 1.0)),
+        (Button::Send, "Send", (0.02, 0.45, 0.9, 1.0)),
+    ] {
+        let (r, g, b, a) = color;
+        let color = if pressed == Some(button) {
+            (r * 0.6, g * 0.6, b * 0.6, a)
+        } else {
+            color
+        };
+        let button_rect = button_rect(env, button);
+        canvas.fill_rounded_rect(button_rect, 5.0, color);
+        let font = ui_font::system_font(env, true, title);
+        canvas.draw_text_centered(
+            font,
+            BUTTON_TITLE_SIZE,
+            title,
+            button_rect,
+            (1.0, 1.0, 1.0, 1.0),
+        );
+    }
+
+    canvas.into_overlay()
+}
+
+/// For use by [crate::frameworks::uikit::overlay]: redraw the sheet if
+/// needed.
+pub fn update_overlay(env: &mut Environment) {
+    let state = State::get(&mut env.framework_state);
+    if state.overlay.is_some() {
+        return;
+    }
+    let Some(composer) = state.composer else {
+        return;
+    };
+    let pressed = state.pressed;
+    let overlay = draw(env, composer, pressed);
+    State::get(&mut env.framework_state).overlay = Some(overlay);
+}
+
+/// For use by [crate::frameworks::uikit::overlay]: get the drawing of the
+/// sheet, if a composer is presented.
+pub fn current_overlay(framework_state: &crate::frameworks::State) -> Option<&Overlay> {
+    let state = &framework_state.message_ui.mf_mail_compose_view_controller;
+    if state.composer.is_none() {
+        None
+    } else {
+        state.overlay.as_ref()
+    }
+}
+
+fn set_pressed(env: &mut Environment, pressed: Option<Button>) {
+    let state = State::get(&mut env.framework_state);
+    if state.pressed != pressed {
+        state.pressed = pressed;
+        state.overlay = None;
+    }
+}
+
+/// For use by [crate::frameworks::uikit::handle_events]: handle a touch event
+/// if a composer is presented. Returns [true] if the event was consumed.
+pub fn handle_event(env: &mut Environment, event: &Event) -> bool {
+    let state = State::get(&mut env.framework_state);
+    let Some(composer) = state.composer else {
+        return false;
+    };
+    let tracked_touch = state.touch;
+
+    match *event {
+        Event::TouchDown(source, location) => {
+            if tracked_touch.is_some() {
+                return true;
+            }
+            let button = button_at(env, location);
+            State::get(&mut env.framework_state).touch = Some((source, button));
+            set_pressed(env, button);
+            true
+        }
+        Event::TouchMove(source, location) => {
+            let Some((tracked_source, button)) = tracked_touch else {
+                // A touch that began before the sheet appeared still belongs
+                // to the app.
+                return false;
+            };
+            if tracked_source == source {
+                let pressed = button.filter(|&button| button_at(env, location) == Some(button));
+                set_pressed(env, pressed);
+            }
+            true
+        }
+        Event::TouchUp(source, location) => {
+            let button = match tracked_touch {
+                Some((tracked_source, button)) if tracked_source == source => button,
+                Some(_) => return true,
+                None => return false,
+            };
+            State::get(&mut env.framework_state).touch = None;
+            set_pressed(env, None);
+            if let Some(button) = button.filter(|&button| button_at(env, location) == Some(button))
+            {
+                // UIKit creates and drains autorelease pools when handling
+                // events.
+                let pool: id = msg_class![env; NSAutoreleasePool new];
+                finish(env, composer, button);
+                release(env, pool);
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Hide the sheet and tell the delegate what happened to the message.
+fn finish(env: &mut Environment, composer: id, button: Button) {
+    let state = State::get(&mut env.framework_state);
+    state.composer = None;
+    state.overlay = None;
+
+    let (result, error) = match button {
+        Button::Cancel => {
+            log_dbg!("Mail composer {:?} cancelled", composer);
+            (MFMailComposeResultCancelled, nil)
+        }
+        Button::Send => match send(env, composer) {
+            Ok(()) => (MFMailComposeResultSent, nil),
+            Err(e) => {
+                log!("Warning: Couldn't send message from {:?}: {}", composer, e);
+                let error = new_error(
+                    env,
+                    MFMailComposeErrorDomain,
+                    MFMailComposeErrorCodeSendFailed,
+                );
+                (MFMailComposeResultFailed, error)
+            }
+        },
+    };
+
+    // The delegate is likely to dismiss the composer.
+    retain(env, composer);
+    let delegate = composer_state(env, composer).delegate;
+    let responds = match env
+        .objc
+        .lookup_selector("mailComposeController:didFinishWithResult:error:")
+    {
+        Some(selector) if delegate != nil => msg![env; delegate respondsToSelector:selector],
+        _ => false,
+    };
+    if responds {
+        () = msg![env; delegate mailComposeController:composer
+                                 didFinishWithResult:result
+                                               error:error];
+    } else {
+        // The real composer would stay up, with no way to get rid of it.
+        log!(
+            "Warning: Delegate of mail composer {:?} doesn't handle the result, dismissing it",
+            composer
+        );
+        () = msg![env; composer dismissModalViewControllerAnimated:true];
+    }
+    release(env, composer);
+}
+
+fn send(env: &mut Environment, composer: id) -> Result<(), String> {
+    let message = composer_state(env, composer).message.clone();
+    let unix_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0);
+    let mut delivered = false;
+    if let Some(dir) = &env.options.mail_dir {
+        let boundary = format!("touchHLE-{}", unix_time);
+        let eml = build_eml(&message, &format_date(unix_time), &boundary);
+        let path = save_eml(dir, &eml, unix_time)
+            .map_err(|e| format!("couldn't save it in {:?} ({})", dir, e))?;
+        log!("Saved message from {:?} as {:?}", composer, path);
+        delivered = true;
+    }
+    if env.options.open_mail_client {
+        if !message.attachments.is_empty() {
+            log!(
+                "Warning: The host's mail client can't be given the attachments of the message from {:?}",
+                composer
+            );
+        }
+        crate::window::open_url(&mailto_url(&message));
+        delivered = true;
+    }
+    if !delivered {
+        log!("App sent a message from {:?}, but it went nowhere. Use the --mail-dir= or --open-mail-client options to keep messages like this.", composer);
+    }
+    Ok(())
+}
+
+fn save_eml(dir: &Path, eml: &str, unix_time: i64) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let mut path = dir.join(format!("{}.eml", unix_time));
+    let mut suffix = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.eml", unix_time, suffix));
+        suffix += 1;
+    }
+    std::fs::write(&path, eml)?;
+    Ok(path)
+}
+
+/// Crudely turn an HTML message body into plain text: tags are removed,
+/// whitespace is collapsed, line breaks and block elements become newlines,
+/// and the most common entities are decoded.
+fn html_to_text(html: &str) -> String {
+    fn push_collapsed(text: &mut String, segment: &str) {
+        for c in segment.chars() {
+            if !c.is_whitespace() {
+                text.push(c);
+            } else if !(text.is_empty() || text.ends_with(' ') || text.ends_with('\n')) {
+                text.push(' ');
+            }
+        }
+    }
+
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        push_collapsed(&mut text, &rest[..start]);
+        let tag = rest[start + 1..start + end].to_ascii_lowercase();
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("");
+        let is_block = matches!(name, "p" | "div" | "li" | "tr" | "h1" | "h2" | "h3");
+        if name == "br" || (is_block && !text.is_empty() && !text.ends_with('\n')) {
+            while text.ends_with(' ') {
+                text.pop();
+            }
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    push_collapsed(&mut text, rest);
+    text.trim_end()
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (u32::from(chunk[0]) << 16)
+            | (u32::from(*chunk.get(1).unwrap_or(&0)) << 8)
+            | u32::from(*chunk.get(2).unwrap_or(&0));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Encode a header value so that it's plain ASCII (RFC 2047).
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", base64_encode(value.as_bytes()))
+    }
+}
+
+/// Format a date for a message's `Date:` header (RFC 5322), in UTC.
+fn format_date(unix_time: i64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let days = unix_time.div_euclid(24 * 60 * 60);
+    let seconds = unix_time.rem_euclid(24 * 60 * 60);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {} {} {} {:02}:{:02}:{:02} +0000",
+        // 1970-01-01 was a Thursday.
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Write a message out in the Internet Message Format, i.e. the contents of an
+/// `.eml` file. It's marked as unsent, so that mail clients open it ready to
+/// be sent rather than as a received message.
+fn build_eml(message: &Message, date: &str, boundary: &str) -> String {
+    let mut eml = String::new();
+    for (name, recipients) in [
+        ("To", &message.to),
+        ("Cc", &message.cc),
+        ("Bcc", &message.bcc),
+    ] {
+        if !recipients.is_empty() {
+            eml.push_str(&format!("{}: {}\r\n", name, recipients.join(", ")));
+        }
+    }
+    eml.push_str(&format!("Subject: {}\r\n", encode_header(&message.subject)));
+    eml.push_str(&format!("Date: {}\r\n", date));
+    eml.push_str("MIME-Version: 1.0\r\n");
+    eml.push_str("X-Unsent: 1\r\n");
+
+    let text_headers = format!(
+        "Content-Type: text/{}; charset=UTF-8\r\nContent-Transfer-Encoding: 8bit\r\n",
+        if message.is_html { "html" } else { "plain" }
+    );
+    let body = message.body.replace("\r\n", "\n").replace('\n', "\r\n");
+    if message.attachments.is_empty() {
+        eml.push_str(&text_headers);
+        eml.push_str("\r\n");
+        eml.push_str(&body);
+        eml.push_str("\r\n");
+        return eml;
+    }
+
+    eml.push_str(&format!(
+        "Content-Type: multipart/mixed; boundary=\"{}\"\r\n\r\n",
+        boundary
+    ));
+    eml.push_str(&format!(
+        "--{}\r\n{}\r\n{}\r\n",
+        boundary, text_headers, body
+    ));
+    for attachment in &message.attachments {
+        let file_name = encode_header(&attachment.file_name);
+        eml.push_str(&format!(
+            "--{}\r\nContent-Type: {}; name=\"{}\"\r\n\
+             Content-Disposition: attachment; filename=\"{}\"\r\n\
+             Content-Transfer-Encoding: base64\r\n\r\n",
+            boundary, attachment.mime_type, file_name, file_name
+        ));
+        let encoded = base64_encode(&attachment.data);
+        for line in encoded.as_bytes().chunks(76) {
+            eml.push_str(std::str::from_utf8(line).unwrap());
+            eml.push_str("\r\n");
+        }
+    }
+    eml.push_str(&format!("--{}--\r\n", boundary));
+    eml
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for &byte in text.as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~@".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Make a `mailto:` URL for a message (RFC 6068). Attachments can't be
+/// included.
+fn mailto_url(message: &Message) -> String {
+    let to: Vec<String> = message.to.iter().map(|to| percent_encode(to)).collect();
+    let mut url = format!("mailto:{}", to.join(","));
+    let body = if message.is_html {
+        html_to_text(&message.body)
+    } else {
+        message.body.clone()
+    };
+    let fields = [
+        ("cc", message.cc.join(",")),
+        ("bcc", message.bcc.join(",")),
+        ("subject", message.subject.clone()),
+        ("body", body.replace("\r\n", "\n").replace('\n', "\r\n")),
+    ];
+    let mut separator = '?';
+    for (name, value) in fields {
+        if !value.is_empty() {
+            url.push_str(&format!("{}{}={}", separator, name, percent_encode(&value)));
+            separator = '&';
+        }
+    }
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html() {
+        assert_eq!(
+            html_to_text("<p>Hello&nbsp;<b>world</b></p>\n<p>Line<br>two &amp; three</p>"),
+            "Hello world\nLine\ntwo & three"
+        );
+        assert_eq!(html_to_text("plain  text"), "plain text");
+        assert_eq!(html_to_text("1 < 2"), "1 < 2");
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"Hi"), "SGk=");
+        assert_eq!(base64_encode(b"Hi!"), "SGkh");
+        assert_eq!(base64_encode("Grüße".as_bytes()), "R3LDvMOfZQ==");
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(format_date(0), "Thu, 1 Jan 1970 00:00:00 +0000");
+        assert_eq!(format_date(951782400), "Tue, 29 Feb 2000 00:00:00 +0000");
+        assert_eq!(format_date(1792238400), "Sat, 17 Oct 2026 12:00:00 +0000");
+    }
+
+    fn feedback() -> Message {
+        Message {
+            to: vec!["support@example.com".to_string()],
+            subject: "Feedback".to_string(),
+            body: "Great game!\nThanks".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn eml() {
+        let date = "Thu, 1 Jan 1970 00:00:00 +0000";
+        assert_eq!(
+            build_eml(&feedback(), date, "b"),
+            "To: support@example.com\r\n\
+             Subject: Feedback\r\n\
+             Date: Thu, 1 Jan 1970 00:00:00 +0000\r\n\
+             MIME-Version: 1.0\r\n\
+             X-Unsent: 1\r\n\
+             Content-Type: text/plain; charset=UTF-8\r\n\
+             Content-Transfer-Encoding: 8bit\r\n\
+             \r\n\
+             Great game!\r\nThanks\r\n"
+        );
+
+        let mut message = feedback();
+        message.subject = "Grüße".to_string();
+        message.attachments.push(Attachment {
+            mime_type: "text/plain".to_string(),
+            file_name: "log.txt".to_string(),
+            data: b"Hi!".to_vec(),
+        });
+        let eml = build_eml(&message, date, "b");
+        assert!(eml.contains("Subject: =?UTF-8?B?R3LDvMOfZQ==?=\r\n"));
+        assert!(eml.contains("Content-Type: multipart/mixed; boundary=\"b\"\r\n\r\n--b\r\n"));
+        assert!(eml.contains("Content-Disposition: attachment; filename=\"log.txt\"\r\n"));
+        assert!(eml.ends_with("\r\n\r\nSGkh\r\n--b--\r\n"));
+    }
+
+    #[test]
+    fn mailto() {
+        assert_eq!(
+            mailto_url(&feedback()),
+            "mailto:support@example.com?subject=Feedback&body=Great%20game%21%0D%0AThanks"
+        );
+        assert_eq!(mailto_url(&Message::default()), "mailto:");
+    }
+}
@@ -11,24 +11,52 @@
 //! - [OpenAL 1.1 specification](https://www.openal.org/documentation/openal-1.1-specification.pdf)
 //! - Apple's [Technical Note TN2199: OpenAL FAQ for iPhone OS](https://web.archive.org/web/20090826202158/http://developer.apple.com/iPhone/library/technotes/tn2008/tn2199.html) (also available [here](https://developer.apple.com/library/archive/technotes/tn2199/_index.html))
 
+use crate::audio::mixer;
 use crate::audio::openal as al;
 use crate::audio::openal::al_types::*;
 use crate::audio::openal::alc_types::*;
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, Ptr, SafeWrite};
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr, SafeWrite};
 use crate::Environment;
 use std::collections::HashMap;
+use std::ffi::CStr;
 
 #[derive(Default)]
 pub struct State {
     devices: HashMap<MutPtr<GuestALCdevice>, *mut ALCdevice>,
     contexts: HashMap<MutPtr<GuestALCcontext>, *mut ALCcontext>,
+    /// The listener gain the app has set for each context, which is scaled by
+    /// the volume (see `--volume=`) before being given to OpenAL Soft.
+    listener_gains: HashMap<*mut ALCcontext, ALfloat>,
+    /// Strings returned by `alcGetString` and `alGetString`, which the app
+    /// doesn't own, so they're allocated once and kept forever.
+    strings: HashMap<StringKey, ConstPtr<u8>>,
 }
 impl State {
     fn get(env: &mut Environment) -> &mut Self {
         &mut env.framework_state.openal
     }
 }
+impl Drop for State {
+    fn drop(&mut self) {
+        // Whatever the app didn't destroy or close itself before it exited.
+        unsafe {
+            al::alcMakeContextCurrent(std::ptr::null_mut());
+            for &host_context in self.contexts.values() {
+                al::alcDestroyContext(host_context);
+            }
+            for &host_device in self.devices.values() {
+                al::alcCloseDevice(host_device);
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum StringKey {
+    Al(ALenum),
+    Alc(MutPtr<GuestALCdevice>, ALCenum),
+}
 
 /// Opaque type in guest memory standing in for [ALCdevice] in host memory.
 struct GuestALCdevice {
@@ -41,6 +69,82 @@ struct GuestALCcontext {
 }
 impl SafeWrite for GuestALCcontext {}
 
+/// The `AL_EXTENSIONS` string. OpenAL Soft supports many more, but apps can't
+/// use extensions with functions that aren't implemented here.
+const AL_EXTENSIONS: &str =
+    "AL_EXT_EXPONENT_DISTANCE AL_EXT_FLOAT32 AL_EXT_LINEAR_DISTANCE AL_EXT_OFFSET AL_EXT_STATIC_BUFFER";
+/// The `ALC_EXTENSIONS` string. Capture is "supported", but there are never
+/// any capture devices.
+const ALC_EXTENSIONS: &str = "ALC_ENUMERATION_EXT ALC_EXT_CAPTURE";
+
+/// Number of values for a parameter that is set or queried with a vector
+/// function like `alSourcefv`.
+fn param_value_count(param: ALenum) -> GuestUSize {
+    match param {
+        al::AL_POSITION | al::AL_VELOCITY | al::AL_DIRECTION => 3,
+        // Listener only: "at" and "up" vectors
+        al::AL_ORIENTATION => 6,
+        _ => 1,
+    }
+}
+
+/// Get a host pointer to an array of source or buffer names. The app may pass
+/// NULL if there are none, which OpenAL Soft accepts.
+fn names_ptr(env: &mut Environment, n: ALsizei, names: ConstPtr<ALuint>) -> *const ALuint {
+    let n: GuestUSize = n.try_into().unwrap();
+    if n == 0 {
+        std::ptr::null()
+    } else {
+        env.mem.ptr_at(names, n)
+    }
+}
+fn names_ptr_mut(env: &mut Environment, n: ALsizei, names: MutPtr<ALuint>) -> *mut ALuint {
+    let n: GuestUSize = n.try_into().unwrap();
+    if n == 0 {
+        std::ptr::null_mut()
+    } else {
+        env.mem.ptr_at_mut(names, n)
+    }
+}
+
+/// Get a string in guest memory for `alcGetString` or `alGetString`. The
+/// string is created with `make_string` the first time, and then reused.
+fn cached_string(
+    env: &mut Environment,
+    key: StringKey,
+    make_string: impl FnOnce() -> Option<Vec<u8>>,
+) -> ConstPtr<u8> {
+    if let Some(&string) = State::get(env).strings.get(&key) {
+        return string;
+    }
+    let Some(bytes) = make_string() else {
+        return Ptr::null();
+    };
+    let string = env.mem.alloc_and_write_cstr(&bytes).cast_const();
+    State::get(env).strings.insert(key, string);
+    string
+}
+
+/// Copy a string from OpenAL Soft. If `is_list` is set, it's a list of strings
+/// ending with an empty string, and the NUL separators are included.
+fn host_string_bytes(host_string: *const ALchar, is_list: bool) -> Option<Vec<u8>> {
+    if host_string.is_null() {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    let mut host_string = host_string;
+    loop {
+        let string = unsafe { CStr::from_ptr(host_string) }.to_bytes();
+        bytes.extend_from_slice(string);
+        if !is_list || string.is_empty() {
+            break;
+        }
+        bytes.push(b'\0');
+        host_string = unsafe { host_string.add(string.len() + 1) };
+    }
+    Some(bytes)
+}
+
 // === alc.h ===
 
 fn alcOpenDevice(env: &mut Environment, devicename: ConstPtr<u8>) -> MutPtr<GuestALCdevice> {
@@ -69,33 +173,75 @@ fn alcCloseDevice(env: &mut Environment, device: MutPtr<GuestALCdevice>) -> bool
 }
 
 fn alcGetError(env: &mut Environment, device: MutPtr<GuestALCdevice>) -> i32 {
-    let &host_device = State::get(env).devices.get(&device).unwrap();
+    // NULL is allowed here, for errors that aren't specific to a device.
+    let host_device = host_device_or_null(env, device);
 
     let res = unsafe { al::alcGetError(host_device) };
     log_dbg!("alcGetError({:?}) => {:#x}", host_device, res);
     res
 }
 
+fn host_device_or_null(env: &mut Environment, device: MutPtr<GuestALCdevice>) -> *mut ALCdevice {
+    if device.is_null() {
+        std::ptr::null_mut()
+    } else {
+        State::get(env).devices.get(&device).copied().unwrap()
+    }
+}
+
 fn alcCreateContext(
     env: &mut Environment,
     device: MutPtr<GuestALCdevice>,
     attrlist: ConstPtr<i32>,
 ) -> MutPtr<GuestALCcontext> {
-    assert!(attrlist.is_null()); // unimplemented
+    // The attribute list is pairs of keys and values, ending with a 0 key.
+    let attrs = if attrlist.is_null() {
+        None
+    } else {
+        let mut attrs = Vec::new();
+        loop {
+            let key = env.mem.read(attrlist + attrs.len().try_into().unwrap());
+            attrs.push(key);
+            if key == 0 {
+                break;
+            }
+            let value = env.mem.read(attrlist + attrs.len().try_into().unwrap());
+            attrs.push(value);
+        }
+        Some(attrs)
+    };
+    log_dbg!("alcCreateContext() attributes: {:?}", attrs);
 
     let &host_device = State::get(env).devices.get(&device).unwrap();
 
-    let res = unsafe { al::alcCreateContext(host_device, std::ptr::null()) };
+    // The mixer settings from the command line take precedence over the app's.
+    let mut host_attrs = mixer::context_attributes(host_device, &env.options);
+    // The terminating 0 is left out by chunks_exact().
+    for pair in attrs.as_deref().unwrap_or(&[]).chunks_exact(2) {
+        if !host_attrs
+            .chunks_exact(2)
+            .any(|host_pair| host_pair[0] == pair[0])
+        {
+            host_attrs.extend_from_slice(pair);
+        }
+    }
+    host_attrs.push(0);
+    let res = unsafe { al::alcCreateContext(host_device, host_attrs.as_ptr()) };
     if res.is_null() {
-        log_dbg!("alcCreateContext({:?}, NULL) returned NULL", device);
+        log_dbg!(
+            "alcCreateContext({:?}, {:?}) returned NULL",
+            device,
+            attrlist
+        );
         return Ptr::null();
     }
 
     let guest_res = env.mem.alloc_and_write(GuestALCcontext { _filler: 0 });
     State::get(env).contexts.insert(guest_res, res);
     log_dbg!(
-        "alcCreateContext({:?}, NULL) => {:?} (host: {:?})",
+        "alcCreateContext({:?}, {:?}) => {:?} (host: {:?})",
         device,
+        attrlist,
         guest_res,
         res,
     );
@@ -103,6 +249,7 @@ fn alcCreateContext(
 }
 fn alcDestroyContext(env: &mut Environment, context: MutPtr<GuestALCcontext>) {
     let host_context = State::get(env).contexts.remove(&context).unwrap();
+    State::get(env).listener_gains.remove(&host_context);
     env.mem.free(context.cast());
     unsafe { al::alcDestroyContext(host_context) };
     log_dbg!("alcDestroyContext({:?})", context);
@@ -116,9 +263,171 @@ fn alcMakeContextCurrent(env: &mut Environment, context: MutPtr<GuestALCcontext>
     };
     let res = unsafe { al::alcMakeContextCurrent(host_context) };
     log_dbg!("alcMakeContextCurrent({:?}) => {:?}", context, res);
+    if res != al::ALC_FALSE && !host_context.is_null() {
+        apply_listener_gain(env, host_context);
+    }
     res != al::ALC_FALSE
 }
 
+/// Give OpenAL Soft the listener gain for a context, which must be current,
+/// scaled by the volume.
+fn apply_listener_gain(env: &mut Environment, host_context: *mut ALCcontext) {
+    let gain = State::get(env)
+        .listener_gains
+        .get(&host_context)
+        .copied()
+        .unwrap_or(1.0);
+    unsafe { al::alListenerf(al::AL_GAIN, gain * env.options.volume) };
+}
+
+/// Apply a change of the volume (see `--volume=`) to the app's contexts.
+pub fn set_volume(env: &mut Environment) {
+    let old_context = unsafe { al::alcGetCurrentContext() };
+    let host_contexts: Vec<_> = State::get(env).contexts.values().copied().collect();
+    for host_context in host_contexts {
+        unsafe { al::alcMakeContextCurrent(host_context) };
+        apply_listener_gain(env, host_context);
+    }
+    unsafe { al::alcMakeContextCurrent(old_context) };
+}
+
+fn alcProcessContext(env: &mut Environment, context: MutPtr<GuestALCcontext>) {
+    let &host_context = State::get(env).contexts.get(&context).unwrap();
+    unsafe { al::alcProcessContext(host_context) };
+}
+fn alcSuspendContext(env: &mut Environment, context: MutPtr<GuestALCcontext>) {
+    let &host_context = State::get(env).contexts.get(&context).unwrap();
+    unsafe { al::alcSuspendContext(host_context) };
+}
+
+fn alcGetCurrentContext(env: &mut Environment) -> MutPtr<GuestALCcontext> {
+    let host_context = unsafe { al::alcGetCurrentContext() };
+    // The internal context used by touchHLE itself is only current while
+    // touchHLE is using it, so it can't be found here.
+    State::get(env)
+        .contexts
+        .iter()
+        .find(|&(_, &other)| other == host_context)
+        .map_or(Ptr::null(), |(&guest_context, _)| guest_context)
+}
+fn alcGetContextsDevice(
+    env: &mut Environment,
+    context: MutPtr<GuestALCcontext>,
+) -> MutPtr<GuestALCdevice> {
+    let &host_context = State::get(env).contexts.get(&context).unwrap();
+    let host_device = unsafe { al::alcGetContextsDevice(host_context) };
+    State::get(env)
+        .devices
+        .iter()
+        .find(|&(_, &other)| other == host_device)
+        .map_or(Ptr::null(), |(&guest_device, _)| guest_device)
+}
+
+fn alcGetString(
+    env: &mut Environment,
+    device: MutPtr<GuestALCdevice>,
+    param: ALCenum,
+) -> ConstPtr<u8> {
+    let host_device = host_device_or_null(env, device);
+    let res = match param {
+        al::ALC_EXTENSIONS => cached_string(env, StringKey::Alc(device, param), || {
+            Some(ALC_EXTENSIONS.as_bytes().to_vec())
+        }),
+        // There is no microphone, so there are no capture devices. The list
+        // and the name of the default device are both empty.
+        al::ALC_CAPTURE_DEVICE_SPECIFIER | al::ALC_CAPTURE_DEFAULT_DEVICE_SPECIFIER => {
+            cached_string(env, StringKey::Alc(device, param), || Some(b"\0".to_vec()))
+        }
+        // With no device, this is a list of all devices.
+        al::ALC_DEVICE_SPECIFIER if device.is_null() => {
+            cached_string(env, StringKey::Alc(device, param), || {
+                host_string_bytes(unsafe { al::alcGetString(host_device, param) }, true)
+            })
+        }
+        _ => cached_string(env, StringKey::Alc(device, param), || {
+            host_string_bytes(unsafe { al::alcGetString(host_device, param) }, false)
+        }),
+    };
+    log_dbg!("alcGetString({:?}, {:#x}) => {:?}", device, param, res);
+    res
+}
+
+fn alcGetIntegerv(
+    env: &mut Environment,
+    device: MutPtr<GuestALCdevice>,
+    param: ALCenum,
+    size: ALCsizei,
+    values: MutPtr<ALCint>,
+) {
+    let host_device = host_device_or_null(env, device);
+    let size_usize: GuestUSize = size.try_into().unwrap();
+    if size_usize == 0 {
+        // OpenAL Soft will report the error.
+        unsafe { al::alcGetIntegerv(host_device, param, size, std::ptr::null_mut()) };
+        return;
+    }
+    let values = env.mem.ptr_at_mut(values, size_usize);
+    unsafe { al::alcGetIntegerv(host_device, param, size, values) };
+}
+
+fn alcIsExtensionPresent(
+    env: &mut Environment,
+    _device: MutPtr<GuestALCdevice>,
+    extname: ConstPtr<u8>,
+) -> bool {
+    let extname = env.mem.cstr_at_utf8(extname);
+    ALC_EXTENSIONS
+        .split(' ')
+        .any(|ext| ext.eq_ignore_ascii_case(extname))
+}
+
+// ALC_EXT_CAPTURE. touchHLE doesn't support recording, so opening a capture
+// device always fails, and the other functions should never be called.
+
+fn alcCaptureOpenDevice(
+    env: &mut Environment,
+    devicename: ConstPtr<u8>,
+    frequency: ALCuint,
+    format: ALCenum,
+    buffersize: ALCsizei,
+) -> MutPtr<GuestALCdevice> {
+    let devicename = if devicename.is_null() {
+        None
+    } else {
+        Some(env.mem.cstr_at_utf8(devicename))
+    };
+    log!(
+        "Warning: alcCaptureOpenDevice({:?}, {}, {:#x}, {}) failed because recording isn't supported",
+        devicename,
+        frequency,
+        format,
+        buffersize
+    );
+    Ptr::null()
+}
+fn alcCaptureCloseDevice(_env: &mut Environment, device: MutPtr<GuestALCdevice>) -> bool {
+    log!("Warning: alcCaptureCloseDevice({:?}) ignored", device);
+    false
+}
+fn alcCaptureStart(_env: &mut Environment, device: MutPtr<GuestALCdevice>) {
+    log!("Warning: alcCaptureStart({:?}) ignored", device);
+}
+fn alcCaptureStop(_env: &mut Environment, device: MutPtr<GuestALCdevice>) {
+    log!("Warning: alcCaptureStop({:?}) ignored", device);
+}
+fn alcCaptureSamples(
+    _env: &mut Environment,
+    device: MutPtr<GuestALCdevice>,
+    _buffer: MutVoidPtr,
+    samples: ALCsizei,
+) {
+    log!(
+        "Warning: alcCaptureSamples({:?}, _, {}) ignored",
+        device,
+        samples
+    );
+}
+
 fn alcGetProcAddress(
     env: &mut Environment,
     _device: ConstPtr<GuestALCdevice>,
@@ -137,9 +446,9 @@ fn alcGetProcAddress(
     let mangled_func_name = format!("_{}", env.mem.cstr_at_utf8(func_name));
     assert!(mangled_func_name.starts_with("_al"));
 
-    if let Ok(ptr) = env
-        .dyld
-        .create_proc_address(&mut env.mem, &mut env.cpu, &mangled_func_name)
+    if let Ok(ptr) =
+        env.dyld
+            .create_proc_address(&mut env.mem, &mut env.cpu, &env.plugins, &mangled_func_name)
     {
         Ptr::from_bits(ptr.addr_with_thumb_bit())
     } else {
@@ -170,26 +479,253 @@ fn alGetError(_env: &mut Environment) -> i32 {
     res
 }
 
+fn alGetString(env: &mut Environment, param: ALenum) -> ConstPtr<u8> {
+    let res = cached_string(env, StringKey::Al(param), || {
+        if param == al::AL_EXTENSIONS {
+            Some(AL_EXTENSIONS.as_bytes().to_vec())
+        } else {
+            host_string_bytes(unsafe { al::alGetString(param) }, false)
+        }
+    });
+    log_dbg!("alGetString({:#x}) => {:?}", param, res);
+    res
+}
+fn alIsExtensionPresent(env: &mut Environment, extname: ConstPtr<u8>) -> bool {
+    let extname = env.mem.cstr_at_utf8(extname);
+    AL_EXTENSIONS
+        .split(' ')
+        .any(|ext| ext.eq_ignore_ascii_case(extname))
+}
+
+fn alGetBooleanv(env: &mut Environment, param: ALenum, values: MutPtr<ALboolean>) {
+    unsafe { al::alGetBooleanv(param, env.mem.ptr_at_mut(values, 1)) };
+}
+fn alGetIntegerv(env: &mut Environment, param: ALenum, values: MutPtr<ALint>) {
+    unsafe { al::alGetIntegerv(param, env.mem.ptr_at_mut(values, 1)) };
+}
+fn alGetFloatv(env: &mut Environment, param: ALenum, values: MutPtr<ALfloat>) {
+    unsafe { al::alGetFloatv(param, env.mem.ptr_at_mut(values, 1)) };
+}
+fn alGetDoublev(env: &mut Environment, param: ALenum, values: MutPtr<ALdouble>) {
+    unsafe { al::alGetDoublev(param, env.mem.ptr_at_mut(values, 1)) };
+}
+fn alGetBoolean(_env: &mut Environment, param: ALenum) -> bool {
+    let mut value = al::AL_FALSE;
+    unsafe { al::alGetBooleanv(param, &mut value) };
+    value != al::AL_FALSE
+}
+fn alGetInteger(_env: &mut Environment, param: ALenum) -> ALint {
+    let mut value = 0;
+    unsafe { al::alGetIntegerv(param, &mut value) };
+    value
+}
+fn alGetFloat(_env: &mut Environment, param: ALenum) -> ALfloat {
+    let mut value = 0.0;
+    unsafe { al::alGetFloatv(param, &mut value) };
+    value
+}
+fn alGetDouble(_env: &mut Environment, param: ALenum) -> ALdouble {
+    let mut value = 0.0;
+    unsafe { al::alGetDoublev(param, &mut value) };
+    value
+}
+
+fn alDopplerFactor(_env: &mut Environment, value: ALfloat) {
+    unsafe { al::alDopplerFactor(value) };
+}
+fn alDopplerVelocity(_env: &mut Environment, value: ALfloat) {
+    unsafe { al::alDopplerVelocity(value) };
+}
+fn alSpeedOfSound(_env: &mut Environment, value: ALfloat) {
+    unsafe { al::alSpeedOfSound(value) };
+}
+fn alDistanceModel(_env: &mut Environment, value: ALenum) {
+    unsafe { al::alDistanceModel(value) };
+}
+
+fn alListenerf(env: &mut Environment, param: ALenum, value: ALfloat) {
+    let host_context = unsafe { al::alcGetCurrentContext() };
+    // Invalid gains are passed through, so that OpenAL Soft reports the error.
+    if param == al::AL_GAIN && value >= 0.0 && !host_context.is_null() {
+        State::get(env).listener_gains.insert(host_context, value);
+        apply_listener_gain(env, host_context);
+    } else {
+        unsafe { al::alListenerf(param, value) };
+    }
+}
+fn alListener3f(
+    _env: &mut Environment,
+    param: ALenum,
+    value1: ALfloat,
+    value2: ALfloat,
+    value3: ALfloat,
+) {
+    // Padded in case the parameter actually has more values.
+    let values = [value1, value2, value3, 0.0, 0.0, 0.0];
+    unsafe { al::alListenerfv(param, values.as_ptr()) };
+}
+fn alListenerfv(env: &mut Environment, param: ALenum, values: ConstPtr<ALfloat>) {
+    if param == al::AL_GAIN {
+        let value = env.mem.read(values);
+        return alListenerf(env, param, value);
+    }
+    let count = param_value_count(param);
+    unsafe { al::alListenerfv(param, env.mem.ptr_at(values, count)) };
+}
+fn alListeneri(_env: &mut Environment, param: ALenum, value: ALint) {
+    unsafe { al::alListeneri(param, value) };
+}
+fn alListener3i(
+    _env: &mut Environment,
+    param: ALenum,
+    value1: ALint,
+    value2: ALint,
+    value3: ALint,
+) {
+    // Padded in case the parameter actually has more values.
+    let values = [value1, value2, value3, 0, 0, 0];
+    unsafe { al::alListeneriv(param, values.as_ptr()) };
+}
+fn alListeneriv(env: &mut Environment, param: ALenum, values: ConstPtr<ALint>) {
+    let count = param_value_count(param);
+    unsafe { al::alListeneriv(param, env.mem.ptr_at(values, count)) };
+}
+fn alGetListenerf(env: &mut Environment, param: ALenum, value: MutPtr<ALfloat>) {
+    let host_context = unsafe { al::alcGetCurrentContext() };
+    if param == al::AL_GAIN && !host_context.is_null() {
+        let gain = State::get(env)
+            .listener_gains
+            .get(&host_context)
+            .copied()
+            .unwrap_or(1.0);
+        env.mem.write(value, gain);
+        return;
+    }
+    unsafe { al::alGetListenerf(param, env.mem.ptr_at_mut(value, 1)) };
+}
+fn alGetListener3f(
+    env: &mut Environment,
+    param: ALenum,
+    value1: MutPtr<ALfloat>,
+    value2: MutPtr<ALfloat>,
+    value3: MutPtr<ALfloat>,
+) {
+    let mut values = [0.0; 6];
+    unsafe { al::alGetListenerfv(param, values.as_mut_ptr()) };
+    env.mem.write(value1, values[0]);
+    env.mem.write(value2, values[1]);
+    env.mem.write(value3, values[2]);
+}
+fn alGetListenerfv(env: &mut Environment, param: ALenum, values: MutPtr<ALfloat>) {
+    if param == al::AL_GAIN {
+        return alGetListenerf(env, param, values);
+    }
+    let count = param_value_count(param);
+    unsafe { al::alGetListenerfv(param, env.mem.ptr_at_mut(values, count)) };
+}
+fn alGetListeneri(env: &mut Environment, param: ALenum, value: MutPtr<ALint>) {
+    unsafe { al::alGetListeneri(param, env.mem.ptr_at_mut(value, 1)) };
+}
+fn alGetListener3i(
+    env: &mut Environment,
+    param: ALenum,
+    value1: MutPtr<ALint>,
+    value2: MutPtr<ALint>,
+    value3: MutPtr<ALint>,
+) {
+    let mut values = [0; 6];
+    unsafe { al::alGetListeneriv(param, values.as_mut_ptr()) };
+    env.mem.write(value1, values[0]);
+    env.mem.write(value2, values[1]);
+    env.mem.write(value3, values[2]);
+}
+fn alGetListeneriv(env: &mut Environment, param: ALenum, values: MutPtr<ALint>) {
+    let count = param_value_count(param);
+    unsafe { al::alGetListeneriv(param, env.mem.ptr_at_mut(values, count)) };
+}
+
 fn alGenSources(env: &mut Environment, n: ALsizei, sources: MutPtr<ALuint>) {
-    let n_usize: GuestUSize = n.try_into().unwrap();
-    let sources = env.mem.ptr_at_mut(sources, n_usize);
-    unsafe { al::alGenSources(n, sources) };
+    let host_sources = names_ptr_mut(env, n, sources);
+    unsafe { al::alGenSources(n, host_sources) };
+    for i in 0..n.max(0).try_into().unwrap() {
+        let source = env.mem.read(sources + i);
+        mixer::configure_source(source, &env.options);
+    }
 }
 fn alDeleteSources(env: &mut Environment, n: ALsizei, sources: ConstPtr<ALuint>) {
-    let n_usize: GuestUSize = n.try_into().unwrap();
-    let sources = env.mem.ptr_at(sources, n_usize);
+    let sources = names_ptr(env, n, sources);
     unsafe { al::alDeleteSources(n, sources) };
 }
+fn alIsSource(_env: &mut Environment, source: ALuint) -> bool {
+    unsafe { al::alIsSource(source) != al::AL_FALSE }
+}
 
 fn alSourcef(_env: &mut Environment, source: ALuint, param: ALenum, value: ALfloat) {
     unsafe { al::alSourcef(source, param, value) };
 }
+fn alSource3f(
+    _env: &mut Environment,
+    source: ALuint,
+    param: ALenum,
+    value1: ALfloat,
+    value2: ALfloat,
+    value3: ALfloat,
+) {
+    // Padded in case the parameter actually has more values.
+    let values = [value1, value2, value3, 0.0, 0.0, 0.0];
+    unsafe { al::alSourcefv(source, param, values.as_ptr()) };
+}
+fn alSourcefv(env: &mut Environment, source: ALuint, param: ALenum, values: ConstPtr<ALfloat>) {
+    let count = param_value_count(param);
+    unsafe { al::alSourcefv(source, param, env.mem.ptr_at(values, count)) };
+}
 fn alSourcei(_env: &mut Environment, source: ALuint, param: ALenum, value: ALint) {
+    // OpenAL Soft only accepts AL_TRUE and AL_FALSE for AL_LOOPING, but some
+    // apps pass other non-zero values, which presumably work on iPhone OS.
+    let value = if param == al::AL_LOOPING && value != 0 {
+        al::AL_TRUE.into()
+    } else {
+        value
+    };
     unsafe { al::alSourcei(source, param, value) };
 }
+fn alSource3i(
+    _env: &mut Environment,
+    source: ALuint,
+    param: ALenum,
+    value1: ALint,
+    value2: ALint,
+    value3: ALint,
+) {
+    // Padded in case the parameter actually has more values.
+    let values = [value1, value2, value3, 0, 0, 0];
+    unsafe { al::alSourceiv(source, param, values.as_ptr()) };
+}
+fn alSourceiv(env: &mut Environment, source: ALuint, param: ALenum, values: ConstPtr<ALint>) {
+    let count = param_value_count(param);
+    unsafe { al::alSourceiv(source, param, env.mem.ptr_at(values, count)) };
+}
 fn alGetSourcef(env: &mut Environment, source: ALuint, param: ALenum, value: MutPtr<ALfloat>) {
     unsafe { al::alGetSourcef(source, param, env.mem.ptr_at_mut(value, 1)) };
 }
+fn alGetSource3f(
+    env: &mut Environment,
+    source: ALuint,
+    param: ALenum,
+    value1: MutPtr<ALfloat>,
+    value2: MutPtr<ALfloat>,
+    value3: MutPtr<ALfloat>,
+) {
+    let mut values = [0.0; 6];
+    unsafe { al::alGetSourcefv(source, param, values.as_mut_ptr()) };
+    env.mem.write(value1, values[0]);
+    env.mem.write(value2, values[1]);
+    env.mem.write(value3, values[2]);
+}
+fn alGetSourcefv(env: &mut Environment, source: ALuint, param: ALenum, values: MutPtr<ALfloat>) {
+    let count = param_value_count(param);
+    unsafe { al::alGetSourcefv(source, param, env.mem.ptr_at_mut(values, count)) };
+}
 fn alGetSourcei(env: &mut Environment, source: ALuint, param: ALenum, value: MutPtr<ALint>) {
     // Game-specific hack: Super Monkey Ball has some code like:
     //
@@ -216,12 +752,58 @@ fn alGetSourcei(env: &mut Environment, source: ALuint, param: ALenum, value: Mut
     }
 }
 
+fn alGetSource3i(
+    env: &mut Environment,
+    source: ALuint,
+    param: ALenum,
+    value1: MutPtr<ALint>,
+    value2: MutPtr<ALint>,
+    value3: MutPtr<ALint>,
+) {
+    let mut values = [0; 6];
+    unsafe { al::alGetSourceiv(source, param, values.as_mut_ptr()) };
+    env.mem.write(value1, values[0]);
+    env.mem.write(value2, values[1]);
+    env.mem.write(value3, values[2]);
+}
+fn alGetSourceiv(env: &mut Environment, source: ALuint, param: ALenum, values: MutPtr<ALint>) {
+    let count = param_value_count(param);
+    if count == 1 {
+        // Go through alGetSourcei for the Super Monkey Ball hack.
+        alGetSourcei(env, source, param, values);
+    } else {
+        unsafe { al::alGetSourceiv(source, param, env.mem.ptr_at_mut(values, count)) };
+    }
+}
+
 fn alSourcePlay(_env: &mut Environment, source: ALuint) {
     unsafe { al::alSourcePlay(source) };
 }
 fn alSourceStop(_env: &mut Environment, source: ALuint) {
     unsafe { al::alSourceStop(source) };
 }
+fn alSourcePause(_env: &mut Environment, source: ALuint) {
+    unsafe { al::alSourcePause(source) };
+}
+fn alSourceRewind(_env: &mut Environment, source: ALuint) {
+    unsafe { al::alSourceRewind(source) };
+}
+fn alSourcePlayv(env: &mut Environment, n: ALsizei, sources: ConstPtr<ALuint>) {
+    let sources = names_ptr(env, n, sources);
+    unsafe { al::alSourcePlayv(n, sources) };
+}
+fn alSourceStopv(env: &mut Environment, n: ALsizei, sources: ConstPtr<ALuint>) {
+    let sources = names_ptr(env, n, sources);
+    unsafe { al::alSourceStopv(n, sources) };
+}
+fn alSourcePausev(env: &mut Environment, n: ALsizei, sources: ConstPtr<ALuint>) {
+    let sources = names_ptr(env, n, sources);
+    unsafe { al::alSourcePausev(n, sources) };
+}
+fn alSourceRewindv(env: &mut Environment, n: ALsizei, sources: ConstPtr<ALuint>) {
+    let sources = names_ptr(env, n, sources);
+    unsafe { al::alSourceRewindv(n, sources) };
+}
 
 fn alSourceQueueBuffers(
     env: &mut Environment,
@@ -229,8 +811,7 @@ fn alSourceQueueBuffers(
     nb: ALsizei,
     buffers: ConstPtr<ALuint>,
 ) {
-    let nb_usize: GuestUSize = nb.try_into().unwrap();
-    let buffers = env.mem.ptr_at(buffers, nb_usize);
+    let buffers = names_ptr(env, nb, buffers);
     unsafe { al::alSourceQueueBuffers(source, nb, buffers) }
 }
 fn alSourceUnqueueBuffers(
@@ -239,21 +820,34 @@ fn alSourceUnqueueBuffers(
     nb: ALsizei,
     buffers: MutPtr<ALuint>,
 ) {
-    let nb_usize: GuestUSize = nb.try_into().unwrap();
-    let buffers = env.mem.ptr_at_mut(buffers, nb_usize);
+    let buffers = names_ptr_mut(env, nb, buffers);
     unsafe { al::alSourceUnqueueBuffers(source, nb, buffers) }
 }
 
 fn alGenBuffers(env: &mut Environment, n: ALsizei, buffers: MutPtr<ALuint>) {
-    let n_usize: GuestUSize = n.try_into().unwrap();
-    let buffers = env.mem.ptr_at_mut(buffers, n_usize);
+    let buffers = names_ptr_mut(env, n, buffers);
     unsafe { al::alGenBuffers(n, buffers) };
 }
 fn alDeleteBuffers(env: &mut Environment, n: ALsizei, buffers: ConstPtr<ALuint>) {
-    let n_usize: GuestUSize = n.try_into().unwrap();
-    let buffers = env.mem.ptr_at(buffers, n_usize);
+    let buffers = names_ptr(env, n, buffers);
     unsafe { al::alDeleteBuffers(n, buffers) };
 }
+fn alIsBuffer(_env: &mut Environment, buffer: ALuint) -> bool {
+    unsafe { al::alIsBuffer(buffer) != al::AL_FALSE }
+}
+
+fn alBufferf(_env: &mut Environment, buffer: ALuint, param: ALenum, value: ALfloat) {
+    unsafe { al::alBufferf(buffer, param, value) };
+}
+fn alBufferi(_env: &mut Environment, buffer: ALuint, param: ALenum, value: ALint) {
+    unsafe { al::alBufferi(buffer, param, value) };
+}
+fn alGetBufferf(env: &mut Environment, buffer: ALuint, param: ALenum, value: MutPtr<ALfloat>) {
+    unsafe { al::alGetBufferf(buffer, param, env.mem.ptr_at_mut(value, 1)) };
+}
+fn alGetBufferi(env: &mut Environment, buffer: ALuint, param: ALenum, value: MutPtr<ALint>) {
+    unsafe { al::alGetBufferi(buffer, param, env.mem.ptr_at_mut(value, 1)) };
+}
 
 fn alBufferData(
     env: &mut Environment,
@@ -300,20 +894,78 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(alcCreateContext(_, _)),
     export_c_func!(alcDestroyContext(_)),
     export_c_func!(alcMakeContextCurrent(_)),
+    export_c_func!(alcProcessContext(_)),
+    export_c_func!(alcSuspendContext(_)),
+    export_c_func!(alcGetCurrentContext()),
+    export_c_func!(alcGetContextsDevice(_)),
+    export_c_func!(alcGetString(_, _)),
+    export_c_func!(alcGetIntegerv(_, _, _, _)),
+    export_c_func!(alcIsExtensionPresent(_, _)),
+    export_c_func!(alcCaptureOpenDevice(_, _, _, _)),
+    export_c_func!(alcCaptureCloseDevice(_)),
+    export_c_func!(alcCaptureStart(_)),
+    export_c_func!(alcCaptureStop(_)),
+    export_c_func!(alcCaptureSamples(_, _, _)),
     export_c_func!(alcGetProcAddress(_, _)),
     export_c_func!(alGetError()),
+    export_c_func!(alGetString(_)),
+    export_c_func!(alIsExtensionPresent(_)),
+    export_c_func!(alGetBoolean(_)),
+    export_c_func!(alGetInteger(_)),
+    export_c_func!(alGetFloat(_)),
+    export_c_func!(alGetDouble(_)),
+    export_c_func!(alGetBooleanv(_, _)),
+    export_c_func!(alGetIntegerv(_, _)),
+    export_c_func!(alGetFloatv(_, _)),
+    export_c_func!(alGetDoublev(_, _)),
+    export_c_func!(alDopplerFactor(_)),
+    export_c_func!(alDopplerVelocity(_)),
+    export_c_func!(alSpeedOfSound(_)),
+    export_c_func!(alDistanceModel(_)),
+    export_c_func!(alListenerf(_, _)),
+    export_c_func!(alListener3f(_, _, _, _)),
+    export_c_func!(alListenerfv(_, _)),
+    export_c_func!(alListeneri(_, _)),
+    export_c_func!(alListener3i(_, _, _, _)),
+    export_c_func!(alListeneriv(_, _)),
+    export_c_func!(alGetListenerf(_, _)),
+    export_c_func!(alGetListener3f(_, _, _, _)),
+    export_c_func!(alGetListenerfv(_, _)),
+    export_c_func!(alGetListeneri(_, _)),
+    export_c_func!(alGetListener3i(_, _, _, _)),
+    export_c_func!(alGetListeneriv(_, _)),
     export_c_func!(alGenSources(_, _)),
     export_c_func!(alDeleteSources(_, _)),
+    export_c_func!(alIsSource(_)),
     export_c_func!(alGetSourcef(_, _, _)),
+    export_c_func!(alGetSource3f(_, _, _, _, _)),
+    export_c_func!(alGetSourcefv(_, _, _)),
     export_c_func!(alGetSourcei(_, _, _)),
+    export_c_func!(alGetSource3i(_, _, _, _, _)),
+    export_c_func!(alGetSourceiv(_, _, _)),
     export_c_func!(alSourcef(_, _, _)),
+    export_c_func!(alSource3f(_, _, _, _, _)),
+    export_c_func!(alSourcefv(_, _, _)),
     export_c_func!(alSourcei(_, _, _)),
+    export_c_func!(alSource3i(_, _, _, _, _)),
+    export_c_func!(alSourceiv(_, _, _)),
     export_c_func!(alSourcePlay(_)),
     export_c_func!(alSourceStop(_)),
+    export_c_func!(alSourcePause(_)),
+    export_c_func!(alSourceRewind(_)),
+    export_c_func!(alSourcePlayv(_, _)),
+    export_c_func!(alSourceStopv(_, _)),
+    export_c_func!(alSourcePausev(_, _)),
+    export_c_func!(alSourceRewindv(_, _)),
     export_c_func!(alSourceQueueBuffers(_, _, _)),
     export_c_func!(alSourceUnqueueBuffers(_, _, _)),
     export_c_func!(alGenBuffers(_, _)),
     export_c_func!(alDeleteBuffers(_, _)),
+    export_c_func!(alIsBuffer(_)),
+    export_c_func!(alBufferf(_, _, _)),
+    export_c_func!(alBufferi(_, _, _)),
+    export_c_func!(alGetBufferf(_, _, _)),
+    export_c_func!(alGetBufferi(_, _, _)),
     export_c_func!(alBufferData(_, _, _, _, _)),
     export_c_func!(alBufferDataStatic(_, _, _, _, _)),
 ];
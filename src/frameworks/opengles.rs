@@ -12,27 +12,46 @@
 //! - Various child modules provide implementations:
 //!   - `gles1_on_gl2` provides an implementation of OpenGL ES 1.1 using OpenGL
 //!     2.1 compatibility profile.
+//!   - `gles2_on_gl2` provides an implementation of OpenGL ES 2.0 using OpenGL
+//!     2.1 compatibility profile.
+//!   - `pvrtc` is used by both to decode PVRTC textures.
+//!   - `framebuffer` is used by both for renderbuffer formats and
+//!     multisampling.
+//!   - `gles_debug` wraps either of them to help with debugging.
 //!   - There are are no others currently, but an obvious future target is
-//!     exposing real OpenGL ES 1.1 provided by Android.
+//!     exposing real OpenGL ES provided by Android.
 //!
 //! Useful resources for OpenGL ES 1.1:
 //! - [Reference pages](https://registry.khronos.org/OpenGL-Refpages/es1.1/xhtml/)
 //! - [Specification](https://registry.khronos.org/OpenGL/specs/es/1.1/es_full_spec_1.1.pdf)
 //! - Extensions:
 //!   - [OES_framebuffer_object](https://registry.khronos.org/OpenGL/extensions/OES/OES_framebuffer_object.txt)
+//!   - [IMG_texture_compression_pvrtc](https://registry.khronos.org/OpenGL/extensions/IMG/IMG_texture_compression_pvrtc.txt)
+//!
+//! Useful resources for OpenGL ES 2.0:
+//! - [Reference pages](https://registry.khronos.org/OpenGL-Refpages/es2.0/)
+//! - [Specification](https://registry.khronos.org/OpenGL/specs/es/2.0/es_full_spec_2.0.pdf)
+//! - [GLSL ES 1.00 specification](https://registry.khronos.org/OpenGL/specs/es/2.0/GLSL_ES_Specification_1.00.pdf)
 //!
 //! Useful resources for OpenGL 2.1:
 //! - [Reference pages](https://registry.khronos.org/OpenGL-Refpages/gl2.1/)
 //! - [Specification](https://registry.khronos.org/OpenGL/specs/gl/glspec21.pdf)
+//! - [GLSL 1.20 specification](https://registry.khronos.org/OpenGL/specs/gl/GLSLangSpec.1.20.pdf)
 //! - Extensions:
 //!   - [EXT_framebuffer_object](https://registry.khronos.org/OpenGL/extensions/EXT/EXT_framebuffer_object.txt)
 
 pub mod eagl;
+mod framebuffer;
 mod gles1_on_gl2;
+mod gles2_on_gl2;
+mod gles_debug;
 mod gles_generic;
 mod gles_guest;
+mod pvrtc;
 
 use gles1_on_gl2::GLES1OnGL2;
+use gles2_on_gl2::GLES2OnGL2;
+use gles_debug::GLESDebug;
 use gles_generic::GLES;
 pub use gles_guest::FUNCTIONS;
 
@@ -67,6 +86,7 @@ fn sync_context<'a>(
             current_thread
         );
         gles_ctx.make_current(window);
+        state.current_ctx_thread = Some(current_thread);
     }
 
     gles_ctx
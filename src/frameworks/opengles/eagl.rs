@@ -5,13 +5,17 @@
  */
 //! EAGL.
 
-use super::{GLES1OnGL2, GLES};
+use super::{GLES1OnGL2, GLES2OnGL2, GLESDebug, GLES};
 use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_animation::{ca_eagl_layer, composition};
 use crate::frameworks::foundation::ns_string::get_static_str;
 use crate::frameworks::foundation::NSUInteger;
-use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports, HostObject};
+use crate::frameworks::uikit::overlay;
+use crate::objc::{
+    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
 use crate::window::gles11;
-use crate::window::Matrix;
+use crate::window::{DisplayFilter, FilterStage, Matrix};
 use crate::Environment; // for constants
 
 // These are used by the EAGLDrawable protocol implemented by CAEAGLayer.
@@ -43,13 +47,46 @@ pub const CONSTANTS: ConstantExports = &[
 
 type EAGLRenderingAPI = u32;
 const kEAGLRenderingAPIOpenGLES1: EAGLRenderingAPI = 1;
-#[allow(dead_code)]
 const kEAGLRenderingAPIOpenGLES2: EAGLRenderingAPI = 2;
 #[allow(dead_code)]
 const kEAGLRenderingAPIOpenGLES3: EAGLRenderingAPI = 3;
 
+/// Create an OpenGL ES context using the implementation `T`, wrapped in
+/// [GLESDebug] if the `--gles-debug` option is enabled. If `share_with` (an
+/// `EAGLContext*`) is provided, the new context shares objects with it.
+fn new_gles_ctx<T: GLES + 'static>(env: &mut Environment, share_with: Option<id>) -> Box<dyn GLES> {
+    let share_with = share_with.map(|context| {
+        env.objc
+            .borrow::<EAGLContextHostObject>(context)
+            .gles_ctx
+            .as_deref()
+            .unwrap()
+            .gl_ctx()
+    });
+    if env.options.gles_debug {
+        Box::new(GLESDebug::<T>::new(&mut env.window, share_with))
+    } else {
+        Box::new(T::new(&mut env.window, share_with))
+    }
+}
+
+#[derive(Default)]
+struct EAGLSharegroupHostObject {
+    /// The `EAGLContext*`s in this sharegroup. Weak references.
+    contexts: Vec<id>,
+}
+impl HostObject for EAGLSharegroupHostObject {}
+
 pub(super) struct EAGLContextHostObject {
+    api: EAGLRenderingAPI,
+    /// `EAGLSharegroup*`. Strong reference.
+    sharegroup: id,
     pub(super) gles_ctx: Option<Box<dyn GLES>>,
+    /// The `CAEAGLLayer*` each renderbuffer got its storage from, so frames
+    /// presented from it can be given to the compositor. Strong references.
+    renderbuffer_layers: Vec<(u32, id)>,
+    /// For [present_frame], which presents using this context.
+    filter_stage: Option<FilterStage>,
 }
 impl HostObject for EAGLContextHostObject {}
 
@@ -57,10 +94,28 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
+// Contexts in the same sharegroup share textures, buffers, renderbuffers etc.
+// Apps use this to load textures on a background thread, with a second
+// context.
+@implementation EAGLSharegroup: NSObject
+
++ (id)alloc {
+    let host_object = Box::<EAGLSharegroupHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+@end
+
 @implementation EAGLContext: NSObject
 
 + (id)alloc {
-    let host_object = Box::new(EAGLContextHostObject { gles_ctx: None });
+    let host_object = Box::new(EAGLContextHostObject {
+        api: 0,
+        sharegroup: nil,
+        gles_ctx: None,
+        renderbuffer_layers: Vec::new(),
+        filter_stage: None,
+    });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
@@ -91,19 +146,78 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (id)initWithAPI:(EAGLRenderingAPI)api {
-    assert!(api == kEAGLRenderingAPIOpenGLES1);
+    msg![env; this initWithAPI:api sharegroup:nil]
+}
 
-    let gles1_ctx = Box::new(GLES1OnGL2::new(&mut env.window));
+- (id)initWithAPI:(EAGLRenderingAPI)api
+       sharegroup:(id)sharegroup { // EAGLSharegroup*
+    // All contexts in a sharegroup belong to the same API, so any of them can
+    // be used to share with.
+    let share_with = if sharegroup == nil {
+        None
+    } else {
+        env.objc.borrow::<EAGLSharegroupHostObject>(sharegroup).contexts.first().copied()
+    };
+    if let Some(share_with) = share_with {
+        let other_api = env.objc.borrow::<EAGLContextHostObject>(share_with).api;
+        if other_api != api {
+            log!("App requested EAGLRenderingAPI {} for a sharegroup using EAGLRenderingAPI {}, returning nil", api, other_api);
+            release(env, this);
+            return nil;
+        }
+    }
 
-    *env.objc.borrow_mut(this) = EAGLContextHostObject {
-        gles_ctx: Some(gles1_ctx),
+    let gles_ctx: Box<dyn GLES> = match api {
+        kEAGLRenderingAPIOpenGLES1 => new_gles_ctx::<GLES1OnGL2>(env, share_with),
+        kEAGLRenderingAPIOpenGLES2 => new_gles_ctx::<GLES2OnGL2>(env, share_with),
+        _ => {
+            // Apps are expected to check for this and try an older API.
+            log!("App requested unsupported EAGLRenderingAPI {}, returning nil", api);
+            release(env, this);
+            return nil;
+        }
     };
 
+    let sharegroup = if sharegroup == nil {
+        let new: id = msg_class![env; EAGLSharegroup alloc];
+        msg![env; new init]
+    } else {
+        retain(env, sharegroup)
+    };
+    env.objc.borrow_mut::<EAGLSharegroupHostObject>(sharegroup).contexts.push(this);
+
+    let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(this);
+    host_obj.api = api;
+    host_obj.sharegroup = sharegroup;
+    host_obj.gles_ctx = Some(gles_ctx);
+
     this
 }
 
+- (EAGLRenderingAPI)API {
+    env.objc.borrow::<EAGLContextHostObject>(this).api
+}
+
+- (id)sharegroup {
+    env.objc.borrow::<EAGLContextHostObject>(this).sharegroup
+}
+
+- (())dealloc {
+    let layers = std::mem::take(&mut env.objc.borrow_mut::<EAGLContextHostObject>(this).renderbuffer_layers);
+    for (_, layer) in layers {
+        release(env, layer);
+    }
+    let sharegroup = env.objc.borrow::<EAGLContextHostObject>(this).sharegroup;
+    if sharegroup != nil {
+        env.objc.borrow_mut::<EAGLSharegroupHostObject>(sharegroup).contexts.retain(|&context| context != this);
+        release(env, sharegroup);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
 - (bool)renderbufferStorage:(NSUInteger)target
                fromDrawable:(id)drawable { // EAGLDrawable (always CAEAGLayer*)
+    // This is also the value of GL_RENDERBUFFER in OpenGL ES 2.0.
     assert!(target == gles11::RENDERBUFFER_OES);
 
     let props: id = msg![env; drawable drawableProperties];
@@ -131,6 +245,18 @@ pub const CLASSES: ClassExports = objc_classes! {
         gles.RenderbufferStorageOES(target, internalformat, width.try_into().unwrap(), height.try_into().unwrap())
     }
 
+    let renderbuffer = unsafe { bound_renderbuffer() };
+    retain(env, drawable);
+    let layers = &mut env.objc.borrow_mut::<EAGLContextHostObject>(this).renderbuffer_layers;
+    let old = match layers.iter_mut().find(|(name, _)| *name == renderbuffer) {
+        Some((_, layer)) => std::mem::replace(layer, drawable),
+        None => {
+            layers.push((renderbuffer, drawable));
+            nil
+        }
+    };
+    release(env, old);
+
     true
 }
 
@@ -140,8 +266,24 @@ pub const CLASSES: ClassExports = objc_classes! {
     // Unclear from documentation if this method requires an appropriate context
     // to already be active, but that seems to be the case in practice?
     super::sync_context(&mut env.framework_state.opengles, &mut env.objc, &mut env.window, env.current_thread);
-    unsafe {
-        present_renderbuffer(env);
+
+    let renderbuffer = unsafe { bound_renderbuffer() };
+    let layer = env.objc.borrow::<EAGLContextHostObject>(this)
+        .renderbuffer_layers
+        .iter()
+        .find(|&&(name, _)| name == renderbuffer)
+        .map(|&(_, layer)| layer);
+    match layer {
+        Some(layer) if !composition::should_present_directly(env, layer) => {
+            let (pixels, width, height) = unsafe { read_renderbuffer() };
+            ca_eagl_layer::set_presented_frame(env, layer, pixels, width, height);
+        }
+        _ => {
+            unsafe {
+                present_renderbuffer(env, this);
+            }
+            composition::note_direct_presentation(env);
+        }
     }
 
     true
@@ -151,9 +293,75 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// Get the name of the renderbuffer bound in the current context.
+unsafe fn bound_renderbuffer() -> u32 {
+    use crate::window::gl21compat as gl;
+
+    let mut renderbuffer: gl::types::GLuint = 0;
+    gl::GetIntegerv(
+        gl::RENDERBUFFER_BINDING_EXT,
+        &mut renderbuffer as *mut _ as *mut _,
+    );
+    renderbuffer
+}
+
+/// Copies the pixels of the bound renderbuffer, as RGBA8 with the bottom row
+/// first, for the compositor (see [composition]).
+unsafe fn read_renderbuffer() -> (Vec<u8>, u32, u32) {
+    use crate::window::gl21compat as gl;
+    use crate::window::gl21compat::types::*;
+
+    let renderbuffer = bound_renderbuffer();
+    let mut width: GLint = 0;
+    let mut height: GLint = 0;
+    gl::GetRenderbufferParameterivEXT(gl::RENDERBUFFER_EXT, gl::RENDERBUFFER_WIDTH_EXT, &mut width);
+    gl::GetRenderbufferParameterivEXT(
+        gl::RENDERBUFFER_EXT,
+        gl::RENDERBUFFER_HEIGHT_EXT,
+        &mut height,
+    );
+
+    let mut old_read_framebuffer: GLuint = 0;
+    gl::GetIntegerv(
+        gl::READ_FRAMEBUFFER_BINDING_EXT,
+        &mut old_read_framebuffer as *mut _ as *mut _,
+    );
+
+    let mut src_framebuffer = 0;
+    gl::GenFramebuffersEXT(1, &mut src_framebuffer);
+    gl::BindFramebufferEXT(gl::READ_FRAMEBUFFER_EXT, src_framebuffer);
+    gl::FramebufferRenderbufferEXT(
+        gl::READ_FRAMEBUFFER_EXT,
+        gl::COLOR_ATTACHMENT0_EXT,
+        gl::RENDERBUFFER_EXT,
+        renderbuffer,
+    );
+
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    gl::PushClientAttrib(gl::CLIENT_PIXEL_STORE_BIT);
+    gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+    gl::PixelStorei(gl::PACK_ROW_LENGTH, 0);
+    gl::ReadPixels(
+        0,
+        0,
+        width,
+        height,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        pixels.as_mut_ptr() as *mut _,
+    );
+    gl::PopClientAttrib();
+
+    gl::DeleteFramebuffersEXT(1, &src_framebuffer);
+    gl::BindFramebufferEXT(gl::READ_FRAMEBUFFER_EXT, old_read_framebuffer);
+
+    (pixels, width as u32, height as u32)
+}
+
 /// Copies the renderbuffer provided by the app to the window's framebuffer,
-/// rotated if necessary, and presents that framebuffer.
-unsafe fn present_renderbuffer(env: &mut Environment) {
+/// rotated if necessary, and presents that framebuffer. `context` must be the
+/// current `EAGLContext*`.
+unsafe fn present_renderbuffer(env: &mut Environment, context: id) {
     // Renderbuffers can't be directly read from, but GL_EXT_framebuffer_blit
     // provides a way to blit between framebuffers, which may have renderbuffers
     // attached to them. Since OpenGL ES 1.1 doesn't have that extension, we
@@ -180,6 +388,15 @@ unsafe fn present_renderbuffer(env: &mut Environment) {
 
     // To avoid confusing the guest app, we need to be able to undo any
     // state changes we make.
+    let mut old_active_texture: GLenum = 0;
+    gl::GetIntegerv(
+        gl::ACTIVE_TEXTURE,
+        &mut old_active_texture as *mut _ as *mut _,
+    );
+    gl::ActiveTexture(gl::TEXTURE0);
+    let mut old_program: GLuint = 0;
+    gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut old_program as *mut _ as *mut _);
+    gl::UseProgram(0);
     let mut old_draw_framebuffer: GLuint = 0;
     let mut old_read_framebuffer: GLuint = 0;
     let mut old_texture_2d: GLuint = 0;
@@ -238,7 +455,8 @@ unsafe fn present_renderbuffer(env: &mut Environment) {
         renderbuffer,
     );
 
-    // Blit!
+    // Blit! The sizes are the same, so there's no filtering, and nearest is
+    // the only filter allowed if the source is multisampled.
     gl::BlitFramebufferEXT(
         0,
         0,
@@ -249,7 +467,7 @@ unsafe fn present_renderbuffer(env: &mut Environment) {
         width,
         height,
         gl::COLOR_BUFFER_BIT,
-        gl::LINEAR,
+        gl::NEAREST,
     );
 
     // Clean up the framebuffer objects since we no longer need them.
@@ -264,6 +482,14 @@ unsafe fn present_renderbuffer(env: &mut Environment) {
     for array in super::gles1_on_gl2::ARRAYS {
         gl::DisableClientState(array.name);
     }
+    // OpenGL ES 2.0 apps use generic vertex attributes instead, and one of
+    // these may alias the vertex array.
+    let mut max_vertex_attribs: GLint = 0;
+    gl::GetIntegerv(gl::MAX_VERTEX_ATTRIBS, &mut max_vertex_attribs);
+    for index in 0..(max_vertex_attribs as GLuint) {
+        gl::DisableVertexAttribArray(index);
+    }
+    gl::ClientActiveTexture(gl::TEXTURE0);
     gl::PushAttrib(gl::ALL_ATTRIB_BITS);
     for &cap in super::gles1_on_gl2::CAPABILITIES {
         gl::Disable(cap);
@@ -281,11 +507,89 @@ unsafe fn present_renderbuffer(env: &mut Environment) {
         &mut old_array_buffer as *mut _ as *mut _,
     );
 
-    // Draw the quad
-    let viewport_size = env.window.size_in_current_orientation();
-    gl::Viewport(0, 0, viewport_size.0 as _, viewport_size.1 as _);
+    let mut filter_stage = env
+        .objc
+        .borrow_mut::<EAGLContextHostObject>(context)
+        .filter_stage
+        .take();
+    present_frame(env, texture, &mut filter_stage);
+    env.objc
+        .borrow_mut::<EAGLContextHostObject>(context)
+        .filter_stage = filter_stage;
+
+    // Clean up the texture
+    gl::DeleteTextures(1, &texture);
+
+    // Restore all the state saved before rendering
+    gl::BindBuffer(gl::ARRAY_BUFFER, old_array_buffer);
+    for mode in [gl::MODELVIEW, gl::PROJECTION, gl::TEXTURE] {
+        gl::MatrixMode(mode);
+        gl::PopMatrix();
+    }
+    gl::MatrixMode(old_matrix_mode);
+    gl::PopAttrib();
+    gl::PopClientAttrib();
+
+    // Restore the other bindings
+    gl::BindTexture(gl::TEXTURE_2D, old_texture_2d);
+    gl::ActiveTexture(old_active_texture);
+    gl::UseProgram(old_program);
+    gl::BindFramebufferEXT(gl::DRAW_FRAMEBUFFER_EXT, old_draw_framebuffer);
+    gl::BindFramebufferEXT(gl::READ_FRAMEBUFFER_EXT, old_read_framebuffer);
+
+    //{ let err = gl::GetError(); if err != 0 { panic!("{:#x}", err); } }
+}
+
+/// Draws a frame of the app's content to the window, rotated if necessary,
+/// draws UI drawn by the host on top, and presents the result. `texture` must
+/// be a texture in the current context containing the app's content, which
+/// will be stretched to fill the window.
+///
+/// The current context must be an OpenGL 2.1 compatibility profile context,
+/// with the default framebuffer bound, the default state for capabilities and
+/// client arrays, and identity matrices. `filter_stage` holds the objects used
+/// to apply the display filter (see `--display-filter=`) in that context, and
+/// should start out as [None].
+pub(crate) unsafe fn present_frame(
+    env: &mut Environment,
+    texture: u32,
+    filter_stage: &mut Option<FilterStage>,
+) {
+    use crate::window::gl21compat as gl;
+    use crate::window::gl21compat::types::*;
+
+    let activity = crate::benchmark::set_activity(env, crate::benchmark::Activity::Gl);
+
+    crate::snapshots::frame_presented(env, texture);
+
+    // Host UI is positioned in the emulated screen's pixels, but the screen
+    // is drawn at whatever size fits the window.
+    let screen_size = env.window.size_in_current_orientation();
+    let (viewport_offset, viewport_size) = env.window.viewport();
+
+    // Clear the whole window, including any letterboxing.
     gl::ClearColor(0.0, 0.0, 0.0, 1.0);
     gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+
+    // If there's a display filter, everything is drawn to a texture first.
+    let filter = env.options.display_filter;
+    if filter_stage.as_ref().map(|stage| stage.filter()) != Some(filter) {
+        *filter_stage = (filter != DisplayFilter::Linear).then(|| FilterStage::new(filter));
+    }
+    if let Some(filter_stage) = filter_stage {
+        filter_stage.begin(viewport_size);
+        gl::Viewport(0, 0, viewport_size.0 as _, viewport_size.1 as _);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+    } else {
+        gl::Viewport(
+            viewport_offset.0 as _,
+            viewport_offset.1 as _,
+            viewport_size.0 as _,
+            viewport_size.1 as _,
+        );
+    }
+
+    // Draw the quad
     gl::BindBuffer(gl::ARRAY_BUFFER, 0);
     let vertices: [f32; 12] = [
         -1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0,
@@ -299,10 +603,39 @@ unsafe fn present_renderbuffer(env: &mut Environment) {
     gl::MatrixMode(gl::TEXTURE);
     gl::LoadMatrixf(matrix.columns().as_ptr() as *const _);
     gl::Enable(gl::TEXTURE_2D);
+    gl::BindTexture(gl::TEXTURE_2D, texture);
     gl::DrawArrays(gl::TRIANGLES, 0, 6);
 
-    // Display virtual cursor
-    if let Some((x, y, pressed)) = env.window.virtual_cursor_visible_at() {
+    // Display UI drawn by the host, like alerts and the on-screen keyboard,
+    // which is in the same orientation as the app's content, so the same quad
+    // can be used.
+    let mut overlay_texture: GLuint = 0;
+    gl::GenTextures(1, &mut overlay_texture);
+    gl::BindTexture(gl::TEXTURE_2D, overlay_texture);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+    for overlay in overlay::overlays(env) {
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::PixelStorei(gl::UNPACK_ROW_LENGTH, 0);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA as _,
+            overlay.width as _,
+            overlay.height as _,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            overlay.pixels.as_ptr() as *const _,
+        );
+        // The pixels are premultiplied.
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+    }
+    gl::DeleteTextures(1, &overlay_texture);
+
+    // Display virtual cursors
+    for (x, y, pressed) in env.window.virtual_cursors_visible_at() {
         gl::DisableClientState(gl::TEXTURE_COORD_ARRAY);
         gl::Disable(gl::TEXTURE_2D);
 
@@ -314,34 +647,55 @@ unsafe fn present_renderbuffer(env: &mut Environment) {
 
         let mut vertices = vertices;
         for i in (0..vertices.len()).step_by(2) {
-            vertices[i] = (vertices[i] * radius + x) / (viewport_size.0 as f32 / 2.0) - 1.0;
-            vertices[i + 1] = 1.0 - (vertices[i + 1] * radius + y) / (viewport_size.1 as f32 / 2.0);
+            vertices[i] = (vertices[i] * radius + x) / (screen_size.0 as f32 / 2.0) - 1.0;
+            vertices[i + 1] = 1.0 - (vertices[i + 1] * radius + y) / (screen_size.1 as f32 / 2.0);
         }
         gl::VertexPointer(2, gl::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
         gl::DrawArrays(gl::TRIANGLES, 0, 6);
     }
 
-    // Clean up the texture
-    gl::DeleteTextures(1, &texture);
+    // Display on-screen virtual controls (see --virtual-controls-dir=)
+    for (x, y, radius, pressed) in env.window.virtual_controls_visible_at() {
+        gl::DisableClientState(gl::TEXTURE_COORD_ARRAY);
+        gl::Disable(gl::TEXTURE_2D);
 
-    // Restore all the state saved before rendering
-    gl::BindBuffer(gl::ARRAY_BUFFER, old_array_buffer);
-    for mode in [gl::MODELVIEW, gl::PROJECTION, gl::TEXTURE] {
-        gl::MatrixMode(mode);
-        gl::PopMatrix();
+        // Translucent white, premultiplied.
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+        let alpha = if pressed { 0.5 } else { 0.25 };
+        gl::Color4f(alpha, alpha, alpha, alpha);
+
+        const SEGMENTS: usize = 32;
+        let mut vertices = [0f32; (SEGMENTS + 2) * 2];
+        for i in 0..(SEGMENTS + 2) {
+            // The first vertex is the center of the fan.
+            let (dx, dy) = if i == 0 {
+                (0.0, 0.0)
+            } else {
+                let angle = (i - 1) as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+                (angle.cos() * radius, angle.sin() * radius)
+            };
+            vertices[i * 2] = (x + dx) / (screen_size.0 as f32 / 2.0) - 1.0;
+            vertices[i * 2 + 1] = 1.0 - (y + dy) / (screen_size.1 as f32 / 2.0);
+        }
+        gl::VertexPointer(2, gl::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+        gl::DrawArrays(gl::TRIANGLE_FAN, 0, (SEGMENTS + 2) as _);
+    }
+
+    if let Some(filter_stage) = filter_stage {
+        gl::Viewport(
+            viewport_offset.0 as _,
+            viewport_offset.1 as _,
+            viewport_size.0 as _,
+            viewport_size.1 as _,
+        );
+        filter_stage.end(env.window.size_in_current_orientation_unscaled());
     }
-    gl::MatrixMode(old_matrix_mode);
-    gl::PopAttrib();
-    gl::PopClientAttrib();
 
     // SDL2's documentation warns 0 should be bound to the draw framebuffer
     // when swapping the window, so this is the perfect moment.
     env.window.swap_window();
-
-    // Restore the other bindings
-    gl::BindTexture(gl::TEXTURE_2D, old_texture_2d);
-    gl::BindFramebufferEXT(gl::DRAW_FRAMEBUFFER_EXT, old_draw_framebuffer);
-    gl::BindFramebufferEXT(gl::READ_FRAMEBUFFER_EXT, old_read_framebuffer);
-
-    //{ let err = gl::GetError(); if err != 0 { panic!("{:#x}", err); } }
+    crate::report::frame_presented(env);
+    crate::benchmark::set_activity(env, activity);
+    crate::benchmark::frame_presented(env);
 }
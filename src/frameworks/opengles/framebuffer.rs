@@ -0,0 +1,149 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Framebuffer object support shared by the OpenGL ES implementations on
+//! OpenGL 2.1: renderbuffer formats, multisampling
+//! (`GL_APPLE_framebuffer_multisample`) and `GL_EXT_discard_framebuffer`.
+//!
+//! Useful resources:
+//! - [APPLE_framebuffer_multisample](https://registry.khronos.org/OpenGL/extensions/APPLE/APPLE_framebuffer_multisample.txt)
+//! - [EXT_discard_framebuffer](https://registry.khronos.org/OpenGL/extensions/EXT/EXT_discard_framebuffer.txt)
+//! - [EXT_framebuffer_multisample](https://registry.khronos.org/OpenGL/extensions/EXT/EXT_framebuffer_multisample.txt)
+//! - [EXT_packed_depth_stencil](https://registry.khronos.org/OpenGL/extensions/EXT/EXT_packed_depth_stencil.txt)
+
+use crate::window::gl21compat as gl21;
+use crate::window::gl21compat::types::*;
+use crate::window::gles11;
+
+/// Translate a renderbuffer internal format from OpenGL ES to OpenGL 2.1.
+///
+/// iPhone OS supports the color formats, depth-only formats, stencil-only
+/// format and the packed depth/stencil format below. Games usually use either
+/// a depth-only buffer or the packed format, attached to both the depth and
+/// stencil attachment points, which OpenGL 2.1 with
+/// `GL_EXT_packed_depth_stencil` supports in the same way.
+pub(super) fn renderbuffer_internal_format(internalformat: GLenum) -> GLenum {
+    match internalformat {
+        // OpenGL 2.1 has no 16-bit RGB format (it's an OpenGL ES addition),
+        // so use the nearest one. This is invisible to the app, except that
+        // dithering won't happen.
+        gles11::RGB565_OES => gl21::RGB8,
+        gles11::RGBA4_OES
+        | gles11::RGB5_A1_OES
+        | gles11::RGB8_OES
+        | gles11::RGBA8_OES
+        | gles11::DEPTH_COMPONENT16_OES
+        | gles11::DEPTH_COMPONENT24_OES
+        | gles11::STENCIL_INDEX8_OES
+        | gles11::DEPTH24_STENCIL8_OES => internalformat,
+        _ => panic!("Unhandled renderbuffer format {:#x}", internalformat),
+    }
+}
+
+pub(super) unsafe fn renderbuffer_storage_multisample(
+    target: GLenum,
+    samples: GLsizei,
+    internalformat: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+) {
+    assert!(target == gl21::RENDERBUFFER_EXT);
+    // The host may support fewer samples than the iPhone (4), and the app is
+    // allowed to get fewer than it asked for.
+    let mut max_samples = 0;
+    gl21::GetIntegerv(gl21::MAX_SAMPLES_EXT, &mut max_samples);
+    gl21::RenderbufferStorageMultisampleEXT(
+        target,
+        samples.min(max_samples),
+        renderbuffer_internal_format(internalformat),
+        width,
+        height,
+    )
+}
+
+/// Implementation of `glResolveMultisampleFramebufferAPPLE`: resolve the color
+/// buffer of the read framebuffer to the color buffer of the draw framebuffer.
+/// Like with the original, the scissor test applies.
+pub(super) unsafe fn resolve_multisample_framebuffer() {
+    // The extension requires both color buffers to have the same size, so the
+    // resolved area is just the size of the multisampled renderbuffer.
+    let mut object_type = 0;
+    gl21::GetFramebufferAttachmentParameterivEXT(
+        gl21::READ_FRAMEBUFFER_EXT,
+        gl21::COLOR_ATTACHMENT0_EXT,
+        gl21::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE_EXT,
+        &mut object_type,
+    );
+    if object_type as GLenum != gl21::RENDERBUFFER_EXT {
+        log!("Warning: glResolveMultisampleFramebufferAPPLE() with no multisampled renderbuffer, ignoring");
+        return;
+    }
+    let mut renderbuffer = 0;
+    gl21::GetFramebufferAttachmentParameterivEXT(
+        gl21::READ_FRAMEBUFFER_EXT,
+        gl21::COLOR_ATTACHMENT0_EXT,
+        gl21::FRAMEBUFFER_ATTACHMENT_OBJECT_NAME_EXT,
+        &mut renderbuffer,
+    );
+    let mut old_renderbuffer = 0;
+    gl21::GetIntegerv(gl21::RENDERBUFFER_BINDING_EXT, &mut old_renderbuffer);
+    gl21::BindRenderbufferEXT(gl21::RENDERBUFFER_EXT, renderbuffer as GLuint);
+    let mut width = 0;
+    let mut height = 0;
+    gl21::GetRenderbufferParameterivEXT(
+        gl21::RENDERBUFFER_EXT,
+        gl21::RENDERBUFFER_WIDTH_EXT,
+        &mut width,
+    );
+    gl21::GetRenderbufferParameterivEXT(
+        gl21::RENDERBUFFER_EXT,
+        gl21::RENDERBUFFER_HEIGHT_EXT,
+        &mut height,
+    );
+    gl21::BindRenderbufferEXT(gl21::RENDERBUFFER_EXT, old_renderbuffer as GLuint);
+
+    gl21::BlitFramebufferEXT(
+        0,
+        0,
+        width,
+        height,
+        0,
+        0,
+        width,
+        height,
+        gl21::COLOR_BUFFER_BIT,
+        gl21::NEAREST,
+    );
+}
+
+/// Implementation of `glDiscardFramebufferEXT`. This is only a performance
+/// hint for tile-based GPUs like the iPhone's, so it is just validated.
+pub(super) unsafe fn discard_framebuffer(
+    target: GLenum,
+    num_attachments: GLsizei,
+    attachments: *const GLenum,
+) {
+    // Apple's sample code discards the multisampled framebuffer after
+    // resolving it, using GL_READ_FRAMEBUFFER_APPLE.
+    assert!([
+        gles11::FRAMEBUFFER_OES,
+        gles11::READ_FRAMEBUFFER_APPLE,
+        gles11::DRAW_FRAMEBUFFER_APPLE
+    ]
+    .contains(&target));
+    let num_attachments: usize = num_attachments.try_into().unwrap();
+    let attachments = std::slice::from_raw_parts(attachments, num_attachments);
+    for &attachment in attachments {
+        assert!([
+            gles11::COLOR_ATTACHMENT0_OES,
+            gles11::DEPTH_ATTACHMENT_OES,
+            gles11::STENCIL_ATTACHMENT_OES,
+            gles11::COLOR_EXT,
+            gles11::DEPTH_EXT,
+            gles11::STENCIL_EXT,
+        ]
+        .contains(&attachment));
+    }
+}
@@ -18,6 +18,11 @@
 //! on macOS. It's also a version supported on various other OSes.
 //! It is therefore a convenient target for our implementation.
 
+use super::framebuffer::{
+    discard_framebuffer, renderbuffer_internal_format, renderbuffer_storage_multisample,
+    resolve_multisample_framebuffer,
+};
+use super::pvrtc::{compressed_tex_image_2d_pvrtc, get_compressed_texture_formats, PVRTC_FORMATS};
 use super::GLES;
 use crate::window::gl21compat as gl21;
 use crate::window::gl21compat::types::*;
@@ -281,9 +286,13 @@ impl GLES1OnGL2 {
     }
 }
 impl GLES for GLES1OnGL2 {
-    fn new(window: &mut Window) -> Self {
+    fn new(window: &mut Window, share_with: Option<&GLContext>) -> Self {
+        let gl_ctx = match share_with {
+            Some(share_with) => window.create_shared_gl_context(GLVersion::GL21Compat, share_with),
+            None => window.create_gl_context(GLVersion::GL21Compat),
+        };
         Self {
-            gl_ctx: window.create_gl_context(GLVersion::GL21Compat),
+            gl_ctx,
             pointer_is_fixed_point: [false; ARRAYS.len()],
             fixed_point_translation_buffers: [Vec::new(), Vec::new(), Vec::new(), Vec::new()],
         }
@@ -293,6 +302,10 @@ impl GLES for GLES1OnGL2 {
         window.make_gl_context_current(&self.gl_ctx);
     }
 
+    fn gl_ctx(&self) -> &GLContext {
+        &self.gl_ctx
+    }
+
     // Generic state manipulation
     unsafe fn GetError(&mut self) -> GLenum {
         gl21::GetError()
@@ -314,17 +327,33 @@ impl GLES for GLES1OnGL2 {
         gl21::DisableClientState(array);
     }
     unsafe fn GetIntegerv(&mut self, pname: GLenum, params: *mut GLint) {
+        if get_compressed_texture_formats(pname, params) {
+            return;
+        }
+
         // This function family can return a huge number of things.
         // TODO: support more possible values.
         assert!([
             gl21::ARRAY_BUFFER_BINDING,
             gl21::ELEMENT_ARRAY_BUFFER_BINDING,
+            gl21::FRAMEBUFFER_BINDING_EXT,
             gl21::MATRIX_MODE,
-            gl21::TEXTURE_BINDING_2D
+            gl21::MAX_SAMPLES_EXT,
+            gl21::READ_FRAMEBUFFER_BINDING_EXT,
+            gl21::RENDERBUFFER_BINDING_EXT,
+            gl21::SCISSOR_BOX,
+            gl21::TEXTURE_BINDING_2D,
+            gl21::VIEWPORT,
         ]
         .contains(&pname));
         gl21::GetIntegerv(pname, params);
     }
+    unsafe fn Flush(&mut self) {
+        gl21::Flush()
+    }
+    unsafe fn Finish(&mut self) {
+        gl21::Finish()
+    }
 
     // Other state manipulation
     unsafe fn AlphaFunc(&mut self, func: GLenum, ref_: GLclampf) {
@@ -383,6 +412,18 @@ impl GLES for GLES1OnGL2 {
     unsafe fn Viewport(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
         gl21::Viewport(x, y, width, height)
     }
+    unsafe fn LineWidth(&mut self, width: GLfloat) {
+        gl21::LineWidth(width)
+    }
+    unsafe fn LineWidthx(&mut self, width: GLfixed) {
+        self.LineWidth(fixed_to_float(width))
+    }
+    unsafe fn PointSize(&mut self, size: GLfloat) {
+        gl21::PointSize(size)
+    }
+    unsafe fn PointSizex(&mut self, size: GLfixed) {
+        self.PointSize(fixed_to_float(size))
+    }
 
     // Lighting
     unsafe fn Lightf(&mut self, light: GLenum, pname: GLenum, param: GLfloat) {
@@ -422,6 +463,28 @@ impl GLES for GLES1OnGL2 {
         assert!(target == gl21::ARRAY_BUFFER || target == gl21::ELEMENT_ARRAY_BUFFER);
         gl21::BindBuffer(target, buffer)
     }
+    unsafe fn BufferData(
+        &mut self,
+        target: GLenum,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+        usage: GLenum,
+    ) {
+        assert!(target == gl21::ARRAY_BUFFER || target == gl21::ELEMENT_ARRAY_BUFFER);
+        // OpenGL ES 1.1 has no GL_STREAM_DRAW.
+        assert!(usage == gl21::STATIC_DRAW || usage == gl21::DYNAMIC_DRAW);
+        gl21::BufferData(target, size, data, usage)
+    }
+    unsafe fn BufferSubData(
+        &mut self,
+        target: GLenum,
+        offset: GLintptr,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+    ) {
+        assert!(target == gl21::ARRAY_BUFFER || target == gl21::ELEMENT_ARRAY_BUFFER);
+        gl21::BufferSubData(target, offset, size, data)
+    }
 
     // Non-pointers
     unsafe fn Color4f(&mut self, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
@@ -644,6 +707,10 @@ impl GLES for GLES1OnGL2 {
     }
 
     // Textures
+    unsafe fn ActiveTexture(&mut self, texture: GLenum) {
+        // TODO: glClientActiveTexture, without which multitexturing is useless
+        gl21::ActiveTexture(texture)
+    }
     unsafe fn GenTextures(&mut self, n: GLsizei, textures: *mut GLuint) {
         gl21::GenTextures(n, textures)
     }
@@ -713,6 +780,34 @@ impl GLES for GLES1OnGL2 {
         )
     }
 
+    unsafe fn CompressedTexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+        image_size: GLsizei,
+        data: *const GLvoid,
+    ) {
+        assert!(target == gl21::TEXTURE_2D);
+        // TODO: paletted texture formats (OES_compressed_paletted_texture)
+        if !PVRTC_FORMATS.contains(&internalformat) {
+            unimplemented!("Compressed texture format {:#x}", internalformat);
+        }
+        compressed_tex_image_2d_pvrtc(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            image_size,
+            data,
+        )
+    }
+
     // Matrix stack operations
     unsafe fn MatrixMode(&mut self, mode: GLenum) {
         assert!(mode == gl21::MODELVIEW || mode == gl21::PROJECTION || mode == gl21::TEXTURE);
@@ -857,7 +952,12 @@ impl GLES for GLES1OnGL2 {
         width: GLsizei,
         height: GLsizei,
     ) {
-        gl21::RenderbufferStorageEXT(target, internalformat, width, height)
+        gl21::RenderbufferStorageEXT(
+            target,
+            renderbuffer_internal_format(internalformat),
+            width,
+            height,
+        )
     }
     unsafe fn FramebufferRenderbufferOES(
         &mut self,
@@ -876,7 +976,41 @@ impl GLES for GLES1OnGL2 {
     ) {
         gl21::GetRenderbufferParameterivEXT(target, pname, params)
     }
+    unsafe fn GetFramebufferAttachmentParameterivOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gl21::GetFramebufferAttachmentParameterivEXT(target, attachment, pname, params)
+    }
     unsafe fn CheckFramebufferStatusOES(&mut self, target: GLenum) -> GLenum {
         gl21::CheckFramebufferStatusEXT(target)
     }
+
+    // APPLE_framebuffer_multisample
+    unsafe fn RenderbufferStorageMultisampleAPPLE(
+        &mut self,
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        renderbuffer_storage_multisample(target, samples, internalformat, width, height)
+    }
+    unsafe fn ResolveMultisampleFramebufferAPPLE(&mut self) {
+        resolve_multisample_framebuffer()
+    }
+
+    // EXT_discard_framebuffer
+    unsafe fn DiscardFramebufferEXT(
+        &mut self,
+        target: GLenum,
+        num_attachments: GLsizei,
+        attachments: *const GLenum,
+    ) {
+        discard_framebuffer(target, num_attachments, attachments)
+    }
 }
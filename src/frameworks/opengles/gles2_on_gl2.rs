@@ -0,0 +1,1146 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Implementation of OpenGL ES 2.0 on top of OpenGL 2.1 compatibility profile.
+//!
+//! OpenGL ES 2.0 is based on OpenGL 2.0, minus the fixed-function pipeline,
+//! so nearly all of it maps directly onto OpenGL 2.1. Framebuffer objects,
+//! which are core in OpenGL ES 2.0, are provided by EXT_framebuffer_object.
+//!
+//! The main difference is the shading language: apps provide shaders written
+//! in GLSL ES 1.00, which desktop OpenGL doesn't accept, so they are
+//! translated to GLSL 1.20 (see [translate_shader_source]).
+//!
+//! Using the same kind of context as [super::gles1_on_gl2] means that EAGL can
+//! present frames the same way for both.
+
+use super::framebuffer::{
+    discard_framebuffer, renderbuffer_internal_format, renderbuffer_storage_multisample,
+    resolve_multisample_framebuffer,
+};
+use super::gles_generic::GLES2;
+use super::pvrtc::{compressed_tex_image_2d_pvrtc, get_compressed_texture_formats, PVRTC_FORMATS};
+use super::GLES;
+use crate::window::gl21compat as gl21;
+use crate::window::gl21compat::types::*;
+use crate::window::gles20;
+use crate::window::{GLContext, GLVersion, Window};
+
+/// List of capabilities shared by OpenGL ES 2.0 and OpenGL 2.1.
+const CAPABILITIES: &[GLenum] = &[
+    gl21::BLEND,
+    gl21::CULL_FACE,
+    gl21::DEPTH_TEST,
+    gl21::DITHER,
+    gl21::POLYGON_OFFSET_FILL,
+    gl21::SAMPLE_ALPHA_TO_COVERAGE,
+    gl21::SAMPLE_COVERAGE,
+    gl21::SCISSOR_TEST,
+    gl21::STENCIL_TEST,
+];
+
+/// List of blend factors shared by OpenGL ES 2.0 and OpenGL 2.1.
+/// `GL_SRC_ALPHA_SATURATE` is also allowed, but only as a source factor.
+const BLEND_FACTORS: &[GLenum] = &[
+    gl21::ZERO,
+    gl21::ONE,
+    gl21::SRC_COLOR,
+    gl21::ONE_MINUS_SRC_COLOR,
+    gl21::DST_COLOR,
+    gl21::ONE_MINUS_DST_COLOR,
+    gl21::SRC_ALPHA,
+    gl21::ONE_MINUS_SRC_ALPHA,
+    gl21::DST_ALPHA,
+    gl21::ONE_MINUS_DST_ALPHA,
+    gl21::CONSTANT_COLOR,
+    gl21::ONE_MINUS_CONSTANT_COLOR,
+    gl21::CONSTANT_ALPHA,
+    gl21::ONE_MINUS_CONSTANT_ALPHA,
+];
+
+const DRAW_MODES: &[GLenum] = &[
+    gl21::POINTS,
+    gl21::LINE_STRIP,
+    gl21::LINE_LOOP,
+    gl21::LINES,
+    gl21::TRIANGLE_STRIP,
+    gl21::TRIANGLE_FAN,
+    gl21::TRIANGLES,
+];
+
+const TEXTURE_TARGETS: &[GLenum] = &[gl21::TEXTURE_2D, gl21::TEXTURE_CUBE_MAP];
+
+const TEXTURE_IMAGE_TARGETS: &[GLenum] = &[
+    gl21::TEXTURE_2D,
+    gl21::TEXTURE_CUBE_MAP_POSITIVE_X,
+    gl21::TEXTURE_CUBE_MAP_NEGATIVE_X,
+    gl21::TEXTURE_CUBE_MAP_POSITIVE_Y,
+    gl21::TEXTURE_CUBE_MAP_NEGATIVE_Y,
+    gl21::TEXTURE_CUBE_MAP_POSITIVE_Z,
+    gl21::TEXTURE_CUBE_MAP_NEGATIVE_Z,
+];
+
+/// Called for OpenGL ES 1.1 functions, which an OpenGL ES 2.0 context doesn't
+/// have. Real iPhone OS ignores these, so we do too.
+fn not_in_gles2(name: &str) {
+    log!("Warning: {}() is not part of OpenGL ES 2.0, ignoring", name);
+}
+
+/// Translate shader source code from GLSL ES 1.00 to GLSL 1.20.
+///
+/// GLSL 1.20 is very nearly a superset of GLSL ES 1.00, so this only needs to
+/// remove what desktop GLSL doesn't understand: the `#version` directive,
+/// precision qualifiers, default precision statements and the
+/// `GL_OES_standard_derivatives` extension (which is built in). Removed text
+/// is replaced with spaces, so positions in compiler messages still match the
+/// app's source, except that lines are offset by the header added at the top.
+///
+/// This works line-by-line, so a default precision statement split across
+/// lines won't be removed.
+pub(super) fn translate_shader_source(source: &str) -> String {
+    let mut translated = String::from("#version 120\n#define GL_ES 1\n");
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if let Some(directive) = trimmed.strip_prefix('#') {
+            let directive = directive.trim_start();
+            if directive.starts_with("version")
+                || (directive.starts_with("extension")
+                    && directive.contains("GL_OES_standard_derivatives"))
+            {
+                translated.push_str(&blank_out(line));
+            } else {
+                translated.push_str(line);
+            }
+            continue;
+        }
+
+        // Comments are skipped so that their contents aren't mistaken for
+        // statements. Block comments aren't handled, but removing words from
+        // them is harmless.
+        let (code, comment) = match line.find("//") {
+            Some(comment_start) => line.split_at(comment_start),
+            None => (line, ""),
+        };
+        let mut rest = code;
+        while let Some(word_start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            translated.push_str(&rest[..word_start]);
+            rest = &rest[word_start..];
+            let word_end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let removed_len = match &rest[..word_end] {
+                "lowp" | "mediump" | "highp" => word_end,
+                "precision" => rest.find(';').map_or(0, |semicolon| semicolon + 1),
+                _ => 0,
+            };
+            if removed_len != 0 {
+                translated.push_str(&blank_out(&rest[..removed_len]));
+                rest = &rest[removed_len..];
+            } else {
+                translated.push_str(&rest[..word_end]);
+                rest = &rest[word_end..];
+            }
+        }
+        translated.push_str(rest);
+        translated.push_str(comment);
+    }
+    translated
+}
+
+/// Replace everything except line breaks with spaces.
+fn blank_out(text: &str) -> String {
+    text.chars()
+        .map(|c| if c == '\n' || c == '\r' { c } else { ' ' })
+        .collect()
+}
+
+pub struct GLES2OnGL2 {
+    gl_ctx: GLContext,
+}
+impl GLES for GLES2OnGL2 {
+    fn new(window: &mut Window, share_with: Option<&GLContext>) -> Self {
+        let gl_ctx = match share_with {
+            Some(share_with) => window.create_shared_gl_context(GLVersion::GL21Compat, share_with),
+            None => window.create_gl_context(GLVersion::GL21Compat),
+        };
+        Self { gl_ctx }
+    }
+
+    fn make_current(&self, window: &mut Window) {
+        window.make_gl_context_current(&self.gl_ctx);
+    }
+
+    fn gl_ctx(&self) -> &GLContext {
+        &self.gl_ctx
+    }
+
+    fn as_gles2(&mut self) -> Option<&mut dyn GLES2> {
+        Some(self)
+    }
+
+    // Generic state manipulation
+    unsafe fn GetError(&mut self) -> GLenum {
+        gl21::GetError()
+    }
+    unsafe fn Enable(&mut self, cap: GLenum) {
+        assert!(CAPABILITIES.contains(&cap));
+        gl21::Enable(cap);
+    }
+    unsafe fn Disable(&mut self, cap: GLenum) {
+        assert!(CAPABILITIES.contains(&cap));
+        gl21::Disable(cap);
+    }
+    unsafe fn EnableClientState(&mut self, _array: GLenum) {
+        not_in_gles2("glEnableClientState");
+    }
+    unsafe fn DisableClientState(&mut self, _array: GLenum) {
+        not_in_gles2("glDisableClientState");
+    }
+    unsafe fn GetIntegerv(&mut self, pname: GLenum, params: *mut GLint) {
+        if get_compressed_texture_formats(pname, params) {
+            return;
+        }
+
+        // OpenGL ES 2.0 counts these limits in vectors, OpenGL 2.1 counts them
+        // in components.
+        let components_pname = match pname {
+            gles20::MAX_VERTEX_UNIFORM_VECTORS => Some(gl21::MAX_VERTEX_UNIFORM_COMPONENTS),
+            gles20::MAX_FRAGMENT_UNIFORM_VECTORS => Some(gl21::MAX_FRAGMENT_UNIFORM_COMPONENTS),
+            gles20::MAX_VARYING_VECTORS => Some(gl21::MAX_VARYING_FLOATS),
+            _ => None,
+        };
+        if let Some(components_pname) = components_pname {
+            let mut components = 0;
+            gl21::GetIntegerv(components_pname, &mut components);
+            params.write(components / 4);
+            return;
+        }
+
+        // This function family can return a huge number of things.
+        // TODO: support more possible values.
+        assert!([
+            gl21::ACTIVE_TEXTURE,
+            gl21::ARRAY_BUFFER_BINDING,
+            gl21::CURRENT_PROGRAM,
+            gl21::ELEMENT_ARRAY_BUFFER_BINDING,
+            gl21::FRAMEBUFFER_BINDING_EXT,
+            gl21::MAX_COMBINED_TEXTURE_IMAGE_UNITS,
+            gl21::MAX_CUBE_MAP_TEXTURE_SIZE,
+            gl21::MAX_RENDERBUFFER_SIZE_EXT,
+            gl21::MAX_SAMPLES_EXT,
+            gl21::MAX_TEXTURE_IMAGE_UNITS,
+            gl21::MAX_TEXTURE_SIZE,
+            gl21::MAX_VERTEX_ATTRIBS,
+            gl21::MAX_VERTEX_TEXTURE_IMAGE_UNITS,
+            gl21::READ_FRAMEBUFFER_BINDING_EXT,
+            gl21::RENDERBUFFER_BINDING_EXT,
+            gl21::SCISSOR_BOX,
+            gl21::TEXTURE_BINDING_2D,
+            gl21::TEXTURE_BINDING_CUBE_MAP,
+            gl21::VIEWPORT,
+        ]
+        .contains(&pname));
+        gl21::GetIntegerv(pname, params);
+    }
+    unsafe fn Flush(&mut self) {
+        gl21::Flush()
+    }
+    unsafe fn Finish(&mut self) {
+        gl21::Finish()
+    }
+
+    // Other state manipulation
+    unsafe fn AlphaFunc(&mut self, _func: GLenum, _ref: GLclampf) {
+        not_in_gles2("glAlphaFunc");
+    }
+    unsafe fn AlphaFuncx(&mut self, _func: GLenum, _ref: GLclampx) {
+        not_in_gles2("glAlphaFuncx");
+    }
+    unsafe fn BlendFunc(&mut self, sfactor: GLenum, dfactor: GLenum) {
+        assert!(BLEND_FACTORS.contains(&sfactor) || sfactor == gl21::SRC_ALPHA_SATURATE);
+        assert!(BLEND_FACTORS.contains(&dfactor));
+        gl21::BlendFunc(sfactor, dfactor);
+    }
+    unsafe fn DepthMask(&mut self, flag: GLboolean) {
+        gl21::DepthMask(flag)
+    }
+    unsafe fn ShadeModel(&mut self, _mode: GLenum) {
+        not_in_gles2("glShadeModel");
+    }
+    unsafe fn Scissor(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        gl21::Scissor(x, y, width, height)
+    }
+    unsafe fn Viewport(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        gl21::Viewport(x, y, width, height)
+    }
+    unsafe fn LineWidth(&mut self, width: GLfloat) {
+        gl21::LineWidth(width)
+    }
+    unsafe fn LineWidthx(&mut self, _width: GLfixed) {
+        not_in_gles2("glLineWidthx");
+    }
+    unsafe fn PointSize(&mut self, _size: GLfloat) {
+        not_in_gles2("glPointSize");
+    }
+    unsafe fn PointSizex(&mut self, _size: GLfixed) {
+        not_in_gles2("glPointSizex");
+    }
+
+    // Lighting
+    unsafe fn Lightf(&mut self, _light: GLenum, _pname: GLenum, _param: GLfloat) {
+        not_in_gles2("glLightf");
+    }
+    unsafe fn Lightx(&mut self, _light: GLenum, _pname: GLenum, _param: GLfixed) {
+        not_in_gles2("glLightx");
+    }
+    unsafe fn Lightfv(&mut self, _light: GLenum, _pname: GLenum, _params: *const GLfloat) {
+        not_in_gles2("glLightfv");
+    }
+    unsafe fn Lightxv(&mut self, _light: GLenum, _pname: GLenum, _params: *const GLfixed) {
+        not_in_gles2("glLightxv");
+    }
+
+    // Buffers
+    unsafe fn GenBuffers(&mut self, n: GLsizei, buffers: *mut GLuint) {
+        gl21::GenBuffers(n, buffers)
+    }
+    unsafe fn DeleteBuffers(&mut self, n: GLsizei, buffers: *const GLuint) {
+        gl21::DeleteBuffers(n, buffers)
+    }
+    unsafe fn BindBuffer(&mut self, target: GLenum, buffer: GLuint) {
+        assert!(target == gl21::ARRAY_BUFFER || target == gl21::ELEMENT_ARRAY_BUFFER);
+        gl21::BindBuffer(target, buffer)
+    }
+    unsafe fn BufferData(
+        &mut self,
+        target: GLenum,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+        usage: GLenum,
+    ) {
+        assert!(target == gl21::ARRAY_BUFFER || target == gl21::ELEMENT_ARRAY_BUFFER);
+        assert!(
+            usage == gl21::STREAM_DRAW || usage == gl21::STATIC_DRAW || usage == gl21::DYNAMIC_DRAW
+        );
+        gl21::BufferData(target, size, data, usage)
+    }
+    unsafe fn BufferSubData(
+        &mut self,
+        target: GLenum,
+        offset: GLintptr,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+    ) {
+        assert!(target == gl21::ARRAY_BUFFER || target == gl21::ELEMENT_ARRAY_BUFFER);
+        gl21::BufferSubData(target, offset, size, data)
+    }
+
+    // Non-pointers
+    unsafe fn Color4f(&mut self, _red: GLfloat, _green: GLfloat, _blue: GLfloat, _alpha: GLfloat) {
+        not_in_gles2("glColor4f");
+    }
+    unsafe fn Color4x(&mut self, _red: GLfixed, _green: GLfixed, _blue: GLfixed, _alpha: GLfixed) {
+        not_in_gles2("glColor4x");
+    }
+
+    // Pointers
+    unsafe fn ColorPointer(
+        &mut self,
+        _size: GLint,
+        _type: GLenum,
+        _stride: GLsizei,
+        _pointer: *const GLvoid,
+    ) {
+        not_in_gles2("glColorPointer");
+    }
+    unsafe fn NormalPointer(&mut self, _type: GLenum, _stride: GLsizei, _pointer: *const GLvoid) {
+        not_in_gles2("glNormalPointer");
+    }
+    unsafe fn TexCoordPointer(
+        &mut self,
+        _size: GLint,
+        _type: GLenum,
+        _stride: GLsizei,
+        _pointer: *const GLvoid,
+    ) {
+        not_in_gles2("glTexCoordPointer");
+    }
+    unsafe fn VertexPointer(
+        &mut self,
+        _size: GLint,
+        _type: GLenum,
+        _stride: GLsizei,
+        _pointer: *const GLvoid,
+    ) {
+        not_in_gles2("glVertexPointer");
+    }
+
+    // Drawing
+    unsafe fn DrawArrays(&mut self, mode: GLenum, first: GLint, count: GLsizei) {
+        assert!(DRAW_MODES.contains(&mode));
+        gl21::DrawArrays(mode, first, count)
+    }
+    unsafe fn DrawElements(
+        &mut self,
+        mode: GLenum,
+        count: GLsizei,
+        type_: GLenum,
+        indices: *const GLvoid,
+    ) {
+        assert!(DRAW_MODES.contains(&mode));
+        assert!(type_ == gl21::UNSIGNED_BYTE || type_ == gl21::UNSIGNED_SHORT);
+        gl21::DrawElements(mode, count, type_, indices)
+    }
+
+    // Clearing
+    unsafe fn Clear(&mut self, mask: GLbitfield) {
+        assert!(
+            mask & !(gl21::COLOR_BUFFER_BIT | gl21::DEPTH_BUFFER_BIT | gl21::STENCIL_BUFFER_BIT)
+                == 0
+        );
+        gl21::Clear(mask)
+    }
+    unsafe fn ClearColor(
+        &mut self,
+        red: GLclampf,
+        green: GLclampf,
+        blue: GLclampf,
+        alpha: GLclampf,
+    ) {
+        gl21::ClearColor(red, green, blue, alpha)
+    }
+    unsafe fn ClearColorx(
+        &mut self,
+        _red: GLclampx,
+        _green: GLclampx,
+        _blue: GLclampx,
+        _alpha: GLclampx,
+    ) {
+        not_in_gles2("glClearColorx");
+    }
+    unsafe fn ClearDepthf(&mut self, depth: GLclampf) {
+        gl21::ClearDepth(depth.into())
+    }
+    unsafe fn ClearDepthx(&mut self, _depth: GLclampx) {
+        not_in_gles2("glClearDepthx");
+    }
+    unsafe fn ClearStencil(&mut self, s: GLint) {
+        gl21::ClearStencil(s)
+    }
+
+    // Textures
+    unsafe fn ActiveTexture(&mut self, texture: GLenum) {
+        gl21::ActiveTexture(texture)
+    }
+    unsafe fn GenTextures(&mut self, n: GLsizei, textures: *mut GLuint) {
+        gl21::GenTextures(n, textures)
+    }
+    unsafe fn DeleteTextures(&mut self, n: GLsizei, textures: *const GLuint) {
+        gl21::DeleteTextures(n, textures)
+    }
+    unsafe fn BindTexture(&mut self, target: GLenum, texture: GLuint) {
+        assert!(TEXTURE_TARGETS.contains(&target));
+        gl21::BindTexture(target, texture)
+    }
+    unsafe fn TexParameteri(&mut self, target: GLenum, pname: GLenum, param: GLint) {
+        assert!(TEXTURE_TARGETS.contains(&target));
+        assert!(
+            pname == gl21::TEXTURE_MIN_FILTER
+                || pname == gl21::TEXTURE_MAG_FILTER
+                || pname == gl21::TEXTURE_WRAP_S
+                || pname == gl21::TEXTURE_WRAP_T
+        );
+        gl21::TexParameteri(target, pname, param);
+    }
+    unsafe fn TexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+        format: GLenum,
+        type_: GLenum,
+        pixels: *const GLvoid,
+    ) {
+        assert!(TEXTURE_IMAGE_TARGETS.contains(&target));
+        assert!(level >= 0);
+        assert!(
+            internalformat as GLenum == gl21::ALPHA
+                || internalformat as GLenum == gl21::RGB
+                || internalformat as GLenum == gl21::RGBA
+                || internalformat as GLenum == gl21::LUMINANCE
+                || internalformat as GLenum == gl21::LUMINANCE_ALPHA
+        );
+        assert!(border == 0);
+        assert!(
+            format == gl21::ALPHA
+                || format == gl21::RGB
+                || format == gl21::RGBA
+                || format == gl21::LUMINANCE
+                || format == gl21::LUMINANCE_ALPHA
+        );
+        assert!(
+            type_ == gl21::UNSIGNED_BYTE
+                || type_ == gl21::UNSIGNED_SHORT_5_6_5
+                || type_ == gl21::UNSIGNED_SHORT_4_4_4_4
+                || type_ == gl21::UNSIGNED_SHORT_5_5_5_1
+        );
+        gl21::TexImage2D(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            format,
+            type_,
+            pixels,
+        )
+    }
+
+    unsafe fn CompressedTexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+        image_size: GLsizei,
+        data: *const GLvoid,
+    ) {
+        assert!(TEXTURE_IMAGE_TARGETS.contains(&target));
+        // TODO: paletted texture formats (OES_compressed_paletted_texture)
+        if !PVRTC_FORMATS.contains(&internalformat) {
+            unimplemented!("Compressed texture format {:#x}", internalformat);
+        }
+        compressed_tex_image_2d_pvrtc(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            image_size,
+            data,
+        )
+    }
+
+    // Matrix stack operations
+    unsafe fn MatrixMode(&mut self, _mode: GLenum) {
+        not_in_gles2("glMatrixMode");
+    }
+    unsafe fn LoadIdentity(&mut self) {
+        not_in_gles2("glLoadIdentity");
+    }
+    unsafe fn LoadMatrixf(&mut self, _m: *const GLfloat) {
+        not_in_gles2("glLoadMatrixf");
+    }
+    unsafe fn LoadMatrixx(&mut self, _m: *const GLfixed) {
+        not_in_gles2("glLoadMatrixx");
+    }
+    unsafe fn MultMatrixf(&mut self, _m: *const GLfloat) {
+        not_in_gles2("glMultMatrixf");
+    }
+    unsafe fn MultMatrixx(&mut self, _m: *const GLfixed) {
+        not_in_gles2("glMultMatrixx");
+    }
+    unsafe fn PushMatrix(&mut self) {
+        not_in_gles2("glPushMatrix");
+    }
+    unsafe fn PopMatrix(&mut self) {
+        not_in_gles2("glPopMatrix");
+    }
+    unsafe fn Orthof(
+        &mut self,
+        _left: GLfloat,
+        _right: GLfloat,
+        _bottom: GLfloat,
+        _top: GLfloat,
+        _near: GLfloat,
+        _far: GLfloat,
+    ) {
+        not_in_gles2("glOrthof");
+    }
+    unsafe fn Orthox(
+        &mut self,
+        _left: GLfixed,
+        _right: GLfixed,
+        _bottom: GLfixed,
+        _top: GLfixed,
+        _near: GLfixed,
+        _far: GLfixed,
+    ) {
+        not_in_gles2("glOrthox");
+    }
+    unsafe fn Frustumf(
+        &mut self,
+        _left: GLfloat,
+        _right: GLfloat,
+        _bottom: GLfloat,
+        _top: GLfloat,
+        _near: GLfloat,
+        _far: GLfloat,
+    ) {
+        not_in_gles2("glFrustumf");
+    }
+    unsafe fn Frustumx(
+        &mut self,
+        _left: GLfixed,
+        _right: GLfixed,
+        _bottom: GLfixed,
+        _top: GLfixed,
+        _near: GLfixed,
+        _far: GLfixed,
+    ) {
+        not_in_gles2("glFrustumx");
+    }
+    unsafe fn Rotatef(&mut self, _angle: GLfloat, _x: GLfloat, _y: GLfloat, _z: GLfloat) {
+        not_in_gles2("glRotatef");
+    }
+    unsafe fn Rotatex(&mut self, _angle: GLfixed, _x: GLfixed, _y: GLfixed, _z: GLfixed) {
+        not_in_gles2("glRotatex");
+    }
+    unsafe fn Scalef(&mut self, _x: GLfloat, _y: GLfloat, _z: GLfloat) {
+        not_in_gles2("glScalef");
+    }
+    unsafe fn Scalex(&mut self, _x: GLfixed, _y: GLfixed, _z: GLfixed) {
+        not_in_gles2("glScalex");
+    }
+    unsafe fn Translatef(&mut self, _x: GLfloat, _y: GLfloat, _z: GLfloat) {
+        not_in_gles2("glTranslatef");
+    }
+    unsafe fn Translatex(&mut self, _x: GLfixed, _y: GLfixed, _z: GLfixed) {
+        not_in_gles2("glTranslatex");
+    }
+
+    // OES_framebuffer_object -> EXT_framebuffer_object
+    // This extension is not available in OpenGL ES 2.0, where the same thing
+    // is core functionality, but EAGL uses it internally.
+    unsafe fn GenFramebuffersOES(&mut self, n: GLsizei, framebuffers: *mut GLuint) {
+        gl21::GenFramebuffersEXT(n, framebuffers)
+    }
+    unsafe fn GenRenderbuffersOES(&mut self, n: GLsizei, renderbuffers: *mut GLuint) {
+        gl21::GenRenderbuffersEXT(n, renderbuffers)
+    }
+    unsafe fn BindFramebufferOES(&mut self, target: GLenum, framebuffer: GLuint) {
+        gl21::BindFramebufferEXT(target, framebuffer)
+    }
+    unsafe fn BindRenderbufferOES(&mut self, target: GLenum, renderbuffer: GLuint) {
+        gl21::BindRenderbufferEXT(target, renderbuffer)
+    }
+    unsafe fn RenderbufferStorageOES(
+        &mut self,
+        target: GLenum,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        gl21::RenderbufferStorageEXT(
+            target,
+            renderbuffer_internal_format(internalformat),
+            width,
+            height,
+        )
+    }
+    unsafe fn FramebufferRenderbufferOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        renderbuffertarget: GLenum,
+        renderbuffer: GLuint,
+    ) {
+        gl21::FramebufferRenderbufferEXT(target, attachment, renderbuffertarget, renderbuffer)
+    }
+    unsafe fn GetRenderbufferParameterivOES(
+        &mut self,
+        target: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gl21::GetRenderbufferParameterivEXT(target, pname, params)
+    }
+    unsafe fn GetFramebufferAttachmentParameterivOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gl21::GetFramebufferAttachmentParameterivEXT(target, attachment, pname, params)
+    }
+    unsafe fn CheckFramebufferStatusOES(&mut self, target: GLenum) -> GLenum {
+        gl21::CheckFramebufferStatusEXT(target)
+    }
+
+    // APPLE_framebuffer_multisample
+    unsafe fn RenderbufferStorageMultisampleAPPLE(
+        &mut self,
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        renderbuffer_storage_multisample(target, samples, internalformat, width, height)
+    }
+    unsafe fn ResolveMultisampleFramebufferAPPLE(&mut self) {
+        resolve_multisample_framebuffer()
+    }
+
+    // EXT_discard_framebuffer
+    unsafe fn DiscardFramebufferEXT(
+        &mut self,
+        target: GLenum,
+        num_attachments: GLsizei,
+        attachments: *const GLenum,
+    ) {
+        discard_framebuffer(target, num_attachments, attachments)
+    }
+}
+impl GLES2 for GLES2OnGL2 {
+    // Framebuffer objects -> EXT_framebuffer_object
+    unsafe fn GenFramebuffers(&mut self, n: GLsizei, framebuffers: *mut GLuint) {
+        gl21::GenFramebuffersEXT(n, framebuffers)
+    }
+    unsafe fn DeleteFramebuffers(&mut self, n: GLsizei, framebuffers: *const GLuint) {
+        gl21::DeleteFramebuffersEXT(n, framebuffers)
+    }
+    unsafe fn BindFramebuffer(&mut self, target: GLenum, framebuffer: GLuint) {
+        // Separate read and draw framebuffers are from
+        // GL_APPLE_framebuffer_multisample.
+        assert!([
+            gl21::FRAMEBUFFER_EXT,
+            gl21::READ_FRAMEBUFFER_EXT,
+            gl21::DRAW_FRAMEBUFFER_EXT
+        ]
+        .contains(&target));
+        gl21::BindFramebufferEXT(target, framebuffer)
+    }
+    unsafe fn GenRenderbuffers(&mut self, n: GLsizei, renderbuffers: *mut GLuint) {
+        gl21::GenRenderbuffersEXT(n, renderbuffers)
+    }
+    unsafe fn DeleteRenderbuffers(&mut self, n: GLsizei, renderbuffers: *const GLuint) {
+        gl21::DeleteRenderbuffersEXT(n, renderbuffers)
+    }
+    unsafe fn BindRenderbuffer(&mut self, target: GLenum, renderbuffer: GLuint) {
+        assert!(target == gl21::RENDERBUFFER_EXT);
+        gl21::BindRenderbufferEXT(target, renderbuffer)
+    }
+    unsafe fn RenderbufferStorage(
+        &mut self,
+        target: GLenum,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        assert!(target == gl21::RENDERBUFFER_EXT);
+        gl21::RenderbufferStorageEXT(
+            target,
+            renderbuffer_internal_format(internalformat),
+            width,
+            height,
+        )
+    }
+    unsafe fn FramebufferRenderbuffer(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        renderbuffertarget: GLenum,
+        renderbuffer: GLuint,
+    ) {
+        gl21::FramebufferRenderbufferEXT(target, attachment, renderbuffertarget, renderbuffer)
+    }
+    unsafe fn FramebufferTexture2D(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        textarget: GLenum,
+        texture: GLuint,
+        level: GLint,
+    ) {
+        assert!(TEXTURE_IMAGE_TARGETS.contains(&textarget));
+        // OpenGL ES 2.0 only allows attaching the base level.
+        assert!(level == 0);
+        gl21::FramebufferTexture2DEXT(target, attachment, textarget, texture, level)
+    }
+    unsafe fn GetRenderbufferParameteriv(
+        &mut self,
+        target: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gl21::GetRenderbufferParameterivEXT(target, pname, params)
+    }
+    unsafe fn GetFramebufferAttachmentParameteriv(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gl21::GetFramebufferAttachmentParameterivEXT(target, attachment, pname, params)
+    }
+    unsafe fn CheckFramebufferStatus(&mut self, target: GLenum) -> GLenum {
+        gl21::CheckFramebufferStatusEXT(target)
+    }
+    unsafe fn GenerateMipmap(&mut self, target: GLenum) {
+        assert!(TEXTURE_TARGETS.contains(&target));
+        gl21::GenerateMipmapEXT(target)
+    }
+
+    // Shaders
+    unsafe fn CreateShader(&mut self, type_: GLenum) -> GLuint {
+        assert!(type_ == gl21::VERTEX_SHADER || type_ == gl21::FRAGMENT_SHADER);
+        gl21::CreateShader(type_)
+    }
+    unsafe fn DeleteShader(&mut self, shader: GLuint) {
+        gl21::DeleteShader(shader)
+    }
+    unsafe fn ShaderSource(
+        &mut self,
+        shader: GLuint,
+        count: GLsizei,
+        string: *const *const GLchar,
+        length: *const GLint,
+    ) {
+        // The strings are concatenated first, since a construct that needs
+        // translating could be split between them.
+        let count: usize = count.try_into().unwrap();
+        let mut source = Vec::<u8>::new();
+        for i in 0..count {
+            let string: *const u8 = string.add(i).read().cast();
+            let length = if length.is_null() {
+                -1
+            } else {
+                length.add(i).read()
+            };
+            if length < 0 {
+                source.extend_from_slice(std::ffi::CStr::from_ptr(string.cast()).to_bytes());
+            } else {
+                source.extend_from_slice(std::slice::from_raw_parts(string, length as usize));
+            }
+        }
+        let source = translate_shader_source(&String::from_utf8_lossy(&source));
+        log_dbg!("Translated shader {} source: {}", shader, source);
+
+        let string: *const GLchar = source.as_ptr().cast();
+        let length: GLint = source.len().try_into().unwrap();
+        gl21::ShaderSource(shader, 1, &string, &length)
+    }
+    unsafe fn CompileShader(&mut self, shader: GLuint) {
+        gl21::CompileShader(shader);
+
+        let mut status = 0;
+        gl21::GetShaderiv(shader, gl21::COMPILE_STATUS, &mut status);
+        if status != GLint::from(gl21::TRUE) {
+            let mut log_length = 0;
+            gl21::GetShaderiv(shader, gl21::INFO_LOG_LENGTH, &mut log_length);
+            let mut log = vec![0u8; log_length.max(1) as usize];
+            gl21::GetShaderInfoLog(
+                shader,
+                log_length,
+                std::ptr::null_mut(),
+                log.as_mut_ptr().cast(),
+            );
+            log!(
+                "Warning: compiling translated shader {} failed: {}",
+                shader,
+                String::from_utf8_lossy(&log).trim_end_matches('\0')
+            );
+        }
+    }
+    unsafe fn GetShaderiv(&mut self, shader: GLuint, pname: GLenum, params: *mut GLint) {
+        assert!([
+            gl21::SHADER_TYPE,
+            gl21::DELETE_STATUS,
+            gl21::COMPILE_STATUS,
+            gl21::INFO_LOG_LENGTH,
+            gl21::SHADER_SOURCE_LENGTH,
+        ]
+        .contains(&pname));
+        gl21::GetShaderiv(shader, pname, params)
+    }
+    unsafe fn GetShaderInfoLog(
+        &mut self,
+        shader: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        gl21::GetShaderInfoLog(shader, buf_size, length, info_log)
+    }
+
+    // Programs
+    unsafe fn CreateProgram(&mut self) -> GLuint {
+        gl21::CreateProgram()
+    }
+    unsafe fn DeleteProgram(&mut self, program: GLuint) {
+        gl21::DeleteProgram(program)
+    }
+    unsafe fn AttachShader(&mut self, program: GLuint, shader: GLuint) {
+        gl21::AttachShader(program, shader)
+    }
+    unsafe fn DetachShader(&mut self, program: GLuint, shader: GLuint) {
+        gl21::DetachShader(program, shader)
+    }
+    unsafe fn LinkProgram(&mut self, program: GLuint) {
+        gl21::LinkProgram(program)
+    }
+    unsafe fn ValidateProgram(&mut self, program: GLuint) {
+        gl21::ValidateProgram(program)
+    }
+    unsafe fn UseProgram(&mut self, program: GLuint) {
+        gl21::UseProgram(program)
+    }
+    unsafe fn GetProgramiv(&mut self, program: GLuint, pname: GLenum, params: *mut GLint) {
+        assert!([
+            gl21::DELETE_STATUS,
+            gl21::LINK_STATUS,
+            gl21::VALIDATE_STATUS,
+            gl21::INFO_LOG_LENGTH,
+            gl21::ATTACHED_SHADERS,
+            gl21::ACTIVE_ATTRIBUTES,
+            gl21::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+            gl21::ACTIVE_UNIFORMS,
+            gl21::ACTIVE_UNIFORM_MAX_LENGTH,
+        ]
+        .contains(&pname));
+        gl21::GetProgramiv(program, pname, params)
+    }
+    unsafe fn GetProgramInfoLog(
+        &mut self,
+        program: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        gl21::GetProgramInfoLog(program, buf_size, length, info_log)
+    }
+    unsafe fn BindAttribLocation(&mut self, program: GLuint, index: GLuint, name: *const GLchar) {
+        gl21::BindAttribLocation(program, index, name)
+    }
+    unsafe fn GetAttribLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        gl21::GetAttribLocation(program, name)
+    }
+    unsafe fn GetUniformLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        gl21::GetUniformLocation(program, name)
+    }
+    unsafe fn GetActiveAttrib(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    ) {
+        gl21::GetActiveAttrib(program, index, buf_size, length, size, type_, name)
+    }
+    unsafe fn GetActiveUniform(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    ) {
+        gl21::GetActiveUniform(program, index, buf_size, length, size, type_, name)
+    }
+
+    // Uniforms
+    unsafe fn Uniform1i(&mut self, location: GLint, v0: GLint) {
+        gl21::Uniform1i(location, v0)
+    }
+    unsafe fn Uniform2i(&mut self, location: GLint, v0: GLint, v1: GLint) {
+        gl21::Uniform2i(location, v0, v1)
+    }
+    unsafe fn Uniform3i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint) {
+        gl21::Uniform3i(location, v0, v1, v2)
+    }
+    unsafe fn Uniform4i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint, v3: GLint) {
+        gl21::Uniform4i(location, v0, v1, v2, v3)
+    }
+    unsafe fn Uniform1f(&mut self, location: GLint, v0: GLfloat) {
+        gl21::Uniform1f(location, v0)
+    }
+    unsafe fn Uniform2f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat) {
+        gl21::Uniform2f(location, v0, v1)
+    }
+    unsafe fn Uniform3f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat) {
+        gl21::Uniform3f(location, v0, v1, v2)
+    }
+    unsafe fn Uniform4f(
+        &mut self,
+        location: GLint,
+        v0: GLfloat,
+        v1: GLfloat,
+        v2: GLfloat,
+        v3: GLfloat,
+    ) {
+        gl21::Uniform4f(location, v0, v1, v2, v3)
+    }
+    unsafe fn Uniform1iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gl21::Uniform1iv(location, count, value)
+    }
+    unsafe fn Uniform2iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gl21::Uniform2iv(location, count, value)
+    }
+    unsafe fn Uniform3iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gl21::Uniform3iv(location, count, value)
+    }
+    unsafe fn Uniform4iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gl21::Uniform4iv(location, count, value)
+    }
+    unsafe fn Uniform1fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gl21::Uniform1fv(location, count, value)
+    }
+    unsafe fn Uniform2fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gl21::Uniform2fv(location, count, value)
+    }
+    unsafe fn Uniform3fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gl21::Uniform3fv(location, count, value)
+    }
+    unsafe fn Uniform4fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gl21::Uniform4fv(location, count, value)
+    }
+    unsafe fn UniformMatrix2fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        // OpenGL ES 2.0 doesn't allow transposition.
+        assert!(transpose == gl21::FALSE);
+        gl21::UniformMatrix2fv(location, count, transpose, value)
+    }
+    unsafe fn UniformMatrix3fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        assert!(transpose == gl21::FALSE);
+        gl21::UniformMatrix3fv(location, count, transpose, value)
+    }
+    unsafe fn UniformMatrix4fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        assert!(transpose == gl21::FALSE);
+        gl21::UniformMatrix4fv(location, count, transpose, value)
+    }
+
+    // Generic vertex attributes
+    unsafe fn EnableVertexAttribArray(&mut self, index: GLuint) {
+        gl21::EnableVertexAttribArray(index)
+    }
+    unsafe fn DisableVertexAttribArray(&mut self, index: GLuint) {
+        gl21::DisableVertexAttribArray(index)
+    }
+    unsafe fn VertexAttribPointer(
+        &mut self,
+        index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        normalized: GLboolean,
+        stride: GLsizei,
+        pointer: *const GLvoid,
+    ) {
+        assert!((1..=4).contains(&size));
+        // TODO: GL_FIXED, which OpenGL 2.1 doesn't have
+        assert!([
+            gl21::BYTE,
+            gl21::UNSIGNED_BYTE,
+            gl21::SHORT,
+            gl21::UNSIGNED_SHORT,
+            gl21::FLOAT,
+        ]
+        .contains(&type_));
+        gl21::VertexAttribPointer(index, size, type_, normalized, stride, pointer)
+    }
+    unsafe fn VertexAttrib1f(&mut self, index: GLuint, x: GLfloat) {
+        gl21::VertexAttrib1f(index, x)
+    }
+    unsafe fn VertexAttrib2f(&mut self, index: GLuint, x: GLfloat, y: GLfloat) {
+        gl21::VertexAttrib2f(index, x, y)
+    }
+    unsafe fn VertexAttrib3f(&mut self, index: GLuint, x: GLfloat, y: GLfloat, z: GLfloat) {
+        gl21::VertexAttrib3f(index, x, y, z)
+    }
+    unsafe fn VertexAttrib4f(
+        &mut self,
+        index: GLuint,
+        x: GLfloat,
+        y: GLfloat,
+        z: GLfloat,
+        w: GLfloat,
+    ) {
+        gl21::VertexAttrib4f(index, x, y, z, w)
+    }
+    unsafe fn VertexAttrib4fv(&mut self, index: GLuint, v: *const GLfloat) {
+        gl21::VertexAttrib4fv(index, v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_shader_source;
+
+    #[test]
+    fn translate_precision() {
+        let source = "#version 100\n\
+                      precision mediump float;\n\
+                      uniform lowp sampler2D tex; // highp\n\
+                      varying highp vec2 uv;\n\
+                      void main() { gl_FragColor = texture2D(tex, uv); }\n";
+        let translated = translate_shader_source(source);
+        let mut lines = translated.lines();
+        assert_eq!(lines.next(), Some("#version 120"));
+        assert_eq!(lines.next(), Some("#define GL_ES 1"));
+        assert_eq!(lines.next().unwrap().trim(), "");
+        assert_eq!(lines.next().unwrap().trim(), "");
+        assert_eq!(
+            lines.next().unwrap().split_whitespace().collect::<Vec<_>>(),
+            ["uniform", "sampler2D", "tex;", "//", "highp"]
+        );
+        assert_eq!(
+            lines.next().unwrap().split_whitespace().collect::<Vec<_>>(),
+            ["varying", "vec2", "uv;"]
+        );
+        assert_eq!(
+            lines.next(),
+            Some("void main() { gl_FragColor = texture2D(tex, uv); }")
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn translate_keeps_similar_identifiers() {
+        let source = "uniform float highpass;\nfloat precision_x = mediump_value;\n";
+        let translated = translate_shader_source(source);
+        assert!(translated.ends_with(source));
+    }
+
+    #[test]
+    fn translate_extension() {
+        let source = "#extension GL_OES_standard_derivatives : enable\n\
+                      #ifdef GL_ES\n\
+                      #endif\n";
+        let translated = translate_shader_source(source);
+        assert!(!translated.contains("GL_OES_standard_derivatives"));
+        assert!(translated.ends_with("#ifdef GL_ES\n#endif\n"));
+    }
+}
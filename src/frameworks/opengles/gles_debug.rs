@@ -0,0 +1,1849 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Debugging layer for OpenGL ES, enabled with the `--gles-debug` option.
+//!
+//! [GLESDebug] wraps another implementation and:
+//! - logs every call with its arguments,
+//! - checks for errors after every call, while still reporting them to the app
+//!   through `glGetError()`,
+//! - skips calls with some kinds of invalid arguments, recording an error like
+//!   a real implementation would, rather than letting them reach the
+//!   implementation (which would usually panic),
+//! - warns about suspicious state when drawing,
+//! - warns about things that would behave differently on the PowerVR MBX GPU
+//!   of the original iPhone and iPod touch, which only supports OpenGL ES 1.1.
+//!
+//! The argument checks cover a small part of the OpenGL ES 1.1 specification.
+//! Most of them don't apply to OpenGL ES 2.0 contexts, which are only traced.
+
+use super::gles1_on_gl2;
+use super::gles_generic::GLES2;
+use super::GLES;
+use crate::window::gles11;
+use crate::window::gles11::types::*;
+use crate::window::{GLContext, Window};
+use std::fmt;
+
+const DRAW_MODES: &[GLenum] = &[
+    gles11::POINTS,
+    gles11::LINE_STRIP,
+    gles11::LINE_LOOP,
+    gles11::LINES,
+    gles11::TRIANGLE_STRIP,
+    gles11::TRIANGLE_FAN,
+    gles11::TRIANGLES,
+];
+
+const BLEND_SFACTORS: &[GLenum] = &[
+    gles11::ZERO,
+    gles11::ONE,
+    gles11::DST_COLOR,
+    gles11::ONE_MINUS_DST_COLOR,
+    gles11::SRC_ALPHA,
+    gles11::ONE_MINUS_SRC_ALPHA,
+    gles11::DST_ALPHA,
+    gles11::ONE_MINUS_DST_ALPHA,
+    gles11::SRC_ALPHA_SATURATE,
+];
+
+const BLEND_DFACTORS: &[GLenum] = &[
+    gles11::ZERO,
+    gles11::ONE,
+    gles11::SRC_COLOR,
+    gles11::ONE_MINUS_SRC_COLOR,
+    gles11::SRC_ALPHA,
+    gles11::ONE_MINUS_SRC_ALPHA,
+    gles11::DST_ALPHA,
+    gles11::ONE_MINUS_DST_ALPHA,
+];
+
+const TEXTURE_FORMATS: &[GLenum] = &[
+    gles11::ALPHA,
+    gles11::RGB,
+    gles11::RGBA,
+    gles11::LUMINANCE,
+    gles11::LUMINANCE_ALPHA,
+];
+
+const TEXTURE_TYPES: &[GLenum] = &[
+    gles11::UNSIGNED_BYTE,
+    gles11::UNSIGNED_SHORT_5_6_5,
+    gles11::UNSIGNED_SHORT_4_4_4_4,
+    gles11::UNSIGNED_SHORT_5_5_5_1,
+];
+
+/// The largest texture the MBX supports.
+const MBX_MAX_TEXTURE_SIZE: GLsizei = 1024;
+
+/// The packed pixel types can only be used with one format.
+fn texture_type_matches_format(type_: GLenum, format: GLenum) -> bool {
+    match type_ {
+        gles11::UNSIGNED_SHORT_5_6_5 => format == gles11::RGB,
+        gles11::UNSIGNED_SHORT_4_4_4_4 | gles11::UNSIGNED_SHORT_5_5_5_1 => format == gles11::RGBA,
+        _ => true,
+    }
+}
+
+pub struct GLESDebug<T> {
+    inner: T,
+    is_gles1: bool,
+    /// The first error not yet retrieved by the app with `glGetError()`.
+    pending_error: GLenum,
+    /// Arrays enabled with `glEnableClientState`.
+    enabled_arrays: Vec<GLenum>,
+}
+impl<T: GLES> GLESDebug<T> {
+    fn trace(&self, name: &str, args: fmt::Arguments) {
+        log!("{}({})", name, args);
+    }
+
+    fn record_error(&mut self, error: GLenum) {
+        if self.pending_error == gles11::NO_ERROR {
+            self.pending_error = error;
+        }
+    }
+
+    /// Skip an invalid call and record an error, like a real implementation.
+    fn reject(&mut self, error: GLenum, name: &str, reason: fmt::Arguments) {
+        log!(
+            "Warning: invalid {}() call skipped, generating error {:#x}: {}",
+            name,
+            error,
+            reason
+        );
+        self.record_error(error);
+    }
+
+    fn mbx_warning(&self, name: &str, reason: fmt::Arguments) {
+        log!(
+            "Warning: {}() call would behave differently on an MBX GPU: {}",
+            name,
+            reason
+        );
+    }
+
+    unsafe fn check_errors(&mut self, name: &str) {
+        loop {
+            let error = self.inner.GetError();
+            if error == gles11::NO_ERROR {
+                break;
+            }
+            log!("Warning: {}() generated error {:#x}", name, error);
+            self.record_error(error);
+        }
+    }
+
+    unsafe fn check_draw_state(&mut self, name: &str) {
+        if self.is_gles1 && !self.enabled_arrays.contains(&gles11::VERTEX_ARRAY) {
+            log!(
+                "Warning: {}() called without GL_VERTEX_ARRAY enabled, nothing will be drawn",
+                name
+            );
+        }
+        let status = self
+            .inner
+            .CheckFramebufferStatusOES(gles11::FRAMEBUFFER_OES);
+        if status != gles11::FRAMEBUFFER_COMPLETE_OES {
+            log!(
+                "Warning: {}() called with an incomplete framebuffer (status {:#x})",
+                name,
+                status
+            );
+        }
+    }
+
+    fn check_mbx_texture_size(&self, name: &str, width: GLsizei, height: GLsizei) {
+        if width > MBX_MAX_TEXTURE_SIZE || height > MBX_MAX_TEXTURE_SIZE {
+            self.mbx_warning(
+                name,
+                format_args!(
+                    "{}×{} is bigger than the maximum texture size, {}×{}",
+                    width, height, MBX_MAX_TEXTURE_SIZE, MBX_MAX_TEXTURE_SIZE
+                ),
+            );
+        }
+        if (width as u32).count_ones() > 1 || (height as u32).count_ones() > 1 {
+            self.mbx_warning(
+                name,
+                format_args!(
+                    "{}×{} is not a power-of-two size, which is required",
+                    width, height
+                ),
+            );
+        }
+    }
+}
+impl<T: GLES> GLES for GLESDebug<T> {
+    fn new(window: &mut Window, share_with: Option<&GLContext>) -> Self {
+        let mut inner = T::new(window, share_with);
+        let is_gles1 = inner.as_gles2().is_none();
+        Self {
+            inner,
+            is_gles1,
+            pending_error: gles11::NO_ERROR,
+            enabled_arrays: Vec::new(),
+        }
+    }
+
+    fn make_current(&self, window: &mut Window) {
+        self.inner.make_current(window);
+    }
+
+    fn gl_ctx(&self) -> &GLContext {
+        self.inner.gl_ctx()
+    }
+
+    fn as_gles2(&mut self) -> Option<&mut dyn GLES2> {
+        if self.is_gles1 {
+            None
+        } else {
+            Some(self)
+        }
+    }
+
+    // Generic state manipulation
+    unsafe fn GetError(&mut self) -> GLenum {
+        self.trace("glGetError", format_args!(""));
+        // Errors are always retrieved from the wrapped implementation right
+        // after each call, so the only one left to report is the recorded one.
+        std::mem::replace(&mut self.pending_error, gles11::NO_ERROR)
+    }
+    unsafe fn Enable(&mut self, cap: GLenum) {
+        self.trace("glEnable", format_args!("{:#x}", cap));
+        if self.is_gles1 && !gles1_on_gl2::CAPABILITIES.contains(&cap) {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glEnable",
+                format_args!("{:#x} is not a capability", cap),
+            );
+        }
+        if self.is_gles1 && cap == gles11::STENCIL_TEST {
+            self.mbx_warning("glEnable", format_args!("the MBX has no stencil buffer"));
+        }
+        self.inner.Enable(cap);
+        self.check_errors("glEnable");
+    }
+    unsafe fn Disable(&mut self, cap: GLenum) {
+        self.trace("glDisable", format_args!("{:#x}", cap));
+        if self.is_gles1 && !gles1_on_gl2::CAPABILITIES.contains(&cap) {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glDisable",
+                format_args!("{:#x} is not a capability", cap),
+            );
+        }
+        self.inner.Disable(cap);
+        self.check_errors("glDisable");
+    }
+    unsafe fn EnableClientState(&mut self, array: GLenum) {
+        self.trace("glEnableClientState", format_args!("{:#x}", array));
+        if self.is_gles1 && !gles1_on_gl2::ARRAYS.iter().any(|info| info.name == array) {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glEnableClientState",
+                format_args!("{:#x} is not an array", array),
+            );
+        }
+        if !self.enabled_arrays.contains(&array) {
+            self.enabled_arrays.push(array);
+        }
+        self.inner.EnableClientState(array);
+        self.check_errors("glEnableClientState");
+    }
+    unsafe fn DisableClientState(&mut self, array: GLenum) {
+        self.trace("glDisableClientState", format_args!("{:#x}", array));
+        if self.is_gles1 && !gles1_on_gl2::ARRAYS.iter().any(|info| info.name == array) {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glDisableClientState",
+                format_args!("{:#x} is not an array", array),
+            );
+        }
+        self.enabled_arrays.retain(|&enabled| enabled != array);
+        self.inner.DisableClientState(array);
+        self.check_errors("glDisableClientState");
+    }
+    unsafe fn GetIntegerv(&mut self, pname: GLenum, params: *mut GLint) {
+        self.trace("glGetIntegerv", format_args!("{:#x}, {:?}", pname, params));
+        self.inner.GetIntegerv(pname, params);
+        self.check_errors("glGetIntegerv");
+    }
+    unsafe fn Flush(&mut self) {
+        self.trace("glFlush", format_args!(""));
+        self.inner.Flush();
+        self.check_errors("glFlush");
+    }
+    unsafe fn Finish(&mut self) {
+        self.trace("glFinish", format_args!(""));
+        self.inner.Finish();
+        self.check_errors("glFinish");
+    }
+
+    // Other state manipulation
+    unsafe fn AlphaFunc(&mut self, func: GLenum, ref_: GLclampf) {
+        self.trace("glAlphaFunc", format_args!("{:#x}, {:?}", func, ref_));
+        self.inner.AlphaFunc(func, ref_);
+        self.check_errors("glAlphaFunc");
+    }
+    unsafe fn AlphaFuncx(&mut self, func: GLenum, ref_: GLclampx) {
+        self.trace("glAlphaFuncx", format_args!("{:#x}, {:?}", func, ref_));
+        self.inner.AlphaFuncx(func, ref_);
+        self.check_errors("glAlphaFuncx");
+    }
+    unsafe fn BlendFunc(&mut self, sfactor: GLenum, dfactor: GLenum) {
+        self.trace(
+            "glBlendFunc",
+            format_args!("{:#x}, {:#x}", sfactor, dfactor),
+        );
+        if self.is_gles1 && !BLEND_SFACTORS.contains(&sfactor) {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glBlendFunc",
+                format_args!("{:#x} is not a source blend factor", sfactor),
+            );
+        }
+        if self.is_gles1 && !BLEND_DFACTORS.contains(&dfactor) {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glBlendFunc",
+                format_args!("{:#x} is not a destination blend factor", dfactor),
+            );
+        }
+        self.inner.BlendFunc(sfactor, dfactor);
+        self.check_errors("glBlendFunc");
+    }
+    unsafe fn DepthMask(&mut self, flag: GLboolean) {
+        self.trace("glDepthMask", format_args!("{:?}", flag));
+        self.inner.DepthMask(flag);
+        self.check_errors("glDepthMask");
+    }
+    unsafe fn ShadeModel(&mut self, mode: GLenum) {
+        self.trace("glShadeModel", format_args!("{:#x}", mode));
+        self.inner.ShadeModel(mode);
+        self.check_errors("glShadeModel");
+    }
+    unsafe fn Scissor(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        self.trace(
+            "glScissor",
+            format_args!("{:?}, {:?}, {:?}, {:?}", x, y, width, height),
+        );
+        self.inner.Scissor(x, y, width, height);
+        self.check_errors("glScissor");
+    }
+    unsafe fn Viewport(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        self.trace(
+            "glViewport",
+            format_args!("{:?}, {:?}, {:?}, {:?}", x, y, width, height),
+        );
+        self.inner.Viewport(x, y, width, height);
+        self.check_errors("glViewport");
+    }
+    unsafe fn LineWidth(&mut self, width: GLfloat) {
+        self.trace("glLineWidth", format_args!("{:?}", width));
+        self.inner.LineWidth(width);
+        self.check_errors("glLineWidth");
+    }
+    unsafe fn LineWidthx(&mut self, width: GLfixed) {
+        self.trace("glLineWidthx", format_args!("{:?}", width));
+        self.inner.LineWidthx(width);
+        self.check_errors("glLineWidthx");
+    }
+    unsafe fn PointSize(&mut self, size: GLfloat) {
+        self.trace("glPointSize", format_args!("{:?}", size));
+        self.inner.PointSize(size);
+        self.check_errors("glPointSize");
+    }
+    unsafe fn PointSizex(&mut self, size: GLfixed) {
+        self.trace("glPointSizex", format_args!("{:?}", size));
+        self.inner.PointSizex(size);
+        self.check_errors("glPointSizex");
+    }
+
+    // Lighting
+    unsafe fn Lightf(&mut self, light: GLenum, pname: GLenum, param: GLfloat) {
+        self.trace(
+            "glLightf",
+            format_args!("{:#x}, {:#x}, {:?}", light, pname, param),
+        );
+        self.inner.Lightf(light, pname, param);
+        self.check_errors("glLightf");
+    }
+    unsafe fn Lightx(&mut self, light: GLenum, pname: GLenum, param: GLfixed) {
+        self.trace(
+            "glLightx",
+            format_args!("{:#x}, {:#x}, {:?}", light, pname, param),
+        );
+        self.inner.Lightx(light, pname, param);
+        self.check_errors("glLightx");
+    }
+    unsafe fn Lightfv(&mut self, light: GLenum, pname: GLenum, params: *const GLfloat) {
+        self.trace(
+            "glLightfv",
+            format_args!("{:#x}, {:#x}, {:?}", light, pname, params),
+        );
+        self.inner.Lightfv(light, pname, params);
+        self.check_errors("glLightfv");
+    }
+    unsafe fn Lightxv(&mut self, light: GLenum, pname: GLenum, params: *const GLfixed) {
+        self.trace(
+            "glLightxv",
+            format_args!("{:#x}, {:#x}, {:?}", light, pname, params),
+        );
+        self.inner.Lightxv(light, pname, params);
+        self.check_errors("glLightxv");
+    }
+
+    // Buffers
+    unsafe fn GenBuffers(&mut self, n: GLsizei, buffers: *mut GLuint) {
+        self.trace("glGenBuffers", format_args!("{:?}, {:?}", n, buffers));
+        self.inner.GenBuffers(n, buffers);
+        self.check_errors("glGenBuffers");
+    }
+    unsafe fn DeleteBuffers(&mut self, n: GLsizei, buffers: *const GLuint) {
+        self.trace("glDeleteBuffers", format_args!("{:?}, {:?}", n, buffers));
+        self.inner.DeleteBuffers(n, buffers);
+        self.check_errors("glDeleteBuffers");
+    }
+    unsafe fn BindBuffer(&mut self, target: GLenum, buffer: GLuint) {
+        self.trace("glBindBuffer", format_args!("{:#x}, {:?}", target, buffer));
+        if target != gles11::ARRAY_BUFFER && target != gles11::ELEMENT_ARRAY_BUFFER {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glBindBuffer",
+                format_args!("{:#x} is not a buffer target", target),
+            );
+        }
+        self.inner.BindBuffer(target, buffer);
+        self.check_errors("glBindBuffer");
+    }
+    unsafe fn BufferData(
+        &mut self,
+        target: GLenum,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+        usage: GLenum,
+    ) {
+        self.trace(
+            "glBufferData",
+            format_args!("{:#x}, {:?}, {:?}, {:#x}", target, size, data, usage),
+        );
+        self.inner.BufferData(target, size, data, usage);
+        self.check_errors("glBufferData");
+    }
+    unsafe fn BufferSubData(
+        &mut self,
+        target: GLenum,
+        offset: GLintptr,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+    ) {
+        self.trace(
+            "glBufferSubData",
+            format_args!("{:#x}, {:?}, {:?}, {:?}", target, offset, size, data),
+        );
+        self.inner.BufferSubData(target, offset, size, data);
+        self.check_errors("glBufferSubData");
+    }
+
+    // Non-pointers
+    unsafe fn Color4f(&mut self, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
+        self.trace(
+            "glColor4f",
+            format_args!("{:?}, {:?}, {:?}, {:?}", red, green, blue, alpha),
+        );
+        self.inner.Color4f(red, green, blue, alpha);
+        self.check_errors("glColor4f");
+    }
+    unsafe fn Color4x(&mut self, red: GLfixed, green: GLfixed, blue: GLfixed, alpha: GLfixed) {
+        self.trace(
+            "glColor4x",
+            format_args!("{:?}, {:?}, {:?}, {:?}", red, green, blue, alpha),
+        );
+        self.inner.Color4x(red, green, blue, alpha);
+        self.check_errors("glColor4x");
+    }
+
+    // Pointers
+    unsafe fn ColorPointer(
+        &mut self,
+        size: GLint,
+        type_: GLenum,
+        stride: GLsizei,
+        pointer: *const GLvoid,
+    ) {
+        self.trace(
+            "glColorPointer",
+            format_args!("{:?}, {:#x}, {:?}, {:?}", size, type_, stride, pointer),
+        );
+        self.inner.ColorPointer(size, type_, stride, pointer);
+        self.check_errors("glColorPointer");
+    }
+    unsafe fn NormalPointer(&mut self, type_: GLenum, stride: GLsizei, pointer: *const GLvoid) {
+        self.trace(
+            "glNormalPointer",
+            format_args!("{:#x}, {:?}, {:?}", type_, stride, pointer),
+        );
+        self.inner.NormalPointer(type_, stride, pointer);
+        self.check_errors("glNormalPointer");
+    }
+    unsafe fn TexCoordPointer(
+        &mut self,
+        size: GLint,
+        type_: GLenum,
+        stride: GLsizei,
+        pointer: *const GLvoid,
+    ) {
+        self.trace(
+            "glTexCoordPointer",
+            format_args!("{:?}, {:#x}, {:?}, {:?}", size, type_, stride, pointer),
+        );
+        self.inner.TexCoordPointer(size, type_, stride, pointer);
+        self.check_errors("glTexCoordPointer");
+    }
+    unsafe fn VertexPointer(
+        &mut self,
+        size: GLint,
+        type_: GLenum,
+        stride: GLsizei,
+        pointer: *const GLvoid,
+    ) {
+        self.trace(
+            "glVertexPointer",
+            format_args!("{:?}, {:#x}, {:?}, {:?}", size, type_, stride, pointer),
+        );
+        self.inner.VertexPointer(size, type_, stride, pointer);
+        self.check_errors("glVertexPointer");
+    }
+
+    // Drawing
+    unsafe fn DrawArrays(&mut self, mode: GLenum, first: GLint, count: GLsizei) {
+        self.trace(
+            "glDrawArrays",
+            format_args!("{:#x}, {:?}, {:?}", mode, first, count),
+        );
+        if !DRAW_MODES.contains(&mode) {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glDrawArrays",
+                format_args!("{:#x} is not a primitive mode", mode),
+            );
+        }
+        if first < 0 || count < 0 {
+            return self.reject(
+                gles11::INVALID_VALUE,
+                "glDrawArrays",
+                format_args!(
+                    "first ({}) and count ({}) must not be negative",
+                    first, count
+                ),
+            );
+        }
+        self.check_draw_state("glDrawArrays");
+        self.inner.DrawArrays(mode, first, count);
+        self.check_errors("glDrawArrays");
+    }
+    unsafe fn DrawElements(
+        &mut self,
+        mode: GLenum,
+        count: GLsizei,
+        type_: GLenum,
+        indices: *const GLvoid,
+    ) {
+        self.trace(
+            "glDrawElements",
+            format_args!("{:#x}, {:?}, {:#x}, {:?}", mode, count, type_, indices),
+        );
+        if !DRAW_MODES.contains(&mode) {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glDrawElements",
+                format_args!("{:#x} is not a primitive mode", mode),
+            );
+        }
+        if type_ != gles11::UNSIGNED_BYTE && type_ != gles11::UNSIGNED_SHORT {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glDrawElements",
+                format_args!("{:#x} is not an index type", type_),
+            );
+        }
+        if count < 0 {
+            return self.reject(
+                gles11::INVALID_VALUE,
+                "glDrawElements",
+                format_args!("count ({}) must not be negative", count),
+            );
+        }
+        self.check_draw_state("glDrawElements");
+        self.inner.DrawElements(mode, count, type_, indices);
+        self.check_errors("glDrawElements");
+    }
+
+    // Clearing
+    unsafe fn Clear(&mut self, mask: GLbitfield) {
+        self.trace("glClear", format_args!("{:#x}", mask));
+        if mask
+            & !(gles11::COLOR_BUFFER_BIT | gles11::DEPTH_BUFFER_BIT | gles11::STENCIL_BUFFER_BIT)
+            != 0
+        {
+            return self.reject(
+                gles11::INVALID_VALUE,
+                "glClear",
+                format_args!("{:#x} contains unknown bits", mask),
+            );
+        }
+        self.inner.Clear(mask);
+        self.check_errors("glClear");
+    }
+    unsafe fn ClearColor(
+        &mut self,
+        red: GLclampf,
+        green: GLclampf,
+        blue: GLclampf,
+        alpha: GLclampf,
+    ) {
+        self.trace(
+            "glClearColor",
+            format_args!("{:?}, {:?}, {:?}, {:?}", red, green, blue, alpha),
+        );
+        self.inner.ClearColor(red, green, blue, alpha);
+        self.check_errors("glClearColor");
+    }
+    unsafe fn ClearColorx(
+        &mut self,
+        red: GLclampx,
+        green: GLclampx,
+        blue: GLclampx,
+        alpha: GLclampx,
+    ) {
+        self.trace(
+            "glClearColorx",
+            format_args!("{:?}, {:?}, {:?}, {:?}", red, green, blue, alpha),
+        );
+        self.inner.ClearColorx(red, green, blue, alpha);
+        self.check_errors("glClearColorx");
+    }
+    unsafe fn ClearDepthf(&mut self, depth: GLclampf) {
+        self.trace("glClearDepthf", format_args!("{:?}", depth));
+        self.inner.ClearDepthf(depth);
+        self.check_errors("glClearDepthf");
+    }
+    unsafe fn ClearDepthx(&mut self, depth: GLclampx) {
+        self.trace("glClearDepthx", format_args!("{:?}", depth));
+        self.inner.ClearDepthx(depth);
+        self.check_errors("glClearDepthx");
+    }
+    unsafe fn ClearStencil(&mut self, s: GLint) {
+        self.trace("glClearStencil", format_args!("{:?}", s));
+        self.inner.ClearStencil(s);
+        self.check_errors("glClearStencil");
+    }
+
+    // Textures
+    unsafe fn ActiveTexture(&mut self, texture: GLenum) {
+        self.trace("glActiveTexture", format_args!("{:#x}", texture));
+        if self.is_gles1 && texture > gles11::TEXTURE1 {
+            self.mbx_warning(
+                "glActiveTexture",
+                format_args!("the MBX only has two texture units"),
+            );
+        }
+        self.inner.ActiveTexture(texture);
+        self.check_errors("glActiveTexture");
+    }
+    unsafe fn GenTextures(&mut self, n: GLsizei, textures: *mut GLuint) {
+        self.trace("glGenTextures", format_args!("{:?}, {:?}", n, textures));
+        self.inner.GenTextures(n, textures);
+        self.check_errors("glGenTextures");
+    }
+    unsafe fn DeleteTextures(&mut self, n: GLsizei, textures: *const GLuint) {
+        self.trace("glDeleteTextures", format_args!("{:?}, {:?}", n, textures));
+        self.inner.DeleteTextures(n, textures);
+        self.check_errors("glDeleteTextures");
+    }
+    unsafe fn BindTexture(&mut self, target: GLenum, texture: GLuint) {
+        self.trace(
+            "glBindTexture",
+            format_args!("{:#x}, {:?}", target, texture),
+        );
+        if self.is_gles1 && target != gles11::TEXTURE_2D {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glBindTexture",
+                format_args!("{:#x} is not a texture target", target),
+            );
+        }
+        self.inner.BindTexture(target, texture);
+        self.check_errors("glBindTexture");
+    }
+    unsafe fn TexParameteri(&mut self, target: GLenum, pname: GLenum, param: GLint) {
+        self.trace(
+            "glTexParameteri",
+            format_args!("{:#x}, {:#x}, {:?}", target, pname, param),
+        );
+        self.inner.TexParameteri(target, pname, param);
+        self.check_errors("glTexParameteri");
+    }
+    unsafe fn TexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+        format: GLenum,
+        type_: GLenum,
+        pixels: *const GLvoid,
+    ) {
+        self.trace(
+            "glTexImage2D",
+            format_args!(
+                "{:#x}, {:?}, {:?}, {:?}, {:?}, {:?}, {:#x}, {:#x}, {:?}",
+                target, level, internalformat, width, height, border, format, type_, pixels
+            ),
+        );
+        if self.is_gles1 && target != gles11::TEXTURE_2D {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glTexImage2D",
+                format_args!("{:#x} is not a texture target", target),
+            );
+        }
+        if level < 0 || width < 0 || height < 0 || border != 0 {
+            return self.reject(
+                gles11::INVALID_VALUE,
+                "glTexImage2D",
+                format_args!(
+                    "level ({}), width ({}), height ({}) or border ({}) is invalid",
+                    level, width, height, border
+                ),
+            );
+        }
+        if !TEXTURE_FORMATS.contains(&format) || !TEXTURE_TYPES.contains(&type_) {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glTexImage2D",
+                format_args!("format {:#x} or type {:#x} is invalid", format, type_),
+            );
+        }
+        if internalformat as GLenum != format {
+            return self.reject(
+                gles11::INVALID_OPERATION,
+                "glTexImage2D",
+                format_args!(
+                    "internal format {:#x} must match format {:#x}",
+                    internalformat, format
+                ),
+            );
+        }
+        if !texture_type_matches_format(type_, format) {
+            return self.reject(
+                gles11::INVALID_OPERATION,
+                "glTexImage2D",
+                format_args!("type {:#x} can't be used with format {:#x}", type_, format),
+            );
+        }
+        if self.is_gles1 {
+            self.check_mbx_texture_size("glTexImage2D", width, height);
+        }
+        self.inner.TexImage2D(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            format,
+            type_,
+            pixels,
+        );
+        self.check_errors("glTexImage2D");
+    }
+    unsafe fn CompressedTexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+        image_size: GLsizei,
+        data: *const GLvoid,
+    ) {
+        self.trace(
+            "glCompressedTexImage2D",
+            format_args!(
+                "{:#x}, {:?}, {:#x}, {:?}, {:?}, {:?}, {:?}, {:?}",
+                target, level, internalformat, width, height, border, image_size, data
+            ),
+        );
+        if self.is_gles1 {
+            self.check_mbx_texture_size("glCompressedTexImage2D", width, height);
+        }
+        self.inner.CompressedTexImage2D(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            image_size,
+            data,
+        );
+        self.check_errors("glCompressedTexImage2D");
+    }
+
+    // Matrix stack operations
+    unsafe fn MatrixMode(&mut self, mode: GLenum) {
+        self.trace("glMatrixMode", format_args!("{:#x}", mode));
+        if self.is_gles1
+            && ![gles11::MODELVIEW, gles11::PROJECTION, gles11::TEXTURE].contains(&mode)
+        {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glMatrixMode",
+                format_args!("{:#x} is not a matrix mode", mode),
+            );
+        }
+        self.inner.MatrixMode(mode);
+        self.check_errors("glMatrixMode");
+    }
+    unsafe fn LoadIdentity(&mut self) {
+        self.trace("glLoadIdentity", format_args!(""));
+        self.inner.LoadIdentity();
+        self.check_errors("glLoadIdentity");
+    }
+    unsafe fn LoadMatrixf(&mut self, m: *const GLfloat) {
+        self.trace("glLoadMatrixf", format_args!("{:?}", m));
+        self.inner.LoadMatrixf(m);
+        self.check_errors("glLoadMatrixf");
+    }
+    unsafe fn LoadMatrixx(&mut self, m: *const GLfixed) {
+        self.trace("glLoadMatrixx", format_args!("{:?}", m));
+        self.inner.LoadMatrixx(m);
+        self.check_errors("glLoadMatrixx");
+    }
+    unsafe fn MultMatrixf(&mut self, m: *const GLfloat) {
+        self.trace("glMultMatrixf", format_args!("{:?}", m));
+        self.inner.MultMatrixf(m);
+        self.check_errors("glMultMatrixf");
+    }
+    unsafe fn MultMatrixx(&mut self, m: *const GLfixed) {
+        self.trace("glMultMatrixx", format_args!("{:?}", m));
+        self.inner.MultMatrixx(m);
+        self.check_errors("glMultMatrixx");
+    }
+    unsafe fn PushMatrix(&mut self) {
+        self.trace("glPushMatrix", format_args!(""));
+        self.inner.PushMatrix();
+        self.check_errors("glPushMatrix");
+    }
+    unsafe fn PopMatrix(&mut self) {
+        self.trace("glPopMatrix", format_args!(""));
+        self.inner.PopMatrix();
+        self.check_errors("glPopMatrix");
+    }
+    unsafe fn Orthof(
+        &mut self,
+        left: GLfloat,
+        right: GLfloat,
+        bottom: GLfloat,
+        top: GLfloat,
+        near: GLfloat,
+        far: GLfloat,
+    ) {
+        self.trace(
+            "glOrthof",
+            format_args!(
+                "{:?}, {:?}, {:?}, {:?}, {:?}, {:?}",
+                left, right, bottom, top, near, far
+            ),
+        );
+        self.inner.Orthof(left, right, bottom, top, near, far);
+        self.check_errors("glOrthof");
+    }
+    unsafe fn Orthox(
+        &mut self,
+        left: GLfixed,
+        right: GLfixed,
+        bottom: GLfixed,
+        top: GLfixed,
+        near: GLfixed,
+        far: GLfixed,
+    ) {
+        self.trace(
+            "glOrthox",
+            format_args!(
+                "{:?}, {:?}, {:?}, {:?}, {:?}, {:?}",
+                left, right, bottom, top, near, far
+            ),
+        );
+        self.inner.Orthox(left, right, bottom, top, near, far);
+        self.check_errors("glOrthox");
+    }
+    unsafe fn Frustumf(
+        &mut self,
+        left: GLfloat,
+        right: GLfloat,
+        bottom: GLfloat,
+        top: GLfloat,
+        near: GLfloat,
+        far: GLfloat,
+    ) {
+        self.trace(
+            "glFrustumf",
+            format_args!(
+                "{:?}, {:?}, {:?}, {:?}, {:?}, {:?}",
+                left, right, bottom, top, near, far
+            ),
+        );
+        self.inner.Frustumf(left, right, bottom, top, near, far);
+        self.check_errors("glFrustumf");
+    }
+    unsafe fn Frustumx(
+        &mut self,
+        left: GLfixed,
+        right: GLfixed,
+        bottom: GLfixed,
+        top: GLfixed,
+        near: GLfixed,
+        far: GLfixed,
+    ) {
+        self.trace(
+            "glFrustumx",
+            format_args!(
+                "{:?}, {:?}, {:?}, {:?}, {:?}, {:?}",
+                left, right, bottom, top, near, far
+            ),
+        );
+        self.inner.Frustumx(left, right, bottom, top, near, far);
+        self.check_errors("glFrustumx");
+    }
+    unsafe fn Rotatef(&mut self, angle: GLfloat, x: GLfloat, y: GLfloat, z: GLfloat) {
+        self.trace(
+            "glRotatef",
+            format_args!("{:?}, {:?}, {:?}, {:?}", angle, x, y, z),
+        );
+        self.inner.Rotatef(angle, x, y, z);
+        self.check_errors("glRotatef");
+    }
+    unsafe fn Rotatex(&mut self, angle: GLfixed, x: GLfixed, y: GLfixed, z: GLfixed) {
+        self.trace(
+            "glRotatex",
+            format_args!("{:?}, {:?}, {:?}, {:?}", angle, x, y, z),
+        );
+        self.inner.Rotatex(angle, x, y, z);
+        self.check_errors("glRotatex");
+    }
+    unsafe fn Scalef(&mut self, x: GLfloat, y: GLfloat, z: GLfloat) {
+        self.trace("glScalef", format_args!("{:?}, {:?}, {:?}", x, y, z));
+        self.inner.Scalef(x, y, z);
+        self.check_errors("glScalef");
+    }
+    unsafe fn Scalex(&mut self, x: GLfixed, y: GLfixed, z: GLfixed) {
+        self.trace("glScalex", format_args!("{:?}, {:?}, {:?}", x, y, z));
+        self.inner.Scalex(x, y, z);
+        self.check_errors("glScalex");
+    }
+    unsafe fn Translatef(&mut self, x: GLfloat, y: GLfloat, z: GLfloat) {
+        self.trace("glTranslatef", format_args!("{:?}, {:?}, {:?}", x, y, z));
+        self.inner.Translatef(x, y, z);
+        self.check_errors("glTranslatef");
+    }
+    unsafe fn Translatex(&mut self, x: GLfixed, y: GLfixed, z: GLfixed) {
+        self.trace("glTranslatex", format_args!("{:?}, {:?}, {:?}", x, y, z));
+        self.inner.Translatex(x, y, z);
+        self.check_errors("glTranslatex");
+    }
+
+    // OES_framebuffer_object (incomplete)
+    unsafe fn GenFramebuffersOES(&mut self, n: GLsizei, framebuffers: *mut GLuint) {
+        self.trace(
+            "glGenFramebuffersOES",
+            format_args!("{:?}, {:?}", n, framebuffers),
+        );
+        self.inner.GenFramebuffersOES(n, framebuffers);
+        self.check_errors("glGenFramebuffersOES");
+    }
+    unsafe fn GenRenderbuffersOES(&mut self, n: GLsizei, renderbuffers: *mut GLuint) {
+        self.trace(
+            "glGenRenderbuffersOES",
+            format_args!("{:?}, {:?}", n, renderbuffers),
+        );
+        self.inner.GenRenderbuffersOES(n, renderbuffers);
+        self.check_errors("glGenRenderbuffersOES");
+    }
+    unsafe fn BindFramebufferOES(&mut self, target: GLenum, framebuffer: GLuint) {
+        self.trace(
+            "glBindFramebufferOES",
+            format_args!("{:#x}, {:?}", target, framebuffer),
+        );
+        if target != gles11::FRAMEBUFFER_OES {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glBindFramebufferOES",
+                format_args!("{:#x} is not a framebuffer target", target),
+            );
+        }
+        self.inner.BindFramebufferOES(target, framebuffer);
+        self.check_errors("glBindFramebufferOES");
+    }
+    unsafe fn BindRenderbufferOES(&mut self, target: GLenum, renderbuffer: GLuint) {
+        self.trace(
+            "glBindRenderbufferOES",
+            format_args!("{:#x}, {:?}", target, renderbuffer),
+        );
+        if target != gles11::RENDERBUFFER_OES {
+            return self.reject(
+                gles11::INVALID_ENUM,
+                "glBindRenderbufferOES",
+                format_args!("{:#x} is not a renderbuffer target", target),
+            );
+        }
+        self.inner.BindRenderbufferOES(target, renderbuffer);
+        self.check_errors("glBindRenderbufferOES");
+    }
+    unsafe fn RenderbufferStorageOES(
+        &mut self,
+        target: GLenum,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        self.trace(
+            "glRenderbufferStorageOES",
+            format_args!(
+                "{:#x}, {:#x}, {:?}, {:?}",
+                target, internalformat, width, height
+            ),
+        );
+        if self.is_gles1
+            && (internalformat == gles11::STENCIL_INDEX8_OES
+                || internalformat == gles11::DEPTH24_STENCIL8_OES)
+        {
+            self.mbx_warning(
+                "glRenderbufferStorageOES",
+                format_args!("the MBX has no stencil buffer"),
+            );
+        }
+        self.inner
+            .RenderbufferStorageOES(target, internalformat, width, height);
+        self.check_errors("glRenderbufferStorageOES");
+    }
+    unsafe fn FramebufferRenderbufferOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        renderbuffertarget: GLenum,
+        renderbuffer: GLuint,
+    ) {
+        self.trace(
+            "glFramebufferRenderbufferOES",
+            format_args!(
+                "{:#x}, {:#x}, {:#x}, {:?}",
+                target, attachment, renderbuffertarget, renderbuffer
+            ),
+        );
+        self.inner
+            .FramebufferRenderbufferOES(target, attachment, renderbuffertarget, renderbuffer);
+        self.check_errors("glFramebufferRenderbufferOES");
+    }
+    unsafe fn GetRenderbufferParameterivOES(
+        &mut self,
+        target: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        self.trace(
+            "glGetRenderbufferParameterivOES",
+            format_args!("{:#x}, {:#x}, {:?}", target, pname, params),
+        );
+        self.inner
+            .GetRenderbufferParameterivOES(target, pname, params);
+        self.check_errors("glGetRenderbufferParameterivOES");
+    }
+    unsafe fn GetFramebufferAttachmentParameterivOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        self.trace(
+            "glGetFramebufferAttachmentParameterivOES",
+            format_args!(
+                "{:#x}, {:#x}, {:#x}, {:?}",
+                target, attachment, pname, params
+            ),
+        );
+        self.inner
+            .GetFramebufferAttachmentParameterivOES(target, attachment, pname, params);
+        self.check_errors("glGetFramebufferAttachmentParameterivOES");
+    }
+    unsafe fn CheckFramebufferStatusOES(&mut self, target: GLenum) -> GLenum {
+        self.trace("glCheckFramebufferStatusOES", format_args!("{:#x}", target));
+        let result = self.inner.CheckFramebufferStatusOES(target);
+        self.check_errors("glCheckFramebufferStatusOES");
+        result
+    }
+
+    // APPLE_framebuffer_multisample
+    unsafe fn RenderbufferStorageMultisampleAPPLE(
+        &mut self,
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        self.trace(
+            "glRenderbufferStorageMultisampleAPPLE",
+            format_args!(
+                "{:#x}, {:?}, {:#x}, {:?}, {:?}",
+                target, samples, internalformat, width, height
+            ),
+        );
+        self.inner.RenderbufferStorageMultisampleAPPLE(
+            target,
+            samples,
+            internalformat,
+            width,
+            height,
+        );
+        self.check_errors("glRenderbufferStorageMultisampleAPPLE");
+    }
+    unsafe fn ResolveMultisampleFramebufferAPPLE(&mut self) {
+        self.trace("glResolveMultisampleFramebufferAPPLE", format_args!(""));
+        self.inner.ResolveMultisampleFramebufferAPPLE();
+        self.check_errors("glResolveMultisampleFramebufferAPPLE");
+    }
+
+    // EXT_discard_framebuffer
+    unsafe fn DiscardFramebufferEXT(
+        &mut self,
+        target: GLenum,
+        num_attachments: GLsizei,
+        attachments: *const GLenum,
+    ) {
+        self.trace(
+            "glDiscardFramebufferEXT",
+            format_args!("{:#x}, {:?}, {:?}", target, num_attachments, attachments),
+        );
+        self.inner
+            .DiscardFramebufferEXT(target, num_attachments, attachments);
+        self.check_errors("glDiscardFramebufferEXT");
+    }
+}
+impl<T: GLES> GLES2 for GLESDebug<T> {
+    // Framebuffer objects
+    unsafe fn GenFramebuffers(&mut self, n: GLsizei, framebuffers: *mut GLuint) {
+        self.trace(
+            "glGenFramebuffers",
+            format_args!("{:?}, {:?}", n, framebuffers),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .GenFramebuffers(n, framebuffers);
+        self.check_errors("glGenFramebuffers");
+    }
+    unsafe fn DeleteFramebuffers(&mut self, n: GLsizei, framebuffers: *const GLuint) {
+        self.trace(
+            "glDeleteFramebuffers",
+            format_args!("{:?}, {:?}", n, framebuffers),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .DeleteFramebuffers(n, framebuffers);
+        self.check_errors("glDeleteFramebuffers");
+    }
+    unsafe fn BindFramebuffer(&mut self, target: GLenum, framebuffer: GLuint) {
+        self.trace(
+            "glBindFramebuffer",
+            format_args!("{:#x}, {:?}", target, framebuffer),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .BindFramebuffer(target, framebuffer);
+        self.check_errors("glBindFramebuffer");
+    }
+    unsafe fn GenRenderbuffers(&mut self, n: GLsizei, renderbuffers: *mut GLuint) {
+        self.trace(
+            "glGenRenderbuffers",
+            format_args!("{:?}, {:?}", n, renderbuffers),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .GenRenderbuffers(n, renderbuffers);
+        self.check_errors("glGenRenderbuffers");
+    }
+    unsafe fn DeleteRenderbuffers(&mut self, n: GLsizei, renderbuffers: *const GLuint) {
+        self.trace(
+            "glDeleteRenderbuffers",
+            format_args!("{:?}, {:?}", n, renderbuffers),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .DeleteRenderbuffers(n, renderbuffers);
+        self.check_errors("glDeleteRenderbuffers");
+    }
+    unsafe fn BindRenderbuffer(&mut self, target: GLenum, renderbuffer: GLuint) {
+        self.trace(
+            "glBindRenderbuffer",
+            format_args!("{:#x}, {:?}", target, renderbuffer),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .BindRenderbuffer(target, renderbuffer);
+        self.check_errors("glBindRenderbuffer");
+    }
+    unsafe fn RenderbufferStorage(
+        &mut self,
+        target: GLenum,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        self.trace(
+            "glRenderbufferStorage",
+            format_args!(
+                "{:#x}, {:#x}, {:?}, {:?}",
+                target, internalformat, width, height
+            ),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .RenderbufferStorage(target, internalformat, width, height);
+        self.check_errors("glRenderbufferStorage");
+    }
+    unsafe fn FramebufferRenderbuffer(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        renderbuffertarget: GLenum,
+        renderbuffer: GLuint,
+    ) {
+        self.trace(
+            "glFramebufferRenderbuffer",
+            format_args!(
+                "{:#x}, {:#x}, {:#x}, {:?}",
+                target, attachment, renderbuffertarget, renderbuffer
+            ),
+        );
+        self.inner.as_gles2().unwrap().FramebufferRenderbuffer(
+            target,
+            attachment,
+            renderbuffertarget,
+            renderbuffer,
+        );
+        self.check_errors("glFramebufferRenderbuffer");
+    }
+    unsafe fn FramebufferTexture2D(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        textarget: GLenum,
+        texture: GLuint,
+        level: GLint,
+    ) {
+        self.trace(
+            "glFramebufferTexture2D",
+            format_args!(
+                "{:#x}, {:#x}, {:#x}, {:?}, {:?}",
+                target, attachment, textarget, texture, level
+            ),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .FramebufferTexture2D(target, attachment, textarget, texture, level);
+        self.check_errors("glFramebufferTexture2D");
+    }
+    unsafe fn GetRenderbufferParameteriv(
+        &mut self,
+        target: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        self.trace(
+            "glGetRenderbufferParameteriv",
+            format_args!("{:#x}, {:#x}, {:?}", target, pname, params),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .GetRenderbufferParameteriv(target, pname, params);
+        self.check_errors("glGetRenderbufferParameteriv");
+    }
+    unsafe fn GetFramebufferAttachmentParameteriv(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        self.trace(
+            "glGetFramebufferAttachmentParameteriv",
+            format_args!(
+                "{:#x}, {:#x}, {:#x}, {:?}",
+                target, attachment, pname, params
+            ),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .GetFramebufferAttachmentParameteriv(target, attachment, pname, params);
+        self.check_errors("glGetFramebufferAttachmentParameteriv");
+    }
+    unsafe fn CheckFramebufferStatus(&mut self, target: GLenum) -> GLenum {
+        self.trace("glCheckFramebufferStatus", format_args!("{:#x}", target));
+        let result = self
+            .inner
+            .as_gles2()
+            .unwrap()
+            .CheckFramebufferStatus(target);
+        self.check_errors("glCheckFramebufferStatus");
+        result
+    }
+    unsafe fn GenerateMipmap(&mut self, target: GLenum) {
+        self.trace("glGenerateMipmap", format_args!("{:#x}", target));
+        self.inner.as_gles2().unwrap().GenerateMipmap(target);
+        self.check_errors("glGenerateMipmap");
+    }
+
+    // Shaders
+    unsafe fn CreateShader(&mut self, type_: GLenum) -> GLuint {
+        self.trace("glCreateShader", format_args!("{:#x}", type_));
+        let result = self.inner.as_gles2().unwrap().CreateShader(type_);
+        self.check_errors("glCreateShader");
+        result
+    }
+    unsafe fn DeleteShader(&mut self, shader: GLuint) {
+        self.trace("glDeleteShader", format_args!("{:?}", shader));
+        self.inner.as_gles2().unwrap().DeleteShader(shader);
+        self.check_errors("glDeleteShader");
+    }
+    unsafe fn ShaderSource(
+        &mut self,
+        shader: GLuint,
+        count: GLsizei,
+        string: *const *const GLchar,
+        length: *const GLint,
+    ) {
+        self.trace(
+            "glShaderSource",
+            format_args!("{:?}, {:?}, {:?}, {:?}", shader, count, string, length),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .ShaderSource(shader, count, string, length);
+        self.check_errors("glShaderSource");
+    }
+    unsafe fn CompileShader(&mut self, shader: GLuint) {
+        self.trace("glCompileShader", format_args!("{:?}", shader));
+        self.inner.as_gles2().unwrap().CompileShader(shader);
+        self.check_errors("glCompileShader");
+    }
+    unsafe fn GetShaderiv(&mut self, shader: GLuint, pname: GLenum, params: *mut GLint) {
+        self.trace(
+            "glGetShaderiv",
+            format_args!("{:?}, {:#x}, {:?}", shader, pname, params),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .GetShaderiv(shader, pname, params);
+        self.check_errors("glGetShaderiv");
+    }
+    unsafe fn GetShaderInfoLog(
+        &mut self,
+        shader: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        self.trace(
+            "glGetShaderInfoLog",
+            format_args!("{:?}, {:?}, {:?}, {:?}", shader, buf_size, length, info_log),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .GetShaderInfoLog(shader, buf_size, length, info_log);
+        self.check_errors("glGetShaderInfoLog");
+    }
+
+    // Programs
+    unsafe fn CreateProgram(&mut self) -> GLuint {
+        self.trace("glCreateProgram", format_args!(""));
+        let result = self.inner.as_gles2().unwrap().CreateProgram();
+        self.check_errors("glCreateProgram");
+        result
+    }
+    unsafe fn DeleteProgram(&mut self, program: GLuint) {
+        self.trace("glDeleteProgram", format_args!("{:?}", program));
+        self.inner.as_gles2().unwrap().DeleteProgram(program);
+        self.check_errors("glDeleteProgram");
+    }
+    unsafe fn AttachShader(&mut self, program: GLuint, shader: GLuint) {
+        self.trace(
+            "glAttachShader",
+            format_args!("{:?}, {:?}", program, shader),
+        );
+        self.inner.as_gles2().unwrap().AttachShader(program, shader);
+        self.check_errors("glAttachShader");
+    }
+    unsafe fn DetachShader(&mut self, program: GLuint, shader: GLuint) {
+        self.trace(
+            "glDetachShader",
+            format_args!("{:?}, {:?}", program, shader),
+        );
+        self.inner.as_gles2().unwrap().DetachShader(program, shader);
+        self.check_errors("glDetachShader");
+    }
+    unsafe fn LinkProgram(&mut self, program: GLuint) {
+        self.trace("glLinkProgram", format_args!("{:?}", program));
+        self.inner.as_gles2().unwrap().LinkProgram(program);
+        self.check_errors("glLinkProgram");
+    }
+    unsafe fn ValidateProgram(&mut self, program: GLuint) {
+        self.trace("glValidateProgram", format_args!("{:?}", program));
+        self.inner.as_gles2().unwrap().ValidateProgram(program);
+        self.check_errors("glValidateProgram");
+    }
+    unsafe fn UseProgram(&mut self, program: GLuint) {
+        self.trace("glUseProgram", format_args!("{:?}", program));
+        self.inner.as_gles2().unwrap().UseProgram(program);
+        self.check_errors("glUseProgram");
+    }
+    unsafe fn GetProgramiv(&mut self, program: GLuint, pname: GLenum, params: *mut GLint) {
+        self.trace(
+            "glGetProgramiv",
+            format_args!("{:?}, {:#x}, {:?}", program, pname, params),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .GetProgramiv(program, pname, params);
+        self.check_errors("glGetProgramiv");
+    }
+    unsafe fn GetProgramInfoLog(
+        &mut self,
+        program: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        self.trace(
+            "glGetProgramInfoLog",
+            format_args!(
+                "{:?}, {:?}, {:?}, {:?}",
+                program, buf_size, length, info_log
+            ),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .GetProgramInfoLog(program, buf_size, length, info_log);
+        self.check_errors("glGetProgramInfoLog");
+    }
+    unsafe fn BindAttribLocation(&mut self, program: GLuint, index: GLuint, name: *const GLchar) {
+        self.trace(
+            "glBindAttribLocation",
+            format_args!("{:?}, {:?}, {:?}", program, index, name),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .BindAttribLocation(program, index, name);
+        self.check_errors("glBindAttribLocation");
+    }
+    unsafe fn GetAttribLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        self.trace(
+            "glGetAttribLocation",
+            format_args!("{:?}, {:?}", program, name),
+        );
+        let result = self
+            .inner
+            .as_gles2()
+            .unwrap()
+            .GetAttribLocation(program, name);
+        self.check_errors("glGetAttribLocation");
+        result
+    }
+    unsafe fn GetUniformLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        self.trace(
+            "glGetUniformLocation",
+            format_args!("{:?}, {:?}", program, name),
+        );
+        let result = self
+            .inner
+            .as_gles2()
+            .unwrap()
+            .GetUniformLocation(program, name);
+        self.check_errors("glGetUniformLocation");
+        result
+    }
+    unsafe fn GetActiveAttrib(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    ) {
+        self.trace(
+            "glGetActiveAttrib",
+            format_args!(
+                "{:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}",
+                program, index, buf_size, length, size, type_, name
+            ),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .GetActiveAttrib(program, index, buf_size, length, size, type_, name);
+        self.check_errors("glGetActiveAttrib");
+    }
+    unsafe fn GetActiveUniform(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    ) {
+        self.trace(
+            "glGetActiveUniform",
+            format_args!(
+                "{:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}",
+                program, index, buf_size, length, size, type_, name
+            ),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .GetActiveUniform(program, index, buf_size, length, size, type_, name);
+        self.check_errors("glGetActiveUniform");
+    }
+
+    // Uniforms
+    unsafe fn Uniform1i(&mut self, location: GLint, v0: GLint) {
+        self.trace("glUniform1i", format_args!("{:?}, {:?}", location, v0));
+        self.inner.as_gles2().unwrap().Uniform1i(location, v0);
+        self.check_errors("glUniform1i");
+    }
+    unsafe fn Uniform2i(&mut self, location: GLint, v0: GLint, v1: GLint) {
+        self.trace(
+            "glUniform2i",
+            format_args!("{:?}, {:?}, {:?}", location, v0, v1),
+        );
+        self.inner.as_gles2().unwrap().Uniform2i(location, v0, v1);
+        self.check_errors("glUniform2i");
+    }
+    unsafe fn Uniform3i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint) {
+        self.trace(
+            "glUniform3i",
+            format_args!("{:?}, {:?}, {:?}, {:?}", location, v0, v1, v2),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform3i(location, v0, v1, v2);
+        self.check_errors("glUniform3i");
+    }
+    unsafe fn Uniform4i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint, v3: GLint) {
+        self.trace(
+            "glUniform4i",
+            format_args!("{:?}, {:?}, {:?}, {:?}, {:?}", location, v0, v1, v2, v3),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform4i(location, v0, v1, v2, v3);
+        self.check_errors("glUniform4i");
+    }
+    unsafe fn Uniform1f(&mut self, location: GLint, v0: GLfloat) {
+        self.trace("glUniform1f", format_args!("{:?}, {:?}", location, v0));
+        self.inner.as_gles2().unwrap().Uniform1f(location, v0);
+        self.check_errors("glUniform1f");
+    }
+    unsafe fn Uniform2f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat) {
+        self.trace(
+            "glUniform2f",
+            format_args!("{:?}, {:?}, {:?}", location, v0, v1),
+        );
+        self.inner.as_gles2().unwrap().Uniform2f(location, v0, v1);
+        self.check_errors("glUniform2f");
+    }
+    unsafe fn Uniform3f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat) {
+        self.trace(
+            "glUniform3f",
+            format_args!("{:?}, {:?}, {:?}, {:?}", location, v0, v1, v2),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform3f(location, v0, v1, v2);
+        self.check_errors("glUniform3f");
+    }
+    unsafe fn Uniform4f(
+        &mut self,
+        location: GLint,
+        v0: GLfloat,
+        v1: GLfloat,
+        v2: GLfloat,
+        v3: GLfloat,
+    ) {
+        self.trace(
+            "glUniform4f",
+            format_args!("{:?}, {:?}, {:?}, {:?}, {:?}", location, v0, v1, v2, v3),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform4f(location, v0, v1, v2, v3);
+        self.check_errors("glUniform4f");
+    }
+    unsafe fn Uniform1iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        self.trace(
+            "glUniform1iv",
+            format_args!("{:?}, {:?}, {:?}", location, count, value),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform1iv(location, count, value);
+        self.check_errors("glUniform1iv");
+    }
+    unsafe fn Uniform2iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        self.trace(
+            "glUniform2iv",
+            format_args!("{:?}, {:?}, {:?}", location, count, value),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform2iv(location, count, value);
+        self.check_errors("glUniform2iv");
+    }
+    unsafe fn Uniform3iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        self.trace(
+            "glUniform3iv",
+            format_args!("{:?}, {:?}, {:?}", location, count, value),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform3iv(location, count, value);
+        self.check_errors("glUniform3iv");
+    }
+    unsafe fn Uniform4iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        self.trace(
+            "glUniform4iv",
+            format_args!("{:?}, {:?}, {:?}", location, count, value),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform4iv(location, count, value);
+        self.check_errors("glUniform4iv");
+    }
+    unsafe fn Uniform1fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        self.trace(
+            "glUniform1fv",
+            format_args!("{:?}, {:?}, {:?}", location, count, value),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform1fv(location, count, value);
+        self.check_errors("glUniform1fv");
+    }
+    unsafe fn Uniform2fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        self.trace(
+            "glUniform2fv",
+            format_args!("{:?}, {:?}, {:?}", location, count, value),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform2fv(location, count, value);
+        self.check_errors("glUniform2fv");
+    }
+    unsafe fn Uniform3fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        self.trace(
+            "glUniform3fv",
+            format_args!("{:?}, {:?}, {:?}", location, count, value),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform3fv(location, count, value);
+        self.check_errors("glUniform3fv");
+    }
+    unsafe fn Uniform4fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        self.trace(
+            "glUniform4fv",
+            format_args!("{:?}, {:?}, {:?}", location, count, value),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .Uniform4fv(location, count, value);
+        self.check_errors("glUniform4fv");
+    }
+    unsafe fn UniformMatrix2fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        self.trace(
+            "glUniformMatrix2fv",
+            format_args!("{:?}, {:?}, {:?}, {:?}", location, count, transpose, value),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .UniformMatrix2fv(location, count, transpose, value);
+        self.check_errors("glUniformMatrix2fv");
+    }
+    unsafe fn UniformMatrix3fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        self.trace(
+            "glUniformMatrix3fv",
+            format_args!("{:?}, {:?}, {:?}, {:?}", location, count, transpose, value),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .UniformMatrix3fv(location, count, transpose, value);
+        self.check_errors("glUniformMatrix3fv");
+    }
+    unsafe fn UniformMatrix4fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        self.trace(
+            "glUniformMatrix4fv",
+            format_args!("{:?}, {:?}, {:?}, {:?}", location, count, transpose, value),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .UniformMatrix4fv(location, count, transpose, value);
+        self.check_errors("glUniformMatrix4fv");
+    }
+
+    // Generic vertex attributes
+    unsafe fn EnableVertexAttribArray(&mut self, index: GLuint) {
+        self.trace("glEnableVertexAttribArray", format_args!("{:?}", index));
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .EnableVertexAttribArray(index);
+        self.check_errors("glEnableVertexAttribArray");
+    }
+    unsafe fn DisableVertexAttribArray(&mut self, index: GLuint) {
+        self.trace("glDisableVertexAttribArray", format_args!("{:?}", index));
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .DisableVertexAttribArray(index);
+        self.check_errors("glDisableVertexAttribArray");
+    }
+    unsafe fn VertexAttribPointer(
+        &mut self,
+        index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        normalized: GLboolean,
+        stride: GLsizei,
+        pointer: *const GLvoid,
+    ) {
+        self.trace(
+            "glVertexAttribPointer",
+            format_args!(
+                "{:?}, {:?}, {:#x}, {:?}, {:?}, {:?}",
+                index, size, type_, normalized, stride, pointer
+            ),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .VertexAttribPointer(index, size, type_, normalized, stride, pointer);
+        self.check_errors("glVertexAttribPointer");
+    }
+    unsafe fn VertexAttrib1f(&mut self, index: GLuint, x: GLfloat) {
+        self.trace("glVertexAttrib1f", format_args!("{:?}, {:?}", index, x));
+        self.inner.as_gles2().unwrap().VertexAttrib1f(index, x);
+        self.check_errors("glVertexAttrib1f");
+    }
+    unsafe fn VertexAttrib2f(&mut self, index: GLuint, x: GLfloat, y: GLfloat) {
+        self.trace(
+            "glVertexAttrib2f",
+            format_args!("{:?}, {:?}, {:?}", index, x, y),
+        );
+        self.inner.as_gles2().unwrap().VertexAttrib2f(index, x, y);
+        self.check_errors("glVertexAttrib2f");
+    }
+    unsafe fn VertexAttrib3f(&mut self, index: GLuint, x: GLfloat, y: GLfloat, z: GLfloat) {
+        self.trace(
+            "glVertexAttrib3f",
+            format_args!("{:?}, {:?}, {:?}, {:?}", index, x, y, z),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .VertexAttrib3f(index, x, y, z);
+        self.check_errors("glVertexAttrib3f");
+    }
+    unsafe fn VertexAttrib4f(
+        &mut self,
+        index: GLuint,
+        x: GLfloat,
+        y: GLfloat,
+        z: GLfloat,
+        w: GLfloat,
+    ) {
+        self.trace(
+            "glVertexAttrib4f",
+            format_args!("{:?}, {:?}, {:?}, {:?}, {:?}", index, x, y, z, w),
+        );
+        self.inner
+            .as_gles2()
+            .unwrap()
+            .VertexAttrib4f(index, x, y, z, w);
+        self.check_errors("glVertexAttrib4f");
+    }
+    unsafe fn VertexAttrib4fv(&mut self, index: GLuint, v: *const GLfloat) {
+        self.trace("glVertexAttrib4fv", format_args!("{:?}, {:?}", index, v));
+        self.inner.as_gles2().unwrap().VertexAttrib4fv(index, v);
+        self.check_errors("glVertexAttrib4fv");
+    }
+}
@@ -3,21 +3,37 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! Generic OpenGL ES 1.1 interface.
+//! Generic OpenGL ES 1.1 and 2.0 interfaces.
 //!
 //! Unfortunately this does not provide the types and constants, so the correct
 //! usage is to import `GLES` and `types` from this module, but get the
-//! constants from [crate::window::gles11].
+//! constants from [crate::window::gles11] or [crate::window::gles20].
+//!
+//! [GLES] covers OpenGL ES 1.1 and the parts of OpenGL ES 2.0 it has in common
+//! with it. Functions that only exist in OpenGL ES 2.0 are in [GLES2], which
+//! OpenGL ES 2.0 contexts provide via [GLES::as_gles2].
 
 use crate::window::gles11::types::*;
 
 /// Trait representing an OpenGL ES implementation and context.
 #[allow(clippy::upper_case_acronyms)]
 pub trait GLES {
-    fn new(window: &mut crate::window::Window) -> Self
+    /// Create a context. If `share_with` is provided, the new context shares
+    /// objects (textures, buffers etc) with that one, see [Self::gl_ctx].
+    fn new(
+        window: &mut crate::window::Window,
+        share_with: Option<&crate::window::GLContext>,
+    ) -> Self
     where
         Self: Sized;
     fn make_current(&self, window: &mut crate::window::Window);
+    /// Get the host context this is implemented with.
+    fn gl_ctx(&self) -> &crate::window::GLContext;
+    /// Get the OpenGL ES 2.0-only functions, if this is an OpenGL ES 2.0
+    /// context.
+    fn as_gles2(&mut self) -> Option<&mut dyn GLES2> {
+        None
+    }
 
     // Generic state manipulation
     unsafe fn GetError(&mut self) -> GLenum;
@@ -26,6 +42,8 @@ pub trait GLES {
     unsafe fn EnableClientState(&mut self, array: GLenum);
     unsafe fn DisableClientState(&mut self, array: GLenum);
     unsafe fn GetIntegerv(&mut self, pname: GLenum, params: *mut GLint);
+    unsafe fn Flush(&mut self);
+    unsafe fn Finish(&mut self);
 
     // Other state manipulation
     unsafe fn AlphaFunc(&mut self, func: GLenum, ref_: GLclampf);
@@ -35,6 +53,10 @@ pub trait GLES {
     unsafe fn ShadeModel(&mut self, mode: GLenum);
     unsafe fn Scissor(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei);
     unsafe fn Viewport(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei);
+    unsafe fn LineWidth(&mut self, width: GLfloat);
+    unsafe fn LineWidthx(&mut self, width: GLfixed);
+    unsafe fn PointSize(&mut self, size: GLfloat);
+    unsafe fn PointSizex(&mut self, size: GLfixed);
 
     // Lighting
     unsafe fn Lightf(&mut self, light: GLenum, pname: GLenum, param: GLfloat);
@@ -46,6 +68,20 @@ pub trait GLES {
     unsafe fn GenBuffers(&mut self, n: GLsizei, buffers: *mut GLuint);
     unsafe fn DeleteBuffers(&mut self, n: GLsizei, buffers: *const GLuint);
     unsafe fn BindBuffer(&mut self, target: GLenum, buffer: GLuint);
+    unsafe fn BufferData(
+        &mut self,
+        target: GLenum,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+        usage: GLenum,
+    );
+    unsafe fn BufferSubData(
+        &mut self,
+        target: GLenum,
+        offset: GLintptr,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+    );
 
     // Non-pointers
     unsafe fn Color4f(&mut self, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat);
@@ -106,6 +142,7 @@ pub trait GLES {
     unsafe fn ClearStencil(&mut self, s: GLint);
 
     // Textures
+    unsafe fn ActiveTexture(&mut self, texture: GLenum);
     unsafe fn GenTextures(&mut self, n: GLsizei, textures: *mut GLuint);
     unsafe fn DeleteTextures(&mut self, n: GLsizei, textures: *const GLuint);
     unsafe fn BindTexture(&mut self, target: GLenum, texture: GLuint);
@@ -122,6 +159,17 @@ pub trait GLES {
         type_: GLenum,
         pixels: *const GLvoid,
     );
+    unsafe fn CompressedTexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+        image_size: GLsizei,
+        data: *const GLvoid,
+    );
 
     // Matrix stack operations
     unsafe fn MatrixMode(&mut self, mode: GLenum);
@@ -200,5 +248,211 @@ pub trait GLES {
         pname: GLenum,
         params: *mut GLint,
     );
+    unsafe fn GetFramebufferAttachmentParameterivOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    );
     unsafe fn CheckFramebufferStatusOES(&mut self, target: GLenum) -> GLenum;
+
+    // APPLE_framebuffer_multisample
+    unsafe fn RenderbufferStorageMultisampleAPPLE(
+        &mut self,
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    );
+    unsafe fn ResolveMultisampleFramebufferAPPLE(&mut self);
+
+    // EXT_discard_framebuffer
+    unsafe fn DiscardFramebufferEXT(
+        &mut self,
+        target: GLenum,
+        num_attachments: GLsizei,
+        attachments: *const GLenum,
+    );
+}
+
+/// Trait representing the functions only found in OpenGL ES 2.0.
+#[allow(clippy::upper_case_acronyms)]
+pub trait GLES2: GLES {
+    // Framebuffer objects
+    unsafe fn GenFramebuffers(&mut self, n: GLsizei, framebuffers: *mut GLuint);
+    unsafe fn DeleteFramebuffers(&mut self, n: GLsizei, framebuffers: *const GLuint);
+    unsafe fn BindFramebuffer(&mut self, target: GLenum, framebuffer: GLuint);
+    unsafe fn GenRenderbuffers(&mut self, n: GLsizei, renderbuffers: *mut GLuint);
+    unsafe fn DeleteRenderbuffers(&mut self, n: GLsizei, renderbuffers: *const GLuint);
+    unsafe fn BindRenderbuffer(&mut self, target: GLenum, renderbuffer: GLuint);
+    unsafe fn RenderbufferStorage(
+        &mut self,
+        target: GLenum,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    );
+    unsafe fn FramebufferRenderbuffer(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        renderbuffertarget: GLenum,
+        renderbuffer: GLuint,
+    );
+    unsafe fn FramebufferTexture2D(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        textarget: GLenum,
+        texture: GLuint,
+        level: GLint,
+    );
+    unsafe fn GetRenderbufferParameteriv(
+        &mut self,
+        target: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    );
+    unsafe fn GetFramebufferAttachmentParameteriv(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    );
+    unsafe fn CheckFramebufferStatus(&mut self, target: GLenum) -> GLenum;
+    unsafe fn GenerateMipmap(&mut self, target: GLenum);
+
+    // Shaders
+    unsafe fn CreateShader(&mut self, type_: GLenum) -> GLuint;
+    unsafe fn DeleteShader(&mut self, shader: GLuint);
+    unsafe fn ShaderSource(
+        &mut self,
+        shader: GLuint,
+        count: GLsizei,
+        string: *const *const GLchar,
+        length: *const GLint,
+    );
+    unsafe fn CompileShader(&mut self, shader: GLuint);
+    unsafe fn GetShaderiv(&mut self, shader: GLuint, pname: GLenum, params: *mut GLint);
+    unsafe fn GetShaderInfoLog(
+        &mut self,
+        shader: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    );
+
+    // Programs
+    unsafe fn CreateProgram(&mut self) -> GLuint;
+    unsafe fn DeleteProgram(&mut self, program: GLuint);
+    unsafe fn AttachShader(&mut self, program: GLuint, shader: GLuint);
+    unsafe fn DetachShader(&mut self, program: GLuint, shader: GLuint);
+    unsafe fn LinkProgram(&mut self, program: GLuint);
+    unsafe fn ValidateProgram(&mut self, program: GLuint);
+    unsafe fn UseProgram(&mut self, program: GLuint);
+    unsafe fn GetProgramiv(&mut self, program: GLuint, pname: GLenum, params: *mut GLint);
+    unsafe fn GetProgramInfoLog(
+        &mut self,
+        program: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    );
+    unsafe fn BindAttribLocation(&mut self, program: GLuint, index: GLuint, name: *const GLchar);
+    unsafe fn GetAttribLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint;
+    unsafe fn GetUniformLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint;
+    unsafe fn GetActiveAttrib(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    );
+    unsafe fn GetActiveUniform(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    );
+
+    // Uniforms
+    unsafe fn Uniform1i(&mut self, location: GLint, v0: GLint);
+    unsafe fn Uniform2i(&mut self, location: GLint, v0: GLint, v1: GLint);
+    unsafe fn Uniform3i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint);
+    unsafe fn Uniform4i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint, v3: GLint);
+    unsafe fn Uniform1f(&mut self, location: GLint, v0: GLfloat);
+    unsafe fn Uniform2f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat);
+    unsafe fn Uniform3f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat);
+    unsafe fn Uniform4f(
+        &mut self,
+        location: GLint,
+        v0: GLfloat,
+        v1: GLfloat,
+        v2: GLfloat,
+        v3: GLfloat,
+    );
+    unsafe fn Uniform1iv(&mut self, location: GLint, count: GLsizei, value: *const GLint);
+    unsafe fn Uniform2iv(&mut self, location: GLint, count: GLsizei, value: *const GLint);
+    unsafe fn Uniform3iv(&mut self, location: GLint, count: GLsizei, value: *const GLint);
+    unsafe fn Uniform4iv(&mut self, location: GLint, count: GLsizei, value: *const GLint);
+    unsafe fn Uniform1fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat);
+    unsafe fn Uniform2fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat);
+    unsafe fn Uniform3fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat);
+    unsafe fn Uniform4fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat);
+    unsafe fn UniformMatrix2fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    );
+    unsafe fn UniformMatrix3fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    );
+    unsafe fn UniformMatrix4fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    );
+
+    // Generic vertex attributes
+    unsafe fn EnableVertexAttribArray(&mut self, index: GLuint);
+    unsafe fn DisableVertexAttribArray(&mut self, index: GLuint);
+    unsafe fn VertexAttribPointer(
+        &mut self,
+        index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        normalized: GLboolean,
+        stride: GLsizei,
+        pointer: *const GLvoid,
+    );
+    unsafe fn VertexAttrib1f(&mut self, index: GLuint, x: GLfloat);
+    unsafe fn VertexAttrib2f(&mut self, index: GLuint, x: GLfloat, y: GLfloat);
+    unsafe fn VertexAttrib3f(&mut self, index: GLuint, x: GLfloat, y: GLfloat, z: GLfloat);
+    unsafe fn VertexAttrib4f(
+        &mut self,
+        index: GLuint,
+        x: GLfloat,
+        y: GLfloat,
+        z: GLfloat,
+        w: GLfloat,
+    );
+    unsafe fn VertexAttrib4fv(&mut self, index: GLuint, v: *const GLfloat);
 }
@@ -5,11 +5,12 @@
  */
 //! Wrapper functions exposing OpenGL ES to the guest.
 
+use super::gles_generic::GLES2;
 use super::GLES;
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, Mem, MutPtr};
-use crate::window::gles11;
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestISize, GuestUSize, Mem, MutPtr};
 use crate::window::gles11::types::*;
+use crate::window::{gles11, gles20};
 use crate::Environment;
 
 fn with_ctx_and_mem<T, U>(env: &mut Environment, f: T) -> U
@@ -30,6 +31,132 @@ where
     res
 }
 
+/// Like [with_ctx_and_mem], but for OpenGL ES 2.0-only functions.
+fn with_ctx2_and_mem<T, U>(env: &mut Environment, f: T) -> U
+where
+    T: FnOnce(&mut dyn GLES2, &mut Mem) -> U,
+{
+    with_ctx_and_mem(env, |gles, mem| f(as_gles2(gles), mem))
+}
+
+fn as_gles2(gles: &mut dyn GLES) -> &mut dyn GLES2 {
+    let Some(gles2) = gles.as_gles2() else {
+        panic!("OpenGL ES 2.0 function called without an OpenGL ES 2.0 context");
+    };
+    gles2
+}
+
+/// Get a host pointer to a null-terminated string in guest memory.
+fn cstr_ptr(mem: &Mem, string: ConstPtr<u8>) -> *const GLchar {
+    let len: GuestUSize = mem.cstr_at(string).len().try_into().unwrap();
+    mem.ptr_at(string, len + 1).cast()
+}
+
+/// Like [Mem::ptr_at_mut], but null pointers are allowed.
+fn ptr_at_mut_or_null<T: crate::mem::SafeRead>(
+    mem: &mut Mem,
+    ptr: MutPtr<T>,
+    count: GuestUSize,
+) -> *mut T {
+    if ptr.is_null() {
+        std::ptr::null_mut()
+    } else {
+        mem.ptr_at_mut(ptr, count)
+    }
+}
+
+/// The scale hack (see `--internal-resolution=`) enlarges the storage of the
+/// renderbuffers the app renders to the screen with, so pixel co-ordinates and
+/// sizes the app uses with them must be scaled too. The app should never see
+/// the enlarged values.
+#[derive(Copy, Clone)]
+struct ScaleHack {
+    factor: GLint,
+    /// Size of a renderbuffer with enlarged storage.
+    scaled_size: (GLint, GLint),
+}
+impl ScaleHack {
+    fn get(env: &Environment) -> ScaleHack {
+        let (width, height) = env.window.size_unrotated_scalehacked();
+        ScaleHack {
+            factor: env.window.scale_hack().get().try_into().unwrap(),
+            scaled_size: (width.try_into().unwrap(), height.try_into().unwrap()),
+        }
+    }
+
+    /// Apply the scale hack to the size requested for a renderbuffer's
+    /// storage, if it is the size of the screen.
+    fn renderbuffer_size(self, width: GLsizei, height: GLsizei) -> (GLsizei, GLsizei) {
+        let (scaled_width, scaled_height) = self.scaled_size;
+        if (width * self.factor, height * self.factor) == (scaled_width, scaled_height) {
+            (scaled_width, scaled_height)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Get the factor the currently bound renderbuffer was enlarged by, or 1.
+    unsafe fn bound_renderbuffer_factor(self, gles: &mut dyn GLES) -> GLint {
+        if self.factor == 1 {
+            return 1;
+        }
+        let mut width = 0;
+        let mut height = 0;
+        gles.GetRenderbufferParameterivOES(
+            gles11::RENDERBUFFER_OES,
+            gles11::RENDERBUFFER_WIDTH_OES,
+            &mut width,
+        );
+        gles.GetRenderbufferParameterivOES(
+            gles11::RENDERBUFFER_OES,
+            gles11::RENDERBUFFER_HEIGHT_OES,
+            &mut height,
+        );
+        if (width, height) == self.scaled_size {
+            self.factor
+        } else {
+            1
+        }
+    }
+
+    /// Get the factor the color buffer of the currently bound framebuffer was
+    /// enlarged by, or 1.
+    unsafe fn bound_framebuffer_factor(self, gles: &mut dyn GLES) -> GLint {
+        if self.factor == 1 {
+            return 1;
+        }
+        let mut framebuffer = 0;
+        gles.GetIntegerv(gles11::FRAMEBUFFER_BINDING_OES, &mut framebuffer);
+        if framebuffer == 0 {
+            return 1;
+        }
+        let mut object_type = 0;
+        gles.GetFramebufferAttachmentParameterivOES(
+            gles11::FRAMEBUFFER_OES,
+            gles11::COLOR_ATTACHMENT0_OES,
+            gles11::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE_OES,
+            &mut object_type,
+        );
+        // Textures are never enlarged.
+        if object_type as GLenum != gles11::RENDERBUFFER_OES {
+            return 1;
+        }
+        let mut renderbuffer = 0;
+        gles.GetFramebufferAttachmentParameterivOES(
+            gles11::FRAMEBUFFER_OES,
+            gles11::COLOR_ATTACHMENT0_OES,
+            gles11::FRAMEBUFFER_ATTACHMENT_OBJECT_NAME_OES,
+            &mut renderbuffer,
+        );
+        let mut old_renderbuffer = 0;
+        gles.GetIntegerv(gles11::RENDERBUFFER_BINDING_OES, &mut old_renderbuffer);
+        gles.BindRenderbufferOES(gles11::RENDERBUFFER_OES, renderbuffer as GLuint);
+        let factor = self.bound_renderbuffer_factor(gles);
+        gles.BindRenderbufferOES(gles11::RENDERBUFFER_OES, old_renderbuffer as GLuint);
+        factor
+    }
+}
+
 /// Useful for debugging
 #[allow(dead_code)]
 fn panic_on_gl_errors(gles: &mut dyn GLES) {
@@ -78,17 +205,56 @@ fn glDisableClientState(env: &mut Environment, array: GLenum) {
     });
 }
 fn glGetIntegerv(env: &mut Environment, pname: GLenum, params: MutPtr<GLint>) {
+    let scale_hack = ScaleHack::get(env);
     with_ctx_and_mem(env, |gles, mem| {
         // This function family can return a huge number of things.
         // TODO: support more possible values.
         let param_count = match pname {
             gles11::MATRIX_MODE | gles11::TEXTURE_BINDING_2D => 1,
+            gles11::NUM_COMPRESSED_TEXTURE_FORMATS => 1,
+            gles11::COMPRESSED_TEXTURE_FORMATS => {
+                let mut count = 0;
+                unsafe { gles.GetIntegerv(gles11::NUM_COMPRESSED_TEXTURE_FORMATS, &mut count) };
+                count.try_into().unwrap()
+            }
+            gles11::MAX_SAMPLES_APPLE | gles11::READ_FRAMEBUFFER_BINDING_APPLE => 1,
+            gles20::ACTIVE_TEXTURE
+            | gles20::ARRAY_BUFFER_BINDING
+            | gles20::CURRENT_PROGRAM
+            | gles20::ELEMENT_ARRAY_BUFFER_BINDING
+            | gles20::FRAMEBUFFER_BINDING
+            | gles20::MAX_COMBINED_TEXTURE_IMAGE_UNITS
+            | gles20::MAX_CUBE_MAP_TEXTURE_SIZE
+            | gles20::MAX_FRAGMENT_UNIFORM_VECTORS
+            | gles20::MAX_RENDERBUFFER_SIZE
+            | gles20::MAX_TEXTURE_IMAGE_UNITS
+            | gles20::MAX_TEXTURE_SIZE
+            | gles20::MAX_VARYING_VECTORS
+            | gles20::MAX_VERTEX_ATTRIBS
+            | gles20::MAX_VERTEX_TEXTURE_IMAGE_UNITS
+            | gles20::MAX_VERTEX_UNIFORM_VECTORS
+            | gles20::RENDERBUFFER_BINDING
+            | gles20::TEXTURE_BINDING_CUBE_MAP => 1,
+            gles11::VIEWPORT | gles11::SCISSOR_BOX => 4,
             _ => unimplemented!("pname value {:#x}", pname),
         };
         let params = mem.ptr_at_mut(params, param_count);
         unsafe { gles.GetIntegerv(pname, params) };
+        if pname == gles11::VIEWPORT || pname == gles11::SCISSOR_BOX {
+            // undo scale hack
+            let factor = unsafe { scale_hack.bound_framebuffer_factor(gles) };
+            for i in 0..4 {
+                unsafe { *params.add(i) /= factor };
+            }
+        }
     });
 }
+fn glFlush(env: &mut Environment) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Flush() })
+}
+fn glFinish(env: &mut Environment) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Finish() })
+}
 
 // Other state manipulation
 fn glAlphaFunc(env: &mut Environment, func: GLenum, ref_: GLclampf) {
@@ -109,33 +275,51 @@ fn glShadeModel(env: &mut Environment, mode: GLenum) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.ShadeModel(mode) })
 }
 fn glScissor(env: &mut Environment, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
-    // apply scale hack
-    let (width, height) = if x == 0
-        && y == 0
-        && (width as u32, height as u32) == env.window.size_unrotated_unscaled()
-    {
-        let (width, height) = env.window.size_unrotated_scalehacked();
-        (width as GLsizei, height as GLsizei)
-    } else {
-        (width, height)
-    };
+    let scale_hack = ScaleHack::get(env);
     with_ctx_and_mem(env, |gles, _mem| unsafe {
-        gles.Scissor(x, y, width, height)
+        // apply scale hack
+        let f = scale_hack.bound_framebuffer_factor(gles);
+        gles.Scissor(x * f, y * f, width * f, height * f)
     })
 }
 fn glViewport(env: &mut Environment, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
-    // apply scale hack
-    let (width, height) = if x == 0
-        && y == 0
-        && (width as u32, height as u32) == env.window.size_unrotated_unscaled()
-    {
-        let (width, height) = env.window.size_unrotated_scalehacked();
-        (width as GLsizei, height as GLsizei)
-    } else {
-        (width, height)
-    };
+    let scale_hack = ScaleHack::get(env);
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        // apply scale hack
+        let f = scale_hack.bound_framebuffer_factor(gles);
+        gles.Viewport(x * f, y * f, width * f, height * f)
+    })
+}
+fn glLineWidth(env: &mut Environment, width: GLfloat) {
+    let scale_hack = ScaleHack::get(env);
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        // apply scale hack
+        let f = scale_hack.bound_framebuffer_factor(gles);
+        gles.LineWidth(width * f as GLfloat)
+    })
+}
+fn glLineWidthx(env: &mut Environment, width: GLfixed) {
+    let scale_hack = ScaleHack::get(env);
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        // apply scale hack
+        let f = scale_hack.bound_framebuffer_factor(gles);
+        gles.LineWidthx(width.saturating_mul(f))
+    })
+}
+fn glPointSize(env: &mut Environment, size: GLfloat) {
+    let scale_hack = ScaleHack::get(env);
     with_ctx_and_mem(env, |gles, _mem| unsafe {
-        gles.Viewport(x, y, width, height)
+        // apply scale hack
+        let f = scale_hack.bound_framebuffer_factor(gles);
+        gles.PointSize(size * f as GLfloat)
+    })
+}
+fn glPointSizex(env: &mut Environment, size: GLfixed) {
+    let scale_hack = ScaleHack::get(env);
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        // apply scale hack
+        let f = scale_hack.bound_framebuffer_factor(gles);
+        gles.PointSizex(size.saturating_mul(f))
     })
 }
 
@@ -171,7 +355,7 @@ fn glLightxv(env: &mut Environment, light: GLenum, pname: GLenum, params: ConstP
     })
 }
 
-// Textures
+// Buffers
 fn glGenBuffers(env: &mut Environment, n: GLsizei, buffers: MutPtr<GLuint>) {
     with_ctx_and_mem(env, |gles, mem| {
         let n_usize: GuestUSize = n.try_into().unwrap();
@@ -189,6 +373,37 @@ fn glDeleteBuffers(env: &mut Environment, n: GLsizei, buffers: ConstPtr<GLuint>)
 fn glBindBuffer(env: &mut Environment, target: GLenum, buffer: GLuint) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.BindBuffer(target, buffer) })
 }
+fn glBufferData(
+    env: &mut Environment,
+    target: GLenum,
+    size: GuestISize, // GLsizeiptr
+    data: ConstVoidPtr,
+    usage: GLenum,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let data = if data.is_null() {
+            std::ptr::null()
+        } else {
+            mem.ptr_at(data.cast::<u8>(), size.try_into().unwrap())
+                .cast::<GLvoid>()
+        };
+        unsafe { gles.BufferData(target, size as GLsizeiptr, data, usage) }
+    })
+}
+fn glBufferSubData(
+    env: &mut Environment,
+    target: GLenum,
+    offset: GuestISize, // GLintptr
+    size: GuestISize,   // GLsizeiptr
+    data: ConstVoidPtr,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let data = mem
+            .ptr_at(data.cast::<u8>(), size.try_into().unwrap())
+            .cast::<GLvoid>();
+        unsafe { gles.BufferSubData(target, offset as GLintptr, size as GLsizeiptr, data) }
+    })
+}
 
 // Non-pointers
 fn glColor4f(env: &mut Environment, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
@@ -452,6 +667,9 @@ fn glTranslatex(env: &mut Environment, x: GLfixed, y: GLfixed, z: GLfixed) {
 }
 
 // Textures
+fn glActiveTexture(env: &mut Environment, texture: GLenum) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.ActiveTexture(texture) })
+}
 fn glGenTextures(env: &mut Environment, n: GLsizei, textures: MutPtr<GLuint>) {
     with_ctx_and_mem(env, |gles, mem| {
         let n_usize: GuestUSize = n.try_into().unwrap();
@@ -520,6 +738,33 @@ fn glTexImage2D(
         )
     })
 }
+fn glCompressedTexImage2D(
+    env: &mut Environment,
+    target: GLenum,
+    level: GLint,
+    internalformat: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+    border: GLint,
+    image_size: GLsizei,
+    data: ConstVoidPtr,
+) {
+    with_ctx_and_mem(env, |gles, mem| unsafe {
+        let data = mem
+            .ptr_at(data.cast::<u8>(), image_size.try_into().unwrap())
+            .cast::<GLvoid>();
+        gles.CompressedTexImage2D(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            image_size,
+            data,
+        )
+    })
+}
 
 // OES_framebuffer_object
 fn glGenFramebuffersOES(env: &mut Environment, n: GLsizei, framebuffers: MutPtr<GLuint>) {
@@ -554,12 +799,7 @@ fn glRenderbufferStorageOES(
     height: GLsizei,
 ) {
     // apply scale hack
-    let (width, height) = if (width as u32, height as u32) == env.window.size_unrotated_unscaled() {
-        let (width, height) = env.window.size_unrotated_scalehacked();
-        (width as GLsizei, height as GLsizei)
-    } else {
-        (width, height)
-    };
+    let (width, height) = ScaleHack::get(env).renderbuffer_size(width, height);
     with_ctx_and_mem(env, |gles, _mem| unsafe {
         gles.RenderbufferStorageOES(target, internalformat, width, height)
     })
@@ -581,9 +821,26 @@ fn glGetRenderbufferParameterivOES(
     pname: GLenum,
     params: MutPtr<GLint>,
 ) {
+    let scale_hack = ScaleHack::get(env);
     with_ctx_and_mem(env, |gles, mem| {
         let params = mem.ptr_at_mut(params, 1);
-        unsafe { gles.GetRenderbufferParameterivOES(target, pname, params) }
+        unsafe { gles.GetRenderbufferParameterivOES(target, pname, params) };
+        if pname == gles11::RENDERBUFFER_WIDTH_OES || pname == gles11::RENDERBUFFER_HEIGHT_OES {
+            // undo scale hack
+            unsafe { *params /= scale_hack.bound_renderbuffer_factor(gles) };
+        }
+    })
+}
+fn glGetFramebufferAttachmentParameterivOES(
+    env: &mut Environment,
+    target: GLenum,
+    attachment: GLenum,
+    pname: GLenum,
+    params: MutPtr<GLint>,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 1);
+        unsafe { gles.GetFramebufferAttachmentParameterivOES(target, attachment, pname, params) }
     })
 }
 fn glCheckFramebufferStatusOES(env: &mut Environment, target: GLenum) -> GLenum {
@@ -592,6 +849,514 @@ fn glCheckFramebufferStatusOES(env: &mut Environment, target: GLenum) -> GLenum
     })
 }
 
+// APPLE_framebuffer_multisample
+fn glRenderbufferStorageMultisampleAPPLE(
+    env: &mut Environment,
+    target: GLenum,
+    samples: GLsizei,
+    internalformat: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+) {
+    // apply scale hack
+    let (width, height) = ScaleHack::get(env).renderbuffer_size(width, height);
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.RenderbufferStorageMultisampleAPPLE(target, samples, internalformat, width, height)
+    })
+}
+fn glResolveMultisampleFramebufferAPPLE(env: &mut Environment) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.ResolveMultisampleFramebufferAPPLE()
+    })
+}
+
+// EXT_discard_framebuffer
+fn glDiscardFramebufferEXT(
+    env: &mut Environment,
+    target: GLenum,
+    num_attachments: GLsizei,
+    attachments: ConstPtr<GLenum>,
+) {
+    with_ctx_and_mem(env, |gles, mem| {
+        let num_attachments_usize: GuestUSize = num_attachments.try_into().unwrap();
+        let attachments = mem.ptr_at(attachments, num_attachments_usize);
+        unsafe { gles.DiscardFramebufferEXT(target, num_attachments, attachments) }
+    })
+}
+
+// OpenGL ES 2.0 framebuffer objects
+fn glGenFramebuffers(env: &mut Environment, n: GLsizei, framebuffers: MutPtr<GLuint>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let n_usize: GuestUSize = n.try_into().unwrap();
+        let framebuffers = mem.ptr_at_mut(framebuffers, n_usize);
+        unsafe { gles.GenFramebuffers(n, framebuffers) }
+    })
+}
+fn glDeleteFramebuffers(env: &mut Environment, n: GLsizei, framebuffers: ConstPtr<GLuint>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let n_usize: GuestUSize = n.try_into().unwrap();
+        let framebuffers = mem.ptr_at(framebuffers, n_usize);
+        unsafe { gles.DeleteFramebuffers(n, framebuffers) }
+    })
+}
+fn glBindFramebuffer(env: &mut Environment, target: GLenum, framebuffer: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.BindFramebuffer(target, framebuffer)
+    })
+}
+fn glGenRenderbuffers(env: &mut Environment, n: GLsizei, renderbuffers: MutPtr<GLuint>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let n_usize: GuestUSize = n.try_into().unwrap();
+        let renderbuffers = mem.ptr_at_mut(renderbuffers, n_usize);
+        unsafe { gles.GenRenderbuffers(n, renderbuffers) }
+    })
+}
+fn glDeleteRenderbuffers(env: &mut Environment, n: GLsizei, renderbuffers: ConstPtr<GLuint>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let n_usize: GuestUSize = n.try_into().unwrap();
+        let renderbuffers = mem.ptr_at(renderbuffers, n_usize);
+        unsafe { gles.DeleteRenderbuffers(n, renderbuffers) }
+    })
+}
+fn glBindRenderbuffer(env: &mut Environment, target: GLenum, renderbuffer: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.BindRenderbuffer(target, renderbuffer)
+    })
+}
+fn glRenderbufferStorage(
+    env: &mut Environment,
+    target: GLenum,
+    internalformat: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+) {
+    // apply scale hack
+    let (width, height) = ScaleHack::get(env).renderbuffer_size(width, height);
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.RenderbufferStorage(target, internalformat, width, height)
+    })
+}
+fn glFramebufferRenderbuffer(
+    env: &mut Environment,
+    target: GLenum,
+    attachment: GLenum,
+    renderbuffertarget: GLenum,
+    renderbuffer: GLuint,
+) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.FramebufferRenderbuffer(target, attachment, renderbuffertarget, renderbuffer)
+    })
+}
+fn glFramebufferTexture2D(
+    env: &mut Environment,
+    target: GLenum,
+    attachment: GLenum,
+    textarget: GLenum,
+    texture: GLuint,
+    level: GLint,
+) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.FramebufferTexture2D(target, attachment, textarget, texture, level)
+    })
+}
+fn glGetRenderbufferParameteriv(
+    env: &mut Environment,
+    target: GLenum,
+    pname: GLenum,
+    params: MutPtr<GLint>,
+) {
+    let scale_hack = ScaleHack::get(env);
+    with_ctx_and_mem(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 1);
+        unsafe { as_gles2(gles).GetRenderbufferParameteriv(target, pname, params) };
+        if pname == gles11::RENDERBUFFER_WIDTH_OES || pname == gles11::RENDERBUFFER_HEIGHT_OES {
+            // undo scale hack
+            unsafe { *params /= scale_hack.bound_renderbuffer_factor(gles) };
+        }
+    })
+}
+fn glGetFramebufferAttachmentParameteriv(
+    env: &mut Environment,
+    target: GLenum,
+    attachment: GLenum,
+    pname: GLenum,
+    params: MutPtr<GLint>,
+) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 1);
+        unsafe { gles.GetFramebufferAttachmentParameteriv(target, attachment, pname, params) }
+    })
+}
+fn glCheckFramebufferStatus(env: &mut Environment, target: GLenum) -> GLenum {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.CheckFramebufferStatus(target)
+    })
+}
+fn glGenerateMipmap(env: &mut Environment, target: GLenum) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.GenerateMipmap(target) })
+}
+
+// Shaders
+fn glCreateShader(env: &mut Environment, type_: GLenum) -> GLuint {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.CreateShader(type_) })
+}
+fn glDeleteShader(env: &mut Environment, shader: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.DeleteShader(shader) })
+}
+fn glShaderSource(
+    env: &mut Environment,
+    shader: GLuint,
+    count: GLsizei,
+    string: ConstPtr<ConstPtr<u8>>,
+    length: ConstPtr<GLint>,
+) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let count_usize: GuestUSize = count.try_into().unwrap();
+        let mut strings = Vec::with_capacity(count_usize as usize);
+        let mut lengths = Vec::with_capacity(count_usize as usize);
+        for i in 0..count_usize {
+            let string = mem.read(string + i);
+            let length = if length.is_null() {
+                -1
+            } else {
+                mem.read(length + i)
+            };
+            // A negative length means the string is null-terminated.
+            if length < 0 {
+                strings.push(cstr_ptr(mem, string));
+                lengths.push(-1);
+            } else {
+                strings.push(mem.ptr_at(string, length as GuestUSize).cast());
+                lengths.push(length);
+            }
+        }
+        unsafe { gles.ShaderSource(shader, count, strings.as_ptr(), lengths.as_ptr()) }
+    })
+}
+fn glCompileShader(env: &mut Environment, shader: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.CompileShader(shader) })
+}
+fn glGetShaderiv(env: &mut Environment, shader: GLuint, pname: GLenum, params: MutPtr<GLint>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 1);
+        unsafe { gles.GetShaderiv(shader, pname, params) }
+    })
+}
+fn glGetShaderInfoLog(
+    env: &mut Environment,
+    shader: GLuint,
+    buf_size: GLsizei,
+    length: MutPtr<GLsizei>,
+    info_log: MutPtr<u8>,
+) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let length = ptr_at_mut_or_null(mem, length, 1);
+        let info_log = mem
+            .ptr_at_mut(info_log, buf_size.try_into().unwrap())
+            .cast();
+        unsafe { gles.GetShaderInfoLog(shader, buf_size, length, info_log) }
+    })
+}
+
+// Programs
+fn glCreateProgram(env: &mut Environment) -> GLuint {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.CreateProgram() })
+}
+fn glDeleteProgram(env: &mut Environment, program: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.DeleteProgram(program) })
+}
+fn glAttachShader(env: &mut Environment, program: GLuint, shader: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.AttachShader(program, shader)
+    })
+}
+fn glDetachShader(env: &mut Environment, program: GLuint, shader: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.DetachShader(program, shader)
+    })
+}
+fn glLinkProgram(env: &mut Environment, program: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.LinkProgram(program) })
+}
+fn glValidateProgram(env: &mut Environment, program: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.ValidateProgram(program) })
+}
+fn glUseProgram(env: &mut Environment, program: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.UseProgram(program) })
+}
+fn glGetProgramiv(env: &mut Environment, program: GLuint, pname: GLenum, params: MutPtr<GLint>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 1);
+        unsafe { gles.GetProgramiv(program, pname, params) }
+    })
+}
+fn glGetProgramInfoLog(
+    env: &mut Environment,
+    program: GLuint,
+    buf_size: GLsizei,
+    length: MutPtr<GLsizei>,
+    info_log: MutPtr<u8>,
+) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let length = ptr_at_mut_or_null(mem, length, 1);
+        let info_log = mem
+            .ptr_at_mut(info_log, buf_size.try_into().unwrap())
+            .cast();
+        unsafe { gles.GetProgramInfoLog(program, buf_size, length, info_log) }
+    })
+}
+fn glBindAttribLocation(env: &mut Environment, program: GLuint, index: GLuint, name: ConstPtr<u8>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let name = cstr_ptr(mem, name);
+        unsafe { gles.BindAttribLocation(program, index, name) }
+    })
+}
+fn glGetAttribLocation(env: &mut Environment, program: GLuint, name: ConstPtr<u8>) -> GLint {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let name = cstr_ptr(mem, name);
+        unsafe { gles.GetAttribLocation(program, name) }
+    })
+}
+fn glGetUniformLocation(env: &mut Environment, program: GLuint, name: ConstPtr<u8>) -> GLint {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let name = cstr_ptr(mem, name);
+        unsafe { gles.GetUniformLocation(program, name) }
+    })
+}
+fn glGetActiveAttrib(
+    env: &mut Environment,
+    program: GLuint,
+    index: GLuint,
+    buf_size: GLsizei,
+    length: MutPtr<GLsizei>,
+    size: MutPtr<GLint>,
+    type_: MutPtr<GLenum>,
+    name: MutPtr<u8>,
+) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let length = ptr_at_mut_or_null(mem, length, 1);
+        let size = mem.ptr_at_mut(size, 1);
+        let type_ = mem.ptr_at_mut(type_, 1);
+        let name = mem.ptr_at_mut(name, buf_size.try_into().unwrap()).cast();
+        unsafe { gles.GetActiveAttrib(program, index, buf_size, length, size, type_, name) }
+    })
+}
+fn glGetActiveUniform(
+    env: &mut Environment,
+    program: GLuint,
+    index: GLuint,
+    buf_size: GLsizei,
+    length: MutPtr<GLsizei>,
+    size: MutPtr<GLint>,
+    type_: MutPtr<GLenum>,
+    name: MutPtr<u8>,
+) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let length = ptr_at_mut_or_null(mem, length, 1);
+        let size = mem.ptr_at_mut(size, 1);
+        let type_ = mem.ptr_at_mut(type_, 1);
+        let name = mem.ptr_at_mut(name, buf_size.try_into().unwrap()).cast();
+        unsafe { gles.GetActiveUniform(program, index, buf_size, length, size, type_, name) }
+    })
+}
+
+// Uniforms
+fn glUniform1i(env: &mut Environment, location: GLint, v0: GLint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.Uniform1i(location, v0) })
+}
+fn glUniform2i(env: &mut Environment, location: GLint, v0: GLint, v1: GLint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.Uniform2i(location, v0, v1)
+    })
+}
+fn glUniform3i(env: &mut Environment, location: GLint, v0: GLint, v1: GLint, v2: GLint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.Uniform3i(location, v0, v1, v2)
+    })
+}
+fn glUniform4i(env: &mut Environment, location: GLint, v0: GLint, v1: GLint, v2: GLint, v3: GLint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.Uniform4i(location, v0, v1, v2, v3)
+    })
+}
+fn glUniform1f(env: &mut Environment, location: GLint, v0: GLfloat) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.Uniform1f(location, v0) })
+}
+fn glUniform2f(env: &mut Environment, location: GLint, v0: GLfloat, v1: GLfloat) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.Uniform2f(location, v0, v1)
+    })
+}
+fn glUniform3f(env: &mut Environment, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.Uniform3f(location, v0, v1, v2)
+    })
+}
+fn glUniform4f(
+    env: &mut Environment,
+    location: GLint,
+    v0: GLfloat,
+    v1: GLfloat,
+    v2: GLfloat,
+    v3: GLfloat,
+) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.Uniform4f(location, v0, v1, v2, v3)
+    })
+}
+/// Get a host pointer to the `count` vectors or matrices, each with
+/// `components` components, that a `glUniform*v()` function reads.
+fn uniform_values_ptr<T: crate::mem::SafeRead>(
+    mem: &Mem,
+    value: ConstPtr<T>,
+    count: GLsizei,
+    components: GuestUSize,
+) -> *const T {
+    let count: GuestUSize = count.try_into().unwrap();
+    mem.ptr_at(value, count.checked_mul(components).unwrap())
+}
+fn glUniform1iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 1);
+        unsafe { gles.Uniform1iv(location, count, value) }
+    })
+}
+fn glUniform2iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 2);
+        unsafe { gles.Uniform2iv(location, count, value) }
+    })
+}
+fn glUniform3iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 3);
+        unsafe { gles.Uniform3iv(location, count, value) }
+    })
+}
+fn glUniform4iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 4);
+        unsafe { gles.Uniform4iv(location, count, value) }
+    })
+}
+fn glUniform1fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 1);
+        unsafe { gles.Uniform1fv(location, count, value) }
+    })
+}
+fn glUniform2fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 2);
+        unsafe { gles.Uniform2fv(location, count, value) }
+    })
+}
+fn glUniform3fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 3);
+        unsafe { gles.Uniform3fv(location, count, value) }
+    })
+}
+fn glUniform4fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 4);
+        unsafe { gles.Uniform4fv(location, count, value) }
+    })
+}
+fn glUniformMatrix2fv(
+    env: &mut Environment,
+    location: GLint,
+    count: GLsizei,
+    transpose: GLboolean,
+    value: ConstPtr<GLfloat>,
+) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 2 * 2);
+        unsafe { gles.UniformMatrix2fv(location, count, transpose, value) }
+    })
+}
+fn glUniformMatrix3fv(
+    env: &mut Environment,
+    location: GLint,
+    count: GLsizei,
+    transpose: GLboolean,
+    value: ConstPtr<GLfloat>,
+) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 3 * 3);
+        unsafe { gles.UniformMatrix3fv(location, count, transpose, value) }
+    })
+}
+fn glUniformMatrix4fv(
+    env: &mut Environment,
+    location: GLint,
+    count: GLsizei,
+    transpose: GLboolean,
+    value: ConstPtr<GLfloat>,
+) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 4 * 4);
+        unsafe { gles.UniformMatrix4fv(location, count, transpose, value) }
+    })
+}
+
+// Generic vertex attributes
+fn glEnableVertexAttribArray(env: &mut Environment, index: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.EnableVertexAttribArray(index)
+    })
+}
+fn glDisableVertexAttribArray(env: &mut Environment, index: GLuint) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.DisableVertexAttribArray(index)
+    })
+}
+fn glVertexAttribPointer(
+    env: &mut Environment,
+    index: GLuint,
+    size: GLint,
+    type_: GLenum,
+    normalized: GLboolean,
+    stride: GLsizei,
+    pointer: ConstVoidPtr,
+) {
+    with_ctx_and_mem(env, |gles, mem| unsafe {
+        let pointer = translate_pointer_or_offset(gles, mem, pointer, gles20::ARRAY_BUFFER_BINDING);
+        as_gles2(gles).VertexAttribPointer(index, size, type_, normalized, stride, pointer)
+    })
+}
+fn glVertexAttrib1f(env: &mut Environment, index: GLuint, x: GLfloat) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe { gles.VertexAttrib1f(index, x) })
+}
+fn glVertexAttrib2f(env: &mut Environment, index: GLuint, x: GLfloat, y: GLfloat) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.VertexAttrib2f(index, x, y)
+    })
+}
+fn glVertexAttrib3f(env: &mut Environment, index: GLuint, x: GLfloat, y: GLfloat, z: GLfloat) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.VertexAttrib3f(index, x, y, z)
+    })
+}
+fn glVertexAttrib4f(
+    env: &mut Environment,
+    index: GLuint,
+    x: GLfloat,
+    y: GLfloat,
+    z: GLfloat,
+    w: GLfloat,
+) {
+    with_ctx2_and_mem(env, |gles, _mem| unsafe {
+        gles.VertexAttrib4f(index, x, y, z, w)
+    })
+}
+fn glVertexAttrib4fv(env: &mut Environment, index: GLuint, v: ConstPtr<GLfloat>) {
+    with_ctx2_and_mem(env, |gles, mem| {
+        let v = mem.ptr_at(v, 4);
+        unsafe { gles.VertexAttrib4fv(index, v) }
+    })
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     // Generic state manipulation
     export_c_func!(glGetError()),
@@ -600,6 +1365,8 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(glEnableClientState(_)),
     export_c_func!(glDisableClientState(_)),
     export_c_func!(glGetIntegerv(_, _)),
+    export_c_func!(glFlush()),
+    export_c_func!(glFinish()),
     // Other state manipulation
     export_c_func!(glAlphaFunc(_, _)),
     export_c_func!(glAlphaFuncx(_, _)),
@@ -608,6 +1375,10 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(glShadeModel(_)),
     export_c_func!(glScissor(_, _, _, _)),
     export_c_func!(glViewport(_, _, _, _)),
+    export_c_func!(glLineWidth(_)),
+    export_c_func!(glLineWidthx(_)),
+    export_c_func!(glPointSize(_)),
+    export_c_func!(glPointSizex(_)),
     // Lighting
     export_c_func!(glLightf(_, _, _)),
     export_c_func!(glLightx(_, _, _)),
@@ -617,6 +1388,8 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(glGenBuffers(_, _)),
     export_c_func!(glDeleteBuffers(_, _)),
     export_c_func!(glBindBuffer(_, _)),
+    export_c_func!(glBufferData(_, _, _, _)),
+    export_c_func!(glBufferSubData(_, _, _, _)),
     // Non-pointers
     export_c_func!(glColor4f(_, _, _, _)),
     export_c_func!(glColor4x(_, _, _, _)),
@@ -655,11 +1428,13 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(glTranslatef(_, _, _)),
     export_c_func!(glTranslatex(_, _, _)),
     // Textures
+    export_c_func!(glActiveTexture(_)),
     export_c_func!(glGenTextures(_, _)),
     export_c_func!(glDeleteTextures(_, _)),
     export_c_func!(glBindTexture(_, _)),
     export_c_func!(glTexParameteri(_, _, _)),
     export_c_func!(glTexImage2D(_, _, _, _, _, _, _, _, _)),
+    export_c_func!(glCompressedTexImage2D(_, _, _, _, _, _, _, _)),
     // OES_framebuffer_object
     export_c_func!(glGenFramebuffersOES(_, _)),
     export_c_func!(glGenRenderbuffersOES(_, _)),
@@ -668,5 +1443,76 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(glRenderbufferStorageOES(_, _, _, _)),
     export_c_func!(glFramebufferRenderbufferOES(_, _, _, _)),
     export_c_func!(glGetRenderbufferParameterivOES(_, _, _)),
+    export_c_func!(glGetFramebufferAttachmentParameterivOES(_, _, _, _)),
     export_c_func!(glCheckFramebufferStatusOES(_)),
+    // APPLE_framebuffer_multisample
+    export_c_func!(glRenderbufferStorageMultisampleAPPLE(_, _, _, _, _)),
+    export_c_func!(glResolveMultisampleFramebufferAPPLE()),
+    // EXT_discard_framebuffer
+    export_c_func!(glDiscardFramebufferEXT(_, _, _)),
+    // OpenGL ES 2.0 framebuffer objects
+    export_c_func!(glGenFramebuffers(_, _)),
+    export_c_func!(glDeleteFramebuffers(_, _)),
+    export_c_func!(glBindFramebuffer(_, _)),
+    export_c_func!(glGenRenderbuffers(_, _)),
+    export_c_func!(glDeleteRenderbuffers(_, _)),
+    export_c_func!(glBindRenderbuffer(_, _)),
+    export_c_func!(glRenderbufferStorage(_, _, _, _)),
+    export_c_func!(glFramebufferRenderbuffer(_, _, _, _)),
+    export_c_func!(glFramebufferTexture2D(_, _, _, _, _)),
+    export_c_func!(glGetRenderbufferParameteriv(_, _, _)),
+    export_c_func!(glGetFramebufferAttachmentParameteriv(_, _, _, _)),
+    export_c_func!(glCheckFramebufferStatus(_)),
+    export_c_func!(glGenerateMipmap(_)),
+    // Shaders
+    export_c_func!(glCreateShader(_)),
+    export_c_func!(glDeleteShader(_)),
+    export_c_func!(glShaderSource(_, _, _, _)),
+    export_c_func!(glCompileShader(_)),
+    export_c_func!(glGetShaderiv(_, _, _)),
+    export_c_func!(glGetShaderInfoLog(_, _, _, _)),
+    // Programs
+    export_c_func!(glCreateProgram()),
+    export_c_func!(glDeleteProgram(_)),
+    export_c_func!(glAttachShader(_, _)),
+    export_c_func!(glDetachShader(_, _)),
+    export_c_func!(glLinkProgram(_)),
+    export_c_func!(glValidateProgram(_)),
+    export_c_func!(glUseProgram(_)),
+    export_c_func!(glGetProgramiv(_, _, _)),
+    export_c_func!(glGetProgramInfoLog(_, _, _, _)),
+    export_c_func!(glBindAttribLocation(_, _, _)),
+    export_c_func!(glGetAttribLocation(_, _)),
+    export_c_func!(glGetUniformLocation(_, _)),
+    export_c_func!(glGetActiveAttrib(_, _, _, _, _, _, _)),
+    export_c_func!(glGetActiveUniform(_, _, _, _, _, _, _)),
+    // Uniforms
+    export_c_func!(glUniform1i(_, _)),
+    export_c_func!(glUniform2i(_, _, _)),
+    export_c_func!(glUniform3i(_, _, _, _)),
+    export_c_func!(glUniform4i(_, _, _, _, _)),
+    export_c_func!(glUniform1f(_, _)),
+    export_c_func!(glUniform2f(_, _, _)),
+    export_c_func!(glUniform3f(_, _, _, _)),
+    export_c_func!(glUniform4f(_, _, _, _, _)),
+    export_c_func!(glUniform1iv(_, _, _)),
+    export_c_func!(glUniform2iv(_, _, _)),
+    export_c_func!(glUniform3iv(_, _, _)),
+    export_c_func!(glUniform4iv(_, _, _)),
+    export_c_func!(glUniform1fv(_, _, _)),
+    export_c_func!(glUniform2fv(_, _, _)),
+    export_c_func!(glUniform3fv(_, _, _)),
+    export_c_func!(glUniform4fv(_, _, _)),
+    export_c_func!(glUniformMatrix2fv(_, _, _, _)),
+    export_c_func!(glUniformMatrix3fv(_, _, _, _)),
+    export_c_func!(glUniformMatrix4fv(_, _, _, _)),
+    // Generic vertex attributes
+    export_c_func!(glEnableVertexAttribArray(_)),
+    export_c_func!(glDisableVertexAttribArray(_)),
+    export_c_func!(glVertexAttribPointer(_, _, _, _, _, _)),
+    export_c_func!(glVertexAttrib1f(_, _)),
+    export_c_func!(glVertexAttrib2f(_, _, _)),
+    export_c_func!(glVertexAttrib3f(_, _, _, _)),
+    export_c_func!(glVertexAttrib4f(_, _, _, _, _)),
+    export_c_func!(glVertexAttrib4fv(_, _)),
 ];
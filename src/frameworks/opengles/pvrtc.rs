@@ -0,0 +1,487 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! PVRTC texture support (`GL_IMG_texture_compression_pvrtc`).
+//!
+//! Every iPhone OS device has a PowerVR GPU, and so most 3D games ship their
+//! textures in PVRTC format. Very few desktop GPUs support it, so usually we
+//! have to decode it in software and upload the result as an RGBA texture.
+//!
+//! Useful resources:
+//! - [IMG_texture_compression_pvrtc](https://registry.khronos.org/OpenGL/extensions/IMG/IMG_texture_compression_pvrtc.txt)
+//! - Simon Fenney's paper, [_Texture Compression using Low-Frequency Signal Modulation_](https://web.archive.org/web/20210506132205/https://www.imgtec.com/wp-content/uploads/2012/12/Texture-Compression-using-Low-Frequency-Signal-Modulation.pdf)
+//! - Imagination's reference decoder, `PVRTDecompress.cpp`, in the PowerVR SDK.
+//!
+//! The decoder here is structured differently from the reference decoder, but
+//! should produce nearly the same results.
+
+use crate::window::gl21compat as gl21;
+use crate::window::gl21compat::types::*;
+use crate::window::gles11;
+
+pub const PVRTC_FORMATS: [GLenum; 4] = [
+    gles11::COMPRESSED_RGB_PVRTC_4BPPV1_IMG,
+    gles11::COMPRESSED_RGB_PVRTC_2BPPV1_IMG,
+    gles11::COMPRESSED_RGBA_PVRTC_4BPPV1_IMG,
+    gles11::COMPRESSED_RGBA_PVRTC_2BPPV1_IMG,
+];
+
+/// Implementation of `glCompressedTexImage2D` for the PVRTC formats, shared by
+/// the OpenGL ES 1.1 and 2.0 implementations.
+///
+/// If the host supports PVRTC, the data is passed through, otherwise it is
+/// decoded to RGBA.
+pub(super) unsafe fn compressed_tex_image_2d_pvrtc(
+    target: GLenum,
+    level: GLint,
+    internalformat: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+    border: GLint,
+    image_size: GLsizei,
+    data: *const GLvoid,
+) {
+    assert!(PVRTC_FORMATS.contains(&internalformat));
+    assert!(level >= 0);
+    assert!(border == 0);
+
+    if host_supports_pvrtc() {
+        // The IMG enum values are the same in desktop OpenGL.
+        gl21::CompressedTexImage2D(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            image_size,
+            data,
+        );
+        return;
+    }
+
+    let is_2bit = internalformat == gles11::COMPRESSED_RGB_PVRTC_2BPPV1_IMG
+        || internalformat == gles11::COMPRESSED_RGBA_PVRTC_2BPPV1_IMG;
+    let has_alpha = internalformat == gles11::COMPRESSED_RGBA_PVRTC_4BPPV1_IMG
+        || internalformat == gles11::COMPRESSED_RGBA_PVRTC_2BPPV1_IMG;
+
+    let width_u32: u32 = width.try_into().unwrap();
+    let height_u32: u32 = height.try_into().unwrap();
+    let data = std::slice::from_raw_parts(data.cast::<u8>(), image_size.try_into().unwrap());
+    let pixels = decode_pvrtc(data, width_u32, height_u32, is_2bit);
+
+    // The RGB formats still encode alpha, but it must be ignored, which
+    // OpenGL will do for us if the internal format has no alpha.
+    let decoded_internalformat = if has_alpha { gl21::RGBA } else { gl21::RGB };
+    gl21::TexImage2D(
+        target,
+        level,
+        decoded_internalformat as _,
+        width,
+        height,
+        border,
+        gl21::RGBA,
+        gl21::UNSIGNED_BYTE,
+        pixels.as_ptr().cast(),
+    );
+}
+
+/// Implementation of `glGetIntegerv` for `GL_NUM_COMPRESSED_TEXTURE_FORMATS`
+/// and `GL_COMPRESSED_TEXTURE_FORMATS`, shared by the OpenGL ES 1.1 and 2.0
+/// implementations. The host's formats are irrelevant, since only PVRTC is
+/// supported for the guest. Returns `false` for other `pname` values.
+pub(super) unsafe fn get_compressed_texture_formats(pname: GLenum, params: *mut GLint) -> bool {
+    match pname {
+        gl21::NUM_COMPRESSED_TEXTURE_FORMATS => {
+            params.write_unaligned(PVRTC_FORMATS.len() as GLint);
+            true
+        }
+        gl21::COMPRESSED_TEXTURE_FORMATS => {
+            for (i, &format) in PVRTC_FORMATS.iter().enumerate() {
+                params.add(i).write_unaligned(format as GLint);
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Check whether the current context natively supports PVRTC.
+unsafe fn host_supports_pvrtc() -> bool {
+    let extensions = gl21::GetString(gl21::EXTENSIONS);
+    if extensions.is_null() {
+        return false;
+    }
+    std::ffi::CStr::from_ptr(extensions.cast())
+        .to_bytes()
+        .split(|&c| c == b' ')
+        .any(|extension| extension == b"GL_IMG_texture_compression_pvrtc")
+}
+
+/// Get the size in bytes of a PVRTC image with the given dimensions. Images
+/// are always at least 2×2 blocks in size, even when this is bigger than the
+/// dimensions in pixels.
+pub fn pvrtc_data_size(width: u32, height: u32, is_2bit: bool) -> u32 {
+    let (blocks_x, blocks_y) = block_counts(width, height, is_2bit);
+    blocks_x * blocks_y * 8
+}
+
+fn block_size(is_2bit: bool) -> (u32, u32) {
+    if is_2bit {
+        (8, 4)
+    } else {
+        (4, 4)
+    }
+}
+
+fn block_counts(width: u32, height: u32, is_2bit: bool) -> (u32, u32) {
+    let (block_width, block_height) = block_size(is_2bit);
+    (
+        width.div_ceil(block_width).max(2),
+        height.div_ceil(block_height).max(2),
+    )
+}
+
+/// Get the index of a block in the twiddled (Morton) order PVRTC data is
+/// stored in. The y coordinate provides the lower bit of each pair. If the
+/// grid of blocks is not square, the excess bits of the larger coordinate are
+/// placed at the top.
+fn twiddle(blocks_x: u32, blocks_y: u32, x: u32, y: u32) -> u32 {
+    let min_dimension = blocks_x.min(blocks_y);
+    let mut twiddled = 0;
+    let mut bit = 0;
+    while (1 << bit) < min_dimension {
+        twiddled |= ((y >> bit) & 1) << (2 * bit);
+        twiddled |= ((x >> bit) & 1) << (2 * bit + 1);
+        bit += 1;
+    }
+    let excess = if blocks_x > blocks_y { x } else { y };
+    twiddled | ((excess >> bit) << (2 * bit))
+}
+
+/// Expand an `n`-bit color channel to 8 bits.
+fn expand_bits(value: u32, n: u32) -> u8 {
+    let max = (1 << n) - 1;
+    ((value * 255 + max / 2) / max) as u8
+}
+
+/// Unpack the two base colors of a block, as RGBA with 8 bits per channel.
+fn unpack_colors(color_data: u32) -> ([u8; 4], [u8; 4]) {
+    let color_a = if color_data & 0x8000 != 0 {
+        // Opaque: RGB 554
+        [
+            expand_bits((color_data >> 10) & 0x1f, 5),
+            expand_bits((color_data >> 5) & 0x1f, 5),
+            expand_bits((color_data >> 1) & 0xf, 4),
+            255,
+        ]
+    } else {
+        // Translucent: ARGB 3443
+        [
+            expand_bits((color_data >> 8) & 0xf, 4),
+            expand_bits((color_data >> 4) & 0xf, 4),
+            expand_bits((color_data >> 1) & 0x7, 3),
+            expand_bits(((color_data >> 12) & 0x7) << 1, 4),
+        ]
+    };
+    let color_b = if color_data & 0x8000_0000 != 0 {
+        // Opaque: RGB 555
+        [
+            expand_bits((color_data >> 26) & 0x1f, 5),
+            expand_bits((color_data >> 21) & 0x1f, 5),
+            expand_bits((color_data >> 16) & 0x1f, 5),
+            255,
+        ]
+    } else {
+        // Translucent: ARGB 3444
+        [
+            expand_bits((color_data >> 24) & 0xf, 4),
+            expand_bits((color_data >> 20) & 0xf, 4),
+            expand_bits((color_data >> 16) & 0xf, 4),
+            expand_bits(((color_data >> 28) & 0x7) << 1, 4),
+        ]
+    };
+    (color_a, color_b)
+}
+
+/// Modulation state of a single pixel. The weight of color B is in eighths.
+#[derive(Copy, Clone, Default)]
+struct Modulation {
+    weight: u8,
+    punch_through: bool,
+}
+
+/// How a 2bpp pixel that doesn't have a stored modulation value gets one.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Interpolation {
+    /// The pixel has its own value.
+    None,
+    HorizontalAndVertical,
+    Horizontal,
+    Vertical,
+}
+
+const MODULATION_WEIGHTS: [u8; 4] = [0, 3, 5, 8];
+
+/// Decode PVRTC image data to RGBA with 8 bits per channel.
+pub fn decode_pvrtc(data: &[u8], width: u32, height: u32, is_2bit: bool) -> Vec<u8> {
+    let (block_width, block_height) = block_size(is_2bit);
+    let (blocks_x, blocks_y) = block_counts(width, height, is_2bit);
+    assert!(data.len() >= pvrtc_data_size(width, height, is_2bit) as usize);
+
+    // The decoding is done at a size of at least 2×2 blocks, and cropped at
+    // the end.
+    let full_width = blocks_x * block_width;
+    let full_height = blocks_y * block_height;
+
+    let mut colors = Vec::with_capacity((blocks_x * blocks_y) as usize);
+    let mut modulations = vec![Modulation::default(); (full_width * full_height) as usize];
+    let mut interpolations = vec![Interpolation::None; (full_width * full_height) as usize];
+
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let offset = twiddle(blocks_x, blocks_y, block_x, block_y) as usize * 8;
+            let mut modulation_data =
+                u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+            let color_data = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap());
+            colors.push(unpack_colors(color_data));
+
+            let mode_flag = color_data & 1 != 0;
+            let base_x = block_x * block_width;
+            let base_y = block_y * block_height;
+            let pixel_idx = |x: u32, y: u32| ((base_y + y) * full_width + base_x + x) as usize;
+
+            if !is_2bit {
+                for y in 0..4 {
+                    for x in 0..4 {
+                        let value = (modulation_data & 3) as usize;
+                        modulation_data >>= 2;
+                        modulations[pixel_idx(x, y)] = if mode_flag {
+                            // Punch-through mode
+                            Modulation {
+                                weight: [0, 4, 4, 8][value],
+                                punch_through: value == 2,
+                            }
+                        } else {
+                            Modulation {
+                                weight: MODULATION_WEIGHTS[value],
+                                punch_through: false,
+                            }
+                        };
+                    }
+                }
+            } else if !mode_flag {
+                // One bit per pixel, selecting either color A or color B.
+                for y in 0..4 {
+                    for x in 0..8 {
+                        let value = modulation_data & 1;
+                        modulation_data >>= 1;
+                        modulations[pixel_idx(x, y)] = Modulation {
+                            weight: if value != 0 { 8 } else { 0 },
+                            punch_through: false,
+                        };
+                    }
+                }
+            } else {
+                // Two bits for every other pixel in a checkerboard pattern,
+                // with the rest interpolated from their neighbors. The lowest
+                // bit of the first value and of the value for the pixel at
+                // (4, 2) are repurposed to select the interpolation mode.
+                let interpolation = if modulation_data & 1 == 0 {
+                    Interpolation::HorizontalAndVertical
+                } else if modulation_data & (1 << 20) != 0 {
+                    Interpolation::Vertical
+                } else {
+                    Interpolation::Horizontal
+                };
+                if modulation_data & 1 != 0 {
+                    // Copy the high bit of (4, 2)'s value into its low bit.
+                    if modulation_data & (1 << 21) != 0 {
+                        modulation_data |= 1 << 20;
+                    } else {
+                        modulation_data &= !(1 << 20);
+                    }
+                }
+                // Copy the high bit of the first value into its low bit.
+                if modulation_data & 2 != 0 {
+                    modulation_data |= 1;
+                } else {
+                    modulation_data &= !1;
+                }
+
+                for y in 0..4 {
+                    for x in 0..8 {
+                        if (x ^ y) & 1 == 0 {
+                            let value = (modulation_data & 3) as usize;
+                            modulation_data >>= 2;
+                            modulations[pixel_idx(x, y)] = Modulation {
+                                weight: MODULATION_WEIGHTS[value],
+                                punch_through: false,
+                            };
+                        } else {
+                            interpolations[pixel_idx(x, y)] = interpolation;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Fill in the interpolated 2bpp modulation values. Their neighbors always
+    // have stored values. Like the colors, this wraps at the edges.
+    if is_2bit {
+        for y in 0..full_height {
+            for x in 0..full_width {
+                let idx = (y * full_width + x) as usize;
+                let interpolation = interpolations[idx];
+                if interpolation == Interpolation::None {
+                    continue;
+                }
+                let weight_at = |x: u32, y: u32| -> u32 {
+                    let x = x % full_width;
+                    let y = y % full_height;
+                    modulations[(y * full_width + x) as usize].weight.into()
+                };
+                let horizontal = weight_at(x + full_width - 1, y) + weight_at(x + 1, y);
+                let vertical = weight_at(x, y + full_height - 1) + weight_at(x, y + 1);
+                let weight = match interpolation {
+                    Interpolation::HorizontalAndVertical => (horizontal + vertical + 2) / 4,
+                    Interpolation::Horizontal => (horizontal + 1) / 2,
+                    Interpolation::Vertical => (vertical + 1) / 2,
+                    Interpolation::None => unreachable!(),
+                };
+                modulations[idx].weight = weight as u8;
+            }
+        }
+    }
+
+    // The base colors of each block are located at its center, and the colors
+    // for a pixel are bilinearly interpolated from the four nearest blocks.
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        // Position relative to the center of the first block, offset by one
+        // whole grid to avoid negative numbers.
+        let rel_y = y + full_height - block_height / 2;
+        let block_y0 = (rel_y / block_height) % blocks_y;
+        let block_y1 = (block_y0 + 1) % blocks_y;
+        let frac_y = rel_y % block_height;
+        for x in 0..width {
+            let rel_x = x + full_width - block_width / 2;
+            let block_x0 = (rel_x / block_width) % blocks_x;
+            let block_x1 = (block_x0 + 1) % blocks_x;
+            let frac_x = rel_x % block_width;
+
+            let corners = [
+                (
+                    block_x0,
+                    block_y0,
+                    (block_width - frac_x) * (block_height - frac_y),
+                ),
+                (block_x1, block_y0, frac_x * (block_height - frac_y)),
+                (block_x0, block_y1, (block_width - frac_x) * frac_y),
+                (block_x1, block_y1, frac_x * frac_y),
+            ];
+            let total_weight = block_width * block_height;
+
+            let Modulation {
+                weight,
+                punch_through,
+            } = modulations[(y * full_width + x) as usize];
+            let weight = u32::from(weight);
+
+            for channel in 0..4 {
+                let mut color_a = 0;
+                let mut color_b = 0;
+                for &(block_x, block_y, corner_weight) in &corners {
+                    let (a, b) = colors[(block_y * blocks_x + block_x) as usize];
+                    color_a += u32::from(a[channel]) * corner_weight;
+                    color_b += u32::from(b[channel]) * corner_weight;
+                }
+                let value = (color_a * (8 - weight) + color_b * weight) / (8 * total_weight);
+                if channel == 3 && punch_through {
+                    pixels.push(0);
+                } else {
+                    pixels.push(value as u8);
+                }
+            }
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn twiddle_square() {
+        assert_eq!(twiddle(4, 4, 0, 0), 0);
+        assert_eq!(twiddle(4, 4, 0, 1), 1);
+        assert_eq!(twiddle(4, 4, 1, 0), 2);
+        assert_eq!(twiddle(4, 4, 1, 1), 3);
+        assert_eq!(twiddle(4, 4, 2, 0), 8);
+        assert_eq!(twiddle(4, 4, 3, 3), 15);
+    }
+
+    #[test]
+    fn twiddle_rectangular() {
+        // 2bpp square textures have twice as many blocks vertically.
+        assert_eq!(twiddle(2, 4, 1, 1), 3);
+        assert_eq!(twiddle(2, 4, 0, 2), 4);
+        assert_eq!(twiddle(2, 4, 1, 3), 7);
+    }
+
+    /// Build a block with the same opaque colors everywhere.
+    fn opaque_block(modulation_data: u32, color_a: u32, color_b: u32, mode: bool) -> [u8; 8] {
+        let color_data = 0x8000_0000 | (color_b << 16) | 0x8000 | (color_a << 1) | mode as u32;
+        let mut block = [0u8; 8];
+        block[..4].copy_from_slice(&modulation_data.to_le_bytes());
+        block[4..].copy_from_slice(&color_data.to_le_bytes());
+        block
+    }
+
+    #[test]
+    fn decode_4bpp_solid() {
+        // Color A is pure red (RGB 554), color B is pure blue (RGB 555).
+        let red = 0x1f << 9;
+        let blue = 0x1f;
+        let all_a = opaque_block(0, red, blue, false);
+        let all_b = opaque_block(0xffff_ffff, red, blue, false);
+        for (block, expected) in [(all_a, [255, 0, 0, 255]), (all_b, [0, 0, 255, 255])] {
+            let data = block.repeat(4);
+            let pixels = decode_pvrtc(&data, 8, 8, false);
+            assert_eq!(pixels.len(), 8 * 8 * 4);
+            for pixel in pixels.chunks(4) {
+                assert_eq!(pixel, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_4bpp_punch_through() {
+        // Modulation value 2 in punch-through mode is half-way and transparent.
+        let white_a = 0x3fff;
+        let white_b = 0x7fff;
+        let data = opaque_block(0xaaaa_aaaa, white_a, white_b, true).repeat(4);
+        let pixels = decode_pvrtc(&data, 8, 8, false);
+        for pixel in pixels.chunks(4) {
+            assert_eq!(pixel[3], 0);
+        }
+    }
+
+    #[test]
+    fn decode_2bpp_small() {
+        // Textures smaller than 2×2 blocks are still stored as 2×2 blocks.
+        assert_eq!(pvrtc_data_size(8, 8, true), 32);
+        let green_a = 0x1f << 4;
+        let green_b = 0x1f << 5;
+        let data = opaque_block(0xffff_ffff, green_a, green_b, false).repeat(4);
+        let pixels = decode_pvrtc(&data, 8, 8, true);
+        assert_eq!(pixels.len(), 8 * 8 * 4);
+        for pixel in pixels.chunks(4) {
+            assert_eq!(pixel, [0, 255, 0, 255]);
+        }
+    }
+}
@@ -0,0 +1,496 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `SecureTransport.h`
+//!
+//! The app moves the encrypted bytes itself, with the read and write functions
+//! it provides, while the TLS itself is done by [crate::tls::Session].
+//!
+//! Only the client side of stream connections is supported. The handshake
+//! isn't broken for `kSSLSessionOptionBreakOnServerAuth`, so apps can't do
+//! their own evaluation of the server's certificate. Certificates are still
+//! validated the normal way instead, unless `--relaxed-tls` is used, since the
+//! app's own checks may well be stricter (e.g. certificate pinning).
+
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use crate::frameworks::core_foundation::CFTypeRef;
+use crate::frameworks::mac_types::OSStatus;
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr};
+use crate::objc::{nil, objc_classes, ClassExports, HostObject};
+use crate::tls;
+use crate::Environment;
+use std::io::ErrorKind;
+
+pub type SSLContextRef = CFTypeRef;
+/// Opaque value passed to the app's read and write functions.
+pub type SSLConnectionRef = ConstVoidPtr;
+
+type SSLProtocolSide = u32;
+const kSSLServerSide: SSLProtocolSide = 0;
+const kSSLClientSide: SSLProtocolSide = 1;
+
+type SSLConnectionType = u32;
+const kSSLStreamType: SSLConnectionType = 0;
+
+type SSLSessionOption = u32;
+const kSSLSessionOptionBreakOnServerAuth: SSLSessionOption = 0;
+
+type SSLProtocol = u32;
+const kSSLProtocol3: SSLProtocol = 2;
+
+type SSLSessionState = u32;
+const kSSLIdle: SSLSessionState = 0;
+const kSSLHandshake: SSLSessionState = 1;
+const kSSLConnected: SSLSessionState = 2;
+const kSSLClosed: SSLSessionState = 3;
+const kSSLAborted: SSLSessionState = 4;
+
+const noErr: OSStatus = 0;
+const paramErr: OSStatus = -50;
+const errSSLProtocol: OSStatus = -9800;
+const errSSLWouldBlock: OSStatus = -9803;
+const errSSLClosedGraceful: OSStatus = -9805;
+const errSSLClosedAbort: OSStatus = -9806;
+const errSSLClosedNoNotify: OSStatus = -9816;
+
+/// How many bytes to ask the app's read function for at once.
+const READ_CHUNK_SIZE: GuestUSize = 16 * 1024;
+
+struct SSLContextHostObject {
+    /// `OSStatus (*)(SSLConnectionRef, void *data, size_t *dataLength)`
+    read_func: Option<GuestFunction>,
+    /// `OSStatus (*)(SSLConnectionRef, const void *data, size_t *dataLength)`
+    write_func: Option<GuestFunction>,
+    connection: SSLConnectionRef,
+    /// Set by `SSLSetPeerDomainName`. Sent to the server, and unless the
+    /// settings say otherwise, checked against its certificate.
+    peer_domain_name: String,
+    settings: tls::Settings,
+    /// Created by the first `SSLHandshake`.
+    session: Option<tls::Session>,
+    state: SSLSessionState,
+}
+impl HostObject for SSLContextHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// SSLContext is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not exposed.
+@implementation _touchHLE_SSLContext: NSObject
+@end
+
+};
+
+fn SSLCreateContext(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    protocol_side: SSLProtocolSide,
+    connection_type: SSLConnectionType,
+) -> SSLContextRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    if protocol_side == kSSLServerSide {
+        log!("TODO: SSLCreateContext() for the server side");
+        return nil;
+    }
+    assert_eq!(protocol_side, kSSLClientSide);
+    if connection_type != kSSLStreamType {
+        log!("TODO: SSLCreateContext() for datagram connections");
+        return nil;
+    }
+
+    let mut settings = tls::Settings::new("", env.options.relaxed_tls);
+    // Without a peer domain name, the certificate's name isn't checked.
+    settings.peer_name = None;
+    let host_object = Box::new(SSLContextHostObject {
+        read_func: None,
+        write_func: None,
+        connection: Ptr::null(),
+        peer_domain_name: String::new(),
+        settings,
+        session: None,
+        state: kSSLIdle,
+    });
+    let class = env
+        .objc
+        .get_known_class("_touchHLE_SSLContext", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+fn SSLSetIOFuncs(
+    env: &mut Environment,
+    context: SSLContextRef,
+    read_func: GuestFunction,
+    write_func: GuestFunction,
+) -> OSStatus {
+    let host_object = env.objc.borrow_mut::<SSLContextHostObject>(context);
+    host_object.read_func = Some(read_func);
+    host_object.write_func = Some(write_func);
+    noErr
+}
+
+fn SSLSetConnection(
+    env: &mut Environment,
+    context: SSLContextRef,
+    connection: SSLConnectionRef,
+) -> OSStatus {
+    env.objc
+        .borrow_mut::<SSLContextHostObject>(context)
+        .connection = connection;
+    noErr
+}
+
+fn SSLGetConnection(
+    env: &mut Environment,
+    context: SSLContextRef,
+    connection: MutPtr<SSLConnectionRef>,
+) -> OSStatus {
+    let value = env.objc.borrow::<SSLContextHostObject>(context).connection;
+    env.mem.write(connection, value);
+    noErr
+}
+
+fn SSLSetPeerDomainName(
+    env: &mut Environment,
+    context: SSLContextRef,
+    peer_name: ConstPtr<u8>,
+    peer_name_len: GuestUSize,
+) -> OSStatus {
+    let name = env.mem.bytes_at(peer_name, peer_name_len);
+    // The length may or may not include a null terminator.
+    let name = name.strip_suffix(b"\0").unwrap_or(name);
+    let name = String::from_utf8_lossy(name).into_owned();
+    let relaxed = env.options.relaxed_tls;
+    let host_object = env.objc.borrow_mut::<SSLContextHostObject>(context);
+    if host_object.session.is_some() {
+        return paramErr;
+    }
+    if !relaxed {
+        host_object.settings.peer_name = Some(name.clone());
+    }
+    host_object.peer_domain_name = name;
+    noErr
+}
+
+fn SSLSetSessionOption(
+    env: &mut Environment,
+    context: SSLContextRef,
+    option: SSLSessionOption,
+    value: bool,
+) -> OSStatus {
+    if option == kSSLSessionOptionBreakOnServerAuth {
+        if value {
+            log!(
+                "Warning: SSLSetSessionOption({:?}): the app wants to evaluate the server's certificate itself, which isn't supported. {}",
+                context,
+                if env.options.relaxed_tls {
+                    "Certificates aren't validated because of --relaxed-tls."
+                } else {
+                    "It will be validated the normal way instead."
+                }
+            );
+        }
+    } else {
+        log!(
+            "TODO: SSLSetSessionOption({:?}, {}, {})",
+            context,
+            option,
+            value
+        );
+    }
+    noErr
+}
+
+fn SSLSetProtocolVersionMin(
+    env: &mut Environment,
+    context: SSLContextRef,
+    min_version: SSLProtocol,
+) -> OSStatus {
+    if min_version <= kSSLProtocol3 {
+        env.objc
+            .borrow_mut::<SSLContextHostObject>(context)
+            .settings
+            .allow_legacy_protocols = true;
+    }
+    noErr
+}
+
+fn SSLSetProtocolVersionMax(
+    _env: &mut Environment,
+    context: SSLContextRef,
+    max_version: SSLProtocol,
+) -> OSStatus {
+    log_dbg!(
+        "Ignoring SSLSetProtocolVersionMax({:?}, {})",
+        context,
+        max_version
+    );
+    noErr
+}
+
+fn SSLGetSessionState(
+    env: &mut Environment,
+    context: SSLContextRef,
+    state: MutPtr<SSLSessionState>,
+) -> OSStatus {
+    let value = env.objc.borrow::<SSLContextHostObject>(context).state;
+    env.mem.write(state, value);
+    noErr
+}
+
+fn session(env: &mut Environment, context: SSLContextRef) -> &mut tls::Session {
+    env.objc
+        .borrow_mut::<SSLContextHostObject>(context)
+        .session
+        .as_mut()
+        .unwrap()
+}
+
+/// Give the app's write function everything the session wants to send.
+fn send_pending(env: &mut Environment, context: SSLContextRef) -> Result<(), OSStatus> {
+    let bytes = session(env, context).take_outgoing();
+    if bytes.is_empty() {
+        return Ok(());
+    }
+    let host_object = env.objc.borrow::<SSLContextHostObject>(context);
+    let write_func = host_object.write_func.unwrap();
+    let connection = host_object.connection;
+
+    let len: GuestUSize = bytes.len().try_into().unwrap();
+    let data: MutPtr<u8> = env.mem.alloc(len).cast();
+    env.mem.bytes_at_mut(data, len).copy_from_slice(&bytes);
+    let len_ptr = env.mem.alloc_and_write(len);
+    let data_arg: ConstVoidPtr = data.cast_const().cast();
+    let status: OSStatus = write_func.call_from_host(env, (connection, data_arg, len_ptr));
+    let written = env.mem.read(len_ptr).min(len);
+    env.mem.free(len_ptr.cast());
+    env.mem.free(data.cast());
+
+    if written < len {
+        session(env, context).unsend(bytes[written as usize..].to_vec());
+        return Err(if status == noErr {
+            errSSLWouldBlock
+        } else {
+            status
+        });
+    }
+    Ok(())
+}
+
+/// Get whatever the app's read function has for the session.
+fn receive(env: &mut Environment, context: SSLContextRef) -> Result<(), OSStatus> {
+    let host_object = env.objc.borrow::<SSLContextHostObject>(context);
+    let read_func = host_object.read_func.unwrap();
+    let connection = host_object.connection;
+
+    let data: MutVoidPtr = env.mem.alloc(READ_CHUNK_SIZE);
+    let len_ptr = env.mem.alloc_and_write(READ_CHUNK_SIZE);
+    let status: OSStatus = read_func.call_from_host(env, (connection, data, len_ptr));
+    let read = env.mem.read(len_ptr).min(READ_CHUNK_SIZE);
+    let bytes = env.mem.bytes_at(data.cast(), read).to_vec();
+    env.mem.free(len_ptr.cast());
+    env.mem.free(data);
+
+    if !bytes.is_empty() {
+        session(env, context).receive(&bytes);
+        return Ok(());
+    }
+    match status {
+        noErr | errSSLWouldBlock => Err(errSSLWouldBlock),
+        errSSLClosedGraceful | errSSLClosedNoNotify | errSSLClosedAbort => {
+            // Let the session find out, so it fails rather than waiting.
+            session(env, context).receive(&[]);
+            Ok(())
+        }
+        _ => Err(status),
+    }
+}
+
+fn SSLHandshake(env: &mut Environment, context: SSLContextRef) -> OSStatus {
+    let host_object = env.objc.borrow_mut::<SSLContextHostObject>(context);
+    match host_object.state {
+        kSSLIdle => {
+            if host_object.read_func.is_none() || host_object.write_func.is_none() {
+                return paramErr;
+            }
+            host_object.session = Some(tls::Session::new(
+                &host_object.peer_domain_name,
+                host_object.settings.clone(),
+            ));
+            host_object.state = kSSLHandshake;
+        }
+        kSSLHandshake => (),
+        kSSLConnected => return noErr,
+        _ => return errSSLClosedAbort,
+    }
+
+    loop {
+        let result = session(env, context).handshake();
+        // Whatever the result, there may be something to send: the next part
+        // of the handshake, its final message, or an alert.
+        let sent = send_pending(env, context);
+        match result {
+            Ok(()) => {
+                log_dbg!("SSLHandshake({:?}) done", context);
+                env.objc.borrow_mut::<SSLContextHostObject>(context).state = kSSLConnected;
+                return sent.err().unwrap_or(noErr);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                if let Err(status) = sent.and_then(|_| receive(env, context)) {
+                    return status;
+                }
+            }
+            Err(e) => {
+                log!("Warning: SSLHandshake({:?}) failed: {}", context, e);
+                env.objc.borrow_mut::<SSLContextHostObject>(context).state = kSSLAborted;
+                return errSSLProtocol;
+            }
+        }
+    }
+}
+
+/// Handshake first if the app hasn't, like the real Secure Transport does.
+fn ensure_connected(env: &mut Environment, context: SSLContextRef) -> Result<(), OSStatus> {
+    match env.objc.borrow::<SSLContextHostObject>(context).state {
+        kSSLConnected => Ok(()),
+        kSSLIdle | kSSLHandshake => match SSLHandshake(env, context) {
+            noErr => Ok(()),
+            status => Err(status),
+        },
+        kSSLClosed => Err(errSSLClosedGraceful),
+        _ => Err(errSSLClosedAbort),
+    }
+}
+
+fn SSLWrite(
+    env: &mut Environment,
+    context: SSLContextRef,
+    data: ConstVoidPtr,
+    data_length: GuestUSize,
+    processed: MutPtr<GuestUSize>,
+) -> OSStatus {
+    env.mem.write(processed, 0);
+    if let Err(status) = ensure_connected(env, context) {
+        return status;
+    }
+    // Anything left over from last time has to go first.
+    if let Err(status) = send_pending(env, context) {
+        return status;
+    }
+    if data_length == 0 {
+        return noErr;
+    }
+
+    let bytes = env.mem.bytes_at(data.cast(), data_length).to_vec();
+    if let Err(e) = session(env, context).write(&bytes) {
+        log!("Warning: SSLWrite({:?}) failed: {}", context, e);
+        env.objc.borrow_mut::<SSLContextHostObject>(context).state = kSSLAborted;
+        return errSSLClosedAbort;
+    }
+    // The data has been accepted even if it can't all be sent yet. The rest
+    // is sent by the next call.
+    env.mem.write(processed, data_length);
+    match send_pending(env, context) {
+        Ok(()) => noErr,
+        Err(status) => status,
+    }
+}
+
+fn SSLRead(
+    env: &mut Environment,
+    context: SSLContextRef,
+    data: MutVoidPtr,
+    data_length: GuestUSize,
+    processed: MutPtr<GuestUSize>,
+) -> OSStatus {
+    env.mem.write(processed, 0);
+    if let Err(status) = ensure_connected(env, context) {
+        return status;
+    }
+    if data_length == 0 {
+        return noErr;
+    }
+
+    let mut buffer = vec![0u8; data_length as usize];
+    loop {
+        let result = session(env, context).read(&mut buffer);
+        // Reading can make the session want to send something too.
+        let _ = send_pending(env, context);
+        match result {
+            Ok(0) => {
+                env.objc.borrow_mut::<SSLContextHostObject>(context).state = kSSLClosed;
+                return errSSLClosedGraceful;
+            }
+            Ok(count) => {
+                let count: GuestUSize = count.try_into().unwrap();
+                env.mem
+                    .bytes_at_mut(data.cast(), count)
+                    .copy_from_slice(&buffer[..count as usize]);
+                env.mem.write(processed, count);
+                return noErr;
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                if let Err(status) = receive(env, context) {
+                    return status;
+                }
+            }
+            Err(e) => {
+                log!("Warning: SSLRead({:?}) failed: {}", context, e);
+                env.objc.borrow_mut::<SSLContextHostObject>(context).state = kSSLAborted;
+                return errSSLClosedAbort;
+            }
+        }
+    }
+}
+
+fn SSLGetBufferedReadSize(
+    env: &mut Environment,
+    context: SSLContextRef,
+    buf_size: MutPtr<GuestUSize>,
+) -> OSStatus {
+    let host_object = env.objc.borrow::<SSLContextHostObject>(context);
+    let size = host_object
+        .session
+        .as_ref()
+        .map_or(0, |session| session.buffered_read_size());
+    env.mem.write(buf_size, size.try_into().unwrap());
+    noErr
+}
+
+fn SSLClose(env: &mut Environment, context: SSLContextRef) -> OSStatus {
+    let host_object = env.objc.borrow_mut::<SSLContextHostObject>(context);
+    let Some(ref mut session) = host_object.session else {
+        host_object.state = kSSLClosed;
+        return noErr;
+    };
+    session.close();
+    let was_connected = host_object.state == kSSLConnected;
+    host_object.state = kSSLClosed;
+    if was_connected {
+        // Try to send the close alert, but it doesn't matter if it fails.
+        let _ = send_pending(env, context);
+    }
+    noErr
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(SSLCreateContext(_, _, _)),
+    export_c_func!(SSLSetIOFuncs(_, _, _)),
+    export_c_func!(SSLSetConnection(_, _)),
+    export_c_func!(SSLGetConnection(_, _)),
+    export_c_func!(SSLSetPeerDomainName(_, _, _)),
+    export_c_func!(SSLSetSessionOption(_, _, _)),
+    export_c_func!(SSLSetProtocolVersionMin(_, _)),
+    export_c_func!(SSLSetProtocolVersionMax(_, _)),
+    export_c_func!(SSLGetSessionState(_, _)),
+    export_c_func!(SSLHandshake(_)),
+    export_c_func!(SSLWrite(_, _, _, _)),
+    export_c_func!(SSLRead(_, _, _, _)),
+    export_c_func!(SSLGetBufferedReadSize(_, _)),
+    export_c_func!(SSLClose(_)),
+];
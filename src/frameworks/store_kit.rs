@@ -0,0 +1,28 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The StoreKit framework, for in-app purchases.
+//!
+//! There's no App Store to talk to, so the store is simulated: the products
+//! and how purchases of them turn out come from a file the user writes, and
+//! completed purchases are recorded in another (see [catalog]).
+//!
+//! Useful resources:
+//! - Apple's [In-App Purchase Programming Guide](https://developer.apple.com/library/archive/documentation/NetworkingInternet/Conceptual/StoreKitGuide/Introduction.html)
+
+pub mod catalog;
+pub mod sk_error;
+pub mod sk_payment;
+pub mod sk_payment_queue;
+pub mod sk_payment_transaction;
+pub mod sk_product;
+pub mod sk_request;
+
+#[derive(Default)]
+pub struct State {
+    catalog: catalog::State,
+    sk_payment_queue: sk_payment_queue::State,
+    sk_request: sk_request::State,
+}
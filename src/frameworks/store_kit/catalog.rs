@@ -0,0 +1,460 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The simulated store: the products an app can buy, how purchases turn out,
+//! and the receipts of past purchases.
+//!
+//! Both are kept in the directory set with `--store-dir=`, in files named
+//! after the app's bundle identifier. The products file is written by the user
+//! and looks like this:
+//!
+//! ```text
+//! # Comments start with '#'.
+//! locale = en_US
+//!
+//! [com.example.game.levels]
+//! title = More Levels
+//! description = Twenty new levels to play.
+//! price = 0.99
+//! type = non-consumable
+//! results = cancelled, purchased
+//! ```
+//!
+//! `type` is `consumable` (the default), `non-consumable` or `subscription`.
+//! `results` lists how successive purchases of the product turn out:
+//! `purchased`, `failed`, `cancelled`, `not-allowed` or `invalid`. Once the
+//! list runs out, purchases succeed.
+//!
+//! The receipts file is written by touchHLE, one purchase per line, so that
+//! purchases can be restored in later runs.
+
+use crate::frameworks::foundation::ns_date::now_since_reference_date;
+use crate::frameworks::foundation::{NSInteger, NSTimeInterval};
+use crate::Environment;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Transaction identifiers are numbers starting from here, like the App
+/// Store's.
+const FIRST_TRANSACTION_IDENTIFIER: u64 = 1_000_000_001;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProductType {
+    Consumable,
+    NonConsumable,
+    Subscription,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PurchaseResult {
+    Purchased,
+    Failed,
+    Cancelled,
+    NotAllowed,
+    Invalid,
+}
+impl PurchaseResult {
+    fn parse(value: &str) -> Option<PurchaseResult> {
+        match value {
+            "purchased" => Some(PurchaseResult::Purchased),
+            "failed" => Some(PurchaseResult::Failed),
+            "cancelled" => Some(PurchaseResult::Cancelled),
+            "not-allowed" => Some(PurchaseResult::NotAllowed),
+            "invalid" => Some(PurchaseResult::Invalid),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Product {
+    pub identifier: String,
+    pub title: String,
+    pub description: String,
+    /// Kept as written, e.g. `0.99`.
+    pub price: String,
+    pub product_type: ProductType,
+    pub results: Vec<PurchaseResult>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catalog {
+    /// Locale identifier for prices, e.g. `en_US`.
+    pub locale: String,
+    pub products: Vec<Product>,
+}
+impl Default for Catalog {
+    fn default() -> Self {
+        Catalog {
+            locale: "en_US".to_string(),
+            products: Vec::new(),
+        }
+    }
+}
+impl Catalog {
+    pub fn parse(text: &str) -> Result<Catalog, String> {
+        let mut catalog = Catalog::default();
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = line_number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(identifier) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let identifier = identifier.trim();
+                if identifier.is_empty() {
+                    return Err(format!("Line {}: empty product identifier", line_number));
+                }
+                catalog.products.push(Product {
+                    identifier: identifier.to_string(),
+                    title: identifier.to_string(),
+                    description: String::new(),
+                    price: "0.99".to_string(),
+                    product_type: ProductType::Consumable,
+                    results: Vec::new(),
+                });
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("Line {}: expected 'key = value'", line_number));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let Some(product) = catalog.products.last_mut() else {
+                if key == "locale" {
+                    catalog.locale = value.to_string();
+                    continue;
+                }
+                return Err(format!(
+                    "Line {}: {:?} outside of a product",
+                    line_number, key
+                ));
+            };
+            match key {
+                "title" => product.title = value.to_string(),
+                "description" => product.description = value.to_string(),
+                "price" => {
+                    if !value.parse::<f64>().is_ok_and(|price| price >= 0.0) {
+                        return Err(format!("Line {}: invalid price {:?}", line_number, value));
+                    }
+                    product.price = value.to_string();
+                }
+                "type" => {
+                    product.product_type = match value {
+                        "consumable" => ProductType::Consumable,
+                        "non-consumable" => ProductType::NonConsumable,
+                        "subscription" => ProductType::Subscription,
+                        _ => {
+                            return Err(format!(
+                                "Line {}: invalid product type {:?}",
+                                line_number, value
+                            ))
+                        }
+                    }
+                }
+                "results" => {
+                    product.results = value
+                        .split(',')
+                        .map(|result| {
+                            PurchaseResult::parse(result.trim()).ok_or_else(|| {
+                                format!("Line {}: invalid result {:?}", line_number, result)
+                            })
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => return Err(format!("Line {}: unknown key {:?}", line_number, key)),
+            }
+        }
+        Ok(catalog)
+    }
+
+    pub fn product(&self, identifier: &str) -> Option<&Product> {
+        self.products
+            .iter()
+            .find(|product| product.identifier == identifier)
+    }
+}
+
+/// A completed purchase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Receipt {
+    pub transaction_identifier: String,
+    pub product_identifier: String,
+    pub quantity: NSInteger,
+    /// When the purchase was made.
+    pub date: NSTimeInterval,
+}
+impl Receipt {
+    /// Parse a line of the receipts file, which has tab-separated fields.
+    fn parse(line: &str) -> Option<Receipt> {
+        let mut fields = line.split('\t');
+        let receipt = Receipt {
+            transaction_identifier: fields.next()?.to_string(),
+            product_identifier: fields.next()?.to_string(),
+            quantity: fields.next()?.parse().ok()?,
+            date: fields.next()?.parse().ok()?,
+        };
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(receipt)
+    }
+
+    fn format(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\n",
+            self.transaction_identifier, self.product_identifier, self.quantity, self.date
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct State {
+    /// Loaded on first use.
+    catalog: Option<Catalog>,
+    /// Loaded on first use.
+    receipts: Option<Vec<Receipt>>,
+    /// How many of each product's scripted results have been used.
+    results_used: HashMap<String, usize>,
+    next_transaction_identifier: Option<u64>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.store_kit.catalog
+    }
+}
+
+fn products_path(env: &Environment) -> PathBuf {
+    env.options
+        .store_dir
+        .join(format!("{}.txt", env.bundle.bundle_identifier()))
+}
+
+fn receipts_path(env: &Environment) -> PathBuf {
+    env.options
+        .store_dir
+        .join(format!("{}.receipts", env.bundle.bundle_identifier()))
+}
+
+/// Get the app's products, reading them from disk if that hasn't happened yet.
+pub fn catalog(env: &mut Environment) -> &Catalog {
+    if State::get(&mut env.framework_state).catalog.is_none() {
+        let path = products_path(env);
+        let catalog = match std::fs::read_to_string(&path) {
+            Ok(text) => Catalog::parse(&text).unwrap_or_else(|e| {
+                log!(
+                    "Warning: ignoring invalid products file {}: {}",
+                    path.display(),
+                    e
+                );
+                Catalog::default()
+            }),
+            Err(_) => {
+                log!(
+                    "The app is using StoreKit, but there's no products file at {}, so it has \
+                     nothing to sell. See --store-dir=.",
+                    path.display()
+                );
+                Catalog::default()
+            }
+        };
+        State::get(&mut env.framework_state).catalog = Some(catalog);
+    }
+    State::get(&mut env.framework_state)
+        .catalog
+        .as_ref()
+        .unwrap()
+}
+
+/// Get the receipts of past purchases, reading them from disk if that hasn't
+/// happened yet.
+pub fn receipts(env: &mut Environment) -> &[Receipt] {
+    if State::get(&mut env.framework_state).receipts.is_none() {
+        let path = receipts_path(env);
+        let receipts: Vec<Receipt> = std::fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let receipt = Receipt::parse(line);
+                if receipt.is_none() {
+                    log!(
+                        "Warning: ignoring invalid receipt in {}: {:?}",
+                        path.display(),
+                        line
+                    );
+                }
+                receipt
+            })
+            .collect();
+        State::get(&mut env.framework_state).receipts = Some(receipts);
+    }
+    State::get(&mut env.framework_state)
+        .receipts
+        .as_ref()
+        .unwrap()
+}
+
+/// Decide how a purchase of a product turns out.
+pub fn next_result(env: &mut Environment, product_identifier: &str) -> PurchaseResult {
+    let Some(product) = catalog(env).product(product_identifier) else {
+        return PurchaseResult::Invalid;
+    };
+    let results = product.results.clone();
+    let state = State::get(&mut env.framework_state);
+    let used = state
+        .results_used
+        .entry(product_identifier.to_string())
+        .or_insert(0);
+    let result = results
+        .get(*used)
+        .copied()
+        .unwrap_or(PurchaseResult::Purchased);
+    *used += 1;
+    result
+}
+
+/// Get a new, unique transaction identifier.
+pub fn new_transaction_identifier(env: &mut Environment) -> String {
+    if State::get(&mut env.framework_state)
+        .next_transaction_identifier
+        .is_none()
+    {
+        let next = receipts(env)
+            .iter()
+            .filter_map(|receipt| receipt.transaction_identifier.parse::<u64>().ok())
+            .map(|identifier| identifier + 1)
+            .max()
+            .unwrap_or(0)
+            .max(FIRST_TRANSACTION_IDENTIFIER);
+        State::get(&mut env.framework_state).next_transaction_identifier = Some(next);
+    }
+    let next = State::get(&mut env.framework_state)
+        .next_transaction_identifier
+        .as_mut()
+        .unwrap();
+    let identifier = *next;
+    *next += 1;
+    identifier.to_string()
+}
+
+/// Record a completed purchase, so it can be restored later.
+pub fn add_receipt(
+    env: &mut Environment,
+    transaction_identifier: String,
+    product_identifier: String,
+    quantity: NSInteger,
+) -> Receipt {
+    let receipt = Receipt {
+        transaction_identifier,
+        product_identifier,
+        quantity,
+        date: now_since_reference_date(),
+    };
+    let path = receipts_path(env);
+    let result = std::fs::create_dir_all(&env.options.store_dir).and_then(|()| {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?
+            .write_all(receipt.format().as_bytes())
+    });
+    if let Err(e) = result {
+        log!(
+            "Warning: couldn't save receipt to {}: {}",
+            path.display(),
+            e
+        );
+    }
+    receipts(env);
+    State::get(&mut env.framework_state)
+        .receipts
+        .as_mut()
+        .unwrap()
+        .push(receipt.clone());
+    receipt
+}
+
+/// Make a fake receipt for a transaction, in the style of the App Store's
+/// old-style (iOS 6 and earlier) transaction receipts. It isn't signed, so it
+/// won't pass validation by Apple's servers.
+pub fn receipt_data(receipt: &Receipt, bundle_identifier: &str) -> Vec<u8> {
+    let date_ms = ((receipt.date + 978_307_200.0) * 1000.0) as u64;
+    format!(
+        "{{\n\t\"purchase-info\" = {{\n\
+         \t\t\"bid\" = \"{}\";\n\
+         \t\t\"product-id\" = \"{}\";\n\
+         \t\t\"quantity\" = \"{}\";\n\
+         \t\t\"transaction-id\" = \"{}\";\n\
+         \t\t\"purchase-date-ms\" = \"{}\";\n\
+         \t}};\n\
+         \t\"environment\" = \"Sandbox\";\n\
+         \t\"signing-status\" = \"0\";\n}}\n",
+        bundle_identifier,
+        receipt.product_identifier,
+        receipt.quantity,
+        receipt.transaction_identifier,
+        date_ms,
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn products_file() {
+        let catalog = Catalog::parse(
+            "# A comment\n\
+             locale = fr_FR\n\
+             \n\
+             [com.example.levels]\n\
+             title = More Levels\n\
+             description = Twenty new levels = lots of fun.\n\
+             price = 1.99\n\
+             type = non-consumable\n\
+             results = cancelled, failed\n\
+             [com.example.coins]\n",
+        )
+        .unwrap();
+        assert_eq!(catalog.locale, "fr_FR");
+        assert_eq!(
+            catalog.product("com.example.levels"),
+            Some(&Product {
+                identifier: "com.example.levels".to_string(),
+                title: "More Levels".to_string(),
+                description: "Twenty new levels = lots of fun.".to_string(),
+                price: "1.99".to_string(),
+                product_type: ProductType::NonConsumable,
+                results: vec![PurchaseResult::Cancelled, PurchaseResult::Failed],
+            })
+        );
+        let coins = catalog.product("com.example.coins").unwrap();
+        assert_eq!(coins.title, "com.example.coins");
+        assert_eq!(coins.product_type, ProductType::Consumable);
+        assert!(coins.results.is_empty());
+        assert!(catalog.product("com.example.other").is_none());
+
+        assert!(Catalog::parse("title = No product").is_err());
+        assert!(Catalog::parse("[a]\nprice = free").is_err());
+        assert!(Catalog::parse("[a]\ntype = rental").is_err());
+        assert!(Catalog::parse("[a]\nresults = purchased, refunded").is_err());
+        assert!(Catalog::parse("[a]\ncolor = red").is_err());
+    }
+
+    #[test]
+    fn receipts_file() {
+        let receipt = Receipt {
+            transaction_identifier: "1000000001".to_string(),
+            product_identifier: "com.example.levels".to_string(),
+            quantity: 1,
+            date: 300000000.5,
+        };
+        let line = receipt.format();
+        assert_eq!(line, "1000000001\tcom.example.levels\t1\t300000000.5\n");
+        assert_eq!(Receipt::parse(line.trim_end()), Some(receipt));
+        assert_eq!(Receipt::parse("1000000001\tcom.example.levels"), None);
+    }
+}
@@ -0,0 +1,21 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `SKError.h`
+
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::NSInteger;
+
+pub const SKErrorDomain: &str = "SKErrorDomain";
+
+pub type SKError = NSInteger;
+pub const SKErrorUnknown: SKError = 0;
+#[allow(dead_code)]
+pub const SKErrorClientInvalid: SKError = 1;
+pub const SKErrorPaymentCancelled: SKError = 2;
+pub const SKErrorPaymentInvalid: SKError = 3;
+pub const SKErrorPaymentNotAllowed: SKError = 4;
+
+pub const CONSTANTS: ConstantExports = &[("_SKErrorDomain", HostConstant::NSString(SKErrorDomain))];
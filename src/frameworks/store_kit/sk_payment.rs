@@ -0,0 +1,163 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `SKPayment` and `SKMutablePayment`.
+
+use crate::frameworks::foundation::{ns_string, NSInteger};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, Class, ClassExports,
+    HostObject,
+};
+use crate::Environment;
+
+struct SKPaymentHostObject {
+    /// `NSString*`, strong reference.
+    product_identifier: id,
+    quantity: NSInteger,
+    /// `NSData*`, strong reference.
+    request_data: id,
+}
+impl HostObject for SKPaymentHostObject {}
+
+fn borrow(env: &mut Environment, payment: id) -> &mut SKPaymentHostObject {
+    env.objc.borrow_mut(payment)
+}
+
+/// Create a payment (+1 reference) with the same contents as another.
+fn copy_payment(env: &mut Environment, payment: id, class: Class) -> id {
+    let &mut SKPaymentHostObject {
+        product_identifier,
+        quantity,
+        request_data,
+    } = borrow(env, payment);
+    let product_identifier: id = msg![env; product_identifier copy];
+    let request_data: id = msg![env; request_data copy];
+    let new: id = msg![env; class alloc];
+    *borrow(env, new) = SKPaymentHostObject {
+        product_identifier,
+        quantity,
+        request_data,
+    };
+    new
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation SKPayment: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(SKPaymentHostObject {
+        product_identifier: nil,
+        quantity: 1,
+        request_data: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)paymentWithProduct:(id)product { // SKProduct*
+    let product_identifier: id = msg![env; product productIdentifier];
+    msg![env; this paymentWithProductIdentifier:product_identifier]
+}
+
+// Deprecated in iOS 5, but that's after the apps we care about.
++ (id)paymentWithProductIdentifier:(id)product_identifier { // NSString*
+    let product_identifier: id = msg![env; product_identifier copy];
+    let new: id = msg![env; this alloc];
+    borrow(env, new).product_identifier = product_identifier;
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let &mut SKPaymentHostObject {
+        product_identifier,
+        request_data,
+        ..
+    } = borrow(env, this);
+    release(env, product_identifier);
+    release(env, request_data);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    // SKPayment is immutable
+    retain(env, this)
+}
+
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(MutVoidPtr)_zone {
+    let class = env.objc.get_known_class("SKMutablePayment", &mut env.mem);
+    copy_payment(env, this, class)
+}
+
+- (id)productIdentifier {
+    borrow(env, this).product_identifier
+}
+- (NSInteger)quantity {
+    borrow(env, this).quantity
+}
+- (id)requestData {
+    borrow(env, this).request_data
+}
+
+@end
+
+@implementation SKMutablePayment: SKPayment
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    let class = env.objc.get_known_class("SKPayment", &mut env.mem);
+    copy_payment(env, this, class)
+}
+
+- (())setProductIdentifier:(id)product_identifier { // NSString*
+    let product_identifier: id = msg![env; product_identifier copy];
+    let old = std::mem::replace(&mut borrow(env, this).product_identifier, product_identifier);
+    release(env, old);
+}
+- (())setQuantity:(NSInteger)quantity {
+    borrow(env, this).quantity = quantity;
+}
+- (())setRequestData:(id)request_data { // NSData*
+    let request_data: id = msg![env; request_data copy];
+    let old = std::mem::replace(&mut borrow(env, this).request_data, request_data);
+    release(env, old);
+}
+
+@end
+
+};
+
+/// Shortcut for host code: get the product identifier and quantity of a
+/// payment.
+pub fn get_product_and_quantity(env: &mut Environment, payment: id) -> (String, NSInteger) {
+    let &mut SKPaymentHostObject {
+        product_identifier,
+        quantity,
+        ..
+    } = borrow(env, payment);
+    let product_identifier = if product_identifier == nil {
+        String::new()
+    } else {
+        ns_string::to_rust_string(env, product_identifier).into_owned()
+    };
+    (product_identifier, quantity)
+}
+
+/// For use by `SKPaymentQueue`: create a payment (+1 reference) for restoring
+/// a purchase.
+pub fn new_payment(env: &mut Environment, product_identifier: &str, quantity: NSInteger) -> id {
+    let product_identifier = ns_string::from_rust_string(env, product_identifier.to_string());
+    let new: id = msg_class![env; SKPayment alloc];
+    *borrow(env, new) = SKPaymentHostObject {
+        product_identifier,
+        quantity,
+        request_data: nil,
+    };
+    new
+}
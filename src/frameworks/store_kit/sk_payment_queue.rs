@@ -0,0 +1,292 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `SKPaymentQueue`.
+//!
+//! Purchases complete after a short delay, and turn out however the products
+//! file says (see [super::catalog]). There's no confirmation dialog. Observers
+//! are told about changes from the run loop, like on a real device, rather
+//! than from inside the method that caused them.
+//!
+//! Transactions the app doesn't finish are forgotten when it exits, rather
+//! than being delivered again on the next launch.
+
+use super::catalog::{self, ProductType, PurchaseResult};
+use super::sk_error::{
+    SKErrorPaymentCancelled, SKErrorPaymentInvalid, SKErrorPaymentNotAllowed, SKErrorUnknown,
+};
+use super::sk_payment;
+use super::sk_payment_transaction::{self, SKPaymentTransactionStatePurchasing};
+use crate::frameworks::foundation::ns_array;
+use crate::objc::{
+    autorelease, id, msg, msg_class, msg_send, objc_classes, release, retain, ClassExports, SEL,
+};
+use crate::Environment;
+use std::time::{Duration, Instant};
+
+/// How long the "App Store" takes to process a payment.
+const PURCHASE_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub struct State {
+    default_queue: Option<id>,
+    /// `SKPaymentTransactionObserver`s, weak references.
+    observers: Vec<id>,
+    /// Transactions that haven't been finished, strong references, in the
+    /// order they were added.
+    transactions: Vec<id>,
+    /// Transactions that are being purchased, with when they complete. These
+    /// are also in [Self::transactions].
+    purchasing: Vec<(id, Instant)>,
+    /// Transactions that observers haven't been told about since they
+    /// changed. These are also in [Self::transactions].
+    updated: Vec<id>,
+    /// Transactions that have been finished, but observers haven't been told
+    /// yet. Strong references.
+    removed: Vec<id>,
+    /// Set by `restoreCompletedTransactions`.
+    restore_requested: bool,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.store_kit.sk_payment_queue
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation SKPaymentQueue: NSObject
+
++ (id)defaultQueue {
+    if let Some(existing) = State::get(&mut env.framework_state).default_queue {
+        return existing;
+    }
+    let new: id = msg![env; this new];
+    State::get(&mut env.framework_state).default_queue = Some(new);
+    new
+}
+
+// The user is always allowed to buy things.
++ (bool)canMakePayments {
+    true
+}
+
+- (())addTransactionObserver:(id)observer { // id<SKPaymentTransactionObserver>
+    let state = State::get(&mut env.framework_state);
+    if state.observers.contains(&observer) {
+        return;
+    }
+    // Transactions that weren't finished are delivered to the first observer.
+    if state.observers.is_empty() {
+        for &transaction in &state.transactions {
+            if !state.updated.contains(&transaction) {
+                state.updated.push(transaction);
+            }
+        }
+    }
+    state.observers.push(observer);
+}
+- (())removeTransactionObserver:(id)observer { // id<SKPaymentTransactionObserver>
+    State::get(&mut env.framework_state)
+        .observers
+        .retain(|&other| other != observer);
+}
+
+- (id)transactions {
+    let transactions = State::get(&mut env.framework_state).transactions.clone();
+    for &transaction in &transactions {
+        retain(env, transaction);
+    }
+    let array = ns_array::from_vec(env, transactions);
+    autorelease(env, array)
+}
+
+- (())addPayment:(id)payment { // SKPayment*
+    let (product_identifier, quantity) = sk_payment::get_product_and_quantity(env, payment);
+    log!(
+        "The app is buying {} of product {:?}",
+        quantity,
+        product_identifier
+    );
+    let payment: id = msg![env; payment copy];
+    let transaction = sk_payment_transaction::new_purchasing(env, payment);
+    release(env, payment);
+    let state = State::get(&mut env.framework_state);
+    state.transactions.push(transaction);
+    state.purchasing.push((transaction, Instant::now() + PURCHASE_DELAY));
+    state.updated.push(transaction);
+}
+
+- (())finishTransaction:(id)transaction { // SKPaymentTransaction*
+    let (transaction_state, _) = sk_payment_transaction::get_state_and_payment(env, transaction);
+    if transaction_state == SKPaymentTransactionStatePurchasing {
+        log!(
+            "Warning: ignoring attempt to finish transaction {:?} while it's being purchased",
+            transaction
+        );
+        return;
+    }
+    let state = State::get(&mut env.framework_state);
+    let Some(idx) = state.transactions.iter().position(|&t| t == transaction) else {
+        return;
+    };
+    state.transactions.remove(idx);
+    state.updated.retain(|&t| t != transaction);
+    state.removed.push(transaction);
+}
+
+- (())restoreCompletedTransactions {
+    log!("The app is restoring previous purchases");
+    State::get(&mut env.framework_state).restore_requested = true;
+}
+
+@end
+
+};
+
+/// For use by `NSRunLoop`: complete purchases that are due and tell observers
+/// about changes.
+pub fn handle_payment_queue(env: &mut Environment) {
+    let state = State::get(&mut env.framework_state);
+    if state.purchasing.is_empty()
+        && state.updated.is_empty()
+        && state.removed.is_empty()
+        && !state.restore_requested
+    {
+        return;
+    }
+
+    let now = Instant::now();
+    let due: Vec<id> = state
+        .purchasing
+        .iter()
+        .filter(|&&(_, due)| due <= now)
+        .map(|&(transaction, _)| transaction)
+        .collect();
+    state.purchasing.retain(|&(_, due)| due > now);
+    for transaction in due {
+        complete_purchase(env, transaction);
+        State::get(&mut env.framework_state)
+            .updated
+            .push(transaction);
+    }
+
+    let restoring = std::mem::take(&mut State::get(&mut env.framework_state).restore_requested);
+    if restoring {
+        restore(env);
+    }
+
+    let state = State::get(&mut env.framework_state);
+    let updated = std::mem::take(&mut state.updated);
+    let removed = std::mem::take(&mut state.removed);
+    if !updated.is_empty() {
+        notify_observers(env, "paymentQueue:updatedTransactions:", updated);
+    }
+    if !removed.is_empty() {
+        notify_observers(env, "paymentQueue:removedTransactions:", removed.clone());
+        for transaction in removed {
+            release(env, transaction);
+        }
+    }
+    if restoring {
+        let queue: id = msg_class![env; SKPaymentQueue defaultQueue];
+        for_each_observer(
+            env,
+            "paymentQueueRestoreCompletedTransactionsFinished:",
+            |env, observer, selector| {
+                let () = msg_send(env, (observer, selector, queue));
+            },
+        );
+    }
+}
+
+fn complete_purchase(env: &mut Environment, transaction: id) {
+    let (_, payment) = sk_payment_transaction::get_state_and_payment(env, transaction);
+    let (product_identifier, quantity) = sk_payment::get_product_and_quantity(env, payment);
+    let result = catalog::next_result(env, &product_identifier);
+    log!(
+        "Purchase of {} of product {:?}: {:?}",
+        quantity,
+        product_identifier,
+        result
+    );
+    let code = match result {
+        PurchaseResult::Purchased => {
+            let transaction_identifier = catalog::new_transaction_identifier(env);
+            let receipt =
+                catalog::add_receipt(env, transaction_identifier, product_identifier, quantity);
+            sk_payment_transaction::set_purchased(env, transaction, &receipt);
+            return;
+        }
+        PurchaseResult::Failed => SKErrorUnknown,
+        PurchaseResult::Cancelled => SKErrorPaymentCancelled,
+        PurchaseResult::NotAllowed => SKErrorPaymentNotAllowed,
+        PurchaseResult::Invalid => SKErrorPaymentInvalid,
+    };
+    sk_payment_transaction::set_failed(env, transaction, code);
+}
+
+/// Add a restored transaction for every purchase of a product that isn't
+/// consumable.
+fn restore(env: &mut Environment) {
+    let catalog = catalog::catalog(env).clone();
+    let receipts: Vec<_> = catalog::receipts(env)
+        .iter()
+        .filter(|receipt| {
+            catalog
+                .product(&receipt.product_identifier)
+                .is_some_and(|product| product.product_type != ProductType::Consumable)
+        })
+        .cloned()
+        .collect();
+    log!("Restoring {} previous purchases", receipts.len());
+    for receipt in receipts {
+        let payment = sk_payment::new_payment(env, &receipt.product_identifier, receipt.quantity);
+        let transaction = sk_payment_transaction::new_restored(env, payment, &receipt);
+        release(env, payment);
+        let state = State::get(&mut env.framework_state);
+        state.transactions.push(transaction);
+        state.updated.push(transaction);
+    }
+}
+
+/// Call a method on every observer that implements it.
+fn for_each_observer<F>(env: &mut Environment, selector: &str, mut f: F)
+where
+    F: FnMut(&mut Environment, id, SEL),
+{
+    // The selector won't exist if no class implements this method.
+    let Some(selector) = env.objc.lookup_selector(selector) else {
+        return;
+    };
+    let observers = State::get(&mut env.framework_state).observers.clone();
+    for observer in observers {
+        // An observer might have removed another.
+        if !State::get(&mut env.framework_state)
+            .observers
+            .contains(&observer)
+        {
+            continue;
+        }
+        if msg![env; observer respondsToSelector:selector] {
+            f(env, observer, selector);
+        }
+    }
+}
+
+/// Send an array of transactions to observers.
+fn notify_observers(env: &mut Environment, selector: &str, transactions: Vec<id>) {
+    for &transaction in &transactions {
+        retain(env, transaction);
+    }
+    let transactions = ns_array::from_vec(env, transactions);
+    let queue: id = msg_class![env; SKPaymentQueue defaultQueue];
+    for_each_observer(env, selector, |env, observer, selector| {
+        let () = msg_send(env, (observer, selector, queue, transactions));
+    });
+    release(env, transactions);
+}
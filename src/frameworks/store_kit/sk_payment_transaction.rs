@@ -0,0 +1,179 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `SKPaymentTransaction`.
+//!
+//! Transactions are created and updated by `SKPaymentQueue` (see
+//! [super::sk_payment_queue]); apps only read them.
+
+use super::catalog::{self, Receipt};
+use super::sk_error::SKErrorDomain;
+use crate::frameworks::foundation::{ns_data, ns_date, ns_error, ns_string, NSInteger};
+use crate::mem::MutVoidPtr;
+use crate::objc::{id, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject};
+use crate::Environment;
+
+pub type SKPaymentTransactionState = NSInteger;
+pub const SKPaymentTransactionStatePurchasing: SKPaymentTransactionState = 0;
+pub const SKPaymentTransactionStatePurchased: SKPaymentTransactionState = 1;
+pub const SKPaymentTransactionStateFailed: SKPaymentTransactionState = 2;
+pub const SKPaymentTransactionStateRestored: SKPaymentTransactionState = 3;
+
+struct SKPaymentTransactionHostObject {
+    /// `SKPayment*`, strong reference.
+    payment: id,
+    state: SKPaymentTransactionState,
+    /// `NSString*`, strong reference, or [nil] until the transaction is
+    /// complete.
+    identifier: id,
+    /// `NSDate*`, strong reference, or [nil] until the transaction is
+    /// complete.
+    date: id,
+    /// `NSData*`, strong reference, or [nil] until the transaction is
+    /// complete.
+    receipt: id,
+    /// `NSError*`, strong reference, or [nil] unless the transaction failed.
+    error: id,
+    /// `SKPaymentTransaction*`, strong reference, or [nil] unless the
+    /// transaction was restored.
+    original: id,
+}
+impl HostObject for SKPaymentTransactionHostObject {}
+
+fn borrow(env: &mut Environment, transaction: id) -> &mut SKPaymentTransactionHostObject {
+    env.objc.borrow_mut(transaction)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation SKPaymentTransaction: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(SKPaymentTransactionHostObject {
+        payment: nil,
+        state: SKPaymentTransactionStatePurchasing,
+        identifier: nil,
+        date: nil,
+        receipt: nil,
+        error: nil,
+        original: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &mut SKPaymentTransactionHostObject {
+        payment,
+        identifier,
+        date,
+        receipt,
+        error,
+        original,
+        ..
+    } = borrow(env, this);
+    release(env, payment);
+    release(env, identifier);
+    release(env, date);
+    release(env, receipt);
+    release(env, error);
+    release(env, original);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)payment {
+    borrow(env, this).payment
+}
+- (SKPaymentTransactionState)transactionState {
+    borrow(env, this).state
+}
+- (id)transactionIdentifier {
+    borrow(env, this).identifier
+}
+- (id)transactionDate {
+    borrow(env, this).date
+}
+- (id)transactionReceipt {
+    borrow(env, this).receipt
+}
+- (id)error {
+    borrow(env, this).error
+}
+- (id)originalTransaction {
+    borrow(env, this).original
+}
+
+@end
+
+};
+
+/// Create a transaction (+1 reference) for a payment that's being purchased.
+pub fn new_purchasing(env: &mut Environment, payment: id) -> id {
+    retain(env, payment);
+    let new: id = msg_class![env; SKPaymentTransaction alloc];
+    borrow(env, new).payment = payment;
+    new
+}
+
+/// Create a transaction (+1 reference) for a purchase restored from its
+/// receipt. Its original transaction is the purchase.
+pub fn new_restored(env: &mut Environment, payment: id, receipt: &Receipt) -> id {
+    let original = new_purchasing(env, payment);
+    set_receipt(env, original, receipt);
+    borrow(env, original).state = SKPaymentTransactionStatePurchased;
+
+    let restored = new_purchasing(env, payment);
+    let restored_receipt = Receipt {
+        transaction_identifier: catalog::new_transaction_identifier(env),
+        ..receipt.clone()
+    };
+    set_receipt(env, restored, &restored_receipt);
+    let host_object = borrow(env, restored);
+    host_object.state = SKPaymentTransactionStateRestored;
+    host_object.original = original;
+    restored
+}
+
+fn set_receipt(env: &mut Environment, transaction: id, receipt: &Receipt) {
+    let identifier = ns_string::from_rust_string(env, receipt.transaction_identifier.clone());
+    let date = ns_date::from_time_interval(env, receipt.date);
+    let receipt_data = catalog::receipt_data(receipt, env.bundle.bundle_identifier());
+    let receipt_data = ns_data::from_bytes(env, &receipt_data);
+    let host_object = borrow(env, transaction);
+    let old = [
+        std::mem::replace(&mut host_object.identifier, identifier),
+        std::mem::replace(&mut host_object.date, date),
+        std::mem::replace(&mut host_object.receipt, receipt_data),
+    ];
+    for old in old {
+        release(env, old);
+    }
+}
+
+/// Mark a transaction as purchased.
+pub fn set_purchased(env: &mut Environment, transaction: id, receipt: &Receipt) {
+    set_receipt(env, transaction, receipt);
+    borrow(env, transaction).state = SKPaymentTransactionStatePurchased;
+}
+
+/// Mark a transaction as failed, with an error from `SKErrorDomain`.
+pub fn set_failed(env: &mut Environment, transaction: id, code: NSInteger) {
+    let error = ns_error::new_error(env, SKErrorDomain, code);
+    retain(env, error);
+    let host_object = borrow(env, transaction);
+    host_object.state = SKPaymentTransactionStateFailed;
+    let old = std::mem::replace(&mut host_object.error, error);
+    release(env, old);
+}
+
+/// Shortcut for host code: get a transaction's state and payment.
+pub fn get_state_and_payment(
+    env: &mut Environment,
+    transaction: id,
+) -> (SKPaymentTransactionState, id) {
+    let host_object = borrow(env, transaction);
+    (host_object.state, host_object.payment)
+}
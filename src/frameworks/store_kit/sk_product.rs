@@ -0,0 +1,111 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `SKProduct`.
+
+use super::catalog::Product;
+use crate::frameworks::foundation::ns_string;
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+
+struct SKProductHostObject {
+    /// `NSString*`, strong reference.
+    identifier: id,
+    /// `NSString*`, strong reference.
+    title: id,
+    /// `NSString*`, strong reference.
+    description: id,
+    /// `NSDecimalNumber*`, strong reference.
+    price: id,
+    /// `NSLocale*`, strong reference.
+    price_locale: id,
+}
+impl HostObject for SKProductHostObject {}
+
+fn borrow(env: &mut Environment, product: id) -> &mut SKProductHostObject {
+    env.objc.borrow_mut(product)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation SKProduct: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(SKProductHostObject {
+        identifier: nil,
+        title: nil,
+        description: nil,
+        price: nil,
+        price_locale: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &mut SKProductHostObject {
+        identifier,
+        title,
+        description,
+        price,
+        price_locale,
+    } = borrow(env, this);
+    release(env, identifier);
+    release(env, title);
+    release(env, description);
+    release(env, price);
+    release(env, price_locale);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)productIdentifier {
+    borrow(env, this).identifier
+}
+- (id)localizedTitle {
+    borrow(env, this).title
+}
+- (id)localizedDescription {
+    borrow(env, this).description
+}
+- (id)price {
+    borrow(env, this).price
+}
+- (id)priceLocale {
+    borrow(env, this).price_locale
+}
+
+@end
+
+};
+
+/// For use by `SKProductsRequest`: create a product (+1 reference) from its
+/// entry in the products file.
+pub fn from_product(env: &mut Environment, product: &Product, locale: &str) -> id {
+    let identifier = ns_string::from_rust_string(env, product.identifier.clone());
+    let title = ns_string::from_rust_string(env, product.title.clone());
+    let description = ns_string::from_rust_string(env, product.description.clone());
+    let price_string = ns_string::from_rust_string(env, product.price.clone());
+    let price: id = msg_class![env; NSDecimalNumber decimalNumberWithString:price_string];
+    retain(env, price);
+    release(env, price_string);
+    let locale = ns_string::from_rust_string(env, locale.to_string());
+    let price_locale: id = msg_class![env; NSLocale alloc];
+    let price_locale: id = msg![env; price_locale initWithLocaleIdentifier:locale];
+    release(env, locale);
+
+    let new: id = msg_class![env; SKProduct alloc];
+    *borrow(env, new) = SKProductHostObject {
+        identifier,
+        title,
+        description,
+        price,
+        price_locale,
+    };
+    new
+}
@@ -0,0 +1,219 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `SKRequest`, `SKProductsRequest` and `SKProductsResponse`.
+//!
+//! Requests are answered from the products file (see [super::catalog]) after
+//! a short delay, so apps see the delegate called later like on a real device.
+
+use super::{catalog, sk_product};
+use crate::frameworks::foundation::{ns_array, ns_string, NSUInteger};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::time::{Duration, Instant};
+
+/// How long the "App Store" takes to answer a request.
+const RESPONSE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Default)]
+pub struct State {
+    /// Requests between `start` and their response, with when they're
+    /// answered. Strong references, so apps can release a request after
+    /// starting it.
+    pending: Vec<(id, Instant)>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.store_kit.sk_request
+    }
+}
+
+struct SKRequestHostObject {
+    /// Weak reference.
+    delegate: id,
+    /// Only for `SKProductsRequest`.
+    product_identifiers: Vec<String>,
+}
+impl HostObject for SKRequestHostObject {}
+
+struct SKProductsResponseHostObject {
+    /// `NSArray*` of `SKProduct*`, strong reference.
+    products: id,
+    /// `NSArray*` of `NSString*`, strong reference.
+    invalid_product_identifiers: id,
+}
+impl HostObject for SKProductsResponseHostObject {}
+
+fn borrow(env: &mut Environment, request: id) -> &mut SKRequestHostObject {
+    env.objc.borrow_mut(request)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation SKRequest: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(SKRequestHostObject {
+        delegate: nil,
+        product_identifiers: Vec::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)delegate {
+    borrow(env, this).delegate
+}
+- (())setDelegate:(id)delegate { // something implementing SKRequestDelegate
+    borrow(env, this).delegate = delegate;
+}
+
+- (())start {
+    let pending = &mut State::get(&mut env.framework_state).pending;
+    if pending.iter().any(|&(request, _)| request == this) {
+        return;
+    }
+    log_dbg!("SKRequest {:?} started", this);
+    pending.push((this, Instant::now() + RESPONSE_DELAY));
+    retain(env, this);
+}
+
+- (())cancel {
+    let pending = &mut State::get(&mut env.framework_state).pending;
+    let Some(idx) = pending.iter().position(|&(request, _)| request == this) else {
+        return;
+    };
+    log_dbg!("SKRequest {:?} cancelled", this);
+    pending.remove(idx);
+    release(env, this);
+}
+
+@end
+
+@implementation SKProductsRequest: SKRequest
+
+- (id)initWithProductIdentifiers:(id)identifiers { // NSSet* of NSString*
+    let identifiers: id = msg![env; identifiers allObjects];
+    let count: NSUInteger = msg![env; identifiers count];
+    let identifiers = (0..count)
+        .map(|i| {
+            let identifier: id = msg![env; identifiers objectAtIndex:i];
+            ns_string::to_rust_string(env, identifier).into_owned()
+        })
+        .collect();
+    borrow(env, this).product_identifiers = identifiers;
+    this
+}
+
+@end
+
+@implementation SKProductsResponse: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(SKProductsResponseHostObject {
+        products: nil,
+        invalid_product_identifiers: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &SKProductsResponseHostObject {
+        products,
+        invalid_product_identifiers,
+    } = env.objc.borrow(this);
+    release(env, products);
+    release(env, invalid_product_identifiers);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)products {
+    env.objc.borrow::<SKProductsResponseHostObject>(this).products
+}
+- (id)invalidProductIdentifiers {
+    env.objc
+        .borrow::<SKProductsResponseHostObject>(this)
+        .invalid_product_identifiers
+}
+
+@end
+
+};
+
+/// Create the response to a products request (+1 reference).
+fn new_response(env: &mut Environment, product_identifiers: &[String]) -> id {
+    let catalog = catalog::catalog(env).clone();
+    let mut products = Vec::new();
+    let mut invalid_product_identifiers = Vec::new();
+    for identifier in product_identifiers {
+        if let Some(product) = catalog.product(identifier) {
+            products.push(sk_product::from_product(env, product, &catalog.locale));
+        } else {
+            log!(
+                "Warning: the app asked for product {:?}, which isn't in the products file",
+                identifier
+            );
+            let identifier = ns_string::from_rust_string(env, identifier.clone());
+            invalid_product_identifiers.push(identifier);
+        }
+    }
+    let products = ns_array::from_vec(env, products);
+    let invalid_product_identifiers = ns_array::from_vec(env, invalid_product_identifiers);
+
+    let new: id = msg_class![env; SKProductsResponse alloc];
+    *env.objc.borrow_mut(new) = SKProductsResponseHostObject {
+        products,
+        invalid_product_identifiers,
+    };
+    new
+}
+
+/// For use by `NSRunLoop`: answer requests that are due.
+pub fn handle_requests(env: &mut Environment) {
+    let pending = &mut State::get(&mut env.framework_state).pending;
+    if pending.is_empty() {
+        return;
+    }
+    let now = Instant::now();
+    let due: Vec<id> = pending
+        .iter()
+        .filter(|&&(_, due)| due <= now)
+        .map(|&(request, _)| request)
+        .collect();
+    pending.retain(|&(_, due)| due > now);
+
+    for request in due {
+        let host_object = borrow(env, request);
+        let delegate = host_object.delegate;
+        let product_identifiers = host_object.product_identifiers.clone();
+        if delegate != nil {
+            // Plain SKRequests (which apps don't create themselves) have
+            // nothing to respond with.
+            let products_request_class =
+                env.objc.get_known_class("SKProductsRequest", &mut env.mem);
+            if msg![env; request isKindOfClass:products_request_class] {
+                let response = new_response(env, &product_identifiers);
+                log_dbg!(
+                    "SKProductsRequest {:?} responding with {:?}",
+                    request,
+                    response
+                );
+                () = msg![env; delegate productsRequest:request didReceiveResponse:response];
+                release(env, response);
+            }
+            // The selector won't exist if no class implements this method.
+            if let Some(selector) = env.objc.lookup_selector("requestDidFinish:") {
+                if msg![env; delegate respondsToSelector:selector] {
+                    let () = msg_send(env, (delegate, selector, request));
+                }
+            }
+        }
+        release(env, request);
+    }
+}
@@ -9,40 +9,105 @@
 //! likely to use UIKit in very simple and limited ways, so this implementation
 //! will probably take a lot of shortcuts.
 
+use crate::frameworks::audio_toolbox::audio_session;
+use crate::frameworks::media_player::mp_movie_player_controller;
+use crate::frameworks::message_ui::mf_mail_compose_view_controller;
 use crate::Environment;
 
+pub mod overlay;
+pub mod status_bar;
 pub mod ui_accelerometer;
+pub mod ui_action_sheet;
+pub mod ui_activity_indicator_view;
+pub mod ui_alert_view;
 pub mod ui_application;
+pub mod ui_button;
+pub mod ui_color;
+pub mod ui_control;
 pub mod ui_device;
 pub mod ui_event;
 pub mod ui_font;
 pub mod ui_graphics;
+pub mod ui_image;
+pub mod ui_image_picker_controller;
+pub mod ui_keyboard;
 pub mod ui_nib;
+pub mod ui_progress_view;
 pub mod ui_responder;
 pub mod ui_screen;
+pub mod ui_scroll_view;
+pub mod ui_segmented_control;
+pub mod ui_slider;
+pub mod ui_switch;
+pub mod ui_tab_bar;
+pub mod ui_tab_bar_controller;
+pub mod ui_table_view;
+pub mod ui_table_view_cell;
+pub mod ui_text_field;
+pub mod ui_text_view;
 pub mod ui_touch;
 pub mod ui_view;
+pub mod ui_view_controller;
+pub mod ui_web_view;
 pub mod ui_window;
 
 #[derive(Default)]
 pub struct State {
+    status_bar: status_bar::State,
     ui_accelerometer: ui_accelerometer::State,
+    ui_activity_indicator_view: ui_activity_indicator_view::State,
+    ui_alert_view: ui_alert_view::State,
     ui_application: ui_application::State,
+    ui_control: ui_control::State,
+    ui_device: ui_device::State,
     ui_font: ui_font::State,
     ui_graphics: ui_graphics::State,
+    ui_image: ui_image::State,
+    ui_image_picker_controller: ui_image_picker_controller::State,
+    ui_keyboard: ui_keyboard::State,
+    ui_progress_view: ui_progress_view::State,
+    ui_responder: ui_responder::State,
     ui_screen: ui_screen::State,
+    ui_scroll_view: ui_scroll_view::State,
+    ui_tab_bar: ui_tab_bar::State,
+    ui_text_field: ui_text_field::State,
     ui_touch: ui_touch::State,
     ui_view: ui_view::State,
+    ui_view_controller: ui_view_controller::State,
+    ui_web_view: ui_web_view::State,
+    ui_window: ui_window::State,
 }
 
 /// For use by `NSRunLoop`: handles any events that have queued up.
 pub fn handle_events(env: &mut Environment) {
     use crate::window::Event;
 
+    crate::replay::next_tick(env);
+
     loop {
         let Some(event) = env.window.pop_event() else {
             break;
         };
+        // Pausing, fast-forwarding and the menu are for the user rather than
+        // the app, so they aren't recorded.
+        match event {
+            Event::TogglePause => {
+                pause(env);
+                continue;
+            }
+            Event::ToggleFastForward => {
+                toggle_fast_forward(env);
+                continue;
+            }
+            Event::OpenMenu => {
+                menu(env);
+                continue;
+            }
+            // Left over from the menu being closed.
+            Event::MenuInput(..) => continue,
+            _ => (),
+        }
+        crate::replay::record_event(env, &event);
 
         match event {
             Event::Quit => {
@@ -50,10 +115,152 @@ pub fn handle_events(env: &mut Environment) {
                 ui_application::exit(env);
             }
             Event::TouchDown(..) | Event::TouchMove(..) | Event::TouchUp(..) => {
-                ui_touch::handle_event(env, event)
+                // Alerts, action sheets, image pickers, mail composers and
+                // movies are modal.
+                if !ui_alert_view::handle_event(env, &event)
+                    && !mp_movie_player_controller::handle_event(env, &event)
+                    && !ui_image_picker_controller::handle_event(env, &event)
+                    && !mf_mail_compose_view_controller::handle_event(env, &event)
+                    && !ui_keyboard::handle_event(env, &event)
+                    && !ui_text_field::handle_event(env, &event)
+                    && !ui_tab_bar::handle_event(env, &event)
+                    && !ui_web_view::handle_event(env, &event)
+                {
+                    ui_touch::handle_event(env, event)
+                }
+            }
+            Event::TextInput(..) | Event::EditingKey(..) => {
+                ui_text_field::handle_event(env, &event);
+            }
+            Event::NavigationButton(..) => {
+                ui_keyboard::handle_event(env, &event);
+            }
+            Event::RotateDevice { clockwise } => {
+                ui_device::handle_rotation(env, clockwise);
+            }
+            Event::Shake => {
+                ui_application::handle_shake(env);
+            }
+            Event::ToggleAudioInterruption => {
+                audio_session::toggle_interruption(env);
             }
+            Event::HardwareKey(key) => {
+                ui_application::handle_hardware_key(env, key);
+            }
+            Event::TogglePause
+            | Event::ToggleFastForward
+            | Event::OpenMenu
+            | Event::MenuInput(..) => unreachable!(),
         }
     }
 
     ui_accelerometer::handle_accelerometer(env);
+
+    ui_scroll_view::handle_animations(env);
+
+    ui_view::handle_animations(env);
+
+    ui_web_view::handle_loads(env);
+
+    ui_application::check_memory_pressure(env);
+
+    crate::memory_tool::handle_commands(env);
+}
+
+/// How many times faster than normal the app runs while fast-forwarding.
+const FAST_FORWARD_SPEED: f64 = 4.0;
+
+/// Start or stop fast-forwarding, by changing the speed of the app's clock
+/// (see [crate::clock]). This only speeds up apps that keep time by it, which
+/// most do, and only as far as the host can keep up.
+fn toggle_fast_forward(env: &mut Environment) {
+    if env.clock.speed() == 1.0 {
+        log!("Fast-forwarding at {}x speed.", FAST_FORWARD_SPEED);
+        env.clock.set_speed(FAST_FORWARD_SPEED);
+    } else {
+        log!("Stopped fast-forwarding.");
+        env.clock.set_speed(1.0);
+    }
+}
+
+/// Stop running the app until the user resumes it or quits. Nothing is drawn
+/// while paused, and any other input is dropped.
+fn pause(env: &mut Environment) {
+    use crate::window::Event;
+
+    log!("Paused. Press the pause hotkey again to resume.");
+    loop {
+        env.window.poll_for_events(&env.options);
+        while let Some(event) = env.window.pop_event() {
+            match event {
+                Event::TogglePause => {
+                    log!("Resumed.");
+                    return;
+                }
+                Event::Quit => {
+                    println!("User requested quit, exiting.");
+                    ui_application::exit(env);
+                }
+                _ => (),
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(16));
+    }
+}
+
+/// Show the options menu (see [crate::window::Menu]) until the user resumes or
+/// quits, applying settings as they're changed. The app is paused meanwhile.
+fn menu(env: &mut Environment) {
+    use crate::window::{Event, Menu, MenuChoice, MenuSettings};
+
+    let mut menu = Menu::new(env.in_launcher);
+    loop {
+        let old_settings = MenuSettings {
+            volume: env.options.volume,
+            display_filter: env.options.display_filter,
+            tilt_source: env.options.tilt_source,
+            virtual_controls: env.window.virtual_controls_shown(),
+            fullscreen: env.window.is_fullscreen(),
+        };
+        env.window.draw_menu(&menu, &old_settings);
+
+        env.window.poll_for_events(&env.options);
+        let mut settings = old_settings;
+        while let Some(event) = env.window.pop_event() {
+            let choice = match event {
+                Event::MenuInput(input) => menu.input(input, &mut settings),
+                Event::Quit => Some(MenuChoice::Quit),
+                _ => None,
+            };
+            match choice {
+                Some(MenuChoice::Resume) => {
+                    env.window.close_menu();
+                    return;
+                }
+                Some(MenuChoice::Quit) => {
+                    if env.in_launcher {
+                        println!("User requested quit, returning to the launcher.");
+                    } else {
+                        println!("User requested quit, exiting.");
+                    }
+                    ui_application::exit(env);
+                }
+                None => (),
+            }
+        }
+
+        if settings.volume != old_settings.volume {
+            env.options.volume = settings.volume;
+            crate::frameworks::openal::set_volume(env);
+        }
+        env.options.display_filter = settings.display_filter;
+        env.options.tilt_source = settings.tilt_source;
+        env.window
+            .set_virtual_controls_shown(settings.virtual_controls);
+        if settings.fullscreen != old_settings.fullscreen {
+            env.window.toggle_fullscreen();
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(16));
+    }
 }
@@ -0,0 +1,430 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! UI drawn by the host on top of the app's content, for things that would be
+//! views if UIKit views were composited: standard controls, web views, tab
+//! bars, activity indicators, progress bars, image pickers, the status bar,
+//! alerts, action sheets and the on-screen keyboard. Movies played by the
+//! Media Player framework, MapKit's maps and MessageUI's mail composers are
+//! shown this way too.
+//!
+//! TODO: Overlays other than the status bar are always drawn in portrait
+//! orientation, in the same co-ordinate space as touches.
+
+use super::{
+    status_bar, ui_activity_indicator_view, ui_alert_view, ui_control, ui_image_picker_controller,
+    ui_keyboard, ui_progress_view, ui_tab_bar, ui_web_view,
+};
+use crate::font::{Font, TextAlignment, WrapMode};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::map_kit::mk_map_view;
+use crate::frameworks::media_player::mp_movie_player_controller;
+use crate::frameworks::message_ui::mf_mail_compose_view_controller;
+use crate::image::Image;
+use crate::window::DeviceOrientation;
+use crate::Environment;
+
+/// A screen-sized image drawn by the host.
+pub struct Overlay {
+    pub width: u32,
+    pub height: u32,
+    /// Premultiplied RGBA8, bottom row first (like OpenGL).
+    pub pixels: Vec<u8>,
+}
+
+/// For use when presenting a frame: get the overlays to draw on top of the
+/// app's content, back to front.
+pub fn overlays(env: &mut Environment) -> Vec<&Overlay> {
+    ui_control::update_overlay(env);
+    ui_web_view::update_overlay(env);
+    mk_map_view::update_overlay(env);
+    ui_tab_bar::update_overlay(env);
+    ui_activity_indicator_view::update_overlay(env);
+    ui_progress_view::update_overlay(env);
+    ui_image_picker_controller::update_overlay(env);
+    mf_mail_compose_view_controller::update_overlay(env);
+    status_bar::update_overlay(env);
+    ui_keyboard::update_overlay(env);
+    mp_movie_player_controller::update_overlay(env);
+    ui_alert_view::update_overlay(env);
+    let uikit = &env.framework_state.uikit;
+    [
+        ui_control::current_overlay(&uikit.ui_control),
+        ui_web_view::current_overlay(&uikit.ui_web_view),
+        mk_map_view::current_overlay(&env.framework_state),
+        ui_tab_bar::current_overlay(&uikit.ui_tab_bar),
+        ui_activity_indicator_view::current_overlay(&uikit.ui_activity_indicator_view),
+        ui_progress_view::current_overlay(&uikit.ui_progress_view),
+        ui_image_picker_controller::current_overlay(&uikit.ui_image_picker_controller),
+        mf_mail_compose_view_controller::current_overlay(&env.framework_state),
+        status_bar::current_overlay(&uikit.status_bar),
+        ui_keyboard::current_overlay(&uikit.ui_keyboard),
+        mp_movie_player_controller::current_overlay(&env.framework_state),
+        ui_alert_view::current_overlay(&uikit.ui_alert_view),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Non-premultiplied RGBA.
+pub(crate) type Color = (f32, f32, f32, f32);
+
+pub(crate) fn rect(x: CGFloat, y: CGFloat, width: CGFloat, height: CGFloat) -> CGRect {
+    CGRect {
+        origin: CGPoint { x, y },
+        size: CGSize { width, height },
+    }
+}
+
+pub(crate) fn contains(rect: CGRect, (x, y): (f32, f32)) -> bool {
+    x >= rect.origin.x
+        && y >= rect.origin.y
+        && x < rect.origin.x + rect.size.width
+        && y < rect.origin.y + rect.size.height
+}
+
+/// A very simple rasterizer for overlays. Drawing is done in points.
+pub(crate) struct Canvas {
+    width: u32,
+    height: u32,
+    /// Pixels per point.
+    scale: f32,
+    /// Pixel bounds that drawing is restricted to: left, top, right, bottom.
+    clip: (i32, i32, i32, i32),
+    pixels: Vec<u8>,
+    /// Which way up the canvas is drawn, relative to the overlay.
+    orientation: DeviceOrientation,
+}
+
+impl Canvas {
+    /// Create a transparent screen-sized canvas.
+    pub(crate) fn new(env: &mut Environment) -> Canvas {
+        Self::new_for_orientation(env, DeviceOrientation::Portrait)
+    }
+
+    /// Create a transparent screen-sized canvas that is drawn on the right way
+    /// up for a device orientation, i.e. for landscape orientations its width
+    /// and height are swapped. It's rotated back when it becomes an overlay.
+    pub(crate) fn new_for_orientation(
+        env: &mut Environment,
+        orientation: DeviceOrientation,
+    ) -> Canvas {
+        let (points_width, _) = env.window.size_unrotated_unscaled();
+        let (width, height) = env.window.size_unrotated_scalehacked();
+        let scale = width as f32 / points_width as f32;
+        let (width, height) = match orientation {
+            DeviceOrientation::Portrait | DeviceOrientation::PortraitUpsideDown => (width, height),
+            DeviceOrientation::LandscapeLeft | DeviceOrientation::LandscapeRight => (height, width),
+        };
+        Canvas {
+            width,
+            height,
+            scale,
+            clip: (0, 0, width as i32, height as i32),
+            pixels: vec![0; (width * height * 4) as usize],
+            orientation,
+        }
+    }
+
+    pub(crate) fn into_overlay(self) -> Overlay {
+        if self.orientation == DeviceOrientation::Portrait {
+            return Overlay {
+                width: self.width,
+                height: self.height,
+                pixels: self.pixels,
+            };
+        }
+        let (width, height) = match self.orientation {
+            DeviceOrientation::LandscapeLeft | DeviceOrientation::LandscapeRight => {
+                (self.height, self.width)
+            }
+            _ => (self.width, self.height),
+        };
+        let mut pixels = vec![0; self.pixels.len()];
+        // Both buffers are bottom row first, but the maths is easier with
+        // rows counted from the top.
+        for y in 0..self.height {
+            let src_row = self.height - 1 - y;
+            for x in 0..self.width {
+                let (dst_x, dst_y) = match self.orientation {
+                    // The top of the content is on the right of the screen.
+                    DeviceOrientation::LandscapeLeft => (width - 1 - y, x),
+                    // The top of the content is on the left of the screen.
+                    DeviceOrientation::LandscapeRight => (y, height - 1 - x),
+                    _ => (width - 1 - x, height - 1 - y),
+                };
+                let dst_row = height - 1 - dst_y;
+                let src = ((src_row * self.width + x) * 4) as usize;
+                let dst = ((dst_row * width + dst_x) * 4) as usize;
+                pixels[dst..dst + 4].copy_from_slice(&self.pixels[src..src + 4]);
+            }
+        }
+        Overlay {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Restrict drawing to a rect, or to the whole canvas if [None].
+    pub(crate) fn set_clip(&mut self, rect: Option<CGRect>) {
+        let (width, height) = (self.width as i32, self.height as i32);
+        self.clip = match rect {
+            Some(rect) => {
+                let s = self.scale;
+                let x0 = (rect.origin.x * s).round() as i32;
+                let y0 = (rect.origin.y * s).round() as i32;
+                let x1 = ((rect.origin.x + rect.size.width) * s).round() as i32;
+                let y1 = ((rect.origin.y + rect.size.height) * s).round() as i32;
+                (x0.max(0), y0.max(0), x1.min(width), y1.min(height))
+            }
+            None => (0, 0, width, height),
+        };
+    }
+
+    /// Blend a color onto a pixel. `y` counts from the top.
+    fn blend(&mut self, x: i32, y: i32, (r, g, b, a): Color, coverage: f32) {
+        let (left, top, right, bottom) = self.clip;
+        if x < left || y < top || x >= right || y >= bottom {
+            return;
+        }
+        let a = a * coverage.clamp(0.0, 1.0);
+        let row = self.height - 1 - y as u32;
+        let idx = ((row * self.width + x as u32) * 4) as usize;
+        let pixel = &mut self.pixels[idx..idx + 4];
+        for (channel, value) in pixel.iter_mut().zip([r * a, g * a, b * a, a]) {
+            let dst = *channel as f32 / 255.0;
+            *channel = ((value + dst * (1.0 - a)) * 255.0).round() as u8;
+        }
+    }
+
+    pub(crate) fn fill_rounded_rect(&mut self, rect: CGRect, radius: CGFloat, color: Color) {
+        let s = self.scale;
+        let (x0, y0) = (rect.origin.x * s, rect.origin.y * s);
+        let (x1, y1) = (x0 + rect.size.width * s, y0 + rect.size.height * s);
+        let radius = (radius * s).min((x1 - x0) / 2.0).min((y1 - y0) / 2.0);
+        let (left, top, right, bottom) = self.clip;
+        for y in (y0.floor() as i32).max(top)..(y1.ceil() as i32).min(bottom) {
+            for x in (x0.floor() as i32).max(left)..(x1.ceil() as i32).min(right) {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                // Distance outside the rounded corners, if in a corner.
+                let cx = px.clamp(x0 + radius, x1 - radius);
+                let cy = py.clamp(y0 + radius, y1 - radius);
+                let distance = ((px - cx).powi(2) + (py - cy).powi(2)).sqrt();
+                let coverage = (radius - distance + 0.5).min(1.0)
+                    * (px - x0 + 0.5).clamp(0.0, 1.0)
+                    * (x1 - px + 0.5).clamp(0.0, 1.0)
+                    * (py - y0 + 0.5).clamp(0.0, 1.0)
+                    * (y1 - py + 0.5).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    self.blend(x, y, color, coverage);
+                }
+            }
+        }
+    }
+
+    /// Draw a straight line with round ends.
+    pub(crate) fn draw_line(&mut self, from: CGPoint, to: CGPoint, width: CGFloat, color: Color) {
+        let s = self.scale;
+        let (x0, y0, x1, y1) = (from.x * s, from.y * s, to.x * s, to.y * s);
+        let radius = width * s / 2.0;
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let length_squared = (dx * dx + dy * dy).max(f32::EPSILON);
+        let (left, top, right, bottom) = self.clip;
+        let min_x = ((x0.min(x1) - radius).floor() as i32).max(left);
+        let max_x = ((x0.max(x1) + radius).ceil() as i32).min(right);
+        let min_y = ((y0.min(y1) - radius).floor() as i32).max(top);
+        let max_y = ((y0.max(y1) + radius).ceil() as i32).min(bottom);
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                // Distance from the nearest point on the line.
+                let t = (((px - x0) * dx + (py - y0) * dy) / length_squared).clamp(0.0, 1.0);
+                let (cx, cy) = (x0 + t * dx, y0 + t * dy);
+                let distance = ((px - cx).powi(2) + (py - cy).powi(2)).sqrt();
+                let coverage = radius - distance + 0.5;
+                if coverage > 0.0 {
+                    self.blend(x, y, color, coverage);
+                }
+            }
+        }
+    }
+
+    /// Draw text horizontally centered in a rect, starting at its top.
+    pub(super) fn draw_text(
+        &mut self,
+        font: &Font,
+        size: CGFloat,
+        text: &str,
+        rect: CGRect,
+        color: Color,
+    ) {
+        let s = self.scale;
+        let (_, text_height) =
+            font.calculate_text_size(size * s, text, Some((rect.size.width * s, WrapMode::Word)));
+        // The font code uses y-up co-ordinates with the origin at the bottom
+        // of the text.
+        let bottom = self.height as f32 - (rect.origin.y * s + text_height);
+        let height = self.height as i32;
+        font.draw(
+            size * s,
+            text,
+            ((rect.origin.x + rect.size.width / 2.0) * s, bottom),
+            Some((rect.size.width * s, WrapMode::Word)),
+            TextAlignment::Center,
+            |(x, y), coverage| self.blend(x, height - 1 - y, color, coverage),
+        );
+    }
+
+    /// Draw text centered within a rect, on a single line.
+    pub(crate) fn draw_text_centered(
+        &mut self,
+        font: &Font,
+        size: CGFloat,
+        text: &str,
+        rect: CGRect,
+        color: Color,
+    ) {
+        let (_, text_height) = font.calculate_text_size(size, text, None);
+        let text_rect = CGRect {
+            origin: CGPoint {
+                x: rect.origin.x,
+                y: rect.origin.y + (rect.size.height - text_height) / 2.0,
+            },
+            size: CGSize {
+                width: rect.size.width,
+                height: text_height,
+            },
+        };
+        self.draw_text(font, size, text, text_rect, color);
+    }
+
+    /// Draw text left-aligned and wrapped to the width of a rect, starting at
+    /// its top.
+    pub(crate) fn draw_paragraph(
+        &mut self,
+        font: &Font,
+        size: CGFloat,
+        text: &str,
+        rect: CGRect,
+        color: Color,
+    ) {
+        let s = self.scale;
+        let wrap = Some((rect.size.width * s, WrapMode::Word));
+        let (_, text_height) = font.calculate_text_size(size * s, text, wrap);
+        let bottom = self.height as f32 - (rect.origin.y * s + text_height);
+        let height = self.height as i32;
+        font.draw(
+            size * s,
+            text,
+            (rect.origin.x * s, bottom),
+            wrap,
+            TextAlignment::Left,
+            |(x, y), coverage| self.blend(x, height - 1 - y, color, coverage),
+        );
+    }
+
+    /// Draw a single line of text, with its top-left corner at a point.
+    pub(crate) fn draw_text_at(
+        &mut self,
+        font: &Font,
+        size: CGFloat,
+        text: &str,
+        origin: CGPoint,
+        color: Color,
+    ) {
+        let s = self.scale;
+        let (_, text_height) = font.calculate_text_size(size * s, text, None);
+        let bottom = self.height as f32 - (origin.y * s + text_height);
+        let height = self.height as i32;
+        font.draw(
+            size * s,
+            text,
+            (origin.x * s, bottom),
+            None,
+            TextAlignment::Left,
+            |(x, y), coverage| self.blend(x, height - 1 - y, color, coverage),
+        );
+    }
+
+    /// Draw an image scaled to fill a rect.
+    pub(crate) fn draw_image(&mut self, image: &Image, rect: CGRect) {
+        self.draw_image_with_brightness(image, rect, 1.0);
+    }
+
+    /// Like [Self::draw_image], but with the colors multiplied by a factor,
+    /// e.g. to darken a highlighted button's image.
+    pub(super) fn draw_image_with_brightness(
+        &mut self,
+        image: &Image,
+        rect: CGRect,
+        brightness: f32,
+    ) {
+        let s = self.scale;
+        let (image_width, image_height) = image.dimensions();
+        let pixels = image.pixels();
+        let (x0, y0) = (rect.origin.x * s, rect.origin.y * s);
+        let (width, height) = (rect.size.width * s, rect.size.height * s);
+        if image_width == 0 || image_height == 0 || width <= 0.0 || height <= 0.0 {
+            return;
+        }
+        let (left, top, right, bottom) = self.clip;
+        for y in (y0.round() as i32).max(top)..((y0 + height).round() as i32).min(bottom) {
+            let v = ((y as f32 + 0.5 - y0) / height * image_height as f32) as u32;
+            let v = v.min(image_height - 1);
+            for x in (x0.round() as i32).max(left)..((x0 + width).round() as i32).min(right) {
+                let u = ((x as f32 + 0.5 - x0) / width * image_width as f32) as u32;
+                let u = u.min(image_width - 1);
+                let idx = ((v * image_width + u) * 4) as usize;
+                let [r, g, b, a] = [0, 1, 2, 3].map(|i| pixels[idx + i] as f32 / 255.0);
+                if a > 0.0 {
+                    let (r, g, b) = (r * brightness, g * brightness, b * brightness);
+                    self.blend(x, y, (r, g, b, a), 1.0);
+                }
+            }
+        }
+    }
+
+    /// Draw an image scaled to fill a rect, using only its alpha channel, with
+    /// a vertical gradient between two colors. This is how UIKit tints icons,
+    /// e.g. in tab bars.
+    pub(super) fn draw_image_mask(
+        &mut self,
+        image: &Image,
+        rect: CGRect,
+        top: Color,
+        bottom: Color,
+    ) {
+        let s = self.scale;
+        let (image_width, image_height) = image.dimensions();
+        let pixels = image.pixels();
+        let (x0, y0) = (rect.origin.x * s, rect.origin.y * s);
+        let (width, height) = (rect.size.width * s, rect.size.height * s);
+        if image_width == 0 || image_height == 0 || width <= 0.0 || height <= 0.0 {
+            return;
+        }
+        let (left, top_clip, right, bottom_clip) = self.clip;
+        for y in (y0.round() as i32).max(top_clip)..((y0 + height).round() as i32).min(bottom_clip)
+        {
+            let t = ((y as f32 + 0.5 - y0) / height).clamp(0.0, 1.0);
+            let color = (
+                top.0 + (bottom.0 - top.0) * t,
+                top.1 + (bottom.1 - top.1) * t,
+                top.2 + (bottom.2 - top.2) * t,
+                top.3 + (bottom.3 - top.3) * t,
+            );
+            let v = ((t * image_height as f32) as u32).min(image_height - 1);
+            for x in (x0.round() as i32).max(left)..((x0 + width).round() as i32).min(right) {
+                let u = ((x as f32 + 0.5 - x0) / width * image_width as f32) as u32;
+                let u = u.min(image_width - 1);
+                let a = pixels[((v * image_width + u) * 4 + 3) as usize] as f32 / 255.0;
+                if a > 0.0 {
+                    self.blend(x, y, color, a);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,259 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The status bar.
+//!
+//! It's only drawn if the `--status-bar` option is used, as an overlay (see
+//! [super::overlay]). Either way, apps are told that it takes up the top
+//! 20 points of the screen while it isn't hidden, because many apps lay out
+//! their views relative to it.
+
+use super::overlay::{rect, Canvas, Color, Overlay};
+use super::{ui_device, ui_font};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::foundation::ns_calendar::BrokenDownTime;
+use crate::frameworks::foundation::{ns_date, ns_time_zone, NSInteger};
+use crate::objc::{id, msg_class};
+use crate::window::{BatteryState, DeviceOrientation};
+use crate::Environment;
+
+pub const STATUS_BAR_HEIGHT: CGFloat = 20.0;
+
+pub type UIStatusBarStyle = NSInteger;
+pub const UIStatusBarStyleDefault: UIStatusBarStyle = 0;
+pub const UIStatusBarStyleBlackTranslucent: UIStatusBarStyle = 1;
+pub const UIStatusBarStyleBlackOpaque: UIStatusBarStyle = 2;
+
+const FONT_SIZE: CGFloat = 12.0;
+
+#[derive(Default)]
+pub struct State {
+    hidden: bool,
+    style: UIStatusBarStyle,
+    overlay: Option<Overlay>,
+    /// What the overlay shows.
+    overlay_contents: Option<DrawnStatusBar>,
+}
+
+/// Everything that affects how the status bar looks, so it's only redrawn
+/// when something changes.
+#[derive(PartialEq)]
+struct DrawnStatusBar {
+    orientation: DeviceOrientation,
+    style: UIStatusBarStyle,
+    time: String,
+    /// From 0 to 1, rounded to the nearest 5%.
+    battery_level: f32,
+    charging: bool,
+}
+
+/// Apps can ask for the status bar to be hidden, or have a particular style,
+/// at launch with the `UIStatusBarHidden` and `UIStatusBarStyle` keys in
+/// `Info.plist`.
+pub(super) fn set_initial_state_from_info_plist(env: &mut Environment) {
+    let info_plist = env.bundle.info_plist();
+    let hidden = info_plist
+        .get("UIStatusBarHidden")
+        .and_then(|value| value.as_boolean())
+        .unwrap_or(false);
+    let style = match info_plist
+        .get("UIStatusBarStyle")
+        .and_then(|value| value.as_string())
+    {
+        None | Some("UIStatusBarStyleDefault") => UIStatusBarStyleDefault,
+        Some("UIStatusBarStyleBlackTranslucent") => UIStatusBarStyleBlackTranslucent,
+        Some("UIStatusBarStyleBlackOpaque") => UIStatusBarStyleBlackOpaque,
+        Some(name) => {
+            log!("Warning: unknown UIStatusBarStyle {:?} in Info.plist", name);
+            UIStatusBarStyleDefault
+        }
+    };
+    log_dbg!(
+        "Initial status bar from Info.plist: hidden {}, style {}",
+        hidden,
+        style
+    );
+    let state = &mut env.framework_state.uikit.status_bar;
+    state.hidden = hidden;
+    state.style = style;
+}
+
+pub(super) fn is_hidden(env: &mut Environment) -> bool {
+    env.framework_state.uikit.status_bar.hidden
+}
+pub(super) fn set_hidden(env: &mut Environment, hidden: bool) {
+    env.framework_state.uikit.status_bar.hidden = hidden;
+}
+
+pub(super) fn style(env: &mut Environment) -> UIStatusBarStyle {
+    env.framework_state.uikit.status_bar.style
+}
+pub(super) fn set_style(env: &mut Environment, style: UIStatusBarStyle) {
+    env.framework_state.uikit.status_bar.style = style;
+}
+
+/// The frame of the status bar in screen co-ordinates, which are always in
+/// portrait orientation, or an empty rect if it's hidden.
+pub(super) fn frame(env: &mut Environment) -> CGRect {
+    if is_hidden(env) {
+        return rect(0.0, 0.0, 0.0, 0.0);
+    }
+    let (width, height) = env.window.size_unrotated_unscaled();
+    let (width, height) = (width as CGFloat, height as CGFloat);
+    match env.window.device_orientation() {
+        DeviceOrientation::Portrait => rect(0.0, 0.0, width, STATUS_BAR_HEIGHT),
+        DeviceOrientation::PortraitUpsideDown => {
+            rect(0.0, height - STATUS_BAR_HEIGHT, width, STATUS_BAR_HEIGHT)
+        }
+        DeviceOrientation::LandscapeLeft => {
+            rect(width - STATUS_BAR_HEIGHT, 0.0, STATUS_BAR_HEIGHT, height)
+        }
+        DeviceOrientation::LandscapeRight => rect(0.0, 0.0, STATUS_BAR_HEIGHT, height),
+    }
+}
+
+/// The part of the screen not covered by the status bar, in screen
+/// co-ordinates, which are always in portrait orientation.
+pub(super) fn application_frame(env: &mut Environment) -> CGRect {
+    let (width, height) = env.window.size_unrotated_unscaled();
+    let (width, height) = (width as CGFloat, height as CGFloat);
+    let bar = frame(env);
+    if bar.size.width == width {
+        // A horizontal bar, at the top or bottom.
+        let y = if bar.origin.y == 0.0 {
+            bar.size.height
+        } else {
+            0.0
+        };
+        rect(0.0, y, width, height - bar.size.height)
+    } else {
+        // A vertical bar, on the left or right.
+        let x = if bar.origin.x == 0.0 {
+            bar.size.width
+        } else {
+            0.0
+        };
+        rect(x, 0.0, width - bar.size.width, height)
+    }
+}
+
+fn drawn_status_bar(env: &mut Environment) -> DrawnStatusBar {
+    let time_zone: id = msg_class![env; NSTimeZone defaultTimeZone];
+    let offset = ns_time_zone::seconds_from_gmt(env, time_zone);
+    let now = BrokenDownTime::from_time_interval(ns_date::now_since_reference_date(), offset);
+    let hour = match now.hour % 12 {
+        0 => 12,
+        hour => hour,
+    };
+    let am_pm = if now.hour < 12 { "AM" } else { "PM" };
+    let time = format!("{}:{:02} {}", hour, now.minute, am_pm);
+
+    let (battery_state, battery_level) = ui_device::battery_info(env);
+    let battery_level = (battery_level.unwrap_or(1.0) * 20.0).round() / 20.0;
+
+    let state = &env.framework_state.uikit.status_bar;
+    DrawnStatusBar {
+        orientation: env.window.device_orientation(),
+        style: state.style,
+        time,
+        battery_level,
+        charging: battery_state == BatteryState::Charging,
+    }
+}
+
+fn draw_status_bar(env: &mut Environment, contents: &DrawnStatusBar) -> Overlay {
+    let mut canvas = Canvas::new_for_orientation(env, contents.orientation);
+    let (width, height) = env.window.size_unrotated_unscaled();
+    let width = match contents.orientation {
+        DeviceOrientation::Portrait | DeviceOrientation::PortraitUpsideDown => width,
+        DeviceOrientation::LandscapeLeft | DeviceOrientation::LandscapeRight => height,
+    } as CGFloat;
+
+    let (background, foreground): (Color, Color) = match contents.style {
+        UIStatusBarStyleBlackTranslucent => ((0.0, 0.0, 0.0, 0.5), (1.0, 1.0, 1.0, 1.0)),
+        UIStatusBarStyleBlackOpaque => ((0.0, 0.0, 0.0, 1.0), (1.0, 1.0, 1.0, 1.0)),
+        _ => ((0.75, 0.75, 0.75, 1.0), (0.0, 0.0, 0.0, 1.0)),
+    };
+    canvas.fill_rounded_rect(rect(0.0, 0.0, width, STATUS_BAR_HEIGHT), 0.0, background);
+
+    // Signal strength: always full.
+    for i in 0..5 {
+        let bar_height = 4.0 + 2.0 * i as CGFloat;
+        let bar = rect(5.0 + 4.0 * i as CGFloat, 15.0 - bar_height, 3.0, bar_height);
+        canvas.fill_rounded_rect(bar, 0.0, foreground);
+    }
+
+    let carrier = env.options.carrier.clone();
+    let font = ui_font::system_font(env, true, &carrier);
+    canvas.draw_text_at(
+        font,
+        FONT_SIZE,
+        &carrier,
+        CGPoint { x: 27.0, y: 3.0 },
+        foreground,
+    );
+
+    let font = ui_font::system_font(env, true, &contents.time);
+    canvas.draw_text_centered(
+        font,
+        FONT_SIZE,
+        &contents.time,
+        rect(0.0, 0.0, width, STATUS_BAR_HEIGHT),
+        foreground,
+    );
+
+    // Battery: an outline with a tip, filled according to the level.
+    let (x, y, w, h) = (width - 32.0, 5.0, 24.0, 11.0);
+    for line in [
+        rect(x, y, w, 1.0),
+        rect(x, y + h - 1.0, w, 1.0),
+        rect(x, y, 1.0, h),
+        rect(x + w - 1.0, y, 1.0, h),
+        rect(x + w, y + 3.0, 2.0, h - 6.0),
+    ] {
+        canvas.fill_rounded_rect(line, 0.0, foreground);
+    }
+    let fill_color = if contents.charging {
+        (0.3, 0.85, 0.3, 1.0)
+    } else {
+        foreground
+    };
+    let fill = rect(
+        x + 2.0,
+        y + 2.0,
+        (w - 4.0) * contents.battery_level,
+        h - 4.0,
+    );
+    canvas.fill_rounded_rect(fill, 0.0, fill_color);
+
+    canvas.into_overlay()
+}
+
+/// For use by [super::overlay]: redraw the status bar if needed.
+pub(super) fn update_overlay(env: &mut Environment) {
+    if !env.options.status_bar || is_hidden(env) {
+        let state = &mut env.framework_state.uikit.status_bar;
+        state.overlay = None;
+        state.overlay_contents = None;
+        return;
+    }
+
+    let contents = drawn_status_bar(env);
+    let state = &env.framework_state.uikit.status_bar;
+    if state.overlay.is_some() && state.overlay_contents.as_ref() == Some(&contents) {
+        return;
+    }
+
+    let overlay = draw_status_bar(env, &contents);
+    let state = &mut env.framework_state.uikit.status_bar;
+    state.overlay = Some(overlay);
+    state.overlay_contents = Some(contents);
+}
+
+/// For use by [super::overlay]: get the drawing of the status bar, if it's
+/// visible.
+pub(super) fn current_overlay(state: &State) -> Option<&Overlay> {
+    state.overlay.as_ref()
+}
@@ -17,6 +17,13 @@ use crate::objc::{
 use crate::Environment;
 use std::time::{Duration, Instant};
 
+/// The update interval used if the app never sets one. The real default is
+/// undocumented, but this is a common choice in apps.
+const DEFAULT_UPDATE_INTERVAL: NSTimeInterval = 1.0 / 60.0;
+/// Apps sometimes ask for an interval of zero, meaning "as fast as possible".
+/// The real hardware tops out at around 100Hz.
+const MINIMUM_UPDATE_INTERVAL: NSTimeInterval = 1.0 / 100.0;
+
 #[derive(Default)]
 pub struct State {
     /// [UIAccelerometer sharedAccelerometer]
@@ -72,16 +79,18 @@ pub const CLASSES: ClassExports = objc_classes! {
         env.framework_state.uikit.ui_accelerometer.delegate = None;
     } else {
         env.framework_state.uikit.ui_accelerometer.delegate = Some(delegate);
-        env.window.print_accelerometer_notice();
+        env.window.print_accelerometer_notice(&env.options);
     }
 }
 
 - (NSTimeInterval)updateInterval {
-    // TODO: return some reasonable default value
-    env.framework_state.uikit.ui_accelerometer.update_interval.unwrap()
+    env.framework_state.uikit.ui_accelerometer.update_interval.unwrap_or(DEFAULT_UPDATE_INTERVAL)
 }
 - (())setUpdateInterval:(NSTimeInterval)interval {
-    env.framework_state.uikit.ui_accelerometer.update_interval = Some(interval);
+    let state = &mut env.framework_state.uikit.ui_accelerometer;
+    state.update_interval = Some(interval);
+    // Start counting the new interval from the next update.
+    state.due_by = None;
 }
 
 @end
@@ -124,8 +133,10 @@ pub(super) fn handle_accelerometer(env: &mut Environment) {
         return;
     };
 
-    // TODO: use some reasonable default value
-    let ns_interval = state.update_interval.unwrap();
+    let ns_interval = state
+        .update_interval
+        .unwrap_or(DEFAULT_UPDATE_INTERVAL)
+        .max(MINIMUM_UPDATE_INTERVAL);
     let rust_interval = Duration::from_secs_f64(ns_interval);
 
     let now = Instant::now();
@@ -154,7 +165,7 @@ pub(super) fn handle_accelerometer(env: &mut Environment) {
     // UIKit creates and drains autorelease pools when handling events.
     let pool: id = msg_class![env; NSAutoreleasePool new];
 
-    let (x, y, z) = env.window.get_acceleration(&env.options);
+    let (x, y, z) = crate::replay::acceleration(env);
     let timestamp: NSTimeInterval = msg_class![env; NSProcessInfo systemUptime];
     let acceleration: id = msg_class![env; UIAcceleration alloc];
     *env.objc.borrow_mut(acceleration) = UIAccelerationHostObject {
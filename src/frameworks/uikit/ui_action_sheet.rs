@@ -0,0 +1,132 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIActionSheet`.
+//!
+//! This shares its implementation with `UIAlertView`, see [super::ui_alert_view].
+
+use super::ui_alert_view::{
+    add_button, add_button_titles, button_title, changed, dismiss, first_other_button_index,
+    is_visible, optional_ns_string, optional_string, show, state, AlertKind,
+};
+use crate::frameworks::foundation::NSInteger;
+use crate::objc::{id, msg, objc_classes, ClassExports};
+
+pub type UIActionSheetStyle = NSInteger;
+#[allow(dead_code)]
+pub const UIActionSheetStyleAutomatic: UIActionSheetStyle = -1;
+#[allow(dead_code)]
+pub const UIActionSheetStyleDefault: UIActionSheetStyle = 0;
+
+const KIND: AlertKind = AlertKind::ActionSheet;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIActionSheet: UIView
+
+- (id)initWithTitle:(id)title // NSString*
+           delegate:(id)delegate
+  cancelButtonTitle:(id)cancel_button_title // NSString*
+destructiveButtonTitle:(id)destructive_button_title // NSString*
+  otherButtonTitles:(id)first_other_button_title, // NSString*
+                    ...rest {
+    let this: id = msg![env; this init];
+    let title = optional_string(env, title);
+    let cancel_button_title = optional_string(env, cancel_button_title);
+    let destructive_button_title = optional_string(env, destructive_button_title);
+    let alert_state = state(env, this, KIND);
+    alert_state.title = title;
+    alert_state.delegate = delegate;
+    if let Some(destructive_button_title) = destructive_button_title {
+        alert_state.destructive_button_index = 0;
+        alert_state.buttons.push(destructive_button_title);
+    }
+    add_button_titles(env, this, KIND, first_other_button_title, rest);
+    // The cancel button comes after all the others.
+    if let Some(cancel_button_title) = cancel_button_title {
+        let alert_state = state(env, this, KIND);
+        alert_state.cancel_button_index = alert_state.buttons.len().try_into().unwrap();
+        alert_state.buttons.push(cancel_button_title);
+    }
+    this
+}
+
+- (id)delegate {
+    state(env, this, KIND).delegate
+}
+- (())setDelegate:(id)delegate {
+    state(env, this, KIND).delegate = delegate;
+}
+
+- (id)title {
+    let title = state(env, this, KIND).title.clone();
+    optional_ns_string(env, title)
+}
+- (())setTitle:(id)title { // NSString*
+    let title = optional_string(env, title);
+    state(env, this, KIND).title = title;
+    changed(env, this);
+}
+
+- (UIActionSheetStyle)actionSheetStyle {
+    state(env, this, KIND).style
+}
+- (())setActionSheetStyle:(UIActionSheetStyle)style {
+    state(env, this, KIND).style = style;
+}
+
+- (NSInteger)addButtonWithTitle:(id)title { // NSString*
+    add_button(env, this, KIND, title)
+}
+- (id)buttonTitleAtIndex:(NSInteger)index {
+    button_title(env, this, KIND, index)
+}
+- (NSInteger)numberOfButtons {
+    state(env, this, KIND).buttons.len().try_into().unwrap()
+}
+- (NSInteger)cancelButtonIndex {
+    state(env, this, KIND).cancel_button_index
+}
+- (())setCancelButtonIndex:(NSInteger)index {
+    state(env, this, KIND).cancel_button_index = index;
+    changed(env, this);
+}
+- (NSInteger)destructiveButtonIndex {
+    state(env, this, KIND).destructive_button_index
+}
+- (())setDestructiveButtonIndex:(NSInteger)index {
+    state(env, this, KIND).destructive_button_index = index;
+    changed(env, this);
+}
+- (NSInteger)firstOtherButtonIndex {
+    first_other_button_index(env, this, KIND)
+}
+
+- (bool)isVisible {
+    is_visible(env, this)
+}
+
+// The sheet is always shown at the bottom of the screen, so the view doesn't
+// matter.
+- (())showInView:(id)_view { // UIView*
+    show(env, this, KIND);
+}
+- (())showFromTabBar:(id)_tab_bar { // UITabBar*
+    show(env, this, KIND);
+}
+- (())showFromToolbar:(id)_toolbar { // UIToolbar*
+    show(env, this, KIND);
+}
+
+- (())dismissWithClickedButtonIndex:(NSInteger)index
+                           animated:(bool)_animated {
+    dismiss(env, this, KIND, index);
+}
+
+@end
+
+};
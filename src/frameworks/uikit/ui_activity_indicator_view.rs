@@ -0,0 +1,213 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIActivityIndicatorView`.
+//!
+//! The spinner is drawn by the host as an overlay (see [super::overlay]),
+//! since views aren't composited yet. Like in iPhone OS 3, it has twelve
+//! spokes and the bright one moves round once per second. It's redrawn when
+//! the app presents a frame, so it only moves while the app is drawing.
+
+use super::overlay::{rect, Canvas, Color, Overlay};
+use super::ui_view::{frame_on_screen, UIViewHostObject};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint};
+use crate::frameworks::foundation::NSInteger;
+use crate::objc::{id, msg, objc_classes, ClassExports};
+use crate::Environment;
+use std::f32::consts::PI;
+use std::time::Instant;
+
+pub type UIActivityIndicatorViewStyle = NSInteger;
+pub const UIActivityIndicatorViewStyleWhiteLarge: UIActivityIndicatorViewStyle = 0;
+pub const UIActivityIndicatorViewStyleWhite: UIActivityIndicatorViewStyle = 1;
+pub const UIActivityIndicatorViewStyleGray: UIActivityIndicatorViewStyle = 2;
+
+const SPOKES: u32 = 12;
+
+#[derive(Default)]
+pub struct State {
+    overlay: Option<Overlay>,
+    /// What the overlay shows.
+    overlay_contents: Vec<DrawnSpinner>,
+}
+
+#[derive(PartialEq)]
+struct DrawnSpinner {
+    center: CGPoint,
+    style: UIActivityIndicatorViewStyle,
+    /// Which spoke is the brightest.
+    step: u32,
+}
+
+pub(super) struct ActivityIndicatorState {
+    style: UIActivityIndicatorViewStyle,
+    /// When the animation started, if it's animating.
+    animating_since: Option<Instant>,
+    hides_when_stopped: bool,
+}
+
+fn state(env: &mut Environment, view: id) -> &mut ActivityIndicatorState {
+    env.objc
+        .borrow_mut::<UIViewHostObject>(view)
+        .activity_indicator
+        .get_or_insert_with(|| {
+            Box::new(ActivityIndicatorState {
+                style: UIActivityIndicatorViewStyleWhite,
+                animating_since: None,
+                hides_when_stopped: true,
+            })
+        })
+}
+
+/// The size of the spinner, which doesn't depend on the view's frame.
+fn size_for_style(style: UIActivityIndicatorViewStyle) -> CGFloat {
+    if style == UIActivityIndicatorViewStyleWhiteLarge {
+        37.0
+    } else {
+        20.0
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIActivityIndicatorView: UIView
+
+- (id)initWithActivityIndicatorStyle:(UIActivityIndicatorViewStyle)style {
+    let size = size_for_style(style);
+    let frame = rect(0.0, 0.0, size, size);
+    let this: id = msg![env; this initWithFrame:frame];
+    state(env, this).style = style;
+    // Not animating yet.
+    () = msg![env; this setHidden:true];
+    this
+}
+
+- (UIActivityIndicatorViewStyle)activityIndicatorViewStyle {
+    state(env, this).style
+}
+- (())setActivityIndicatorViewStyle:(UIActivityIndicatorViewStyle)style {
+    state(env, this).style = style;
+}
+
+- (bool)hidesWhenStopped {
+    state(env, this).hides_when_stopped
+}
+- (())setHidesWhenStopped:(bool)hides {
+    let state = state(env, this);
+    state.hides_when_stopped = hides;
+    let hidden = hides && state.animating_since.is_none();
+    () = msg![env; this setHidden:hidden];
+}
+
+- (())startAnimating {
+    let state = state(env, this);
+    if state.animating_since.is_none() {
+        state.animating_since = Some(Instant::now());
+    }
+    () = msg![env; this setHidden:false];
+}
+- (())stopAnimating {
+    let state = state(env, this);
+    state.animating_since = None;
+    if state.hides_when_stopped {
+        () = msg![env; this setHidden:true];
+    }
+}
+- (bool)isAnimating {
+    state(env, this).animating_since.is_some()
+}
+
+@end
+
+};
+
+fn visible_spinners(env: &mut Environment) -> Vec<DrawnSpinner> {
+    let class = env
+        .objc
+        .get_known_class("UIActivityIndicatorView", &mut env.mem);
+    let views = env.framework_state.uikit.ui_view.views.clone();
+    let mut spinners = Vec::new();
+    for view in views {
+        if !msg![env; view isKindOfClass:class] || env.objc.borrow::<UIViewHostObject>(view).hidden
+        {
+            continue;
+        }
+        let Some(frame) = frame_on_screen(env, view) else {
+            continue;
+        };
+        let state = state(env, view);
+        let step = match state.animating_since {
+            Some(since) => (since.elapsed().as_secs_f64() * f64::from(SPOKES)) as u32 % SPOKES,
+            None => 0,
+        };
+        spinners.push(DrawnSpinner {
+            center: CGPoint {
+                x: frame.origin.x + frame.size.width / 2.0,
+                y: frame.origin.y + frame.size.height / 2.0,
+            },
+            style: state.style,
+            step,
+        });
+    }
+    spinners
+}
+
+fn draw_spinner(canvas: &mut Canvas, spinner: &DrawnSpinner) {
+    let size = size_for_style(spinner.style);
+    let (r, g, b) = if spinner.style == UIActivityIndicatorViewStyleGray {
+        (0.5, 0.5, 0.5)
+    } else {
+        (1.0, 1.0, 1.0)
+    };
+    let inner_radius = size * 0.24;
+    let outer_radius = size * 0.44;
+    let width = size * 0.1;
+    for i in 0..SPOKES {
+        // The brightest spoke is the current step, and the ones behind it
+        // fade out.
+        let age = (spinner.step + SPOKES - i) % SPOKES;
+        let alpha = 1.0 - (age as f32 / SPOKES as f32) * 0.75;
+        let color: Color = (r, g, b, alpha);
+        // Spoke 0 points straight up, and the steps go clockwise.
+        let angle = (i as f32 / SPOKES as f32) * 2.0 * PI;
+        let (sin, cos) = angle.sin_cos();
+        let point = |radius: CGFloat| CGPoint {
+            x: spinner.center.x + sin * radius,
+            y: spinner.center.y - cos * radius,
+        };
+        canvas.draw_line(point(inner_radius), point(outer_radius), width, color);
+    }
+}
+
+/// For use by [super::overlay]: redraw the visible spinners if needed.
+pub(super) fn update_overlay(env: &mut Environment) {
+    let contents = visible_spinners(env);
+
+    let state = &mut env.framework_state.uikit.ui_activity_indicator_view;
+    if contents == state.overlay_contents && (state.overlay.is_some() || contents.is_empty()) {
+        return;
+    }
+    if contents.is_empty() {
+        state.overlay_contents = contents;
+        state.overlay = None;
+        return;
+    }
+
+    let mut canvas = Canvas::new(env);
+    for spinner in &contents {
+        draw_spinner(&mut canvas, spinner);
+    }
+    let state = &mut env.framework_state.uikit.ui_activity_indicator_view;
+    state.overlay_contents = contents;
+    state.overlay = Some(canvas.into_overlay());
+}
+
+/// For use by [super::overlay]: get the drawing of the spinners, if any are
+/// visible.
+pub(super) fn current_overlay(state: &State) -> Option<&Overlay> {
+    state.overlay.as_ref()
+}
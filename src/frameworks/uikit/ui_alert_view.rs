@@ -0,0 +1,716 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIAlertView`, and the modal overlay it shares with `UIActionSheet`.
+//!
+//! Since UIKit views aren't composited yet, alerts and action sheets are drawn
+//! by the host as an overlay (see [super::overlay]). While one is visible, it
+//! takes all new touches, so the app doesn't see them.
+
+use super::overlay::{contains, rect, Canvas, Color, Overlay};
+use super::ui_font;
+use super::ui_view::UIViewHostObject;
+use crate::abi::VAList;
+use crate::font::WrapMode;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::{ns_string, NSInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    SEL,
+};
+use crate::window::{Event, TouchSource};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// Strong references to the visible alerts and action sheets. The last one
+    /// is on top and is the only one that's drawn and gets touches.
+    visible: Vec<id>,
+    /// Cached drawing of the top alert, cleared when it needs redrawing.
+    overlay: Option<Overlay>,
+    /// The touch being tracked by the top alert, if any.
+    touch: Option<TouchSource>,
+    /// Index of the button under the current touch.
+    pressed_button: Option<usize>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(super) enum AlertKind {
+    AlertView,
+    ActionSheet,
+}
+
+pub(super) struct AlertState {
+    kind: AlertKind,
+    pub(super) title: Option<String>,
+    pub(super) message: Option<String>,
+    pub(super) buttons: Vec<String>,
+    pub(super) cancel_button_index: NSInteger,
+    pub(super) destructive_button_index: NSInteger,
+    /// Weak reference.
+    pub(super) delegate: id,
+    /// `UIActionSheetStyle`, currently ignored.
+    pub(super) style: NSInteger,
+}
+impl AlertState {
+    fn new(kind: AlertKind) -> AlertState {
+        AlertState {
+            kind,
+            title: None,
+            message: None,
+            buttons: Vec::new(),
+            cancel_button_index: -1,
+            destructive_button_index: -1,
+            delegate: nil,
+            style: 0,
+        }
+    }
+}
+
+/// Get the state of an alert view or action sheet.
+pub(super) fn state(env: &mut Environment, alert: id, kind: AlertKind) -> &mut AlertState {
+    env.objc
+        .borrow_mut::<UIViewHostObject>(alert)
+        .alert
+        .get_or_insert_with(|| Box::new(AlertState::new(kind)))
+}
+
+/// Convert an optional `NSString*` to a Rust string.
+pub(super) fn optional_string(env: &mut Environment, string: id) -> Option<String> {
+    if string == nil {
+        None
+    } else {
+        Some(ns_string::to_rust_string(env, string).into_owned())
+    }
+}
+
+/// Get a string from an optional Rust string.
+pub(super) fn optional_ns_string(env: &mut Environment, string: Option<String>) -> id {
+    match string {
+        Some(string) => {
+            let string = ns_string::from_rust_string(env, string);
+            autorelease(env, string)
+        }
+        None => nil,
+    }
+}
+
+/// Add the button titles from a `nil`-terminated variable arguments list.
+pub(super) fn add_button_titles(
+    env: &mut Environment,
+    alert: id,
+    kind: AlertKind,
+    first: id,
+    mut rest: VAList,
+) {
+    let mut title = first;
+    while title != nil {
+        let title_string = ns_string::to_rust_string(env, title).into_owned();
+        state(env, alert, kind).buttons.push(title_string);
+        title = rest.next(env);
+    }
+}
+
+const KIND: AlertKind = AlertKind::AlertView;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIAlertView: UIView
+
+- (id)initWithTitle:(id)title // NSString*
+            message:(id)message // NSString*
+           delegate:(id)delegate
+  cancelButtonTitle:(id)cancel_button_title // NSString*
+  otherButtonTitles:(id)first_other_button_title, // NSString*
+                    ...rest {
+    let this: id = msg![env; this init];
+    let title = optional_string(env, title);
+    let message = optional_string(env, message);
+    let cancel_button_title = optional_string(env, cancel_button_title);
+    let state = state(env, this, KIND);
+    state.title = title;
+    state.message = message;
+    state.delegate = delegate;
+    if let Some(cancel_button_title) = cancel_button_title {
+        state.cancel_button_index = 0;
+        state.buttons.push(cancel_button_title);
+    }
+    add_button_titles(env, this, KIND, first_other_button_title, rest);
+    this
+}
+
+- (id)delegate {
+    state(env, this, KIND).delegate
+}
+- (())setDelegate:(id)delegate {
+    state(env, this, KIND).delegate = delegate;
+}
+
+- (id)title {
+    let title = state(env, this, KIND).title.clone();
+    optional_ns_string(env, title)
+}
+- (())setTitle:(id)title { // NSString*
+    let title = optional_string(env, title);
+    state(env, this, KIND).title = title;
+    changed(env, this);
+}
+- (id)message {
+    let message = state(env, this, KIND).message.clone();
+    optional_ns_string(env, message)
+}
+- (())setMessage:(id)message { // NSString*
+    let message = optional_string(env, message);
+    state(env, this, KIND).message = message;
+    changed(env, this);
+}
+
+- (NSInteger)addButtonWithTitle:(id)title { // NSString*
+    add_button(env, this, KIND, title)
+}
+- (id)buttonTitleAtIndex:(NSInteger)index {
+    button_title(env, this, KIND, index)
+}
+- (NSInteger)numberOfButtons {
+    state(env, this, KIND).buttons.len().try_into().unwrap()
+}
+- (NSInteger)cancelButtonIndex {
+    state(env, this, KIND).cancel_button_index
+}
+- (())setCancelButtonIndex:(NSInteger)index {
+    state(env, this, KIND).cancel_button_index = index;
+    changed(env, this);
+}
+- (NSInteger)firstOtherButtonIndex {
+    first_other_button_index(env, this, KIND)
+}
+
+- (bool)isVisible {
+    is_visible(env, this)
+}
+
+- (())show {
+    show(env, this, KIND);
+}
+- (())dismissWithClickedButtonIndex:(NSInteger)index
+                           animated:(bool)_animated {
+    dismiss(env, this, KIND, index);
+}
+
+@end
+
+};
+
+pub(super) fn add_button(
+    env: &mut Environment,
+    alert: id,
+    kind: AlertKind,
+    title: id,
+) -> NSInteger {
+    let title = ns_string::to_rust_string(env, title).into_owned();
+    let buttons = &mut state(env, alert, kind).buttons;
+    buttons.push(title);
+    let index = (buttons.len() - 1).try_into().unwrap();
+    changed(env, alert);
+    index
+}
+
+pub(super) fn button_title(
+    env: &mut Environment,
+    alert: id,
+    kind: AlertKind,
+    index: NSInteger,
+) -> id {
+    let buttons = &state(env, alert, kind).buttons;
+    let title = usize::try_from(index)
+        .ok()
+        .and_then(|index| buttons.get(index))
+        .cloned();
+    optional_ns_string(env, title)
+}
+
+pub(super) fn first_other_button_index(
+    env: &mut Environment,
+    alert: id,
+    kind: AlertKind,
+) -> NSInteger {
+    let state = state(env, alert, kind);
+    (0..state.buttons.len())
+        .map(|i| i as NSInteger)
+        .find(|&i| i != state.cancel_button_index && i != state.destructive_button_index)
+        .unwrap_or(-1)
+}
+
+pub(super) fn is_visible(env: &mut Environment, alert: id) -> bool {
+    env.framework_state
+        .uikit
+        .ui_alert_view
+        .visible
+        .contains(&alert)
+}
+
+/// Mark the overlay for redrawing if the alert is visible.
+pub(super) fn changed(env: &mut Environment, alert: id) {
+    let state = &mut env.framework_state.uikit.ui_alert_view;
+    if state.visible.last() == Some(&alert) {
+        state.overlay = None;
+    }
+}
+
+/// Call a delegate method taking the alert, if it's implemented. The method
+/// names for `UIAlertView` and `UIActionSheet` delegates are given in that
+/// order.
+fn notify_delegate(env: &mut Environment, alert: id, kind: AlertKind, names: (&str, &str)) {
+    let delegate = state(env, alert, kind).delegate;
+    if let Some(selector) = responding_selector(env, delegate, kind, names) {
+        let () = msg_send(env, (delegate, selector, alert));
+    }
+}
+
+/// Like [notify_delegate], but for methods that also take a button index.
+fn notify_delegate_with_index(
+    env: &mut Environment,
+    alert: id,
+    kind: AlertKind,
+    names: (&str, &str),
+    index: NSInteger,
+) {
+    let delegate = state(env, alert, kind).delegate;
+    if let Some(selector) = responding_selector(env, delegate, kind, names) {
+        let () = msg_send(env, (delegate, selector, alert, index));
+    }
+}
+
+fn responding_selector(
+    env: &mut Environment,
+    object: id,
+    kind: AlertKind,
+    (alert_view_name, action_sheet_name): (&str, &str),
+) -> Option<SEL> {
+    if object == nil {
+        return None;
+    }
+    let name = match kind {
+        AlertKind::AlertView => alert_view_name,
+        AlertKind::ActionSheet => action_sheet_name,
+    };
+    let selector = env.objc.lookup_selector(name)?;
+    if msg![env; object respondsToSelector:selector] {
+        Some(selector)
+    } else {
+        None
+    }
+}
+
+/// Implementation of `show` and its `UIActionSheet` equivalents.
+pub(super) fn show(env: &mut Environment, alert: id, kind: AlertKind) {
+    if is_visible(env, alert) {
+        return;
+    }
+    log_dbg!("Showing {:?}", alert);
+    notify_delegate(
+        env,
+        alert,
+        kind,
+        ("willPresentAlertView:", "willPresentActionSheet:"),
+    );
+    retain(env, alert);
+    let state = &mut env.framework_state.uikit.ui_alert_view;
+    state.visible.push(alert);
+    state.overlay = None;
+    state.touch = None;
+    state.pressed_button = None;
+    notify_delegate(
+        env,
+        alert,
+        kind,
+        ("didPresentAlertView:", "didPresentActionSheet:"),
+    );
+}
+
+/// Implementation of `dismissWithClickedButtonIndex:animated:`.
+pub(super) fn dismiss(env: &mut Environment, alert: id, kind: AlertKind, index: NSInteger) {
+    if !is_visible(env, alert) {
+        return;
+    }
+    log_dbg!("Dismissing {:?} with button index {}", alert, index);
+    notify_delegate_with_index(
+        env,
+        alert,
+        kind,
+        (
+            "alertView:willDismissWithButtonIndex:",
+            "actionSheet:willDismissWithButtonIndex:",
+        ),
+        index,
+    );
+    let state = &mut env.framework_state.uikit.ui_alert_view;
+    let was_top = state.visible.last() == Some(&alert);
+    state.visible.retain(|&visible| visible != alert);
+    state.overlay = None;
+    if was_top {
+        state.touch = None;
+        state.pressed_button = None;
+    }
+    notify_delegate_with_index(
+        env,
+        alert,
+        kind,
+        (
+            "alertView:didDismissWithButtonIndex:",
+            "actionSheet:didDismissWithButtonIndex:",
+        ),
+        index,
+    );
+    release(env, alert);
+}
+
+fn kind_of(env: &mut Environment, alert: id) -> AlertKind {
+    env.objc
+        .borrow::<UIViewHostObject>(alert)
+        .alert
+        .as_ref()
+        .unwrap()
+        .kind
+}
+
+/// A button tapped by the user.
+fn clicked(env: &mut Environment, alert: id, index: usize) {
+    let kind = kind_of(env, alert);
+    let index: NSInteger = index.try_into().unwrap();
+    // Keep the alert alive until the end, since the delegate might release it.
+    retain(env, alert);
+    notify_delegate_with_index(
+        env,
+        alert,
+        kind,
+        (
+            "alertView:clickedButtonAtIndex:",
+            "actionSheet:clickedButtonAtIndex:",
+        ),
+        index,
+    );
+    dismiss(env, alert, kind, index);
+    release(env, alert);
+}
+
+// Layout, all in points, in the same co-ordinate space as touches.
+
+const ALERT_WIDTH: CGFloat = 284.0;
+const ALERT_PADDING: CGFloat = 16.0;
+const ALERT_TITLE_SIZE: CGFloat = 18.0;
+const ALERT_MESSAGE_SIZE: CGFloat = 16.0;
+const ALERT_BUTTON_HEIGHT: CGFloat = 43.0;
+const SHEET_PADDING: CGFloat = 20.0;
+const SHEET_TITLE_SIZE: CGFloat = 13.0;
+const SHEET_BUTTON_HEIGHT: CGFloat = 46.0;
+const BUTTON_TITLE_SIZE: CGFloat = 18.0;
+const BUTTON_GAP: CGFloat = 8.0;
+const CORNER_RADIUS: CGFloat = 8.0;
+
+struct TextBox {
+    rect: CGRect,
+    text: String,
+    size: CGFloat,
+    bold: bool,
+    color: Color,
+}
+
+struct ButtonBox {
+    index: usize,
+    rect: CGRect,
+}
+
+struct Layout {
+    dim_color: Color,
+    panel: CGRect,
+    texts: Vec<TextBox>,
+    buttons: Vec<ButtonBox>,
+}
+
+fn text_height(
+    env: &mut Environment,
+    text: &str,
+    size: CGFloat,
+    bold: bool,
+    width: CGFloat,
+) -> CGFloat {
+    let font = ui_font::system_font(env, bold, text);
+    font.calculate_text_size(size, text, Some((width, WrapMode::Word)))
+        .1
+}
+
+fn lay_out(env: &mut Environment, alert: id) -> Layout {
+    let (screen_width, screen_height) = env.window.size_unrotated_unscaled();
+    let (screen_width, screen_height) = (screen_width as CGFloat, screen_height as CGFloat);
+
+    let kind = kind_of(env, alert);
+    let state = state(env, alert, kind);
+    let title = state.title.clone();
+    let message = state.message.clone();
+    let button_count = state.buttons.len();
+    let cancel_index = usize::try_from(state.cancel_button_index).ok();
+
+    // The cancel button goes last, except for alerts with two buttons side by
+    // side, where the buttons are in index order.
+    let side_by_side = kind == AlertKind::AlertView && button_count == 2;
+    let mut button_order: Vec<usize> = (0..button_count).collect();
+    if !side_by_side {
+        if let Some(cancel_index) = cancel_index.filter(|&i| i < button_count) {
+            button_order.retain(|&i| i != cancel_index);
+            button_order.push(cancel_index);
+        }
+    }
+
+    let (panel_width, padding, text_specs, button_height) = match kind {
+        AlertKind::AlertView => (
+            ALERT_WIDTH,
+            ALERT_PADDING,
+            [
+                (title, ALERT_TITLE_SIZE, true),
+                (message, ALERT_MESSAGE_SIZE, false),
+            ],
+            ALERT_BUTTON_HEIGHT,
+        ),
+        AlertKind::ActionSheet => (
+            screen_width,
+            SHEET_PADDING,
+            [(title, SHEET_TITLE_SIZE, false), (None, 0.0, false)],
+            SHEET_BUTTON_HEIGHT,
+        ),
+    };
+    let content_width = panel_width - padding * 2.0;
+
+    // Lay out relative to the top of the panel first.
+    let mut y = ALERT_PADDING;
+    let mut texts = Vec::new();
+    for (text, size, bold) in text_specs {
+        let Some(text) = text else {
+            continue;
+        };
+        if !texts.is_empty() {
+            y += BUTTON_GAP;
+        }
+        let height = text_height(env, &text, size, bold, content_width);
+        texts.push(TextBox {
+            rect: rect(padding, y, content_width, height),
+            text,
+            size,
+            bold,
+            color: match kind {
+                AlertKind::AlertView => (1.0, 1.0, 1.0, 1.0),
+                AlertKind::ActionSheet => (0.8, 0.8, 0.8, 1.0),
+            },
+        });
+        y += height;
+    }
+    if !texts.is_empty() {
+        y += ALERT_PADDING;
+    }
+
+    let mut buttons = Vec::new();
+    if side_by_side {
+        let width = (content_width - BUTTON_GAP) / 2.0;
+        for (i, &index) in button_order.iter().enumerate() {
+            let x = padding + (width + BUTTON_GAP) * i as CGFloat;
+            buttons.push(ButtonBox {
+                index,
+                rect: rect(x, y, width, button_height),
+            });
+        }
+        y += button_height + BUTTON_GAP;
+    } else {
+        for &index in &button_order {
+            // Extra space separates the cancel button from the others.
+            if Some(index) == cancel_index && button_count > 1 {
+                y += BUTTON_GAP;
+            }
+            buttons.push(ButtonBox {
+                index,
+                rect: rect(padding, y, content_width, button_height),
+            });
+            y += button_height + BUTTON_GAP;
+        }
+    }
+    let panel_height = y - BUTTON_GAP + ALERT_PADDING;
+
+    let (panel_x, panel_y, dim_color) = match kind {
+        AlertKind::AlertView => (
+            (screen_width - panel_width) / 2.0,
+            ((screen_height - panel_height) / 2.0).max(0.0),
+            (0.0, 0.0, 0.0, 0.4),
+        ),
+        AlertKind::ActionSheet => (0.0, screen_height - panel_height, (0.0, 0.0, 0.0, 0.3)),
+    };
+    for text in &mut texts {
+        text.rect.origin.x += panel_x;
+        text.rect.origin.y += panel_y;
+    }
+    for button in &mut buttons {
+        button.rect.origin.x += panel_x;
+        button.rect.origin.y += panel_y;
+    }
+
+    Layout {
+        dim_color,
+        panel: rect(panel_x, panel_y, panel_width, panel_height),
+        texts,
+        buttons,
+    }
+}
+
+fn draw(env: &mut Environment, alert: id) -> Overlay {
+    let (points_width, points_height) = env.window.size_unrotated_unscaled();
+    let mut canvas = Canvas::new(env);
+
+    let layout = lay_out(env, alert);
+    let kind = kind_of(env, alert);
+    let state = state(env, alert, kind);
+    let cancel_index = state.cancel_button_index;
+    let destructive_index = state.destructive_button_index;
+    let button_titles = state.buttons.clone();
+    let pressed = env.framework_state.uikit.ui_alert_view.pressed_button;
+
+    let screen = rect(0.0, 0.0, points_width as CGFloat, points_height as CGFloat);
+    canvas.fill_rounded_rect(screen, 0.0, layout.dim_color);
+
+    match kind {
+        AlertKind::AlertView => {
+            let border = CGRect {
+                origin: CGPoint {
+                    x: layout.panel.origin.x - 2.0,
+                    y: layout.panel.origin.y - 2.0,
+                },
+                size: CGSize {
+                    width: layout.panel.size.width + 4.0,
+                    height: layout.panel.size.height + 4.0,
+                },
+            };
+            canvas.fill_rounded_rect(border, CORNER_RADIUS + 2.0, (0.9, 0.9, 0.95, 0.9));
+            canvas.fill_rounded_rect(layout.panel, CORNER_RADIUS, (0.09, 0.15, 0.36, 0.95));
+        }
+        AlertKind::ActionSheet => {
+            canvas.fill_rounded_rect(layout.panel, 0.0, (0.15, 0.15, 0.17, 0.92));
+        }
+    }
+
+    for text in &layout.texts {
+        let font = ui_font::system_font(env, text.bold, &text.text);
+        canvas.draw_text(font, text.size, &text.text, text.rect, text.color);
+    }
+
+    for button in &layout.buttons {
+        let index = button.index as NSInteger;
+        let is_pressed = pressed == Some(button.index);
+        let (fill, text_color) = match kind {
+            AlertKind::AlertView if is_pressed => ((1.0, 1.0, 1.0, 0.5), (1.0, 1.0, 1.0, 1.0)),
+            AlertKind::AlertView if index == cancel_index => {
+                ((1.0, 1.0, 1.0, 0.12), (1.0, 1.0, 1.0, 1.0))
+            }
+            AlertKind::AlertView => ((1.0, 1.0, 1.0, 0.25), (1.0, 1.0, 1.0, 1.0)),
+            AlertKind::ActionSheet if is_pressed => ((0.2, 0.4, 0.9, 1.0), (1.0, 1.0, 1.0, 1.0)),
+            AlertKind::ActionSheet if index == destructive_index => {
+                ((0.8, 0.1, 0.1, 1.0), (1.0, 1.0, 1.0, 1.0))
+            }
+            AlertKind::ActionSheet if index == cancel_index => {
+                ((0.3, 0.3, 0.33, 1.0), (1.0, 1.0, 1.0, 1.0))
+            }
+            AlertKind::ActionSheet => ((0.93, 0.93, 0.93, 1.0), (0.0, 0.0, 0.0, 1.0)),
+        };
+        canvas.fill_rounded_rect(button.rect, CORNER_RADIUS, fill);
+
+        let title = &button_titles[button.index];
+        let font = ui_font::system_font(env, true, title);
+        canvas.draw_text_centered(font, BUTTON_TITLE_SIZE, title, button.rect, text_color);
+    }
+
+    canvas.into_overlay()
+}
+
+/// For use by [super::overlay]: redraw the top alert or action sheet if
+/// needed.
+pub(super) fn update_overlay(env: &mut Environment) {
+    let Some(&top) = env.framework_state.uikit.ui_alert_view.visible.last() else {
+        return;
+    };
+    if env.framework_state.uikit.ui_alert_view.overlay.is_none() {
+        let overlay = draw(env, top);
+        env.framework_state.uikit.ui_alert_view.overlay = Some(overlay);
+    }
+}
+
+/// For use by [super::overlay]: get the drawing of the top alert or action
+/// sheet, if one is visible.
+pub(super) fn current_overlay(state: &State) -> Option<&Overlay> {
+    if state.visible.is_empty() {
+        None
+    } else {
+        state.overlay.as_ref()
+    }
+}
+
+fn button_at(env: &mut Environment, alert: id, location: (f32, f32)) -> Option<usize> {
+    lay_out(env, alert)
+        .buttons
+        .into_iter()
+        .find(|button| contains(button.rect, location))
+        .map(|button| button.index)
+}
+
+fn set_pressed_button(env: &mut Environment, pressed: Option<usize>) {
+    let state = &mut env.framework_state.uikit.ui_alert_view;
+    if state.pressed_button != pressed {
+        state.pressed_button = pressed;
+        state.overlay = None;
+    }
+}
+
+/// For use by [super::handle_events]: handle a touch event if an alert or
+/// action sheet is visible. Returns [true] if the event was consumed.
+pub(super) fn handle_event(env: &mut Environment, event: &Event) -> bool {
+    let Some(&top) = env.framework_state.uikit.ui_alert_view.visible.last() else {
+        return false;
+    };
+    let tracked_touch = env.framework_state.uikit.ui_alert_view.touch;
+    match *event {
+        Event::TouchDown(source, location) => {
+            if tracked_touch.is_some() && tracked_touch != Some(source) {
+                return true;
+            }
+            env.framework_state.uikit.ui_alert_view.touch = Some(source);
+            let pressed = button_at(env, top, location);
+            set_pressed_button(env, pressed);
+            true
+        }
+        Event::TouchMove(source, location) => {
+            if tracked_touch != Some(source) {
+                // A touch that began before the alert appeared still belongs
+                // to the app.
+                return false;
+            }
+            let pressed = button_at(env, top, location);
+            set_pressed_button(env, pressed);
+            true
+        }
+        Event::TouchUp(source, location) => {
+            if tracked_touch != Some(source) {
+                return false;
+            }
+            env.framework_state.uikit.ui_alert_view.touch = None;
+            set_pressed_button(env, None);
+            if let Some(index) = button_at(env, top, location) {
+                // UIKit creates and drains autorelease pools when handling
+                // events.
+                let pool: id = msg_class![env; NSAutoreleasePool new];
+                clicked(env, top, index);
+                release(env, pool);
+            }
+            true
+        }
+        _ => false,
+    }
+}
@@ -5,26 +5,114 @@
  */
 //! `UIApplication` and `UIApplicationMain`.
 
+use super::status_bar::{self, UIStatusBarStyle};
 use super::ui_device::*;
-use crate::dyld::{export_c_func, FunctionExports};
-use crate::frameworks::foundation::ns_string;
+use super::ui_event::{self, EventKind};
+use super::ui_view::UIViewHostObject;
+use super::{ui_responder, ui_touch, ui_view_controller, ui_window};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::core_graphics::CGRect;
+use crate::frameworks::foundation::{
+    ns_array, ns_cache, ns_notification_center, ns_string, ns_user_defaults, NSInteger,
+};
 use crate::frameworks::uikit::ui_nib::load_main_nib_file;
-use crate::mem::{MutPtr, MutVoidPtr};
-use crate::objc::{id, msg, msg_class, nil, objc_classes, retain, ClassExports, HostObject};
-use crate::window::DeviceOrientation;
+use crate::mem::{GuestUSize, MutPtr, MutVoidPtr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    HostObject, SEL,
+};
+use crate::window::{DeviceOrientation, Event, HardwareKey, TouchSource};
 use crate::Environment;
 
 #[derive(Default)]
 pub struct State {
     /// [UIApplication sharedApplication]
     shared_application: Option<id>,
+    /// Whether a memory warning has been sent since memory usage last went
+    /// above [MEMORY_WARNING_THRESHOLD].
+    memory_warning_sent: bool,
+    /// URLs that `openURL:` has already said it won't open.
+    suppressed_urls: Vec<String>,
+}
+
+/// What `openURL:` does with a URL, depending on its scheme.
+#[derive(Debug)]
+enum UrlAction {
+    /// Open it in the host's web browser and exit (`--open-external-links`).
+    OpenInHost,
+    /// Do nothing, since the user didn't ask for websites to be opened.
+    Suppress,
+    /// Do nothing, since there's nothing on the host that would make sense,
+    /// e.g. for `mailto:` or App Store URLs.
+    Unsupported,
+    /// Send it to the app's own delegate (`--handle-url-scheme=`).
+    HandleInApp,
+}
+
+fn url_action(env: &mut Environment, scheme: &str) -> UrlAction {
+    if env
+        .options
+        .handled_url_schemes
+        .iter()
+        .any(|handled| handled == scheme)
+    {
+        return UrlAction::HandleInApp;
+    }
+    match scheme {
+        "http" | "https" if env.options.open_external_links => UrlAction::OpenInHost,
+        "http" | "https" => UrlAction::Suppress,
+        _ => UrlAction::Unsupported,
+    }
+}
+
+/// Get an `NSURL*`'s string and lowercase scheme.
+fn url_and_scheme(env: &mut Environment, url: id) -> (String, String) {
+    let ns_string: id = msg![env; url absoluteString];
+    let url_string = ns_string::to_rust_string(env, ns_string).into_owned();
+    let scheme: id = msg![env; url scheme];
+    let scheme = if scheme == nil {
+        String::new()
+    } else {
+        ns_string::to_rust_string(env, scheme).to_ascii_lowercase()
+    };
+    (url_string, scheme)
 }
 
+/// The run loop mode used while tracking touches, e.g. while a scroll view is
+/// being dragged. It is one of the common modes.
+pub const UITrackingRunLoopMode: &str = "UITrackingRunLoopMode";
+
+pub const UIApplicationDidReceiveMemoryWarningNotification: &str =
+    "UIApplicationDidReceiveMemoryWarningNotification";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UITrackingRunLoopMode",
+        HostConstant::NSString(UITrackingRunLoopMode),
+    ),
+    (
+        "_UIApplicationDidReceiveMemoryWarningNotification",
+        HostConstant::NSString(UIApplicationDidReceiveMemoryWarningNotification),
+    ),
+];
+
+/// When the guest's heap grows beyond this size, the app is sent a memory
+/// warning. This is roughly what apps could use on devices with 128MiB of RAM
+/// before getting a warning.
+const MEMORY_WARNING_THRESHOLD: GuestUSize = 64 * 1024 * 1024;
+
 struct UIApplicationHostObject {
     delegate: id,
 }
 impl HostObject for UIApplicationHostObject {}
 
+/// Belongs to _touchHLE_AutoTapper
+struct AutoTapperHostObject {
+    x: f32,
+    y: f32,
+}
+impl HostObject for AutoTapperHostObject {}
+
 type UIInterfaceOrientation = UIDeviceOrientation;
 
 pub const CLASSES: ClassExports = objc_classes! {
@@ -68,23 +156,53 @@ pub const CLASSES: ClassExports = objc_classes! {
     host_object.delegate = delegate;
 }
 
-// TODO: statusBarHidden getter
-- (())setStatusBarHidden:(bool)_hidden {
-    // TODO: store this somewhere
+- (bool)isStatusBarHidden {
+    status_bar::is_hidden(env)
+}
+- (bool)statusBarHidden {
+    status_bar::is_hidden(env)
+}
+- (())setStatusBarHidden:(bool)hidden {
+    status_bar::set_hidden(env, hidden);
 }
 - (())setStatusBarHidden:(bool)hidden
                 animated:(bool)_animated {
     // TODO: animation
     msg![env; this setStatusBarHidden:hidden]
 }
+- (())setStatusBarHidden:(bool)hidden
+           withAnimation:(NSInteger)_animation { // UIStatusBarAnimation
+    // TODO: animation
+    msg![env; this setStatusBarHidden:hidden]
+}
 
-// TODO: statusBarOrientation getter
+- (UIStatusBarStyle)statusBarStyle {
+    status_bar::style(env)
+}
+- (())setStatusBarStyle:(UIStatusBarStyle)style {
+    status_bar::set_style(env, style);
+}
+- (())setStatusBarStyle:(UIStatusBarStyle)style
+               animated:(bool)_animated {
+    // TODO: animation
+    msg![env; this setStatusBarStyle:style]
+}
+
+- (CGRect)statusBarFrame {
+    status_bar::frame(env)
+}
+
+// The host window is rotated to match the status bar, since that's what
+// determines which way up the app's content is.
+- (UIInterfaceOrientation)statusBarOrientation {
+    orientation_from_window(env.window.device_orientation())
+}
 - (())setStatusBarOrientation:(UIInterfaceOrientation)orientation {
-    env.window.rotate_device(match orientation {
-        UIDeviceOrientationPortrait => DeviceOrientation::Portrait,
-        UIDeviceOrientationLandscapeLeft => DeviceOrientation::LandscapeLeft,
-        _ => unimplemented!("Orientation {} not handled yet", orientation),
-    });
+    if let Some(orientation) = window_orientation(orientation) {
+        env.window.rotate_device(orientation);
+    } else {
+        log!("Warning: [{:?} setStatusBarOrientation:{}] ignored, not an interface orientation", this, orientation);
+    }
 }
 - (())setStatusBarOrientation:(UIInterfaceOrientation)orientation
                      animated:(bool)_animated {
@@ -99,20 +217,171 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.window.set_screen_saver_enabled(!disabled);
 }
 
-- (bool)openURL:(id)url { // NSURL
-    let ns_string = msg![env; url absoluteURL];
-    let url_string = ns_string::to_rust_string(env, ns_string);
-    crate::window::open_url(&url_string);
+- (bool)canOpenURL:(id)url { // NSURL*
+    let (url_string, scheme) = url_and_scheme(env, url);
+    let action = url_action(env, &scheme);
+    log_dbg!("[{:?} canOpenURL:{:?}] => {:?}", this, url_string, action);
+    // The host's web browser would be a stand-in for Safari, so whether it
+    // should be used doesn't matter here.
+    matches!(action, UrlAction::OpenInHost | UrlAction::Suppress | UrlAction::HandleInApp)
+}
+
+- (bool)openURL:(id)url { // NSURL*
+    let (url_string, scheme) = url_and_scheme(env, url);
+    match url_action(env, &scheme) {
+        UrlAction::OpenInHost => {
+            crate::window::open_url(&url_string);
+
+            // iPhone OS doesn't really do multitasking, so the app expects to
+            // close when a URL is opened, e.g. Super Monkey Ball keeps opening
+            // the URL every frame! Super Monkey Ball also doesn't check whether
+            // opening failed, so it's probably best to always exit.
+            println!("App opened URL {:?}, exiting.", url_string);
+            exit(env);
+            true
+        }
+        UrlAction::Suppress => {
+            // Some apps keep trying, so only say this once.
+            let state = &mut env.framework_state.uikit.ui_application;
+            if !state.suppressed_urls.contains(&url_string) {
+                log!("App tried to open URL {:?}. Use the --open-external-links option to open links like this in your web browser.", url_string);
+                state.suppressed_urls.push(url_string);
+            }
+            false
+        }
+        UrlAction::Unsupported => {
+            log!("App tried to open URL {:?}, but {:?} URLs aren't supported.", url_string, scheme);
+            false
+        }
+        UrlAction::HandleInApp => {
+            log!("App opened URL {:?}, delivering it back to the app (--handle-url-scheme=).", url_string);
+            let delegate = env.objc.borrow::<UIApplicationHostObject>(this).delegate;
+            let selector = env.objc.lookup_selector("application:handleOpenURL:");
+            match selector {
+                Some(selector) if msg![env; delegate respondsToSelector:selector] => {
+                    msg![env; delegate application:this handleOpenURL:url]
+                }
+                _ => {
+                    log!("Warning: The app delegate doesn't implement application:handleOpenURL:.");
+                    false
+                }
+            }
+        }
+    }
+}
 
-    // iPhone OS doesn't really do multitasking, so the app expects to close
-    // when a URL is opened, e.g. Super Monkey Ball keeps opening the URL every
-    // frame! Super Monkey Ball also doesn't check whether opening failed, so
-    // it's probably best to always exit.
-    println!("App opened URL {:?}, exiting.", url_string);
-    exit(env);
+- (bool)sendAction:(SEL)action
+                to:(id)target
+              from:(id)sender
+          forEvent:(id)event { // UIEvent*
+    let target = if target != nil {
+        target
+    } else {
+        // A nil target means the first object in the responder chain that
+        // responds to the action.
+        // TODO: use nextResponder once the responder chain is implemented
+        let mut candidates = Vec::new();
+        candidates.extend(ui_responder::first_responder(env));
+        let ui_view_class = env.objc.get_known_class("UIView", &mut env.mem);
+        let mut view = sender;
+        while view != nil && msg![env; view isKindOfClass:ui_view_class] {
+            candidates.push(view);
+            view = env.objc.borrow::<UIViewHostObject>(view).superview;
+        }
+        candidates.push(this);
+        candidates.push(env.objc.borrow::<UIApplicationHostObject>(this).delegate);
+        let Some(target) = candidates
+            .into_iter()
+            .find(|&candidate| candidate != nil && msg![env; candidate respondsToSelector:action])
+        else {
+            log!(
+                "Warning: no target found for action {:?} from {:?}",
+                action.as_str(&env.mem),
+                sender
+            );
+            return false;
+        };
+        target
+    };
+    let () = msg_send(env, (target, action, sender, event));
     true
 }
 
+- (id)keyWindow {
+    ui_window::key_window(env).unwrap_or(nil)
+}
+- (id)windows { // NSArray* of UIWindow*
+    let ui_window_class = env.objc.get_known_class("UIWindow", &mut env.mem);
+    let views = env.framework_state.uikit.ui_view.views.clone();
+    let windows: Vec<id> = views
+        .into_iter()
+        .filter(|&view| msg![env; view isKindOfClass:ui_window_class])
+        .collect();
+    for &window in &windows {
+        retain(env, window);
+    }
+    let windows = ns_array::from_vec(env, windows);
+    autorelease(env, windows)
+}
+
+- (())sendEvent:(id)event { // UIEvent*
+    match ui_event::kind(env, event) {
+        EventKind::Touches => {
+            let mut windows = Vec::new();
+            for touch in ui_touch::changed_touches(env, event) {
+                let window: id = msg![env; touch window];
+                if window == nil {
+                    // The view isn't in a window, but it should still get
+                    // the touch.
+                    ui_touch::deliver_touch(env, touch, event);
+                } else if !windows.contains(&window) {
+                    windows.push(window);
+                }
+            }
+            for window in windows {
+                () = msg![env; window sendEvent:event];
+            }
+        }
+        EventKind::Motion { ended } => {
+            if let Some(window) = ui_window::key_window(env) {
+                () = msg![env; window sendEvent:event];
+            } else if let Some(responder) = ui_responder::first_responder(env) {
+                ui_window::send_motion(env, responder, ended, event);
+            }
+        }
+        EventKind::Key { .. } => {
+            // There's no public API for key events. Apps that support
+            // external keyboards override this method and look at the
+            // GSEvent themselves.
+            log_dbg!("Key event {:?} not handled by app", event);
+        }
+    }
+}
+
+// Private method used for `--memory-warning=`, the target of an NSTimer.
+- (())_touchHLE_simulateMemoryWarning:(id)_timer { // NSTimer*
+    println!("Simulating a low memory warning.");
+    send_memory_warning(env, this);
+}
+
+@end
+
+// Private class used for `--auto-tap=`. Each instance is the target of an
+// NSTimer which fires at the time the tap should happen.
+@implementation _touchHLE_AutoTapper: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(AutoTapperHostObject { x: 0.0, y: 0.0 });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())tap:(id)_timer { // NSTimer*
+    let &AutoTapperHostObject { x, y } = env.objc.borrow(this);
+    println!("Auto-tapping at ({}, {}).", x, y);
+    env.window.push_event(Event::TouchDown(TouchSource::Mouse, (x, y)));
+    env.window.push_event(Event::TouchUp(TouchSource::Mouse, (x, y)));
+}
+
 @end
 
 };
@@ -135,6 +404,9 @@ pub(super) fn UIApplicationMain(
     // It's not clear what granularity this should happen with, but this
     // granularity has already caught several bugs. :)
 
+    set_initial_orientation_from_info_plist(env);
+    status_bar::set_initial_state_from_info_plist(env);
+
     let (ui_application, delegate) = {
         let pool: id = msg_class![env; NSAutoreleasePool new];
 
@@ -142,6 +414,10 @@ pub(super) fn UIApplicationMain(
 
         load_main_nib_file(env, ui_application);
 
+        if let Some(class_name) = env.options.delegate_class.clone() {
+            substitute_delegate(env, ui_application, &class_name);
+        }
+
         // The delegate must have been created by this point.
         // While notionally UIApplication does not retain its delegate (see
         // `setDelegate:` above), we do have to retain this first one.
@@ -160,6 +436,13 @@ pub(super) fn UIApplicationMain(
         let _: () = msg![env; pool drain];
     }
 
+    if !env.options.auto_taps.is_empty() || !env.options.memory_warnings.is_empty() {
+        let pool: id = msg_class![env; NSAutoreleasePool new];
+        schedule_auto_taps(env);
+        schedule_memory_warnings(env, ui_application);
+        let _: () = msg![env; pool drain];
+    }
+
     // FIXME: There are more messages we should send.
     // TODO: Send UIApplicationDidFinishLaunchingNotification?
 
@@ -172,7 +455,172 @@ pub(super) fn UIApplicationMain(
     let _: () = msg![env; run_loop run];
 }
 
-/// Tell the app it's about to quit and then exit.
+/// Apps can ask for the status bar to be in a particular orientation at launch
+/// with the `UIInterfaceOrientation` key in `Info.plist`.
+fn set_initial_orientation_from_info_plist(env: &mut Environment) {
+    let Some(name) = env
+        .bundle
+        .info_plist()
+        .get("UIInterfaceOrientation")
+        .and_then(|value| value.as_string())
+    else {
+        return;
+    };
+    // The interface orientation names are the other way round from the
+    // device orientation names.
+    let orientation = match name {
+        "UIInterfaceOrientationPortrait" => DeviceOrientation::Portrait,
+        "UIInterfaceOrientationPortraitUpsideDown" => DeviceOrientation::PortraitUpsideDown,
+        "UIInterfaceOrientationLandscapeLeft" => DeviceOrientation::LandscapeRight,
+        "UIInterfaceOrientationLandscapeRight" => DeviceOrientation::LandscapeLeft,
+        _ => {
+            log!(
+                "Warning: unknown UIInterfaceOrientation {:?} in Info.plist",
+                name
+            );
+            return;
+        }
+    };
+    log_dbg!("Initial orientation from Info.plist: {:?}", orientation);
+    set_initial_orientation(env, orientation);
+    env.window.rotate_device(orientation);
+}
+
+/// Replace the delegate loaded from the main nib file with a new instance of
+/// some other class (`--delegate-class=`).
+///
+/// Messages aren't forwarded to the original delegate, because there's no
+/// message forwarding in touchHLE's Objective-C runtime, so the new class must
+/// be a complete delegate by itself.
+fn substitute_delegate(env: &mut Environment, ui_application: id, class_name: &str) {
+    // Only the app's classes are wanted, and a class the user has typed the
+    // name of might not exist.
+    let Some(class) = env.objc.lookup_class(class_name, &env.mem) else {
+        log!(
+            "Error: --delegate-class={}: the app has no class with that name. Keeping the original delegate.",
+            class_name
+        );
+        return;
+    };
+    let new_delegate: id = msg![env; class alloc];
+    let new_delegate: id = msg![env; new_delegate init];
+    // This will be retained by UIApplicationMain, like the original delegate.
+    autorelease(env, new_delegate);
+
+    let host_object = env
+        .objc
+        .borrow_mut::<UIApplicationHostObject>(ui_application);
+    log!(
+        "Replacing app delegate {:?} with new instance {:?} of class {}",
+        host_object.delegate,
+        new_delegate,
+        class_name
+    );
+    host_object.delegate = new_delegate;
+}
+
+/// Set up timers for the `--auto-tap=` option.
+fn schedule_auto_taps(env: &mut Environment) {
+    let selector: SEL = env.objc.lookup_selector("tap:").unwrap();
+    for (x, y, delay) in env.options.auto_taps.clone() {
+        let tapper: id = msg_class![env; _touchHLE_AutoTapper alloc];
+        *env.objc.borrow_mut(tapper) = AutoTapperHostObject { x, y };
+        // The timer retains its target.
+        let _: id = msg_class![env; NSTimer scheduledTimerWithTimeInterval:delay
+                                                                    target:tapper
+                                                                  selector:selector
+                                                                  userInfo:nil
+                                                                   repeats:false];
+        release(env, tapper);
+    }
+}
+
+/// Set up timers for the `--memory-warning=` option.
+fn schedule_memory_warnings(env: &mut Environment, ui_application: id) {
+    let selector: SEL = env
+        .objc
+        .lookup_selector("_touchHLE_simulateMemoryWarning:")
+        .unwrap();
+    for delay in env.options.memory_warnings.clone() {
+        let _: id = msg_class![env; NSTimer scheduledTimerWithTimeInterval:delay
+                                                                    target:ui_application
+                                                                  selector:selector
+                                                                  userInfo:nil
+                                                                   repeats:false];
+    }
+}
+
+/// Free up memory where possible and tell the app that memory is low.
+fn send_memory_warning(env: &mut Environment, ui_application: id) {
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+
+    ns_cache::handle_memory_warning(env);
+
+    let delegate: id = msg![env; ui_application delegate];
+    if let Some(selector) = env
+        .objc
+        .lookup_selector("applicationDidReceiveMemoryWarning:")
+    {
+        if msg![env; delegate respondsToSelector:selector] {
+            () = msg![env; delegate applicationDidReceiveMemoryWarning:ui_application];
+        }
+    }
+    ui_view_controller::handle_memory_warning(env);
+    let name = ns_string::get_static_str(env, UIApplicationDidReceiveMemoryWarningNotification);
+    ns_notification_center::post(env, name, ui_application, nil);
+
+    let _: () = msg![env; pool drain];
+}
+
+/// For use by [super::handle_events]: shake the device, which sends a shake
+/// motion event that begins and ends immediately.
+pub(super) fn handle_shake(env: &mut Environment) {
+    log_dbg!("Shaking device");
+    let app: id = msg_class![env; UIApplication sharedApplication];
+    for ended in [false, true] {
+        let pool: id = msg_class![env; NSAutoreleasePool new];
+        let event = ui_event::new_motion_event(env, ended);
+        () = msg![env; app sendEvent:event];
+        release(env, pool);
+    }
+}
+
+/// For use by [super::handle_events]: send a key event for a key on the
+/// host's keyboard.
+pub(super) fn handle_hardware_key(env: &mut Environment, key: HardwareKey) {
+    log_dbg!("Hardware key: {:?}", key);
+    let app: id = msg_class![env; UIApplication sharedApplication];
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let event = ui_event::new_key_event(env, key);
+    () = msg![env; app sendEvent:event];
+    release(env, pool);
+}
+
+/// For use by `NSRunLoop` via [super::handle_events]: send a memory warning if
+/// the guest is using a lot of memory.
+pub(super) fn check_memory_pressure(env: &mut Environment) {
+    let Some(ui_application) = env.framework_state.uikit.ui_application.shared_application else {
+        return;
+    };
+    let bytes_allocated = env.mem.bytes_allocated();
+    let state = &mut env.framework_state.uikit.ui_application;
+    if bytes_allocated < MEMORY_WARNING_THRESHOLD {
+        state.memory_warning_sent = false;
+        return;
+    }
+    if state.memory_warning_sent {
+        return;
+    }
+    state.memory_warning_sent = true;
+    log!(
+        "Guest heap usage is {:#x} bytes, sending a memory warning.",
+        bytes_allocated
+    );
+    send_memory_warning(env, ui_application);
+}
+
+/// Tell the app it's about to quit and then stop running it (see
+/// [Environment::exit]).
 pub(super) fn exit(env: &mut Environment) {
     let ui_application: id = msg_class![env; UIApplication sharedApplication];
     let delegate: id = msg![env; ui_application delegate];
@@ -185,7 +633,10 @@ pub(super) fn exit(env: &mut Environment) {
         let _: () = msg![env; pool drain];
     }
 
-    std::process::exit(0);
+    // Apple's implementation also saves preferences periodically.
+    ns_user_defaults::synchronize(env);
+
+    env.exit(0);
 }
 
 pub const FUNCTIONS: FunctionExports = &[export_c_func!(UIApplicationMain(_, _, _, _))];
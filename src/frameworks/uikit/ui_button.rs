@@ -0,0 +1,426 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIButton`.
+
+use super::overlay::{rect, Canvas, Color};
+use super::ui_control::{
+    self, ControlKind, UIControlState, UIControlStateDisabled, UIControlStateHighlighted,
+    UIControlStateNormal,
+};
+use super::{ui_color, ui_font, ui_image};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::{ns_string, NSInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports,
+};
+use crate::Environment;
+use std::collections::HashMap;
+
+pub type UIButtonType = NSInteger;
+pub const UIButtonTypeCustom: UIButtonType = 0;
+pub const UIButtonTypeRoundedRect: UIButtonType = 1;
+pub const UIButtonTypeDetailDisclosure: UIButtonType = 2;
+pub const UIButtonTypeInfoLight: UIButtonType = 3;
+pub const UIButtonTypeInfoDark: UIButtonType = 4;
+pub const UIButtonTypeContactAdd: UIButtonType = 5;
+
+const DEFAULT_FONT_SIZE: CGFloat = 15.0;
+const CORNER_RADIUS: CGFloat = 9.0;
+
+pub(super) struct ButtonState {
+    button_type: UIButtonType,
+    /// Per-state content. If there's nothing for the current state, the
+    /// content for [UIControlStateNormal] is used.
+    titles: HashMap<UIControlState, String>,
+    /// Strong references to `UIColor*`s.
+    title_colors: HashMap<UIControlState, id>,
+    /// Strong references to `UIImage*`s.
+    images: HashMap<UIControlState, id>,
+    /// Strong references to `UIImage*`s.
+    background_images: HashMap<UIControlState, id>,
+    /// Strong reference to a `UIFont*`, or `nil` for the default font.
+    font: id,
+    adjusts_image_when_highlighted: bool,
+    adjusts_image_when_disabled: bool,
+    /// TODO: This is stored but the glow isn't drawn yet.
+    shows_touch_when_highlighted: bool,
+}
+impl Default for ButtonState {
+    fn default() -> Self {
+        ButtonState {
+            button_type: UIButtonTypeCustom,
+            titles: HashMap::new(),
+            title_colors: HashMap::new(),
+            images: HashMap::new(),
+            background_images: HashMap::new(),
+            font: nil,
+            adjusts_image_when_highlighted: true,
+            adjusts_image_when_disabled: true,
+            shows_touch_when_highlighted: false,
+        }
+    }
+}
+
+fn state(env: &mut Environment, button: id) -> &mut ButtonState {
+    let ControlKind::Button(ref mut state) = ui_control::state(env, button).kind else {
+        panic!("{:?} is not a UIButton", button);
+    };
+    state
+}
+
+/// For use by `UIControl`'s part of `UIView`'s `dealloc`.
+pub(super) fn release_state(env: &mut Environment, state: ButtonState) {
+    for object in state
+        .title_colors
+        .into_values()
+        .chain(state.images.into_values())
+        .chain(state.background_images.into_values())
+    {
+        release(env, object);
+    }
+    if state.font != nil {
+        release(env, state.font);
+    }
+}
+
+/// Look up the value for a control state, falling back to the normal state.
+fn for_state<T: Clone>(
+    map: &HashMap<UIControlState, T>,
+    control_state: UIControlState,
+) -> Option<T> {
+    map.get(&control_state)
+        .or_else(|| map.get(&UIControlStateNormal))
+        .cloned()
+}
+
+/// Replace an object in one of the per-state maps, with retain/release.
+fn set_object_for_state(
+    env: &mut Environment,
+    button: id,
+    map: fn(&mut ButtonState) -> &mut HashMap<UIControlState, id>,
+    object: id,
+    control_state: UIControlState,
+) {
+    if object != nil {
+        retain(env, object);
+    }
+    let old = if object == nil {
+        map(state(env, button)).remove(&control_state)
+    } else {
+        map(state(env, button)).insert(control_state, object)
+    };
+    if let Some(old) = old {
+        release(env, old);
+    }
+    ui_control::changed(env);
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIButton: UIControl
+
++ (id)buttonWithType:(UIButtonType)button_type {
+    let size = match button_type {
+        UIButtonTypeDetailDisclosure
+        | UIButtonTypeInfoLight
+        | UIButtonTypeInfoDark
+        | UIButtonTypeContactAdd => 29.0,
+        _ => 0.0,
+    };
+    let frame = rect(0.0, 0.0, size, size);
+    let button: id = msg![env; this alloc];
+    let button: id = msg![env; button initWithFrame:frame];
+    state(env, button).button_type = button_type;
+    if button_type == UIButtonTypeInfoLight || button_type == UIButtonTypeInfoDark {
+        state(env, button).shows_touch_when_highlighted = true;
+    }
+    autorelease(env, button)
+}
+
+- (UIButtonType)buttonType {
+    state(env, this).button_type
+}
+
+- (())setTitle:(id)title // NSString*
+      forState:(UIControlState)control_state {
+    if title == nil {
+        state(env, this).titles.remove(&control_state);
+    } else {
+        let title = ns_string::to_rust_string(env, title).into_owned();
+        state(env, this).titles.insert(control_state, title);
+    }
+    ui_control::changed(env);
+}
+- (id)titleForState:(UIControlState)control_state {
+    match for_state(&state(env, this).titles, control_state) {
+        Some(title) => {
+            let title = ns_string::from_rust_string(env, title);
+            autorelease(env, title)
+        }
+        None => nil,
+    }
+}
+- (id)currentTitle {
+    let control_state = ui_control::control_state(env, this);
+    msg![env; this titleForState:control_state]
+}
+
+- (())setTitleColor:(id)color // UIColor*
+           forState:(UIControlState)control_state {
+    set_object_for_state(env, this, |state| &mut state.title_colors, color, control_state);
+}
+- (id)titleColorForState:(UIControlState)control_state {
+    for_state(&state(env, this).title_colors, control_state).unwrap_or(nil)
+}
+- (id)currentTitleColor {
+    let control_state = ui_control::control_state(env, this);
+    msg![env; this titleColorForState:control_state]
+}
+- (())setTitleShadowColor:(id)_color // UIColor*
+                 forState:(UIControlState)_control_state {
+    // TODO: draw title shadows
+}
+
+- (())setImage:(id)image // UIImage*
+      forState:(UIControlState)control_state {
+    set_object_for_state(env, this, |state| &mut state.images, image, control_state);
+}
+- (id)imageForState:(UIControlState)control_state {
+    for_state(&state(env, this).images, control_state).unwrap_or(nil)
+}
+- (id)currentImage {
+    let control_state = ui_control::control_state(env, this);
+    msg![env; this imageForState:control_state]
+}
+
+- (())setBackgroundImage:(id)image // UIImage*
+                forState:(UIControlState)control_state {
+    set_object_for_state(env, this, |state| &mut state.background_images, image, control_state);
+}
+- (id)backgroundImageForState:(UIControlState)control_state {
+    for_state(&state(env, this).background_images, control_state).unwrap_or(nil)
+}
+- (id)currentBackgroundImage {
+    let control_state = ui_control::control_state(env, this);
+    msg![env; this backgroundImageForState:control_state]
+}
+
+// Deprecated in iPhone OS 3.0 in favor of titleLabel.font.
+- (id)font {
+    let font = state(env, this).font;
+    if font == nil {
+        msg_class![env; UIFont boldSystemFontOfSize:DEFAULT_FONT_SIZE]
+    } else {
+        font
+    }
+}
+- (())setFont:(id)font { // UIFont*
+    if font != nil {
+        retain(env, font);
+    }
+    let old = std::mem::replace(&mut state(env, this).font, font);
+    if old != nil {
+        release(env, old);
+    }
+    ui_control::changed(env);
+}
+- (id)titleLabel {
+    // TODO: UILabel
+    nil
+}
+
+- (bool)adjustsImageWhenHighlighted {
+    state(env, this).adjusts_image_when_highlighted
+}
+- (())setAdjustsImageWhenHighlighted:(bool)adjusts {
+    state(env, this).adjusts_image_when_highlighted = adjusts;
+}
+- (bool)adjustsImageWhenDisabled {
+    state(env, this).adjusts_image_when_disabled
+}
+- (())setAdjustsImageWhenDisabled:(bool)adjusts {
+    state(env, this).adjusts_image_when_disabled = adjusts;
+}
+- (bool)showsTouchWhenHighlighted {
+    state(env, this).shows_touch_when_highlighted
+}
+- (())setShowsTouchWhenHighlighted:(bool)shows {
+    state(env, this).shows_touch_when_highlighted = shows;
+}
+
+@end
+
+};
+
+/// For use by `UIControl`: draw a button in the controls overlay.
+pub(super) fn draw(env: &mut Environment, canvas: &mut Canvas, button: id, frame: CGRect) {
+    let control_state = ui_control::control_state(env, button);
+    let highlighted = control_state & UIControlStateHighlighted != 0;
+    let disabled = control_state & UIControlStateDisabled != 0;
+    let alignment = ui_control::state(env, button).horizontal_alignment;
+    let state = state(env, button);
+    let button_type = state.button_type;
+    let title = for_state(&state.titles, control_state);
+    let title_color = for_state(&state.title_colors, control_state);
+    let image = for_state(&state.images, control_state);
+    let background_image = for_state(&state.background_images, control_state);
+    let has_highlighted_image = state.images.contains_key(&UIControlStateHighlighted)
+        || state
+            .background_images
+            .contains_key(&UIControlStateHighlighted);
+    let brightness =
+        if highlighted && state.adjusts_image_when_highlighted && !has_highlighted_image {
+            0.6
+        } else if disabled && state.adjusts_image_when_disabled {
+            0.75
+        } else {
+            1.0
+        };
+    let font = state.font;
+
+    // The button's own appearance.
+    match button_type {
+        UIButtonTypeRoundedRect => {
+            canvas.fill_rounded_rect(frame, CORNER_RADIUS, (0.55, 0.58, 0.65, 1.0));
+            let inner = rect(
+                frame.origin.x + 1.0,
+                frame.origin.y + 1.0,
+                frame.size.width - 2.0,
+                frame.size.height - 2.0,
+            );
+            let fill = if highlighted {
+                (0.1, 0.4, 0.9, 1.0)
+            } else {
+                (1.0, 1.0, 1.0, 1.0)
+            };
+            canvas.fill_rounded_rect(inner, CORNER_RADIUS - 1.0, fill);
+        }
+        UIButtonTypeDetailDisclosure
+        | UIButtonTypeInfoLight
+        | UIButtonTypeInfoDark
+        | UIButtonTypeContactAdd => {
+            draw_glyph_button(env, canvas, button_type, frame, highlighted);
+        }
+        _ => (),
+    }
+
+    if let Some(background_image) = background_image {
+        let image = ui_image::borrow_image(&env.objc, background_image);
+        canvas.draw_image_with_brightness(image, frame, brightness);
+    }
+
+    // Lay out the image and title side by side, like UIKit does.
+    let (font_size, bold, italic) = if font == nil {
+        (DEFAULT_FONT_SIZE, true, false)
+    } else {
+        ui_font::font_style(env, font)
+    };
+    let title_size = match title {
+        Some(ref title) => {
+            let font = ui_font::styled_system_font(env, bold, italic, title);
+            let (width, height) = font.calculate_text_size(font_size, title, None);
+            CGSize { width, height }
+        }
+        None => CGSize {
+            width: 0.0,
+            height: 0.0,
+        },
+    };
+    let image_size = match image {
+        Some(image) => {
+            let (width, height) = ui_image::borrow_image(&env.objc, image).dimensions();
+            CGSize {
+                width: width as CGFloat,
+                height: height as CGFloat,
+            }
+        }
+        None => CGSize {
+            width: 0.0,
+            height: 0.0,
+        },
+    };
+    let content = ui_control::place_content(
+        frame,
+        (
+            image_size.width + title_size.width,
+            image_size.height.max(title_size.height),
+        ),
+        alignment,
+    );
+    if let Some(image) = image {
+        let scale = content.size.height / image_size.height.max(title_size.height).max(1.0);
+        let image_rect = rect(
+            content.origin.x,
+            content.origin.y + (content.size.height - image_size.height * scale) / 2.0,
+            image_size.width * scale,
+            image_size.height * scale,
+        );
+        let image = ui_image::borrow_image(&env.objc, image);
+        canvas.draw_image_with_brightness(image, image_rect, brightness);
+    }
+    if let Some(title) = title {
+        let color = match title_color {
+            Some(color) => ui_color::get_rgba(env, color),
+            None if button_type == UIButtonTypeRoundedRect && highlighted => (1.0, 1.0, 1.0, 1.0),
+            None if button_type == UIButtonTypeRoundedRect => (0.2, 0.31, 0.52, 1.0),
+            None => (1.0, 1.0, 1.0, 1.0),
+        };
+        let color: Color = if disabled {
+            (color.0, color.1, color.2, color.3 * 0.5)
+        } else {
+            color
+        };
+        let origin = CGPoint {
+            x: content.origin.x + content.size.width - title_size.width,
+            y: frame.origin.y + (frame.size.height - title_size.height) / 2.0,
+        };
+        let font = ui_font::styled_system_font(env, bold, italic, &title);
+        canvas.draw_text_at(font, font_size, &title, origin, color);
+    }
+}
+
+/// Draw the round glyph of the info, detail disclosure and contact add button
+/// types.
+fn draw_glyph_button(
+    env: &mut Environment,
+    canvas: &mut Canvas,
+    button_type: UIButtonType,
+    frame: CGRect,
+    highlighted: bool,
+) {
+    let (diameter, glyph, fill, text_color): (CGFloat, &str, Color, Color) = match button_type {
+        UIButtonTypeDetailDisclosure => (29.0, "›", (0.15, 0.45, 0.9, 1.0), (1.0, 1.0, 1.0, 1.0)),
+        UIButtonTypeInfoLight => (18.0, "i", (1.0, 1.0, 1.0, 1.0), (0.3, 0.3, 0.3, 1.0)),
+        UIButtonTypeInfoDark => (18.0, "i", (0.3, 0.3, 0.3, 1.0), (1.0, 1.0, 1.0, 1.0)),
+        _ => (29.0, "+", (0.15, 0.45, 0.9, 1.0), (1.0, 1.0, 1.0, 1.0)),
+    };
+    let circle = rect(
+        frame.origin.x + (frame.size.width - diameter) / 2.0,
+        frame.origin.y + (frame.size.height - diameter) / 2.0,
+        diameter,
+        diameter,
+    );
+    if button_type != UIButtonTypeInfoLight && button_type != UIButtonTypeInfoDark {
+        // These two have a white ring.
+        let ring = rect(
+            circle.origin.x - 2.0,
+            circle.origin.y - 2.0,
+            diameter + 4.0,
+            diameter + 4.0,
+        );
+        canvas.fill_rounded_rect(ring, diameter / 2.0 + 2.0, (1.0, 1.0, 1.0, 1.0));
+    }
+    let fill = if highlighted {
+        (fill.0 * 0.6, fill.1 * 0.6, fill.2 * 0.6, fill.3)
+    } else {
+        fill
+    };
+    canvas.fill_rounded_rect(circle, diameter / 2.0, fill);
+    let font = ui_font::system_font(env, true, glyph);
+    canvas.draw_text_centered(font, diameter * 0.7, glyph, circle, text_color);
+}
@@ -0,0 +1,166 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIColor`.
+
+use super::ui_graphics::UIGraphicsGetCurrentContext;
+use crate::frameworks::core_graphics::cg_color::{self, CGColorRef, CGColorRelease};
+use crate::frameworks::core_graphics::cg_context::CGContextSetRGBFillColor;
+use crate::frameworks::core_graphics::CGFloat;
+use crate::mem::{MutPtr, MutVoidPtr};
+use crate::objc::{autorelease, id, msg, msg_class, nil, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+struct UIColorHostObject {
+    rgba: (CGFloat, CGFloat, CGFloat, CGFloat),
+    /// Created the first time it's requested.
+    cg_color: CGColorRef,
+}
+impl HostObject for UIColorHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIColor: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(UIColorHostObject {
+        rgba: (0.0, 0.0, 0.0, 0.0),
+        cg_color: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)colorWithRed:(CGFloat)red
+             green:(CGFloat)green
+              blue:(CGFloat)blue
+             alpha:(CGFloat)alpha {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithRed:red green:green blue:blue alpha:alpha];
+    autorelease(env, new)
+}
++ (id)colorWithWhite:(CGFloat)white
+               alpha:(CGFloat)alpha {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithWhite:white alpha:alpha];
+    autorelease(env, new)
+}
+
++ (id)colorWithCGColor:(CGColorRef)cg_color {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCGColor:cg_color];
+    autorelease(env, new)
+}
+
++ (id)blackColor {
+    msg![env; this colorWithWhite:0.0f32 alpha:1.0f32]
+}
++ (id)darkGrayColor {
+    msg![env; this colorWithWhite:(1.0f32 / 3.0) alpha:1.0f32]
+}
++ (id)grayColor {
+    msg![env; this colorWithWhite:0.5f32 alpha:1.0f32]
+}
++ (id)lightGrayColor {
+    msg![env; this colorWithWhite:(2.0f32 / 3.0) alpha:1.0f32]
+}
++ (id)whiteColor {
+    msg![env; this colorWithWhite:1.0f32 alpha:1.0f32]
+}
++ (id)clearColor {
+    msg![env; this colorWithWhite:0.0f32 alpha:0.0f32]
+}
++ (id)redColor {
+    msg![env; this colorWithRed:1.0f32 green:0.0f32 blue:0.0f32 alpha:1.0f32]
+}
++ (id)greenColor {
+    msg![env; this colorWithRed:0.0f32 green:1.0f32 blue:0.0f32 alpha:1.0f32]
+}
++ (id)blueColor {
+    msg![env; this colorWithRed:0.0f32 green:0.0f32 blue:1.0f32 alpha:1.0f32]
+}
++ (id)yellowColor {
+    msg![env; this colorWithRed:1.0f32 green:1.0f32 blue:0.0f32 alpha:1.0f32]
+}
+
+- (id)initWithRed:(CGFloat)red
+            green:(CGFloat)green
+             blue:(CGFloat)blue
+            alpha:(CGFloat)alpha {
+    let rgba = (
+        red.clamp(0.0, 1.0),
+        green.clamp(0.0, 1.0),
+        blue.clamp(0.0, 1.0),
+        alpha.clamp(0.0, 1.0),
+    );
+    env.objc.borrow_mut::<UIColorHostObject>(this).rgba = rgba;
+    this
+}
+- (id)initWithWhite:(CGFloat)white
+              alpha:(CGFloat)alpha {
+    msg![env; this initWithRed:white green:white blue:white alpha:alpha]
+}
+
+- (id)initWithCGColor:(CGColorRef)cg_color {
+    let (r, g, b, a) = cg_color::get_rgba(env, cg_color);
+    msg![env; this initWithRed:r green:g blue:b alpha:a]
+}
+
+- (())dealloc {
+    let cg_color = env.objc.borrow::<UIColorHostObject>(this).cg_color;
+    CGColorRelease(env, cg_color);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (CGColorRef)CGColor {
+    let &UIColorHostObject { rgba, cg_color } = env.objc.borrow(this);
+    if cg_color != nil {
+        return cg_color;
+    }
+    let cg_color = cg_color::from_rgba(env, rgba);
+    env.objc.borrow_mut::<UIColorHostObject>(this).cg_color = cg_color;
+    cg_color
+}
+
+- (bool)getRed:(MutPtr<CGFloat>)red
+         green:(MutPtr<CGFloat>)green
+          blue:(MutPtr<CGFloat>)blue
+         alpha:(MutPtr<CGFloat>)alpha {
+    let (r, g, b, a) = get_rgba(env, this);
+    for (ptr, value) in [(red, r), (green, g), (blue, b), (alpha, a)] {
+        if !ptr.is_null() {
+            env.mem.write(ptr, value);
+        }
+    }
+    true
+}
+
+- (id)colorWithAlphaComponent:(CGFloat)alpha {
+    let (r, g, b, _) = get_rgba(env, this);
+    msg_class![env; UIColor colorWithRed:r green:g blue:b alpha:alpha]
+}
+
+- (())set {
+    msg![env; this setFill]
+}
+- (())setFill {
+    let context = UIGraphicsGetCurrentContext(env);
+    if context == nil {
+        return;
+    }
+    let (r, g, b, a) = get_rgba(env, this);
+    CGContextSetRGBFillColor(env, context, r, g, b, a);
+}
+
+@end
+
+};
+
+/// Shortcut for host code, gets the red, green, blue and alpha components of
+/// a `UIColor*`.
+pub fn get_rgba(env: &mut Environment, color: id) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+    env.objc.borrow::<UIColorHostObject>(color).rgba
+}
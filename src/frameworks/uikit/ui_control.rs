@@ -0,0 +1,614 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIControl`, the target-action machinery shared by all controls, and the
+//! drawing of the standard controls.
+//!
+//! Touches on a control are delivered to it as `touchesBegan:withEvent:` etc,
+//! like in UIKit, and `UIControl`'s implementations of those methods track the
+//! touch and send the control events.
+//!
+//! The standard controls (`UIButton`, `UISlider`, `UISwitch` and
+//! `UISegmentedControl`) don't draw into their layers yet, so they're drawn by
+//! the host as an overlay (see [super::overlay]) on top of the app's content.
+
+use super::overlay::{contains, rect, Canvas, Overlay};
+use super::ui_view::{frame_on_screen, UIViewHostObject};
+use super::{ui_button, ui_segmented_control, ui_slider, ui_switch};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::foundation::{ns_array, ns_string, NSInteger, NSUInteger};
+use crate::objc::{autorelease, id, msg, msg_class, nil, objc_classes, ClassExports, SEL};
+use crate::Environment;
+
+pub type UIControlEvents = NSUInteger;
+pub const UIControlEventTouchDown: UIControlEvents = 1 << 0;
+#[allow(dead_code)]
+pub const UIControlEventTouchDownRepeat: UIControlEvents = 1 << 1;
+pub const UIControlEventTouchDragInside: UIControlEvents = 1 << 2;
+pub const UIControlEventTouchDragOutside: UIControlEvents = 1 << 3;
+pub const UIControlEventTouchDragEnter: UIControlEvents = 1 << 4;
+pub const UIControlEventTouchDragExit: UIControlEvents = 1 << 5;
+pub const UIControlEventTouchUpInside: UIControlEvents = 1 << 6;
+pub const UIControlEventTouchUpOutside: UIControlEvents = 1 << 7;
+pub const UIControlEventTouchCancel: UIControlEvents = 1 << 8;
+pub const UIControlEventValueChanged: UIControlEvents = 1 << 12;
+
+pub type UIControlState = NSUInteger;
+pub const UIControlStateNormal: UIControlState = 0;
+pub const UIControlStateHighlighted: UIControlState = 1 << 0;
+pub const UIControlStateDisabled: UIControlState = 1 << 1;
+pub const UIControlStateSelected: UIControlState = 1 << 2;
+
+pub type UIControlContentHorizontalAlignment = NSInteger;
+pub const UIControlContentHorizontalAlignmentCenter: UIControlContentHorizontalAlignment = 0;
+pub const UIControlContentHorizontalAlignmentLeft: UIControlContentHorizontalAlignment = 1;
+pub const UIControlContentHorizontalAlignmentRight: UIControlContentHorizontalAlignment = 2;
+
+pub type UIControlContentVerticalAlignment = NSInteger;
+pub const UIControlContentVerticalAlignmentCenter: UIControlContentVerticalAlignment = 0;
+
+/// How far a touch can move outside a control and still count as inside it.
+const TOUCH_SLOP: CGFloat = 70.0;
+
+#[derive(Default)]
+pub struct State {
+    /// Incremented whenever the appearance of a control changes.
+    generation: u64,
+    overlay: Option<DrawnControls>,
+}
+
+/// Cached drawing of the visible controls, along with the generation and the
+/// controls and their frames at the time it was drawn.
+struct DrawnControls {
+    generation: u64,
+    controls: Vec<(id, CGRect)>,
+    overlay: Overlay,
+}
+
+struct TargetAction {
+    /// Weak reference, like in UIKit.
+    target: id,
+    action: SEL,
+    events: UIControlEvents,
+}
+
+pub(super) struct ControlState {
+    targets: Vec<TargetAction>,
+    pub(super) enabled: bool,
+    pub(super) selected: bool,
+    pub(super) highlighted: bool,
+    tracking: bool,
+    /// Whether the current touch began on this control while it was enabled.
+    touching: bool,
+    pub(super) touch_inside: bool,
+    pub(super) horizontal_alignment: UIControlContentHorizontalAlignment,
+    pub(super) vertical_alignment: UIControlContentVerticalAlignment,
+    pub(super) kind: ControlKind,
+}
+
+/// State for the standard subclasses of `UIControl`.
+pub(super) enum ControlKind {
+    Plain,
+    Button(ui_button::ButtonState),
+    Slider(ui_slider::SliderState),
+    Switch(ui_switch::SwitchState),
+    SegmentedControl(ui_segmented_control::SegmentedControlState),
+}
+
+/// Get a control's state, creating it if needed. Since the subclasses can't
+/// do super-calls yet, the kind of state is decided by the control's class.
+pub(super) fn state(env: &mut Environment, control: id) -> &mut ControlState {
+    if env
+        .objc
+        .borrow::<UIViewHostObject>(control)
+        .control
+        .is_none()
+    {
+        let kind = kind_for_class(env, control);
+        env.objc.borrow_mut::<UIViewHostObject>(control).control = Some(Box::new(ControlState {
+            targets: Vec::new(),
+            enabled: true,
+            selected: false,
+            highlighted: false,
+            tracking: false,
+            touching: false,
+            touch_inside: false,
+            horizontal_alignment: UIControlContentHorizontalAlignmentCenter,
+            vertical_alignment: UIControlContentVerticalAlignmentCenter,
+            kind,
+        }));
+    }
+    env.objc
+        .borrow_mut::<UIViewHostObject>(control)
+        .control
+        .as_mut()
+        .unwrap()
+}
+
+fn kind_for_class(env: &mut Environment, control: id) -> ControlKind {
+    let is_kind_of = |env: &mut Environment, name: &str| -> bool {
+        let class = env.objc.get_known_class(name, &mut env.mem);
+        msg![env; control isKindOfClass:class]
+    };
+    if is_kind_of(env, "UIButton") {
+        ControlKind::Button(ui_button::ButtonState::default())
+    } else if is_kind_of(env, "UISlider") {
+        ControlKind::Slider(ui_slider::SliderState::default())
+    } else if is_kind_of(env, "UISwitch") {
+        ControlKind::Switch(ui_switch::SwitchState::default())
+    } else if is_kind_of(env, "UISegmentedControl") {
+        ControlKind::SegmentedControl(ui_segmented_control::SegmentedControlState::default())
+    } else {
+        ControlKind::Plain
+    }
+}
+
+/// For use by `UIView`'s `dealloc`.
+pub(super) fn release_state(env: &mut Environment, state: ControlState) {
+    match state.kind {
+        ControlKind::Plain | ControlKind::Switch(_) => (),
+        ControlKind::Button(state) => ui_button::release_state(env, state),
+        ControlKind::Slider(state) => ui_slider::release_state(env, state),
+        ControlKind::SegmentedControl(state) => ui_segmented_control::release_state(env, state),
+    }
+    changed(env);
+}
+
+/// Mark the controls overlay for redrawing.
+pub(super) fn changed(env: &mut Environment) {
+    env.framework_state.uikit.ui_control.generation += 1;
+}
+
+/// The current `UIControlState` of a control.
+pub(super) fn control_state(env: &mut Environment, control: id) -> UIControlState {
+    let state = state(env, control);
+    let mut control_state = UIControlStateNormal;
+    if state.highlighted {
+        control_state |= UIControlStateHighlighted;
+    }
+    if !state.enabled {
+        control_state |= UIControlStateDisabled;
+    }
+    if state.selected {
+        control_state |= UIControlStateSelected;
+    }
+    control_state
+}
+
+/// Send the actions registered for any of some control events.
+pub(super) fn send_actions(env: &mut Environment, control: id, events: UIControlEvents, event: id) {
+    let actions: Vec<(id, SEL)> = state(env, control)
+        .targets
+        .iter()
+        .filter(|target_action| target_action.events & events != 0)
+        .map(|target_action| (target_action.target, target_action.action))
+        .collect();
+    for (target, action) in actions {
+        log_dbg!(
+            "{:?} sending {:?} to {:?}",
+            control,
+            action.as_str(&env.mem),
+            target
+        );
+        let () = msg![env; control sendAction:action to:target forEvent:event];
+    }
+}
+
+/// Get the location of a touch in a control's co-ordinate space, if the
+/// control is on the screen.
+pub(super) fn touch_location(env: &mut Environment, control: id, touch: id) -> Option<CGPoint> {
+    let location: CGPoint = msg![env; touch locationInView:nil];
+    let frame = frame_on_screen(env, control)?;
+    let bounds = env.objc.borrow::<UIViewHostObject>(control).bounds;
+    Some(CGPoint {
+        x: location.x - frame.origin.x + bounds.origin.x,
+        y: location.y - frame.origin.y + bounds.origin.y,
+    })
+}
+
+fn touch_is_inside(env: &mut Environment, control: id, touch: id) -> bool {
+    let location: CGPoint = msg![env; touch locationInView:nil];
+    let Some(frame) = frame_on_screen(env, control) else {
+        return false;
+    };
+    let area = rect(
+        frame.origin.x - TOUCH_SLOP,
+        frame.origin.y - TOUCH_SLOP,
+        frame.size.width + TOUCH_SLOP * 2.0,
+        frame.size.height + TOUCH_SLOP * 2.0,
+    );
+    contains(area, (location.x, location.y))
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIControl: UIView
+
+- (bool)isEnabled {
+    state(env, this).enabled
+}
+- (())setEnabled:(bool)enabled {
+    state(env, this).enabled = enabled;
+    changed(env);
+}
+- (bool)isSelected {
+    state(env, this).selected
+}
+- (())setSelected:(bool)selected {
+    state(env, this).selected = selected;
+    changed(env);
+}
+- (bool)isHighlighted {
+    state(env, this).highlighted
+}
+- (())setHighlighted:(bool)highlighted {
+    state(env, this).highlighted = highlighted;
+    changed(env);
+}
+- (UIControlState)state {
+    control_state(env, this)
+}
+- (bool)isTracking {
+    state(env, this).tracking
+}
+- (bool)isTouchInside {
+    state(env, this).touch_inside
+}
+
+- (UIControlContentHorizontalAlignment)contentHorizontalAlignment {
+    state(env, this).horizontal_alignment
+}
+- (())setContentHorizontalAlignment:(UIControlContentHorizontalAlignment)alignment {
+    state(env, this).horizontal_alignment = alignment;
+    changed(env);
+}
+- (UIControlContentVerticalAlignment)contentVerticalAlignment {
+    state(env, this).vertical_alignment
+}
+- (())setContentVerticalAlignment:(UIControlContentVerticalAlignment)alignment {
+    state(env, this).vertical_alignment = alignment;
+    changed(env);
+}
+
+- (())addTarget:(id)target
+         action:(SEL)action
+forControlEvents:(UIControlEvents)events {
+    let targets = &mut state(env, this).targets;
+    if let Some(existing) = targets
+        .iter_mut()
+        .find(|existing| existing.target == target && existing.action == action)
+    {
+        existing.events |= events;
+    } else {
+        targets.push(TargetAction { target, action, events });
+    }
+}
+- (())removeTarget:(id)target
+            action:(SEL)action
+  forControlEvents:(UIControlEvents)events {
+    // A nil target or NULL action matches any target or action.
+    let targets = &mut state(env, this).targets;
+    for existing in targets.iter_mut() {
+        if (target == nil || existing.target == target)
+            && (action.is_null() || existing.action == action)
+        {
+            existing.events &= !events;
+        }
+    }
+    targets.retain(|existing| existing.events != 0);
+}
+- (UIControlEvents)allControlEvents {
+    state(env, this)
+        .targets
+        .iter()
+        .fold(0, |events, target_action| events | target_action.events)
+}
+- (id)allTargets {
+    let targets: Vec<id> = state(env, this)
+        .targets
+        .iter()
+        .map(|target_action| target_action.target)
+        .collect();
+    // TODO: use NSSet once it supports more than one object
+    let set: id = msg_class![env; NSCountedSet new];
+    for target in targets {
+        // A nil target means the responder chain, which isn't an object.
+        if target != nil {
+            let () = msg![env; set addObject:target];
+        }
+    }
+    autorelease(env, set)
+}
+- (id)actionsForTarget:(id)target
+       forControlEvent:(UIControlEvents)event {
+    let actions: Vec<SEL> = state(env, this)
+        .targets
+        .iter()
+        .filter(|target_action| target_action.target == target && target_action.events & event != 0)
+        .map(|target_action| target_action.action)
+        .collect();
+    if actions.is_empty() {
+        return nil;
+    }
+    let actions = actions
+        .into_iter()
+        .map(|action| {
+            let name = action.as_str(&env.mem).to_string();
+            ns_string::from_rust_string(env, name)
+        })
+        .collect();
+    let actions = ns_array::from_vec(env, actions);
+    autorelease(env, actions)
+}
+
+- (())sendAction:(SEL)action
+              to:(id)target
+        forEvent:(id)event { // UIEvent*
+    let application: id = msg_class![env; UIApplication sharedApplication];
+    let _: bool = msg![env; application sendAction:action to:target from:this forEvent:event];
+}
+- (())sendActionsForControlEvents:(UIControlEvents)events {
+    send_actions(env, this, events, nil);
+}
+
+// For subclasses to override.
+- (bool)beginTrackingWithTouch:(id)_touch // UITouch*
+                     withEvent:(id)_event { // UIEvent*
+    true
+}
+- (bool)continueTrackingWithTouch:(id)_touch // UITouch*
+                        withEvent:(id)_event { // UIEvent*
+    true
+}
+- (())endTrackingWithTouch:(id)_touch // UITouch*
+                 withEvent:(id)_event { // UIEvent*
+}
+- (())cancelTrackingWithEvent:(id)_event { // UIEvent*
+}
+
+- (())touchesBegan:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    if !state(env, this).enabled {
+        return;
+    }
+    state(env, this).touching = true;
+    state(env, this).touch_inside = true;
+    () = msg![env; this setHighlighted:true];
+    let touch: id = msg![env; touches anyObject];
+    let tracking: bool = msg![env; this beginTrackingWithTouch:touch withEvent:event];
+    state(env, this).tracking = tracking;
+    send_actions(env, this, UIControlEventTouchDown, event);
+}
+- (())touchesMoved:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    if !state(env, this).touching {
+        return;
+    }
+    let touch: id = msg![env; touches anyObject];
+    let inside = touch_is_inside(env, this, touch);
+    let was_inside = std::mem::replace(&mut state(env, this).touch_inside, inside);
+    if inside != was_inside {
+        () = msg![env; this setHighlighted:inside];
+    }
+    if state(env, this).tracking {
+        let tracking: bool = msg![env; this continueTrackingWithTouch:touch withEvent:event];
+        state(env, this).tracking = tracking;
+    }
+    let events = match (was_inside, inside) {
+        (true, true) => UIControlEventTouchDragInside,
+        (false, true) => UIControlEventTouchDragEnter | UIControlEventTouchDragInside,
+        (true, false) => UIControlEventTouchDragExit | UIControlEventTouchDragOutside,
+        (false, false) => UIControlEventTouchDragOutside,
+    };
+    send_actions(env, this, events, event);
+}
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    if !state(env, this).touching {
+        return;
+    }
+    let touch: id = msg![env; touches anyObject];
+    let inside = touch_is_inside(env, this, touch);
+    state(env, this).touch_inside = inside;
+    if state(env, this).tracking {
+        () = msg![env; this endTrackingWithTouch:touch withEvent:event];
+    }
+    end_touch(env, this);
+    let events = if inside {
+        UIControlEventTouchUpInside
+    } else {
+        UIControlEventTouchUpOutside
+    };
+    send_actions(env, this, events, event);
+}
+- (())touchesCancelled:(id)_touches // NSSet* of UITouch*
+             withEvent:(id)event { // UIEvent*
+    if !state(env, this).touching {
+        return;
+    }
+    if state(env, this).tracking {
+        () = msg![env; this cancelTrackingWithEvent:event];
+    }
+    end_touch(env, this);
+    send_actions(env, this, UIControlEventTouchCancel, event);
+}
+
+@end
+
+};
+
+fn end_touch(env: &mut Environment, control: id) {
+    let state = state(env, control);
+    state.touching = false;
+    state.tracking = false;
+    state.touch_inside = false;
+    () = msg![env; control setHighlighted:false];
+}
+
+/// The visible windows, back to front.
+fn windows(env: &mut Environment) -> Vec<id> {
+    let ui_window_class = env.objc.get_known_class("UIWindow", &mut env.mem);
+    let views = env.framework_state.uikit.ui_view.views.clone();
+    views
+        .into_iter()
+        .filter(|&view| {
+            msg![env; view isKindOfClass:ui_window_class]
+                && !env.objc.borrow::<UIViewHostObject>(view).hidden
+        })
+        .collect()
+}
+
+/// Get the frame of a subview on the screen, given the frame of its superview
+/// on the screen.
+fn subview_frame(env: &mut Environment, superview: id, super_frame: CGRect, view: id) -> CGRect {
+    let bounds_origin = env.objc.borrow::<UIViewHostObject>(superview).bounds.origin;
+    let mut frame: CGRect = msg![env; view frame];
+    frame.origin.x += super_frame.origin.x - bounds_origin.x;
+    frame.origin.y += super_frame.origin.y - bounds_origin.y;
+    frame
+}
+
+/// Find the frontmost view that a touch at a point on the screen would hit,
+/// among a view and its subviews.
+///
+/// TODO: take transforms into account
+fn hit_test(env: &mut Environment, view: id, frame: CGRect, point: CGPoint) -> Option<id> {
+    let host_object = env.objc.borrow::<UIViewHostObject>(view);
+    if host_object.hidden
+        || !host_object.user_interaction_enabled
+        || !contains(frame, (point.x, point.y))
+    {
+        return None;
+    }
+    let subviews = host_object.subviews.clone();
+    for subview in subviews.into_iter().rev() {
+        let subview_frame = subview_frame(env, view, frame, subview);
+        if let Some(hit) = hit_test(env, subview, subview_frame, point) {
+            return Some(hit);
+        }
+    }
+    Some(view)
+}
+
+/// For use by `UITouch`: find the enabled control, if any, that should get a
+/// touch at a point on the screen.
+pub(super) fn control_at_point(env: &mut Environment, point: CGPoint) -> Option<id> {
+    let ui_control_class = env.objc.get_known_class("UIControl", &mut env.mem);
+    for window in windows(env).into_iter().rev() {
+        let frame: CGRect = msg![env; window frame];
+        let Some(mut view) = hit_test(env, window, frame, point) else {
+            continue;
+        };
+        // Views inside a control, e.g. a button's image, are part of it.
+        loop {
+            if msg![env; view isKindOfClass:ui_control_class] {
+                return state(env, view).enabled.then_some(view);
+            }
+            view = env.objc.borrow::<UIViewHostObject>(view).superview;
+            if view == nil {
+                return None;
+            }
+        }
+    }
+    None
+}
+
+/// Find the visible controls among a view and its subviews, back to front,
+/// with their frames on the screen.
+fn collect_visible_controls(
+    env: &mut Environment,
+    view: id,
+    frame: CGRect,
+    ui_control_class: crate::objc::Class,
+    controls: &mut Vec<(id, CGRect)>,
+) {
+    if env.objc.borrow::<UIViewHostObject>(view).hidden {
+        return;
+    }
+    if msg![env; view isKindOfClass:ui_control_class] {
+        controls.push((view, frame));
+    }
+    let subviews = env.objc.borrow::<UIViewHostObject>(view).subviews.clone();
+    for subview in subviews {
+        let subview_frame = subview_frame(env, view, frame, subview);
+        collect_visible_controls(env, subview, subview_frame, ui_control_class, controls);
+    }
+}
+
+/// For use by [super::overlay]: redraw the visible controls if needed.
+pub(super) fn update_overlay(env: &mut Environment) {
+    let ui_control_class = env.objc.get_known_class("UIControl", &mut env.mem);
+    let mut controls = Vec::new();
+    for window in windows(env) {
+        let frame: CGRect = msg![env; window frame];
+        collect_visible_controls(env, window, frame, ui_control_class, &mut controls);
+    }
+    // Make sure the state exists, so that the generation doesn't change while
+    // drawing.
+    for &(control, _) in &controls {
+        state(env, control);
+    }
+
+    let generation = env.framework_state.uikit.ui_control.generation;
+    if let Some(ref drawn) = env.framework_state.uikit.ui_control.overlay {
+        if drawn.generation == generation && drawn.controls == controls {
+            return;
+        }
+    }
+    if controls.is_empty() {
+        env.framework_state.uikit.ui_control.overlay = None;
+        return;
+    }
+
+    let mut canvas = Canvas::new(env);
+    for &(control, frame) in &controls {
+        match state(env, control).kind {
+            ControlKind::Plain => (),
+            ControlKind::Button(_) => ui_button::draw(env, &mut canvas, control, frame),
+            ControlKind::Slider(_) => ui_slider::draw(env, &mut canvas, control, frame),
+            ControlKind::Switch(_) => ui_switch::draw(env, &mut canvas, control, frame),
+            ControlKind::SegmentedControl(_) => {
+                ui_segmented_control::draw(env, &mut canvas, control, frame)
+            }
+        }
+    }
+    env.framework_state.uikit.ui_control.overlay = Some(DrawnControls {
+        generation,
+        controls,
+        overlay: canvas.into_overlay(),
+    });
+}
+
+/// For use by [super::overlay]: get the drawing of the visible controls, if
+/// there are any.
+pub(super) fn current_overlay(state: &State) -> Option<&Overlay> {
+    state.overlay.as_ref().map(|drawn| &drawn.overlay)
+}
+
+/// Helper for drawing: scale an image's size down to fit within a rect if
+/// needed, and position it in the rect according to the alignment.
+pub(super) fn place_content(
+    bounds: CGRect,
+    (width, height): (CGFloat, CGFloat),
+    horizontal_alignment: UIControlContentHorizontalAlignment,
+) -> CGRect {
+    let scale = (bounds.size.width / width.max(1.0))
+        .min(bounds.size.height / height.max(1.0))
+        .min(1.0);
+    let (width, height) = (width * scale, height * scale);
+    let x = match horizontal_alignment {
+        UIControlContentHorizontalAlignmentLeft => bounds.origin.x,
+        UIControlContentHorizontalAlignmentRight => bounds.origin.x + bounds.size.width - width,
+        _ => bounds.origin.x + (bounds.size.width - width) / 2.0,
+    };
+    rect(
+        x,
+        bounds.origin.y + (bounds.size.height - height) / 2.0,
+        width,
+        height,
+    )
+}
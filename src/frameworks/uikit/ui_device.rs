@@ -4,19 +4,249 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `UIDevice.h`
+//!
+//! The model and system version can be set with the `--device-model=` and
+//! `--system-version=` options, and the battery level with `--battery-level=`.
+//! The device can be rotated with Ctrl+Left and Ctrl+Right.
 
-use crate::frameworks::foundation::NSInteger;
+use super::ui_view_controller;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::ns_string::{from_rust_string, get_static_str};
+use crate::frameworks::foundation::ns_uuid::splitmix64;
+use crate::frameworks::foundation::{ns_notification_center, NSInteger};
+use crate::objc::{autorelease, id, msg_class, nil, objc_classes, ClassExports, TrivialHostObject};
+use crate::window::{BatteryState, DeviceOrientation};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// [UIDevice currentDevice]
+    current_device: Option<id>,
+    battery_monitoring_enabled: bool,
+    proximity_monitoring_enabled: bool,
+    /// The physical orientation, which might not match the interface's.
+    orientation: DeviceOrientation,
+    /// Nesting count of `beginGeneratingDeviceOrientationNotifications`.
+    orientation_notifications_count: u32,
+}
 
 pub type UIDeviceOrientation = NSInteger;
-#[allow(dead_code)]
 pub const UIDeviceOrientationUnknown: UIDeviceOrientation = 0;
 pub const UIDeviceOrientationPortrait: UIDeviceOrientation = 1;
-#[allow(dead_code)]
 pub const UIDeviceOrientationPortraitUpsideDown: UIDeviceOrientation = 2;
 pub const UIDeviceOrientationLandscapeLeft: UIDeviceOrientation = 3;
-#[allow(dead_code)]
 pub const UIDeviceOrientationLandscapeRight: UIDeviceOrientation = 4;
 #[allow(dead_code)]
 pub const UIDeviceOrientationFaceUp: UIDeviceOrientation = 5;
 #[allow(dead_code)]
 pub const UIDeviceOrientationFaceDown: UIDeviceOrientation = 6;
+
+pub type UIDeviceBatteryState = NSInteger;
+pub const UIDeviceBatteryStateUnknown: UIDeviceBatteryState = 0;
+pub const UIDeviceBatteryStateUnplugged: UIDeviceBatteryState = 1;
+pub const UIDeviceBatteryStateCharging: UIDeviceBatteryState = 2;
+pub const UIDeviceBatteryStateFull: UIDeviceBatteryState = 3;
+
+pub type UIUserInterfaceIdiom = NSInteger;
+pub const UIUserInterfaceIdiomPhone: UIUserInterfaceIdiom = 0;
+
+pub const UIDeviceOrientationDidChangeNotification: &str =
+    "UIDeviceOrientationDidChangeNotification";
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_UIDeviceOrientationDidChangeNotification",
+    HostConstant::NSString(UIDeviceOrientationDidChangeNotification),
+)];
+
+pub fn orientation_from_window(orientation: DeviceOrientation) -> UIDeviceOrientation {
+    match orientation {
+        DeviceOrientation::Portrait => UIDeviceOrientationPortrait,
+        DeviceOrientation::PortraitUpsideDown => UIDeviceOrientationPortraitUpsideDown,
+        DeviceOrientation::LandscapeLeft => UIDeviceOrientationLandscapeLeft,
+        DeviceOrientation::LandscapeRight => UIDeviceOrientationLandscapeRight,
+    }
+}
+
+/// Returns [None] for orientations the window can't have, like face up.
+pub fn window_orientation(orientation: UIDeviceOrientation) -> Option<DeviceOrientation> {
+    match orientation {
+        UIDeviceOrientationPortrait => Some(DeviceOrientation::Portrait),
+        UIDeviceOrientationPortraitUpsideDown => Some(DeviceOrientation::PortraitUpsideDown),
+        UIDeviceOrientationLandscapeLeft => Some(DeviceOrientation::LandscapeLeft),
+        UIDeviceOrientationLandscapeRight => Some(DeviceOrientation::LandscapeRight),
+        _ => None,
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// This is a singleton.
+@implementation UIDevice: NSObject
+
++ (id)currentDevice {
+    if let Some(device) = env.framework_state.uikit.ui_device.current_device {
+        device
+    } else {
+        let new = env.objc.alloc_static_object(
+            this,
+            Box::new(TrivialHostObject),
+            &mut env.mem
+        );
+        env.framework_state.uikit.ui_device.current_device = Some(new);
+        new
+    }
+}
+- (id)retain { this }
+- (())release {}
+- (id)autorelease { this }
+
+- (id)name {
+    let name = env.options.device_model.clone();
+    let name = from_rust_string(env, name);
+    autorelease(env, name)
+}
+- (id)model {
+    let model = env.options.device_model.clone();
+    let model = from_rust_string(env, model);
+    autorelease(env, model)
+}
+- (id)localizedModel {
+    let model = env.options.device_model.clone();
+    let model = from_rust_string(env, model);
+    autorelease(env, model)
+}
+- (id)systemName {
+    get_static_str(env, "iPhone OS")
+}
+- (id)systemVersion {
+    let version = env.options.system_version.clone();
+    let version = from_rust_string(env, version);
+    autorelease(env, version)
+}
+- (id)uniqueIdentifier {
+    let identifier = unique_identifier(env);
+    let identifier = from_rust_string(env, identifier);
+    autorelease(env, identifier)
+}
+
+- (UIUserInterfaceIdiom)userInterfaceIdiom {
+    UIUserInterfaceIdiomPhone
+}
+- (bool)isMultitaskingSupported {
+    false
+}
+
+- (bool)isBatteryMonitoringEnabled {
+    env.framework_state.uikit.ui_device.battery_monitoring_enabled
+}
+- (())setBatteryMonitoringEnabled:(bool)enabled {
+    env.framework_state.uikit.ui_device.battery_monitoring_enabled = enabled;
+}
+- (f32)batteryLevel {
+    if !env.framework_state.uikit.ui_device.battery_monitoring_enabled {
+        return -1.0;
+    }
+    battery_info(env).1.unwrap_or(-1.0)
+}
+- (UIDeviceBatteryState)batteryState {
+    if !env.framework_state.uikit.ui_device.battery_monitoring_enabled {
+        return UIDeviceBatteryStateUnknown;
+    }
+    match battery_info(env).0 {
+        BatteryState::Unknown => UIDeviceBatteryStateUnknown,
+        BatteryState::Unplugged => UIDeviceBatteryStateUnplugged,
+        BatteryState::Charging => UIDeviceBatteryStateCharging,
+        BatteryState::Full => UIDeviceBatteryStateFull,
+    }
+}
+
+- (UIDeviceOrientation)orientation {
+    let state = &env.framework_state.uikit.ui_device;
+    // The orientation isn't known until the accelerometer is being used for
+    // this.
+    if state.orientation_notifications_count == 0 {
+        UIDeviceOrientationUnknown
+    } else {
+        orientation_from_window(state.orientation)
+    }
+}
+- (bool)isGeneratingDeviceOrientationNotifications {
+    env.framework_state.uikit.ui_device.orientation_notifications_count > 0
+}
+- (())beginGeneratingDeviceOrientationNotifications {
+    env.framework_state.uikit.ui_device.orientation_notifications_count += 1;
+}
+- (())endGeneratingDeviceOrientationNotifications {
+    let count = &mut env.framework_state.uikit.ui_device.orientation_notifications_count;
+    *count = count.saturating_sub(1);
+}
+
+// There's no proximity sensor to monitor, so the user is never holding the
+// device close to their face.
+- (bool)isProximityMonitoringEnabled {
+    env.framework_state.uikit.ui_device.proximity_monitoring_enabled
+}
+- (())setProximityMonitoringEnabled:(bool)enabled {
+    env.framework_state.uikit.ui_device.proximity_monitoring_enabled = enabled;
+}
+- (bool)proximityState {
+    false
+}
+
+@end
+
+};
+
+/// For use by `UIApplicationMain`: set the orientation the device is held in
+/// when the app is launched.
+pub(super) fn set_initial_orientation(env: &mut Environment, orientation: DeviceOrientation) {
+    env.framework_state.uikit.ui_device.orientation = orientation;
+}
+
+/// For use by [super::handle_events]: rotate the device by 90° (Ctrl+Left or
+/// Ctrl+Right), and let the app rotate its interface to match.
+pub(super) fn handle_rotation(env: &mut Environment, clockwise: bool) {
+    let state = &mut env.framework_state.uikit.ui_device;
+    state.orientation = state.orientation.rotated(clockwise);
+    let orientation = state.orientation;
+    log!("Rotated the device to {:?}", orientation);
+
+    if state.orientation_notifications_count > 0 {
+        let device: id = msg_class![env; UIDevice currentDevice];
+        let name = get_static_str(env, UIDeviceOrientationDidChangeNotification);
+        ns_notification_center::post(env, name, device, nil);
+    }
+
+    // The interface orientation constants' values are the same as the device
+    // orientation ones that would make them upright.
+    ui_view_controller::autorotate(env, orientation_from_window(orientation));
+}
+
+pub(super) fn battery_info(env: &mut Environment) -> (BatteryState, Option<f32>) {
+    if let Some(level) = env.options.battery_level {
+        (BatteryState::Unplugged, Some(level))
+    } else {
+        env.window.battery_info()
+    }
+}
+
+/// The UDID, a string of 40 hexadecimal digits. A real one identifies the
+/// device, but apps often use it to identify the user, so this is derived from
+/// the app's bundle identifier to make it stable for each app.
+fn unique_identifier(env: &mut Environment) -> String {
+    // FNV-1a, which unlike the standard library's hashers is guaranteed to
+    // give the same result on every run and every version.
+    let mut state: u64 = 0xcbf29ce484222325;
+    for &byte in env.bundle.bundle_identifier().as_bytes() {
+        state ^= byte as u64;
+        state = state.wrapping_mul(0x100000001b3);
+    }
+    let mut identifier = String::with_capacity(48);
+    for _ in 0..3 {
+        identifier.push_str(&format!("{:016x}", splitmix64(&mut state)));
+    }
+    identifier.truncate(40);
+    identifier
+}
@@ -5,14 +5,181 @@
  */
 //! `UIEvent`.
 
-use crate::objc::{objc_classes, ClassExports};
+use super::ui_touch;
+use crate::frameworks::foundation::{ns_set, NSInteger, NSTimeInterval};
+use crate::frameworks::graphics_services;
+use crate::mem::{MutVoidPtr, Ptr};
+use crate::objc::{
+    autorelease, id, msg, msg_class, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::window::HardwareKey;
+use crate::Environment;
+
+type UIEventType = NSInteger;
+const UIEventTypeTouches: UIEventType = 0;
+const UIEventTypeMotion: UIEventType = 1;
+/// Not a public type. Apps that handle key events look at the `GSEvent`
+/// instead.
+const UIEventTypeKey: UIEventType = -1;
+
+pub type UIEventSubtype = NSInteger;
+const UIEventSubtypeNone: UIEventSubtype = 0;
+pub const UIEventSubtypeMotionShake: UIEventSubtype = 1;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(super) enum EventKind {
+    Touches,
+    Motion {
+        ended: bool,
+    },
+    /// The `GSEvent` is in guest memory owned by the `UIEvent`.
+    Key {
+        gs_event: MutVoidPtr,
+    },
+}
+
+pub(super) struct UIEventHostObject {
+    kind: EventKind,
+    /// `UITouch*`s, strong references. For a touch event, these are all the
+    /// touches in progress, including ones that didn't change.
+    touches: Vec<id>,
+    timestamp: NSTimeInterval,
+}
+impl HostObject for UIEventHostObject {}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
 @implementation UIEvent: NSObject
-// TODO
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(UIEventHostObject {
+        kind: EventKind::Touches,
+        touches: Vec::new(),
+        timestamp: 0.0,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<UIEventHostObject>(this);
+    let touches = std::mem::take(&mut host_object.touches);
+    if let EventKind::Key { gs_event } = host_object.kind {
+        env.mem.free(gs_event);
+    }
+    for touch in touches {
+        release(env, touch);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (UIEventType)type {
+    match env.objc.borrow::<UIEventHostObject>(this).kind {
+        EventKind::Touches => UIEventTypeTouches,
+        EventKind::Motion { .. } => UIEventTypeMotion,
+        EventKind::Key { .. } => UIEventTypeKey,
+    }
+}
+- (UIEventSubtype)subtype {
+    match env.objc.borrow::<UIEventHostObject>(this).kind {
+        EventKind::Motion { .. } => UIEventSubtypeMotionShake,
+        _ => UIEventSubtypeNone,
+    }
+}
+
+// Private. Returns a GSEventRef.
+- (MutVoidPtr)_gsEvent {
+    match env.objc.borrow::<UIEventHostObject>(this).kind {
+        EventKind::Key { gs_event } => gs_event,
+        _ => Ptr::null(),
+    }
+}
+
+- (NSTimeInterval)timestamp {
+    env.objc.borrow::<UIEventHostObject>(this).timestamp
+}
+
+- (id)allTouches { // NSSet* of UITouch*
+    let touches = env.objc.borrow::<UIEventHostObject>(this).touches.clone();
+    touches_set(env, touches)
+}
+- (id)touchesForView:(id)view { // NSSet* of UITouch*
+    let touches = env.objc.borrow::<UIEventHostObject>(this).touches.clone();
+    let touches = touches
+        .into_iter()
+        .filter(|&touch| ui_touch::touch_view(env, touch) == view)
+        .collect();
+    touches_set(env, touches)
+}
+- (id)touchesForWindow:(id)window { // NSSet* of UITouch*
+    let touches = env.objc.borrow::<UIEventHostObject>(this).touches.clone();
+    let touches = touches
+        .into_iter()
+        .filter(|&touch| {
+            let touch_window: id = msg![env; touch window];
+            touch_window == window
+        })
+        .collect();
+    touches_set(env, touches)
+}
+
 @end
 
 };
+
+fn touches_set(env: &mut Environment, touches: Vec<id>) -> id {
+    for &touch in &touches {
+        retain(env, touch);
+    }
+    let set = ns_set::from_vec(env, touches);
+    autorelease(env, set)
+}
+
+/// For use by [ui_touch]: replace the touches and timestamp of an event.
+pub(super) fn set_touches(
+    env: &mut Environment,
+    event: id,
+    touches: Vec<id>,
+    timestamp: NSTimeInterval,
+) {
+    for &touch in &touches {
+        retain(env, touch);
+    }
+    let host_object = env.objc.borrow_mut::<UIEventHostObject>(event);
+    host_object.timestamp = timestamp;
+    let old = std::mem::replace(&mut host_object.touches, touches);
+    for touch in old {
+        release(env, touch);
+    }
+}
+
+/// For use by [ui_touch]: get all the touches of an event.
+pub(super) fn touches(env: &mut Environment, event: id) -> Vec<id> {
+    env.objc.borrow::<UIEventHostObject>(event).touches.clone()
+}
+
+/// For use by `UIApplication` etc: what kind of event this is.
+pub(super) fn kind(env: &mut Environment, event: id) -> EventKind {
+    env.objc.borrow::<UIEventHostObject>(event).kind
+}
+
+fn new_event(env: &mut Environment, kind: EventKind) -> id {
+    let event: id = msg_class![env; UIEvent new];
+    let timestamp: NSTimeInterval = msg_class![env; NSProcessInfo systemUptime];
+    let host_object = env.objc.borrow_mut::<UIEventHostObject>(event);
+    host_object.kind = kind;
+    host_object.timestamp = timestamp;
+    autorelease(env, event)
+}
+
+/// Create a new (autoreleased) shake motion event.
+pub(super) fn new_motion_event(env: &mut Environment, ended: bool) -> id {
+    new_event(env, EventKind::Motion { ended })
+}
+
+/// Create a new (autoreleased) key event for a hardware key.
+pub(super) fn new_key_event(env: &mut Environment, key: HardwareKey) -> id {
+    let gs_event = graphics_services::new_key_event(env, key);
+    new_event(env, EventKind::Key { gs_event })
+}
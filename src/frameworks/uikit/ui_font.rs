@@ -3,14 +3,23 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! `UIFont`.
+//! `UIFont`, and the text measuring and drawing behind `UIStringDrawing`.
+//!
+//! iPhone OS fonts are substituted with bundled fonts that have the same
+//! metrics where possible (see [FONT_FAMILIES]). Apps can also ship their own
+//! fonts, listed under `UIAppFonts` in their `Info.plist`.
 
 use super::ui_graphics::UIGraphicsGetCurrentContext;
-use crate::font::{Font, TextAlignment, WrapMode};
-use crate::frameworks::core_graphics::cg_bitmap_context::CGBitmapContextDrawer;
-use crate::frameworks::core_graphics::{CGFloat, CGRect, CGSize};
-use crate::frameworks::foundation::NSInteger;
-use crate::objc::{autorelease, id, objc_classes, ClassExports, HostObject};
+use crate::font::{Font, FontMetrics, OutlineSegment, Truncation, WrapMode};
+use crate::frameworks::core_graphics::cg_context::{
+    self, CGContextClipToRect, CGContextRestoreGState, CGContextSaveGState,
+    CGContextSetRGBFillColor,
+};
+use crate::frameworks::core_graphics::cg_path::Path;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::{ns_array, ns_string, NSInteger};
+use crate::mem::MutVoidPtr;
+use crate::objc::{autorelease, id, nil, objc_classes, retain, ClassExports, HostObject};
 use crate::Environment;
 
 #[derive(Default)]
@@ -20,6 +29,18 @@ pub(super) struct State {
     italic: Option<Font>,
     regular_ja: Option<Font>,
     bold_ja: Option<Font>,
+    /// Fonts from the app bundle, loaded when first looked up by name.
+    app_fonts: Option<Vec<AppFont>>,
+}
+
+/// A font shipped in the app bundle.
+struct AppFont {
+    family_name: String,
+    /// PostScript name.
+    font_name: String,
+    /// Apps can also refer to a font by its full name.
+    full_name: Option<String>,
+    font: Font,
 }
 
 #[derive(Copy, Clone)]
@@ -27,14 +48,54 @@ enum FontKind {
     Regular,
     Bold,
     Italic,
+    /// Japanese fonts, used regardless of the text.
+    RegularJa,
+    BoldJa,
+    /// Index into the app's own fonts.
+    App(usize),
 }
 
 struct UIFontHostObject {
     size: CGFloat,
     kind: FontKind,
+    /// PostScript name.
+    name: String,
+    family_name: String,
 }
 impl HostObject for UIFontHostObject {}
 
+/// The font families that can be requested by name, with the names of the
+/// fonts in each and the bundled fonts used for them. Liberation Sans has the
+/// same metrics as Arial and Helvetica, and Noto Sans JP stands in for
+/// Hiragino. There's no bold italic font, so bold takes priority.
+const FONT_FAMILIES: &[(&str, &[(&str, FontKind)])] = &[
+    (
+        "Arial",
+        &[
+            ("ArialMT", FontKind::Regular),
+            ("Arial-BoldMT", FontKind::Bold),
+            ("Arial-ItalicMT", FontKind::Italic),
+            ("Arial-BoldItalicMT", FontKind::Bold),
+        ],
+    ),
+    (
+        "Helvetica",
+        &[
+            ("Helvetica", FontKind::Regular),
+            ("Helvetica-Bold", FontKind::Bold),
+            ("Helvetica-Oblique", FontKind::Italic),
+            ("Helvetica-BoldOblique", FontKind::Bold),
+        ],
+    ),
+    (
+        "Hiragino Kaku Gothic ProN",
+        &[
+            ("HiraKakuProN-W3", FontKind::RegularJa),
+            ("HiraKakuProN-W6", FontKind::BoldJa),
+        ],
+    ),
+];
+
 /// Line break mode.
 ///
 /// This is put here for convenience since it's font-related.
@@ -42,13 +103,9 @@ impl HostObject for UIFontHostObject {}
 pub type UILineBreakMode = NSInteger;
 pub const UILineBreakModeWordWrap: UILineBreakMode = 0;
 pub const UILineBreakModeCharacterWrap: UILineBreakMode = 1;
-#[allow(dead_code)]
 pub const UILineBreakModeClip: UILineBreakMode = 2;
-#[allow(dead_code)]
 pub const UILineBreakModeHeadTruncation: UILineBreakMode = 3;
-#[allow(dead_code)]
 pub const UILineBreakModeTailTruncation: UILineBreakMode = 4;
-#[allow(dead_code)]
 pub const UILineBreakModeMiddleTruncation: UILineBreakMode = 5;
 
 /// Text alignment.
@@ -60,6 +117,15 @@ pub const UITextAlignmentLeft: UITextAlignment = 0;
 pub const UITextAlignmentCenter: UITextAlignment = 1;
 pub const UITextAlignmentRight: UITextAlignment = 2;
 
+/// Where text goes vertically when it is drawn smaller than requested.
+///
+/// This is put here for convenience since it's font-related.
+/// Apple puts it in `UIStringDrawing.h`.
+pub type UIBaselineAdjustment = NSInteger;
+pub const UIBaselineAdjustmentAlignBaselines: UIBaselineAdjustment = 0;
+pub const UIBaselineAdjustmentAlignCenters: UIBaselineAdjustment = 1;
+pub const UIBaselineAdjustmentNone: UIBaselineAdjustment = 2;
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -67,52 +133,243 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation UIFont: NSObject
 
 + (id)systemFontOfSize:(CGFloat)size {
-    // Cache for later use
-    if env.framework_state.uikit.ui_font.regular.is_none() {
-        env.framework_state.uikit.ui_font.regular = Some(Font::sans_regular());
-    }
-    let host_object = UIFontHostObject {
-        size,
-        kind: FontKind::Regular,
-    };
-    let new = env.objc.alloc_object(this, Box::new(host_object), &mut env.mem);
-    autorelease(env, new)
+    new_font(env, size, FontKind::Regular, "Helvetica", "Helvetica")
 }
 + (id)boldSystemFontOfSize:(CGFloat)size {
-    // Cache for later use
-    if env.framework_state.uikit.ui_font.bold.is_none() {
-        env.framework_state.uikit.ui_font.bold = Some(Font::sans_bold());
+    new_font(env, size, FontKind::Bold, "Helvetica-Bold", "Helvetica")
+}
++ (id)italicSystemFontOfSize:(CGFloat)size {
+    new_font(env, size, FontKind::Italic, "Helvetica-Oblique", "Helvetica")
+}
+
++ (CGFloat)systemFontSize {
+    14.0
+}
++ (CGFloat)smallSystemFontSize {
+    12.0
+}
++ (CGFloat)labelFontSize {
+    17.0
+}
++ (CGFloat)buttonFontSize {
+    18.0
+}
+
++ (id)fontWithName:(id)name // NSString*
+              size:(CGFloat)size {
+    let name = ns_string::to_rust_string(env, name);
+    if let Some((kind, font_name, family_name)) = find_font(env, &name) {
+        return new_font(env, size, kind, &font_name, &family_name);
     }
-    let host_object = UIFontHostObject {
-        size,
-        kind: FontKind::Bold,
+    // Apple's implementation returns nil here, but apps often ask for fonts
+    // that exist on the device without checking.
+    log!("Warning: no font named {:?}, substituting Helvetica", name);
+    let lower = name.to_ascii_lowercase();
+    let kind = if ["bold", "black", "heavy"].iter().any(|s| lower.contains(s)) {
+        FontKind::Bold
+    } else if ["italic", "oblique"].iter().any(|s| lower.contains(s)) {
+        FontKind::Italic
+    } else {
+        FontKind::Regular
     };
-    let new = env.objc.alloc_object(this, Box::new(host_object), &mut env.mem);
-    autorelease(env, new)
+    new_font(env, size, kind, &name, "Helvetica")
 }
-+ (id)italicSystemFontOfSize:(CGFloat)size {
-    // Cache for later use
-    if env.framework_state.uikit.ui_font.italic.is_none() {
-        env.framework_state.uikit.ui_font.italic = Some(Font::sans_italic());
+
++ (id)familyNames {
+    let mut names: Vec<String> = FONT_FAMILIES
+        .iter()
+        .map(|&(family, _)| family.to_string())
+        .collect();
+    for app_font in load_app_fonts(env) {
+        if !names.contains(&app_font.family_name) {
+            names.push(app_font.family_name.clone());
+        }
     }
+    let names = names
+        .into_iter()
+        .map(|name| ns_string::from_rust_string(env, name))
+        .collect();
+    let array = ns_array::from_vec(env, names);
+    autorelease(env, array)
+}
++ (id)fontNamesForFamilyName:(id)family_name { // NSString*
+    let family_name = ns_string::to_rust_string(env, family_name);
+    let mut names: Vec<String> = FONT_FAMILIES
+        .iter()
+        .filter(|&&(family, _)| family == family_name)
+        .flat_map(|&(_, fonts)| fonts.iter().map(|&(name, _)| name.to_string()))
+        .collect();
+    for app_font in load_app_fonts(env) {
+        if app_font.family_name == family_name {
+            names.push(app_font.font_name.clone());
+        }
+    }
+    let names = names
+        .into_iter()
+        .map(|name| ns_string::from_rust_string(env, name))
+        .collect();
+    let array = ns_array::from_vec(env, names);
+    autorelease(env, array)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    // Fonts are immutable.
+    retain(env, this)
+}
+
+- (id)fontWithSize:(CGFloat)size {
+    let host_object = env.objc.borrow::<UIFontHostObject>(this);
+    let kind = host_object.kind;
+    let (name, family_name) = (host_object.name.clone(), host_object.family_name.clone());
+    new_font(env, size, kind, &name, &family_name)
+}
+
+- (id)fontName {
+    let name = env.objc.borrow::<UIFontHostObject>(this).name.clone();
+    let name = ns_string::from_rust_string(env, name);
+    autorelease(env, name)
+}
+- (id)familyName {
+    let name = env.objc.borrow::<UIFontHostObject>(this).family_name.clone();
+    let name = ns_string::from_rust_string(env, name);
+    autorelease(env, name)
+}
+- (CGFloat)pointSize {
+    env.objc.borrow::<UIFontHostObject>(this).size
+}
+
+- (CGFloat)ascender {
+    metrics(env, this).ascender
+}
+- (CGFloat)descender {
+    metrics(env, this).descender
+}
+- (CGFloat)leading {
+    metrics(env, this).leading
+}
+- (CGFloat)capHeight {
+    metrics(env, this).cap_height
+}
+- (CGFloat)xHeight {
+    metrics(env, this).x_height
+}
+- (CGFloat)lineHeight {
+    metrics(env, this).line_height()
+}
+
+@end
+
+};
+
+fn new_font(
+    env: &mut Environment,
+    size: CGFloat,
+    kind: FontKind,
+    name: &str,
+    family_name: &str,
+) -> id {
     let host_object = UIFontHostObject {
         size,
-        kind: FontKind::Italic,
+        kind,
+        name: name.to_string(),
+        family_name: family_name.to_string(),
     };
-    let new = env.objc.alloc_object(this, Box::new(host_object), &mut env.mem);
+    let class = env.objc.get_known_class("UIFont", &mut env.mem);
+    let new = env
+        .objc
+        .alloc_object(class, Box::new(host_object), &mut env.mem);
     autorelease(env, new)
 }
 
-@end
-
-};
+/// Load the fonts listed under `UIAppFonts` in the app's `Info.plist`, if
+/// that hasn't been done already.
+fn load_app_fonts(env: &mut Environment) -> &[AppFont] {
+    if env.framework_state.uikit.ui_font.app_fonts.is_none() {
+        let mut app_fonts = Vec::new();
+        for path in env.bundle.app_font_paths() {
+            let Ok(bytes) = env.fs.read(&path) else {
+                log!("Warning: couldn't read app font {:?}", path);
+                continue;
+            };
+            let Some(font) = Font::from_bytes(bytes) else {
+                log!("Warning: couldn't parse app font {:?}", path);
+                continue;
+            };
+            let names = font.names().clone();
+            let Some(font_name) = names.postscript.or_else(|| names.full.clone()) else {
+                log!("Warning: app font {:?} has no name", path);
+                continue;
+            };
+            let family_name = names.family.unwrap_or_else(|| font_name.clone());
+            log_dbg!("Loaded app font {:?} ({:?})", font_name, family_name);
+            app_fonts.push(AppFont {
+                family_name,
+                font_name,
+                full_name: names.full,
+                font,
+            });
+        }
+        env.framework_state.uikit.ui_font.app_fonts = Some(app_fonts);
+    }
+    env.framework_state
+        .uikit
+        .ui_font
+        .app_fonts
+        .as_deref()
+        .unwrap()
+}
 
-fn convert_line_break_mode(ui_mode: UILineBreakMode) -> WrapMode {
-    match ui_mode {
-        UILineBreakModeWordWrap => WrapMode::Word,
-        UILineBreakModeCharacterWrap => WrapMode::Char,
-        _ => unimplemented!("TODO: line break mode {}", ui_mode),
+/// Find a font by name for `fontWithName:size:`. Like on iPhone OS, the name
+/// can also be a family name, and case doesn't matter. Returns the font's kind,
+/// name and family name.
+fn find_font(env: &mut Environment, name: &str) -> Option<(FontKind, String, String)> {
+    let app_fonts = load_app_fonts(env);
+    let app_font = app_fonts
+        .iter()
+        .position(|font| {
+            font.font_name.eq_ignore_ascii_case(name)
+                || font
+                    .full_name
+                    .as_deref()
+                    .is_some_and(|full_name| full_name.eq_ignore_ascii_case(name))
+        })
+        .or_else(|| {
+            app_fonts
+                .iter()
+                .position(|font| font.family_name.eq_ignore_ascii_case(name))
+        });
+    if let Some(index) = app_font {
+        let font = &app_fonts[index];
+        return Some((
+            FontKind::App(index),
+            font.font_name.clone(),
+            font.family_name.clone(),
+        ));
     }
+
+    FONT_FAMILIES.iter().find_map(|&(family, fonts)| {
+        let &(font_name, kind) = if family.eq_ignore_ascii_case(name) {
+            fonts.first()?
+        } else {
+            fonts
+                .iter()
+                .find(|(font_name, _)| font_name.eq_ignore_ascii_case(name))?
+        };
+        Some((kind, font_name.to_string(), family.to_string()))
+    })
+}
+
+fn font_for_kind(state: &mut State, kind: FontKind) -> &Font {
+    let (font, load): (&mut Option<Font>, fn() -> Font) = match kind {
+        FontKind::Regular => (&mut state.regular, Font::sans_regular),
+        FontKind::Bold => (&mut state.bold, Font::sans_bold),
+        FontKind::Italic => (&mut state.italic, Font::sans_italic),
+        FontKind::RegularJa => (&mut state.regular_ja, Font::sans_regular_ja),
+        FontKind::BoldJa => (&mut state.bold_ja, Font::sans_bold_ja),
+        FontKind::App(index) => return &state.app_fonts.as_ref().unwrap()[index].font,
+    };
+    font.get_or_insert_with(load)
 }
 
 #[rustfmt::skip]
@@ -123,37 +380,259 @@ fn get_font<'a>(state: &'a mut State, kind: FontKind, text: &str) -> &'a Font {
     // Japanese, let's fall back to Noto Sans JP when necessary.
     // FIXME: This heuristic is incomplete and a proper font fallback system
     // should be used instead.
-    for c in text.chars() {
-        let c = c as u32;
-        if (0x3000..=0x30FF).contains(&c) || // JA punctuation, kana
-           (0xFF00..=0xFFEF).contains(&c) || // full-width/half-width chars
-           (0x4e00..=0x9FA0).contains(&c) || // various kanji
-           (0x3400..=0x4DBF).contains(&c) { // more kanji
-            match kind {
-                // CJK has no italic equivalent
-                FontKind::Regular | FontKind::Italic => {
-                    if state.regular_ja.is_none() {
-                        state.regular_ja = Some(Font::sans_regular_ja());
-                    }
-                    return state.regular_ja.as_ref().unwrap();
-                },
-                FontKind::Bold => {
-                    if state.bold_ja.is_none() {
-                        state.bold_ja = Some(Font::sans_bold_ja());
-                    }
-                    return state.bold_ja.as_ref().unwrap();
-                },
+    if matches!(kind, FontKind::Regular | FontKind::Bold | FontKind::Italic) {
+        for c in text.chars() {
+            let c = c as u32;
+            if (0x3000..=0x30FF).contains(&c) || // JA punctuation, kana
+               (0xFF00..=0xFFEF).contains(&c) || // full-width/half-width chars
+               (0x4e00..=0x9FA0).contains(&c) || // various kanji
+               (0x3400..=0x4DBF).contains(&c) { // more kanji
+                let kind = match kind {
+                    // CJK has no italic equivalent
+                    FontKind::Bold => FontKind::BoldJa,
+                    _ => FontKind::RegularJa,
+                };
+                return font_for_kind(state, kind);
             }
         }
     }
 
-    match kind {
-        FontKind::Regular => state.regular.as_ref().unwrap(),
-        FontKind::Bold => state.bold.as_ref().unwrap(),
-        FontKind::Italic => state.italic.as_ref().unwrap(),
+    font_for_kind(state, kind)
+}
+
+/// For UI drawn by the host, like `UIAlertView`: get the regular or bold
+/// system font appropriate for some text, loading it if necessary.
+pub(crate) fn system_font<'a>(env: &'a mut Environment, bold: bool, text: &str) -> &'a Font {
+    styled_system_font(env, bold, false, text)
+}
+
+/// Like [system_font], but can also get the italic font. There's no bold
+/// italic font, so bold takes priority.
+pub(super) fn styled_system_font<'a>(
+    env: &'a mut Environment,
+    bold: bool,
+    italic: bool,
+    text: &str,
+) -> &'a Font {
+    let kind = if bold {
+        FontKind::Bold
+    } else if italic {
+        FontKind::Italic
+    } else {
+        FontKind::Regular
+    };
+    get_font(&mut env.framework_state.uikit.ui_font, kind, text)
+}
+
+/// For UI drawn by the host: get the size of a `UIFont`, and whether it is
+/// bold and whether it is italic.
+pub(super) fn font_style(env: &mut Environment, font: id) -> (CGFloat, bool, bool) {
+    let host_object = env.objc.borrow::<UIFontHostObject>(font);
+    (
+        host_object.size,
+        matches!(host_object.kind, FontKind::Bold | FontKind::BoldJa),
+        matches!(host_object.kind, FontKind::Italic),
+    )
+}
+
+fn size_and_kind(env: &mut Environment, font: id) -> (CGFloat, FontKind) {
+    let host_object = env.objc.borrow::<UIFontHostObject>(font);
+    (host_object.size, host_object.kind)
+}
+
+fn metrics(env: &mut Environment, font: id) -> FontMetrics {
+    let (size, kind) = size_and_kind(env, font);
+    font_for_kind(&mut env.framework_state.uikit.ui_font, kind).metrics(size)
+}
+
+fn truncation_for_mode(mode: UILineBreakMode) -> Option<Truncation> {
+    match mode {
+        UILineBreakModeHeadTruncation => Some(Truncation::Head),
+        UILineBreakModeMiddleTruncation => Some(Truncation::Middle),
+        UILineBreakModeTailTruncation => Some(Truncation::Tail),
+        _ => None,
+    }
+}
+
+/// Text broken into lines, ready to be measured or drawn.
+struct TextLayout {
+    /// Width and text of each line.
+    lines: Vec<(CGFloat, String)>,
+    metrics: FontMetrics,
+}
+impl TextLayout {
+    fn size(&self) -> CGSize {
+        let width = self
+            .lines
+            .iter()
+            .fold(0.0, |widest: CGFloat, &(width, _)| widest.max(width));
+        let height = self.metrics.line_height() * self.lines.len() as CGFloat;
+        // UIKit rounds sizes up to whole points.
+        CGSize {
+            width: width.ceil(),
+            height: height.ceil(),
+        }
     }
 }
 
+/// Break text into lines, wrapping them to fit within a size if one is given.
+/// Lines that don't fit vertically are dropped, and the last line is
+/// truncated if the line break mode asks for that.
+fn lay_out_text(
+    font: &Font,
+    font_size: CGFloat,
+    text: &str,
+    constrained: Option<(CGSize, UILineBreakMode)>,
+) -> TextLayout {
+    let metrics = font.metrics(font_size);
+    let Some((size, mode)) = constrained else {
+        let lines = font
+            .break_lines(font_size, text, None)
+            .into_iter()
+            .map(|(width, line)| (width, line.to_string()))
+            .collect();
+        return TextLayout { lines, metrics };
+    };
+
+    let wrap_mode = match mode {
+        UILineBreakModeCharacterWrap => WrapMode::Char,
+        _ => WrapMode::Word,
+    };
+    let mut lines: Vec<(CGFloat, String)> = font
+        .break_lines(font_size, text, Some((size.width, wrap_mode)))
+        .into_iter()
+        .map(|(width, line)| (width, line.to_string()))
+        .collect();
+
+    // At least one line is always kept, even if it's too tall.
+    let max_lines = ((size.height / metrics.line_height()).floor() as usize).max(1);
+    let truncation = truncation_for_mode(mode);
+    if lines.len() > max_lines {
+        let dropped = lines.split_off(max_lines);
+        if truncation.is_some() {
+            // Join the dropped text onto the last line, so truncating it
+            // puts the ellipsis in the right place.
+            let (_, last) = lines.pop().unwrap();
+            let mut pieces = vec![last.trim()];
+            pieces.extend(dropped.iter().map(|(_, line)| line.trim()));
+            let last = pieces.join(" ");
+            lines.push((font.calculate_line_width(font_size, &last), last));
+        }
+    }
+    if let (Some(truncation), Some(last)) = (truncation, lines.last_mut()) {
+        let truncated = font.truncate_line(font_size, &last.1, size.width, truncation);
+        *last = (font.calculate_line_width(font_size, &truncated), truncated);
+    }
+    TextLayout { lines, metrics }
+}
+
+/// Lay out text on a single line for the `forWidth:` method family. If the
+/// text is too wide, the font size is reduced, down to `min_font_size` if one
+/// is given, and then the text is cut short according to the line break mode.
+/// Returns the layout and the font size used.
+fn lay_out_line(
+    font: &Font,
+    mut font_size: CGFloat,
+    min_font_size: Option<CGFloat>,
+    text: &str,
+    width: CGFloat,
+    mode: UILineBreakMode,
+) -> (TextLayout, CGFloat) {
+    // There's only one line, so line breaks are treated like spaces.
+    let text = text.replace(['\r', '\n'], " ");
+    if let Some(min_font_size) = min_font_size {
+        while font_size > min_font_size && font.calculate_line_width(font_size, &text) > width {
+            font_size = (font_size - 1.0).max(min_font_size);
+        }
+    }
+
+    let line = if let Some(truncation) = truncation_for_mode(mode) {
+        font.truncate_line(font_size, &text, width, truncation)
+    } else {
+        let wrap_mode = match mode {
+            UILineBreakModeWordWrap => WrapMode::Word,
+            _ => WrapMode::Char,
+        };
+        font.break_lines(font_size, &text, Some((width, wrap_mode)))
+            .first()
+            .map(|&(_, line)| line.trim_end().to_string())
+            .unwrap_or_default()
+    };
+    let layout = TextLayout {
+        lines: vec![(font.calculate_line_width(font_size, &line), line)],
+        metrics: font.metrics(font_size),
+    };
+    (layout, font_size)
+}
+
+fn add_outline(path: &mut Path, outline: &[OutlineSegment], origin: CGPoint) {
+    let p = |x: CGFloat, y: CGFloat| CGPoint {
+        x: origin.x + x,
+        y: origin.y + y,
+    };
+    for &segment in outline {
+        match segment {
+            OutlineSegment::MoveTo(x, y) => path.move_to(p(x, y)),
+            OutlineSegment::LineTo(x, y) => path.line_to(p(x, y)),
+            OutlineSegment::QuadTo(x1, y1, x, y) => path.quad_curve_to(p(x1, y1), p(x, y)),
+            OutlineSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+                path.curve_to(p(x1, y1), p(x2, y2), p(x, y))
+            }
+            OutlineSegment::Close => path.close(),
+        }
+    }
+}
+
+/// Get the outlines of laid-out text as a path. `origin` is the top-left
+/// corner of the first line, and lines are aligned within `width`.
+fn text_path(
+    font: &Font,
+    font_size: CGFloat,
+    layout: &TextLayout,
+    origin: CGPoint,
+    width: CGFloat,
+    alignment: UITextAlignment,
+) -> Path {
+    let mut path = Path::default();
+    for (i, (line_width, line)) in layout.lines.iter().enumerate() {
+        let x_offset = match alignment {
+            UITextAlignmentLeft => 0.0,
+            UITextAlignmentCenter => (width - line_width) / 2.0,
+            UITextAlignmentRight => width - line_width,
+            _ => unimplemented!("TODO: text alignment {}", alignment),
+        };
+        let baseline = CGPoint {
+            x: origin.x + x_offset,
+            y: origin.y + layout.metrics.ascender + layout.metrics.line_height() * i as CGFloat,
+        };
+        add_outline(&mut path, &font.line_outline(font_size, line), baseline);
+    }
+    path
+}
+
+/// Fill the outlines of some text in the current context, with the current
+/// fill color unless another is given.
+fn fill_text(
+    env: &mut Environment,
+    path: &Path,
+    color: Option<(CGFloat, CGFloat, CGFloat, CGFloat)>,
+    clip: Option<CGRect>,
+) {
+    let context = UIGraphicsGetCurrentContext(env);
+    if context == nil {
+        return;
+    }
+    CGContextSaveGState(env, context);
+    if let Some((r, g, b, a)) = color {
+        CGContextSetRGBFillColor(env, context, r, g, b, a);
+    }
+    if let Some(clip) = clip {
+        CGContextClipToRect(env, context, clip);
+    }
+    cg_context::fill_path(env, context, path);
+    CGContextRestoreGState(env, context);
+}
+
 /// Called by the `sizeWithFont:` method family on `NSString`.
 pub fn size_with_font(
     env: &mut Environment,
@@ -161,19 +640,26 @@ pub fn size_with_font(
     text: &str,
     constrained: Option<(CGSize, UILineBreakMode)>,
 ) -> CGSize {
-    let host_object = env.objc.borrow::<UIFontHostObject>(font);
-
-    let font = get_font(
-        &mut env.framework_state.uikit.ui_font,
-        host_object.kind,
-        text,
-    );
-
-    let wrap = constrained.map(|(size, ui_mode)| (size.width, convert_line_break_mode(ui_mode)));
-
-    let (width, height) = font.calculate_text_size(host_object.size, text, wrap);
+    let (font_size, kind) = size_and_kind(env, font);
+    let font = get_font(&mut env.framework_state.uikit.ui_font, kind, text);
+    lay_out_text(font, font_size, text, constrained).size()
+}
 
-    CGSize { width, height }
+/// Called by the `sizeWithFont:forWidth:lineBreakMode:` method family on
+/// `NSString`. Returns the size and the font size used.
+pub fn size_with_font_for_width(
+    env: &mut Environment,
+    font: id,
+    text: &str,
+    width: CGFloat,
+    line_break_mode: UILineBreakMode,
+    min_font_size: Option<CGFloat>,
+) -> (CGSize, CGFloat) {
+    let (font_size, kind) = size_and_kind(env, font);
+    let font = get_font(&mut env.framework_state.uikit.ui_font, kind, text);
+    let (layout, font_size) =
+        lay_out_line(font, font_size, min_font_size, text, width, line_break_mode);
+    (layout.size(), font_size)
 }
 
 /// Called by the `drawInRect:` method family on `NSString`.
@@ -185,41 +671,188 @@ pub fn draw_in_rect(
     line_break_mode: UILineBreakMode,
     alignment: UITextAlignment,
 ) -> CGSize {
-    let context = UIGraphicsGetCurrentContext(env);
+    let (font_size, kind) = size_and_kind(env, font);
+    let font = get_font(&mut env.framework_state.uikit.ui_font, kind, text);
+    let layout = lay_out_text(font, font_size, text, Some((rect.size, line_break_mode)));
+    let path = text_path(
+        font,
+        font_size,
+        &layout,
+        rect.origin,
+        rect.size.width,
+        alignment,
+    );
+    let clip = (line_break_mode == UILineBreakModeClip).then_some(rect);
+    fill_text(env, &path, None, clip);
+    layout.size()
+}
 
-    let text_size = size_with_font(env, font, text, Some((rect.size, line_break_mode)));
+/// Called by the `drawAtPoint:` method family on `NSString`. The text is
+/// limited to a single line if `for_width` is given. `font_size` overrides
+/// the font's size, and `baseline_adjustment` says where to put the text if
+/// it gets shrunk. Returns the size and the font size used.
+pub fn draw_at_point(
+    env: &mut Environment,
+    font: id,
+    text: &str,
+    point: CGPoint,
+    for_width: Option<(CGFloat, UILineBreakMode)>,
+    font_size: Option<CGFloat>,
+    min_font_size: Option<CGFloat>,
+    baseline_adjustment: UIBaselineAdjustment,
+) -> (CGSize, CGFloat) {
+    let (default_font_size, kind) = size_and_kind(env, font);
+    let requested_font_size = font_size.unwrap_or(default_font_size);
+    let font = get_font(&mut env.framework_state.uikit.ui_font, kind, text);
+    let (layout, font_size) = match for_width {
+        Some((width, mode)) => {
+            lay_out_line(font, requested_font_size, min_font_size, text, width, mode)
+        }
+        None => (
+            lay_out_text(font, requested_font_size, text, None),
+            requested_font_size,
+        ),
+    };
 
-    let host_object = env.objc.borrow::<UIFontHostObject>(font);
+    let requested = font.metrics(requested_font_size);
+    let top = match baseline_adjustment {
+        UIBaselineAdjustmentAlignBaselines => {
+            point.y + requested.ascender - layout.metrics.ascender
+        }
+        UIBaselineAdjustmentAlignCenters => {
+            point.y + (requested.line_height() - layout.metrics.line_height()) / 2.0
+        }
+        _ => point.y,
+    };
+    let origin = CGPoint { x: point.x, y: top };
+    let path = text_path(font, font_size, &layout, origin, 0.0, UITextAlignmentLeft);
+    fill_text(env, &path, None, None);
+    (layout.size(), font_size)
+}
 
-    let font = get_font(
-        &mut env.framework_state.uikit.ui_font,
-        host_object.kind,
-        text,
-    );
+/// For Core Text: get the width of a line of text in a `UIFont`.
+pub fn text_width(env: &mut Environment, font: id, text: &str) -> CGFloat {
+    let (font_size, kind) = size_and_kind(env, font);
+    let font = get_font(&mut env.framework_state.uikit.ui_font, kind, text);
+    font.calculate_line_width(font_size, text)
+}
 
-    let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
+/// For Core Text: get the metrics of the font used to draw some text with a
+/// `UIFont`.
+pub fn text_metrics(env: &mut Environment, font: id, text: &str) -> FontMetrics {
+    let (font_size, kind) = size_and_kind(env, font);
+    let font = get_font(&mut env.framework_state.uikit.ui_font, kind, text);
+    font.metrics(font_size)
+}
 
-    let fill_color = drawer.rgb_fill_color();
+/// For Core Text: get the outline of a line of text in a `UIFont`, in points,
+/// with y pointing down and the origin at the start of the baseline.
+pub fn text_outline(env: &mut Environment, font: id, text: &str) -> Vec<OutlineSegment> {
+    let (font_size, kind) = size_and_kind(env, font);
+    let font = get_font(&mut env.framework_state.uikit.ui_font, kind, text);
+    font.line_outline(font_size, text)
+}
 
-    let (origin_x_offset, alignment) = match alignment {
-        UITextAlignmentLeft => (0.0, TextAlignment::Left),
-        UITextAlignmentCenter => (rect.size.width / 2.0, TextAlignment::Center),
-        UITextAlignmentRight => (rect.size.width, TextAlignment::Right),
-        _ => unimplemented!(),
-    };
+/// A piece of text with a single font and color, for drawing attributed
+/// strings with [size_with_runs] and [draw_runs].
+pub struct TextRun {
+    pub text: String,
+    /// `UIFont*`
+    pub font: id,
+    pub color: (CGFloat, CGFloat, CGFloat, CGFloat),
+}
 
-    font.draw(
-        host_object.size,
-        text,
-        (rect.origin.x + origin_x_offset, rect.origin.y),
-        Some((rect.size.width, convert_line_break_mode(line_break_mode))),
-        alignment,
-        |(x, y), coverage| {
-            let (r, g, b, a) = fill_color;
-            let (r, g, b, a) = (r * coverage, g * coverage, b * coverage, a * coverage);
-            drawer.put_pixel((x, y), (r, g, b, a));
-        },
-    );
+/// Part of a line of an attributed string.
+struct RunPiece {
+    run_idx: usize,
+    text: String,
+    width: CGFloat,
+    metrics: FontMetrics,
+}
+
+/// Split runs into lines at newlines, and measure each piece. Returns the
+/// pieces of each line, along with each line's width, ascender and height.
+///
+/// TODO: Wrapping. Each line is currently laid out as a single line.
+fn lay_out_runs(
+    env: &mut Environment,
+    runs: &[TextRun],
+) -> Vec<(Vec<RunPiece>, CGFloat, CGFloat, CGFloat)> {
+    let mut lines = vec![Vec::new()];
+    for (run_idx, run) in runs.iter().enumerate() {
+        let (font_size, kind) = size_and_kind(env, run.font);
+        for (i, piece) in run.text.split('\n').enumerate() {
+            if i != 0 {
+                lines.push(Vec::new());
+            }
+            // Empty pieces are kept so that empty lines have a height.
+            let font = get_font(&mut env.framework_state.uikit.ui_font, kind, piece);
+            lines.last_mut().unwrap().push(RunPiece {
+                run_idx,
+                text: piece.to_string(),
+                width: font.calculate_line_width(font_size, piece),
+                metrics: font.metrics(font_size),
+            });
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|pieces| {
+            let width = pieces.iter().map(|piece| piece.width).sum();
+            let ascender = pieces.iter().fold(0.0, |highest: CGFloat, piece| {
+                highest.max(piece.metrics.ascender)
+            });
+            let height = pieces.iter().fold(0.0, |tallest: CGFloat, piece| {
+                tallest.max(piece.metrics.line_height())
+            });
+            (pieces, width, ascender, height)
+        })
+        .collect()
+}
+
+/// Called by the `size` method of `NSAttributedString`.
+pub fn size_with_runs(env: &mut Environment, runs: &[TextRun]) -> CGSize {
+    let lines = lay_out_runs(env, runs);
+    let width = lines
+        .iter()
+        .fold(0.0, |widest: CGFloat, &(_, width, _, _)| widest.max(width));
+    let height: CGFloat = lines.iter().map(|&(_, _, _, height)| height).sum();
+    CGSize {
+        width: width.ceil(),
+        height: height.ceil(),
+    }
+}
+
+/// Called by the `drawAtPoint:` and `drawInRect:` methods of
+/// `NSAttributedString`.
+pub fn draw_runs(env: &mut Environment, runs: &[TextRun], origin: CGPoint) -> CGSize {
+    let size = size_with_runs(env, runs);
+    let lines = lay_out_runs(env, runs);
+
+    let mut line_top = origin.y;
+    for (pieces, _, ascender, height) in lines {
+        let mut x = origin.x;
+        for piece in pieces {
+            let run = &runs[piece.run_idx];
+            let (font_size, kind) = size_and_kind(env, run.font);
+            let font = get_font(&mut env.framework_state.uikit.ui_font, kind, &piece.text);
+            // Pieces with different fonts share a baseline.
+            let baseline = CGPoint {
+                x,
+                y: line_top + ascender,
+            };
+            let mut path = Path::default();
+            add_outline(
+                &mut path,
+                &font.line_outline(font_size, &piece.text),
+                baseline,
+            );
+            fill_text(env, &path, Some(run.color), None);
+            x += piece.width;
+        }
+        line_top += height;
+    }
 
-    text_size
+    size
 }
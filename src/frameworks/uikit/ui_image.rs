@@ -0,0 +1,165 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIImage`.
+//!
+//! Decoding is done by [crate::image], so PNG, JPEG, GIF and BMP are
+//! supported. EXIF orientation is applied when decoding, so the orientation of
+//! a `UIImage` is always `UIImageOrientationUp`.
+
+use crate::frameworks::core_graphics::cg_image::{self, CGImageRef, CGImageRelease, CGImageRetain};
+use crate::frameworks::core_graphics::{CGFloat, CGSize};
+use crate::frameworks::foundation::{ns_data, ns_string, NSInteger};
+use crate::fs::GuestPath;
+use crate::image::Image;
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    ObjC,
+};
+use crate::Environment;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct State {
+    /// Images loaded by `imageNamed:`, which are cached forever, keyed by the
+    /// name they were requested with.
+    named_images: HashMap<String, id>,
+}
+
+pub type UIImageOrientation = NSInteger;
+pub const UIImageOrientationUp: UIImageOrientation = 0;
+
+struct UIImageHostObject {
+    cg_image: CGImageRef,
+}
+impl HostObject for UIImageHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIImage: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(UIImageHostObject { cg_image: nil });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)imageNamed:(id)name { // NSString*
+    let name_string = ns_string::to_rust_string(env, name).into_owned();
+    if let Some(&image) = env.framework_state.uikit.ui_image.named_images.get(&name_string) {
+        return image;
+    }
+
+    // Names without an extension are assumed to be PNG files.
+    let file_name = if name_string.contains('.') {
+        name
+    } else {
+        let file_name = ns_string::from_rust_string(env, format!("{}.png", name_string));
+        autorelease(env, file_name)
+    };
+    let bundle: id = msg_class![env; NSBundle mainBundle];
+    let path: id = msg![env; bundle pathForResource:file_name ofType:nil];
+    if path == nil {
+        log!("Warning: [UIImage imageNamed:{:?}] => nil (not found)", name_string);
+        return nil;
+    }
+    let image: id = msg![env; this alloc];
+    let image: id = msg![env; image initWithContentsOfFile:path];
+    if image != nil {
+        // The cache owns this reference.
+        env.framework_state
+            .uikit
+            .ui_image
+            .named_images
+            .insert(name_string, image);
+    }
+    image
+}
+
++ (id)imageWithContentsOfFile:(id)path { // NSString*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithContentsOfFile:path];
+    autorelease(env, new)
+}
++ (id)imageWithData:(id)data { // NSData*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithData:data];
+    autorelease(env, new)
+}
++ (id)imageWithCGImage:(CGImageRef)cg_image {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCGImage:cg_image];
+    autorelease(env, new)
+}
+
+- (id)initWithContentsOfFile:(id)path { // NSString*
+    let path = ns_string::to_rust_string(env, path).into_owned();
+    let Ok(bytes) = env.fs.read(GuestPath::new(&path)) else {
+        log!("Warning: couldn't read image file {:?}", path);
+        release(env, this);
+        return nil;
+    };
+    init_with_bytes(env, this, &bytes, &path)
+}
+- (id)initWithData:(id)data { // NSData*
+    let bytes = ns_data::to_vec(env, data);
+    init_with_bytes(env, this, &bytes, "NSData")
+}
+- (id)initWithCGImage:(CGImageRef)cg_image {
+    let cg_image = CGImageRetain(env, cg_image);
+    env.objc.borrow_mut::<UIImageHostObject>(this).cg_image = cg_image;
+    this
+}
+
+- (())dealloc {
+    let cg_image = env.objc.borrow::<UIImageHostObject>(this).cg_image;
+    CGImageRelease(env, cg_image);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (CGImageRef)CGImage {
+    env.objc.borrow::<UIImageHostObject>(this).cg_image
+}
+
+- (CGSize)size {
+    let cg_image = env.objc.borrow::<UIImageHostObject>(this).cg_image;
+    let (width, height) = cg_image::borrow_image(&env.objc, cg_image).dimensions();
+    CGSize {
+        width: width as CGFloat,
+        height: height as CGFloat,
+    }
+}
+
+- (UIImageOrientation)imageOrientation {
+    UIImageOrientationUp
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(MutVoidPtr)_zone {
+    retain(env, this)
+}
+
+@end
+
+};
+
+/// For UI drawn by the host: borrow the decoded image a `UIImage` wraps.
+pub(crate) fn borrow_image(objc: &ObjC, image: id) -> &Image {
+    let cg_image = objc.borrow::<UIImageHostObject>(image).cg_image;
+    cg_image::borrow_image(objc, cg_image)
+}
+
+fn init_with_bytes(env: &mut Environment, this: id, bytes: &[u8], source: &str) -> id {
+    let Ok(image) = Image::from_bytes(bytes) else {
+        log!("Warning: couldn't decode image from {:?}", source);
+        release(env, this);
+        return nil;
+    };
+    let cg_image = cg_image::from_image(env, image);
+    env.objc.borrow_mut::<UIImageHostObject>(this).cg_image = cg_image;
+    this
+}
@@ -0,0 +1,567 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIImagePickerController`.
+//!
+//! There's no photo library or camera, so instead the picker offers the images
+//! in a directory on the host (see the `--photos-dir=` option). While a picker
+//! is presented, the list of images is drawn by the host as an overlay (see
+//! [super::overlay]) and takes all new touches.
+
+use super::overlay::{contains, rect, Canvas, Overlay};
+use super::ui_font;
+use super::ui_view_controller::UIViewControllerHostObject;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::cg_image::{self, CGImageRelease};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::foundation::ns_dictionary::dict_from_keys_and_objects;
+use crate::frameworks::foundation::ns_string::get_static_str;
+use crate::frameworks::foundation::{ns_array, NSInteger};
+use crate::image::Image;
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    SEL,
+};
+use crate::window::{Event, TouchSource};
+use crate::Environment;
+
+pub type UIImagePickerControllerSourceType = NSInteger;
+pub const UIImagePickerControllerSourceTypePhotoLibrary: UIImagePickerControllerSourceType = 0;
+pub const UIImagePickerControllerSourceTypeCamera: UIImagePickerControllerSourceType = 1;
+pub const UIImagePickerControllerSourceTypeSavedPhotosAlbum: UIImagePickerControllerSourceType = 2;
+
+pub const UIImagePickerControllerMediaType: &str = "UIImagePickerControllerMediaType";
+pub const UIImagePickerControllerOriginalImage: &str = "UIImagePickerControllerOriginalImage";
+pub const UIImagePickerControllerEditedImage: &str = "UIImagePickerControllerEditedImage";
+
+/// The uniform type identifier for images, the only media type available.
+const kUTTypeImage: &str = "public.image";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UIImagePickerControllerMediaType",
+        HostConstant::NSString(UIImagePickerControllerMediaType),
+    ),
+    (
+        "_UIImagePickerControllerOriginalImage",
+        HostConstant::NSString(UIImagePickerControllerOriginalImage),
+    ),
+    (
+        "_UIImagePickerControllerEditedImage",
+        HostConstant::NSString(UIImagePickerControllerEditedImage),
+    ),
+];
+
+#[derive(Default)]
+pub struct State {
+    /// The images offered by the presented picker, if there is one.
+    browser: Option<Browser>,
+    /// Cached drawing of the browser, cleared when it needs redrawing.
+    overlay: Option<Overlay>,
+    /// The touch being tracked by the browser, if any, with the location and
+    /// scroll offset when it began.
+    touch: Option<(TouchSource, (f32, f32), CGFloat)>,
+}
+
+struct Browser {
+    /// Weak reference. The presenting view controller owns the picker.
+    picker: id,
+    photos: Vec<Photo>,
+    /// Distance scrolled down the list, in points.
+    scroll: CGFloat,
+    /// Index of the photo under the current touch.
+    pressed: Option<usize>,
+}
+
+struct Photo {
+    name: String,
+    image: Image,
+}
+
+pub(super) struct PickerState {
+    source_type: UIImagePickerControllerSourceType,
+    allows_editing: bool,
+}
+
+const NAV_BAR_HEIGHT: CGFloat = 44.0;
+const ROW_HEIGHT: CGFloat = 60.0;
+const THUMBNAIL_SIZE: CGFloat = 56.0;
+const TITLE_SIZE: CGFloat = 20.0;
+const NAME_SIZE: CGFloat = 17.0;
+const BUTTON_TITLE_SIZE: CGFloat = 13.0;
+/// How far a touch can move before it scrolls instead of picking.
+const TAP_SLOP: f32 = 10.0;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIImagePickerController: UINavigationController
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let mut host_object = UIViewControllerHostObject::new();
+    host_object.image_picker = Some(Box::new(PickerState {
+        source_type: UIImagePickerControllerSourceTypePhotoLibrary,
+        allows_editing: false,
+    }));
+    env.objc.alloc_object(this, Box::new(host_object), &mut env.mem)
+}
+
++ (bool)isSourceTypeAvailable:(UIImagePickerControllerSourceType)source_type {
+    matches!(
+        source_type,
+        UIImagePickerControllerSourceTypePhotoLibrary
+            | UIImagePickerControllerSourceTypeSavedPhotosAlbum
+    )
+}
++ (id)availableMediaTypesForSourceType:(UIImagePickerControllerSourceType)source_type {
+    if !msg![env; this isSourceTypeAvailable:source_type] {
+        return nil;
+    }
+    let image_type = get_static_str(env, kUTTypeImage);
+    retain(env, image_type);
+    let types = ns_array::from_vec(env, vec![image_type]);
+    autorelease(env, types)
+}
+
+- (UIImagePickerControllerSourceType)sourceType {
+    picker_state(env, this).source_type
+}
+- (())setSourceType:(UIImagePickerControllerSourceType)source_type {
+    if source_type == UIImagePickerControllerSourceTypeCamera {
+        log!("Warning: There's no camera, the image picker will show photos instead.");
+    }
+    picker_state(env, this).source_type = source_type;
+}
+
+- (bool)allowsEditing {
+    picker_state(env, this).allows_editing
+}
+- (())setAllowsEditing:(bool)allows_editing {
+    picker_state(env, this).allows_editing = allows_editing;
+}
+// Deprecated name from iPhone OS 2.x.
+- (bool)allowsImageEditing {
+    msg![env; this allowsEditing]
+}
+- (())setAllowsImageEditing:(bool)allows_editing {
+    msg![env; this setAllowsEditing:allows_editing]
+}
+
+- (id)mediaTypes {
+    msg_class![env; UIImagePickerController
+                    availableMediaTypesForSourceType:UIImagePickerControllerSourceTypePhotoLibrary]
+}
+- (())setMediaTypes:(id)_media_types { // NSArray*
+    // Only images are available anyway.
+}
+
+@end
+
+};
+
+fn picker_state(env: &mut Environment, picker: id) -> &mut PickerState {
+    env.objc
+        .borrow_mut::<UIViewControllerHostObject>(picker)
+        .image_picker
+        .as_mut()
+        .unwrap()
+}
+
+/// For use by `UIViewController`: show the browser when a picker is presented.
+pub(super) fn present(env: &mut Environment, picker: id) {
+    let dir = &env.options.photos_dir;
+    let mut photos = Vec::new();
+    match std::fs::read_dir(dir) {
+        Ok(entries) => {
+            let mut paths: Vec<_> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file())
+                .collect();
+            paths.sort();
+            for path in paths {
+                let Ok(bytes) = std::fs::read(&path) else {
+                    continue;
+                };
+                let Ok(image) = Image::from_bytes(&bytes) else {
+                    log_dbg!("Skipping {:?}, not a supported image", path);
+                    continue;
+                };
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                photos.push(Photo { name, image });
+            }
+        }
+        Err(e) => {
+            log!(
+                "Warning: Couldn't read photos directory {:?} ({}), the image picker will be empty.",
+                dir,
+                e
+            );
+        }
+    }
+    log_dbg!("Image picker {:?} offering {} photos", picker, photos.len());
+
+    let state = &mut env.framework_state.uikit.ui_image_picker_controller;
+    state.browser = Some(Browser {
+        picker,
+        photos,
+        scroll: 0.0,
+        pressed: None,
+    });
+    state.overlay = None;
+    state.touch = None;
+}
+
+/// For use by `UIViewController`: hide the browser when a picker is
+/// dismissed.
+pub(super) fn dismiss(env: &mut Environment, picker: id) {
+    let state = &mut env.framework_state.uikit.ui_image_picker_controller;
+    if matches!(state.browser, Some(ref browser) if browser.picker == picker) {
+        state.browser = None;
+        state.overlay = None;
+        state.touch = None;
+    }
+}
+
+fn cancel_button_rect(env: &mut Environment) -> CGRect {
+    let (points_width, _) = env.window.size_unrotated_unscaled();
+    rect(points_width as CGFloat - 70.0, 7.0, 62.0, 30.0)
+}
+
+fn row_rect(env: &mut Environment, browser: &Browser, index: usize) -> CGRect {
+    let (points_width, _) = env.window.size_unrotated_unscaled();
+    rect(
+        0.0,
+        NAV_BAR_HEIGHT + index as CGFloat * ROW_HEIGHT - browser.scroll,
+        points_width as CGFloat,
+        ROW_HEIGHT,
+    )
+}
+
+fn photo_at(env: &mut Environment, browser: &Browser, location: (f32, f32)) -> Option<usize> {
+    if location.1 < NAV_BAR_HEIGHT {
+        return None;
+    }
+    (0..browser.photos.len()).find(|&index| contains(row_rect(env, browser, index), location))
+}
+
+fn max_scroll(env: &mut Environment, browser: &Browser) -> CGFloat {
+    let (_, points_height) = env.window.size_unrotated_unscaled();
+    let content_height = browser.photos.len() as CGFloat * ROW_HEIGHT;
+    (content_height - (points_height as CGFloat - NAV_BAR_HEIGHT)).max(0.0)
+}
+
+/// Scale an image's size to fit within a square, keeping it centered.
+fn aspect_fit(image: &Image, bounds: CGRect) -> CGRect {
+    let (width, height) = image.dimensions();
+    let scale = (bounds.size.width / width.max(1) as CGFloat)
+        .min(bounds.size.height / height.max(1) as CGFloat);
+    let (width, height) = (width as CGFloat * scale, height as CGFloat * scale);
+    rect(
+        bounds.origin.x + (bounds.size.width - width) / 2.0,
+        bounds.origin.y + (bounds.size.height - height) / 2.0,
+        width,
+        height,
+    )
+}
+
+fn draw(env: &mut Environment, browser: &Browser) -> Overlay {
+    let (points_width, points_height) = env.window.size_unrotated_unscaled();
+    let (points_width, points_height) = (points_width as CGFloat, points_height as CGFloat);
+    let mut canvas = Canvas::new(env);
+
+    canvas.fill_rounded_rect(
+        rect(0.0, 0.0, points_width, points_height),
+        0.0,
+        (1.0, 1.0, 1.0, 1.0),
+    );
+
+    canvas.set_clip(Some(rect(
+        0.0,
+        NAV_BAR_HEIGHT,
+        points_width,
+        points_height - NAV_BAR_HEIGHT,
+    )));
+    if browser.photos.is_empty() {
+        let message = format!(
+            "No photos. Put some images in {:?} to choose from them here.",
+            env.options.photos_dir
+        );
+        let font = ui_font::system_font(env, false, &message);
+        canvas.draw_text(
+            font,
+            NAME_SIZE,
+            &message,
+            rect(20.0, NAV_BAR_HEIGHT + 40.0, points_width - 40.0, 200.0),
+            (0.5, 0.5, 0.5, 1.0),
+        );
+    }
+    for (index, photo) in browser.photos.iter().enumerate() {
+        let row = row_rect(env, browser, index);
+        if row.origin.y + ROW_HEIGHT < NAV_BAR_HEIGHT || row.origin.y > points_height {
+            continue;
+        }
+        let text_color = if browser.pressed == Some(index) {
+            canvas.fill_rounded_rect(row, 0.0, (0.02, 0.45, 0.9, 1.0));
+            (1.0, 1.0, 1.0, 1.0)
+        } else {
+            (0.0, 0.0, 0.0, 1.0)
+        };
+        let thumbnail = rect(
+            4.0,
+            row.origin.y + (ROW_HEIGHT - THUMBNAIL_SIZE) / 2.0,
+            THUMBNAIL_SIZE,
+            THUMBNAIL_SIZE,
+        );
+        canvas.draw_image(&photo.image, aspect_fit(&photo.image, thumbnail));
+        let font = ui_font::system_font(env, true, &photo.name);
+        canvas.draw_text_at(
+            font,
+            NAME_SIZE,
+            &photo.name,
+            CGPoint {
+                x: THUMBNAIL_SIZE + 14.0,
+                y: row.origin.y + (ROW_HEIGHT - NAME_SIZE) / 2.0,
+            },
+            text_color,
+        );
+        canvas.fill_rounded_rect(
+            rect(0.0, row.origin.y + ROW_HEIGHT - 1.0, points_width, 1.0),
+            0.0,
+            (0.88, 0.88, 0.88, 1.0),
+        );
+    }
+    canvas.set_clip(None);
+
+    canvas.fill_rounded_rect(
+        rect(0.0, 0.0, points_width, NAV_BAR_HEIGHT),
+        0.0,
+        (0.43, 0.52, 0.63, 1.0),
+    );
+    let title = "Photos";
+    let font = ui_font::system_font(env, true, title);
+    canvas.draw_text_centered(
+        font,
+        TITLE_SIZE,
+        title,
+        rect(0.0, 0.0, points_width, NAV_BAR_HEIGHT),
+        (1.0, 1.0, 1.0, 1.0),
+    );
+    let cancel = cancel_button_rect(env);
+    canvas.fill_rounded_rect(cancel, 5.0, (0.29, 0.38, 0.5, 1.0));
+    let title = "Cancel";
+    let font = ui_font::system_font(env, true, title);
+    canvas.draw_text_centered(font, BUTTON_TITLE_SIZE, title, cancel, (1.0, 1.0, 1.0, 1.0));
+
+    canvas.into_overlay()
+}
+
+/// For use by [super::overlay]: redraw the browser if needed.
+pub(super) fn update_overlay(env: &mut Environment) {
+    let state = &mut env.framework_state.uikit.ui_image_picker_controller;
+    if state.overlay.is_some() {
+        return;
+    }
+    // Taken out temporarily so that the state isn't borrowed while drawing.
+    let Some(browser) = state.browser.take() else {
+        return;
+    };
+    let overlay = draw(env, &browser);
+    let state = &mut env.framework_state.uikit.ui_image_picker_controller;
+    state.browser = Some(browser);
+    state.overlay = Some(overlay);
+}
+
+/// For use by [super::overlay]: get the drawing of the browser, if a picker
+/// is presented.
+pub(super) fn current_overlay(state: &State) -> Option<&Overlay> {
+    if state.browser.is_none() {
+        None
+    } else {
+        state.overlay.as_ref()
+    }
+}
+
+/// Give the chosen photo to the picker's delegate.
+fn pick(env: &mut Environment, index: usize) {
+    let state = &mut env.framework_state.uikit.ui_image_picker_controller;
+    let browser = state.browser.take().unwrap();
+    state.overlay = None;
+    let picker = browser.picker;
+    let photo = browser.photos.into_iter().nth(index).unwrap();
+    log_dbg!("Image picker {:?} picked {:?}", picker, photo.name);
+
+    let cg_image = cg_image::from_image(env, photo.image);
+    let image: id = msg_class![env; UIImage alloc];
+    let image: id = msg![env; image initWithCGImage:cg_image];
+    CGImageRelease(env, cg_image);
+
+    let allows_editing = picker_state(env, picker).allows_editing;
+    let delegate = env
+        .objc
+        .borrow::<UIViewControllerHostObject>(picker)
+        .delegate;
+    if let Some(selector) = responding_selector(
+        env,
+        delegate,
+        "imagePickerController:didFinishPickingMediaWithInfo:",
+    ) {
+        let mut keys_and_objects = vec![
+            (
+                get_static_str(env, UIImagePickerControllerMediaType),
+                get_static_str(env, kUTTypeImage),
+            ),
+            (
+                get_static_str(env, UIImagePickerControllerOriginalImage),
+                image,
+            ),
+        ];
+        // TODO: actually let the user edit the image
+        if allows_editing {
+            keys_and_objects.push((
+                get_static_str(env, UIImagePickerControllerEditedImage),
+                image,
+            ));
+        }
+        let info = dict_from_keys_and_objects(env, &keys_and_objects);
+        let () = msg_send(env, (delegate, selector, picker, info));
+        release(env, info);
+    } else if let Some(selector) = responding_selector(
+        env,
+        delegate,
+        "imagePickerController:didFinishPickingImage:editingInfo:",
+    ) {
+        let () = msg_send(env, (delegate, selector, picker, image, nil));
+    } else {
+        log!(
+            "Warning: Delegate of image picker {:?} doesn't handle picked images",
+            picker
+        );
+    }
+    release(env, image);
+}
+
+fn cancel(env: &mut Environment) {
+    let state = &mut env.framework_state.uikit.ui_image_picker_controller;
+    let picker = state.browser.take().unwrap().picker;
+    state.overlay = None;
+    log_dbg!("Image picker {:?} cancelled", picker);
+
+    let delegate = env
+        .objc
+        .borrow::<UIViewControllerHostObject>(picker)
+        .delegate;
+    if let Some(selector) = responding_selector(env, delegate, "imagePickerControllerDidCancel:") {
+        let () = msg_send(env, (delegate, selector, picker));
+    } else {
+        // Without the delegate method, the picker dismisses itself.
+        let () = msg![env; picker dismissModalViewControllerAnimated:true];
+    }
+}
+
+fn responding_selector(env: &mut Environment, object: id, name: &str) -> Option<SEL> {
+    if object == nil {
+        return None;
+    }
+    let selector = env.objc.lookup_selector(name)?;
+    if msg![env; object respondsToSelector:selector] {
+        Some(selector)
+    } else {
+        None
+    }
+}
+
+fn set_pressed(env: &mut Environment, pressed: Option<usize>) {
+    let state = &mut env.framework_state.uikit.ui_image_picker_controller;
+    let browser = state.browser.as_mut().unwrap();
+    if browser.pressed != pressed {
+        browser.pressed = pressed;
+        state.overlay = None;
+    }
+}
+
+/// For use by [super::handle_events]: handle a touch event if a picker is
+/// presented. Returns [true] if the event was consumed.
+pub(super) fn handle_event(env: &mut Environment, event: &Event) -> bool {
+    let state = &mut env.framework_state.uikit.ui_image_picker_controller;
+    let Some(browser) = state.browser.take() else {
+        return false;
+    };
+    let tracked_touch = state.touch;
+    let photo = match *event {
+        Event::TouchDown(_, location)
+        | Event::TouchMove(_, location)
+        | Event::TouchUp(_, location) => photo_at(env, &browser, location),
+        _ => None,
+    };
+    let max_scroll = max_scroll(env, &browser);
+    let scroll = browser.scroll;
+    env.framework_state.uikit.ui_image_picker_controller.browser = Some(browser);
+
+    match *event {
+        Event::TouchDown(source, location) => {
+            if tracked_touch.is_some() {
+                return true;
+            }
+            env.framework_state.uikit.ui_image_picker_controller.touch =
+                Some((source, location, scroll));
+            set_pressed(env, photo);
+            true
+        }
+        Event::TouchMove(source, (x, y)) => {
+            let Some((tracked_source, (start_x, start_y), start_scroll)) = tracked_touch else {
+                // A touch that began before the picker appeared still belongs
+                // to the app.
+                return false;
+            };
+            if tracked_source != source {
+                return true;
+            }
+            if (x - start_x).abs() > TAP_SLOP || (y - start_y).abs() > TAP_SLOP {
+                set_pressed(env, None);
+                let state = &mut env.framework_state.uikit.ui_image_picker_controller;
+                let browser = state.browser.as_mut().unwrap();
+                let new_scroll = (start_scroll - (y - start_y)).clamp(0.0, max_scroll);
+                if browser.scroll != new_scroll {
+                    browser.scroll = new_scroll;
+                    state.overlay = None;
+                }
+            }
+            true
+        }
+        Event::TouchUp(source, location) => {
+            match tracked_touch {
+                Some((tracked_source, ..)) if tracked_source == source => (),
+                Some(_) => return true,
+                None => return false,
+            }
+            env.framework_state.uikit.ui_image_picker_controller.touch = None;
+            let pressed = env
+                .framework_state
+                .uikit
+                .ui_image_picker_controller
+                .browser
+                .as_ref()
+                .unwrap()
+                .pressed;
+            set_pressed(env, None);
+            let cancel_pressed = contains(cancel_button_rect(env), location);
+            if cancel_pressed || (pressed.is_some() && pressed == photo) {
+                // UIKit creates and drains autorelease pools when handling
+                // events.
+                let pool: id = msg_class![env; NSAutoreleasePool new];
+                if cancel_pressed {
+                    cancel(env);
+                } else {
+                    pick(env, pressed.unwrap());
+                }
+                release(env, pool);
+            }
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,575 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The on-screen keyboard (`UIKeyboard`) and its notifications.
+//!
+//! The keyboard is shown while a text field or text view is being edited (see
+//! [super::ui_text_field]), and is drawn by the host as an overlay (see
+//! [super::overlay]). It can be used by tapping, or by navigating with a game
+//! controller's D-pad and pressing A (B is backspace). The host keyboard can
+//! still be used too.
+
+use super::overlay::{contains, rect, Canvas, Color, Overlay};
+use super::{ui_font, ui_text_field};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::foundation::ns_dictionary::dict_from_keys_and_objects;
+use crate::frameworks::foundation::ns_notification_center;
+use crate::frameworks::foundation::ns_string::get_static_str;
+use crate::objc::{id, msg_class, nil, release};
+use crate::window::{EditingKey, Event, NavigationButton, TouchSource};
+use crate::Environment;
+
+pub const UIKeyboardWillShowNotification: &str = "UIKeyboardWillShowNotification";
+pub const UIKeyboardDidShowNotification: &str = "UIKeyboardDidShowNotification";
+pub const UIKeyboardWillHideNotification: &str = "UIKeyboardWillHideNotification";
+pub const UIKeyboardDidHideNotification: &str = "UIKeyboardDidHideNotification";
+
+pub const UIKeyboardFrameBeginUserInfoKey: &str = "UIKeyboardFrameBeginUserInfoKey";
+pub const UIKeyboardFrameEndUserInfoKey: &str = "UIKeyboardFrameEndUserInfoKey";
+pub const UIKeyboardAnimationDurationUserInfoKey: &str = "UIKeyboardAnimationDurationUserInfoKey";
+pub const UIKeyboardAnimationCurveUserInfoKey: &str = "UIKeyboardAnimationCurveUserInfoKey";
+// Deprecated in iPhone OS 3.2, but that's what the apps we care about use.
+pub const UIKeyboardCenterBeginUserInfoKey: &str = "UIKeyboardCenterBeginUserInfoKey";
+pub const UIKeyboardCenterEndUserInfoKey: &str = "UIKeyboardCenterEndUserInfoKey";
+pub const UIKeyboardBoundsUserInfoKey: &str = "UIKeyboardBoundsUserInfoKey";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UIKeyboardWillShowNotification",
+        HostConstant::NSString(UIKeyboardWillShowNotification),
+    ),
+    (
+        "_UIKeyboardDidShowNotification",
+        HostConstant::NSString(UIKeyboardDidShowNotification),
+    ),
+    (
+        "_UIKeyboardWillHideNotification",
+        HostConstant::NSString(UIKeyboardWillHideNotification),
+    ),
+    (
+        "_UIKeyboardDidHideNotification",
+        HostConstant::NSString(UIKeyboardDidHideNotification),
+    ),
+    (
+        "_UIKeyboardFrameBeginUserInfoKey",
+        HostConstant::NSString(UIKeyboardFrameBeginUserInfoKey),
+    ),
+    (
+        "_UIKeyboardFrameEndUserInfoKey",
+        HostConstant::NSString(UIKeyboardFrameEndUserInfoKey),
+    ),
+    (
+        "_UIKeyboardAnimationDurationUserInfoKey",
+        HostConstant::NSString(UIKeyboardAnimationDurationUserInfoKey),
+    ),
+    (
+        "_UIKeyboardAnimationCurveUserInfoKey",
+        HostConstant::NSString(UIKeyboardAnimationCurveUserInfoKey),
+    ),
+    (
+        "_UIKeyboardCenterBeginUserInfoKey",
+        HostConstant::NSString(UIKeyboardCenterBeginUserInfoKey),
+    ),
+    (
+        "_UIKeyboardCenterEndUserInfoKey",
+        HostConstant::NSString(UIKeyboardCenterEndUserInfoKey),
+    ),
+    (
+        "_UIKeyboardBoundsUserInfoKey",
+        HostConstant::NSString(UIKeyboardBoundsUserInfoKey),
+    ),
+];
+
+/// Height of the portrait keyboard, in points.
+const KEYBOARD_HEIGHT: CGFloat = 216.0;
+const ROW_HEIGHT: CGFloat = KEYBOARD_HEIGHT / 4.0;
+const KEY_WIDTH: CGFloat = 32.0;
+/// Width of the keys either side of the third row.
+const SIDE_KEY_WIDTH: CGFloat = 42.0;
+const KEY_INSET_X: CGFloat = 3.0;
+const KEY_INSET_Y: CGFloat = 6.0;
+const KEY_CORNER_RADIUS: CGFloat = 5.0;
+const LABEL_SIZE: CGFloat = 22.0;
+const SMALL_LABEL_SIZE: CGFloat = 15.0;
+/// The keyboard appears instantly, but apps may want to animate their own
+/// views along with it.
+const ANIMATION_DURATION: f64 = 0.3;
+/// `UIViewAnimationCurveEaseInOut`
+const ANIMATION_CURVE: i32 = 0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+enum Mode {
+    #[default]
+    Letters,
+    Numbers,
+    Symbols,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum KeyAction {
+    Char(char),
+    Shift,
+    Backspace,
+    Mode(Mode),
+    Space,
+    Return,
+}
+
+struct Key {
+    action: KeyAction,
+    /// The area that responds to touches. The key is drawn a bit smaller.
+    rect: CGRect,
+}
+
+/// (row, index in row)
+type KeyIndex = (usize, usize);
+
+#[derive(Default)]
+pub struct State {
+    visible: bool,
+    mode: Mode,
+    /// Applies to the next letter only.
+    shift: bool,
+    touch: Option<TouchSource>,
+    pressed_key: Option<KeyIndex>,
+    /// Key selected with a game controller's D-pad.
+    focused_key: Option<KeyIndex>,
+    /// Cached drawing, cleared when it needs redrawing.
+    overlay: Option<Overlay>,
+}
+
+/// Get the frame of the keyboard when it's visible.
+fn keyboard_frame(env: &mut Environment) -> CGRect {
+    let (screen_width, screen_height) = env.window.size_unrotated_unscaled();
+    let (screen_width, screen_height) = (screen_width as CGFloat, screen_height as CGFloat);
+    rect(
+        0.0,
+        screen_height - KEYBOARD_HEIGHT,
+        screen_width,
+        KEYBOARD_HEIGHT,
+    )
+}
+
+fn lay_out(frame: CGRect, mode: Mode) -> Vec<Vec<Key>> {
+    let (row0, row1, row2) = match mode {
+        Mode::Letters => ("qwertyuiop", "asdfghjkl", "zxcvbnm"),
+        Mode::Numbers => ("1234567890", "-/:;()$&@\"", ".,?!'"),
+        Mode::Symbols => ("[]{}#%^*+=", "_\\|~<>€£¥•", ".,?!'"),
+    };
+    let (row2_left, row3_left) = match mode {
+        Mode::Letters => (KeyAction::Shift, KeyAction::Mode(Mode::Numbers)),
+        Mode::Numbers => (
+            KeyAction::Mode(Mode::Symbols),
+            KeyAction::Mode(Mode::Letters),
+        ),
+        Mode::Symbols => (
+            KeyAction::Mode(Mode::Numbers),
+            KeyAction::Mode(Mode::Letters),
+        ),
+    };
+    let x0 = frame.origin.x + (frame.size.width - KEY_WIDTH * 10.0) / 2.0;
+    let row_y = |row: usize| frame.origin.y + ROW_HEIGHT * row as CGFloat;
+    let key = |action, x, row, width| Key {
+        action,
+        rect: rect(x, row_y(row), width, ROW_HEIGHT),
+    };
+
+    let char_row = |chars: &str, row: usize, x: CGFloat, width: CGFloat| -> Vec<Key> {
+        chars
+            .chars()
+            .enumerate()
+            .map(|(i, c)| key(KeyAction::Char(c), x + width * i as CGFloat, row, width))
+            .collect()
+    };
+
+    let mut rows = Vec::new();
+    rows.push(char_row(row0, 0, x0, KEY_WIDTH));
+    let row1_len = row1.chars().count() as CGFloat;
+    rows.push(char_row(
+        row1,
+        1,
+        x0 + (10.0 - row1_len) * KEY_WIDTH / 2.0,
+        KEY_WIDTH,
+    ));
+
+    let middle_x = x0 + SIDE_KEY_WIDTH + KEY_INSET_X * 2.0;
+    let middle_width = KEY_WIDTH * 10.0 - (SIDE_KEY_WIDTH + KEY_INSET_X * 2.0) * 2.0;
+    let row2_width = (middle_width / row2.chars().count() as CGFloat).min(KEY_WIDTH);
+    let row2_x = middle_x + (middle_width - row2_width * row2.chars().count() as CGFloat) / 2.0;
+    let mut row = vec![key(row2_left, x0, 2, SIDE_KEY_WIDTH)];
+    row.extend(char_row(row2, 2, row2_x, row2_width));
+    row.push(key(
+        KeyAction::Backspace,
+        x0 + KEY_WIDTH * 10.0 - SIDE_KEY_WIDTH,
+        2,
+        SIDE_KEY_WIDTH,
+    ));
+    rows.push(row);
+
+    let side_width = KEY_WIDTH * 2.5;
+    rows.push(vec![
+        key(row3_left, x0, 3, side_width),
+        key(
+            KeyAction::Space,
+            x0 + side_width,
+            3,
+            KEY_WIDTH * 10.0 - side_width * 2.0,
+        ),
+        key(
+            KeyAction::Return,
+            x0 + KEY_WIDTH * 10.0 - side_width,
+            3,
+            side_width,
+        ),
+    ]);
+    rows
+}
+
+fn label(action: KeyAction, shift: bool) -> String {
+    match action {
+        KeyAction::Char(c) if shift => c.to_uppercase().collect(),
+        KeyAction::Char(c) => c.to_string(),
+        KeyAction::Shift => "shift".to_string(),
+        KeyAction::Backspace => "del".to_string(),
+        KeyAction::Mode(Mode::Letters) => "ABC".to_string(),
+        KeyAction::Mode(Mode::Numbers) => "123".to_string(),
+        KeyAction::Mode(Mode::Symbols) => "#+=".to_string(),
+        KeyAction::Space => "space".to_string(),
+        KeyAction::Return => "return".to_string(),
+    }
+}
+
+fn draw(env: &mut Environment) -> Overlay {
+    let frame = keyboard_frame(env);
+    let state = &env.framework_state.uikit.ui_keyboard;
+    let (mode, shift, pressed_key, focused_key) = (
+        state.mode,
+        state.shift,
+        state.pressed_key,
+        state.focused_key,
+    );
+    let rows = lay_out(frame, mode);
+
+    let mut canvas = Canvas::new(env);
+    canvas.fill_rounded_rect(frame, 0.0, (0.62, 0.64, 0.68, 1.0));
+
+    for (row_idx, row) in rows.iter().enumerate() {
+        for (key_idx, key) in row.iter().enumerate() {
+            let is_char = matches!(key.action, KeyAction::Char(_) | KeyAction::Space);
+            let active = key.action == KeyAction::Shift && shift;
+            let highlighted = pressed_key == Some((row_idx, key_idx));
+            let fill: Color = if highlighted {
+                (0.35, 0.5, 0.85, 1.0)
+            } else if is_char || active {
+                (0.98, 0.98, 0.98, 1.0)
+            } else {
+                (0.45, 0.48, 0.53, 1.0)
+            };
+            let text_color: Color = if is_char || active {
+                (0.0, 0.0, 0.0, 1.0)
+            } else {
+                (1.0, 1.0, 1.0, 1.0)
+            };
+            let key_rect = rect(
+                key.rect.origin.x + KEY_INSET_X,
+                key.rect.origin.y + KEY_INSET_Y,
+                key.rect.size.width - KEY_INSET_X * 2.0,
+                key.rect.size.height - KEY_INSET_Y * 2.0,
+            );
+            if focused_key == Some((row_idx, key_idx)) {
+                let focus_rect = rect(
+                    key_rect.origin.x - 2.0,
+                    key_rect.origin.y - 2.0,
+                    key_rect.size.width + 4.0,
+                    key_rect.size.height + 4.0,
+                );
+                canvas.fill_rounded_rect(
+                    focus_rect,
+                    KEY_CORNER_RADIUS + 2.0,
+                    (1.0, 0.75, 0.1, 1.0),
+                );
+            }
+            canvas.fill_rounded_rect(key_rect, KEY_CORNER_RADIUS, fill);
+
+            let text = label(key.action, shift && mode == Mode::Letters);
+            let size = if matches!(key.action, KeyAction::Char(_)) {
+                LABEL_SIZE
+            } else {
+                SMALL_LABEL_SIZE
+            };
+            let font = ui_font::system_font(env, false, &text);
+            canvas.draw_text_centered(font, size, &text, key_rect, text_color);
+        }
+    }
+
+    canvas.into_overlay()
+}
+
+/// For use by [super::overlay]: redraw the keyboard if needed.
+pub(super) fn update_overlay(env: &mut Environment) {
+    let state = &env.framework_state.uikit.ui_keyboard;
+    if state.visible && state.overlay.is_none() {
+        let overlay = draw(env);
+        env.framework_state.uikit.ui_keyboard.overlay = Some(overlay);
+    }
+}
+
+/// For use by [super::overlay]: get the drawing of the keyboard, if it's
+/// visible.
+pub(super) fn current_overlay(state: &State) -> Option<&Overlay> {
+    if state.visible {
+        state.overlay.as_ref()
+    } else {
+        None
+    }
+}
+
+/// Post the notifications for the keyboard appearing or disappearing.
+fn post_notifications(env: &mut Environment, showing: bool) {
+    let visible_frame = keyboard_frame(env);
+    let hidden_frame = CGRect {
+        origin: CGPoint {
+            x: visible_frame.origin.x,
+            y: visible_frame.origin.y + visible_frame.size.height,
+        },
+        size: visible_frame.size,
+    };
+    let (begin, end) = if showing {
+        (hidden_frame, visible_frame)
+    } else {
+        (visible_frame, hidden_frame)
+    };
+    let center = |frame: CGRect| CGPoint {
+        x: frame.origin.x + frame.size.width / 2.0,
+        y: frame.origin.y + frame.size.height / 2.0,
+    };
+    let bounds = rect(0.0, 0.0, begin.size.width, begin.size.height);
+
+    let values: [(&'static str, id); 7] = [
+        (
+            UIKeyboardFrameBeginUserInfoKey,
+            msg_class![env; NSValue valueWithCGRect:begin],
+        ),
+        (
+            UIKeyboardFrameEndUserInfoKey,
+            msg_class![env; NSValue valueWithCGRect:end],
+        ),
+        (
+            UIKeyboardCenterBeginUserInfoKey,
+            msg_class![env; NSValue valueWithCGPoint:(center(begin))],
+        ),
+        (
+            UIKeyboardCenterEndUserInfoKey,
+            msg_class![env; NSValue valueWithCGPoint:(center(end))],
+        ),
+        (
+            UIKeyboardBoundsUserInfoKey,
+            msg_class![env; NSValue valueWithCGRect:bounds],
+        ),
+        (
+            UIKeyboardAnimationDurationUserInfoKey,
+            msg_class![env; NSNumber numberWithDouble:ANIMATION_DURATION],
+        ),
+        (
+            UIKeyboardAnimationCurveUserInfoKey,
+            msg_class![env; NSNumber numberWithInt:ANIMATION_CURVE],
+        ),
+    ];
+    let keys_and_objects: Vec<(id, id)> = values
+        .into_iter()
+        .map(|(key, value)| (get_static_str(env, key), value))
+        .collect();
+    let user_info = dict_from_keys_and_objects(env, &keys_and_objects);
+
+    let (will, did) = if showing {
+        (
+            UIKeyboardWillShowNotification,
+            UIKeyboardDidShowNotification,
+        )
+    } else {
+        (
+            UIKeyboardWillHideNotification,
+            UIKeyboardDidHideNotification,
+        )
+    };
+    let will = get_static_str(env, will);
+    ns_notification_center::post(env, will, nil, user_info);
+    let did = get_static_str(env, did);
+    ns_notification_center::post(env, did, nil, user_info);
+    release(env, user_info);
+}
+
+/// For use by [super::ui_text_field]: show the keyboard when editing begins.
+pub(super) fn show(env: &mut Environment) {
+    if env.framework_state.uikit.ui_keyboard.visible {
+        return;
+    }
+    log_dbg!("Showing on-screen keyboard");
+    env.framework_state.uikit.ui_keyboard = State {
+        visible: true,
+        ..Default::default()
+    };
+    post_notifications(env, true);
+}
+
+/// For use by [super::ui_text_field]: hide the keyboard when editing ends.
+pub(super) fn hide(env: &mut Environment) {
+    if !env.framework_state.uikit.ui_keyboard.visible {
+        return;
+    }
+    log_dbg!("Hiding on-screen keyboard");
+    env.framework_state.uikit.ui_keyboard = State::default();
+    post_notifications(env, false);
+}
+
+fn key_at(env: &mut Environment, location: (f32, f32)) -> Option<KeyIndex> {
+    let frame = keyboard_frame(env);
+    let mode = env.framework_state.uikit.ui_keyboard.mode;
+    lay_out(frame, mode)
+        .iter()
+        .enumerate()
+        .find_map(|(row_idx, row)| {
+            row.iter()
+                .position(|key| contains(key.rect, location))
+                .map(|key_idx| (row_idx, key_idx))
+        })
+}
+
+fn set_pressed_key(env: &mut Environment, pressed: Option<KeyIndex>) {
+    let state = &mut env.framework_state.uikit.ui_keyboard;
+    if state.pressed_key != pressed {
+        state.pressed_key = pressed;
+        state.overlay = None;
+    }
+}
+
+fn activate(env: &mut Environment, (row_idx, key_idx): KeyIndex) {
+    let frame = keyboard_frame(env);
+    let state = &mut env.framework_state.uikit.ui_keyboard;
+    let Some(key) = lay_out(frame, state.mode)
+        .get(row_idx)
+        .and_then(|row| row.get(key_idx))
+        .map(|key| key.action)
+    else {
+        return;
+    };
+    state.overlay = None;
+    log_dbg!("On-screen keyboard key pressed: {:?}", key);
+    match key {
+        KeyAction::Char(_) => {
+            let text = label(key, state.shift && state.mode == Mode::Letters);
+            state.shift = false;
+            ui_text_field::type_text(env, &text);
+        }
+        KeyAction::Shift => state.shift = !state.shift,
+        KeyAction::Backspace => ui_text_field::press_key(env, EditingKey::Backspace),
+        KeyAction::Mode(mode) => {
+            state.mode = mode;
+            state.shift = false;
+            // The rows might have different lengths now.
+            if let Some((row, key)) = state.focused_key {
+                let row_len = lay_out(frame, mode)[row].len();
+                state.focused_key = Some((row, key.min(row_len - 1)));
+            }
+        }
+        KeyAction::Space => ui_text_field::type_text(env, " "),
+        KeyAction::Return => ui_text_field::press_key(env, EditingKey::Return),
+    }
+}
+
+fn navigate(env: &mut Environment, button: NavigationButton) {
+    let frame = keyboard_frame(env);
+    let state = &mut env.framework_state.uikit.ui_keyboard;
+    let rows = lay_out(frame, state.mode);
+    let Some((row, key)) = state.focused_key else {
+        // The first press just shows where the focus is.
+        state.focused_key = Some((0, 0));
+        state.overlay = None;
+        if button == NavigationButton::Back {
+            ui_text_field::press_key(env, EditingKey::Backspace);
+        }
+        return;
+    };
+    let center_x = |rect: CGRect| rect.origin.x + rect.size.width / 2.0;
+    // Go to the key in another row that's closest horizontally.
+    let nearest_in_row = |new_row: usize| -> KeyIndex {
+        let x = center_x(rows[row][key].rect);
+        let new_key = rows[new_row]
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                (center_x(a.rect) - x)
+                    .abs()
+                    .total_cmp(&(center_x(b.rect) - x).abs())
+            })
+            .map(|(i, _)| i)
+            .unwrap();
+        (new_row, new_key)
+    };
+    let new_focus = match button {
+        NavigationButton::Up => nearest_in_row(row.saturating_sub(1)),
+        NavigationButton::Down => nearest_in_row((row + 1).min(rows.len() - 1)),
+        NavigationButton::Left => (row, key.saturating_sub(1)),
+        NavigationButton::Right => (row, (key + 1).min(rows[row].len() - 1)),
+        NavigationButton::Select => {
+            activate(env, (row, key));
+            return;
+        }
+        NavigationButton::Back => {
+            ui_text_field::press_key(env, EditingKey::Backspace);
+            return;
+        }
+    };
+    state.focused_key = Some(new_focus);
+    state.overlay = None;
+}
+
+/// For use by [super::handle_events]: handle touches and game controller input
+/// for the keyboard, if it's visible. Returns [true] if the event was
+/// consumed.
+pub(super) fn handle_event(env: &mut Environment, event: &Event) -> bool {
+    if !env.framework_state.uikit.ui_keyboard.visible {
+        return false;
+    }
+    let tracked_touch = env.framework_state.uikit.ui_keyboard.touch;
+    // UIKit creates and drains autorelease pools when handling events.
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let consumed = match *event {
+        Event::TouchDown(source, location) => {
+            if tracked_touch.is_some() && tracked_touch != Some(source) {
+                // Only one key can be pressed at once.
+                contains(keyboard_frame(env), location)
+            } else if contains(keyboard_frame(env), location) {
+                env.framework_state.uikit.ui_keyboard.touch = Some(source);
+                let pressed = key_at(env, location);
+                set_pressed_key(env, pressed);
+                true
+            } else {
+                false
+            }
+        }
+        Event::TouchMove(source, location) if tracked_touch == Some(source) => {
+            let pressed = key_at(env, location);
+            set_pressed_key(env, pressed);
+            true
+        }
+        Event::TouchUp(source, location) if tracked_touch == Some(source) => {
+            env.framework_state.uikit.ui_keyboard.touch = None;
+            set_pressed_key(env, None);
+            if let Some(key) = key_at(env, location) {
+                activate(env, key);
+            }
+            true
+        }
+        Event::NavigationButton(button) => {
+            navigate(env, button);
+            true
+        }
+        _ => false,
+    };
+    release(env, pool);
+    consumed
+}
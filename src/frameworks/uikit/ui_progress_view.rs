@@ -0,0 +1,202 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIProgressView`.
+//!
+//! Progress bars are drawn by the host as an overlay (see [super::overlay]),
+//! since views aren't composited yet. They look like the ones in iPhone OS 3:
+//! a white track with a blue bar, or for the bar style, a dark track with a
+//! white bar.
+
+use super::overlay::{rect, Canvas, Color, Overlay};
+use super::ui_view::{frame_on_screen, UIViewHostObject};
+use crate::frameworks::core_graphics::{CGFloat, CGRect};
+use crate::frameworks::foundation::NSInteger;
+use crate::objc::{id, msg, objc_classes, ClassExports};
+use crate::Environment;
+
+pub type UIProgressViewStyle = NSInteger;
+pub const UIProgressViewStyleDefault: UIProgressViewStyle = 0;
+pub const UIProgressViewStyleBar: UIProgressViewStyle = 1;
+
+/// The height of a progress view, which doesn't depend on its frame.
+const HEIGHT: CGFloat = 9.0;
+
+#[derive(Default)]
+pub struct State {
+    overlay: Option<Overlay>,
+    /// What the overlay shows.
+    overlay_contents: Vec<DrawnProgressView>,
+}
+
+#[derive(PartialEq)]
+struct DrawnProgressView {
+    frame: CGRect,
+    style: UIProgressViewStyle,
+    progress: f32,
+}
+
+pub(super) struct ProgressViewState {
+    style: UIProgressViewStyle,
+    progress: f32,
+}
+
+fn state(env: &mut Environment, view: id) -> &mut ProgressViewState {
+    env.objc
+        .borrow_mut::<UIViewHostObject>(view)
+        .progress_view
+        .get_or_insert_with(|| {
+            Box::new(ProgressViewState {
+                style: UIProgressViewStyleDefault,
+                progress: 0.0,
+            })
+        })
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIProgressView: UIView
+
+- (id)initWithProgressViewStyle:(UIProgressViewStyle)style {
+    let frame = rect(0.0, 0.0, 160.0, HEIGHT);
+    let this: id = msg![env; this initWithFrame:frame];
+    state(env, this).style = style;
+    this
+}
+
+- (UIProgressViewStyle)progressViewStyle {
+    state(env, this).style
+}
+- (())setProgressViewStyle:(UIProgressViewStyle)style {
+    state(env, this).style = style;
+}
+
+- (f32)progress {
+    state(env, this).progress
+}
+- (())setProgress:(f32)progress {
+    let progress = if progress.is_nan() { 0.0 } else { progress.clamp(0.0, 1.0) };
+    state(env, this).progress = progress;
+}
+- (())setProgress:(f32)progress
+         animated:(bool)_animated {
+    // TODO: animation
+    msg![env; this setProgress:progress]
+}
+
+@end
+
+};
+
+fn visible_progress_views(env: &mut Environment) -> Vec<DrawnProgressView> {
+    let class = env.objc.get_known_class("UIProgressView", &mut env.mem);
+    let views = env.framework_state.uikit.ui_view.views.clone();
+    let mut progress_views = Vec::new();
+    for view in views {
+        if !msg![env; view isKindOfClass:class] || env.objc.borrow::<UIViewHostObject>(view).hidden
+        {
+            continue;
+        }
+        let Some(frame) = frame_on_screen(env, view) else {
+            continue;
+        };
+        if frame.size.width <= 0.0 {
+            continue;
+        }
+        let &mut ProgressViewState { style, progress } = state(env, view);
+        progress_views.push(DrawnProgressView {
+            frame,
+            style,
+            progress,
+        });
+    }
+    progress_views
+}
+
+fn draw_progress_view(canvas: &mut Canvas, progress_view: &DrawnProgressView) {
+    let DrawnProgressView {
+        frame,
+        style,
+        progress,
+    } = *progress_view;
+    let track = rect(
+        frame.origin.x,
+        frame.origin.y + (frame.size.height - HEIGHT) / 2.0,
+        frame.size.width,
+        HEIGHT,
+    );
+    let radius = HEIGHT / 2.0;
+    // The bar is never narrower than its rounded ends.
+    let bar_width = (track.size.width * progress).max(HEIGHT);
+
+    let (border, background, bar, highlight): (Color, Color, Color, Color) =
+        if style == UIProgressViewStyleBar {
+            (
+                (0.2, 0.2, 0.2, 1.0),
+                (0.35, 0.35, 0.35, 1.0),
+                (0.9, 0.9, 0.9, 1.0),
+                (1.0, 1.0, 1.0, 0.5),
+            )
+        } else {
+            (
+                (0.55, 0.55, 0.55, 1.0),
+                (0.97, 0.97, 0.97, 1.0),
+                (0.15, 0.45, 0.9, 1.0),
+                (0.6, 0.8, 1.0, 0.6),
+            )
+        };
+
+    canvas.fill_rounded_rect(track, radius, border);
+    let inner = rect(
+        track.origin.x + 1.0,
+        track.origin.y + 1.0,
+        track.size.width - 2.0,
+        HEIGHT - 2.0,
+    );
+    canvas.fill_rounded_rect(inner, radius - 1.0, background);
+    if progress > 0.0 {
+        let bar_rect = rect(track.origin.x, track.origin.y, bar_width, HEIGHT);
+        canvas.fill_rounded_rect(bar_rect, radius, bar);
+        // A gloss on the top half, like the artwork this imitates.
+        let gloss = rect(
+            bar_rect.origin.x + 1.0,
+            bar_rect.origin.y + 1.0,
+            bar_width - 2.0,
+            HEIGHT / 2.0 - 1.0,
+        );
+        canvas.fill_rounded_rect(gloss, radius - 1.0, highlight);
+    }
+}
+
+/// For use by [super::overlay]: redraw the visible progress views if needed.
+pub(super) fn update_overlay(env: &mut Environment) {
+    let contents = visible_progress_views(env);
+
+    let state = &mut env.framework_state.uikit.ui_progress_view;
+    if contents == state.overlay_contents && (state.overlay.is_some() || contents.is_empty()) {
+        return;
+    }
+    if contents.is_empty() {
+        state.overlay_contents = contents;
+        state.overlay = None;
+        return;
+    }
+
+    let mut canvas = Canvas::new(env);
+    for progress_view in &contents {
+        draw_progress_view(&mut canvas, progress_view);
+    }
+    let state = &mut env.framework_state.uikit.ui_progress_view;
+    state.overlay_contents = contents;
+    state.overlay = Some(canvas.into_overlay());
+}
+
+/// For use by [super::overlay]: get the drawing of the progress views, if any
+/// are visible.
+pub(super) fn current_overlay(state: &State) -> Option<&Overlay> {
+    state.overlay.as_ref()
+}
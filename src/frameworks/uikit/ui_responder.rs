@@ -5,7 +5,15 @@
  */
 //! `UIResponder`.
 
-use crate::objc::{id, objc_classes, ClassExports};
+use super::ui_event::UIEventSubtype;
+use crate::objc::{id, msg, nil, objc_classes, ClassExports};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// Weak reference.
+    first_responder: Option<id>,
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -13,42 +21,168 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation UIResponder: NSObject
 
-// TODO: real responder implementation etc
+- (id)nextResponder {
+    nil
+}
+
+- (bool)canBecomeFirstResponder {
+    false
+}
+- (bool)canResignFirstResponder {
+    true
+}
+- (bool)becomeFirstResponder {
+    if !msg![env; this canBecomeFirstResponder] {
+        return false;
+    }
+    become_first_responder(env, this)
+}
+- (bool)resignFirstResponder {
+    resign_first_responder(env, this);
+    true
+}
+- (bool)isFirstResponder {
+    env.framework_state.uikit.ui_responder.first_responder == Some(this)
+}
 
-// These methods print debug logs because they are only likely to get called if
-// a subclass didn't override them, which might mean we delivered the event to
-// the wrong object or it is unhandled.
+// By default, events are passed up the responder chain. These methods print
+// debug logs when they reach the end of it, because that might mean we
+// delivered the event to the wrong object or it is unhandled.
 
 - (())touchesBegan:(id)touches // NSSet* of UITouch*
          withEvent:(id)event { // UIEvent*
-    log_dbg!(
-        "[{:?} touchesBegan:{:?} withEvent:{:?}] (probably unhandled)",
-        this,
-        touches,
-        event,
-    );
+    let next: id = msg![env; this nextResponder];
+    if next == nil {
+        log_dbg!(
+            "[{:?} touchesBegan:{:?} withEvent:{:?}] (probably unhandled)",
+            this,
+            touches,
+            event,
+        );
+        return;
+    }
+    msg![env; next touchesBegan:touches withEvent:event]
 }
 
 - (())touchesMoved:(id)touches // NSSet* of UITouch*
          withEvent:(id)event { // UIEvent*
-    log_dbg!(
-        "[{:?} touchesMoved:{:?} withEvent:{:?}] (probably unhandled)",
-        this,
-        touches,
-        event,
-    );
+    let next: id = msg![env; this nextResponder];
+    if next == nil {
+        log_dbg!(
+            "[{:?} touchesMoved:{:?} withEvent:{:?}] (probably unhandled)",
+            this,
+            touches,
+            event,
+        );
+        return;
+    }
+    msg![env; next touchesMoved:touches withEvent:event]
 }
 
 - (())touchesEnded:(id)touches // NSSet* of UITouch*
          withEvent:(id)event { // UIEvent*
-    log_dbg!(
-        "[{:?} touchesEnded:{:?} withEvent:{:?}] (probably unhandled)",
-        this,
-        touches,
-        event,
-    );
+    let next: id = msg![env; this nextResponder];
+    if next == nil {
+        log_dbg!(
+            "[{:?} touchesEnded:{:?} withEvent:{:?}] (probably unhandled)",
+            this,
+            touches,
+            event,
+        );
+        return;
+    }
+    msg![env; next touchesEnded:touches withEvent:event]
+}
+
+- (())touchesCancelled:(id)touches // NSSet* of UITouch*
+             withEvent:(id)event { // UIEvent*
+    let next: id = msg![env; this nextResponder];
+    if next == nil {
+        log_dbg!(
+            "[{:?} touchesCancelled:{:?} withEvent:{:?}] (probably unhandled)",
+            this,
+            touches,
+            event,
+        );
+        return;
+    }
+    msg![env; next touchesCancelled:touches withEvent:event]
+}
+
+- (())motionBegan:(UIEventSubtype)motion
+        withEvent:(id)event { // UIEvent*
+    let next: id = msg![env; this nextResponder];
+    if next == nil {
+        log_dbg!(
+            "[{:?} motionBegan:{:?} withEvent:{:?}] (probably unhandled)",
+            this,
+            motion,
+            event,
+        );
+        return;
+    }
+    msg![env; next motionBegan:motion withEvent:event]
+}
+
+- (())motionEnded:(UIEventSubtype)motion
+        withEvent:(id)event { // UIEvent*
+    let next: id = msg![env; this nextResponder];
+    if next == nil {
+        log_dbg!(
+            "[{:?} motionEnded:{:?} withEvent:{:?}] (probably unhandled)",
+            this,
+            motion,
+            event,
+        );
+        return;
+    }
+    msg![env; next motionEnded:motion withEvent:event]
+}
+
+- (())motionCancelled:(UIEventSubtype)motion
+            withEvent:(id)event { // UIEvent*
+    let next: id = msg![env; this nextResponder];
+    if next == nil {
+        log_dbg!(
+            "[{:?} motionCancelled:{:?} withEvent:{:?}] (probably unhandled)",
+            this,
+            motion,
+            event,
+        );
+        return;
+    }
+    msg![env; next motionCancelled:motion withEvent:event]
 }
 
 @end
 
 };
+
+pub(super) fn first_responder(env: &mut Environment) -> Option<id> {
+    env.framework_state.uikit.ui_responder.first_responder
+}
+
+/// Make an object the first responder, asking the current one to resign
+/// first. Returns [false] if it refuses. Shared with subclasses, since they
+/// can't do super-calls yet.
+pub(super) fn become_first_responder(env: &mut Environment, responder: id) -> bool {
+    if let Some(current) = first_responder(env) {
+        if current == responder {
+            return true;
+        }
+        if !msg![env; current resignFirstResponder] {
+            return false;
+        }
+    }
+    env.framework_state.uikit.ui_responder.first_responder = Some(responder);
+    true
+}
+
+/// Stop an object being the first responder, if it is one. Shared with
+/// subclasses, since they can't do super-calls yet.
+pub(super) fn resign_first_responder(env: &mut Environment, responder: id) {
+    let state = &mut env.framework_state.uikit.ui_responder;
+    if state.first_responder == Some(responder) {
+        state.first_responder = None;
+    }
+}
@@ -5,7 +5,8 @@
  */
 //! `UIScreen`.
 
-use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use super::status_bar;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
 use crate::objc::{id, objc_classes, ClassExports, TrivialHostObject};
 
 #[derive(Default)]
@@ -48,6 +49,17 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
+- (CGRect)applicationFrame {
+    status_bar::application_frame(env)
+}
+
+- (CGFloat)scale {
+    // Always 1, even with an increased internal resolution: apps from before
+    // the iPhone 4 can't handle anything else, and touchHLE scales their
+    // content itself.
+    1.0
+}
+
 @end
 
 };
@@ -0,0 +1,975 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIScrollView`.
+//!
+//! As in real UIKit, the content offset is the bounds origin. Dragging follows
+//! the touch, with rubber-banding past the edges of the content. After the
+//! touch ends, the scroll view decelerates, bounces back or snaps to a page,
+//! and this is animated by [handle_animations]. The physics are approximations
+//! of what the real thing feels like:
+//!
+//! - Deceleration multiplies the velocity by the deceleration rate every
+//!   millisecond, like the real `decelerationRate` property.
+//! - Bouncing and paging use a critically damped spring.
+//! - Rubber-banding uses the well-known formula
+//!   `(1 - (1 / ((x * 0.55 / d) + 1))) * d`.
+//!
+//! Zooming only works through `setZoomScale:` and friends, because there's
+//! only one touch at a time, so there's no pinch gesture.
+
+use super::ui_view::UIViewHostObject;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::mem::{ConstVoidPtr, Mem};
+use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports, SEL};
+use crate::Environment;
+use std::time::Instant;
+
+pub const UIScrollViewDecelerationRateNormal: CGFloat = 0.998;
+pub const UIScrollViewDecelerationRateFast: CGFloat = 0.99;
+
+fn write_deceleration_rate_normal(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(UIScrollViewDecelerationRateNormal)
+        .cast()
+        .cast_const()
+}
+fn write_deceleration_rate_fast(mem: &mut Mem) -> ConstVoidPtr {
+    mem.alloc_and_write(UIScrollViewDecelerationRateFast)
+        .cast()
+        .cast_const()
+}
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UIScrollViewDecelerationRateNormal",
+        HostConstant::Custom(write_deceleration_rate_normal),
+    ),
+    (
+        "_UIScrollViewDecelerationRateFast",
+        HostConstant::Custom(write_deceleration_rate_fast),
+    ),
+];
+
+/// How far (in points) a touch has to move before it's considered a drag
+/// rather than a tap.
+const DRAG_THRESHOLD: CGFloat = 10.0;
+/// If the touch stays still for this long (in seconds) before it ends, the
+/// scroll view doesn't keep moving.
+const RELEASE_STILLNESS_TIME: f64 = 0.1;
+/// Deceleration stops below this velocity (in points per second).
+const MIN_VELOCITY: CGFloat = 10.0;
+/// A touch that ends at least this fast (in points per second) moves to the
+/// next page, even if it hasn't been dragged halfway.
+const PAGE_FLICK_VELOCITY: CGFloat = 250.0;
+/// Angular frequency (in radians per second) of the spring used for bouncing
+/// and paging. Higher is snappier.
+const SPRING_FREQUENCY: CGFloat = 12.0;
+/// How close (in points) a spring has to be to its target to stop.
+const SPRING_TOLERANCE: CGFloat = 0.5;
+const RUBBER_BAND_COEFFICIENT: CGFloat = 0.55;
+
+#[derive(Default)]
+pub struct State {
+    /// Strong references to scroll views that are decelerating or otherwise
+    /// animating.
+    animating: Vec<id>,
+}
+
+/// Motion along one axis while the scroll view is animating.
+#[derive(Copy, Clone, Debug)]
+enum AxisMotion {
+    Stopped,
+    /// Velocity in points per second.
+    Decelerating {
+        velocity: CGFloat,
+    },
+    /// Critically damped spring. The displacement from `target` at time `t`
+    /// is `(x0 + (v0 + w * x0) * t) * e^(-w * t)`.
+    Spring {
+        target: CGFloat,
+        x0: CGFloat,
+        v0: CGFloat,
+        time: CGFloat,
+    },
+}
+
+struct Animation {
+    x: AxisMotion,
+    y: AxisMotion,
+    /// Whether this is deceleration after dragging, rather than an animation
+    /// requested by the app. This decides which delegate method is called at
+    /// the end.
+    decelerating: bool,
+    last_update: Instant,
+}
+
+struct Tracking {
+    /// Relative to the screen.
+    start_location: CGPoint,
+    start_offset: CGPoint,
+    last_location: CGPoint,
+    last_time: Instant,
+    /// Velocity of the touch in points per second, smoothed.
+    velocity: CGPoint,
+}
+
+pub(super) struct ScrollViewState {
+    pub(super) content_size: CGSize,
+    scroll_enabled: bool,
+    /// Weak reference.
+    pub(super) delegate: id,
+    bounces: bool,
+    always_bounce_horizontal: bool,
+    always_bounce_vertical: bool,
+    paging_enabled: bool,
+    deceleration_rate: CGFloat,
+    shows_horizontal_scroll_indicator: bool,
+    shows_vertical_scroll_indicator: bool,
+    minimum_zoom_scale: CGFloat,
+    maximum_zoom_scale: CGFloat,
+    zoom_scale: CGFloat,
+    /// Size of the view for zooming at a zoom scale of 1.
+    unzoomed_size: Option<CGSize>,
+    tracking: Option<Tracking>,
+    dragging: bool,
+    animation: Option<Animation>,
+}
+impl Default for ScrollViewState {
+    fn default() -> Self {
+        ScrollViewState {
+            content_size: CGSize {
+                width: 0.0,
+                height: 0.0,
+            },
+            scroll_enabled: true,
+            delegate: nil,
+            bounces: true,
+            always_bounce_horizontal: false,
+            always_bounce_vertical: false,
+            paging_enabled: false,
+            deceleration_rate: UIScrollViewDecelerationRateNormal,
+            shows_horizontal_scroll_indicator: true,
+            shows_vertical_scroll_indicator: true,
+            minimum_zoom_scale: 1.0,
+            maximum_zoom_scale: 1.0,
+            zoom_scale: 1.0,
+            unzoomed_size: None,
+            tracking: None,
+            dragging: false,
+            animation: None,
+        }
+    }
+}
+
+pub(super) fn state(env: &mut Environment, scroll_view: id) -> &mut ScrollViewState {
+    env.objc
+        .borrow_mut::<UIViewHostObject>(scroll_view)
+        .scroll_view
+        .get_or_insert_with(Default::default)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIScrollView: UIView
+
+- (id)delegate {
+    state(env, this).delegate
+}
+- (())setDelegate:(id)delegate {
+    state(env, this).delegate = delegate;
+}
+
+- (CGSize)contentSize {
+    state(env, this).content_size
+}
+- (())setContentSize:(CGSize)size {
+    state(env, this).content_size = size;
+}
+
+- (CGPoint)contentOffset {
+    env.objc.borrow::<UIViewHostObject>(this).bounds.origin
+}
+- (())setContentOffset:(CGPoint)offset {
+    stop_animation(env, this);
+    set_content_offset(env, this, offset);
+}
+- (())setContentOffset:(CGPoint)offset
+              animated:(bool)animated {
+    if animated {
+        animate_to_offset(env, this, offset);
+    } else {
+        stop_animation(env, this);
+        set_content_offset(env, this, offset);
+    }
+}
+- (())scrollRectToVisible:(CGRect)rect
+                 animated:(bool)animated {
+    let bounds = env.objc.borrow::<UIViewHostObject>(this).bounds;
+    let x = scroll_to_show(bounds.origin.x, bounds.size.width, rect.origin.x, rect.size.width);
+    let y = scroll_to_show(bounds.origin.y, bounds.size.height, rect.origin.y, rect.size.height);
+    scroll_to_offset(env, this, CGPoint { x, y }, animated);
+}
+
+- (bool)isScrollEnabled {
+    state(env, this).scroll_enabled
+}
+- (())setScrollEnabled:(bool)enabled {
+    state(env, this).scroll_enabled = enabled;
+}
+
+- (bool)bounces {
+    state(env, this).bounces
+}
+- (())setBounces:(bool)bounces {
+    state(env, this).bounces = bounces;
+}
+- (bool)alwaysBounceHorizontal {
+    state(env, this).always_bounce_horizontal
+}
+- (())setAlwaysBounceHorizontal:(bool)bounces {
+    state(env, this).always_bounce_horizontal = bounces;
+}
+- (bool)alwaysBounceVertical {
+    state(env, this).always_bounce_vertical
+}
+- (())setAlwaysBounceVertical:(bool)bounces {
+    state(env, this).always_bounce_vertical = bounces;
+}
+
+- (bool)isPagingEnabled {
+    state(env, this).paging_enabled
+}
+- (())setPagingEnabled:(bool)enabled {
+    state(env, this).paging_enabled = enabled;
+}
+
+- (CGFloat)decelerationRate {
+    state(env, this).deceleration_rate
+}
+- (())setDecelerationRate:(CGFloat)rate {
+    state(env, this).deceleration_rate = rate;
+}
+
+// TODO: draw scroll indicators
+- (bool)showsHorizontalScrollIndicator {
+    state(env, this).shows_horizontal_scroll_indicator
+}
+- (())setShowsHorizontalScrollIndicator:(bool)shows {
+    state(env, this).shows_horizontal_scroll_indicator = shows;
+}
+- (bool)showsVerticalScrollIndicator {
+    state(env, this).shows_vertical_scroll_indicator
+}
+- (())setShowsVerticalScrollIndicator:(bool)shows {
+    state(env, this).shows_vertical_scroll_indicator = shows;
+}
+
+- (bool)isTracking {
+    state(env, this).tracking.is_some()
+}
+- (bool)isDragging {
+    state(env, this).dragging
+}
+- (bool)isDecelerating {
+    matches!(
+        state(env, this).animation,
+        Some(Animation { decelerating: true, .. })
+    )
+}
+
+- (CGFloat)minimumZoomScale {
+    state(env, this).minimum_zoom_scale
+}
+- (())setMinimumZoomScale:(CGFloat)scale {
+    state(env, this).minimum_zoom_scale = scale;
+}
+- (CGFloat)maximumZoomScale {
+    state(env, this).maximum_zoom_scale
+}
+- (())setMaximumZoomScale:(CGFloat)scale {
+    state(env, this).maximum_zoom_scale = scale;
+}
+- (CGFloat)zoomScale {
+    state(env, this).zoom_scale
+}
+- (())setZoomScale:(CGFloat)scale {
+    set_zoom_scale(env, this, scale);
+}
+- (())setZoomScale:(CGFloat)scale
+          animated:(bool)_animated {
+    // TODO: animation
+    set_zoom_scale(env, this, scale);
+}
+- (())zoomToRect:(CGRect)rect
+        animated:(bool)_animated {
+    // TODO: animation
+    let bounds = env.objc.borrow::<UIViewHostObject>(this).bounds;
+    // The rect is in the co-ordinate space of the view for zooming.
+    let scale = (bounds.size.width / rect.size.width).min(bounds.size.height / rect.size.height);
+    if !set_zoom_scale(env, this, scale) {
+        return;
+    }
+    let scale = state(env, this).zoom_scale;
+    let offset = CGPoint {
+        x: rect.origin.x * scale - (bounds.size.width - rect.size.width * scale) / 2.0,
+        y: rect.origin.y * scale - (bounds.size.height - rect.size.height * scale) / 2.0,
+    };
+    scroll_to_offset(env, this, offset, false);
+}
+
+- (())touchesBegan:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    touches_began(env, this, touches);
+}
+- (())touchesMoved:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    touches_moved(env, this, touches);
+}
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    touches_ended(env, this, touches);
+}
+
+@end
+
+};
+
+/// Get the delegate if it implements a method.
+fn delegate_responding_to(env: &mut Environment, scroll_view: id, selector: &str) -> Option<id> {
+    let delegate = state(env, scroll_view).delegate;
+    if delegate == nil {
+        return None;
+    }
+    let selector: SEL = env.objc.lookup_selector(selector)?;
+    if msg![env; delegate respondsToSelector:selector] {
+        Some(delegate)
+    } else {
+        None
+    }
+}
+
+/// Like `[scroll_view setContentOffset:offset]`, but doesn't stop animations.
+pub(super) fn set_content_offset(env: &mut Environment, scroll_view: id, offset: CGPoint) {
+    let host_object = env.objc.borrow_mut::<UIViewHostObject>(scroll_view);
+    let old_offset = host_object.bounds.origin;
+    if old_offset.x == offset.x && old_offset.y == offset.y {
+        return;
+    }
+    host_object.bounds.origin = offset;
+    () = msg![env; scroll_view layoutSubviews];
+
+    if let Some(delegate) = delegate_responding_to(env, scroll_view, "scrollViewDidScroll:") {
+        let () = msg![env; delegate scrollViewDidScroll:scroll_view];
+    }
+}
+
+/// The range of content offsets that keep the content on screen, for each
+/// axis.
+fn offset_limits(env: &mut Environment, scroll_view: id) -> (CGPoint, CGPoint) {
+    let content_size = state(env, scroll_view).content_size;
+    let bounds_size = env.objc.borrow::<UIViewHostObject>(scroll_view).bounds.size;
+    let min = CGPoint { x: 0.0, y: 0.0 };
+    let max = CGPoint {
+        x: (content_size.width - bounds_size.width).max(0.0),
+        y: (content_size.height - bounds_size.height).max(0.0),
+    };
+    (min, max)
+}
+
+fn clamp(value: CGFloat, min: CGFloat, max: CGFloat) -> CGFloat {
+    value.min(max).max(min)
+}
+
+/// The offset along one axis needed to show `[start, start + length)` in a
+/// view of size `view_length`, scrolling as little as possible.
+pub(super) fn scroll_to_show(
+    current: CGFloat,
+    view_length: CGFloat,
+    start: CGFloat,
+    length: CGFloat,
+) -> CGFloat {
+    if start < current {
+        start
+    } else if start + length > current + view_length {
+        (start + length - view_length).min(start)
+    } else {
+        current
+    }
+}
+
+/// Scroll to an offset, clamped to the content size.
+pub(super) fn scroll_to_offset(
+    env: &mut Environment,
+    scroll_view: id,
+    offset: CGPoint,
+    animated: bool,
+) {
+    let (min, max) = offset_limits(env, scroll_view);
+    let offset = CGPoint {
+        x: clamp(offset.x, min.x, max.x),
+        y: clamp(offset.y, min.y, max.y),
+    };
+    if animated {
+        animate_to_offset(env, scroll_view, offset);
+    } else {
+        stop_animation(env, scroll_view);
+        set_content_offset(env, scroll_view, offset);
+    }
+}
+
+/// Displacement from the edge when dragging `overshoot` points past it, in a
+/// view of size `dimension`.
+fn rubber_band(overshoot: CGFloat, dimension: CGFloat) -> CGFloat {
+    if dimension <= 0.0 {
+        return 0.0;
+    }
+    (1.0 - (1.0 / ((overshoot * RUBBER_BAND_COEFFICIENT / dimension) + 1.0))) * dimension
+}
+
+/// Offset along one axis while dragging, with rubber-banding if enabled.
+fn drag_offset(
+    raw: CGFloat,
+    min: CGFloat,
+    max: CGFloat,
+    dimension: CGFloat,
+    bounces: bool,
+) -> CGFloat {
+    if !bounces {
+        clamp(raw, min, max)
+    } else if raw < min {
+        min - rubber_band(min - raw, dimension)
+    } else if raw > max {
+        max + rubber_band(raw - max, dimension)
+    } else {
+        raw
+    }
+}
+
+fn spring_to(target: CGFloat, position: CGFloat, velocity: CGFloat) -> AxisMotion {
+    AxisMotion::Spring {
+        target,
+        x0: position - target,
+        v0: velocity,
+        time: 0.0,
+    }
+}
+
+impl AxisMotion {
+    /// Advance the motion by `dt` seconds, returning the new position.
+    fn step(
+        &mut self,
+        position: CGFloat,
+        dt: CGFloat,
+        (min, max): (CGFloat, CGFloat),
+        deceleration_rate: CGFloat,
+        bounces: bool,
+    ) -> CGFloat {
+        match *self {
+            AxisMotion::Stopped => position,
+            AxisMotion::Decelerating { velocity } => {
+                let velocity = velocity * deceleration_rate.powf(dt * 1000.0);
+                let position = position + velocity * dt;
+                if position < min || position > max {
+                    let edge = clamp(position, min, max);
+                    if bounces {
+                        *self = spring_to(edge, position, velocity);
+                        position
+                    } else {
+                        *self = AxisMotion::Stopped;
+                        edge
+                    }
+                } else if velocity.abs() < MIN_VELOCITY {
+                    *self = AxisMotion::Stopped;
+                    position
+                } else {
+                    *self = AxisMotion::Decelerating { velocity };
+                    position
+                }
+            }
+            AxisMotion::Spring {
+                target,
+                x0,
+                v0,
+                time,
+            } => {
+                let time = time + dt;
+                let w = SPRING_FREQUENCY;
+                let b = v0 + w * x0;
+                let decay = (-w * time).exp();
+                let x = (x0 + b * time) * decay;
+                let v = (b - w * (x0 + b * time)) * decay;
+                if x.abs() < SPRING_TOLERANCE && v.abs() < MIN_VELOCITY {
+                    *self = AxisMotion::Stopped;
+                    target
+                } else {
+                    *self = AxisMotion::Spring {
+                        target,
+                        x0,
+                        v0,
+                        time,
+                    };
+                    target + x
+                }
+            }
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        matches!(self, AxisMotion::Stopped)
+    }
+}
+
+fn start_animation(
+    env: &mut Environment,
+    scroll_view: id,
+    x: AxisMotion,
+    y: AxisMotion,
+    decelerating: bool,
+) {
+    let animation = Animation {
+        x,
+        y,
+        decelerating,
+        last_update: Instant::now(),
+    };
+    let was_animating = state(env, scroll_view)
+        .animation
+        .replace(animation)
+        .is_some();
+    if !was_animating {
+        retain(env, scroll_view);
+        env.framework_state
+            .uikit
+            .ui_scroll_view
+            .animating
+            .push(scroll_view);
+    }
+}
+
+/// Stop any animation without calling the delegate.
+fn stop_animation(env: &mut Environment, scroll_view: id) {
+    if state(env, scroll_view).animation.take().is_none() {
+        return;
+    }
+    let animating = &mut env.framework_state.uikit.ui_scroll_view.animating;
+    animating.retain(|&view| view != scroll_view);
+    release(env, scroll_view);
+}
+
+fn animate_to_offset(env: &mut Environment, scroll_view: id, offset: CGPoint) {
+    let current = env
+        .objc
+        .borrow::<UIViewHostObject>(scroll_view)
+        .bounds
+        .origin;
+    start_animation(
+        env,
+        scroll_view,
+        spring_to(offset.x, current.x, 0.0),
+        spring_to(offset.y, current.y, 0.0),
+        /* decelerating: */ false,
+    );
+}
+
+/// For use by `NSRunLoop` via [super::handle_events]: advance the animations
+/// of scroll views that are decelerating etc.
+pub(super) fn handle_animations(env: &mut Environment) {
+    let animating = env.framework_state.uikit.ui_scroll_view.animating.clone();
+    if animating.is_empty() {
+        return;
+    }
+    let now = Instant::now();
+    for scroll_view in animating {
+        step_animation(env, scroll_view, now);
+    }
+}
+
+fn step_animation(env: &mut Environment, scroll_view: id, now: Instant) {
+    let (min, max) = offset_limits(env, scroll_view);
+    let offset = env
+        .objc
+        .borrow::<UIViewHostObject>(scroll_view)
+        .bounds
+        .origin;
+    let state = state(env, scroll_view);
+    let deceleration_rate = state.deceleration_rate;
+    let bounces = state.bounces;
+    let Some(animation) = state.animation.as_mut() else {
+        return;
+    };
+    let dt = now.duration_since(animation.last_update).as_secs_f64() as CGFloat;
+    animation.last_update = now;
+    let new_offset = CGPoint {
+        x: animation
+            .x
+            .step(offset.x, dt, (min.x, max.x), deceleration_rate, bounces),
+        y: animation
+            .y
+            .step(offset.y, dt, (min.y, max.y), deceleration_rate, bounces),
+    };
+    let finished = animation.x.is_stopped() && animation.y.is_stopped();
+    let decelerating = animation.decelerating;
+
+    set_content_offset(env, scroll_view, new_offset);
+
+    if !finished {
+        return;
+    }
+    // Keep the scroll view alive while calling the delegate.
+    retain(env, scroll_view);
+    stop_animation(env, scroll_view);
+    if decelerating {
+        if let Some(delegate) =
+            delegate_responding_to(env, scroll_view, "scrollViewDidEndDecelerating:")
+        {
+            let () = msg![env; delegate scrollViewDidEndDecelerating:scroll_view];
+        }
+    } else if let Some(delegate) =
+        delegate_responding_to(env, scroll_view, "scrollViewDidEndScrollingAnimation:")
+    {
+        let () = msg![env; delegate scrollViewDidEndScrollingAnimation:scroll_view];
+    }
+    release(env, scroll_view);
+}
+
+/// Implementation of `setZoomScale:`. Returns [false] if there is no view for
+/// zooming.
+fn set_zoom_scale(env: &mut Environment, scroll_view: id, scale: CGFloat) -> bool {
+    let Some(delegate) = delegate_responding_to(env, scroll_view, "viewForZoomingInScrollView:")
+    else {
+        return false;
+    };
+    let zoom_view: id = msg![env; delegate viewForZoomingInScrollView:scroll_view];
+    if zoom_view == nil {
+        return false;
+    }
+
+    let current_size = env.objc.borrow::<UIViewHostObject>(zoom_view).bounds.size;
+    let state = state(env, scroll_view);
+    let scale = clamp(scale, state.minimum_zoom_scale, state.maximum_zoom_scale);
+    let unzoomed_size = *state.unzoomed_size.get_or_insert(current_size);
+    if state.zoom_scale == scale {
+        return true;
+    }
+    state.zoom_scale = scale;
+    let size = CGSize {
+        width: unzoomed_size.width * scale,
+        height: unzoomed_size.height * scale,
+    };
+    state.content_size = size;
+
+    // TODO: This should be done with the view's transform, once UIView has
+    // those. For now the view is resized instead.
+    let frame = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size,
+    };
+    () = msg![env; zoom_view setFrame:frame];
+
+    // Keep the offset within the new content size.
+    let offset = env
+        .objc
+        .borrow::<UIViewHostObject>(scroll_view)
+        .bounds
+        .origin;
+    let (min, max) = offset_limits(env, scroll_view);
+    let offset = CGPoint {
+        x: clamp(offset.x, min.x, max.x),
+        y: clamp(offset.y, min.y, max.y),
+    };
+    set_content_offset(env, scroll_view, offset);
+
+    if let Some(delegate) = delegate_responding_to(env, scroll_view, "scrollViewDidZoom:") {
+        let () = msg![env; delegate scrollViewDidZoom:scroll_view];
+    }
+    if let Some(delegate) = delegate_responding_to(
+        env,
+        scroll_view,
+        "scrollViewDidEndZooming:withView:atScale:",
+    ) {
+        let () = msg![env; delegate scrollViewDidEndZooming:scroll_view
+                                                   withView:zoom_view
+                                                    atScale:scale];
+    }
+    true
+}
+
+fn touch_location(env: &mut Environment, touches: id) -> CGPoint {
+    let touch: id = msg![env; touches anyObject];
+    msg![env; touch locationInView:nil]
+}
+
+/// Whether the scroll view can be dragged along each axis.
+fn scrollable_axes(env: &mut Environment, scroll_view: id) -> (bool, bool) {
+    let (_, max) = offset_limits(env, scroll_view);
+    let state = state(env, scroll_view);
+    (
+        max.x > 0.0 || (state.bounces && state.always_bounce_horizontal),
+        max.y > 0.0 || (state.bounces && state.always_bounce_vertical),
+    )
+}
+
+/// Shared with subclasses, since they can't do super-calls yet.
+pub(super) fn touches_began(env: &mut Environment, scroll_view: id, touches: id) {
+    // Touching a moving scroll view catches it.
+    stop_animation(env, scroll_view);
+
+    let location = touch_location(env, touches);
+    let offset = env
+        .objc
+        .borrow::<UIViewHostObject>(scroll_view)
+        .bounds
+        .origin;
+    let state = state(env, scroll_view);
+    state.tracking = Some(Tracking {
+        start_location: location,
+        start_offset: offset,
+        last_location: location,
+        last_time: Instant::now(),
+        velocity: CGPoint { x: 0.0, y: 0.0 },
+    });
+    state.dragging = false;
+}
+
+/// Shared with subclasses, since they can't do super-calls yet.
+pub(super) fn touches_moved(env: &mut Environment, scroll_view: id, touches: id) {
+    let location = touch_location(env, touches);
+    let (can_scroll_x, can_scroll_y) = scrollable_axes(env, scroll_view);
+    let (min, max) = offset_limits(env, scroll_view);
+    let bounds_size = env.objc.borrow::<UIViewHostObject>(scroll_view).bounds.size;
+
+    let state = state(env, scroll_view);
+    if !state.scroll_enabled {
+        return;
+    }
+    let bounces = state.bounces;
+    let Some(tracking) = state.tracking.as_mut() else {
+        return;
+    };
+
+    let now = Instant::now();
+    let dt = now.duration_since(tracking.last_time).as_secs_f64() as CGFloat;
+    if dt > 0.0 {
+        let new_velocity = CGPoint {
+            x: (location.x - tracking.last_location.x) / dt,
+            y: (location.y - tracking.last_location.y) / dt,
+        };
+        tracking.velocity = CGPoint {
+            x: 0.8 * new_velocity.x + 0.2 * tracking.velocity.x,
+            y: 0.8 * new_velocity.y + 0.2 * tracking.velocity.y,
+        };
+    }
+    tracking.last_location = location;
+    tracking.last_time = now;
+
+    let delta = CGPoint {
+        x: if can_scroll_x {
+            location.x - tracking.start_location.x
+        } else {
+            0.0
+        },
+        y: if can_scroll_y {
+            location.y - tracking.start_location.y
+        } else {
+            0.0
+        },
+    };
+    let start_offset = tracking.start_offset;
+    let began_dragging = !state.dragging;
+    if began_dragging {
+        if delta.x.abs() < DRAG_THRESHOLD && delta.y.abs() < DRAG_THRESHOLD {
+            return;
+        }
+        state.dragging = true;
+        if let Some(delegate) =
+            delegate_responding_to(env, scroll_view, "scrollViewWillBeginDragging:")
+        {
+            let () = msg![env; delegate scrollViewWillBeginDragging:scroll_view];
+        }
+    }
+
+    let offset = CGPoint {
+        x: drag_offset(
+            start_offset.x - delta.x,
+            min.x,
+            max.x,
+            bounds_size.width,
+            bounces,
+        ),
+        y: drag_offset(
+            start_offset.y - delta.y,
+            min.y,
+            max.y,
+            bounds_size.height,
+            bounces,
+        ),
+    };
+    set_content_offset(env, scroll_view, offset);
+}
+
+/// The motion along one axis after the touch ends.
+fn release_motion(
+    offset: CGFloat,
+    velocity: CGFloat,
+    (min, max): (CGFloat, CGFloat),
+    paging: Option<CGFloat>,
+) -> AxisMotion {
+    if let Some(page_size) = paging.filter(|&page_size| page_size > 0.0) {
+        let page = offset / page_size;
+        let page = if velocity > PAGE_FLICK_VELOCITY {
+            page.floor() + 1.0
+        } else if velocity < -PAGE_FLICK_VELOCITY {
+            page.ceil() - 1.0
+        } else {
+            page.round()
+        };
+        let target = clamp(page * page_size, min, max);
+        return spring_to(target, offset, velocity);
+    }
+    if offset < min || offset > max {
+        spring_to(clamp(offset, min, max), offset, velocity)
+    } else if velocity.abs() >= MIN_VELOCITY {
+        AxisMotion::Decelerating { velocity }
+    } else {
+        AxisMotion::Stopped
+    }
+}
+
+/// Shared with subclasses, since they can't do super-calls yet. Returns `true`
+/// if the touch was a tap rather than a drag.
+pub(super) fn touches_ended(env: &mut Environment, scroll_view: id, touches: id) -> bool {
+    touches_moved(env, scroll_view, touches);
+
+    let (min, max) = offset_limits(env, scroll_view);
+    let bounds = env.objc.borrow::<UIViewHostObject>(scroll_view).bounds;
+    let (can_scroll_x, can_scroll_y) = scrollable_axes(env, scroll_view);
+    let state = state(env, scroll_view);
+    let Some(tracking) = state.tracking.take() else {
+        return false;
+    };
+    if !std::mem::take(&mut state.dragging) {
+        return true;
+    }
+
+    // The content moves in the opposite direction to the touch.
+    let still = tracking.last_time.elapsed().as_secs_f64() > RELEASE_STILLNESS_TIME;
+    let velocity = if still {
+        CGPoint { x: 0.0, y: 0.0 }
+    } else {
+        CGPoint {
+            x: if can_scroll_x {
+                -tracking.velocity.x
+            } else {
+                0.0
+            },
+            y: if can_scroll_y {
+                -tracking.velocity.y
+            } else {
+                0.0
+            },
+        }
+    };
+    let paging = state.paging_enabled;
+    let x = release_motion(
+        bounds.origin.x,
+        velocity.x,
+        (min.x, max.x),
+        paging.then_some(bounds.size.width),
+    );
+    let y = release_motion(
+        bounds.origin.y,
+        velocity.y,
+        (min.y, max.y),
+        paging.then_some(bounds.size.height),
+    );
+    let will_decelerate = !x.is_stopped() || !y.is_stopped();
+
+    if let Some(delegate) =
+        delegate_responding_to(env, scroll_view, "scrollViewDidEndDragging:willDecelerate:")
+    {
+        let () = msg![env; delegate scrollViewDidEndDragging:scroll_view
+                                               willDecelerate:will_decelerate];
+    }
+    if will_decelerate {
+        if let Some(delegate) =
+            delegate_responding_to(env, scroll_view, "scrollViewWillBeginDecelerating:")
+        {
+            let () = msg![env; delegate scrollViewWillBeginDecelerating:scroll_view];
+        }
+        start_animation(env, scroll_view, x, y, /* decelerating: */ true);
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rubber_banding() {
+        assert_eq!(rubber_band(0.0, 480.0), 0.0);
+        // Resistance increases with distance and never reaches the view size.
+        let small = rubber_band(50.0, 480.0);
+        let large = rubber_band(5000.0, 480.0);
+        assert!(small > 0.0 && small < 50.0);
+        assert!(large > small && large < 480.0);
+        assert_eq!(drag_offset(-50.0, 0.0, 100.0, 480.0, false), 0.0);
+        assert_eq!(drag_offset(-50.0, 0.0, 100.0, 480.0, true), -small);
+    }
+
+    #[test]
+    fn deceleration_and_springs() {
+        let limits = (0.0, 1000.0);
+        let mut motion = AxisMotion::Decelerating { velocity: 1000.0 };
+        let mut position = 0.0;
+        let mut steps = 0;
+        while !motion.is_stopped() {
+            position = motion.step(position, 1.0 / 60.0, limits, 0.998, true);
+            steps += 1;
+            assert!(steps < 600);
+        }
+        // Roughly v / ln(1 / rate) / 1000 = 500 points.
+        assert!(position > 450.0 && position < 520.0, "{}", position);
+
+        let mut motion = release_motion(-100.0, 0.0, limits, None);
+        let mut position = -100.0;
+        let mut steps = 0;
+        while !motion.is_stopped() {
+            position = motion.step(position, 1.0 / 60.0, limits, 0.998, true);
+            steps += 1;
+            assert!(steps < 600);
+        }
+        assert_eq!(position, 0.0);
+    }
+
+    #[test]
+    fn paging() {
+        let limits = (0.0, 960.0);
+        let target = |motion| match motion {
+            AxisMotion::Spring { target, .. } => target,
+            _ => panic!(),
+        };
+        assert_eq!(
+            target(release_motion(200.0, 0.0, limits, Some(320.0))),
+            320.0
+        );
+        assert_eq!(target(release_motion(100.0, 0.0, limits, Some(320.0))), 0.0);
+        assert_eq!(
+            target(release_motion(100.0, 500.0, limits, Some(320.0))),
+            320.0
+        );
+        assert_eq!(
+            target(release_motion(400.0, -500.0, limits, Some(320.0))),
+            320.0
+        );
+        assert_eq!(
+            target(release_motion(950.0, 500.0, limits, Some(320.0))),
+            960.0
+        );
+    }
+}
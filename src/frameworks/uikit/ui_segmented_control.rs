@@ -0,0 +1,415 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UISegmentedControl`.
+
+use super::overlay::{rect, Canvas, Color};
+use super::ui_control::{self, ControlKind, UIControlEventValueChanged};
+use super::{ui_color, ui_font, ui_image};
+use crate::frameworks::core_graphics::{CGFloat, CGRect};
+use crate::frameworks::foundation::{ns_string, NSInteger, NSUInteger};
+use crate::objc::{autorelease, id, msg, nil, objc_classes, release, retain, ClassExports};
+use crate::Environment;
+
+pub type UISegmentedControlStyle = NSInteger;
+pub const UISegmentedControlStylePlain: UISegmentedControlStyle = 0;
+#[allow(dead_code)]
+pub const UISegmentedControlStyleBordered: UISegmentedControlStyle = 1;
+pub const UISegmentedControlStyleBar: UISegmentedControlStyle = 2;
+
+pub const UISegmentedControlNoSegment: NSInteger = -1;
+
+const FONT_SIZE: CGFloat = 13.0;
+const CORNER_RADIUS: CGFloat = 8.0;
+
+struct Segment {
+    title: Option<String>,
+    /// Strong reference to a `UIImage*`, or `nil`.
+    image: id,
+    enabled: bool,
+    /// Zero means the width is automatic.
+    width: CGFloat,
+}
+
+pub(super) struct SegmentedControlState {
+    segments: Vec<Segment>,
+    selected: NSInteger,
+    momentary: bool,
+    style: UISegmentedControlStyle,
+    /// Strong reference to a `UIColor*`, or `nil`.
+    tint_color: id,
+}
+impl Default for SegmentedControlState {
+    fn default() -> Self {
+        SegmentedControlState {
+            segments: Vec::new(),
+            selected: UISegmentedControlNoSegment,
+            momentary: false,
+            style: UISegmentedControlStylePlain,
+            tint_color: nil,
+        }
+    }
+}
+
+fn state(env: &mut Environment, control: id) -> &mut SegmentedControlState {
+    let ControlKind::SegmentedControl(ref mut state) = ui_control::state(env, control).kind else {
+        panic!("{:?} is not a UISegmentedControl", control);
+    };
+    state
+}
+
+/// For use by `UIControl`'s part of `UIView`'s `dealloc`.
+pub(super) fn release_state(env: &mut Environment, state: SegmentedControlState) {
+    for segment in state.segments {
+        if segment.image != nil {
+            release(env, segment.image);
+        }
+    }
+    if state.tint_color != nil {
+        release(env, state.tint_color);
+    }
+}
+
+fn insert_segment(env: &mut Environment, control: id, index: NSUInteger, title: id, image: id) {
+    let title = if title == nil {
+        None
+    } else {
+        Some(ns_string::to_rust_string(env, title).into_owned())
+    };
+    if image != nil {
+        retain(env, image);
+    }
+    let segments = &mut state(env, control).segments;
+    let index = (index as usize).min(segments.len());
+    segments.insert(
+        index,
+        Segment {
+            title,
+            image,
+            enabled: true,
+            width: 0.0,
+        },
+    );
+    // The selected segment stays selected even if its index changes.
+    let state = state(env, control);
+    if state.selected >= index as NSInteger {
+        state.selected += 1;
+    }
+    ui_control::changed(env);
+}
+
+fn segment_mut(env: &mut Environment, control: id, index: NSUInteger) -> Option<&mut Segment> {
+    state(env, control).segments.get_mut(index as usize)
+}
+
+/// Compute the frame of each segment, relative to the control.
+fn segment_frames(state: &SegmentedControlState, bounds: CGRect) -> Vec<CGRect> {
+    let fixed_width: CGFloat = state.segments.iter().map(|segment| segment.width).sum();
+    let automatic_count = state
+        .segments
+        .iter()
+        .filter(|segment| segment.width == 0.0)
+        .count();
+    let automatic_width = if automatic_count > 0 {
+        ((bounds.size.width - fixed_width) / automatic_count as CGFloat).max(0.0)
+    } else {
+        0.0
+    };
+    let mut x = bounds.origin.x;
+    state
+        .segments
+        .iter()
+        .map(|segment| {
+            let width = if segment.width == 0.0 {
+                automatic_width
+            } else {
+                segment.width
+            };
+            let frame = rect(x, bounds.origin.y, width, bounds.size.height);
+            x += width;
+            frame
+        })
+        .collect()
+}
+
+fn set_selected(env: &mut Environment, control: id, index: NSInteger) {
+    state(env, control).selected = index;
+    ui_control::changed(env);
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UISegmentedControl: UIControl
+
+- (id)initWithItems:(id)items { // NSArray* of NSString* or UIImage*
+    let ns_string_class = env.objc.get_known_class("NSString", &mut env.mem);
+    let count: NSUInteger = if items == nil { 0 } else { msg![env; items count] };
+    // UIKit sizes the control to fit its items.
+    // TODO: measure the items
+    let frame = rect(0.0, 0.0, count as CGFloat * 60.0, 44.0);
+    let this: id = msg![env; this initWithFrame:frame];
+    for i in 0..count {
+        let item: id = msg![env; items objectAtIndex:i];
+        if msg![env; item isKindOfClass:ns_string_class] {
+            insert_segment(env, this, i, item, nil);
+        } else {
+            insert_segment(env, this, i, nil, item);
+        }
+    }
+    this
+}
+
+- (NSUInteger)numberOfSegments {
+    state(env, this).segments.len() as NSUInteger
+}
+
+- (NSInteger)selectedSegmentIndex {
+    state(env, this).selected
+}
+- (())setSelectedSegmentIndex:(NSInteger)index {
+    set_selected(env, this, index);
+}
+
+- (bool)isMomentary {
+    state(env, this).momentary
+}
+- (())setMomentary:(bool)momentary {
+    state(env, this).momentary = momentary;
+}
+
+- (UISegmentedControlStyle)segmentedControlStyle {
+    state(env, this).style
+}
+- (())setSegmentedControlStyle:(UISegmentedControlStyle)style {
+    state(env, this).style = style;
+    // The bar style is shorter.
+    let mut frame: CGRect = msg![env; this frame];
+    frame.size.height = if style == UISegmentedControlStyleBar {
+        30.0
+    } else {
+        44.0
+    };
+    () = msg![env; this setFrame:frame];
+    ui_control::changed(env);
+}
+
+- (id)tintColor {
+    state(env, this).tint_color
+}
+- (())setTintColor:(id)color { // UIColor*
+    if color != nil {
+        retain(env, color);
+    }
+    let old = std::mem::replace(&mut state(env, this).tint_color, color);
+    if old != nil {
+        release(env, old);
+    }
+    ui_control::changed(env);
+}
+
+- (())insertSegmentWithTitle:(id)title // NSString*
+                     atIndex:(NSUInteger)index
+                    animated:(bool)_animated {
+    insert_segment(env, this, index, title, nil);
+}
+- (())insertSegmentWithImage:(id)image // UIImage*
+                     atIndex:(NSUInteger)index
+                    animated:(bool)_animated {
+    insert_segment(env, this, index, nil, image);
+}
+- (())removeSegmentAtIndex:(NSUInteger)index
+                  animated:(bool)_animated {
+    let state = state(env, this);
+    if index as usize >= state.segments.len() {
+        return;
+    }
+    let segment = state.segments.remove(index as usize);
+    let index = index as NSInteger;
+    if state.selected == index {
+        state.selected = UISegmentedControlNoSegment;
+    } else if state.selected > index {
+        state.selected -= 1;
+    }
+    if segment.image != nil {
+        release(env, segment.image);
+    }
+    ui_control::changed(env);
+}
+- (())removeAllSegments {
+    let segments = std::mem::take(&mut state(env, this).segments);
+    state(env, this).selected = UISegmentedControlNoSegment;
+    for segment in segments {
+        if segment.image != nil {
+            release(env, segment.image);
+        }
+    }
+    ui_control::changed(env);
+}
+
+- (())setTitle:(id)title // NSString*
+    forSegmentAtIndex:(NSUInteger)index {
+    let title = if title == nil {
+        None
+    } else {
+        Some(ns_string::to_rust_string(env, title).into_owned())
+    };
+    if let Some(segment) = segment_mut(env, this, index) {
+        segment.title = title;
+    }
+    ui_control::changed(env);
+}
+- (id)titleForSegmentAtIndex:(NSUInteger)index {
+    let title = segment_mut(env, this, index).and_then(|segment| segment.title.clone());
+    match title {
+        Some(title) => {
+            let title = ns_string::from_rust_string(env, title);
+            autorelease(env, title)
+        }
+        None => nil,
+    }
+}
+- (())setImage:(id)image // UIImage*
+    forSegmentAtIndex:(NSUInteger)index {
+    let Some(segment) = segment_mut(env, this, index) else {
+        return;
+    };
+    let old = std::mem::replace(&mut segment.image, image);
+    if image != nil {
+        retain(env, image);
+    }
+    if old != nil {
+        release(env, old);
+    }
+    ui_control::changed(env);
+}
+- (id)imageForSegmentAtIndex:(NSUInteger)index {
+    segment_mut(env, this, index).map_or(nil, |segment| segment.image)
+}
+- (())setEnabled:(bool)enabled
+    forSegmentAtIndex:(NSUInteger)index {
+    if let Some(segment) = segment_mut(env, this, index) {
+        segment.enabled = enabled;
+    }
+    ui_control::changed(env);
+}
+- (bool)isEnabledForSegmentAtIndex:(NSUInteger)index {
+    matches!(segment_mut(env, this, index), Some(segment) if segment.enabled)
+}
+- (())setWidth:(CGFloat)width
+    forSegmentAtIndex:(NSUInteger)index {
+    if let Some(segment) = segment_mut(env, this, index) {
+        segment.width = width;
+    }
+    ui_control::changed(env);
+}
+- (CGFloat)widthForSegmentAtIndex:(NSUInteger)index {
+    segment_mut(env, this, index).map_or(0.0, |segment| segment.width)
+}
+
+- (bool)beginTrackingWithTouch:(id)touch // UITouch*
+                     withEvent:(id)event { // UIEvent*
+    let Some(location) = ui_control::touch_location(env, this, touch) else {
+        return false;
+    };
+    let bounds: CGRect = msg![env; this bounds];
+    let state = state(env, this);
+    let frames = segment_frames(state, bounds);
+    let Some(index) = frames
+        .iter()
+        .position(|frame| location.x >= frame.origin.x && location.x < frame.origin.x + frame.size.width)
+    else {
+        return false;
+    };
+    if !state.segments[index].enabled {
+        return false;
+    }
+    let index = index as NSInteger;
+    // Touching the selected segment again doesn't change anything, unless the
+    // control is momentary.
+    if state.selected != index || state.momentary {
+        set_selected(env, this, index);
+        ui_control::send_actions(env, this, UIControlEventValueChanged, event);
+    }
+    true
+}
+- (())endTrackingWithTouch:(id)_touch // UITouch*
+                 withEvent:(id)_event { // UIEvent*
+    if state(env, this).momentary {
+        set_selected(env, this, UISegmentedControlNoSegment);
+    }
+}
+
+@end
+
+};
+
+/// For use by `UIControl`: draw a segmented control in the controls overlay.
+pub(super) fn draw(env: &mut Environment, canvas: &mut Canvas, control: id, frame: CGRect) {
+    let tint_color = state(env, control).tint_color;
+    let tint: Color = if tint_color != nil {
+        ui_color::get_rgba(env, tint_color)
+    } else {
+        (0.15, 0.4, 0.85, 1.0)
+    };
+    let state = state(env, control);
+    let frames = segment_frames(state, frame);
+    let selected = state.selected;
+    let segments: Vec<(Option<String>, id, bool)> = state
+        .segments
+        .iter()
+        .map(|segment| (segment.title.clone(), segment.image, segment.enabled))
+        .collect();
+
+    canvas.fill_rounded_rect(frame, CORNER_RADIUS, (0.55, 0.55, 0.55, 1.0));
+    let inner = rect(
+        frame.origin.x + 1.0,
+        frame.origin.y + 1.0,
+        frame.size.width - 2.0,
+        frame.size.height - 2.0,
+    );
+    canvas.fill_rounded_rect(inner, CORNER_RADIUS - 1.0, (0.98, 0.98, 0.98, 1.0));
+
+    for (index, ((title, image, enabled), segment_frame)) in
+        segments.into_iter().zip(frames).enumerate()
+    {
+        let is_selected = index as NSInteger == selected;
+        if is_selected {
+            // Only round the corners at the ends of the control.
+            canvas.set_clip(Some(segment_frame));
+            canvas.fill_rounded_rect(inner, CORNER_RADIUS - 1.0, tint);
+            canvas.set_clip(None);
+        }
+        if index > 0 {
+            let separator = rect(
+                segment_frame.origin.x,
+                frame.origin.y,
+                1.0,
+                frame.size.height,
+            );
+            canvas.fill_rounded_rect(separator, 0.0, (0.55, 0.55, 0.55, 1.0));
+        }
+        let alpha = if enabled { 1.0 } else { 0.4 };
+        if image != nil {
+            let image = ui_image::borrow_image(&env.objc, image);
+            let (width, height) = image.dimensions();
+            let content = ui_control::place_content(
+                segment_frame,
+                (width as CGFloat, height as CGFloat),
+                ui_control::UIControlContentHorizontalAlignmentCenter,
+            );
+            canvas.draw_image(image, content);
+        } else if let Some(title) = title {
+            let color = if is_selected {
+                (1.0, 1.0, 1.0, alpha)
+            } else {
+                (0.3, 0.3, 0.3, alpha)
+            };
+            let font = ui_font::system_font(env, true, &title);
+            canvas.draw_text_centered(font, FONT_SIZE, &title, segment_frame, color);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UISlider`.
+
+use super::overlay::{rect, Canvas};
+use super::ui_control::{self, ControlKind, UIControlEventValueChanged, UIControlState};
+use super::ui_image;
+use crate::frameworks::core_graphics::{CGFloat, CGRect};
+use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports};
+use crate::Environment;
+
+const THUMB_SIZE: CGFloat = 23.0;
+const TRACK_HEIGHT: CGFloat = 9.0;
+
+pub(super) struct SliderState {
+    value: f32,
+    minimum_value: f32,
+    maximum_value: f32,
+    continuous: bool,
+    /// Strong reference to a `UIImage*`. Only the normal state's images are
+    /// kept, the other states use them too.
+    thumb_image: id,
+    /// Strong reference to a `UIImage*`.
+    minimum_track_image: id,
+    /// Strong reference to a `UIImage*`.
+    maximum_track_image: id,
+    /// The value when tracking began, to know whether it changed.
+    value_at_begin: f32,
+    /// Where on the thumb the touch began, so the thumb doesn't jump.
+    thumb_offset: CGFloat,
+}
+impl Default for SliderState {
+    fn default() -> Self {
+        SliderState {
+            value: 0.0,
+            minimum_value: 0.0,
+            maximum_value: 1.0,
+            continuous: true,
+            thumb_image: nil,
+            minimum_track_image: nil,
+            maximum_track_image: nil,
+            value_at_begin: 0.0,
+            thumb_offset: 0.0,
+        }
+    }
+}
+
+fn state(env: &mut Environment, slider: id) -> &mut SliderState {
+    let ControlKind::Slider(ref mut state) = ui_control::state(env, slider).kind else {
+        panic!("{:?} is not a UISlider", slider);
+    };
+    state
+}
+
+/// For use by `UIControl`'s part of `UIView`'s `dealloc`.
+pub(super) fn release_state(env: &mut Environment, state: SliderState) {
+    for image in [
+        state.thumb_image,
+        state.minimum_track_image,
+        state.maximum_track_image,
+    ] {
+        if image != nil {
+            release(env, image);
+        }
+    }
+}
+
+fn set_value(env: &mut Environment, slider: id, value: f32) {
+    let state = state(env, slider);
+    state.value = value.clamp(
+        state.minimum_value,
+        state.maximum_value.max(state.minimum_value),
+    );
+    ui_control::changed(env);
+}
+
+fn set_image(
+    env: &mut Environment,
+    slider: id,
+    field: fn(&mut SliderState) -> &mut id,
+    image: id,
+    control_state: UIControlState,
+) {
+    if control_state != ui_control::UIControlStateNormal {
+        log!("TODO: UISlider image for state {}, ignoring", control_state);
+        return;
+    }
+    if image != nil {
+        retain(env, image);
+    }
+    let old = std::mem::replace(field(state(env, slider)), image);
+    if old != nil {
+        release(env, old);
+    }
+    ui_control::changed(env);
+}
+
+/// The position of the thumb's center along the track, relative to the
+/// slider's left edge.
+fn thumb_center(state: &SliderState, width: CGFloat) -> CGFloat {
+    let range = state.maximum_value - state.minimum_value;
+    let fraction = if range > 0.0 {
+        (state.value - state.minimum_value) / range
+    } else {
+        0.0
+    };
+    THUMB_SIZE / 2.0 + fraction * (width - THUMB_SIZE).max(0.0)
+}
+
+/// Update the value for a touch that is dragging the thumb.
+fn track_touch(env: &mut Environment, slider: id, touch: id) {
+    let Some(location) = ui_control::touch_location(env, slider, touch) else {
+        return;
+    };
+    let bounds: CGRect = msg![env; slider bounds];
+    let state = state(env, slider);
+    let travel = (bounds.size.width - THUMB_SIZE).max(1.0);
+    let center = location.x - bounds.origin.x - state.thumb_offset;
+    let fraction = ((center - THUMB_SIZE / 2.0) / travel).clamp(0.0, 1.0);
+    let value = state.minimum_value + fraction * (state.maximum_value - state.minimum_value);
+    set_value(env, slider, value);
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UISlider: UIControl
+
+- (f32)value {
+    state(env, this).value
+}
+- (())setValue:(f32)value {
+    set_value(env, this, value);
+}
+- (())setValue:(f32)value animated:(bool)_animated {
+    set_value(env, this, value);
+}
+- (f32)minimumValue {
+    state(env, this).minimum_value
+}
+- (())setMinimumValue:(f32)value {
+    state(env, this).minimum_value = value;
+    let value = state(env, this).value;
+    set_value(env, this, value);
+}
+- (f32)maximumValue {
+    state(env, this).maximum_value
+}
+- (())setMaximumValue:(f32)value {
+    state(env, this).maximum_value = value;
+    let value = state(env, this).value;
+    set_value(env, this, value);
+}
+- (bool)isContinuous {
+    state(env, this).continuous
+}
+- (())setContinuous:(bool)continuous {
+    state(env, this).continuous = continuous;
+}
+
+- (())setThumbImage:(id)image // UIImage*
+           forState:(UIControlState)control_state {
+    set_image(env, this, |state| &mut state.thumb_image, image, control_state);
+}
+- (id)thumbImageForState:(UIControlState)_control_state {
+    state(env, this).thumb_image
+}
+- (id)currentThumbImage {
+    state(env, this).thumb_image
+}
+- (())setMinimumTrackImage:(id)image // UIImage*
+                  forState:(UIControlState)control_state {
+    set_image(env, this, |state| &mut state.minimum_track_image, image, control_state);
+}
+- (id)minimumTrackImageForState:(UIControlState)_control_state {
+    state(env, this).minimum_track_image
+}
+- (())setMaximumTrackImage:(id)image // UIImage*
+                  forState:(UIControlState)control_state {
+    set_image(env, this, |state| &mut state.maximum_track_image, image, control_state);
+}
+- (id)maximumTrackImageForState:(UIControlState)_control_state {
+    state(env, this).maximum_track_image
+}
+
+- (bool)beginTrackingWithTouch:(id)touch // UITouch*
+                     withEvent:(id)_event { // UIEvent*
+    let Some(location) = ui_control::touch_location(env, this, touch) else {
+        return false;
+    };
+    let bounds: CGRect = msg![env; this bounds];
+    let state = state(env, this);
+    let center = thumb_center(state, bounds.size.width);
+    let offset = location.x - bounds.origin.x - center;
+    // Only touches on (or near) the thumb move it.
+    if offset.abs() > THUMB_SIZE {
+        return false;
+    }
+    state.thumb_offset = offset;
+    state.value_at_begin = state.value;
+    true
+}
+- (bool)continueTrackingWithTouch:(id)touch // UITouch*
+                        withEvent:(id)event { // UIEvent*
+    let old_value = state(env, this).value;
+    track_touch(env, this, touch);
+    let &mut SliderState { value, continuous, .. } = state(env, this);
+    if continuous && value != old_value {
+        ui_control::send_actions(env, this, UIControlEventValueChanged, event);
+    }
+    true
+}
+- (())endTrackingWithTouch:(id)_touch // UITouch*
+                 withEvent:(id)event { // UIEvent*
+    let &mut SliderState {
+        value,
+        value_at_begin,
+        continuous,
+        ..
+    } = state(env, this);
+    if !continuous && value != value_at_begin {
+        ui_control::send_actions(env, this, UIControlEventValueChanged, event);
+    }
+}
+
+@end
+
+};
+
+/// For use by `UIControl`: draw a slider in the controls overlay.
+pub(super) fn draw(env: &mut Environment, canvas: &mut Canvas, slider: id, frame: CGRect) {
+    let state = state(env, slider);
+    let center = frame.origin.x + thumb_center(state, frame.size.width);
+    let (thumb_image, minimum_track_image, maximum_track_image) = (
+        state.thumb_image,
+        state.minimum_track_image,
+        state.maximum_track_image,
+    );
+    let middle = frame.origin.y + frame.size.height / 2.0;
+
+    let track_top = middle - TRACK_HEIGHT / 2.0;
+    let left = frame.origin.x + 2.0;
+    let right = frame.origin.x + frame.size.width - 2.0;
+    let minimum_track = rect(left, track_top, (center - left).max(0.0), TRACK_HEIGHT);
+    let maximum_track = rect(center, track_top, (right - center).max(0.0), TRACK_HEIGHT);
+    if minimum_track_image != nil {
+        let image = ui_image::borrow_image(&env.objc, minimum_track_image);
+        canvas.draw_image(image, minimum_track);
+    } else {
+        canvas.fill_rounded_rect(minimum_track, TRACK_HEIGHT / 2.0, (0.1, 0.35, 0.8, 1.0));
+    }
+    if maximum_track_image != nil {
+        let image = ui_image::borrow_image(&env.objc, maximum_track_image);
+        canvas.draw_image(image, maximum_track);
+    } else {
+        canvas.fill_rounded_rect(maximum_track, TRACK_HEIGHT / 2.0, (0.55, 0.55, 0.55, 1.0));
+        let inner = rect(
+            maximum_track.origin.x,
+            maximum_track.origin.y + 1.0,
+            (maximum_track.size.width - 1.0).max(0.0),
+            TRACK_HEIGHT - 2.0,
+        );
+        canvas.fill_rounded_rect(inner, TRACK_HEIGHT / 2.0 - 1.0, (0.97, 0.97, 0.97, 1.0));
+    }
+
+    if thumb_image != nil {
+        let image = ui_image::borrow_image(&env.objc, thumb_image);
+        let (width, height) = image.dimensions();
+        let (width, height) = (width as CGFloat, height as CGFloat);
+        canvas.draw_image(
+            image,
+            rect(center - width / 2.0, middle - height / 2.0, width, height),
+        );
+    } else {
+        let thumb = rect(
+            center - THUMB_SIZE / 2.0,
+            middle - THUMB_SIZE / 2.0,
+            THUMB_SIZE,
+            THUMB_SIZE,
+        );
+        canvas.fill_rounded_rect(thumb, THUMB_SIZE / 2.0, (0.5, 0.5, 0.5, 1.0));
+        let inner = rect(
+            thumb.origin.x + 1.0,
+            thumb.origin.y + 1.0,
+            THUMB_SIZE - 2.0,
+            THUMB_SIZE - 2.0,
+        );
+        canvas.fill_rounded_rect(inner, THUMB_SIZE / 2.0 - 1.0, (0.98, 0.98, 0.98, 1.0));
+    }
+}
@@ -0,0 +1,104 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UISwitch`.
+
+use super::overlay::{rect, Canvas};
+use super::ui_control::{self, ControlKind, UIControlEventValueChanged};
+use super::ui_font;
+use crate::frameworks::core_graphics::{CGFloat, CGRect};
+use crate::objc::{id, objc_classes, ClassExports};
+use crate::Environment;
+
+/// A switch is always this size, whatever its frame says.
+const WIDTH: CGFloat = 94.0;
+const HEIGHT: CGFloat = 27.0;
+const CORNER_RADIUS: CGFloat = 5.0;
+
+#[derive(Default)]
+pub(super) struct SwitchState {
+    on: bool,
+}
+
+fn state(env: &mut Environment, switch: id) -> &mut SwitchState {
+    let ControlKind::Switch(ref mut state) = ui_control::state(env, switch).kind else {
+        panic!("{:?} is not a UISwitch", switch);
+    };
+    state
+}
+
+fn set_on(env: &mut Environment, switch: id, on: bool) {
+    state(env, switch).on = on;
+    ui_control::changed(env);
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UISwitch: UIControl
+
+- (bool)isOn {
+    state(env, this).on
+}
+- (())setOn:(bool)on {
+    set_on(env, this, on);
+}
+- (())setOn:(bool)on animated:(bool)_animated {
+    set_on(env, this, on);
+}
+
+- (())endTrackingWithTouch:(id)_touch // UITouch*
+                 withEvent:(id)event { // UIEvent*
+    // TODO: dragging the thumb
+    if ui_control::state(env, this).touch_inside {
+        let on = !state(env, this).on;
+        set_on(env, this, on);
+        ui_control::send_actions(env, this, UIControlEventValueChanged, event);
+    }
+}
+
+@end
+
+};
+
+/// For use by `UIControl`: draw a switch in the controls overlay.
+pub(super) fn draw(env: &mut Environment, canvas: &mut Canvas, switch: id, frame: CGRect) {
+    let on = state(env, switch).on;
+    let outer = rect(frame.origin.x, frame.origin.y, WIDTH, HEIGHT);
+    canvas.fill_rounded_rect(outer, CORNER_RADIUS, (0.45, 0.45, 0.45, 1.0));
+    let inner = rect(
+        outer.origin.x + 1.0,
+        outer.origin.y + 1.0,
+        WIDTH - 2.0,
+        HEIGHT - 2.0,
+    );
+    let (fill, text, text_color) = if on {
+        ((0.0, 0.5, 0.95, 1.0), "ON", (1.0, 1.0, 1.0, 1.0))
+    } else {
+        ((0.93, 0.93, 0.93, 1.0), "OFF", (0.45, 0.45, 0.45, 1.0))
+    };
+    canvas.fill_rounded_rect(inner, CORNER_RADIUS - 1.0, fill);
+
+    let thumb_width = WIDTH * 0.42;
+    let (thumb_x, label_x) = if on {
+        (outer.origin.x + WIDTH - thumb_width, outer.origin.x)
+    } else {
+        (outer.origin.x, outer.origin.x + thumb_width)
+    };
+    let thumb = rect(thumb_x, outer.origin.y, thumb_width, HEIGHT);
+    canvas.fill_rounded_rect(thumb, CORNER_RADIUS, (0.55, 0.55, 0.55, 1.0));
+    let thumb_inner = rect(
+        thumb.origin.x + 1.0,
+        thumb.origin.y + 1.0,
+        thumb_width - 2.0,
+        HEIGHT - 2.0,
+    );
+    canvas.fill_rounded_rect(thumb_inner, CORNER_RADIUS - 1.0, (0.98, 0.98, 0.98, 1.0));
+
+    let label = rect(label_x, outer.origin.y, WIDTH - thumb_width, HEIGHT);
+    let font = ui_font::system_font(env, true, text);
+    canvas.draw_text_centered(font, 16.0, text, label, text_color);
+}
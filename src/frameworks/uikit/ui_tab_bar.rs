@@ -0,0 +1,552 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITabBar`, `UIBarItem` and `UITabBarItem`.
+//!
+//! Tab bars are drawn by the host as an overlay (see [super::overlay]), since
+//! views aren't composited yet. Like in iPhone OS 3, item images are only used
+//! as a mask: they are drawn grey, or with a blue gradient when selected.
+
+use super::overlay::{rect, Canvas, Color, Overlay};
+use super::ui_view::{frame_on_screen, UIViewHostObject};
+use super::{ui_font, ui_image};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::foundation::{ns_array, ns_string, NSInteger, NSUInteger};
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::window::{Event, TouchSource};
+use crate::Environment;
+
+pub type UITabBarSystemItem = NSInteger;
+
+/// Titles of the system items, indexed by `UITabBarSystemItem`, from
+/// `UITabBarSystemItemMore` to `UITabBarSystemItemMostViewed`.
+const SYSTEM_ITEM_TITLES: [&str; 12] = [
+    "More",
+    "Favorites",
+    "Featured",
+    "Top Rated",
+    "Recents",
+    "Contacts",
+    "History",
+    "Bookmarks",
+    "Search",
+    "Downloads",
+    "Most Recent",
+    "Most Viewed",
+];
+
+/// The height of a tab bar, which UIKit doesn't let apps change.
+pub(super) const TAB_BAR_HEIGHT: CGFloat = 49.0;
+const ICON_SIZE: CGFloat = 30.0;
+const TITLE_FONT_SIZE: CGFloat = 10.0;
+const BADGE_FONT_SIZE: CGFloat = 13.0;
+
+#[derive(Default)]
+pub struct State {
+    /// The touch that began on a tab bar, if any.
+    touch: Option<TouchSource>,
+    overlay: Option<Overlay>,
+    /// What the overlay shows.
+    overlay_contents: Vec<DrawnTabBar>,
+}
+
+#[derive(Clone, PartialEq)]
+struct DrawnTabBar {
+    frame: CGRect,
+    items: Vec<DrawnItem>,
+    selected: Option<usize>,
+}
+
+#[derive(Clone, PartialEq)]
+struct DrawnItem {
+    title: Option<String>,
+    /// `UIImage*`
+    image: id,
+    badge: Option<String>,
+    enabled: bool,
+}
+
+pub(super) struct TabBarState {
+    /// `UITabBarItem*`s, strong references.
+    items: Vec<id>,
+    /// Weak reference.
+    selected_item: id,
+    /// Weak reference.
+    delegate: id,
+}
+
+/// For use by `UIView`'s `dealloc`.
+pub(super) fn release_state(env: &mut Environment, state: TabBarState) {
+    for item in state.items {
+        release(env, item);
+    }
+}
+
+fn state(env: &mut Environment, tab_bar: id) -> &mut TabBarState {
+    env.objc
+        .borrow_mut::<UIViewHostObject>(tab_bar)
+        .tab_bar
+        .get_or_insert_with(|| {
+            Box::new(TabBarState {
+                items: Vec::new(),
+                selected_item: nil,
+                delegate: nil,
+            })
+        })
+}
+
+struct UIBarItemHostObject {
+    /// `NSString*`, strong reference.
+    title: id,
+    /// `UIImage*`, strong reference.
+    image: id,
+    tag: NSInteger,
+    enabled: bool,
+    /// `UITabBarItem` only. `NSString*`, strong reference.
+    badge_value: id,
+}
+impl HostObject for UIBarItemHostObject {}
+
+/// Replace one of the strong references of a bar item.
+fn set_item_object(
+    env: &mut Environment,
+    item: id,
+    field: fn(&mut UIBarItemHostObject) -> &mut id,
+    object: id,
+) {
+    if object != nil {
+        retain(env, object);
+    }
+    let old = std::mem::replace(field(env.objc.borrow_mut(item)), object);
+    if old != nil {
+        release(env, old);
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIBarItem: NSObject
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(UIBarItemHostObject {
+        title: nil,
+        image: nil,
+        tag: 0,
+        enabled: true,
+        badge_value: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    let &UIBarItemHostObject {
+        title,
+        image,
+        badge_value,
+        ..
+    } = env.objc.borrow(this);
+    for object in [title, image, badge_value] {
+        if object != nil {
+            release(env, object);
+        }
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)title {
+    env.objc.borrow::<UIBarItemHostObject>(this).title
+}
+- (())setTitle:(id)title { // NSString*
+    let title: id = msg![env; title copy];
+    set_item_object(env, this, |host_object| &mut host_object.title, title);
+    if title != nil {
+        release(env, title);
+    }
+}
+- (id)image {
+    env.objc.borrow::<UIBarItemHostObject>(this).image
+}
+- (())setImage:(id)image { // UIImage*
+    set_item_object(env, this, |host_object| &mut host_object.image, image);
+}
+- (NSInteger)tag {
+    env.objc.borrow::<UIBarItemHostObject>(this).tag
+}
+- (())setTag:(NSInteger)tag {
+    env.objc.borrow_mut::<UIBarItemHostObject>(this).tag = tag;
+}
+- (bool)isEnabled {
+    env.objc.borrow::<UIBarItemHostObject>(this).enabled
+}
+- (())setEnabled:(bool)enabled {
+    env.objc.borrow_mut::<UIBarItemHostObject>(this).enabled = enabled;
+}
+
+@end
+
+@implementation UITabBarItem: UIBarItem
+
+- (id)initWithTitle:(id)title // NSString*
+              image:(id)image // UIImage*
+                tag:(NSInteger)tag {
+    () = msg![env; this setTitle:title];
+    () = msg![env; this setImage:image];
+    () = msg![env; this setTag:tag];
+    this
+}
+
+- (id)initWithTabBarSystemItem:(UITabBarSystemItem)system_item
+                           tag:(NSInteger)tag {
+    // TODO: draw the system items' icons
+    let Some(&title) = usize::try_from(system_item)
+        .ok()
+        .and_then(|index| SYSTEM_ITEM_TITLES.get(index))
+    else {
+        panic!("Unknown UITabBarSystemItem {}", system_item);
+    };
+    let title = ns_string::get_static_str(env, title);
+    () = msg![env; this setTitle:title];
+    () = msg![env; this setTag:tag];
+    this
+}
+
+- (id)badgeValue {
+    env.objc.borrow::<UIBarItemHostObject>(this).badge_value
+}
+- (())setBadgeValue:(id)badge_value { // NSString*
+    let badge_value: id = msg![env; badge_value copy];
+    set_item_object(env, this, |host_object| &mut host_object.badge_value, badge_value);
+    if badge_value != nil {
+        release(env, badge_value);
+    }
+}
+
+@end
+
+@implementation UITabBar: UIView
+
+- (id)delegate {
+    state(env, this).delegate
+}
+- (())setDelegate:(id)delegate {
+    state(env, this).delegate = delegate;
+}
+
+- (id)items {
+    let items = state(env, this).items.clone();
+    if items.is_empty() {
+        return nil;
+    }
+    for &item in &items {
+        retain(env, item);
+    }
+    let items = ns_array::from_vec(env, items);
+    autorelease(env, items)
+}
+- (())setItems:(id)items { // NSArray* of UITabBarItem*
+    let count: NSUInteger = if items == nil { 0 } else { msg![env; items count] };
+    let mut new_items = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let item: id = msg![env; items objectAtIndex:i];
+        new_items.push(retain(env, item));
+    }
+    let state = state(env, this);
+    if !new_items.contains(&state.selected_item) {
+        state.selected_item = nil;
+    }
+    let old_items = std::mem::replace(&mut state.items, new_items);
+    for item in old_items {
+        release(env, item);
+    }
+}
+- (())setItems:(id)items // NSArray* of UITabBarItem*
+      animated:(bool)_animated {
+    msg![env; this setItems:items]
+}
+
+- (id)selectedItem {
+    state(env, this).selected_item
+}
+- (())setSelectedItem:(id)item { // UITabBarItem*
+    state(env, this).selected_item = item;
+}
+
+@end
+
+};
+
+/// Selecting an item by touching it, which unlike `setSelectedItem:` informs
+/// the delegate.
+fn select_item(env: &mut Environment, tab_bar: id, item: id) {
+    if !env.objc.borrow::<UIBarItemHostObject>(item).enabled {
+        return;
+    }
+    log_dbg!("{:?} selecting item {:?}", tab_bar, item);
+    let state = state(env, tab_bar);
+    state.selected_item = item;
+    let delegate = state.delegate;
+    if delegate == nil {
+        return;
+    }
+    if let Some(selector) = env.objc.lookup_selector("tabBar:didSelectItem:") {
+        if msg![env; delegate respondsToSelector:selector] {
+            let () = msg![env; delegate tabBar:tab_bar didSelectItem:item];
+        }
+    }
+}
+
+fn visible_tab_bars(env: &mut Environment) -> Vec<(id, CGRect)> {
+    let tab_bar_class = env.objc.get_known_class("UITabBar", &mut env.mem);
+    let views = env.framework_state.uikit.ui_view.views.clone();
+    let mut tab_bars = Vec::new();
+    for view in views {
+        if !msg![env; view isKindOfClass:tab_bar_class]
+            || env.objc.borrow::<UIViewHostObject>(view).hidden
+        {
+            continue;
+        }
+        let Some(frame) = frame_on_screen(env, view) else {
+            continue;
+        };
+        if frame.size.width > 0.0 && frame.size.height > 0.0 {
+            tab_bars.push((view, frame));
+        }
+    }
+    tab_bars
+}
+
+/// The area of each item of a tab bar. Items are evenly spaced.
+fn item_rects(frame: CGRect, count: usize) -> impl Iterator<Item = CGRect> {
+    let width = frame.size.width / count.max(1) as CGFloat;
+    (0..count).map(move |i| {
+        rect(
+            frame.origin.x + i as CGFloat * width,
+            frame.origin.y,
+            width,
+            frame.size.height,
+        )
+    })
+}
+
+fn drawn_tab_bar(env: &mut Environment, tab_bar: id, frame: CGRect) -> DrawnTabBar {
+    let &mut TabBarState {
+        ref items,
+        selected_item,
+        ..
+    } = state(env, tab_bar);
+    let items = items.clone();
+    let selected = items.iter().position(|&item| item == selected_item);
+    let items = items
+        .into_iter()
+        .map(|item| {
+            let &UIBarItemHostObject {
+                title,
+                image,
+                enabled,
+                badge_value,
+                ..
+            } = env.objc.borrow(item);
+            let to_string = |env: &mut Environment, string: id| {
+                (string != nil).then(|| ns_string::to_rust_string(env, string).into_owned())
+            };
+            DrawnItem {
+                title: to_string(env, title),
+                image,
+                badge: to_string(env, badge_value),
+                enabled,
+            }
+        })
+        .collect();
+    DrawnTabBar {
+        frame,
+        items,
+        selected,
+    }
+}
+
+fn draw_tab_bar(env: &mut Environment, canvas: &mut Canvas, tab_bar: &DrawnTabBar) {
+    let frame = tab_bar.frame;
+    canvas.set_clip(Some(frame));
+    // Background: a dark gradient with a glossy top half.
+    let half = frame.size.height / 2.0;
+    canvas.fill_rounded_rect(frame, 0.0, (0.0, 0.0, 0.0, 1.0));
+    canvas.fill_rounded_rect(
+        rect(frame.origin.x, frame.origin.y, frame.size.width, half),
+        0.0,
+        (0.16, 0.16, 0.16, 1.0),
+    );
+    canvas.fill_rounded_rect(
+        rect(frame.origin.x, frame.origin.y, frame.size.width, 1.0),
+        0.0,
+        (0.3, 0.3, 0.3, 1.0),
+    );
+
+    for (i, (item, item_rect)) in tab_bar
+        .items
+        .iter()
+        .zip(item_rects(frame, tab_bar.items.len()))
+        .enumerate()
+    {
+        let selected = tab_bar.selected == Some(i);
+        let alpha = if item.enabled { 1.0 } else { 0.5 };
+        if selected {
+            let highlight = rect(
+                item_rect.origin.x + 2.0,
+                item_rect.origin.y + 2.0,
+                item_rect.size.width - 4.0,
+                item_rect.size.height - 4.0,
+            );
+            canvas.fill_rounded_rect(highlight, 3.0, (1.0, 1.0, 1.0, 0.15));
+        }
+
+        let icon_rect = rect(
+            item_rect.origin.x + (item_rect.size.width - ICON_SIZE) / 2.0,
+            item_rect.origin.y + 3.0,
+            ICON_SIZE,
+            ICON_SIZE,
+        );
+        if item.image != nil {
+            let (top, bottom): (Color, Color) = if selected {
+                ((0.55, 0.8, 1.0, alpha), (0.1, 0.45, 0.95, alpha))
+            } else {
+                ((0.6, 0.6, 0.6, alpha), (0.45, 0.45, 0.45, alpha))
+            };
+            let image = ui_image::borrow_image(&env.objc, item.image);
+            let (width, height) = image.dimensions();
+            let (width, height) = (width as CGFloat, height as CGFloat);
+            // Images are centered and only scaled down if they don't fit.
+            let scale = (ICON_SIZE / width.max(1.0))
+                .min(ICON_SIZE / height.max(1.0))
+                .min(1.0);
+            let image_rect = rect(
+                icon_rect.origin.x + (ICON_SIZE - width * scale) / 2.0,
+                icon_rect.origin.y + (ICON_SIZE - height * scale) / 2.0,
+                width * scale,
+                height * scale,
+            );
+            canvas.draw_image_mask(image, image_rect, top, bottom);
+        }
+
+        if let Some(ref title) = item.title {
+            let color = if selected {
+                (1.0, 1.0, 1.0, alpha)
+            } else {
+                (0.6, 0.6, 0.6, alpha)
+            };
+            let title_rect = rect(
+                item_rect.origin.x,
+                item_rect.origin.y + item_rect.size.height - 14.0,
+                item_rect.size.width,
+                12.0,
+            );
+            let font = ui_font::system_font(env, true, title);
+            canvas.draw_text_centered(font, TITLE_FONT_SIZE, title, title_rect, color);
+        }
+
+        if let Some(ref badge) = item.badge {
+            let font = ui_font::system_font(env, true, badge);
+            let (text_width, _) = font.calculate_text_size(BADGE_FONT_SIZE, badge, None);
+            let width = (text_width + 12.0).max(22.0);
+            let badge_rect = rect(
+                icon_rect.origin.x + ICON_SIZE - 6.0,
+                item_rect.origin.y,
+                width,
+                22.0,
+            );
+            canvas.fill_rounded_rect(badge_rect, 11.0, (1.0, 1.0, 1.0, 1.0));
+            let inner = rect(
+                badge_rect.origin.x + 2.0,
+                badge_rect.origin.y + 2.0,
+                width - 4.0,
+                18.0,
+            );
+            canvas.fill_rounded_rect(inner, 9.0, (0.85, 0.0, 0.0, 1.0));
+            let font = ui_font::system_font(env, true, badge);
+            canvas.draw_text_centered(font, BADGE_FONT_SIZE, badge, inner, (1.0, 1.0, 1.0, 1.0));
+        }
+    }
+    canvas.set_clip(None);
+}
+
+/// For use by [super::overlay]: redraw the visible tab bars if needed.
+pub(super) fn update_overlay(env: &mut Environment) {
+    let tab_bars = visible_tab_bars(env);
+    let contents: Vec<DrawnTabBar> = tab_bars
+        .into_iter()
+        .map(|(tab_bar, frame)| drawn_tab_bar(env, tab_bar, frame))
+        .collect();
+
+    let state = &mut env.framework_state.uikit.ui_tab_bar;
+    if contents == state.overlay_contents && (state.overlay.is_some() || contents.is_empty()) {
+        return;
+    }
+    if contents.is_empty() {
+        state.overlay_contents = contents;
+        state.overlay = None;
+        return;
+    }
+
+    let mut canvas = Canvas::new(env);
+    for tab_bar in &contents {
+        draw_tab_bar(env, &mut canvas, tab_bar);
+    }
+    let state = &mut env.framework_state.uikit.ui_tab_bar;
+    state.overlay_contents = contents;
+    state.overlay = Some(canvas.into_overlay());
+}
+
+/// For use by [super::overlay]: get the drawing of the tab bars, if any are
+/// visible.
+pub(super) fn current_overlay(state: &State) -> Option<&Overlay> {
+    state.overlay.as_ref()
+}
+
+/// For use by [super::handle_events]: select tab bar items when they're
+/// touched. Returns [true] if the event was on a tab bar.
+pub(super) fn handle_event(env: &mut Environment, event: &Event) -> bool {
+    match *event {
+        Event::TouchDown(source, (x, y)) => {
+            let point = CGPoint { x, y };
+            let Some((tab_bar, frame)) = visible_tab_bars(env)
+                .into_iter()
+                .rev()
+                .find(|&(_, frame)| super::overlay::contains(frame, (point.x, point.y)))
+            else {
+                return false;
+            };
+            env.framework_state.uikit.ui_tab_bar.touch = Some(source);
+            let items = state(env, tab_bar).items.clone();
+            let count = items.len();
+            let touched = items
+                .into_iter()
+                .zip(item_rects(frame, count))
+                .find(|&(_, item_rect)| super::overlay::contains(item_rect, (x, y)));
+            if let Some((item, _)) = touched {
+                // UIKit creates and drains autorelease pools when handling
+                // events.
+                let pool: id = msg_class![env; NSAutoreleasePool new];
+                select_item(env, tab_bar, item);
+                release(env, pool);
+            }
+            true
+        }
+        Event::TouchMove(source, _) => env.framework_state.uikit.ui_tab_bar.touch == Some(source),
+        Event::TouchUp(source, _) => {
+            let touch = &mut env.framework_state.uikit.ui_tab_bar.touch;
+            if *touch == Some(source) {
+                *touch = None;
+                true
+            } else {
+                false
+            }
+        }
+        _ => false,
+    }
+}
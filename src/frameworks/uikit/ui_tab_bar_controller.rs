@@ -0,0 +1,345 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITabBarController`.
+
+use super::ui_tab_bar::TAB_BAR_HEIGHT;
+use super::ui_view::{window_for_view, UIViewHostObject};
+use super::ui_view_controller::UIViewControllerHostObject;
+use crate::frameworks::core_graphics::CGRect;
+use crate::frameworks::foundation::{ns_array, NSNotFound, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports,
+};
+use crate::Environment;
+
+pub(super) struct TabBarControllerState {
+    /// Strong references.
+    view_controllers: Vec<id>,
+    selected_index: Option<usize>,
+    /// `UITabBar*`, strong reference. Created when first needed.
+    tab_bar: id,
+    /// The view of the selected view controller, if it has been added to the
+    /// tab bar controller's view. Weak reference.
+    shown_view: id,
+}
+
+impl Default for TabBarControllerState {
+    fn default() -> Self {
+        TabBarControllerState {
+            view_controllers: Vec::new(),
+            selected_index: None,
+            tab_bar: nil,
+            shown_view: nil,
+        }
+    }
+}
+
+/// For use by `UIViewController`'s `dealloc`.
+pub(super) fn release_state(env: &mut Environment, state: TabBarControllerState) {
+    for view_controller in state.view_controllers {
+        env.objc
+            .borrow_mut::<UIViewControllerHostObject>(view_controller)
+            .parent_view_controller = nil;
+        release(env, view_controller);
+    }
+    if state.tab_bar != nil {
+        release(env, state.tab_bar);
+    }
+}
+
+fn state(env: &mut Environment, controller: id) -> &mut TabBarControllerState {
+    env.objc
+        .borrow_mut::<UIViewControllerHostObject>(controller)
+        .tab_bar_controller
+        .get_or_insert_with(Default::default)
+}
+
+fn tab_bar(env: &mut Environment, controller: id) -> id {
+    let tab_bar = state(env, controller).tab_bar;
+    if tab_bar != nil {
+        return tab_bar;
+    }
+    let screen: id = msg_class![env; UIScreen mainScreen];
+    let bounds: CGRect = msg![env; screen bounds];
+    let mut frame = bounds;
+    frame.origin.y += bounds.size.height - TAB_BAR_HEIGHT;
+    frame.size.height = TAB_BAR_HEIGHT;
+    let tab_bar: id = msg_class![env; UITabBar alloc];
+    let tab_bar: id = msg![env; tab_bar initWithFrame:frame];
+    () = msg![env; tab_bar setDelegate:controller];
+    state(env, controller).tab_bar = tab_bar;
+    tab_bar
+}
+
+/// Put the selected view controller's view into the tab bar controller's view,
+/// replacing the previous one. If the tab bar controller's view is in a window,
+/// the appearance callbacks are sent to the view controllers too.
+fn show_selected(env: &mut Environment, controller: id) {
+    if !msg![env; controller isViewLoaded] {
+        return;
+    }
+    let container: id = msg![env; controller view];
+    let &mut TabBarControllerState {
+        ref view_controllers,
+        selected_index,
+        shown_view,
+        ..
+    } = state(env, controller);
+    let selected = selected_index.map(|index| view_controllers[index]);
+    let visible = window_for_view(env, container).is_some();
+
+    if shown_view != nil {
+        let superview: id = msg![env; shown_view superview];
+        if superview == container {
+            let old = if visible {
+                env.objc
+                    .borrow::<UIViewHostObject>(shown_view)
+                    .view_controller
+            } else {
+                nil
+            };
+            if old != nil {
+                () = msg![env; old viewWillDisappear:false];
+            }
+            () = msg![env; shown_view removeFromSuperview];
+            if old != nil {
+                () = msg![env; old viewDidDisappear:false];
+            }
+        }
+        state(env, controller).shown_view = nil;
+    }
+    let Some(selected) = selected else {
+        return;
+    };
+
+    let view: id = msg![env; selected view];
+    let mut frame: CGRect = msg![env; container bounds];
+    frame.size.height -= TAB_BAR_HEIGHT;
+    () = msg![env; view setFrame:frame];
+    if visible {
+        () = msg![env; selected viewWillAppear:false];
+    }
+    let tab_bar = tab_bar(env, controller);
+    () = msg![env; container insertSubview:view belowSubview:tab_bar];
+    state(env, controller).shown_view = view;
+    if visible {
+        () = msg![env; selected viewDidAppear:false];
+    }
+}
+
+fn select(env: &mut Environment, controller: id, index: Option<usize>) {
+    log_dbg!("{:?} selecting tab {:?}", controller, index);
+    state(env, controller).selected_index = index;
+    let tab_bar = tab_bar(env, controller);
+    let item: id = match index {
+        Some(index) => {
+            let items: id = msg![env; tab_bar items];
+            msg![env; items objectAtIndex:(index as NSUInteger)]
+        }
+        None => nil,
+    };
+    () = msg![env; tab_bar setSelectedItem:item];
+    show_selected(env, controller);
+}
+
+fn delegate_responds_to(env: &mut Environment, delegate: id, name: &str) -> bool {
+    if delegate == nil {
+        return false;
+    }
+    match env.objc.lookup_selector(name) {
+        Some(selector) => msg![env; delegate respondsToSelector:selector],
+        None => false,
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UITabBarController: UIViewController
+
+- (())loadView {
+    let screen: id = msg_class![env; UIScreen mainScreen];
+    let bounds: CGRect = msg![env; screen bounds];
+    let view: id = msg_class![env; UIView alloc];
+    let view: id = msg![env; view initWithFrame:bounds];
+    let tab_bar = tab_bar(env, this);
+    () = msg![env; view addSubview:tab_bar];
+    () = msg![env; this setView:view];
+    release(env, view);
+    show_selected(env, this);
+}
+
+- (id)tabBar {
+    tab_bar(env, this)
+}
+
+// The selected view controller appears and disappears along with this one.
+- (())viewWillAppear:(bool)animated {
+    let selected: id = msg![env; this selectedViewController];
+    if selected != nil {
+        () = msg![env; selected viewWillAppear:animated];
+    }
+}
+- (())viewDidAppear:(bool)animated {
+    let selected: id = msg![env; this selectedViewController];
+    if selected != nil {
+        () = msg![env; selected viewDidAppear:animated];
+    }
+}
+- (())viewWillDisappear:(bool)animated {
+    let selected: id = msg![env; this selectedViewController];
+    if selected != nil {
+        () = msg![env; selected viewWillDisappear:animated];
+    }
+}
+- (())viewDidDisappear:(bool)animated {
+    let selected: id = msg![env; this selectedViewController];
+    if selected != nil {
+        () = msg![env; selected viewDidDisappear:animated];
+    }
+}
+
+- (id)delegate {
+    env.objc.borrow::<UIViewControllerHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate {
+    env.objc.borrow_mut::<UIViewControllerHostObject>(this).delegate = delegate;
+}
+
+- (id)viewControllers {
+    let view_controllers = state(env, this).view_controllers.clone();
+    if view_controllers.is_empty() {
+        return nil;
+    }
+    for &view_controller in &view_controllers {
+        retain(env, view_controller);
+    }
+    let view_controllers = ns_array::from_vec(env, view_controllers);
+    autorelease(env, view_controllers)
+}
+- (())setViewControllers:(id)view_controllers { // NSArray* of UIViewController*
+    let count: NSUInteger = if view_controllers == nil {
+        0
+    } else {
+        msg![env; view_controllers count]
+    };
+    let mut new = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let view_controller: id = msg![env; view_controllers objectAtIndex:i];
+        new.push(retain(env, view_controller));
+        env.objc
+            .borrow_mut::<UIViewControllerHostObject>(view_controller)
+            .parent_view_controller = this;
+    }
+
+    // Take the old selected view controller's view out first, since it might
+    // not be one of the new view controllers.
+    let old_selected = state(env, this).selected_index;
+    state(env, this).selected_index = None;
+    show_selected(env, this);
+
+    let old = std::mem::replace(&mut state(env, this).view_controllers, new.clone());
+    for view_controller in old {
+        if !new.contains(&view_controller) {
+            env.objc
+                .borrow_mut::<UIViewControllerHostObject>(view_controller)
+                .parent_view_controller = nil;
+        }
+        release(env, view_controller);
+    }
+
+    let items: Vec<id> = new
+        .iter()
+        .map(|&view_controller| {
+            let item: id = msg![env; view_controller tabBarItem];
+            retain(env, item)
+        })
+        .collect();
+    let items = ns_array::from_vec(env, items);
+    let tab_bar = tab_bar(env, this);
+    () = msg![env; tab_bar setItems:items];
+    release(env, items);
+
+    let selected = match old_selected {
+        Some(index) if index < new.len() => Some(index),
+        _ if !new.is_empty() => Some(0),
+        _ => None,
+    };
+    select(env, this, selected);
+}
+- (())setViewControllers:(id)view_controllers // NSArray* of UIViewController*
+                animated:(bool)_animated {
+    msg![env; this setViewControllers:view_controllers]
+}
+
+- (NSUInteger)selectedIndex {
+    match state(env, this).selected_index {
+        Some(index) => index as NSUInteger,
+        None => NSNotFound as NSUInteger,
+    }
+}
+- (())setSelectedIndex:(NSUInteger)index {
+    let count = state(env, this).view_controllers.len();
+    if (index as usize) < count {
+        select(env, this, Some(index as usize));
+    } else {
+        log!("Warning: [{:?} setSelectedIndex:{}] ignored, there are only {} tabs", this, index, count);
+    }
+}
+- (id)selectedViewController {
+    let &mut TabBarControllerState {
+        ref view_controllers,
+        selected_index,
+        ..
+    } = state(env, this);
+    selected_index.map_or(nil, |index| view_controllers[index])
+}
+- (())setSelectedViewController:(id)view_controller { // UIViewController*
+    let index = state(env, this)
+        .view_controllers
+        .iter()
+        .position(|&candidate| candidate == view_controller);
+    if index.is_some() {
+        select(env, this, index);
+    } else {
+        log!("Warning: [{:?} setSelectedViewController:{:?}] ignored, it's not one of the tabs", this, view_controller);
+    }
+}
+
+// UITabBarDelegate implementation
+- (())tabBar:(id)tab_bar // UITabBar*
+didSelectItem:(id)item { // UITabBarItem*
+    let view_controllers = state(env, this).view_controllers.clone();
+    let Some(index) = view_controllers.iter().position(|&view_controller| {
+        let candidate: id = msg![env; view_controller tabBarItem];
+        candidate == item
+    }) else {
+        log!("Warning: {:?} doesn't have a tab for {:?} from {:?}", this, item, tab_bar);
+        return;
+    };
+    let view_controller = view_controllers[index];
+
+    let delegate = env.objc.borrow::<UIViewControllerHostObject>(this).delegate;
+    if delegate_responds_to(env, delegate, "tabBarController:shouldSelectViewController:") {
+        let should: bool = msg![env; delegate tabBarController:this
+                                            shouldSelectViewController:view_controller];
+        if !should {
+            // Put the selection back.
+            let selected = state(env, this).selected_index;
+            select(env, this, selected);
+            return;
+        }
+    }
+    select(env, this, Some(index));
+    if delegate_responds_to(env, delegate, "tabBarController:didSelectViewController:") {
+        () = msg![env; delegate tabBarController:this didSelectViewController:view_controller];
+    }
+}
+
+@end
+
+};
@@ -0,0 +1,711 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITableView`.
+//!
+//! Nothing is drawn yet, but the layout, the data source and delegate
+//! callbacks, cell reuse, selection and scrolling work the way apps expect.
+//! Only cells for visible rows are requested from the data source, and cells
+//! scrolled off screen go into a reuse queue, just like in real UIKit.
+
+use super::ui_scroll_view::{self, ScrollViewState};
+use super::ui_table_view_cell;
+use super::ui_view::UIViewHostObject;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_index_path::{index_path_for_row, section_and_row};
+use crate::frameworks::foundation::{ns_array, ns_string, NSInteger, NSUInteger};
+use crate::objc::{autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, SEL};
+use crate::Environment;
+use std::collections::HashMap;
+
+pub type UITableViewStyle = NSInteger;
+pub const UITableViewStylePlain: UITableViewStyle = 0;
+#[allow(dead_code)]
+pub const UITableViewStyleGrouped: UITableViewStyle = 1;
+
+pub type UITableViewScrollPosition = NSInteger;
+pub const UITableViewScrollPositionNone: UITableViewScrollPosition = 0;
+pub const UITableViewScrollPositionTop: UITableViewScrollPosition = 1;
+pub const UITableViewScrollPositionMiddle: UITableViewScrollPosition = 2;
+pub const UITableViewScrollPositionBottom: UITableViewScrollPosition = 3;
+
+const DEFAULT_ROW_HEIGHT: CGFloat = 44.0;
+const DEFAULT_SECTION_HEADER_HEIGHT: CGFloat = 22.0;
+
+/// The size of a cell created with `initWithStyle:reuseIdentifier:`, before
+/// the table view resizes it.
+pub(super) fn default_cell_size() -> CGSize {
+    CGSize {
+        width: 320.0,
+        height: DEFAULT_ROW_HEIGHT,
+    }
+}
+
+/// Section and row.
+type RowIndex = (NSUInteger, NSUInteger);
+
+#[derive(Clone)]
+struct Section {
+    header_height: CGFloat,
+    row_heights: Vec<CGFloat>,
+}
+
+pub(super) struct TableViewState {
+    style: UITableViewStyle,
+    /// Weak reference. The delegate is the scroll view's delegate.
+    data_source: id,
+    row_height: CGFloat,
+    section_header_height: CGFloat,
+    allows_selection: bool,
+    /// The layout from the last reload, or [None] if the data needs to be
+    /// (re)loaded.
+    sections: Option<Vec<Section>>,
+    /// Strong references to each section's header `UIView*`, which may be
+    /// `nil`.
+    header_views: Vec<id>,
+    /// Strong references to the cells for visible rows.
+    visible_cells: Vec<(RowIndex, id)>,
+    /// Strong references to cells waiting to be dequeued, by reuse identifier.
+    reuse_queues: HashMap<String, Vec<id>>,
+    selected_row: Option<RowIndex>,
+}
+impl Default for TableViewState {
+    fn default() -> Self {
+        TableViewState {
+            style: UITableViewStylePlain,
+            data_source: nil,
+            row_height: DEFAULT_ROW_HEIGHT,
+            section_header_height: DEFAULT_SECTION_HEADER_HEIGHT,
+            allows_selection: true,
+            sections: None,
+            header_views: Vec::new(),
+            visible_cells: Vec::new(),
+            reuse_queues: HashMap::new(),
+            selected_row: None,
+        }
+    }
+}
+
+fn state(env: &mut Environment, table_view: id) -> &mut TableViewState {
+    env.objc
+        .borrow_mut::<UIViewHostObject>(table_view)
+        .table_view
+        .get_or_insert_with(Default::default)
+}
+
+fn scroll_state(env: &mut Environment, table_view: id) -> &mut ScrollViewState {
+    ui_scroll_view::state(env, table_view)
+}
+
+/// For use by `UIView`'s `dealloc`.
+pub(super) fn release_state(env: &mut Environment, state: TableViewState) {
+    // The cells and header views are also subviews, so UIView's dealloc
+    // releases those references separately.
+    for header_view in state.header_views {
+        release(env, header_view);
+    }
+    for (_, cell) in state.visible_cells {
+        release(env, cell);
+    }
+    for cell in state.reuse_queues.into_values().flatten() {
+        release(env, cell);
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UITableView: UIScrollView
+
+- (id)initWithFrame:(CGRect)frame
+              style:(UITableViewStyle)style {
+    state(env, this).style = style;
+    msg![env; this initWithFrame:frame]
+}
+
+- (UITableViewStyle)style {
+    state(env, this).style
+}
+
+- (id)dataSource {
+    state(env, this).data_source
+}
+- (())setDataSource:(id)data_source {
+    let state = state(env, this);
+    state.data_source = data_source;
+    state.sections = None;
+}
+// The delegate is inherited from UIScrollView, but changing it affects the
+// layout.
+- (())setDelegate:(id)delegate {
+    scroll_state(env, this).delegate = delegate;
+    state(env, this).sections = None;
+}
+
+- (CGFloat)rowHeight {
+    state(env, this).row_height
+}
+- (())setRowHeight:(CGFloat)height {
+    let state = state(env, this);
+    state.row_height = height;
+    state.sections = None;
+}
+- (CGFloat)sectionHeaderHeight {
+    state(env, this).section_header_height
+}
+- (())setSectionHeaderHeight:(CGFloat)height {
+    let state = state(env, this);
+    state.section_header_height = height;
+    state.sections = None;
+}
+
+- (bool)allowsSelection {
+    state(env, this).allows_selection
+}
+- (())setAllowsSelection:(bool)allows {
+    state(env, this).allows_selection = allows;
+}
+
+- (())reloadData {
+    reload_data(env, this);
+}
+
+- (NSInteger)numberOfSections {
+    sections(env, this).len().try_into().unwrap()
+}
+- (NSInteger)numberOfRowsInSection:(NSInteger)section {
+    let sections = sections(env, this);
+    sections[section as usize].row_heights.len().try_into().unwrap()
+}
+
+- (CGRect)rectForSection:(NSInteger)section {
+    let sections = sections(env, this);
+    let width = width(env, this);
+    let y = section_y(&sections, section as usize);
+    let section = &sections[section as usize];
+    let height = section.header_height + section.row_heights.iter().sum::<CGFloat>();
+    rect(y, width, height)
+}
+- (CGRect)rectForHeaderInSection:(NSInteger)section {
+    let sections = sections(env, this);
+    let width = width(env, this);
+    let y = section_y(&sections, section as usize);
+    rect(y, width, sections[section as usize].header_height)
+}
+- (CGRect)rectForRowAtIndexPath:(id)index_path { // NSIndexPath*
+    let row = section_and_row(env, index_path);
+    let sections = sections(env, this);
+    let width = width(env, this);
+    row_rect(&sections, row, width)
+}
+
+- (id)indexPathForRowAtPoint:(CGPoint)point {
+    let sections = sections(env, this);
+    match row_at_y(&sections, point.y) {
+        Some((section, row)) => index_path_for_row(env, section, row),
+        None => nil,
+    }
+}
+- (id)indexPathForCell:(id)cell { // UITableViewCell*
+    ensure_loaded(env, this);
+    let visible_cells = &state(env, this).visible_cells;
+    match visible_cells.iter().find(|&&(_, visible)| visible == cell) {
+        Some(&((section, row), _)) => index_path_for_row(env, section, row),
+        None => nil,
+    }
+}
+- (id)cellForRowAtIndexPath:(id)index_path { // NSIndexPath*
+    ensure_loaded(env, this);
+    let row = section_and_row(env, index_path);
+    let visible_cells = &state(env, this).visible_cells;
+    visible_cells
+        .iter()
+        .find(|&&(visible, _)| visible == row)
+        .map_or(nil, |&(_, cell)| cell)
+}
+
+- (id)visibleCells {
+    ensure_loaded(env, this);
+    let cells: Vec<id> = state(env, this)
+        .visible_cells
+        .iter()
+        .map(|&(_, cell)| cell)
+        .collect();
+    for &cell in &cells {
+        retain(env, cell);
+    }
+    let cells = ns_array::from_vec(env, cells);
+    autorelease(env, cells)
+}
+- (id)indexPathsForVisibleRows {
+    ensure_loaded(env, this);
+    let rows: Vec<RowIndex> = state(env, this)
+        .visible_cells
+        .iter()
+        .map(|&(row, _)| row)
+        .collect();
+    let index_paths = rows
+        .into_iter()
+        .map(|(section, row)| {
+            let index_path = index_path_for_row(env, section, row);
+            retain(env, index_path)
+        })
+        .collect();
+    let index_paths = ns_array::from_vec(env, index_paths);
+    autorelease(env, index_paths)
+}
+
+- (id)dequeueReusableCellWithIdentifier:(id)identifier { // NSString*
+    let identifier = ns_string::to_rust_string(env, identifier);
+    let cell = state(env, this)
+        .reuse_queues
+        .get_mut(&*identifier)
+        .and_then(|queue| queue.pop());
+    let Some(cell) = cell else {
+        return nil;
+    };
+    () = msg![env; cell prepareForReuse];
+    autorelease(env, cell)
+}
+
+- (id)indexPathForSelectedRow {
+    match state(env, this).selected_row {
+        Some((section, row)) => index_path_for_row(env, section, row),
+        None => nil,
+    }
+}
+- (())selectRowAtIndexPath:(id)index_path // NSIndexPath*
+                  animated:(bool)animated
+            scrollPosition:(UITableViewScrollPosition)position {
+    // Unlike selection by the user, this doesn't call the delegate.
+    if index_path == nil {
+        set_selected_row(env, this, None);
+        return;
+    }
+    let row = section_and_row(env, index_path);
+    set_selected_row(env, this, Some(row));
+    if position != UITableViewScrollPositionNone {
+        scroll_to_row(env, this, row, position, animated);
+    }
+}
+- (())deselectRowAtIndexPath:(id)index_path // NSIndexPath*
+                    animated:(bool)_animated {
+    let row = section_and_row(env, index_path);
+    if state(env, this).selected_row == Some(row) {
+        set_selected_row(env, this, None);
+    }
+}
+
+- (())scrollToRowAtIndexPath:(id)index_path // NSIndexPath*
+            atScrollPosition:(UITableViewScrollPosition)position
+                    animated:(bool)animated {
+    let row = section_and_row(env, index_path);
+    scroll_to_row(env, this, row, position, animated);
+}
+
+- (())layoutSubviews {
+    update_visible_cells(env, this);
+}
+- (())didMoveToSuperview {
+    ensure_loaded(env, this);
+}
+
+- (())touchesBegan:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    ensure_loaded(env, this);
+    ui_scroll_view::touches_began(env, this, touches);
+}
+- (())touchesMoved:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    ui_scroll_view::touches_moved(env, this, touches);
+}
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    if !ui_scroll_view::touches_ended(env, this, touches) {
+        return;
+    }
+    let touch: id = msg![env; touches anyObject];
+    let location: CGPoint = msg![env; touch locationInView:nil];
+    // FIXME: assumes the superview is at the origin of the screen, like
+    // UITouch's own co-ordinate handling.
+    let frame: CGRect = msg![env; this frame];
+    let offset = env.objc.borrow::<UIViewHostObject>(this).bounds.origin;
+    let y = location.y - frame.origin.y + offset.y;
+    let sections = sections(env, this);
+    if let Some(row) = row_at_y(&sections, y) {
+        user_selected_row(env, this, row);
+    }
+}
+
+@end
+
+};
+
+fn rect(y: CGFloat, width: CGFloat, height: CGFloat) -> CGRect {
+    CGRect {
+        origin: CGPoint { x: 0.0, y },
+        size: CGSize { width, height },
+    }
+}
+
+fn width(env: &mut Environment, table_view: id) -> CGFloat {
+    env.objc
+        .borrow::<UIViewHostObject>(table_view)
+        .bounds
+        .size
+        .width
+}
+
+/// The y co-ordinate of the top of a section (its header).
+fn section_y(sections: &[Section], section: usize) -> CGFloat {
+    sections[..section]
+        .iter()
+        .map(|section| section.header_height + section.row_heights.iter().sum::<CGFloat>())
+        .sum()
+}
+
+fn row_rect(sections: &[Section], (section, row): RowIndex, width: CGFloat) -> CGRect {
+    let (section, row) = (section as usize, row as usize);
+    let section_info = &sections[section];
+    let y = section_y(sections, section)
+        + section_info.header_height
+        + section_info.row_heights[..row].iter().sum::<CGFloat>();
+    rect(y, width, section_info.row_heights[row])
+}
+
+/// All rows, with the y co-ordinates of their tops and their heights.
+fn rows_with_positions(sections: &[Section]) -> Vec<(RowIndex, CGFloat, CGFloat)> {
+    let mut rows = Vec::new();
+    let mut y = 0.0;
+    for (section_idx, section) in sections.iter().enumerate() {
+        y += section.header_height;
+        for (row_idx, &height) in section.row_heights.iter().enumerate() {
+            rows.push((
+                (section_idx as NSUInteger, row_idx as NSUInteger),
+                y,
+                height,
+            ));
+            y += height;
+        }
+    }
+    rows
+}
+
+fn row_at_y(sections: &[Section], y: CGFloat) -> Option<RowIndex> {
+    rows_with_positions(sections)
+        .into_iter()
+        .find(|&(_, top, height)| top <= y && y < top + height)
+        .map(|(row, _, _)| row)
+}
+
+fn responds_to(env: &mut Environment, object: id, selector: &str) -> Option<SEL> {
+    if object == nil {
+        return None;
+    }
+    let selector = env.objc.lookup_selector(selector)?;
+    if msg![env; object respondsToSelector:selector] {
+        Some(selector)
+    } else {
+        None
+    }
+}
+
+/// Get the layout, loading the data first if necessary.
+fn sections(env: &mut Environment, table_view: id) -> Vec<Section> {
+    ensure_loaded(env, table_view);
+    state(env, table_view).sections.clone().unwrap()
+}
+
+fn ensure_loaded(env: &mut Environment, table_view: id) {
+    if state(env, table_view).sections.is_none() {
+        reload_data(env, table_view);
+    }
+}
+
+/// Remove a cell from the table and put it in the reuse queue, if it has a
+/// reuse identifier. Takes over the table view's reference to the cell.
+fn enqueue_cell(env: &mut Environment, table_view: id, cell: id) {
+    () = msg![env; cell removeFromSuperview];
+    let reuse_identifier = ui_table_view_cell::reuse_identifier(env, cell);
+    if reuse_identifier == nil {
+        release(env, cell);
+        return;
+    }
+    let reuse_identifier = ns_string::to_rust_string(env, reuse_identifier).into_owned();
+    state(env, table_view)
+        .reuse_queues
+        .entry(reuse_identifier)
+        .or_default()
+        .push(cell);
+}
+
+/// The implementation of `reloadData`.
+fn reload_data(env: &mut Environment, table_view: id) {
+    let visible_cells = std::mem::take(&mut state(env, table_view).visible_cells);
+    for (_, cell) in visible_cells {
+        enqueue_cell(env, table_view, cell);
+    }
+    let header_views = std::mem::take(&mut state(env, table_view).header_views);
+    for header_view in header_views {
+        if header_view != nil {
+            () = msg![env; header_view removeFromSuperview];
+            release(env, header_view);
+        }
+    }
+
+    let &mut TableViewState {
+        data_source,
+        row_height,
+        section_header_height,
+        ..
+    } = state(env, table_view);
+    let delegate = scroll_state(env, table_view).delegate;
+
+    let section_count: NSInteger = if data_source == nil {
+        0
+    } else if responds_to(env, data_source, "numberOfSectionsInTableView:").is_some() {
+        msg![env; data_source numberOfSectionsInTableView:table_view]
+    } else {
+        1
+    };
+
+    let header_view_selector = responds_to(env, delegate, "tableView:viewForHeaderInSection:");
+    let header_height_selector = responds_to(env, delegate, "tableView:heightForHeaderInSection:");
+    let header_title_selector = responds_to(env, data_source, "tableView:titleForHeaderInSection:");
+    let row_height_selector = responds_to(env, delegate, "tableView:heightForRowAtIndexPath:");
+
+    let mut sections = Vec::new();
+    let mut header_views = Vec::new();
+    for section in 0..section_count {
+        let header_view: id = if header_view_selector.is_some() {
+            msg![env; delegate tableView:table_view viewForHeaderInSection:section]
+        } else {
+            nil
+        };
+        let header_title: id = if header_title_selector.is_some() {
+            msg![env; data_source tableView:table_view titleForHeaderInSection:section]
+        } else {
+            nil
+        };
+        let header_height: CGFloat = if header_height_selector.is_some() {
+            msg![env; delegate tableView:table_view heightForHeaderInSection:section]
+        } else if header_view != nil || header_title != nil {
+            section_header_height
+        } else {
+            0.0
+        };
+        retain(env, header_view);
+        header_views.push(header_view);
+
+        let row_count: NSInteger =
+            msg![env; data_source tableView:table_view numberOfRowsInSection:section];
+        let row_heights = (0..row_count)
+            .map(|row| {
+                if row_height_selector.is_some() {
+                    let index_path = index_path_for_row(env, section as _, row as _);
+                    msg![env; delegate tableView:table_view heightForRowAtIndexPath:index_path]
+                } else {
+                    row_height
+                }
+            })
+            .collect();
+
+        sections.push(Section {
+            header_height,
+            row_heights,
+        });
+    }
+
+    let width = width(env, table_view);
+    for (section, &header_view) in header_views.iter().enumerate() {
+        if header_view == nil {
+            continue;
+        }
+        let frame = rect(
+            section_y(&sections, section),
+            width,
+            sections[section].header_height,
+        );
+        () = msg![env; header_view setFrame:frame];
+        () = msg![env; table_view addSubview:header_view];
+    }
+
+    let content_height = rows_with_positions(&sections)
+        .last()
+        .map_or(0.0, |&(_, top, height)| top + height);
+    // Trailing empty sections can still have headers.
+    let content_height = content_height.max(section_y(&sections, sections.len()));
+    scroll_state(env, table_view).content_size = CGSize {
+        width,
+        height: content_height,
+    };
+
+    let state = state(env, table_view);
+    if let Some((section, row)) = state.selected_row {
+        let (section, row) = (section as usize, row as usize);
+        let still_exists = section < sections.len() && row < sections[section].row_heights.len();
+        if !still_exists {
+            state.selected_row = None;
+        }
+    }
+    state.sections = Some(sections);
+    state.header_views = header_views;
+
+    update_visible_cells(env, table_view);
+}
+
+/// Request cells for rows that have become visible and enqueue cells for rows
+/// that are no longer visible.
+fn update_visible_cells(env: &mut Environment, table_view: id) {
+    let Some(sections) = state(env, table_view).sections.clone() else {
+        reload_data(env, table_view);
+        return;
+    };
+
+    let bounds = env.objc.borrow::<UIViewHostObject>(table_view).bounds;
+    let top = bounds.origin.y;
+    let bottom = top + bounds.size.height;
+    let visible_rows: Vec<RowIndex> = rows_with_positions(&sections)
+        .into_iter()
+        .filter(|&(_, row_top, height)| row_top < bottom && row_top + height > top)
+        .map(|(row, _, _)| row)
+        .collect();
+
+    let old_visible_cells = std::mem::take(&mut state(env, table_view).visible_cells);
+    let mut visible_cells = Vec::new();
+    for (row, cell) in old_visible_cells {
+        if visible_rows.contains(&row) {
+            visible_cells.push((row, cell));
+        } else {
+            enqueue_cell(env, table_view, cell);
+        }
+    }
+    // Put the table view in a consistent state before calling the data source,
+    // which might call dequeueReusableCellWithIdentifier: etc.
+    state(env, table_view).visible_cells = visible_cells;
+
+    let &mut TableViewState {
+        data_source,
+        selected_row,
+        ..
+    } = state(env, table_view);
+    let delegate = scroll_state(env, table_view).delegate;
+    let will_display_selector = responds_to(
+        env,
+        delegate,
+        "tableView:willDisplayCell:forRowAtIndexPath:",
+    );
+
+    for row in visible_rows {
+        if state(env, table_view)
+            .visible_cells
+            .iter()
+            .any(|&(visible, _)| visible == row)
+        {
+            continue;
+        }
+
+        let index_path = index_path_for_row(env, row.0, row.1);
+        let cell: id = msg![env; data_source tableView:table_view cellForRowAtIndexPath:index_path];
+        assert!(
+            cell != nil,
+            "Data source returned nil cell for row {:?}",
+            row
+        );
+        retain(env, cell);
+
+        let frame = row_rect(&sections, row, bounds.size.width);
+        () = msg![env; cell setFrame:frame];
+        let selected = selected_row == Some(row);
+        () = msg![env; cell setSelected:selected];
+        () = msg![env; table_view addSubview:cell];
+
+        if will_display_selector.is_some() {
+            () = msg![env; delegate tableView:table_view
+                                willDisplayCell:cell
+                              forRowAtIndexPath:index_path];
+        }
+
+        state(env, table_view).visible_cells.push((row, cell));
+    }
+}
+
+fn set_selected_row(env: &mut Environment, table_view: id, new_row: Option<RowIndex>) {
+    let state = state(env, table_view);
+    let old_row = std::mem::replace(&mut state.selected_row, new_row);
+    let cells: Vec<(RowIndex, id)> = state.visible_cells.clone();
+    for (row, cell) in cells {
+        if Some(row) == old_row && old_row != new_row {
+            () = msg![env; cell setSelected:false];
+        } else if Some(row) == new_row {
+            () = msg![env; cell setSelected:true];
+        }
+    }
+}
+
+/// Handle the user tapping a row, which involves the delegate.
+fn user_selected_row(env: &mut Environment, table_view: id, row: RowIndex) {
+    if !state(env, table_view).allows_selection {
+        return;
+    }
+    let delegate = scroll_state(env, table_view).delegate;
+
+    let mut index_path = index_path_for_row(env, row.0, row.1);
+    if responds_to(env, delegate, "tableView:willSelectRowAtIndexPath:").is_some() {
+        index_path = msg![env; delegate tableView:table_view willSelectRowAtIndexPath:index_path];
+        if index_path == nil {
+            return;
+        }
+    }
+    let row = section_and_row(env, index_path);
+
+    let old_row = state(env, table_view).selected_row;
+    set_selected_row(env, table_view, Some(row));
+
+    if let Some(old_row) = old_row.filter(|&old_row| old_row != row) {
+        if responds_to(env, delegate, "tableView:didDeselectRowAtIndexPath:").is_some() {
+            let old_index_path = index_path_for_row(env, old_row.0, old_row.1);
+            () = msg![env; delegate tableView:table_view didDeselectRowAtIndexPath:old_index_path];
+        }
+    }
+    if responds_to(env, delegate, "tableView:didSelectRowAtIndexPath:").is_some() {
+        () = msg![env; delegate tableView:table_view didSelectRowAtIndexPath:index_path];
+    }
+}
+
+/// The implementation of `scrollToRowAtIndexPath:atScrollPosition:animated:`.
+fn scroll_to_row(
+    env: &mut Environment,
+    table_view: id,
+    row: RowIndex,
+    position: UITableViewScrollPosition,
+    animated: bool,
+) {
+    let sections = sections(env, table_view);
+    let bounds = env.objc.borrow::<UIViewHostObject>(table_view).bounds;
+    let row_rect = row_rect(&sections, row, bounds.size.width);
+    let row_top = row_rect.origin.y;
+    let row_bottom = row_top + row_rect.size.height;
+    let view_height = bounds.size.height;
+
+    let y = match position {
+        UITableViewScrollPositionTop => row_top,
+        UITableViewScrollPositionMiddle => row_top - (view_height - row_rect.size.height) / 2.0,
+        UITableViewScrollPositionBottom => row_bottom - view_height,
+        // Scroll as little as possible to make the row visible.
+        _ => ui_scroll_view::scroll_to_show(
+            bounds.origin.y,
+            view_height,
+            row_top,
+            row_rect.size.height,
+        ),
+    };
+    let offset = CGPoint {
+        x: bounds.origin.x,
+        y,
+    };
+    ui_scroll_view::scroll_to_offset(env, table_view, offset, animated);
+}
@@ -0,0 +1,172 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITableViewCell`.
+//!
+//! TODO: `textLabel` and friends, once there's a `UILabel`.
+
+use super::ui_view::UIViewHostObject;
+use crate::frameworks::core_graphics::{CGPoint, CGRect};
+use crate::frameworks::foundation::NSInteger;
+use crate::objc::{id, msg, msg_class, nil, objc_classes, release, ClassExports};
+use crate::Environment;
+
+pub type UITableViewCellStyle = NSInteger;
+#[allow(dead_code)]
+pub const UITableViewCellStyleDefault: UITableViewCellStyle = 0;
+
+pub type UITableViewCellSelectionStyle = NSInteger;
+pub const UITableViewCellSelectionStyleBlue: UITableViewCellSelectionStyle = 1;
+
+pub type UITableViewCellAccessoryType = NSInteger;
+pub const UITableViewCellAccessoryNone: UITableViewCellAccessoryType = 0;
+
+pub(super) struct TableViewCellState {
+    /// `NSString*`, strong reference.
+    reuse_identifier: id,
+    /// `UIView*`, owned by the cell as a subview.
+    content_view: id,
+    /// `NSString*`, strong reference. This is the iPhone OS 2.x `text`
+    /// property.
+    text: id,
+    selected: bool,
+    selection_style: UITableViewCellSelectionStyle,
+    accessory_type: UITableViewCellAccessoryType,
+}
+impl Default for TableViewCellState {
+    fn default() -> Self {
+        TableViewCellState {
+            reuse_identifier: nil,
+            content_view: nil,
+            text: nil,
+            selected: false,
+            selection_style: UITableViewCellSelectionStyleBlue,
+            accessory_type: UITableViewCellAccessoryNone,
+        }
+    }
+}
+
+fn state(env: &mut Environment, cell: id) -> &mut TableViewCellState {
+    env.objc
+        .borrow_mut::<UIViewHostObject>(cell)
+        .table_view_cell
+        .get_or_insert_with(Default::default)
+}
+
+/// For use by `UIView`'s `dealloc`.
+pub(super) fn release_state(env: &mut Environment, state: TableViewCellState) {
+    release(env, state.reuse_identifier);
+    release(env, state.text);
+}
+
+/// Get a cell's reuse identifier (`NSString*`), which may be `nil`.
+pub(super) fn reuse_identifier(env: &mut Environment, cell: id) -> id {
+    state(env, cell).reuse_identifier
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UITableViewCell: UIView
+
+- (id)initWithStyle:(UITableViewCellStyle)_style
+    reuseIdentifier:(id)reuse_identifier { // NSString*
+    // TODO: the style only affects the labels, which aren't implemented yet.
+    let frame = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: super::ui_table_view::default_cell_size(),
+    };
+    msg![env; this initWithFrame:frame reuseIdentifier:reuse_identifier]
+}
+
+// Deprecated in iPhone OS 3.0, but plenty of apps use it.
+- (id)initWithFrame:(CGRect)frame
+    reuseIdentifier:(id)reuse_identifier { // NSString*
+    let this: id = msg![env; this initWithFrame:frame];
+    let reuse_identifier: id = msg![env; reuse_identifier copy];
+    state(env, this).reuse_identifier = reuse_identifier;
+    this
+}
+
+- (id)initWithFrame:(CGRect)frame {
+    // Can't do a super-call, so this does what UIView's initWithFrame: does.
+    let layer: id = msg![env; this layer];
+    () = msg![env; layer setDelegate:this];
+    env.framework_state.uikit.ui_view.views.push(this);
+
+    let content_view: id = msg_class![env; UIView alloc];
+    let content_view: id = msg![env; content_view initWithFrame:frame];
+    () = msg![env; this addSubview:content_view];
+    release(env, content_view);
+    state(env, this).content_view = content_view;
+
+    () = msg![env; this setFrame:frame];
+
+    this
+}
+
+- (id)reuseIdentifier {
+    state(env, this).reuse_identifier
+}
+
+- (())prepareForReuse {
+    // For subclasses to override.
+}
+
+- (id)contentView {
+    state(env, this).content_view
+}
+
+- (id)text {
+    state(env, this).text
+}
+- (())setText:(id)text { // NSString*
+    let text: id = msg![env; text copy];
+    let old_text = std::mem::replace(&mut state(env, this).text, text);
+    release(env, old_text);
+}
+
+- (bool)isSelected {
+    state(env, this).selected
+}
+- (())setSelected:(bool)selected {
+    () = msg![env; this setSelected:selected animated:false];
+}
+- (())setSelected:(bool)selected
+         animated:(bool)_animated {
+    state(env, this).selected = selected;
+}
+
+- (UITableViewCellSelectionStyle)selectionStyle {
+    state(env, this).selection_style
+}
+- (())setSelectionStyle:(UITableViewCellSelectionStyle)style {
+    state(env, this).selection_style = style;
+}
+
+- (UITableViewCellAccessoryType)accessoryType {
+    state(env, this).accessory_type
+}
+- (())setAccessoryType:(UITableViewCellAccessoryType)accessory_type {
+    state(env, this).accessory_type = accessory_type;
+}
+
+- (())layoutSubviews {
+    let content_view = state(env, this).content_view;
+    if content_view == nil {
+        return;
+    }
+    let bounds = env.objc.borrow::<UIViewHostObject>(this).bounds;
+    let frame = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: bounds.size,
+    };
+    () = msg![env; content_view setFrame:frame];
+}
+
+@end
+
+};
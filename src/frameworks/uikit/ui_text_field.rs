@@ -0,0 +1,693 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITextField`, and the text editing it shares with `UITextView`.
+//!
+//! Text is typed with the host's keyboard (and input methods) rather than an
+//! on-screen keyboard: the window accepts text input while a text field or
+//! text view is being edited, i.e. while it is the first responder.
+//!
+//! TODO: Views aren't drawn yet, so neither is the text being edited. Apps that
+//! show the text themselves (e.g. in an OpenGL ES view) work fine.
+
+use super::ui_font::{UITextAlignment, UITextAlignmentLeft};
+use super::ui_view::{frame_on_screen, UIViewHostObject};
+use super::{ui_keyboard, ui_responder};
+use crate::frameworks::core_graphics::CGPoint;
+use crate::frameworks::foundation::{ns_string, NSInteger, NSRange, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    SEL,
+};
+use crate::window::{EditingKey, Event, TouchSource};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// The touch that began on a text field or text view, which is kept from
+    /// the app.
+    touch: Option<TouchSource>,
+    /// Set while editing moves from one text field or text view to another, so
+    /// the on-screen keyboard stays visible.
+    switching: bool,
+}
+
+pub type UITextBorderStyle = NSInteger;
+#[allow(dead_code)]
+pub const UITextBorderStyleNone: UITextBorderStyle = 0;
+#[allow(dead_code)]
+pub const UITextBorderStyleLine: UITextBorderStyle = 1;
+#[allow(dead_code)]
+pub const UITextBorderStyleBezel: UITextBorderStyle = 2;
+#[allow(dead_code)]
+pub const UITextBorderStyleRoundedRect: UITextBorderStyle = 3;
+
+/// Properties from the `UITextInputTraits` protocol. These only affect the
+/// on-screen keyboard, so they're stored but otherwise ignored.
+#[derive(Default)]
+pub(super) struct TextInputTraits {
+    pub(super) autocapitalization_type: NSInteger,
+    pub(super) autocorrection_type: NSInteger,
+    pub(super) keyboard_type: NSInteger,
+    pub(super) keyboard_appearance: NSInteger,
+    pub(super) return_key_type: NSInteger,
+    pub(super) enables_return_key_automatically: bool,
+    pub(super) secure_text_entry: bool,
+}
+
+pub(super) struct TextInputState {
+    /// Whether this is a `UITextView`, which has different delegate methods and
+    /// can contain multiple lines.
+    is_text_view: bool,
+    pub(super) text: String,
+    /// Insertion point, as a byte offset into `text`.
+    cursor: usize,
+    /// `UIFont*`, strong reference.
+    pub(super) font: id,
+    /// `UIColor*`, strong reference.
+    pub(super) text_color: id,
+    text_alignment: UITextAlignment,
+    /// `UITextField` only. Weak reference (`UITextView` uses the scroll view's
+    /// delegate).
+    delegate: id,
+    /// `UITextField` only.
+    placeholder: Option<String>,
+    /// `UITextField` only.
+    border_style: UITextBorderStyle,
+    /// `UITextField` only.
+    clears_on_begin_editing: bool,
+    /// `enabled` for `UITextField`, `editable` for `UITextView`.
+    editable: bool,
+    editing: bool,
+    pub(super) traits: TextInputTraits,
+}
+impl TextInputState {
+    fn new(is_text_view: bool) -> TextInputState {
+        TextInputState {
+            is_text_view,
+            text: String::new(),
+            cursor: 0,
+            font: nil,
+            text_color: nil,
+            text_alignment: UITextAlignmentLeft,
+            delegate: nil,
+            placeholder: None,
+            border_style: UITextBorderStyleNone,
+            clears_on_begin_editing: false,
+            editable: true,
+            editing: false,
+            traits: Default::default(),
+        }
+    }
+}
+
+/// Get the state of a text field or text view.
+pub(super) fn state(env: &mut Environment, view: id, is_text_view: bool) -> &mut TextInputState {
+    env.objc
+        .borrow_mut::<UIViewHostObject>(view)
+        .text_input
+        .get_or_insert_with(|| Box::new(TextInputState::new(is_text_view)))
+}
+
+/// For use by `UIView`'s `dealloc`.
+pub(super) fn release_state(env: &mut Environment, state: TextInputState) {
+    if state.editing {
+        env.window.stop_text_input();
+        ui_keyboard::hide(env);
+    }
+    release(env, state.font);
+    release(env, state.text_color);
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UITextField: UIView
+
+- (id)text {
+    text(env, this, false)
+}
+- (())setText:(id)text { // NSString*
+    set_text(env, this, false, text);
+}
+
+- (id)placeholder {
+    let placeholder = state(env, this, false).placeholder.clone();
+    match placeholder {
+        Some(placeholder) => {
+            let placeholder = ns_string::from_rust_string(env, placeholder);
+            autorelease(env, placeholder)
+        }
+        None => nil,
+    }
+}
+- (())setPlaceholder:(id)placeholder { // NSString*
+    let placeholder = if placeholder == nil {
+        None
+    } else {
+        Some(ns_string::to_rust_string(env, placeholder).into_owned())
+    };
+    state(env, this, false).placeholder = placeholder;
+}
+
+- (id)font {
+    state(env, this, false).font
+}
+- (())setFont:(id)font { // UIFont*
+    set_font(env, this, false, font);
+}
+- (id)textColor {
+    state(env, this, false).text_color
+}
+- (())setTextColor:(id)color { // UIColor*
+    set_text_color(env, this, false, color);
+}
+- (UITextAlignment)textAlignment {
+    state(env, this, false).text_alignment
+}
+- (())setTextAlignment:(UITextAlignment)alignment {
+    state(env, this, false).text_alignment = alignment;
+}
+- (UITextBorderStyle)borderStyle {
+    state(env, this, false).border_style
+}
+- (())setBorderStyle:(UITextBorderStyle)style {
+    state(env, this, false).border_style = style;
+}
+- (bool)clearsOnBeginEditing {
+    state(env, this, false).clears_on_begin_editing
+}
+- (())setClearsOnBeginEditing:(bool)clears {
+    state(env, this, false).clears_on_begin_editing = clears;
+}
+
+- (id)delegate {
+    state(env, this, false).delegate
+}
+- (())setDelegate:(id)delegate {
+    state(env, this, false).delegate = delegate;
+}
+
+// Really from UIControl.
+- (bool)isEnabled {
+    state(env, this, false).editable
+}
+- (())setEnabled:(bool)enabled {
+    state(env, this, false).editable = enabled;
+}
+
+- (bool)isEditing {
+    state(env, this, false).editing
+}
+
+- (bool)canBecomeFirstResponder {
+    state(env, this, false).editable
+}
+- (bool)becomeFirstResponder {
+    begin_editing(env, this, false)
+}
+- (bool)resignFirstResponder {
+    end_editing(env, this, false)
+}
+
+- (())didMoveToSuperview {
+    did_move_to_superview(env, this, false);
+}
+
+// UITextInputTraits implementation
+- (NSInteger)autocapitalizationType {
+    state(env, this, false).traits.autocapitalization_type
+}
+- (())setAutocapitalizationType:(NSInteger)value {
+    state(env, this, false).traits.autocapitalization_type = value;
+}
+- (NSInteger)autocorrectionType {
+    state(env, this, false).traits.autocorrection_type
+}
+- (())setAutocorrectionType:(NSInteger)value {
+    state(env, this, false).traits.autocorrection_type = value;
+}
+- (NSInteger)keyboardType {
+    state(env, this, false).traits.keyboard_type
+}
+- (())setKeyboardType:(NSInteger)value {
+    state(env, this, false).traits.keyboard_type = value;
+}
+- (NSInteger)keyboardAppearance {
+    state(env, this, false).traits.keyboard_appearance
+}
+- (())setKeyboardAppearance:(NSInteger)value {
+    state(env, this, false).traits.keyboard_appearance = value;
+}
+- (NSInteger)returnKeyType {
+    state(env, this, false).traits.return_key_type
+}
+- (())setReturnKeyType:(NSInteger)value {
+    state(env, this, false).traits.return_key_type = value;
+}
+- (bool)enablesReturnKeyAutomatically {
+    state(env, this, false).traits.enables_return_key_automatically
+}
+- (())setEnablesReturnKeyAutomatically:(bool)value {
+    state(env, this, false).traits.enables_return_key_automatically = value;
+}
+- (bool)isSecureTextEntry {
+    state(env, this, false).traits.secure_text_entry
+}
+- (())setSecureTextEntry:(bool)value {
+    state(env, this, false).traits.secure_text_entry = value;
+}
+
+@end
+
+};
+
+// Implementations shared with UITextView. `is_text_view` says which class the
+// methods are from, for when the state hasn't been created yet.
+
+pub(super) fn text(env: &mut Environment, view: id, is_text_view: bool) -> id {
+    let text = state(env, view, is_text_view).text.clone();
+    let text = ns_string::from_rust_string(env, text);
+    autorelease(env, text)
+}
+pub(super) fn set_text(env: &mut Environment, view: id, is_text_view: bool, text: id) {
+    let text = if text == nil {
+        String::new()
+    } else {
+        ns_string::to_rust_string(env, text).into_owned()
+    };
+    let state = state(env, view, is_text_view);
+    state.cursor = text.len();
+    state.text = text;
+}
+pub(super) fn set_font(env: &mut Environment, view: id, is_text_view: bool, font: id) {
+    retain(env, font);
+    let old = std::mem::replace(&mut state(env, view, is_text_view).font, font);
+    release(env, old);
+}
+pub(super) fn set_text_color(env: &mut Environment, view: id, is_text_view: bool, color: id) {
+    retain(env, color);
+    let old = std::mem::replace(&mut state(env, view, is_text_view).text_color, color);
+    release(env, old);
+}
+pub(super) fn text_alignment(
+    env: &mut Environment,
+    view: id,
+    is_text_view: bool,
+) -> UITextAlignment {
+    state(env, view, is_text_view).text_alignment
+}
+pub(super) fn set_text_alignment(
+    env: &mut Environment,
+    view: id,
+    is_text_view: bool,
+    alignment: UITextAlignment,
+) {
+    state(env, view, is_text_view).text_alignment = alignment;
+}
+pub(super) fn is_editable(env: &mut Environment, view: id, is_text_view: bool) -> bool {
+    state(env, view, is_text_view).editable
+}
+pub(super) fn set_editable(env: &mut Environment, view: id, is_text_view: bool, editable: bool) {
+    state(env, view, is_text_view).editable = editable;
+}
+
+/// Get the range of the insertion point, in UTF-16 code units like `NSString`.
+pub(super) fn selected_range(env: &mut Environment, view: id, is_text_view: bool) -> NSRange {
+    let state = state(env, view, is_text_view);
+    NSRange {
+        location: utf16_len(&state.text[..state.cursor]),
+        length: 0,
+    }
+}
+/// Set the insertion point. Selections aren't supported, so only the start of
+/// the range is used.
+pub(super) fn set_selected_range(
+    env: &mut Environment,
+    view: id,
+    is_text_view: bool,
+    range: NSRange,
+) {
+    let state = state(env, view, is_text_view);
+    state.cursor = byte_offset(&state.text, range.location);
+}
+
+/// Implementation of `didMoveToSuperview`: a text field or text view being
+/// removed can't stay the first responder.
+pub(super) fn did_move_to_superview(env: &mut Environment, view: id, is_text_view: bool) {
+    let superview = env.objc.borrow::<UIViewHostObject>(view).superview;
+    if superview == nil && state(env, view, is_text_view).editing {
+        end_editing(env, view, is_text_view);
+        // This can't be refused.
+        if state(env, view, is_text_view).editing {
+            finish_editing(env, view, is_text_view);
+        }
+    }
+}
+
+fn utf16_len(text: &str) -> NSUInteger {
+    text.encode_utf16().count().try_into().unwrap()
+}
+
+/// Convert an offset in UTF-16 code units to a byte offset, clamped to the
+/// length of the text.
+fn byte_offset(text: &str, utf16_offset: NSUInteger) -> usize {
+    let mut units = 0;
+    for (offset, c) in text.char_indices() {
+        if units >= utf16_offset as usize {
+            return offset;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn delegate(env: &mut Environment, view: id) -> id {
+    msg![env; view delegate]
+}
+
+/// Look up a delegate method, if the delegate implements it. The method names
+/// for `UITextField` and `UITextView` delegates are given in that order.
+fn delegate_method(
+    env: &mut Environment,
+    view: id,
+    is_text_view: bool,
+    (text_field_name, text_view_name): (&str, &str),
+) -> Option<(id, SEL)> {
+    let delegate = delegate(env, view);
+    if delegate == nil {
+        return None;
+    }
+    let name = if is_text_view {
+        text_view_name
+    } else {
+        text_field_name
+    };
+    let selector = env.objc.lookup_selector(name)?;
+    if msg![env; delegate respondsToSelector:selector] {
+        Some((delegate, selector))
+    } else {
+        None
+    }
+}
+
+/// Call a delegate method that returns [bool], which defaults to [true].
+fn ask_delegate(env: &mut Environment, view: id, is_text_view: bool, names: (&str, &str)) -> bool {
+    match delegate_method(env, view, is_text_view, names) {
+        Some((delegate, selector)) => msg_send(env, (delegate, selector, view)),
+        None => true,
+    }
+}
+
+fn notify_delegate(env: &mut Environment, view: id, is_text_view: bool, names: (&str, &str)) {
+    if let Some((delegate, selector)) = delegate_method(env, view, is_text_view, names) {
+        let () = msg_send(env, (delegate, selector, view));
+    }
+}
+
+/// Implementation of `becomeFirstResponder`.
+pub(super) fn begin_editing(env: &mut Environment, view: id, is_text_view: bool) -> bool {
+    let input_state = state(env, view, is_text_view);
+    if input_state.editing {
+        return true;
+    }
+    if !input_state.editable {
+        return false;
+    }
+    if !ask_delegate(
+        env,
+        view,
+        is_text_view,
+        (
+            "textFieldShouldBeginEditing:",
+            "textViewShouldBeginEditing:",
+        ),
+    ) {
+        return false;
+    }
+    // This ends editing in any other text field.
+    env.framework_state.uikit.ui_text_field.switching = true;
+    let became_first_responder = ui_responder::become_first_responder(env, view);
+    env.framework_state.uikit.ui_text_field.switching = false;
+    if !became_first_responder {
+        // The keyboard might have been kept for this one.
+        if editing_text_input(env).is_none() {
+            ui_keyboard::hide(env);
+        }
+        return false;
+    }
+
+    log_dbg!("Began editing {:?}", view);
+    let state = state(env, view, is_text_view);
+    state.editing = true;
+    if state.clears_on_begin_editing {
+        state.text.clear();
+    }
+    state.cursor = state.text.len();
+    let rect = frame_on_screen(env, view).map(|frame| {
+        (
+            (frame.origin.x, frame.origin.y),
+            (frame.size.width, frame.size.height),
+        )
+    });
+    env.window.start_text_input(rect);
+
+    notify_delegate(
+        env,
+        view,
+        is_text_view,
+        ("textFieldDidBeginEditing:", "textViewDidBeginEditing:"),
+    );
+    ui_keyboard::show(env);
+    true
+}
+
+/// Implementation of `resignFirstResponder`.
+pub(super) fn end_editing(env: &mut Environment, view: id, is_text_view: bool) -> bool {
+    if !state(env, view, is_text_view).editing {
+        ui_responder::resign_first_responder(env, view);
+        return true;
+    }
+    if !ask_delegate(
+        env,
+        view,
+        is_text_view,
+        ("textFieldShouldEndEditing:", "textViewShouldEndEditing:"),
+    ) {
+        return false;
+    }
+    finish_editing(env, view, is_text_view);
+    true
+}
+
+fn finish_editing(env: &mut Environment, view: id, is_text_view: bool) {
+    log_dbg!("Ended editing {:?}", view);
+    state(env, view, is_text_view).editing = false;
+    ui_responder::resign_first_responder(env, view);
+    env.window.stop_text_input();
+    notify_delegate(
+        env,
+        view,
+        is_text_view,
+        ("textFieldDidEndEditing:", "textViewDidEndEditing:"),
+    );
+    if !env.framework_state.uikit.ui_text_field.switching {
+        ui_keyboard::hide(env);
+    }
+}
+
+/// Replace the text between two byte offsets, if the delegate allows it.
+fn replace(
+    env: &mut Environment,
+    view: id,
+    is_text_view: bool,
+    start: usize,
+    end: usize,
+    replacement: &str,
+) {
+    let text = &state(env, view, is_text_view).text;
+    let range = NSRange {
+        location: utf16_len(&text[..start]),
+        length: utf16_len(&text[start..end]),
+    };
+    if let Some((delegate, selector)) = delegate_method(
+        env,
+        view,
+        is_text_view,
+        (
+            "textField:shouldChangeCharactersInRange:replacementString:",
+            "textView:shouldChangeTextInRange:replacementText:",
+        ),
+    ) {
+        let replacement = ns_string::from_rust_string(env, replacement.to_string());
+        let allowed: bool = msg_send(env, (delegate, selector, view, range, replacement));
+        release(env, replacement);
+        if !allowed {
+            return;
+        }
+    }
+
+    let state = state(env, view, is_text_view);
+    state.text.replace_range(start..end, replacement);
+    state.cursor = start + replacement.len();
+    log_dbg!("Text of {:?} is now {:?}", view, state.text);
+
+    if is_text_view {
+        notify_delegate(env, view, is_text_view, ("", "textViewDidChange:"));
+    }
+}
+
+fn move_cursor(env: &mut Environment, view: id, is_text_view: bool, cursor: usize) {
+    let state = state(env, view, is_text_view);
+    if state.cursor == cursor {
+        return;
+    }
+    state.cursor = cursor;
+    if is_text_view {
+        notify_delegate(env, view, is_text_view, ("", "textViewDidChangeSelection:"));
+    }
+}
+
+fn handle_key(env: &mut Environment, view: id, is_text_view: bool, key: EditingKey) {
+    let state = state(env, view, is_text_view);
+    let text = &state.text;
+    let cursor = state.cursor;
+    let previous = text[..cursor].char_indices().next_back().map(|(i, _)| i);
+    let next = text[cursor..].chars().next().map(|c| cursor + c.len_utf8());
+    let text_len = text.len();
+    match key {
+        EditingKey::Backspace => {
+            if let Some(previous) = previous {
+                replace(env, view, is_text_view, previous, cursor, "");
+            }
+        }
+        EditingKey::Delete => {
+            if let Some(next) = next {
+                replace(env, view, is_text_view, cursor, next, "");
+            }
+        }
+        EditingKey::Left => move_cursor(env, view, is_text_view, previous.unwrap_or(0)),
+        EditingKey::Right => move_cursor(env, view, is_text_view, next.unwrap_or(text_len)),
+        EditingKey::Home => move_cursor(env, view, is_text_view, 0),
+        EditingKey::End => move_cursor(env, view, is_text_view, text_len),
+        EditingKey::Return => {
+            if is_text_view {
+                replace(env, view, is_text_view, cursor, cursor, "\n");
+            } else {
+                // The delegate usually resigns first responder here. The return
+                // value only matters for the on-screen keyboard.
+                let _ = ask_delegate(env, view, is_text_view, ("textFieldShouldReturn:", ""));
+            }
+        }
+    }
+}
+
+/// Find an editable text field or text view in a window at a point on the
+/// screen.
+fn text_input_at_point(env: &mut Environment, point: (f32, f32)) -> Option<(id, bool)> {
+    let text_field_class = env.objc.get_known_class("UITextField", &mut env.mem);
+    let text_view_class = env.objc.get_known_class("UITextView", &mut env.mem);
+    let views = env.framework_state.uikit.ui_view.views.clone();
+    for view in views {
+        let is_text_view = if msg![env; view isKindOfClass:text_field_class] {
+            false
+        } else if msg![env; view isKindOfClass:text_view_class] {
+            true
+        } else {
+            continue;
+        };
+        if !state(env, view, is_text_view).editable {
+            continue;
+        }
+        let Some(frame) = frame_on_screen(env, view) else {
+            continue;
+        };
+        let point = CGPoint {
+            x: point.0,
+            y: point.1,
+        };
+        if point.x >= frame.origin.x
+            && point.y >= frame.origin.y
+            && point.x < frame.origin.x + frame.size.width
+            && point.y < frame.origin.y + frame.size.height
+        {
+            return Some((view, is_text_view));
+        }
+    }
+    None
+}
+
+/// The text field or text view being edited, if any.
+fn editing_text_input(env: &mut Environment) -> Option<(id, bool)> {
+    let responder = ui_responder::first_responder(env)?;
+    let host_object = env.objc.borrow::<UIViewHostObject>(responder);
+    let state = host_object.text_input.as_ref()?;
+    state.editing.then_some((responder, state.is_text_view))
+}
+
+/// For use by [super::handle_events]: handle keyboard input for the text field
+/// or text view being edited, and tapping on text fields and text views to
+/// begin editing them. Returns [true] if the event was consumed.
+pub(super) fn handle_event(env: &mut Environment, event: &Event) -> bool {
+    // UIKit creates and drains autorelease pools when handling events.
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let consumed = match *event {
+        Event::TouchDown(source, location) => match text_input_at_point(env, location) {
+            Some((view, is_text_view)) => {
+                env.framework_state.uikit.ui_text_field.touch = Some(source);
+                begin_editing(env, view, is_text_view);
+                true
+            }
+            None => false,
+        },
+        Event::TouchMove(source, _) | Event::TouchUp(source, _) => {
+            let state = &mut env.framework_state.uikit.ui_text_field;
+            if state.touch == Some(source) {
+                if matches!(event, Event::TouchUp(..)) {
+                    state.touch = None;
+                }
+                true
+            } else {
+                false
+            }
+        }
+        Event::TextInput(ref text) => {
+            type_text(env, text);
+            true
+        }
+        Event::EditingKey(key) => {
+            press_key(env, key);
+            true
+        }
+        _ => false,
+    };
+    release(env, pool);
+    consumed
+}
+
+/// Insert text into the text field or text view being edited, if any. Also
+/// used by the on-screen keyboard.
+pub(super) fn type_text(env: &mut Environment, text: &str) {
+    let Some((view, is_text_view)) = editing_text_input(env) else {
+        return;
+    };
+    let text = if is_text_view {
+        text.to_string()
+    } else {
+        // Text fields are single-line.
+        text.replace(['\r', '\n'], "")
+    };
+    let cursor = state(env, view, is_text_view).cursor;
+    replace(env, view, is_text_view, cursor, cursor, &text);
+}
+
+/// Handle an editing key for the text field or text view being edited, if
+/// any. Also used by the on-screen keyboard.
+pub(super) fn press_key(env: &mut Environment, key: EditingKey) {
+    if let Some((view, is_text_view)) = editing_text_input(env) {
+        handle_key(env, view, is_text_view, key);
+    }
+}
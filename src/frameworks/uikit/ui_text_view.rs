@@ -0,0 +1,115 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITextView`.
+//!
+//! Editing is shared with `UITextField`, see [super::ui_text_field].
+
+use super::ui_font::UITextAlignment;
+use super::ui_text_field::{
+    begin_editing, did_move_to_superview, end_editing, is_editable, selected_range, set_editable,
+    set_font, set_selected_range, set_text, set_text_alignment, set_text_color, state, text,
+    text_alignment,
+};
+use crate::frameworks::foundation::{NSInteger, NSRange};
+use crate::objc::{id, objc_classes, ClassExports};
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UITextView: UIScrollView
+
+// The delegate is the UIScrollView one.
+
+- (id)text {
+    text(env, this, true)
+}
+- (())setText:(id)text { // NSString*
+    set_text(env, this, true, text);
+}
+- (bool)hasText {
+    !state(env, this, true).text.is_empty()
+}
+
+- (id)font {
+    state(env, this, true).font
+}
+- (())setFont:(id)font { // UIFont*
+    set_font(env, this, true, font);
+}
+- (id)textColor {
+    state(env, this, true).text_color
+}
+- (())setTextColor:(id)color { // UIColor*
+    set_text_color(env, this, true, color);
+}
+- (UITextAlignment)textAlignment {
+    text_alignment(env, this, true)
+}
+- (())setTextAlignment:(UITextAlignment)alignment {
+    set_text_alignment(env, this, true, alignment);
+}
+
+- (bool)isEditable {
+    is_editable(env, this, true)
+}
+- (())setEditable:(bool)editable {
+    set_editable(env, this, true, editable);
+}
+
+- (NSRange)selectedRange {
+    selected_range(env, this, true)
+}
+- (())setSelectedRange:(NSRange)range {
+    set_selected_range(env, this, true, range);
+}
+- (())scrollRangeToVisible:(NSRange)_range {
+    // TODO: Text isn't laid out, so there's nowhere to scroll to.
+}
+
+- (bool)canBecomeFirstResponder {
+    is_editable(env, this, true)
+}
+- (bool)becomeFirstResponder {
+    begin_editing(env, this, true)
+}
+- (bool)resignFirstResponder {
+    end_editing(env, this, true)
+}
+
+- (())didMoveToSuperview {
+    did_move_to_superview(env, this, true);
+}
+
+// UITextInputTraits implementation (partial)
+- (NSInteger)autocapitalizationType {
+    state(env, this, true).traits.autocapitalization_type
+}
+- (())setAutocapitalizationType:(NSInteger)value {
+    state(env, this, true).traits.autocapitalization_type = value;
+}
+- (NSInteger)autocorrectionType {
+    state(env, this, true).traits.autocorrection_type
+}
+- (())setAutocorrectionType:(NSInteger)value {
+    state(env, this, true).traits.autocorrection_type = value;
+}
+- (NSInteger)keyboardType {
+    state(env, this, true).traits.keyboard_type
+}
+- (())setKeyboardType:(NSInteger)value {
+    state(env, this, true).traits.keyboard_type = value;
+}
+- (NSInteger)returnKeyType {
+    state(env, this, true).traits.return_key_type
+}
+- (())setReturnKeyType:(NSInteger)value {
+    state(env, this, true).traits.return_key_type = value;
+}
+
+@end
+
+};
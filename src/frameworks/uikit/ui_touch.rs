@@ -5,27 +5,57 @@
  */
 //! `UITouch`.
 
-use super::ui_view::UIViewHostObject;
+use super::ui_view::{point_from_screen, point_to_screen, UIViewHostObject};
+use super::{ui_control, ui_event, ui_window};
+use crate::frameworks::core_graphics::cg_affine_transform::bounding_rect;
 use crate::frameworks::core_graphics::{CGFloat, CGPoint};
-use crate::frameworks::foundation::{NSTimeInterval, NSUInteger};
+use crate::frameworks::foundation::{ns_set, NSInteger, NSTimeInterval, NSUInteger};
 use crate::mem::MutVoidPtr;
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
 };
-use crate::window::Event;
+use crate::window::{Event, TouchSource};
 use crate::Environment;
 
+/// How soon after a tap a new touch must begin to increase the tap count.
+const MULTI_TAP_INTERVAL: NSTimeInterval = 0.35;
+/// How close to a tap a new touch must begin to increase the tap count, and
+/// how far a touch can move while still counting as a tap.
+const MULTI_TAP_DISTANCE: CGFloat = 30.0;
+
 #[derive(Default)]
 pub struct State {
-    current_touch: Option<id>,
+    /// Each input device can have one touch in progress at once. These are
+    /// strong references, in the order the touches began.
+    current_touches: Vec<(TouchSource, id)>,
+    /// Sources whose touch is being ignored, because it landed on a view that
+    /// already has a touch and doesn't have multiple touch enabled.
+    ignored_sources: Vec<TouchSource>,
+    /// The `UIEvent*` used for all touch events, like in UIKit. Strong
+    /// reference, created when first needed.
+    event: Option<id>,
+    /// Location, end time and tap count of the most recent tap.
+    last_tap: Option<(CGPoint, NSTimeInterval, NSUInteger)>,
 }
 
+type UITouchPhase = NSInteger;
+const UITouchPhaseBegan: UITouchPhase = 0;
+const UITouchPhaseMoved: UITouchPhase = 1;
+const UITouchPhaseStationary: UITouchPhase = 2;
+const UITouchPhaseEnded: UITouchPhase = 3;
+
 struct UITouchHostObject {
     /// Strong reference to the `UIView`
     view: id,
     /// Relative to screen
     location: CGPoint,
+    /// Relative to screen
+    previous_location: CGPoint,
+    /// Relative to screen
+    began_location: CGPoint,
     timestamp: NSTimeInterval,
+    phase: UITouchPhase,
+    tap_count: NSUInteger,
 }
 impl HostObject for UITouchHostObject {}
 
@@ -39,7 +69,11 @@ pub const CLASSES: ClassExports = objc_classes! {
     let host_object = Box::new(UITouchHostObject {
         view: nil,
         location: CGPoint { x: 0.0, y: 0.0 },
+        previous_location: CGPoint { x: 0.0, y: 0.0 },
+        began_location: CGPoint { x: 0.0, y: 0.0 },
         timestamp: 0.0,
+        phase: UITouchPhaseBegan,
+        tap_count: 1,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
@@ -52,69 +86,127 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (CGPoint)locationInView:(id)that_view { // UIView*
     let &UITouchHostObject { location, .. } = env.objc.borrow(this);
-    if that_view == nil {
-        location
-    } else {
-        // FIXME, see below
-        resolve_point_in_view(env, that_view, location).unwrap()
-    }
+    convert_location(env, location, that_view)
+}
+- (CGPoint)previousLocationInView:(id)that_view { // UIView*
+    let &UITouchHostObject { previous_location, .. } = env.objc.borrow(this);
+    convert_location(env, previous_location, that_view)
 }
 
 - (id)view {
     env.objc.borrow::<UITouchHostObject>(this).view
 }
+- (id)window {
+    let mut view = env.objc.borrow::<UITouchHostObject>(this).view;
+    while view != nil {
+        let superview = env.objc.borrow::<UIViewHostObject>(view).superview;
+        if superview == nil {
+            break;
+        }
+        view = superview;
+    }
+    let ui_window_class = env.objc.get_known_class("UIWindow", &mut env.mem);
+    if view != nil && msg![env; view isKindOfClass:ui_window_class] {
+        view
+    } else {
+        nil
+    }
+}
 
 - (NSTimeInterval)timestamp {
     env.objc.borrow::<UITouchHostObject>(this).timestamp
 }
 
+- (UITouchPhase)phase {
+    env.objc.borrow::<UITouchHostObject>(this).phase
+}
+
 - (NSUInteger)tapCount {
-    1 // TODO: support double-taps etc
+    env.objc.borrow::<UITouchHostObject>(this).tap_count
 }
 
 @end
 
 };
 
-fn resolve_point_in_view(env: &mut Environment, view: id, point: CGPoint) -> Option<CGPoint> {
+fn convert_location(env: &mut Environment, location: CGPoint, that_view: id) -> CGPoint {
+    if that_view == nil {
+        location
+    } else {
+        point_from_screen(env, that_view, location)
+    }
+}
+
+/// For use by `UIEvent`: get the view a touch belongs to.
+pub(super) fn touch_view(env: &mut Environment, touch: id) -> id {
+    env.objc.borrow::<UITouchHostObject>(touch).view
+}
+
+fn distance(a: CGPoint, b: CGPoint) -> CGFloat {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+/// Whether a view covers the whole screen, possibly after being rotated.
+fn is_full_screen(env: &mut Environment, view: id) -> bool {
     let (expected_width, expected_height) = env.window.size_unrotated_unscaled();
     let expected_width = expected_width as CGFloat;
     let expected_height = expected_height as CGFloat;
 
-    let &UIViewHostObject { bounds, center, .. } = env.objc.borrow(view);
+    let bounds = env.objc.borrow::<UIViewHostObject>(view).bounds;
+    let frame = bounding_rect(bounds, |corner| point_to_screen(env, view, corner));
 
-    if bounds.size.width != expected_width || bounds.size.height != expected_height {
-        return None;
-    }
-    if center.x != expected_width / 2.0 || center.y != expected_height / 2.0 {
-        return None;
-    }
-
-    Some(CGPoint {
-        x: point.x - bounds.origin.x,
-        y: point.y - bounds.origin.y,
-    })
+    frame.origin.x == 0.0
+        && frame.origin.y == 0.0
+        && frame.size.width == expected_width
+        && frame.size.height == expected_height
 }
 
 fn find_view_for_touch(env: &mut Environment, point: CGPoint) -> Option<id> {
-    // FIXME: This is a massive hack that is only going to work for apps that
-    // have a single view which handles all touch inputs. We should eventually
-    // implement the proper responder chain.
+    // Controls are hit-tested properly, so they get priority.
+    if let Some(control) = ui_control::control_at_point(env, point) {
+        log_dbg!("Picked control {:?} for touch event", control);
+        return Some(control);
+    }
 
+    // Hit-test the visible windows, starting with the key window.
     let ui_window_class = env.objc.get_known_class("UIWindow", &mut env.mem);
     // TODO: Can we avoid copying this somehow?
     let views = env.framework_state.uikit.ui_view.views.clone();
+    let mut windows: Vec<id> = views
+        .iter()
+        .copied()
+        .filter(|&view| {
+            msg![env; view isKindOfClass:ui_window_class]
+                && !env.objc.borrow::<UIViewHostObject>(view).hidden
+        })
+        .collect();
+    if let Some(key_window) = ui_window::key_window(env) {
+        windows.retain(|&window| window != key_window);
+        windows.insert(0, key_window);
+    }
+    for window in windows {
+        let window_point = point_from_screen(env, window, point);
+        let hit: id = msg![env; window hitTest:window_point withEvent:nil];
+        // There's no reason a UIWindow can't handle touch events, but apps
+        // which put their views in a window almost never do that, so the
+        // fallback below is tried instead.
+        if hit != nil && hit != window {
+            log_dbg!("Picked view {:?} for touch event", hit);
+            return Some(hit);
+        }
+    }
+
+    // FIXME: This is a hack for apps that have views which aren't in a
+    // window, and only works if there's a single view which handles all touch
+    // inputs.
     for view in views {
-        // There's no reason a UIWindow can't handle touch events, this is just
-        // a hack specific to apps which don't do that.
         if msg![env; view isKindOfClass:ui_window_class] {
             continue;
         }
 
         // FIXME: This is an even bigger hack, it is assuming there is a single
-        // view with the same size as the screen, and can't account for
-        // the view hierarchy's effects on the co-ordinate system!
-        if resolve_point_in_view(env, view, point).is_none() {
+        // view covering the whole screen.
+        if !is_full_screen(env, view) {
             continue;
         }
 
@@ -126,16 +218,116 @@ fn find_view_for_touch(env: &mut Environment, point: CGPoint) -> Option<id> {
     None
 }
 
+/// Send a touch event for `touch` via `-[UIApplication sendEvent:]`, with the
+/// other touches in progress included in the event as stationary touches.
+fn send_touch_event(env: &mut Environment, touch: id, phase: UITouchPhase) {
+    let timestamp = env.objc.borrow::<UITouchHostObject>(touch).timestamp;
+
+    let mut all_touches: Vec<id> = env
+        .framework_state
+        .uikit
+        .ui_touch
+        .current_touches
+        .iter()
+        .map(|&(_source, touch)| touch)
+        .collect();
+    if !all_touches.contains(&touch) {
+        all_touches.push(touch);
+    }
+    for &other_touch in &all_touches {
+        env.objc.borrow_mut::<UITouchHostObject>(other_touch).phase = if other_touch == touch {
+            phase
+        } else {
+            UITouchPhaseStationary
+        };
+    }
+
+    let event = match env.framework_state.uikit.ui_touch.event {
+        Some(event) => event,
+        None => {
+            let event: id = msg_class![env; UIEvent new];
+            env.framework_state.uikit.ui_touch.event = Some(event);
+            event
+        }
+    };
+    ui_event::set_touches(env, event, all_touches, timestamp);
+
+    let app: id = msg_class![env; UIApplication sharedApplication];
+    () = msg![env; app sendEvent:event];
+}
+
+/// For use by `UIApplication` and `UIWindow`: get the touches in an event that
+/// aren't stationary.
+pub(super) fn changed_touches(env: &mut Environment, event: id) -> Vec<id> {
+    ui_event::touches(env, event)
+        .into_iter()
+        .filter(|&touch| {
+            env.objc.borrow::<UITouchHostObject>(touch).phase != UITouchPhaseStationary
+        })
+        .collect()
+}
+
+/// For use by `UIApplication` and `UIWindow`: send a touch to its view, with
+/// the method for its phase.
+pub(super) fn deliver_touch(env: &mut Environment, touch: id, event: id) {
+    let &UITouchHostObject { view, phase, .. } = env.objc.borrow(touch);
+
+    retain(env, touch);
+    let touches = ns_set::from_vec(env, vec![touch]);
+    autorelease(env, touches);
+
+    match phase {
+        UITouchPhaseBegan => {
+            log_dbg!(
+                "Sending [{:?} touchesBegan:{:?} withEvent:{:?}]",
+                view,
+                touches,
+                event
+            );
+            let _: () = msg![env; view touchesBegan:touches withEvent:event];
+        }
+        UITouchPhaseMoved => {
+            log_dbg!(
+                "Sending [{:?} touchesMoved:{:?} withEvent:{:?}]",
+                view,
+                touches,
+                event
+            );
+            let _: () = msg![env; view touchesMoved:touches withEvent:event];
+        }
+        UITouchPhaseEnded => {
+            log_dbg!(
+                "Sending [{:?} touchesEnded:{:?} withEvent:{:?}]",
+                view,
+                touches,
+                event
+            );
+            let _: () = msg![env; view touchesEnded:touches withEvent:event];
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn current_touch(env: &mut Environment, source: TouchSource) -> Option<id> {
+    env.framework_state
+        .uikit
+        .ui_touch
+        .current_touches
+        .iter()
+        .find(|&&(candidate, _touch)| candidate == source)
+        .map(|&(_source, touch)| touch)
+}
+
 /// [super::handle_events] will forward touch events to this function.
 pub fn handle_event(env: &mut Environment, event: Event) {
     match event {
-        Event::TouchDown(coords) => {
-            if env.framework_state.uikit.ui_touch.current_touch.is_some() {
+        Event::TouchDown(source, coords) => {
+            if current_touch(env, source).is_some() {
                 log!("Warning: New touch initiated but current touch did not end yet, treating as movement.");
-                return handle_event(env, Event::TouchMove(coords));
+                return handle_event(env, Event::TouchMove(source, coords));
             }
 
-            log_dbg!("Touch down: {:?}", coords);
+            log_dbg!("Touch down ({:?}): {:?}", source, coords);
 
             let location = CGPoint {
                 x: coords.0,
@@ -146,6 +338,29 @@ pub fn handle_event(env: &mut Environment, event: Event) {
                 return;
             };
 
+            // A view without multiple touch enabled only gets one touch at a
+            // time, and the others are ignored.
+            let view_is_touched = env
+                .framework_state
+                .uikit
+                .ui_touch
+                .current_touches
+                .iter()
+                .any(|&(_source, touch)| env.objc.borrow::<UITouchHostObject>(touch).view == view);
+            if view_is_touched && !msg![env; view isMultipleTouchEnabled] {
+                log_dbg!(
+                    "Ignoring touch ({:?}), {:?} is already touched",
+                    source,
+                    view
+                );
+                env.framework_state
+                    .uikit
+                    .ui_touch
+                    .ignored_sources
+                    .push(source);
+                return;
+            }
+
             // UIKit creates and drains autorelease pools when handling events.
             let pool: id = msg_class![env; NSAutoreleasePool new];
 
@@ -155,40 +370,53 @@ pub fn handle_event(env: &mut Environment, event: Event) {
             // event was dispatched. Maybe we'll need to fix this eventually.
             let timestamp: NSTimeInterval = msg_class![env; NSProcessInfo systemUptime];
 
+            let tap_count = match env.framework_state.uikit.ui_touch.last_tap {
+                Some((tap_location, tap_timestamp, tap_count))
+                    if timestamp - tap_timestamp <= MULTI_TAP_INTERVAL
+                        && distance(tap_location, location) <= MULTI_TAP_DISTANCE =>
+                {
+                    tap_count + 1
+                }
+                _ => 1,
+            };
+
             let new_touch: id = msg_class![env; UITouch alloc];
             retain(env, view);
             *env.objc.borrow_mut(new_touch) = UITouchHostObject {
                 view,
                 location,
+                previous_location: location,
+                began_location: location,
                 timestamp,
+                phase: UITouchPhaseBegan,
+                tap_count,
             };
-            autorelease(env, new_touch);
-
-            env.framework_state.uikit.ui_touch.current_touch = Some(new_touch);
-            retain(env, new_touch);
 
-            let touches: id = msg_class![env; NSSet setWithObject:new_touch];
-            // TODO: populate event object (not all apps care about it)
-            let event: id = msg_class![env; UIEvent new];
-            autorelease(env, event);
+            env.framework_state
+                .uikit
+                .ui_touch
+                .current_touches
+                .push((source, new_touch));
 
-            log_dbg!(
-                "Sending [{:?} touchesBegan:{:?} withEvent:{:?}]",
-                view,
-                touches,
-                event
-            );
-            let _: () = msg![env; view touchesBegan:touches withEvent:event];
+            send_touch_event(env, new_touch, UITouchPhaseBegan);
 
             release(env, pool);
         }
-        Event::TouchMove(coords) => {
-            let Some(touch) = env.framework_state.uikit.ui_touch.current_touch else {
-                log!("Warning: Touch move event received but no current touch, ignoring.");
+        Event::TouchMove(source, coords) => {
+            let Some(touch) = current_touch(env, source) else {
+                if !env
+                    .framework_state
+                    .uikit
+                    .ui_touch
+                    .ignored_sources
+                    .contains(&source)
+                {
+                    log!("Warning: Touch move event received but no current touch, ignoring.");
+                }
                 return;
             };
 
-            log_dbg!("Touch move: {:?}", coords);
+            log_dbg!("Touch move ({:?}): {:?}", source, coords);
 
             let location = CGPoint {
                 x: coords.0,
@@ -197,35 +425,29 @@ pub fn handle_event(env: &mut Environment, event: Event) {
 
             let timestamp: NSTimeInterval = msg_class![env; NSProcessInfo systemUptime];
 
-            let view = env.objc.borrow::<UITouchHostObject>(touch).view;
             let host_object = env.objc.borrow_mut::<UITouchHostObject>(touch);
+            host_object.previous_location = host_object.location;
             host_object.location = location;
             host_object.timestamp = timestamp;
 
             let pool: id = msg_class![env; NSAutoreleasePool new];
 
-            let touches: id = msg_class![env; NSSet setWithObject:touch];
-            // TODO: populate event object (not all apps care about it)
-            let event: id = msg_class![env; UIEvent new];
-            autorelease(env, event);
-
-            log_dbg!(
-                "Sending [{:?} touchesMoved:{:?} withEvent:{:?}]",
-                view,
-                touches,
-                event
-            );
-            let _: () = msg![env; view touchesMoved:touches withEvent:event];
+            send_touch_event(env, touch, UITouchPhaseMoved);
 
             release(env, pool);
         }
-        Event::TouchUp(coords) => {
-            let Some(touch) = env.framework_state.uikit.ui_touch.current_touch else {
-                log!("Warning: Touch up event received but no current touch, ignoring.");
+        Event::TouchUp(source, coords) => {
+            let Some(touch) = current_touch(env, source) else {
+                let ignored_sources = &mut env.framework_state.uikit.ui_touch.ignored_sources;
+                if let Some(index) = ignored_sources.iter().position(|&s| s == source) {
+                    ignored_sources.swap_remove(index);
+                } else {
+                    log!("Warning: Touch up event received but no current touch, ignoring.");
+                }
                 return;
             };
 
-            log_dbg!("Touch up: {:?}", coords);
+            log_dbg!("Touch up ({:?}): {:?}", source, coords);
 
             let location = CGPoint {
                 x: coords.0,
@@ -234,28 +456,34 @@ pub fn handle_event(env: &mut Environment, event: Event) {
 
             let timestamp: NSTimeInterval = msg_class![env; NSProcessInfo systemUptime];
 
-            let view = env.objc.borrow::<UITouchHostObject>(touch).view;
             let host_object = env.objc.borrow_mut::<UITouchHostObject>(touch);
+            host_object.previous_location = host_object.location;
             host_object.location = location;
             host_object.timestamp = timestamp;
+            let &mut UITouchHostObject {
+                began_location,
+                tap_count,
+                ..
+            } = host_object;
+
+            env.framework_state.uikit.ui_touch.last_tap =
+                if distance(began_location, location) <= MULTI_TAP_DISTANCE {
+                    Some((location, timestamp, tap_count))
+                } else {
+                    None
+                };
 
             let pool: id = msg_class![env; NSAutoreleasePool new];
 
-            let touches: id = msg_class![env; NSSet setWithObject:touch];
-            // TODO: populate event object (not all apps care about it)
-            let event: id = msg_class![env; UIEvent new];
-            autorelease(env, event);
+            env.framework_state
+                .uikit
+                .ui_touch
+                .current_touches
+                .retain(|&(candidate, _touch)| candidate != source);
 
-            env.framework_state.uikit.ui_touch.current_touch = None;
-            release(env, touch); // only owner now should be the NSSet
+            send_touch_event(env, touch, UITouchPhaseEnded);
 
-            log_dbg!(
-                "Sending [{:?} touchesEnded:{:?} withEvent:{:?}]",
-                view,
-                touches,
-                event
-            );
-            let _: () = msg![env; view touchesEnded:touches withEvent:event];
+            release(env, touch); // the event and NSSet still own it
 
             release(env, pool);
         }
@@ -5,21 +5,86 @@
  */
 //! `UIView`.
 
-use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+mod animation;
+
+use super::ui_activity_indicator_view::ActivityIndicatorState;
+use super::ui_alert_view::AlertState;
+use super::ui_control::ControlState;
+use super::ui_progress_view::ProgressViewState;
+use super::ui_scroll_view::ScrollViewState;
+use super::ui_tab_bar::TabBarState;
+use super::ui_table_view::TableViewState;
+use super::ui_table_view_cell::TableViewCellState;
+use super::ui_text_field::TextInputState;
+use super::ui_web_view::WebViewState;
+use super::{
+    ui_control, ui_graphics, ui_responder, ui_tab_bar, ui_table_view, ui_table_view_cell,
+    ui_text_field, ui_web_view, ui_window,
+};
+use crate::frameworks::core_animation::presentation;
+use crate::frameworks::core_graphics::cg_affine_transform::{
+    bounding_rect, CGAffineTransform, CGAffineTransformIdentity,
+};
+use crate::frameworks::core_graphics::cg_context::CGContextRef;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
 use crate::frameworks::foundation::ns_string::{get_static_str, to_rust_string};
+use crate::frameworks::foundation::{ns_array, NSInteger, NSTimeInterval};
+use crate::frameworks::map_kit::{mk_annotation_view, mk_map_view};
 use crate::mem::MutVoidPtr;
-use crate::objc::{id, msg, objc_classes, release, Class, ClassExports, HostObject};
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, release, retain, Class, ClassExports, HostObject, SEL,
+};
+use crate::Environment;
+use animation::{UIViewAnimationCurve, UIViewAnimationTransition, UIViewAnimationTransitionNone};
 
 #[derive(Default)]
 pub struct State {
     pub(super) views: Vec<id>,
+    animation: animation::State,
 }
 
 pub(super) struct UIViewHostObject {
     pub(super) bounds: CGRect,
     pub(super) center: CGPoint,
-    /// CALayer or subclass.
+    /// Applied around the center.
+    pub(super) transform: CGAffineTransform,
+    alpha: CGFloat,
+    /// `UIColor*`, strong reference. The layer has the equivalent `CGColorRef`.
+    background_color: id,
+    /// CALayer or subclass, strong reference. The view is its delegate, and
+    /// mirrors its geometry and hierarchy to it so it can be composited.
     layer: id,
+    /// If this is false, touches beyond the first are ignored while the view is
+    /// being touched (see [super::ui_touch]).
+    multiple_touch_enabled: bool,
+    pub(super) hidden: bool,
+    pub(super) user_interaction_enabled: bool,
+    /// Weak reference.
+    pub(super) superview: id,
+    /// Strong references, back to front.
+    pub(super) subviews: Vec<id>,
+    /// The `UIViewController*` whose view this is, if any. Weak reference.
+    pub(super) view_controller: id,
+    /// For UIScrollView and subclasses only.
+    pub(super) scroll_view: Option<Box<ScrollViewState>>,
+    /// For UITableView only.
+    pub(super) table_view: Option<Box<TableViewState>>,
+    /// For UITableViewCell only.
+    pub(super) table_view_cell: Option<Box<TableViewCellState>>,
+    /// For UIAlertView and UIActionSheet only.
+    pub(super) alert: Option<Box<AlertState>>,
+    /// For UITextField and UITextView only.
+    pub(super) text_input: Option<Box<TextInputState>>,
+    /// For UIWebView only.
+    pub(super) web_view: Option<Box<WebViewState>>,
+    /// For UIControl and subclasses only.
+    pub(super) control: Option<Box<ControlState>>,
+    /// For UITabBar only.
+    pub(super) tab_bar: Option<Box<TabBarState>>,
+    /// For UIActivityIndicatorView only.
+    pub(super) activity_indicator: Option<Box<ActivityIndicatorState>>,
+    /// For UIProgressView only.
+    pub(super) progress_view: Option<Box<ProgressViewState>>,
 }
 impl HostObject for UIViewHostObject {}
 
@@ -50,7 +115,8 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 + (id)allocWithZone:(MutVoidPtr)_zone {
     let layer_class: Class = msg![env; this layerClass];
-    let layer: id = msg![env; layer_class layer];
+    let layer: id = msg![env; layer_class alloc];
+    let layer: id = msg![env; layer init];
 
     let host_object = Box::new(UIViewHostObject {
         bounds: CGRect {
@@ -58,7 +124,26 @@ pub const CLASSES: ClassExports = objc_classes! {
             size: CGSize { width: 0.0, height: 0.0 }
         },
         center: CGPoint { x: 0.0, y: 0.0 },
+        transform: CGAffineTransformIdentity,
+        alpha: 1.0,
+        background_color: nil,
         layer,
+        multiple_touch_enabled: false,
+        hidden: false,
+        user_interaction_enabled: true,
+        superview: nil,
+        subviews: Vec::new(),
+        view_controller: nil,
+        scroll_view: None,
+        table_view: None,
+        table_view_cell: None,
+        alert: None,
+        text_input: None,
+        web_view: None,
+        control: None,
+        tab_bar: None,
+        activity_indicator: None,
+        progress_view: None,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
@@ -67,7 +152,99 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.get_known_class("CALayer", &mut env.mem)
 }
 
-// TODO: initWithFrame:, accessors, etc
++ (())beginAnimations:(id)animation_id // NSString*
+              context:(MutVoidPtr)context {
+    animation::begin(env, animation_id, context);
+}
++ (())commitAnimations {
+    animation::commit(env);
+}
++ (bool)areAnimationsEnabled {
+    env.framework_state.uikit.ui_view.animation.enabled
+}
++ (())setAnimationsEnabled:(bool)enabled {
+    env.framework_state.uikit.ui_view.animation.enabled = enabled;
+}
++ (())setAnimationDuration:(NSTimeInterval)duration {
+    if let Some(animation) = animation::pending(env) {
+        animation.duration = duration;
+    }
+}
++ (())setAnimationDelay:(NSTimeInterval)delay {
+    if let Some(animation) = animation::pending(env) {
+        animation.delay = delay;
+    }
+}
++ (())setAnimationStartDate:(id)date { // NSDate*
+    let delay: NSTimeInterval = msg![env; date timeIntervalSinceNow];
+    if let Some(animation) = animation::pending(env) {
+        animation.delay = delay;
+    }
+}
++ (())setAnimationCurve:(UIViewAnimationCurve)curve {
+    if let Some(animation) = animation::pending(env) {
+        animation.curve = curve;
+    }
+}
++ (())setAnimationRepeatCount:(f32)count {
+    if let Some(animation) = animation::pending(env) {
+        animation.repeat_count = count;
+    }
+}
++ (())setAnimationRepeatAutoreverses:(bool)autoreverses {
+    if let Some(animation) = animation::pending(env) {
+        animation.repeat_autoreverses = autoreverses;
+    }
+}
++ (())setAnimationBeginsFromCurrentState:(bool)from_current_state {
+    if let Some(animation) = animation::pending(env) {
+        animation.begins_from_current_state = from_current_state;
+    }
+}
++ (())setAnimationDelegate:(id)delegate {
+    animation::set_delegate(env, delegate);
+}
++ (())setAnimationWillStartSelector:(SEL)selector {
+    if let Some(animation) = animation::pending(env) {
+        animation.will_start_selector = (!selector.is_null()).then_some(selector);
+    }
+}
++ (())setAnimationDidStopSelector:(SEL)selector {
+    if let Some(animation) = animation::pending(env) {
+        animation.did_stop_selector = (!selector.is_null()).then_some(selector);
+    }
+}
++ (())setAnimationTransition:(UIViewAnimationTransition)transition
+                     forView:(id)view // UIView*
+                       cache:(bool)_cache {
+    if transition != UIViewAnimationTransitionNone {
+        // TODO: flip or curl the view.
+        log_dbg!("TODO: transition {} for {:?} isn't drawn", transition, view);
+    }
+}
+
+- (id)init {
+    let frame = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: CGSize { width: 0.0, height: 0.0 },
+    };
+    msg![env; this initWithFrame:frame]
+}
+
+- (id)initWithFrame:(CGRect)frame {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    () = msg![env; layer setDelegate:this];
+
+    () = msg![env; this setFrame:frame];
+
+    log_dbg!("[(UIView*){:?} initWithFrame:{:?}]", this, frame);
+
+    env.framework_state.uikit.ui_view.views.push(this);
+
+    set_needs_display_if_drawn(env, this);
+
+    this
+}
 
 // NSCoding implementation
 - (id)initWithCoder:(id)coder {
@@ -98,15 +275,58 @@ pub const CLASSES: ClassExports = objc_classes! {
 
     let layer = host_object.layer;
     () = msg![env; layer setDelegate:this];
+    sync_layer(env, this);
 
     env.framework_state.uikit.ui_view.views.push(this);
 
+    set_needs_display_if_drawn(env, this);
+
     this
 }
 
 - (())dealloc {
-    let &mut UIViewHostObject { layer, .. } = env.objc.borrow_mut(this);
+    let host_object = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let layer = host_object.layer;
+    let background_color = host_object.background_color;
+    let subviews = std::mem::take(&mut host_object.subviews);
+    let table_view = host_object.table_view.take();
+    let table_view_cell = host_object.table_view_cell.take();
+    let text_input = host_object.text_input.take();
+    let web_view = host_object.web_view.take();
+    let control = host_object.control.take();
+    let tab_bar = host_object.tab_bar.take();
+    () = msg![env; layer setDelegate:nil];
     release(env, layer);
+    release(env, background_color);
+    for subview in subviews {
+        env.objc.borrow_mut::<UIViewHostObject>(subview).superview = nil;
+        release(env, subview);
+    }
+    // Subclasses can't do a super-call yet (see below), so their state is
+    // cleaned up here.
+    if let Some(state) = table_view {
+        ui_table_view::release_state(env, *state);
+    }
+    if let Some(state) = table_view_cell {
+        ui_table_view_cell::release_state(env, *state);
+    }
+    if let Some(state) = text_input {
+        ui_text_field::release_state(env, *state);
+    }
+    if let Some(state) = web_view {
+        ui_web_view::release_state(env, this, *state);
+    }
+    if let Some(state) = control {
+        ui_control::release_state(env, *state);
+    }
+    if let Some(state) = tab_bar {
+        ui_tab_bar::release_state(env, *state);
+    }
+    // MapKit's views keep their state in MapKit.
+    mk_map_view::release_state(env, this);
+    mk_annotation_view::release_state(env, this);
+    ui_responder::resign_first_responder(env, this);
+    ui_window::resign_key_window(env, this);
 
     env.framework_state.uikit.ui_view.views.swap_remove(
         env.framework_state.uikit.ui_view.views.iter().position(|&v| v == this).unwrap()
@@ -116,10 +336,515 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.dealloc_object(this, &mut env.mem);
 }
 
+- (CGRect)bounds {
+    env.objc.borrow::<UIViewHostObject>(this).bounds
+}
+- (())setBounds:(CGRect)bounds {
+    env.objc.borrow_mut::<UIViewHostObject>(this).bounds = bounds;
+    sync_layer(env, this);
+    () = msg![env; this layoutSubviews];
+}
+- (CGPoint)center {
+    env.objc.borrow::<UIViewHostObject>(this).center
+}
+- (())setCenter:(CGPoint)center {
+    env.objc.borrow_mut::<UIViewHostObject>(this).center = center;
+    sync_layer(env, this);
+}
+- (CGRect)frame {
+    let &UIViewHostObject { bounds, center, transform, .. } = env.objc.borrow(this);
+    let size = bounds.size;
+    let frame = transform.apply_to_rect(CGRect {
+        origin: CGPoint { x: -size.width / 2.0, y: -size.height / 2.0 },
+        size,
+    });
+    CGRect {
+        origin: CGPoint {
+            x: center.x + frame.origin.x,
+            y: center.y + frame.origin.y,
+        },
+        size: frame.size,
+    }
+}
+- (())setFrame:(CGRect)frame {
+    let center = CGPoint {
+        x: frame.origin.x + frame.size.width / 2.0,
+        y: frame.origin.y + frame.size.height / 2.0,
+    };
+    let host_object = env.objc.borrow_mut::<UIViewHostObject>(this);
+    host_object.center = center;
+    if host_object.transform.is_identity() {
+        host_object.bounds.size = frame.size;
+    } else {
+        // The frame is undefined in this case, according to Apple, but
+        // scaling the bounds would be the most helpful thing to do.
+        log!("TODO: [{:?} setFrame:{:?}] with a non-identity transform, only moving the view", this, frame);
+    }
+    sync_layer(env, this);
+    () = msg![env; this layoutSubviews];
+}
+
+- (CGAffineTransform)transform {
+    env.objc.borrow::<UIViewHostObject>(this).transform
+}
+- (())setTransform:(CGAffineTransform)transform {
+    env.objc.borrow_mut::<UIViewHostObject>(this).transform = transform;
+    sync_layer(env, this);
+}
+
+- (CGFloat)alpha {
+    env.objc.borrow::<UIViewHostObject>(this).alpha
+}
+- (())setAlpha:(CGFloat)alpha {
+    env.objc.borrow_mut::<UIViewHostObject>(this).alpha = alpha;
+    sync_layer(env, this);
+}
+
+- (id)backgroundColor {
+    env.objc.borrow::<UIViewHostObject>(this).background_color
+}
+- (())setBackgroundColor:(id)color { // UIColor*
+    retain(env, color);
+    let old = std::mem::replace(&mut env.objc.borrow_mut::<UIViewHostObject>(this).background_color, color);
+    release(env, old);
+    let cg_color: id = if color == nil { nil } else { msg![env; color CGColor] };
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    () = msg![env; layer setBackgroundColor:cg_color];
+}
+
+- (bool)clipsToBounds {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer masksToBounds]
+}
+- (())setClipsToBounds:(bool)clips {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    () = msg![env; layer setMasksToBounds:clips];
+}
+
+- (bool)isOpaque {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer isOpaque]
+}
+- (())setOpaque:(bool)opaque {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    () = msg![env; layer setOpaque:opaque];
+}
+
+- (CGPoint)convertPoint:(CGPoint)point
+                 toView:(id)view { // UIView*
+    let point = point_to_screen(env, this, point);
+    if view == nil {
+        point
+    } else {
+        point_from_screen(env, view, point)
+    }
+}
+- (CGPoint)convertPoint:(CGPoint)point
+               fromView:(id)view { // UIView*
+    let point = if view == nil {
+        point
+    } else {
+        point_to_screen(env, view, point)
+    };
+    point_from_screen(env, this, point)
+}
+- (CGRect)convertRect:(CGRect)rect
+               toView:(id)view { // UIView*
+    bounding_rect(rect, |corner| msg![env; this convertPoint:corner toView:view])
+}
+- (CGRect)convertRect:(CGRect)rect
+             fromView:(id)view { // UIView*
+    bounding_rect(rect, |corner| msg![env; this convertPoint:corner fromView:view])
+}
+
+- (id)superview {
+    env.objc.borrow::<UIViewHostObject>(this).superview
+}
+- (id)subviews {
+    let subviews = env.objc.borrow::<UIViewHostObject>(this).subviews.clone();
+    for &subview in &subviews {
+        retain(env, subview);
+    }
+    let subviews = ns_array::from_vec(env, subviews);
+    autorelease(env, subviews)
+}
+- (())addSubview:(id)view { // UIView*
+    if view == nil {
+        return;
+    }
+    retain(env, view);
+    let old_superview = env.objc.borrow::<UIViewHostObject>(view).superview;
+    if old_superview != nil {
+        () = msg![env; view removeFromSuperview];
+    }
+    env.objc.borrow_mut::<UIViewHostObject>(view).superview = this;
+    env.objc.borrow_mut::<UIViewHostObject>(this).subviews.push(view);
+    place_sublayer(env, this, view);
+    () = msg![env; view didMoveToSuperview];
+}
+- (())insertSubview:(id)view // UIView*
+            atIndex:(NSInteger)index {
+    if view == nil {
+        return;
+    }
+    () = msg![env; this addSubview:view];
+    let subviews = &mut env.objc.borrow_mut::<UIViewHostObject>(this).subviews;
+    let view = subviews.pop().unwrap();
+    let index = (index.max(0) as usize).min(subviews.len());
+    subviews.insert(index, view);
+    place_sublayer(env, this, view);
+}
+- (())insertSubview:(id)view // UIView*
+       belowSubview:(id)sibling { // UIView*
+    if view == nil {
+        return;
+    }
+    () = msg![env; this addSubview:view];
+    let subviews = &mut env.objc.borrow_mut::<UIViewHostObject>(this).subviews;
+    let view = subviews.pop().unwrap();
+    let index = subviews
+        .iter()
+        .position(|&subview| subview == sibling)
+        .unwrap_or(subviews.len());
+    subviews.insert(index, view);
+    place_sublayer(env, this, view);
+}
+- (())bringSubviewToFront:(id)view { // UIView*
+    let subviews = &mut env.objc.borrow_mut::<UIViewHostObject>(this).subviews;
+    if let Some(index) = subviews.iter().position(|&subview| subview == view) {
+        let view = subviews.remove(index);
+        subviews.push(view);
+        place_sublayer(env, this, view);
+    }
+}
+- (())removeFromSuperview {
+    let superview = env.objc.borrow::<UIViewHostObject>(this).superview;
+    if superview == nil {
+        return;
+    }
+    env.objc.borrow_mut::<UIViewHostObject>(this).superview = nil;
+    let subviews = &mut env.objc.borrow_mut::<UIViewHostObject>(superview).subviews;
+    subviews.retain(|&subview| subview != this);
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    () = msg![env; layer removeFromSuperlayer];
+    () = msg![env; this didMoveToSuperview];
+    release(env, this);
+}
+- (())didMoveToSuperview {
+    // For subclasses to override.
+}
+
+// TODO: actually lay out views. For now, only subclasses that manage their own
+// subviews, like UITableView, do anything here.
+- (())layoutSubviews {
+    // For subclasses to override.
+}
+- (())setNeedsLayout {
+    // Layout is always done immediately, see layoutIfNeeded.
+}
+- (())layoutIfNeeded {
+    () = msg![env; this layoutSubviews];
+}
+
 - (id)layer {
     env.objc.borrow_mut::<UIViewHostObject>(this).layer
 }
 
+- (())setNeedsDisplay {
+    set_needs_display_if_drawn(env, this);
+}
+- (())setNeedsDisplayInRect:(CGRect)_rect {
+    // TODO: partial redraws
+    set_needs_display_if_drawn(env, this);
+}
+- (())drawRect:(CGRect)_rect {
+    // For subclasses to override.
+}
+
+// CALayerDelegate implementation
+- (())drawLayer:(id)_layer // CALayer*
+      inContext:(CGContextRef)context {
+    let bounds = env.objc.borrow::<UIViewHostObject>(this).bounds;
+    ui_graphics::UIGraphicsPushContext(env, context);
+    () = msg![env; this drawRect:bounds];
+    ui_graphics::UIGraphicsPopContext(env);
+}
+- (id)actionForLayer:(id)layer // CALayer*
+              forKey:(id)key { // NSString*
+    animation::action_for_key(env, layer, key)
+}
+
+- (bool)isHidden {
+    env.objc.borrow::<UIViewHostObject>(this).hidden
+}
+- (())setHidden:(bool)hidden {
+    env.objc.borrow_mut::<UIViewHostObject>(this).hidden = hidden;
+    sync_layer(env, this);
+}
+
+- (bool)isUserInteractionEnabled {
+    env.objc.borrow::<UIViewHostObject>(this).user_interaction_enabled
+}
+- (())setUserInteractionEnabled:(bool)enabled {
+    env.objc.borrow_mut::<UIViewHostObject>(this).user_interaction_enabled = enabled;
+}
+
+- (bool)isMultipleTouchEnabled {
+    env.objc.borrow::<UIViewHostObject>(this).multiple_touch_enabled
+}
+- (())setMultipleTouchEnabled:(bool)enabled {
+    env.objc.borrow_mut::<UIViewHostObject>(this).multiple_touch_enabled = enabled;
+}
+
+- (id)nextResponder {
+    let &UIViewHostObject {
+        view_controller,
+        superview,
+        ..
+    } = env.objc.borrow(this);
+    if view_controller != nil {
+        view_controller
+    } else {
+        superview
+    }
+}
+
+- (id)hitTest:(CGPoint)point
+    withEvent:(id)event { // UIEvent*
+    let &UIViewHostObject {
+        hidden,
+        user_interaction_enabled,
+        alpha,
+        ..
+    } = env.objc.borrow(this);
+    if hidden || !user_interaction_enabled || alpha < 0.01 {
+        return nil;
+    }
+    if !msg![env; this pointInside:point withEvent:event] {
+        return nil;
+    }
+    let subviews = env.objc.borrow::<UIViewHostObject>(this).subviews.clone();
+    for subview in subviews.into_iter().rev() {
+        let subview_point = point_from_superview(geometry(env, subview, false), point);
+        let hit: id = msg![env; subview hitTest:subview_point withEvent:event];
+        if hit != nil {
+            return hit;
+        }
+    }
+    this
+}
+- (bool)pointInside:(CGPoint)point
+          withEvent:(id)_event { // UIEvent*
+    let CGRect { origin, size } = env.objc.borrow::<UIViewHostObject>(this).bounds;
+    point.x >= origin.x
+        && point.y >= origin.y
+        && point.x < origin.x + size.width
+        && point.y < origin.y + size.height
+}
+
 @end
 
 };
+
+/// Copy a view's geometry, opacity and visibility to its layer.
+fn sync_layer(env: &mut Environment, view: id) {
+    let &UIViewHostObject {
+        bounds,
+        center,
+        transform,
+        alpha,
+        hidden,
+        layer,
+        ..
+    } = env.objc.borrow(view);
+    () = msg![env; layer setBounds:bounds];
+    () = msg![env; layer setPosition:center];
+    () = msg![env; layer setAffineTransform:transform];
+    () = msg![env; layer setOpacity:alpha];
+    () = msg![env; layer setHidden:hidden];
+}
+
+/// Put a subview's layer in the right place among its superview's sublayers:
+/// just below the layer of the next subview, if there is one.
+fn place_sublayer(env: &mut Environment, superview: id, view: id) {
+    let host_object = env.objc.borrow::<UIViewHostObject>(superview);
+    let superlayer = host_object.layer;
+    let index = host_object
+        .subviews
+        .iter()
+        .position(|&subview| subview == view)
+        .unwrap();
+    let next = host_object.subviews.get(index + 1).copied();
+    let layer = env.objc.borrow::<UIViewHostObject>(view).layer;
+    if let Some(next) = next {
+        let next_layer = env.objc.borrow::<UIViewHostObject>(next).layer;
+        () = msg![env; superlayer insertSublayer:layer below:next_layer];
+    } else {
+        () = msg![env; superlayer addSublayer:layer];
+    }
+}
+
+/// Views are only drawn by their layer if they override `drawRect:`, like in
+/// UIKit. Other views only have a background color and subviews.
+fn set_needs_display_if_drawn(env: &mut Environment, view: id) {
+    let class = msg![env; view class];
+    let base_class = env.objc.get_known_class("UIView", &mut env.mem);
+    let sel = env.objc.lookup_selector("drawRect:").unwrap();
+    if env.objc.class_overrides_method(class, base_class, sel) {
+        let layer = env.objc.borrow::<UIViewHostObject>(view).layer;
+        () = msg![env; layer setNeedsDisplay];
+    }
+}
+
+/// The properties that determine where a view is within its superview.
+#[derive(Copy, Clone)]
+struct Geometry {
+    bounds: CGRect,
+    center: CGPoint,
+    transform: CGAffineTransform,
+}
+
+/// Get a view's geometry as the app has set it, or if `presentation` is
+/// [true], as it currently appears while being animated.
+fn geometry(env: &mut Environment, view: id, presentation: bool) -> Geometry {
+    let &UIViewHostObject {
+        bounds,
+        center,
+        transform,
+        ..
+    } = env.objc.borrow(view);
+    if !presentation {
+        return Geometry {
+            bounds,
+            center,
+            transform,
+        };
+    }
+    let layer = env.objc.borrow::<UIViewHostObject>(view).layer;
+    let presentation::Properties {
+        bounds,
+        position,
+        transform,
+        ..
+    } = presentation::presentation(env, layer);
+    Geometry {
+        bounds,
+        center: position,
+        transform: transform.to_affine(),
+    }
+}
+
+/// Convert a point in a view's co-ordinate space to its superview's.
+fn point_to_superview(geometry: Geometry, point: CGPoint) -> CGPoint {
+    let Geometry {
+        bounds,
+        center,
+        transform,
+    } = geometry;
+    let relative_to_center = CGPoint {
+        x: point.x - bounds.origin.x - bounds.size.width / 2.0,
+        y: point.y - bounds.origin.y - bounds.size.height / 2.0,
+    };
+    let transformed = transform.apply_to_point(relative_to_center);
+    CGPoint {
+        x: center.x + transformed.x,
+        y: center.y + transformed.y,
+    }
+}
+
+/// Convert a point in a view's superview's co-ordinate space to the view's.
+fn point_from_superview(geometry: Geometry, point: CGPoint) -> CGPoint {
+    let Geometry {
+        bounds,
+        center,
+        transform,
+    } = geometry;
+    let relative_to_center = transform.invert().apply_to_point(CGPoint {
+        x: point.x - center.x,
+        y: point.y - center.y,
+    });
+    CGPoint {
+        x: relative_to_center.x + bounds.origin.x + bounds.size.width / 2.0,
+        y: relative_to_center.y + bounds.origin.y + bounds.size.height / 2.0,
+    }
+}
+
+/// Convert a point in a view's co-ordinate space to screen co-ordinates, by
+/// walking up the view hierarchy. If the view isn't in a window, the
+/// co-ordinate space of the outermost view's superview is used instead.
+pub(super) fn point_to_screen(env: &mut Environment, view: id, point: CGPoint) -> CGPoint {
+    point_to_screen_inner(env, view, point, false)
+}
+
+fn point_to_screen_inner(
+    env: &mut Environment,
+    view: id,
+    point: CGPoint,
+    presentation: bool,
+) -> CGPoint {
+    let mut point = point;
+    let mut view = view;
+    while view != nil {
+        point = point_to_superview(geometry(env, view, presentation), point);
+        view = env.objc.borrow::<UIViewHostObject>(view).superview;
+    }
+    point
+}
+
+/// Inverse of [point_to_screen].
+pub(super) fn point_from_screen(env: &mut Environment, view: id, point: CGPoint) -> CGPoint {
+    let mut ancestors = Vec::new();
+    let mut ancestor = view;
+    while ancestor != nil {
+        ancestors.push(ancestor);
+        ancestor = env.objc.borrow::<UIViewHostObject>(ancestor).superview;
+    }
+    let mut point = point;
+    for &ancestor in ancestors.iter().rev() {
+        point = point_from_superview(geometry(env, ancestor, false), point);
+    }
+    point
+}
+
+/// Get every view that currently exists, in no particular order.
+pub(crate) fn all_views(env: &mut Environment) -> Vec<id> {
+    env.framework_state.uikit.ui_view.views.clone()
+}
+
+/// Get a view's frame in screen co-ordinates, by walking up the view
+/// hierarchy. Returns [None] if the view isn't in a window. If the view or
+/// its superviews are rotated, this is the smallest rectangle containing it.
+/// This is where the view currently appears, which is different from where the
+/// app has put it while it's being animated.
+pub(crate) fn frame_on_screen(env: &mut Environment, view: id) -> Option<CGRect> {
+    window_for_view(env, view)?;
+    let bounds = geometry(env, view, true).bounds;
+    Some(bounding_rect(bounds, |corner| {
+        point_to_screen_inner(env, view, corner, true)
+    }))
+}
+
+/// Find the window a view is in, by walking up the view hierarchy. Returns
+/// [None] if the view isn't in a window. A window is in itself.
+pub(super) fn window_for_view(env: &mut Environment, view: id) -> Option<id> {
+    let mut root = view;
+    loop {
+        let superview = env.objc.borrow::<UIViewHostObject>(root).superview;
+        if superview == nil {
+            break;
+        }
+        root = superview;
+    }
+    let ui_window_class = env.objc.get_known_class("UIWindow", &mut env.mem);
+    if msg![env; root isKindOfClass:ui_window_class] {
+        Some(root)
+    } else {
+        None
+    }
+}
+
+/// For use by [super::handle_events]: send delegate messages for animation
+/// blocks that have started or stopped.
+pub(super) fn handle_animations(env: &mut Environment) {
+    animation::handle_animations(env);
+}
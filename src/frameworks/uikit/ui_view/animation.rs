@@ -0,0 +1,309 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIView`'s animation blocks (`beginAnimations:context:` and so on).
+//!
+//! As in UIKit, these are lowered to Core Animation: a change to an animatable
+//! property inside an animation block asks the view (the layer's delegate)
+//! for an action, and the view answers with a `CABasicAnimation` using the
+//! block's settings (see [action_for_key]). The delegate's will-start and
+//! did-stop selectors are sent separately, at the times the block's
+//! animations start and stop.
+
+use crate::frameworks::core_animation::ca_animation::kCAFillModeBackwards;
+use crate::frameworks::core_animation::ca_base::CACurrentMediaTime;
+use crate::frameworks::core_animation::ca_media_timing_function::{
+    self, kCAMediaTimingFunctionEaseIn, kCAMediaTimingFunctionEaseInEaseOut,
+    kCAMediaTimingFunctionEaseOut, kCAMediaTimingFunctionLinear, ControlPoints,
+};
+use crate::frameworks::core_animation::presentation;
+use crate::frameworks::foundation::ns_string::{get_static_str, to_rust_string};
+use crate::frameworks::foundation::{NSInteger, NSTimeInterval};
+use crate::mem::MutVoidPtr;
+use crate::objc::{id, msg, msg_class, msg_send, nil, release, retain, SEL};
+use crate::Environment;
+use std::time::{Duration, Instant};
+
+pub type UIViewAnimationCurve = NSInteger;
+pub const UIViewAnimationCurveEaseInOut: UIViewAnimationCurve = 0;
+pub const UIViewAnimationCurveEaseIn: UIViewAnimationCurve = 1;
+pub const UIViewAnimationCurveEaseOut: UIViewAnimationCurve = 2;
+pub const UIViewAnimationCurveLinear: UIViewAnimationCurve = 3;
+
+pub type UIViewAnimationTransition = NSInteger;
+pub const UIViewAnimationTransitionNone: UIViewAnimationTransition = 0;
+
+pub(super) struct State {
+    /// Animation blocks that have begun but haven't been committed yet,
+    /// innermost last.
+    pending: Vec<Animation>,
+    /// Committed animations that haven't stopped yet.
+    running: Vec<Animation>,
+    pub(super) enabled: bool,
+}
+impl Default for State {
+    fn default() -> Self {
+        State {
+            pending: Vec::new(),
+            running: Vec::new(),
+            enabled: true,
+        }
+    }
+}
+
+/// An animation block's settings.
+pub(super) struct Animation {
+    /// `NSString*`, strong reference.
+    animation_id: id,
+    context: MutVoidPtr,
+    pub(super) duration: NSTimeInterval,
+    pub(super) delay: NSTimeInterval,
+    pub(super) curve: UIViewAnimationCurve,
+    pub(super) repeat_count: f32,
+    pub(super) repeat_autoreverses: bool,
+    pub(super) begins_from_current_state: bool,
+    /// Strong reference, released when the animation stops.
+    delegate: id,
+    pub(super) will_start_selector: Option<SEL>,
+    pub(super) did_stop_selector: Option<SEL>,
+    /// When the animation starts, after the delay. Set when committed.
+    start: Option<Instant>,
+    /// Whether the will-start selector has been sent.
+    started: bool,
+}
+
+impl Animation {
+    /// The time taken by all the repeats.
+    fn total_duration(&self) -> NSTimeInterval {
+        let cycles = if self.repeat_count > 0.0 {
+            self.repeat_count as NSTimeInterval
+        } else {
+            1.0
+        };
+        let cycles = if self.repeat_autoreverses {
+            cycles * 2.0
+        } else {
+            cycles
+        };
+        self.duration * cycles
+    }
+
+    /// Whether the animation has finished at `now`.
+    fn finished(&self, now: Instant) -> bool {
+        let start = self.start.unwrap();
+        now >= start
+            && (self.duration <= 0.0
+                || now.duration_since(start).as_secs_f64() >= self.total_duration())
+    }
+}
+
+/// Get the control points of the timing function equivalent to an animation
+/// curve.
+fn control_points(curve: UIViewAnimationCurve) -> ControlPoints {
+    let name = match curve {
+        UIViewAnimationCurveEaseIn => kCAMediaTimingFunctionEaseIn,
+        UIViewAnimationCurveEaseOut => kCAMediaTimingFunctionEaseOut,
+        UIViewAnimationCurveLinear => kCAMediaTimingFunctionLinear,
+        _ => kCAMediaTimingFunctionEaseInEaseOut,
+    };
+    ca_media_timing_function::control_points_for_name(name).unwrap()
+}
+
+fn state(env: &mut Environment) -> &mut State {
+    &mut env.framework_state.uikit.ui_view.animation
+}
+
+/// Implementation of `beginAnimations:context:`.
+pub(super) fn begin(env: &mut Environment, animation_id: id, context: MutVoidPtr) {
+    let animation_id = if animation_id == nil {
+        nil
+    } else {
+        msg![env; animation_id copy]
+    };
+    state(env).pending.push(Animation {
+        animation_id,
+        context,
+        duration: 0.2,
+        delay: 0.0,
+        curve: UIViewAnimationCurveEaseInOut,
+        repeat_count: 0.0,
+        repeat_autoreverses: false,
+        begins_from_current_state: false,
+        delegate: nil,
+        will_start_selector: None,
+        did_stop_selector: None,
+        start: None,
+        started: false,
+    });
+}
+
+/// The innermost animation block that hasn't been committed yet, if any. The
+/// `setAnimation...` methods change its settings.
+pub(super) fn pending(env: &mut Environment) -> Option<&mut Animation> {
+    state(env).pending.last_mut()
+}
+
+/// Implementation of `setAnimationDelegate:`.
+pub(super) fn set_delegate(env: &mut Environment, delegate: id) {
+    let Some(animation) = pending(env) else {
+        return;
+    };
+    let old = std::mem::replace(&mut animation.delegate, delegate);
+    retain(env, delegate);
+    release(env, old);
+}
+
+/// Implementation of `commitAnimations`.
+pub(super) fn commit(env: &mut Environment) {
+    let Some(mut animation) = state(env).pending.pop() else {
+        log!("Warning: commitAnimations without beginAnimations:context:, ignoring");
+        return;
+    };
+    if !state(env).enabled {
+        animation.duration = 0.0;
+    }
+    log_dbg!(
+        "Committing animation {:?}: duration {}, delay {}, curve {}",
+        animation.animation_id,
+        animation.duration,
+        animation.delay,
+        animation.curve,
+    );
+    animation.start = Some(Instant::now() + Duration::from_secs_f64(animation.delay.max(0.0)));
+    state(env).running.push(animation);
+}
+
+/// For use by `UIView`'s `actionForLayer:forKey:`: get the animation for a
+/// change to one of the layer's properties, if it's inside an animation block.
+/// Otherwise, the change isn't animated, so the result is `NSNull`.
+pub(super) fn action_for_key(env: &mut Environment, layer: id, key: id) -> id {
+    let null: id = msg_class![env; NSNull null];
+    if !state(env).enabled {
+        return null;
+    }
+    let Some(&mut Animation {
+        duration,
+        delay,
+        curve,
+        repeat_count,
+        repeat_autoreverses,
+        begins_from_current_state,
+        ..
+    }) = pending(env)
+    else {
+        return null;
+    };
+    if duration <= 0.0 {
+        return null;
+    }
+
+    let animation: id = msg_class![env; CABasicAnimation animationWithKeyPath:key];
+    () = msg![env; animation setDuration:duration];
+    if delay > 0.0 {
+        let begin_time = CACurrentMediaTime(env) + delay;
+        () = msg![env; animation setBeginTime:begin_time];
+        // The old value should stay until the animation begins.
+        let fill_mode = get_static_str(env, kCAFillModeBackwards);
+        () = msg![env; animation setFillMode:fill_mode];
+    }
+    let function = ca_media_timing_function::from_control_points(env, control_points(curve));
+    () = msg![env; animation setTimingFunction:function];
+    () = msg![env; animation setRepeatCount:repeat_count];
+    () = msg![env; animation setAutoreverses:repeat_autoreverses];
+    // Otherwise, the layer fills in the value currently presented.
+    if !begins_from_current_state {
+        let key = to_rust_string(env, key);
+        let model = presentation::model(env, layer);
+        if let Some(value) = presentation::get(&model, &key) {
+            let from_value = value.to_object(env);
+            () = msg![env; animation setFromValue:from_value];
+        }
+    }
+    animation
+}
+
+/// For use by [super::super::handle_events]: send the delegate messages for
+/// animations that have started or stopped.
+pub(super) fn handle_animations(env: &mut Environment) {
+    if state(env).running.is_empty() {
+        return;
+    }
+    let now = Instant::now();
+
+    let mut starting = Vec::new();
+    for animation in state(env).running.iter_mut() {
+        if !animation.started && now >= animation.start.unwrap() {
+            animation.started = true;
+            if let Some(selector) = animation.will_start_selector {
+                starting.push((
+                    animation.delegate,
+                    selector,
+                    animation.animation_id,
+                    animation.context,
+                ));
+            }
+        }
+    }
+    // The delegate might begin new animations, so the state can't be borrowed
+    // while sending these.
+    for (delegate, selector, animation_id, context) in starting {
+        if delegate != nil {
+            let () = msg_send(env, (delegate, selector, animation_id, context));
+        }
+    }
+
+    let (stopped, running) = std::mem::take(&mut state(env).running)
+        .into_iter()
+        .partition::<Vec<_>, _>(|animation| animation.finished(now));
+    state(env).running.extend(running);
+    for animation in stopped {
+        stop(env, animation);
+    }
+}
+
+fn stop(env: &mut Environment, animation: Animation) {
+    let Animation {
+        animation_id,
+        context,
+        delegate,
+        did_stop_selector,
+        ..
+    } = animation;
+    if let (Some(selector), true) = (did_stop_selector, delegate != nil) {
+        let finished: id = msg_class![env; NSNumber numberWithBool:true];
+        let () = msg_send(env, (delegate, selector, animation_id, finished, context));
+    }
+    release(env, delegate);
+    release(env, animation_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves() {
+        let apply_curve = |curve, t| ca_media_timing_function::evaluate(control_points(curve), t);
+        for curve in [
+            UIViewAnimationCurveEaseInOut,
+            UIViewAnimationCurveEaseIn,
+            UIViewAnimationCurveEaseOut,
+            UIViewAnimationCurveLinear,
+        ] {
+            assert!(apply_curve(curve, 0.0).abs() < 0.001);
+            assert!((apply_curve(curve, 1.0) - 1.0).abs() < 0.001);
+            let mut last = 0.0;
+            for i in 1..=10 {
+                let value = apply_curve(curve, i as f32 / 10.0);
+                assert!(value >= last);
+                last = value;
+            }
+        }
+        assert!((apply_curve(UIViewAnimationCurveEaseInOut, 0.5) - 0.5).abs() < 0.001);
+        assert!(apply_curve(UIViewAnimationCurveEaseIn, 0.5) < 0.5);
+        assert!(apply_curve(UIViewAnimationCurveEaseOut, 0.5) > 0.5);
+        assert_eq!(apply_curve(UIViewAnimationCurveLinear, 0.25), 0.25);
+    }
+}
@@ -0,0 +1,738 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIViewController` and `UINavigationController`.
+//!
+//! Only the basics are implemented so far: owning a view and the callbacks for
+//! it appearing, disappearing and being unloaded after a memory warning,
+//! presenting another view controller modally, being a tab of a
+//! `UITabBarController`, and autorotation.
+//!
+//! Presenting a view controller modally puts its view in the window and takes
+//! the presenting view controller's view out, like UIKit does. Views aren't
+//! composited, so this doesn't show anything by itself, except for the view
+//! controllers the host draws, like `UIImagePickerController` and
+//! `MFMailComposeViewController`.
+
+use super::ui_device::{
+    orientation_from_window, UIDeviceOrientation, UIDeviceOrientationLandscapeLeft,
+    UIDeviceOrientationLandscapeRight, UIDeviceOrientationPortrait,
+    UIDeviceOrientationPortraitUpsideDown,
+};
+use super::ui_tab_bar_controller::TabBarControllerState;
+use super::ui_view::{window_for_view, UIViewHostObject};
+use super::{ui_image_picker_controller, ui_tab_bar_controller};
+use crate::frameworks::core_graphics::cg_affine_transform::{
+    CGAffineTransform, CGAffineTransformMakeRotation,
+};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::{NSInteger, NSTimeInterval};
+use crate::frameworks::message_ui::mf_mail_compose_view_controller;
+use crate::mem::MutVoidPtr;
+use crate::objc::{
+    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
+use crate::Environment;
+use std::f32::consts::{FRAC_PI_2, PI};
+
+type UIInterfaceOrientation = UIDeviceOrientation;
+// The interface orientation names are the other way round from the device
+// orientation names: when the device is rotated left, the interface is rotated
+// right to stay upright.
+const UIInterfaceOrientationPortrait: UIInterfaceOrientation = UIDeviceOrientationPortrait;
+const UIInterfaceOrientationPortraitUpsideDown: UIInterfaceOrientation =
+    UIDeviceOrientationPortraitUpsideDown;
+const UIInterfaceOrientationLandscapeLeft: UIInterfaceOrientation =
+    UIDeviceOrientationLandscapeRight;
+const UIInterfaceOrientationLandscapeRight: UIInterfaceOrientation =
+    UIDeviceOrientationLandscapeLeft;
+
+/// How long the rotation animation would take, as reported to the callbacks.
+const ROTATION_DURATION: NSTimeInterval = 0.3;
+
+pub type UIModalTransitionStyle = NSInteger;
+pub const UIModalTransitionStyleCoverVertical: UIModalTransitionStyle = 0;
+pub const UIModalTransitionStyleFlipHorizontal: UIModalTransitionStyle = 1;
+pub const UIModalTransitionStyleCrossDissolve: UIModalTransitionStyle = 2;
+pub const UIModalTransitionStylePartialCurl: UIModalTransitionStyle = 3;
+
+/// How long presenting or dismissing a modal view controller takes.
+const MODAL_TRANSITION_DURATION: NSTimeInterval = 0.3;
+
+#[derive(Default)]
+pub struct State {
+    /// All the view controllers, so they can be told about memory warnings.
+    /// Weak references.
+    view_controllers: Vec<id>,
+}
+
+/// What a view controller presenting a modal view controller took out of the
+/// window, so it can be put back when the modal view controller is dismissed.
+enum CoveredView {
+    /// The view controller's own view, which might be unloaded meanwhile.
+    Own,
+    /// A superview of the view controller's view. Strong reference.
+    Other(id),
+}
+
+pub(crate) struct UIViewControllerHostObject {
+    /// Strong reference.
+    view: id,
+    /// Strong reference.
+    modal_view_controller: id,
+    modal_transition_style: UIModalTransitionStyle,
+    covered_view: Option<CoveredView>,
+    /// Weak reference.
+    pub(super) parent_view_controller: id,
+    /// `NSString*`, strong reference.
+    title: id,
+    /// `UITabBarItem*`, strong reference. Created when first needed.
+    tab_bar_item: id,
+    /// `UINavigationController` and `UITabBarController` only. Weak reference.
+    pub(super) delegate: id,
+    /// `UIImagePickerController` only.
+    pub(super) image_picker: Option<Box<ui_image_picker_controller::PickerState>>,
+    /// `MFMailComposeViewController` only.
+    pub(crate) mail_composer: Option<Box<mf_mail_compose_view_controller::ComposerState>>,
+    /// `UITabBarController` only.
+    pub(super) tab_bar_controller: Option<Box<TabBarControllerState>>,
+}
+impl HostObject for UIViewControllerHostObject {}
+impl UIViewControllerHostObject {
+    pub(crate) fn new() -> UIViewControllerHostObject {
+        UIViewControllerHostObject {
+            view: nil,
+            modal_view_controller: nil,
+            modal_transition_style: UIModalTransitionStyleCoverVertical,
+            covered_view: None,
+            parent_view_controller: nil,
+            title: nil,
+            tab_bar_item: nil,
+            delegate: nil,
+            image_picker: None,
+            mail_composer: None,
+            tab_bar_controller: None,
+        }
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIViewController: UIResponder
+
++ (id)allocWithZone:(MutVoidPtr)_zone {
+    let host_object = Box::new(UIViewControllerHostObject::new());
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    env.framework_state.uikit.ui_view_controller.view_controllers.push(new);
+    new
+}
+
+- (id)init {
+    msg![env; this initWithNibName:nil bundle:nil]
+}
+
+- (id)initWithNibName:(id)_nib_name // NSString*
+               bundle:(id)_bundle { // NSBundle*
+    // TODO: load the view from the nib
+    this
+}
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<UIViewControllerHostObject>(this);
+    if host_object.view != nil {
+        let view = host_object.view;
+        env.objc.borrow_mut::<UIViewHostObject>(view).view_controller = nil;
+    }
+    let host_object = env.objc.borrow_mut::<UIViewControllerHostObject>(this);
+    let objects = [
+        host_object.view,
+        host_object.modal_view_controller,
+        host_object.title,
+        host_object.tab_bar_item,
+    ];
+    let tab_bar_controller = host_object.tab_bar_controller.take();
+    let covered_view = host_object.covered_view.take();
+    if let Some(CoveredView::Other(view)) = covered_view {
+        release(env, view);
+    }
+    for object in objects {
+        if object != nil {
+            release(env, object);
+        }
+    }
+    if let Some(state) = tab_bar_controller {
+        ui_tab_bar_controller::release_state(env, *state);
+    }
+    let view_controllers = &mut env.framework_state.uikit.ui_view_controller.view_controllers;
+    view_controllers.swap_remove(view_controllers.iter().position(|&v| v == this).unwrap());
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (())loadView {
+    let screen: id = msg_class![env; UIScreen mainScreen];
+    let frame: CGRect = msg![env; screen applicationFrame];
+    let view: id = msg_class![env; UIView alloc];
+    let view: id = msg![env; view initWithFrame:frame];
+    () = msg![env; this setView:view];
+    release(env, view);
+}
+
+- (id)view {
+    if env.objc.borrow::<UIViewControllerHostObject>(this).view == nil {
+        () = msg![env; this loadView];
+        () = msg![env; this viewDidLoad];
+    }
+    env.objc.borrow::<UIViewControllerHostObject>(this).view
+}
+- (())setView:(id)view { // UIView*
+    retain(env, view);
+    if view != nil {
+        env.objc.borrow_mut::<UIViewHostObject>(view).view_controller = this;
+    }
+    let host_object = env.objc.borrow_mut::<UIViewControllerHostObject>(this);
+    let old = std::mem::replace(&mut host_object.view, view);
+    if old != nil {
+        let old_host_object = env.objc.borrow_mut::<UIViewHostObject>(old);
+        if old_host_object.view_controller == this {
+            old_host_object.view_controller = nil;
+        }
+        release(env, old);
+    }
+}
+- (bool)isViewLoaded {
+    env.objc.borrow::<UIViewControllerHostObject>(this).view != nil
+}
+- (())viewDidLoad {
+    // Subclasses may override this.
+}
+- (())viewDidUnload {
+    // Subclasses may override this.
+}
+- (())viewWillAppear:(bool)_animated {
+    // Subclasses may override this.
+}
+- (())viewDidAppear:(bool)_animated {
+    // Subclasses may override this.
+}
+- (())viewWillDisappear:(bool)_animated {
+    // Subclasses may override this.
+}
+- (())viewDidDisappear:(bool)_animated {
+    // Subclasses may override this.
+}
+
+- (())didReceiveMemoryWarning {
+    // UIKit releases the view if it's not in use, i.e. it has no superview.
+    // Subclasses are expected to call this and to recreate anything they
+    // dropped in viewDidUnload when viewDidLoad is sent again.
+    let view = env.objc.borrow::<UIViewControllerHostObject>(this).view;
+    if view == nil || env.objc.borrow::<UIViewHostObject>(view).superview != nil {
+        return;
+    }
+    log_dbg!("{:?} unloading its view {:?} after a memory warning", this, view);
+    () = msg![env; this setView:nil];
+    () = msg![env; this viewDidUnload];
+}
+
+- (id)nextResponder {
+    let view = env.objc.borrow::<UIViewControllerHostObject>(this).view;
+    if view == nil {
+        nil
+    } else {
+        env.objc.borrow::<UIViewHostObject>(view).superview
+    }
+}
+
+- (id)title {
+    env.objc.borrow::<UIViewControllerHostObject>(this).title
+}
+- (())setTitle:(id)title { // NSString*
+    let title: id = msg![env; title copy];
+    let old = std::mem::replace(
+        &mut env.objc.borrow_mut::<UIViewControllerHostObject>(this).title,
+        title,
+    );
+    if old != nil {
+        release(env, old);
+    }
+    // UIKit keeps the tab bar item's title in sync.
+    let tab_bar_item = env.objc.borrow::<UIViewControllerHostObject>(this).tab_bar_item;
+    if tab_bar_item != nil {
+        () = msg![env; tab_bar_item setTitle:title];
+    }
+}
+
+- (id)tabBarItem {
+    let tab_bar_item = env.objc.borrow::<UIViewControllerHostObject>(this).tab_bar_item;
+    if tab_bar_item != nil {
+        return tab_bar_item;
+    }
+    let title: id = msg![env; this title];
+    let tab_bar_item: id = msg_class![env; UITabBarItem alloc];
+    let tab_bar_item: id = msg![env; tab_bar_item initWithTitle:title image:nil tag:0];
+    env.objc.borrow_mut::<UIViewControllerHostObject>(this).tab_bar_item = tab_bar_item;
+    tab_bar_item
+}
+- (())setTabBarItem:(id)tab_bar_item { // UITabBarItem*
+    retain(env, tab_bar_item);
+    let old = std::mem::replace(
+        &mut env.objc.borrow_mut::<UIViewControllerHostObject>(this).tab_bar_item,
+        tab_bar_item,
+    );
+    if old != nil {
+        release(env, old);
+    }
+}
+- (id)tabBarController {
+    let mut controller = env.objc.borrow::<UIViewControllerHostObject>(this).parent_view_controller;
+    while controller != nil {
+        let host_object = env.objc.borrow::<UIViewControllerHostObject>(controller);
+        if host_object.tab_bar_controller.is_some() {
+            break;
+        }
+        controller = host_object.parent_view_controller;
+    }
+    controller
+}
+
+- (UIInterfaceOrientation)interfaceOrientation {
+    orientation_from_window(env.window.device_orientation())
+}
+- (bool)shouldAutorotateToInterfaceOrientation:(UIInterfaceOrientation)orientation {
+    orientation == UIInterfaceOrientationPortrait
+}
+- (())willRotateToInterfaceOrientation:(UIInterfaceOrientation)_orientation
+                              duration:(NSTimeInterval)_duration {
+    // Subclasses may override this.
+}
+- (())willAnimateRotationToInterfaceOrientation:(UIInterfaceOrientation)_orientation
+                                       duration:(NSTimeInterval)_duration {
+    // Subclasses may override this.
+}
+- (())didRotateFromInterfaceOrientation:(UIInterfaceOrientation)_orientation {
+    // Subclasses may override this.
+}
+
+- (id)modalViewController {
+    env.objc.borrow::<UIViewControllerHostObject>(this).modal_view_controller
+}
+- (id)parentViewController {
+    env.objc.borrow::<UIViewControllerHostObject>(this).parent_view_controller
+}
+- (id)navigationController {
+    let class = env.objc.get_known_class("UINavigationController", &mut env.mem);
+    let mut controller = env.objc.borrow::<UIViewControllerHostObject>(this).parent_view_controller;
+    while controller != nil && !msg![env; controller isKindOfClass:class] {
+        controller = env.objc.borrow::<UIViewControllerHostObject>(controller).parent_view_controller;
+    }
+    controller
+}
+
+- (UIModalTransitionStyle)modalTransitionStyle {
+    env.objc.borrow::<UIViewControllerHostObject>(this).modal_transition_style
+}
+- (())setModalTransitionStyle:(UIModalTransitionStyle)style {
+    env.objc.borrow_mut::<UIViewControllerHostObject>(this).modal_transition_style = style;
+}
+
+- (())presentModalViewController:(id)controller // UIViewController*
+                        animated:(bool)animated {
+    let existing = env.objc.borrow::<UIViewControllerHostObject>(this).modal_view_controller;
+    if existing != nil {
+        // UIKit doesn't allow this, but presenting it from the modal view
+        // controller is probably what was meant.
+        log!(
+            "Warning: {:?} is already presenting {:?}, presenting {:?} from that instead",
+            this,
+            existing,
+            controller
+        );
+        () = msg![env; existing presentModalViewController:controller animated:animated];
+        return;
+    }
+    log_dbg!("{:?} presenting {:?}", this, controller);
+    retain(env, controller);
+    env.objc.borrow_mut::<UIViewControllerHostObject>(this).modal_view_controller = controller;
+    env.objc.borrow_mut::<UIViewControllerHostObject>(controller).parent_view_controller = this;
+    if env.objc.borrow::<UIViewControllerHostObject>(controller).image_picker.is_some() {
+        ui_image_picker_controller::present(env, controller);
+    } else if env.objc.borrow::<UIViewControllerHostObject>(controller).mail_composer.is_some() {
+        mf_mail_compose_view_controller::present(env, controller);
+    } else {
+        begin_modal_transition(env, this, controller, animated, true);
+    }
+}
+- (())dismissModalViewControllerAnimated:(bool)animated {
+    let &UIViewControllerHostObject {
+        modal_view_controller: controller,
+        parent_view_controller: parent,
+        ..
+    } = env.objc.borrow(this);
+    if controller == nil {
+        // Sending this to the modal view controller itself is allowed.
+        if parent != nil {
+            () = msg![env; parent dismissModalViewControllerAnimated:animated];
+        }
+        return;
+    }
+    // Any view controller the modal view controller is presenting goes too.
+    let nested = env.objc.borrow::<UIViewControllerHostObject>(controller).modal_view_controller;
+    if nested != nil {
+        () = msg![env; controller dismissModalViewControllerAnimated:false];
+    }
+    log_dbg!("{:?} dismissing {:?}", this, controller);
+    if env.objc.borrow::<UIViewControllerHostObject>(controller).image_picker.is_some() {
+        ui_image_picker_controller::dismiss(env, controller);
+    } else if env.objc.borrow::<UIViewControllerHostObject>(controller).mail_composer.is_some() {
+        mf_mail_compose_view_controller::dismiss(env, controller);
+    } else {
+        begin_modal_transition(env, this, controller, animated, false);
+    }
+    env.objc.borrow_mut::<UIViewControllerHostObject>(controller).parent_view_controller = nil;
+    env.objc.borrow_mut::<UIViewControllerHostObject>(this).modal_view_controller = nil;
+    release(env, controller);
+}
+
+// Private methods, the did-stop selectors of the animations in
+// begin_modal_transition. The context is the retained modal view controller.
+- (())_touchHLE_modalTransitionDidShow:(id)_animation_id // NSString*
+                              finished:(id)_finished // NSNumber*
+                               context:(MutVoidPtr)context {
+    let controller: id = context.cast();
+    finish_modal_transition(env, this, controller, true, true);
+    release(env, controller);
+}
+- (())_touchHLE_modalTransitionDidHide:(id)_animation_id // NSString*
+                              finished:(id)_finished // NSNumber*
+                               context:(MutVoidPtr)context {
+    let controller: id = context.cast();
+    finish_modal_transition(env, this, controller, false, true);
+    release(env, controller);
+}
+
+@end
+
+@implementation UINavigationController: UIViewController
+
+- (id)delegate {
+    env.objc.borrow::<UIViewControllerHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate {
+    env.objc.borrow_mut::<UIViewControllerHostObject>(this).delegate = delegate;
+}
+
+@end
+
+};
+
+/// For use by `UIApplication`: tell the view controllers about a memory
+/// warning, so they can unload views that aren't in use.
+pub(super) fn handle_memory_warning(env: &mut Environment) {
+    let view_controllers = env
+        .framework_state
+        .uikit
+        .ui_view_controller
+        .view_controllers
+        .clone();
+    for controller in view_controllers {
+        // An earlier one might have released it.
+        if !env
+            .framework_state
+            .uikit
+            .ui_view_controller
+            .view_controllers
+            .contains(&controller)
+        {
+            continue;
+        }
+        () = msg![env; controller didReceiveMemoryWarning];
+    }
+}
+
+/// The subview of a window that a view is in, or the view itself if it's
+/// directly in the window.
+fn window_subview_containing(env: &mut Environment, window: id, view: id) -> id {
+    let mut view = view;
+    loop {
+        let superview = env.objc.borrow::<UIViewHostObject>(view).superview;
+        if superview == window || superview == nil {
+            return view;
+        }
+        view = superview;
+    }
+}
+
+/// How far to move a view so it's just below the bottom of the screen, from
+/// the point of view of its interface orientation.
+fn offscreen_offset(transform: CGAffineTransform, bounds: CGRect) -> CGPoint {
+    CGPoint {
+        x: transform.c * bounds.size.height,
+        y: transform.d * bounds.size.height,
+    }
+}
+
+/// Start presenting or dismissing a modal view controller: send the
+/// will-appear and will-disappear callbacks, put the views that will be seen
+/// in the window, and animate the transition if requested.
+/// [finish_modal_transition] is called once the animation is done.
+fn begin_modal_transition(
+    env: &mut Environment,
+    presenting: id,
+    modal: id,
+    animated: bool,
+    showing: bool,
+) {
+    let view: id = if showing {
+        () = msg![env; presenting viewWillDisappear:animated];
+        // This loads the view if needed, so viewDidLoad comes first.
+        let view: id = msg![env; modal view];
+        () = msg![env; modal viewWillAppear:animated];
+
+        let presenting_view: id = msg![env; presenting view];
+        if let Some(window) = window_for_view(env, presenting_view) {
+            // The modal view covers whatever the presenting view is in,
+            // including any rotation.
+            let covered = window_subview_containing(env, window, presenting_view);
+            let transform: CGAffineTransform = msg![env; covered transform];
+            let bounds: CGRect = msg![env; covered bounds];
+            let center: CGPoint = msg![env; covered center];
+            () = msg![env; view setTransform:transform];
+            () = msg![env; view setBounds:bounds];
+            () = msg![env; view setCenter:center];
+            () = msg![env; view setAlpha:1.0];
+            () = msg![env; window addSubview:view];
+        } else {
+            log!(
+                "Warning: {:?} isn't in a window, so the view of {:?} won't be either",
+                presenting,
+                modal
+            );
+        }
+        view
+    } else {
+        () = msg![env; modal viewWillDisappear:animated];
+        let view = env.objc.borrow::<UIViewControllerHostObject>(modal).view;
+        let covered = env
+            .objc
+            .borrow_mut::<UIViewControllerHostObject>(presenting)
+            .covered_view
+            .take();
+        let (restored, owned) = match covered {
+            // This loads the view again if a memory warning unloaded it.
+            Some(CoveredView::Own) => (msg![env; presenting view], false),
+            Some(CoveredView::Other(covered)) => (covered, true),
+            None => (nil, false),
+        };
+        let window = if view == nil {
+            nil
+        } else {
+            env.objc.borrow::<UIViewHostObject>(view).superview
+        };
+        if restored != nil && window != nil {
+            () = msg![env; window insertSubview:restored belowSubview:view];
+        }
+        if owned {
+            release(env, restored);
+        }
+        () = msg![env; presenting viewWillAppear:animated];
+        view
+    };
+
+    let in_window = view != nil && window_for_view(env, view).is_some();
+    if !animated || !in_window {
+        finish_modal_transition(env, presenting, modal, showing, animated);
+        return;
+    }
+
+    let style = env
+        .objc
+        .borrow::<UIViewControllerHostObject>(modal)
+        .modal_transition_style;
+    let center: CGPoint = msg![env; view center];
+    let transform: CGAffineTransform = msg![env; view transform];
+    let bounds: CGRect = msg![env; view bounds];
+    let offset = offscreen_offset(transform, bounds);
+    let offscreen_center = CGPoint {
+        x: center.x + offset.x,
+        y: center.y + offset.y,
+    };
+    if showing {
+        match style {
+            UIModalTransitionStyleCoverVertical => {
+                () = msg![env; view setCenter:offscreen_center];
+            }
+            UIModalTransitionStyleCrossDissolve => {
+                () = msg![env; view setAlpha:0.0];
+            }
+            _ => (),
+        }
+    }
+
+    let selector = if showing {
+        "_touchHLE_modalTransitionDidShow:finished:context:"
+    } else {
+        "_touchHLE_modalTransitionDidHide:finished:context:"
+    };
+    let selector = env.objc.lookup_selector(selector).unwrap();
+    let context: MutVoidPtr = retain(env, modal).cast();
+    () = msg_class![env; UIView beginAnimations:nil context:context];
+    () = msg_class![env; UIView setAnimationDuration:MODAL_TRANSITION_DURATION];
+    () = msg_class![env; UIView setAnimationDelegate:presenting];
+    () = msg_class![env; UIView setAnimationDidStopSelector:selector];
+    match style {
+        UIModalTransitionStyleCoverVertical => {
+            let new_center = if showing { center } else { offscreen_center };
+            () = msg![env; view setCenter:new_center];
+        }
+        UIModalTransitionStyleCrossDissolve => {
+            let alpha: CGFloat = if showing { 1.0 } else { 0.0 };
+            () = msg![env; view setAlpha:alpha];
+        }
+        UIModalTransitionStyleFlipHorizontal | UIModalTransitionStylePartialCurl => {
+            // TODO: flip or curl the views. The animation still takes the
+            // right amount of time.
+        }
+        _ => {
+            log!("Warning: unknown modal transition style {}", style);
+        }
+    }
+    () = msg_class![env; UIView commitAnimations];
+}
+
+/// Finish presenting or dismissing a modal view controller: take the views
+/// that can't be seen any more out of the window and send the did-appear and
+/// did-disappear callbacks.
+fn finish_modal_transition(
+    env: &mut Environment,
+    presenting: id,
+    modal: id,
+    showing: bool,
+    animated: bool,
+) {
+    let view = env.objc.borrow::<UIViewControllerHostObject>(modal).view;
+    if showing {
+        // The presenting view controller's view is taken out of the window
+        // while it's covered, which means a memory warning can unload it.
+        let presenting_view = env
+            .objc
+            .borrow::<UIViewControllerHostObject>(presenting)
+            .view;
+        let window = if presenting_view == nil {
+            None
+        } else {
+            window_for_view(env, presenting_view)
+        };
+        if let Some(window) = window {
+            let covered = window_subview_containing(env, window, presenting_view);
+            if covered != window && covered != view {
+                let covered_view = if covered == presenting_view {
+                    CoveredView::Own
+                } else {
+                    CoveredView::Other(retain(env, covered))
+                };
+                let old = env
+                    .objc
+                    .borrow_mut::<UIViewControllerHostObject>(presenting)
+                    .covered_view
+                    .replace(covered_view);
+                if let Some(CoveredView::Other(old)) = old {
+                    release(env, old);
+                }
+                () = msg![env; covered removeFromSuperview];
+            }
+        }
+        () = msg![env; presenting viewDidDisappear:animated];
+        () = msg![env; modal viewDidAppear:animated];
+    } else {
+        if view != nil {
+            () = msg![env; view removeFromSuperview];
+        }
+        () = msg![env; modal viewDidDisappear:animated];
+        () = msg![env; presenting viewDidAppear:animated];
+    }
+}
+
+/// The rotation of a view controller's view for an interface orientation.
+fn rotation_angle(orientation: UIInterfaceOrientation) -> CGFloat {
+    match orientation {
+        UIInterfaceOrientationPortraitUpsideDown => PI,
+        UIInterfaceOrientationLandscapeLeft => -FRAC_PI_2,
+        UIInterfaceOrientationLandscapeRight => FRAC_PI_2,
+        _ => 0.0,
+    }
+}
+
+fn is_landscape(orientation: UIInterfaceOrientation) -> bool {
+    matches!(
+        orientation,
+        UIInterfaceOrientationLandscapeLeft | UIInterfaceOrientationLandscapeRight
+    )
+}
+
+/// For use by `UIDevice`: when the device has been rotated, ask the view
+/// controller of each window's view whether it wants to rotate its interface
+/// to match, and if it does, rotate it and the status bar (and therefore the
+/// host window).
+///
+/// UIKit only asks the frontmost view controller, which is identified here as
+/// the one owning the first subview of a window that has one.
+pub(super) fn autorotate(env: &mut Environment, new_orientation: UIInterfaceOrientation) {
+    let ui_window_class = env.objc.get_known_class("UIWindow", &mut env.mem);
+    let views = env.framework_state.uikit.ui_view.views.clone();
+    for window in views {
+        if !msg![env; window isKindOfClass:ui_window_class] {
+            continue;
+        }
+        let subviews = env.objc.borrow::<UIViewHostObject>(window).subviews.clone();
+        let Some(controller) = subviews.into_iter().find_map(|subview| {
+            let controller = env.objc.borrow::<UIViewHostObject>(subview).view_controller;
+            (controller != nil).then_some(controller)
+        }) else {
+            continue;
+        };
+
+        let old_orientation: UIInterfaceOrientation = msg![env; controller interfaceOrientation];
+        if old_orientation == new_orientation {
+            continue;
+        }
+        let should: bool =
+            msg![env; controller shouldAutorotateToInterfaceOrientation:new_orientation];
+        log_dbg!(
+            "{:?} should autorotate from {} to {}: {}",
+            controller,
+            old_orientation,
+            new_orientation,
+            should
+        );
+        if !should {
+            continue;
+        }
+
+        () = msg![env; controller willRotateToInterfaceOrientation:new_orientation
+                                                          duration:ROTATION_DURATION];
+
+        let ui_application: id = msg_class![env; UIApplication sharedApplication];
+        () = msg![env; ui_application setStatusBarOrientation:new_orientation];
+
+        // The window stays in portrait, and the view is rotated within it.
+        let view: id = msg![env; controller view];
+        let mut bounds: CGRect = msg![env; view bounds];
+        if is_landscape(old_orientation) != is_landscape(new_orientation) {
+            bounds.size = CGSize {
+                width: bounds.size.height,
+                height: bounds.size.width,
+            };
+        }
+        let transform = CGAffineTransformMakeRotation(env, rotation_angle(new_orientation));
+        () = msg![env; view setTransform:transform];
+        () = msg![env; view setBounds:bounds];
+
+        () = msg![env; controller willAnimateRotationToInterfaceOrientation:new_orientation
+                                                                   duration:ROTATION_DURATION];
+        () = msg![env; controller didRotateFromInterfaceOrientation:old_orientation];
+    }
+}
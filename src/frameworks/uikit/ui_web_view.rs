@@ -0,0 +1,1091 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIWebView`.
+//!
+//! Pages are rendered by the tiny HTML engine in [html] and drawn as an overlay
+//! (see [super::overlay]), since views aren't composited yet. Only pages that
+//! are part of the app can be displayed. Links to anything else are still
+//! offered to the delegate, and then opened in the host's web browser if the
+//! `--open-external-links` option was used.
+
+mod html;
+
+use super::overlay::{Canvas, Overlay};
+use super::ui_font;
+use super::ui_view::{frame_on_screen, UIViewHostObject};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::{ns_data, ns_string, NSInteger, NSUInteger};
+use crate::fs::GuestPath;
+use crate::image::Image;
+use crate::objc::{
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    SEL,
+};
+use crate::window::{Event, TouchSource};
+use crate::Environment;
+use std::collections::HashMap;
+
+pub type UIWebViewNavigationType = NSInteger;
+pub const UIWebViewNavigationTypeLinkClicked: UIWebViewNavigationType = 0;
+#[allow(dead_code)]
+pub const UIWebViewNavigationTypeFormSubmitted: UIWebViewNavigationType = 1;
+pub const UIWebViewNavigationTypeBackForward: UIWebViewNavigationType = 2;
+pub const UIWebViewNavigationTypeReload: UIWebViewNavigationType = 3;
+#[allow(dead_code)]
+pub const UIWebViewNavigationTypeFormResubmitted: UIWebViewNavigationType = 4;
+pub const UIWebViewNavigationTypeOther: UIWebViewNavigationType = 5;
+
+pub type UIDataDetectorTypes = NSUInteger;
+pub const UIDataDetectorTypePhoneNumber: UIDataDetectorTypes = 1 << 0;
+
+/// Width that pages without a viewport are laid out at when they're scaled to
+/// fit, like in Mobile Safari.
+const DEFAULT_PAGE_WIDTH: CGFloat = 980.0;
+
+/// Distance in points a touch has to move before it scrolls rather than taps.
+const DRAG_THRESHOLD: f32 = 8.0;
+
+#[derive(Default)]
+pub struct State {
+    /// Web views with a load that hasn't started yet. Strong references.
+    pending_loads: Vec<id>,
+    touch: Option<Touch>,
+    overlay: Option<Overlay>,
+    /// What the overlay shows: web views with their frame (x, y, width,
+    /// height), scroll position and page generation.
+    overlay_contents: Vec<(id, [CGFloat; 4], CGFloat, u32)>,
+}
+
+struct Touch {
+    source: TouchSource,
+    web_view: id,
+    start: (f32, f32),
+    start_scroll: CGFloat,
+    dragging: bool,
+}
+
+/// Where relative URLs are resolved from.
+#[derive(Clone, Debug, PartialEq)]
+enum Base {
+    None,
+    /// Guest path of a directory.
+    Directory(String),
+    /// Non-file URL.
+    Url(String),
+}
+
+#[derive(Clone)]
+enum Source {
+    Html {
+        html: String,
+        base: Base,
+    },
+    File {
+        path: String,
+        fragment: Option<String>,
+    },
+    /// Something that can't be loaded.
+    Remote(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum Target {
+    File {
+        path: String,
+        fragment: Option<String>,
+    },
+    /// Somewhere on the current page.
+    Fragment(String),
+    External(String),
+}
+
+pub(super) struct WebViewState {
+    /// Weak reference.
+    delegate: id,
+    /// `NSURLRequest*`, strong reference, or [nil].
+    request: id,
+    scales_page_to_fit: bool,
+    data_detector_types: UIDataDetectorTypes,
+    loading: bool,
+    pending: Option<Source>,
+    base: Base,
+    document: Option<html::Document>,
+    /// Images used by the page, by `src`.
+    images: HashMap<String, Image>,
+    /// The page width the layout was done for, and the layout.
+    layout: Option<(CGFloat, html::Layout)>,
+    /// Vertical scroll position, in page pixels.
+    scroll: CGFloat,
+    /// Incremented whenever the page changes, so the overlay is redrawn.
+    generation: u32,
+    history: Vec<Source>,
+    history_index: usize,
+}
+impl Default for WebViewState {
+    fn default() -> Self {
+        WebViewState {
+            delegate: nil,
+            request: nil,
+            scales_page_to_fit: false,
+            data_detector_types: UIDataDetectorTypePhoneNumber,
+            loading: false,
+            pending: None,
+            base: Base::None,
+            document: None,
+            images: HashMap::new(),
+            layout: None,
+            scroll: 0.0,
+            generation: 0,
+            history: Vec::new(),
+            history_index: 0,
+        }
+    }
+}
+
+pub(super) fn state(env: &mut Environment, web_view: id) -> &mut WebViewState {
+    env.objc
+        .borrow_mut::<UIViewHostObject>(web_view)
+        .web_view
+        .get_or_insert_with(Default::default)
+}
+
+/// For use by `UIView`'s `dealloc`.
+pub(super) fn release_state(env: &mut Environment, web_view: id, state: WebViewState) {
+    release(env, state.request);
+    let touch = &mut env.framework_state.uikit.ui_web_view.touch;
+    if matches!(*touch, Some(ref touch) if touch.web_view == web_view) {
+        *touch = None;
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIWebView: UIView
+
+- (id)delegate {
+    state(env, this).delegate
+}
+- (())setDelegate:(id)delegate { // something implementing UIWebViewDelegate
+    state(env, this).delegate = delegate;
+}
+
+- (id)request {
+    state(env, this).request
+}
+- (bool)isLoading {
+    state(env, this).loading
+}
+
+- (bool)scalesPageToFit {
+    state(env, this).scales_page_to_fit
+}
+- (())setScalesPageToFit:(bool)scales {
+    let web_view_state = state(env, this);
+    web_view_state.scales_page_to_fit = scales;
+    web_view_state.layout = None;
+    web_view_state.generation = web_view_state.generation.wrapping_add(1);
+}
+
+// TODO: Phone numbers etc aren't detected.
+- (UIDataDetectorTypes)dataDetectorTypes {
+    state(env, this).data_detector_types
+}
+- (())setDataDetectorTypes:(UIDataDetectorTypes)types {
+    state(env, this).data_detector_types = types;
+}
+- (bool)detectsPhoneNumbers {
+    state(env, this).data_detector_types & UIDataDetectorTypePhoneNumber != 0
+}
+- (())setDetectsPhoneNumbers:(bool)detects {
+    let web_view_state = state(env, this);
+    if detects {
+        web_view_state.data_detector_types |= UIDataDetectorTypePhoneNumber;
+    } else {
+        web_view_state.data_detector_types &= !UIDataDetectorTypePhoneNumber;
+    }
+}
+
+- (())loadRequest:(id)request { // NSURLRequest*
+    let url: id = msg![env; request URL];
+    let target = url_target(env, url);
+    navigate(env, this, request, target, UIWebViewNavigationTypeOther);
+}
+- (())loadHTMLString:(id)string // NSString*
+             baseURL:(id)base_url { // NSURL*
+    let html = ns_string::to_rust_string(env, string).into_owned();
+    let base = base_from_url(env, base_url);
+    set_request(env, this, nil);
+    start_load(env, this, Source::Html { html, base }, UIWebViewNavigationTypeOther);
+}
+- (())loadData:(id)data // NSData*
+      MIMEType:(id)mime_type // NSString*
+textEncodingName:(id)_encoding_name // NSString*
+       baseURL:(id)base_url { // NSURL*
+    // TODO: Respect the encoding name.
+    let bytes = ns_data::to_vec(env, data);
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+    let mime_type = if mime_type == nil {
+        "text/html".into()
+    } else {
+        ns_string::to_rust_string(env, mime_type)
+    };
+    let html = if mime_type == "text/plain" {
+        plain_text_to_html(&text)
+    } else {
+        if mime_type != "text/html" && mime_type != "application/xhtml+xml" {
+            log!("TODO: UIWebView can't display {:?}, treating it as HTML", mime_type);
+        }
+        text
+    };
+    let base = base_from_url(env, base_url);
+    set_request(env, this, nil);
+    start_load(env, this, Source::Html { html, base }, UIWebViewNavigationTypeOther);
+}
+
+- (bool)canGoBack {
+    state(env, this).history_index > 0
+}
+- (bool)canGoForward {
+    let web_view_state = state(env, this);
+    web_view_state.history_index + 1 < web_view_state.history.len()
+}
+- (())goBack {
+    let web_view_state = state(env, this);
+    if web_view_state.history_index > 0 {
+        web_view_state.history_index -= 1;
+        let source = web_view_state.history[web_view_state.history_index].clone();
+        go_to(env, this, source, UIWebViewNavigationTypeBackForward);
+    }
+}
+- (())goForward {
+    let web_view_state = state(env, this);
+    if web_view_state.history_index + 1 < web_view_state.history.len() {
+        web_view_state.history_index += 1;
+        let source = web_view_state.history[web_view_state.history_index].clone();
+        go_to(env, this, source, UIWebViewNavigationTypeBackForward);
+    }
+}
+- (())reload {
+    let web_view_state = state(env, this);
+    if let Some(source) = web_view_state.history.get(web_view_state.history_index).cloned() {
+        go_to(env, this, source, UIWebViewNavigationTypeReload);
+    }
+}
+- (())stopLoading {
+    let web_view_state = state(env, this);
+    web_view_state.pending = None;
+    web_view_state.loading = false;
+}
+
+- (id)stringByEvaluatingJavaScriptFromString:(id)script { // NSString*
+    let script = ns_string::to_rust_string(env, script);
+    let result = match script.trim().trim_end_matches(';') {
+        "document.title" => state(env, this)
+            .document
+            .as_ref()
+            .and_then(|document| document.title.clone())
+            .unwrap_or_default(),
+        _ => {
+            log!("TODO: UIWebView can't run JavaScript, ignoring {:?}", script);
+            String::new()
+        }
+    };
+    let result = ns_string::from_rust_string(env, result);
+    autorelease(env, result)
+}
+
+@end
+
+};
+
+fn responding_delegate(env: &mut Environment, web_view: id, selector: &str) -> Option<(id, SEL)> {
+    let delegate = state(env, web_view).delegate;
+    if delegate == nil {
+        return None;
+    }
+    let selector = env.objc.lookup_selector(selector)?;
+    if msg![env; delegate respondsToSelector:selector] {
+        Some((delegate, selector))
+    } else {
+        None
+    }
+}
+
+fn notify_delegate(env: &mut Environment, web_view: id, selector: &str) {
+    if let Some((delegate, selector)) = responding_delegate(env, web_view, selector) {
+        let () = msg_send(env, (delegate, selector, web_view));
+    }
+}
+
+fn should_start_load(
+    env: &mut Environment,
+    web_view: id,
+    request: id,
+    navigation_type: UIWebViewNavigationType,
+) -> bool {
+    let selector = "webView:shouldStartLoadWithRequest:navigationType:";
+    match responding_delegate(env, web_view, selector) {
+        Some((delegate, selector)) => msg_send(
+            env,
+            (delegate, selector, web_view, request, navigation_type),
+        ),
+        None => true,
+    }
+}
+
+fn set_request(env: &mut Environment, web_view: id, request: id) {
+    retain(env, request);
+    let old = std::mem::replace(&mut state(env, web_view).request, request);
+    release(env, old);
+}
+
+fn plain_text_to_html(text: &str) -> String {
+    let escaped = text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!("<pre>{}</pre>", escaped)
+}
+
+fn parent_directory(path: &str) -> String {
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) | None => "/".to_string(),
+        Some((parent, _)) => parent.to_string(),
+    }
+}
+
+fn base_from_url(env: &mut Environment, url: id) -> Base {
+    if url == nil {
+        return Base::None;
+    }
+    if msg![env; url isFileURL] {
+        let path: id = msg![env; url path];
+        let path = ns_string::to_rust_string(env, path).into_owned();
+        // The base is usually the bundle directory, but it can be a file.
+        if env.fs.is_file(GuestPath::new(&path)) {
+            Base::Directory(parent_directory(&path))
+        } else {
+            Base::Directory(path.trim_end_matches('/').to_string())
+        }
+    } else {
+        let string: id = msg![env; url absoluteString];
+        Base::Url(ns_string::to_rust_string(env, string).into_owned())
+    }
+}
+
+fn url_target(env: &mut Environment, url: id) -> Option<Target> {
+    if url == nil {
+        return None;
+    }
+    if msg![env; url isFileURL] {
+        let path: id = msg![env; url path];
+        let path = ns_string::to_rust_string(env, path).into_owned();
+        Some(Target::File {
+            path,
+            fragment: None,
+        })
+    } else {
+        let string: id = msg![env; url absoluteString];
+        let string = ns_string::to_rust_string(env, string);
+        resolve(&Base::None, &string)
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Resolve `.` and `..` in an absolute path.
+fn normalize_path(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    format!("/{}", components.join("/"))
+}
+
+fn split_fragment(href: &str) -> (&str, Option<String>) {
+    match href.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(percent_decode(fragment))),
+        None => (href, None),
+    }
+}
+
+/// Resolve a relative URL against a non-file URL.
+fn join_url(base: &str, href: &str) -> String {
+    let Some((scheme, rest)) = base.split_once("://") else {
+        return href.to_string();
+    };
+    let host_end = rest.find('/').unwrap_or(rest.len());
+    if href.starts_with("//") {
+        format!("{}:{}", scheme, href)
+    } else if href.starts_with('/') {
+        format!("{}://{}{}", scheme, &rest[..host_end], href)
+    } else {
+        let path = &rest[host_end..];
+        let directory = path.rfind('/').map_or("/", |i| &path[..i + 1]);
+        format!("{}://{}{}{}", scheme, &rest[..host_end], directory, href)
+    }
+}
+
+fn resolve(base: &Base, href: &str) -> Option<Target> {
+    let href = href.trim();
+    if href.is_empty() {
+        return None;
+    }
+    if let Some(fragment) = href.strip_prefix('#') {
+        return Some(Target::Fragment(percent_decode(fragment)));
+    }
+
+    let scheme = href
+        .split_once(':')
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| {
+            scheme.len() > 1
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        });
+    if let Some(scheme) = scheme {
+        return match scheme.to_ascii_lowercase().as_str() {
+            "file" => {
+                let path = href[scheme.len() + 1..].trim_start_matches('/');
+                let path = path.strip_prefix("localhost/").unwrap_or(path);
+                let (path, fragment) = split_fragment(path);
+                Some(Target::File {
+                    path: normalize_path(&percent_decode(path)),
+                    fragment,
+                })
+            }
+            "javascript" | "about" => None,
+            _ => Some(Target::External(href.to_string())),
+        };
+    }
+
+    match base {
+        Base::Directory(directory) => {
+            let (path, fragment) = split_fragment(href);
+            let path = path.split('?').next().unwrap();
+            let path = if path.starts_with('/') {
+                path.to_string()
+            } else {
+                format!("{}/{}", directory, path)
+            };
+            Some(Target::File {
+                path: normalize_path(&percent_decode(&path)),
+                fragment,
+            })
+        }
+        Base::Url(url) => Some(Target::External(join_url(url, href))),
+        Base::None => None,
+    }
+}
+
+fn request_for_target(env: &mut Environment, target: &Target) -> id {
+    let url: id = match target {
+        Target::File { path, .. } => {
+            let path = ns_string::from_rust_string(env, path.clone());
+            let url: id = msg_class![env; NSURL fileURLWithPath:path];
+            release(env, path);
+            url
+        }
+        Target::External(string) | Target::Fragment(string) => {
+            let string = ns_string::from_rust_string(env, string.clone());
+            let url: id = msg_class![env; NSURL URLWithString:string];
+            release(env, string);
+            url
+        }
+    };
+    msg_class![env; NSURLRequest requestWithURL:url]
+}
+
+/// Start loading a request, if the delegate allows it.
+fn navigate(
+    env: &mut Environment,
+    web_view: id,
+    request: id,
+    target: Option<Target>,
+    navigation_type: UIWebViewNavigationType,
+) {
+    if !should_start_load(env, web_view, request, navigation_type) {
+        return;
+    }
+    match target {
+        None => {
+            log!("UIWebView can't load {:?}", request);
+        }
+        Some(Target::Fragment(name)) => scroll_to_anchor(env, web_view, &name),
+        Some(Target::File { path, fragment }) => {
+            set_request(env, web_view, request);
+            start_load(
+                env,
+                web_view,
+                Source::File { path, fragment },
+                navigation_type,
+            );
+        }
+        Some(Target::External(url)) if navigation_type == UIWebViewNavigationTypeLinkClicked => {
+            if env.options.open_external_links {
+                log!("Opening {:?} in the host's web browser.", url);
+                crate::window::open_url(&url);
+            } else {
+                log!("UIWebView can't display {:?}. Use the --open-external-links option to open links like this in your web browser.", url);
+            }
+        }
+        Some(Target::External(url)) => {
+            set_request(env, web_view, request);
+            start_load(env, web_view, Source::Remote(url), navigation_type);
+        }
+    }
+}
+
+/// Go to a page in the history.
+fn go_to(
+    env: &mut Environment,
+    web_view: id,
+    source: Source,
+    navigation_type: UIWebViewNavigationType,
+) {
+    match source {
+        Source::File { path, fragment } => {
+            let target = Target::File { path, fragment };
+            let request = request_for_target(env, &target);
+            navigate(env, web_view, request, Some(target), navigation_type);
+        }
+        source => start_load(env, web_view, source, navigation_type),
+    }
+}
+
+/// Queue a load. Like on a real device, the page isn't loaded immediately.
+fn start_load(
+    env: &mut Environment,
+    web_view: id,
+    source: Source,
+    navigation_type: UIWebViewNavigationType,
+) {
+    let web_view_state = state(env, web_view);
+    let is_new_page = navigation_type != UIWebViewNavigationTypeBackForward
+        && navigation_type != UIWebViewNavigationTypeReload
+        && !matches!(source, Source::Remote(_));
+    if is_new_page {
+        web_view_state
+            .history
+            .truncate(web_view_state.history_index + 1);
+        web_view_state.history.push(source.clone());
+        web_view_state.history_index = web_view_state.history.len() - 1;
+    }
+    web_view_state.pending = Some(source);
+    web_view_state.loading = true;
+
+    if !env
+        .framework_state
+        .uikit
+        .ui_web_view
+        .pending_loads
+        .contains(&web_view)
+    {
+        retain(env, web_view);
+        env.framework_state
+            .uikit
+            .ui_web_view
+            .pending_loads
+            .push(web_view);
+    }
+}
+
+/// For use by [super::handle_events]: load pages that were requested since
+/// the last time this was called.
+pub(super) fn handle_loads(env: &mut Environment) {
+    let pending = std::mem::take(&mut env.framework_state.uikit.ui_web_view.pending_loads);
+    for web_view in pending {
+        if let Some(source) = state(env, web_view).pending.take() {
+            let pool: id = msg_class![env; NSAutoreleasePool new];
+            load(env, web_view, source);
+            release(env, pool);
+        }
+        release(env, web_view);
+    }
+}
+
+fn load(env: &mut Environment, web_view: id, source: Source) {
+    notify_delegate(env, web_view, "webViewDidStartLoad:");
+
+    let (html, base, fragment) = match source {
+        Source::Html { html, base } => (html, base, None),
+        Source::File { path, fragment } => {
+            let Ok(bytes) = env.fs.read(GuestPath::new(&path)) else {
+                log!("UIWebView couldn't read {:?}", path);
+                fail_load(env, web_view);
+                return;
+            };
+            // TODO: Respect the encoding declared by the page.
+            let text = String::from_utf8_lossy(&bytes).into_owned();
+            let html = if path.ends_with(".txt") {
+                plain_text_to_html(&text)
+            } else {
+                text
+            };
+            (html, Base::Directory(parent_directory(&path)), fragment)
+        }
+        Source::Remote(url) => {
+            log!(
+                "UIWebView can't load {:?}, only pages in the app can be displayed",
+                url
+            );
+            fail_load(env, web_view);
+            return;
+        }
+    };
+
+    let mut document = html::parse(&html);
+    let mut images = HashMap::new();
+    for item in &mut document.items {
+        let html::Item::Image { src, size, .. } = item else {
+            continue;
+        };
+        if !images.contains_key(src) {
+            let Some(Target::File { path, .. }) = resolve(&base, src) else {
+                continue;
+            };
+            let image = env
+                .fs
+                .read(GuestPath::new(&path))
+                .ok()
+                .and_then(|bytes| Image::from_bytes(&bytes).ok());
+            let Some(image) = image else {
+                log!("UIWebView couldn't load image {:?}", path);
+                continue;
+            };
+            images.insert(src.clone(), image);
+        }
+        let (width, height) = images[src].dimensions();
+        *size = Some((width as f32, height as f32));
+    }
+
+    let web_view_state = state(env, web_view);
+    web_view_state.document = Some(document);
+    web_view_state.images = images;
+    web_view_state.base = base;
+    web_view_state.layout = None;
+    web_view_state.scroll = 0.0;
+    web_view_state.generation = web_view_state.generation.wrapping_add(1);
+    web_view_state.loading = false;
+    if let Some(fragment) = fragment {
+        scroll_to_anchor(env, web_view, &fragment);
+    }
+
+    notify_delegate(env, web_view, "webViewDidFinishLoad:");
+}
+
+fn fail_load(env: &mut Environment, web_view: id) {
+    state(env, web_view).loading = false;
+    let selector = "webView:didFailLoadWithError:";
+    if let Some((delegate, selector)) = responding_delegate(env, web_view, selector) {
+        // TODO: NSError
+        let error = nil;
+        let () = msg_send(env, (delegate, selector, web_view, error));
+    }
+}
+
+/// Lay out the page for the web view's current width if that hasn't been done
+/// already. Returns the zoom factor (points per page pixel).
+fn ensure_layout(env: &mut Environment, web_view: id) -> CGFloat {
+    let width = env
+        .objc
+        .borrow::<UIViewHostObject>(web_view)
+        .bounds
+        .size
+        .width;
+    let web_view_state = state(env, web_view);
+    let Some(document) = web_view_state.document.take() else {
+        return 1.0;
+    };
+    let page_width = match document.viewport_width {
+        Some(html::ViewportWidth::DeviceWidth) => width,
+        Some(html::ViewportWidth::Fixed(page_width)) => page_width,
+        None if web_view_state.scales_page_to_fit => DEFAULT_PAGE_WIDTH,
+        None => width,
+    };
+    if !matches!(web_view_state.layout, Some((laid_out_width, _)) if laid_out_width == page_width) {
+        let layout = html::lay_out(&document, page_width, &mut |style, text| {
+            ui_font::styled_system_font(env, style.bold, style.italic, text)
+                .calculate_text_size(style.size, text, None)
+        });
+        state(env, web_view).layout = Some((page_width, layout));
+    }
+    state(env, web_view).document = Some(document);
+    if page_width > 0.0 {
+        width / page_width
+    } else {
+        1.0
+    }
+}
+
+/// Furthest the page can be scrolled, in page pixels.
+fn max_scroll(env: &mut Environment, web_view: id, zoom: CGFloat) -> CGFloat {
+    let height = env
+        .objc
+        .borrow::<UIViewHostObject>(web_view)
+        .bounds
+        .size
+        .height;
+    match state(env, web_view).layout {
+        Some((_, ref layout)) => (layout.height - height / zoom).max(0.0),
+        None => 0.0,
+    }
+}
+
+fn scroll_to_anchor(env: &mut Environment, web_view: id, name: &str) {
+    let zoom = ensure_layout(env, web_view);
+    let max_scroll = max_scroll(env, web_view, zoom);
+    let web_view_state = state(env, web_view);
+    let Some((_, ref layout)) = web_view_state.layout else {
+        return;
+    };
+    let y = if name.is_empty() || name == "top" {
+        Some(0.0)
+    } else {
+        layout
+            .anchors
+            .iter()
+            .find(|(anchor, _)| anchor == name)
+            .map(|&(_, y)| y)
+    };
+    match y {
+        Some(y) => web_view_state.scroll = y.clamp(0.0, max_scroll),
+        None => log_dbg!("UIWebView: no anchor named {:?}", name),
+    }
+}
+
+/// Web views that are in a window, with their frames on the screen.
+fn visible_web_views(env: &mut Environment) -> Vec<(id, CGRect)> {
+    let web_view_class = env.objc.get_known_class("UIWebView", &mut env.mem);
+    let views = env.framework_state.uikit.ui_view.views.clone();
+    let mut web_views = Vec::new();
+    for view in views {
+        if !msg![env; view isKindOfClass:web_view_class] {
+            continue;
+        }
+        let Some(frame) = frame_on_screen(env, view) else {
+            continue;
+        };
+        if frame.size.width > 0.0 && frame.size.height > 0.0 {
+            web_views.push((view, frame));
+        }
+    }
+    web_views
+}
+
+fn draw_web_view(env: &mut Environment, canvas: &mut Canvas, web_view: id, frame: CGRect) {
+    let zoom = ensure_layout(env, web_view);
+    canvas.set_clip(Some(frame));
+
+    let web_view_state = state(env, web_view);
+    let background = web_view_state
+        .document
+        .as_ref()
+        .and_then(|document| document.background)
+        .unwrap_or((1.0, 1.0, 1.0, 1.0));
+    canvas.fill_rounded_rect(frame, 0.0, background);
+
+    let scroll = web_view_state.scroll;
+    let Some((_, ref layout)) = web_view_state.layout else {
+        canvas.set_clip(None);
+        return;
+    };
+    let visible_height = frame.size.height / zoom;
+    let on_screen = |fragment: &html::Fragment| CGRect {
+        origin: CGPoint {
+            x: frame.origin.x + fragment.x * zoom,
+            y: frame.origin.y + (fragment.y - scroll) * zoom,
+        },
+        size: CGSize {
+            width: fragment.width * zoom,
+            height: fragment.height * zoom,
+        },
+    };
+
+    let mut texts = Vec::new();
+    for fragment in &layout.fragments {
+        if fragment.y + fragment.height < scroll || fragment.y > scroll + visible_height {
+            continue;
+        }
+        let rect = on_screen(fragment);
+        match fragment.kind {
+            html::FragmentKind::Text(ref text) => {
+                texts.push((rect, text.clone(), fragment.style.clone()));
+            }
+            html::FragmentKind::Image(index) => {
+                let document = web_view_state.document.as_ref().unwrap();
+                let html::Item::Image { ref src, .. } = document.items[index] else {
+                    unreachable!();
+                };
+                if let Some(image) = web_view_state.images.get(src) {
+                    canvas.draw_image(image, rect);
+                }
+            }
+            html::FragmentKind::Rule => canvas.fill_rounded_rect(rect, 0.0, fragment.style.color),
+        }
+    }
+
+    for (rect, text, style) in texts {
+        let font = ui_font::styled_system_font(env, style.bold, style.italic, &text);
+        canvas.draw_text_at(font, style.size * zoom, &text, rect.origin, style.color);
+        if style.underline {
+            let underline = CGRect {
+                origin: CGPoint {
+                    x: rect.origin.x,
+                    y: rect.origin.y + rect.size.height * 0.85,
+                },
+                size: CGSize {
+                    width: rect.size.width,
+                    height: zoom.max(0.5),
+                },
+            };
+            canvas.fill_rounded_rect(underline, 0.0, style.color);
+        }
+    }
+
+    canvas.set_clip(None);
+}
+
+/// For use by [super::overlay]: redraw the web views if anything changed.
+pub(super) fn update_overlay(env: &mut Environment) {
+    let web_views = visible_web_views(env);
+    let mut contents = Vec::with_capacity(web_views.len());
+    for &(web_view, frame) in &web_views {
+        let web_view_state = state(env, web_view);
+        contents.push((
+            web_view,
+            [
+                frame.origin.x,
+                frame.origin.y,
+                frame.size.width,
+                frame.size.height,
+            ],
+            web_view_state.scroll,
+            web_view_state.generation,
+        ));
+    }
+
+    let state = &mut env.framework_state.uikit.ui_web_view;
+    if contents == state.overlay_contents && (state.overlay.is_some() || contents.is_empty()) {
+        return;
+    }
+    state.overlay_contents = contents;
+    if web_views.is_empty() {
+        state.overlay = None;
+        return;
+    }
+
+    let mut canvas = Canvas::new(env);
+    for (web_view, frame) in web_views {
+        draw_web_view(env, &mut canvas, web_view, frame);
+    }
+    env.framework_state.uikit.ui_web_view.overlay = Some(canvas.into_overlay());
+}
+
+/// For use by [super::overlay]: get the drawing of the web views, if any are
+/// visible.
+pub(super) fn current_overlay(state: &State) -> Option<&Overlay> {
+    state.overlay.as_ref()
+}
+
+fn web_view_at_point(env: &mut Environment, point: (f32, f32)) -> Option<(id, CGRect)> {
+    visible_web_views(env)
+        .into_iter()
+        .rev()
+        .find(|&(_, frame)| {
+            point.0 >= frame.origin.x
+                && point.1 >= frame.origin.y
+                && point.0 < frame.origin.x + frame.size.width
+                && point.1 < frame.origin.y + frame.size.height
+        })
+}
+
+fn tap(env: &mut Environment, web_view: id, location: (f32, f32)) {
+    let Some(frame) = frame_on_screen(env, web_view) else {
+        return;
+    };
+    let zoom = ensure_layout(env, web_view);
+    let web_view_state = state(env, web_view);
+    let Some((_, ref layout)) = web_view_state.layout else {
+        return;
+    };
+    let x = (location.0 - frame.origin.x) / zoom;
+    let y = (location.1 - frame.origin.y) / zoom + web_view_state.scroll;
+    // Links are small, so allow for some imprecision.
+    let slop = 4.0 / zoom;
+    let href = layout
+        .fragments
+        .iter()
+        .find(|fragment| {
+            fragment.style.href.is_some()
+                && x >= fragment.x - slop
+                && x < fragment.x + fragment.width + slop
+                && y >= fragment.y - slop
+                && y < fragment.y + fragment.height + slop
+        })
+        .and_then(|fragment| fragment.style.href.clone());
+    let Some(href) = href else {
+        return;
+    };
+
+    let base = web_view_state.base.clone();
+    let Some(target) = resolve(&base, &href) else {
+        log_dbg!("UIWebView: ignoring link {:?}", href);
+        return;
+    };
+    if let Target::Fragment(ref name) = target {
+        // TODO: Ask the delegate. NSURL can't represent these URLs yet.
+        scroll_to_anchor(env, web_view, name);
+        return;
+    }
+    let request = request_for_target(env, &target);
+    navigate(
+        env,
+        web_view,
+        request,
+        Some(target),
+        UIWebViewNavigationTypeLinkClicked,
+    );
+}
+
+/// For use by [super::handle_events]: handle a touch event on a web view.
+/// Returns [true] if the event was consumed.
+pub(super) fn handle_event(env: &mut Environment, event: &Event) -> bool {
+    match *event {
+        Event::TouchDown(source, location) => {
+            let Some((web_view, _)) = web_view_at_point(env, location) else {
+                return false;
+            };
+            if env.framework_state.uikit.ui_web_view.touch.is_none() {
+                let start_scroll = state(env, web_view).scroll;
+                env.framework_state.uikit.ui_web_view.touch = Some(Touch {
+                    source,
+                    web_view,
+                    start: location,
+                    start_scroll,
+                    dragging: false,
+                });
+            }
+            true
+        }
+        Event::TouchMove(source, location) => {
+            let Some(touch) = env
+                .framework_state
+                .uikit
+                .ui_web_view
+                .touch
+                .as_mut()
+                .filter(|touch| touch.source == source)
+            else {
+                return false;
+            };
+            let (dx, dy) = (location.0 - touch.start.0, location.1 - touch.start.1);
+            if dx.abs() > DRAG_THRESHOLD || dy.abs() > DRAG_THRESHOLD {
+                touch.dragging = true;
+            }
+            if touch.dragging {
+                let (web_view, start_scroll) = (touch.web_view, touch.start_scroll);
+                let zoom = ensure_layout(env, web_view);
+                let max_scroll = max_scroll(env, web_view, zoom);
+                state(env, web_view).scroll = (start_scroll - dy / zoom).clamp(0.0, max_scroll);
+            }
+            true
+        }
+        Event::TouchUp(source, location) => {
+            let touch = &mut env.framework_state.uikit.ui_web_view.touch;
+            if !matches!(*touch, Some(ref touch) if touch.source == source) {
+                return false;
+            }
+            let touch = touch.take().unwrap();
+            if !touch.dragging {
+                // UIKit creates and drains autorelease pools when handling
+                // events.
+                let pool: id = msg_class![env; NSAutoreleasePool new];
+                tap(env, touch.web_view, location);
+                release(env, pool);
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_links() {
+        let base = Base::Directory("/app/Game.app/help".to_string());
+        assert_eq!(
+            resolve(&base, "../credits%20page.html?x=1#Top"),
+            Some(Target::File {
+                path: "/app/Game.app/credits page.html".to_string(),
+                fragment: Some("Top".to_string()),
+            })
+        );
+        assert_eq!(
+            resolve(&base, "#section"),
+            Some(Target::Fragment("section".to_string()))
+        );
+        assert_eq!(
+            resolve(&base, "http://example.com/"),
+            Some(Target::External("http://example.com/".to_string()))
+        );
+        assert_eq!(
+            resolve(&base, "file:///app/Game.app/a.html"),
+            Some(Target::File {
+                path: "/app/Game.app/a.html".to_string(),
+                fragment: None,
+            })
+        );
+        assert_eq!(resolve(&base, "javascript:void(0)"), None);
+        assert_eq!(resolve(&Base::None, "a.html"), None);
+
+        let base = Base::Url("http://example.com/news/index.html".to_string());
+        assert_eq!(
+            resolve(&base, "item.html"),
+            Some(Target::External(
+                "http://example.com/news/item.html".to_string()
+            ))
+        );
+        assert_eq!(
+            resolve(&base, "/about"),
+            Some(Target::External("http://example.com/about".to_string()))
+        );
+    }
+}
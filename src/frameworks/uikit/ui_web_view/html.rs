@@ -0,0 +1,1345 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! A very small HTML and CSS engine for `UIWebView`.
+//!
+//! This only aims to handle the kind of local help, credits and news pages
+//! that apps ship: text with basic styling, images and links. There's no
+//! JavaScript, tables are laid out as a sequence of blocks, and CSS supports
+//! only simple selectors and a handful of properties.
+//!
+//! Parsing produces a flat list of [Item]s rather than a tree, since block
+//! boundaries and inherited styles are all the layout code needs.
+
+use crate::frameworks::uikit::overlay::Color;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Computed style of some text or an image. Lengths are in CSS pixels.
+#[derive(Clone, Debug)]
+pub struct Style {
+    pub color: Color,
+    pub size: f32,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub align: Align,
+    /// Left indentation, e.g. for lists.
+    pub indent: f32,
+    /// Link target, if this is part of a link.
+    pub href: Option<String>,
+    /// Not inherited. Only used for the page background.
+    background: Option<Color>,
+    /// `display: none`.
+    hidden: bool,
+    /// Whitespace is preserved and lines aren't wrapped.
+    pre: bool,
+}
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            color: (0.0, 0.0, 0.0, 1.0),
+            size: 16.0,
+            bold: false,
+            italic: false,
+            underline: false,
+            align: Align::Left,
+            indent: 0.0,
+            href: None,
+            background: None,
+            hidden: false,
+            pre: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Item {
+    /// Text with whitespace collapsed, unless the style is preformatted.
+    Text(String, Style),
+    Image {
+        /// Unresolved URL.
+        src: String,
+        /// From the `width` and `height` attributes.
+        width: Option<f32>,
+        height: Option<f32>,
+        /// Intrinsic size, to be filled in by the user of the document once
+        /// the image has been loaded.
+        size: Option<(f32, f32)>,
+        style: Style,
+    },
+    /// `<br>`.
+    LineBreak(Style),
+    /// `<hr>`.
+    Rule,
+    /// Start or end of a block, with its vertical margin.
+    Block(f32),
+    /// Target for fragment links, from `id` or `<a name="...">`.
+    Anchor(String),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ViewportWidth {
+    DeviceWidth,
+    Fixed(f32),
+}
+
+#[derive(Debug, Default)]
+pub struct Document {
+    pub title: Option<String>,
+    pub background: Option<Color>,
+    /// From `<meta name="viewport">`.
+    pub viewport_width: Option<ViewportWidth>,
+    pub items: Vec<Item>,
+}
+
+/// Simple selector, e.g. `p.note`, possibly with ancestors, e.g. `#nav a`.
+#[derive(Debug)]
+struct Selector {
+    /// Outermost first. The last one must match the element itself.
+    compounds: Vec<Compound>,
+    specificity: u32,
+}
+
+#[derive(Debug, Default)]
+struct Compound {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    /// Pseudo-classes like `:hover` that never match here.
+    never_matches: bool,
+}
+
+#[derive(Debug)]
+struct Rule {
+    selector: Selector,
+    declarations: Vec<(String, String)>,
+}
+
+struct Element {
+    tag: String,
+    id: Option<String>,
+    classes: Vec<String>,
+    style: Style,
+    /// Bottom margin, for block elements.
+    block_margin: Option<f32>,
+    /// Number of list items so far, for lists.
+    list_counter: u32,
+}
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "wbr",
+];
+
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "center",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "li",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+/// Elements whose contents aren't parsed as HTML.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title"];
+
+struct Parser {
+    document: Document,
+    stylesheet: Vec<Rule>,
+    stack: Vec<Element>,
+    /// Whether the text so far ends in (collapsed) whitespace, or at the start
+    /// of a line, so that more whitespace should be dropped.
+    after_space: bool,
+}
+
+/// Parse an HTML document.
+pub fn parse(html: &str) -> Document {
+    let mut parser = Parser {
+        document: Document::default(),
+        stylesheet: Vec::new(),
+        stack: Vec::new(),
+        after_space: true,
+    };
+
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(tag_start) = rest.find('<') else {
+            parser.text(rest);
+            break;
+        };
+        parser.text(&rest[..tag_start]);
+        rest = &rest[tag_start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            // Doctype or processing instruction
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+        } else if let Some(tag) = rest.strip_prefix("</") {
+            let end = tag.find('>').unwrap_or(tag.len());
+            let name = tag[..end]
+                .split(|c: char| c.is_ascii_whitespace())
+                .next()
+                .unwrap()
+                .to_ascii_lowercase();
+            parser.end_tag(&name);
+            rest = tag.get(end + 1..).unwrap_or("");
+        } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let (name, attributes, self_closing, after) = parse_tag(&rest[1..]);
+            rest = after;
+            if RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+                let (content, after) = split_raw_text(rest, &name);
+                rest = after;
+                parser.raw_text_element(&name, content);
+            } else {
+                parser.start_tag(&name, &attributes);
+                if self_closing && !VOID_ELEMENTS.contains(&name.as_str()) {
+                    parser.end_tag(&name);
+                }
+            }
+        } else {
+            parser.text("<");
+            rest = &rest[1..];
+        }
+    }
+
+    while !parser.stack.is_empty() {
+        parser.pop_element();
+    }
+    parser.document
+}
+
+/// Parse a start tag, without the initial `<`. Returns the lowercase name,
+/// the attributes, whether it ends with `/>` and the text after it.
+fn parse_tag(tag: &str) -> (String, Vec<(String, String)>, bool, &str) {
+    let name_end = tag
+        .find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+        .unwrap_or(tag.len());
+    let name = tag[..name_end].to_ascii_lowercase();
+    let mut rest = &tag[name_end..];
+    let mut attributes = Vec::new();
+    let mut self_closing = false;
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            rest = after;
+            break;
+        }
+        if let Some(after) = rest.strip_prefix("/>") {
+            self_closing = true;
+            rest = after;
+            break;
+        }
+        if let Some(after) = rest.strip_prefix('/') {
+            rest = after;
+            continue;
+        }
+
+        let attr_end = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len())
+            .max(1);
+        let attr_name = rest[..attr_end].to_ascii_lowercase();
+        rest = rest[attr_end..].trim_start();
+        let value = if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (value, after) =
+                if let Some(quote) = after.chars().next().filter(|&c| c == '"' || c == '\'') {
+                    let after = &after[1..];
+                    let end = after.find(quote).unwrap_or(after.len());
+                    (&after[..end], after.get(end + 1..).unwrap_or(""))
+                } else {
+                    let end = after
+                        .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                        .unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                };
+            rest = after;
+            decode_entities(value)
+        } else {
+            String::new()
+        };
+        attributes.push((attr_name, value));
+    }
+    (name, attributes, self_closing, rest)
+}
+
+/// Split the contents of a raw text element like `<style>` from the text
+/// after its end tag.
+fn split_raw_text<'a>(html: &'a str, name: &str) -> (&'a str, &'a str) {
+    let lower = html.to_ascii_lowercase();
+    let end_tag = format!("</{}", name);
+    let Some(end) = lower.find(&end_tag) else {
+        return (html, "");
+    };
+    let after = &html[end..];
+    (
+        &html[..end],
+        after.find('>').map_or("", |i| &after[i + 1..]),
+    )
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| &rest[1..end + 1]);
+        let c = entity.and_then(|entity| {
+            if let Some(number) = entity.strip_prefix('#') {
+                let code = if let Some(hex) = number.strip_prefix(['x', 'X']) {
+                    u32::from_str_radix(hex, 16).ok()
+                } else {
+                    number.parse().ok()
+                };
+                return code.and_then(char::from_u32);
+            }
+            Some(match entity {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => '\u{a0}',
+                "copy" => '©',
+                "reg" => '®',
+                "trade" => '™',
+                "hellip" => '…',
+                "mdash" => '—',
+                "ndash" => '–',
+                "lsquo" => '‘',
+                "rsquo" => '’',
+                "ldquo" => '“',
+                "rdquo" => '”',
+                "bull" => '•',
+                "middot" => '·',
+                "times" => '×',
+                "deg" => '°',
+                "euro" => '€',
+                "pound" => '£',
+                "yen" => '¥',
+                _ => return None,
+            })
+        });
+        if let (Some(entity), Some(c)) = (entity, c) {
+            decoded.push(c);
+            rest = &rest[entity.len() + 2..];
+        } else {
+            decoded.push('&');
+            rest = &rest[1..];
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+impl Parser {
+    fn style(&self) -> Style {
+        self.stack
+            .last()
+            .map_or_else(Style::default, |element| element.style.clone())
+    }
+
+    fn push_item(&mut self, item: Item) {
+        if matches!(self.stack.last(), Some(element) if element.style.hidden) {
+            return;
+        }
+        if matches!(item, Item::Block(_) | Item::LineBreak(_) | Item::Rule) {
+            self.after_space = true;
+        }
+        self.document.items.push(item);
+    }
+
+    fn text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let style = self.style();
+        if style.pre {
+            let text = decode_entities(text);
+            let mut lines = text.split('\n');
+            let first = lines.next().unwrap();
+            if !first.is_empty() {
+                self.push_item(Item::Text(first.replace('\t', "    "), style.clone()));
+            }
+            for line in lines {
+                self.push_item(Item::LineBreak(style.clone()));
+                if !line.is_empty() {
+                    self.push_item(Item::Text(line.replace('\t', "    "), style.clone()));
+                }
+            }
+            self.after_space = false;
+            return;
+        }
+
+        let mut collapsed = String::with_capacity(text.len());
+        for c in text.chars() {
+            if c.is_ascii_whitespace() {
+                if !self.after_space {
+                    collapsed.push(' ');
+                    self.after_space = true;
+                }
+            } else {
+                collapsed.push(c);
+                self.after_space = false;
+            }
+        }
+        if !collapsed.is_empty() {
+            self.push_item(Item::Text(decode_entities(&collapsed), style));
+        }
+    }
+
+    fn raw_text_element(&mut self, name: &str, content: &str) {
+        match name {
+            "style" => self.stylesheet.extend(parse_stylesheet(content)),
+            "title" => {
+                let title = decode_entities(content.trim());
+                self.document.title.get_or_insert(title);
+            }
+            "textarea" => {
+                // Form controls aren't supported, but showing the contents is
+                // better than nothing.
+                self.text(content);
+            }
+            _ => (),
+        }
+    }
+
+    fn start_tag(&mut self, name: &str, attributes: &[(String, String)]) {
+        let attribute = |key: &str| {
+            attributes
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let is_block = BLOCK_ELEMENTS.contains(&name) || name == "html";
+        // Some end tags are optional.
+        if let Some(top) = self.stack.last() {
+            let implied_end = match top.tag.as_str() {
+                "p" => is_block,
+                "li" => name == "li",
+                "dt" | "dd" => name == "dt" || name == "dd",
+                "td" | "th" => matches!(name, "td" | "th" | "tr"),
+                "head" => name == "body",
+                _ => false,
+            };
+            if implied_end {
+                self.pop_element();
+            }
+        }
+
+        let mut style = self.style();
+        style.background = None;
+        let em = style.size;
+        let mut block_margin = if is_block { Some(0.0) } else { None };
+        match name {
+            "b" | "strong" => style.bold = true,
+            "i" | "em" | "cite" | "var" | "dfn" | "address" => style.italic = true,
+            "u" | "ins" => style.underline = true,
+            "a" => {
+                if let Some(href) = attribute("href") {
+                    style.href = Some(href.to_string());
+                    style.color = (0.0, 0.0, 0.93, 1.0);
+                    style.underline = true;
+                }
+            }
+            "small" | "sub" | "sup" => style.size *= 0.83,
+            "big" => style.size *= 1.2,
+            "center" => style.align = Align::Center,
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let (scale, margin) = match name {
+                    "h1" => (2.0, 0.67),
+                    "h2" => (1.5, 0.83),
+                    "h3" => (1.17, 1.0),
+                    "h4" => (1.0, 1.33),
+                    "h5" => (0.83, 1.67),
+                    _ => (0.67, 2.33),
+                };
+                style.size *= scale;
+                style.bold = true;
+                block_margin = Some(style.size * margin);
+            }
+            "p" | "dl" => block_margin = Some(em),
+            "pre" => {
+                style.pre = true;
+                block_margin = Some(em);
+            }
+            "ul" | "ol" => {
+                style.indent += 40.0;
+                let nested = self
+                    .stack
+                    .iter()
+                    .any(|element| element.tag == "ul" || element.tag == "ol");
+                block_margin = Some(if nested { 0.0 } else { em });
+            }
+            "blockquote" => {
+                style.indent += 40.0;
+                block_margin = Some(em);
+            }
+            "dd" => style.indent += 40.0,
+            "th" => {
+                style.bold = true;
+                style.align = Align::Center;
+            }
+            "head" => style.hidden = true,
+            "font" => {
+                if let Some(color) = attribute("color").and_then(parse_color) {
+                    style.color = color;
+                }
+                if let Some(size) = attribute("size") {
+                    const SIZES: [f32; 7] = [10.0, 13.0, 16.0, 18.0, 24.0, 32.0, 48.0];
+                    let size = size.trim();
+                    let index: Option<i32> = if let Some(relative) = size.strip_prefix('+') {
+                        relative.parse().ok().map(|n: i32| 3 + n)
+                    } else if size.starts_with('-') {
+                        size.parse().ok().map(|n: i32| 3 + n)
+                    } else {
+                        size.parse().ok()
+                    };
+                    if let Some(index) = index {
+                        style.size = SIZES[(index.clamp(1, 7) - 1) as usize];
+                    }
+                }
+            }
+            "body" | "html" => {
+                if let Some(color) = attribute("bgcolor").and_then(parse_color) {
+                    style.background = Some(color);
+                }
+                if let Some(color) = attribute("text").and_then(parse_color) {
+                    style.color = color;
+                }
+            }
+            _ => (),
+        }
+        if let Some(align) = attribute("align") {
+            match align.to_ascii_lowercase().as_str() {
+                "left" => style.align = Align::Left,
+                "center" | "middle" => style.align = Align::Center,
+                "right" => style.align = Align::Right,
+                _ => (),
+            }
+        }
+
+        let element = Element {
+            tag: name.to_string(),
+            id: attribute("id").map(str::to_string),
+            classes: attribute("class")
+                .unwrap_or("")
+                .split_ascii_whitespace()
+                .map(str::to_string)
+                .collect(),
+            style: Style::default(),
+            block_margin,
+            list_counter: 0,
+        };
+
+        let mut matched: Vec<&Rule> = self
+            .stylesheet
+            .iter()
+            .filter(|rule| self.matches(&rule.selector, &element))
+            .collect();
+        // The sort is stable, so later rules still win among equals.
+        matched.sort_by_key(|rule| rule.selector.specificity);
+        for rule in matched {
+            for (property, value) in &rule.declarations {
+                apply_declaration(&mut style, property, value);
+            }
+        }
+        if let Some(inline) = attribute("style") {
+            for (property, value) in parse_declarations(inline) {
+                apply_declaration(&mut style, &property, &value);
+            }
+        }
+
+        let is_viewport =
+            matches!(attribute("name"), Some(n) if n.eq_ignore_ascii_case("viewport"));
+        if name == "meta" && is_viewport {
+            self.document.viewport_width = attribute("content").and_then(parse_viewport_width);
+        }
+        if (name == "body" || name == "html") && style.background.is_some() {
+            self.document.background = style.background;
+        }
+
+        let element = Element { style, ..element };
+        let hidden = element.style.hidden;
+        let anchor = element.id.clone().or_else(|| {
+            if name == "a" {
+                attribute("name").map(str::to_string)
+            } else {
+                None
+            }
+        });
+        let is_void = VOID_ELEMENTS.contains(&name);
+        let style = element.style.clone();
+        if !is_void {
+            self.stack.push(element);
+        }
+        if hidden {
+            return;
+        }
+        if let Some(margin) = block_margin {
+            self.push_item(Item::Block(margin));
+        }
+        if let Some(anchor) = anchor {
+            self.push_item(Item::Anchor(anchor));
+        }
+
+        match name {
+            "br" => self.push_item(Item::LineBreak(style)),
+            "hr" => {
+                self.push_item(Item::Block(style.size / 2.0));
+                self.push_item(Item::Rule);
+                self.push_item(Item::Block(style.size / 2.0));
+            }
+            "img" => {
+                let length = |key: &str| {
+                    attribute(key).and_then(|value| {
+                        value
+                            .trim()
+                            .trim_end_matches("px")
+                            .parse::<f32>()
+                            .ok()
+                            .filter(|&n| n > 0.0)
+                    })
+                };
+                let item = Item::Image {
+                    src: attribute("src").unwrap_or("").to_string(),
+                    width: length("width"),
+                    height: length("height"),
+                    size: None,
+                    style,
+                };
+                self.push_item(item);
+                self.after_space = false;
+            }
+            "li" => {
+                let list = self
+                    .stack
+                    .iter_mut()
+                    .rev()
+                    .find(|element| element.tag == "ul" || element.tag == "ol");
+                let marker = match list {
+                    Some(list) if list.tag == "ol" => {
+                        list.list_counter += 1;
+                        format!("{}. ", list.list_counter)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.push_item(Item::Text(
+                    marker,
+                    Style {
+                        href: None,
+                        ..style
+                    },
+                ));
+                self.after_space = true;
+            }
+            _ => (),
+        }
+    }
+
+    fn end_tag(&mut self, name: &str) {
+        if let Some(index) = self.stack.iter().rposition(|element| element.tag == name) {
+            while self.stack.len() > index {
+                self.pop_element();
+            }
+        } else if name == "br" {
+            // `</br>` is treated as `<br>` by browsers.
+            self.push_item(Item::LineBreak(self.style()));
+        }
+    }
+
+    fn pop_element(&mut self) {
+        let element = self.stack.pop().unwrap();
+        if element.style.hidden {
+            return;
+        }
+        if let Some(margin) = element.block_margin {
+            self.push_item(Item::Block(margin));
+        }
+    }
+
+    fn matches(&self, selector: &Selector, element: &Element) -> bool {
+        let (last, ancestors) = selector.compounds.split_last().unwrap();
+        if !compound_matches(last, element) {
+            return false;
+        }
+        let mut ancestors = ancestors.iter().rev().peekable();
+        for element in self.stack.iter().rev() {
+            let Some(&compound) = ancestors.peek() else {
+                break;
+            };
+            if compound_matches(compound, element) {
+                ancestors.next();
+            }
+        }
+        ancestors.peek().is_none()
+    }
+}
+
+fn compound_matches(compound: &Compound, element: &Element) -> bool {
+    !compound.never_matches
+        && (compound.tag.is_none() || compound.tag.as_ref() == Some(&element.tag))
+        && (compound.id.is_none() || compound.id == element.id)
+        && compound
+            .classes
+            .iter()
+            .all(|class| element.classes.contains(class))
+}
+
+fn parse_stylesheet(css: &str) -> Vec<Rule> {
+    // Strip comments
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = rest[start + 2..]
+            .find("*/")
+            .map_or("", |end| &rest[start + 2 + end + 2..]);
+    }
+    stripped.push_str(rest);
+
+    let mut rules = Vec::new();
+    let mut rest = stripped.as_str();
+    while let Some(open) = rest.find('{') {
+        let prelude = rest[..open].trim();
+        let after = &rest[open + 1..];
+        if prelude.starts_with('@') {
+            // At-rules like @media: skip the whole (possibly nested) block.
+            let mut depth = 1;
+            let mut end = after.len();
+            for (i, c) in after.char_indices() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = i + 1;
+                            break;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            rest = &after[end..];
+            continue;
+        }
+        let close = after.find('}').unwrap_or(after.len());
+        let declarations = parse_declarations(&after[..close]);
+        rest = after.get(close + 1..).unwrap_or("");
+
+        for selector in prelude.split(',') {
+            if let Some(selector) = parse_selector(selector) {
+                rules.push(Rule {
+                    selector,
+                    declarations: declarations.clone(),
+                });
+            }
+        }
+    }
+    rules
+}
+
+fn parse_selector(selector: &str) -> Option<Selector> {
+    let mut compounds = Vec::new();
+    let mut specificity = 0;
+    for part in selector
+        .split(|c: char| c.is_ascii_whitespace() || c == '>' || c == '+' || c == '~')
+        .filter(|part| !part.is_empty())
+    {
+        let mut compound = Compound::default();
+        // Split into pieces each starting with '.', '#' or ':'.
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        for (i, c) in part.char_indices() {
+            if i > 0 && (c == '.' || c == '#' || c == ':') && !part[..i].ends_with(':') {
+                pieces.push(&part[start..i]);
+                start = i;
+            }
+        }
+        pieces.push(&part[start..]);
+        for piece in pieces {
+            if let Some(class) = piece.strip_prefix('.') {
+                compound.classes.push(class.to_string());
+                specificity += 10;
+            } else if let Some(id) = piece.strip_prefix('#') {
+                compound.id = Some(id.to_string());
+                specificity += 100;
+            } else if let Some(pseudo) = piece.strip_prefix(':') {
+                // Links are always unvisited and nothing is ever hovered.
+                if !matches!(pseudo, "link" | ":link") {
+                    compound.never_matches = true;
+                }
+                specificity += 10;
+            } else if piece == "*" {
+            } else if piece.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                compound.tag = Some(piece.to_ascii_lowercase());
+                specificity += 1;
+            } else {
+                // Attribute selectors etc aren't supported.
+                return None;
+            }
+        }
+        compounds.push(compound);
+    }
+    if compounds.is_empty() {
+        None
+    } else {
+        Some(Selector {
+            compounds,
+            specificity,
+        })
+    }
+}
+
+fn parse_declarations(declarations: &str) -> Vec<(String, String)> {
+    declarations
+        .split(';')
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let value = value.trim();
+            let value = value.strip_suffix("!important").unwrap_or(value).trim();
+            Some((property.trim().to_ascii_lowercase(), value.to_string()))
+        })
+        .collect()
+}
+
+fn apply_declaration(style: &mut Style, property: &str, value: &str) {
+    let em = style.size;
+    match property {
+        "color" => {
+            if let Some(color) = parse_color(value) {
+                style.color = color;
+            }
+        }
+        "background-color" | "background" => {
+            if let Some(color) = value.split_ascii_whitespace().find_map(parse_color) {
+                style.background = Some(color);
+            }
+        }
+        "font-size" => {
+            if let Some(size) = parse_font_size(value, em) {
+                style.size = size;
+            }
+        }
+        "font-weight" => {
+            style.bold = match value {
+                "bold" | "bolder" => true,
+                "normal" | "lighter" => false,
+                _ => value
+                    .parse::<u32>()
+                    .map_or(style.bold, |weight| weight >= 600),
+            }
+        }
+        "font-style" => style.italic = value == "italic" || value == "oblique",
+        "font" => {
+            for token in value.split_ascii_whitespace() {
+                match token {
+                    "bold" | "bolder" => style.bold = true,
+                    "italic" | "oblique" => style.italic = true,
+                    _ => {
+                        let size = token.split('/').next().unwrap();
+                        if let Some(size) = parse_font_size(size, em) {
+                            style.size = size;
+                        }
+                    }
+                }
+            }
+        }
+        "text-align" => match value {
+            "left" | "justify" | "start" => style.align = Align::Left,
+            "center" => style.align = Align::Center,
+            "right" | "end" => style.align = Align::Right,
+            _ => (),
+        },
+        "text-decoration" => style.underline = value.contains("underline"),
+        "display" => style.hidden = value == "none",
+        "white-space" => style.pre = value == "pre",
+        _ => (),
+    }
+}
+
+/// Parse a CSS length that's relative to a font size.
+fn parse_length(value: &str, em: f32) -> Option<f32> {
+    let value = value.trim().to_ascii_lowercase();
+    let (number, scale) = if let Some(number) = value.strip_suffix("px") {
+        (number, 1.0)
+    } else if let Some(number) = value.strip_suffix("pt") {
+        (number, 4.0 / 3.0)
+    } else if let Some(number) = value.strip_suffix("em") {
+        (number, em)
+    } else if let Some(number) = value.strip_suffix('%') {
+        (number, em / 100.0)
+    } else {
+        (value.as_str(), 1.0)
+    };
+    let number: f32 = number.trim().parse().ok()?;
+    Some(number * scale).filter(|n| n.is_finite() && *n >= 0.0)
+}
+
+fn parse_font_size(value: &str, em: f32) -> Option<f32> {
+    Some(match value.trim() {
+        "xx-small" => 9.0,
+        "x-small" => 10.0,
+        "small" => 13.0,
+        "medium" => 16.0,
+        "large" => 18.0,
+        "x-large" => 24.0,
+        "xx-large" => 32.0,
+        "smaller" => em / 1.2,
+        "larger" => em * 1.2,
+        value => parse_length(value, em)?,
+    })
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u32> = hex.chars().map(|c| c.to_digit(16)).collect::<Option<_>>()?;
+        let (r, g, b) = match digits.len() {
+            3 => (digits[0] * 17, digits[1] * 17, digits[2] * 17),
+            6 => (
+                digits[0] * 16 + digits[1],
+                digits[2] * 16 + digits[3],
+                digits[4] * 16 + digits[5],
+            ),
+            _ => return None,
+        };
+        return Some((r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0));
+    }
+    if let Some(args) = value
+        .strip_prefix("rgba(")
+        .or_else(|| value.strip_prefix("rgb("))
+    {
+        let args: Vec<&str> = args.strip_suffix(')')?.split(',').map(str::trim).collect();
+        if args.len() < 3 {
+            return None;
+        }
+        let channel = |arg: &str| -> Option<f32> {
+            if let Some(percent) = arg.strip_suffix('%') {
+                Some(percent.parse::<f32>().ok()? / 100.0)
+            } else {
+                Some(arg.parse::<f32>().ok()? / 255.0)
+            }
+        };
+        let alpha = match args.get(3) {
+            Some(alpha) => alpha.parse().ok()?,
+            None => 1.0,
+        };
+        return Some((
+            channel(args[0])?.clamp(0.0, 1.0),
+            channel(args[1])?.clamp(0.0, 1.0),
+            channel(args[2])?.clamp(0.0, 1.0),
+            f32::clamp(alpha, 0.0, 1.0),
+        ));
+    }
+    let (r, g, b) = match value.as_str() {
+        "transparent" => return Some((0.0, 0.0, 0.0, 0.0)),
+        "black" => (0, 0, 0),
+        "white" => (255, 255, 255),
+        "red" => (255, 0, 0),
+        "lime" => (0, 255, 0),
+        "green" => (0, 128, 0),
+        "blue" => (0, 0, 255),
+        "yellow" => (255, 255, 0),
+        "cyan" | "aqua" => (0, 255, 255),
+        "magenta" | "fuchsia" => (255, 0, 255),
+        "gray" | "grey" => (128, 128, 128),
+        "silver" => (192, 192, 192),
+        "maroon" => (128, 0, 0),
+        "olive" => (128, 128, 0),
+        "navy" => (0, 0, 128),
+        "purple" => (128, 0, 128),
+        "teal" => (0, 128, 128),
+        "orange" => (255, 165, 0),
+        _ => return None,
+    };
+    Some((r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0))
+}
+
+fn parse_viewport_width(content: &str) -> Option<ViewportWidth> {
+    content.split([',', ';']).find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        if key.trim() != "width" {
+            return None;
+        }
+        match value.trim() {
+            "device-width" => Some(ViewportWidth::DeviceWidth),
+            value => value
+                .parse()
+                .ok()
+                .filter(|&width: &f32| width > 0.0)
+                .map(ViewportWidth::Fixed),
+        }
+    })
+}
+
+pub enum FragmentKind {
+    Text(String),
+    /// Index into [Document::items].
+    Image(usize),
+    Rule,
+}
+
+/// Something positioned on the page.
+pub struct Fragment {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub kind: FragmentKind,
+    pub style: Style,
+}
+
+pub struct Layout {
+    /// Total height of the page.
+    pub height: f32,
+    pub fragments: Vec<Fragment>,
+    pub anchors: Vec<(String, f32)>,
+}
+
+/// Body margin.
+const PAGE_MARGIN: f32 = 8.0;
+
+struct LineBuilder<'a> {
+    width: f32,
+    y: f32,
+    /// Vertical margin to add before the next line, collapsed with any others.
+    pending_margin: f32,
+    line: Vec<Fragment>,
+    line_x: f32,
+    line_indent: f32,
+    line_align: Align,
+    layout: &'a mut Layout,
+}
+
+impl LineBuilder<'_> {
+    fn start_line_if_needed(&mut self, style: &Style) {
+        if self.line.is_empty() {
+            self.y += self.pending_margin;
+            self.pending_margin = 0.0;
+            self.line_x = 0.0;
+            self.line_indent = style.indent;
+            self.line_align = style.align;
+        }
+    }
+
+    fn available_width(&self) -> f32 {
+        (self.width - self.line_indent).max(0.0)
+    }
+
+    fn push(&mut self, fragment: Fragment) {
+        self.line_x += fragment.width;
+        self.line.push(fragment);
+    }
+
+    fn finish_line(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+        let height = self
+            .line
+            .iter()
+            .fold(0f32, |height, fragment| height.max(fragment.height));
+        let offset = PAGE_MARGIN
+            + self.line_indent
+            + match self.line_align {
+                Align::Left => 0.0,
+                Align::Center => (self.available_width() - self.line_x).max(0.0) / 2.0,
+                Align::Right => (self.available_width() - self.line_x).max(0.0),
+            };
+        for mut fragment in self.line.drain(..) {
+            // Everything sits on the bottom of the line.
+            fragment.x += offset;
+            fragment.y = self.y + height - fragment.height;
+            self.layout.fragments.push(fragment);
+        }
+        self.y += height;
+    }
+
+    fn add_margin(&mut self, margin: f32) {
+        self.finish_line();
+        self.pending_margin = self.pending_margin.max(margin);
+    }
+}
+
+/// Lay out a document for a page width. `measure` returns the width and height
+/// of a piece of text in a style, which must be non-empty.
+pub fn lay_out(
+    document: &Document,
+    page_width: f32,
+    measure: &mut dyn FnMut(&Style, &str) -> (f32, f32),
+) -> Layout {
+    let mut layout = Layout {
+        height: 0.0,
+        fragments: Vec::new(),
+        anchors: Vec::new(),
+    };
+    let mut builder = LineBuilder {
+        width: (page_width - PAGE_MARGIN * 2.0).max(0.0),
+        y: 0.0,
+        // The body's margin collapses with those of the blocks inside it.
+        pending_margin: PAGE_MARGIN,
+        line: Vec::new(),
+        line_x: 0.0,
+        line_indent: 0.0,
+        line_align: Align::Left,
+        layout: &mut layout,
+    };
+    // Whether there's a space to put before the next word on the line.
+    let mut space_before = false;
+
+    for (index, item) in document.items.iter().enumerate() {
+        match item {
+            Item::Text(text, style) if style.pre => {
+                builder.start_line_if_needed(style);
+                let (width, height) = measure(style, text);
+                builder.push(Fragment {
+                    x: builder.line_x,
+                    y: 0.0,
+                    width,
+                    height,
+                    kind: FragmentKind::Text(text.clone()),
+                    style: style.clone(),
+                });
+            }
+            Item::Text(text, style) => {
+                let space_width = measure(style, "x x").0 - measure(style, "xx").0;
+                for (i, word) in text.split(' ').enumerate() {
+                    if i > 0 {
+                        space_before = true;
+                    }
+                    if word.is_empty() {
+                        continue;
+                    }
+                    let (width, height) = measure(style, word);
+                    let space = if space_before && !builder.line.is_empty() {
+                        space_width
+                    } else {
+                        0.0
+                    };
+                    if !builder.line.is_empty()
+                        && builder.line_x + space + width > builder.available_width()
+                    {
+                        builder.finish_line();
+                    }
+                    builder.start_line_if_needed(style);
+                    if space_before && !builder.line.is_empty() {
+                        builder.line_x += space_width;
+                    }
+                    space_before = false;
+                    builder.push(Fragment {
+                        x: builder.line_x,
+                        y: 0.0,
+                        width,
+                        height,
+                        kind: FragmentKind::Text(word.to_string()),
+                        style: style.clone(),
+                    });
+                }
+            }
+            Item::Image {
+                width: attr_width,
+                height: attr_height,
+                size,
+                style,
+                ..
+            } => {
+                let (width, height) = match (*attr_width, *attr_height, *size) {
+                    (Some(width), Some(height), _) => (width, height),
+                    (Some(width), None, Some((w, h))) if w > 0.0 => (width, width * h / w),
+                    (None, Some(height), Some((w, h))) if h > 0.0 => (height * w / h, height),
+                    (Some(width), None, _) => (width, width),
+                    (None, Some(height), _) => (height, height),
+                    (None, None, Some(size)) => size,
+                    (None, None, None) => continue,
+                };
+                if space_before && !builder.line.is_empty() {
+                    builder.line_x += measure(style, "x x").0 - measure(style, "xx").0;
+                }
+                space_before = false;
+                builder.start_line_if_needed(style);
+                // Like `max-width: 100%`, which pages for phones usually want.
+                let available = builder.available_width();
+                let (width, height) = if width > available && width > 0.0 {
+                    (available, height * available / width)
+                } else {
+                    (width, height)
+                };
+                if !builder.line.is_empty() && builder.line_x + width > available {
+                    builder.finish_line();
+                    builder.start_line_if_needed(style);
+                }
+                builder.push(Fragment {
+                    x: builder.line_x,
+                    y: 0.0,
+                    width,
+                    height,
+                    kind: FragmentKind::Image(index),
+                    style: style.clone(),
+                });
+            }
+            Item::LineBreak(style) => {
+                if builder.line.is_empty() {
+                    builder.y += builder.pending_margin + measure(style, "X").1;
+                    builder.pending_margin = 0.0;
+                } else {
+                    builder.finish_line();
+                }
+                space_before = false;
+            }
+            Item::Rule => {
+                builder.finish_line();
+                builder.start_line_if_needed(&Style::default());
+                let width = builder.available_width();
+                builder.push(Fragment {
+                    x: 0.0,
+                    y: 0.0,
+                    width,
+                    height: 1.0,
+                    kind: FragmentKind::Rule,
+                    style: Style {
+                        color: (0.5, 0.5, 0.5, 1.0),
+                        ..Style::default()
+                    },
+                });
+                builder.finish_line();
+            }
+            Item::Block(margin) => {
+                builder.add_margin(*margin);
+                space_before = false;
+            }
+            Item::Anchor(name) => {
+                let y = if builder.line.is_empty() {
+                    builder.y + builder.pending_margin
+                } else {
+                    builder.y
+                };
+                builder.layout.anchors.push((name.clone(), y));
+            }
+        }
+    }
+    builder.finish_line();
+    layout.height = builder.y + builder.pending_margin.max(PAGE_MARGIN);
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(document: &Document) -> Vec<&str> {
+        document
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Text(text, _) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_text_and_styles() {
+        let document = parse(
+            "<!DOCTYPE html><html><head><title>Help &amp; Info</title>\
+             <style>.note { color: #f00 } p b { font-size: 20px }</style></head>\
+             <body bgcolor=white><p>Hello,\n   <b>world</b>!</p>\
+             <p class=note>Red&nbsp;text<script>ignored()</script></p></body></html>",
+        );
+        assert_eq!(document.title.as_deref(), Some("Help & Info"));
+        assert_eq!(document.background, Some((1.0, 1.0, 1.0, 1.0)));
+        assert_eq!(texts(&document), ["Hello, ", "world", "!", "Red\u{a0}text"]);
+        let styles: Vec<&Style> = document
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Text(_, style) => Some(style),
+                _ => None,
+            })
+            .collect();
+        assert!(!styles[0].bold && styles[1].bold);
+        assert_eq!(styles[1].size, 20.0);
+        assert_eq!(styles[3].color, (1.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn parse_lists_and_links() {
+        let document = parse(
+            "<ol><li>One<li><a href=\"two.html#top\">Two</a></ol>\
+             <ul><li style=\"display: none\">Hidden</li><li>Bullet</ul>",
+        );
+        assert_eq!(
+            texts(&document),
+            ["1. ", "One", "2. ", "Two", "• ", "Bullet"]
+        );
+        let link = document.items.iter().find_map(|item| match item {
+            Item::Text(text, style) if text == "Two" => Some(style),
+            _ => None,
+        });
+        assert_eq!(link.unwrap().href.as_deref(), Some("two.html#top"));
+        assert_eq!(link.unwrap().indent, 40.0);
+    }
+
+    #[test]
+    fn lay_out_wraps_words() {
+        let document = parse("<p>aaaa bbbb cccc</p><p align=right>dd</p>");
+        let mut measure =
+            |style: &Style, text: &str| (text.chars().count() as f32 * 10.0, style.size);
+        // Room for two words per line.
+        let layout = lay_out(&document, 16.0 + 100.0, &mut measure);
+        let positions: Vec<(f32, f32)> = layout
+            .fragments
+            .iter()
+            .map(|fragment| (fragment.x, fragment.y))
+            .collect();
+        assert_eq!(
+            positions,
+            [(8.0, 16.0), (58.0, 16.0), (8.0, 32.0), (88.0, 64.0)]
+        );
+        assert_eq!(layout.height, 64.0 + 16.0 + 16.0);
+    }
+}
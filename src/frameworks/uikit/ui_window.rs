@@ -5,7 +5,17 @@
  */
 //! `UIWindow`.
 
-use crate::objc::{objc_classes, ClassExports};
+use super::ui_event::{self, EventKind, UIEventSubtypeMotionShake};
+use super::ui_view::UIViewHostObject;
+use super::{ui_responder, ui_touch};
+use crate::objc::{id, msg, msg_class, nil, objc_classes, ClassExports};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// Weak reference.
+    key_window: Option<id>,
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -13,8 +23,103 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation UIWindow: UIView
 
-// TODO
+- (())makeKeyAndVisible {
+    () = msg![env; this setHidden:false];
+    () = msg![env; this makeKeyWindow];
+}
+- (())makeKeyWindow {
+    env.framework_state.uikit.ui_window.key_window = Some(this);
+}
+- (bool)isKeyWindow {
+    env.framework_state.uikit.ui_window.key_window == Some(this)
+}
+
+- (id)nextResponder {
+    msg_class![env; UIApplication sharedApplication]
+}
+
+- (())sendEvent:(id)event { // UIEvent*
+    match ui_event::kind(env, event) {
+        EventKind::Touches => {
+            for touch in ui_touch::changed_touches(env, event) {
+                let window: id = msg![env; touch window];
+                if window == this {
+                    ui_touch::deliver_touch(env, touch, event);
+                }
+            }
+        }
+        EventKind::Motion { ended } => {
+            let responder = ui_responder::first_responder(env).unwrap_or(this);
+            send_motion(env, responder, ended, event);
+        }
+        EventKind::Key { .. } => {
+            log_dbg!("Key event {:?} not delivered to {:?}", event, this);
+        }
+    }
+}
 
 @end
 
 };
+
+/// For use by `UIApplication`: get the key window, if any.
+pub(super) fn key_window(env: &mut Environment) -> Option<id> {
+    env.framework_state.uikit.ui_window.key_window
+}
+
+/// For use by the compositor: get the visible windows, back to front (the key
+/// window is in front).
+pub fn visible_windows(env: &mut Environment) -> Vec<id> {
+    let window_class = env.objc.get_known_class("UIWindow", &mut env.mem);
+    let key_window = key_window(env);
+    let mut windows = Vec::new();
+    let mut key_window_visible = false;
+    for view in env.framework_state.uikit.ui_view.views.clone() {
+        let host_object = env.objc.borrow::<UIViewHostObject>(view);
+        if host_object.hidden || host_object.superview != nil {
+            continue;
+        }
+        if !msg![env; view isKindOfClass:window_class] {
+            continue;
+        }
+        if Some(view) == key_window {
+            key_window_visible = true;
+        } else {
+            windows.push(view);
+        }
+    }
+    if key_window_visible {
+        windows.push(key_window.unwrap());
+    }
+    windows
+}
+
+/// For use by `UIView`: stop a window being the key window, if it is one.
+pub(super) fn resign_key_window(env: &mut Environment, window: id) {
+    let state = &mut env.framework_state.uikit.ui_window;
+    if state.key_window == Some(window) {
+        state.key_window = None;
+    }
+}
+
+/// Send `motionBegan:withEvent:` or `motionEnded:withEvent:` to a responder.
+pub(super) fn send_motion(env: &mut Environment, responder: id, ended: bool, event: id) {
+    let motion = UIEventSubtypeMotionShake;
+    if ended {
+        log_dbg!(
+            "Sending [{:?} motionEnded:{:?} withEvent:{:?}]",
+            responder,
+            motion,
+            event
+        );
+        () = msg![env; responder motionEnded:motion withEvent:event];
+    } else {
+        log_dbg!(
+            "Sending [{:?} motionBegan:{:?} withEvent:{:?}]",
+            responder,
+            motion,
+            event
+        );
+        () = msg![env; responder motionBegan:motion withEvent:event];
+    }
+}
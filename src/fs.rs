@@ -19,9 +19,16 @@
 //!
 //! Directories only need a corresponding directory in the host filesystem if
 //! they are writeable (i.e. if new files can be created in them).
+//!
+//! The timestamps of the host files for the app bundle are not meaningful
+//! (they might be from when the app was extracted, or from when it was built),
+//! so read-only nodes report the app's "install date" as their creation and
+//! modification time. This is recorded in the sandbox directory the first time
+//! the app is run, so it stays consistent between runs.
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 #[derive(Debug)]
 enum FsNode {
@@ -85,7 +92,11 @@ impl FsNode {
         }
     }
     fn with_child(mut self, name: &str, child: FsNode) -> Self {
-        let FsNode::Directory { ref mut children, writeable: _ } = self else {
+        let FsNode::Directory {
+            ref mut children,
+            writeable: _,
+        } = self
+        else {
             panic!();
         };
         assert!(children.insert(String::from(name), child).is_none());
@@ -276,16 +287,56 @@ fn handle_open_err<T>(open_result: std::io::Result<T>, host_path: &Path) -> T {
     }
 }
 
+/// Metadata for a file or directory in the guest filesystem, see
+/// [Fs::metadata].
+#[derive(Debug)]
+pub struct GuestMetadata {
+    pub is_dir: bool,
+    pub created: SystemTime,
+    pub modified: SystemTime,
+}
+
+/// Read the app's install date from the sandbox directory, or record the
+/// current time as the install date if there is none yet.
+fn get_or_create_install_date(sandbox_host_path: &Path) -> SystemTime {
+    let install_date_path = sandbox_host_path.join("install_date.txt");
+
+    if let Ok(contents) = std::fs::read_to_string(&install_date_path) {
+        if let Ok(secs) = contents.trim().parse::<u64>() {
+            return SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        }
+        log!(
+            "Warning: {:?} is not a valid install date, replacing it",
+            install_date_path
+        );
+    }
+
+    // Whole seconds, so that the value doesn't change after a round-trip.
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    if let Err(e) = std::fs::write(&install_date_path, format!("{}\n", secs)) {
+        log!(
+            "Warning: could not record install date at {:?}: {:?}",
+            install_date_path,
+            e
+        );
+    }
+    SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+}
+
 /// The type that owns the guest filesystem and provides accessors for it.
 #[derive(Debug)]
 pub struct Fs {
     root: FsNode,
     current_directory: GuestPathBuf,
     home_directory: GuestPathBuf,
+    install_date: SystemTime,
 }
 impl Fs {
     /// Construct a filesystem containing a home directory for the app, its
-    /// bundle and documents, and the bundled shared libraries. Returns the new
+    /// bundle, documents and preferences, and the bundled shared libraries. Returns the new
     /// filesystem and the guest path of the bundle.
     ///
     /// The `bundle_dir_name` argument will be used as the name of the bundle
@@ -296,8 +347,8 @@ impl Fs {
     ///
     /// The `bundle_id` argument should be some value that uniquely identifies
     /// the app. This will be used to construct the host path for the app's
-    /// sandbox directory, where documents can be stored. A directory will be
-    /// created at that path if it does not already exist.
+    /// sandbox directory, where documents and preferences can be stored.
+    /// Directories will be created at that path if they do not already exist.
     pub fn new(
         bundle_host_path: &Path,
         bundle_dir_name: String,
@@ -310,16 +361,20 @@ impl Fs {
 
         let bundle_guest_path = home_directory.join(&bundle_dir_name);
 
-        let documents_host_path = Path::new("touchHLE_sandbox")
-            .join(bundle_id)
-            .join("Documents");
-        if let Err(e) = std::fs::create_dir_all(&documents_host_path) {
-            panic!(
-                "Could not create documents directory for app at {:?}: {:?}",
-                documents_host_path, e
-            );
+        let sandbox_host_path = Path::new("touchHLE_sandbox").join(bundle_id);
+        let documents_host_path = sandbox_host_path.join("Documents");
+        let preferences_host_path = sandbox_host_path.join("Library/Preferences");
+        for host_path in [&documents_host_path, &preferences_host_path] {
+            if let Err(e) = std::fs::create_dir_all(host_path) {
+                panic!(
+                    "Could not create directory for app at {:?}: {:?}",
+                    host_path, e
+                );
+            }
         }
 
+        let install_date = get_or_create_install_date(&sandbox_host_path);
+
         // Some Free Software libraries are bundled with touchHLE.
         let dylibs_host_path = Path::new("touchHLE_dylibs");
         let usr_lib = FsNode::dir()
@@ -360,6 +415,16 @@ impl Fs {
                                         /* writeable: */ true,
                                     ),
                                 ),
+                                (
+                                    "Library".to_string(),
+                                    FsNode::dir().with_child(
+                                        "Preferences",
+                                        FsNode::from_host_dir(
+                                            &preferences_host_path,
+                                            /* writeable: */ true,
+                                        ),
+                                    ),
+                                ),
                             ]),
                             writeable: None,
                         },
@@ -375,6 +440,7 @@ impl Fs {
                 root,
                 current_directory,
                 home_directory,
+                install_date,
             },
             bundle_guest_path,
         )
@@ -389,7 +455,11 @@ impl Fs {
     fn lookup_node(&self, path: &GuestPath) -> Option<&FsNode> {
         let mut node = &self.root;
         for component in resolve_path(path, Some(&self.current_directory)) {
-            let FsNode::Directory { children, writeable: _ } = node else {
+            let FsNode::Directory {
+                children,
+                writeable: _,
+            } = node
+            else {
                 return None;
             };
             node = children.get(component)?
@@ -407,7 +477,11 @@ impl Fs {
 
         let mut parent = &mut self.root;
         for &component in parent_components {
-            let FsNode::Directory { children, writeable: _ } = parent else {
+            let FsNode::Directory {
+                children,
+                writeable: _,
+            } = parent
+            else {
                 return None;
             };
             parent = children.get_mut(component)?
@@ -421,14 +495,66 @@ impl Fs {
         matches!(self.lookup_node(path), Some(FsNode::File { .. }))
     }
 
+    /// Get the host path of a file in the guest filesystem, and whether it's
+    /// writeable. This is only for host libraries that must open files by
+    /// path themselves (like SQLite, which also creates journal files next to
+    /// its databases). Everything else should use [Self::open] and friends.
+    pub fn host_file_path(&self, path: &GuestPath) -> Option<(&Path, bool)> {
+        match self.lookup_node(path)? {
+            FsNode::File {
+                host_path,
+                writeable,
+            } => Some((host_path, *writeable)),
+            FsNode::Directory { .. } => None,
+        }
+    }
+
+    /// Like [std::fs::metadata] but for the guest filesystem. Returns [None]
+    /// if the node doesn't exist.
+    pub fn metadata(&self, path: &GuestPath) -> Option<GuestMetadata> {
+        let (is_dir, host_path) = match self.lookup_node(path)? {
+            FsNode::File {
+                host_path,
+                writeable,
+            } => (false, writeable.then_some(host_path)),
+            FsNode::Directory { writeable, .. } => (true, writeable.as_ref()),
+        };
+
+        // Only writeable nodes can have been changed since the app was
+        // installed, so only their host timestamps are meaningful.
+        let host_times = host_path.and_then(|host_path| {
+            let metadata = std::fs::metadata(host_path).ok()?;
+            let modified = metadata.modified().ok()?;
+            let created = metadata.created().unwrap_or(modified);
+            Some((created, modified))
+        });
+        let (created, modified) = host_times.unwrap_or((self.install_date, self.install_date));
+
+        Some(GuestMetadata {
+            is_dir,
+            created,
+            modified,
+        })
+    }
+
+    /// Like [std::fs::read_dir] but for the guest filesystem, and only
+    /// returning the names of the entries, in no particular order.
+    pub fn read_dir_names(&self, path: &GuestPath) -> Result<Vec<String>, ()> {
+        let Some(FsNode::Directory { children, .. }) = self.lookup_node(path) else {
+            return Err(());
+        };
+        Ok(children.keys().cloned().collect())
+    }
+
     /// Like [std::fs::read] but for the guest filesystem.
     pub fn read<P: AsRef<GuestPath>>(&self, path: P) -> Result<Vec<u8>, ()> {
         let node = self.lookup_node(path.as_ref()).ok_or(())?;
         let FsNode::File {
             host_path,
             writeable: _,
-        } = node else {
-            return Err(())
+        } = node
+        else {
+            return Err(());
         };
         Ok(handle_open_err(std::fs::read(host_path), host_path))
     }
@@ -440,8 +566,9 @@ impl Fs {
         let FsNode::File {
             host_path,
             writeable: _,
-        } = node else {
-            return Err(())
+        } = node
+        else {
+            return Err(());
         };
         Ok(handle_open_err(std::fs::File::open(host_path), host_path))
     }
@@ -467,7 +594,8 @@ impl Fs {
         let FsNode::Directory {
             children,
             writeable: dir_host_path,
-        } = parent_node else {
+        } = parent_node
+        else {
             return Err(());
         };
 
@@ -477,7 +605,8 @@ impl Fs {
             let FsNode::File {
                 host_path,
                 writeable,
-            } = existing_file else {
+            } = existing_file
+            else {
                 return Err(());
             };
             if !writeable && (append || write) {
@@ -503,7 +632,10 @@ impl Fs {
         }
 
         let Some(dir_host_path) = dir_host_path else {
-            log!("Warning: attempt to create file at path {:?}, but directory is read-only", path);
+            log!(
+                "Warning: attempt to create file at path {:?}, but directory is read-only",
+                path
+            );
             return Err(());
         };
 
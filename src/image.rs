@@ -3,17 +3,24 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! Image decoding. Currently only supports PNG.
+//! Image decoding. Supports PNG, JPEG, GIF (first frame only) and BMP.
 //!
 //! Implemented as a wrapper around the C library stb_image, since it supports
 //! "CgBI" PNG files (an Apple proprietary extension used in iPhone OS apps).
+//!
+//! stb_image doesn't know about EXIF, so the orientation of JPEG photos is
+//! handled here: the pixels are rotated/flipped on load so that the image is
+//! always upright.
+//!
+//! stb_image's zlib decoder (which it has for PNG) is also exposed here, see
+//! [inflate].
 
 use std::ffi::{c_int, c_uchar};
 
 use touchHLE_stb_image_wrapper::*;
 
 pub struct Image {
-    pixels: *mut c_uchar,
+    pixels: Vec<u8>,
     dimensions: (u32, u32),
 }
 
@@ -25,7 +32,7 @@ impl Image {
         let mut y: c_int = 0;
         let mut _channels_in_file: c_int = 0;
 
-        let pixels = unsafe {
+        let pixels: *mut c_uchar = unsafe {
             stbi_convert_iphone_png_to_rgb(1);
             stbi_set_unpremultiply_on_load(1);
             stbi_load_from_memory(
@@ -44,29 +51,202 @@ impl Image {
         let width: u32 = x.try_into().unwrap();
         let height: u32 = y.try_into().unwrap();
 
-        Ok(Image {
+        let pixels = unsafe {
+            let owned =
+                std::slice::from_raw_parts(pixels, width as usize * height as usize * 4).to_vec();
+            stbi_image_free(pixels.cast());
+            owned
+        };
+
+        let image = Image {
             pixels,
             dimensions: (width, height),
+        };
+        Ok(match exif_orientation(bytes) {
+            Some(orientation) => image.oriented(orientation),
+            None => image,
         })
     }
 
+    /// Wrap pixels that are already decoded (8 bits per channel RGBA, not
+    /// premultiplied, top row first).
+    pub fn from_pixels(pixels: Vec<u8>, dimensions: (u32, u32)) -> Image {
+        assert_eq!(
+            pixels.len(),
+            dimensions.0 as usize * dimensions.1 as usize * 4
+        );
+        Image { pixels, dimensions }
+    }
+
     pub fn dimensions(&self) -> (u32, u32) {
         self.dimensions
     }
 
     /// Get image data as bytes (8 bits per channel RGBA)
     pub fn pixels(&self) -> &[u8] {
-        unsafe {
-            std::slice::from_raw_parts(
-                self.pixels,
-                self.dimensions.0 as usize * self.dimensions.1 as usize * 4,
-            )
+        &self.pixels
+    }
+
+    /// Transform the image so that an image stored with a particular EXIF
+    /// orientation (1 to 8) is upright.
+    fn oriented(self, orientation: u16) -> Image {
+        let (width, height) = self.dimensions;
+        let (w, h) = (width as usize, height as usize);
+        // Maps a destination pixel to a source pixel.
+        let source: fn(usize, usize, usize, usize) -> (usize, usize) = match orientation {
+            2 => |x, y, w, _h| (w - 1 - x, y),        // mirrored horizontally
+            3 => |x, y, w, h| (w - 1 - x, h - 1 - y), // rotated 180°
+            4 => |x, y, _w, h| (x, h - 1 - y),        // mirrored vertically
+            5 => |x, y, _w, _h| (y, x),               // transposed
+            6 => |x, y, _w, h| (y, h - 1 - x),        // rotated 90° clockwise
+            7 => |x, y, w, h| (w - 1 - y, h - 1 - x), // transversed
+            8 => |x, y, w, _h| (w - 1 - y, x),        // rotated 90° anti-clockwise
+            _ => return self,
+        };
+        let (new_width, new_height) = if orientation >= 5 {
+            (height, width)
+        } else {
+            (width, height)
+        };
+
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for y in 0..new_height as usize {
+            for x in 0..new_width as usize {
+                let (sx, sy) = source(x, y, w, h);
+                let idx = (sy * w + sx) * 4;
+                pixels.extend_from_slice(&self.pixels[idx..idx + 4]);
+            }
         }
+        Image {
+            pixels,
+            dimensions: (new_width, new_height),
+        }
+    }
+}
+
+/// Decompress zlib-wrapped (`has_header`) or raw DEFLATE data.
+pub fn inflate(bytes: &[u8], has_header: bool) -> Result<Vec<u8>, ()> {
+    let len: c_int = bytes.len().try_into().map_err(|_| ())?;
+    let initial_size = len.saturating_mul(4).max(1024);
+    let mut out_len: c_int = 0;
+    let out = unsafe {
+        stbi_zlib_decode_malloc_guesssize_headerflag(
+            bytes.as_ptr().cast(),
+            len,
+            initial_size,
+            &mut out_len,
+            has_header as c_int,
+        )
+    };
+    if out.is_null() {
+        return Err(());
     }
+    let owned = unsafe {
+        let owned = std::slice::from_raw_parts(out.cast::<u8>(), out_len as usize).to_vec();
+        stbi_image_free(out.cast());
+        owned
+    };
+    Ok(owned)
 }
 
-impl Drop for Image {
-    fn drop(&mut self) {
-        unsafe { stbi_image_free(self.pixels.cast()) }
+/// Find the orientation tag in a JPEG file's EXIF data, if there is one.
+fn exif_orientation(bytes: &[u8]) -> Option<u16> {
+    // SOI marker
+    if bytes.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut pos = 2;
+    loop {
+        let &[0xFF, marker, len_hi, len_lo] = bytes.get(pos..pos + 4)? else {
+            return None;
+        };
+        // SOS: the image data follows, so there can't be any more metadata.
+        if marker == 0xDA {
+            return None;
+        }
+        let len = u16::from_be_bytes([len_hi, len_lo]) as usize;
+        let segment = bytes.get(pos + 4..pos + 2 + len)?;
+        // APP1
+        if marker == 0xE1 {
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                return tiff_orientation(tiff);
+            }
+        }
+        pos += 2 + len;
+    }
+}
+
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(..2)? {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes = tiff.get(offset..offset + 2)?.try_into().unwrap();
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        let bytes = tiff.get(offset..offset + 4)?.try_into().unwrap();
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+
+    if u16_at(2)? != 42 {
+        return None;
+    }
+    let ifd0 = u32_at(4)? as usize;
+    let entry_count = u16_at(ifd0)? as usize;
+    for i in 0..entry_count {
+        let entry = ifd0 + 2 + i * 12;
+        const ORIENTATION_TAG: u16 = 0x0112;
+        const SHORT_TYPE: u16 = 3;
+        if u16_at(entry)? == ORIENTATION_TAG && u16_at(entry + 2)? == SHORT_TYPE {
+            return u16_at(entry + 8);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exif_orientation_parsing() {
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1, 0x00, 0x22];
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(b"MM\x00\x2a\x00\x00\x00\x08");
+        jpeg.extend_from_slice(&[0x00, 0x01]);
+        jpeg.extend_from_slice(&[0x01, 0x12, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01]);
+        jpeg.extend_from_slice(&[0x00, 0x06, 0x00, 0x00]);
+        jpeg.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        jpeg.extend_from_slice(&[0xFF, 0xDA]);
+        assert_eq!(exif_orientation(&jpeg), Some(6));
+        assert_eq!(exif_orientation(&[0x89, b'P', b'N', b'G']), None);
+    }
+
+    #[test]
+    fn orientation_transform() {
+        // 2×1 image: a red pixel followed by a blue pixel.
+        let image = || Image {
+            pixels: vec![255, 0, 0, 255, 0, 0, 255, 255],
+            dimensions: (2, 1),
+        };
+        let rotated = image().oriented(6);
+        assert_eq!(rotated.dimensions(), (1, 2));
+        assert_eq!(rotated.pixels(), &[255, 0, 0, 255, 0, 0, 255, 255]);
+        let rotated = image().oriented(8);
+        assert_eq!(rotated.dimensions(), (1, 2));
+        assert_eq!(rotated.pixels(), &[0, 0, 255, 255, 255, 0, 0, 255]);
+        let mirrored = image().oriented(2);
+        assert_eq!(mirrored.pixels(), &[0, 0, 255, 255, 255, 0, 0, 255]);
     }
 }
@@ -4,6 +4,9 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 #define STB_IMAGE_IMPLEMENTATION
-#define STB_ONLY_PNG
-#define STB_NO_STDIO
+#define STBI_ONLY_PNG
+#define STBI_ONLY_JPEG
+#define STBI_ONLY_GIF
+#define STBI_ONLY_BMP
+#define STBI_NO_STDIO
 #include "../../../vendor/stb/stb_image.h"
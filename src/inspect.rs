@@ -0,0 +1,158 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Inspecting an app without running it (`--inspect`), so that users can get
+//! an idea of whether it will work before trying it or filing an issue.
+//!
+//! The most useful part is the list of symbols the app needs that touchHLE
+//! has no implementation of. An app that needs none might still not work, but
+//! one that needs many probably won't get far.
+
+use crate::bundle::Bundle;
+use crate::dyld::Dyld;
+use crate::fs::{Fs, GuestPath};
+use crate::mach_o::MachO;
+use crate::plugins;
+use plist::Value;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// The keys from `Info.plist` that are shown, and what they mean.
+const INFO_PLIST_KEYS: &[(&str, &str)] = &[
+    ("CFBundleDisplayName", "Name"),
+    ("CFBundleIdentifier", "Bundle ID"),
+    ("CFBundleShortVersionString", "Version"),
+    ("CFBundleVersion", "Build"),
+    ("MinimumOSVersion", "Minimum OS version"),
+    ("DTSDKName", "SDK"),
+    ("UIDeviceFamily", "Device families"),
+    ("UIRequiredDeviceCapabilities", "Required capabilities"),
+    ("UIInterfaceOrientation", "Initial orientation"),
+    ("UIStatusBarHidden", "Status bar hidden"),
+];
+
+/// Print what can be found out about an app without running it.
+pub fn print(bundle: &Bundle, fs: &Fs, plugins: &plugins::Enabled) -> Result<(), String> {
+    let plist = bundle.info_plist();
+    println!("Info.plist:");
+    for &(key, description) in INFO_PLIST_KEYS {
+        if let Some(value) = plist.get(key) {
+            println!("    {}: {}", description, describe(value));
+        }
+    }
+    println!();
+
+    let executable_path = bundle.executable_path();
+    let executable = fs
+        .read(&executable_path)
+        .map_err(|()| "Could not read executable file".to_string())?;
+    let executable =
+        MachO::summarize(&executable).map_err(|e| format!("Could not read executable: {}", e))?;
+    println!("Executable: {}", executable_path.as_str());
+    if executable.is_encrypted {
+        println!(
+            "    Encrypted: yes. touchHLE can't run encrypted apps, a decrypted copy is needed."
+        );
+    } else {
+        println!("    Encrypted: no");
+    }
+    match &executable.entitlements {
+        None => println!("    Entitlements: none"),
+        Some(entitlements) => match Value::from_reader(Cursor::new(entitlements)) {
+            Ok(Value::Dictionary(entitlements)) => {
+                println!("    Entitlements:");
+                for (key, value) in entitlements.iter() {
+                    println!("        {}: {}", key, describe(value));
+                }
+            }
+            _ => println!("    Entitlements: couldn't be read"),
+        },
+    }
+    println!();
+
+    // Symbols exported by libraries bundled with touchHLE, with the name of
+    // the library.
+    let mut library_symbols = HashMap::new();
+    println!("Linked libraries:");
+    for library in &executable.dynamic_libraries {
+        let status = if Dyld::HOST_IMPLEMENTED_LIBRARIES.contains(&library.as_str()) {
+            "implemented by touchHLE"
+        } else if fs.is_file(GuestPath::new(library)) {
+            let name = GuestPath::new(library).file_name().unwrap();
+            let summary = fs
+                .read(GuestPath::new(library))
+                .ok()
+                .and_then(|bytes| MachO::summarize(&bytes).ok());
+            if let Some(summary) = summary {
+                for symbol in summary.exported_symbols {
+                    library_symbols.insert(symbol, name);
+                }
+                "bundled with touchHLE"
+            } else {
+                "bundled with touchHLE, but couldn't be read"
+            }
+        } else if library.starts_with("/System/Library/Frameworks/") {
+            "system framework, partly implemented by touchHLE"
+        } else {
+            "not available"
+        };
+        println!("    {} ({})", library, status);
+    }
+    println!();
+
+    let mut implemented = Vec::new();
+    let mut unimplemented = Vec::new();
+    let mut symbols = executable.undefined_symbols.clone();
+    symbols.sort();
+    symbols.dedup();
+    for symbol in symbols {
+        if let Some(library) = library_symbols.get(&symbol) {
+            implemented.push(format!("{} (in {})", symbol, library));
+        } else if Dyld::has_host_implementation(plugins, &symbol) {
+            implemented.push(symbol);
+        } else {
+            unimplemented.push(symbol);
+        }
+    }
+    println!(
+        "Symbols the app needs: {} implemented, {} unimplemented",
+        implemented.len(),
+        unimplemented.len()
+    );
+    println!();
+    println!("Implemented:");
+    for symbol in &implemented {
+        println!("    {}", symbol);
+    }
+    println!();
+    println!("Unimplemented:");
+    for symbol in &unimplemented {
+        println!("    {}", symbol);
+    }
+
+    Ok(())
+}
+
+/// Format a property list value on one line.
+fn describe(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        Value::Boolean(boolean) => boolean.to_string(),
+        Value::Integer(integer) => integer.to_string(),
+        Value::Real(real) => real.to_string(),
+        Value::Array(array) => {
+            let items: Vec<String> = array.iter().map(describe).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Dictionary(dictionary) => {
+            let items: Vec<String> = dictionary
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, describe(value)))
+                .collect();
+            format!("{{{}}}", items.join(", "))
+        }
+        _ => "(data)".to_string(),
+    }
+}
@@ -0,0 +1,95 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The launcher, used when touchHLE is run without the path to an app. It
+//! lists the apps in the apps directory and asks which one to run. When the
+//! app exits, is quit or crashes, the user is asked again, so several apps
+//! can be run one after another without restarting touchHLE.
+//!
+//! There's no graphical user interface yet, so this uses the terminal.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The directory the apps are listed from, relative to the current directory.
+pub const APPS_DIR: &str = "touchHLE_apps";
+
+/// Find the app bundles (`.app` directories) in a directory, sorted by name.
+pub fn find_apps(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        format!(
+            "Couldn't open the apps directory {}: {}. Put your apps there, or give the path to an app.",
+            dir.display(),
+            e
+        )
+    })?;
+    let mut apps: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && path.extension().is_some_and(|ext| ext == "app"))
+        .collect();
+    apps.sort();
+    Ok(apps)
+}
+
+/// Print a list of apps and ask the user which one to run. Returns [None] if
+/// the user wants to quit touchHLE.
+pub fn pick_app(apps: &[PathBuf]) -> Option<PathBuf> {
+    println!("Apps in {}:", APPS_DIR);
+    for (i, app) in apps.iter().enumerate() {
+        let name = app.file_stem().unwrap_or_default().to_string_lossy();
+        println!("    {}. {}", i + 1, name);
+    }
+    if apps.is_empty() {
+        println!("    (none)");
+    }
+
+    loop {
+        print!("Enter the number of an app to run, or 'q' to quit: ");
+        let _ = std::io::stdout().flush();
+        let Some(line) = crate::terminal::read_line() else {
+            // End of input.
+            println!();
+            return None;
+        };
+        match parse_choice(&line, apps.len()) {
+            Ok(Some(index)) => return Some(apps[index].clone()),
+            Ok(None) => return None,
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+/// Parse the user's answer to [pick_app]: the number of an app, counting from
+/// one, or `q` to quit. Returns the index of the app, or [None] for quitting.
+fn parse_choice(input: &str, app_count: usize) -> Result<Option<usize>, String> {
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("q") {
+        return Ok(None);
+    }
+    match input.parse::<usize>() {
+        Ok(number) if (1..=app_count).contains(&number) => Ok(Some(number - 1)),
+        Ok(_) => Err(format!("There's no app number {}.", input)),
+        Err(_) => Err(format!("{:?} isn't a number.", input)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choices() {
+        assert_eq!(parse_choice("1\n", 3), Ok(Some(0)));
+        assert_eq!(parse_choice(" 3 ", 3), Ok(Some(2)));
+        assert_eq!(parse_choice("q\n", 3), Ok(None));
+        assert_eq!(parse_choice("Q", 0), Ok(None));
+        assert!(parse_choice("0", 3).is_err());
+        assert!(parse_choice("4", 3).is_err());
+        assert!(parse_choice("", 3).is_err());
+        assert!(parse_choice("one", 3).is_err());
+        assert!(parse_choice("1", 0).is_err());
+    }
+}
@@ -31,7 +31,7 @@ fn dlsym(env: &mut Environment, handle: MutVoidPtr, symbol: ConstPtr<u8>) -> Mut
     // no symbol found, since it most likely indicates a missing host function.
     let addr = env
         .dyld
-        .create_proc_address(&mut env.mem, &mut env.cpu, &symbol)
+        .create_proc_address(&mut env.mem, &mut env.cpu, &env.plugins, &symbol)
         .unwrap_or_else(|_| panic!("dlsym() for unimplemented function {}", symbol));
     Ptr::from_bits(addr.addr_with_thumb_bit())
 }
@@ -89,7 +89,7 @@ fn fread(
         .unwrap();
     if bytes_read < buffer_slice.len() {
         // TODO: set errno
-        log!(
+        log_limited!(
             "Warning: fread({:?}, {:#x}, {:#x}, {:?}) read only {:#x} of requested {:#x} bytes",
             buffer,
             item_size,
@@ -99,7 +99,7 @@ fn fread(
             bytes_read
         );
     } else {
-        log_dbg!(
+        log_trace!(
             "fread({:?}, {:#x}, {:#x}, {:?}) => {:#x}",
             buffer,
             item_size,
@@ -128,7 +128,7 @@ fn fwrite(
         .unwrap();
     if bytes_written < buffer_slice.len() {
         // TODO: set errno
-        log!(
+        log_limited!(
             "Warning: fwrite({:?}, {:#x}, {:#x}, {:?}) wrote only {:#x} of requested {:#x} bytes",
             buffer,
             item_size,
@@ -138,7 +138,7 @@ fn fwrite(
             bytes_written
         );
     } else {
-        log_dbg!(
+        log_trace!(
             "fwrite({:?}, {:#x}, {:#x}, {:?}) => {:#x}",
             buffer,
             item_size,
@@ -42,6 +42,12 @@ fn atexit(
     0 // success
 }
 
+fn exit(env: &mut Environment, status: i32) {
+    // TODO: call the functions registered with atexit() and __cxa_atexit()
+    log!("App called exit({}), exiting.", status);
+    env.exit(status);
+}
+
 fn skip_whitespace(env: &mut Environment, s: ConstPtr<u8>) -> ConstPtr<u8> {
     let mut start = s;
     loop {
@@ -124,7 +130,7 @@ fn prng(state: u32) -> u32 {
 const RAND_MAX: i32 = i32::MAX;
 
 fn srand(env: &mut Environment, seed: u32) {
-    env.libc_state.stdlib.rand = seed;
+    env.libc_state.stdlib.rand = crate::replay::srand_seed(env, seed);
 }
 fn rand(env: &mut Environment) -> i32 {
     env.libc_state.stdlib.rand = prng(env.libc_state.stdlib.rand);
@@ -134,7 +140,7 @@ fn rand(env: &mut Environment) -> i32 {
 // BSD's "better" random number generator, with an implementation that is not
 // actually better.
 fn srandom(env: &mut Environment, seed: u32) {
-    env.libc_state.stdlib.random = seed;
+    env.libc_state.stdlib.random = crate::replay::srandom_seed(env, seed);
 }
 fn random(env: &mut Environment) -> i32 {
     env.libc_state.stdlib.random = prng(env.libc_state.stdlib.random);
@@ -146,6 +152,7 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(calloc(_, _)),
     export_c_func!(free(_)),
     export_c_func!(atexit(_)),
+    export_c_func!(exit(_)),
     export_c_func!(atoi(_)),
     export_c_func!(atof(_)),
     export_c_func!(srand(_)),
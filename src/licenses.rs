@@ -116,6 +116,11 @@ which is available either as Public Domain or under the terms of the MIT
 license.
 ";
 
+const SQLITE: &str = "
+touchHLE, and therefore this executable, incorporates the library SQLite, which
+is in the Public Domain.
+";
+
 pub fn divider() {
     println!("---");
 }
@@ -142,4 +147,6 @@ pub fn print() {
     println!("{}", OPENAL_SOFT);
     divider();
     println!("{}", STB_IMAGE);
+    divider();
+    println!("{}", SQLITE);
 }
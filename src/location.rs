@@ -0,0 +1,420 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Where the device is, for Core Location. See `--location=`.
+//!
+//! The position can be fixed, replayed from a GPX track, or read from a file
+//! on the host that another program keeps up to date, which is how the host's
+//! own location service can be used without touchHLE needing to know about
+//! every platform's API for it.
+
+use regex::Regex;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Mean radius of the Earth, in meters.
+pub const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// How often a location file is checked for changes.
+const FILE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fix {
+    /// In degrees.
+    pub latitude: f64,
+    /// In degrees.
+    pub longitude: f64,
+    /// In meters above sea level.
+    pub altitude: Option<f64>,
+}
+
+/// A [Fix] and, if the device is known to be moving, how.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Reading {
+    pub fix: Fix,
+    /// In degrees clockwise from north.
+    pub course: Option<f64>,
+    /// In meters per second.
+    pub speed: Option<f64>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TrackPoint {
+    pub fix: Fix,
+    /// In seconds since the start of the track.
+    pub time: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum LocationSource {
+    Coordinate(Fix),
+    Gpx(Vec<TrackPoint>),
+    File(PathBuf),
+}
+impl LocationSource {
+    pub fn parse(value: &str) -> Result<LocationSource, String> {
+        if let Some(path) = value.strip_prefix("gpx:") {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Couldn't read GPX file {:?}: {}", path, e))?;
+            let points =
+                parse_gpx(&text).map_err(|e| format!("Invalid GPX file {:?}: {}", path, e))?;
+            Ok(LocationSource::Gpx(points))
+        } else if let Some(path) = value.strip_prefix("file:") {
+            Ok(LocationSource::File(PathBuf::from(path)))
+        } else {
+            parse_coordinate(value)
+                .map(LocationSource::Coordinate)
+                .ok_or_else(|| format!("Invalid location {:?}", value))
+        }
+    }
+}
+
+/// Parse a latitude and longitude in degrees, optionally followed by an
+/// altitude in meters, separated by commas.
+pub fn parse_coordinate(text: &str) -> Option<Fix> {
+    let mut parts = text
+        .trim()
+        .split(',')
+        .map(|part| part.trim().parse::<f64>());
+    let latitude = parts.next()?.ok()?;
+    let longitude = parts.next()?.ok()?;
+    let altitude = parts.next().transpose().ok()?;
+    if parts.next().is_some()
+        || !(-90.0..=90.0).contains(&latitude)
+        || !(-180.0..=180.0).contains(&longitude)
+        || altitude.is_some_and(|altitude| !altitude.is_finite())
+    {
+        return None;
+    }
+    Some(Fix {
+        latitude,
+        longitude,
+        altitude,
+    })
+}
+
+/// Get the track or route points from a GPX file. If the points have no
+/// times, they're one second apart.
+pub fn parse_gpx(text: &str) -> Result<Vec<TrackPoint>, String> {
+    let point_regex =
+        Regex::new(r"(?s)<(trkpt|rtept)\b([^>]*?)(?:/>|>(.*?)</(?:trkpt|rtept)>)").unwrap();
+    let lat_regex = Regex::new(r#"\blat\s*=\s*["']([^"']*)["']"#).unwrap();
+    let lon_regex = Regex::new(r#"\blon\s*=\s*["']([^"']*)["']"#).unwrap();
+    let ele_regex = Regex::new(r"<ele>\s*([^<]*?)\s*</ele>").unwrap();
+    let time_regex = Regex::new(r"<time>\s*([^<]*?)\s*</time>").unwrap();
+
+    let mut fixes = Vec::new();
+    let mut times = Vec::new();
+    for captures in point_regex.captures_iter(text) {
+        let attributes = &captures[2];
+        let content = captures.get(3).map_or("", |content| content.as_str());
+        let attribute =
+            |regex: &Regex| -> Option<f64> { regex.captures(attributes)?[1].parse().ok() };
+        let (Some(latitude), Some(longitude)) = (attribute(&lat_regex), attribute(&lon_regex))
+        else {
+            return Err(format!("Point {} has no valid position", fixes.len() + 1));
+        };
+        let altitude = ele_regex
+            .captures(content)
+            .and_then(|captures| captures[1].parse().ok());
+        fixes.push(Fix {
+            latitude,
+            longitude,
+            altitude,
+        });
+        times.push(
+            time_regex
+                .captures(content)
+                .and_then(|captures| parse_iso8601(&captures[1])),
+        );
+    }
+    if fixes.is_empty() {
+        return Err("No track or route points".to_string());
+    }
+
+    let times: Vec<f64> = if times.iter().all(Option::is_some) {
+        let start = times[0].unwrap();
+        // Times shouldn't go backwards, but it's easy to cope if they do.
+        let mut latest = 0.0f64;
+        times
+            .into_iter()
+            .map(|time| {
+                latest = latest.max(time.unwrap() - start);
+                latest
+            })
+            .collect()
+    } else {
+        (0..fixes.len()).map(|i| i as f64).collect()
+    };
+    Ok(fixes
+        .into_iter()
+        .zip(times)
+        .map(|(fix, time)| TrackPoint { fix, time })
+        .collect())
+}
+
+/// Parse an ISO 8601 date and time, as used in GPX files, into seconds since
+/// 1970. A missing time zone is treated as UTC.
+fn parse_iso8601(text: &str) -> Option<f64> {
+    let regex = Regex::new(
+        r"^(\d{4})-(\d{2})-(\d{2})T(\d{2}):(\d{2}):(\d{2}(?:\.\d+)?)(Z|[+-]\d{2}:?\d{2})?$",
+    )
+    .unwrap();
+    let captures = regex.captures(text)?;
+    let field = |i: usize| -> i64 { captures[i].parse().unwrap() };
+    let (year, month, day) = (field(1), field(2), field(3));
+    let (hour, minute) = (field(4), field(5));
+    let second: f64 = captures[6].parse().unwrap();
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar, see
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let offset = match captures.get(7).map(|zone| zone.as_str()) {
+        None | Some("Z") => 0,
+        Some(zone) => {
+            let sign = if zone.starts_with('-') { -1 } else { 1 };
+            let digits = zone[1..].replace(':', "");
+            let hours: i64 = digits[0..2].parse().unwrap();
+            let minutes: i64 = digits[2..4].parse().unwrap();
+            sign * (hours * 60 + minutes) * 60
+        }
+    };
+    Some((days * 86400 + hour * 3600 + minute * 60 - offset) as f64 + second)
+}
+
+/// Great-circle distance between two fixes in meters, ignoring altitude.
+pub fn distance(a: Fix, b: Fix) -> f64 {
+    let (lat_a, lat_b) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.longitude - a.longitude).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().min(1.0).asin()
+}
+
+/// Initial bearing from one fix to another, in degrees clockwise from north.
+pub fn bearing(from: Fix, to: Fix) -> f64 {
+    let (lat_a, lat_b) = (from.latitude.to_radians(), to.latitude.to_radians());
+    let d_lon = (to.longitude - from.longitude).to_radians();
+    let y = d_lon.sin() * lat_b.cos();
+    let x = lat_a.cos() * lat_b.sin() - lat_a.sin() * lat_b.cos() * d_lon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Where the device is at some time into a track, moving in a straight line
+/// between points. It stays at the last point once the track is over.
+fn track_reading(points: &[TrackPoint], time: f64) -> Reading {
+    let stationary = |point: &TrackPoint| Reading {
+        fix: point.fix,
+        course: None,
+        speed: None,
+    };
+    let Some(next) = points.iter().position(|point| point.time > time) else {
+        return stationary(points.last().unwrap());
+    };
+    if next == 0 {
+        return stationary(&points[0]);
+    }
+    let (a, b) = (points[next - 1], points[next]);
+    let t = (time - a.time) / (b.time - a.time);
+    let lerp = |a: f64, b: f64| a + (b - a) * t;
+    let fix = Fix {
+        latitude: lerp(a.fix.latitude, b.fix.latitude),
+        longitude: lerp(a.fix.longitude, b.fix.longitude),
+        altitude: match (a.fix.altitude, b.fix.altitude) {
+            (Some(alt_a), Some(alt_b)) => Some(lerp(alt_a, alt_b)),
+            (altitude, None) | (None, altitude) => altitude,
+        },
+    };
+    let segment_distance = distance(a.fix, b.fix);
+    Reading {
+        fix,
+        course: (segment_distance > 0.0).then(|| bearing(a.fix, b.fix)),
+        speed: Some(segment_distance / (b.time - a.time)),
+    }
+}
+
+/// Provides [Reading]s from a [LocationSource] as time passes.
+pub struct Provider {
+    source: LocationSource,
+    started_at: Instant,
+    file_checked_at: Option<Instant>,
+    file_modified: Option<SystemTime>,
+    file_fix: Option<Fix>,
+}
+impl Provider {
+    /// GPX tracks start playing from when this is called.
+    pub fn new(source: LocationSource) -> Provider {
+        Provider {
+            source,
+            started_at: Instant::now(),
+            file_checked_at: None,
+            file_modified: None,
+            file_fix: None,
+        }
+    }
+
+    /// Get the current reading. [None] if there isn't one yet, which is only
+    /// possible for a location file.
+    pub fn reading(&mut self) -> Option<Reading> {
+        match self.source {
+            LocationSource::Coordinate(fix) => Some(Reading {
+                fix,
+                course: None,
+                speed: None,
+            }),
+            LocationSource::Gpx(ref points) => Some(track_reading(
+                points,
+                self.started_at.elapsed().as_secs_f64(),
+            )),
+            LocationSource::File(_) => {
+                self.check_file();
+                self.file_fix.map(|fix| Reading {
+                    fix,
+                    course: None,
+                    speed: None,
+                })
+            }
+        }
+    }
+
+    fn check_file(&mut self) {
+        let LocationSource::File(ref path) = self.source else {
+            unreachable!();
+        };
+        let now = Instant::now();
+        if self
+            .file_checked_at
+            .is_some_and(|checked_at| now.duration_since(checked_at) < FILE_CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.file_checked_at = Some(now);
+
+        let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+        let Ok(modified) = modified else {
+            return;
+        };
+        if self.file_modified == Some(modified) {
+            return;
+        }
+        self.file_modified = Some(modified);
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return;
+        };
+        match parse_coordinate(&text) {
+            Some(fix) => self.file_fix = Some(fix),
+            None => log!(
+                "Warning: Location file {:?} doesn't contain a valid location: {:?}",
+                path,
+                text
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fix(latitude: f64, longitude: f64) -> Fix {
+        Fix {
+            latitude,
+            longitude,
+            altitude: None,
+        }
+    }
+
+    #[test]
+    fn coordinates() {
+        assert_eq!(parse_coordinate("51.5, -0.125"), Some(fix(51.5, -0.125)));
+        assert_eq!(
+            parse_coordinate("27.9881,86.925,8848\n"),
+            Some(Fix {
+                latitude: 27.9881,
+                longitude: 86.925,
+                altitude: Some(8848.0),
+            })
+        );
+        assert_eq!(parse_coordinate("91,0"), None);
+        assert_eq!(parse_coordinate("0"), None);
+        assert_eq!(parse_coordinate("0,0,0,0"), None);
+        assert_eq!(parse_coordinate("north,south"), None);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_iso8601("1970-01-01T00:00:00Z"), Some(0.0));
+        assert_eq!(parse_iso8601("2001-01-01T00:00:00Z"), Some(978307200.0));
+        assert_eq!(parse_iso8601("2000-03-01T00:00:01.5"), Some(951868801.5));
+        assert_eq!(
+            parse_iso8601("2001-01-01T01:00:00+01:00"),
+            Some(978307200.0)
+        );
+        assert_eq!(parse_iso8601("yesterday"), None);
+    }
+
+    #[test]
+    fn gpx() {
+        let text = r#"<?xml version="1.0"?>
+            <gpx version="1.1"><trk><trkseg>
+              <trkpt lat="10.0" lon="20.0"><ele>5</ele><time>2001-01-01T00:00:00Z</time></trkpt>
+              <trkpt lon="20.5" lat='10.5'><time>2001-01-01T00:00:10Z</time></trkpt>
+            </trkseg></trk></gpx>"#;
+        let points = parse_gpx(text).unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].fix.altitude, Some(5.0));
+        assert_eq!(points[1].fix, fix(10.5, 20.5));
+        assert_eq!(points[1].time, 10.0);
+
+        let untimed = parse_gpx(r#"<rtept lat="1" lon="2"/><rtept lat="3" lon="4"/>"#).unwrap();
+        assert_eq!(untimed[1].time, 1.0);
+
+        assert!(parse_gpx("<gpx></gpx>").is_err());
+        assert!(parse_gpx(r#"<trkpt lat="1"/>"#).is_err());
+    }
+
+    #[test]
+    fn geometry() {
+        let london = fix(51.5074, -0.1278);
+        let paris = fix(48.8566, 2.3522);
+        assert!((distance(london, paris) - 343_500.0).abs() < 1000.0);
+        assert_eq!(distance(paris, paris), 0.0);
+        assert!((bearing(fix(0.0, 0.0), fix(1.0, 0.0)) - 0.0).abs() < 1e-9);
+        assert!((bearing(fix(0.0, 0.0), fix(0.0, 1.0)) - 90.0).abs() < 1e-9);
+        assert!((bearing(fix(0.0, 0.0), fix(0.0, -1.0)) - 270.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn track() {
+        let points = [
+            TrackPoint {
+                fix: fix(0.0, 0.0),
+                time: 0.0,
+            },
+            TrackPoint {
+                fix: fix(0.0, 1.0),
+                time: 100.0,
+            },
+        ];
+        let start = track_reading(&points, 0.0);
+        assert_eq!(start.fix, fix(0.0, 0.0));
+        let middle = track_reading(&points, 50.0);
+        assert_eq!(middle.fix, fix(0.0, 0.5));
+        assert!((middle.course.unwrap() - 90.0).abs() < 1e-9);
+        assert!(
+            (middle.speed.unwrap() - distance(points[0].fix, points[1].fix) / 100.0).abs() < 1e-9
+        );
+        let end = track_reading(&points, 1000.0);
+        assert_eq!(end.fix, fix(0.0, 1.0));
+        assert_eq!(end.speed, None);
+    }
+}
@@ -3,13 +3,30 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-/// Prints a log message unconditionally. Use this for errors or warnings.
+//! Logging. Messages go to standard error, and also to a file with timestamps
+//! if `--log-file=` is used. How much each module logs can be changed with
+//! `--log=`, e.g. `--log=objc=debug,libc::stdio=trace`.
+//!
+//! Until [configure] is called, everything is logged at [Level::Info].
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Prints a log message, unless logging is turned off for the module where it
+/// is used. Use this for errors or warnings.
 ///
 /// The message is prefixed with the module path, so it is clear where it comes
 /// from.
 macro_rules! log {
     ($($arg:tt)+) => {
-        eprintln!("{}: {}", module_path!(), format_args!($($arg)+));
+        if $crate::log::enabled(module_path!(), $crate::log::Level::Info) {
+            $crate::log::write(module_path!(), format_args!($($arg)+));
+        }
     }
 }
 
@@ -18,12 +35,278 @@ macro_rules! log {
 /// when debugging.
 macro_rules! log_dbg {
     ($($arg:tt)+) => {
-        if $crate::log::ENABLED_MODULES.contains(&module_path!()) {
-            log!($($arg)*);
+        if $crate::log::enabled(module_path!(), $crate::log::Level::Debug) {
+            $crate::log::write(module_path!(), format_args!($($arg)+));
+        }
+    }
+}
+
+/// Like [log_dbg], but for things so frequent that they would get in the way
+/// of other debugging output, e.g. every read from a file.
+macro_rules! log_trace {
+    ($($arg:tt)+) => {
+        if $crate::log::enabled(module_path!(), $crate::log::Level::Trace) {
+            $crate::log::write(module_path!(), format_args!($($arg)+));
+        }
+    }
+}
+
+/// Like [log], but for warnings that an app might cause over and over again.
+/// After a few times, only some of the messages from the place where it is
+/// used are printed, along with how many times it has happened.
+macro_rules! log_limited {
+    ($($arg:tt)+) => {
+        if $crate::log::enabled(module_path!(), $crate::log::Level::Info) {
+            $crate::log::write_limited(
+                module_path!(),
+                (file!(), line!()),
+                format_args!($($arg)+),
+            );
+        }
+    }
+}
+
+/// How much a module logs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Nothing, not even warnings.
+    Off,
+    /// Only [log] and [log_limited].
+    Info,
+    /// Also [log_dbg].
+    Debug,
+    /// Also [log_trace].
+    Trace,
+}
+impl Level {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "off" => Ok(Level::Off),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(format!("Unknown log level {:?}", name)),
+        }
+    }
+}
+
+/// The name of the crate, which module paths start with.
+const CRATE_NAME: &str = "touchHLE";
+
+/// Which level each module logs at. See `--log=`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    default: Level,
+    /// Module paths without the crate name, and their levels. A module's
+    /// submodules use the same level unless they have their own entry.
+    modules: Vec<(String, Level)>,
+}
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            default: Level::Info,
+            modules: Vec::new(),
         }
     }
 }
+impl Filter {
+    /// Add the settings in a value of `--log=`, which replace any earlier ones
+    /// for the same modules.
+    pub fn add(&mut self, value: &str) -> Result<(), String> {
+        for item in value.split(',') {
+            let item = item.trim();
+            let Some((module, level)) = item.split_once('=') else {
+                self.default = Level::parse(item)?;
+                continue;
+            };
+            let module = module.trim();
+            let module = module
+                .strip_prefix(CRATE_NAME)
+                .and_then(|module| module.strip_prefix("::"))
+                .unwrap_or(module);
+            if module.is_empty() || module.split("::").any(|part| part.is_empty()) {
+                return Err(format!("Invalid module name {:?}", module));
+            }
+            let level = Level::parse(level.trim())?;
+            self.modules.retain(|(other, _)| other != module);
+            self.modules.push((module.to_string(), level));
+        }
+        Ok(())
+    }
+
+    /// Get the level for a module, given its full path.
+    pub fn level(&self, module_path: &str) -> Level {
+        let module_path = module_path
+            .strip_prefix(CRATE_NAME)
+            .map_or(module_path, |path| path.trim_start_matches("::"));
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                module_path
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    /// Get the highest level that any module logs at.
+    pub fn max_level(&self) -> Level {
+        self.modules
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, Level::max)
+    }
+}
+
+/// How many times a [log_limited] message is always printed.
+const LIMITED_ALWAYS_SHOWN: u32 = 10;
 
-/// Put modules to enable [log_dbg] for here, e.g. "touchHLE::mem" to see when
-/// memory is allocated and freed.
-pub const ENABLED_MODULES: &[&str] = &[];
+/// Whether the `count`th time a [log_limited] message happens should be
+/// printed: the first few times, and then every power of ten.
+fn limited_shown(count: u32) -> bool {
+    if count <= LIMITED_ALWAYS_SHOWN {
+        return true;
+    }
+    let mut count = count;
+    while count.is_multiple_of(10) {
+        count /= 10;
+    }
+    count == 1
+}
+
+struct Logger {
+    filter: Filter,
+    /// The log file and when logging started, if there is one.
+    file: Option<(File, Instant)>,
+    /// How many times each [log_limited] call site has happened.
+    limited_counts: HashMap<(&'static str, u32), u32>,
+}
+
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+/// Copies of [Filter::max_level] and whether the filter has settings for
+/// particular modules, so that [enabled] can usually avoid locking [LOGGER].
+/// Disabled [log_dbg] calls are on some very hot paths.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static HAS_MODULE_LEVELS: AtomicBool = AtomicBool::new(false);
+
+fn with_logger<T>(f: impl FnOnce(&mut Logger) -> T) -> T {
+    // A panic while logging shouldn't stop anything else being logged.
+    let mut logger = LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+    f(logger.get_or_insert_with(|| Logger {
+        filter: Filter::default(),
+        file: None,
+        limited_counts: HashMap::new(),
+    }))
+}
+
+/// Set which levels modules log at, and start writing to a log file if one is
+/// given.
+pub fn configure(filter: Filter, file: Option<&Path>) -> Result<(), String> {
+    let file = file
+        .map(|path| {
+            File::create(path)
+                .map(|file| (file, Instant::now()))
+                .map_err(|e| format!("Couldn't create log file {}: {}", path.display(), e))
+        })
+        .transpose()?;
+    with_logger(|logger| {
+        MAX_LEVEL.store(filter.max_level() as u8, Ordering::Relaxed);
+        HAS_MODULE_LEVELS.store(!filter.modules.is_empty(), Ordering::Relaxed);
+        logger.filter = filter;
+        logger.file = file;
+    });
+    Ok(())
+}
+
+/// For use by the logging macros.
+pub fn enabled(module_path: &str, level: Level) -> bool {
+    if level as u8 > MAX_LEVEL.load(Ordering::Relaxed) {
+        return false;
+    }
+    // Without settings for particular modules, every module logs at the
+    // maximum level.
+    if !HAS_MODULE_LEVELS.load(Ordering::Relaxed) {
+        return true;
+    }
+    with_logger(|logger| logger.filter.level(module_path) >= level)
+}
+
+/// For use by the logging macros.
+pub fn write(module_path: &str, args: std::fmt::Arguments) {
+    // Formatting is done first, in case it logs something itself.
+    let message = args.to_string();
+    with_logger(|logger| {
+        eprintln!("{}: {}", module_path, message);
+        if let Some((ref mut file, start)) = logger.file {
+            let time = start.elapsed().as_secs_f64();
+            // There's nowhere to report a failure to.
+            let _ = writeln!(file, "[{:10.3}] {}: {}", time, module_path, message);
+        }
+    });
+}
+
+/// For use by [log_limited].
+pub fn write_limited(module_path: &str, call_site: (&'static str, u32), args: std::fmt::Arguments) {
+    let count = with_logger(|logger| {
+        let count = logger.limited_counts.entry(call_site).or_insert(0);
+        *count += 1;
+        *count
+    });
+    if !limited_shown(count) {
+        return;
+    }
+    if count <= LIMITED_ALWAYS_SHOWN {
+        write(module_path, args);
+    } else {
+        write(
+            module_path,
+            format_args!(
+                "{} (this has happened {} times, not every time is logged)",
+                args, count
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter() {
+        let mut filter = Filter::default();
+        assert_eq!(filter.level("touchHLE::objc::messages"), Level::Info);
+        assert_eq!(filter.max_level(), Level::Info);
+
+        filter
+            .add("objc=debug, touchHLE::libc::stdio=trace,mem=off")
+            .unwrap();
+        filter.add("objc::messages=info").unwrap();
+        assert_eq!(filter.level("touchHLE::objc"), Level::Debug);
+        assert_eq!(filter.level("touchHLE::objc::classes"), Level::Debug);
+        assert_eq!(filter.level("touchHLE::objc::messages"), Level::Info);
+        assert_eq!(filter.level("touchHLE::objcx"), Level::Info);
+        assert_eq!(filter.level("touchHLE::libc::stdio"), Level::Trace);
+        assert_eq!(filter.level("touchHLE::libc::stdio::printf"), Level::Trace);
+        assert_eq!(filter.level("touchHLE::mem"), Level::Off);
+        assert_eq!(filter.level("touchHLE"), Level::Info);
+        assert_eq!(filter.max_level(), Level::Trace);
+
+        filter.add("debug,mem=info").unwrap();
+        assert_eq!(filter.level("touchHLE::window"), Level::Debug);
+        assert_eq!(filter.level("touchHLE::mem"), Level::Info);
+
+        assert!(filter.add("objc=loud").is_err());
+        assert!(filter.add("quiet").is_err());
+        assert!(filter.add("=debug").is_err());
+        assert!(filter.add("objc::=debug").is_err());
+    }
+
+    #[test]
+    fn limited() {
+        let shown: Vec<u32> = (1..=2000).filter(|&count| limited_shown(count)).collect();
+        assert_eq!(shown, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 100, 1000]);
+    }
+}
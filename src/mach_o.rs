@@ -20,7 +20,9 @@
 
 use crate::fs::{Fs, GuestPath};
 use crate::mem::{Mem, Ptr};
-use mach_object::{DyLib, LoadCommand, MachCommand, OFile, Symbol, SymbolIter};
+use mach_object::{
+    DyLib, LinkEditData, LoadCommand, MachCommand, MachHeader, OFile, Symbol, SymbolIter,
+};
 use std::collections::HashMap;
 use std::io::{Cursor, Seek, SeekFrom};
 
@@ -35,6 +37,10 @@ pub struct MachO {
     /// Symbols exported by the binary. This is a hashmap so the dynamic linker
     /// can look things up quickly.
     pub exported_symbols: HashMap<String, u32>,
+    /// Addresses and names of all the symbols defined by the binary, including
+    /// ones that aren't exported, sorted by address. This is for describing
+    /// addresses in debugging output (see [Self::symbol_for_address]).
+    pub symbols: Vec<(u32, String)>,
     /// List of addresses and names of external relocations for the dynamic
     /// linker to resolve.
     pub external_relocations: Vec<(u32, String)>,
@@ -165,7 +171,156 @@ impl Reloc {
     }
 }
 
+/// Parse the header and load commands of a Mach-O binary, and check it's one
+/// touchHLE can run.
+fn parse_file(cursor: &mut Cursor<&[u8]>) -> Result<(MachHeader, Vec<MachCommand>), &'static str> {
+    let file = OFile::parse(cursor).map_err(|_| "Could not parse Mach-O file")?;
+
+    let (header, commands) = match file {
+        OFile::MachFile { header, commands } => (header, commands),
+        OFile::FatFile { .. } => {
+            unimplemented!("Fat binary support is not implemented yet");
+        }
+        OFile::ArFile { .. } | OFile::SymDef { .. } => {
+            return Err("Unexpected Mach-O file kind: not an executable");
+        }
+    };
+
+    if header.cputype != mach_object::CPU_TYPE_ARM {
+        return Err("Executable is not for an ARM CPU!");
+    }
+    if header.is_bigend() {
+        return Err("Executable is not little-endian!");
+    }
+    if header.is_64bit() {
+        return Err("Executable is not 32-bit!");
+    }
+    // TODO: Check cpusubtype (should be some flavour of ARMv6/ARMv7)
+
+    Ok((header, commands))
+}
+
+/// Find the entitlements (an XML property list) in a code signature, which is
+/// a "superblob" containing other blobs. All the integers are big-endian.
+fn find_entitlements(signature: &[u8]) -> Option<&[u8]> {
+    const SUPERBLOB_MAGIC: u32 = 0xfade0cc0;
+    const ENTITLEMENTS_MAGIC: u32 = 0xfade7171;
+
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes = signature.get(offset..)?.get(..4)?;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    };
+
+    if read_u32(0)? != SUPERBLOB_MAGIC {
+        return None;
+    }
+    // Each index entry is a type and an offset.
+    let count = (read_u32(8)? as usize).min(signature.len() / 8);
+    (0..count).find_map(|i| {
+        let offset = read_u32(12 + i * 8 + 4)? as usize;
+        if read_u32(offset)? != ENTITLEMENTS_MAGIC {
+            return None;
+        }
+        // The length includes the magic and the length.
+        let length = read_u32(offset + 4)? as usize;
+        signature.get(offset + 8..offset.checked_add(length)?)
+    })
+}
+
+/// What `--inspect` shows about a binary. Unlike [MachO], this is read without
+/// loading the binary, so it works for encrypted binaries too.
+pub struct Summary {
+    pub dynamic_libraries: Vec<String>,
+    /// Symbols the binary needs from other binaries or from touchHLE.
+    pub undefined_symbols: Vec<String>,
+    pub exported_symbols: Vec<String>,
+    /// The contents of the entitlements property list in the code signature,
+    /// if there is one.
+    pub entitlements: Option<Vec<u8>>,
+    pub is_encrypted: bool,
+}
+
 impl MachO {
+    /// Read a [Summary] of a Mach-O binary.
+    pub fn summarize(bytes: &[u8]) -> Result<Summary, &'static str> {
+        let mut cursor = Cursor::new(bytes);
+
+        let (header, commands) = parse_file(&mut cursor)?;
+
+        let mut all_sections = Vec::new();
+        let mut summary = Summary {
+            dynamic_libraries: Vec::new(),
+            undefined_symbols: Vec::new(),
+            exported_symbols: Vec::new(),
+            entitlements: None,
+            is_encrypted: false,
+        };
+
+        for MachCommand(command, _size) in commands {
+            match command {
+                LoadCommand::Segment { sections, .. } => {
+                    all_sections.extend_from_slice(&sections);
+                }
+                LoadCommand::SymTab {
+                    symoff,
+                    nsyms,
+                    stroff,
+                    strsize,
+                } => {
+                    if cursor.seek(SeekFrom::Start(symoff.into())).is_err() {
+                        continue;
+                    }
+                    let mut cursor = cursor.clone();
+                    let symbols = SymbolIter::new(
+                        &mut cursor,
+                        all_sections.clone(),
+                        nsyms,
+                        stroff,
+                        strsize,
+                        header.is_bigend(),
+                        header.is_64bit(),
+                    );
+                    for symbol in symbols {
+                        match symbol {
+                            Symbol::Undefined {
+                                name: Some(name),
+                                external: true,
+                                ..
+                            }
+                            | Symbol::Prebound {
+                                name: Some(name),
+                                external: true,
+                                ..
+                            } => summary.undefined_symbols.push(name.to_string()),
+                            Symbol::Defined {
+                                name: Some(name),
+                                external: true,
+                                ..
+                            } => summary.exported_symbols.push(name.to_string()),
+                            _ => (),
+                        }
+                    }
+                }
+                LoadCommand::EncryptionInfo { id, .. } => {
+                    summary.is_encrypted = id != 0;
+                }
+                LoadCommand::LoadDyLib(DyLib { name, .. }) => {
+                    summary.dynamic_libraries.push(String::from(&*name));
+                }
+                LoadCommand::CodeSignature(LinkEditData { off, size }) => {
+                    summary.entitlements = bytes
+                        .get(off as usize..)
+                        .and_then(|signature| signature.get(..size as usize))
+                        .and_then(find_entitlements)
+                        .map(|entitlements| entitlements.to_vec());
+                }
+                _ => (),
+            }
+        }
+
+        Ok(summary)
+    }
+
     /// Load the all the sections from a Mach-O binary (provided as `bytes`)
     /// into the guest memory (`into_mem`), and return a struct containing
     /// metadata (e.g. symbols).
@@ -178,30 +333,9 @@ impl MachO {
 
         let mut cursor = Cursor::new(bytes);
 
-        let file = OFile::parse(&mut cursor).map_err(|_| "Could not parse Mach-O file")?;
-
-        let (header, commands) = match file {
-            OFile::MachFile { header, commands } => (header, commands),
-            OFile::FatFile { .. } => {
-                unimplemented!("Fat binary support is not implemented yet");
-            }
-            OFile::ArFile { .. } | OFile::SymDef { .. } => {
-                return Err("Unexpected Mach-O file kind: not an executable");
-            }
-        };
-
-        if header.cputype != mach_object::CPU_TYPE_ARM {
-            return Err("Executable is not for an ARM CPU!");
-        }
+        let (header, commands) = parse_file(&mut cursor)?;
         let is_bigend = header.is_bigend();
-        if is_bigend {
-            return Err("Executable is not little-endian!");
-        }
         let is_64bit = header.is_64bit();
-        if is_64bit {
-            return Err("Executable is not 32-bit!");
-        }
-        // TODO: Check cpusubtype (should be some flavour of ARMv6/ARMv7)
 
         // Info used while parsing file
         let mut all_sections = Vec::new();
@@ -210,6 +344,7 @@ impl MachO {
         // Info used for the result
         let mut dynamic_libraries = Vec::new();
         let mut exported_symbols = HashMap::new();
+        let mut defined_symbols = Vec::new();
         let mut indirect_undef_symbols: Vec<Option<String>> = Vec::new();
         let mut external_relocations: Vec<(u32, String)> = Vec::new();
 
@@ -287,13 +422,16 @@ impl MachO {
                             }
                             if let Symbol::Defined {
                                 name: Some(name),
-                                external: true,
+                                external,
                                 entry,
                                 ..
                             } = symbol
                             {
                                 let entry: u32 = entry.try_into().unwrap();
-                                exported_symbols.insert(name.to_string(), entry);
+                                if external {
+                                    exported_symbols.insert(name.to_string(), entry);
+                                }
+                                defined_symbols.push((entry, name.to_string()));
                             };
                         }
                     }
@@ -337,7 +475,8 @@ impl MachO {
                             is_pc_relative: false,
                             size: 4,
                             type_: 0, // generic
-                        } = reloc else {
+                        } = reloc
+                        else {
                             panic!("Unhandled extrel: {:?}", reloc)
                         };
 
@@ -414,11 +553,14 @@ impl MachO {
             })
             .collect();
 
+        defined_symbols.sort();
+
         Ok(MachO {
             name,
             dynamic_libraries,
             sections,
             exported_symbols,
+            symbols: defined_symbols,
             external_relocations,
         })
     }
@@ -443,4 +585,67 @@ impl MachO {
     pub fn get_section(&self, name: &str) -> Option<&Section> {
         self.sections.iter().find(|s| s.name == name)
     }
+
+    /// Find the symbol an address is in, if it's in one of the binary's
+    /// sections, returning the name and the offset from the symbol's address.
+    pub fn symbol_for_address(&self, addr: u32) -> Option<(&str, u32)> {
+        if !self
+            .sections
+            .iter()
+            .any(|section| addr.wrapping_sub(section.addr) < section.size)
+        {
+            return None;
+        }
+        nearest_symbol(&self.symbols, addr)
+    }
+}
+
+/// Find the last symbol at or before an address, in a list sorted by address.
+fn nearest_symbol(symbols: &[(u32, String)], addr: u32) -> Option<(&str, u32)> {
+    let idx = symbols.partition_point(|&(symbol_addr, _)| symbol_addr <= addr);
+    let (symbol_addr, name) = symbols.get(idx.checked_sub(1)?)?;
+    Some((name, addr - symbol_addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entitlements() {
+        let plist = b"<plist/>";
+        let mut signature = Vec::new();
+        // Superblob with two entries: something else, then the entitlements.
+        for word in [0xfade0cc0, 0, 2, 0, 28, 5, 36] {
+            signature.extend_from_slice(&u32::to_be_bytes(word));
+        }
+        for word in [0xfade0c02, 8, 0xfade7171, 8 + plist.len() as u32] {
+            signature.extend_from_slice(&u32::to_be_bytes(word));
+        }
+        signature.extend_from_slice(plist);
+        assert_eq!(find_entitlements(&signature), Some(&plist[..]));
+
+        // No entitlements blob
+        assert_eq!(find_entitlements(&signature[..36]), None);
+        // Truncated
+        assert_eq!(find_entitlements(&signature[..signature.len() - 1]), None);
+        // Not a superblob
+        assert_eq!(find_entitlements(&[0; 16]), None);
+    }
+
+    #[test]
+    fn nearest_symbols() {
+        let symbols = vec![
+            (0x1000, "_a".to_string()),
+            (0x1010, "_b".to_string()),
+            (0x1010, "_c".to_string()),
+            (0x1040, "_d".to_string()),
+        ];
+        assert_eq!(nearest_symbol(&symbols, 0xfff), None);
+        assert_eq!(nearest_symbol(&symbols, 0x1000), Some(("_a", 0)));
+        assert_eq!(nearest_symbol(&symbols, 0x100c), Some(("_a", 0xc)));
+        assert_eq!(nearest_symbol(&symbols, 0x1012), Some(("_c", 2)));
+        assert_eq!(nearest_symbol(&symbols, 0x2000), Some(("_d", 0xfc0)));
+        assert_eq!(nearest_symbol(&[], 0x1000), None);
+    }
 }
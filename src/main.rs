@@ -23,23 +23,39 @@
 #[macro_use]
 mod log;
 mod abi;
+mod api_trace;
 mod audio;
+mod benchmark;
 mod bundle;
+mod clock;
+mod config;
 mod cpu;
 mod dyld;
 mod font;
 mod frameworks;
 mod fs;
 mod image;
+mod inspect;
+mod launcher;
 mod libc;
 mod licenses;
+mod location;
 mod mach_o;
 mod mem;
+mod memory_tool;
 mod objc;
+mod plugins;
+mod replay;
+mod report;
+mod scripting;
+mod snapshots;
+mod sqlite3;
 mod stack;
+mod terminal;
+mod tls;
 mod window;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Current version. See `build.rs` for how this is generated.
 const VERSION: &str = include_str!(concat!(env!("OUT_DIR"), "/version.txt"));
@@ -47,6 +63,11 @@ const VERSION: &str = include_str!(concat!(env!("OUT_DIR"), "/version.txt"));
 const USAGE: &str = "\
 Usage:
     touchHLE path/to/some.app
+    touchHLE --inspect path/to/some.app
+    touchHLE
+
+Without the path to an app, touchHLE lists the apps in the 'touchHLE_apps'
+directory and asks which one to run. When that app exits, it asks again.
 
 General options:
     --help
@@ -55,17 +76,325 @@ General options:
     --copyright
         Display copyright, authorship and license information.
 
+    --inspect
+        Instead of running the app, print information about it: some of its
+        Info.plist, whether it's encrypted, its entitlements, the libraries it
+        links to, and which of the symbols it needs touchHLE has or doesn't
+        have implementations of. This can help predict whether an app will
+        work before trying it or reporting a problem with it.
+
+    --config-dir=...
+        Set the directory on the host that holds configuration files, which
+        give options without a long command line. They are TOML files where
+        each key is the name of an option without the leading '--', e.g.:
+
+            internal-resolution = 2
+            frame-rate = \"vsync\"
+            fullscreen = true
+            auto-tap = [\"160,240,5\", \"160,400,10\"]
+
+        'default.toml' has options for every app, and a file named after an
+        app's bundle identifier, e.g. 'com.example.game.toml', has options for
+        that app only. The app's file takes priority over 'default.toml', and
+        the command line takes priority over both.
+
+        The default is a directory called 'touchHLE_config' in the current
+        directory.
+
+    --no-config
+        Ignore the configuration files.
+
+    --scripts-dir=...
+        Set the directory on the host that holds Lua scripts, which can patch
+        apps, e.g. to skip a broken copy protection check. Each app's script is
+        in a file named after its bundle identifier, e.g.
+        'com.example.game.lua'. See the README in 'touchHLE_scripts' for what
+        scripts can do.
+
+        The default is a directory called 'touchHLE_scripts' in the current
+        directory.
+
+    --plugin=...
+        Use a plugin that was built into touchHLE, which adds implementations
+        of functions and classes that aren't part of touchHLE itself, e.g. for
+        a middleware SDK that only a few apps use. This is best put in an
+        app's configuration file. See 'src/plugins.rs' for how to build
+        plugins in.
+
+        To use several plugins, use several '--plugin=' arguments.
+
+    --report
+        Write a compatibility report, with information about the app, anything
+        it used that touchHLE doesn't implement, whether touchHLE crashed, the
+        average frame rate and how long the app ran for, to a JSON file. The
+        file is in the current directory and named after the app's bundle ID,
+        e.g. 'touchHLE_report_com.example.game.json'. It's written when the app
+        exits or touchHLE crashes, and can be attached to a submission to the
+        compatibility tracker.
+
 View options:
-    --scale-hack=...
+    --internal-resolution=...
         Set a scaling factor for the window. touchHLE will attempt to run the
-        app with an increased internal resolution. This is a hack and there's
-        no guarantee it will work correctly for all apps.
-
-        The default is no scale hack, which is equivalent to a value of 1 (i.e.
+        app with an increased internal resolution: OpenGL ES content is
+        rendered at this multiple of the iPhone's 320×480 resolution, with
+        viewports, scissor boxes, line widths and point sizes scaled to match,
+        and the rest of the app's UI is scaled up to the same resolution. The
+        app still sees a 320×480 screen. This is a hack and there's no
+        guarantee it will work correctly for all apps.
+
+        The default is no scaling, which is equivalent to a value of 1 (i.e.
         a scale of 1×).
 
         This is a natural number that is at least 1.
 
+    --scale-hack=...
+        Old name for --internal-resolution=.
+
+    --display-filter=...
+        Choose a post-processing filter for the window's contents, including
+        the virtual cursor. The filters treat the window as an iPhone screen of
+        320×480 pixels, so they look best when the window is larger than that,
+        e.g. when it's been resized or made fullscreen. The options are:
+
+        - 'linear': no filter.
+        - 'nearest': blocky pixels.
+        - 'sharp-bilinear': blocky pixels, but with smooth edges between them,
+          which avoids uneven pixel sizes at non-integer scales.
+        - 'crt': scanlines and an aperture grille, like a CRT monitor.
+        - 'lcd-grid': visible gaps between pixels, like an old LCD.
+
+        The default is 'linear'.
+
+    --fullscreen
+        Start with the window in borderless fullscreen mode. Fullscreen can
+        also be toggled with Ctrl+F (see --hotkeys-file=). The window can
+        otherwise be resized freely, and the app's content is kept at the
+        right aspect ratio with black bars around it.
+
+    --display=...
+        Choose which of the host's displays (monitors) to open the window on,
+        counting from 0. By default, the window goes back to where it was last
+        time the app was run, or otherwise to the primary display.
+
+    --window-geometry-dir=...
+        Set the directory on the host where the position, size and fullscreen
+        state of each app's window is remembered, in a file named after the
+        app's bundle identifier.
+
+        The default is a directory called 'touchHLE_window_geometry' in the
+        current directory.
+
+    --open-external-links
+        Open links to websites in the host's web browser when they are tapped
+        in a web view inside the app, or when the app asks to open them. On a
+        real device, the app would quit when it opens a website, so touchHLE
+        exits after opening it. By default, such links do nothing.
+
+    --photos-dir=...
+        Set the directory on the host whose images are offered when the app
+        asks the user to choose a photo. PNG, JPEG, GIF and BMP images are
+        supported.
+
+        The default is a directory called 'touchHLE_photos' in the current
+        directory.
+
+    --music-dir=...
+        Set the directory on the host whose songs make up the iPod library,
+        for apps that let you play your own music. MP3, AAC, WAV, CAF and AIFF
+        files are supported.
+
+        The default is a directory called 'touchHLE_music' in the current
+        directory.
+
+    --map-tile-server=...
+        Set the server that map tiles are downloaded from, for apps that show
+        maps. The value is a URL template where '{z}', '{x}' and '{y}' are
+        replaced with the zoom level and tile co-ordinates, e.g.
+        '--map-tile-server=http://localhost:8080/{z}/{x}/{y}.png'.
+
+        Both HTTP and HTTPS tile servers can be used. Please respect the tile
+        server's usage policy.
+
+        By default, there is no tile server, and only tiles that are already
+        in the tile directory are shown.
+
+    --map-tile-dir=...
+        Set the directory on the host where map tiles are cached, laid out as
+        'ZOOM/X/Y.png'. Tiles can be put there in advance to use maps
+        offline.
+
+        The default is a directory called 'touchHLE_map_tiles' in the current
+        directory.
+
+    --store-dir=...
+        Set the directory on the host that holds the simulated App Store, for
+        apps with in-app purchases. Each app's products are listed in a file
+        named after its bundle identifier, e.g. 'com.example.game.txt', like
+        this:
+
+            [com.example.game.levels]
+            title = More Levels
+            description = Twenty new levels to play.
+            price = 0.99
+            type = non-consumable
+            results = cancelled, purchased
+
+        'type' is 'consumable' (the default), 'non-consumable' or
+        'subscription'. 'results' is optional and lists how successive
+        purchases turn out: 'purchased', 'failed', 'cancelled', 'not-allowed'
+        or 'invalid'. Once the list runs out, purchases succeed. A 'locale'
+        line before the first product sets the locale of the prices.
+
+        Purchases are recorded in a '.receipts' file next to the products
+        file, so they can be restored later.
+
+        The default is a directory called 'touchHLE_store' in the current
+        directory.
+
+    --contacts=...
+        Set a file on the host whose contacts make up the address book, for
+        apps that let you pick a friend to challenge or share with. This can
+        be a vCard file ('.vcf') exported from an address book app, or a CSV
+        file ('.csv') with a header row naming its columns, such as
+        'First Name', 'Last Name', 'Mobile Phone' and 'E-mail Address'.
+
+        By default, the address book is empty.
+
+    --mail-dir=...
+        Set a directory on the host where messages the app sends by e-mail,
+        e.g. from a feedback button, are saved as '.eml' files. Most mail
+        clients can open these files and send the messages for real.
+
+        By default, sent messages go nowhere, but the app is told they were
+        sent.
+
+    --open-mail-client
+        Open messages the app sends by e-mail in the host's mail client, so
+        they can be checked and sent for real. Attachments are left out.
+
+    --network-rules-dir=...
+        Set the directory on the host that holds network rules, for apps whose
+        servers are gone. Each app's rules are in a file named after its
+        bundle identifier, e.g. 'com.example.game.txt', like this:
+
+            scores.example.com = scores.example.org
+            *.example.com = localhost:8080
+            api.example.com/news.xml = file:news.xml
+            203.0.113.7:5000 = 192.168.1.20
+
+        The left side is a host name or IP address, optionally with a port and
+        a path prefix. The right side is where to go instead, optionally with
+        a port and 'http://' or 'https://', or 'file:' and a file in the rules
+        directory to answer HTTP requests with. The first matching rule is
+        used. Lines starting with '#' are comments.
+
+        The default is a directory called 'touchHLE_network_rules' in the
+        current directory.
+
+    --relaxed-tls
+        Don't check that servers the app connects to over HTTPS or TLS have a
+        valid certificate for their name, and allow old protocol versions if
+        the host supports them. This is for apps whose servers are gone but
+        have been redirected to a replacement, e.g. with the hosts file or
+        --network-rules-dir=. Connections are still encrypted, but no longer
+        safe from tampering.
+
+    --status-bar
+        Draw the status bar at the top of the screen, with the time, the
+        battery level and a carrier name, unless the app hides it. Whether or
+        not it's drawn, the app is told that it takes up the top 20 points of
+        the screen, like on a real device.
+
+    --carrier=...
+        Set the carrier name shown in the status bar, as in
+        '--carrier=\"My Carrier\"'.
+
+        The default is touchHLE.
+
+    --frame-rate=...
+        Set the refresh rate of the simulated screen in Hz, as in
+        '--frame-rate=30'. CADisplayLink callbacks are locked to it, so this
+        sets the frame rate of games that use one. Use '--frame-rate=vsync'
+        to follow the refresh rate of the host's display instead.
+
+        The default is 60, like a real device.
+
+Device options:
+    --system-version=...
+        Set the iPhone OS version the app is told it's running on, as in
+        '--system-version=3.1.3'. Some apps refuse to run or change their
+        behavior depending on the version.
+
+        The default is 2.0.
+
+    --device-model=...
+        Set the device model the app is told it's running on, as in
+        '--device-model=\"iPod touch\"'.
+
+        The default is iPhone.
+
+    --battery-level=...
+        Report a fixed battery level to the app, as a percentage from 0 to 100.
+        The device is then always running on its battery.
+
+        By default, the host's battery level and charging state are reported.
+        If the host has no battery, the device is always plugged in and fully
+        charged.
+
+    --allow-microphone
+        Let the app record audio from the host's default microphone. By
+        default, apps that record audio get silence, as if the microphone
+        were muted.
+
+    --location=...
+        Turn on location services and set where the device reports being.
+        This can be a fixed coordinate in degrees, optionally followed by an
+        altitude in meters, as in '--location=51.5007,-0.1246'. It can also
+        be 'gpx:PATH', to replay the track in a GPX file in real time, or
+        'file:PATH', to read a coordinate in the same format from a text file
+        that is checked for changes every second, so another program on the
+        host can keep it up to date.
+
+        By default, location services are off, as if the user had denied the
+        app access to them.
+
+    --gamekit-network=...
+        Let apps that use GameKit for local multiplayer find and connect to
+        other copies of touchHLE, in place of Bluetooth. This can be 'lan', to
+        find them on the local network, or 'loopback', to find them on the
+        same machine. Both copies must be started with the same option.
+
+        By default, Bluetooth is unavailable, and GameKit sessions never find
+        any peers.
+
+Audio options:
+    --volume=...
+        Set the volume of all the app's audio, as a percentage, e.g.
+        '--volume=50'. It can also be changed in the options menu (Ctrl+M).
+
+        The default is 100.
+
+    --audio-buffer-size=...
+        Set the size, in frames, of each buffer of audio mixed for the host's
+        audio device, as in '--audio-buffer-size=512'. Smaller buffers mean
+        less latency, but may crackle on slower hosts.
+
+        By default, OpenAL Soft chooses the size.
+
+    --audio-latency=...
+        Set how far ahead, in milliseconds, audio is rendered for apps that
+        generate it while it plays, as in '--audio-latency=100'. Try raising
+        this if such an app's audio crackles.
+
+        The default is 50.
+
+    --audio-resampler=...
+        Choose how audio is resampled when its sample rate differs from the
+        host audio device's. The options, from fastest to highest quality, are
+        'point', 'linear', 'cubic' and 'sinc'.
+
+        By default, OpenAL Soft's default resampler is used.
+
 Game controller options:
     --deadzone=...
         Configures the size of the \"dead zone\" for analog stick inputs.
@@ -109,6 +438,182 @@ Game controller options:
         This is a floating-point (decimal) number of degrees, without a degree
         symbol. It may be negative.
 
+    --tilt-source=...
+        Choose where device tilt (accelerometer input) comes from. The options
+        are:
+
+        - 'sensor': the motion sensor of a game controller that has one, for
+          example a DualShock 4 or a Switch Pro Controller. This gives real
+          accelerometer input, so the tilt range and offset options don't
+          apply.
+        - 'stick': the left analog stick of a game controller, or whatever the
+          app's controller mapping binds to tilt (see --controller-map-dir=).
+        - 'keys': the arrow keys on the keyboard. They aren't used while the
+          app is showing a text input.
+        - 'auto': the motion sensor of a connected controller if there is one,
+          otherwise both the left analog stick and the arrow keys.
+
+        The default is 'auto'.
+
+    --split-coop
+        Split the screen between two players, for games where each player
+        touches their own half of the screen. The mouse can only touch the left
+        half, and the game controller's virtual cursor can only touch the right
+        half. Touches from the two can happen at the same time.
+
+        With two or more game controllers connected, the first controller's
+        cursor gets the left half and the second's gets the right half instead,
+        so the mouse isn't needed.
+
+        The halves are the left and right of the window as it is currently
+        displayed, so this is most useful for games played in landscape.
+
+    --controller-map-dir=...
+        Set the directory on the host that holds game controller mappings,
+        for games that are easier to play with buttons bound to parts of the
+        screen. Each app's mapping is in a file named after its bundle
+        identifier, e.g. 'com.example.game.txt', like this:
+
+            a = touch 420, 270
+            start = shake
+            left-stick = joystick 80, 240, 50
+            dpad-left = tilt left
+
+            [player 2]
+            region = 240, 0, 240, 320
+            a = touch 440, 270
+
+        Buttons are 'a', 'b', 'x', 'y', 'back', 'guide', 'start',
+        'left-shoulder', 'right-shoulder', 'left-trigger', 'right-trigger',
+        'left-stick-click', 'right-stick-click', 'dpad-up', 'dpad-down',
+        'dpad-left', 'dpad-right', 'misc', 'paddle1' to 'paddle4' and
+        'touchpad'. A button can be bound to 'touch X, Y' (a point on the
+        screen as it is currently displayed, in points), 'cursor' (pressing
+        the virtual cursor), 'tilt left/right/up/down', 'shake',
+        'rotate-clockwise', 'rotate-counterclockwise', 'interruption' or
+        'none'. The sticks ('left-stick' and 'right-stick') can be bound to
+        'tilt', 'cursor', 'joystick X, Y, RADIUS' (a touch that is dragged
+        around a point) or 'none'. 'region = X, Y, WIDTH, HEIGHT' confines the
+        virtual cursor to part of the screen.
+
+        Lines before the first '[player N]' section apply to every controller,
+        and lines in a section apply on top of those to the Nth controller
+        connected. Controllers can be connected and disconnected at any time.
+        The D-pad and A/B buttons still control the on-screen keyboard when it
+        is shown. Lines starting with '#' are comments.
+
+        The default is a directory called 'touchHLE_controller_maps' in the
+        current directory.
+
+    --keyboard-map-dir=...
+        Set the directory on the host that holds keyboard mappings, which turn
+        keys into touches on parts of the screen, for games with on-screen
+        buttons or joysticks. Each app's mapping is in a file named after its
+        bundle identifier, e.g. 'com.example.game.txt', like this:
+
+            point fire = 420, 270
+            joystick move = 60, 260, 40
+
+            Space = fire
+            W = move up
+            A = move left
+            S = move down
+            D = move right
+
+        A 'point' is touched while any of its keys are held. A 'joystick' is
+        touched at its center (X, Y) and dragged up to its radius towards its
+        held keys. Points are on the screen as it is currently displayed, in
+        points. Keys use SDL's names for them, e.g. 'Left Shift' or 'Up'. The
+        arrow keys don't tilt the device if the mapping uses them, and the
+        keys don't touch anything while text input is active or Ctrl is held.
+        Lines starting with '#' are comments.
+
+        The default is a directory called 'touchHLE_keyboard_maps' in the
+        current directory, which comes with touchHLE so that mappings can be
+        shared.
+
+    --virtual-controls-dir=...
+        Set the directory on the host that holds layouts of on-screen virtual
+        controls, which are drawn on top of the app and turn presses with the
+        mouse or a touchscreen into touches, for handheld PCs and touchscreen
+        laptops. Each app's layout is in a file named after its bundle
+        identifier, e.g. 'com.example.game.txt', like this:
+
+            button fire = 420, 270, 30
+            button pause = 450, 20, 20 -> 470, 10
+            stick move = 70, 250, 50
+
+        Each control is a circle, given by its center (X, Y) and radius. A
+        'button' touches the app at its center while pressed, and a 'stick'
+        touches the app at its center and drags the touch towards where it's
+        pressed. '-> X, Y' moves where the app is touched. Points are on the
+        screen as it is currently displayed, in points. Lines starting with
+        '#' are comments.
+
+        The default is a directory called 'touchHLE_virtual_controls' in the
+        current directory, which comes with touchHLE so that layouts can be
+        shared.
+
+    --hotkeys-file=...
+        Change the keyboard shortcuts for touchHLE's own actions, by reading
+        them from a text file. Each line gives an action and the keys for it,
+        or 'none', for example:
+
+            screenshot = F12
+            pause = Ctrl+P, Pause
+            shake = none
+
+        The actions, and their default keys, are 'rotate-clockwise'
+        (Ctrl+Right), 'rotate-counterclockwise' (Ctrl+Left), 'shake' (Ctrl+Z),
+        'interruption' (Ctrl+I), 'pause' (Ctrl+P), 'screenshot' (Ctrl+S),
+        'fullscreen' (Ctrl+F), 'menu' (Ctrl+M), 'fast-forward' (Ctrl+Tab,
+        which makes the app run 4 times faster until it's pressed again) and
+        'virtual-controls' (Ctrl+O, which shows or hides the on-screen
+        controls). Saving and loading states isn't supported.
+        Keys use SDL's names for them, optionally after 'Ctrl+', 'Shift+',
+        'Alt+' and 'Cmd+'. Lines starting with '#' are comments. Screenshots
+        are saved in a directory called 'touchHLE_screenshots'.
+
+        The default is a file called 'touchHLE_hotkeys.txt' in the current
+        directory, which is used if it exists.
+
+    --vibration-strength=...
+        Set how strongly game controllers rumble when the app vibrates the
+        device, from 0 to 1. Use '--vibration-strength=0' to turn off rumble.
+
+        The default is 1.
+
+    --vibration-duration=...
+        Set how long game controllers rumble for when the app vibrates the
+        device, in seconds, as in '--vibration-duration=0.2'.
+
+        The default is 0.4, which is about as long as an iPhone vibrates.
+
+    --vibration-shape=...
+        Set how the strength of the rumble changes while it lasts:
+
+        - 'constant': full strength throughout, like an iPhone.
+        - 'fade': starts at full strength and fades away, like a tap.
+        - 'pulse': short bursts with gaps in between.
+
+        The default is 'constant'.
+
+    --haptics-file=...
+        Change the vibration settings for some apps, by reading them from a
+        text file. Each line gives an app's bundle identifier, or '*' for
+        every app, and the settings for it, separated by commas, for example:
+
+            * = shape fade
+            com.example.game = off
+            com.example.racer = strength 0.5, duration 0.2, shape pulse
+
+        The settings are 'off', and 'strength', 'duration' and 'shape', which
+        work like the options above. Later lines win. Lines starting with '#'
+        are comments.
+
+        The default is a file called 'touchHLE_haptics.txt' in the current
+        directory, which is used if it exists.
+
 Debugging options:
     --breakpoint=...
         This option sets a primitive breakpoint at a provided memory address.
@@ -121,89 +626,726 @@ Debugging options:
         e.g. 'T0xF00' or 'TF00'.
 
         To set multiple breakpoints, use several '--breakpoint=' arguments.
+
+    --objc-breakpoint=...
+        This option sets a breakpoint on an Objective-C method, written as in
+        '-[UIView setFrame:]' or '+[NSObject alloc]'. Messages sent to
+        subclasses of the class also match, and '*' matches any class. When the
+        breakpoint is hit, touchHLE prints the receiver, the arguments and the
+        registers, then pauses until Enter is pressed.
+
+        The arguments are printed as raw 32-bit words, with the class of any
+        that are objects.
+
+        To set multiple breakpoints, use several '--objc-breakpoint=' arguments.
+
+    --watchpoint=...
+        This option sets a watchpoint on a range of memory. When code in the
+        app reads or writes it, touchHLE logs the address, the value, and the
+        instruction that did it, with the nearest symbol if the app has them.
+        This helps with finding out what is corrupting a value.
+
+        The value is a hexadecimal address, with an optional '0x' prefix, then
+        optionally the size in bytes (the default is 4) and the kind of access:
+        'w' for writes (the default), 'r' for reads or 'rw' for both, e.g.
+        '--watchpoint=0x1F2A4' or '--watchpoint=1F2A4,16,rw'. Accesses by
+        touchHLE itself, e.g. by memcpy(), aren't noticed. The app runs much
+        more slowly while watchpoints are set.
+
+        To set multiple watchpoints, use several '--watchpoint=' arguments.
+
+    --watchpoint-pause
+        When a watchpoint is hit, also print the registers and a stack trace,
+        then pause until Enter is pressed.
+
+    --gles-debug
+        Log every OpenGL ES call the app makes, with its arguments, and check
+        for errors after each one. Some calls with invalid arguments are
+        skipped, as a real device would do, instead of making touchHLE crash.
+        Warnings are also logged for some things that would behave differently
+        on the GPU of the original iPhone and iPod touch.
+
+        This produces a lot of output and makes rendering much slower.
+
+    --trace-api=...
+        Log every call the app makes to touchHLE's implementation of a
+        function or Objective-C method that matches a pattern, with its
+        arguments and return value, e.g. '--trace-api=fopen',
+        '--trace-api=gl*' or '--trace-api=-[UIView *]'. '*' matches anything.
+        A pattern can also be the name of a module of touchHLE, to trace
+        everything it implements, e.g. '--trace-api=libc::stdio' or
+        '--trace-api=uikit'.
+
+        This is useful for finding out what an app was doing when it hung or
+        crashed. It can produce a lot of output, so '--log-file=' may help.
+
+        To use several patterns, use several '--trace-api=' arguments.
+
+    --log=...
+        Change how much is logged by parts of touchHLE, as a comma-separated
+        list of module names and levels, e.g. '--log=objc=debug,mem=off'.
+        Submodules use the same level as their module unless they're given
+        their own. A level on its own applies to every module, e.g.
+        '--log=debug'.
+
+        The levels are 'off', 'info' (warnings and errors, the default),
+        'debug' (also messages that are only useful when debugging that part
+        of touchHLE) and 'trace' (also very frequent messages, e.g. for every
+        file read).
+
+        This option can be used several times. Later settings win.
+
+    --log-file=...
+        Also write everything that's logged to a file, with the time in
+        seconds since touchHLE started before each message, e.g.
+        '--log-file=touchHLE_log.txt'. The file is overwritten.
+
+    --memory-tool
+        Accept commands typed into the terminal for searching the app's memory
+        for values, watching addresses, and changing or freezing the values at
+        them, like a cheat engine. Type 'help' for a list of commands.
+
+        This can't be used together with '--objc-breakpoint=' or
+        '--watchpoint-pause', which also read from the terminal.
+
+Testing options:
+    --delegate-class=...
+        Replace the app delegate loaded from the main nib file with a new
+        instance of the named class, which may be any class in the app. This is
+        useful if an app contains a delegate intended for testing. The original
+        delegate isn't sent anything, so the new class has to implement all
+        the delegate methods the app needs.
+
+    --auto-tap=...
+        Tap the screen automatically at a fixed time after the app finishes
+        launching. This can be used to get through menus so that automated
+        testing can reach gameplay.
+
+        The value is the X and Y co-ordinates of the tap, and the delay in
+        seconds, separated by commas, e.g. '--auto-tap=160,240,5.5'. The
+        co-ordinates are in points, relative to the top-left corner of the
+        screen in portrait orientation.
+
+        To tap several times, use several '--auto-tap=' arguments.
+
+    --record-input=...
+        Record the input the app gets (touches, keys, buttons, device tilt and
+        the seeds of its random number generators) to a file, so that it can
+        be replayed with '--replay-input='. This is for regression testing,
+        tool-assisted play and reproducible bug reports.
+
+    --replay-input=...
+        Replay input recorded with '--record-input=' for the same app. Input
+        from the host is ignored, except for quitting, until the recording
+        runs out. Both options can be used at once to extend a recording.
+
+        Input is replayed on the same frames it was recorded on, but touchHLE
+        doesn't emulate time, so an app that uses the time rather than
+        counting frames may not replay exactly.
+
+    --memory-warning=...
+        Simulate a low memory warning at a fixed time after the app finishes
+        launching, so that the app's handling of memory pressure can be tested.
+
+        The value is the delay in seconds, e.g. '--memory-warning=30'.
+
+        To simulate several warnings, use several '--memory-warning=' arguments.
+
+    --handle-url-scheme=...
+        When the app asks to open a URL with this scheme, deliver it back to
+        the app itself with 'application:handleOpenURL:', as if another app had
+        opened it. This can be used to test inter-app flows, like logging in
+        through another app that then returns to this one.
+
+        The value is a scheme without the colon, e.g.
+        '--handle-url-scheme=myapp'.
+
+        To handle several schemes, use several '--handle-url-scheme='
+        arguments.
+
+    --uuid-seed=...
+        Generate UUIDs from a fixed seed instead of randomly, so that the app
+        sees the same sequence of UUIDs on every run. This is useful when
+        replaying a recorded session.
+
+        The value is a non-negative integer, e.g. '--uuid-seed=1234'.
+
+    --benchmark-seconds=...
+    --benchmark-frames=...
+        Run the app for a number of seconds or frames, then print statistics
+        and exit: frame times, how the time was split between emulating the
+        CPU, OpenGL ES and everything else, and how much the app allocated.
+        This is for measuring the effects of changes to touchHLE objectively.
+
+        Measuring starts when the app presents its first frame. If both
+        options are used, touchHLE stops at whichever limit is reached first.
+        For results that can be compared, use the same limits, app and input
+        each time, e.g. with '--replay-input='.
+
+    --headless
+        Don't show the window. The app still runs and renders as normal. This
+        is useful for automated testing, e.g. with '--snapshot='.
+
+    --snapshot=...
+        Capture what the app draws on a particular frame, counting from 1 for
+        the first frame the app presents, and compare it against a golden
+        image saved by an earlier run. If there's no golden image yet, the
+        frame is saved as the golden image. After the last frame to capture,
+        touchHLE prints the results and exits, with a non-zero exit code if any
+        frame differed. This is for finding rendering regressions, normally
+        together with '--replay-input=' so that the app gets the same input
+        each time.
+
+        To capture several frames, use several '--snapshot=' arguments.
+
+    --snapshot-dir=...
+        Change the directory golden images are kept in. Each app's images are
+        in a subdirectory named after its bundle ID. When a frame differs, what
+        was captured is saved next to its golden image, with '.actual.bmp' at
+        the end of the file name. The default is a directory called
+        'touchHLE_snapshots' in the current directory.
+
+    --snapshot-tolerance=...
+        Allow the color channels of pixels to differ from the golden image by
+        up to this much, from 0 (the default, exact match) to 255, e.g.
+        '--snapshot-tolerance=4'. This is useful when comparing images
+        rendered by different GPUs or drivers.
 ";
 
 pub struct Options {
+    scripts_dir: PathBuf,
+    plugins: Vec<String>,
+    report: bool,
     scale_hack: std::num::NonZeroU32,
+    display_filter: window::DisplayFilter,
+    fullscreen: bool,
+    display: Option<u32>,
+    window_geometry_dir: PathBuf,
+    open_external_links: bool,
+    photos_dir: PathBuf,
+    music_dir: PathBuf,
+    map_tile_server: Option<String>,
+    map_tile_dir: PathBuf,
+    store_dir: PathBuf,
+    /// [None] means the address book is empty.
+    contacts_file: Option<PathBuf>,
+    /// [None] means sent e-mails aren't saved.
+    mail_dir: Option<PathBuf>,
+    open_mail_client: bool,
+    network_rules_dir: PathBuf,
+    relaxed_tls: bool,
+    status_bar: bool,
+    carrier: String,
+    /// In Hz. [None] means the host display's refresh rate.
+    frame_rate: Option<f64>,
+    system_version: String,
+    device_model: String,
+    /// Fixed battery level in the range [0, 1].
+    battery_level: Option<f32>,
+    allow_microphone: bool,
+    /// [None] means location services are off.
+    location: Option<location::LocationSource>,
+    /// [None] means GameKit sessions never find peers.
+    gamekit_network: Option<frameworks::game_kit::transport::NetworkMode>,
+    /// In frames. [None] means OpenAL Soft's default.
+    audio_buffer_size: Option<u32>,
+    /// In the range [0, 1].
+    volume: f32,
+    /// In seconds.
+    audio_latency: f64,
+    /// [None] means OpenAL Soft's default.
+    audio_resampler: Option<audio::mixer::Resampler>,
     deadzone: f32,
     x_tilt_range: f32,
     y_tilt_range: f32,
     x_tilt_offset: f32,
     y_tilt_offset: f32,
+    tilt_source: window::TiltSource,
+    split_coop: bool,
+    controller_map_dir: PathBuf,
+    keyboard_map_dir: PathBuf,
+    virtual_controls_dir: PathBuf,
+    hotkeys_file: PathBuf,
+    /// In the range [0, 1].
+    vibration_strength: f32,
+    /// In seconds.
+    vibration_duration: f64,
+    vibration_shape: window::VibrationShape,
+    haptics_file: PathBuf,
     breakpoints: Vec<u32>,
+    objc_breakpoints: Vec<objc::SelectorBreakpoint>,
+    watchpoints: Vec<mem::Watchpoint>,
+    watchpoint_pause: bool,
+    gles_debug: bool,
+    trace_api: Vec<api_trace::Pattern>,
+    log_filter: log::Filter,
+    log_file: Option<PathBuf>,
+    memory_tool: bool,
+    delegate_class: Option<String>,
+    /// X and Y co-ordinates, and delay in seconds.
+    auto_taps: Vec<(f32, f32, f64)>,
+    record_input: Option<PathBuf>,
+    replay_input: Option<PathBuf>,
+    /// Delays in seconds.
+    memory_warnings: Vec<f64>,
+    /// Lowercase.
+    handled_url_schemes: Vec<String>,
+    uuid_seed: Option<u64>,
+    benchmark_seconds: Option<f64>,
+    benchmark_frames: Option<u32>,
+    headless: bool,
+    snapshot_frames: Vec<u32>,
+    snapshot_dir: PathBuf,
+    snapshot_tolerance: u8,
 }
 
-fn main() -> Result<(), String> {
-    println!("touchHLE {} — https://touchhle.org/", VERSION);
-    println!();
-
-    fn parse_degrees(arg: &str, name: &str) -> Result<f32, String> {
-        let arg: f32 = arg
-            .parse()
-            .map_err(|_| format!("Value for {} is invalid", name))?;
-        if !arg.is_finite() || !(-360.0..=360.0).contains(&arg) {
-            return Err(format!("Value for {} is out of range", name));
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            scripts_dir: PathBuf::from("touchHLE_scripts"),
+            plugins: Vec::new(),
+            report: false,
+            scale_hack: std::num::NonZeroU32::new(1).unwrap(),
+            display_filter: window::DisplayFilter::Linear,
+            fullscreen: false,
+            display: None,
+            window_geometry_dir: PathBuf::from("touchHLE_window_geometry"),
+            open_external_links: false,
+            photos_dir: PathBuf::from("touchHLE_photos"),
+            music_dir: PathBuf::from("touchHLE_music"),
+            map_tile_server: None,
+            map_tile_dir: PathBuf::from("touchHLE_map_tiles"),
+            store_dir: PathBuf::from("touchHLE_store"),
+            contacts_file: None,
+            mail_dir: None,
+            open_mail_client: false,
+            network_rules_dir: PathBuf::from("touchHLE_network_rules"),
+            relaxed_tls: false,
+            status_bar: false,
+            carrier: "touchHLE".to_string(),
+            frame_rate: Some(60.0),
+            system_version: "2.0".to_string(),
+            device_model: "iPhone".to_string(),
+            battery_level: None,
+            allow_microphone: false,
+            location: None,
+            gamekit_network: None,
+            audio_buffer_size: None,
+            volume: 1.0,
+            audio_latency: 0.05,
+            audio_resampler: None,
+            deadzone: 0.1,
+            x_tilt_range: 60.0,
+            y_tilt_range: 60.0,
+            x_tilt_offset: 0.0,
+            y_tilt_offset: 0.0,
+            tilt_source: window::TiltSource::Auto,
+            split_coop: false,
+            controller_map_dir: PathBuf::from("touchHLE_controller_maps"),
+            keyboard_map_dir: PathBuf::from("touchHLE_keyboard_maps"),
+            virtual_controls_dir: PathBuf::from("touchHLE_virtual_controls"),
+            hotkeys_file: PathBuf::from("touchHLE_hotkeys.txt"),
+            vibration_strength: 1.0,
+            vibration_duration: 0.4,
+            vibration_shape: window::VibrationShape::Constant,
+            haptics_file: PathBuf::from("touchHLE_haptics.txt"),
+            breakpoints: Vec::new(),
+            objc_breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            watchpoint_pause: false,
+            gles_debug: false,
+            trace_api: Vec::new(),
+            log_filter: log::Filter::default(),
+            log_file: None,
+            memory_tool: false,
+            delegate_class: None,
+            auto_taps: Vec::new(),
+            record_input: None,
+            replay_input: None,
+            memory_warnings: Vec::new(),
+            handled_url_schemes: Vec::new(),
+            uuid_seed: None,
+            benchmark_seconds: None,
+            benchmark_frames: None,
+            headless: false,
+            snapshot_frames: Vec::new(),
+            snapshot_dir: PathBuf::from("touchHLE_snapshots"),
+            snapshot_tolerance: 0,
         }
-        Ok(arg)
     }
+}
 
-    let mut args = std::env::args();
-    let _ = args.next().unwrap(); // skip argv[0]
-
-    let mut options = Options {
-        scale_hack: std::num::NonZeroU32::new(1).unwrap(),
-        deadzone: 0.1,
-        x_tilt_range: 60.0,
-        y_tilt_range: 60.0,
-        x_tilt_offset: 0.0,
-        y_tilt_offset: 0.0,
-        breakpoints: Vec::new(),
-    };
+impl Options {
+    /// Apply an option given as a command-line argument. Returns [false] if
+    /// the argument isn't an option.
+    fn parse_argument(&mut self, arg: &str) -> Result<bool, String> {
+        fn parse_degrees(arg: &str, name: &str) -> Result<f32, String> {
+            let arg: f32 = arg
+                .parse()
+                .map_err(|_| format!("Value for {} is invalid", name))?;
+            if !arg.is_finite() || !(-360.0..=360.0).contains(&arg) {
+                return Err(format!("Value for {} is out of range", name));
+            }
+            Ok(arg)
+        }
 
-    let mut bundle_path: Option<PathBuf> = None;
-    for arg in args {
-        if arg == "--help" {
-            println!("{}", USAGE);
-            return Ok(());
-        } else if arg == "--copyright" {
-            licenses::print();
-            return Ok(());
-        } else if bundle_path.is_none() {
-            bundle_path = Some(PathBuf::from(arg));
-        } else if let Some(value) = arg.strip_prefix("--scale-hack=") {
-            options.scale_hack = value
+        if let Some(value) = arg.strip_prefix("--scripts-dir=") {
+            self.scripts_dir = PathBuf::from(value);
+        } else if let Some(value) = arg.strip_prefix("--plugin=") {
+            self.plugins.push(value.to_string());
+        } else if arg == "--report" {
+            self.report = true;
+        } else if let Some(value) = arg
+            .strip_prefix("--internal-resolution=")
+            .or_else(|| arg.strip_prefix("--scale-hack="))
+        {
+            self.scale_hack = value
+                .parse()
+                .map_err(|_| "Invalid internal resolution factor".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--display-filter=") {
+            self.display_filter = window::DisplayFilter::parse(value)?;
+        } else if arg == "--fullscreen" {
+            self.fullscreen = true;
+        } else if let Some(value) = arg.strip_prefix("--display=") {
+            self.display = Some(
+                value
+                    .parse()
+                    .map_err(|_| "Invalid display number".to_string())?,
+            );
+        } else if let Some(value) = arg.strip_prefix("--window-geometry-dir=") {
+            self.window_geometry_dir = PathBuf::from(value);
+        } else if arg == "--open-external-links" {
+            self.open_external_links = true;
+        } else if let Some(value) = arg.strip_prefix("--photos-dir=") {
+            self.photos_dir = PathBuf::from(value);
+        } else if let Some(value) = arg.strip_prefix("--music-dir=") {
+            self.music_dir = PathBuf::from(value);
+        } else if let Some(value) = arg.strip_prefix("--map-tile-server=") {
+            self.map_tile_server = Some(value.to_string());
+        } else if let Some(value) = arg.strip_prefix("--map-tile-dir=") {
+            self.map_tile_dir = PathBuf::from(value);
+        } else if let Some(value) = arg.strip_prefix("--store-dir=") {
+            self.store_dir = PathBuf::from(value);
+        } else if let Some(value) = arg.strip_prefix("--contacts=") {
+            self.contacts_file = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--mail-dir=") {
+            self.mail_dir = Some(PathBuf::from(value));
+        } else if arg == "--open-mail-client" {
+            self.open_mail_client = true;
+        } else if let Some(value) = arg.strip_prefix("--network-rules-dir=") {
+            self.network_rules_dir = PathBuf::from(value);
+        } else if arg == "--relaxed-tls" {
+            self.relaxed_tls = true;
+        } else if arg == "--status-bar" {
+            self.status_bar = true;
+        } else if let Some(value) = arg.strip_prefix("--carrier=") {
+            self.carrier = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("--frame-rate=") {
+            self.frame_rate = if value == "vsync" {
+                None
+            } else {
+                let rate: f64 = value
+                    .parse()
+                    .map_err(|_| "Invalid frame rate".to_string())?;
+                if !rate.is_finite() || rate < 1.0 {
+                    return Err("Frame rate is out of range".to_string());
+                }
+                Some(rate)
+            };
+        } else if let Some(value) = arg.strip_prefix("--system-version=") {
+            self.system_version = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("--device-model=") {
+            self.device_model = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("--battery-level=") {
+            let level: f32 = value
                 .parse()
-                .map_err(|_| "Invalid scale hack factor".to_string())?;
+                .map_err(|_| "Invalid battery level".to_string())?;
+            if !(0.0..=100.0).contains(&level) {
+                return Err("Battery level is out of range".to_string());
+            }
+            self.battery_level = Some(level / 100.0);
+        } else if arg == "--allow-microphone" {
+            self.allow_microphone = true;
+        } else if let Some(value) = arg.strip_prefix("--location=") {
+            self.location = Some(location::LocationSource::parse(value)?);
+        } else if let Some(value) = arg.strip_prefix("--gamekit-network=") {
+            self.gamekit_network =
+                Some(frameworks::game_kit::transport::NetworkMode::parse(value)?);
+        } else if let Some(value) = arg.strip_prefix("--volume=") {
+            let volume: f32 = value.parse().map_err(|_| "Invalid volume".to_string())?;
+            if !(0.0..=100.0).contains(&volume) {
+                return Err("Volume is out of range".to_string());
+            }
+            self.volume = volume / 100.0;
+        } else if let Some(value) = arg.strip_prefix("--audio-buffer-size=") {
+            let size: u32 = value
+                .parse()
+                .map_err(|_| "Invalid audio buffer size".to_string())?;
+            if !(64..=16384).contains(&size) {
+                return Err("Audio buffer size is out of range".to_string());
+            }
+            self.audio_buffer_size = Some(size);
+        } else if let Some(value) = arg.strip_prefix("--audio-latency=") {
+            let latency: f64 = value
+                .parse()
+                .map_err(|_| "Invalid audio latency".to_string())?;
+            if !(10.0..=1000.0).contains(&latency) {
+                return Err("Audio latency is out of range".to_string());
+            }
+            self.audio_latency = latency / 1000.0;
+        } else if let Some(value) = arg.strip_prefix("--audio-resampler=") {
+            self.audio_resampler = Some(audio::mixer::Resampler::parse(value)?);
         } else if let Some(value) = arg.strip_prefix("--deadzone=") {
-            options.deadzone = parse_degrees(value, "deadzone")?;
+            self.deadzone = parse_degrees(value, "deadzone")?;
         } else if let Some(value) = arg.strip_prefix("--x-tilt-range=") {
-            options.x_tilt_range = parse_degrees(value, "X tilt range")?;
+            self.x_tilt_range = parse_degrees(value, "X tilt range")?;
         } else if let Some(value) = arg.strip_prefix("--y-tilt-range=") {
-            options.y_tilt_range = parse_degrees(value, "Y tilt range")?;
+            self.y_tilt_range = parse_degrees(value, "Y tilt range")?;
         } else if let Some(value) = arg.strip_prefix("--x-tilt-offset=") {
-            options.x_tilt_offset = parse_degrees(value, "X tilt offset")?;
+            self.x_tilt_offset = parse_degrees(value, "X tilt offset")?;
         } else if let Some(value) = arg.strip_prefix("--y-tilt-offset=") {
-            options.y_tilt_offset = parse_degrees(value, "Y tilt offset")?;
+            self.y_tilt_offset = parse_degrees(value, "Y tilt offset")?;
+        } else if let Some(value) = arg.strip_prefix("--tilt-source=") {
+            self.tilt_source = window::TiltSource::parse(value)?;
+        } else if arg == "--split-coop" {
+            self.split_coop = true;
+        } else if let Some(value) = arg.strip_prefix("--controller-map-dir=") {
+            self.controller_map_dir = PathBuf::from(value);
+        } else if let Some(value) = arg.strip_prefix("--keyboard-map-dir=") {
+            self.keyboard_map_dir = PathBuf::from(value);
+        } else if let Some(value) = arg.strip_prefix("--virtual-controls-dir=") {
+            self.virtual_controls_dir = PathBuf::from(value);
+        } else if let Some(value) = arg.strip_prefix("--hotkeys-file=") {
+            self.hotkeys_file = PathBuf::from(value);
+        } else if let Some(value) = arg.strip_prefix("--vibration-strength=") {
+            let strength: f32 = value
+                .parse()
+                .map_err(|_| "Invalid vibration strength".to_string())?;
+            if !(0.0..=1.0).contains(&strength) {
+                return Err("Vibration strength is out of range".to_string());
+            }
+            self.vibration_strength = strength;
+        } else if let Some(value) = arg.strip_prefix("--vibration-duration=") {
+            let duration: f64 = value
+                .parse()
+                .map_err(|_| "Invalid vibration duration".to_string())?;
+            if !(0.0..=10.0).contains(&duration) {
+                return Err("Vibration duration is out of range".to_string());
+            }
+            self.vibration_duration = duration;
+        } else if let Some(value) = arg.strip_prefix("--vibration-shape=") {
+            self.vibration_shape = window::VibrationShape::parse(value)?;
+        } else if let Some(value) = arg.strip_prefix("--haptics-file=") {
+            self.haptics_file = PathBuf::from(value);
         } else if let Some(addr) = arg.strip_prefix("--breakpoint=") {
             let is_thumb = addr.starts_with('T');
             let addr = addr.strip_prefix('T').unwrap_or(addr);
             let addr = addr.strip_prefix("0x").unwrap_or(addr);
             let addr = u32::from_str_radix(addr, 16)
                 .map_err(|_| "Incorrect breakpoint syntax".to_string())?;
-            options
-                .breakpoints
+            self.breakpoints
                 .push(if is_thumb { addr | 0x1 } else { addr });
+        } else if let Some(spec) = arg.strip_prefix("--objc-breakpoint=") {
+            self.objc_breakpoints
+                .push(objc::SelectorBreakpoint::parse(spec)?);
+        } else if let Some(spec) = arg.strip_prefix("--watchpoint=") {
+            self.watchpoints.push(mem::Watchpoint::parse(spec)?);
+        } else if arg == "--watchpoint-pause" {
+            self.watchpoint_pause = true;
+        } else if arg == "--gles-debug" {
+            self.gles_debug = true;
+        } else if let Some(value) = arg.strip_prefix("--trace-api=") {
+            self.trace_api.push(api_trace::Pattern::parse(value)?);
+        } else if let Some(value) = arg.strip_prefix("--log=") {
+            self.log_filter.add(value)?;
+        } else if let Some(value) = arg.strip_prefix("--log-file=") {
+            self.log_file = Some(PathBuf::from(value));
+        } else if arg == "--memory-tool" {
+            self.memory_tool = true;
+        } else if let Some(value) = arg.strip_prefix("--delegate-class=") {
+            self.delegate_class = Some(value.to_string());
+        } else if let Some(value) = arg.strip_prefix("--auto-tap=") {
+            let parse = || -> Option<(f32, f32, f64)> {
+                let mut parts = value.split(',');
+                let x = parts.next()?.parse().ok()?;
+                let y = parts.next()?.parse().ok()?;
+                let delay = parts.next()?.parse().ok()?;
+                if parts.next().is_some() {
+                    return None;
+                }
+                Some((x, y, delay))
+            };
+            self.auto_taps
+                .push(parse().ok_or_else(|| "Incorrect auto-tap syntax".to_string())?);
+        } else if let Some(value) = arg.strip_prefix("--record-input=") {
+            self.record_input = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--replay-input=") {
+            self.replay_input = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--memory-warning=") {
+            let delay: f64 = value
+                .parse()
+                .map_err(|_| "Invalid memory warning delay".to_string())?;
+            self.memory_warnings.push(delay);
+        } else if let Some(value) = arg.strip_prefix("--handle-url-scheme=") {
+            self.handled_url_schemes.push(value.to_ascii_lowercase());
+        } else if let Some(value) = arg.strip_prefix("--uuid-seed=") {
+            let seed: u64 = value.parse().map_err(|_| "Invalid UUID seed".to_string())?;
+            self.uuid_seed = Some(seed);
+        } else if let Some(value) = arg.strip_prefix("--benchmark-seconds=") {
+            let seconds: f64 = value
+                .parse()
+                .map_err(|_| "Invalid benchmark duration".to_string())?;
+            if !seconds.is_finite() || seconds <= 0.0 {
+                return Err("Benchmark duration is out of range".to_string());
+            }
+            self.benchmark_seconds = Some(seconds);
+        } else if let Some(value) = arg.strip_prefix("--benchmark-frames=") {
+            let frames: u32 = value
+                .parse()
+                .map_err(|_| "Invalid benchmark frame count".to_string())?;
+            if frames == 0 {
+                return Err("Benchmark frame count is out of range".to_string());
+            }
+            self.benchmark_frames = Some(frames);
+        } else if arg == "--headless" {
+            self.headless = true;
+        } else if let Some(value) = arg.strip_prefix("--snapshot=") {
+            let frame: u32 = value
+                .parse()
+                .map_err(|_| "Invalid snapshot frame number".to_string())?;
+            if frame == 0 {
+                return Err("Snapshot frame number is out of range".to_string());
+            }
+            self.snapshot_frames.push(frame);
+        } else if let Some(value) = arg.strip_prefix("--snapshot-dir=") {
+            self.snapshot_dir = PathBuf::from(value);
+        } else if let Some(value) = arg.strip_prefix("--snapshot-tolerance=") {
+            self.snapshot_tolerance = value
+                .parse()
+                .map_err(|_| "Invalid snapshot tolerance".to_string())?;
         } else {
-            eprintln!("{}", USAGE);
-            return Err(format!("Unexpected argument: {:?}", arg));
+            return Ok(false);
         }
+        Ok(true)
     }
+}
 
-    let Some(bundle_path) = bundle_path else {
-        eprintln!("{}", USAGE);
-        return Err("Path to bundle must be specified".to_string());
+fn main() -> Result<(), String> {
+    println!("touchHLE {} — https://touchhle.org/", VERSION);
+    println!();
+
+    let mut args = std::env::args();
+    let _ = args.next().unwrap(); // skip argv[0]
+
+    let Some(CommandLine {
+        bundle_path,
+        config_dir,
+        option_args,
+        inspect,
+    }) = parse_command_line(args)?
+    else {
+        return Ok(());
     };
 
+    if let Some(bundle_path) = bundle_path {
+        let status = run_app(
+            bundle_path,
+            config_dir.as_deref(),
+            &option_args,
+            inspect,
+            false,
+        )?;
+        // Everything has been torn down and the report written by now.
+        if status != 0 {
+            std::process::exit(status);
+        }
+        Ok(())
+    } else if inspect {
+        eprintln!("{}", USAGE);
+        Err("Path to bundle must be specified".to_string())
+    } else {
+        run_launcher(config_dir.as_deref(), &option_args)
+    }
+}
+
+/// The command-line arguments, other than `--help` and `--copyright`.
+#[derive(Debug, PartialEq)]
+struct CommandLine {
+    /// If this is [None], the launcher is used.
+    bundle_path: Option<PathBuf>,
+    config_dir: Option<PathBuf>,
+    /// Options to pass on to [Options::parse_argument] for each app.
+    option_args: Vec<String>,
+    inspect: bool,
+}
+
+/// Parse the command-line arguments (without `argv[0]`). Returns [None] if
+/// there's nothing to run, because `--help` or `--copyright` was handled.
+fn parse_command_line(args: impl Iterator<Item = String>) -> Result<Option<CommandLine>, String> {
+    let mut bundle_path: Option<PathBuf> = None;
+    let mut config_dir = Some(PathBuf::from("touchHLE_config"));
+    let mut option_args = Vec::new();
+    let mut inspect = false;
+    for arg in args {
+        if arg == "--help" {
+            println!("{}", USAGE);
+            return Ok(None);
+        } else if arg == "--copyright" {
+            licenses::print();
+            return Ok(None);
+        } else if arg == "--inspect" {
+            inspect = true;
+        } else if bundle_path.is_none() && !arg.starts_with("--") {
+            bundle_path = Some(PathBuf::from(arg));
+        } else if let Some(value) = arg.strip_prefix("--config-dir=") {
+            config_dir = Some(PathBuf::from(value));
+        } else if arg == "--no-config" {
+            config_dir = None;
+        } else {
+            // Check the option now, so that mistakes are reported before
+            // anything is loaded.
+            if !Options::default().parse_argument(&arg)? {
+                eprintln!("{}", USAGE);
+                return Err(format!("Unexpected argument: {:?}", arg));
+            }
+            option_args.push(arg);
+        }
+    }
+    Ok(Some(CommandLine {
+        bundle_path,
+        config_dir,
+        option_args,
+        inspect,
+    }))
+}
+
+/// Let the user pick apps to run until they quit (see [launcher]).
+fn run_launcher(config_dir: Option<&Path>, option_args: &[String]) -> Result<(), String> {
+    loop {
+        let apps = launcher::find_apps(Path::new(launcher::APPS_DIR))?;
+        let Some(bundle_path) = launcher::pick_app(&apps) else {
+            return Ok(());
+        };
+        // A problem with one app shouldn't stop the others being run.
+        match run_app(bundle_path, config_dir, option_args, false, true) {
+            Ok(0) => (),
+            Ok(status) => log!("The app exited with status {}.", status),
+            Err(e) => log!("Error: {}. Returning to the launcher.", e),
+        }
+        println!();
+    }
+}
+
+/// Load an app with its options and run it until it exits, returning the exit
+/// status. If `in_launcher` is set, a crash is returned as an error after the
+/// app has been torn down, so that the launcher can continue.
+fn run_app(
+    bundle_path: PathBuf,
+    config_dir: Option<&Path>,
+    option_args: &[String],
+    inspect: bool,
+    in_launcher: bool,
+) -> Result<i32, String> {
     // When PowerShell does tab-completion on a directory, for some reason it
     // expands it to `'..\My Bundle.app\'` and that trailing \ seems to
     // get interpreted as escaping a double quotation mark?
@@ -212,11 +1354,78 @@ fn main() -> Result<(), String> {
         log!("Warning: The bundle path has a trailing quotation mark! This often happens accidentally on Windows when tab-completing, because '\\\"' gets interpreted by Rust in the wrong way. Did you meant to write {:?}?", fixed);
     }
 
-    let mut env = Environment::new(bundle_path, options)?;
-    env.run();
-    Ok(())
+    let (bundle, fs) = match bundle::Bundle::new_bundle_and_fs_from_host_path(bundle_path) {
+        Ok(bundle) => bundle,
+        Err(err) => {
+            return Err(format!("Application bundle error: {}. Check that the path is to a .app directory. If this is a .ipa file, you need to extract it as a ZIP file to get the .app directory.", err));
+        }
+    };
+
+    let mut merged_config = toml::value::Table::new();
+    if let Some(config_dir) = config_dir {
+        for file_name in [
+            "default.toml".to_string(),
+            format!("{}.toml", bundle.bundle_identifier()),
+        ] {
+            let path = config_dir.join(file_name);
+            let file_config = config::load(&path)?;
+            // Check the file on its own, so that mistakes can be reported
+            // with the file they're in.
+            let mut options = Options::default();
+            for arg in config::to_arguments(&file_config)? {
+                if !options
+                    .parse_argument(&arg)
+                    .map_err(|e| format!("{} (in {})", e, path.display()))?
+                {
+                    return Err(format!("Unknown option {:?} in {}", arg, path.display()));
+                }
+            }
+            merged_config.extend(file_config);
+        }
+    }
+
+    let mut options = Options::default();
+    for arg in config::to_arguments(&merged_config)?
+        .iter()
+        .chain(option_args)
+    {
+        options.parse_argument(arg)?;
+    }
+    log::configure(options.log_filter.clone(), options.log_file.as_deref())?;
+    if inspect {
+        let plugins = plugins::Enabled::new(&options.plugins)?;
+        return inspect::print(&bundle, &fs, &plugins).map(|()| 0);
+    }
+    if options.memory_tool && !options.objc_breakpoints.is_empty() {
+        return Err("--memory-tool can't be used with --objc-breakpoint=".to_string());
+    }
+    if options.memory_tool && options.watchpoint_pause {
+        return Err("--memory-tool can't be used with --watchpoint-pause".to_string());
+    }
+
+    let mut env = Environment::new(bundle, fs, options, in_launcher)?;
+    let result = match env.run() {
+        Ok(status) => Ok(status),
+        Err(e) => {
+            if !in_launcher {
+                std::panic::resume_unwind(e);
+            }
+            // The panic message has already been printed by the panic hook.
+            let message = e
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| e.downcast_ref::<String>().map(|s| s.as_str()))
+                .unwrap_or("unknown error");
+            Err(format!("The app crashed: {}", message))
+        }
+    };
+    env.tear_down();
+    result
 }
 
+/// Payload of the unwind used by [Environment::exit], with the exit status.
+struct AppExited(i32);
+
 /// Index into the [Vec] of threads. Thread 0 is always the main thread.
 type ThreadID = usize;
 
@@ -258,8 +1467,8 @@ struct Thread {
 
 /// The struct containing the entire emulator state.
 pub struct Environment {
-    /// Reference point for various timing functions.
-    startup_time: std::time::Instant,
+    /// The clock the app sees, for various timing functions.
+    clock: clock::Clock,
     bundle: bundle::Bundle,
     fs: fs::Fs,
     window: window::Window,
@@ -273,21 +1482,37 @@ pub struct Environment {
     current_thread: ThreadID,
     threads: Vec<Thread>,
     libc_state: libc::State,
+    replay: replay::Replay,
+    memory_tool: memory_tool::MemoryTool,
+    /// [Some] in benchmark mode.
+    benchmark: Option<benchmark::Benchmark>,
+    /// [Some] if there are frames to capture for snapshot testing.
+    snapshots: Option<snapshots::Snapshots>,
+    scripts: scripting::Scripts,
+    sqlite3_state: sqlite3::State,
     framework_state: frameworks::State,
+    plugins: plugins::Enabled,
+    plugin_state: plugins::State,
+    /// [Some] if a compatibility report is being collected (`--report`).
+    report: Option<report::Reporter>,
     options: Options,
+    /// Set if the app was picked in the launcher, which it goes back to when
+    /// the app is quit.
+    in_launcher: bool,
 }
 
 impl Environment {
     /// Loads the binary and sets up the emulator.
-    fn new(bundle_path: PathBuf, options: Options) -> Result<Environment, String> {
-        let startup_time = std::time::Instant::now();
+    fn new(
+        bundle: bundle::Bundle,
+        fs: fs::Fs,
+        options: Options,
+        in_launcher: bool,
+    ) -> Result<Environment, String> {
+        let clock = clock::Clock::new();
 
-        let (bundle, fs) = match bundle::Bundle::new_bundle_and_fs_from_host_path(bundle_path) {
-            Ok(bundle) => bundle,
-            Err(err) => {
-                return Err(format!("Application bundle error: {}. Check that the path is to a .app directory. If this is a .ipa file, you need to extract it as a ZIP file to get the .app directory.", err));
-            }
-        };
+        let plugins = plugins::Enabled::new(&options.plugins)?;
+        let report = options.report.then(|| report::start(&bundle));
 
         let icon = fs
             .read(bundle.icon_path())
@@ -304,6 +1529,16 @@ impl Environment {
             &format!("{} (touchHLE {})", bundle.display_name(), VERSION),
             icon,
             launch_image,
+            window::ControllerMap::load(&options.controller_map_dir, bundle.bundle_identifier()),
+            window::KeyboardMap::load(&options.keyboard_map_dir, bundle.bundle_identifier()),
+            window::VirtualControls::load(
+                &options.virtual_controls_dir,
+                bundle.bundle_identifier(),
+            ),
+            window::Hotkeys::load(&options.hotkeys_file),
+            options
+                .window_geometry_dir
+                .join(format!("{}.txt", bundle.bundle_identifier())),
             &options,
         );
 
@@ -314,7 +1549,7 @@ impl Environment {
 
         let mut dylibs = Vec::new();
         for dylib in &executable.dynamic_libraries {
-            if dylib == "/usr/lib/libSystem.B.dylib" || dylib == "/usr/lib/libobjc.A.dylib" {
+            if dyld::Dyld::HOST_IMPLEMENTED_LIBRARIES.contains(&dylib.as_str()) {
                 // We have host implementations of these
                 continue;
             }
@@ -348,17 +1583,30 @@ impl Environment {
         let mut bins = dylibs;
         bins.insert(0, executable);
 
-        let mut objc = objc::ObjC::new();
+        let mut objc = objc::ObjC::new(plugins.clone());
 
         let mut dyld = dyld::Dyld::new();
-        dyld.do_initial_linking(&bins, &mut mem, &mut objc);
+        dyld.do_initial_linking(&bins, &mut mem, &mut objc, &plugins, report.as_ref());
 
         for &breakpoint in &options.breakpoints {
             dyld.set_breakpoint(&mut mem, breakpoint);
         }
+        for watchpoint in &options.watchpoints {
+            mem.add_watchpoint(watchpoint.clone());
+        }
 
         let cpu = cpu::Cpu::new();
 
+        let replay = replay::Replay::new(
+            options.record_input.as_deref(),
+            options.replay_input.as_deref(),
+            bundle.bundle_identifier(),
+        )?;
+
+        let memory_tool = memory_tool::MemoryTool::new(options.memory_tool);
+        let benchmark = benchmark::Benchmark::new(&options);
+        let snapshots = snapshots::Snapshots::new(&options, bundle.bundle_identifier());
+
         let main_thread = Thread {
             active: true,
             in_start_routine: false, // main thread never terminates
@@ -368,7 +1616,7 @@ impl Environment {
         };
 
         let mut env = Environment {
-            startup_time,
+            clock,
             bundle,
             fs,
             window,
@@ -380,12 +1628,24 @@ impl Environment {
             current_thread: 0,
             threads: vec![main_thread],
             libc_state: Default::default(),
+            replay,
+            memory_tool,
+            benchmark,
+            snapshots,
+            scripts: Default::default(),
+            sqlite3_state: Default::default(),
             framework_state: Default::default(),
+            plugins,
+            plugin_state: Default::default(),
+            report,
             options,
+            in_launcher,
         };
 
         dyld::Dyld::do_late_linking(&mut env);
 
+        scripting::load(&mut env)?;
+
         {
             let bin_path = env.bundle.executable_path();
             let bin_path_apple_key = format!("executable_path={}", bin_path.as_str());
@@ -418,6 +1678,56 @@ impl Environment {
         Ok(env)
     }
 
+    /// Describe an address in guest code for debugging output, with the symbol
+    /// it's in if that's known, e.g. `0x2f1c (_foo + 0x1c in Game)`.
+    fn describe_code_address(&self, addr: u32) -> String {
+        for bin in &self.bins {
+            match bin.symbol_for_address(addr) {
+                Some((symbol, 0)) => return format!("{:#x} ({} in {})", addr, symbol, bin.name),
+                Some((symbol, offset)) => {
+                    return format!("{:#x} ({} + {:#x} in {})", addr, symbol, offset, bin.name)
+                }
+                None => (),
+            }
+        }
+        format!("{:#x}", addr)
+    }
+
+    /// Report that the guest instruction at `pc` hit a watchpoint (see
+    /// `--watchpoint=`). The instruction has already completed.
+    fn watchpoint_hit(&mut self, pc: u32, hits: Vec<mem::WatchpointHit>) {
+        for hit in hits {
+            // Show the whole value, e.g. 0x0000002a for 4 bytes.
+            let width = 2 + 2 * hit.size as usize;
+            let access = match hit.old_value {
+                Some(old_value) => format!(
+                    "wrote {:#0width$x} (was {:#0width$x}) to",
+                    hit.value,
+                    old_value,
+                    width = width
+                ),
+                None => format!("read {:#0width$x} from", hit.value, width = width),
+            };
+            log!(
+                "Watchpoint hit on thread {}: {} {:#x} ({} bytes)",
+                self.current_thread,
+                access,
+                hit.addr,
+                hit.size
+            );
+        }
+        log!("PC: {}", self.describe_code_address(pc));
+        let lr = self.cpu.regs()[cpu::Cpu::LR];
+        log!("LR: {}", self.describe_code_address(lr));
+
+        if self.options.watchpoint_pause {
+            self.cpu.dump_regs();
+            self.stack_trace();
+            log!("Execution is paused. Press Enter to continue.");
+            let _ = crate::terminal::read_line();
+        }
+    }
+
     fn stack_trace(&self) {
         let stack_range = self.threads[self.current_thread].stack.clone().unwrap();
         eprintln!(
@@ -490,28 +1800,66 @@ impl Environment {
         new_thread_id
     }
 
-    /// Run the emulator. This is the main loop and won't return until app exit.
-    /// Only `main.rs` should call this.
-    fn run(&mut self) {
+    /// Run the emulator. This is the main loop and won't return until the app
+    /// exits (see [Self::exit]), in which case the exit status is returned, or
+    /// touchHLE crashes, in which case the panic is returned so that it can be
+    /// resumed. Either way, the environment can't be used afterwards except to
+    /// [Self::tear_down] it. Only `main.rs` should call this.
+    fn run(&mut self) -> Result<i32, Box<dyn std::any::Any + Send>> {
         // I'm not sure if this actually is unwind-safe, but considering
-        // the emulator will crash anyway, maybe this is okay.
+        // the environment is thrown away afterwards, maybe this is okay.
         let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.run_inner(true)));
-        if let Err(e) = res {
-            eprintln!("Register state immediately after panic:");
-            self.cpu.dump_regs();
-            if self.current_thread == 0 {
-                eprintln!("Attempting to produce stack trace for main thread:");
-            } else {
-                eprintln!(
-                    "Attempting to produce stack trace for thread {}:",
-                    self.current_thread
-                );
+        match res {
+            Ok(()) => Ok(0),
+            Err(e) if e.is::<AppExited>() => Ok(e.downcast::<AppExited>().unwrap().0),
+            Err(e) => {
+                eprintln!("Register state immediately after panic:");
+                self.cpu.dump_regs();
+                if self.current_thread == 0 {
+                    eprintln!("Attempting to produce stack trace for main thread:");
+                } else {
+                    eprintln!(
+                        "Attempting to produce stack trace for thread {}:",
+                        self.current_thread
+                    );
+                }
+                self.stack_trace();
+                Err(e)
             }
-            self.stack_trace();
-            std::panic::resume_unwind(e);
         }
     }
 
+    /// Stop running the app and return from [Self::run] with an exit status.
+    /// This is for when the app exits or is quit, after it has been told about
+    /// it if necessary, and for when a benchmark or snapshot test is done.
+    /// touchHLE itself exits with the status, unless it's using the launcher.
+    pub fn exit(&mut self, status: i32) -> ! {
+        // Unwinding gets back to the main loop from however deep in host and
+        // guest calls this is. resume_unwind() is used rather than panic!() so
+        // that it isn't reported as a crash.
+        std::panic::resume_unwind(Box::new(AppExited(status)))
+    }
+
+    /// Free everything the app was using, so that another app can be run.
+    /// OpenGL contexts and OpenAL devices are held by the app's objects and
+    /// framework state, so those must go before the window, which owns SDL.
+    fn tear_down(self) {
+        let Environment {
+            window,
+            objc,
+            framework_state,
+            report,
+            ..
+        } = self;
+        drop(framework_state);
+        drop(objc);
+        drop(window);
+        if let Some(report) = report {
+            report.finish();
+        }
+        log_dbg!("Environment torn down");
+    }
+
     /// Run the emulator until the app returns control to the host. This is for
     /// host-to-guest function calls (see [abi::GuestFunction::call]).
     ///
@@ -551,10 +1899,14 @@ impl Environment {
             // This is not free so we should avoid doing it too often.
             // 100,000 ticks is an arbitrary number.
             self.window.poll_for_events(&self.options);
+            benchmark::check_finished(self);
 
             let mut ticks = 100_000;
             while ticks > 0 {
-                match self.cpu.run(&mut self.mem, &mut ticks) {
+                let activity = benchmark::set_activity(self, benchmark::Activity::Guest);
+                let state = self.cpu.run(&mut self.mem, &mut ticks);
+                benchmark::set_activity(self, activity);
+                match state {
                     cpu::CpuState::Normal => (),
                     cpu::CpuState::Svc(svc) => {
                         // the program counter is pointing at the
@@ -595,23 +1947,39 @@ impl Environment {
                             }
                         }
 
-                        if let Some(f) = self.dyld.get_svc_handler(
+                        if svc == dyld::Dyld::SVC_SCRIPT_HOOK {
+                            scripting::run_replacement(self);
+                        } else if let Some(f) = self.dyld.get_svc_handler(
                             &self.bins,
                             &mut self.mem,
                             &mut self.cpu,
+                            &self.plugins,
+                            self.report.as_ref(),
                             svc_pc,
                             svc,
                         ) {
                             let was_in_host_function =
                                 self.threads[self.current_thread].in_host_function;
                             self.threads[self.current_thread].in_host_function = true;
-                            f.call_from_guest(self);
+                            let activity =
+                                if f.module == "opengles" || f.module.starts_with("opengles::") {
+                                    benchmark::Activity::Gl
+                                } else {
+                                    benchmark::Activity::Host
+                                };
+                            let activity = benchmark::set_activity(self, activity);
+                            if !scripting::intercept(self, &f) {
+                                let trace = api_trace::c_function(&self.options, &f);
+                                f.function.call_from_guest_traced(self, trace);
+                            }
+                            benchmark::set_activity(self, activity);
                             self.threads[self.current_thread].in_host_function =
                                 was_in_host_function;
                         } else {
                             self.cpu.regs_mut()[cpu::Cpu::PC] = svc_pc;
                         }
                     }
+                    cpu::CpuState::Watchpoint { pc, hits } => self.watchpoint_hit(pc, hits),
                 }
             }
 
@@ -635,3 +2003,34 @@ impl Environment {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> CommandLine {
+        parse_command_line(args.iter().map(|arg| arg.to_string()))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn command_line() {
+        let command_line = parse(&["Game.app", "--fullscreen", "--no-config"]);
+        assert_eq!(command_line.bundle_path, Some(PathBuf::from("Game.app")));
+        assert_eq!(command_line.config_dir, None);
+        assert_eq!(command_line.option_args, ["--fullscreen"]);
+
+        // Without an app, the options are for the launcher's apps.
+        let command_line = parse(&["--fullscreen", "--config-dir=config", "--inspect"]);
+        assert_eq!(command_line.bundle_path, None);
+        assert_eq!(command_line.config_dir, Some(PathBuf::from("config")));
+        assert_eq!(command_line.option_args, ["--fullscreen"]);
+        assert!(command_line.inspect);
+
+        let command_line = parse(&["--fullscreen", "Game.app"]);
+        assert_eq!(command_line.bundle_path, Some(PathBuf::from("Game.app")));
+
+        assert!(parse_command_line(["--foo".to_string()].into_iter()).is_err());
+    }
+}
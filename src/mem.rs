@@ -15,6 +15,9 @@
 //! * [Memory Usage Performance Guidelines](https://developer.apple.com/library/archive/documentation/Performance/Conceptual/ManagingMemory/ManagingMemory.html)
 
 mod allocator;
+mod watchpoints;
+
+pub use watchpoints::{Watchpoint, WatchpointHit};
 
 /// Equivalent of `usize` for guest memory.
 pub type GuestUSize = u32;
@@ -207,6 +210,11 @@ pub struct Mem {
     bytes: *mut Bytes,
 
     allocator: allocator::Allocator,
+
+    watchpoints: Vec<Watchpoint>,
+    /// Watchpoint hits by the instruction guest code is executing, until
+    /// [crate::cpu] reports them.
+    watchpoint_hits: Vec<WatchpointHit>,
 }
 
 impl Drop for Mem {
@@ -246,7 +254,12 @@ impl Mem {
 
         let allocator = allocator::Allocator::new();
 
-        Mem { bytes, allocator }
+        Mem {
+            bytes,
+            allocator,
+            watchpoints: Vec::new(),
+            watchpoint_hits: Vec::new(),
+        }
     }
 
     fn bytes(&self) -> &Bytes {
@@ -347,6 +360,36 @@ impl Mem {
         unsafe { ptr.write_unaligned(value) }
     }
 
+    /// Add a watchpoint, so that guest code accessing the memory it covers
+    /// is reported (see [crate::cpu::CpuState::Watchpoint]).
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// For use by [crate::cpu]: check whether any watchpoints are set.
+    pub fn has_watchpoints(&self) -> bool {
+        !self.watchpoints.is_empty()
+    }
+
+    /// For use by [crate::cpu]: check whether a guest memory access hits a
+    /// watchpoint.
+    pub fn is_watched(&self, addr: VAddr, size: GuestUSize, is_write: bool) -> bool {
+        self.watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.is_hit_by(addr, size, is_write))
+    }
+
+    /// For use by [crate::cpu]: remember that a watchpoint was hit, until
+    /// [Self::take_watchpoint_hits] is called.
+    pub fn add_watchpoint_hit(&mut self, hit: WatchpointHit) {
+        self.watchpoint_hits.push(hit);
+    }
+
+    /// For use by [crate::cpu]: get the watchpoint hits since the last call.
+    pub fn take_watchpoint_hits(&mut self) -> Vec<WatchpointHit> {
+        std::mem::take(&mut self.watchpoint_hits)
+    }
+
     /// Allocate `size` bytes.
     pub fn alloc(&mut self, size: GuestUSize) -> MutVoidPtr {
         let ptr = Ptr::from_bits(self.allocator.alloc(size));
@@ -361,6 +404,39 @@ impl Mem {
         log_dbg!("Freed {:?} ({:#x} bytes)", ptr, size);
     }
 
+    /// Total size of the allocations made with the `alloc` methods on this
+    /// type that haven't been freed yet.
+    pub fn bytes_allocated(&self) -> GuestUSize {
+        self.allocator.allocated_bytes()
+    }
+
+    /// The highest [Self::bytes_allocated] has been since the last call to
+    /// [Self::reset_peak_bytes_allocated], or since this was created.
+    pub fn peak_bytes_allocated(&self) -> GuestUSize {
+        self.allocator.peak_allocated_bytes()
+    }
+
+    pub fn reset_peak_bytes_allocated(&mut self) {
+        self.allocator.reset_peak_allocated_bytes()
+    }
+
+    /// How many allocations have been made with the `alloc` methods on this
+    /// type, and how many have been freed.
+    pub fn allocation_counts(&self) -> (u64, u64) {
+        self.allocator.alloc_and_free_counts()
+    }
+
+    /// The regions of memory that are allocated or reserved, other than the
+    /// null page, in address order.
+    pub fn used_regions(&self) -> Vec<(ConstVoidPtr, GuestUSize)> {
+        self.allocator
+            .used_chunks()
+            .into_iter()
+            .filter(|&(base, _)| base >= Self::NULL_PAGE_SIZE)
+            .map(|(base, size)| (Ptr::from_bits(base), size))
+            .collect()
+    }
+
     /// Allocate memory large enough for a value of type `T` and write the value
     /// to it. Equivalent to [Self::alloc] + [Self::write].
     pub fn alloc_and_write<T>(&mut self, value: T) -> MutPtr<T>
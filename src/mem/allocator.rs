@@ -92,6 +92,15 @@ mod chunk_tests {
 pub struct Allocator {
     used_chunks: Vec<Chunk>,
     unused_chunks: Vec<Chunk>,
+    /// Total size of the chunks allocated with [Self::alloc] and not yet
+    /// freed. Reserved chunks don't count.
+    allocated_bytes: GuestUSize,
+    /// The highest [Self::allocated_bytes] has been since the last
+    /// [Self::reset_peak_allocated_bytes].
+    peak_allocated_bytes: GuestUSize,
+    /// How many times [Self::alloc] and [Self::free] have been called.
+    alloc_count: u64,
+    free_count: u64,
 }
 
 impl Allocator {
@@ -107,6 +116,10 @@ impl Allocator {
         Allocator {
             used_chunks: vec![null_page, main_thread_stack],
             unused_chunks: vec![rest],
+            allocated_bytes: 0,
+            peak_allocated_bytes: 0,
+            alloc_count: 0,
+            free_count: 0,
         }
     }
 
@@ -142,6 +155,10 @@ impl Allocator {
             size
         };
 
+        self.allocated_bytes += size;
+        self.peak_allocated_bytes = self.peak_allocated_bytes.max(self.allocated_bytes);
+        self.alloc_count += 1;
+
         let existing_chunk = {
             let mut perfect_chunk: Option<usize> = None;
             let mut big_enough_chunk: Option<(usize, GuestUSize)> = None;
@@ -190,6 +207,34 @@ impl Allocator {
         }
     }
 
+    pub fn allocated_bytes(&self) -> GuestUSize {
+        self.allocated_bytes
+    }
+
+    pub fn peak_allocated_bytes(&self) -> GuestUSize {
+        self.peak_allocated_bytes
+    }
+
+    pub fn reset_peak_allocated_bytes(&mut self) {
+        self.peak_allocated_bytes = self.allocated_bytes;
+    }
+
+    pub fn alloc_and_free_counts(&self) -> (u64, u64) {
+        (self.alloc_count, self.free_count)
+    }
+
+    /// The base and size of each chunk that is allocated or reserved, in
+    /// address order.
+    pub fn used_chunks(&self) -> Vec<(VAddr, GuestUSize)> {
+        let mut chunks: Vec<_> = self
+            .used_chunks
+            .iter()
+            .map(|chunk| (chunk.base, chunk.size.get()))
+            .collect();
+        chunks.sort();
+        chunks
+    }
+
     /// Returns the size of the freed chunk so it can be zeroed if desired
     #[must_use]
     pub fn free(&mut self, base: VAddr) -> GuestUSize {
@@ -198,6 +243,8 @@ impl Allocator {
         };
         let chunk = self.used_chunks.remove(idx);
         let size = chunk.size.get();
+        self.allocated_bytes -= size;
+        self.free_count += 1;
 
         if let Some(other_chunk_idx) = self.unused_chunks.iter().position(|other_chunk| {
             (other_chunk.base as u64) == (chunk.last_byte() as u64 + 1)
@@ -215,3 +262,25 @@ impl Allocator {
         size
     }
 }
+
+#[cfg(test)]
+mod allocator_tests {
+    use super::Allocator;
+
+    #[test]
+    fn peak_allocated_bytes() {
+        let mut allocator = Allocator::new();
+        let a = allocator.alloc(0x100);
+        let b = allocator.alloc(0x200);
+        let _ = allocator.free(a);
+        let _ = allocator.free(b);
+        assert_eq!(allocator.allocated_bytes(), 0);
+        assert_eq!(allocator.peak_allocated_bytes(), 0x300);
+
+        let c = allocator.alloc(0x10);
+        allocator.reset_peak_allocated_bytes();
+        assert_eq!(allocator.peak_allocated_bytes(), 0x10);
+        let _ = allocator.free(c);
+        assert_eq!(allocator.peak_allocated_bytes(), 0x10);
+    }
+}
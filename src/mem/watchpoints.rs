@@ -0,0 +1,133 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Watchpoints (`--watchpoint=`), for finding out which guest code reads or
+//! writes some memory, e.g. to track down what is corrupting a value.
+//!
+//! Only accesses by guest code are checked, in the CPU's memory callbacks (see
+//! [crate::cpu]). Host code accessing guest memory doesn't hit watchpoints.
+
+use super::{GuestUSize, VAddr};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addr: VAddr,
+    pub size: GuestUSize,
+    pub on_read: bool,
+    pub on_write: bool,
+}
+
+impl Watchpoint {
+    /// Parse the value of `--watchpoint=`: a hexadecimal address, optionally
+    /// followed by a size in bytes and the kind of access, e.g. `0x1f2a4`,
+    /// `1f2a4,16` or `0x1f2a4,4,rw`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.split(',');
+        let addr = parts.next().unwrap();
+        let addr = addr.strip_prefix("0x").unwrap_or(addr);
+        let addr = VAddr::from_str_radix(addr, 16)
+            .map_err(|_| "Incorrect watchpoint syntax".to_string())?;
+        let size = match parts.next() {
+            Some(size) => size
+                .parse()
+                .map_err(|_| "Incorrect watchpoint syntax".to_string())?,
+            None => 4,
+        };
+        if size == 0 || addr.checked_add(size - 1).is_none() {
+            return Err("Watchpoint size is out of range".to_string());
+        }
+        let (on_read, on_write) = match parts.next() {
+            None | Some("w") => (false, true),
+            Some("r") => (true, false),
+            Some("rw") => (true, true),
+            Some(_) => return Err("Incorrect watchpoint syntax".to_string()),
+        };
+        if parts.next().is_some() {
+            return Err("Incorrect watchpoint syntax".to_string());
+        }
+        Ok(Watchpoint {
+            addr,
+            size,
+            on_read,
+            on_write,
+        })
+    }
+
+    /// Check whether an access of `size` bytes at `addr` hits this watchpoint.
+    pub fn is_hit_by(&self, addr: VAddr, size: GuestUSize, is_write: bool) -> bool {
+        let kind_matches = if is_write {
+            self.on_write
+        } else {
+            self.on_read
+        };
+        // The ranges are inclusive so that ones ending at the top of the
+        // address space don't overflow.
+        let last = addr.wrapping_add(size - 1);
+        let watched_last = self.addr + (self.size - 1);
+        kind_matches && addr <= watched_last && self.addr <= last
+    }
+}
+
+/// A guest memory access that hit a watchpoint.
+#[derive(Debug)]
+pub struct WatchpointHit {
+    pub addr: VAddr,
+    pub size: GuestUSize,
+    /// The value that was read or written.
+    pub value: u64,
+    /// For a write, the value that was there before.
+    pub old_value: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        assert_eq!(
+            Watchpoint::parse("0x1f2a4"),
+            Ok(Watchpoint {
+                addr: 0x1f2a4,
+                size: 4,
+                on_read: false,
+                on_write: true,
+            })
+        );
+        assert_eq!(
+            Watchpoint::parse("1f2a4,16,rw"),
+            Ok(Watchpoint {
+                addr: 0x1f2a4,
+                size: 16,
+                on_read: true,
+                on_write: true,
+            })
+        );
+        assert!(Watchpoint::parse("0x10,1,r").unwrap().on_read);
+        assert!(Watchpoint::parse("ffffffff,1").is_ok());
+        assert!(Watchpoint::parse("ffffffff,2").is_err());
+        assert!(Watchpoint::parse("0x10,0").is_err());
+        assert!(Watchpoint::parse("0x10,4,x").is_err());
+        assert!(Watchpoint::parse("0x10,4,w,1").is_err());
+        assert!(Watchpoint::parse("foo").is_err());
+    }
+
+    #[test]
+    fn hits() {
+        let watchpoint = Watchpoint::parse("0x1000,8").unwrap();
+        assert!(watchpoint.is_hit_by(0x1000, 4, true));
+        assert!(watchpoint.is_hit_by(0x1004, 4, true));
+        assert!(watchpoint.is_hit_by(0xffe, 4, true));
+        assert!(watchpoint.is_hit_by(0x1007, 1, true));
+        assert!(!watchpoint.is_hit_by(0x1008, 4, true));
+        assert!(!watchpoint.is_hit_by(0xffc, 4, true));
+        assert!(!watchpoint.is_hit_by(0x1000, 4, false));
+
+        let watchpoint = Watchpoint::parse("0xfffffffc,4,r").unwrap();
+        assert!(watchpoint.is_hit_by(0xffffffff, 1, false));
+        assert!(watchpoint.is_hit_by(0xfffffff8, 8, false));
+        assert!(!watchpoint.is_hit_by(0xfffffff8, 4, false));
+    }
+}
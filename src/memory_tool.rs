@@ -0,0 +1,671 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! A tool for finding and changing values in the app's memory while it runs,
+//! like a cheat engine (`--memory-tool`).
+//!
+//! Commands are typed into the terminal touchHLE was started from, one per
+//! line, and are handled between frames. The usual workflow is to search for
+//! a value shown in the app, e.g. a number of lives, change it in the app, and
+//! then narrow the search down with the new value until only a few addresses
+//! are left. Those can then be watched, written to, or frozen so that the app
+//! can't change them.
+//!
+//! Only memory that is in use is searched: the loaded binaries, the heap and
+//! the stacks.
+
+use crate::mem::{ConstPtr, GuestUSize, Mem, MutPtr};
+use crate::Environment;
+
+const HELP: &str = "\
+Memory tool commands:
+    search <type> <value>     Find addresses that hold a value.
+    next <value>              Keep the results that now hold a value.
+    changed                   Keep the results that changed since last time.
+    unchanged                 Keep the results that didn't change.
+    increased                 Keep the results that increased.
+    decreased                 Keep the results that decreased.
+    list                      Show the results.
+    read <addr> [count]       Show the bytes at an address.
+    write <addr> <type> <value>
+                              Change the value at an address.
+    watch <addr> <type>       Print the value at an address when it changes.
+    unwatch <addr>
+    freeze <addr> <type> [value]
+                              Keep the value at an address the same.
+    unfreeze <addr>
+    help
+
+Types are u8, u16, u32, i8, i16, i32, f32 and f64. Addresses are hexadecimal.
+Integer values can be hexadecimal with a '0x' prefix. A float value matches
+when it's the same to as many decimal places as were typed.";
+
+/// Searches stop after finding this many results, so a very common value
+/// doesn't use up all the host's memory.
+const MAX_RESULTS: usize = 1_000_000;
+
+/// How many results `list` shows.
+const LIST_LIMIT: usize = 20;
+
+/// The most bytes `read` shows at once.
+const READ_LIMIT: GuestUSize = 4096;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ValueType {
+    U8,
+    U16,
+    U32,
+    I8,
+    I16,
+    I32,
+    F32,
+    F64,
+}
+impl ValueType {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "u8" => Ok(ValueType::U8),
+            "u16" => Ok(ValueType::U16),
+            "u32" => Ok(ValueType::U32),
+            "i8" => Ok(ValueType::I8),
+            "i16" => Ok(ValueType::I16),
+            "i32" => Ok(ValueType::I32),
+            "f32" => Ok(ValueType::F32),
+            "f64" => Ok(ValueType::F64),
+            _ => Err(format!("Unknown type {:?}", name)),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ValueType::U8 => "u8",
+            ValueType::U16 => "u16",
+            ValueType::U32 => "u32",
+            ValueType::I8 => "i8",
+            ValueType::I16 => "i16",
+            ValueType::I32 => "i32",
+            ValueType::F32 => "f32",
+            ValueType::F64 => "f64",
+        }
+    }
+
+    fn size(self) -> GuestUSize {
+        match self {
+            ValueType::U8 | ValueType::I8 => 1,
+            ValueType::U16 | ValueType::I16 => 2,
+            ValueType::U32 | ValueType::I32 | ValueType::F32 => 4,
+            ValueType::F64 => 8,
+        }
+    }
+
+    /// Values are only searched for at addresses with this alignment. ARMv6
+    /// doesn't need more than 4-byte alignment for anything.
+    fn align(self) -> GuestUSize {
+        self.size().min(4)
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, ValueType::F32 | ValueType::F64)
+    }
+
+    /// Get a value from its little-endian bytes.
+    fn decode(self, bytes: &[u8]) -> Value {
+        let bytes = &bytes[..self.size() as usize];
+        match self {
+            ValueType::U8 => Value::Int(bytes[0].into()),
+            ValueType::U16 => Value::Int(u16::from_le_bytes(bytes.try_into().unwrap()).into()),
+            ValueType::U32 => Value::Int(u32::from_le_bytes(bytes.try_into().unwrap()).into()),
+            ValueType::I8 => Value::Int((bytes[0] as i8).into()),
+            ValueType::I16 => Value::Int(i16::from_le_bytes(bytes.try_into().unwrap()).into()),
+            ValueType::I32 => Value::Int(i32::from_le_bytes(bytes.try_into().unwrap()).into()),
+            ValueType::F32 => Value::Float(f32::from_le_bytes(bytes.try_into().unwrap()).into()),
+            ValueType::F64 => Value::Float(f64::from_le_bytes(bytes.try_into().unwrap())),
+        }
+    }
+
+    /// Get the little-endian bytes of a value, which must have come from
+    /// [Self::parse_value] for the same type.
+    fn encode(self, value: Value) -> Vec<u8> {
+        match (self, value) {
+            (ValueType::U8, Value::Int(i)) => (i as u8).to_le_bytes().to_vec(),
+            (ValueType::U16, Value::Int(i)) => (i as u16).to_le_bytes().to_vec(),
+            (ValueType::U32, Value::Int(i)) => (i as u32).to_le_bytes().to_vec(),
+            (ValueType::I8, Value::Int(i)) => (i as i8).to_le_bytes().to_vec(),
+            (ValueType::I16, Value::Int(i)) => (i as i16).to_le_bytes().to_vec(),
+            (ValueType::I32, Value::Int(i)) => (i as i32).to_le_bytes().to_vec(),
+            (ValueType::F32, Value::Float(f)) => (f as f32).to_le_bytes().to_vec(),
+            (ValueType::F64, Value::Float(f)) => f.to_le_bytes().to_vec(),
+            _ => panic!("{:?} isn't a {} value", value, self.name()),
+        }
+    }
+
+    /// Parse a value of this type. Integers must be in range.
+    fn parse_value(self, text: &str) -> Result<Value, String> {
+        let error = || format!("Invalid {} value {:?}", self.name(), text);
+        if self.is_float() {
+            return text.parse().map(Value::Float).map_err(|_| error());
+        }
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+        let magnitude = match digits.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16),
+            None => digits.parse(),
+        }
+        .map_err(|_| error())?;
+        let value = if negative { -magnitude } else { magnitude };
+        let (min, max) = match self {
+            ValueType::U8 => (0, u8::MAX.into()),
+            ValueType::U16 => (0, u16::MAX.into()),
+            ValueType::U32 => (0, u32::MAX.into()),
+            ValueType::I8 => (i8::MIN.into(), i8::MAX.into()),
+            ValueType::I16 => (i16::MIN.into(), i16::MAX.into()),
+            ValueType::I32 => (i32::MIN.into(), i32::MAX.into()),
+            ValueType::F32 | ValueType::F64 => unreachable!(),
+        };
+        if !(min..=max).contains(&value) {
+            return Err(format!("{} is out of range for {}", value, self.name()));
+        }
+        Ok(Value::Int(value))
+    }
+}
+
+/// A value of any [ValueType], widened so that values can be compared.
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+enum Value {
+    Int(i64),
+    Float(f64),
+}
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+/// A value to search for.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Target {
+    value: Value,
+    /// For floats, how far a value can be from [Self::value] and still match,
+    /// based on how many decimal places were typed.
+    tolerance: f64,
+}
+impl Target {
+    fn parse(ty: ValueType, text: &str) -> Result<Self, String> {
+        let value = ty.parse_value(text)?;
+        let tolerance = if ty.is_float() {
+            let decimals = text.split_once('.').map_or(0, |(_, d)| d.len());
+            0.5 * 10f64.powi(-(decimals as i32))
+        } else {
+            0.0
+        };
+        Ok(Target { value, tolerance })
+    }
+
+    fn matches(&self, value: Value) -> bool {
+        match (self.value, value) {
+            (Value::Float(target), Value::Float(value)) => (value - target).abs() <= self.tolerance,
+            (target, value) => target == value,
+        }
+    }
+}
+
+/// How `changed` and friends compare each result with its previous value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Comparison {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+impl Comparison {
+    fn matches(self, old: Value, new: Value) -> bool {
+        match self {
+            Comparison::Changed => new != old,
+            Comparison::Unchanged => new == old,
+            Comparison::Increased => new > old,
+            Comparison::Decreased => new < old,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Help,
+    Search(ValueType, Target),
+    /// The value is parsed later, because its type is that of the last search.
+    Next(String),
+    Compare(Comparison),
+    List,
+    Read(u32, GuestUSize),
+    Write(u32, ValueType, Value),
+    Watch(u32, ValueType),
+    Unwatch(u32),
+    Freeze(u32, ValueType, Option<Value>),
+    Unfreeze(u32),
+}
+
+/// Parse an address, which is hexadecimal with an optional `0x` prefix, like
+/// for `--breakpoint=`.
+fn parse_addr(text: &str) -> Result<u32, String> {
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    u32::from_str_radix(digits, 16).map_err(|_| format!("Invalid address {:?}", text))
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (&name, args) = words
+        .split_first()
+        .ok_or_else(|| "Empty command".to_string())?;
+    let command = match (name, args) {
+        ("help", []) => Command::Help,
+        ("search", &[ty, value]) => {
+            let ty = ValueType::parse(ty)?;
+            Command::Search(ty, Target::parse(ty, value)?)
+        }
+        ("next", &[value]) => Command::Next(value.to_string()),
+        ("changed", []) => Command::Compare(Comparison::Changed),
+        ("unchanged", []) => Command::Compare(Comparison::Unchanged),
+        ("increased", []) => Command::Compare(Comparison::Increased),
+        ("decreased", []) => Command::Compare(Comparison::Decreased),
+        ("list", []) => Command::List,
+        ("read", &[addr]) => Command::Read(parse_addr(addr)?, 64),
+        ("read", &[addr, count]) => {
+            let count = count
+                .parse()
+                .ok()
+                .filter(|&count| count > 0 && count <= READ_LIMIT)
+                .ok_or_else(|| format!("The count must be from 1 to {}", READ_LIMIT))?;
+            Command::Read(parse_addr(addr)?, count)
+        }
+        ("write", &[addr, ty, value]) => {
+            let ty = ValueType::parse(ty)?;
+            Command::Write(parse_addr(addr)?, ty, ty.parse_value(value)?)
+        }
+        ("watch", &[addr, ty]) => Command::Watch(parse_addr(addr)?, ValueType::parse(ty)?),
+        ("unwatch", &[addr]) => Command::Unwatch(parse_addr(addr)?),
+        ("freeze", &[addr, ty]) => Command::Freeze(parse_addr(addr)?, ValueType::parse(ty)?, None),
+        ("freeze", &[addr, ty, value]) => {
+            let ty = ValueType::parse(ty)?;
+            Command::Freeze(parse_addr(addr)?, ty, Some(ty.parse_value(value)?))
+        }
+        ("unfreeze", &[addr]) => Command::Unfreeze(parse_addr(addr)?),
+        _ => {
+            return Err(format!(
+                "Unknown command or wrong arguments: {:?}. Type 'help' for a list of commands.",
+                line.trim()
+            ))
+        }
+    };
+    Ok(command)
+}
+
+/// Find the values matching a target in some bytes starting at `base`, and
+/// add their addresses and values to `results`. Returns [false] if
+/// [MAX_RESULTS] was reached.
+fn search_bytes(
+    bytes: &[u8],
+    base: u32,
+    ty: ValueType,
+    target: &Target,
+    results: &mut Vec<(u32, Value)>,
+) -> bool {
+    let size = ty.size() as usize;
+    let align = ty.align() as usize;
+    let mut offset = (align - base as usize % align) % align;
+    while offset + size <= bytes.len() {
+        let value = ty.decode(&bytes[offset..]);
+        if target.matches(value) {
+            if results.len() == MAX_RESULTS {
+                return false;
+            }
+            results.push((base + offset as u32, value));
+        }
+        offset += align;
+    }
+    true
+}
+
+/// Format some bytes like a hex editor, 16 to a line.
+fn hex_dump(bytes: &[u8], base: u32) -> String {
+    let mut dump = String::new();
+    for (idx, line) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        let text: String = line
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        dump.push_str(&format!(
+            "{:#010x}: {:<47}  {}\n",
+            base + idx as u32 * 16,
+            hex.join(" "),
+            text
+        ));
+    }
+    dump
+}
+
+/// Check that an address range is entirely within memory that is in use.
+fn check_range(mem: &Mem, addr: u32, size: GuestUSize) -> Result<(), String> {
+    let end = addr as u64 + size as u64;
+    if mem.used_regions().into_iter().any(|(base, region_size)| {
+        let base = base.to_bits() as u64;
+        addr as u64 >= base && end <= base + region_size as u64
+    }) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{:#x} ({} bytes) isn't in memory that's in use",
+            addr, size
+        ))
+    }
+}
+
+pub struct MemoryTool {
+    enabled: bool,
+    /// The type of the last search and the results so far, with the value
+    /// each had when last checked.
+    results: Option<(ValueType, Vec<(u32, Value)>)>,
+    watches: Vec<(u32, ValueType, Value)>,
+    /// Addresses and the bytes to keep writing to them.
+    freezes: Vec<(u32, Vec<u8>)>,
+}
+
+impl MemoryTool {
+    pub fn new(enabled: bool) -> Self {
+        if enabled {
+            crate::terminal::start_reading();
+            println!("The memory tool is enabled. Type 'help' for a list of commands.");
+        }
+        MemoryTool {
+            enabled,
+            results: None,
+            watches: Vec::new(),
+            freezes: Vec::new(),
+        }
+    }
+
+    fn run(&mut self, mem: &mut Mem, command: Command) -> Result<(), String> {
+        match command {
+            Command::Help => println!("{}", HELP),
+            Command::Search(ty, target) => {
+                let mut results = Vec::new();
+                let mut complete = true;
+                for (base, size) in mem.used_regions() {
+                    let bytes = mem.bytes_at(base.cast(), size);
+                    complete = search_bytes(bytes, base.to_bits(), ty, &target, &mut results);
+                    if !complete {
+                        break;
+                    }
+                }
+                if !complete {
+                    println!(
+                        "Stopped after {} results. Try searching for a less common value.",
+                        results.len()
+                    );
+                }
+                self.results = Some((ty, results));
+                self.print_result_count();
+            }
+            Command::Next(value) => {
+                let (ty, _) = self
+                    .results
+                    .as_ref()
+                    .ok_or("Nothing has been searched for")?;
+                let target = Target::parse(*ty, &value)?;
+                self.narrow(mem, |_, new| target.matches(new))?;
+            }
+            Command::Compare(comparison) => {
+                self.narrow(mem, |old, new| comparison.matches(old, new))?;
+            }
+            Command::List => {
+                let (_, results) = self
+                    .results
+                    .as_ref()
+                    .ok_or("Nothing has been searched for")?;
+                for &(addr, value) in results.iter().take(LIST_LIMIT) {
+                    println!("{:#010x}: {}", addr, value);
+                }
+                if results.len() > LIST_LIMIT {
+                    println!("... and {} more", results.len() - LIST_LIMIT);
+                }
+            }
+            Command::Read(addr, count) => {
+                check_range(mem, addr, count)?;
+                let bytes = mem.bytes_at(ConstPtr::<u8>::from_bits(addr), count);
+                print!("{}", hex_dump(bytes, addr));
+            }
+            Command::Write(addr, ty, value) => {
+                check_range(mem, addr, ty.size())?;
+                let bytes = ty.encode(value);
+                mem.bytes_at_mut(MutPtr::from_bits(addr), ty.size())
+                    .copy_from_slice(&bytes);
+                println!("Wrote {} to {:#x}", value, addr);
+            }
+            Command::Watch(addr, ty) => {
+                check_range(mem, addr, ty.size())?;
+                let value = ty.decode(mem.bytes_at(ConstPtr::<u8>::from_bits(addr), ty.size()));
+                self.watches.retain(|&(other, _, _)| other != addr);
+                self.watches.push((addr, ty, value));
+                println!("Watching {:#x}, which is {}", addr, value);
+            }
+            Command::Unwatch(addr) => {
+                let count = self.watches.len();
+                self.watches.retain(|&(other, _, _)| other != addr);
+                if self.watches.len() == count {
+                    return Err(format!("{:#x} isn't being watched", addr));
+                }
+            }
+            Command::Freeze(addr, ty, value) => {
+                check_range(mem, addr, ty.size())?;
+                let bytes = match value {
+                    Some(value) => ty.encode(value),
+                    None => mem
+                        .bytes_at(ConstPtr::<u8>::from_bits(addr), ty.size())
+                        .to_vec(),
+                };
+                println!("Freezing {:#x} at {}", addr, ty.decode(&bytes));
+                self.freezes.retain(|&(other, _)| other != addr);
+                self.freezes.push((addr, bytes));
+            }
+            Command::Unfreeze(addr) => {
+                let count = self.freezes.len();
+                self.freezes.retain(|&(other, _)| other != addr);
+                if self.freezes.len() == count {
+                    return Err(format!("{:#x} isn't frozen", addr));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep the results for which `keep` returns [true] given the old and new
+    /// values, and update their values.
+    fn narrow(&mut self, mem: &Mem, keep: impl Fn(Value, Value) -> bool) -> Result<(), String> {
+        let (ty, results) = self
+            .results
+            .as_mut()
+            .ok_or("Nothing has been searched for")?;
+        let ty = *ty;
+        results.retain_mut(|(addr, value)| {
+            let new = ty.decode(mem.bytes_at(ConstPtr::<u8>::from_bits(*addr), ty.size()));
+            let old = std::mem::replace(value, new);
+            keep(old, new)
+        });
+        self.print_result_count();
+        Ok(())
+    }
+
+    fn print_result_count(&self) {
+        let Some((_, ref results)) = self.results else {
+            return;
+        };
+        match results.len() {
+            0 => println!("No results. Use 'search' to start again."),
+            1 => println!("1 result: {:#010x}", results[0].0),
+            count if count <= LIST_LIMIT => println!("{} results. Use 'list' to show them.", count),
+            count => println!("{} results", count),
+        }
+    }
+}
+
+/// For use by the emulator's main loop: handle any commands that have been
+/// typed, write frozen values and report changes to watched ones. This does
+/// nothing unless the tool is enabled.
+pub fn handle_commands(env: &mut Environment) {
+    let tool = &mut env.memory_tool;
+    if !tool.enabled {
+        return;
+    }
+
+    for line in crate::terminal::read_lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Err(e) = parse_command(&line).and_then(|command| tool.run(&mut env.mem, command)) {
+            println!("{}", e);
+        }
+    }
+
+    for (addr, bytes) in &tool.freezes {
+        env.mem
+            .bytes_at_mut(MutPtr::from_bits(*addr), bytes.len() as GuestUSize)
+            .copy_from_slice(bytes);
+    }
+
+    for (addr, ty, value) in &mut tool.watches {
+        let new = ty.decode(
+            env.mem
+                .bytes_at(ConstPtr::<u8>::from_bits(*addr), ty.size()),
+        );
+        if new != *value {
+            println!("{:#x} changed from {} to {}", addr, value, new);
+            *value = new;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        assert_eq!(ValueType::U8.parse_value("255"), Ok(Value::Int(255)));
+        assert!(ValueType::U8.parse_value("256").is_err());
+        assert!(ValueType::U32.parse_value("-1").is_err());
+        assert_eq!(ValueType::I16.parse_value("-0x10"), Ok(Value::Int(-16)));
+        assert_eq!(
+            ValueType::U32.parse_value("0xFFFFFFFF"),
+            Ok(Value::Int(0xFFFFFFFF))
+        );
+        assert!(ValueType::I32.parse_value("1.5").is_err());
+        assert_eq!(ValueType::F32.parse_value("1.5"), Ok(Value::Float(1.5)));
+
+        assert_eq!(ValueType::I16.decode(&[0xfe, 0xff, 0x12]), Value::Int(-2));
+        assert_eq!(ValueType::U16.decode(&[0xfe, 0xff]), Value::Int(0xfffe));
+        assert_eq!(ValueType::I16.encode(Value::Int(-2)), [0xfe, 0xff]);
+        assert_eq!(
+            ValueType::F32.decode(&ValueType::F32.encode(Value::Float(0.25))),
+            Value::Float(0.25)
+        );
+
+        let target = Target::parse(ValueType::F32, "3.14").unwrap();
+        assert!(target.matches(Value::Float(std::f32::consts::PI.into())));
+        assert!(!target.matches(Value::Float(3.146)));
+        let target = Target::parse(ValueType::F32, "3").unwrap();
+        assert!(target.matches(Value::Float(3.4)));
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse_command("help"), Ok(Command::Help));
+        assert_eq!(
+            parse_command("  search  u32 100 "),
+            Ok(Command::Search(
+                ValueType::U32,
+                Target {
+                    value: Value::Int(100),
+                    tolerance: 0.0
+                }
+            ))
+        );
+        assert_eq!(
+            parse_command("next 99"),
+            Ok(Command::Next("99".to_string()))
+        );
+        assert_eq!(
+            parse_command("decreased"),
+            Ok(Command::Compare(Comparison::Decreased))
+        );
+        assert_eq!(parse_command("read 0x1000"), Ok(Command::Read(0x1000, 64)));
+        assert_eq!(parse_command("read 2000 16"), Ok(Command::Read(0x2000, 16)));
+        assert!(parse_command("read 2000 0").is_err());
+        assert_eq!(
+            parse_command("write 4000 i8 -1"),
+            Ok(Command::Write(0x4000, ValueType::I8, Value::Int(-1)))
+        );
+        assert_eq!(
+            parse_command("freeze 4000 u16"),
+            Ok(Command::Freeze(0x4000, ValueType::U16, None))
+        );
+        assert!(parse_command("write 4000 u8 300").is_err());
+        assert!(parse_command("search u32").is_err());
+        assert!(parse_command("search u64 1").is_err());
+        assert!(parse_command("unwatch xyz").is_err());
+        assert!(parse_command("").is_err());
+    }
+
+    #[test]
+    fn search() {
+        let mut bytes = vec![0u8; 32];
+        bytes[2..4].copy_from_slice(&100u16.to_le_bytes());
+        bytes[5] = 100;
+        bytes[8..12].copy_from_slice(&100u32.to_le_bytes());
+        let target = Target::parse(ValueType::U16, "100").unwrap();
+        let mut results = Vec::new();
+        assert!(search_bytes(
+            &bytes,
+            0x1000,
+            ValueType::U16,
+            &target,
+            &mut results
+        ));
+        // The unaligned match at 0x1005 isn't found.
+        assert_eq!(
+            results,
+            [(0x1002, Value::Int(100)), (0x1008, Value::Int(100))]
+        );
+
+        let target = Target::parse(ValueType::U8, "100").unwrap();
+        let mut results = Vec::new();
+        search_bytes(&bytes, 0x1000, ValueType::U8, &target, &mut results);
+        assert_eq!(results.len(), 3);
+
+        // The alignment is of the address, not the offset.
+        let target = Target::parse(ValueType::U32, "100").unwrap();
+        let mut results = Vec::new();
+        search_bytes(&bytes[1..], 0x1001, ValueType::U32, &target, &mut results);
+        assert_eq!(results, [(0x1008, Value::Int(100))]);
+    }
+
+    #[test]
+    fn dump() {
+        assert_eq!(
+            hex_dump(b"Hello\0", 0x2000),
+            format!("0x00002000: {:<47}  Hello.\n", "48 65 6c 6c 6f 00")
+        );
+    }
+}
@@ -19,9 +19,11 @@
 //! categories and dynamic class editing).
 
 use crate::dyld::{export_c_func, FunctionExports};
+use crate::plugins;
 
 use std::collections::HashMap;
 
+mod breakpoints;
 mod classes;
 mod messages;
 mod methods;
@@ -29,10 +31,11 @@ mod objects;
 mod properties;
 mod selectors;
 
+pub use breakpoints::SelectorBreakpoint;
 pub use classes::{objc_classes, Class, ClassExports, ClassTemplate};
 pub use messages::{autorelease, msg, msg_class, msg_send, release, retain};
 pub use methods::{GuestIMP, HostIMP, IMP};
-pub use objects::{id, nil, AnyHostObject, HostObject, TrivialHostObject};
+pub use objects::{id, nil, AnyHostObject, HostObject, TrivialHostObject, WeakRef};
 pub use selectors::{selector, SEL};
 
 use classes::{ClassHostObject, UnimplementedClass, CLASS_LISTS};
@@ -51,18 +54,27 @@ pub struct ObjC {
     /// If an object isn't in this map, we will consider it not to exist.
     objects: HashMap<id, HostObjectEntry>,
 
+    /// Serial number to give to the next object, see [WeakRef].
+    next_object_serial: u64,
+
     /// Known classes.
     ///
     /// Look at the `isa` to get the metaclass for a class.
     classes: HashMap<String, Class>,
+
+    /// The app's plugins, which can have classes (a copy of
+    /// [crate::Environment]'s, because classes are linked without one).
+    plugins: plugins::Enabled,
 }
 
 impl ObjC {
-    pub fn new() -> ObjC {
+    pub fn new(plugins: plugins::Enabled) -> ObjC {
         ObjC {
             selectors: HashMap::new(),
             objects: HashMap::new(),
+            next_object_serial: 0,
             classes: HashMap::new(),
+            plugins,
         }
     }
 }
@@ -0,0 +1,199 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Selector-level breakpoints (`--objc-breakpoint=`).
+//!
+//! These pause execution when a message matching a class and selector pair is
+//! sent, e.g. `-[UIView setFrame:]`, and dump the arguments. Subclasses match
+//! too, so that breakpoint would also be hit by a message sent to a `UIWindow`.
+
+use super::{id, nil, Class, ClassHostObject, ObjC, UnimplementedClass, SEL};
+use crate::cpu::Cpu;
+use crate::mem::{ConstPtr, Mem};
+use crate::Environment;
+
+/// A breakpoint on a class and selector pair.
+#[derive(Debug)]
+pub struct SelectorBreakpoint {
+    /// `None` matches any class (written as `*`).
+    class_name: Option<String>,
+    is_class_method: bool,
+    selector: String,
+}
+
+impl SelectorBreakpoint {
+    /// Parse a breakpoint written like an Objective-C method name, e.g.
+    /// `-[UIView setFrame:]`, `+[NSObject alloc]` or `-[* dealloc]`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let error = || format!("Invalid selector breakpoint {:?}", spec);
+
+        let (is_class_method, rest) = if let Some(rest) = spec.strip_prefix("-[") {
+            (false, rest)
+        } else if let Some(rest) = spec.strip_prefix("+[") {
+            (true, rest)
+        } else {
+            return Err(error());
+        };
+        let rest = rest.strip_suffix(']').ok_or_else(error)?;
+        let (class_name, selector) = rest.split_once(' ').ok_or_else(error)?;
+        let selector = selector.trim();
+        if class_name.is_empty() || selector.is_empty() || selector.contains(' ') {
+            return Err(error());
+        }
+
+        Ok(SelectorBreakpoint {
+            class_name: if class_name == "*" {
+                None
+            } else {
+                Some(class_name.to_string())
+            },
+            is_class_method,
+            selector: selector.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for SelectorBreakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}[{} {}]",
+            if self.is_class_method { '+' } else { '-' },
+            self.class_name.as_deref().unwrap_or("*"),
+            self.selector
+        )
+    }
+}
+
+impl ObjC {
+    /// Get the name of a class or metaclass, and whether it is a metaclass.
+    fn class_name_and_kind(&self, class: Class) -> Option<(&str, bool)> {
+        let host_object = self.get_host_object(class)?;
+        if let Some(ClassHostObject {
+            name, is_metaclass, ..
+        }) = host_object.as_any().downcast_ref()
+        {
+            Some((name, *is_metaclass))
+        } else if let Some(UnimplementedClass { name, is_metaclass }) =
+            host_object.as_any().downcast_ref()
+        {
+            Some((name, *is_metaclass))
+        } else {
+            None
+        }
+    }
+
+    /// Check if a class or one of its superclasses has a particular name.
+    fn class_or_superclass_named(&self, class: Class, name: &str) -> bool {
+        let mut class = class;
+        while class != nil {
+            let Some(host_object) = self.get_host_object(class) else {
+                return false;
+            };
+            let Some(&ClassHostObject {
+                name: ref class_name,
+                superclass,
+                ..
+            }) = host_object.as_any().downcast_ref()
+            else {
+                // unimplemented classes have no known superclass
+                return self
+                    .class_name_and_kind(class)
+                    .map(|(class_name, _)| class_name)
+                    == Some(name);
+            };
+            if class_name == name {
+                return true;
+            }
+            class = superclass;
+        }
+        false
+    }
+
+    /// Produce a short description of an argument word, identifying it as an
+    /// object if it is one.
+    fn describe_word(&self, word: u32, mem: &Mem) -> String {
+        let object = id::from_bits(word);
+        if object == nil || self.get_host_object(object).is_none() {
+            return format!("{:#x}", word);
+        }
+        let isa = ObjC::read_isa(object, mem);
+        match self.class_name_and_kind(isa) {
+            Some((name, _)) => format!("{:#x} ({} instance)", word, name),
+            None => match self.class_name_and_kind(object) {
+                Some((name, true)) => format!("{:#x} ({} metaclass)", word, name),
+                Some((name, false)) => format!("{:#x} ({} class)", word, name),
+                None => format!("{:#x}", word),
+            },
+        }
+    }
+}
+
+/// Called by `objc_msgSend` and friends before dispatching a message. Does
+/// nothing unless one of the selector breakpoints matches. `is_stret` is set
+/// for `objc_msgSend_stret`, where the receiver is in r1 rather than r0.
+pub(super) fn check_selector_breakpoints(
+    env: &mut Environment,
+    receiver: id,
+    selector: SEL,
+    class: Class,
+    is_stret: bool,
+) {
+    let selector_str = selector.as_str(&env.mem);
+    let Some(breakpoint) = env.options.objc_breakpoints.iter().find(|breakpoint| {
+        if breakpoint.selector != selector_str {
+            return false;
+        }
+        let Some((_, is_metaclass)) = env.objc.class_name_and_kind(class) else {
+            return false;
+        };
+        if is_metaclass != breakpoint.is_class_method {
+            return false;
+        }
+        match breakpoint.class_name {
+            None => true,
+            Some(ref name) => env.objc.class_or_superclass_named(class, name),
+        }
+    }) else {
+        return;
+    };
+
+    let (class_name, _) = env.objc.class_name_and_kind(class).unwrap();
+    log!(
+        "Hit selector breakpoint {} on thread {}: {}[{} {}]",
+        breakpoint,
+        env.current_thread,
+        if breakpoint.is_class_method { '+' } else { '-' },
+        class_name,
+        selector_str,
+    );
+    log!(
+        "Receiver: {}",
+        env.objc.describe_word(receiver.to_bits(), &env.mem)
+    );
+
+    // Arguments after self and _cmd are in r2 and r3, then on the stack. This
+    // doesn't know about the argument types, so e.g. a CGRect is shown as four
+    // consecutive words. For objc_msgSend_stret everything is shifted by one
+    // word, because r0 is the struct return pointer.
+    let regs = env.cpu.regs();
+    let arg_count = selector_str.matches(':').count();
+    let first_arg_word = if is_stret { 3 } else { 2 };
+    for i in 0..arg_count {
+        let word_index = first_arg_word + i;
+        let word = if word_index < 4 {
+            regs[word_index]
+        } else {
+            let sp = regs[Cpu::SP];
+            let ptr: ConstPtr<u32> = ConstPtr::from_bits(sp + 4 * (word_index as u32 - 4));
+            env.mem.read(ptr)
+        };
+        log!("Argument {}: {}", i, env.objc.describe_word(word, &env.mem));
+    }
+    env.cpu.dump_regs();
+
+    log!("Execution is paused. Press Enter to continue.");
+    let _ = crate::terminal::read_line();
+}
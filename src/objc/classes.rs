@@ -378,8 +378,28 @@ impl ObjC {
         })
     }
 
-    fn find_template(name: &str) -> Option<&'static ClassTemplate> {
-        crate::dyld::search_lists(CLASS_LISTS, name)
+    /// Search the enabled plugins (see [crate::plugins]) and then
+    /// [CLASS_LISTS] for a class we have an implementation of.
+    fn search_templates(
+        plugins: &crate::plugins::Enabled,
+        name: &str,
+    ) -> Option<(&'static str, &'static str, &'static ClassTemplate)> {
+        plugins
+            .search_classes(name)
+            .or_else(|| crate::dyld::search_labelled_lists(CLASS_LISTS, name))
+    }
+
+    fn find_template(&self, name: &str) -> Option<&'static ClassTemplate> {
+        Self::search_templates(&self.plugins, name).map(|(_, _, template)| template)
+    }
+
+    /// Get the path of the module a class we have an implementation of is
+    /// from. See [crate::dyld::labelled_lists].
+    pub fn find_template_module(
+        plugins: &crate::plugins::Enabled,
+        name: &str,
+    ) -> Option<&'static str> {
+        Self::search_templates(plugins, name).map(|(module, _, _)| module)
     }
 
     /// For use by [crate::dyld]: get the class or metaclass referenced by an
@@ -395,6 +415,12 @@ impl ObjC {
         self.link_class_inner(name, /* is_metaclass: */ false, mem, false)
     }
 
+    /// For use by host functions: get a class from the app, or one of ours
+    /// that has already been used, like `objc_lookUpClass()`.
+    pub fn lookup_class(&self, name: &str, mem: &Mem) -> Option<Class> {
+        self.get_class(name, /* is_metaclass: */ false, mem)
+    }
+
     fn link_class_inner(
         &mut self,
         name: &str,
@@ -413,14 +439,14 @@ impl ObjC {
 
         let class_host_object: Box<dyn AnyHostObject>;
         let metaclass_host_object: Box<dyn AnyHostObject>;
-        if let Some(template) = Self::find_template(name) {
+        if let Some(template) = self.find_template(name) {
             // We have a template (host implementation) for this class, use it.
 
             if let Some(superclass_name) = template.superclass {
                 // Make sure we actually have a template for the superclass
                 // before we try to link it, else we might get an unimplemented
                 // class back and have weird problems down the line
-                assert!(Self::find_template(superclass_name).is_some());
+                assert!(self.find_template(superclass_name).is_some());
             }
 
             class_host_object = Box::new(ClassHostObject::from_template(
@@ -6,42 +6,148 @@
 //! Separate module just for the class lists, since this will probably be a
 //! very long and frequently-updated list.
 
-use crate::frameworks::{core_animation, core_graphics, foundation, opengles, uikit};
+use super::ClassTemplate;
+use crate::dyld::{labelled_lists, LabelledLists};
+use crate::frameworks::{
+    address_book, address_book_ui, av_foundation, cf_network, core_animation, core_data,
+    core_foundation, core_graphics, core_location, core_text, foundation, game_kit, map_kit,
+    media_player, message_ui, opengles, security, store_kit, uikit,
+};
 
 /// All the lists of classes that the runtime should search through.
-pub const CLASS_LISTS: &[super::ClassExports] = &[
-    core_animation::ca_eagl_layer::CLASSES,
-    core_animation::ca_layer::CLASSES,
-    core_graphics::cg_color_space::CLASSES,
-    core_graphics::cg_context::CLASSES,
-    foundation::ns_array::CLASSES,
-    foundation::ns_autorelease_pool::CLASSES,
-    foundation::ns_bundle::CLASSES,
-    foundation::ns_character_set::CLASSES,
-    foundation::ns_coder::CLASSES,
-    foundation::ns_data::CLASSES,
-    foundation::ns_dictionary::CLASSES,
-    foundation::ns_keyed_unarchiver::CLASSES,
-    foundation::ns_locale::CLASSES,
-    foundation::ns_null::CLASSES,
-    foundation::ns_object::CLASSES,
-    foundation::ns_process_info::CLASSES,
-    foundation::ns_run_loop::CLASSES,
-    foundation::ns_set::CLASSES,
-    foundation::ns_string::CLASSES,
-    foundation::ns_thread::CLASSES,
-    foundation::ns_timer::CLASSES,
-    foundation::ns_url::CLASSES,
-    foundation::ns_value::CLASSES,
-    opengles::eagl::CLASSES,
-    uikit::ui_accelerometer::CLASSES,
-    uikit::ui_application::CLASSES,
-    uikit::ui_event::CLASSES,
-    uikit::ui_font::CLASSES,
-    uikit::ui_nib::CLASSES,
-    uikit::ui_responder::CLASSES,
-    uikit::ui_screen::CLASSES,
-    uikit::ui_touch::CLASSES,
-    uikit::ui_view::CLASSES,
-    uikit::ui_window::CLASSES,
+pub const CLASS_LISTS: LabelledLists<ClassTemplate> = labelled_lists![CLASSES;
+    address_book::ab_address_book,
+    address_book::ab_multi_value,
+    address_book::ab_person,
+    address_book_ui::ab_people_picker_navigation_controller,
+    av_foundation::av_audio_player,
+    cf_network::cf_http_message,
+    core_animation::ca_animation,
+    core_animation::ca_display_link,
+    core_animation::ca_eagl_layer,
+    core_animation::ca_layer,
+    core_animation::ca_media_timing_function,
+    core_animation::ca_transaction,
+    core_data::ns_entity_description,
+    core_data::ns_fetch_request,
+    core_data::ns_managed_object,
+    core_data::ns_managed_object_context,
+    core_data::ns_managed_object_model,
+    core_data::ns_persistent_store_coordinator,
+    core_foundation::cf_bag,
+    core_foundation::cf_binary_heap,
+    core_foundation::cf_run_loop,
+    core_foundation::cf_socket,
+    core_foundation::cf_tree,
+    core_foundation::cf_uuid,
+    core_graphics::cg_color,
+    core_graphics::cg_color_space,
+    core_graphics::cg_context,
+    core_graphics::cg_data_provider,
+    core_graphics::cg_function,
+    core_graphics::cg_gradient,
+    core_graphics::cg_image,
+    core_graphics::cg_path,
+    core_graphics::cg_pattern,
+    core_graphics::cg_pdf_document,
+    core_graphics::cg_pdf_page,
+    core_graphics::cg_shading,
+    core_location::cl_location,
+    core_location::cl_location_manager,
+    core_text::ct_frame,
+    core_text::ct_framesetter,
+    core_text::ct_line,
+    core_text::ct_paragraph_style,
+    foundation::ns_array,
+    foundation::ns_attributed_string,
+    foundation::ns_autorelease_pool,
+    foundation::ns_bundle,
+    foundation::ns_cache,
+    foundation::ns_calendar,
+    foundation::ns_character_set,
+    foundation::ns_coder,
+    foundation::ns_data,
+    foundation::ns_date,
+    foundation::ns_date_components,
+    foundation::ns_date_formatter,
+    foundation::ns_dictionary,
+    foundation::ns_error,
+    foundation::ns_file_manager,
+    foundation::ns_hash_table,
+    foundation::ns_index_path,
+    foundation::ns_index_set,
+    foundation::ns_keyed_unarchiver,
+    foundation::ns_locale,
+    foundation::ns_map_table,
+    foundation::ns_notification,
+    foundation::ns_notification_center,
+    foundation::ns_null,
+    foundation::ns_object,
+    foundation::ns_pointer_array,
+    foundation::ns_predicate,
+    foundation::ns_process_info,
+    foundation::ns_regular_expression,
+    foundation::ns_run_loop,
+    foundation::ns_set,
+    foundation::ns_sort_descriptor,
+    foundation::ns_stream,
+    foundation::ns_string,
+    foundation::ns_thread,
+    foundation::ns_time_zone,
+    foundation::ns_timer,
+    foundation::ns_url,
+    foundation::ns_url_request,
+    foundation::ns_user_defaults,
+    foundation::ns_uuid,
+    foundation::ns_value,
+    game_kit::gk_peer_picker_controller,
+    game_kit::gk_session,
+    map_kit::mk_annotation_view,
+    map_kit::mk_map_view,
+    map_kit::mk_user_location,
+    media_player::mp_media_item,
+    media_player::mp_media_item_collection,
+    media_player::mp_media_query,
+    media_player::mp_movie_player_controller,
+    media_player::mp_music_player_controller,
+    message_ui::mf_mail_compose_view_controller,
+    opengles::eagl,
+    security::secure_transport,
+    store_kit::sk_payment,
+    store_kit::sk_payment_queue,
+    store_kit::sk_payment_transaction,
+    store_kit::sk_product,
+    store_kit::sk_request,
+    uikit::ui_accelerometer,
+    uikit::ui_action_sheet,
+    uikit::ui_activity_indicator_view,
+    uikit::ui_alert_view,
+    uikit::ui_application,
+    uikit::ui_button,
+    uikit::ui_color,
+    uikit::ui_control,
+    uikit::ui_device,
+    uikit::ui_event,
+    uikit::ui_font,
+    uikit::ui_image,
+    uikit::ui_image_picker_controller,
+    uikit::ui_nib,
+    uikit::ui_progress_view,
+    uikit::ui_responder,
+    uikit::ui_screen,
+    uikit::ui_scroll_view,
+    uikit::ui_segmented_control,
+    uikit::ui_slider,
+    uikit::ui_switch,
+    uikit::ui_tab_bar,
+    uikit::ui_tab_bar_controller,
+    uikit::ui_table_view,
+    uikit::ui_table_view_cell,
+    uikit::ui_text_field,
+    uikit::ui_text_view,
+    uikit::ui_touch,
+    uikit::ui_view,
+    uikit::ui_view_controller,
+    uikit::ui_web_view,
+    uikit::ui_window,
 ];
@@ -29,7 +29,13 @@ use crate::Environment;
 /// by the method implementation. We are relying on CallFromGuest not
 /// overwriting it.
 #[allow(non_snake_case)]
-fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2: Option<Class>) {
+fn objc_msgSend_inner(
+    env: &mut Environment,
+    receiver: id,
+    selector: SEL,
+    super2: Option<Class>,
+    is_stret: bool,
+) {
     if receiver == nil {
         // https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjectiveC/Chapters/ocObjectsClasses.html#//apple_ref/doc/uid/TP30001163-CH11-SW7
         log_dbg!("[nil {}]", selector.as_str(&env.mem));
@@ -40,6 +46,12 @@ fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2
     let orig_class = super2.unwrap_or_else(|| ObjC::read_isa(receiver, &env.mem));
     assert!(orig_class != nil);
 
+    if !env.options.objc_breakpoints.is_empty() {
+        super::breakpoints::check_selector_breakpoints(
+            env, receiver, selector, orig_class, is_stret,
+        );
+    }
+
     // Traverse the chain of superclasses to find the method implementation.
 
     let mut class = orig_class;
@@ -54,6 +66,15 @@ fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2
                 ..
             } = class_host_object.as_any().downcast_ref().unwrap();
 
+            crate::report::unimplemented(
+                env.report.as_ref(),
+                &format!(
+                    "{}[{} {}]",
+                    if is_metaclass { '+' } else { '-' },
+                    name,
+                    selector.as_str(&env.mem)
+                ),
+            );
             panic!(
                 "{} {:?} ({}class \"{}\", {:?}){} does not respond to selector \"{}\"!",
                 if is_metaclass { "Class" } else { "Object" },
@@ -73,6 +94,8 @@ fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2
         let host_object = env.objc.get_host_object(class).unwrap();
 
         if let Some(&super::ClassHostObject {
+            ref name,
+            is_metaclass,
             superclass,
             ref methods,
             ..
@@ -87,7 +110,11 @@ fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2
 
             if let Some(imp) = methods.get(&selector) {
                 match imp {
-                    IMP::Host(host_imp) => host_imp.call_from_guest(env),
+                    IMP::Host(host_imp) => {
+                        let trace =
+                            crate::api_trace::objc_method(env, name, is_metaclass, selector);
+                        host_imp.call_from_guest_traced(env, trace.as_deref())
+                    }
                     IMP::Guest(guest_imp) => guest_imp.call(env),
                 }
                 return;
@@ -99,6 +126,7 @@ fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2
             is_metaclass,
         }) = host_object.as_any().downcast_ref()
         {
+            crate::report::unimplemented(env.report.as_ref(), name);
             panic!(
                 "Class \"{}\" ({:?}) is unimplemented. Call to {} method \"{}\".",
                 name,
@@ -118,7 +146,9 @@ fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2
 /// Standard variant of `objc_msgSend`. See [objc_msgSend_inner].
 #[allow(non_snake_case)]
 pub(super) fn objc_msgSend(env: &mut Environment, receiver: id, selector: SEL) {
-    objc_msgSend_inner(env, receiver, selector, /* super2: */ None)
+    objc_msgSend_inner(
+        env, receiver, selector, /* super2: */ None, /* is_stret: */ false,
+    )
 }
 
 /// Variant of `objc_msgSend` for methods that return a struct via a pointer.
@@ -136,7 +166,9 @@ pub(super) fn objc_msgSend_stret(
     receiver: id,
     selector: SEL,
 ) {
-    objc_msgSend_inner(env, receiver, selector, /* super2: */ None)
+    objc_msgSend_inner(
+        env, receiver, selector, /* super2: */ None, /* is_stret: */ true,
+    )
 }
 
 #[repr(C, packed)]
@@ -168,7 +200,13 @@ pub(super) fn objc_msgSendSuper2(
     // Rewrite first argument to match the normal ABI.
     crate::abi::write_next_arg(&mut 0, env.cpu.regs_mut(), &mut env.mem, receiver);
 
-    objc_msgSend_inner(env, receiver, selector, /* super2: */ Some(class))
+    objc_msgSend_inner(
+        env,
+        receiver,
+        selector,
+        /* super2: */ Some(class),
+        /* is_stret: */ false,
+    )
 }
 
 /// Wrapper around [objc_msgSend] which, together with [msg], makes it easy to
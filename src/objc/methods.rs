@@ -189,4 +189,35 @@ impl ObjC {
             }
         }
     }
+
+    /// Add a method to a class at runtime, like `class_addMethod()`. Returns
+    /// [false] and changes nothing if the class itself (not counting its
+    /// superclasses) already has a method for the selector.
+    pub fn add_method(&mut self, class: Class, sel: SEL, imp: IMP) -> bool {
+        let host_object: &mut ClassHostObject = self.borrow_mut(class);
+        if host_object.methods.contains_key(&sel) {
+            return false;
+        }
+        host_object.methods.insert(sel, imp);
+        true
+    }
+
+    /// Check whether `class` or a class between it and `base_class` (a
+    /// superclass) has its own implementation of a method, e.g. so that
+    /// `UIView` can tell if a subclass draws anything.
+    pub fn class_overrides_method(&self, class: Class, base_class: Class, sel: SEL) -> bool {
+        let mut class = class;
+        while class != base_class && class != nil {
+            let &ClassHostObject {
+                superclass,
+                ref methods,
+                ..
+            } = self.borrow(class);
+            if methods.contains_key(&sel) {
+                return true;
+            }
+            class = superclass;
+        }
+        false
+    }
 }
@@ -62,6 +62,20 @@ pub const nil: id = Ptr::null();
 pub(super) struct HostObjectEntry {
     host_object: Box<dyn AnyHostObject>,
     refcount: Option<NonZeroU32>,
+    /// Unique for every object ever allocated, unlike the address.
+    serial: u64,
+}
+
+/// Weak reference to an object, for host code that needs to know whether an
+/// object it doesn't own still exists (e.g. `NSMapTable` with weak keys).
+/// See [super::ObjC::weak_ref] and [super::ObjC::load_weak].
+///
+/// This is needed because the address of a deallocated object might be reused
+/// for a new object.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WeakRef {
+    object: id,
+    serial: u64,
 }
 
 /// Type for host objects.
@@ -95,6 +109,12 @@ impl super::ObjC {
         mem.read(object).isa
     }
 
+    fn next_object_serial(&mut self) -> u64 {
+        let serial = self.next_object_serial;
+        self.next_object_serial += 1;
+        serial
+    }
+
     fn alloc_object_inner(
         &mut self,
         isa: Class,
@@ -109,11 +129,13 @@ impl super::ObjC {
         let ptr: MutPtr<objc_object> = mem.alloc(instance_size).cast();
         mem.write(ptr, guest_object);
         assert!(!self.objects.contains_key(&ptr));
+        let serial = self.next_object_serial();
         self.objects.insert(
             ptr,
             HostObjectEntry {
                 host_object,
                 refcount,
+                serial,
             },
         );
         ptr
@@ -165,15 +187,32 @@ impl super::ObjC {
         host_object: Box<dyn AnyHostObject>,
     ) {
         assert!(!self.objects.contains_key(&guest_object));
+        let serial = self.next_object_serial();
         self.objects.insert(
             guest_object,
             HostObjectEntry {
                 host_object,
                 refcount: None,
+                serial,
             },
         );
     }
 
+    /// Create a weak reference to an object. `nil` is allowed.
+    pub fn weak_ref(&self, object: id) -> WeakRef {
+        let serial = self.objects.get(&object).map_or(0, |entry| entry.serial);
+        WeakRef { object, serial }
+    }
+
+    /// Get the object a weak reference refers to, or `nil` if it has been
+    /// deallocated.
+    pub fn load_weak(&self, weak: WeakRef) -> id {
+        match self.objects.get(&weak.object) {
+            Some(entry) if entry.serial == weak.serial => weak.object,
+            _ => nil,
+        }
+    }
+
     /// Get a reference to a host object, if the object exists.
     pub(super) fn get_host_object(&self, object: id) -> Option<&dyn AnyHostObject> {
         self.objects.get(&object).map(|entry| &*entry.host_object)
@@ -238,6 +277,7 @@ impl super::ObjC {
         let HostObjectEntry {
             host_object,
             refcount,
+            ..
         } = self.objects.remove(&object).unwrap();
         assert!(refcount.is_none());
         std::mem::drop(host_object);
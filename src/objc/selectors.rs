@@ -53,6 +53,11 @@ impl SEL {
         // selectors are probably always UTF-8 but this hasn't been verified
         mem.cstr_at_utf8(self.0)
     }
+
+    /// Selectors passed by apps can be `NULL`, e.g. to mean "any action".
+    pub fn is_null(self) -> bool {
+        self.0.is_null()
+    }
 }
 
 impl ObjC {
@@ -66,7 +71,11 @@ impl ObjC {
     /// [ObjC::register_bin_selectors], so that selector strings in the app
     /// binary can be re-used. [crate::dyld] calls both of these.
     pub fn register_host_selectors(&mut self, mem: &mut Mem) {
-        for &class_list in super::CLASS_LISTS {
+        let class_lists = super::CLASS_LISTS
+            .iter()
+            .copied()
+            .chain(self.plugins.labelled_lists(|plugin| plugin.classes));
+        for (_module, class_list) in class_lists {
             for (_name, template) in class_list {
                 for method_list in [template.class_methods, template.instance_methods] {
                     for &(name, _imp) in method_list {
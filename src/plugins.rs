@@ -0,0 +1,167 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Plugins, which add host implementations of functions, classes and constants
+//! that aren't part of touchHLE's own source tree, e.g. for a middleware SDK
+//! that only a few apps use.
+//!
+//! A plugin is a directory with a `mod.rs` file in it. It's compiled into
+//! touchHLE as a module named after the directory, so it can use touchHLE's
+//! internals in the same way as the modules in [crate::frameworks]. Plugins
+//! are built in by setting the `TOUCHHLE_PLUGINS` environment variable to a
+//! list of their directories, separated like `PATH`, when building touchHLE
+//! (see `build.rs`). A plugin that's built in is only used for apps it's
+//! enabled for with `--plugin=`, which is best put in an app's configuration
+//! file.
+//!
+//! `mod.rs` must have a `PLUGIN` constant listing what it exports, e.g.:
+//!
+//! ```rust
+//! use crate::dyld::{export_c_func, FunctionExports};
+//! use crate::mem::ConstPtr;
+//! use crate::plugins::Plugin;
+//! use crate::Environment;
+//!
+//! fn FooSDKStart(env: &mut Environment, api_key: ConstPtr<u8>) -> bool {
+//!     log!("FooSDKStart({:?})", env.mem.cstr_at_utf8(api_key));
+//!     true
+//! }
+//!
+//! const FUNCTIONS: FunctionExports = &[export_c_func!(FooSDKStart(_))];
+//!
+//! pub const PLUGIN: Plugin = Plugin {
+//!     functions: &[FUNCTIONS],
+//!     ..Plugin::EMPTY
+//! };
+//! ```
+//!
+//! The exports of enabled plugins are searched before touchHLE's own, so a
+//! plugin can also replace one of touchHLE's implementations for an app.
+//! Plugins can keep their own data with [state].
+
+use crate::dyld::{ConstantExports, FunctionExports, HostConstant, HostFunction};
+use crate::objc::{ClassExports, ClassTemplate};
+use crate::Environment;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// What a plugin exports. See the module documentation.
+pub struct Plugin {
+    pub functions: &'static [FunctionExports],
+    pub classes: &'static [ClassExports],
+    pub constants: &'static [ConstantExports],
+}
+impl Plugin {
+    #[allow(dead_code)] // only used by plugins
+    pub const EMPTY: Plugin = Plugin {
+        functions: &[],
+        classes: &[],
+        constants: &[],
+    };
+}
+
+// Defines `PLUGINS: &[(&str, &Plugin)]`, the name and exports of each plugin
+// that's built in.
+include!(concat!(env!("OUT_DIR"), "/plugins.rs"));
+
+/// The plugins enabled for an app with `--plugin=`. This is kept in the
+/// [Environment], and [crate::objc::ObjC] has a copy for linking classes.
+#[derive(Clone, Default)]
+pub struct Enabled(Vec<(&'static str, &'static Plugin)>);
+
+/// Gets one kind of list from a plugin, e.g. `|plugin| plugin.classes`.
+type ListsOf<T> = fn(&'static Plugin) -> &'static [&'static [(&'static str, T)]];
+
+impl Enabled {
+    /// Look up the plugins with these names.
+    pub fn new(names: &[String]) -> Result<Enabled, String> {
+        let plugins = names
+            .iter()
+            .map(|name| {
+                PLUGINS
+                    .iter()
+                    .find(|&&(other, _)| other == name)
+                    .copied()
+                    .ok_or_else(|| {
+                        let available: Vec<&str> =
+                            PLUGINS.iter().map(|&(name, _)| name).collect();
+                        format!(
+                            "Unknown plugin {:?}. The plugins built into this copy of touchHLE are: {}",
+                            name,
+                            if available.is_empty() {
+                                "none".to_string()
+                            } else {
+                                available.join(", ")
+                            }
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for &(name, _) in &plugins {
+            log!("Using the plugin {}", name);
+        }
+        Ok(Enabled(plugins))
+    }
+
+    /// Get one kind of list from each enabled plugin, labelled with the
+    /// plugin's name like with [crate::dyld::labelled_lists].
+    pub fn labelled_lists<T: 'static>(
+        &self,
+        lists: ListsOf<T>,
+    ) -> impl Iterator<Item = (&'static str, &'static [(&'static str, T)])> + '_ {
+        self.0
+            .iter()
+            .flat_map(move |&(name, plugin)| lists(plugin).iter().map(move |&list| (name, list)))
+    }
+
+    /// Like [crate::dyld::search_labelled_lists], but for the enabled plugins.
+    fn search<T: 'static>(
+        &self,
+        lists: ListsOf<T>,
+        symbol: &str,
+    ) -> Option<(&'static str, &'static str, &'static T)> {
+        self.labelled_lists(lists).find_map(|(name, list)| {
+            list.iter()
+                .find(|&(sym, _)| *sym == symbol)
+                .map(|(sym, item)| (name, *sym, item))
+        })
+    }
+
+    pub fn search_functions(
+        &self,
+        symbol: &str,
+    ) -> Option<(&'static str, &'static str, &'static HostFunction)> {
+        self.search(|plugin| plugin.functions, symbol)
+    }
+
+    pub fn search_classes(
+        &self,
+        name: &str,
+    ) -> Option<(&'static str, &'static str, &'static ClassTemplate)> {
+        self.search(|plugin| plugin.classes, name)
+    }
+
+    pub fn search_constants(&self, symbol: &str) -> Option<&'static HostConstant> {
+        self.search(|plugin| plugin.constants, symbol)
+            .map(|(_, _, constant)| constant)
+    }
+}
+
+/// Data kept by plugins, one value of each type. See [state].
+#[derive(Default)]
+pub struct State(HashMap<TypeId, Box<dyn Any>>);
+
+/// For use by plugins: get the value of some type that the plugin keeps in the
+/// [Environment], creating it the first time. Using a type private to the
+/// plugin avoids clashing with other plugins.
+#[allow(dead_code)] // only used by plugins
+pub fn state<T: Default + 'static>(env: &mut Environment) -> &mut T {
+    env.plugin_state
+        .0
+        .entry(TypeId::of::<T>())
+        .or_insert_with(|| Box::<T>::default())
+        .downcast_mut()
+        .unwrap()
+}
@@ -0,0 +1,524 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Recording input and replaying it. See `--record-input=` and
+//! `--replay-input=`.
+//!
+//! Input is timestamped by tick: how many times UIKit has handled events so
+//! far. A recording is a text file like this:
+//!
+//! ```text
+//! touchHLE input recording 1
+//! app com.example.game
+//! 0 srand 1700000000
+//! 12 accel 0 -0.5 -0.866
+//! 40 touch-down 0 160 240
+//! 41 touch-move 0 162 250
+//! 43 touch-up 0 162 250
+//! 90 text "Hi!"
+//! ```
+//!
+//! Replaying delivers each event at the same tick it was recorded at, answers
+//! accelerometer queries with the recorded readings, and makes the app's RNG
+//! seeds the recorded ones, so the app sees the same input, in the same
+//! order, with the same random numbers. Input from the host is ignored until
+//! the recording runs out. Replaying and recording can be done at the same
+//! time, which extends a recording, e.g. for a tool-assisted run.
+//!
+//! touchHLE doesn't emulate time, so the number of ticks in a second varies,
+//! and an app whose logic depends on the time rather than on frames can still
+//! behave differently.
+
+use crate::window::{EditingKey, Event, HardwareKey, NavigationButton, TouchSource};
+use crate::Environment;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const HEADER: &str = "touchHLE input recording 1";
+
+#[derive(Debug)]
+enum Entry {
+    Event(Event),
+    Acceleration(f32, f32, f32),
+    /// `srand()`'s seed.
+    Srand(u32),
+    /// `srandom()`'s seed.
+    Srandom(u32),
+}
+
+/// Touch sources are numbered in the order they're first seen, and replayed
+/// as fingers with those numbers.
+type SourceNumber<'a> = &'a mut dyn FnMut(TouchSource) -> usize;
+
+fn format_event(event: &Event, source_number: SourceNumber) -> String {
+    let mut touch = |kind, source, (x, y): (f32, f32)| {
+        format!("{} {} {} {}", kind, source_number(source), x, y)
+    };
+    match *event {
+        Event::Quit => "quit".to_string(),
+        Event::TouchDown(source, coords) => touch("touch-down", source, coords),
+        Event::TouchMove(source, coords) => touch("touch-move", source, coords),
+        Event::TouchUp(source, coords) => touch("touch-up", source, coords),
+        Event::TextInput(ref text) => format!("text {:?}", text),
+        Event::EditingKey(key) => format!(
+            "editing-key {}",
+            match key {
+                EditingKey::Backspace => "backspace",
+                EditingKey::Delete => "delete",
+                EditingKey::Return => "return",
+                EditingKey::Left => "left",
+                EditingKey::Right => "right",
+                EditingKey::Home => "home",
+                EditingKey::End => "end",
+            }
+        ),
+        Event::NavigationButton(button) => format!(
+            "navigation {}",
+            match button {
+                NavigationButton::Up => "up",
+                NavigationButton::Down => "down",
+                NavigationButton::Left => "left",
+                NavigationButton::Right => "right",
+                NavigationButton::Select => "select",
+                NavigationButton::Back => "back",
+            }
+        ),
+        Event::RotateDevice { clockwise } => format!(
+            "rotate {}",
+            if clockwise {
+                "clockwise"
+            } else {
+                "counterclockwise"
+            }
+        ),
+        Event::Shake => "shake".to_string(),
+        Event::ToggleAudioInterruption => "interruption".to_string(),
+        Event::TogglePause => unreachable!("pausing isn't recorded"),
+        Event::ToggleFastForward => unreachable!("fast-forwarding isn't recorded"),
+        Event::OpenMenu | Event::MenuInput(..) => unreachable!("the menu isn't recorded"),
+        Event::HardwareKey(key) => {
+            let mut modifiers = String::new();
+            for (held, letter) in [
+                (key.shift, 's'),
+                (key.control, 'c'),
+                (key.alt, 'a'),
+                (key.command, 'm'),
+                (key.caps_lock, 'l'),
+            ] {
+                if held {
+                    modifiers.push(letter);
+                }
+            }
+            if modifiers.is_empty() {
+                modifiers.push('-');
+            }
+            format!(
+                "key {} {} {} {}",
+                match (key.down, key.repeat) {
+                    (true, false) => "down",
+                    (true, true) => "repeat",
+                    (false, _) => "up",
+                },
+                key.usage,
+                key.character.map_or(0, u32::from),
+                modifiers
+            )
+        }
+    }
+}
+
+fn format_entry(entry: &Entry, source_number: SourceNumber) -> String {
+    match *entry {
+        Entry::Event(ref event) => format_event(event, source_number),
+        Entry::Acceleration(x, y, z) => format!("accel {} {} {}", x, y, z),
+        Entry::Srand(seed) => format!("srand {}", seed),
+        Entry::Srandom(seed) => format!("srandom {}", seed),
+    }
+}
+
+/// Undo the escaping of `{:?}` for a string, which must be in quotes.
+fn unescape(text: &str) -> Option<String> {
+    let text = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        result.push(match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            'u' => {
+                let rest = chars.as_str().strip_prefix('{')?;
+                let (hex, rest) = rest.split_once('}')?;
+                chars = rest.chars();
+                char::from_u32(u32::from_str_radix(hex, 16).ok()?)?
+            }
+            _ => return None,
+        });
+    }
+    Some(result)
+}
+
+fn parse_entry(line: &str) -> Option<(u64, Entry)> {
+    let (tick, rest) = line.split_once(' ')?;
+    let tick = tick.parse().ok()?;
+    let (kind, args) = rest.split_once(' ').unwrap_or((rest, ""));
+    if kind == "text" {
+        return Some((tick, Entry::Event(Event::TextInput(unescape(args)?))));
+    }
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let entry = match (kind, &args[..]) {
+        ("quit", []) => Entry::Event(Event::Quit),
+        ("touch-down" | "touch-move" | "touch-up", &[source, x, y]) => {
+            let source = TouchSource::Finger(source.parse().ok()?);
+            let coords = (x.parse().ok()?, y.parse().ok()?);
+            Entry::Event(match kind {
+                "touch-down" => Event::TouchDown(source, coords),
+                "touch-move" => Event::TouchMove(source, coords),
+                _ => Event::TouchUp(source, coords),
+            })
+        }
+        ("editing-key", &[key]) => Entry::Event(Event::EditingKey(match key {
+            "backspace" => EditingKey::Backspace,
+            "delete" => EditingKey::Delete,
+            "return" => EditingKey::Return,
+            "left" => EditingKey::Left,
+            "right" => EditingKey::Right,
+            "home" => EditingKey::Home,
+            "end" => EditingKey::End,
+            _ => return None,
+        })),
+        ("navigation", &[button]) => Entry::Event(Event::NavigationButton(match button {
+            "up" => NavigationButton::Up,
+            "down" => NavigationButton::Down,
+            "left" => NavigationButton::Left,
+            "right" => NavigationButton::Right,
+            "select" => NavigationButton::Select,
+            "back" => NavigationButton::Back,
+            _ => return None,
+        })),
+        ("rotate", &[direction]) => Entry::Event(Event::RotateDevice {
+            clockwise: match direction {
+                "clockwise" => true,
+                "counterclockwise" => false,
+                _ => return None,
+            },
+        }),
+        ("shake", []) => Entry::Event(Event::Shake),
+        ("interruption", []) => Entry::Event(Event::ToggleAudioInterruption),
+        ("key", &[state, usage, character, modifiers]) => {
+            let (down, repeat) = match state {
+                "down" => (true, false),
+                "repeat" => (true, true),
+                "up" => (false, false),
+                _ => return None,
+            };
+            let character = match character.parse().ok()? {
+                0 => None,
+                character => Some(char::from_u32(character)?),
+            };
+            Entry::Event(Event::HardwareKey(HardwareKey {
+                down,
+                repeat,
+                usage: usage.parse().ok()?,
+                character,
+                shift: modifiers.contains('s'),
+                control: modifiers.contains('c'),
+                alt: modifiers.contains('a'),
+                command: modifiers.contains('m'),
+                caps_lock: modifiers.contains('l'),
+            }))
+        }
+        ("accel", &[x, y, z]) => {
+            Entry::Acceleration(x.parse().ok()?, y.parse().ok()?, z.parse().ok()?)
+        }
+        ("srand", &[seed]) => Entry::Srand(seed.parse().ok()?),
+        ("srandom", &[seed]) => Entry::Srandom(seed.parse().ok()?),
+        _ => return None,
+    };
+    Some((tick, entry))
+}
+
+struct Recorder {
+    file: BufWriter<File>,
+    /// Touch sources seen so far. The index is the source's number.
+    sources: Vec<TouchSource>,
+}
+
+struct Replayer {
+    /// Events, in the order they happen.
+    events: VecDeque<(u64, Event)>,
+    /// Accelerometer readings, in the order they happen.
+    accelerations: VecDeque<(u64, (f32, f32, f32))>,
+    /// The latest reading that has been reached.
+    acceleration: Option<(f32, f32, f32)>,
+    srand_seeds: VecDeque<u32>,
+    srandom_seeds: VecDeque<u32>,
+    /// The last tick with anything on it.
+    end: u64,
+}
+
+#[derive(Default)]
+pub struct Replay {
+    recorder: Option<Recorder>,
+    replayer: Option<Replayer>,
+    /// How many times events have been handled so far.
+    tick: u64,
+}
+impl Replay {
+    /// Open the files given with `--record-input=` and `--replay-input=`, if
+    /// any.
+    pub fn new(
+        record_path: Option<&Path>,
+        replay_path: Option<&Path>,
+        bundle_identifier: &str,
+    ) -> Result<Replay, String> {
+        let replayer = replay_path
+            .map(|path| {
+                let text = std::fs::read_to_string(path).map_err(|e| {
+                    format!("Couldn't read input recording {}: {}", path.display(), e)
+                })?;
+                let replayer = Replayer::parse(&text, bundle_identifier)
+                    .map_err(|e| format!("Invalid input recording {}: {}", path.display(), e))?;
+                log!(
+                    "Replaying input from {}, which lasts {} ticks.",
+                    path.display(),
+                    replayer.end
+                );
+                Ok::<_, String>(replayer)
+            })
+            .transpose()?;
+
+        let recorder = record_path
+            .map(|path| {
+                let mut file = File::create(path).map(BufWriter::new).map_err(|e| {
+                    format!("Couldn't create input recording {}: {}", path.display(), e)
+                })?;
+                writeln!(file, "{}\napp {}", HEADER, bundle_identifier).unwrap();
+                log!("Recording input to {}.", path.display());
+                Ok::<_, String>(Recorder {
+                    file,
+                    sources: Vec::new(),
+                })
+            })
+            .transpose()?;
+
+        Ok(Replay {
+            recorder,
+            replayer,
+            tick: 0,
+        })
+    }
+
+    fn record(&mut self, entry: &Entry) {
+        self.record_with(|source_number| format_entry(entry, source_number));
+    }
+
+    fn record_with(&mut self, format: impl FnOnce(SourceNumber) -> String) {
+        let Some(ref mut recorder) = self.recorder else {
+            return;
+        };
+        let sources = &mut recorder.sources;
+        let line = format(&mut |source| {
+            sources
+                .iter()
+                .position(|&other| other == source)
+                .unwrap_or_else(|| {
+                    sources.push(source);
+                    sources.len() - 1
+                })
+        });
+        if let Err(e) = writeln!(recorder.file, "{} {}", self.tick, line) {
+            log!(
+                "Warning: Couldn't write to input recording, stopping: {}",
+                e
+            );
+            self.recorder = None;
+        }
+    }
+}
+impl Replayer {
+    fn parse(text: &str, bundle_identifier: &str) -> Result<Replayer, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err("not a touchHLE input recording".to_string());
+        }
+        match lines.next().and_then(|line| line.strip_prefix("app ")) {
+            Some(app) if app == bundle_identifier => (),
+            Some(app) => log!(
+                "Warning: This input recording is for {:?}, not {:?}. It probably won't replay correctly.",
+                app,
+                bundle_identifier
+            ),
+            None => return Err("the app isn't named".to_string()),
+        }
+
+        let mut replayer = Replayer {
+            events: VecDeque::new(),
+            accelerations: VecDeque::new(),
+            acceleration: None,
+            srand_seeds: VecDeque::new(),
+            srandom_seeds: VecDeque::new(),
+            end: 0,
+        };
+        for (line_number, line) in lines.enumerate() {
+            // The header takes up two lines.
+            let line_number = line_number + 3;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some((tick, entry)) = parse_entry(line) else {
+                return Err(format!("Line {}: invalid entry {:?}", line_number, line));
+            };
+            if tick < replayer.end {
+                return Err(format!(
+                    "Line {}: tick {} is out of order",
+                    line_number, tick
+                ));
+            }
+            replayer.end = tick;
+            match entry {
+                Entry::Event(event) => replayer.events.push_back((tick, event)),
+                Entry::Acceleration(x, y, z) => replayer.accelerations.push_back((tick, (x, y, z))),
+                Entry::Srand(seed) => replayer.srand_seeds.push_back(seed),
+                Entry::Srandom(seed) => replayer.srandom_seeds.push_back(seed),
+            }
+        }
+        Ok(replayer)
+    }
+}
+
+/// For use by UIKit's event handling: start a new tick. While replaying, this
+/// replaces the queued host input with the recorded input for the tick.
+pub fn next_tick(env: &mut Environment) {
+    env.replay.tick += 1;
+    let tick = env.replay.tick;
+    let Some(ref mut replayer) = env.replay.replayer else {
+        return;
+    };
+
+    // Quitting, pausing, fast-forwarding and the menu must still be possible.
+    let mut kept = Vec::new();
+    while let Some(event) = env.window.pop_event() {
+        if matches!(
+            event,
+            Event::Quit | Event::TogglePause | Event::ToggleFastForward | Event::OpenMenu
+        ) {
+            kept.push(event);
+        }
+    }
+    while replayer.events.front().is_some_and(|&(at, _)| at <= tick) {
+        let (_, event) = replayer.events.pop_front().unwrap();
+        env.window.push_event(event);
+    }
+    while replayer
+        .accelerations
+        .front()
+        .is_some_and(|&(at, _)| at <= tick)
+    {
+        replayer.acceleration = Some(replayer.accelerations.pop_front().unwrap().1);
+    }
+    for event in kept {
+        env.window.push_event(event);
+    }
+
+    if tick > replayer.end {
+        log!("Finished replaying input at tick {}.", tick);
+        env.replay.replayer = None;
+    }
+}
+
+/// For use by UIKit's event handling: record an event that is about to be
+/// handled.
+pub fn record_event(env: &mut Environment, event: &Event) {
+    env.replay
+        .record_with(|source_number| format_event(event, source_number));
+}
+
+/// For use by `UIAccelerometer`: get the device's acceleration, which comes
+/// from the recording while replaying.
+pub fn acceleration(env: &mut Environment) -> (f32, f32, f32) {
+    let acceleration = match env.replay.replayer {
+        Some(ref replayer) => replayer.acceleration.unwrap_or((0.0, 0.0, -1.0)),
+        None => env.window.get_acceleration(&env.options),
+    };
+    let (x, y, z) = acceleration;
+    env.replay.record(&Entry::Acceleration(x, y, z));
+    acceleration
+}
+
+/// For use by `srand()`: get the seed to use, which comes from the recording
+/// while replaying.
+pub fn srand_seed(env: &mut Environment, seed: u32) -> u32 {
+    let seed = env
+        .replay
+        .replayer
+        .as_mut()
+        .and_then(|replayer| replayer.srand_seeds.pop_front())
+        .unwrap_or(seed);
+    env.replay.record(&Entry::Srand(seed));
+    seed
+}
+
+/// For use by `srandom()`: see [srand_seed].
+pub fn srandom_seed(env: &mut Environment, seed: u32) -> u32 {
+    let seed = env
+        .replay
+        .replayer
+        .as_mut()
+        .and_then(|replayer| replayer.srandom_seeds.pop_front())
+        .unwrap_or(seed);
+    env.replay.record(&Entry::Srandom(seed));
+    seed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip() {
+        let lines = [
+            "0 srand 1700000000",
+            "3 srandom 42",
+            "12 accel 0 -0.5 -0.866",
+            "40 touch-down 0 160 240.5",
+            "41 touch-move 0 162 250",
+            "43 touch-up 1 162 250",
+            "50 text \"Hi!\\n\\\"\\u{7f}é\"",
+            "51 editing-key backspace",
+            "52 navigation select",
+            "53 rotate counterclockwise",
+            "54 shake",
+            "55 interruption",
+            "56 key down 4 97 sc",
+            "57 key up 41 0 -",
+            "58 quit",
+        ];
+        for line in lines {
+            let (tick, entry) = parse_entry(line).unwrap();
+            let formatted = format_entry(&entry, &mut |source| match source {
+                TouchSource::Finger(number) => number as usize,
+                _ => unreachable!(),
+            });
+            assert_eq!(format!("{} {}", tick, formatted), line);
+        }
+
+        assert!(parse_entry("x quit").is_none());
+        assert!(parse_entry("1 touch-down 0 1").is_none());
+        assert!(parse_entry("1 rotate sideways").is_none());
+        assert!(parse_entry("1 text unquoted").is_none());
+        assert!(parse_entry("1 fly").is_none());
+    }
+}
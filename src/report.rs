@@ -0,0 +1,286 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Compatibility reports (`--report`): a JSON file with information about the
+//! app and how it ran, which can be attached to a submission to the
+//! compatibility tracker.
+//!
+//! The report is kept in the [Environment] and written when the app exits or
+//! touchHLE crashes. Crashes are noticed with a panic hook, which has its own
+//! reference to the report, so they're recorded wherever they happen.
+
+use crate::bundle::Bundle;
+use crate::Environment;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The keys from the app's `Info.plist` that are included in the report, if
+/// the app has them.
+const APP_KEYS: &[&str] = &[
+    "CFBundleIdentifier",
+    "CFBundleDisplayName",
+    "CFBundleName",
+    "CFBundleVersion",
+    "CFBundleShortVersionString",
+    "DTSDKName",
+    "DTPlatformVersion",
+    "MinimumOSVersion",
+];
+
+struct Report {
+    path: PathBuf,
+    started: Instant,
+    /// Values for [APP_KEYS].
+    app: Vec<(&'static str, String)>,
+    /// Functions, classes, methods etc that the app used but touchHLE doesn't
+    /// implement.
+    unimplemented: BTreeSet<String>,
+    /// How many frames the app has presented, and when it presented the first
+    /// one.
+    frames: u64,
+    first_frame: Option<Instant>,
+    /// The panic message and where it happened, if touchHLE crashed.
+    crash: Option<(String, Option<String>)>,
+}
+
+/// A report being collected for an app, see [start].
+pub struct Reporter {
+    report: Arc<Mutex<Report>>,
+    /// Puts back the panic hook there was before [start].
+    restore_hook: Option<Box<dyn FnOnce()>>,
+}
+
+/// Start collecting a report for an app. It will be written to a file in the
+/// current directory named after the app's bundle identifier.
+pub fn start(bundle: &Bundle) -> Reporter {
+    let path = PathBuf::from(format!(
+        "touchHLE_report_{}.json",
+        bundle.bundle_identifier()
+    ));
+    let plist = bundle.info_plist();
+    let app = APP_KEYS
+        .iter()
+        .filter_map(|&key| Some((key, plist.get(key)?.as_string()?.to_string())))
+        .collect();
+    let report = Arc::new(Mutex::new(Report {
+        path,
+        started: Instant::now(),
+        app,
+        unimplemented: BTreeSet::new(),
+        frames: 0,
+        first_frame: None,
+        crash: None,
+    }));
+
+    let previous_hook = Arc::new(std::panic::take_hook());
+    let hook_report = report.clone();
+    let next_hook = previous_hook.clone();
+    std::panic::set_hook(Box::new(move |info| {
+        next_hook(info);
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("(unknown)")
+            .to_string();
+        let location = info.location().map(|location| location.to_string());
+        // The report is most useful after a panic, so a poisoned lock is
+        // ignored.
+        let mut report = hook_report.lock().unwrap_or_else(|e| e.into_inner());
+        // Only the first panic is the cause of the crash.
+        if report.crash.is_none() {
+            report.crash = Some((message, location));
+            report.write();
+        }
+    }));
+    // When apps are run from the launcher, there's a report for each, so the
+    // hook mustn't outlive this one.
+    let restore_hook = move || {
+        // Dropping this hook drops its reference to the previous one.
+        drop(std::panic::take_hook());
+        if let Ok(previous_hook) = Arc::try_unwrap(previous_hook) {
+            std::panic::set_hook(previous_hook);
+        }
+    };
+
+    Reporter {
+        report,
+        restore_hook: Some(Box::new(restore_hook)),
+    }
+}
+
+impl Reporter {
+    fn with_report(&self, f: impl FnOnce(&mut Report)) {
+        f(&mut self.report.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+
+    /// Write the report, if it wasn't already written because of a crash,
+    /// and stop collecting. This should be done when the app exits.
+    pub fn finish(self) {
+        self.with_report(|report| {
+            if report.crash.is_none() {
+                report.write();
+            }
+        });
+    }
+}
+
+impl Drop for Reporter {
+    fn drop(&mut self) {
+        // The hook can't be changed while panicking, but then touchHLE is
+        // about to exit anyway.
+        if std::thread::panicking() {
+            return;
+        }
+        if let Some(restore_hook) = self.restore_hook.take() {
+            restore_hook();
+        }
+    }
+}
+
+/// Record that the app used something touchHLE doesn't implement, e.g. the
+/// symbol of a function or a method like `-[UIView foo]`.
+pub fn unimplemented(report: Option<&Reporter>, what: &str) {
+    let Some(report) = report else {
+        return;
+    };
+    report.with_report(|report| {
+        if !report.unimplemented.contains(what) {
+            report.unimplemented.insert(what.to_string());
+        }
+    });
+}
+
+/// For use by [crate::frameworks::opengles::eagl]: record that the app
+/// presented a frame.
+pub fn frame_presented(env: &Environment) {
+    let Some(report) = &env.report else {
+        return;
+    };
+    report.with_report(|report| {
+        report.frames += 1;
+        report.first_frame.get_or_insert_with(Instant::now);
+    });
+}
+
+impl Report {
+    fn write(&self) {
+        let json = self.to_json(Instant::now());
+        match std::fs::write(&self.path, json) {
+            Ok(()) => log!("Wrote compatibility report to {}", self.path.display()),
+            Err(e) => log!(
+                "Couldn't write compatibility report to {}: {}",
+                self.path.display(),
+                e
+            ),
+        }
+    }
+
+    fn to_json(&self, now: Instant) -> String {
+        let mut json = String::new();
+        writeln!(json, "{{").unwrap();
+        writeln!(
+            json,
+            "  \"touchHLE_version\": {},",
+            json_string(crate::VERSION)
+        )
+        .unwrap();
+        let platform = format!("{} {}", std::env::consts::OS, std::env::consts::ARCH);
+        writeln!(json, "  \"platform\": {},", json_string(&platform)).unwrap();
+
+        writeln!(json, "  \"app\": {{").unwrap();
+        for (i, (key, value)) in self.app.iter().enumerate() {
+            let comma = if i + 1 < self.app.len() { "," } else { "" };
+            let (key, value) = (json_string(key), json_string(value));
+            writeln!(json, "    {}: {}{}", key, value, comma).unwrap();
+        }
+        writeln!(json, "  }},").unwrap();
+
+        let unimplemented: Vec<String> = self
+            .unimplemented
+            .iter()
+            .map(|what| format!("\n    {}", json_string(what)))
+            .collect();
+        let end = if unimplemented.is_empty() { "" } else { "\n  " };
+        let unimplemented = unimplemented.join(",");
+        writeln!(json, "  \"unimplemented\": [{}{}],", unimplemented, end).unwrap();
+
+        match &self.crash {
+            Some((message, location)) => {
+                writeln!(json, "  \"crash\": {{").unwrap();
+                writeln!(json, "    \"message\": {},", json_string(message)).unwrap();
+                let location = location.as_deref().map_or("null".to_string(), json_string);
+                writeln!(json, "    \"location\": {}", location).unwrap();
+                writeln!(json, "  }},").unwrap();
+            }
+            None => writeln!(json, "  \"crash\": null,").unwrap(),
+        }
+
+        writeln!(json, "  \"frames\": {},", self.frames).unwrap();
+        let fps = self
+            .first_frame
+            .and_then(|first_frame| average_fps(self.frames, now - first_frame));
+        let fps = fps.map_or("null".to_string(), |fps| format!("{:.1}", fps));
+        writeln!(json, "  \"average_fps\": {},", fps).unwrap();
+        let duration = (now - self.started).as_secs_f64();
+        writeln!(json, "  \"duration_seconds\": {:.1}", duration).unwrap();
+        writeln!(json, "}}").unwrap();
+        json
+    }
+}
+
+/// The average frame rate over some time, if it's long enough to be
+/// meaningful.
+fn average_fps(frames: u64, duration: Duration) -> Option<f64> {
+    if duration < Duration::from_secs(1) {
+        return None;
+    }
+    Some(frames as f64 / duration.as_secs_f64())
+}
+
+/// Quote and escape a string for JSON.
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings() {
+        assert_eq!(json_string("abc"), "\"abc\"");
+        assert_eq!(json_string(""), "\"\"");
+        assert_eq!(json_string("a \"b\" \\c"), "\"a \\\"b\\\" \\\\c\"");
+        assert_eq!(json_string("a\nb\tc"), "\"a\\nb\\tc\"");
+        assert_eq!(json_string("\u{1}"), "\"\\u0001\"");
+        assert_eq!(json_string("–ü日本"), "\"–ü日本\"");
+    }
+
+    #[test]
+    fn fps() {
+        assert_eq!(average_fps(30, Duration::from_millis(500)), None);
+        assert_eq!(average_fps(60, Duration::from_secs(2)), Some(30.0));
+        assert_eq!(average_fps(0, Duration::from_secs(5)), Some(0.0));
+    }
+}
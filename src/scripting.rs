@@ -0,0 +1,288 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Lua scripts that patch apps, e.g. to skip a broken copy protection check,
+//! without changing touchHLE. See `--scripts-dir=` and the README in
+//! `touchHLE_scripts/`, which documents what scripts can do.
+//!
+//! An app's script is run once, after the app is loaded but before any of its
+//! code runs. It can patch memory straight away, and it can register hooks:
+//! Lua functions that replace guest functions, or that are called before
+//! touchHLE's implementation of a function.
+//!
+//! The functions scripts use to access the emulator are only valid while
+//! touchHLE is running a script, because they borrow the [Environment]. They
+//! are recreated each time with [mlua::Lua::scope].
+
+use crate::abi::GuestFunction;
+use crate::cpu::Cpu;
+use crate::dyld::LinkedHostFunction;
+use crate::mem::{guest_size_of, ConstPtr, GuestUSize, Mem, SafeRead};
+use crate::Environment;
+use mlua::{Function, Integer, Lua, RegistryKey, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct Scripts {
+    /// [None] if the app has no script, or while a script is running.
+    lua: Option<Lua>,
+    /// Hooks that replace guest functions, by address without the Thumb bit.
+    replacements: HashMap<u32, RegistryKey>,
+    /// Hooks on touchHLE's functions, by name without the leading underscore.
+    intercepts: HashMap<String, RegistryKey>,
+}
+
+fn error(message: String) -> mlua::Error {
+    mlua::Error::RuntimeError(message)
+}
+
+/// Check that a script can access `size` bytes at an address.
+fn guest_addr(addr: Integer, size: GuestUSize) -> mlua::Result<u32> {
+    let max = (1 << 32) - Integer::from(size);
+    if (Integer::from(Mem::NULL_PAGE_SIZE)..=max).contains(&addr) {
+        Ok(addr as u32)
+    } else {
+        Err(error(format!("Invalid address {:#x}", addr)))
+    }
+}
+
+/// Find a function in the app or a library loaded with it. Names are like for
+/// `--trace-api=`, without the leading underscore.
+fn find_symbol(env: &Environment, name: &str) -> Option<u32> {
+    let symbol = format!("_{}", name);
+    env.bins.iter().find_map(|bin| {
+        bin.exported_symbols
+            .get(&symbol)
+            .or_else(|| bin.exported_symbols.get(name))
+            .copied()
+    })
+}
+
+/// Run something with the `touchHLE` table of functions available to scripts.
+fn with_api<R>(
+    env: &mut Environment,
+    lua: &Lua,
+    f: impl FnOnce() -> mlua::Result<R>,
+) -> mlua::Result<R> {
+    let env = RefCell::new(env);
+    lua.scope(|scope| {
+        let api = lua.create_table()?;
+
+        api.set(
+            "log",
+            scope.create_function(|_, message: String| {
+                log!("{}", message);
+                Ok(())
+            })?,
+        )?;
+        api.set(
+            "symbol",
+            scope.create_function(|_, name: String| Ok(find_symbol(&env.borrow(), &name)))?,
+        )?;
+        api.set(
+            "replace",
+            scope.create_function(|lua, (target, hook): (Value, Function)| {
+                let addr = match target {
+                    Value::String(name) => {
+                        let name = name.to_str()?;
+                        find_symbol(&env.borrow(), name)
+                            .ok_or_else(|| error(format!("No function called {:?}", name)))?
+                    }
+                    Value::Integer(addr) => guest_addr(addr, 4)?,
+                    _ => return Err(error("Expected an address or name".to_string())),
+                };
+                let addr = GuestFunction::from_addr_with_thumb_bit(addr);
+                let key = lua.create_registry_value(hook)?;
+                let mut env = env.borrow_mut();
+                let env = &mut **env;
+                env.dyld.set_script_hook(&mut env.mem, &mut env.cpu, addr);
+                env.scripts
+                    .replacements
+                    .insert(addr.addr_without_thumb_bit(), key);
+                Ok(())
+            })?,
+        )?;
+        api.set(
+            "intercept",
+            scope.create_function(|lua, (name, hook): (String, Function)| {
+                let key = lua.create_registry_value(hook)?;
+                env.borrow_mut().scripts.intercepts.insert(name, key);
+                Ok(())
+            })?,
+        )?;
+
+        api.set(
+            "reg",
+            scope.create_function(|_, reg: usize| {
+                env.borrow()
+                    .cpu
+                    .regs()
+                    .get(reg)
+                    .copied()
+                    .ok_or_else(|| error(format!("Invalid register {}", reg)))
+            })?,
+        )?;
+        api.set(
+            "set_reg",
+            scope.create_function(|_, (reg, value): (usize, Integer)| {
+                let mut env = env.borrow_mut();
+                let reg = env
+                    .cpu
+                    .regs_mut()
+                    .get_mut(reg)
+                    .ok_or_else(|| error(format!("Invalid register {}", reg)))?;
+                *reg = value as u32;
+                Ok(())
+            })?,
+        )?;
+
+        api.set(
+            "read_string",
+            scope.create_function(|lua, addr: Integer| {
+                let ptr = ConstPtr::<u8>::from_bits(guest_addr(addr, 1)?);
+                lua.create_string(env.borrow().mem.cstr_at(ptr))
+            })?,
+        )?;
+        // Each type has a read_ and write_ function. Writes clear the CPU's
+        // cache for the address, so that they can be used to patch code.
+        macro_rules! accessors {
+            ($($read:literal, $write:literal: $type:ty as $lua_type:ty;)*) => {
+                $(
+                    api.set(
+                        $read,
+                        scope.create_function(|_, addr: Integer| {
+                            let ptr = typed_ptr::<$type>(addr)?;
+                            Ok(env.borrow().mem.read(ptr) as $lua_type)
+                        })?,
+                    )?;
+                    api.set(
+                        $write,
+                        scope.create_function(|_, (addr, value): (Integer, $lua_type)| {
+                            let ptr = typed_ptr::<$type>(addr)?;
+                            let mut env = env.borrow_mut();
+                            let env = &mut **env;
+                            env.mem.write(ptr.cast_mut(), value as $type);
+                            env.cpu
+                                .invalidate_cache_range(ptr.to_bits(), guest_size_of::<$type>());
+                            Ok(())
+                        })?,
+                    )?;
+                )*
+            }
+        }
+        accessors! {
+            "read_u8", "write_u8": u8 as Integer;
+            "read_u16", "write_u16": u16 as Integer;
+            "read_u32", "write_u32": u32 as Integer;
+            "read_i8", "write_i8": i8 as Integer;
+            "read_i16", "write_i16": i16 as Integer;
+            "read_i32", "write_i32": i32 as Integer;
+            "read_f32", "write_f32": f32 as f64;
+            "read_f64", "write_f64": f64 as f64;
+        }
+
+        lua.globals().set("touchHLE", api)?;
+        f()
+    })
+}
+
+fn typed_ptr<T: SafeRead>(addr: Integer) -> mlua::Result<ConstPtr<T>> {
+    Ok(ConstPtr::from_bits(guest_addr(addr, guest_size_of::<T>())?))
+}
+
+/// Run the app's script, if it has one. This should be done after linking.
+pub fn load(env: &mut Environment) -> Result<(), String> {
+    let path = env
+        .options
+        .scripts_dir
+        .join(format!("{}.lua", env.bundle.bundle_identifier()));
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("Couldn't read {}: {}", path.display(), e)),
+    };
+    log!("Running the script {}", path.display());
+
+    let lua = Lua::new();
+    let chunk = lua.load(&source).set_name(path.display().to_string());
+    let result = with_api(env, &lua, || chunk.exec());
+    env.scripts.lua = Some(lua);
+    result.map_err(|e| format!("Error in script {}: {}", path.display(), e))
+}
+
+/// Call a hook with the first four argument registers, and get the value it
+/// returned, if any.
+fn call_hook(env: &mut Environment, key: impl FnOnce(&Scripts) -> &RegistryKey) -> Option<u32> {
+    let lua = env.scripts.lua.take().unwrap();
+    let result = lua
+        .registry_value::<Function>(key(&env.scripts))
+        .and_then(|hook| {
+            let regs = env.cpu.regs();
+            let args = (regs[0], regs[1], regs[2], regs[3]);
+            with_api(env, &lua, || hook.call::<_, Option<Integer>>(args))
+        });
+    env.scripts.lua = Some(lua);
+    match result {
+        Ok(retval) => retval.map(|retval| retval as u32),
+        Err(e) => panic!("Error in script hook: {}", e),
+    }
+}
+
+/// For use by the emulator's main loop: run the hook for a guest function
+/// replaced by a script, when the function is called. Any value the hook
+/// returns is put in R0, and then the function returns to its caller.
+pub fn run_replacement(env: &mut Environment) {
+    let is_thumb = (env.cpu.cpsr() & Cpu::CPSR_THUMB) == Cpu::CPSR_THUMB;
+    // The PC is after the SVC.
+    let addr = env.cpu.regs()[Cpu::PC] - if is_thumb { 2 } else { 4 };
+    if !env.scripts.replacements.contains_key(&addr) {
+        panic!("No script hook for replaced function at {:#x}", addr);
+    }
+
+    if let Some(retval) = call_hook(env, |scripts| &scripts.replacements[&addr]) {
+        env.cpu.regs_mut()[0] = retval;
+    }
+    // Do what `bx lr` would.
+    let lr = env.cpu.regs()[Cpu::LR];
+    env.cpu.branch(GuestFunction::from_addr_with_thumb_bit(lr));
+}
+
+/// For use by the emulator's main loop: run a script's hook on one of
+/// touchHLE's functions, if there is one. If the hook returns a value, it's put
+/// in R0 and [true] is returned, meaning touchHLE's function shouldn't be
+/// called.
+pub fn intercept(env: &mut Environment, f: &LinkedHostFunction) -> bool {
+    if env.scripts.intercepts.is_empty() {
+        return false;
+    }
+    let name = f.symbol.strip_prefix('_').unwrap_or(f.symbol);
+    if !env.scripts.intercepts.contains_key(name) {
+        return false;
+    }
+
+    match call_hook(env, |scripts| &scripts.intercepts[name]) {
+        Some(retval) => {
+            env.cpu.regs_mut()[0] = retval;
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses() {
+        assert_eq!(guest_addr(0x1000, 4).ok(), Some(0x1000));
+        assert_eq!(guest_addr(0xFFFFFFFC, 4).ok(), Some(0xFFFFFFFC));
+        assert!(guest_addr(0xFFFFFFFD, 4).is_err());
+        assert!(guest_addr(0xFFF, 1).is_err());
+        assert!(guest_addr(-1, 1).is_err());
+        assert!(guest_addr(1 << 32, 1).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Snapshot testing (`--snapshot=` and related options), for finding
+//! regressions in rendering.
+//!
+//! The app's content is captured at chosen frames and compared against golden
+//! images saved by an earlier run, in `<snapshot dir>/<bundle id>/`. A frame
+//! with no golden image yet is saved as the new golden image. After the last
+//! chosen frame, the results are printed and the app exits, with a non-zero
+//! exit code if any frame differed (see [Environment::exit]). The captured frame is what the app drew,
+//! before touchHLE draws things like alerts and virtual controls on top, so it
+//! doesn't depend on the window size or the display filter.
+//!
+//! For the app to draw the same frames each time, it needs the same input:
+//! `--replay-input=` can provide that, and `--headless` avoids needing to see
+//! the window. Putting all the options for an app in its configuration file
+//! means a whole collection of apps can be tested just by running each one.
+
+use crate::image::Image;
+use crate::Environment;
+use std::path::{Path, PathBuf};
+
+pub struct Snapshots {
+    /// The frames to capture, in order, with the earliest last.
+    frames: Vec<u32>,
+    /// How many frames the app has presented.
+    frame_count: u32,
+    dir: PathBuf,
+    tolerance: u8,
+    matched: u32,
+    differed: u32,
+    saved: u32,
+}
+
+impl Snapshots {
+    /// Returns [None] if there are no frames to capture.
+    pub fn new(options: &crate::Options, bundle_id: &str) -> Option<Self> {
+        if options.snapshot_frames.is_empty() {
+            return None;
+        }
+        let mut frames = options.snapshot_frames.clone();
+        frames.sort_by(|a, b| b.cmp(a));
+        frames.dedup();
+        Some(Snapshots {
+            frames,
+            frame_count: 0,
+            dir: options.snapshot_dir.join(bundle_id),
+            tolerance: options.snapshot_tolerance,
+            matched: 0,
+            differed: 0,
+            saved: 0,
+        })
+    }
+}
+
+/// For use by [crate::frameworks::opengles::eagl]: capture the app's content
+/// for a frame it's presenting, if this is one of the chosen frames. `texture`
+/// must be a texture in the current context containing the content.
+pub fn frame_presented(env: &mut Environment, texture: u32) {
+    let Some(snapshots) = &mut env.snapshots else {
+        return;
+    };
+    snapshots.frame_count += 1;
+    let frame = snapshots.frame_count;
+    if snapshots.frames.last() != Some(&frame) {
+        return;
+    }
+    snapshots.frames.pop();
+
+    let (pixels, size) = unsafe { crate::window::read_texture_pixels(texture) };
+    let hash = hash(&pixels);
+    let path = snapshots.dir.join(format!("frame_{}.bmp", frame));
+    match check(snapshots, &path, &pixels, size) {
+        Ok(true) => log!("Snapshot of frame {} (hash {:016x}) matches", frame, hash),
+        Ok(false) => log!(
+            "Saved snapshot of frame {} (hash {:016x}) to {} as the golden image",
+            frame,
+            hash,
+            path.display()
+        ),
+        Err(e) => {
+            snapshots.differed += 1;
+            log!(
+                "Snapshot of frame {} (hash {:016x}) failed: {}",
+                frame,
+                hash,
+                e
+            );
+        }
+    }
+
+    if snapshots.frames.is_empty() {
+        let failed = snapshots.differed > 0;
+        println!(
+            "Snapshots: {} matched, {} differed, {} saved as new golden images",
+            snapshots.matched, snapshots.differed, snapshots.saved
+        );
+        env.exit(if failed { 1 } else { 0 });
+    }
+}
+
+/// Compare a frame against its golden image, or save it as the golden image
+/// if there isn't one yet. Returns whether there was a golden image.
+fn check(
+    snapshots: &mut Snapshots,
+    path: &Path,
+    pixels: &[u8],
+    size: (u32, u32),
+) -> Result<bool, String> {
+    let golden = match std::fs::read(path) {
+        Ok(golden) => golden,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            std::fs::create_dir_all(&snapshots.dir)
+                .map_err(|e| e.to_string())
+                .and_then(|()| crate::window::save_bmp(path, pixels, size))
+                .map_err(|e| format!("Couldn't save {}: {}", path.display(), e))?;
+            snapshots.saved += 1;
+            return Ok(false);
+        }
+        Err(e) => return Err(format!("Couldn't read {}: {}", path.display(), e)),
+    };
+    let golden =
+        Image::from_bytes(&golden).map_err(|()| format!("Couldn't decode {}", path.display()))?;
+
+    let result = if golden.dimensions() != size {
+        Err(format!(
+            "The size is {}x{}, but the golden image's is {}x{}",
+            size.0,
+            size.1,
+            golden.dimensions().0,
+            golden.dimensions().1
+        ))
+    } else {
+        match compare(golden.pixels(), pixels, snapshots.tolerance) {
+            (0, _) => Ok(true),
+            (count, max_difference) => Err(format!(
+                "{} pixels differ from the golden image, by up to {}",
+                count, max_difference
+            )),
+        }
+    };
+    match result {
+        Ok(_) => snapshots.matched += 1,
+        Err(_) => {
+            // Keep what was captured, so it can be looked at, or used as the
+            // new golden image if the difference is an improvement.
+            let actual_path = path.with_extension("actual.bmp");
+            if let Err(e) = crate::window::save_bmp(&actual_path, pixels, size) {
+                log!("Warning: couldn't save {}: {}", actual_path.display(), e);
+            }
+        }
+    }
+    result
+}
+
+/// Compare pixels of a golden image (RGBA8) with captured pixels (RGB8).
+/// Returns how many pixels have a channel that differs by more than
+/// `tolerance`, and the largest difference in any channel.
+fn compare(golden: &[u8], pixels: &[u8], tolerance: u8) -> (usize, u8) {
+    let mut count = 0;
+    let mut max_difference = 0;
+    for (golden, pixel) in golden.chunks_exact(4).zip(pixels.chunks_exact(3)) {
+        let difference = golden[..3]
+            .iter()
+            .zip(pixel)
+            .map(|(&a, &b)| a.abs_diff(b))
+            .max()
+            .unwrap();
+        if difference > tolerance {
+            count += 1;
+        }
+        max_difference = max_difference.max(difference);
+    }
+    (count, max_difference)
+}
+
+/// FNV-1a hash of a frame's pixels, so that frames can be told apart in logs.
+fn hash(pixels: &[u8]) -> u64 {
+    pixels.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comparison() {
+        let golden = [10, 20, 30, 255, 40, 50, 60, 255];
+        assert_eq!(compare(&golden, &[10, 20, 30, 40, 50, 60], 0), (0, 0));
+        assert_eq!(compare(&golden, &[12, 20, 30, 40, 50, 60], 0), (1, 2));
+        assert_eq!(compare(&golden, &[12, 20, 30, 40, 50, 60], 2), (0, 2));
+        assert_eq!(compare(&golden, &[10, 20, 25, 40, 55, 60], 4), (2, 5));
+        assert_eq!(compare(&golden, &[0, 20, 30, 40, 50, 60], 255), (0, 10));
+    }
+
+    #[test]
+    fn hashes() {
+        assert_eq!(hash(&[]), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_ne!(hash(&[0, 1]), hash(&[1, 0]));
+    }
+}
@@ -0,0 +1,977 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Our implementation of `libsqlite3.dylib`, the SQLite library iPhone OS
+//! ships with.
+//!
+//! This wraps a build of SQLite bundled with touchHLE, so it's only a matter
+//! of translating between guest and host pointers. Databases are files in the
+//! guest filesystem, which SQLite opens by their host path. Databases in the
+//! app bundle are always opened read-only.
+//!
+//! Only the commonly-used part of the C API is exposed. Functions taking
+//! variadic arguments (e.g. `sqlite3_mprintf`) and those registering callbacks
+//! other than `sqlite3_exec`'s are missing.
+//!
+//! Useful resources:
+//! - [C-language Interface Specification for SQLite](https://www.sqlite.org/c3ref/intro.html)
+
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::fs::{GuestOpenOptions, GuestPath};
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr, SafeRead};
+use crate::Environment;
+use libsqlite3_sys as ffi;
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+
+#[derive(Default)]
+pub struct State {
+    databases: HashMap<MutPtr<sqlite3>, Database>,
+    statements: HashMap<MutPtr<sqlite3_stmt>, Statement>,
+    /// Guest copies of strings that never change, e.g. the library version.
+    static_strings: HashMap<&'static CStr, ConstPtr<u8>>,
+    /// Results of `sqlite3_get_table`, with the strings they contain.
+    tables: HashMap<MutPtr<MutPtr<u8>>, Vec<MutPtr<u8>>>,
+}
+
+struct Database {
+    raw: *mut ffi::sqlite3,
+    /// Result of the last `sqlite3_errmsg`, freed when it's next called.
+    errmsg: Option<MutPtr<u8>>,
+}
+
+struct Statement {
+    raw: *mut ffi::sqlite3_stmt,
+    db: MutPtr<sqlite3>,
+    /// Text and blob values returned for the current row, freed when the
+    /// statement is stepped, reset or finalized.
+    values: Vec<MutVoidPtr>,
+    /// Column names and declared types, freed when the statement is
+    /// finalized.
+    names: HashMap<(i32, bool), MutPtr<u8>>,
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+pub struct sqlite3 {
+    _filler: u8,
+}
+unsafe impl SafeRead for sqlite3 {}
+
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+pub struct sqlite3_stmt {
+    _filler: u8,
+}
+unsafe impl SafeRead for sqlite3_stmt {}
+
+/// `sqlite3_destructor_type` value meaning the data won't change.
+const SQLITE_STATIC: u32 = 0;
+/// `sqlite3_destructor_type` value meaning the data must be copied.
+const SQLITE_TRANSIENT: u32 = u32::MAX;
+
+fn lookup_database(env: &mut Environment, db: MutPtr<sqlite3>) -> Option<&mut Database> {
+    env.sqlite3_state.databases.get_mut(&db)
+}
+
+fn lookup_statement(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>) -> Option<&mut Statement> {
+    env.sqlite3_state.statements.get_mut(&stmt)
+}
+
+/// Get a guest copy of a string that lives as long as the library.
+fn static_str(env: &mut Environment, string: &'static CStr) -> ConstPtr<u8> {
+    if let Some(&existing) = env.sqlite3_state.static_strings.get(string) {
+        return existing;
+    }
+    let new = env.mem.alloc_and_write_cstr(string.to_bytes()).cast_const();
+    env.sqlite3_state.static_strings.insert(string, new);
+    new
+}
+
+/// Copy a string from the guest, or [None] if the pointer is null.
+fn guest_cstring(env: &Environment, string: ConstPtr<u8>) -> Option<CString> {
+    if string.is_null() {
+        return None;
+    }
+    Some(CString::new(env.mem.cstr_at(string)).unwrap())
+}
+
+/// Copy a string from the host.
+///
+/// # Safety
+///
+/// `string` must be null or point to a valid C string.
+unsafe fn host_cstr(string: *const c_char) -> Option<&'static CStr> {
+    (!string.is_null()).then(|| CStr::from_ptr(string))
+}
+
+/// Free the values returned for a statement's current row.
+fn free_values(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>) {
+    let values = std::mem::take(&mut lookup_statement(env, stmt).unwrap().values);
+    for value in values {
+        env.mem.free(value);
+    }
+}
+
+/// Find the host file for a database, creating it if necessary and allowed.
+/// Returns the host path and the flags to open it with.
+pub fn host_path_for_open(
+    env: &mut Environment,
+    filename: &CStr,
+    flags: i32,
+) -> Result<(CString, i32), ()> {
+    let Ok(filename_str) = filename.to_str() else {
+        return Err(());
+    };
+    // These databases have no file.
+    if filename_str.is_empty() || filename_str == ":memory:" {
+        return Ok((filename.to_owned(), flags));
+    }
+    if filename_str.starts_with("file:") {
+        log!(
+            "Warning: SQLite URI filename {:?} will be treated as a path",
+            filename_str
+        );
+    }
+
+    let path = GuestPath::new(filename_str);
+    if !env.fs.is_file(path) {
+        if flags & ffi::SQLITE_OPEN_CREATE == 0 {
+            return Err(());
+        }
+        // An empty file is a valid empty database.
+        let mut options = GuestOpenOptions::new();
+        options.write().create();
+        env.fs.open_with_options(path, options)?;
+    }
+    let (host_path, writeable) = env.fs.host_file_path(path).unwrap();
+    let mut flags = flags;
+    if !writeable && flags & ffi::SQLITE_OPEN_READWRITE != 0 {
+        log!(
+            "Warning: SQLite database {:?} is read-only, opening it read-only",
+            filename_str
+        );
+        flags &= !(ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE);
+        flags |= ffi::SQLITE_OPEN_READONLY;
+    }
+    let host_path = host_path.to_str().ok_or(())?;
+    Ok((CString::new(host_path).unwrap(), flags))
+}
+
+fn sqlite3_open(
+    env: &mut Environment,
+    filename: ConstPtr<u8>,
+    pp_db: MutPtr<MutPtr<sqlite3>>,
+) -> i32 {
+    let flags = ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE;
+    sqlite3_open_v2(env, filename, pp_db, flags, Ptr::null())
+}
+
+fn sqlite3_open_v2(
+    env: &mut Environment,
+    filename: ConstPtr<u8>,
+    pp_db: MutPtr<MutPtr<sqlite3>>,
+    flags: i32,
+    vfs: ConstPtr<u8>,
+) -> i32 {
+    if !vfs.is_null() {
+        log!(
+            "Warning: ignoring SQLite VFS {:?}",
+            env.mem.cstr_at_utf8(vfs)
+        );
+    }
+    let Some(filename) = guest_cstring(env, filename) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    let Ok((host_path, flags)) = host_path_for_open(env, &filename, flags) else {
+        log!("Warning: couldn't open SQLite database {:?}", filename);
+        env.mem.write(pp_db, Ptr::null());
+        return ffi::SQLITE_CANTOPEN;
+    };
+
+    let mut raw = std::ptr::null_mut();
+    let result =
+        unsafe { ffi::sqlite3_open_v2(host_path.as_ptr(), &mut raw, flags, std::ptr::null()) };
+    if raw.is_null() {
+        env.mem.write(pp_db, Ptr::null());
+        return result;
+    }
+    // Even if opening failed, the handle is returned so the app can get the
+    // error message.
+    let db = env.mem.alloc_and_write(sqlite3 { _filler: 0 });
+    env.sqlite3_state
+        .databases
+        .insert(db, Database { raw, errmsg: None });
+    env.mem.write(pp_db, db);
+    log_dbg!(
+        "sqlite3_open_v2({:?}, {:#x}) => {} (handle {:?})",
+        filename,
+        flags,
+        result,
+        db
+    );
+    result
+}
+
+fn sqlite3_close(env: &mut Environment, db: MutPtr<sqlite3>) -> i32 {
+    close(env, db, false)
+}
+
+fn sqlite3_close_v2(env: &mut Environment, db: MutPtr<sqlite3>) -> i32 {
+    close(env, db, true)
+}
+
+fn close(env: &mut Environment, db: MutPtr<sqlite3>, v2: bool) -> i32 {
+    if db.is_null() {
+        return ffi::SQLITE_OK;
+    }
+    let Some(database) = lookup_database(env, db) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    let result = unsafe {
+        if v2 {
+            ffi::sqlite3_close_v2(database.raw)
+        } else {
+            ffi::sqlite3_close(database.raw)
+        }
+    };
+    // sqlite3_close() fails if there are unfinalized statements.
+    if result != ffi::SQLITE_OK {
+        return result;
+    }
+    let database = env.sqlite3_state.databases.remove(&db).unwrap();
+    if let Some(errmsg) = database.errmsg {
+        env.mem.free(errmsg.cast());
+    }
+    env.mem.free(db.cast());
+    result
+}
+
+fn sqlite3_errmsg(env: &mut Environment, db: MutPtr<sqlite3>) -> ConstPtr<u8> {
+    let Some(database) = lookup_database(env, db) else {
+        return static_str(env, CStr::from_bytes_with_nul(b"out of memory\0").unwrap());
+    };
+    let raw = database.raw;
+    let old = database.errmsg.take();
+    let message = unsafe { host_cstr(ffi::sqlite3_errmsg(raw)) }.unwrap();
+    let new = env.mem.alloc_and_write_cstr(message.to_bytes());
+    if let Some(old) = old {
+        env.mem.free(old.cast());
+    }
+    lookup_database(env, db).unwrap().errmsg = Some(new);
+    new.cast_const()
+}
+
+fn sqlite3_errcode(env: &mut Environment, db: MutPtr<sqlite3>) -> i32 {
+    let Some(database) = lookup_database(env, db) else {
+        return ffi::SQLITE_NOMEM;
+    };
+    unsafe { ffi::sqlite3_errcode(database.raw) }
+}
+
+fn sqlite3_extended_errcode(env: &mut Environment, db: MutPtr<sqlite3>) -> i32 {
+    let Some(database) = lookup_database(env, db) else {
+        return ffi::SQLITE_NOMEM;
+    };
+    unsafe { ffi::sqlite3_extended_errcode(database.raw) }
+}
+
+fn sqlite3_busy_timeout(env: &mut Environment, db: MutPtr<sqlite3>, ms: i32) -> i32 {
+    let Some(database) = lookup_database(env, db) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    unsafe { ffi::sqlite3_busy_timeout(database.raw, ms) }
+}
+
+fn sqlite3_last_insert_rowid(env: &mut Environment, db: MutPtr<sqlite3>) -> i64 {
+    let Some(database) = lookup_database(env, db) else {
+        return 0;
+    };
+    unsafe { ffi::sqlite3_last_insert_rowid(database.raw) }
+}
+
+fn sqlite3_changes(env: &mut Environment, db: MutPtr<sqlite3>) -> i32 {
+    let Some(database) = lookup_database(env, db) else {
+        return 0;
+    };
+    unsafe { ffi::sqlite3_changes(database.raw) }
+}
+
+fn sqlite3_total_changes(env: &mut Environment, db: MutPtr<sqlite3>) -> i32 {
+    let Some(database) = lookup_database(env, db) else {
+        return 0;
+    };
+    unsafe { ffi::sqlite3_total_changes(database.raw) }
+}
+
+fn sqlite3_prepare(
+    env: &mut Environment,
+    db: MutPtr<sqlite3>,
+    sql: ConstPtr<u8>,
+    n_byte: i32,
+    pp_stmt: MutPtr<MutPtr<sqlite3_stmt>>,
+    pz_tail: MutPtr<ConstPtr<u8>>,
+) -> i32 {
+    // The differences between these are in how errors are reported by
+    // sqlite3_step(), which apps written for the original can cope with.
+    sqlite3_prepare_v2(env, db, sql, n_byte, pp_stmt, pz_tail)
+}
+
+fn sqlite3_prepare_v2(
+    env: &mut Environment,
+    db: MutPtr<sqlite3>,
+    sql: ConstPtr<u8>,
+    n_byte: i32,
+    pp_stmt: MutPtr<MutPtr<sqlite3_stmt>>,
+    pz_tail: MutPtr<ConstPtr<u8>>,
+) -> i32 {
+    if !pp_stmt.is_null() {
+        env.mem.write(pp_stmt, Ptr::null());
+    }
+    let Some(raw_db) = lookup_database(env, db).map(|database| database.raw) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    if sql.is_null() || pp_stmt.is_null() {
+        return ffi::SQLITE_MISUSE;
+    }
+    let mut sql_bytes = env.mem.cstr_at(sql);
+    if n_byte >= 0 && (n_byte as usize) < sql_bytes.len() {
+        sql_bytes = &sql_bytes[..n_byte as usize];
+    }
+    let host_sql = CString::new(sql_bytes).unwrap();
+
+    let mut raw = std::ptr::null_mut();
+    let mut tail = std::ptr::null();
+    let result =
+        unsafe { ffi::sqlite3_prepare_v2(raw_db, host_sql.as_ptr(), -1, &mut raw, &mut tail) };
+    log_dbg!("sqlite3_prepare_v2({:?}, {:?}) => {}", db, host_sql, result);
+    if !pz_tail.is_null() {
+        let offset = if tail.is_null() {
+            host_sql.as_bytes().len()
+        } else {
+            tail as usize - host_sql.as_ptr() as usize
+        };
+        env.mem.write(pz_tail, sql + offset as GuestUSize);
+    }
+    // There's no statement if the SQL was empty or only a comment.
+    if !raw.is_null() {
+        let stmt = env.mem.alloc_and_write(sqlite3_stmt { _filler: 0 });
+        env.sqlite3_state.statements.insert(
+            stmt,
+            Statement {
+                raw,
+                db,
+                values: Vec::new(),
+                names: HashMap::new(),
+            },
+        );
+        env.mem.write(pp_stmt, stmt);
+    }
+    result
+}
+
+fn sqlite3_step(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>) -> i32 {
+    let Some(raw) = lookup_statement(env, stmt).map(|statement| statement.raw) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    free_values(env, stmt);
+    unsafe { ffi::sqlite3_step(raw) }
+}
+
+fn sqlite3_reset(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>) -> i32 {
+    let Some(raw) = lookup_statement(env, stmt).map(|statement| statement.raw) else {
+        return ffi::SQLITE_OK;
+    };
+    free_values(env, stmt);
+    unsafe { ffi::sqlite3_reset(raw) }
+}
+
+fn sqlite3_finalize(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>) -> i32 {
+    if stmt.is_null() {
+        return ffi::SQLITE_OK;
+    }
+    let Some(statement) = env.sqlite3_state.statements.remove(&stmt) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    let result = unsafe { ffi::sqlite3_finalize(statement.raw) };
+    for value in statement.values {
+        env.mem.free(value);
+    }
+    for name in statement.names.into_values() {
+        env.mem.free(name.cast());
+    }
+    env.mem.free(stmt.cast());
+    result
+}
+
+fn sqlite3_clear_bindings(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>) -> i32 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    unsafe { ffi::sqlite3_clear_bindings(statement.raw) }
+}
+
+fn sqlite3_db_handle(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>) -> MutPtr<sqlite3> {
+    lookup_statement(env, stmt).map_or(Ptr::null(), |statement| statement.db)
+}
+
+fn sqlite3_bind_parameter_count(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>) -> i32 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return 0;
+    };
+    unsafe { ffi::sqlite3_bind_parameter_count(statement.raw) }
+}
+
+fn sqlite3_bind_parameter_index(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    name: ConstPtr<u8>,
+) -> i32 {
+    let Some(name) = guest_cstring(env, name) else {
+        return 0;
+    };
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return 0;
+    };
+    unsafe { ffi::sqlite3_bind_parameter_index(statement.raw, name.as_ptr()) }
+}
+
+fn sqlite3_bind_null(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>, index: i32) -> i32 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    unsafe { ffi::sqlite3_bind_null(statement.raw, index) }
+}
+
+fn sqlite3_bind_int(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    index: i32,
+    value: i32,
+) -> i32 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    unsafe { ffi::sqlite3_bind_int(statement.raw, index, value) }
+}
+
+fn sqlite3_bind_int64(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    index: i32,
+    value: i64,
+) -> i32 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    unsafe { ffi::sqlite3_bind_int64(statement.raw, index, value) }
+}
+
+fn sqlite3_bind_double(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    index: i32,
+    value: f64,
+) -> i32 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    unsafe { ffi::sqlite3_bind_double(statement.raw, index, value) }
+}
+
+/// Shared part of `sqlite3_bind_text` and `sqlite3_bind_blob`. The data is
+/// always copied, so the app's destructor can be called right away.
+fn bind_bytes(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    index: i32,
+    data: ConstVoidPtr,
+    size: i32,
+    destructor: GuestFunction,
+    is_text: bool,
+) -> i32 {
+    let Some(raw) = lookup_statement(env, stmt).map(|statement| statement.raw) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    if data.is_null() {
+        return unsafe { ffi::sqlite3_bind_null(raw, index) };
+    }
+    let bytes = if size < 0 {
+        // Only valid for text.
+        env.mem.cstr_at(data.cast::<u8>()).to_vec()
+    } else {
+        env.mem.bytes_at(data.cast(), size as GuestUSize).to_vec()
+    };
+    let result = unsafe {
+        if is_text {
+            ffi::sqlite3_bind_text(
+                raw,
+                index,
+                bytes.as_ptr().cast(),
+                bytes.len() as i32,
+                ffi::SQLITE_TRANSIENT(),
+            )
+        } else {
+            ffi::sqlite3_bind_blob(
+                raw,
+                index,
+                bytes.as_ptr().cast(),
+                bytes.len() as i32,
+                ffi::SQLITE_TRANSIENT(),
+            )
+        }
+    };
+    let destructor_bits = destructor.addr_with_thumb_bit();
+    if destructor_bits != SQLITE_STATIC && destructor_bits != SQLITE_TRANSIENT {
+        let () = destructor.call_from_host(env, (data,));
+    }
+    result
+}
+
+fn sqlite3_bind_text(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    index: i32,
+    text: ConstPtr<u8>,
+    size: i32,
+    destructor: GuestFunction,
+) -> i32 {
+    bind_bytes(env, stmt, index, text.cast(), size, destructor, true)
+}
+
+fn sqlite3_bind_blob(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    index: i32,
+    blob: ConstVoidPtr,
+    size: i32,
+    destructor: GuestFunction,
+) -> i32 {
+    bind_bytes(env, stmt, index, blob, size, destructor, false)
+}
+
+fn sqlite3_column_count(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>) -> i32 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return 0;
+    };
+    unsafe { ffi::sqlite3_column_count(statement.raw) }
+}
+
+fn sqlite3_data_count(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>) -> i32 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return 0;
+    };
+    unsafe { ffi::sqlite3_data_count(statement.raw) }
+}
+
+/// Shared part of `sqlite3_column_name` and `sqlite3_column_decltype`.
+fn column_name_or_decltype(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    column: i32,
+    decltype: bool,
+) -> ConstPtr<u8> {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return Ptr::null();
+    };
+    if let Some(&existing) = statement.names.get(&(column, decltype)) {
+        return existing.cast_const();
+    }
+    let name = unsafe {
+        host_cstr(if decltype {
+            ffi::sqlite3_column_decltype(statement.raw, column)
+        } else {
+            ffi::sqlite3_column_name(statement.raw, column)
+        })
+    };
+    let Some(name) = name else {
+        return Ptr::null();
+    };
+    let new = env.mem.alloc_and_write_cstr(name.to_bytes());
+    lookup_statement(env, stmt)
+        .unwrap()
+        .names
+        .insert((column, decltype), new);
+    new.cast_const()
+}
+
+fn sqlite3_column_name(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    column: i32,
+) -> ConstPtr<u8> {
+    column_name_or_decltype(env, stmt, column, false)
+}
+
+fn sqlite3_column_decltype(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    column: i32,
+) -> ConstPtr<u8> {
+    column_name_or_decltype(env, stmt, column, true)
+}
+
+fn sqlite3_column_type(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>, column: i32) -> i32 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return ffi::SQLITE_NULL;
+    };
+    unsafe { ffi::sqlite3_column_type(statement.raw, column) }
+}
+
+fn sqlite3_column_int(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>, column: i32) -> i32 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return 0;
+    };
+    unsafe { ffi::sqlite3_column_int(statement.raw, column) }
+}
+
+fn sqlite3_column_int64(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>, column: i32) -> i64 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return 0;
+    };
+    unsafe { ffi::sqlite3_column_int64(statement.raw, column) }
+}
+
+fn sqlite3_column_double(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>, column: i32) -> f64 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return 0.0;
+    };
+    unsafe { ffi::sqlite3_column_double(statement.raw, column) }
+}
+
+fn sqlite3_column_bytes(env: &mut Environment, stmt: MutPtr<sqlite3_stmt>, column: i32) -> i32 {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return 0;
+    };
+    unsafe { ffi::sqlite3_column_bytes(statement.raw, column) }
+}
+
+fn sqlite3_column_text(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    column: i32,
+) -> ConstPtr<u8> {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return Ptr::null();
+    };
+    let raw = statement.raw;
+    // The text is followed by a null terminator, which isn't counted.
+    let bytes = unsafe {
+        let text = ffi::sqlite3_column_text(raw, column);
+        if text.is_null() {
+            return Ptr::null();
+        }
+        let size = ffi::sqlite3_column_bytes(raw, column) as usize;
+        std::slice::from_raw_parts(text, size + 1)
+    };
+    let value = env.mem.alloc(bytes.len() as GuestUSize);
+    env.mem
+        .bytes_at_mut(value.cast(), bytes.len() as GuestUSize)
+        .copy_from_slice(bytes);
+    lookup_statement(env, stmt).unwrap().values.push(value);
+    value.cast().cast_const()
+}
+
+fn sqlite3_column_blob(
+    env: &mut Environment,
+    stmt: MutPtr<sqlite3_stmt>,
+    column: i32,
+) -> ConstVoidPtr {
+    let Some(statement) = lookup_statement(env, stmt) else {
+        return Ptr::null();
+    };
+    let raw = statement.raw;
+    let bytes = unsafe {
+        let blob = ffi::sqlite3_column_blob(raw, column);
+        // Zero-length blobs are returned as null pointers.
+        if blob.is_null() {
+            return Ptr::null();
+        }
+        let size = ffi::sqlite3_column_bytes(raw, column) as usize;
+        std::slice::from_raw_parts(blob.cast::<u8>(), size)
+    };
+    let value = env.mem.alloc(bytes.len() as GuestUSize);
+    env.mem
+        .bytes_at_mut(value.cast(), bytes.len() as GuestUSize)
+        .copy_from_slice(bytes);
+    lookup_statement(env, stmt).unwrap().values.push(value);
+    value.cast_const()
+}
+
+/// Run each statement in some SQL, calling `f` with the column names and
+/// values of each row of results. `f` returns [false] to stop. Returns the
+/// error code and message, if any. This is the shared part of
+/// `sqlite3_exec` and `sqlite3_get_table`.
+fn run_sql<F>(
+    env: &mut Environment,
+    raw_db: *mut ffi::sqlite3,
+    sql: &CStr,
+    mut f: F,
+) -> (i32, Option<CString>)
+where
+    F: FnMut(&mut Environment, &[Option<Vec<u8>>], &[Option<Vec<u8>>]) -> bool,
+{
+    let mut remaining = sql.as_ptr();
+    loop {
+        let mut raw = std::ptr::null_mut();
+        let mut tail = std::ptr::null();
+        let result = unsafe { ffi::sqlite3_prepare_v2(raw_db, remaining, -1, &mut raw, &mut tail) };
+        let error = || unsafe { host_cstr(ffi::sqlite3_errmsg(raw_db)).map(CStr::to_owned) };
+        if result != ffi::SQLITE_OK {
+            return (result, error());
+        }
+        if raw.is_null() {
+            // Whitespace or a comment, or the end of the SQL.
+            if tail.is_null() || unsafe { *tail } == 0 {
+                return (ffi::SQLITE_OK, None);
+            }
+            remaining = tail;
+            continue;
+        }
+
+        let column_count = unsafe { ffi::sqlite3_column_count(raw) };
+        let names: Vec<Option<Vec<u8>>> = (0..column_count)
+            .map(|i| {
+                unsafe { host_cstr(ffi::sqlite3_column_name(raw, i)) }
+                    .map(|name| name.to_bytes().to_vec())
+            })
+            .collect();
+        let result = loop {
+            let result = unsafe { ffi::sqlite3_step(raw) };
+            if result != ffi::SQLITE_ROW {
+                break result;
+            }
+            let values: Vec<Option<Vec<u8>>> = (0..column_count)
+                .map(|i| {
+                    unsafe { host_cstr(ffi::sqlite3_column_text(raw, i).cast()) }
+                        .map(|value| value.to_bytes().to_vec())
+                })
+                .collect();
+            if !f(env, &names, &values) {
+                break ffi::SQLITE_ABORT;
+            }
+        };
+        if result == ffi::SQLITE_ABORT {
+            unsafe { ffi::sqlite3_finalize(raw) };
+            return (result, Some(CString::new("query aborted").unwrap()));
+        }
+        if result != ffi::SQLITE_DONE {
+            let error = error();
+            unsafe { ffi::sqlite3_finalize(raw) };
+            return (result, error);
+        }
+        unsafe { ffi::sqlite3_finalize(raw) };
+        if tail.is_null() || unsafe { *tail } == 0 {
+            return (ffi::SQLITE_OK, None);
+        }
+        remaining = tail;
+    }
+}
+
+/// Allocate guest strings for a row, with null pointers for null values.
+fn alloc_row(env: &mut Environment, row: &[Option<Vec<u8>>]) -> Vec<MutPtr<u8>> {
+    row.iter()
+        .map(|value| match value {
+            Some(value) => env.mem.alloc_and_write_cstr(value),
+            None => Ptr::null(),
+        })
+        .collect()
+}
+
+/// Write an error message for the app to free with `sqlite3_free`.
+fn write_errmsg(env: &mut Environment, errmsg: MutPtr<MutPtr<u8>>, message: Option<CString>) {
+    if errmsg.is_null() {
+        return;
+    }
+    let message = match message {
+        Some(message) => env.mem.alloc_and_write_cstr(message.as_bytes()),
+        None => Ptr::null(),
+    };
+    env.mem.write(errmsg, message);
+}
+
+fn sqlite3_exec(
+    env: &mut Environment,
+    db: MutPtr<sqlite3>,
+    sql: ConstPtr<u8>,
+    callback: GuestFunction, // int (*)(void*, int, char**, char**)
+    arg: MutVoidPtr,
+    errmsg: MutPtr<MutPtr<u8>>,
+) -> i32 {
+    let Some(raw_db) = lookup_database(env, db).map(|database| database.raw) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    let Some(sql) = guest_cstring(env, sql) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    log_dbg!("sqlite3_exec({:?}, {:?})", db, sql);
+    let (result, message) = run_sql(env, raw_db, &sql, |env, names, values| {
+        if callback.addr_with_thumb_bit() == 0 {
+            return true;
+        }
+        let column_count = names.len() as GuestUSize;
+        let mut strings = alloc_row(env, names);
+        strings.extend(alloc_row(env, values));
+        let array: MutPtr<MutPtr<u8>> = env.mem.alloc((column_count * 2 * 4).max(1)).cast();
+        for (i, &string) in strings.iter().enumerate() {
+            env.mem.write(array + i as GuestUSize, string);
+        }
+        let abort: i32 =
+            callback.call_from_host(env, (arg, column_count as i32, array + column_count, array));
+        for string in strings {
+            if !string.is_null() {
+                env.mem.free(string.cast());
+            }
+        }
+        env.mem.free(array.cast());
+        abort == 0
+    });
+    write_errmsg(env, errmsg, message);
+    result
+}
+
+fn sqlite3_get_table(
+    env: &mut Environment,
+    db: MutPtr<sqlite3>,
+    sql: ConstPtr<u8>,
+    result_table: MutPtr<MutPtr<MutPtr<u8>>>,
+    row_count: MutPtr<i32>,
+    column_count: MutPtr<i32>,
+    errmsg: MutPtr<MutPtr<u8>>,
+) -> i32 {
+    let Some(raw_db) = lookup_database(env, db).map(|database| database.raw) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    let Some(sql) = guest_cstring(env, sql) else {
+        return ffi::SQLITE_MISUSE;
+    };
+    // The column names come first, then the values of each row.
+    let mut header = None;
+    let mut rows = Vec::new();
+    let (result, message) = run_sql(env, raw_db, &sql, |_env, names, values| {
+        header.get_or_insert_with(|| names.to_vec());
+        rows.push(values.to_vec());
+        true
+    });
+    write_errmsg(env, errmsg, message);
+    if result != ffi::SQLITE_OK {
+        return result;
+    }
+
+    let header = header.unwrap_or_default();
+    let mut strings = alloc_row(env, &header);
+    for row in &rows {
+        strings.extend(alloc_row(env, row));
+    }
+    let table: MutPtr<MutPtr<u8>> = env
+        .mem
+        .alloc((strings.len().max(1) * 4) as GuestUSize)
+        .cast();
+    for (i, &string) in strings.iter().enumerate() {
+        env.mem.write(table + i as GuestUSize, string);
+    }
+    env.sqlite3_state.tables.insert(table, strings);
+    env.mem.write(result_table, table);
+    if !row_count.is_null() {
+        env.mem.write(row_count, rows.len() as i32);
+    }
+    if !column_count.is_null() {
+        env.mem.write(column_count, header.len() as i32);
+    }
+    result
+}
+
+fn sqlite3_free_table(env: &mut Environment, table: MutPtr<MutPtr<u8>>) {
+    if table.is_null() {
+        return;
+    }
+    let Some(strings) = env.sqlite3_state.tables.remove(&table) else {
+        log!("Warning: sqlite3_free_table() on unknown table {:?}", table);
+        return;
+    };
+    for string in strings {
+        if !string.is_null() {
+            env.mem.free(string.cast());
+        }
+    }
+    env.mem.free(table.cast());
+}
+
+fn sqlite3_malloc(env: &mut Environment, size: i32) -> MutVoidPtr {
+    if size <= 0 {
+        return Ptr::null();
+    }
+    env.mem.alloc(size as GuestUSize)
+}
+
+fn sqlite3_free(env: &mut Environment, ptr: MutVoidPtr) {
+    if !ptr.is_null() {
+        env.mem.free(ptr);
+    }
+}
+
+fn sqlite3_libversion(env: &mut Environment) -> ConstPtr<u8> {
+    static_str(
+        env,
+        unsafe { host_cstr(ffi::sqlite3_libversion()) }.unwrap(),
+    )
+}
+
+fn sqlite3_libversion_number(_env: &mut Environment) -> i32 {
+    unsafe { ffi::sqlite3_libversion_number() }
+}
+
+fn sqlite3_threadsafe(_env: &mut Environment) -> i32 {
+    unsafe { ffi::sqlite3_threadsafe() }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(sqlite3_open(_, _)),
+    export_c_func!(sqlite3_open_v2(_, _, _, _)),
+    export_c_func!(sqlite3_close(_)),
+    export_c_func!(sqlite3_close_v2(_)),
+    export_c_func!(sqlite3_errmsg(_)),
+    export_c_func!(sqlite3_errcode(_)),
+    export_c_func!(sqlite3_extended_errcode(_)),
+    export_c_func!(sqlite3_busy_timeout(_, _)),
+    export_c_func!(sqlite3_last_insert_rowid(_)),
+    export_c_func!(sqlite3_changes(_)),
+    export_c_func!(sqlite3_total_changes(_)),
+    export_c_func!(sqlite3_prepare(_, _, _, _, _)),
+    export_c_func!(sqlite3_prepare_v2(_, _, _, _, _)),
+    export_c_func!(sqlite3_step(_)),
+    export_c_func!(sqlite3_reset(_)),
+    export_c_func!(sqlite3_finalize(_)),
+    export_c_func!(sqlite3_clear_bindings(_)),
+    export_c_func!(sqlite3_db_handle(_)),
+    export_c_func!(sqlite3_bind_parameter_count(_)),
+    export_c_func!(sqlite3_bind_parameter_index(_, _)),
+    export_c_func!(sqlite3_bind_null(_, _)),
+    export_c_func!(sqlite3_bind_int(_, _, _)),
+    export_c_func!(sqlite3_bind_int64(_, _, _)),
+    export_c_func!(sqlite3_bind_double(_, _, _)),
+    export_c_func!(sqlite3_bind_text(_, _, _, _, _)),
+    export_c_func!(sqlite3_bind_blob(_, _, _, _, _)),
+    export_c_func!(sqlite3_column_count(_)),
+    export_c_func!(sqlite3_data_count(_)),
+    export_c_func!(sqlite3_column_name(_, _)),
+    export_c_func!(sqlite3_column_decltype(_, _)),
+    export_c_func!(sqlite3_column_type(_, _)),
+    export_c_func!(sqlite3_column_int(_, _)),
+    export_c_func!(sqlite3_column_int64(_, _)),
+    export_c_func!(sqlite3_column_double(_, _)),
+    export_c_func!(sqlite3_column_bytes(_, _)),
+    export_c_func!(sqlite3_column_text(_, _)),
+    export_c_func!(sqlite3_column_blob(_, _)),
+    export_c_func!(sqlite3_exec(_, _, _, _, _)),
+    export_c_func!(sqlite3_get_table(_, _, _, _, _, _)),
+    export_c_func!(sqlite3_free_table(_)),
+    export_c_func!(sqlite3_malloc(_)),
+    export_c_func!(sqlite3_free(_)),
+    export_c_func!(sqlite3_libversion()),
+    export_c_func!(sqlite3_libversion_number()),
+    export_c_func!(sqlite3_threadsafe()),
+];
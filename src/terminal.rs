@@ -0,0 +1,53 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Reading lines typed into the terminal touchHLE was started from.
+//!
+//! Reading from the terminal blocks, so the memory tool (`--memory-tool`)
+//! needs it to be done on another thread. That thread can't be stopped while
+//! it's waiting for input, so there's only ever one, which lives as long as
+//! touchHLE, and everything else that reads from the terminal (the launcher,
+//! breakpoints and watchpoints) goes through it too. Otherwise the thread
+//! would still be holding the lock on stdin after the app exits, and would
+//! take the next line typed into the launcher.
+
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Mutex;
+
+static LINES: Mutex<Option<Receiver<String>>> = Mutex::new(None);
+
+fn with_lines<T>(f: impl FnOnce(&Receiver<String>) -> T) -> T {
+    let mut lines = LINES.lock().unwrap();
+    f(lines.get_or_insert_with(|| {
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }))
+}
+
+/// Start reading from the terminal, if that hasn't been done already, so that
+/// lines typed from now on are returned by [read_lines].
+pub fn start_reading() {
+    with_lines(|_| ());
+}
+
+/// Wait for a line to be typed. Returns [None] at the end of input.
+pub fn read_line() -> Option<String> {
+    with_lines(|lines| lines.recv().ok())
+}
+
+/// Get the lines that have been typed since the last call, without waiting.
+pub fn read_lines() -> Vec<String> {
+    with_lines(|lines| lines.try_iter().collect())
+}
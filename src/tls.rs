@@ -0,0 +1,242 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! TLS client connections, for HTTPS requests and Secure Transport.
+//!
+//! Implemented as a wrapper around the host's TLS library (via the native-tls
+//! crate), so that certificates are checked against the host's trusted roots.
+//!
+//! Many servers iPhone OS apps talked to are long gone. Sometimes a hostname
+//! is redirected to a replacement server (e.g. with the hosts file), but that
+//! server can't have a valid certificate for the old name, or the app expects
+//! protocol versions the host no longer allows by default. The
+//! `--relaxed-tls` option turns off those checks.
+
+use native_tls::{HandshakeError, MidHandshakeTlsStream, Protocol, TlsConnector, TlsStream};
+use std::cell::RefCell;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::rc::Rc;
+
+/// How a TLS connection's server is checked.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// The name the server's certificate must be for. [None] means it isn't
+    /// checked. This is also sent to the server (SNI).
+    pub peer_name: Option<String>,
+    /// Whether the server's certificate must be valid and from a trusted root.
+    pub validate_certificate_chain: bool,
+    /// Whether protocol versions older than TLS 1.0, i.e. SSL 3.0, are
+    /// allowed, if the host's TLS library supports them at all.
+    pub allow_legacy_protocols: bool,
+}
+impl Settings {
+    /// The defaults for a connection to `host`, which are all relaxed if
+    /// `relaxed` is set (see `--relaxed-tls`).
+    pub fn new(host: &str, relaxed: bool) -> Settings {
+        Settings {
+            peer_name: if relaxed {
+                None
+            } else {
+                Some(host.to_string())
+            },
+            validate_certificate_chain: !relaxed,
+            allow_legacy_protocols: relaxed,
+        }
+    }
+
+    /// `domain` is the name sent to the server (SNI), if any.
+    fn connector(&self, domain: &str) -> Result<TlsConnector, String> {
+        let mut builder = TlsConnector::builder();
+        builder
+            .danger_accept_invalid_certs(!self.validate_certificate_chain)
+            .danger_accept_invalid_hostnames(self.peer_name.is_none())
+            .use_sni(!domain.is_empty())
+            .min_protocol_version(if self.allow_legacy_protocols {
+                None
+            } else {
+                Some(Protocol::Tlsv10)
+            });
+        builder
+            .build()
+            .map_err(|e| format!("couldn't set up TLS: {}", e))
+    }
+}
+
+/// A TLS connection over a socket.
+pub type Stream = TlsStream<TcpStream>;
+
+/// Make a TLS connection over a connected socket. This blocks until the
+/// handshake is done, and `host` is used if there's no peer name.
+pub fn connect(host: &str, socket: TcpStream, settings: &Settings) -> Result<Stream, String> {
+    let domain = settings.peer_name.as_deref().unwrap_or(host);
+    let connector = settings.connector(domain)?;
+    connector
+        .connect(domain, socket)
+        .map_err(|e| format!("TLS handshake with {:?} failed: {}", host, e))
+}
+
+/// The encrypted side of a [Session], shared with the TLS library.
+#[derive(Default)]
+struct Pipe {
+    /// Bytes received from the server that the TLS library hasn't taken yet.
+    incoming: Vec<u8>,
+    /// Bytes from the TLS library that haven't been sent to the server yet.
+    outgoing: Vec<u8>,
+    /// Whether the server has closed the connection.
+    closed: bool,
+}
+
+struct PipeHandle(Rc<RefCell<Pipe>>);
+impl Read for PipeHandle {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut pipe = self.0.borrow_mut();
+        if pipe.incoming.is_empty() {
+            return if pipe.closed {
+                Ok(0)
+            } else {
+                Err(ErrorKind::WouldBlock.into())
+            };
+        }
+        let count = buf.len().min(pipe.incoming.len());
+        buf[..count].copy_from_slice(&pipe.incoming[..count]);
+        pipe.incoming.drain(..count);
+        Ok(count)
+    }
+}
+impl Write for PipeHandle {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().outgoing.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+enum SessionState {
+    NotStarted(Settings),
+    Handshaking(MidHandshakeTlsStream<PipeHandle>),
+    Connected(TlsStream<PipeHandle>),
+    Closed,
+    /// Taken out temporarily while the state changes.
+    Invalid,
+}
+
+/// A TLS connection where moving the encrypted bytes to and from the server is
+/// up to the caller, e.g. for Secure Transport, where the app does it. Nothing
+/// here blocks: whenever more bytes are needed from the server, an operation
+/// fails with [ErrorKind::WouldBlock] and should be retried once they've been
+/// given to [Session::receive].
+pub struct Session {
+    host: String,
+    state: SessionState,
+    pipe: Rc<RefCell<Pipe>>,
+}
+impl Session {
+    pub fn new(host: &str, settings: Settings) -> Session {
+        Session {
+            host: host.to_string(),
+            state: SessionState::NotStarted(settings),
+            pipe: Default::default(),
+        }
+    }
+
+    /// Give the session bytes received from the server. An empty slice means
+    /// the server closed the connection.
+    pub fn receive(&mut self, bytes: &[u8]) {
+        let mut pipe = self.pipe.borrow_mut();
+        if bytes.is_empty() {
+            pipe.closed = true;
+        }
+        pipe.incoming.extend_from_slice(bytes);
+    }
+
+    /// Bytes that should be sent to the server. Anything that can't be sent
+    /// yet should be given back with [Session::unsend].
+    pub fn take_outgoing(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.pipe.borrow_mut().outgoing)
+    }
+
+    /// Put back bytes from [Session::take_outgoing] that couldn't be sent.
+    pub fn unsend(&mut self, bytes: Vec<u8>) {
+        let mut pipe = self.pipe.borrow_mut();
+        let rest = std::mem::replace(&mut pipe.outgoing, bytes);
+        pipe.outgoing.extend(rest);
+    }
+
+    /// Continue the handshake. Returns [Ok] once it's done.
+    pub fn handshake(&mut self) -> std::io::Result<()> {
+        let result = match std::mem::replace(&mut self.state, SessionState::Invalid) {
+            SessionState::NotStarted(settings) => {
+                let domain = settings.peer_name.as_deref().unwrap_or(&self.host);
+                let connector = settings.connector(domain).map_err(|e| {
+                    self.state = SessionState::Closed;
+                    std::io::Error::other(e)
+                })?;
+                connector.connect(domain, PipeHandle(self.pipe.clone()))
+            }
+            SessionState::Handshaking(stream) => stream.handshake(),
+            state @ SessionState::Connected(_) => {
+                self.state = state;
+                return Ok(());
+            }
+            SessionState::Closed | SessionState::Invalid => {
+                self.state = SessionState::Closed;
+                return Err(ErrorKind::NotConnected.into());
+            }
+        };
+        match result {
+            Ok(stream) => {
+                self.state = SessionState::Connected(stream);
+                Ok(())
+            }
+            Err(HandshakeError::WouldBlock(stream)) => {
+                self.state = SessionState::Handshaking(stream);
+                Err(ErrorKind::WouldBlock.into())
+            }
+            Err(HandshakeError::Failure(e)) => {
+                self.state = SessionState::Closed;
+                Err(std::io::Error::other(format!(
+                    "TLS handshake with {:?} failed: {}",
+                    self.host, e
+                )))
+            }
+        }
+    }
+
+    /// Read decrypted bytes. Returns 0 if the server closed the connection.
+    pub fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.state {
+            SessionState::Connected(ref mut stream) => stream.read(buf),
+            _ => Err(ErrorKind::NotConnected.into()),
+        }
+    }
+
+    /// How many decrypted bytes can be read without receiving any more.
+    pub fn buffered_read_size(&self) -> usize {
+        match self.state {
+            SessionState::Connected(ref stream) => stream.buffered_read_size().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    /// Encrypt bytes to send. They're all accepted at once.
+    pub fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.state {
+            SessionState::Connected(ref mut stream) => stream.write(buf),
+            _ => Err(ErrorKind::NotConnected.into()),
+        }
+    }
+
+    /// Tell the server the connection is closing. The alert still has to be
+    /// sent, see [Session::take_outgoing].
+    pub fn close(&mut self) {
+        if let SessionState::Connected(ref mut stream) = self.state {
+            let _ = stream.shutdown();
+        }
+        self.state = SessionState::Closed;
+    }
+}
@@ -13,42 +13,284 @@
 //! window system interaction in general, because it is assumed only one window
 //! will be needed for the runtime of the app.
 
+mod controller_map;
+mod filter;
+mod geometry;
 mod gl;
+mod haptics;
+mod hotkeys;
+mod keyboard_map;
 mod matrix;
+mod menu;
+mod virtual_controls;
 
-pub use gl::{gl21compat, gl32core, gles11, GLContext, GLVersion};
+pub use controller_map::ControllerMap;
+pub use filter::{DisplayFilter, FilterStage};
+pub use gl::{gl21compat, gl32core, gles11, gles20, read_texture_pixels, GLContext, GLVersion};
+pub use haptics::{Vibration, VibrationShape};
+pub use hotkeys::Hotkeys;
+pub use keyboard_map::KeyboardMap;
 pub use matrix::Matrix;
+pub use menu::{Menu, MenuChoice, MenuInput, MenuSettings};
+pub use virtual_controls::VirtualControls;
 
+use crate::font::Font;
 use crate::image::Image;
 use crate::Options;
+use controller_map::{Bindings, ButtonAction, Input, Stick, StickAction};
+use geometry::WindowGeometry;
+use hotkeys::Action;
+use sdl2::controller::{Button, GameController};
+use sdl2::event::WindowEvent;
+use sdl2::keyboard::{Keycode, Mod, Scancode};
 use sdl2::mouse::MouseButton;
 use sdl2::pixels::PixelFormatEnum;
+use sdl2::sensor::SensorType;
 use sdl2::surface::Surface;
+use sdl2::video::FullscreenType;
 use std::collections::VecDeque;
-use std::f32::consts::FRAC_PI_2;
+use std::f32::consts::{FRAC_PI_2, PI};
 use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+/// The orientation of the emulated device, named like `UIDeviceOrientation`:
+/// `LandscapeLeft` means the device has been rotated counterclockwise, so the
+/// home button is on the right.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum DeviceOrientation {
+    #[default]
     Portrait,
+    PortraitUpsideDown,
     LandscapeLeft,
+    LandscapeRight,
+}
+impl DeviceOrientation {
+    /// The orientation after rotating the device by 90° in either direction.
+    pub fn rotated(self, clockwise: bool) -> DeviceOrientation {
+        use DeviceOrientation::*;
+        match (self, clockwise) {
+            (Portrait, true) | (PortraitUpsideDown, false) => LandscapeRight,
+            (LandscapeRight, true) | (LandscapeLeft, false) => PortraitUpsideDown,
+            (PortraitUpsideDown, true) | (Portrait, false) => LandscapeLeft,
+            (LandscapeLeft, true) | (LandscapeRight, false) => Portrait,
+        }
+    }
 }
 fn size_for_orientation(orientation: DeviceOrientation, scale_hack: NonZeroU32) -> (u32, u32) {
     let scale_hack = scale_hack.get();
     match orientation {
-        DeviceOrientation::Portrait => (320 * scale_hack, 480 * scale_hack),
-        DeviceOrientation::LandscapeLeft => (480 * scale_hack, 320 * scale_hack),
+        DeviceOrientation::Portrait | DeviceOrientation::PortraitUpsideDown => {
+            (320 * scale_hack, 480 * scale_hack)
+        }
+        DeviceOrientation::LandscapeLeft | DeviceOrientation::LandscapeRight => {
+            (480 * scale_hack, 320 * scale_hack)
+        }
     }
 }
 
+/// Fit a rectangle of size `inner` into one of size `outer`, as large as
+/// possible while keeping its aspect ratio, and centered. Returns the offset
+/// and the scale factor.
+fn letterbox(outer: (u32, u32), inner: (u32, u32)) -> ((f32, f32), f32) {
+    let (outer_w, outer_h) = (outer.0 as f32, outer.1 as f32);
+    let (inner_w, inner_h) = (inner.0 as f32, inner.1 as f32);
+    let scale = (outer_w / inner_w).min(outer_h / inner_h);
+    let offset = (
+        (outer_w - inner_w * scale) / 2.0,
+        (outer_h - inner_h * scale) / 2.0,
+    );
+    (offset, scale)
+}
+
 #[derive(Debug)]
 pub enum Event {
     Quit,
-    TouchDown((f32, f32)),
-    TouchMove((f32, f32)),
-    TouchUp((f32, f32)),
+    TouchDown(TouchSource, (f32, f32)),
+    TouchMove(TouchSource, (f32, f32)),
+    TouchUp(TouchSource, (f32, f32)),
+    /// Text typed while text input is active (see [Window::start_text_input]).
+    /// Text composed with an input method only arrives once it's committed.
+    TextInput(String),
+    /// A key with a special meaning for text editing was pressed while text
+    /// input is active.
+    EditingKey(EditingKey),
+    /// A game controller button for navigating UI drawn by the host, like the
+    /// on-screen keyboard, was pressed.
+    NavigationButton(NavigationButton),
+    /// The user asked for the device to be rotated by 90° (Ctrl+Left or
+    /// Ctrl+Right by default).
+    RotateDevice {
+        clockwise: bool,
+    },
+    /// The user asked for the device to be shaken (Ctrl+Z by default, like
+    /// shaking to undo).
+    Shake,
+    /// The user asked for an audio interruption, like an incoming phone call,
+    /// to begin or end (Ctrl+I by default).
+    ToggleAudioInterruption,
+    /// The user asked for the app to be paused or resumed (Ctrl+P by
+    /// default).
+    TogglePause,
+    /// The user asked for fast-forwarding to start or stop (Ctrl+Tab by
+    /// default, see [crate::clock]).
+    ToggleFastForward,
+    /// The user opened the options menu (Ctrl+M by default, see
+    /// [menu]). Until [Window::close_menu] is called, input only produces
+    /// [Event::MenuInput].
+    OpenMenu,
+    MenuInput(MenuInput),
+    /// A key was pressed or released while text input isn't active.
+    HardwareKey(HardwareKey),
+}
+
+/// A key on the host's keyboard, for apps that support external keyboards.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HardwareKey {
+    pub down: bool,
+    pub repeat: bool,
+    /// USB HID usage ID, which is what SDL's scancodes are.
+    pub usage: u16,
+    /// What the key types without modifiers, if anything.
+    pub character: Option<char>,
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub command: bool,
+    pub caps_lock: bool,
+}
+impl HardwareKey {
+    fn new(
+        down: bool,
+        repeat: bool,
+        scancode: Scancode,
+        keycode: Option<Keycode>,
+        keymod: Mod,
+    ) -> Self {
+        let character = keycode
+            .and_then(|keycode| char::from_u32(keycode as u32))
+            .filter(|c| !c.is_control());
+        HardwareKey {
+            down,
+            repeat,
+            usage: scancode as u16,
+            character,
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            control: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+            command: keymod.intersects(Mod::LGUIMOD | Mod::RGUIMOD),
+            caps_lock: keymod.contains(Mod::CAPSMOD),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EditingKey {
+    Backspace,
+    Delete,
+    Return,
+    Left,
+    Right,
+    Home,
+    End,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NavigationButton {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// The A button (or equivalent).
+    Select,
+    /// The B button (or equivalent).
+    Back,
+}
+
+/// Where simulated accelerometer input comes from. See `--tilt-source=`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TiltSource {
+    /// A game controller's motion sensor if one is connected, otherwise the
+    /// analog stick and the arrow keys.
+    Auto,
+    /// A game controller's motion sensor.
+    Sensor,
+    /// The analog sticks and buttons of game controllers that are bound to
+    /// tilt, by default the left stick.
+    Stick,
+    /// The arrow keys of the keyboard.
+    Keys,
+}
+impl TiltSource {
+    pub fn parse(value: &str) -> Result<TiltSource, String> {
+        match value {
+            "auto" => Ok(TiltSource::Auto),
+            "sensor" => Ok(TiltSource::Sensor),
+            "stick" => Ok(TiltSource::Stick),
+            "keys" => Ok(TiltSource::Keys),
+            _ => Err(format!("Unknown tilt source {:?}", value)),
+        }
+    }
+
+    pub const ALL: [TiltSource; 4] = [
+        TiltSource::Auto,
+        TiltSource::Sensor,
+        TiltSource::Stick,
+        TiltSource::Keys,
+    ];
+
+    /// The name used by `--tilt-source=`.
+    pub fn name(self) -> &'static str {
+        match self {
+            TiltSource::Auto => "auto",
+            TiltSource::Sensor => "sensor",
+            TiltSource::Stick => "stick",
+            TiltSource::Keys => "keys",
+        }
+    }
+}
+
+/// The input device a touch comes from. Each source can have one touch in
+/// progress at a time, so simultaneous touches from different sources are
+/// independent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TouchSource {
+    Mouse,
+    /// The second touch of a mouse gesture (see [MouseGesture]).
+    MouseGesture,
+    /// A game controller's analog stick-controlled virtual cursor, by player
+    /// (see [Controller::player]).
+    VirtualCursor(usize),
+    /// A game controller button bound to a touch, by player and index in
+    /// [Bindings::buttons].
+    ControllerButton(usize, usize),
+    /// A game controller analog stick bound to a joystick touch, by player.
+    ControllerStick(usize, Stick),
+    /// Keys bound to a region of the screen, by its index in the app's
+    /// keyboard mapping.
+    KeyboardRegion(usize),
+    /// An on-screen virtual control, by its index in the app's layout.
+    VirtualControl(usize),
+    /// A finger on a real touchscreen, identified by SDL's finger ID.
+    Finger(i64),
 }
 
+/// The state of the host's power supply, see [Window::battery_info].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BatteryState {
+    Unknown,
+    /// Running on the battery.
+    Unplugged,
+    /// Plugged in and charging.
+    Charging,
+    /// Plugged in and either fully charged or without a battery.
+    Full,
+}
+
+/// SDL's `SDL_TOUCH_MOUSEID`: the mouse ID of mouse events that SDL generates
+/// from touchscreen events. Those touches are handled directly instead.
+const SDL_TOUCH_MOUSEID: u32 = u32::MAX;
+
 fn surface_from_image(image: &Image) -> Surface {
     let src_pixels = image.pixels();
     let (width, height) = image.dimensions();
@@ -70,6 +312,131 @@ fn surface_from_image(image: &Image) -> Surface {
     surface
 }
 
+/// Save pixels (RGB8, top row first) to a BMP file.
+pub fn save_bmp(path: &Path, pixels: &[u8], (width, height): (u32, u32)) -> Result<(), String> {
+    let mut surface = Surface::new(width, height, PixelFormatEnum::RGB24).unwrap();
+    let pitch = surface.pitch() as usize;
+    let row_size = width as usize * 3;
+    surface.with_lock_mut(|dest| {
+        for (y, row) in pixels.chunks_exact(row_size).enumerate() {
+            dest[y * pitch..][..row_size].copy_from_slice(row);
+        }
+    });
+    surface.save_bmp(path)
+}
+
+/// What the second touch of a mouse gesture does, if there is one. This is
+/// decided by the modifier keys held when the left mouse button is pressed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum MouseGesture {
+    None,
+    /// Mirrored around the pinch anchor (Ctrl held), for pinching and
+    /// rotating.
+    Pinch,
+    /// A fixed distance from the mouse touch (Shift held), for two-finger
+    /// dragging. The value is the offset in window co-ordinates.
+    TwoFinger(f32, f32),
+}
+
+/// A pinch gesture made with the mouse wheel, which lasts until the wheel has
+/// been still for [WHEEL_PINCH_TIMEOUT].
+#[derive(Debug, Copy, Clone)]
+struct WheelPinch {
+    /// In window co-ordinates.
+    center: (f32, f32),
+    /// The horizontal distance of each touch from the center.
+    spread: f32,
+    last_scroll: Instant,
+}
+
+/// What is pressing an on-screen virtual control.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ControlPointer {
+    Mouse,
+    /// A finger on a real touchscreen, identified by SDL's finger ID.
+    Finger(i64),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum TouchPhase {
+    Down,
+    Move,
+    Up,
+}
+
+const WHEEL_PINCH_TIMEOUT: Duration = Duration::from_millis(250);
+/// How long the window must stay still before its geometry is saved, so that
+/// the file isn't written constantly while it's being dragged.
+const GEOMETRY_SAVE_DELAY: Duration = Duration::from_millis(500);
+/// In points, like the rest of these distances.
+const WHEEL_PINCH_START_SPREAD: f32 = 40.0;
+/// How much each notch of the mouse wheel changes the spread by.
+const WHEEL_PINCH_STEP: f32 = 8.0;
+const TWO_FINGER_DISTANCE: f32 = 40.0;
+
+/// A connected game controller.
+struct Controller {
+    controller: GameController,
+    /// Counting from 0, in the order controllers were connected. The number
+    /// of a disconnected controller is given to the next one connected.
+    player: usize,
+    bindings: Bindings,
+    /// Which of [Bindings::buttons] are held.
+    held: Vec<bool>,
+    /// On-screen position, press state and visibility of the analog
+    /// stick-controlled virtual cursor.
+    cursor_last: Option<(f32, f32, bool, bool)>,
+    /// For each stick bound to a joystick, where its touch is, if there is
+    /// one.
+    joystick_touches: [Option<(f32, f32)>; 2],
+}
+
+/// Get the X and Y positions of an analog stick, with each axis in the range
+/// [-1, 1].
+fn stick_position(controller: &GameController, stick: Stick, deadzone: f32) -> (f32, f32) {
+    fn convert_axis(axis: i16, deadzone: f32) -> f32 {
+        assert!(deadzone >= 0.0);
+        let axis = ((axis as f32) / (i16::MAX as f32)).clamp(-1.0, 1.0);
+        let abs_axis = (axis.abs().max(deadzone) - deadzone) / (1.0 - deadzone);
+        abs_axis.copysign(axis)
+    }
+
+    use sdl2::controller::Axis;
+    let (x_axis, y_axis) = match stick {
+        Stick::Left => (Axis::LeftX, Axis::LeftY),
+        Stick::Right => (Axis::RightX, Axis::RightY),
+    };
+    (
+        convert_axis(controller.axis(x_axis), deadzone),
+        convert_axis(controller.axis(y_axis), deadzone),
+    )
+}
+
+/// Whether a button is held. The buttons for navigating UI drawn by the host
+/// are ignored while text input is active, since they're used for the
+/// on-screen keyboard then.
+fn input_held(controller: &GameController, input: Input, text_input_active: bool) -> bool {
+    match input {
+        Input::Button(button) => {
+            !(text_input_active && navigation_button(button).is_some()) && controller.button(button)
+        }
+        Input::Trigger(axis) => controller.axis(axis) > i16::MAX / 2,
+    }
+}
+
+fn navigation_button(button: sdl2::controller::Button) -> Option<NavigationButton> {
+    use sdl2::controller::Button;
+    match button {
+        Button::DPadUp => Some(NavigationButton::Up),
+        Button::DPadDown => Some(NavigationButton::Down),
+        Button::DPadLeft => Some(NavigationButton::Left),
+        Button::DPadRight => Some(NavigationButton::Right),
+        Button::A => Some(NavigationButton::Select),
+        Button::B => Some(NavigationButton::Back),
+        _ => None,
+    }
+}
+
 pub struct Window {
     _sdl_ctx: sdl2::Sdl,
     video_ctx: sdl2::VideoSubsystem,
@@ -85,11 +452,58 @@ pub struct Window {
     device_orientation: DeviceOrientation,
     app_gl_ctx_no_longer_current: bool,
     controller_ctx: sdl2::GameControllerSubsystem,
-    controllers: Vec<sdl2::controller::GameController>,
-    virtual_cursor_last: Option<(f32, f32, bool, bool)>,
+    controllers: Vec<Controller>,
+    controller_map: ControllerMap,
+    keyboard_map: KeyboardMap,
+    /// Where each region of the keyboard mapping is being touched, if it is.
+    keyboard_touches: Vec<Option<(f32, f32)>>,
+    /// What the current mouse touch's second touch does.
+    mouse_gesture: MouseGesture,
+    /// The point pinches are mirrored around, as a fraction of the window's
+    /// width and height, so that it survives rotation.
+    pinch_anchor: (f32, f32),
+    wheel_pinch: Option<WheelPinch>,
+    virtual_controls: VirtualControls,
+    /// For each on-screen virtual control, what is pressing it and where, in
+    /// points, if it's pressed.
+    virtual_control_presses: Vec<Option<(ControlPointer, (f32, f32))>>,
+    /// Whether the virtual controls are shown and can be pressed, which can be
+    /// changed in the options menu.
+    virtual_controls_shown: bool,
+    hotkeys: Hotkeys,
+    /// Set by the screenshot hotkey, so that the next frame is saved.
+    screenshot_requested: bool,
+    geometry: WindowGeometry,
+    geometry_path: PathBuf,
+    /// When the window was last moved or resized, if its geometry hasn't
+    /// been saved since.
+    geometry_changed: Option<Instant>,
+    /// The vibration the game controllers are rumbling for, if any, with
+    /// when it began and the intensity last given to SDL.
+    rumble: Option<(Vibration, Instant, Option<u16>)>,
+    menu_open: bool,
+    /// What was on screen when the options menu was opened (RGB8, top row
+    /// first), and its size.
+    menu_background: Option<(Vec<u8>, (u32, u32))>,
+    /// The options menu as last drawn, so it can be redrawn if the window is
+    /// resized.
+    menu_drawn: Option<(Menu, MenuSettings)>,
+    /// The context and font the options menu is drawn with, kept once it has
+    /// been opened.
+    menu_gl_ctx_and_font: Option<(GLContext, Font)>,
 }
 impl Window {
-    pub fn new(title: &str, icon: Image, launch_image: Option<Image>, options: &Options) -> Window {
+    pub fn new(
+        title: &str,
+        icon: Image,
+        launch_image: Option<Image>,
+        controller_map: ControllerMap,
+        keyboard_map: KeyboardMap,
+        virtual_controls: VirtualControls,
+        hotkeys: Hotkeys,
+        geometry_path: PathBuf,
+        options: &Options,
+    ) -> Window {
         let sdl_ctx = sdl2::init().unwrap();
         let video_ctx = sdl_ctx.video().unwrap();
 
@@ -98,19 +512,79 @@ impl Window {
         // here, and then the app can disable it if it wants to.
         video_ctx.enable_screen_saver();
 
+        // SDL2 starts accepting text input by default, but there's nothing to
+        // type into until the app has a text field being edited.
+        video_ctx.text_input().stop();
+
         let scale_hack = options.scale_hack;
 
         // TODO: some apps specify their orientation in Info.plist, we could use
         // that here.
         let device_orientation = DeviceOrientation::Portrait;
 
-        let (width, height) = size_for_orientation(device_orientation, scale_hack);
-        let mut window = video_ctx
-            .window(title, width, height)
-            .position_centered()
-            .opengl()
-            .build()
-            .unwrap();
+        let saved_geometry = WindowGeometry::load(&geometry_path);
+        let (width, height) = saved_geometry.map_or_else(
+            || size_for_orientation(device_orientation, scale_hack),
+            |geometry| (geometry.width, geometry.height),
+        );
+
+        let display_count = video_ctx.num_video_displays().unwrap_or(1);
+        let display = match options.display {
+            Some(display) if display as i32 >= display_count => {
+                log!(
+                    "Warning: there is no display {} (there are {}), using display 0.",
+                    display,
+                    display_count
+                );
+                Some(0)
+            }
+            other => other,
+        };
+        // The saved position is only used if it's still on a display, and if
+        // no display was asked for.
+        let saved_position = saved_geometry
+            .filter(|geometry| {
+                let rect = sdl2::rect::Rect::new(geometry.x, geometry.y, width, height);
+                (0..display_count).any(|idx| {
+                    video_ctx
+                        .display_bounds(idx)
+                        .is_ok_and(|bounds| bounds.has_intersection(rect))
+                })
+            })
+            .map(|geometry| (geometry.x, geometry.y));
+        let (x, y) = match (display, saved_position) {
+            (None, Some(position)) => position,
+            (display, _) => {
+                let bounds = video_ctx
+                    .display_bounds(display.unwrap_or(0) as i32)
+                    .unwrap();
+                (
+                    bounds.x() + (bounds.width() as i32 - width as i32) / 2,
+                    bounds.y() + (bounds.height() as i32 - height as i32) / 2,
+                )
+            }
+        };
+
+        let mut window_builder = video_ctx.window(title, width, height);
+        window_builder.position(x, y).opengl().resizable();
+        if options.headless {
+            window_builder.hidden();
+        }
+        let mut window = window_builder.build().unwrap();
+
+        let fullscreen = options.fullscreen || saved_geometry.is_some_and(|g| g.fullscreen);
+        if fullscreen {
+            if let Err(e) = window.set_fullscreen(FullscreenType::Desktop) {
+                log!("Warning: couldn't make the window fullscreen: {}", e);
+            }
+        }
+        let geometry = WindowGeometry {
+            x,
+            y,
+            width,
+            height,
+            fullscreen,
+        };
 
         window.set_icon(surface_from_image(&icon));
 
@@ -123,7 +597,7 @@ impl Window {
             // contexts in this window, so let's use something relatively modern
             // and compatible. OpenGL 3.2 is the baseline version of OpenGL
             // available on macOS.
-            let gl_ctx = gl::create_gl_context(&video_ctx, &window, GLVersion::GL32Core);
+            let gl_ctx = gl::create_gl_context(&video_ctx, &window, GLVersion::GL32Core, None);
             Some((launch_image, gl_ctx))
         } else {
             None
@@ -147,7 +621,25 @@ impl Window {
             app_gl_ctx_no_longer_current: false,
             controller_ctx,
             controllers: Vec::new(),
-            virtual_cursor_last: None,
+            controller_map,
+            keyboard_touches: vec![None; keyboard_map.region_count()],
+            keyboard_map,
+            mouse_gesture: MouseGesture::None,
+            pinch_anchor: (0.5, 0.5),
+            wheel_pinch: None,
+            virtual_control_presses: vec![None; virtual_controls.count()],
+            virtual_controls,
+            virtual_controls_shown: true,
+            hotkeys,
+            screenshot_requested: false,
+            geometry,
+            geometry_path,
+            geometry_changed: None,
+            rumble: None,
+            menu_open: false,
+            menu_background: None,
+            menu_drawn: None,
+            menu_gl_ctx_and_font: None,
         };
         if window.splash_image_and_gl_ctx.is_some() {
             window.display_splash();
@@ -175,61 +667,686 @@ impl Window {
             (out_x, out_y)
         }
 
+        /// Get the position of the mouse on the emulated screen. In split
+        /// co-op mode, the mouse is confined to the left half of the screen.
+        fn mouse_coords(window: &Window, options: &Options, x: i32, y: i32) -> (f32, f32) {
+            let (x, y) = window.window_to_screen((x as f32, y as f32));
+            if options.split_coop {
+                let (width, _) = window.size_in_current_orientation();
+                (x.min(width as f32 / 2.0), y)
+            } else {
+                (x, y)
+            }
+        }
+
+        /// Queue a touch event for the mouse, and for the mirrored second touch
+        /// if the mouse is pinching.
+        fn push_mouse_touch(
+            window: &mut Window,
+            options: &Options,
+            (x, y): (i32, i32),
+            event: fn(TouchSource, (f32, f32)) -> Event,
+        ) {
+            let (x, y) = mouse_coords(window, options, x, y);
+            let coords = transform_input_coords(window, (x, y));
+            window
+                .event_queue
+                .push_back(event(TouchSource::Mouse, coords));
+            let (width, height) = window.size_in_current_orientation();
+            let (width, height) = (width as f32, height as f32);
+            let second = match window.mouse_gesture {
+                MouseGesture::None => return,
+                MouseGesture::Pinch => {
+                    let (anchor_x, anchor_y) = window.pinch_anchor;
+                    (anchor_x * width * 2.0 - x, anchor_y * height * 2.0 - y)
+                }
+                MouseGesture::TwoFinger(offset_x, offset_y) => (x + offset_x, y + offset_y),
+            };
+            let second = (second.0.clamp(0.0, width), second.1.clamp(0.0, height));
+            let coords = transform_input_coords(window, second);
+            window
+                .event_queue
+                .push_back(event(TouchSource::MouseGesture, coords));
+        }
+        /// Release the touches of a mouse wheel pinch.
+        fn end_wheel_pinch(window: &mut Window) {
+            let Some(WheelPinch { center, spread, .. }) = window.wheel_pinch.take() else {
+                return;
+            };
+            for (source, x) in [
+                (TouchSource::Mouse, center.0 - spread),
+                (TouchSource::MouseGesture, center.0 + spread),
+            ] {
+                let coords = transform_input_coords(window, (x, center.1));
+                window.event_queue.push_back(Event::TouchUp(source, coords));
+            }
+        }
+
+        /// Touchscreen co-ordinates are normalized to the range [0, 1].
+        fn finger_coords(window: &Window, x: f32, y: f32) -> (f32, f32) {
+            transform_input_coords(window, finger_screen_coords(window, x, y))
+        }
+        /// Get the position of a finger on the emulated screen, without
+        /// rotation. SDL gives it as a fraction of the window's size.
+        fn finger_screen_coords(window: &Window, x: f32, y: f32) -> (f32, f32) {
+            let (width, height) = window.window.size();
+            window.window_to_screen((x * width as f32, y * height as f32))
+        }
+
+        /// Let the on-screen virtual controls handle a press, movement or
+        /// release by the mouse or a finger at some window co-ordinates.
+        /// Returns [true] if a control used it, in which case the app
+        /// shouldn't see it as a touch of its own.
+        fn virtual_control_input(
+            window: &mut Window,
+            pointer: ControlPointer,
+            phase: TouchPhase,
+            (x, y): (f32, f32),
+        ) -> bool {
+            let scale = window.scale_hack.get() as f32;
+            let at = (x / scale, y / scale);
+            let held = window
+                .virtual_control_presses
+                .iter()
+                .position(|press| press.is_some_and(|(other, _)| other == pointer));
+            let idx = match (phase, held) {
+                (TouchPhase::Down, _) => {
+                    // Hidden controls can't be pressed, but any that are
+                    // pressed can still be moved and released.
+                    if !window.virtual_controls_shown {
+                        return false;
+                    }
+                    let Some(idx) = window.virtual_controls.hit(at) else {
+                        return false;
+                    };
+                    // A control can only be pressed by one thing at a time.
+                    if window.virtual_control_presses[idx].is_some() {
+                        return true;
+                    }
+                    idx
+                }
+                (_, Some(idx)) => idx,
+                (_, None) => return false,
+            };
+
+            let point_coords = |window: &Window, (x, y): (f32, f32)| {
+                transform_input_coords(window, (x * scale, y * scale))
+            };
+            let source = TouchSource::VirtualControl(idx);
+            let start = point_coords(window, window.virtual_controls.touch_start(idx));
+            let now = point_coords(window, window.virtual_controls.touch_at(idx, at));
+            let stick = window.virtual_controls.is_stick(idx);
+            match phase {
+                TouchPhase::Down => {
+                    window.virtual_control_presses[idx] = Some((pointer, at));
+                    window
+                        .event_queue
+                        .push_back(Event::TouchDown(source, start));
+                    if stick {
+                        window.event_queue.push_back(Event::TouchMove(source, now));
+                    }
+                }
+                TouchPhase::Move => {
+                    window.virtual_control_presses[idx] = Some((pointer, at));
+                    if stick {
+                        window.event_queue.push_back(Event::TouchMove(source, now));
+                    }
+                }
+                TouchPhase::Up => {
+                    window.virtual_control_presses[idx] = None;
+                    window.event_queue.push_back(Event::TouchUp(source, start));
+                }
+            }
+            true
+        }
+
+        /// Queue the touch and other events for whatever changed on a game
+        /// controller since last time. Accelerometer handling uses polling.
+        /// If the controller was disconnected, everything is released.
+        fn update_controller(
+            window: &mut Window,
+            options: &Options,
+            instance_id: u32,
+            disconnected: bool,
+        ) {
+            let Some(idx) = window
+                .controllers
+                .iter()
+                .position(|controller| controller.controller.instance_id() == instance_id)
+            else {
+                return;
+            };
+            let text_input_active = window.video_ctx.text_input().is_active();
+            let scale = window.scale_hack.get() as f32;
+            let point_coords = |window: &Window, (x, y): (f32, f32)| {
+                transform_input_coords(window, (x * scale, y * scale))
+            };
+
+            let controller = &window.controllers[idx];
+            let player = controller.player;
+            let mut events = Vec::new();
+
+            let mut held = controller.held.clone();
+            for (i, &(input, action)) in controller.bindings.buttons.iter().enumerate() {
+                let now_held =
+                    !disconnected && input_held(&controller.controller, input, text_input_active);
+                if now_held == held[i] {
+                    continue;
+                }
+                held[i] = now_held;
+                match action {
+                    ButtonAction::Touch(x, y) => {
+                        let source = TouchSource::ControllerButton(player, i);
+                        let coords = point_coords(window, (x, y));
+                        events.push(if now_held {
+                            Event::TouchDown(source, coords)
+                        } else {
+                            Event::TouchUp(source, coords)
+                        });
+                    }
+                    ButtonAction::Shake if now_held => events.push(Event::Shake),
+                    ButtonAction::RotateDevice { clockwise } if now_held => {
+                        events.push(Event::RotateDevice { clockwise })
+                    }
+                    ButtonAction::ToggleAudioInterruption if now_held => {
+                        events.push(Event::ToggleAudioInterruption)
+                    }
+                    // Cursor presses and tilting are polled.
+                    _ => (),
+                }
+            }
+
+            let mut joystick_touches = controller.joystick_touches;
+            for stick in [Stick::Left, Stick::Right] {
+                let StickAction::Joystick(center_x, center_y, radius) =
+                    controller.bindings.stick(stick)
+                else {
+                    continue;
+                };
+                let (x, y) = if disconnected {
+                    (0.0, 0.0)
+                } else {
+                    stick_position(&controller.controller, stick, options.deadzone)
+                };
+                // The stick's range is a square, but a joystick's is a circle.
+                let length = x.hypot(y).max(1.0);
+                let new = if (x, y) == (0.0, 0.0) {
+                    None
+                } else {
+                    Some((
+                        center_x + x / length * radius,
+                        center_y + y / length * radius,
+                    ))
+                };
+                let old = joystick_touches[stick as usize];
+                joystick_touches[stick as usize] = new;
+                let source = TouchSource::ControllerStick(player, stick);
+                let center = point_coords(window, (center_x, center_y));
+                match (old, new) {
+                    (None, Some(new)) => {
+                        events.push(Event::TouchDown(source, center));
+                        events.push(Event::TouchMove(source, point_coords(window, new)));
+                    }
+                    (Some(old), Some(new)) if old != new => {
+                        events.push(Event::TouchMove(source, point_coords(window, new)));
+                    }
+                    (Some(_), None) => events.push(Event::TouchUp(source, center)),
+                    _ => (),
+                }
+            }
+
+            let (new_x, new_y, new_pressed, visible) = if disconnected {
+                let (x, y, _, _) = controller.cursor_last.unwrap_or_default();
+                (x, y, false, false)
+            } else {
+                window.get_virtual_cursor(options, idx)
+            };
+            let (old_x, old_y, old_pressed, _) = controller.cursor_last.unwrap_or_default();
+            let source = TouchSource::VirtualCursor(player);
+            let coords = transform_input_coords(window, (new_x, new_y));
+            match (old_pressed, new_pressed) {
+                (false, true) => events.push(Event::TouchDown(source, coords)),
+                (true, false) => events.push(Event::TouchUp(source, coords)),
+                _ if (new_x, new_y) != (old_x, old_y) && new_pressed => {
+                    events.push(Event::TouchMove(source, coords))
+                }
+                _ => (),
+            }
+
+            let controller = &mut window.controllers[idx];
+            controller.held = held;
+            controller.joystick_touches = joystick_touches;
+            controller.cursor_last = Some((new_x, new_y, new_pressed, visible));
+            window.event_queue.extend(events);
+        }
+
+        /// Queue touches for whatever changed about the keys bound by the
+        /// keyboard mapping since last time. Keys don't touch anything while
+        /// text input is active or Ctrl is held, since they're for editing or
+        /// shortcuts then.
+        fn update_keyboard_touches(window: &mut Window) {
+            let keyboard = window.event_pump.keyboard_state();
+            let ignore_keys = window.video_ctx.text_input().is_active()
+                || keyboard.is_scancode_pressed(Scancode::LCtrl)
+                || keyboard.is_scancode_pressed(Scancode::RCtrl);
+            let touches = window
+                .keyboard_map
+                .touches(|scancode| !ignore_keys && keyboard.is_scancode_pressed(scancode));
+
+            let scale = window.scale_hack.get() as f32;
+            let point_coords = |window: &Window, (x, y): (f32, f32)| {
+                transform_input_coords(window, (x * scale, y * scale))
+            };
+            let mut events = Vec::new();
+            for (idx, (&old, &new)) in window.keyboard_touches.iter().zip(&touches).enumerate() {
+                let source = TouchSource::KeyboardRegion(idx);
+                // A joystick's touch starts and ends at its center.
+                let center = point_coords(window, window.keyboard_map.region_center(idx));
+                let joystick = window.keyboard_map.region_is_joystick(idx);
+                match (old, new) {
+                    (None, Some(new)) => {
+                        events.push(Event::TouchDown(source, center));
+                        if joystick {
+                            events.push(Event::TouchMove(source, point_coords(window, new)));
+                        }
+                    }
+                    (Some(old), Some(new)) if old != new => {
+                        events.push(Event::TouchMove(source, point_coords(window, new)));
+                    }
+                    (Some(_), None) => events.push(Event::TouchUp(source, center)),
+                    _ => (),
+                }
+            }
+            window.keyboard_touches = touches;
+            window.event_queue.extend(events);
+        }
+
         while let Some(event) = self.event_pump.poll_event() {
             use sdl2::event::Event as E;
             self.event_queue.push_back(match event {
                 E::Quit { .. } => Event::Quit,
-                // TODO: support for real touch inputs and multi-touch
+                E::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    ..
+                } if self.menu_open => Event::MenuInput(match keycode {
+                    _ if self.hotkeys.action(keycode, keymod) == Some(Action::OpenMenu) => {
+                        MenuInput::Back
+                    }
+                    Keycode::Up => MenuInput::Up,
+                    Keycode::Down => MenuInput::Down,
+                    Keycode::Left => MenuInput::Left,
+                    Keycode::Right => MenuInput::Right,
+                    Keycode::Return | Keycode::KpEnter | Keycode::Space => MenuInput::Select,
+                    Keycode::Escape | Keycode::Backspace => MenuInput::Back,
+                    _ => continue,
+                }),
+                E::ControllerButtonDown { button, .. } if self.menu_open => {
+                    Event::MenuInput(match button {
+                        Button::Start | Button::Guide => MenuInput::Back,
+                        _ => match navigation_button(button) {
+                            Some(NavigationButton::Up) => MenuInput::Up,
+                            Some(NavigationButton::Down) => MenuInput::Down,
+                            Some(NavigationButton::Left) => MenuInput::Left,
+                            Some(NavigationButton::Right) => MenuInput::Right,
+                            Some(NavigationButton::Select) => MenuInput::Select,
+                            Some(NavigationButton::Back) => MenuInput::Back,
+                            None => continue,
+                        },
+                    })
+                }
+                // The app doesn't get any other input while the menu is open.
+                _ if self.menu_open
+                    && !matches!(
+                        event,
+                        E::Window { .. }
+                            | E::ControllerDeviceAdded { .. }
+                            | E::ControllerDeviceRemoved { .. }
+                    ) =>
+                {
+                    continue
+                }
+                E::MouseButtonDown { which, .. }
+                | E::MouseButtonUp { which, .. }
+                | E::MouseMotion { which, .. }
+                | E::MouseWheel { which, .. }
+                    if which == SDL_TOUCH_MOUSEID =>
+                {
+                    continue
+                }
                 E::MouseButtonDown {
                     x,
                     y,
                     mouse_btn: MouseButton::Left,
                     ..
-                } => Event::TouchDown(transform_input_coords(self, (x as f32, y as f32))),
+                } => {
+                    end_wheel_pinch(self);
+                    let coords = mouse_coords(self, options, x, y);
+                    if virtual_control_input(self, ControlPointer::Mouse, TouchPhase::Down, coords)
+                    {
+                        continue;
+                    }
+                    let keyboard = self.event_pump.keyboard_state();
+                    self.mouse_gesture = if self.ctrl_held() {
+                        MouseGesture::Pinch
+                    } else if keyboard.is_scancode_pressed(Scancode::LShift)
+                        || keyboard.is_scancode_pressed(Scancode::RShift)
+                    {
+                        // Put the second finger to the right, unless there's
+                        // no room.
+                        let (width, _) = self.size_in_current_orientation();
+                        let offset = TWO_FINGER_DISTANCE * self.scale_hack.get() as f32;
+                        if x as f32 + offset <= width as f32 {
+                            MouseGesture::TwoFinger(offset, 0.0)
+                        } else {
+                            MouseGesture::TwoFinger(-offset, 0.0)
+                        }
+                    } else {
+                        MouseGesture::None
+                    };
+                    push_mouse_touch(self, options, (x, y), Event::TouchDown);
+                    continue;
+                }
+                E::MouseButtonDown {
+                    x,
+                    y,
+                    mouse_btn: MouseButton::Right,
+                    ..
+                } if self.ctrl_held() => {
+                    let (width, height) = self.size_in_current_orientation();
+                    let (x, y) = mouse_coords(self, options, x, y);
+                    self.pinch_anchor = (x / width as f32, y / height as f32);
+                    log!(
+                        "Pinch anchor set to {:.0}%, {:.0}% of the window.",
+                        self.pinch_anchor.0 * 100.0,
+                        self.pinch_anchor.1 * 100.0
+                    );
+                    continue;
+                }
+                E::MouseWheel { y, direction, .. } if y != 0 => {
+                    let mouse = self.event_pump.mouse_state();
+                    if mouse.left() {
+                        continue;
+                    }
+                    let y = if direction == sdl2::mouse::MouseWheelDirection::Flipped {
+                        -y
+                    } else {
+                        y
+                    };
+                    let scale = self.scale_hack.get() as f32;
+                    let (center, old_spread) = match self.wheel_pinch {
+                        Some(WheelPinch { center, spread, .. }) => (center, spread),
+                        None => {
+                            let center = mouse_coords(self, options, mouse.x(), mouse.y());
+                            let spread = WHEEL_PINCH_START_SPREAD * scale;
+                            for (source, x) in [
+                                (TouchSource::Mouse, center.0 - spread),
+                                (TouchSource::MouseGesture, center.0 + spread),
+                            ] {
+                                let coords = transform_input_coords(self, (x, center.1));
+                                self.event_queue.push_back(Event::TouchDown(source, coords));
+                            }
+                            (center, spread)
+                        }
+                    };
+                    // Scrolling up spreads the fingers, i.e. zooms in.
+                    let spread = (old_spread + y as f32 * WHEEL_PINCH_STEP * scale)
+                        .max(WHEEL_PINCH_STEP * scale);
+                    self.wheel_pinch = Some(WheelPinch {
+                        center,
+                        spread,
+                        last_scroll: Instant::now(),
+                    });
+                    for (source, x) in [
+                        (TouchSource::Mouse, center.0 - spread),
+                        (TouchSource::MouseGesture, center.0 + spread),
+                    ] {
+                        let coords = transform_input_coords(self, (x, center.1));
+                        self.event_queue.push_back(Event::TouchMove(source, coords));
+                    }
+                    continue;
+                }
                 E::MouseMotion {
                     x, y, mousestate, ..
                 } if mousestate.left() => {
-                    Event::TouchMove(transform_input_coords(self, (x as f32, y as f32)))
+                    let coords = mouse_coords(self, options, x, y);
+                    if !virtual_control_input(self, ControlPointer::Mouse, TouchPhase::Move, coords)
+                    {
+                        push_mouse_touch(self, options, (x, y), Event::TouchMove);
+                    }
+                    continue;
                 }
                 E::MouseButtonUp {
                     x,
                     y,
                     mouse_btn: MouseButton::Left,
                     ..
-                } => Event::TouchUp(transform_input_coords(self, (x as f32, y as f32))),
-                E::ControllerDeviceAdded { which, .. } => {
-                    self.controller_added(which);
+                } => {
+                    let coords = mouse_coords(self, options, x, y);
+                    if !virtual_control_input(self, ControlPointer::Mouse, TouchPhase::Up, coords) {
+                        push_mouse_touch(self, options, (x, y), Event::TouchUp);
+                    }
+                    self.mouse_gesture = MouseGesture::None;
                     continue;
                 }
-                E::ControllerDeviceRemoved { which, .. } => {
-                    self.controller_removed(which);
+                E::FingerDown {
+                    finger_id, x, y, ..
+                }
+                | E::FingerMotion {
+                    finger_id, x, y, ..
+                }
+                | E::FingerUp {
+                    finger_id, x, y, ..
+                } => {
+                    let (phase, event): (TouchPhase, fn(TouchSource, (f32, f32)) -> Event) =
+                        match event {
+                            E::FingerDown { .. } => (TouchPhase::Down, Event::TouchDown),
+                            E::FingerMotion { .. } => (TouchPhase::Move, Event::TouchMove),
+                            _ => (TouchPhase::Up, Event::TouchUp),
+                        };
+                    let screen_coords = finger_screen_coords(self, x, y);
+                    let pointer = ControlPointer::Finger(finger_id);
+                    if !virtual_control_input(self, pointer, phase, screen_coords) {
+                        let coords = finger_coords(self, x, y);
+                        self.event_queue
+                            .push_back(event(TouchSource::Finger(finger_id), coords));
+                    }
                     continue;
                 }
-                // Virtual cursor handling only. Accelerometer handling uses
-                // polling.
-                E::ControllerButtonUp { .. }
-                | E::ControllerButtonDown { .. }
-                | E::ControllerAxisMotion { .. } => {
-                    let (new_x, new_y, new_pressed, visible) = self.get_virtual_cursor(options);
-                    let (old_x, old_y, old_pressed, _) =
-                        self.virtual_cursor_last.unwrap_or_default();
-                    self.virtual_cursor_last = Some((new_x, new_y, new_pressed, visible));
-                    match (old_pressed, new_pressed) {
-                        (false, true) => {
-                            Event::TouchDown(transform_input_coords(self, (new_x, new_y)))
+                E::TextInput { text, .. } => Event::TextInput(text),
+                E::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if self.hotkeys.action(keycode, keymod).is_some() => {
+                    match self.hotkeys.action(keycode, keymod).unwrap() {
+                        Action::RotateClockwise => Event::RotateDevice { clockwise: true },
+                        Action::RotateCounterclockwise => Event::RotateDevice { clockwise: false },
+                        Action::Shake => Event::Shake,
+                        Action::ToggleAudioInterruption => Event::ToggleAudioInterruption,
+                        Action::TogglePause => Event::TogglePause,
+                        Action::ToggleFastForward => Event::ToggleFastForward,
+                        Action::ToggleVirtualControls => {
+                            self.virtual_controls_shown = !self.virtual_controls_shown;
+                            continue;
+                        }
+                        Action::Screenshot => {
+                            self.screenshot_requested = true;
+                            continue;
                         }
-                        (true, false) => {
-                            Event::TouchUp(transform_input_coords(self, (new_x, new_y)))
+                        Action::ToggleFullscreen => {
+                            self.toggle_fullscreen();
+                            continue;
                         }
-                        _ if (new_x, new_y) != (old_x, old_y) && new_pressed => {
-                            Event::TouchMove(transform_input_coords(self, (new_x, new_y)))
+                        Action::OpenMenu => {
+                            self.open_menu();
+                            Event::OpenMenu
                         }
-                        _ => continue,
                     }
                 }
+                // Releasing a bound key always counts, so that a touch isn't
+                // left behind if text input began while it was held.
+                E::KeyUp {
+                    scancode: Some(scancode),
+                    ..
+                } if self.keyboard_map.binds(scancode) => {
+                    update_keyboard_touches(self);
+                    continue;
+                }
+                E::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } if self.video_ctx.text_input().is_active() => Event::EditingKey(match keycode {
+                    Keycode::Backspace => EditingKey::Backspace,
+                    Keycode::Delete => EditingKey::Delete,
+                    Keycode::Return | Keycode::KpEnter => EditingKey::Return,
+                    Keycode::Left => EditingKey::Left,
+                    Keycode::Right => EditingKey::Right,
+                    Keycode::Home => EditingKey::Home,
+                    Keycode::End => EditingKey::End,
+                    _ => continue,
+                }),
+                E::KeyDown {
+                    scancode: Some(scancode),
+                    ..
+                } if self.keyboard_map.binds(scancode) => {
+                    update_keyboard_touches(self);
+                    continue;
+                }
+                E::KeyDown {
+                    scancode: Some(scancode),
+                    keycode,
+                    keymod,
+                    repeat,
+                    ..
+                } => Event::HardwareKey(HardwareKey::new(true, repeat, scancode, keycode, keymod)),
+                E::KeyUp {
+                    scancode: Some(scancode),
+                    keycode,
+                    keymod,
+                    ..
+                } if !self.video_ctx.text_input().is_active() => {
+                    Event::HardwareKey(HardwareKey::new(false, false, scancode, keycode, keymod))
+                }
+                E::ControllerDeviceAdded { which, .. } => {
+                    self.controller_added(which);
+                    continue;
+                }
+                E::ControllerDeviceRemoved { which, .. } => {
+                    update_controller(self, options, which, true);
+                    self.controller_removed(which);
+                    continue;
+                }
+                E::ControllerButtonDown { which, button, .. }
+                    if button == Button::Guide || self.menu_combo_pressed(which, button) =>
+                {
+                    self.open_menu();
+                    Event::OpenMenu
+                }
+                // Buttons the app's controller mapping doesn't use are only
+                // for navigation, and while text input is active, they all
+                // are.
+                E::ControllerButtonDown { which, button, .. }
+                    if navigation_button(button).is_some()
+                        && (self.video_ctx.text_input().is_active()
+                            || !self.controller_binds(which, Input::Button(button))) =>
+                {
+                    Event::NavigationButton(navigation_button(button).unwrap())
+                }
+                E::ControllerButtonUp { which, .. }
+                | E::ControllerButtonDown { which, .. }
+                | E::ControllerAxisMotion { which, .. } => {
+                    update_controller(self, options, which, false);
+                    continue;
+                }
+                E::Window {
+                    win_event: WindowEvent::Moved(..) | WindowEvent::SizeChanged(..),
+                    ..
+                } => {
+                    self.geometry_moved();
+                    continue;
+                }
                 _ => continue,
             })
         }
+
+        if self
+            .wheel_pinch
+            .is_some_and(|pinch| pinch.last_scroll.elapsed() >= WHEEL_PINCH_TIMEOUT)
+        {
+            end_wheel_pinch(self);
+        }
+
+        self.update_rumble();
+
+        if self
+            .geometry_changed
+            .is_some_and(|changed| changed.elapsed() >= GEOMETRY_SAVE_DELAY)
+        {
+            self.geometry_changed = None;
+            self.geometry.save(&self.geometry_path);
+        }
+    }
+
+    /// Keep track of where the window is and how big it is, after it has been
+    /// moved or resized, so it can be saved.
+    fn geometry_moved(&mut self) {
+        if self.splash_image_and_gl_ctx.is_some() {
+            self.display_splash();
+        }
+        if self.menu_drawn.is_some() {
+            self.redraw_menu();
+        }
+        // While fullscreen, the geometry to go back to is kept.
+        if self.window.fullscreen_state() == FullscreenType::Off {
+            let (x, y) = self.window.position();
+            let (width, height) = self.window.size();
+            // The size is saved for the portrait orientation.
+            let (width, height) = match self.device_orientation {
+                DeviceOrientation::Portrait | DeviceOrientation::PortraitUpsideDown => {
+                    (width, height)
+                }
+                DeviceOrientation::LandscapeLeft | DeviceOrientation::LandscapeRight => {
+                    (height, width)
+                }
+            };
+            self.geometry = WindowGeometry {
+                x,
+                y,
+                width,
+                height,
+                fullscreen: false,
+            };
+        }
+        self.geometry_changed = Some(Instant::now());
+    }
+
+    /// Whether the window is currently borderless fullscreen.
+    pub fn is_fullscreen(&self) -> bool {
+        self.geometry.fullscreen
+    }
+
+    /// Switch between a normal window and borderless fullscreen.
+    pub fn toggle_fullscreen(&mut self) {
+        let fullscreen = self.window.fullscreen_state() == FullscreenType::Off;
+        let new_state = if fullscreen {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Off
+        };
+        if let Err(e) = self.window.set_fullscreen(new_state) {
+            log!("Warning: couldn't toggle fullscreen: {}", e);
+            return;
+        }
+        self.geometry.fullscreen = fullscreen;
+        self.geometry_changed = Some(Instant::now());
+    }
+
+    fn ctrl_held(&self) -> bool {
+        let keyboard = self.event_pump.keyboard_state();
+        keyboard.is_scancode_pressed(Scancode::LCtrl)
+            || keyboard.is_scancode_pressed(Scancode::RCtrl)
     }
 
     /// Pop an event from the queue (in FIFO order)
@@ -237,44 +1354,205 @@ impl Window {
         self.event_queue.pop_front()
     }
 
+    /// Add a synthetic event to the end of the queue, as if it came from the
+    /// OS. This is used for `--auto-tap=` and `--replay-input=`.
+    pub fn push_event(&mut self, event: Event) {
+        self.event_queue.push_back(event)
+    }
+
     fn controller_added(&mut self, joystick_idx: u32) {
         let Ok(controller) = self.controller_ctx.open(joystick_idx) else {
             log!("Warning: A new controller was connected, but it couldn't be accessed!");
             return;
         };
-        log!(
-            "New controller connected: {}. Left stick = device tilt. Right stick = touch input (press the stick or shoulder button to tap/hold).",
-            controller.name()
-        );
-        self.controllers.push(controller);
+        let player = (0..)
+            .find(|&player| self.controllers.iter().all(|other| other.player != player))
+            .unwrap();
+        let bindings = self.controller_map.bindings(player);
+        if bindings.is_default() {
+            log!(
+                "New controller connected for player {}: {}. Left stick = device tilt. Right stick = touch input (press the stick or shoulder button to tap/hold). D-pad and A/B buttons = on-screen keyboard.",
+                player + 1,
+                controller.name()
+            );
+        } else {
+            log!(
+                "New controller connected for player {}: {}. It uses this app's controller mapping. D-pad and A/B buttons = on-screen keyboard.",
+                player + 1,
+                controller.name()
+            );
+        }
+        if controller.has_sensor(SensorType::Accelerometer) {
+            if controller
+                .sensor_set_enabled(SensorType::Accelerometer, true)
+                .is_ok()
+            {
+                log!("The controller has a motion sensor, which can be used for device tilt.");
+            } else {
+                log!("Warning: The controller has a motion sensor, but it couldn't be enabled.");
+            }
+        }
+        self.controllers.push(Controller {
+            controller,
+            player,
+            held: vec![false; bindings.buttons.len()],
+            bindings,
+            cursor_last: None,
+            joystick_touches: [None; 2],
+        });
     }
     fn controller_removed(&mut self, instance_id: u32) {
-        let Some(idx) = self.controllers.iter().position(|controller| controller.instance_id() == instance_id) else {
+        let Some(idx) = self
+            .controllers
+            .iter()
+            .position(|controller| controller.controller.instance_id() == instance_id)
+        else {
             return;
         };
         let controller = self.controllers.remove(idx);
-        log!("Warning: Controller disconnected: {}", controller.name());
+        log!(
+            "Warning: Controller for player {} disconnected: {}",
+            controller.player + 1,
+            controller.controller.name()
+        );
+    }
+    /// Whether a button press completes Back+Start, which opens the options
+    /// menu.
+    fn menu_combo_pressed(&self, instance_id: u32, button: Button) -> bool {
+        let other = match button {
+            Button::Start => Button::Back,
+            Button::Back => Button::Start,
+            _ => return false,
+        };
+        self.controllers
+            .iter()
+            .find(|controller| controller.controller.instance_id() == instance_id)
+            .is_some_and(|controller| controller.controller.button(other))
+    }
+    /// Whether the app's controller mapping gives a controller's button
+    /// something to do.
+    fn controller_binds(&self, instance_id: u32, input: Input) -> bool {
+        self.controllers
+            .iter()
+            .find(|controller| controller.controller.instance_id() == instance_id)
+            .is_some_and(|controller| {
+                controller
+                    .bindings
+                    .buttons
+                    .iter()
+                    .any(|&(other, _)| other == input)
+            })
     }
-    pub fn print_accelerometer_notice(&self) {
+    pub fn print_accelerometer_notice(&self, options: &Options) {
         log!("This app uses the accelerometer.");
-        if self.controllers.is_empty() {
-            log!("Connect a controller to get accelerometer simulation.");
+        match options.tilt_source {
+            TiltSource::Auto if self.has_motion_sensor() => {
+                log!("Your connected controller's motion sensor will be used for accelerometer input.");
+            }
+            TiltSource::Auto if self.controllers.is_empty() => {
+                log!("The arrow keys will be used for accelerometer simulation. Connect a controller to use its analog stick or motion sensor instead.");
+            }
+            TiltSource::Auto => {
+                log!("Your connected controller's left analog stick and the arrow keys will be used for accelerometer simulation.");
+            }
+            TiltSource::Sensor if !self.has_motion_sensor() => {
+                log!("Connect a controller with a motion sensor to get accelerometer input.");
+            }
+            TiltSource::Sensor => {
+                log!("Your connected controller's motion sensor will be used for accelerometer input.");
+            }
+            TiltSource::Stick if self.controllers.is_empty() => {
+                log!("Connect a controller to get accelerometer simulation.");
+            }
+            TiltSource::Stick => {
+                log!("Your connected controller's left analog stick will be used for accelerometer simulation.");
+            }
+            TiltSource::Keys => {
+                log!("The arrow keys will be used for accelerometer simulation.");
+            }
+        }
+    }
+
+    fn has_motion_sensor(&self) -> bool {
+        self.controllers.iter().any(|controller| {
+            controller
+                .controller
+                .sensor_enabled(SensorType::Accelerometer)
+        })
+    }
+
+    /// Make all connected game controllers rumble, to simulate the device
+    /// vibrating. Returns `false` if there are no controllers that can rumble.
+    pub fn vibrate(&mut self, vibration: Vibration) -> bool {
+        self.rumble = Some((vibration, Instant::now(), None));
+        self.update_rumble()
+    }
+
+    /// Set the game controllers' rumble to the current point in the shape of
+    /// the vibration, if it has changed. Returns `false` if it had to be
+    /// changed and there are no controllers that can rumble.
+    fn update_rumble(&mut self) -> bool {
+        let Some((vibration, start, old_intensity)) = self.rumble else {
+            return false;
+        };
+        let elapsed = start.elapsed().as_secs_f64();
+        let strength = vibration.strength.clamp(0.0, 1.0)
+            * vibration.shape.intensity(elapsed, vibration.duration);
+        let intensity = (strength * f32::from(u16::MAX)) as u16;
+        if elapsed >= vibration.duration {
+            self.rumble = None;
         } else {
-            log!("Your connected controller's left analog stick will be used for accelerometer simulation.");
+            self.rumble = Some((vibration, start, Some(intensity)));
+        }
+        if old_intensity == Some(intensity) {
+            return true;
+        }
+        // SDL stops the rumble by itself at the end, in case this isn't
+        // called again in time.
+        let remaining_ms = ((vibration.duration - elapsed).max(0.0) * 1000.0).ceil() as u32;
+        let mut rumbled = false;
+        for controller in &mut self.controllers {
+            // The vibration motor of an iPhone is a single off-center weight,
+            // so the same strength is used for both motors.
+            rumbled |= controller
+                .controller
+                .set_rumble(intensity, intensity, remaining_ms)
+                .is_ok();
         }
+        rumbled
     }
 
-    /// Get the real (TODO) or simulated accelerometer output.
+    /// Get the real or simulated accelerometer output, depending on the
+    /// `--tilt-source=` option.
     /// See also [crate::frameworks::uikit::ui_accelerometer].
     pub fn get_acceleration(&self, options: &Options) -> (f32, f32, f32) {
-        // Get left analog stick input. The range is [-1, 1] on each axis.
-        let (x, y, _) = self.get_controller_stick(options, true);
+        let use_sensor = match options.tilt_source {
+            TiltSource::Auto => self.has_motion_sensor(),
+            TiltSource::Sensor => true,
+            TiltSource::Stick | TiltSource::Keys => false,
+        };
+        if use_sensor {
+            // If there's no sensor, the device is simply lying flat.
+            return self.get_sensor_acceleration().unwrap_or((0.0, 0.0, -1.0));
+        }
+
+        // Get controller and/or arrow key input. The range is [-1, 1] on each
+        // axis.
+        let (x, y) = match options.tilt_source {
+            TiltSource::Stick => self.get_controller_tilt(options),
+            TiltSource::Keys => self.get_tilt_keys(),
+            _ => {
+                let (stick_x, stick_y) = self.get_controller_tilt(options);
+                let (keys_x, keys_y) = self.get_tilt_keys();
+                (stick_x + keys_x, stick_y + keys_y)
+            }
+        };
 
         // Correct for window rotation
         let [x, y] = self.input_rotation_matrix().transform([x, y]);
         let (x, y) = (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0)); // just in case
 
-        // Let's simulate tilting the device based on the analog stick inputs.
+        // Let's simulate tilting the device based on the stick/key inputs.
         //
         // If an iPhone is lying flat on its back, level with the ground, and it
         // is on Earth, the accelerometer will report approximately (0, 0, -1).
@@ -303,23 +1581,128 @@ impl Window {
         (x, y, z)
     }
 
-    /// For use when redrawing the screen: Get the cached on-screen position and
-    /// press state of the analog stick-controlled virtual cursor, if it is
-    /// visible.
-    pub fn virtual_cursor_visible_at(&self) -> Option<(f32, f32, bool)> {
-        let (x, y, pressed, visible) = self.virtual_cursor_last?;
-        if visible {
-            Some((x, y, pressed))
-        } else {
-            None
+    /// Get the acceleration measured by the first game controller with a
+    /// motion sensor, in iPhone OS units and axes.
+    fn get_sensor_acceleration(&self) -> Option<(f32, f32, f32)> {
+        let controller = self
+            .controllers
+            .iter()
+            .map(|controller| &controller.controller)
+            .find(|controller| controller.sensor_enabled(SensorType::Accelerometer))?;
+        let mut data = [0f32; 3];
+        controller
+            .sensor_get_data(SensorType::Accelerometer, &mut data)
+            .ok()?;
+        // SDL's axes for a controller held in front of you are the same as an
+        // iPhone's axes when it's held upright in portrait in front of you, but
+        // SDL reports the force opposing gravity in m/s², whereas iPhone OS
+        // reports gravity itself in units of g.
+        let [x, y, z] = data.map(|axis| -axis / sdl2::sys::SDL_STANDARD_GRAVITY as f32);
+        // Correct for window rotation
+        let [x, y] = self.input_rotation_matrix().transform([x, y]);
+        Some((x, y, z))
+    }
+
+    /// Get the tilt from the arrow keys, like a digital analog stick. Each
+    /// axis value is in the range [-1, 1]. The keys are ignored while text
+    /// input is active, since they're used for editing then, and if the
+    /// keyboard mapping uses them.
+    fn get_tilt_keys(&self) -> (f32, f32) {
+        if self.video_ctx.text_input().is_active() {
+            return (0.0, 0.0);
+        }
+        let keyboard = self.event_pump.keyboard_state();
+        // Ctrl+Left/Right rotates the device instead, by default.
+        if keyboard.is_scancode_pressed(Scancode::LCtrl)
+            || keyboard.is_scancode_pressed(Scancode::RCtrl)
+        {
+            return (0.0, 0.0);
         }
+        let pressed =
+            |scancode| !self.keyboard_map.binds(scancode) && keyboard.is_scancode_pressed(scancode);
+        let axis = |negative, positive| {
+            let mut value = 0.0;
+            if pressed(negative) {
+                value -= 1.0;
+            }
+            if pressed(positive) {
+                value += 1.0;
+            }
+            value
+        };
+        (
+            axis(Scancode::Left, Scancode::Right),
+            axis(Scancode::Up, Scancode::Down),
+        )
+    }
+
+    /// For use when redrawing the screen: Get the cached on-screen positions
+    /// and press states of the analog stick-controlled virtual cursors that
+    /// are visible.
+    pub fn virtual_cursors_visible_at(&self) -> Vec<(f32, f32, bool)> {
+        self.controllers
+            .iter()
+            .filter_map(|controller| {
+                let (x, y, pressed, visible) = controller.cursor_last?;
+                visible.then_some((x, y, pressed))
+            })
+            .collect()
+    }
+
+    /// Get the on-screen virtual controls to draw, as circles in window
+    /// co-ordinates with whether they're pressed. A pressed stick has a
+    /// smaller circle, its knob, drawn where it's been dragged to.
+    pub fn virtual_controls_visible_at(&self) -> Vec<(f32, f32, f32, bool)> {
+        let scale = self.scale_hack.get() as f32;
+        let mut circles = Vec::new();
+        if !self.virtual_controls_shown {
+            return circles;
+        }
+        for (idx, press) in self.virtual_control_presses.iter().enumerate() {
+            let (x, y, radius) = self.virtual_controls.circle(idx);
+            circles.push((x * scale, y * scale, radius * scale, press.is_some()));
+            if let Some((_, at)) = *press {
+                if self.virtual_controls.is_stick(idx) {
+                    let (knob_x, knob_y) = self.virtual_controls.touch_at(idx, at);
+                    let (target_x, target_y) = self.virtual_controls.touch_start(idx);
+                    let (knob_x, knob_y) = (knob_x - target_x + x, knob_y - target_y + y);
+                    circles.push((knob_x * scale, knob_y * scale, radius * scale / 2.0, true));
+                }
+            }
+        }
+        circles
+    }
+
+    pub fn virtual_controls_shown(&self) -> bool {
+        self.virtual_controls_shown
+    }
+
+    pub fn set_virtual_controls_shown(&mut self, shown: bool) {
+        self.virtual_controls_shown = shown;
     }
 
-    /// Get the new  on-screen position, click state and visibility of the
-    /// analog stick-controlled virtual cursor.
-    fn get_virtual_cursor(&self, options: &Options) -> (f32, f32, bool, bool) {
-        // Get right analog stick input. The range is [-1, 1] on each axis.
-        let (x, y, pressed) = self.get_controller_stick(options, false);
+    /// Get the new on-screen position, click state and visibility of a game
+    /// controller's analog stick-controlled virtual cursor.
+    fn get_virtual_cursor(&self, options: &Options, idx: usize) -> (f32, f32, bool, bool) {
+        let controller = &self.controllers[idx];
+        let text_input_active = self.video_ctx.text_input().is_active();
+
+        // Get input from the sticks bound to the cursor. The range is [-1, 1]
+        // on each axis.
+        let (mut x, mut y) = (0.0, 0.0);
+        for stick in [Stick::Left, Stick::Right] {
+            if controller.bindings.stick(stick) == StickAction::Cursor {
+                let (stick_x, stick_y) =
+                    stick_position(&controller.controller, stick, options.deadzone);
+                x += stick_x;
+                y += stick_y;
+            }
+        }
+        let (x, y) = (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0));
+        let pressed = controller.bindings.buttons.iter().any(|&(input, action)| {
+            action == ButtonAction::CursorPress
+                && input_held(&controller.controller, input, text_input_active)
+        });
 
         // The cursor is intended to only show up once you move the analog stick
         // out of its deadzone, or while the button is held.
@@ -335,11 +1718,30 @@ impl Window {
             (x_abs.copysign(x), y_abs.copysign(y))
         };
 
+        // The cursor can be confined to a region by the controller mapping. In
+        // split co-op mode, it's confined to the right half of the window, or
+        // with more than one controller, the first two players get a half
+        // each.
+        let (window_width, window_height) = self.size_in_current_orientation();
+        let (window_width, window_height) = (window_width as f32, window_height as f32);
+        let (left, top, width, height) =
+            if let Some((x, y, width, height)) = controller.bindings.region {
+                let scale = self.scale_hack.get() as f32;
+                (x * scale, y * scale, width * scale, height * scale)
+            } else if options.split_coop {
+                let half = window_width / 2.0;
+                match (self.controllers.len(), controller.player) {
+                    (1, _) | (_, 1) => (half, 0.0, half, window_height),
+                    (_, 0) => (0.0, 0.0, half, window_height),
+                    _ => (0.0, 0.0, window_width, window_height),
+                }
+            } else {
+                (0.0, 0.0, window_width, window_height)
+            };
+
         // Aspect ratio handling: cut the square down to a rectangle
         // TODO: It would be better to directly cut out a rectangle from the
         // circle.
-        let (width, height) = self.size_in_current_orientation();
-        let (width, height) = (width as f32, height as f32);
         let (x, y) = {
             let (x_abs, y_abs) = if width < height {
                 (x.abs().min(width / height) / (width / height), y.abs())
@@ -350,60 +1752,70 @@ impl Window {
         };
 
         // Convert to window co-ordinates
-        let x = (x / 2.0 + 0.5) * width;
-        let y = (y / 2.0 + 0.5) * height;
+        let x = (x / 2.0 + 0.5) * width + left;
+        let y = (y / 2.0 + 0.5) * height + top;
 
         (x, y, pressed, visible)
     }
 
-    /// Get the summed X and Y positions and button state of the left or right
-    /// analog stick of the game controllers. Each axis value is in the range
+    /// Get the summed tilt from the analog sticks and buttons of the game
+    /// controllers that are bound to it. Each axis value is in the range
     /// [-1, 1].
-    fn get_controller_stick(&self, options: &Options, left: bool) -> (f32, f32, bool) {
-        fn convert_axis(axis: i16, deadzone: f32) -> f32 {
-            assert!(deadzone >= 0.0);
-            let axis = ((axis as f32) / (i16::MAX as f32)).clamp(-1.0, 1.0);
-            let abs_axis = (axis.abs().max(deadzone) - deadzone) / (1.0 - deadzone);
-            abs_axis.copysign(axis)
-        }
-
+    fn get_controller_tilt(&self, options: &Options) -> (f32, f32) {
+        let text_input_active = self.video_ctx.text_input().is_active();
         let (mut x, mut y) = (0.0, 0.0);
-        let mut pressed = false;
         for controller in &self.controllers {
-            use sdl2::controller::{Axis, Button};
-            let (x_axis, y_axis, button1, button2) = if left {
-                (
-                    Axis::LeftX,
-                    Axis::LeftY,
-                    Button::LeftStick,
-                    Button::LeftShoulder,
-                )
-            } else {
-                (
-                    Axis::RightX,
-                    Axis::RightY,
-                    Button::RightStick,
-                    Button::RightShoulder,
-                )
-            };
-            x += convert_axis(controller.axis(x_axis), options.deadzone);
-            y += convert_axis(controller.axis(y_axis), options.deadzone);
-            pressed |= controller.button(button1);
-            pressed |= controller.button(button2);
+            for stick in [Stick::Left, Stick::Right] {
+                if controller.bindings.stick(stick) == StickAction::Tilt {
+                    let (stick_x, stick_y) =
+                        stick_position(&controller.controller, stick, options.deadzone);
+                    x += stick_x;
+                    y += stick_y;
+                }
+            }
+            for &(input, action) in &controller.bindings.buttons {
+                if let ButtonAction::Tilt(button_x, button_y) = action {
+                    if input_held(&controller.controller, input, text_input_active) {
+                        x += button_x;
+                        y += button_y;
+                    }
+                }
+            }
         }
-        let (x, y) = (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0));
-
-        (x, y, pressed)
+        (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0))
     }
 
+    /// Create an OpenGL context. This makes it current, so
+    /// [Self::is_app_gl_ctx_no_longer_current] will return [true].
     pub fn create_gl_context(&mut self, version: GLVersion) -> GLContext {
-        gl::create_gl_context(&self.video_ctx, &self.window, version)
+        self.app_gl_ctx_no_longer_current = true;
+        gl::create_gl_context(&self.video_ctx, &self.window, version, None)
+    }
+
+    /// Like [Self::create_gl_context], but the new context shares objects
+    /// (textures, buffers etc) with `share_with` and any other contexts that
+    /// one shares with.
+    pub fn create_shared_gl_context(
+        &mut self,
+        version: GLVersion,
+        share_with: &GLContext,
+    ) -> GLContext {
+        self.app_gl_ctx_no_longer_current = true;
+        gl::create_gl_context(&self.video_ctx, &self.window, version, Some(share_with))
     }
 
     pub fn make_gl_context_current(&mut self, gl_ctx: &GLContext) {
         gl::make_gl_context_current(&self.video_ctx, &self.window, gl_ctx);
     }
 
+    /// Like [Self::make_gl_context_current], but for contexts used by the host
+    /// rather than the guest app, so this sets the flag checked by
+    /// [Self::is_app_gl_ctx_no_longer_current].
+    pub fn make_internal_gl_context_current(&mut self, gl_ctx: &GLContext) {
+        self.app_gl_ctx_no_longer_current = true;
+        gl::make_gl_context_current(&self.video_ctx, &self.window, gl_ctx);
+    }
+
     /// Retrieve and reset the flag that indicates if the current OpenGL context
     /// was changed to one outside of the control of the guest app.
     ///
@@ -421,8 +1833,7 @@ impl Window {
         };
 
         let matrix = self.output_rotation_matrix();
-        let viewport_size = self.size_in_current_orientation();
-        let viewport_offset = (0, self.viewport_y_offset());
+        let (viewport_offset, viewport_size) = self.viewport();
 
         self.app_gl_ctx_no_longer_current = true;
 
@@ -434,12 +1845,142 @@ impl Window {
         // onto image so we can rotate later if necessary
     }
 
+    /// Start showing the options menu, keeping a copy of what's on screen to
+    /// draw it over.
+    fn open_menu(&mut self) {
+        self.menu_open = true;
+        self.menu_drawn = None;
+        let (gl_ctx, _) = self.menu_gl_ctx_and_font.get_or_insert_with(|| {
+            let gl_ctx =
+                gl::create_gl_context(&self.video_ctx, &self.window, GLVersion::GL32Core, None);
+            (gl_ctx, Font::sans_regular())
+        });
+        self.app_gl_ctx_no_longer_current = true;
+        gl::make_gl_context_current(&self.video_ctx, &self.window, gl_ctx);
+        let (width, height) = self.window.drawable_size();
+        let pixels = unsafe { gl::read_front_buffer_pixels(width, height) };
+        self.menu_background = Some((pixels, (width, height)));
+    }
+
+    /// Draw the options menu, if it has changed since it was last drawn. See
+    /// [Event::OpenMenu].
+    pub fn draw_menu(&mut self, menu: &Menu, settings: &MenuSettings) {
+        if self.menu_drawn != Some((*menu, *settings)) {
+            self.menu_drawn = Some((*menu, *settings));
+            self.redraw_menu();
+        }
+    }
+
+    fn redraw_menu(&mut self) {
+        let (Some((menu, settings)), Some((gl_ctx, font))) =
+            (self.menu_drawn, &self.menu_gl_ctx_and_font)
+        else {
+            return;
+        };
+        let size = self.window.drawable_size();
+        let background = self
+            .menu_background
+            .as_ref()
+            .filter(|&&(_, background_size)| background_size == size)
+            .map(|(pixels, _)| pixels.as_slice());
+        let image = Image::from_pixels(menu.draw(&settings, font, background, size), size);
+
+        self.app_gl_ctx_no_longer_current = true;
+        gl::make_gl_context_current(&self.video_ctx, &self.window, gl_ctx);
+        unsafe { gl::display_image(&image, (0, 0), size, &Matrix::identity()) };
+        self.window.gl_swap_window();
+    }
+
+    /// Stop showing the options menu, and put back what was on screen until
+    /// the app draws something new.
+    pub fn close_menu(&mut self) {
+        self.menu_open = false;
+        self.menu_drawn = None;
+        let (Some((pixels, size)), Some((gl_ctx, _))) =
+            (self.menu_background.take(), &self.menu_gl_ctx_and_font)
+        else {
+            return;
+        };
+        if size != self.window.drawable_size() {
+            return;
+        }
+        let pixels = pixels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect();
+        let image = Image::from_pixels(pixels, size);
+
+        self.app_gl_ctx_no_longer_current = true;
+        gl::make_gl_context_current(&self.video_ctx, &self.window, gl_ctx);
+        unsafe { gl::display_image(&image, (0, 0), size, &Matrix::identity()) };
+        self.window.gl_swap_window();
+    }
+
     /// Swap front-buffer and back-buffer so the result of OpenGL rendering is
     /// presented.
     pub fn swap_window(&mut self) {
+        if std::mem::take(&mut self.screenshot_requested) {
+            self.save_screenshot();
+        }
         self.window.gl_swap_window();
     }
 
+    /// Save what's about to be presented to a BMP file in
+    /// `touchHLE_screenshots`, named after the current time.
+    fn save_screenshot(&self) {
+        let size = self.window.drawable_size();
+        let pixels = unsafe { gl::read_window_pixels(size.0, size.1) };
+
+        let dir = Path::new("touchHLE_screenshots");
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = dir.join(format!("{}.bmp", time));
+        match std::fs::create_dir_all(dir)
+            .map_err(|e| e.to_string())
+            .and_then(|()| save_bmp(&path, &pixels, size))
+        {
+            Ok(()) => log!("Saved a screenshot to {}", path.display()),
+            Err(e) => log!(
+                "Warning: couldn't save a screenshot to {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+
+    /// Get the refresh rate of the host display the window is on, in Hz, if
+    /// known.
+    pub fn refresh_rate(&self) -> Option<u32> {
+        let mode = self.window.display_mode().ok()?;
+        u32::try_from(mode.refresh_rate)
+            .ok()
+            .filter(|&rate| rate > 0)
+    }
+
+    /// Get the state of the host's battery, and its charge level in the range
+    /// [0, 1] if known.
+    pub fn battery_info(&self) -> (BatteryState, Option<f32>) {
+        use sdl2::sys::SDL_PowerState as S;
+        let mut percent: std::os::raw::c_int = -1;
+        let state = unsafe { sdl2::sys::SDL_GetPowerInfo(std::ptr::null_mut(), &mut percent) };
+        let state = match state {
+            S::SDL_POWERSTATE_UNKNOWN => BatteryState::Unknown,
+            S::SDL_POWERSTATE_ON_BATTERY => BatteryState::Unplugged,
+            S::SDL_POWERSTATE_CHARGING => BatteryState::Charging,
+            S::SDL_POWERSTATE_NO_BATTERY | S::SDL_POWERSTATE_CHARGED => {
+                return (BatteryState::Full, Some(1.0))
+            }
+        };
+        let level = if percent >= 0 {
+            Some((percent as f32 / 100.0).min(1.0))
+        } else {
+            None
+        };
+        (state, level)
+    }
+
     /// Consider the emulated device to be rotated to a particular orientation.
     ///
     /// On a PC or laptop, this will make the window be rotated so the app
@@ -450,7 +1991,23 @@ impl Window {
             return;
         }
 
-        let (width, height) = size_for_orientation(new_orientation, self.scale_hack);
+        // The window keeps its size while fullscreen or maximized, but
+        // otherwise turns so that the screen still fills it.
+        let (old_width, old_height) = self.window.size();
+        let turned = (old_width > old_height)
+            != matches!(
+                new_orientation,
+                DeviceOrientation::LandscapeLeft | DeviceOrientation::LandscapeRight
+            );
+        let keep_size = self.window.fullscreen_state() != FullscreenType::Off
+            || self.window.window_flags()
+                & (sdl2::sys::SDL_WindowFlags::SDL_WINDOW_MAXIMIZED as u32)
+                != 0;
+        let (width, height) = if turned && !keep_size {
+            (old_height, old_width)
+        } else {
+            (old_width, old_height)
+        };
 
         // macOS quirk: when resizing the window, the new framebuffer's size is
         // apparently max(new_size, old_size) in each dimension, but the
@@ -460,7 +2017,6 @@ impl Window {
         // apparently stops other OpenGL contexts drawing to the framebuffer!
         #[cfg(target_os = "macos")]
         {
-            let (_old_width, old_height) = self.window.size();
             self.max_height = self.max_height.max(old_height).max(height);
             self.viewport_y_offset = self.max_height - height;
         }
@@ -474,38 +2030,98 @@ impl Window {
         }
     }
 
-    /// Get the size in pixels of the window with the aspect ratio reflecting
-    /// rotation (see [Self::rotate_device]). This also has the scale hack
-    /// applied.
+    pub fn device_orientation(&self) -> DeviceOrientation {
+        self.device_orientation
+    }
+
+    /// Get the size in pixels of the emulated screen with the aspect ratio
+    /// reflecting rotation (see [Self::rotate_device]). This also has the
+    /// scale hack applied. The window can be a different size, see
+    /// [Self::viewport].
     pub fn size_in_current_orientation(&self) -> (u32, u32) {
         size_for_orientation(self.device_orientation, self.scale_hack)
     }
 
-    /// Get the size in pixels of the window without rotation or scaling.
+    /// Get the size in pixels of the emulated screen with the aspect ratio
+    /// reflecting rotation (see [Self::rotate_device]), but without scaling.
+    pub fn size_in_current_orientation_unscaled(&self) -> (u32, u32) {
+        size_for_orientation(self.device_orientation, NonZeroU32::new(1).unwrap())
+    }
+
+    /// Get the size in pixels of the emulated screen without rotation or
+    /// scaling.
     pub fn size_unrotated_unscaled(&self) -> (u32, u32) {
         size_for_orientation(DeviceOrientation::Portrait, NonZeroU32::new(1).unwrap())
     }
 
-    /// Get the size in pixels of the window without rotation but with the
-    /// scale hack.
+    /// Get the size in pixels of the emulated screen without rotation but
+    /// with the scale hack.
     pub fn size_unrotated_scalehacked(&self) -> (u32, u32) {
         size_for_orientation(DeviceOrientation::Portrait, self.scale_hack)
     }
 
-    pub fn viewport_y_offset(&self) -> u32 {
+    /// Get the scale hack factor, i.e. how many times larger than the
+    /// iPhone's resolution the app's content is rendered at (see the
+    /// `--internal-resolution=` option).
+    pub fn scale_hack(&self) -> NonZeroU32 {
+        self.scale_hack
+    }
+
+    fn viewport_y_offset(&self) -> u32 {
         #[cfg(target_os = "macos")]
         return self.viewport_y_offset;
         #[cfg(not(target_os = "macos"))]
         return 0;
     }
 
+    /// Get the area of the window's framebuffer that the emulated screen is
+    /// drawn in, as an offset and size in pixels, with the origin in the
+    /// bottom-left corner like OpenGL. It's as large as fits in the window
+    /// while keeping the screen's aspect ratio, and the rest of the window is
+    /// left black.
+    pub fn viewport(&self) -> ((u32, u32), (u32, u32)) {
+        let (width, height) = self.window.drawable_size();
+        let screen_size = self.size_in_current_orientation();
+        let (_, scale) = letterbox((width, height), screen_size);
+        let size = (
+            ((screen_size.0 as f32 * scale).round() as u32).clamp(1, width.max(1)),
+            ((screen_size.1 as f32 * scale).round() as u32).clamp(1, height.max(1)),
+        );
+        let offset = (
+            width.saturating_sub(size.0) / 2,
+            height.saturating_sub(size.1) / 2 + self.viewport_y_offset(),
+        );
+        (offset, size)
+    }
+
+    /// Convert a position in the window, in the co-ordinates SDL uses for
+    /// input, to one on the emulated screen, undoing the letterboxing.
+    /// Positions outside the screen are moved to its edge.
+    fn window_to_screen(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let (width, height) = self.size_in_current_orientation();
+        let ((offset_x, offset_y), scale) = letterbox(self.window.size(), (width, height));
+        (
+            ((x - offset_x) / scale).clamp(0.0, width as f32),
+            ((y - offset_y) / scale).clamp(0.0, height as f32),
+        )
+    }
+
+    /// Inverse of [Self::window_to_screen].
+    fn screen_to_window(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let screen_size = self.size_in_current_orientation();
+        let ((offset_x, offset_y), scale) = letterbox(self.window.size(), screen_size);
+        (x * scale + offset_x, y * scale + offset_y)
+    }
+
     /// Transformation matrix for texture co-ordinates when sampling the
     /// framebuffer presented by the app. Rotates the framebuffer to match the
     /// window. See [Self::rotate_device].
     pub fn output_rotation_matrix(&self) -> Matrix<2> {
         match self.device_orientation {
             DeviceOrientation::Portrait => Matrix::identity(),
+            DeviceOrientation::PortraitUpsideDown => Matrix::z_rotation(PI),
             DeviceOrientation::LandscapeLeft => Matrix::z_rotation(-FRAC_PI_2),
+            DeviceOrientation::LandscapeRight => Matrix::z_rotation(FRAC_PI_2),
         }
     }
 
@@ -514,10 +2130,42 @@ impl Window {
     pub fn input_rotation_matrix(&self) -> Matrix<2> {
         match self.device_orientation {
             DeviceOrientation::Portrait => Matrix::identity(),
+            DeviceOrientation::PortraitUpsideDown => Matrix::z_rotation(PI),
             DeviceOrientation::LandscapeLeft => Matrix::z_rotation(FRAC_PI_2),
+            DeviceOrientation::LandscapeRight => Matrix::z_rotation(-FRAC_PI_2),
         }
     }
 
+    /// Start accepting text input from the keyboard, including input methods.
+    /// `rect` is the area of the text being edited in the app's co-ordinate
+    /// space (origin, size), which lets the host put input method UI next to
+    /// it.
+    pub fn start_text_input(&mut self, rect: Option<((f32, f32), (f32, f32))>) {
+        if let Some(((x, y), (width, height))) = rect {
+            let corners = [(x, y), (x + width, y + height)].map(|(x, y)| {
+                // Inverse of the transformation for touch inputs.
+                let (in_w, in_h) = self.size_unrotated_unscaled();
+                let x = x / in_w as f32 - 0.5;
+                let y = y / in_h as f32 - 0.5;
+                let [x, y] = self.output_rotation_matrix().transform([x, y]);
+                let (out_w, out_h) = self.size_in_current_orientation();
+                self.screen_to_window(((x + 0.5) * out_w as f32, (y + 0.5) * out_h as f32))
+            });
+            let [(x1, y1), (x2, y2)] = corners;
+            let rect = sdl2::rect::Rect::new(
+                x1.min(x2) as i32,
+                y1.min(y2) as i32,
+                (x1 - x2).abs().max(1.0) as u32,
+                (y1 - y2).abs().max(1.0) as u32,
+            );
+            self.video_ctx.text_input().set_rect(rect);
+        }
+        self.video_ctx.text_input().start();
+    }
+    pub fn stop_text_input(&mut self) {
+        self.video_ctx.text_input().stop();
+    }
+
     pub fn is_screen_saver_enabled(&self) -> bool {
         self.video_ctx.is_screen_saver_enabled()
     }
@@ -0,0 +1,379 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Per-app game controller mappings. See `--controller-map-dir=`.
+//!
+//! A mapping file is named after the app's bundle identifier and looks like
+//! this:
+//!
+//! ```text
+//! # Comments start with '#'.
+//! a = touch 420, 270
+//! start = shake
+//! left-stick = joystick 80, 240, 50
+//! dpad-left = tilt left
+//!
+//! [player 2]
+//! region = 240, 0, 240, 320
+//! a = touch 440, 270
+//! ```
+//!
+//! Lines before the first `[player N]` section apply to every controller, and
+//! lines in a section apply on top of those to the Nth controller, counting in
+//! the order they were connected. Co-ordinates are in points on the screen as
+//! it is currently displayed, with (0, 0) in the top-left corner.
+
+use sdl2::controller::{Axis, Button};
+use std::path::Path;
+
+/// Something on a controller that is either pressed or not.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Input {
+    Button(Button),
+    /// Counts as pressed once it's pulled more than halfway.
+    Trigger(Axis),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ButtonAction {
+    /// Touch this point while the button is held.
+    Touch(f32, f32),
+    /// Press the virtual cursor while the button is held.
+    CursorPress,
+    /// Tilt the device while the button is held, like the arrow keys. The
+    /// value is the direction, with each axis in the range [-1, 1].
+    Tilt(f32, f32),
+    Shake,
+    RotateDevice {
+        clockwise: bool,
+    },
+    ToggleAudioInterruption,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StickAction {
+    /// Tilt the device.
+    Tilt,
+    /// Move the virtual cursor.
+    Cursor,
+    /// Touch and drag around a point, for on-screen joysticks: the point and
+    /// how far the touch can move from it.
+    Joystick(f32, f32, f32),
+    None,
+}
+
+/// What a controller's inputs do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    pub buttons: Vec<(Input, ButtonAction)>,
+    pub left_stick: StickAction,
+    pub right_stick: StickAction,
+    /// The area the virtual cursor is confined to, as (x, y, width, height).
+    pub region: Option<(f32, f32, f32, f32)>,
+}
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            buttons: vec![
+                (Input::Button(Button::RightStick), ButtonAction::CursorPress),
+                (
+                    Input::Button(Button::RightShoulder),
+                    ButtonAction::CursorPress,
+                ),
+            ],
+            left_stick: StickAction::Tilt,
+            right_stick: StickAction::Cursor,
+            region: None,
+        }
+    }
+}
+impl Bindings {
+    pub fn stick(&self, stick: Stick) -> StickAction {
+        match stick {
+            Stick::Left => self.left_stick,
+            Stick::Right => self.right_stick,
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Bindings::default()
+    }
+
+    fn apply(&mut self, entry: &Entry) {
+        match *entry {
+            Entry::Button(input, action) => {
+                self.buttons.retain(|&(other, _)| other != input);
+                if let Some(action) = action {
+                    self.buttons.push((input, action));
+                }
+            }
+            Entry::Stick(Stick::Left, action) => self.left_stick = action,
+            Entry::Stick(Stick::Right, action) => self.right_stick = action,
+            Entry::Region(region) => self.region = Some(region),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Entry {
+    /// [None] removes the binding.
+    Button(Input, Option<ButtonAction>),
+    Stick(Stick, StickAction),
+    Region((f32, f32, f32, f32)),
+}
+
+fn parse_input(name: &str) -> Option<Result<Input, Stick>> {
+    let button = match name {
+        "a" => Button::A,
+        "b" => Button::B,
+        "x" => Button::X,
+        "y" => Button::Y,
+        "back" => Button::Back,
+        "guide" => Button::Guide,
+        "start" => Button::Start,
+        "left-stick-click" => Button::LeftStick,
+        "right-stick-click" => Button::RightStick,
+        "left-shoulder" => Button::LeftShoulder,
+        "right-shoulder" => Button::RightShoulder,
+        "dpad-up" => Button::DPadUp,
+        "dpad-down" => Button::DPadDown,
+        "dpad-left" => Button::DPadLeft,
+        "dpad-right" => Button::DPadRight,
+        "misc" => Button::Misc1,
+        "paddle1" => Button::Paddle1,
+        "paddle2" => Button::Paddle2,
+        "paddle3" => Button::Paddle3,
+        "paddle4" => Button::Paddle4,
+        "touchpad" => Button::Touchpad,
+        "left-trigger" => return Some(Ok(Input::Trigger(Axis::TriggerLeft))),
+        "right-trigger" => return Some(Ok(Input::Trigger(Axis::TriggerRight))),
+        "left-stick" => return Some(Err(Stick::Left)),
+        "right-stick" => return Some(Err(Stick::Right)),
+        _ => return None,
+    };
+    Some(Ok(Input::Button(button)))
+}
+
+/// Parse comma-separated numbers, e.g. `420, 270`.
+pub(super) fn parse_numbers<const N: usize>(text: &str) -> Option<[f32; N]> {
+    let mut numbers = [0.0; N];
+    let mut parts = text.split(',');
+    for number in &mut numbers {
+        *number = parts.next()?.trim().parse().ok()?;
+    }
+    parts.next().is_none().then_some(numbers)
+}
+
+fn parse_button_action(value: &str) -> Option<Option<ButtonAction>> {
+    let (word, rest) = value.split_once(' ').unwrap_or((value, ""));
+    let rest = rest.trim();
+    let action = match (word, rest) {
+        ("touch", _) => {
+            let [x, y] = parse_numbers(rest)?;
+            ButtonAction::Touch(x, y)
+        }
+        ("cursor", "") => ButtonAction::CursorPress,
+        ("tilt", "left") => ButtonAction::Tilt(-1.0, 0.0),
+        ("tilt", "right") => ButtonAction::Tilt(1.0, 0.0),
+        ("tilt", "up") => ButtonAction::Tilt(0.0, -1.0),
+        ("tilt", "down") => ButtonAction::Tilt(0.0, 1.0),
+        ("shake", "") => ButtonAction::Shake,
+        ("rotate-clockwise", "") => ButtonAction::RotateDevice { clockwise: true },
+        ("rotate-counterclockwise", "") => ButtonAction::RotateDevice { clockwise: false },
+        ("interruption", "") => ButtonAction::ToggleAudioInterruption,
+        ("none", "") => return Some(None),
+        _ => return None,
+    };
+    Some(Some(action))
+}
+
+fn parse_stick_action(value: &str) -> Option<StickAction> {
+    let (word, rest) = value.split_once(' ').unwrap_or((value, ""));
+    let rest = rest.trim();
+    match (word, rest) {
+        ("tilt", "") => Some(StickAction::Tilt),
+        ("cursor", "") => Some(StickAction::Cursor),
+        ("joystick", _) => {
+            let [x, y, radius] = parse_numbers(rest)?;
+            (radius > 0.0).then_some(StickAction::Joystick(x, y, radius))
+        }
+        ("none", "") => Some(StickAction::None),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ControllerMap {
+    /// Each entry and the player it's for, or [None] if it's for everyone.
+    entries: Vec<(Option<usize>, Entry)>,
+}
+impl ControllerMap {
+    pub fn parse(text: &str) -> Result<ControllerMap, String> {
+        let mut entries = Vec::new();
+        let mut player = None;
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = line_number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let number = section
+                    .trim()
+                    .strip_prefix("player ")
+                    .and_then(|number| number.trim().parse::<usize>().ok())
+                    .filter(|&number| number >= 1);
+                let Some(number) = number else {
+                    return Err(format!(
+                        "Line {}: expected '[player N]', got {:?}",
+                        line_number, line
+                    ));
+                };
+                player = Some(number - 1);
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("Line {}: expected 'input = action'", line_number));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let entry = if key == "region" {
+                let Some(region) = parse_numbers(value) else {
+                    return Err(format!(
+                        "Line {}: expected 'region = x, y, width, height'",
+                        line_number
+                    ));
+                };
+                Entry::Region(region.into())
+            } else {
+                match parse_input(key) {
+                    Some(Ok(input)) => match parse_button_action(value) {
+                        Some(action) => Entry::Button(input, action),
+                        None => {
+                            return Err(format!(
+                                "Line {}: invalid button action {:?}",
+                                line_number, value
+                            ))
+                        }
+                    },
+                    Some(Err(stick)) => match parse_stick_action(value) {
+                        Some(action) => Entry::Stick(stick, action),
+                        None => {
+                            return Err(format!(
+                                "Line {}: invalid stick action {:?}",
+                                line_number, value
+                            ))
+                        }
+                    },
+                    None => {
+                        return Err(format!(
+                            "Line {}: unknown controller input {:?}",
+                            line_number, key
+                        ))
+                    }
+                }
+            };
+            entries.push((player, entry));
+        }
+        Ok(ControllerMap { entries })
+    }
+
+    /// Read the mapping for an app, if there is one.
+    pub fn load(dir: &Path, bundle_identifier: &str) -> ControllerMap {
+        let path = dir.join(format!("{}.txt", bundle_identifier));
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return ControllerMap::default();
+        };
+        match ControllerMap::parse(&text) {
+            Ok(map) => {
+                log!("Using the controller mapping in {}", path.display());
+                map
+            }
+            Err(e) => {
+                log!(
+                    "Warning: ignoring invalid controller mapping {}: {}",
+                    path.display(),
+                    e
+                );
+                ControllerMap::default()
+            }
+        }
+    }
+
+    /// Get the bindings for a player, counting from 0.
+    pub fn bindings(&self, player: usize) -> Bindings {
+        let mut bindings = Bindings::default();
+        for (_, entry) in self.entries.iter().filter(|(p, _)| p.is_none()) {
+            bindings.apply(entry);
+        }
+        for (_, entry) in self.entries.iter().filter(|(p, _)| *p == Some(player)) {
+            bindings.apply(entry);
+        }
+        bindings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapping_file() {
+        let map = ControllerMap::parse(
+            "# A comment\n\
+             a = touch 420, 270\n\
+             right-shoulder = none\n\
+             left-trigger = tilt left\n\
+             start = shake\n\
+             left-stick = joystick 80, 240, 50\n\
+             \n\
+             [player 2]\n\
+             region = 240, 0, 240, 320\n\
+             a = touch 440.5, 270\n\
+             right-stick = none\n",
+        )
+        .unwrap();
+
+        let first = map.bindings(0);
+        assert_eq!(
+            first.buttons,
+            vec![
+                (Input::Button(Button::RightStick), ButtonAction::CursorPress),
+                (Input::Button(Button::A), ButtonAction::Touch(420.0, 270.0)),
+                (
+                    Input::Trigger(Axis::TriggerLeft),
+                    ButtonAction::Tilt(-1.0, 0.0)
+                ),
+                (Input::Button(Button::Start), ButtonAction::Shake),
+            ]
+        );
+        assert_eq!(first.left_stick, StickAction::Joystick(80.0, 240.0, 50.0));
+        assert_eq!(first.right_stick, StickAction::Cursor);
+        assert_eq!(first.region, None);
+        assert_eq!(map.bindings(2), first);
+
+        let second = map.bindings(1);
+        assert_eq!(
+            second.buttons[3],
+            (Input::Button(Button::A), ButtonAction::Touch(440.5, 270.0))
+        );
+        assert_eq!(second.right_stick, StickAction::None);
+        assert_eq!(second.region, Some((240.0, 0.0, 240.0, 320.0)));
+
+        assert!(ControllerMap::default().bindings(0).is_default());
+        assert!(ControllerMap::parse("a = touch 1").is_err());
+        assert!(ControllerMap::parse("a = tilt sideways").is_err());
+        assert!(ControllerMap::parse("z = shake").is_err());
+        assert!(ControllerMap::parse("left-stick = shake").is_err());
+        assert!(ControllerMap::parse("left-stick = joystick 1, 2, 0").is_err());
+        assert!(ControllerMap::parse("[player 0]").is_err());
+        assert!(ControllerMap::parse("region = 1, 2, 3").is_err());
+    }
+}
@@ -0,0 +1,309 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Post-processing filters applied to the window's contents. See
+//! `--display-filter=`.
+//!
+//! The filters treat the window as an iPhone screen: a grid of 320×480 (or
+//! 480×320) pixels, however many pixels of the window each of them covers.
+
+use super::gl21compat as gl;
+use super::gl21compat::types::*;
+
+/// Post-processing filter for the window's contents. See `--display-filter=`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DisplayFilter {
+    /// No filter: the window's contents are drawn as-is.
+    Linear,
+    /// Blocky pixels.
+    Nearest,
+    /// Blocky pixels, with smooth edges between them.
+    SharpBilinear,
+    /// Scanlines and an aperture grille, like a CRT monitor.
+    Crt,
+    /// Visible gaps between pixels, like an old LCD.
+    LcdGrid,
+}
+impl DisplayFilter {
+    pub fn parse(value: &str) -> Result<DisplayFilter, String> {
+        match value {
+            "linear" => Ok(DisplayFilter::Linear),
+            "nearest" => Ok(DisplayFilter::Nearest),
+            "sharp-bilinear" => Ok(DisplayFilter::SharpBilinear),
+            "crt" => Ok(DisplayFilter::Crt),
+            "lcd-grid" => Ok(DisplayFilter::LcdGrid),
+            _ => Err(format!("Unknown display filter {:?}", value)),
+        }
+    }
+
+    pub const ALL: [DisplayFilter; 5] = [
+        DisplayFilter::Linear,
+        DisplayFilter::Nearest,
+        DisplayFilter::SharpBilinear,
+        DisplayFilter::Crt,
+        DisplayFilter::LcdGrid,
+    ];
+
+    /// The name used by `--display-filter=`.
+    pub fn name(self) -> &'static str {
+        match self {
+            DisplayFilter::Linear => "linear",
+            DisplayFilter::Nearest => "nearest",
+            DisplayFilter::SharpBilinear => "sharp-bilinear",
+            DisplayFilter::Crt => "crt",
+            DisplayFilter::LcdGrid => "lcd-grid",
+        }
+    }
+
+    fn main_source(self) -> &'static str {
+        match self {
+            DisplayFilter::Linear => unreachable!(),
+            DisplayFilter::Nearest => {
+                "
+void main() {
+    gl_FragColor = grid_texel(floor(tex_coord * grid_size));
+}
+"
+            }
+            DisplayFilter::SharpBilinear => {
+                "
+void main() {
+    vec2 pixel = tex_coord * grid_size;
+    vec2 scale = max(floor(output_size / grid_size), 1.0);
+    vec2 region = 0.5 - 0.5 / scale;
+    vec2 center_dist = fract(pixel) - 0.5;
+    vec2 f = (center_dist - clamp(center_dist, -region, region)) * scale + 0.5;
+    gl_FragColor = grid_bilinear(floor(pixel) + f);
+}
+"
+            }
+            DisplayFilter::Crt => {
+                "
+void main() {
+    vec2 pixel = tex_coord * grid_size;
+    // Smooth horizontally, but keep the rows distinct.
+    vec3 color = grid_bilinear(vec2(pixel.x, floor(pixel.y) + 0.5)).rgb;
+    float scanline = mix(0.55, 1.0, cos((fract(pixel.y) - 0.5) * 3.14159));
+    vec3 mask = vec3(0.85);
+    float column = mod(floor(gl_FragCoord.x), 3.0);
+    if (column < 1.0) {
+        mask.r = 1.15;
+    } else if (column < 2.0) {
+        mask.g = 1.15;
+    } else {
+        mask.b = 1.15;
+    }
+    gl_FragColor = vec4(color * scanline * mask * 1.1, 1.0);
+}
+"
+            }
+            DisplayFilter::LcdGrid => {
+                "
+void main() {
+    vec2 pixel = tex_coord * grid_size;
+    vec3 color = grid_texel(floor(pixel)).rgb;
+    // The gaps are one window pixel wide, so only draw them if they would
+    // leave something of the pixels.
+    vec2 lit = step(grid_size / output_size, fract(pixel));
+    if (output_size.x < 2.0 * grid_size.x) {
+        lit = vec2(1.0);
+    }
+    gl_FragColor = vec4(color * mix(0.65, 1.0, lit.x * lit.y), 1.0);
+}
+"
+            }
+        }
+    }
+}
+
+const VERTEX_SHADER: &str = "
+#version 120
+varying vec2 tex_coord;
+void main() {
+    gl_Position = gl_Vertex;
+    tex_coord = gl_Vertex.xy * 0.5 + 0.5;
+}
+";
+
+/// Shared by all fragment shaders. The grid is the emulated screen's pixels.
+const FRAGMENT_SHADER_PRELUDE: &str = "
+#version 120
+uniform sampler2D tex;
+uniform vec2 grid_size;
+uniform vec2 output_size;
+varying vec2 tex_coord;
+
+// Color of the grid pixel at integer co-ordinates.
+vec4 grid_texel(vec2 pixel) {
+    return texture2D(tex, (pixel + 0.5) / grid_size);
+}
+
+// Bilinear interpolation between the grid pixels, where pixel centers are at
+// half-integer co-ordinates.
+vec4 grid_bilinear(vec2 pixel) {
+    vec2 p = pixel - 0.5;
+    vec2 i = floor(p);
+    vec2 t = p - i;
+    return mix(
+        mix(grid_texel(i), grid_texel(i + vec2(1.0, 0.0)), t.x),
+        mix(grid_texel(i + vec2(0.0, 1.0)), grid_texel(i + vec2(1.0, 1.0)), t.x),
+        t.y
+    );
+}
+";
+
+/// The OpenGL objects needed to apply a [DisplayFilter]: the window's contents
+/// are drawn to a texture first, then the filter draws that texture to the
+/// window. These belong to the OpenGL 2.1 compatibility profile context they
+/// were created in.
+pub struct FilterStage {
+    filter: DisplayFilter,
+    program: GLuint,
+    texture: GLuint,
+    framebuffer: GLuint,
+    size: (u32, u32),
+}
+impl FilterStage {
+    /// Create the objects for a filter, which must not be
+    /// [DisplayFilter::Linear].
+    pub unsafe fn new(filter: DisplayFilter) -> FilterStage {
+        assert!(filter != DisplayFilter::Linear);
+
+        let vertex_shader = compile_shader(gl::VERTEX_SHADER, &[VERTEX_SHADER]);
+        let fragment_shader = compile_shader(
+            gl::FRAGMENT_SHADER,
+            &[FRAGMENT_SHADER_PRELUDE, filter.main_source()],
+        );
+        let program = gl::CreateProgram();
+        gl::AttachShader(program, vertex_shader);
+        gl::AttachShader(program, fragment_shader);
+        gl::LinkProgram(program);
+        gl::DeleteShader(vertex_shader);
+        gl::DeleteShader(fragment_shader);
+        let mut success = 0;
+        gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
+        assert!(success == gl::TRUE.into(), "Display filter failed to link");
+
+        let mut texture = 0;
+        gl::GenTextures(1, &mut texture);
+        let mut framebuffer = 0;
+        gl::GenFramebuffersEXT(1, &mut framebuffer);
+
+        FilterStage {
+            filter,
+            program,
+            texture,
+            framebuffer,
+            size: (0, 0),
+        }
+    }
+
+    pub fn filter(&self) -> DisplayFilter {
+        self.filter
+    }
+
+    /// Bind a framebuffer of `size` for the window's contents to be drawn
+    /// into, instead of the window's framebuffer.
+    pub unsafe fn begin(&mut self, size: (u32, u32)) {
+        let mut old_texture_2d = 0;
+        gl::GetIntegerv(gl::TEXTURE_BINDING_2D, &mut old_texture_2d);
+        gl::BindTexture(gl::TEXTURE_2D, self.texture);
+        if size != self.size {
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as _,
+                size.0 as _,
+                size.1 as _,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+            self.size = size;
+        }
+        gl::BindTexture(gl::TEXTURE_2D, old_texture_2d as _);
+
+        gl::BindFramebufferEXT(gl::FRAMEBUFFER_EXT, self.framebuffer);
+        gl::FramebufferTexture2DEXT(
+            gl::FRAMEBUFFER_EXT,
+            gl::COLOR_ATTACHMENT0_EXT,
+            gl::TEXTURE_2D,
+            self.texture,
+            0,
+        );
+    }
+
+    /// Draw what was drawn since [Self::begin] to the window's framebuffer,
+    /// applying the filter. `grid_size` is the size of the emulated screen in
+    /// pixels, in the same orientation as the window.
+    ///
+    /// The viewport must be the same size as the one used since
+    /// [Self::begin], but can be moved, e.g. for letterboxing. Texture and
+    /// blending state is overwritten.
+    pub unsafe fn end(&mut self, grid_size: (u32, u32)) {
+        gl::BindFramebufferEXT(gl::FRAMEBUFFER_EXT, 0);
+
+        gl::Disable(gl::BLEND);
+        gl::BindTexture(gl::TEXTURE_2D, self.texture);
+        gl::UseProgram(self.program);
+        let uniform = |name: &[u8]| gl::GetUniformLocation(self.program, name.as_ptr() as *const _);
+        gl::Uniform1i(uniform(b"tex\0"), 0);
+        gl::Uniform2f(
+            uniform(b"grid_size\0"),
+            grid_size.0 as f32,
+            grid_size.1 as f32,
+        );
+        gl::Uniform2f(
+            uniform(b"output_size\0"),
+            self.size.0 as f32,
+            self.size.1 as f32,
+        );
+
+        let vertices: [f32; 12] = [
+            -1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0,
+        ];
+        gl::EnableClientState(gl::VERTEX_ARRAY);
+        gl::VertexPointer(2, gl::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+
+        gl::UseProgram(0);
+    }
+}
+
+unsafe fn compile_shader(type_: GLenum, sources: &[&str]) -> GLuint {
+    let shader = gl::CreateShader(type_);
+    let pointers: Vec<*const GLchar> = sources.iter().map(|s| s.as_ptr() as *const _).collect();
+    let lengths: Vec<GLint> = sources.iter().map(|s| s.len() as _).collect();
+    gl::ShaderSource(
+        shader,
+        sources.len() as _,
+        pointers.as_ptr(),
+        lengths.as_ptr(),
+    );
+    gl::CompileShader(shader);
+    let mut success = 0;
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+    if success != gl::TRUE.into() {
+        let mut log = vec![0u8; 1024];
+        let mut length = 0;
+        gl::GetShaderInfoLog(
+            shader,
+            log.len() as _,
+            &mut length,
+            log.as_mut_ptr() as *mut _,
+        );
+        log.truncate(length as usize);
+        panic!(
+            "Display filter shader failed to compile: {}",
+            String::from_utf8_lossy(&log)
+        );
+    }
+    shader
+}
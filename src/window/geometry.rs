@@ -0,0 +1,140 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Remembering where each app's window was and how big it was. See
+//! `--window-geometry-dir=`.
+//!
+//! The file is written by touchHLE and looks like this:
+//!
+//! ```text
+//! x = 100
+//! y = 80
+//! width = 640
+//! height = 960
+//! fullscreen = false
+//! ```
+//!
+//! The size is for the portrait orientation, so that it can be used when the
+//! app starts, whichever way the device was rotated when it was saved. While
+//! fullscreen, the size and position are the ones to go back to.
+
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+}
+impl WindowGeometry {
+    pub fn parse(text: &str) -> Result<WindowGeometry, String> {
+        let (mut x, mut y, mut width, mut height) = (None, None, None, None);
+        let mut fullscreen = false;
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = line_number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("Line {}: expected 'key = value'", line_number));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = || format!("Line {}: invalid {} {:?}", line_number, key, value);
+            match key {
+                "x" => x = Some(value.parse().map_err(|_| invalid())?),
+                "y" => y = Some(value.parse().map_err(|_| invalid())?),
+                "width" => width = Some(value.parse().map_err(|_| invalid())?),
+                "height" => height = Some(value.parse().map_err(|_| invalid())?),
+                "fullscreen" => fullscreen = value.parse().map_err(|_| invalid())?,
+                _ => return Err(format!("Line {}: unknown key {:?}", line_number, key)),
+            }
+        }
+        let (Some(x), Some(y), Some(width), Some(height)) = (x, y, width, height) else {
+            return Err("x, y, width and height must all be given".to_string());
+        };
+        if width == 0 || height == 0 {
+            return Err("The size must not be zero".to_string());
+        }
+        Ok(WindowGeometry {
+            x,
+            y,
+            width,
+            height,
+            fullscreen,
+        })
+    }
+
+    pub fn format(&self) -> String {
+        format!(
+            "x = {}\ny = {}\nwidth = {}\nheight = {}\nfullscreen = {}\n",
+            self.x, self.y, self.width, self.height, self.fullscreen
+        )
+    }
+
+    /// Read the saved geometry of an app's window, if there is any.
+    pub fn load(path: &Path) -> Option<WindowGeometry> {
+        let text = std::fs::read_to_string(path).ok()?;
+        match WindowGeometry::parse(&text) {
+            Ok(geometry) => Some(geometry),
+            Err(e) => {
+                log!(
+                    "Warning: ignoring invalid window geometry file {}: {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) {
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(path, self.format()));
+        if let Err(e) = result {
+            log!(
+                "Warning: couldn't save the window geometry to {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_geometry() {
+        let geometry = WindowGeometry {
+            x: -20,
+            y: 80,
+            width: 640,
+            height: 960,
+            fullscreen: true,
+        };
+        assert_eq!(WindowGeometry::parse(&geometry.format()), Ok(geometry));
+        assert_eq!(
+            WindowGeometry::parse("x = 1\ny = 2\nwidth = 3\nheight = 4"),
+            Ok(WindowGeometry {
+                x: 1,
+                y: 2,
+                width: 3,
+                height: 4,
+                fullscreen: false,
+            })
+        );
+
+        assert!(WindowGeometry::parse("x = 1\ny = 2\nwidth = 3").is_err());
+        assert!(WindowGeometry::parse("x = 1\ny = 2\nwidth = 0\nheight = 4").is_err());
+        assert!(WindowGeometry::parse("x = one\ny = 2\nwidth = 3\nheight = 4").is_err());
+        assert!(WindowGeometry::parse("depth = 1").is_err());
+    }
+}
@@ -9,7 +9,7 @@ use super::Matrix;
 use crate::image::Image;
 use sdl2::video::GLProfile;
 
-pub use touchHLE_gl_bindings::{gl21compat, gl32core, gles11};
+pub use touchHLE_gl_bindings::{gl21compat, gl32core, gles11, gles20};
 
 pub enum GLVersion {
     /// OpenGL ES 1.1
@@ -26,12 +26,21 @@ pub struct GLContext {
     version: GLVersion,
 }
 
+/// Create an OpenGL context. If `share_with` is provided, the new context
+/// shares objects (textures, buffers etc) with it. The new context becomes the
+/// current context.
 pub fn create_gl_context(
     video_ctx: &sdl2::VideoSubsystem,
     window: &sdl2::video::Window,
     version: GLVersion,
+    share_with: Option<&GLContext>,
 ) -> GLContext {
     let attr = video_ctx.gl_attr();
+    // SDL can only share with the current context.
+    if let Some(share_with) = share_with {
+        window.gl_make_current(&share_with.gl_ctx).unwrap();
+    }
+    attr.set_share_with_current_context(share_with.is_some());
     match version {
         GLVersion::GLES11 => {
             attr.set_context_version(1, 1);
@@ -69,12 +78,134 @@ pub fn make_gl_context_current(
     }
 }
 
+/// Read the pixels of the window's framebuffer, as RGB8 with the top row
+/// first. The current context must be an OpenGL 2.1 one.
+pub unsafe fn read_window_pixels(width: u32, height: u32) -> Vec<u8> {
+    use gl21compat as gl;
+    use gl21compat::types::*;
+
+    let mut old_read_framebuffer: GLuint = 0;
+    gl::GetIntegerv(
+        gl::READ_FRAMEBUFFER_BINDING_EXT,
+        &mut old_read_framebuffer as *mut _ as *mut _,
+    );
+    gl::BindFramebufferEXT(gl::READ_FRAMEBUFFER_EXT, 0);
+
+    let row_size = width as usize * 3;
+    let mut pixels = vec![0u8; row_size * height as usize];
+    gl::PushClientAttrib(gl::CLIENT_PIXEL_STORE_BIT);
+    gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+    gl::PixelStorei(gl::PACK_ROW_LENGTH, 0);
+    gl::ReadPixels(
+        0,
+        0,
+        width as _,
+        height as _,
+        gl::RGB,
+        gl::UNSIGNED_BYTE,
+        pixels.as_mut_ptr() as *mut _,
+    );
+    gl::PopClientAttrib();
+
+    gl::BindFramebufferEXT(gl::READ_FRAMEBUFFER_EXT, old_read_framebuffer);
+
+    // OpenGL puts the bottom row first.
+    pixels
+        .chunks_exact(row_size)
+        .rev()
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// Read the pixels of a 2D texture's first level, as RGB8 with the top row
+/// first, and get its size. The current context must be an OpenGL 2.1 one.
+pub unsafe fn read_texture_pixels(texture: u32) -> (Vec<u8>, (u32, u32)) {
+    use gl21compat as gl;
+    use gl21compat::types::*;
+
+    let mut old_texture_2d: GLuint = 0;
+    gl::GetIntegerv(
+        gl::TEXTURE_BINDING_2D,
+        &mut old_texture_2d as *mut _ as *mut _,
+    );
+    gl::BindTexture(gl::TEXTURE_2D, texture);
+
+    let mut width: GLint = 0;
+    let mut height: GLint = 0;
+    gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_WIDTH, &mut width);
+    gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_HEIGHT, &mut height);
+    let (width, height) = (width as u32, height as u32);
+
+    let row_size = width as usize * 3;
+    let mut pixels = vec![0u8; row_size * height as usize];
+    gl::PushClientAttrib(gl::CLIENT_PIXEL_STORE_BIT);
+    gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+    gl::PixelStorei(gl::PACK_ROW_LENGTH, 0);
+    gl::GetTexImage(
+        gl::TEXTURE_2D,
+        0,
+        gl::RGB,
+        gl::UNSIGNED_BYTE,
+        pixels.as_mut_ptr() as *mut _,
+    );
+    gl::PopClientAttrib();
+
+    gl::BindTexture(gl::TEXTURE_2D, old_texture_2d);
+
+    // OpenGL puts the bottom row first.
+    let pixels = pixels
+        .chunks_exact(row_size)
+        .rev()
+        .flatten()
+        .copied()
+        .collect();
+    (pixels, (width, height))
+}
+
+/// Read the pixels of the window's front buffer, i.e. what was last presented,
+/// as RGB8 with the top row first. The current context must be an OpenGL 3.2
+/// one. Some systems don't keep the front buffer, and then it may be blank.
+pub unsafe fn read_front_buffer_pixels(width: u32, height: u32) -> Vec<u8> {
+    use gl32core as gl;
+
+    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+    gl::ReadBuffer(gl::FRONT);
+    let row_size = width as usize * 3;
+    let mut pixels = vec![0u8; row_size * height as usize];
+    gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+    gl::ReadPixels(
+        0,
+        0,
+        width as _,
+        height as _,
+        gl::RGB,
+        gl::UNSIGNED_BYTE,
+        pixels.as_mut_ptr() as *mut _,
+    );
+    gl::ReadBuffer(gl::BACK);
+
+    // OpenGL puts the bottom row first.
+    pixels
+        .chunks_exact(row_size)
+        .rev()
+        .flatten()
+        .copied()
+        .collect()
+}
+
 pub unsafe fn display_image(
     image: &Image,
     viewport_offset: (u32, u32),
     viewport_size: (u32, u32),
     rotation: &Matrix<2>,
 ) {
+    use gl32core as gl;
+
+    // Clear the whole window, including any letterboxing.
+    gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+    gl::Clear(gl::COLOR_BUFFER_BIT);
+
     gl::Viewport(
         viewport_offset.0.try_into().unwrap(),
         viewport_offset.1.try_into().unwrap(),
@@ -85,8 +216,6 @@ pub unsafe fn display_image(
     let src_pixels = image.pixels();
     let (width, height) = image.dimensions();
 
-    use gl32core as gl;
-
     let mut texture = 0;
     gl::GenTextures(1, &mut texture);
 
@@ -21,7 +21,12 @@ fn main() {
         (2, 1),
         Profile::Compatibility,
         Fallbacks::None,
-        ["GL_EXT_framebuffer_object", "GL_EXT_framebuffer_blit"],
+        [
+            "GL_EXT_framebuffer_object",
+            "GL_EXT_framebuffer_blit",
+            "GL_EXT_framebuffer_multisample",
+            "GL_EXT_packed_depth_stencil",
+        ],
     )
     .write_bindings(GlobalGenerator, &mut file)
     .unwrap();
@@ -32,7 +37,34 @@ fn main() {
         (1, 1),
         Profile::Core,
         Fallbacks::None,
-        ["GL_OES_framebuffer_object", "GL_OES_rgb8_rgba8"],
+        [
+            "GL_OES_framebuffer_object",
+            "GL_OES_rgb8_rgba8",
+            "GL_OES_stencil8",
+            "GL_OES_packed_depth_stencil",
+            "GL_OES_depth24",
+            "GL_IMG_texture_compression_pvrtc",
+            "GL_APPLE_framebuffer_multisample",
+            "GL_EXT_discard_framebuffer",
+        ],
+    )
+    .write_bindings(GlobalGenerator, &mut file)
+    .unwrap();
+
+    let mut file = File::create(out_dir.join("gles20.rs")).unwrap();
+    Registry::new(
+        Api::Gles2,
+        (2, 0),
+        Profile::Core,
+        Fallbacks::None,
+        [
+            "GL_OES_rgb8_rgba8",
+            "GL_OES_packed_depth_stencil",
+            "GL_OES_depth24",
+            "GL_IMG_texture_compression_pvrtc",
+            "GL_APPLE_framebuffer_multisample",
+            "GL_EXT_discard_framebuffer",
+        ],
     )
     .write_bindings(GlobalGenerator, &mut file)
     .unwrap();
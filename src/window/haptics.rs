@@ -0,0 +1,216 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! How the device vibrating is turned into game controller rumble. See the
+//! `--vibration-strength=`, `--vibration-duration=`, `--vibration-shape=`
+//! and `--haptics-file=` options.
+//!
+//! The haptics file changes the settings for some apps and looks like this:
+//!
+//! ```text
+//! # Comments start with '#'.
+//! * = shape fade
+//! com.example.game = off
+//! com.example.racer = strength 0.5, duration 0.2, shape pulse
+//! ```
+//!
+//! The left side is an app's bundle identifier, or `*` for every app. Lines
+//! are applied in order, so later ones win.
+
+use std::path::Path;
+
+/// How the strength of a vibration changes while it lasts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VibrationShape {
+    /// Full strength throughout, like the iPhone's motor.
+    Constant,
+    /// Starts at full strength and fades away, which feels more like a tap.
+    Fade,
+    /// Short bursts at full strength with gaps in between.
+    Pulse,
+}
+impl VibrationShape {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "constant" => Ok(VibrationShape::Constant),
+            "fade" => Ok(VibrationShape::Fade),
+            "pulse" => Ok(VibrationShape::Pulse),
+            _ => Err(format!("Unknown vibration shape {:?}", name)),
+        }
+    }
+
+    /// The fraction of the full strength to use after `elapsed` seconds of a
+    /// vibration that lasts `duration` seconds.
+    pub fn intensity(self, elapsed: f64, duration: f64) -> f32 {
+        if elapsed >= duration {
+            return 0.0;
+        }
+        match self {
+            VibrationShape::Constant => 1.0,
+            VibrationShape::Fade => (1.0 - elapsed / duration) as f32,
+            VibrationShape::Pulse => {
+                if elapsed % (PULSE_LENGTH * 2.0) < PULSE_LENGTH {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// How long each burst and gap of [VibrationShape::Pulse] is, in seconds.
+const PULSE_LENGTH: f64 = 0.08;
+
+/// The settings for the app's vibrations.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vibration {
+    /// In the range [0, 1], where 0 means off.
+    pub strength: f32,
+    /// In seconds.
+    pub duration: f64,
+    pub shape: VibrationShape,
+}
+impl Vibration {
+    /// Apply the lines of a haptics file that are for an app to some settings.
+    pub fn parse_overrides(
+        mut self,
+        text: &str,
+        bundle_identifier: &str,
+    ) -> Result<Vibration, String> {
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = line_number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((app, settings)) = line.split_once('=') else {
+                return Err(format!("Line {}: expected 'app = settings'", line_number));
+            };
+            let app = app.trim();
+            // Settings for other apps are still checked, so mistakes in them
+            // aren't missed.
+            let mut new = self;
+            for setting in settings.split(',') {
+                let setting = setting.trim();
+                let (name, value) = setting.split_once(' ').unwrap_or((setting, ""));
+                let value = value.trim();
+                let invalid = || format!("Line {}: invalid {} {:?}", line_number, name, value);
+                match name {
+                    "off" if value.is_empty() => new.strength = 0.0,
+                    "strength" => {
+                        new.strength = value
+                            .parse()
+                            .ok()
+                            .filter(|strength| (0.0..=1.0).contains(strength))
+                            .ok_or_else(invalid)?
+                    }
+                    "duration" => {
+                        new.duration = value
+                            .parse()
+                            .ok()
+                            .filter(|&duration: &f64| duration >= 0.0)
+                            .ok_or_else(invalid)?
+                    }
+                    "shape" => {
+                        new.shape = VibrationShape::parse(value)
+                            .map_err(|e| format!("Line {}: {}", line_number, e))?
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Line {}: unknown setting {:?}",
+                            line_number, setting
+                        ))
+                    }
+                }
+            }
+            if app == "*" || app == bundle_identifier {
+                self = new;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Apply the haptics file, if there is one, to the settings from the
+    /// options.
+    pub fn load(self, path: &Path, bundle_identifier: &str) -> Vibration {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return self;
+        };
+        match self.parse_overrides(&text, bundle_identifier) {
+            Ok(vibration) => {
+                if vibration != self {
+                    log!("Using the haptics settings in {}", path.display());
+                }
+                vibration
+            }
+            Err(e) => {
+                log!(
+                    "Warning: ignoring invalid haptics file {}: {}",
+                    path.display(),
+                    e
+                );
+                self
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn haptics_file() {
+        let defaults = Vibration {
+            strength: 1.0,
+            duration: 0.4,
+            shape: VibrationShape::Constant,
+        };
+        let text = "# A comment\n\
+                    * = shape fade\n\
+                    com.example.game = off\n\
+                    com.example.racer = strength 0.5, duration 0.2, shape pulse\n";
+        assert_eq!(
+            defaults.parse_overrides(text, "com.example.other"),
+            Ok(Vibration {
+                shape: VibrationShape::Fade,
+                ..defaults
+            })
+        );
+        assert_eq!(
+            defaults.parse_overrides(text, "com.example.game"),
+            Ok(Vibration {
+                strength: 0.0,
+                shape: VibrationShape::Fade,
+                ..defaults
+            })
+        );
+        assert_eq!(
+            defaults.parse_overrides(text, "com.example.racer"),
+            Ok(Vibration {
+                strength: 0.5,
+                duration: 0.2,
+                shape: VibrationShape::Pulse,
+            })
+        );
+
+        assert!(defaults.parse_overrides("* off", "a").is_err());
+        assert!(defaults.parse_overrides("b = strength 2", "a").is_err());
+        assert!(defaults.parse_overrides("b = shape wobble", "a").is_err());
+        assert!(defaults.parse_overrides("b = loud", "a").is_err());
+    }
+
+    #[test]
+    fn shapes() {
+        assert_eq!(VibrationShape::Constant.intensity(0.3, 0.4), 1.0);
+        assert_eq!(VibrationShape::Constant.intensity(0.4, 0.4), 0.0);
+        assert_eq!(VibrationShape::Fade.intensity(0.0, 0.4), 1.0);
+        assert_eq!(VibrationShape::Fade.intensity(0.3, 0.4), 0.25);
+        assert_eq!(VibrationShape::Pulse.intensity(0.05, 0.4), 1.0);
+        assert_eq!(VibrationShape::Pulse.intensity(0.1, 0.4), 0.0);
+        assert_eq!(VibrationShape::Pulse.intensity(0.17, 0.4), 1.0);
+    }
+}
@@ -0,0 +1,261 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Keyboard shortcuts for things touchHLE does, rather than the app. See
+//! `--hotkeys-file=`.
+//!
+//! The file looks like this:
+//!
+//! ```text
+//! # Comments start with '#'.
+//! screenshot = F12
+//! pause = Ctrl+P, Pause
+//! shake = none
+//! ```
+//!
+//! Each line replaces the default keys for an action. Keys use SDL's names for
+//! them, optionally after `Ctrl+`, `Shift+`, `Alt+` and `Cmd+`.
+//!
+//! There are no actions for saving and loading states. A state would have to
+//! include everything touchHLE keeps on the host side for the app, like
+//! Objective-C objects, OpenGL ES contexts, audio and threads, not just the
+//! app's memory and registers, and none of that can be saved yet.
+
+use sdl2::keyboard::{Keycode, Mod};
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    RotateClockwise,
+    RotateCounterclockwise,
+    Shake,
+    ToggleAudioInterruption,
+    TogglePause,
+    Screenshot,
+    ToggleFullscreen,
+    OpenMenu,
+    ToggleFastForward,
+    ToggleVirtualControls,
+}
+impl Action {
+    const ALL: [(&'static str, Action); 10] = [
+        ("rotate-clockwise", Action::RotateClockwise),
+        ("rotate-counterclockwise", Action::RotateCounterclockwise),
+        ("shake", Action::Shake),
+        ("interruption", Action::ToggleAudioInterruption),
+        ("pause", Action::TogglePause),
+        ("screenshot", Action::Screenshot),
+        ("fullscreen", Action::ToggleFullscreen),
+        ("menu", Action::OpenMenu),
+        ("fast-forward", Action::ToggleFastForward),
+        ("virtual-controls", Action::ToggleVirtualControls),
+    ];
+
+    fn default_hotkeys(self) -> Vec<Hotkey> {
+        let ctrl = |keycode| Hotkey {
+            keycode,
+            ctrl: true,
+            shift: false,
+            alt: false,
+            gui: false,
+        };
+        match self {
+            Action::RotateClockwise => vec![ctrl(Keycode::Right)],
+            Action::RotateCounterclockwise => vec![ctrl(Keycode::Left)],
+            Action::Shake => vec![ctrl(Keycode::Z)],
+            Action::ToggleAudioInterruption => vec![ctrl(Keycode::I)],
+            Action::TogglePause => vec![ctrl(Keycode::P)],
+            Action::Screenshot => vec![ctrl(Keycode::S)],
+            Action::ToggleFullscreen => vec![ctrl(Keycode::F)],
+            Action::OpenMenu => vec![ctrl(Keycode::M)],
+            Action::ToggleFastForward => vec![ctrl(Keycode::Tab)],
+            Action::ToggleVirtualControls => vec![ctrl(Keycode::O)],
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Hotkey {
+    keycode: Keycode,
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+    /// Cmd on macOS, the Windows key elsewhere.
+    gui: bool,
+}
+impl Hotkey {
+    fn parse(text: &str) -> Option<Hotkey> {
+        let mut hotkey = Hotkey {
+            keycode: Keycode::Space,
+            ctrl: false,
+            shift: false,
+            alt: false,
+            gui: false,
+        };
+        let mut rest = text.trim();
+        // A key can be called "+" (Keypad +), so only split off modifiers.
+        while let Some((modifier, after)) = rest.split_once('+') {
+            let flag = match modifier.trim().to_ascii_lowercase().as_str() {
+                "ctrl" => &mut hotkey.ctrl,
+                "shift" => &mut hotkey.shift,
+                "alt" => &mut hotkey.alt,
+                "cmd" => &mut hotkey.gui,
+                _ => break,
+            };
+            *flag = true;
+            rest = after.trim();
+        }
+        hotkey.keycode = Keycode::from_name(rest)?;
+        Some(hotkey)
+    }
+
+    fn matches(&self, keycode: Keycode, keymod: Mod) -> bool {
+        keycode == self.keycode
+            && keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) == self.ctrl
+            && keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) == self.shift
+            && keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) == self.alt
+            && keymod.intersects(Mod::LGUIMOD | Mod::RGUIMOD) == self.gui
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hotkeys {
+    bindings: Vec<(Hotkey, Action)>,
+}
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Hotkeys {
+            bindings: Action::ALL
+                .iter()
+                .flat_map(|&(_, action)| {
+                    action
+                        .default_hotkeys()
+                        .into_iter()
+                        .map(move |hotkey| (hotkey, action))
+                })
+                .collect(),
+        }
+    }
+}
+impl Hotkeys {
+    pub fn parse(text: &str) -> Result<Hotkeys, String> {
+        let mut hotkeys = Hotkeys::default();
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = line_number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((name, keys)) = line.split_once('=') else {
+                return Err(format!("Line {}: expected 'action = keys'", line_number));
+            };
+            let (name, keys) = (name.trim(), keys.trim());
+            let Some(&(_, action)) = Action::ALL.iter().find(|&&(other, _)| other == name) else {
+                return Err(format!("Line {}: unknown action {:?}", line_number, name));
+            };
+            hotkeys.bindings.retain(|&(_, other)| other != action);
+            if keys == "none" {
+                continue;
+            }
+            for key in keys.split(',') {
+                let Some(hotkey) = Hotkey::parse(key) else {
+                    return Err(format!(
+                        "Line {}: unknown key {:?}",
+                        line_number,
+                        key.trim()
+                    ));
+                };
+                hotkeys.bindings.push((hotkey, action));
+            }
+        }
+        Ok(hotkeys)
+    }
+
+    /// Read the user's hotkeys, if they've changed any.
+    pub fn load(path: &Path) -> Hotkeys {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return Hotkeys::default();
+        };
+        match Hotkeys::parse(&text) {
+            Ok(hotkeys) => {
+                log!("Using the hotkeys in {}", path.display());
+                hotkeys
+            }
+            Err(e) => {
+                log!(
+                    "Warning: ignoring invalid hotkeys file {}: {}",
+                    path.display(),
+                    e
+                );
+                Hotkeys::default()
+            }
+        }
+    }
+
+    /// Get what a key press does, if it's a hotkey.
+    pub fn action(&self, keycode: Keycode, keymod: Mod) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(hotkey, _)| hotkey.matches(keycode, keymod))
+            .map(|&(_, action)| action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hotkeys_file() {
+        let defaults = Hotkeys::default();
+        assert_eq!(
+            defaults.action(Keycode::Left, Mod::LCTRLMOD),
+            Some(Action::RotateCounterclockwise)
+        );
+        assert_eq!(defaults.action(Keycode::Left, Mod::NOMOD), None);
+        assert_eq!(
+            defaults.action(Keycode::Left, Mod::LCTRLMOD | Mod::LSHIFTMOD),
+            None
+        );
+
+        let hotkeys = Hotkeys::parse(
+            "# A comment\n\
+             screenshot = F12\n\
+             pause = ctrl+shift+P, Pause\n\
+             shake = none\n",
+        )
+        .unwrap();
+        assert_eq!(
+            hotkeys.action(Keycode::F12, Mod::NOMOD),
+            Some(Action::Screenshot)
+        );
+        assert_eq!(hotkeys.action(Keycode::S, Mod::LCTRLMOD), None);
+        assert_eq!(
+            hotkeys.action(Keycode::P, Mod::RCTRLMOD | Mod::LSHIFTMOD),
+            Some(Action::TogglePause)
+        );
+        assert_eq!(
+            hotkeys.action(Keycode::Pause, Mod::NOMOD),
+            Some(Action::TogglePause)
+        );
+        assert_eq!(hotkeys.action(Keycode::Z, Mod::LCTRLMOD), None);
+        assert_eq!(
+            hotkeys.action(Keycode::I, Mod::LCTRLMOD),
+            Some(Action::ToggleAudioInterruption)
+        );
+        assert_eq!(
+            hotkeys.action(Keycode::Tab, Mod::LCTRLMOD),
+            Some(Action::ToggleFastForward)
+        );
+        assert_eq!(
+            hotkeys.action(Keycode::O, Mod::RCTRLMOD),
+            Some(Action::ToggleVirtualControls)
+        );
+
+        assert!(Hotkeys::parse("screenshot").is_err());
+        assert!(Hotkeys::parse("fly = F1").is_err());
+        assert!(Hotkeys::parse("screenshot = Ctrl+NotAKey").is_err());
+    }
+}
@@ -0,0 +1,290 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Per-app keyboard mappings, which turn keys into touches. See
+//! `--keyboard-map-dir=`.
+//!
+//! A mapping file is named after the app's bundle identifier and looks like
+//! this:
+//!
+//! ```text
+//! # Comments start with '#'.
+//! point fire = 420, 270
+//! joystick move = 60, 260, 40
+//!
+//! Space = fire
+//! W = move up
+//! A = move left
+//! S = move down
+//! D = move right
+//! ```
+//!
+//! First the regions of the screen are named: a `point` is touched while any
+//! of its keys are held, and a `joystick` is touched at its center and dragged
+//! up to the radius in the direction of its held keys. Then keys, by their SDL
+//! scancode names, are bound to them. Co-ordinates are in points on the screen
+//! as it is currently displayed, with (0, 0) in the top-left corner.
+
+use super::controller_map::parse_numbers;
+use sdl2::keyboard::Scancode;
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Shape {
+    Point,
+    Joystick { radius: f32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Region {
+    name: String,
+    x: f32,
+    y: f32,
+    shape: Shape,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Binding {
+    scancode: Scancode,
+    /// Index in [KeyboardMap::regions].
+    region: usize,
+    /// For joysticks only.
+    direction: Option<Direction>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct KeyboardMap {
+    regions: Vec<Region>,
+    bindings: Vec<Binding>,
+}
+impl KeyboardMap {
+    pub fn parse(text: &str) -> Result<KeyboardMap, String> {
+        let mut map = KeyboardMap::default();
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = line_number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!(
+                    "Line {}: expected 'key = region' or 'point name = x, y'",
+                    line_number
+                ));
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            let declaration = if let Some(name) = key.strip_prefix("point ") {
+                parse_numbers(value).map(|[x, y]| (name, x, y, Shape::Point))
+            } else if let Some(name) = key.strip_prefix("joystick ") {
+                parse_numbers(value)
+                    .filter(|&[_, _, radius]| radius > 0.0)
+                    .map(|[x, y, radius]| (name, x, y, Shape::Joystick { radius }))
+            } else {
+                None
+            };
+            if let Some((name, x, y, shape)) = declaration {
+                let name = name.trim();
+                if map.regions.iter().any(|region| region.name == name) {
+                    return Err(format!(
+                        "Line {}: region {:?} is already defined",
+                        line_number, name
+                    ));
+                }
+                map.regions.push(Region {
+                    name: name.to_string(),
+                    x,
+                    y,
+                    shape,
+                });
+                continue;
+            } else if key.starts_with("point ") || key.starts_with("joystick ") {
+                return Err(format!(
+                    "Line {}: expected 'point name = x, y' or 'joystick name = x, y, radius'",
+                    line_number
+                ));
+            }
+
+            let Some(scancode) = Scancode::from_name(key) else {
+                return Err(format!("Line {}: unknown key {:?}", line_number, key));
+            };
+            let (name, direction) = value.split_once(' ').unwrap_or((value, ""));
+            let Some(region) = map.regions.iter().position(|region| region.name == name) else {
+                return Err(format!(
+                    "Line {}: unknown region {:?} (regions must be defined before keys)",
+                    line_number, name
+                ));
+            };
+            let direction = match (map.regions[region].shape, direction.trim()) {
+                (Shape::Point, "") => None,
+                (Shape::Joystick { .. }, "up") => Some(Direction::Up),
+                (Shape::Joystick { .. }, "down") => Some(Direction::Down),
+                (Shape::Joystick { .. }, "left") => Some(Direction::Left),
+                (Shape::Joystick { .. }, "right") => Some(Direction::Right),
+                (Shape::Point, _) => {
+                    return Err(format!(
+                        "Line {}: expected just the name of point {:?}",
+                        line_number, name
+                    ))
+                }
+                (Shape::Joystick { .. }, _) => {
+                    return Err(format!(
+                        "Line {}: expected up, down, left or right after joystick {:?}",
+                        line_number, name
+                    ))
+                }
+            };
+            map.bindings.retain(|binding| binding.scancode != scancode);
+            map.bindings.push(Binding {
+                scancode,
+                region,
+                direction,
+            });
+        }
+        Ok(map)
+    }
+
+    /// Read the mapping for an app, if there is one.
+    pub fn load(dir: &Path, bundle_identifier: &str) -> KeyboardMap {
+        let path = dir.join(format!("{}.txt", bundle_identifier));
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return KeyboardMap::default();
+        };
+        match KeyboardMap::parse(&text) {
+            Ok(map) => {
+                log!("Using the keyboard mapping in {}", path.display());
+                map
+            }
+            Err(e) => {
+                log!(
+                    "Warning: ignoring invalid keyboard mapping {}: {}",
+                    path.display(),
+                    e
+                );
+                KeyboardMap::default()
+            }
+        }
+    }
+
+    /// How many regions there are, i.e. how many touches there can be.
+    pub fn region_count(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Whether a key is bound to something.
+    pub fn binds(&self, scancode: Scancode) -> bool {
+        self.bindings
+            .iter()
+            .any(|binding| binding.scancode == scancode)
+    }
+
+    /// For each region, where it's being touched given which keys are held,
+    /// if it is. A joystick's touch is where it has been dragged to.
+    pub fn touches(&self, is_pressed: impl Fn(Scancode) -> bool) -> Vec<Option<(f32, f32)>> {
+        self.regions
+            .iter()
+            .enumerate()
+            .map(|(idx, region)| {
+                let mut held = false;
+                let (mut dx, mut dy) = (0.0f32, 0.0f32);
+                for binding in &self.bindings {
+                    if binding.region != idx || !is_pressed(binding.scancode) {
+                        continue;
+                    }
+                    held = true;
+                    match binding.direction {
+                        Some(Direction::Up) => dy -= 1.0,
+                        Some(Direction::Down) => dy += 1.0,
+                        Some(Direction::Left) => dx -= 1.0,
+                        Some(Direction::Right) => dx += 1.0,
+                        None => (),
+                    }
+                }
+                if !held {
+                    return None;
+                }
+                match region.shape {
+                    Shape::Point => Some((region.x, region.y)),
+                    Shape::Joystick { radius } => {
+                        // Opposite keys cancel out, which lets go.
+                        if (dx, dy) == (0.0, 0.0) {
+                            return None;
+                        }
+                        let length = dx.hypot(dy);
+                        Some((
+                            region.x + dx / length * radius,
+                            region.y + dy / length * radius,
+                        ))
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Where a region's touch starts and ends: its center.
+    pub fn region_center(&self, idx: usize) -> (f32, f32) {
+        (self.regions[idx].x, self.regions[idx].y)
+    }
+
+    /// Whether a region is a joystick, which is touched at its center and
+    /// then dragged.
+    pub fn region_is_joystick(&self, idx: usize) -> bool {
+        matches!(self.regions[idx].shape, Shape::Joystick { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyboard_map() {
+        let map = KeyboardMap::parse(
+            "# A comment\n\
+             point fire = 420, 270\n\
+             joystick move = 60, 260, 40\n\
+             \n\
+             Space = fire\n\
+             Return = fire\n\
+             W = move up\n\
+             A = move left\n\
+             D = move right\n",
+        )
+        .unwrap();
+        assert_eq!(map.region_count(), 2);
+        assert!(map.binds(Scancode::Space));
+        assert!(!map.binds(Scancode::S));
+
+        assert_eq!(map.touches(|_| false), vec![None, None]);
+        assert_eq!(
+            map.touches(|scancode| scancode == Scancode::Return || scancode == Scancode::W),
+            vec![Some((420.0, 270.0)), Some((60.0, 220.0))]
+        );
+        assert_eq!(
+            map.touches(|scancode| scancode == Scancode::A || scancode == Scancode::D),
+            vec![None, None]
+        );
+        let touches = map.touches(|scancode| scancode == Scancode::W || scancode == Scancode::D);
+        let (x, y) = touches[1].unwrap();
+        assert!((x - (60.0 + 40.0 * std::f32::consts::FRAC_1_SQRT_2)).abs() < 0.001);
+        assert!((y - (260.0 - 40.0 * std::f32::consts::FRAC_1_SQRT_2)).abs() < 0.001);
+
+        assert!(KeyboardMap::parse("Space").is_err());
+        assert!(KeyboardMap::parse("Space = fire").is_err());
+        assert!(KeyboardMap::parse("point fire = 1, 2\nNotAKey = fire").is_err());
+        assert!(KeyboardMap::parse("point fire = 1, 2\nSpace = fire up").is_err());
+        assert!(KeyboardMap::parse("joystick move = 1, 2, 3\nW = move").is_err());
+        assert!(KeyboardMap::parse("joystick move = 1, 2, 0").is_err());
+        assert!(KeyboardMap::parse("point fire = 1, 2\npoint fire = 3, 4").is_err());
+    }
+}
@@ -0,0 +1,331 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The options menu, which can be opened while an app is running (Ctrl+M, or
+//! Back+Start or the Guide button on a game controller) to change common
+//! settings without restarting touchHLE. The app is paused while it's open.
+//!
+//! This module keeps track of what's selected and draws the menu. The settings
+//! are applied by [crate::frameworks::uikit], which has access to everything
+//! they affect.
+//!
+//! When the app was picked in the launcher (see [crate::launcher]), quitting
+//! goes back to it rather than quitting touchHLE. There are no save state
+//! slots, because states can't be saved (see [super::hotkeys]).
+
+use super::{DisplayFilter, TiltSource};
+use crate::font::{Font, TextAlignment};
+
+/// Input for the menu, from the keyboard or a game controller.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MenuInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+/// The settings the menu can change.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MenuSettings {
+    /// In the range [0, 1]. See `--volume=`.
+    pub volume: f32,
+    pub display_filter: DisplayFilter,
+    pub tilt_source: TiltSource,
+    /// Whether the on-screen virtual controls are shown and can be pressed.
+    pub virtual_controls: bool,
+    pub fullscreen: bool,
+}
+
+/// What the user chose to do, other than changing a setting.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MenuChoice {
+    Resume,
+    Quit,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Item {
+    Resume,
+    Volume,
+    DisplayFilter,
+    TiltSource,
+    VirtualControls,
+    Fullscreen,
+    Quit,
+}
+
+const ITEMS: [Item; 7] = [
+    Item::Resume,
+    Item::Volume,
+    Item::DisplayFilter,
+    Item::TiltSource,
+    Item::VirtualControls,
+    Item::Fullscreen,
+    Item::Quit,
+];
+
+/// How much Left and Right change the volume by.
+const VOLUME_STEPS: f32 = 10.0;
+
+/// Pick the next or previous value in a list, wrapping around.
+fn cycle<T: Copy + PartialEq>(values: &[T], current: T, forward: bool) -> T {
+    let idx = values.iter().position(|&v| v == current).unwrap_or(0);
+    let idx = if forward {
+        (idx + 1) % values.len()
+    } else {
+        (idx + values.len() - 1) % values.len()
+    };
+    values[idx]
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Menu {
+    selected: usize,
+    /// Whether [MenuChoice::Quit] goes back to the launcher.
+    in_launcher: bool,
+}
+impl Menu {
+    pub fn new(in_launcher: bool) -> Menu {
+        Menu {
+            selected: 0,
+            in_launcher,
+        }
+    }
+
+    /// Handle input. Left, Right and Select change the selected setting.
+    pub fn input(&mut self, input: MenuInput, settings: &mut MenuSettings) -> Option<MenuChoice> {
+        let forward = match input {
+            MenuInput::Up => {
+                self.selected = (self.selected + ITEMS.len() - 1) % ITEMS.len();
+                return None;
+            }
+            MenuInput::Down => {
+                self.selected = (self.selected + 1) % ITEMS.len();
+                return None;
+            }
+            MenuInput::Back => return Some(MenuChoice::Resume),
+            MenuInput::Left => false,
+            MenuInput::Right | MenuInput::Select => true,
+        };
+        match ITEMS[self.selected] {
+            Item::Resume if input == MenuInput::Select => return Some(MenuChoice::Resume),
+            Item::Quit if input == MenuInput::Select => return Some(MenuChoice::Quit),
+            Item::Resume | Item::Quit => (),
+            Item::Volume => {
+                let step = if forward { 1.0 } else { -1.0 };
+                // Rounded to a whole step, so that any volume can be stepped.
+                settings.volume = ((settings.volume * VOLUME_STEPS).round() + step)
+                    .clamp(0.0, VOLUME_STEPS)
+                    / VOLUME_STEPS;
+            }
+            Item::DisplayFilter => {
+                settings.display_filter =
+                    cycle(&DisplayFilter::ALL, settings.display_filter, forward)
+            }
+            Item::TiltSource => {
+                settings.tilt_source = cycle(&TiltSource::ALL, settings.tilt_source, forward)
+            }
+            Item::VirtualControls => settings.virtual_controls = !settings.virtual_controls,
+            Item::Fullscreen => settings.fullscreen = !settings.fullscreen,
+        }
+        None
+    }
+
+    /// The text of each item, and whether it's selected.
+    fn lines(&self, settings: &MenuSettings) -> Vec<(String, bool)> {
+        let on_off = |on| if on { "on" } else { "off" };
+        ITEMS
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| {
+                let text = match item {
+                    Item::Resume => "Resume".to_string(),
+                    Item::Volume => format!("Volume: {:.0}%", settings.volume * 100.0),
+                    Item::DisplayFilter => {
+                        format!("Display filter: {}", settings.display_filter.name())
+                    }
+                    Item::TiltSource => format!("Tilt controls: {}", settings.tilt_source.name()),
+                    Item::VirtualControls => {
+                        format!("On-screen controls: {}", on_off(settings.virtual_controls))
+                    }
+                    Item::Fullscreen => format!("Fullscreen: {}", on_off(settings.fullscreen)),
+                    Item::Quit if self.in_launcher => "Quit to app picker".to_string(),
+                    Item::Quit => "Quit touchHLE".to_string(),
+                };
+                (text, idx == self.selected)
+            })
+            .collect()
+    }
+
+    /// Draw the menu over a darkened copy of what was on screen when it was
+    /// opened, if there is one of the right size. Pixels are RGB8 for the
+    /// background and RGBA8 for the result, both with the top row first.
+    pub fn draw(
+        &self,
+        settings: &MenuSettings,
+        font: &Font,
+        background: Option<&[u8]>,
+        (width, height): (u32, u32),
+    ) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        match background.filter(|b| b.len() == width as usize * height as usize * 3) {
+            Some(background) => {
+                for rgb in background.chunks_exact(3) {
+                    pixels.extend(rgb.iter().map(|&c| (c as f32 * 0.3) as u8));
+                    pixels.push(255);
+                }
+            }
+            None => {
+                for _ in 0..(width * height) {
+                    pixels.extend_from_slice(&[0, 0, 0, 255]);
+                }
+            }
+        }
+
+        let mut blend = |x: i32, y: i32, color: (f32, f32, f32), alpha: f32| {
+            if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                return;
+            }
+            let idx = (y as usize * width as usize + x as usize) * 4;
+            let alpha = alpha.clamp(0.0, 1.0);
+            for (channel, value) in pixels[idx..idx + 3]
+                .iter_mut()
+                .zip([color.0, color.1, color.2])
+            {
+                *channel = (value * 255.0 * alpha + *channel as f32 * (1.0 - alpha)) as u8;
+            }
+        };
+        let mut fill = |(x0, y0, x1, y1): (f32, f32, f32, f32), color, alpha| {
+            for y in (y0 as i32)..(y1 as i32) {
+                for x in (x0 as i32)..(x1 as i32) {
+                    blend(x, y, color, alpha);
+                }
+            }
+        };
+
+        let lines = self.lines(settings);
+        let row_height = (height as f32 / (lines.len() + 3) as f32).min(width as f32 / 10.0);
+        let font_size = row_height * 0.5;
+        let panel_width = (font_size * 16.0).min(width as f32);
+        let left = (width as f32 - panel_width) / 2.0;
+        let top = (height as f32 - row_height * (lines.len() + 1) as f32) / 2.0;
+        let panel = (
+            left,
+            top - row_height / 2.0,
+            left + panel_width,
+            top + row_height * (lines.len() as f32 + 0.5),
+        );
+        fill(panel, (0.15, 0.15, 0.15), 0.9);
+        for (idx, &(_, selected)) in lines.iter().enumerate() {
+            if selected {
+                let row_top = top + row_height * idx as f32;
+                let row = (left, row_top, left + panel_width, row_top + row_height);
+                fill(row, (0.2, 0.4, 0.8), 1.0);
+            }
+        }
+
+        for (idx, (text, _)) in lines.iter().enumerate() {
+            let (_, text_height) = font.calculate_text_size(font_size, text, None);
+            let row_top = top + row_height * idx as f32;
+            // The font code uses y-up co-ordinates with the origin at the
+            // bottom of the text.
+            let bottom = height as f32 - (row_top + (row_height + text_height) / 2.0);
+            font.draw(
+                font_size,
+                text,
+                (width as f32 / 2.0, bottom),
+                None,
+                TextAlignment::Center,
+                |(x, y), coverage| blend(x, height as i32 - 1 - y, (1.0, 1.0, 1.0), coverage),
+            );
+        }
+
+        pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu() {
+        let mut settings = MenuSettings {
+            volume: 0.75,
+            display_filter: DisplayFilter::Linear,
+            tilt_source: TiltSource::Auto,
+            virtual_controls: true,
+            fullscreen: false,
+        };
+        let mut menu = Menu::default();
+
+        assert_eq!(menu.input(MenuInput::Up, &mut settings), None);
+        assert_eq!(
+            menu.input(MenuInput::Select, &mut settings),
+            Some(MenuChoice::Quit)
+        );
+        assert_eq!(menu.input(MenuInput::Down, &mut settings), None);
+        assert_eq!(
+            menu.input(MenuInput::Select, &mut settings),
+            Some(MenuChoice::Resume)
+        );
+
+        menu.input(MenuInput::Down, &mut settings);
+        menu.input(MenuInput::Left, &mut settings);
+        assert_eq!(settings.volume, 0.7);
+        for _ in 0..5 {
+            menu.input(MenuInput::Right, &mut settings);
+        }
+        assert_eq!(settings.volume, 1.0);
+
+        menu.input(MenuInput::Down, &mut settings);
+        menu.input(MenuInput::Left, &mut settings);
+        assert_eq!(settings.display_filter, DisplayFilter::LcdGrid);
+        menu.input(MenuInput::Select, &mut settings);
+        assert_eq!(settings.display_filter, DisplayFilter::Linear);
+
+        menu.input(MenuInput::Down, &mut settings);
+        menu.input(MenuInput::Right, &mut settings);
+        assert_eq!(settings.tilt_source, TiltSource::Sensor);
+
+        menu.input(MenuInput::Down, &mut settings);
+        menu.input(MenuInput::Select, &mut settings);
+        assert!(!settings.virtual_controls);
+        menu.input(MenuInput::Down, &mut settings);
+        menu.input(MenuInput::Right, &mut settings);
+        assert!(settings.fullscreen);
+
+        assert_eq!(
+            menu.lines(&settings)[1],
+            ("Volume: 100%".to_string(), false)
+        );
+        assert_eq!(
+            menu.lines(&settings)[5],
+            ("Fullscreen: on".to_string(), true)
+        );
+        assert_eq!(
+            menu.input(MenuInput::Back, &mut settings),
+            Some(MenuChoice::Resume)
+        );
+        assert_eq!(
+            menu.lines(&settings)[6],
+            ("Quit touchHLE".to_string(), false)
+        );
+
+        let mut menu = Menu::new(true);
+        assert_eq!(
+            menu.lines(&settings)[6],
+            ("Quit to app picker".to_string(), false)
+        );
+        menu.input(MenuInput::Up, &mut settings);
+        assert_eq!(
+            menu.input(MenuInput::Select, &mut settings),
+            Some(MenuChoice::Quit)
+        );
+    }
+}
@@ -0,0 +1,223 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Per-app on-screen virtual controls, which are drawn on top of the app and
+//! turn presses into touches. See `--virtual-controls-dir=`.
+//!
+//! A layout file is named after the app's bundle identifier and looks like
+//! this:
+//!
+//! ```text
+//! # Comments start with '#'.
+//! button fire = 420, 270, 30
+//! button pause = 450, 20, 20 -> 470, 10
+//! stick move = 70, 250, 50
+//! ```
+//!
+//! Each control is a circle, given by its center and radius. A `button`
+//! touches the app at its center while it's pressed, and a `stick` touches
+//! the app at its center and drags the touch up to the radius towards where
+//! it's pressed. The place the app is touched can be moved with `-> x, y`,
+//! for example to put a control for a small button somewhere easier to reach.
+//! Co-ordinates are in points on the screen as it is currently displayed,
+//! with (0, 0) in the top-left corner.
+
+use super::controller_map::parse_numbers;
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Button,
+    Stick,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Control {
+    x: f32,
+    y: f32,
+    radius: f32,
+    /// Where the app is touched, which is the center unless moved.
+    target: (f32, f32),
+    kind: Kind,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct VirtualControls {
+    controls: Vec<Control>,
+}
+impl VirtualControls {
+    pub fn parse(text: &str) -> Result<VirtualControls, String> {
+        let mut names = Vec::new();
+        let mut controls = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line_number = line_number + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!(
+                    "Line {}: expected 'button name = x, y, radius'",
+                    line_number
+                ));
+            };
+            let (kind, name) = if let Some(name) = key.trim().strip_prefix("button ") {
+                (Kind::Button, name.trim())
+            } else if let Some(name) = key.trim().strip_prefix("stick ") {
+                (Kind::Stick, name.trim())
+            } else {
+                return Err(format!(
+                    "Line {}: expected 'button' or 'stick' before the name",
+                    line_number
+                ));
+            };
+            if names.contains(&name) {
+                return Err(format!(
+                    "Line {}: control {:?} is already defined",
+                    line_number, name
+                ));
+            }
+            let (circle, target) = match value.split_once("->") {
+                Some((circle, target)) => (circle, Some(target)),
+                None => (value, None),
+            };
+            let Some([x, y, radius]) = parse_numbers(circle).filter(|&[_, _, r]| r > 0.0) else {
+                return Err(format!(
+                    "Line {}: expected 'x, y, radius' for control {:?}",
+                    line_number, name
+                ));
+            };
+            let target = match target.map(parse_numbers) {
+                Some(Some([target_x, target_y])) => (target_x, target_y),
+                Some(None) => {
+                    return Err(format!(
+                        "Line {}: expected 'x, y' after '->' for control {:?}",
+                        line_number, name
+                    ))
+                }
+                None => (x, y),
+            };
+            names.push(name);
+            controls.push(Control {
+                x,
+                y,
+                radius,
+                target,
+                kind,
+            });
+        }
+        Ok(VirtualControls { controls })
+    }
+
+    /// Read the layout for an app, if there is one.
+    pub fn load(dir: &Path, bundle_identifier: &str) -> VirtualControls {
+        let path = dir.join(format!("{}.txt", bundle_identifier));
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return VirtualControls::default();
+        };
+        match VirtualControls::parse(&text) {
+            Ok(controls) => {
+                log!("Using the virtual controls in {}", path.display());
+                controls
+            }
+            Err(e) => {
+                log!(
+                    "Warning: ignoring invalid virtual controls {}: {}",
+                    path.display(),
+                    e
+                );
+                VirtualControls::default()
+            }
+        }
+    }
+
+    /// How many controls there are, i.e. how many touches there can be.
+    pub fn count(&self) -> usize {
+        self.controls.len()
+    }
+
+    /// Find the control at a point on the screen, if there is one. Later
+    /// controls are on top.
+    pub fn hit(&self, (x, y): (f32, f32)) -> Option<usize> {
+        self.controls
+            .iter()
+            .rposition(|control| (x - control.x).hypot(y - control.y) <= control.radius)
+    }
+
+    /// Where a control's touch starts and ends.
+    pub fn touch_start(&self, idx: usize) -> (f32, f32) {
+        self.controls[idx].target
+    }
+
+    /// Where the app is touched while a control is pressed at a point, and,
+    /// for sticks, where the knob is drawn.
+    pub fn touch_at(&self, idx: usize, (x, y): (f32, f32)) -> (f32, f32) {
+        let control = &self.controls[idx];
+        let (target_x, target_y) = control.target;
+        match control.kind {
+            Kind::Button => control.target,
+            Kind::Stick => {
+                let (dx, dy) = (x - control.x, y - control.y);
+                let length = dx.hypot(dy);
+                let scale = if length > control.radius {
+                    control.radius / length
+                } else {
+                    1.0
+                };
+                (target_x + dx * scale, target_y + dy * scale)
+            }
+        }
+    }
+
+    /// Whether a control is a stick, whose touch is dragged.
+    pub fn is_stick(&self, idx: usize) -> bool {
+        self.controls[idx].kind == Kind::Stick
+    }
+
+    /// The center and radius of a control, for drawing it.
+    pub fn circle(&self, idx: usize) -> (f32, f32, f32) {
+        let control = &self.controls[idx];
+        (control.x, control.y, control.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtual_controls() {
+        let controls = VirtualControls::parse(
+            "# A comment\n\
+             button fire = 420, 270, 30\n\
+             button pause = 450, 20, 20 -> 470, 10\n\
+             \n\
+             stick move = 70, 250, 50\n",
+        )
+        .unwrap();
+        assert_eq!(controls.count(), 3);
+
+        assert_eq!(controls.hit((430.0, 280.0)), Some(0));
+        assert_eq!(controls.hit((455.0, 25.0)), Some(1));
+        assert_eq!(controls.hit((70.0, 299.0)), Some(2));
+        assert_eq!(controls.hit((200.0, 200.0)), None);
+
+        assert_eq!(controls.touch_at(0, (430.0, 280.0)), (420.0, 270.0));
+        assert_eq!(controls.touch_start(1), (470.0, 10.0));
+        assert_eq!(controls.touch_at(1, (455.0, 25.0)), (470.0, 10.0));
+        assert!(controls.is_stick(2));
+        assert_eq!(controls.touch_start(2), (70.0, 250.0));
+        assert_eq!(controls.touch_at(2, (80.0, 240.0)), (80.0, 240.0));
+        assert_eq!(controls.touch_at(2, (170.0, 250.0)), (120.0, 250.0));
+        assert_eq!(controls.circle(2), (70.0, 250.0, 50.0));
+
+        assert!(VirtualControls::parse("button fire").is_err());
+        assert!(VirtualControls::parse("fire = 1, 2, 3").is_err());
+        assert!(VirtualControls::parse("button fire = 1, 2").is_err());
+        assert!(VirtualControls::parse("button fire = 1, 2, 0").is_err());
+        assert!(VirtualControls::parse("button fire = 1, 2, 3 -> 4").is_err());
+        assert!(VirtualControls::parse("button a = 1, 2, 3\nstick a = 4, 5, 6").is_err());
+    }
+}